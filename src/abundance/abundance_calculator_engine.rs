@@ -6,6 +6,7 @@ use std::io::Write;
 use std::path::Path;
 
 use crate::model::variant_context::VariantContext;
+use crate::abundance::abundance_formats::{AbundanceFormat, CamiProfiles};
 use crate::abundance::strain_abundances_calculator::StrainAbundanceCalculator;
 use crate::annotator::variant_annotation::VariantAnnotations;
 use crate::genotype::genotype_builder::AttributeObject;
@@ -19,6 +20,9 @@ pub struct AbundanceCalculatorEngine<'a> {
     reference_name: &'a str,
     output_prefix: &'a str,
     sample_names: &'a [&'a str],
+    output_formats: Vec<AbundanceFormat>,
+    cami_profiles: Option<CamiProfiles>,
+    reference_bias_correction: Vec<f64>,
 }
 
 impl<'a> AbundanceCalculatorEngine<'a> {
@@ -36,9 +40,28 @@ impl<'a> AbundanceCalculatorEngine<'a> {
             reference_name,
             output_prefix,
             sample_names,
+            output_formats: Vec::new(),
+            cami_profiles: None,
+            reference_bias_correction: Vec::new(),
         }
     }
 
+    /// Formats the strain abundances are written in as well as the strain coverage TSV
+    pub fn set_output_formats(&mut self, output_formats: Vec<AbundanceFormat>) {
+        self.output_formats = output_formats;
+    }
+
+    /// Run wide CAMI profiles the strain abundances are added to when the CAMI format is requested
+    pub fn set_cami_profiles(&mut self, cami_profiles: CamiProfiles) {
+        self.cami_profiles = Some(cami_profiles);
+    }
+
+    /// Factors the reference depth of each sample is divided by before abundances are estimated,
+    /// see `ReferenceBias::correction_factors`
+    pub fn set_reference_bias_correction(&mut self, reference_bias_correction: Vec<f64>) {
+        self.reference_bias_correction = reference_bias_correction;
+    }
+
     pub fn run_abundance_calculator(
         mut self,
         mut n_strains: usize,
@@ -103,6 +126,11 @@ impl<'a> AbundanceCalculatorEngine<'a> {
 
             for (sample_index, sample_vector) in abundance_vectors.iter_mut().enumerate() {
                 let mut vi = 0; // variant index
+                let reference_correction = self
+                    .reference_bias_correction
+                    .get(sample_index)
+                    .copied()
+                    .unwrap_or(1.0);
                 for vc in self.variant_contexts.iter_mut() {
                     match vc.attributes.get_mut(VariantAnnotations::Strain.to_key()) {
                         None => continue,
@@ -113,12 +141,15 @@ impl<'a> AbundanceCalculatorEngine<'a> {
                                     panic!("Strain annotation was not the correct AttributeObject")
                                 }
                             };
-                            // Total depth of variant is its sum of reference + alt coverage
-                            let mut total_depth = vc.genotypes.genotypes()[sample_index]
-                                .ad
-                                .iter()
-                                .sum::<i32>()
-                                as f64;
+                            // Total depth of variant is its sum of reference + alt coverage,
+                            // with the reference depth corrected for reference bias
+                            let reference_depth = vc.genotypes.genotypes()[sample_index].ad[0]
+                                as f64
+                                / reference_correction;
+                            let mut total_depth = reference_depth
+                                + vc.genotypes.genotypes()[sample_index].ad[1..]
+                                    .iter()
+                                    .sum::<i32>() as f64;
 
                             // catch sample where no mapping occured at this location
                             // 0.0 dp causes NaN, so just change it to 1.0 so we get 0.0 weight
@@ -204,9 +235,7 @@ impl<'a> AbundanceCalculatorEngine<'a> {
                                     // We divide the total depth of variant here by the total amount of strains that
                                     // variant occurs in. E.g. if a variant had a depth of 6
                                     // and occurred in 3 genotypes, then for each genotype its initialization value would be 2
-                                    let weight = vc.genotypes.genotypes()[sample_index].ad[0]
-                                        as f64
-                                        / total_depth;
+                                    let weight = reference_depth / total_depth;
 
                                     // let weight =
                                     //     reference_depth / (n_strains - strains.len()) as f64;
@@ -434,6 +463,8 @@ impl<'a> AbundanceCalculatorEngine<'a> {
             }
             writeln!(file_open).unwrap();
         }
+
+        self.write_output_formats(&printing_genotype);
     }
 
     pub fn print_single_strain_coverage(&self) {
@@ -480,6 +511,44 @@ impl<'a> AbundanceCalculatorEngine<'a> {
             write!(file_open, "\t{:.2}", 1.0).unwrap();
         }
         writeln!(file_open).unwrap();
+
+        let mut single_strain = LinkedHashMap::new();
+        single_strain.insert(0, vec![1.0; self.sample_names.len()]);
+        self.write_output_formats(&single_strain);
+    }
+
+    fn write_output_formats(&self, abundances: &LinkedHashMap<usize, Vec<f64>>) {
+        for output_format in self.output_formats.iter() {
+            if *output_format == AbundanceFormat::Cami {
+                // profiles span every genome, so are written once the run is done
+                if let Some(cami_profiles) = &self.cami_profiles {
+                    cami_profiles.add(self.reference_name, self.sample_names, abundances);
+                }
+                continue;
+            }
+
+            let file_name = format!(
+                "{}/{}_{}",
+                self.output_prefix,
+                self.reference_name,
+                output_format.file_suffix()
+            );
+
+            let mut file_open = match File::create(Path::new(&file_name)) {
+                Ok(abundance_file) => abundance_file,
+                Err(e) => {
+                    panic!("Cannot create file {:?}", e);
+                }
+            };
+
+            AbundanceFormat::write_biom(
+                &mut file_open,
+                self.reference_name,
+                self.sample_names,
+                abundances,
+            )
+            .expect("Unable to write to file");
+        }
     }
 
     fn reference_strain_potentially_present(&self, n_samples: usize) -> bool {
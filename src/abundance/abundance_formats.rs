@@ -0,0 +1,251 @@
+use hashlink::LinkedHashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::utils::errors::BirdToolError;
+
+/// Additional formats the strain abundances of a genome can be written in, alongside the
+/// default `<genome>_strain_coverages.tsv`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbundanceFormat {
+    /// CAMI profiling format, as read by OPAL and other profiler benchmarking tools. Written once
+    /// per sample across every genome of the run, see [`CamiProfiles`]
+    Cami,
+    /// Classic BIOM table in TSV form, as read by phyloseq and `biom convert`
+    Biom,
+}
+
+impl AbundanceFormat {
+    pub fn from_name(format: &str) -> Result<Self, BirdToolError> {
+        match format.to_ascii_lowercase().as_str() {
+            "cami" => Ok(Self::Cami),
+            "biom" => Ok(Self::Biom),
+            _ => Err(BirdToolError::ConfigError(format!(
+                "Unknown abundance format '{}', expected one of cami, biom",
+                format
+            ))),
+        }
+    }
+
+    /// The formats requested on the command line, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Result<Vec<Self>, BirdToolError> {
+        match args.try_get_many::<String>("abundance-formats") {
+            Ok(Some(formats)) => formats.map(|format| Self::from_name(format)).collect(),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// The file name suffix used for this format
+    pub fn file_suffix(&self) -> &str {
+        match self {
+            Self::Cami => "strain_abundances.profile",
+            Self::Biom => "strain_abundances.biom.tsv",
+        }
+    }
+
+    /// The abundance of each strain in a sample divided by the total abundance of that sample.
+    /// Samples without any abundance are left as 0
+    pub fn relative_abundances(
+        abundances: &LinkedHashMap<usize, Vec<f64>>,
+        n_samples: usize,
+    ) -> LinkedHashMap<usize, Vec<f64>> {
+        let mut totals = vec![0.0; n_samples];
+        for sample_abundances in abundances.values() {
+            for (sample_idx, abundance) in sample_abundances.iter().enumerate() {
+                if abundance.is_finite() && *abundance > 0.0 {
+                    totals[sample_idx] += abundance;
+                }
+            }
+        }
+
+        abundances
+            .iter()
+            .map(|(strain_id, sample_abundances)| {
+                let relative = sample_abundances
+                    .iter()
+                    .zip(totals.iter())
+                    .map(|(abundance, total)| {
+                        if *total > 0.0 && abundance.is_finite() && *abundance > 0.0 {
+                            abundance / total
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect::<Vec<f64>>();
+                (*strain_id, relative)
+            })
+            .collect()
+    }
+
+    /// The identifier of a strain that is unique across genomes
+    pub fn strain_name(reference_name: &str, strain_id: usize) -> String {
+        format!("{}.strain_{}", reference_name, strain_id)
+    }
+
+    /// Writes the CAMI profile of one sample across the strain abundances of every genome, with
+    /// percentages relative to the total abundance of the sample. Strains have no taxonomy, so
+    /// the genome is used as the parent rank of its strains and the identifiers are used as both
+    /// TAXID and name. Genomes and strains absent from the sample are left out
+    pub fn write_cami<W: Write>(
+        writer: &mut W,
+        sample_name: &str,
+        sample_idx: usize,
+        genomes: &[(String, LinkedHashMap<usize, Vec<f64>>)],
+    ) -> std::io::Result<()> {
+        let abundance = |sample_abundances: &Vec<f64>| match sample_abundances.get(sample_idx) {
+            Some(abundance) if abundance.is_finite() && *abundance > 0.0 => *abundance,
+            _ => 0.0,
+        };
+        let total = genomes
+            .iter()
+            .flat_map(|(_, abundances)| abundances.values().map(abundance))
+            .sum::<f64>();
+
+        writeln!(writer, "@SampleID:{}", sample_name)?;
+        writeln!(writer, "@Version:0.9.1")?;
+        writeln!(writer, "@Ranks:genome|strain")?;
+        writeln!(
+            writer,
+            "@__program__:lorikeet-v{}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+        writeln!(writer, "@@TAXID\tRANK\tTAXPATH\tTAXPATHSN\tPERCENTAGE")?;
+        if total <= 0.0 {
+            return Ok(());
+        }
+
+        for (reference_name, abundances) in genomes.iter() {
+            let genome_percentage = abundances.values().map(abundance).sum::<f64>() / total * 100.0;
+            if genome_percentage <= 0.0 {
+                continue;
+            }
+            writeln!(
+                writer,
+                "{}\tgenome\t{}\t{}\t{:.5}",
+                reference_name, reference_name, reference_name, genome_percentage
+            )?;
+
+            for (strain_id, sample_abundances) in abundances.iter() {
+                let percentage = abundance(sample_abundances) / total * 100.0;
+                if percentage <= 0.0 {
+                    continue;
+                }
+                let strain_name = Self::strain_name(reference_name, *strain_id);
+                writeln!(
+                    writer,
+                    "{}\tstrain\t{}|{}\t{}|{}\t{:.5}",
+                    strain_name,
+                    reference_name,
+                    strain_name,
+                    reference_name,
+                    strain_name,
+                    percentage
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a strain by sample table of relative abundances in the classic BIOM TSV layout
+    pub fn write_biom<W: Write>(
+        writer: &mut W,
+        reference_name: &str,
+        sample_names: &[&str],
+        abundances: &LinkedHashMap<usize, Vec<f64>>,
+    ) -> std::io::Result<()> {
+        let relative_abundances = Self::relative_abundances(abundances, sample_names.len());
+        writeln!(writer, "# Constructed from biom file")?;
+        write!(writer, "#OTU ID")?;
+        for sample_name in sample_names.iter() {
+            write!(writer, "\t{}", sample_name)?;
+        }
+        writeln!(writer, "\ttaxonomy")?;
+
+        for (strain_id, sample_abundances) in relative_abundances.iter() {
+            write!(writer, "{}", Self::strain_name(reference_name, *strain_id))?;
+            for abundance in sample_abundances.iter() {
+                write!(writer, "\t{:.6}", abundance)?;
+            }
+            writeln!(
+                writer,
+                "\tg__{}; s__{}",
+                reference_name,
+                Self::strain_name(reference_name, *strain_id)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The strain abundances of every genome of a run, gathered as each genome finishes so that one
+/// CAMI profile per sample can be written once the whole run is done. Every clone shares the same
+/// abundances, so genomes run in parallel can each add their own
+#[derive(Debug, Clone, Default)]
+pub struct CamiProfiles {
+    genomes: Arc<Mutex<CamiGenomes>>,
+}
+
+#[derive(Debug, Default)]
+struct CamiGenomes {
+    sample_names: Vec<String>,
+    abundances: Vec<(String, LinkedHashMap<usize, Vec<f64>>)>,
+}
+
+impl CamiProfiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the strain abundances of a genome, one value per sample for each strain
+    pub fn add(
+        &self,
+        reference_name: &str,
+        sample_names: &[&str],
+        abundances: &LinkedHashMap<usize, Vec<f64>>,
+    ) {
+        let mut genomes = self.genomes.lock().unwrap();
+        if genomes.sample_names.is_empty() {
+            genomes.sample_names = sample_names.iter().map(|name| name.to_string()).collect();
+        }
+        genomes
+            .abundances
+            .push((reference_name.to_string(), abundances.clone()));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genomes.lock().unwrap().abundances.is_empty()
+    }
+
+    /// Writes `<sample>_strain_abundances.profile` into the output directory for every sample,
+    /// listing the genomes by name. Returns the paths written
+    pub fn write<P: AsRef<Path>>(
+        &self,
+        output_directory: P,
+    ) -> Result<Vec<PathBuf>, BirdToolError> {
+        let mut genomes = self.genomes.lock().unwrap();
+        genomes.abundances.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut paths = Vec::with_capacity(genomes.sample_names.len());
+        for (sample_idx, sample_name) in genomes.sample_names.iter().enumerate() {
+            let path = output_directory.as_ref().join(format!(
+                "{}_{}",
+                sample_name,
+                AbundanceFormat::Cami.file_suffix()
+            ));
+            let write_error = |e: std::io::Error| {
+                BirdToolError::IOError(format!("Unable to write to {}: {}", path.display(), e))
+            };
+            let mut writer = BufWriter::new(File::create(&path).map_err(write_error)?);
+            AbundanceFormat::write_cami(&mut writer, sample_name, sample_idx, &genomes.abundances)
+                .map_err(write_error)?;
+            writer.flush().map_err(write_error)?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+}
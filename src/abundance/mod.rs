@@ -1,2 +1,7 @@
 pub mod abundance_calculator_engine;
+pub mod abundance_formats;
+pub mod reference_bias;
 pub mod strain_abundances_calculator;
+pub mod strain_count;
+pub mod strain_discrimination;
+pub mod strain_frequencies;
@@ -0,0 +1,264 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// Reference and alternate support of one sample at its heterozygous-like sites
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SampleReferenceBias {
+    pub reference_fractions: Vec<f64>,
+    pub reference_reads: u64,
+    pub alternate_reads: u64,
+}
+
+impl SampleReferenceBias {
+    pub fn new() -> Self {
+        Self {
+            reference_fractions: Vec::new(),
+            reference_reads: 0,
+            alternate_reads: 0,
+        }
+    }
+
+    pub fn add_site(&mut self, reference_reads: i32, alternate_reads: i32) {
+        let total = reference_reads + alternate_reads;
+        if total <= 0 {
+            return;
+        }
+        self.reference_fractions
+            .push(reference_reads as f64 / total as f64);
+        self.reference_reads += reference_reads as u64;
+        self.alternate_reads += alternate_reads as u64;
+    }
+
+    pub fn sites(&self) -> usize {
+        self.reference_fractions.len()
+    }
+
+    /// Mean fraction of reads supporting the reference allele, 0.5 without bias
+    pub fn mean_reference_fraction(&self) -> f64 {
+        if self.reference_fractions.is_empty() {
+            return f64::NAN;
+        }
+        self.reference_fractions.iter().sum::<f64>() / self.sites() as f64
+    }
+
+    pub fn median_reference_fraction(&self) -> f64 {
+        if self.reference_fractions.is_empty() {
+            return f64::NAN;
+        }
+        let mut fractions = self.reference_fractions.clone();
+        fractions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let middle = fractions.len() / 2;
+        if fractions.len() % 2 == 0 {
+            (fractions[middle - 1] + fractions[middle]) / 2.0
+        } else {
+            fractions[middle]
+        }
+    }
+
+    /// t statistic of the mean reference fraction against 0.5. Sites rather than reads are the
+    /// unit, as reads within a site are not independent
+    pub fn t_statistic(&self) -> f64 {
+        let n = self.sites();
+        if n < 2 {
+            return f64::NAN;
+        }
+        let mean = self.mean_reference_fraction();
+        let variance = self
+            .reference_fractions
+            .iter()
+            .map(|fraction| (fraction - mean).powi(2))
+            .sum::<f64>()
+            / (n - 1) as f64;
+        if variance <= f64::EPSILON {
+            return if (mean - 0.5).abs() <= f64::EPSILON {
+                0.0
+            } else {
+                (mean - 0.5).signum() * f64::INFINITY
+            };
+        }
+        (mean - 0.5) / (variance / n as f64).sqrt()
+    }
+
+    /// The ratio of reference to alternate reads expected at a site where both alleles are
+    /// equally abundant
+    pub fn bias_ratio(&self) -> f64 {
+        let mean = self.mean_reference_fraction();
+        if mean.is_nan() || mean >= 1.0 {
+            return f64::NAN;
+        }
+        mean / (1.0 - mean)
+    }
+}
+
+/// Reference bias of the allele depths of each sample, measured at biallelic SNVs where both
+/// alleles pass --depth-per-sample-filter and the alternate fraction lies within
+/// --reference-bias-min-fraction of 0.5. A sample is biased when it has at least `MIN_SITES` sites
+/// and the t statistic of its mean reference fraction against 0.5 exceeds `T_THRESHOLD`. With
+/// --correct-reference-bias the reference depths of biased samples are divided by their bias ratio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReferenceBias {
+    samples: Vec<SampleReferenceBias>,
+    depth_per_sample_filter: i32,
+    min_fraction: f64,
+}
+
+impl ReferenceBias {
+    pub const MIN_SITES: usize = 10;
+    pub const T_THRESHOLD: f64 = 3.0;
+    // corrections beyond these bounds are more likely to come from too few or unusual sites
+    const MIN_CORRECTION: f64 = 0.25;
+    const MAX_CORRECTION: f64 = 4.0;
+
+    pub fn new(n_samples: usize, depth_per_sample_filter: i32, min_fraction: f64) -> Self {
+        Self {
+            samples: vec![SampleReferenceBias::new(); n_samples],
+            depth_per_sample_filter: depth_per_sample_filter.max(1),
+            min_fraction: min_fraction.clamp(0.0, 0.5),
+        }
+    }
+
+    /// Whether the reference bias should be measured, i.e. whether --reference-bias or
+    /// --correct-reference-bias were given
+    pub fn requested(args: &clap::ArgMatches) -> bool {
+        ["reference-bias", "correct-reference-bias"]
+            .iter()
+            .any(|id| {
+                args.try_get_one::<bool>(id)
+                    .ok()
+                    .flatten()
+                    .copied()
+                    .unwrap_or(false)
+            })
+    }
+
+    pub fn from_args(
+        args: &clap::ArgMatches,
+        n_samples: usize,
+        depth_per_sample_filter: i64,
+    ) -> Self {
+        Self::new(
+            n_samples,
+            depth_per_sample_filter as i32,
+            args.try_get_one::<f64>("reference-bias-min-fraction")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(0.2),
+        )
+    }
+
+    pub fn samples(&self) -> &[SampleReferenceBias] {
+        &self.samples
+    }
+
+    /// The reference and alternate depths of a sample if the site is heterozygous-like in it
+    pub fn heterozygous_like(&self, ad: &[i32]) -> Option<(i32, i32)> {
+        if ad.len() != 2
+            || ad[0] < self.depth_per_sample_filter
+            || ad[1] < self.depth_per_sample_filter
+        {
+            return None;
+        }
+        let alternate_fraction = ad[1] as f64 / (ad[0] + ad[1]) as f64;
+        if alternate_fraction < self.min_fraction || alternate_fraction > 1.0 - self.min_fraction {
+            return None;
+        }
+        Some((ad[0], ad[1]))
+    }
+
+    /// Adds the heterozygous-like sites of every sample from unfiltered biallelic SNVs
+    pub fn add_contexts(&mut self, contexts: &[VariantContext]) {
+        for context in contexts.iter() {
+            if context.is_filtered()
+                || context.alleles.len() != 2
+                || context.alleles.iter().any(|allele| allele.len() != 1)
+            {
+                continue;
+            }
+            for (sample_index, genotype) in context.genotypes.genotypes().iter().enumerate() {
+                let site = self.heterozygous_like(&genotype.ad);
+                if let (Some((reference_reads, alternate_reads)), Some(sample)) =
+                    (site, self.samples.get_mut(sample_index))
+                {
+                    sample.add_site(reference_reads, alternate_reads);
+                }
+            }
+        }
+    }
+
+    /// Whether a sample shows a systematic bias towards the reference allele
+    pub fn is_biased(&self, sample_index: usize) -> bool {
+        let sample = &self.samples[sample_index];
+        sample.sites() >= Self::MIN_SITES && sample.t_statistic() > Self::T_THRESHOLD
+    }
+
+    /// The factor the reference depth of each sample is divided by: its bias ratio if the sample
+    /// is biased and 1 otherwise
+    pub fn correction_factors(&self) -> Vec<f64> {
+        (0..self.samples.len())
+            .map(|sample_index| {
+                if self.is_biased(sample_index) {
+                    self.samples[sample_index]
+                        .bias_ratio()
+                        .clamp(Self::MIN_CORRECTION, Self::MAX_CORRECTION)
+                } else {
+                    1.0
+                }
+            })
+            .collect()
+    }
+
+    /// Writes the bias of every sample to `<reference>_reference_bias.tsv`
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+    ) -> Result<(), BirdToolError> {
+        let file_name = format!("{}/{}_reference_bias.tsv", output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        let format_value = |value: f64| {
+            if value.is_finite() {
+                format!("{:.6}", value)
+            } else {
+                "NA".to_string()
+            }
+        };
+        writeln!(
+            writer,
+            "sample\tsites\treference_reads\talternate_reads\tmean_reference_fraction\t\
+            median_reference_fraction\tbias_ratio\tt_statistic\tbiased\tcorrection_factor"
+        )
+        .map_err(write_error)?;
+        let correction_factors = self.correction_factors();
+        for (sample_index, sample) in self.samples.iter().enumerate() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.6}",
+                sample_names.get(sample_index).copied().unwrap_or("NA"),
+                sample.sites(),
+                sample.reference_reads,
+                sample.alternate_reads,
+                format_value(sample.mean_reference_fraction()),
+                format_value(sample.median_reference_fraction()),
+                format_value(sample.bias_ratio()),
+                format_value(sample.t_statistic()),
+                self.is_biased(sample_index),
+                correction_factors[sample_index]
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
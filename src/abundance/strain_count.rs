@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// The strain count estimated for one sample from its within-sample polymorphic sites
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleStrainCount {
+    // sites at which the sample carries both the reference and an alternate allele
+    pub polymorphic_sites: usize,
+    pub strains: usize,
+}
+
+/// Estimates the number of strains of a genome in each sample before variant calling. The alternate
+/// counts of the polymorphic sites of a sample are fit with binomial mixtures at 1/k, ..., (k-1)/k
+/// for every k up to --max-estimated-ploidy, and the k with the lowest BIC is chosen. Samples with
+/// too few polymorphic sites carry a single strain. The largest estimate replaces --ploidy unless
+/// --per-genome-config sets one for the genome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrainCountEstimator {
+    max_strains: usize,
+}
+
+impl StrainCountEstimator {
+    pub const DEFAULT_MAX_STRAINS: usize = 6;
+    pub const MIN_SITES: usize = 20;
+    pub const MIN_POLYMORPHIC_RATE: f64 = 5e-4;
+    const EM_ITERATIONS: usize = 50;
+    const EM_TOLERANCE: f64 = 1e-6;
+
+    pub fn new(max_strains: usize) -> Self {
+        Self {
+            max_strains: max_strains.max(1),
+        }
+    }
+
+    /// Whether the strain count should be estimated, i.e. whether --estimate-ploidy was given
+    pub fn requested(args: &clap::ArgMatches) -> bool {
+        args.try_get_one::<bool>("estimate-ploidy")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            args.try_get_one::<usize>("max-estimated-ploidy")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(Self::DEFAULT_MAX_STRAINS),
+        )
+    }
+
+    pub fn max_strains(&self) -> usize {
+        self.max_strains
+    }
+
+    /// The alternate and total depth of each sample at each of the sites polymorphic within it
+    pub fn polymorphic_counts(
+        contexts: &[VariantContext],
+        n_samples: usize,
+    ) -> Vec<Vec<(u32, u32)>> {
+        let mut counts = vec![Vec::new(); n_samples];
+        for context in contexts.iter() {
+            for (sample_index, genotype) in context.genotypes.genotypes().iter().enumerate() {
+                if sample_index >= n_samples || genotype.ad.len() < 2 {
+                    continue;
+                }
+                let reference = genotype.ad[0].max(0) as u32;
+                let depth = genotype
+                    .ad
+                    .iter()
+                    .map(|depth| (*depth).max(0) as u32)
+                    .sum::<u32>();
+                if reference > 0 && depth > reference {
+                    counts[sample_index].push((depth - reference, depth));
+                }
+            }
+        }
+        counts
+    }
+
+    /// Log likelihood, up to a constant, of the alternate counts under a mixture of binomials at
+    /// 1/strains, ..., (strains - 1)/strains, with the weights fit by EM
+    pub fn log_likelihood(counts: &[(u32, u32)], strains: usize) -> f64 {
+        if strains < 2 || counts.is_empty() {
+            return f64::NEG_INFINITY;
+        }
+        let fractions = (1..strains)
+            .map(|component| component as f64 / strains as f64)
+            .collect::<Vec<f64>>();
+        // log probability of each site under each component
+        let component_log_probabilities = counts
+            .iter()
+            .map(|(alternate, depth)| {
+                let alternate = *alternate as f64;
+                let reference = (*depth as f64) - alternate;
+                fractions
+                    .iter()
+                    .map(|fraction| alternate * fraction.ln() + reference * (1.0 - fraction).ln())
+                    .collect::<Vec<f64>>()
+            })
+            .collect::<Vec<Vec<f64>>>();
+
+        let mut weights = vec![1.0 / fractions.len() as f64; fractions.len()];
+        let mut previous = f64::NEG_INFINITY;
+        let mut log_likelihood = f64::NEG_INFINITY;
+        for _ in 0..Self::EM_ITERATIONS {
+            let mut totals = vec![0.0; weights.len()];
+            log_likelihood = 0.0;
+            for site in component_log_probabilities.iter() {
+                let weighted = site
+                    .iter()
+                    .zip(weights.iter())
+                    .map(|(log_probability, weight)| log_probability + weight.ln())
+                    .collect::<Vec<f64>>();
+                let max = weighted.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                let sum = weighted
+                    .iter()
+                    .map(|value| (value - max).exp())
+                    .sum::<f64>();
+                let log_sum = max + sum.ln();
+                log_likelihood += log_sum;
+                for (total, value) in totals.iter_mut().zip(weighted.iter()) {
+                    *total += (value - log_sum).exp();
+                }
+            }
+            for (weight, total) in weights.iter_mut().zip(totals.iter()) {
+                // keeps every component possible so ln never sees 0
+                *weight = (total / counts.len() as f64).max(f64::MIN_POSITIVE);
+            }
+            if (log_likelihood - previous).abs() < Self::EM_TOLERANCE {
+                break;
+            }
+            previous = log_likelihood;
+        }
+        log_likelihood
+    }
+
+    /// The number of strains best explaining the polymorphic sites of one sample
+    pub fn estimate_sample(&self, counts: &[(u32, u32)], genome_size: u64) -> usize {
+        if counts.len() < Self::MIN_SITES
+            || (counts.len() as f64) < Self::MIN_POLYMORPHIC_RATE * genome_size as f64
+            || self.max_strains < 2
+        {
+            return 1;
+        }
+        let n_sites = (counts.len() as f64).ln();
+        let mut best = (2, f64::INFINITY);
+        for strains in 2..=self.max_strains {
+            // one free weight per component beyond the first
+            let bic = -2.0 * Self::log_likelihood(counts, strains) + (strains - 2) as f64 * n_sites;
+            if bic < best.1 {
+                best = (strains, bic);
+            }
+        }
+        best.0
+    }
+
+    /// The strain count of every sample, given the pileup sites of the genome
+    pub fn estimate(
+        &self,
+        contexts: &[VariantContext],
+        n_samples: usize,
+        genome_size: u64,
+    ) -> Vec<SampleStrainCount> {
+        Self::polymorphic_counts(contexts, n_samples)
+            .into_iter()
+            .map(|counts| SampleStrainCount {
+                polymorphic_sites: counts.len(),
+                strains: self.estimate_sample(&counts, genome_size),
+            })
+            .collect()
+    }
+
+    /// The strain count of the genome, the largest across samples
+    pub fn genome_estimate(samples: &[SampleStrainCount]) -> usize {
+        samples
+            .iter()
+            .map(|sample| sample.strains)
+            .max()
+            .unwrap_or(1)
+            .max(1)
+    }
+
+    /// Writes the estimate of every sample to `<reference>_strain_count.tsv`
+    pub fn write(
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+        samples: &[SampleStrainCount],
+    ) -> Result<(), BirdToolError> {
+        let file_name = format!("{}/{}_strain_count.tsv", output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(writer, "sample\tpolymorphic_sites\testimated_strains").map_err(write_error)?;
+        for (sample_index, sample) in samples.iter().enumerate() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                sample_names.get(sample_index).copied().unwrap_or("NA"),
+                sample.polymorphic_sites,
+                sample.strains
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
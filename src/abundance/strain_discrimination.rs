@@ -0,0 +1,132 @@
+use crate::abundance::strain_frequencies::StrainFrequencyEstimator;
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+
+/// Strain discriminating power (SDP) of each strain assigned variant: the scaled variance p(1 - p)
+/// of its allele frequency between strains, times its concordance with the allele fractions the
+/// strain frequencies of each sample predict.
+pub struct StrainDiscrimination {
+    strain_ids: Vec<usize>,
+    // strain frequencies of each sample, in the order of the strain ids
+    sample_frequencies: Vec<Vec<f64>>,
+}
+
+impl StrainDiscrimination {
+    pub fn new(strain_ids: &[usize], sample_frequencies: Vec<Vec<f64>>) -> Self {
+        Self {
+            strain_ids: strain_ids.to_vec(),
+            sample_frequencies,
+        }
+    }
+
+    /// Estimates the strain frequencies of each sample from the strain assigned variants
+    pub fn from_contexts(
+        contexts: &[VariantContext],
+        strain_ids: &[usize],
+        n_samples: usize,
+    ) -> Self {
+        let estimator = StrainFrequencyEstimator::new(strain_ids);
+        let sample_frequencies = (0..n_samples)
+            .map(|sample_index| {
+                estimator
+                    .estimate(&estimator.sample_sites(contexts, sample_index))
+                    .into_iter()
+                    .map(|estimate| estimate.frequency)
+                    .collect::<Vec<f64>>()
+            })
+            .collect::<Vec<Vec<f64>>>();
+        Self::new(strain_ids, sample_frequencies)
+    }
+
+    /// Between strain variance of the allele frequency when `n_carriers` of `n_strains` strains
+    /// carry the alternate allele, scaled to lie between zero and one
+    pub fn between_strain_variance(n_carriers: usize, n_strains: usize) -> f64 {
+        if n_strains == 0 {
+            return 0.0;
+        }
+        let p = n_carriers.min(n_strains) as f64 / n_strains as f64;
+        4.0 * p * (1.0 - p)
+    }
+
+    /// One minus the read weighted mean absolute difference between the expected and observed
+    /// allele fractions of the samples. `samples` holds the ref and alt read counts of each sample
+    pub fn concordance(&self, carriers: &[bool], samples: &[(f64, f64)]) -> f64 {
+        let (total_error, total_reads) = samples
+            .iter()
+            .zip(self.sample_frequencies.iter())
+            .filter(|((ref_reads, alt_reads), _)| ref_reads + alt_reads > 0.0)
+            .fold(
+                (0.0, 0.0),
+                |(error, reads), ((ref_reads, alt_reads), frequencies)| {
+                    let depth = ref_reads + alt_reads;
+                    let expected = frequencies
+                        .iter()
+                        .zip(carriers.iter())
+                        .filter(|(_, carries_alt)| **carries_alt)
+                        .map(|(frequency, _)| *frequency)
+                        .sum::<f64>()
+                        .min(1.0);
+                    let observed = alt_reads / depth;
+                    (error + depth * (expected - observed).abs(), reads + depth)
+                },
+            );
+
+        if total_reads > 0.0 {
+            1.0 - total_error / total_reads
+        } else {
+            0.0
+        }
+    }
+
+    /// The strain discriminating power of a variant, or None if it was not assigned to strains
+    pub fn score(&self, vc: &VariantContext) -> Option<f64> {
+        let strains = match vc.attributes.get(VariantAnnotations::Strain.to_key()) {
+            Some(AttributeObject::VecUnsize(strains)) => strains,
+            _ => return None,
+        };
+        let carriers = self
+            .strain_ids
+            .iter()
+            .map(|strain_id| strains.contains(strain_id))
+            .collect::<Vec<bool>>();
+        let n_carriers = carriers.iter().filter(|carries_alt| **carries_alt).count();
+
+        let samples = vc
+            .genotypes
+            .genotypes()
+            .iter()
+            .map(|genotype| {
+                if genotype.ad.len() < 2 {
+                    return (0.0, 0.0);
+                }
+                let ref_reads = genotype.ad[0].max(0) as f64;
+                let alt_reads = genotype.ad[1..].iter().map(|ad| (*ad).max(0)).sum::<i32>() as f64;
+                (ref_reads, alt_reads)
+            })
+            .collect::<Vec<(f64, f64)>>();
+
+        Some(
+            Self::between_strain_variance(n_carriers, self.strain_ids.len())
+                * self.concordance(&carriers, &samples),
+        )
+    }
+
+    /// Annotates each strain assigned variant with its strain discriminating power. Returns the
+    /// number of annotated variants
+    pub fn annotate_contexts(&self, contexts: &mut [VariantContext]) -> usize {
+        let mut annotated = 0;
+        for vc in contexts.iter_mut() {
+            if let Some(score) = self.score(vc) {
+                vc.set_attribute(
+                    VariantAnnotations::StrainDiscriminatingPower
+                        .to_key()
+                        .to_string(),
+                    AttributeObject::f64(score),
+                );
+                annotated += 1;
+            }
+        }
+        annotated
+    }
+}
@@ -0,0 +1,246 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// The reads of one sample at a variant site along with the strains carrying the alternate allele.
+/// Strains not carrying the alternate allele are taken to carry the reference allele
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrainSite {
+    // indices into the strain ids of the estimator
+    pub alt_strains: Vec<usize>,
+    pub ref_reads: f64,
+    pub alt_reads: f64,
+    // confidence in the genotype of the sample at this site, between 0 and 1
+    pub weight: f64,
+}
+
+/// The estimated frequency of a strain within a sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct StrainFrequency {
+    pub strain: usize,
+    pub frequency: f64,
+    pub standard_error: f64,
+    // expected number of reads assigned to the strain
+    pub reads: f64,
+    // number of sites at which the strain could be told apart from another strain
+    pub sites: usize,
+}
+
+/// Estimates the frequency of each strain within each sample by EM over the reads at strain
+/// assigned variants. Reads are shared between the strains carrying the allele they support and
+/// weighted by the confidence of the sample genotype, giving frequencies that sum to one with a
+/// binomial standard error.
+pub struct StrainFrequencyEstimator {
+    strain_ids: Vec<usize>,
+    reference_bias_correction: Vec<f64>,
+}
+
+impl StrainFrequencyEstimator {
+    const MAX_ITERATIONS: usize = 1000;
+    const TOLERANCE: f64 = 1e-8;
+
+    pub fn new(strain_ids: &[usize]) -> Self {
+        Self {
+            strain_ids: strain_ids.to_vec(),
+            reference_bias_correction: Vec::new(),
+        }
+    }
+
+    /// Factors the reference reads of each sample are divided by, see
+    /// `ReferenceBias::correction_factors`
+    pub fn set_reference_bias_correction(&mut self, reference_bias_correction: Vec<f64>) {
+        self.reference_bias_correction = reference_bias_correction;
+    }
+
+    /// The sites of a sample, taken from variants annotated with their strains. Variants without
+    /// a strain annotation or reads in the sample are skipped
+    pub fn sample_sites(
+        &self,
+        contexts: &[VariantContext],
+        sample_index: usize,
+    ) -> Vec<StrainSite> {
+        let reference_correction = self
+            .reference_bias_correction
+            .get(sample_index)
+            .copied()
+            .unwrap_or(1.0);
+        contexts
+            .iter()
+            .filter_map(|vc| {
+                let strains = match vc.attributes.get(VariantAnnotations::Strain.to_key()) {
+                    Some(AttributeObject::VecUnsize(strains)) => strains,
+                    _ => return None,
+                };
+                let genotype = vc.genotypes.genotypes().get(sample_index)?;
+                if genotype.ad.len() < 2 {
+                    return None;
+                }
+                let ref_reads = genotype.ad[0].max(0) as f64 / reference_correction;
+                let alt_reads = genotype.ad[1..].iter().map(|ad| (*ad).max(0)).sum::<i32>() as f64;
+                if ref_reads + alt_reads <= 0.0 {
+                    return None;
+                }
+                let weight = if genotype.gq >= 0 {
+                    1.0 - 10.0_f64.powf(-(genotype.gq as f64) / 10.0)
+                } else {
+                    1.0
+                };
+
+                Some(StrainSite {
+                    alt_strains: self
+                        .strain_ids
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, strain_id)| strains.contains(strain_id))
+                        .map(|(index, _)| index)
+                        .collect(),
+                    ref_reads,
+                    alt_reads,
+                    weight,
+                })
+            })
+            .collect()
+    }
+
+    /// Estimates the strain frequencies of a single sample from its sites
+    pub fn estimate(&self, sites: &[StrainSite]) -> Vec<StrainFrequency> {
+        let n_strains = self.strain_ids.len();
+        if n_strains == 0 {
+            return Vec::new();
+        }
+
+        // whether the alleles at a site tell at least two of the strains apart
+        let informative =
+            |site: &StrainSite| !site.alt_strains.is_empty() && site.alt_strains.len() < n_strains;
+
+        let mut frequencies = vec![1.0 / n_strains as f64; n_strains];
+        let mut assigned = vec![0.0; n_strains];
+        for _ in 0..Self::MAX_ITERATIONS {
+            assigned = vec![0.0; n_strains];
+            for site in sites.iter() {
+                let carries_alt = (0..n_strains)
+                    .map(|strain| site.alt_strains.contains(&strain))
+                    .collect::<Vec<bool>>();
+                for (reads, allele_is_alt) in [(site.ref_reads, false), (site.alt_reads, true)] {
+                    let carrier_frequency = (0..n_strains)
+                        .filter(|strain| carries_alt[*strain] == allele_is_alt)
+                        .map(|strain| frequencies[strain])
+                        .sum::<f64>();
+                    // reads supporting an allele no strain carries are treated as errors
+                    if carrier_frequency <= f64::EPSILON {
+                        continue;
+                    }
+                    for strain in
+                        (0..n_strains).filter(|strain| carries_alt[*strain] == allele_is_alt)
+                    {
+                        assigned[strain] +=
+                            site.weight * reads * frequencies[strain] / carrier_frequency;
+                    }
+                }
+            }
+
+            let total = assigned.iter().sum::<f64>();
+            if total <= f64::EPSILON {
+                break;
+            }
+            let updated = assigned
+                .iter()
+                .map(|reads| reads / total)
+                .collect::<Vec<f64>>();
+            let change = updated
+                .iter()
+                .zip(frequencies.iter())
+                .map(|(curr, prev)| (curr - prev).abs())
+                .sum::<f64>();
+            frequencies = updated;
+            if change < Self::TOLERANCE {
+                break;
+            }
+        }
+
+        let (effective_reads, informative_sites) = sites
+            .iter()
+            .filter(|site| informative(site))
+            .fold((0.0, 0), |(reads, count), site| {
+                (
+                    reads + site.weight * (site.ref_reads + site.alt_reads),
+                    count + 1,
+                )
+            });
+
+        (0..n_strains)
+            .map(|strain| {
+                let frequency = frequencies[strain];
+                let standard_error = if effective_reads > 0.0 {
+                    (frequency * (1.0 - frequency) / effective_reads).sqrt()
+                } else {
+                    f64::NAN
+                };
+
+                StrainFrequency {
+                    strain: self.strain_ids[strain],
+                    frequency,
+                    standard_error,
+                    reads: assigned[strain],
+                    sites: informative_sites,
+                }
+            })
+            .collect()
+    }
+
+    /// Estimates the strain frequencies of every sample and writes them to
+    /// {output_prefix}/{reference_name}_strain_frequencies.tsv
+    pub fn write_strain_frequencies(
+        &self,
+        contexts: &[VariantContext],
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+    ) -> Result<(), BirdToolError> {
+        let file_name = format!(
+            "{}/{}_strain_frequencies.tsv",
+            output_prefix, reference_name
+        );
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(
+            writer,
+            "sample\tstrain\tfrequency\tstandard_error\treads\tsites"
+        )
+        .map_err(write_error)?;
+        for (sample_index, sample_name) in sample_names.iter().enumerate() {
+            let sites = self.sample_sites(contexts, sample_index);
+            for estimate in self.estimate(&sites) {
+                let standard_error = if estimate.standard_error.is_nan() {
+                    "NA".to_string()
+                } else {
+                    format!("{:.6}", estimate.standard_error)
+                };
+                writeln!(
+                    writer,
+                    "{}\tstrain_{}\t{:.6}\t{}\t{:.2}\t{}",
+                    sample_name,
+                    estimate.strain,
+                    estimate.frequency,
+                    standard_error,
+                    estimate.reads,
+                    estimate.sites
+                )
+                .map_err(write_error)?;
+            }
+        }
+
+        writer.flush().map_err(write_error)
+    }
+}
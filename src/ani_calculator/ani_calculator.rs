@@ -7,6 +7,8 @@ use std::path::Path;
 
 use crate::model::variant_context::VariantContext;
 use crate::model::variant_context_utils::VariantContextUtils;
+use crate::phylogeny::upgma::upgma;
+use crate::utils::errors::BirdToolError;
 
 
 /// Holds the population and consensus ANI & Fst arrays
@@ -35,23 +37,53 @@ use crate::model::variant_context_utils::VariantContextUtils;
 ///
 /// Since lorikeet calls Indels, we compare the length of the allele rather than just the position
 /// So the rather than alleles different, it is bases different.
+///
+/// Alongside the ANI tables, the number of SNVs at which the consensus alleles of each pair of
+/// samples differ is counted (the diagonal counts SNVs against the reference). With
+/// --sample-dendrogram these distances, per compared base, are clustered with UPGMA into a
+/// dendrogram of the samples written in Newick format.
 pub struct ANICalculator {
     popANI: Array2<f32>,
     subpopANI: Array2<f32>,
     conANI: Array2<f32>,
+    snv_distances: Array2<f32>,
+    compared_bases: Array2<f32>,
+    write_dendrogram: bool,
     // fst: Array2<f64>
 }
 
 impl ANICalculator {
+    /// The names of the tables, used as the suffix of their file names
+    pub const TABLE_NAMES: [&'static str; 3] =
+        ["consensus_ani", "population_ani", "subpopulation_ani"];
+    /// The suffix of the file name of the pairwise SNV distance table
+    pub const DISTANCE_TABLE_NAME: &'static str = "snv_distances";
+    /// The suffix of the file name of the sample dendrogram
+    pub const DENDROGRAM_NAME: &'static str = "sample_dendrogram";
+
     pub fn new(n_samples: usize) -> Self {
         Self {
             popANI: Array2::default((n_samples, n_samples)),
             subpopANI: Array2::default((n_samples, n_samples)),
             conANI: Array2::default((n_samples, n_samples)),
+            snv_distances: Array2::default((n_samples, n_samples)),
+            compared_bases: Array2::default((n_samples, n_samples)),
+            write_dendrogram: false,
             // fst: Array2::default((n_samples, n_samples)),
         }
     }
 
+    pub fn from_args(args: &clap::ArgMatches, n_samples: usize) -> Self {
+        let mut ani_calculator = Self::new(n_samples);
+        ani_calculator.write_dendrogram = args
+            .try_get_one::<bool>("sample-dendrogram")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        ani_calculator
+    }
+
     pub fn run_calculator(
         &mut self,
         contexts: &mut [VariantContext],
@@ -63,11 +95,34 @@ impl ANICalculator {
         qual_by_depth_filter: f64,
         qual_threshold: f64,
         depth_per_sample_filter: i64,
+    ) {
+        self.calculate(
+            contexts,
+            genome_size,
+            compared_bases,
+            qual_by_depth_filter,
+            qual_threshold,
+            depth_per_sample_filter,
+        );
+        self.write_tables(output_prefix, sample_names, reference_name);
+    }
+
+    /// Calculates the ANI tables without writing them. If `compared_bases` is not provided, every
+    /// base of the genome is assumed to be comparable between every pair of samples
+    pub fn calculate(
+        &mut self,
+        contexts: &mut [VariantContext],
+        genome_size: u64,
+        compared_bases: Option<Array2<f32>>,
+        qual_by_depth_filter: f64,
+        qual_threshold: f64,
+        depth_per_sample_filter: i64,
     ) {
         let compared_bases = match compared_bases {
             Some(compared_bases) => compared_bases,
-            None => Self::calculate_compared_bases(None, genome_size, sample_names.len()),
+            None => Self::calculate_compared_bases(None, genome_size, self.conANI.ncols()),
         };
+        self.compared_bases = compared_bases.clone();
         // debug!("Comparable bases \n{:?}", &compared_bases);
         self.calculate_from_contexts(
             contexts,
@@ -77,28 +132,108 @@ impl ANICalculator {
             depth_per_sample_filter,
             compared_bases,
         );
+    }
 
+    /// The table with the given name, one of `TABLE_NAMES`
+    pub fn table_mut(&mut self, table_name: &str) -> Option<&mut Array2<f32>> {
+        match table_name {
+            "consensus_ani" => Some(&mut self.conANI),
+            "population_ani" => Some(&mut self.popANI),
+            "subpopulation_ani" => Some(&mut self.subpopANI),
+            _ => None,
+        }
+    }
+
+    /// The number of SNVs at which the consensus alleles of each pair of samples differ. The
+    /// diagonal holds the SNVs at which each sample differs from the reference
+    pub fn snv_distances(&self) -> &Array2<f32> {
+        &self.snv_distances
+    }
+
+    /// The SNV distances between samples per compared base, with a diagonal of 0
+    pub fn sample_distances(&self) -> Array2<f64> {
+        Array2::from_shape_fn(self.snv_distances.raw_dim(), |(i, j)| {
+            let compared_bases = self.compared_bases[[i, j]];
+            if i == j || compared_bases <= 0.0 {
+                0.0
+            } else {
+                (self.snv_distances[[i, j]] / compared_bases) as f64
+            }
+        })
+    }
+
+    /// UPGMA dendrogram of the samples in Newick format
+    pub fn dendrogram(&self, sample_names: &[&str]) -> String {
+        let names = sample_names
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<String>>();
+        upgma(&names, &self.sample_distances())
+    }
+
+    pub fn write_tables(&self, output_prefix: &str, sample_names: &[&str], reference_name: &str) {
+        for (table_name, table) in Self::TABLE_NAMES
+            .iter()
+            .zip([&self.conANI, &self.popANI, &self.subpopANI])
+        {
+            Self::write_ani_tables(
+                output_prefix,
+                sample_names,
+                reference_name,
+                table,
+                table_name,
+                8,
+            );
+        }
         Self::write_ani_tables(
             output_prefix,
             sample_names,
             reference_name,
-            &self.conANI,
-            "consensus_ani",
-        );
-        Self::write_ani_tables(
-            output_prefix,
-            sample_names,
-            reference_name,
-            &self.popANI,
-            "population_ani",
-        );
-        Self::write_ani_tables(
-            output_prefix,
-            sample_names,
-            reference_name,
-            &self.subpopANI,
-            "subpopulation_ani",
+            &self.snv_distances,
+            Self::DISTANCE_TABLE_NAME,
+            0,
         );
+
+        if self.write_dendrogram {
+            let file_name = format!(
+                "{}/{}_{}.nwk",
+                output_prefix,
+                reference_name,
+                Self::DENDROGRAM_NAME
+            );
+            std::fs::write(&file_name, format!("{}\n", self.dendrogram(sample_names)))
+                .unwrap_or_else(|e| panic!("Unable to write {}: {:?}", file_name, e));
+        }
+    }
+
+    /// Reads a table written by `write_tables` back in
+    pub fn read_ani_table(file_name: &str) -> Result<Array2<f32>, BirdToolError> {
+        let contents = std::fs::read_to_string(file_name).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read ANI table {}: {}", file_name, e))
+        })?;
+        let rows = contents
+            .lines()
+            .filter(|line| !line.starts_with("##") && !line.starts_with("SampleID"))
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                line.split('\t')
+                    .skip(1)
+                    .map(|value| value.trim().parse::<f32>())
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|e| {
+                        BirdToolError::IOError(format!(
+                            "Invalid value in ANI table {}: {}",
+                            file_name, e
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<Vec<f32>>, BirdToolError>>()?;
+
+        let n_samples = rows.len();
+        if rows.iter().any(|row| row.len() != n_samples) {
+            return Err(BirdToolError::IOError(format!("ANI table {} is not square", file_name)));
+        }
+        Ok(Array2::from_shape_fn((n_samples, n_samples), |(i, j)| rows[i][j]))
     }
 
     pub fn calculate_compared_bases(
@@ -244,6 +379,12 @@ impl ANICalculator {
                         let allele_present_2 = &present_alleles[sample_idx_2];
 
                         if consensus_1 != consensus_2 {
+                            if context.alleles[*consensus_1].len() == 1
+                                && context.alleles[*consensus_2].len() == 1
+                            {
+                                self.snv_distances[[sample_idx_1, sample_idx_2]] += 1.0;
+                                self.snv_distances[[sample_idx_2, sample_idx_1]] += 1.0;
+                            }
                             if context.alleles[*consensus_1].len() > 1
                                 || context.alleles[*consensus_2].len() > 1
                             {
@@ -294,6 +435,11 @@ impl ANICalculator {
                         let allele_present_1 = &present_alleles[sample_idx_1];
 
                         if *consensus_1 != 0 {
+                            if context.alleles[*consensus_1].len() == 1
+                                && context.alleles[0].len() == 1
+                            {
+                                self.snv_distances[[sample_idx_1, sample_idx_1]] += 1.0;
+                            }
                             if context.alleles[*consensus_1].len() > 1
                                 || context.alleles[0].len() > 1
                             {
@@ -356,6 +502,7 @@ impl ANICalculator {
         reference_name: &str,
         table: &Array2<f32>,
         table_name: &str,
+        precision: usize,
     ) {
         // debug!("Printing ani calculations {}", reference_name);
         let file_name = format!("{}/{}_{}.tsv", output_prefix, reference_name, table_name);
@@ -397,7 +544,7 @@ impl ANICalculator {
             write!(file_open, "{}", counter + 1).unwrap();
             counter += 1;
             for ani in ani_vals {
-                write!(file_open, "\t{:.8}", ani).unwrap();
+                write!(file_open, "\t{:.*}", precision, ani).unwrap();
             }
             writeln!(file_open).unwrap();
         }
@@ -0,0 +1,78 @@
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::{AttributeObject, Genotype};
+use crate::model::variant_context::VariantContext;
+
+/// The reads counted in the denominator of the per-sample AF FORMAT field. `Informative` divides by
+/// the sum of AD, `All` also counts the overlapping reads that could not be assigned to an allele.
+/// Genotypes read back from a VCF file always use AD alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlleleFractionDenominator {
+    Informative,
+    All,
+}
+
+impl AlleleFractionDenominator {
+    /// Genotype attribute holding the number of reads that could not be assigned to any allele.
+    /// It is not written to the VCF
+    pub const UNASSIGNED_READS_KEY: &'static str = "UNASSIGNED_READS";
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        match args
+            .try_get_one::<String>("allele-fraction-denominator")
+            .ok()
+            .flatten()
+            .map(|denominator| denominator.as_str())
+        {
+            Some("all") => Self::All,
+            _ => Self::Informative,
+        }
+    }
+
+    /// The fraction of the reads of a genotype supporting each alternate allele. None if the
+    /// genotype has no allele depths or no reads
+    pub fn allele_fractions(&self, genotype: &Genotype) -> Option<Vec<f64>> {
+        if genotype.ad.len() < 2 {
+            return None;
+        }
+        let mut denominator = genotype
+            .ad
+            .iter()
+            .map(|depth| (*depth).max(0) as u64)
+            .sum::<u64>();
+        if *self == Self::All {
+            if let Some(AttributeObject::UnsizedInteger(unassigned)) =
+                genotype.attributes.get(Self::UNASSIGNED_READS_KEY)
+            {
+                denominator += *unassigned as u64;
+            }
+        }
+        if denominator == 0 {
+            return None;
+        }
+
+        Some(
+            genotype.ad[1..]
+                .iter()
+                .map(|depth| (*depth).max(0) as f64 / denominator as f64)
+                .collect(),
+        )
+    }
+
+    /// Sets the AF attribute of every genotype from its final allele depths, after any alleles
+    /// have been removed by genotyping
+    pub fn annotate_contexts(&self, contexts: &mut [VariantContext]) {
+        let key = VariantAnnotations::AlleleFraction.to_key();
+        for context in contexts.iter_mut() {
+            for genotype in context.genotypes.genotypes_mut() {
+                match self.allele_fractions(genotype) {
+                    Some(fractions) => {
+                        genotype.attribute(key.to_string(), AttributeObject::Vecf64(fractions))
+                    }
+                    None => {
+                        genotype.attributes.remove(key);
+                    }
+                }
+            }
+        }
+    }
+}
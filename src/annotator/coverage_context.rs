@@ -0,0 +1,225 @@
+use rayon::prelude::*;
+use rust_htslib::bam::ext::BamRecordExtensions;
+use rust_htslib::bam::{self, Read, Record};
+use std::collections::HashMap;
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::model::variants::Filter;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
+
+/// Mean read depth of every window along each contig of a genome in a single sample
+#[derive(Debug, Clone)]
+pub struct WindowDepths {
+    pub window_size: usize,
+    pub depths: HashMap<usize, Vec<f64>>,
+}
+
+impl WindowDepths {
+    /// Depth of the window containing `pos`, or None if the contig was not read
+    pub fn depth_at(&self, tid: usize, pos: usize) -> Option<f64> {
+        self.depths
+            .get(&tid)
+            .and_then(|windows| windows.get(pos / self.window_size))
+            .copied()
+    }
+
+    /// Median window depth across the genome. Only full windows are used, as the depth of the
+    /// last window of each contig is diluted by reads hanging off the end
+    pub fn median(&self, contig_lengths: &HashMap<usize, u64>) -> f64 {
+        let mut depths = self
+            .depths
+            .iter()
+            .flat_map(|(tid, windows)| {
+                let full_windows = contig_lengths
+                    .get(tid)
+                    .map(|length| *length as usize / self.window_size)
+                    .unwrap_or(0);
+                windows.iter().take(full_windows).copied()
+            })
+            .collect::<Vec<f64>>();
+        if depths.is_empty() {
+            return 0.0;
+        }
+
+        depths.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        let middle = depths.len() / 2;
+        if depths.len() % 2 == 0 {
+            (depths[middle - 1] + depths[middle]) / 2.0
+        } else {
+            depths[middle]
+        }
+    }
+}
+
+/// Copy number ratio (CNR) of the window around each variant: the depth of each sample in fixed
+/// windows, normalized by its median window depth and pooled across samples. Variants outside of
+/// the accepted range get the CopyNumber filter but are still used for ANI, Fst and dN/dS.
+#[derive(Debug, Clone)]
+pub struct CoverageContext {
+    pub window_size: usize,
+    pub min_ratio: f64,
+    pub max_ratio: f64,
+}
+
+impl CoverageContext {
+    pub fn new(window_size: usize, min_ratio: f64, max_ratio: f64) -> Self {
+        Self {
+            window_size: window_size.max(1),
+            min_ratio,
+            max_ratio,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            *args.get_one::<usize>("coverage-window-size").unwrap(),
+            *args.get_one::<f64>("min-copy-number-ratio").unwrap(),
+            *args.get_one::<f64>("max-copy-number-ratio").unwrap(),
+        )
+    }
+
+    /// Adds the aligned bases of a read to the windows it overlaps
+    pub fn add_read(windows: &mut [f64], window_size: usize, record: &Record) {
+        for [start, end] in record.aligned_blocks() {
+            let mut pos = start.max(0) as usize;
+            let end = end.max(0) as usize;
+            while pos < end {
+                let window = pos / window_size;
+                if window >= windows.len() {
+                    break;
+                }
+                let window_end = ((window + 1) * window_size).min(end);
+                windows[window] += (window_end - pos) as f64;
+                pos = window_end;
+            }
+        }
+    }
+
+    /// Measures the depth of every window along the given contigs of a BAM file. Unmapped,
+    /// secondary, supplementary and duplicate reads are not counted
+    pub fn window_depths(
+        &self,
+        bam_path: &str,
+        contig_lengths: &HashMap<usize, u64>,
+    ) -> Result<WindowDepths, BirdToolError> {
+        let mut reader = bam::IndexedReader::from_path(bam_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read BAM file {}: {}", bam_path, e))
+        })?;
+
+        let mut depths = HashMap::with_capacity(contig_lengths.len());
+        let mut record = Record::new();
+        for (tid, length) in contig_lengths.iter() {
+            let length = *length as usize;
+            let mut windows = vec![0.0; (length + self.window_size - 1) / self.window_size];
+            reader.fetch(*tid as u32).map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to fetch contig {} from {}: {}",
+                    tid, bam_path, e
+                ))
+            })?;
+            while let Some(result) = reader.read(&mut record) {
+                result.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read record in {}: {}", bam_path, e))
+                })?;
+                if record.is_unmapped()
+                    || record.is_secondary()
+                    || record.is_supplementary()
+                    || record.is_duplicate()
+                {
+                    continue;
+                }
+                Self::add_read(&mut windows, self.window_size, &record);
+            }
+
+            for (window, depth) in windows.iter_mut().enumerate() {
+                let window_length = (length - window * self.window_size).min(self.window_size);
+                *depth /= window_length as f64;
+            }
+            depths.insert(*tid, windows);
+        }
+
+        Ok(WindowDepths {
+            window_size: self.window_size,
+            depths,
+        })
+    }
+
+    /// Depth at a position relative to the median depth, pooled across samples. None if no
+    /// sample has any coverage
+    pub fn copy_number_ratio(
+        samples: &[(WindowDepths, f64)],
+        tid: usize,
+        pos: usize,
+    ) -> Option<f64> {
+        let (depth, median) = samples
+            .iter()
+            .filter(|(_, median)| *median > 0.0)
+            .filter_map(|(depths, median)| depths.depth_at(tid, pos).map(|d| (d, *median)))
+            .fold((0.0, 0.0), |(depth, total), (d, median)| (depth + d, total + median));
+        if median > 0.0 {
+            Some(depth / median)
+        } else {
+            None
+        }
+    }
+
+    /// Annotates a single variant with its copy number ratio. Returns true if the variant was
+    /// filtered
+    pub fn annotate(&self, vc: &mut VariantContext, ratio: f64) -> bool {
+        vc.set_attribute(
+            VariantAnnotations::CopyNumberRatio.to_key().to_string(),
+            AttributeObject::f64(ratio),
+        );
+
+        if ratio < self.min_ratio || ratio > self.max_ratio {
+            vc.filter(Filter::CopyNumber);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Annotates every variant of the reference at `ref_idx` using the depths of the given
+    /// BAM files. Returns the number of filtered variants
+    pub fn annotate_contexts(
+        &self,
+        contexts: &mut [VariantContext],
+        bam_paths: &[String],
+        reference_reader: &ReferenceReader,
+        ref_idx: usize,
+    ) -> Result<usize, BirdToolError> {
+        if contexts.is_empty() || bam_paths.is_empty() {
+            return Ok(0);
+        }
+
+        let contig_lengths = match reference_reader.retrieve_tids_for_ref_index(ref_idx) {
+            Some(tids) => tids
+                .iter()
+                .filter_map(|tid| reference_reader.target_lens.get(tid).map(|l| (*tid, *l)))
+                .collect::<HashMap<usize, u64>>(),
+            None => return Ok(0),
+        };
+
+        let samples = bam_paths
+            .par_iter()
+            .map(|bam_path| {
+                let depths = self.window_depths(bam_path, &contig_lengths)?;
+                let median = depths.median(&contig_lengths);
+                Ok((depths, median))
+            })
+            .collect::<Result<Vec<(WindowDepths, f64)>, BirdToolError>>()?;
+
+        let mut filtered = 0;
+        for vc in contexts.iter_mut() {
+            if let Some(ratio) = Self::copy_number_ratio(&samples, vc.loc.tid, vc.loc.start) {
+                if self.annotate(vc, ratio) {
+                    filtered += 1;
+                }
+            }
+        }
+        Ok(filtered)
+    }
+}
@@ -1,3 +1,7 @@
+pub mod allele_fraction;
+pub mod coverage_context;
+pub mod repeat_context;
+pub mod sequence_complexity;
 pub mod tandem_repeat;
 pub mod variant_annotation;
 pub mod variant_annotator_engine;
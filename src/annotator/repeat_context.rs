@@ -0,0 +1,117 @@
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::model::variant_context_utils::VariantContextUtils;
+use crate::model::variants::Filter;
+use crate::reference::reference_reader::ReferenceReader;
+
+/// Homopolymer run (HRUN) and tandem repeat context (RU, RPA) of each variant, taken from the
+/// reference. Indels in homopolymers at least `homopolymer_indel_filter` long can be given the
+/// HomopolymerIndel filter, as long reads produce many false indels there.
+#[derive(Debug, Clone)]
+pub struct RepeatContext {
+    pub homopolymer_indel_filter: Option<usize>,
+}
+
+impl RepeatContext {
+    pub fn new(homopolymer_indel_filter: Option<usize>) -> Self {
+        Self {
+            homopolymer_indel_filter,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            args.try_get_one::<usize>("homopolymer-indel-filter")
+                .ok()
+                .flatten()
+                .copied(),
+        )
+    }
+
+    /// Length of the run of identical bases in `reference` that contains `pos`
+    pub fn run_length_at(reference: &[u8], pos: usize) -> usize {
+        if pos >= reference.len() {
+            return 0;
+        }
+        let base = reference[pos].to_ascii_uppercase();
+        let left = reference[..pos]
+            .iter()
+            .rev()
+            .take_while(|b| b.to_ascii_uppercase() == base)
+            .count();
+        let right = reference[pos + 1..]
+            .iter()
+            .take_while(|b| b.to_ascii_uppercase() == base)
+            .count();
+        left + 1 + right
+    }
+
+    /// The longest homopolymer run touching a variant. SNVs and MNVs consider the bases either side of
+    /// the variant, indels consider the padding base and the first base after it
+    pub fn homopolymer_run(vc: &mut VariantContext, reference: &[u8]) -> usize {
+        let pos = vc.loc.start;
+        let window = if vc.is_indel() {
+            pos..=pos + 1
+        } else {
+            pos.saturating_sub(1)..=vc.loc.end + 1
+        };
+        window
+            .map(|p| Self::run_length_at(reference, p))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Annotates a single variant given the full sequence of its contig. Returns true if the
+    /// variant was filtered
+    pub fn annotate(&self, vc: &mut VariantContext, reference: &[u8]) -> bool {
+        let homopolymer_run = Self::homopolymer_run(vc, reference);
+        vc.set_attribute(
+            VariantAnnotations::HomopolymerRun.to_key().to_string(),
+            AttributeObject::I32(homopolymer_run as i32),
+        );
+
+        if !vc.is_indel() {
+            return false;
+        }
+
+        let context_start = (vc.loc.start + 1).min(reference.len());
+        if let Some((repeats, repeat_unit)) =
+            VariantContextUtils::get_num_tandem_repeat_units(vc, &reference[context_start..])
+        {
+            vc.set_attribute(
+                VariantAnnotations::RepeatUnit.to_key().to_string(),
+                AttributeObject::String(String::from_utf8_lossy(&repeat_unit).to_string()),
+            );
+            vc.set_attribute(
+                VariantAnnotations::RepeatsPerAllele.to_key().to_string(),
+                AttributeObject::VecI32(repeats.into_iter().map(|r| r as i32).collect()),
+            );
+        }
+
+        match self.homopolymer_indel_filter {
+            Some(min_run) if homopolymer_run >= min_run => {
+                vc.filter_unqualified(Filter::HomopolymerIndel);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Annotates every variant of the reference at `ref_idx`. Returns the number of filtered
+    /// variants
+    pub fn annotate_contexts(
+        &self,
+        contexts: &mut [VariantContext],
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+    ) -> usize {
+        VariantAnnotationEngine::annotate_with_reference(
+            contexts,
+            reference_reader,
+            ref_idx,
+            |vc, reference| self.annotate(vc, reference),
+        )
+    }
+}
@@ -0,0 +1,153 @@
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::model::variants::Filter;
+use crate::reference::reference_reader::ReferenceReader;
+
+/// Shannon entropy (ENTROPY) and sdust score (DUST) of the reference window around each variant.
+/// Variants with a DUST score at or above `low_complexity_filter` get the LowComplexity filter.
+#[derive(Debug, Clone)]
+pub struct SequenceComplexity {
+    pub window_size: usize,
+    pub low_complexity_filter: Option<f64>,
+}
+
+impl SequenceComplexity {
+    pub const DEFAULT_WINDOW_SIZE: usize = 64;
+
+    pub fn new(window_size: usize, low_complexity_filter: Option<f64>) -> Self {
+        Self {
+            window_size: window_size.max(3),
+            low_complexity_filter,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            args.try_get_one::<usize>("complexity-window-size")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(Self::DEFAULT_WINDOW_SIZE),
+            args.try_get_one::<f64>("low-complexity-filter")
+                .ok()
+                .flatten()
+                .copied(),
+        )
+    }
+
+    /// Shannon entropy, in bits, of the A, C, G and T content of `sequence`. Other bases are ignored
+    pub fn entropy(sequence: &[u8]) -> f64 {
+        let mut counts = [0usize; 4];
+        for base in sequence.iter() {
+            match base.to_ascii_uppercase() {
+                b'A' => counts[0] += 1,
+                b'C' => counts[1] += 1,
+                b'G' => counts[2] += 1,
+                b'T' => counts[3] += 1,
+                _ => {}
+            }
+        }
+        let total = counts.iter().sum::<usize>() as f64;
+        if total == 0.0 {
+            return 0.0;
+        }
+
+        counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| {
+                let p = *count as f64 / total;
+                p * (1.0 / p).log2()
+            })
+            .sum::<f64>()
+    }
+
+    /// Symmetric DUST score of `sequence`. Trinucleotides containing bases other than A, C, G or
+    /// T are skipped
+    pub fn dust_score(sequence: &[u8]) -> f64 {
+        let mut counts = [0usize; 64];
+        let mut n_triplets = 0;
+        for triplet in sequence.windows(3) {
+            let mut code = 0;
+            let mut valid = true;
+            for base in triplet.iter() {
+                code = code * 4
+                    + match base.to_ascii_uppercase() {
+                        b'A' => 0,
+                        b'C' => 1,
+                        b'G' => 2,
+                        b'T' => 3,
+                        _ => {
+                            valid = false;
+                            0
+                        }
+                    };
+            }
+            if valid {
+                counts[code] += 1;
+                n_triplets += 1;
+            }
+        }
+        if n_triplets < 2 {
+            return 0.0;
+        }
+
+        let pairs = counts
+            .iter()
+            .map(|count| count * count.saturating_sub(1) / 2)
+            .sum::<usize>();
+        pairs as f64 / (n_triplets - 1) as f64
+    }
+
+    /// The reference window centred on a variant
+    pub fn window<'a>(&self, vc: &VariantContext, reference: &'a [u8]) -> &'a [u8] {
+        let centre = (vc.loc.start + vc.loc.end) / 2;
+        let start = centre
+            .saturating_sub(self.window_size / 2)
+            .min(reference.len());
+        let end = (start + self.window_size).min(reference.len());
+        &reference[start..end]
+    }
+
+    /// Annotates a single variant given the full sequence of its contig. Returns true if the
+    /// variant was filtered
+    pub fn annotate(&self, vc: &mut VariantContext, reference: &[u8]) -> bool {
+        let window = self.window(vc, reference);
+        let entropy = Self::entropy(window);
+        let dust_score = Self::dust_score(window);
+        vc.set_attribute(
+            VariantAnnotations::SequenceEntropy.to_key().to_string(),
+            AttributeObject::f64(entropy),
+        );
+        vc.set_attribute(
+            VariantAnnotations::DustScore.to_key().to_string(),
+            AttributeObject::f64(dust_score),
+        );
+
+        match self.low_complexity_filter {
+            Some(max_dust_score) if dust_score >= max_dust_score => {
+                vc.filter_unqualified(Filter::LowComplexity);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Annotates every variant of the reference at `ref_idx`. Returns the number of filtered
+    /// variants
+    pub fn annotate_contexts(
+        &self,
+        contexts: &mut [VariantContext],
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+    ) -> usize {
+        VariantAnnotationEngine::annotate_with_reference(
+            contexts,
+            reference_reader,
+            ref_idx,
+            |vc, reference| self.annotate(vc, reference),
+        )
+    }
+}
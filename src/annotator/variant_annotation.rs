@@ -1,14 +1,17 @@
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use rand::distributions::{Distribution, Normal};
-use rand::rngs::ThreadRng;
+use rust_htslib::bcf::record::Numeric;
 use std::cmp::Ordering;
 
+use crate::annotator::allele_fraction::AlleleFractionDenominator;
 use crate::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
 use crate::model::allele_likelihoods::AlleleLikelihoods;
 use crate::model::byte_array_allele::Allele;
 use crate::model::variant_context::VariantContext;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::read_utils::ReadUtils;
+use crate::reads::split_alignment_policy::SplitAlignmentPolicy;
+use crate::utils::run_rng::RunRng;
 use crate::utils::math_utils::MathUtils;
 
 /// Determine whether the annotation appears in the info or format field of the VCF
@@ -45,6 +48,9 @@ pub enum VariantAnnotations {
     AlleleCount,
     MappingQuality,
     BaseQuality,
+    SampleMappingQuality,
+    SampleBaseQuality,
+    StrandCounts,
     DepthPerAlleleBySample,
     QualByDepth,
     MLEAC,
@@ -54,7 +60,21 @@ pub enum VariantAnnotations {
     Genotype,
     VariantGroup,
     Strain,
+    LinkedReads,
+    StrainDiscriminatingPower,
     Qualified,
+    End,
+    StructuralVariantLength,
+    StructuralVariantType,
+    HomopolymerRun,
+    RepeatUnit,
+    RepeatsPerAllele,
+    CopyNumberRatio,
+    SequenceEntropy,
+    DustScore,
+    OriginalQual,
+    HaplotypeSequence,
+    AssemblyFallback,
 }
 
 /// The actual annotation struct, Holds all information about an annotation
@@ -77,6 +97,9 @@ impl VariantAnnotations {
             Self::AlleleCount => "AC",
             Self::MappingQuality => "MQ",
             Self::BaseQuality => "BQ",
+            Self::SampleMappingQuality => "SMQ",
+            Self::SampleBaseQuality => "SBQ",
+            Self::StrandCounts => "SC",
             Self::DepthPerAlleleBySample => "AD",
             Self::QualByDepth => "QD",
             Self::MLEAC => "MLEAC",
@@ -86,7 +109,21 @@ impl VariantAnnotations {
             Self::Genotype => "GT",
             Self::VariantGroup => "VG",
             Self::Strain => "ST",
+            Self::LinkedReads => "LINKED_READS",
+            Self::StrainDiscriminatingPower => "SDP",
             Self::Qualified => "QF",
+            Self::End => "END",
+            Self::StructuralVariantLength => "SVLEN",
+            Self::StructuralVariantType => "SVTYPE",
+            Self::HomopolymerRun => "HRUN",
+            Self::RepeatUnit => "RU",
+            Self::RepeatsPerAllele => "RPA",
+            Self::CopyNumberRatio => "CNR",
+            Self::SequenceEntropy => "ENTROPY",
+            Self::DustScore => "DUST",
+            Self::OriginalQual => "OQUAL",
+            Self::HaplotypeSequence => "HAPSEQ",
+            Self::AssemblyFallback => "ASSEMBLY_FALLBACK",
         }
     }
 
@@ -124,20 +161,7 @@ impl VariantAnnotations {
             Self::AlleleFraction => {
                 let genotype = genotype.unwrap();
                 // debug!("Allele Fraction");
-                if genotype.has_ad() {
-                    let allele_fractions = MathUtils::normalize_sum_to_one(
-                        genotype
-                            .get_ad()
-                            .into_iter()
-                            .map(|a| *a as f64)
-                            .collect::<Vec<f64>>(),
-                    );
-                    genotype.attribute(
-                        self.to_key().to_string(),
-                        AttributeObject::Vecf64(allele_fractions),
-                    );
-                    return AttributeObject::None;
-                } else {
+                if !genotype.has_ad() {
                     // if there is no AD value calculate it now using likelihoods
                     Self::DepthPerAlleleBySample.annotate(
                         vc,
@@ -145,19 +169,35 @@ impl VariantAnnotations {
                         likelihoods,
                         annotation_type,
                     );
-                    let allele_fractions = MathUtils::normalize_sum_to_one(
-                        genotype
-                            .get_ad()
-                            .into_iter()
-                            .map(|a| *a as f64)
-                            .collect::<Vec<f64>>(),
-                    );
+                }
+
+                // reads that do not support any allele better than the others are not part of
+                // AD, but can be counted in the allele fractions with
+                // --allele-fraction-denominator all
+                let sample_index = likelihoods
+                    .samples
+                    .iter()
+                    .position(|s| s == &genotype.sample_name)
+                    .unwrap_or(0);
+                let unassigned_reads = likelihoods
+                    .best_alleles_breaking_ties_for_sample(sample_index)
+                    .into_iter()
+                    .filter(|ba| !ba.is_informative())
+                    .count();
+                genotype.attribute(
+                    AlleleFractionDenominator::UNASSIGNED_READS_KEY.to_string(),
+                    AttributeObject::UnsizedInteger(unassigned_reads),
+                );
+
+                if let Some(allele_fractions) =
+                    AlleleFractionDenominator::Informative.allele_fractions(genotype)
+                {
                     genotype.attribute(
                         self.to_key().to_string(),
                         AttributeObject::Vecf64(allele_fractions),
                     );
-                    return AttributeObject::None;
                 }
+                return AttributeObject::None;
             }
             Self::AlleleCount => {
                 let genotype = genotype.unwrap();
@@ -234,6 +274,81 @@ impl VariantAnnotations {
 
                 return AttributeObject::VecU8(statistics);
             }
+            Self::StrandCounts => {
+                // forward and reverse counts of each allele, flattened allele by allele
+                let mut counts = vec![0; vc.alleles.len() * 2];
+                likelihoods
+                    .best_alleles_breaking_ties_main(Box::new(|allele: &A| {
+                        if allele.is_reference() {
+                            1
+                        } else {
+                            0
+                        }
+                    }))
+                    .into_iter()
+                    .filter(|ba| ba.is_informative())
+                    .for_each(|ba| {
+                        let allele_index = ba.allele_index.unwrap();
+                        if allele_index < vc.alleles.len() {
+                            let reverse = likelihoods
+                                .evidence_by_sample_index
+                                .get(&ba.sample_index)
+                                .unwrap()[ba.evidence_index]
+                                .read
+                                .is_reverse();
+                            counts[allele_index * 2 + reverse as usize] += 1;
+                        }
+                    });
+
+                return AttributeObject::VecI32(counts);
+            }
+            Self::SampleMappingQuality | Self::SampleBaseQuality => {
+                let genotype = genotype.unwrap();
+                let sample_index = match likelihoods
+                    .samples
+                    .iter()
+                    .position(|s| s == &genotype.sample_name)
+                {
+                    Some(sample_index) => sample_index,
+                    None => return AttributeObject::None,
+                };
+
+                let mut values: Vec<Vec<u8>> = vec![Vec::new(); vc.alleles.len()];
+                // a sample can have no reads in the region
+                let evidence = likelihoods
+                    .evidence_by_sample_index
+                    .get(&sample_index)
+                    .map_or(&[][..], |evidence| evidence.as_slice());
+                likelihoods
+                    .best_alleles_breaking_ties_for_sample(sample_index)
+                    .into_iter()
+                    .filter(|ba| {
+                        ba.is_informative() && Self::is_usable_read(&evidence[ba.evidence_index])
+                    })
+                    .for_each(|ba| {
+                        let allele_index = ba.allele_index.unwrap();
+                        if allele_index < values.len() {
+                            if let Some(val) = self.get_value_u8(&evidence[ba.evidence_index], vc) {
+                                values[allele_index].push(val);
+                            }
+                        }
+                    });
+
+                // alleles without supporting reads in this sample are written as missing values
+                let medians = values
+                    .into_iter()
+                    .map(|mut vals| {
+                        if vals.is_empty() {
+                            i32::missing()
+                        } else {
+                            MathUtils::median(&mut vals) as i32
+                        }
+                    })
+                    .collect::<Vec<i32>>();
+                genotype.attribute(self.to_key().to_string(), AttributeObject::VecI32(medians));
+
+                return AttributeObject::None;
+            }
             Self::DepthPerAlleleBySample => {
                 let genotype = genotype.unwrap();
                 let alleles = vc.alleles.clone().into_iter().collect::<LinkedHashSet<_>>();
@@ -253,7 +368,7 @@ impl VariantAnnotations {
                 let mut allele_counts = LinkedHashMap::new();
                 // let mut subset = LinkedHashMap::new();
                 for (allele_index, _allele) in alleles.iter().enumerate() {
-                    allele_counts.insert(allele_index, 0);
+                    allele_counts.insert(allele_index, 0.0);
                     // subset.insert(allele_index, vec![allele]);
                 }
 
@@ -262,14 +377,24 @@ impl VariantAnnotations {
                     .iter()
                     .position(|s| s == &genotype.sample_name)
                     .unwrap_or(0);
+                // reads only count for their share when they were redistributed between genomes
+                // or weighted by the split alignment policy, rounded to whole reads
+                let evidence = likelihoods.evidence_by_sample_index.get(&sample_index);
                 likelihoods
                     .best_alleles_breaking_ties_for_sample(sample_index)
                     .into_iter()
                     .filter(|ba| ba.is_informative())
                     .for_each(|ba| {
-                        let count = allele_counts.entry(ba.allele_index.unwrap()).or_insert(0);
-                        *count += 1;
+                        let weight = evidence.map_or(1.0, |evidence| {
+                            SplitAlignmentPolicy::alignment_weight(&evidence[ba.evidence_index])
+                        });
+                        let count = allele_counts.entry(ba.allele_index.unwrap()).or_insert(0.0);
+                        *count += weight;
                     });
+                let allele_counts = allele_counts
+                    .into_iter()
+                    .map(|(allele_index, count)| (allele_index, count.round() as i32))
+                    .collect::<LinkedHashMap<usize, i32>>();
                 let mut counts = vec![0; vc.alleles.len()];
                 counts[0] = *allele_counts.get(&vc.get_reference_and_index().0).unwrap();
                 // debug!("Allele counts {:?}", &allele_counts);
@@ -332,8 +457,22 @@ impl VariantAnnotations {
             | Self::Genotype
             | Self::GenotypeQuality
             | Self::Strain
+            | Self::LinkedReads
+            | Self::StrainDiscriminatingPower
             | Self::VariantGroup
-            | Self::Qualified => {
+            | Self::Qualified
+            | Self::End
+            | Self::StructuralVariantLength
+            | Self::StructuralVariantType
+            | Self::HomopolymerRun
+            | Self::RepeatUnit
+            | Self::RepeatsPerAllele
+            | Self::CopyNumberRatio
+            | Self::SequenceEntropy
+            | Self::DustScore
+            | Self::OriginalQual
+            | Self::HaplotypeSequence
+            | Self::AssemblyFallback => {
                 // These are returned in genotype contexts already
                 // Or calculated elsewhere i.e. Strain & Qualified
                 AttributeObject::None
@@ -343,8 +482,8 @@ impl VariantAnnotations {
 
     fn get_value_u8(&self, read: &BirdToolRead, vc: &VariantContext) -> Option<u8> {
         let return_val = match self {
-            Self::MappingQuality => Some(read.read.mapq()),
-            Self::BaseQuality => {
+            Self::MappingQuality | Self::SampleMappingQuality => Some(read.read.mapq()),
+            Self::BaseQuality | Self::SampleBaseQuality => {
                 ReadUtils::get_read_base_quality_at_reference_coordinate(read, vc.loc.start)
             }
             _ => panic!("u8 read value not appropriate for {:?}", &self),
@@ -417,7 +556,8 @@ impl VariantAnnotations {
         if qd < Self::MAX_QD_BEFORE_FIXING {
             return qd;
         } else {
-            let mut rng = ThreadRng::default();
+            // jitter drawn from the run seed so that repeated runs give the same QD
+            let mut rng = RunRng::for_component("qd_jitter", qd.to_bits());
             let normal = Normal::new(0.0, 1.0);
             return Self::IDEAL_HIGH_QD + normal.sample(&mut rng) * Self::JITTER_SIGMA;
         }
@@ -444,9 +584,23 @@ impl VariantAnnotations {
                     self.to_key()
                 )
             }
-            VariantAnnotations::AlleleFraction => {
-                format!("##INFO=<ID={},Number=A,Type=Float,Description=\"Allele Frequency, for each ALT allele, in the same order as listed\">", self.to_key())
+            VariantAnnotations::SampleMappingQuality => {
+                format!("##FORMAT=<ID={},Number=R,Type=Integer,Description=\"Median mapping quality of the reads supporting each allele in this sample\">", self.to_key())
+            }
+            VariantAnnotations::StrandCounts => {
+                format!("##INFO=<ID={},Number=.,Type=Integer,Description=\"Number of forward and reverse strand reads supporting each allele, given as the forward then reverse count of the reference followed by each ALT allele\">", self.to_key())
             }
+            VariantAnnotations::SampleBaseQuality => {
+                format!("##FORMAT=<ID={},Number=R,Type=Integer,Description=\"Median PHRED-scaled base quality of the reads supporting each allele in this sample\">", self.to_key())
+            }
+            VariantAnnotations::AlleleFraction => match annotation_type {
+                AnnotationType::Info => {
+                    format!("##INFO=<ID={},Number=A,Type=Float,Description=\"Allele Frequency, for each ALT allele, in the same order as listed\">", self.to_key())
+                }
+                AnnotationType::Format => {
+                    format!("##FORMAT=<ID={},Number=A,Type=Float,Description=\"Fraction of the reads of this sample supporting each ALT allele\">", self.to_key())
+                }
+            },
             VariantAnnotations::AlleleCount => {
                 format!("##INFO=<ID={},Number=A,Type=Integer,Description=\"Allele count in genotypes, for each ALT allele, in the same order as listed\">", self.to_key())
             }
@@ -486,6 +640,48 @@ impl VariantAnnotations {
             VariantAnnotations::Strain => {
                 format!("##INFO=<ID={},Number=N,Type=Integer,Description=\"A list of potential strain ids associated with this variant location\">", self.to_key())
             }
+            VariantAnnotations::LinkedReads => {
+                format!("##INFO=<ID={},Number=1,Type=Integer,Description=\"Number of reads supporting this variant that also support a variant from another variant group of the same strain\">", self.to_key())
+            }
+            VariantAnnotations::StrainDiscriminatingPower => {
+                format!("##INFO=<ID={},Number=1,Type=Float,Description=\"Strain discriminating power, the scaled between strain variance of the allele frequency weighted by the concordance of the variant with the strain frequencies of each sample\">", self.to_key())
+            }
+            VariantAnnotations::End => {
+                format!("##INFO=<ID={},Number=1,Type=Integer,Description=\"End position of the variant described in this record\">", self.to_key())
+            }
+            VariantAnnotations::StructuralVariantLength => {
+                format!("##INFO=<ID={},Number=.,Type=Integer,Description=\"Difference in length between REF and ALT alleles, negative for deletions\">", self.to_key())
+            }
+            VariantAnnotations::StructuralVariantType => {
+                format!("##INFO=<ID={},Number=1,Type=String,Description=\"Type of structural variant\">", self.to_key())
+            }
+            VariantAnnotations::HomopolymerRun => {
+                format!("##INFO=<ID={},Number=1,Type=Integer,Description=\"Length of the longest reference homopolymer run touching the variant\">", self.to_key())
+            }
+            VariantAnnotations::RepeatUnit => {
+                format!("##INFO=<ID={},Number=1,Type=String,Description=\"Tandem repeat unit (bases)\">", self.to_key())
+            }
+            VariantAnnotations::CopyNumberRatio => {
+                format!("##INFO=<ID={},Number=1,Type=Float,Description=\"Read depth in the window around the variant relative to the median window depth of the genome, pooled across samples\">", self.to_key())
+            }
+            VariantAnnotations::SequenceEntropy => {
+                format!("##INFO=<ID={},Number=1,Type=Float,Description=\"Shannon entropy, in bits, of the reference bases in the window around the variant\">", self.to_key())
+            }
+            VariantAnnotations::DustScore => {
+                format!("##INFO=<ID={},Number=1,Type=Float,Description=\"Symmetric DUST score of the reference window around the variant, higher in low complexity sequence\">", self.to_key())
+            }
+            VariantAnnotations::OriginalQual => {
+                format!("##INFO=<ID={},Number=1,Type=Float,Description=\"QUAL before calibration against technical replicates\">", self.to_key())
+            }
+            VariantAnnotations::HaplotypeSequence => {
+                format!("##INFO=<ID={},Number=1,Type=String,Description=\"Sequence of the assembled haplotype of a symbolic haplotype record\">", self.to_key())
+            }
+            VariantAnnotations::AssemblyFallback => {
+                format!("##INFO=<ID={},Number=0,Type=Flag,Description=\"SNV called from a pileup of the reads of a region whose local assembly failed, without genotypes\">", self.to_key())
+            }
+            VariantAnnotations::RepeatsPerAllele => {
+                format!("##INFO=<ID={},Number=R,Type=Integer,Description=\"Number of times tandem repeat unit is repeated, for each allele (including reference)\">", self.to_key())
+            }
         }
     }
 }
@@ -6,6 +6,7 @@ use crate::genotype::genotype_builder::{AttributeObject, GenotypesContext};
 use crate::model::allele_likelihoods::AlleleLikelihoods;
 use crate::model::byte_array_allele::Allele;
 use crate::model::variant_context::VariantContext;
+use crate::reference::reference_reader::ReferenceReader;
 
 /**
  * The class responsible for computing annotations for variants.
@@ -88,6 +89,44 @@ impl VariantAnnotationEngine {
         return info_annot_map;
     }
 
+    /// Annotates every variant of the reference at `ref_idx` given the full sequence of its
+    /// contig, reading each contig once. Returns the number of variants `annotate` filtered
+    pub fn annotate_with_reference<F>(
+        contexts: &mut [VariantContext],
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        mut annotate: F,
+    ) -> usize
+    where
+        F: FnMut(&mut VariantContext, &[u8]) -> bool,
+    {
+        let mut filtered = 0;
+        let mut current_tid = None;
+        let mut order = (0..contexts.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|i| contexts[*i].loc.tid);
+        for i in order {
+            let tid = contexts[i].loc.tid;
+            if current_tid != Some(tid) {
+                match reference_reader.fetch_contig_from_reference_by_tid(tid, ref_idx) {
+                    Ok(_) => {
+                        reference_reader.read_sequence_to_vec();
+                        current_tid = Some(tid);
+                    }
+                    Err(_) => {
+                        debug!("Unable to fetch contig {} for annotation", tid);
+                        current_tid = None;
+                        continue;
+                    }
+                }
+            }
+
+            if annotate(&mut contexts[i], &reference_reader.current_sequence) {
+                filtered += 1;
+            }
+        }
+        filtered
+    }
+
     /// Annotations added to the VariantContext
     pub fn vc_annotations() -> Vec<Annotation> {
         vec![
@@ -95,6 +134,7 @@ impl VariantAnnotationEngine {
             Annotation::new(VariantAnnotations::QualByDepth, AnnotationType::Info),
             Annotation::new(VariantAnnotations::MappingQuality, AnnotationType::Info),
             Annotation::new(VariantAnnotations::BaseQuality, AnnotationType::Info),
+            Annotation::new(VariantAnnotations::StrandCounts, AnnotationType::Info),
             Annotation::new(VariantAnnotations::Qualified, AnnotationType::Info),
         ]
     }
@@ -107,8 +147,13 @@ impl VariantAnnotationEngine {
                 VariantAnnotations::DepthPerAlleleBySample,
                 AnnotationType::Format,
             ),
-            Annotation::new(VariantAnnotations::AlleleFraction, AnnotationType::Info),
+            Annotation::new(VariantAnnotations::AlleleFraction, AnnotationType::Format),
             Annotation::new(VariantAnnotations::AlleleCount, AnnotationType::Info),
+            Annotation::new(
+                VariantAnnotations::SampleMappingQuality,
+                AnnotationType::Format,
+            ),
+            Annotation::new(VariantAnnotations::SampleBaseQuality, AnnotationType::Format),
         ]
     }
 
@@ -120,6 +165,7 @@ impl VariantAnnotationEngine {
             Annotation::new(VariantAnnotations::PhredLikelihoods, AnnotationType::Format),
             Annotation::new(VariantAnnotations::MLEAC, AnnotationType::Info),
             Annotation::new(VariantAnnotations::MLEAF, AnnotationType::Info),
+            Annotation::new(VariantAnnotations::AlleleFraction, AnnotationType::Info),
         ]
     }
 
@@ -132,10 +178,36 @@ impl VariantAnnotationEngine {
         return annotations;
     }
 
-    fn strain_annotations() -> Vec<Annotation> {
+    pub fn strain_annotations() -> Vec<Annotation> {
         vec![
             Annotation::new(VariantAnnotations::VariantGroup, AnnotationType::Info),
             Annotation::new(VariantAnnotations::Strain, AnnotationType::Format),
+            Annotation::new(VariantAnnotations::LinkedReads, AnnotationType::Info),
+            Annotation::new(
+                VariantAnnotations::StrainDiscriminatingPower,
+                AnnotationType::Info,
+            ),
+        ]
+    }
+
+    /// Annotations describing the extent of deletions and symbolic alleles
+    pub fn structural_variant_annotations() -> Vec<Annotation> {
+        vec![
+            Annotation::new(VariantAnnotations::End, AnnotationType::Info),
+            Annotation::new(
+                VariantAnnotations::StructuralVariantLength,
+                AnnotationType::Info,
+            ),
+            Annotation::new(VariantAnnotations::StructuralVariantType, AnnotationType::Info),
+        ]
+    }
+
+    /// Annotations describing the homopolymer and tandem repeat context of a variant
+    pub fn repeat_annotations() -> Vec<Annotation> {
+        vec![
+            Annotation::new(VariantAnnotations::HomopolymerRun, AnnotationType::Info),
+            Annotation::new(VariantAnnotations::RepeatUnit, AnnotationType::Info),
+            Annotation::new(VariantAnnotations::RepeatsPerAllele, AnnotationType::Info),
         ]
     }
 
@@ -144,6 +216,42 @@ impl VariantAnnotationEngine {
         for annotation in Self::all_annotations() {
             header.push_record(annotation.generate_header_record().as_bytes());
         }
+        for annotation in Self::structural_variant_annotations() {
+            header.push_record(annotation.generate_header_record().as_bytes());
+        }
+        for annotation in Self::repeat_annotations() {
+            header.push_record(annotation.generate_header_record().as_bytes());
+        }
+        header.push_record(
+            Annotation::new(VariantAnnotations::CopyNumberRatio, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
+        header.push_record(
+            Annotation::new(VariantAnnotations::SequenceEntropy, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
+        header.push_record(
+            Annotation::new(VariantAnnotations::DustScore, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
+        header.push_record(
+            Annotation::new(VariantAnnotations::OriginalQual, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
+        header.push_record(
+            Annotation::new(VariantAnnotations::HaplotypeSequence, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
+        header.push_record(
+            Annotation::new(VariantAnnotations::AssemblyFallback, AnnotationType::Info)
+                .generate_header_record()
+                .as_bytes(),
+        );
         if strain_info {
             for annotation in Self::strain_annotations() {
                 header.push_record(annotation.generate_header_record().as_bytes());
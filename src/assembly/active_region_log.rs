@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
+
+/// What became of an assembly region once it was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionStatus {
+    // variation was assembled and the region was genotyped
+    AssembledVariation,
+    // the region was inactive or only the reference haplotype was supported
+    ReferenceOnly,
+    // assembly or genotyping of the region failed
+    Failed,
+    // the region reached --max-input-depth, so reads were discarded before assembly
+    Downsampled,
+}
+
+impl RegionStatus {
+    pub fn to_key(&self) -> &'static str {
+        match self {
+            Self::AssembledVariation => "assembled_variation",
+            Self::ReferenceOnly => "reference_only",
+            Self::Failed => "failed",
+            Self::Downsampled => "downsampled",
+        }
+    }
+}
+
+/// The outcome of calling a single assembly region
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionOutcome {
+    pub tid: usize,
+    // 0-based, inclusive active span of the region
+    pub start: usize,
+    pub end: usize,
+    pub status: RegionStatus,
+    pub reads: usize,
+    pub haplotypes: usize,
+    pub variants: usize,
+}
+
+impl RegionOutcome {
+    pub fn new(
+        tid: usize,
+        start: usize,
+        end: usize,
+        status: RegionStatus,
+        reads: usize,
+        haplotypes: usize,
+        variants: usize,
+    ) -> Self {
+        Self {
+            tid,
+            start,
+            end,
+            status,
+            reads,
+            haplotypes,
+            variants,
+        }
+    }
+}
+
+/// Status, read count, haplotypes and variants of every assembly region of a reference, written as
+/// a BED file. Every clone shares the same records.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveRegionLog {
+    outcomes: Arc<Mutex<Vec<RegionOutcome>>>,
+}
+
+impl ActiveRegionLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, outcome: RegionOutcome) {
+        self.outcomes.lock().unwrap().push(outcome);
+    }
+
+    pub fn len(&self) -> usize {
+        self.outcomes.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The logged regions sorted by position
+    pub fn outcomes(&self) -> Vec<RegionOutcome> {
+        let mut outcomes = self.outcomes.lock().unwrap().clone();
+        outcomes.sort_by_key(|outcome| (outcome.tid, outcome.start, outcome.end));
+        outcomes
+    }
+
+    /// The number of logged regions with the given status
+    pub fn count(&self, status: RegionStatus) -> usize {
+        self.outcomes
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|outcome| outcome.status == status)
+            .count()
+    }
+
+    /// The path of the active region file of a reference
+    pub fn file_path(output_prefix: &str, reference_name: &str) -> String {
+        format!("{}/{}_active_regions.bed", output_prefix, reference_name)
+    }
+
+    /// Writes the regions to {output_prefix}/{reference_name}_active_regions.bed
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        reference_reader: &ReferenceReader,
+    ) -> Result<(), BirdToolError> {
+        let file_name = Self::file_path(output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(
+            writer,
+            "#contig\tstart\tend\tstatus\treads\thaplotypes\tvariants"
+        )
+        .map_err(write_error)?;
+        for outcome in self.outcomes() {
+            let contig = reference_reader
+                .retrieve_contig_name_from_tid(outcome.tid)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .unwrap_or_else(|| outcome.tid.to_string());
+            // BED intervals are 0-based and half open
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                contig,
+                outcome.start,
+                outcome.end + 1,
+                outcome.status.to_key(),
+                outcome.reads,
+                outcome.haplotypes,
+                outcome.variants
+            )
+            .map_err(write_error)?;
+        }
+
+        writer.flush().map_err(write_error)
+    }
+}
@@ -9,6 +9,9 @@ use std::collections::{HashMap, HashSet};
 
 use crate::assembly::assembly_region::AssemblyRegion;
 use crate::assembly::assembly_result_set::AssemblyResultSet;
+use crate::assembly::minimizer_filter::MinimizerFilter;
+use crate::assembly::soft_clip_rescue::SoftClipRescue;
+use crate::reads::read_end_profile::{AdaptiveEndTrimmer, ReadEndProfile};
 use crate::genotype::genotype_builder::AttributeObject;
 use crate::haplotype::haplotype::Haplotype;
 use crate::haplotype::haplotype_caller_engine::HaplotypeCallerEngine;
@@ -29,7 +32,7 @@ use crate::read_threading::abstract_read_threading_graph::AbstractReadThreadingG
 use crate::read_threading::read_threading_assembler::ReadThreadingAssembler;
 use crate::read_threading::read_threading_graph::ReadThreadingGraph;
 use crate::reads::alignment_utils::AlignmentUtils;
-use crate::smith_waterman::smith_waterman_aligner::{NEW_SW_PARAMETERS, STANDARD_NGS};
+use crate::smith_waterman::smith_waterman_aligner::AssemblySWParameters;
 use crate::utils::fragment_collection::FragmentCollection;
 use crate::utils::fragment_utils::adjust_quals_of_overlapping_paired_fragments;
 use crate::utils::quality_utils::QualityUtils;
@@ -38,6 +41,7 @@ use crate::utils::vcf_constants::*;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::cigar_utils::CigarUtils;
 use crate::reads::read_clipper::ReadClipper;
+use crate::reads::read_compression::ReadCompression;
 use crate::reads::read_utils::ReadUtils;
 use crate::reference::reference_reader::ReferenceReader;
 
@@ -300,7 +304,26 @@ impl AssemblyBasedCallerUtils {
         assembly_engine: &mut ReadThreadingAssembler,
         correct_overlapping_base_qualities: bool,
         sample_names: &[String],
+        minimizer_filter: Option<&MinimizerFilter>,
+        read_end_profile: Option<&ReadEndProfile>,
     ) -> AssemblyResultSet<ReadThreadingGraph> {
+        // soft clips are hard clipped away during finalization, so collect them first
+        let soft_clip_rescue = if args.get_flag("soft-clip-rescue") {
+            Some(SoftClipRescue::from_args(args))
+        } else {
+            None
+        };
+        let clipped_sequences = match &soft_clip_rescue {
+            Some(rescue) => rescue.collect_clips(region.get_reads()),
+            None => Vec::new(),
+        };
+        if let Some(read_end_profile) = read_end_profile {
+            region
+                .get_reads()
+                .iter()
+                .for_each(|read| read_end_profile.record_read(read));
+        }
+
         Self::finalize_regions(
             &mut region,
             args.get_flag("error-correct-reads"),
@@ -350,7 +373,55 @@ impl AssemblyBasedCallerUtils {
         //     read_error_corrector = None
         // }
 
+        // trim read ends disagreeing with the reference, e.g. adapters and chimeric tails
+        if let Some(end_trimmer) = AdaptiveEndTrimmer::from_args(args) {
+            let reference_start = padded_reference_loc.get_start();
+            let trimmed_reads = region
+                .move_reads()
+                .into_par_iter()
+                .map(|read| {
+                    let sample = read.sample_index;
+                    let (read, trimmed_bases) =
+                        end_trimmer.trim(read, &full_reference_with_padding, reference_start);
+                    if let Some(read_end_profile) = read_end_profile {
+                        read_end_profile.record_trim(sample, trimmed_bases);
+                    }
+                    read
+                })
+                .filter(|read| !read.is_empty())
+                .collect::<Vec<BirdToolRead>>();
+            region.add_all(trimmed_reads);
+        }
+
+        // drop reads that share too few minimizers with the reference window before threading
+        if let Some(minimizer_filter) = minimizer_filter {
+            let reads = region.move_reads();
+            let n_reads = reads.len();
+            let (kept_reads, discarded) =
+                minimizer_filter.filter_reads(reads, &full_reference_with_padding);
+            if discarded > 0 {
+                debug!(
+                    "Minimizer pre-filter discarded {} of {} reads in region {:?}",
+                    discarded,
+                    n_reads,
+                    region.get_padded_span()
+                );
+            }
+            region.add_all(kept_reads);
+        }
+
         let region_padded_start = region.get_padded_span().get_start();
+        let rescued_insertions = match soft_clip_rescue {
+            Some(rescue) => rescue.rescue(
+                clipped_sequences,
+                region.get_contig(),
+                &full_reference_with_padding,
+                padded_reference_loc.get_start(),
+                &region.get_padded_span(),
+            ),
+            None => Vec::new(),
+        };
+        let sw_parameters = AssemblySWParameters::from_args(args);
         let additional_kmer_sizes = if args.get_flag("disable-automatic-kmer-adjustment") {
             None
         } else {
@@ -363,8 +434,8 @@ impl AssemblyBasedCallerUtils {
             padded_reference_loc,
             // read_error_corrector,
             sample_names,
-            *STANDARD_NGS,
-            *NEW_SW_PARAMETERS,
+            sw_parameters.dangling_end_parameters(),
+            sw_parameters.haplotype_to_reference_parameters(),
             if args.get_flag("disable-avx") {
                 AVXMode::None
             } else {
@@ -373,12 +444,14 @@ impl AssemblyBasedCallerUtils {
             additional_kmer_sizes
         );
 
-        if !given_alleles.is_empty() {
+        if !given_alleles.is_empty() || !rescued_insertions.is_empty() {
+            let mut alleles_to_inject = given_alleles.clone();
+            alleles_to_inject.extend(rescued_insertions);
             Self::add_given_alleles(
                 region_padded_start,
-                given_alleles,
+                &alleles_to_inject,
                 *args.get_one::<usize>("max-mnp-distance").unwrap(),
-                *NEW_SW_PARAMETERS,
+                sw_parameters.haplotype_to_reference_parameters(),
                 &ref_haplotype,
                 &mut assembly_result_set,
                 if args.get_flag("disable-avx") {
@@ -942,7 +1015,7 @@ impl AssemblyBasedCallerUtils {
             )
         };
 
-        PairHMMLikelihoodCalculationEngine::new(
+        let mut likelihood_calculation_engine = PairHMMLikelihoodCalculationEngine::new(
             *args.get_one::<u8>("pair-hmm-gap-continuation-penalty")
                 .unwrap(),
             log10_global_read_mismapping_rate,
@@ -962,7 +1035,12 @@ impl AssemblyBasedCallerUtils {
             } else {
                 AVXMode::detect_mode()
             },
-        )
+        );
+        likelihood_calculation_engine
+            .set_pair_hmm_batch_size(*args.get_one::<usize>("pair-hmm-batch-size").unwrap());
+        likelihood_calculation_engine.set_read_compression(ReadCompression::from_args(args));
+
+        likelihood_calculation_engine
     }
 
     /**
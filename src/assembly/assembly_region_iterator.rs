@@ -3,11 +3,14 @@ use rayon::prelude::*;
 use std::cmp::Reverse;
 use rust_htslib::bam::Record;
 
+use crate::processing::bams::multi_mapping::MultiMappingReassignment;
 use crate::processing::lorikeet_engine::ReadType;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::read_utils::ReadUtils;
+use crate::reads::split_alignment_policy::SplitAlignmentPolicy;
 use crate::utils::interval_utils::IntervalUtils;
 use crate::utils::simple_interval::SimpleInterval;
+use crate::utils::thread_budget::ThreadBudget;
 use crate::assembly::assembly_region::AssemblyRegion;
 use crate::bam_parsing::{
     FlagFilter, 
@@ -73,6 +76,8 @@ impl<'a> AssemblyRegionIterator<'a> {
             .unwrap();
 
         let _limiting_interval = IntervalUtils::parse_limiting_interval(args);
+        let reader_threads = ThreadBudget::from_args(args).reader_threads();
+        let split_alignment_policy = SplitAlignmentPolicy::from_args(args);
 
         let mut records: Vec<BirdToolRead> = self
             .indexed_bam_readers
@@ -95,6 +100,7 @@ impl<'a> AssemblyRegionIterator<'a> {
                         .into_iter()
                         .next()
                         .unwrap();
+                        bam_generated.set_threads(reader_threads);
                         // debug!(
                         //     "samples: {} -> {}: {} - {}",
                         //     bam_generator,
@@ -124,14 +130,21 @@ impl<'a> AssemblyRegionIterator<'a> {
                             {
                                 continue;
                             } else {
-                                records.push(BirdToolRead::new(
-                                    record.clone(),
-                                    sample_idx,
-                                    read_type,
-                                ));
+                                let mut read =
+                                    BirdToolRead::new(record.clone(), sample_idx, read_type);
+                                // reads redistributed between genomes only count for their share
+                                if let Some(weight) = MultiMappingReassignment::read_weight(&record)
+                                {
+                                    read.set_transient_attribute(
+                                        SplitAlignmentPolicy::WEIGHT_ATTRIBUTE.to_string(),
+                                        weight.to_le_bytes().to_vec(),
+                                    );
+                                }
+                                records.push(read);
                             };
                         }
 
+                        split_alignment_policy.apply(&mut records);
                         records
                     }
                 }
@@ -1,7 +1,5 @@
 use ndarray::Array2;
 use rayon::prelude::*;
-use rust_htslib::bcf::Read;
-use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 
@@ -10,7 +8,10 @@ use crate::activity_profile::activity_profile::Profile;
 use crate::activity_profile::band_pass_activity_profile::BandPassActivityProfile;
 use crate::assembly::assembly_region::AssemblyRegion;
 use crate::assembly::assembly_region_iterator::AssemblyRegionIterator;
+use crate::assembly::feature_context::FeatureContext;
+use crate::assembly::forced_alleles::ForcedAlleles;
 use crate::processing::lorikeet_engine::Elem;
+use crate::processing::per_genome_config::GenomeOverrides;
 use crate::reference::reference_reader_utils::GenomesAndContigs;
 use crate::haplotype::haplotype_caller_engine::HaplotypeCallerEngine;
 use crate::model::variant_context::VariantContext;
@@ -36,6 +37,7 @@ impl AssemblyRegionWalker {
         short_read_bam_count: usize,
         long_read_bam_count: usize,
         indexed_bam_readers: &[String],
+        genome_overrides: &GenomeOverrides,
         // n_threads: usize,
     ) -> AssemblyRegionWalker {
         let hc_engine = HaplotypeCallerEngine::new(
@@ -43,18 +45,21 @@ impl AssemblyRegionWalker {
             ref_idx,
             indexed_bam_readers.to_vec(),
             false,
-            *args.get_one::<usize>("ploidy").unwrap(),
+            genome_overrides
+                .ploidy
+                .unwrap_or_else(|| *args.get_one::<usize>("ploidy").unwrap()),
+            genome_overrides,
         );
 
         let assembly_region_padding = *args
             .get_one::<usize>("assembly-region-padding")
             .unwrap();
-        let min_assembly_region_size = *args
-            .get_one::<usize>("min-assembly-region-size")
-            .unwrap();
-        let max_assembly_region_size = *args
-            .get_one::<usize>("max-assembly-region-size")
-            .unwrap();
+        let min_assembly_region_size = genome_overrides
+            .min_assembly_region_size
+            .unwrap_or_else(|| *args.get_one::<usize>("min-assembly-region-size").unwrap());
+        let max_assembly_region_size = genome_overrides
+            .max_assembly_region_size
+            .unwrap_or_else(|| *args.get_one::<usize>("max-assembly-region-size").unwrap());
 
         AssemblyRegionWalker {
             evaluator: hc_engine,
@@ -130,177 +135,263 @@ impl AssemblyRegionWalker {
             false, // not used, calculated in function
         );
 
-        let features = args.get_one::<String>("features-vcf");
+        let feature_vcfs = FeatureContext::from_args(args, Some(output_prefix)).paths();
+        if let Err(e) = FeatureContext::check(&feature_vcfs) {
+            panic!("{:?}", e)
+        }
         let limiting_interval = IntervalUtils::parse_limiting_interval(args);
-        match features {
-            Some(indexed_vcf_reader) => {
-                // debug!("Attempting to extract features...");
-
-                let contexts = pending_regions
-                    .into_par_iter()
-                    .flat_map(|mut assembly_region| {
-                        let within_bounds = match &limiting_interval {
-                            Some(limit) => {
-                                let limit = SimpleInterval::new(
-                                    assembly_region.tid,
-                                    limit.start,
-                                    limit.end,
-                                );
-                                assembly_region.padded_span.overlaps(&limit)
-                            }
-                            None => true,
-                        };
-
-                        if within_bounds {
-                            let mut reference_reader = reference_reader.clone();
-                            let mut evaluator = evaluator.clone();
-
-                            // read in feature variants across the assembly region location
-                            let feature_variants = retrieve_feature_variants(
-                                indexed_vcf_reader,
-                                &reference_reader,
-                                &assembly_region,
-                            );
-
-                            // if long_read_bam_count > 0 && !args.is_present("do-not-call-svs") {
-                            //     let sv_path = format!("{}/structural_variants.vcf.gz", output_prefix);
-                            //     if Path::new(&sv_path).exists() {
-                            //         // structural variants present so we will add them to feature variants
-                            //         let structural_variants = retrieve_feature_variants(
-                            //             &sv_path,
-                            //             &reference_reader,
-                            //             &assembly_region,
-                            //         );
-                            //
-                            //         feature_variants.extend(structural_variants);
-                            //     }
-                            // }
-
-                            // debug!("Feature variants {:?}", &feature_variants);
-
-                            assembly_region_iter.fill_next_assembly_region_with_reads(
-                                &mut assembly_region,
-                                flag_filters,
-                                n_threads,
-                                short_read_bam_count,
-                                long_read_bam_count,
-                                max_input_depth,
-                                args,
-                            );
-
-                            evaluator
-                                .call_region(
-                                    assembly_region,
-                                    &mut reference_reader,
-                                    feature_variants,
-                                    args,
-                                    sample_names,
-                                    flag_filters,
-                                )
-                                .into_par_iter()
-                        } else {
-                            Vec::new().into_par_iter()
-                        }
-                    })
-                    .collect::<Vec<VariantContext>>();
-
-                contexts
-            }
-            None => {
-                let contexts = pending_regions
-                    .into_par_iter()
-                    .flat_map(|mut assembly_region| {
-                        let within_bounds = match &limiting_interval {
-                            Some(limit) => {
-                                let limit = SimpleInterval::new(
-                                    assembly_region.tid,
-                                    limit.start,
-                                    limit.end,
-                                );
-                                assembly_region.padded_span.overlaps(&limit)
-                            }
-                            None => true,
-                        };
-
-                        if within_bounds {
-                            let mut reference_reader = reference_reader.clone();
-                            let mut evaluator = evaluator.clone();
-
-                            let feature_variants =
-                                if long_read_bam_count > 0 && !args.get_flag("do-not-call-svs") {
-                                    let sv_path =
-                                        format!("{}/structural_variants.vcf.gz", output_prefix);
-                                    if Path::new(&sv_path).exists() {
-                                        // structural variants present so we will add them to feature variants
-                                        // retrieve_feature_variants(
-                                        //     &sv_path,
-                                        //     &reference_reader,
-                                        //     &assembly_region,
-                                        // )
-                                        Vec::new()
-                                    } else {
-                                        Vec::new()
-                                    }
-                                } else {
-                                    Vec::new()
-                                };
-
-                            // debug!("Filling with reads...");
-                            assembly_region_iter.fill_next_assembly_region_with_reads(
-                                &mut assembly_region,
-                                flag_filters,
-                                n_threads,
-                                short_read_bam_count,
-                                long_read_bam_count,
-                                max_input_depth,
-                                args,
-                            );
-
-                            evaluator
-                                .call_region(
-                                    assembly_region,
-                                    &mut reference_reader,
-                                    feature_variants,
-                                    args,
-                                    sample_names,
-                                    flag_filters,
-                                )
-                                .into_par_iter()
-                        } else {
-                            Vec::new().into_par_iter()
-                        }
-                    })
-                    .collect::<Vec<VariantContext>>();
-
-                contexts
+
+        pending_regions
+            .into_par_iter()
+            // open readers can not be shared, so each thread queries through its own
+            .map_init(
+                || FeatureContext::new(feature_vcfs.clone(), FeatureContext::DEFAULT_LOOKAHEAD),
+                |feature_context, mut assembly_region| {
+                    if !region_within_bounds(&assembly_region, &limiting_interval) {
+                        return Vec::new();
+                    }
+
+                    // active regions left out of a subsampled run are not assembled
+                    if assembly_region.is_active()
+                        && !evaluator.subsampler().map_or(true, |subsampler| {
+                            subsampler.keeps_region(
+                                assembly_region.tid,
+                                assembly_region.active_span.start,
+                            )
+                        })
+                    {
+                        return Vec::new();
+                    }
+
+                    let mut reference_reader = reference_reader.clone();
+                    let mut evaluator = evaluator.clone();
+
+                    // read in feature variants across the assembly region location
+                    let feature_variants = feature_variants_for_region(
+                        feature_context,
+                        evaluator.forced_alleles(),
+                        &reference_reader,
+                        &assembly_region,
+                    );
+
+                    assembly_region_iter.fill_next_assembly_region_with_reads(
+                        &mut assembly_region,
+                        flag_filters,
+                        n_threads,
+                        short_read_bam_count,
+                        long_read_bam_count,
+                        max_input_depth,
+                        args,
+                    );
+
+                    evaluator.call_region(
+                        assembly_region,
+                        &mut reference_reader,
+                        feature_variants,
+                        args,
+                        sample_names,
+                        flag_filters,
+                    )
+                },
+            )
+            .flatten()
+            .collect::<Vec<VariantContext>>()
+    }
+
+    /// Returns an iterator that calls variants on each of the given assembly regions in turn,
+    /// yielding every region with the contexts called inside of it. `bam_files` are the indexed
+    /// BAM files in the same order the walker was started with.
+    pub fn iter_regions<'a>(
+        &self,
+        regions: Vec<AssemblyRegion>,
+        args: &'a clap::ArgMatches,
+        bam_files: &'a [String],
+        flag_filters: &'a FlagFilter,
+        reference_reader: &ReferenceReader,
+        n_threads: u32,
+    ) -> AssemblyRegionCalls<'a> {
+        AssemblyRegionCalls {
+            pending_regions: regions.into_iter(),
+            assembly_region_iter: AssemblyRegionIterator::new(bam_files, n_threads),
+            evaluator: self.evaluator.clone(),
+            reference_reader: reference_reader.clone(),
+            args,
+            bam_files,
+            flag_filters,
+            feature_context: FeatureContext::from_args(args, None),
+            limiting_interval: IntervalUtils::parse_limiting_interval(args),
+            n_threads,
+            short_read_bam_count: self.short_read_bam_count,
+            long_read_bam_count: self.long_read_bam_count,
+            max_input_depth: *args.get_one::<usize>("max-input-depth").unwrap(),
+            keep_reads: false,
+        }
+    }
+
+    /// Splits an activity profile into assembly regions using the walker's region size settings
+    /// and returns an iterator over the calls made in each of them. See [`Self::iter_regions`]
+    pub fn iter_shard<'a>(
+        &self,
+        shard: BandPassActivityProfile,
+        args: &'a clap::ArgMatches,
+        bam_files: &'a [String],
+        flag_filters: &'a FlagFilter,
+        reference_reader: &ReferenceReader,
+        n_threads: u32,
+    ) -> AssemblyRegionCalls<'a> {
+        let regions = shard.pop_ready_assembly_regions(
+            self.assembly_region_padding,
+            self.min_assembly_region_size,
+            self.max_assembly_region_size,
+            false,
+        );
+        self.iter_regions(
+            regions,
+            args,
+            bam_files,
+            flag_filters,
+            reference_reader,
+            n_threads,
+        )
+    }
+}
+
+/// Calls variants on assembly regions one at a time. Each region is only filled with reads and
+/// assembled when the iterator is advanced, so library users can filter contexts or extract
+/// features per region without buffering every context for a genome in memory.
+///
+/// Yields each region along with the variant contexts called in it. Regions are yielded without
+/// their reads unless [`AssemblyRegionCalls::keep_reads`] is set, as the reads are consumed by
+/// calling. Regions outside of the limiting interval are skipped.
+pub struct AssemblyRegionCalls<'a> {
+    pending_regions: std::vec::IntoIter<AssemblyRegion>,
+    assembly_region_iter: AssemblyRegionIterator<'a>,
+    evaluator: HaplotypeCallerEngine,
+    reference_reader: ReferenceReader,
+    args: &'a clap::ArgMatches,
+    bam_files: &'a [String],
+    flag_filters: &'a FlagFilter,
+    feature_context: FeatureContext,
+    limiting_interval: Option<SimpleInterval>,
+    n_threads: u32,
+    short_read_bam_count: usize,
+    long_read_bam_count: usize,
+    max_input_depth: usize,
+    keep_reads: bool,
+}
+
+impl<'a> AssemblyRegionCalls<'a> {
+    /// Keep a copy of the reads of each region in the yielded region. This doubles the memory
+    /// held for the region currently being called
+    pub fn keep_reads(mut self, keep_reads: bool) -> Self {
+        self.keep_reads = keep_reads;
+        self
+    }
+
+    /// Number of regions that have not been called yet
+    pub fn remaining(&self) -> usize {
+        self.pending_regions.len()
+    }
+}
+
+impl<'a> Iterator for AssemblyRegionCalls<'a> {
+    type Item = (AssemblyRegion, Vec<VariantContext>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut assembly_region = self.pending_regions.next()?;
+            if !region_within_bounds(&assembly_region, &self.limiting_interval) {
+                continue;
             }
+
+            let feature_variants = feature_variants_for_region(
+                &mut self.feature_context,
+                self.evaluator.forced_alleles(),
+                &self.reference_reader,
+                &assembly_region,
+            );
+
+            self.assembly_region_iter.fill_next_assembly_region_with_reads(
+                &mut assembly_region,
+                self.flag_filters,
+                self.n_threads,
+                self.short_read_bam_count,
+                self.long_read_bam_count,
+                self.max_input_depth,
+                self.args,
+            );
+
+            let yielded_region = if self.keep_reads {
+                assembly_region.clone()
+            } else {
+                assembly_region.clone_without_reads()
+            };
+
+            let contexts = self.evaluator.call_region(
+                assembly_region,
+                &mut self.reference_reader,
+                feature_variants,
+                self.args,
+                self.bam_files,
+                self.flag_filters,
+            );
+
+            return Some((yielded_region, contexts));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.pending_regions.len()))
+    }
+}
+
+fn region_within_bounds(
+    assembly_region: &AssemblyRegion,
+    limiting_interval: &Option<SimpleInterval>,
+) -> bool {
+    match limiting_interval {
+        Some(limit) => {
+            let limit = SimpleInterval::new(assembly_region.tid, limit.start, limit.end);
+            assembly_region.padded_span.overlaps(&limit)
         }
+        None => true,
     }
 }
 
-fn retrieve_feature_variants(
-    indexed_vcf_reader: &str,
+fn feature_variants_for_region(
+    feature_context: &mut FeatureContext,
+    forced_alleles: Option<&ForcedAlleles>,
     reference_reader: &ReferenceReader,
     assembly_region: &AssemblyRegion,
 ) -> Vec<VariantContext> {
-    let mut indexed_vcf_reader = VariantContext::retrieve_indexed_vcf_file(indexed_vcf_reader);
-    // debug!("Retrieved indexed VCF...");
-
-    let vcf_rid = VariantContext::get_contig_vcf_tid(
-        indexed_vcf_reader.header(),
-        reference_reader
-            .retrieve_contig_name_from_tid(assembly_region.get_contig())
-            .unwrap(),
-    );
-    // debug!("VCF Rid {:?}", &vcf_rid);
-
-    match vcf_rid {
-        Some(rid) => VariantContext::process_vcf_in_region(
-            &mut indexed_vcf_reader,
-            rid,
-            assembly_region.get_start() as u64,
-            assembly_region.get_end() as u64,
-        ),
-        None => Vec::new(),
+    let mut feature_variants = Vec::new();
+    if !feature_context.is_empty() {
+        if let Some(contig_name) =
+            reference_reader.retrieve_contig_name_from_tid(assembly_region.get_contig())
+        {
+            feature_variants = feature_context.query(
+                contig_name,
+                &reference_reader.genomes_and_contigs.separator,
+                assembly_region.get_start() as u64,
+                assembly_region.get_end() as u64,
+            );
+        }
     }
+
+    // alleles from positions TSVs
+    if let Some(forced_alleles) = forced_alleles {
+        if let Some(contig_name) =
+            reference_reader.retrieve_contig_name_from_tid(assembly_region.get_contig())
+        {
+            feature_variants.extend(forced_alleles.variants_in(
+                contig_name,
+                &reference_reader.genomes_and_contigs.separator,
+                assembly_region.get_contig(),
+                assembly_region.get_start(),
+                assembly_region.get_end(),
+            ));
+        }
+    }
+
+    feature_variants
 }
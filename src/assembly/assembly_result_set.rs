@@ -44,6 +44,8 @@ pub struct AssemblyResultSet<A: AbstractReadThreadingGraph> {
     pub(crate) variation_events: BTreeSet<VariantContext>,
     pub(crate) last_max_mnp_distance_used: Option<usize>,
     pub(crate) assembly_results: Vec<AssemblyResult<SimpleInterval, A>>,
+    // no kmer size gave a usable graph, e.g. because of cycles or low complexity
+    pub(crate) assembly_failed: bool,
 }
 
 impl<A: AbstractReadThreadingGraph> AssemblyResultSet<A> {
@@ -72,6 +74,7 @@ impl<A: AbstractReadThreadingGraph> AssemblyResultSet<A> {
             variation_events: BTreeSet::new(),
             last_max_mnp_distance_used: None,
             assembly_results: Vec::new(),
+            assembly_failed: false,
         }
     }
 
@@ -97,6 +100,7 @@ impl<A: AbstractReadThreadingGraph> AssemblyResultSet<A> {
             variation_events: BTreeSet::new(),
             last_max_mnp_distance_used: None,
             assembly_results: Vec::new(),
+            assembly_failed: false,
         }
     }
 
@@ -329,6 +333,7 @@ impl<A: AbstractReadThreadingGraph> AssemblyResultSet<A> {
             self.full_reference_with_padding.as_slice(),
             &self.padded_reference_loc,
             max_mnp_distance,
+            false,
         ) {
             Ok(_) => {
                 // pass
@@ -0,0 +1,192 @@
+use rust_htslib::bcf::{IndexedReader, Read};
+use std::path::Path;
+
+use crate::model::variant_context::VariantContext;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// The features of one VCF fetched across a window of a contig, along with the last position
+/// each of them covers
+#[derive(Debug, Clone)]
+pub struct FeatureWindow {
+    rid: u32,
+    start: u64,
+    end: u64,
+    features: Vec<(u64, u64, VariantContext)>,
+}
+
+impl FeatureWindow {
+    /// `features` are given as their first and last covered positions, 0-based and inclusive
+    pub fn new(rid: u32, start: u64, end: u64, features: Vec<(u64, u64, VariantContext)>) -> Self {
+        Self {
+            rid,
+            start,
+            end,
+            features,
+        }
+    }
+
+    /// Whether every feature overlapping the interval is in the window
+    pub fn covers(&self, rid: u32, start: u64, end: u64) -> bool {
+        self.rid == rid && self.start <= start && end <= self.end
+    }
+
+    /// The features of the window overlapping the interval
+    pub fn overlapping(&self, start: u64, end: u64) -> Vec<VariantContext> {
+        self.features
+            .iter()
+            .filter(|(feature_start, feature_end, _)| {
+                *feature_start <= end && *feature_end >= start
+            })
+            .map(|(_, _, feature)| feature.clone())
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.features.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.features.is_empty()
+    }
+}
+
+/// An indexed feature VCF, opened the first time it is queried
+struct FeatureSource {
+    path: String,
+    reader: Option<IndexedReader>,
+    window: Option<FeatureWindow>,
+}
+
+impl FeatureSource {
+    fn new(path: String) -> Self {
+        Self {
+            path,
+            reader: None,
+            window: None,
+        }
+    }
+
+    fn query(
+        &mut self,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+        start: u64,
+        end: u64,
+        lookahead: u64,
+    ) -> Vec<VariantContext> {
+        if self.reader.is_none() {
+            self.reader = Some(VariantContext::retrieve_indexed_vcf_file(&self.path));
+        }
+        let reader = self.reader.as_mut().unwrap();
+        let rid =
+            match VariantContext::get_contig_vcf_tid(reader.header(), contig_name, separator) {
+                Some(rid) => rid,
+                None => return Vec::new(),
+            };
+
+        let cached = self
+            .window
+            .as_ref()
+            .is_some_and(|window| window.covers(rid, start, end));
+        if !cached {
+            let window_end = end + lookahead;
+            reader
+                .fetch(rid, start, Some(window_end))
+                .unwrap_or_else(|e| panic!("Failed to fetch region of {}: {}", self.path, e));
+            let features = reader
+                .records()
+                .map(|record| {
+                    let mut record = record.unwrap();
+                    // the end of symbolic alleles such as <DEL> is given by their END
+                    let feature_start = record.pos().max(0) as u64;
+                    let feature_end = (record.end().max(1) - 1).max(record.pos()) as u64;
+                    let feature = VariantContext::from_vcf_record(&mut record, true);
+                    (feature_start, feature_end, feature)
+                })
+                .filter_map(|(feature_start, feature_end, feature)| {
+                    feature.map(|feature| (feature_start, feature_end, feature))
+                })
+                .collect::<Vec<(u64, u64, VariantContext)>>();
+            self.window = Some(FeatureWindow::new(rid, start, window_end, features));
+        }
+
+        self.window.as_ref().unwrap().overlapping(start, end)
+    }
+}
+
+/// Region by region access to the feature VCFs guiding variant calling, read through their index.
+/// Regions are called in position order, so each query reads `lookahead` past the region and serves
+/// the following regions from memory. Readers can not be shared between threads, so make one
+/// context per thread.
+pub struct FeatureContext {
+    sources: Vec<FeatureSource>,
+    lookahead: u64,
+}
+
+impl FeatureContext {
+    pub const DEFAULT_LOOKAHEAD: u64 = 10_000;
+
+    pub fn new(paths: Vec<String>, lookahead: u64) -> Self {
+        Self {
+            sources: paths.into_iter().map(FeatureSource::new).collect(),
+            lookahead,
+        }
+    }
+
+    /// The VCFs given by --features-vcf, along with the structural variants called from long
+    /// reads into `output_prefix`, if any
+    pub fn from_args(args: &clap::ArgMatches, output_prefix: Option<&str>) -> Self {
+        let mut paths = args
+            .try_get_many::<String>("features-vcf")
+            .ok()
+            .flatten()
+            .map(|paths| paths.cloned().collect::<Vec<String>>())
+            .unwrap_or_default();
+        if let Some(output_prefix) = output_prefix {
+            // structural variants called from long reads guide the regions they fall in as well
+            let called_svs = format!("{}/structural_variants.vcf.gz", output_prefix);
+            if Path::new(&called_svs).exists() {
+                paths.push(called_svs);
+            }
+        }
+        Self::new(paths, Self::DEFAULT_LOOKAHEAD)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .map(|source| source.path.clone())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// The features of every VCF overlapping a region of a contig. `start` and `end` are 0-based
+    /// and inclusive
+    pub fn query(
+        &mut self,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+        start: u64,
+        end: u64,
+    ) -> Vec<VariantContext> {
+        let lookahead = self.lookahead;
+        self.sources
+            .iter_mut()
+            .flat_map(|source| source.query(contig_name, separator, start, end, lookahead))
+            .collect()
+    }
+
+    /// Checks that every VCF can be queried by region, indexing any local VCF that is not indexed
+    /// yet. Run before querying from several threads so that a VCF is only ever indexed once
+    pub fn check(paths: &[String]) -> Result<(), BirdToolError> {
+        for path in paths.iter() {
+            VcfInput::open_indexed(path)?;
+        }
+        Ok(())
+    }
+}
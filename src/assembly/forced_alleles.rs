@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use crate::model::byte_array_allele::ByteArrayAllele;
+use crate::model::variant_context::VariantContext;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::utils::errors::BirdToolError;
+
+/// A locus given in a positions TSV along with the alleles to genotype there. `pos` is 0-based
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForcedPosition {
+    pub pos: usize,
+    pub ref_allele: Vec<u8>,
+    pub alt_alleles: Vec<Vec<u8>>,
+}
+
+impl ForcedPosition {
+    /// Last reference position covered by the reference allele
+    pub fn end(&self) -> usize {
+        self.pos + self.ref_allele.len() - 1
+    }
+
+    pub fn to_variant_context(&self, tid: usize) -> VariantContext {
+        let mut alleles = Vec::with_capacity(self.alt_alleles.len() + 1);
+        alleles.push(ByteArrayAllele::new(&self.ref_allele, true));
+        for alt_allele in self.alt_alleles.iter() {
+            alleles.push(ByteArrayAllele::new(alt_allele, false));
+        }
+        VariantContext::build(tid, self.pos, self.end(), alleles)
+    }
+}
+
+/// Alleles to genotype regardless of evidence, read from a TSV of contig, 1-based position,
+/// reference allele and comma separated alternate alleles. Forced positions get maximum activity
+/// and their alleles are injected into the given alleles of their region.
+#[derive(Debug, Clone, Default)]
+pub struct ForcedAlleles {
+    // sorted by position within each contig
+    positions: Arc<HashMap<String, Vec<ForcedPosition>>>,
+}
+
+impl ForcedAlleles {
+    pub const ACTIVE_PROBABILITY: f32 = 1.0;
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let paths = args
+            .try_get_many::<String>("features-tsv")
+            .ok()
+            .flatten()?
+            .cloned()
+            .collect::<Vec<String>>();
+
+        match Self::from_tsvs(&paths) {
+            Ok(forced_alleles) => Some(forced_alleles),
+            Err(e) => {
+                panic!("Unable to read positions TSV files {:?}: {:?}", paths, e);
+            }
+        }
+    }
+
+    pub fn from_tsvs(paths: &[String]) -> Result<Self, BirdToolError> {
+        let mut positions: HashMap<String, Vec<ForcedPosition>> = HashMap::new();
+        for path in paths {
+            let file = File::open(path)
+                .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {}", path, e)))?;
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read {}: {}", path, e))
+                })?;
+                if let Some((contig, position)) = Self::parse_line(&line).map_err(|e| {
+                    BirdToolError::IOError(format!("{} in {}: {}", e, path, line))
+                })? {
+                    positions.entry(contig).or_default().push(position);
+                }
+            }
+        }
+
+        for contig_positions in positions.values_mut() {
+            contig_positions.sort_by_key(|position| position.pos);
+        }
+
+        Ok(Self {
+            positions: Arc::new(positions),
+        })
+    }
+
+    /// Parses a single line of a positions TSV. Returns None for empty, comment and header lines
+    pub fn parse_line(line: &str) -> Result<Option<(String, ForcedPosition)>, String> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            return Err("Expected contig, position, ref and alt columns".to_string());
+        }
+
+        let pos = match fields[1].parse::<usize>() {
+            Ok(pos) if pos > 0 => pos - 1,
+            Ok(_) => return Err("Positions are 1-based".to_string()),
+            Err(_) if ["pos", "position"].contains(&fields[1].to_lowercase().as_str()) => {
+                return Ok(None)
+            }
+            Err(_) => return Err(format!("Invalid position {}", fields[1])),
+        };
+
+        let ref_allele = Self::parse_allele(fields[2])?;
+        let alt_alleles = fields[3]
+            .split(',')
+            .map(Self::parse_allele)
+            .collect::<Result<Vec<Vec<u8>>, String>>()?;
+        if alt_alleles.iter().any(|alt_allele| *alt_allele == ref_allele) {
+            return Err("Alternate allele is the same as the reference allele".to_string());
+        }
+
+        Ok(Some((
+            fields[0].to_string(),
+            ForcedPosition {
+                pos,
+                ref_allele,
+                alt_alleles,
+            },
+        )))
+    }
+
+    fn parse_allele(allele: &str) -> Result<Vec<u8>, String> {
+        let bases = allele.trim().to_ascii_uppercase().into_bytes();
+        if bases.is_empty() || !bases.iter().all(|base| b"ACGTN".contains(base)) {
+            return Err(format!("Invalid allele {}", allele));
+        }
+        Ok(bases)
+    }
+
+    /// Number of forced positions across all contigs
+    pub fn len(&self) -> usize {
+        self.positions.values().map(|positions| positions.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forced positions on a contig, looked up by its full name in the reference and then by the
+    /// name without the genome prefix
+    pub fn positions_for_contig(
+        &self,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+    ) -> &[ForcedPosition] {
+        let contig_name = String::from_utf8_lossy(contig_name);
+        self.positions
+            .get(contig_name.as_ref())
+            .or_else(|| self.positions.get(separator.contig(&contig_name)))
+            .map(|positions| positions.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Forced positions whose reference allele overlaps the inclusive interval start..=end
+    pub fn positions_in<'a>(
+        positions: &'a [ForcedPosition],
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = &'a ForcedPosition> {
+        // reference alleles are short so look back far enough to catch any that start before
+        // the interval
+        let longest_ref = positions
+            .iter()
+            .map(|position| position.ref_allele.len())
+            .max()
+            .unwrap_or(1);
+        let first = positions.partition_point(|position| position.pos + longest_ref <= start);
+        positions[first..]
+            .iter()
+            .take_while(move |position| position.pos <= end)
+            .filter(move |position| position.end() >= start)
+    }
+
+    /// Variant contexts of the forced alleles overlapping the given interval of a contig
+    pub fn variants_in(
+        &self,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+        tid: usize,
+        start: usize,
+        end: usize,
+    ) -> Vec<VariantContext> {
+        Self::positions_in(self.positions_for_contig(contig_name, separator), start, end)
+            .map(|position| position.to_variant_context(tid))
+            .collect()
+    }
+}
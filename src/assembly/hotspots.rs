@@ -0,0 +1,205 @@
+use rust_htslib::bcf::Read;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// Known polymorphic loci, read from BED or VCF files, whose covered positions get an activity of
+/// at least --hotspot-activity so that they are always assembled. No alleles are injected.
+#[derive(Debug, Clone)]
+pub struct Hotspots {
+    // sorted, non-overlapping 0-based half open intervals of each contig
+    intervals: Arc<HashMap<String, Vec<(usize, usize)>>>,
+    activity: f32,
+}
+
+impl Hotspots {
+    pub const DEFAULT_ACTIVITY: f32 = 1.0;
+
+    pub fn new(intervals: HashMap<String, Vec<(usize, usize)>>, activity: f32) -> Self {
+        let mut intervals = intervals;
+        for contig_intervals in intervals.values_mut() {
+            Self::merge(contig_intervals);
+        }
+        Self {
+            intervals: Arc::new(intervals),
+            activity,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let paths = args
+            .try_get_many::<String>("hotspots")
+            .ok()
+            .flatten()?
+            .cloned()
+            .collect::<Vec<String>>();
+        let activity = args
+            .try_get_one::<f32>("hotspot-activity")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(Self::DEFAULT_ACTIVITY);
+
+        match Self::from_files(&paths, activity) {
+            Ok(hotspots) => Some(hotspots),
+            Err(e) => {
+                panic!("Unable to read hotspot files {:?}: {:?}", paths, e);
+            }
+        }
+    }
+
+    pub fn from_files(paths: &[String], activity: f32) -> Result<Self, BirdToolError> {
+        let mut intervals: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+        for path in paths {
+            if Self::is_vcf(path) {
+                Self::read_vcf(path, &mut intervals)?;
+            } else {
+                Self::read_bed(path, &mut intervals)?;
+            }
+        }
+        Ok(Self::new(intervals, activity))
+    }
+
+    fn is_vcf(path: &str) -> bool {
+        [".vcf", ".vcf.gz", ".bcf"]
+            .iter()
+            .any(|suffix| path.ends_with(suffix))
+    }
+
+    fn read_bed(
+        path: &str,
+        intervals: &mut HashMap<String, Vec<(usize, usize)>>,
+    ) -> Result<(), BirdToolError> {
+        let file = File::open(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {}", path, e)))?;
+        for line in BufReader::new(file).lines() {
+            let line = line
+                .map_err(|e| BirdToolError::IOError(format!("Unable to read {}: {}", path, e)))?;
+            if let Some((contig, start, end)) = Self::parse_bed_line(&line)
+                .map_err(|e| BirdToolError::IOError(format!("{} in {}: {}", e, path, line)))?
+            {
+                intervals.entry(contig).or_default().push((start, end));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses a single line of a BED file. Returns None for empty, comment, track and browser
+    /// lines
+    pub fn parse_bed_line(line: &str) -> Result<Option<(String, usize, usize)>, String> {
+        let line = line.trim_end();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with("track")
+            || line.starts_with("browser")
+        {
+            return Ok(None);
+        }
+
+        let fields = line.split('\t').collect::<Vec<&str>>();
+        if fields.len() < 3 {
+            return Err("Expected contig, start and end columns".to_string());
+        }
+        let start = fields[1]
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid start {}", fields[1]))?;
+        let end = fields[2]
+            .parse::<usize>()
+            .map_err(|_| format!("Invalid end {}", fields[2]))?;
+        if end <= start {
+            return Err("End must be greater than start".to_string());
+        }
+        Ok(Some((fields[0].to_string(), start, end)))
+    }
+
+    fn read_vcf(
+        path: &str,
+        intervals: &mut HashMap<String, Vec<(usize, usize)>>,
+    ) -> Result<(), BirdToolError> {
+        let mut reader = VcfInput::open(path)?;
+        let header = reader.header().clone();
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", path, e))
+            })?;
+            let rid = match record.rid() {
+                Some(rid) => rid,
+                None => continue,
+            };
+            let contig = header.rid2name(rid).map_err(|e| {
+                BirdToolError::IOError(format!("Unknown contig in {}: {}", path, e))
+            })?;
+            let start = record.pos().max(0) as usize;
+            // the end of symbolic alleles such as <DEL> is given by their END
+            let end = (record.end().max(0) as usize).max(start + 1);
+            intervals
+                .entry(String::from_utf8_lossy(contig).to_string())
+                .or_default()
+                .push((start, end));
+        }
+        Ok(())
+    }
+
+    /// Sorts intervals and merges those that overlap or abut
+    fn merge(intervals: &mut Vec<(usize, usize)>) {
+        intervals.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+        for (start, end) in intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        *intervals = merged;
+    }
+
+    /// The minimum activity probability of covered hotspot positions
+    pub fn activity(&self) -> f32 {
+        self.activity
+    }
+
+    /// Number of hotspot intervals across all contigs, after merging
+    pub fn len(&self) -> usize {
+        self.intervals
+            .values()
+            .map(|intervals| intervals.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Hotspot intervals on a contig, looked up by its full name in the reference and then by the
+    /// name without the genome prefix
+    pub fn intervals_for_contig(
+        &self,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+    ) -> &[(usize, usize)] {
+        let contig_name = String::from_utf8_lossy(contig_name);
+        self.intervals
+            .get(contig_name.as_ref())
+            .or_else(|| self.intervals.get(separator.contig(&contig_name)))
+            .map(|intervals| intervals.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Whether a 0-based position falls within one of the intervals of a contig
+    pub fn contains(intervals: &[(usize, usize)], position: usize) -> bool {
+        let index = intervals.partition_point(|(_, end)| *end <= position);
+        intervals
+            .get(index)
+            .is_some_and(|(start, _)| *start <= position)
+    }
+
+    /// The activity probability of a hotspot position, given its probability from the reads
+    pub fn boost(&self, probability: f64) -> f64 {
+        probability.max(self.activity as f64)
+    }
+}
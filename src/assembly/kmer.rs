@@ -2,6 +2,10 @@ use std::cmp::min;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
+/// Hash builder of the maps and sets keyed by kmers. Kmers carry their own hash, so a fast
+/// non-cryptographic hasher is all that is needed to spread them over the buckets
+pub type KmerHashBuilder = ahash::RandomState;
+
 /**
  * Fast wrapper for byte[] kmers
  *
@@ -11,6 +15,11 @@ use std::hash::{Hash, Hasher};
  * -- Fast equals and hashcode methods
  * -- can get actual byte[] of the kmer, even if it's from a larger byte[], and this operation
  *    only does the work of that operation once, updating its internal state
+ *
+ * Kmers of up to `MAX_ENCODED_LENGTH` A, C, G and T bases are identified by their 2-bit encoding,
+ * which is exact and can be rolled along a sequence one base at a time with `Kmer::rolling`.
+ * Longer kmers and kmers containing any other base, lower case bases included, fall back to a
+ * hash of their bases, so kmers differing only in case are different kmers.
  */
 #[derive(Debug, Clone)]
 pub struct Kmer {
@@ -19,11 +28,16 @@ pub struct Kmer {
     start: usize,
     // two constants
     length: usize,
-    hash: usize,
+    hash: u64,
 }
 
 // TODO: Change Kmer to take a reference to a sequence and have a lifetime
 impl Kmer {
+    /// The longest kmer identified by its 2-bit encoding. One bit is left spare so that encoded
+    /// kmers never collide with hashed kmers
+    pub const MAX_ENCODED_LENGTH: usize = 31;
+    const HASHED_FLAG: u64 = 1 << 63;
+
     /**
      * Create a new kmer using all bases in kmer
      * @param kmer a non-null byte[]. The input array must not be modified by the caller.
@@ -67,25 +81,49 @@ impl Kmer {
         Self::new_with_start_and_length(bases, self.start + new_start, new_length)
     }
 
+    /// Iterates over the kmers of `length` starting at each position of `bases` from `start` up
+    /// to and including `last_start`, along with their start. Each kmer is encoded from the
+    /// previous one by adding a single base rather than from all of its bases
+    pub fn rolling(bases: &[u8], start: usize, last_start: usize, length: usize) -> RollingKmers {
+        RollingKmers::new(bases, start, last_start, length)
+    }
+
     ///
     /// Compute the hashcode for a KMer.
     ///
-    fn hash_code(bases: &[u8], start: usize, length: usize) -> usize {
+    fn hash_code(bases: &[u8], start: usize, length: usize) -> u64 {
         if length == 0 {
             return 0;
         }
 
         let stop = min(start + length, bases.len());
-        
+        if stop - start <= Self::MAX_ENCODED_LENGTH {
+            let encoded = bases[start..stop].iter().try_fold(0, |code, base| {
+                Self::encode_base(*base).map(|encoded_base| (code << 2) | encoded_base)
+            });
+            if let Some(code) = encoded {
+                return code;
+            }
+        }
+
+        Self::slice_hash(&bases[start..stop])
+    }
+
+    fn slice_hash(bases: &[u8]) -> u64 {
         let mut hasher = DefaultHasher::new();
-        bases[start..stop].hash(&mut hasher);
-        let hash = hasher.finish() as usize;
-        
-        // for i in start..stop {
-        //     h = 31 * h + bases[i] as usize;
-        // }
-
-        hash
+        bases.hash(&mut hasher);
+        hasher.finish() | Self::HASHED_FLAG
+    }
+
+    #[inline]
+    fn encode_base(base: u8) -> Option<u64> {
+        match base {
+            b'A' => Some(0),
+            b'C' => Some(1),
+            b'G' => Some(2),
+            b'T' => Some(3),
+            _ => None,
+        }
     }
 
     ///
@@ -164,3 +202,83 @@ impl Hash for Kmer {
         self.hash.hash(state)
     }
 }
+
+/// Iterator over consecutive kmers of a sequence, see [`Kmer::rolling`]
+pub struct RollingKmers<'a> {
+    bases: &'a [u8],
+    length: usize,
+    next_start: usize,
+    last_start: usize,
+    mask: u64,
+    code: u64,
+    // number of consecutive encodable bases ending at the last base added to code
+    encoded_run: usize,
+    // the next base to be added to code
+    next_base: usize,
+}
+
+impl<'a> RollingKmers<'a> {
+    fn new(bases: &'a [u8], start: usize, last_start: usize, length: usize) -> Self {
+        let mask = if length <= Kmer::MAX_ENCODED_LENGTH {
+            (1 << (2 * length)) - 1
+        } else {
+            0
+        };
+        Self {
+            bases,
+            length,
+            next_start: start,
+            last_start,
+            mask,
+            code: 0,
+            encoded_run: 0,
+            next_base: start,
+        }
+    }
+
+    fn add_base(&mut self, base: u8) {
+        match Kmer::encode_base(base) {
+            Some(encoded_base) => {
+                self.code = ((self.code << 2) | encoded_base) & self.mask;
+                self.encoded_run += 1;
+            }
+            None => {
+                self.code = 0;
+                self.encoded_run = 0;
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for RollingKmers<'a> {
+    type Item = (usize, Kmer);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.next_start;
+        if start > self.last_start || start + self.length > self.bases.len() {
+            return None;
+        }
+        self.next_start += 1;
+
+        let kmer = if self.length == 0 || self.length > Kmer::MAX_ENCODED_LENGTH {
+            Kmer::new_with_start_and_length(self.bases, start, self.length)
+        } else {
+            while self.next_base < start + self.length {
+                self.add_base(self.bases[self.next_base]);
+                self.next_base += 1;
+            }
+            let hash = if self.encoded_run >= self.length {
+                self.code
+            } else {
+                Kmer::slice_hash(&self.bases[start..start + self.length])
+            };
+            Kmer {
+                start,
+                length: self.length,
+                hash,
+            }
+        };
+
+        Some((start, kmer))
+    }
+}
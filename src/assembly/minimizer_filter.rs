@@ -0,0 +1,175 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::reads::bird_tool_reads::BirdToolRead;
+
+/// Number of reads seen and discarded by a [`MinimizerFilter`]. Shared between every clone of
+/// the filter so that counts can be reported once all regions of a reference have been called
+#[derive(Debug, Default)]
+pub struct MinimizerFilterCounts {
+    seen: AtomicUsize,
+    discarded: AtomicUsize,
+}
+
+/// Discards reads of an active region sharing less than `min_shared_fraction` of their (w, k)
+/// minimizers with the reference window, before they are threaded into the assembly graph. Reads
+/// shorter than a full window are kept.
+#[derive(Debug, Clone)]
+pub struct MinimizerFilter {
+    pub kmer_size: usize,
+    pub window_size: usize,
+    pub min_shared_fraction: f64,
+    counts: Arc<MinimizerFilterCounts>,
+}
+
+impl MinimizerFilter {
+    // kmers are packed two bits per base into a u64
+    const MAX_KMER_SIZE: usize = 32;
+
+    pub fn new(kmer_size: usize, window_size: usize, min_shared_fraction: f64) -> Self {
+        Self {
+            kmer_size: kmer_size.clamp(1, Self::MAX_KMER_SIZE),
+            window_size: window_size.max(1),
+            min_shared_fraction,
+            counts: Arc::new(MinimizerFilterCounts::default()),
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        if !args.get_flag("minimizer-prefilter") {
+            return None;
+        }
+
+        Some(Self::new(
+            *args.get_one::<usize>("minimizer-kmer-size").unwrap(),
+            *args.get_one::<usize>("minimizer-window-size").unwrap(),
+            *args.get_one::<f64>("min-shared-minimizer-fraction").unwrap(),
+        ))
+    }
+
+    fn encode(base: u8) -> Option<u64> {
+        match base {
+            b'A' | b'a' => Some(0),
+            b'C' | b'c' => Some(1),
+            b'G' | b'g' => Some(2),
+            b'T' | b't' => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Invertible integer hash so that minimizers are not biased towards poly-A kmers
+    fn hash(kmer: u64) -> u64 {
+        let mut x = kmer.wrapping_add(0x9e3779b97f4a7c15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+        x ^ (x >> 31)
+    }
+
+    /// Hashes of every kmer in the sequence, None for kmers containing an ambiguous base
+    fn kmer_hashes(&self, bases: &[u8]) -> Vec<Option<u64>> {
+        if bases.len() < self.kmer_size {
+            return Vec::new();
+        }
+
+        let mask = if self.kmer_size == Self::MAX_KMER_SIZE {
+            u64::MAX
+        } else {
+            (1u64 << (2 * self.kmer_size)) - 1
+        };
+        let mut hashes = Vec::with_capacity(bases.len() - self.kmer_size + 1);
+        let mut kmer = 0u64;
+        // number of valid bases at the end of the current kmer
+        let mut valid = 0;
+        for (i, base) in bases.iter().enumerate() {
+            match Self::encode(*base) {
+                Some(code) => {
+                    kmer = ((kmer << 2) | code) & mask;
+                    valid += 1;
+                }
+                None => valid = 0,
+            }
+
+            if i + 1 >= self.kmer_size {
+                if valid >= self.kmer_size {
+                    hashes.push(Some(Self::hash(kmer)));
+                } else {
+                    hashes.push(None);
+                }
+            }
+        }
+        hashes
+    }
+
+    /// The set of (w, k) minimizer hashes of a sequence. Empty if the sequence is shorter than a
+    /// single window of kmers
+    pub fn sketch(&self, bases: &[u8]) -> HashSet<u64> {
+        let hashes = self.kmer_hashes(bases);
+        if hashes.len() < self.window_size {
+            return HashSet::new();
+        }
+
+        hashes
+            .windows(self.window_size)
+            .filter_map(|window| window.iter().flatten().min().copied())
+            .collect()
+    }
+
+    /// Fraction of the minimizers of `bases` found in the reference sketch, or None if the
+    /// sequence has no minimizers
+    pub fn shared_fraction(&self, reference_sketch: &HashSet<u64>, bases: &[u8]) -> Option<f64> {
+        let sketch = self.sketch(bases);
+        if sketch.is_empty() {
+            return None;
+        }
+
+        let shared = sketch
+            .iter()
+            .filter(|hash| reference_sketch.contains(hash))
+            .count();
+        Some(shared as f64 / sketch.len() as f64)
+    }
+
+    /// Removes the reads sharing too few minimizers with the reference window of a region.
+    /// Returns the kept reads and the number discarded
+    pub fn filter_reads(
+        &self,
+        reads: Vec<BirdToolRead>,
+        reference: &[u8],
+    ) -> (Vec<BirdToolRead>, usize) {
+        let reference_sketch = self.sketch(reference);
+        let n_reads = reads.len();
+        let kept = reads
+            .into_iter()
+            .filter(|read| {
+                match self.shared_fraction(&reference_sketch, &read.read.seq().as_bytes()) {
+                    Some(fraction) => fraction >= self.min_shared_fraction,
+                    None => true,
+                }
+            })
+            .collect::<Vec<BirdToolRead>>();
+
+        let discarded = n_reads - kept.len();
+        self.counts.seen.fetch_add(n_reads, Ordering::Relaxed);
+        self.counts.discarded.fetch_add(discarded, Ordering::Relaxed);
+        (kept, discarded)
+    }
+
+    /// Total number of reads seen and discarded by this filter and all of its clones
+    pub fn counts(&self) -> (usize, usize) {
+        (
+            self.counts.seen.load(Ordering::Relaxed),
+            self.counts.discarded.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Percentage of the reads seen that were discarded
+    pub fn discard_rate(&self) -> f64 {
+        let (seen, discarded) = self.counts();
+        if seen == 0 {
+            0.0
+        } else {
+            discarded as f64 / seen as f64 * 100.0
+        }
+    }
+}
@@ -1,3 +1,4 @@
+pub mod active_region_log;
 pub mod assembly_based_caller_utils;
 pub mod assembly_region;
 pub mod assembly_region_iterator;
@@ -5,5 +6,10 @@ pub mod assembly_region_trimmer;
 pub mod assembly_region_walker;
 pub mod assembly_result;
 pub mod assembly_result_set;
+pub mod feature_context;
+pub mod forced_alleles;
+pub mod hotspots;
 pub mod kmer;
 pub mod kmer_counter;
+pub mod minimizer_filter;
+pub mod soft_clip_rescue;
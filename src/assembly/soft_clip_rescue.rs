@@ -0,0 +1,317 @@
+use rust_htslib::bam::record::{Cigar, Record};
+use std::collections::BTreeMap;
+
+use crate::model::byte_array_allele::ByteArrayAllele;
+use crate::model::variant_context::VariantContext;
+use crate::reads::bird_tool_reads::BirdToolRead;
+use crate::utils::simple_interval::{Locatable, SimpleInterval};
+
+/// The end of a read that was soft clipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ClipSide {
+    // bases before the alignment start are clipped, so the clip lies upstream of the breakpoint
+    Left,
+    // bases after the alignment end are clipped, so the clip lies downstream of the breakpoint
+    Right,
+}
+
+/// The soft clipped bases of a single read. `bases` are stored in reference orientation and
+/// `breakpoint` is the 0-based reference position of the first base after the junction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClippedSequence {
+    pub breakpoint: usize,
+    pub side: ClipSide,
+    pub bases: Vec<u8>,
+}
+
+impl ClippedSequence {
+    pub fn new(breakpoint: usize, side: ClipSide, bases: Vec<u8>) -> Self {
+        Self {
+            breakpoint,
+            side,
+            bases,
+        }
+    }
+}
+
+/// Recovers insertions that are too long for reads to align across. Reads spanning such an
+/// insertion are soft clipped at the breakpoint and their clipped bases are hard clipped away
+/// before assembly, so the graph never sees the inserted sequence. This collects the clipped
+/// sequence of each read before finalization, clusters clips that share a breakpoint and builds
+/// a consensus of the inserted sequence from the clips on either side of it. The resulting
+/// insertions are injected into the assembly as candidate haplotypes and are genotyped like any
+/// other assembled event.
+#[derive(Debug, Clone)]
+pub struct SoftClipRescue {
+    pub min_clip_length: usize,
+    pub min_clipped_reads: usize,
+    pub min_base_quality: u8,
+}
+
+impl SoftClipRescue {
+    // number of reference bases that must be found in a clip to mark the end of the insertion
+    const ANCHOR_LENGTH: usize = 8;
+    // right and left clipped clusters this close together are treated as the same breakpoint
+    const BREAKPOINT_TOLERANCE: usize = 3;
+    // minimum overlap between the left and right clip consensus when neither spans the insertion
+    const MIN_OVERLAP: usize = 8;
+    const MIN_COLUMN_DEPTH: usize = 2;
+    const MIN_CONSENSUS_FRACTION: f64 = 0.75;
+
+    pub fn new(min_clip_length: usize, min_clipped_reads: usize, min_base_quality: u8) -> Self {
+        Self {
+            min_clip_length,
+            min_clipped_reads,
+            min_base_quality,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            *args.get_one::<usize>("min-rescue-clip-length").unwrap(),
+            *args.get_one::<usize>("min-rescue-clipped-reads").unwrap(),
+            *args.get_one::<u8>("min-base-quality").unwrap(),
+        )
+    }
+
+    /// Collects the soft clips of every read long enough to be evidence of a breakpoint
+    pub fn collect_clips(&self, reads: &[BirdToolRead]) -> Vec<ClippedSequence> {
+        reads
+            .iter()
+            .flat_map(|read| self.clips_of_record(&read.read))
+            .collect()
+    }
+
+    /// The left and right soft clips of a single alignment. Clipped bases are truncated at the
+    /// first base, moving away from the breakpoint, below the minimum base quality.
+    pub fn clips_of_record(&self, record: &Record) -> Vec<ClippedSequence> {
+        let mut clips = Vec::new();
+        if record.is_unmapped() || record.seq_len() == 0 {
+            return clips;
+        }
+
+        let cigar = record.cigar();
+        let ops = cigar
+            .iter()
+            .filter(|op| !matches!(op, Cigar::HardClip(_)))
+            .collect::<Vec<&Cigar>>();
+        let seq = record.seq().as_bytes();
+        let quals = record.qual();
+
+        if let Some(Cigar::SoftClip(len)) = ops.first() {
+            let len = *len as usize;
+            let kept = (0..len)
+                .rev()
+                .take_while(|i| quals[*i] >= self.min_base_quality)
+                .count();
+            if kept >= self.min_clip_length {
+                clips.push(ClippedSequence::new(
+                    record.pos() as usize,
+                    ClipSide::Left,
+                    seq[len - kept..len].to_ascii_uppercase(),
+                ));
+            }
+        }
+
+        if ops.len() > 1 {
+            if let Some(Cigar::SoftClip(len)) = ops.last() {
+                let start = seq.len() - *len as usize;
+                let kept = (start..seq.len())
+                    .take_while(|i| quals[*i] >= self.min_base_quality)
+                    .count();
+                if kept >= self.min_clip_length {
+                    clips.push(ClippedSequence::new(
+                        cigar.end_pos() as usize,
+                        ClipSide::Right,
+                        seq[start..start + kept].to_ascii_uppercase(),
+                    ));
+                }
+            }
+        }
+
+        clips
+    }
+
+    /// Majority consensus of a set of clips, built outwards from the breakpoint. The consensus
+    /// stops at the first column covered by too few clips or without a clear majority base.
+    pub fn consensus(clips: &[&ClippedSequence], side: ClipSide) -> Vec<u8> {
+        let oriented = clips
+            .iter()
+            .map(|clip| match side {
+                ClipSide::Right => clip.bases.clone(),
+                ClipSide::Left => clip.bases.iter().rev().cloned().collect(),
+            })
+            .collect::<Vec<Vec<u8>>>();
+
+        let longest = oriented.iter().map(|bases| bases.len()).max().unwrap_or(0);
+        let mut consensus = Vec::with_capacity(longest);
+        for column in 0..longest {
+            let mut counts = [0usize; 4];
+            let mut depth = 0;
+            for bases in oriented.iter() {
+                if let Some(base) = bases.get(column) {
+                    depth += 1;
+                    match base {
+                        b'A' => counts[0] += 1,
+                        b'C' => counts[1] += 1,
+                        b'G' => counts[2] += 1,
+                        b'T' => counts[3] += 1,
+                        _ => {}
+                    }
+                }
+            }
+
+            if depth < Self::MIN_COLUMN_DEPTH.min(clips.len()) {
+                break;
+            }
+
+            let (best, count) = counts
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, count)| **count)
+                .unwrap();
+            if (*count as f64) < Self::MIN_CONSENSUS_FRACTION * depth as f64 {
+                break;
+            }
+            consensus.push(b"ACGT"[best]);
+        }
+
+        if side == ClipSide::Left {
+            consensus.reverse();
+        }
+        consensus
+    }
+
+    /// Works out the inserted sequence at `breakpoint`, an index into `reference`, from the
+    /// consensus of the clips downstream (`right`) and upstream (`left`) of the junction. A clip
+    /// that spans the whole insertion reaches back into the reference on the far side, which marks
+    /// where the insertion ends. Otherwise the two halves are joined by their overlap.
+    pub fn resolve_insertion(
+        right: Option<&[u8]>,
+        left: Option<&[u8]>,
+        reference: &[u8],
+        breakpoint: usize,
+    ) -> Option<Vec<u8>> {
+        if breakpoint == 0 || breakpoint + Self::ANCHOR_LENGTH > reference.len() {
+            return None;
+        }
+
+        if let Some(right) = right {
+            let anchor = &reference[breakpoint..breakpoint + Self::ANCHOR_LENGTH];
+            if let Some(i) = right
+                .windows(Self::ANCHOR_LENGTH)
+                .position(|window| window == anchor)
+            {
+                return if i > 0 { Some(right[..i].to_vec()) } else { None };
+            }
+        }
+
+        if let Some(left) = left {
+            if breakpoint >= Self::ANCHOR_LENGTH {
+                let anchor = &reference[breakpoint - Self::ANCHOR_LENGTH..breakpoint];
+                if let Some(i) = left
+                    .windows(Self::ANCHOR_LENGTH)
+                    .rposition(|window| window == anchor)
+                {
+                    let end = i + Self::ANCHOR_LENGTH;
+                    return if end < left.len() {
+                        Some(left[end..].to_vec())
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+
+        match (right, left) {
+            (Some(right), Some(left)) => {
+                let max_overlap = right.len().min(left.len());
+                (Self::MIN_OVERLAP..=max_overlap)
+                    .rev()
+                    .find(|k| right[right.len() - k..] == left[..*k])
+                    .map(|k| {
+                        let mut inserted = right.to_vec();
+                        inserted.extend_from_slice(&left[k..]);
+                        inserted
+                    })
+            }
+            _ => None,
+        }
+    }
+
+    /// Clusters the clips by breakpoint and returns an insertion for each cluster with enough
+    /// supporting reads whose inserted sequence could be resolved. `reference` starts at
+    /// `reference_start` and insertions are only reported within `span`.
+    pub fn rescue(
+        &self,
+        clips: Vec<ClippedSequence>,
+        tid: usize,
+        reference: &[u8],
+        reference_start: usize,
+        span: &SimpleInterval,
+    ) -> Vec<VariantContext> {
+        let mut right_clusters: BTreeMap<usize, Vec<&ClippedSequence>> = BTreeMap::new();
+        let mut left_clusters: BTreeMap<usize, Vec<&ClippedSequence>> = BTreeMap::new();
+        for clip in clips.iter() {
+            match clip.side {
+                ClipSide::Right => right_clusters.entry(clip.breakpoint).or_default().push(clip),
+                ClipSide::Left => left_clusters.entry(clip.breakpoint).or_default().push(clip),
+            }
+        }
+
+        let mut candidates = Vec::new();
+        for (breakpoint, right) in right_clusters.iter() {
+            let low = breakpoint.saturating_sub(Self::BREAKPOINT_TOLERANCE);
+            let partner = left_clusters
+                .range(low..=breakpoint + Self::BREAKPOINT_TOLERANCE)
+                .max_by_key(|(_, left)| left.len())
+                .map(|(position, _)| *position);
+            let left = partner.and_then(|position| left_clusters.remove(&position));
+            candidates.push((*breakpoint, Some(right.clone()), left));
+        }
+        candidates.extend(
+            left_clusters
+                .into_iter()
+                .map(|(breakpoint, left)| (breakpoint, None, Some(left))),
+        );
+
+        let mut insertions = Vec::new();
+        for (breakpoint, right, left) in candidates {
+            let support = right.as_ref().map(|c| c.len()).unwrap_or(0)
+                + left.as_ref().map(|c| c.len()).unwrap_or(0);
+            if support < self.min_clipped_reads
+                || breakpoint <= reference_start
+                || breakpoint <= span.get_start()
+                || breakpoint > span.get_end()
+            {
+                continue;
+            }
+
+            let right_consensus = right.map(|c| Self::consensus(&c, ClipSide::Right));
+            let left_consensus = left.map(|c| Self::consensus(&c, ClipSide::Left));
+            let offset = breakpoint - reference_start;
+            if let Some(inserted) = Self::resolve_insertion(
+                right_consensus.as_deref(),
+                left_consensus.as_deref(),
+                reference,
+                offset,
+            ) {
+                let ref_base = reference[offset - 1].to_ascii_uppercase();
+                let mut alt_bases = vec![ref_base];
+                alt_bases.extend(inserted);
+                let position = breakpoint - 1;
+                insertions.push(VariantContext::build(
+                    tid,
+                    position,
+                    position,
+                    vec![
+                        ByteArrayAllele::new(&[ref_base], true),
+                        ByteArrayAllele::new(&alt_bases, false),
+                    ],
+                ));
+            }
+        }
+
+        insertions
+    }
+}
@@ -2,14 +2,15 @@ use needletail::parse_fastx_file;
 use std::{
     collections::HashSet,
     io::Read,
-    path::Path,
-    process
 };
-use tempdir::TempDir;
 use tempfile::{Builder, NamedTempFile};
 
 use crate::bam_parsing::bam_generator::MappingProgram;
-use crate::bam_parsing::CONCATENATED_FASTA_FILE_SEPARATOR;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::reference::reference_reader_utils::ReferenceReaderUtils;
+use crate::utils::errors::BirdToolError;
+use crate::utils::exit_status::ExitStatus;
+use crate::utils::temp_resources::{TempResource, TempResources};
 
 
 pub trait MappingIndex {
@@ -34,7 +35,7 @@ impl MappingIndex for VanillaBwaIndexStuct {
 
 pub struct TemporaryIndexStruct {
     #[allow(dead_code)] // field is never used, it just needs to be kept in scope.
-    tempdir: TempDir,
+    tempdir: TempResource,
     index_path_internal: String,
 }
 
@@ -47,108 +48,126 @@ impl TemporaryIndexStruct {
     ) -> TemporaryIndexStruct {
         // Generate a BWA/minimap index in a temporary directory, where the
         // temporary directory does not go out of scope until the struct does.
-        let td =
-            TempDir::new("coverm-mapping-index").expect("Unable to create temporary directory");
+        let td = TempResources::create_dir("coverm-mapping-index")
+            .expect("Unable to create temporary directory");
         let index_path = std::path::Path::new(td.path()).join(
             std::path::Path::new(reference_path)
                 .file_name()
                 .expect("Failed to glean file stem from reference DB. Strange."),
         );
 
-        info!(
-            "Generating {:?} index for {} ..",
-            mapping_program, reference_path
+        run_index_command(
+            mapping_program,
+            reference_path,
+            &index_path,
+            num_threads,
+            index_creation_options,
         );
-        let mut cmd = match mapping_program {
-            MappingProgram::BWA_MEM => std::process::Command::new("bwa"),
-            MappingProgram::BWA_MEM2 => std::process::Command::new("bwa-mem2"),
-            MappingProgram::MINIMAP2_SR
-            | MappingProgram::MINIMAP2_ONT
-            | MappingProgram::MINIMAP2_PB
-            | MappingProgram::MINIMAP2_HIFI
-            | MappingProgram::MINIMAP2_NO_PRESET => std::process::Command::new("minimap2"),
+        return TemporaryIndexStruct {
+            index_path_internal: index_path.to_string_lossy().to_string(),
+            tempdir: td,
         };
-        match &mapping_program {
-            MappingProgram::BWA_MEM | MappingProgram::BWA_MEM2 => {
-                cmd.arg("index")
-                    .arg("-p")
-                    .arg(&index_path)
-                    .arg(&reference_path);
-            }
-            MappingProgram::MINIMAP2_SR
-            | MappingProgram::MINIMAP2_ONT
-            | MappingProgram::MINIMAP2_HIFI
-            | MappingProgram::MINIMAP2_PB
-            | MappingProgram::MINIMAP2_NO_PRESET => {
-                match &mapping_program {
-                    MappingProgram::MINIMAP2_SR => {
-                        cmd.arg("-x").arg("sr");
-                    }
-                    MappingProgram::MINIMAP2_ONT => {
-                        cmd.arg("-x").arg("map-ont");
-                    }
-                    MappingProgram::MINIMAP2_HIFI => {
-                        cmd.arg("-x").arg("map-hifi");
-                    }
-                    MappingProgram::MINIMAP2_PB => {
-                        cmd.arg("-x").arg("map-pb");
-                    }
-                    MappingProgram::MINIMAP2_NO_PRESET
-                    | MappingProgram::BWA_MEM
-                    | MappingProgram::BWA_MEM2 => {}
-                };
-                match num_threads {
-                    Some(t) => {
-                        cmd.arg("-t").arg(&format!("{}", t));
-                    }
-                    None => {}
+    }
+}
+
+/// Runs the indexer of the mapping program, writing the index of the reference to index_path
+fn run_index_command(
+    mapping_program: MappingProgram,
+    reference_path: &str,
+    index_path: &std::path::Path,
+    num_threads: Option<u16>,
+    index_creation_options: Option<&str>,
+) {
+    info!(
+        "Generating {:?} index for {} ..",
+        mapping_program, reference_path
+    );
+    let mut cmd = match mapping_program {
+        MappingProgram::BWA_MEM => std::process::Command::new("bwa"),
+        MappingProgram::BWA_MEM2 => std::process::Command::new("bwa-mem2"),
+        MappingProgram::MINIMAP2_SR
+        | MappingProgram::MINIMAP2_ONT
+        | MappingProgram::MINIMAP2_PB
+        | MappingProgram::MINIMAP2_HIFI
+        | MappingProgram::MINIMAP2_NO_PRESET => std::process::Command::new("minimap2"),
+    };
+    match &mapping_program {
+        MappingProgram::BWA_MEM | MappingProgram::BWA_MEM2 => {
+            cmd.arg("index")
+                .arg("-p")
+                .arg(&index_path)
+                .arg(&reference_path);
+        }
+        MappingProgram::MINIMAP2_SR
+        | MappingProgram::MINIMAP2_ONT
+        | MappingProgram::MINIMAP2_HIFI
+        | MappingProgram::MINIMAP2_PB
+        | MappingProgram::MINIMAP2_NO_PRESET => {
+            match &mapping_program {
+                MappingProgram::MINIMAP2_SR => {
+                    cmd.arg("-x").arg("sr");
                 }
-                cmd.arg("-d").arg(&index_path).arg(&reference_path);
-            }
-        };
-        match index_creation_options {
-            Some(params) => {
-                for s in params.split_whitespace() {
-                    cmd.arg(s);
+                MappingProgram::MINIMAP2_ONT => {
+                    cmd.arg("-x").arg("map-ont");
+                }
+                MappingProgram::MINIMAP2_HIFI => {
+                    cmd.arg("-x").arg("map-hifi");
+                }
+                MappingProgram::MINIMAP2_PB => {
+                    cmd.arg("-x").arg("map-pb");
+                }
+                MappingProgram::MINIMAP2_NO_PRESET
+                | MappingProgram::BWA_MEM
+                | MappingProgram::BWA_MEM2 => {}
+            };
+            match num_threads {
+                Some(t) => {
+                    cmd.arg("-t").arg(&format!("{}", t));
                 }
+                None => {}
             }
-            None => {}
-        };
-        // Some BWA versions output log info to stdout. Ignore this.
-        cmd.stdout(std::process::Stdio::piped());
-        cmd.stderr(std::process::Stdio::piped());
-        // debug!("Running DB indexing command: {:?}", cmd);
-
-        let mut process = cmd.spawn().expect(&format!(
-            "Failed to start {:?} index process",
-            mapping_program
-        ));
-        let es = process.wait().expect(&format!(
-            "Failed to glean exitstatus from failing {:?} index process",
-            mapping_program
-        ));
-        if !es.success() {
-            error!("Error when running {:?} index process.", mapping_program);
-            let mut err = String::new();
-            process
-                .stderr
-                .expect(&format!(
-                    "Failed to grab stderr from failed {:?} index process",
-                    mapping_program
-                ))
-                .read_to_string(&mut err)
-                .expect("Failed to read stderr into string");
-            error!("The STDERR was: {:?}", err);
-            error!("Cannot continue after {:?} index failed.", mapping_program);
-            process::exit(1);
+            cmd.arg("-d").arg(&index_path).arg(&reference_path);
         }
-        info!("Finished generating {:?} index.", mapping_program);
-        return TemporaryIndexStruct {
-            index_path_internal: index_path.to_string_lossy().to_string(),
-            tempdir: td,
-        };
+    };
+    match index_creation_options {
+        Some(params) => {
+            for s in params.split_whitespace() {
+                cmd.arg(s);
+            }
+        }
+        None => {}
+    };
+    // Some BWA versions output log info to stdout. Ignore this.
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+    // debug!("Running DB indexing command: {:?}", cmd);
+
+    let mut process = cmd.spawn().expect(&format!(
+        "Failed to start {:?} index process",
+        mapping_program
+    ));
+    let es = process.wait().expect(&format!(
+        "Failed to glean exitstatus from failing {:?} index process",
+        mapping_program
+    ));
+    if !es.success() {
+        error!("Error when running {:?} index process.", mapping_program);
+        let mut err = String::new();
+        process
+            .stderr
+            .expect(&format!(
+                "Failed to grab stderr from failed {:?} index process",
+                mapping_program
+            ))
+            .read_to_string(&mut err)
+            .expect("Failed to read stderr into string");
+        error!("The STDERR was: {:?}", err);
+        error!("Cannot continue after {:?} index failed.", mapping_program);
+        ExitStatus::ExternalToolError.exit();
     }
+    info!("Finished generating {:?} index.", mapping_program);
 }
+
 impl MappingIndex for TemporaryIndexStruct {
     fn index_path(&self) -> &String {
         return &self.index_path_internal;
@@ -182,7 +201,7 @@ fn check_for_bwa_index_existence(reference_path: &str, mapping_program: &Mapping
         return true;
     } else {
         error!("BWA index appears to be incomplete, cannot continue.");
-        process::exit(1);
+        ExitStatus::ExternalToolError.exit();
     }
 }
 
@@ -247,7 +266,67 @@ pub fn generate_minimap2_index(
     ));
 }
 
-pub fn generate_concatenated_fasta_file(fasta_file_paths: &Vec<String>) -> NamedTempFile {
+/// Index of a reference in the reference cache. The index is written next to the reference the
+/// first time it is needed and reused by later runs. Minimap2 indexes depend on the preset and
+/// index parameters, so each combination gets its own index
+pub fn generate_cached_index(
+    reference_path: &str,
+    num_threads: Option<u16>,
+    index_creation_parameters: Option<&str>,
+    mapping_program: MappingProgram,
+) -> Box<dyn MappingIndex> {
+    match mapping_program {
+        MappingProgram::BWA_MEM | MappingProgram::BWA_MEM2 => {
+            if check_for_bwa_index_existence(reference_path, &mapping_program) {
+                info!("Using cached {:?} index of {}", mapping_program, reference_path);
+            } else {
+                run_index_command(
+                    mapping_program,
+                    reference_path,
+                    std::path::Path::new(reference_path),
+                    None,
+                    index_creation_parameters,
+                );
+            }
+            Box::new(VanillaBwaIndexStuct::new(reference_path))
+        }
+        MappingProgram::MINIMAP2_SR
+        | MappingProgram::MINIMAP2_ONT
+        | MappingProgram::MINIMAP2_HIFI
+        | MappingProgram::MINIMAP2_PB
+        | MappingProgram::MINIMAP2_NO_PRESET => {
+            let index_path = format!(
+                "{}.{:?}.{:x}.mmi",
+                reference_path,
+                mapping_program,
+                md5::compute(index_creation_parameters.unwrap_or("").as_bytes())
+            );
+            if std::path::Path::new(&index_path).exists() {
+                info!("Using cached {:?} index {}", mapping_program, index_path);
+            } else {
+                // written to a temporary name first so an interrupted run leaves no partial index
+                let partial_index_path = format!("{}.partial", index_path);
+                run_index_command(
+                    mapping_program,
+                    reference_path,
+                    std::path::Path::new(&partial_index_path),
+                    num_threads,
+                    index_creation_parameters,
+                );
+                if let Err(e) = std::fs::rename(&partial_index_path, &index_path) {
+                    error!("Unable to add {:?} index to cache: {:?}", mapping_program, e);
+                    ExitStatus::Failure.exit();
+                }
+            }
+            Box::new(VanillaBwaIndexStuct::new(&index_path))
+        }
+    }
+}
+
+pub fn generate_concatenated_fasta_file(
+    fasta_file_paths: &Vec<String>,
+    separator: GenomeSeparator,
+) -> NamedTempFile {
     let tmpfile: NamedTempFile = Builder::new()
         .prefix("lorikeet-concatenated-fasta")
         .tempfile()
@@ -265,35 +344,12 @@ pub fn generate_concatenated_fasta_file(fasta_file_paths: &Vec<String>) -> Named
             let mut reader =
                 parse_fastx_file(path).expect(&format!("Unable to read fasta file {}", file));
 
-            // Remove .gz .bz .xz from file names if present
-            let genome_name1 =
-                String::from(path.to_str().expect("File name string conversion problem"));
-            
-            // if genome_name1 ends with .gz, .bz, or .xz, the Rust HTSLIB indexed reader
-            // will not be able to parse it. So we need to gracefully exit and inform the user
-            // that they need to decompress the file first. Additionally, if they have provided BAM
-            // files the BAM files will need to be regenerated with decompressed input or reheadered
-            // to remove the .gz, .bz, or .xz extension.
-            if genome_name1.ends_with(".gz") || genome_name1.ends_with(".bz") || genome_name1.ends_with(".xz") {
-                error!("The genome file {} is compressed. Please decompress the file before running lorikeet.", genome_name1);
-                error!("If you are using BAM files, please regenerate the BAM files with decompressed input");
-                error!("Or reheader the BAM files to remove the .gz, .bz, or .xz extension from genome names");
-                process::exit(1);
-            }
-
-
-            let path1 = Path::new(&genome_name1);
-
-            let genome_name = String::from(
-                path1
-                    .file_stem()
-                    .expect("Problem while determining file stem")
-                    .to_str()
-                    .expect("File name string conversion problem"),
-            );
+            // Compressed genomes are decompressed into the concatenated file and named without
+            // their compression extension
+            let genome_name = ReferenceReaderUtils::genome_name_from_path(file);
             if genome_names.contains(&genome_name) {
                 error!("The genome name {} was derived from >1 file", genome_name);
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
             while let Some(record) = reader.next() {
                 let record_expected =
@@ -313,16 +369,19 @@ pub fn generate_concatenated_fasta_file(fasta_file_paths: &Vec<String>) -> Named
                     std::str::from_utf8(record_expected.id())
                         .expect("UTF-8 conversion problem in contig name"),
                 );
+                if let Err(BirdToolError::DebugError(message)) = GenomeSeparator::validate_contig_name(
+                    contig_name.split_once(' ').map(|(contig, _)| contig).unwrap_or(&contig_name),
+                ) {
+                    warn!("{}", message);
+                }
                 writer
                     .write(
-                        &format!(
-                            "{}{}{}",
-                            genome_name,
-                            CONCATENATED_FASTA_FILE_SEPARATOR,
+                        &separator.join(
+                            &genome_name,
                             match contig_name.split_once(' ') {
-                                Some((contig, _)) => contig.to_string(),
-                                None => contig_name,
-                            }
+                                Some((contig, _)) => contig,
+                                None => &contig_name,
+                            },
                         ),
                         None,
                         &record_expected.seq(),
@@ -335,13 +394,13 @@ pub fn generate_concatenated_fasta_file(fasta_file_paths: &Vec<String>) -> Named
                     "FASTA file {} appears to be empty as no sequences were contained in it",
                     file
                 );
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
         }
     }
     if !something_written_at_all {
         error!("Concatenated FASTA file to use as a reference is empty");
-        process::exit(1);
+        ExitStatus::ConfigurationError.exit();
     }
     return tmpfile;
 }
\ No newline at end of file
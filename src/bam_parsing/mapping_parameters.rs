@@ -1,7 +1,7 @@
-use std::process;
-use tempfile::NamedTempFile;
 
 use crate::bam_parsing::{bam_generator::MappingProgram, mapping_index_maintenance::check_reference_existence};
+use crate::reference::reference_cache::ConcatenatedReference;
+use crate::utils::exit_status::ExitStatus;
 
 #[derive(Clone)]
 pub enum ReadFormat {
@@ -25,7 +25,7 @@ impl<'a> MappingParameters<'a> {
     pub fn generate_from_clap(
         m: &'a clap::ArgMatches,
         mapping_program: MappingProgram,
-        reference_tempfile: &'a Option<NamedTempFile>,
+        reference_tempfile: &'a Option<ConcatenatedReference>,
     ) -> MappingParameters<'a> {
         let mut read1: Vec<_> = vec![];
         let mut read2: Vec<_> = vec![];
@@ -51,7 +51,7 @@ impl<'a> MappingParameters<'a> {
                     read1.len(),
                     read2.len()
                 );
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
         }
 
@@ -68,7 +68,7 @@ impl<'a> MappingParameters<'a> {
                      sets, but an odd number ({}) was specified",
                     coupled.len()
                 );
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
             let mut i = 0;
             while i < coupled.len() {
@@ -105,7 +105,7 @@ impl<'a> MappingParameters<'a> {
                         minimap2-no-params if -ont or -pb mapping \
                         is desired."
                     );
-                    process::exit(1);
+                    ExitStatus::ConfigurationError.exit();
                 }
             }
             _ => {}
@@ -159,7 +159,7 @@ impl<'a> MappingParameters<'a> {
     pub fn generate_longread_from_clap(
         m: &'a clap::ArgMatches,
         mapping_program: MappingProgram,
-        reference_tempfile: &'a Option<NamedTempFile>,
+        reference_tempfile: &'a Option<ConcatenatedReference>,
     ) -> MappingParameters<'a> {
         let mut unpaired: Vec<&str> = vec![];
 
@@ -2,6 +2,7 @@ pub mod bam_generator;
 pub mod mapping_index_maintenance;
 pub mod mapping_parameters;
 pub mod filter;
+pub mod split_alignment;
 
 use rust_htslib::bam::record::Record;
 use std::sync::Arc;
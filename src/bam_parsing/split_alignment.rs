@@ -0,0 +1,162 @@
+use rust_htslib::bam::record::{Aux, Cigar, CigarString, Record};
+use std::collections::BTreeSet;
+
+/// One alignment of a read, either a BAM record or an entry of the SA tag of one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlignmentSegment {
+    // None when the contig of an SA entry is not in the header
+    pub tid: Option<u32>,
+    // 0-based, end exclusive
+    pub ref_start: i64,
+    pub ref_end: i64,
+    pub reverse: bool,
+    pub mapq: u8,
+    // the aligned bases of the read in the orientation it was sequenced in, 0-based, end exclusive
+    pub query_start: usize,
+    pub query_end: usize,
+}
+
+impl AlignmentSegment {
+    pub fn from_cigar(
+        tid: Option<u32>,
+        ref_start: i64,
+        reverse: bool,
+        mapq: u8,
+        cigar: &[Cigar],
+    ) -> Self {
+        let is_clip = |op: &&Cigar| matches!(op, Cigar::SoftClip(_) | Cigar::HardClip(_));
+        let leading_clip = cigar
+            .iter()
+            .take_while(is_clip)
+            .map(|op| op.len() as usize)
+            .sum::<usize>();
+        let trailing_clip = cigar
+            .iter()
+            .rev()
+            .take_while(is_clip)
+            .map(|op| op.len() as usize)
+            .sum::<usize>();
+        let (mut query_length, mut ref_length) = (0, 0);
+        for op in cigar {
+            match op {
+                Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) => {
+                    query_length += *len as usize;
+                    ref_length += *len as i64;
+                }
+                Cigar::Ins(len) => query_length += *len as usize,
+                Cigar::Del(len) | Cigar::RefSkip(len) => ref_length += *len as i64,
+                Cigar::SoftClip(_) | Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+
+        // a reverse strand alignment starts at the end of the sequenced read
+        let query_start = if reverse { trailing_clip } else { leading_clip };
+        Self {
+            tid,
+            ref_start,
+            ref_end: ref_start + ref_length,
+            reverse,
+            mapq,
+            query_start,
+            query_end: query_start + query_length,
+        }
+    }
+
+    pub fn from_record(record: &Record) -> Self {
+        Self::from_cigar(
+            u32::try_from(record.tid()).ok(),
+            record.pos(),
+            record.is_reverse(),
+            record.mapq(),
+            &record.cigar(),
+        )
+    }
+
+    /// Parses a single `rname,pos,strand,CIGAR,mapQ,NM` entry of an SA tag, with a 1-based pos.
+    /// `tid_of` gives the tid of a contig name
+    pub fn from_sa_entry<F: Fn(&[u8]) -> Option<u32>>(entry: &str, tid_of: F) -> Option<Self> {
+        let fields = entry.split(',').collect::<Vec<&str>>();
+        if fields.len() < 5 {
+            return None;
+        }
+        let pos = fields[1].parse::<i64>().ok().filter(|pos| *pos > 0)?;
+        let reverse = match fields[2] {
+            "+" => false,
+            "-" => true,
+            _ => return None,
+        };
+        let cigar = CigarString::try_from(fields[3]).ok()?;
+        let mapq = fields[4].parse::<u8>().ok()?;
+
+        Some(Self::from_cigar(
+            tid_of(fields[0].as_bytes()),
+            pos - 1,
+            reverse,
+            mapq,
+            &cigar.0,
+        ))
+    }
+}
+
+/// Every alignment of a read, reconstructed from a single record and its SA tag and ordered along
+/// the read as it was sequenced, so split reads can be treated as one observation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitAlignmentChain {
+    pub segments: Vec<AlignmentSegment>,
+}
+
+impl SplitAlignmentChain {
+    pub fn new(mut segments: Vec<AlignmentSegment>) -> Self {
+        segments.sort_by_key(|segment| (segment.query_start, segment.query_end));
+        segments.dedup();
+        Self { segments }
+    }
+
+    /// The chain of a record, including the record itself. `tid_of` gives the tid of a contig name
+    pub fn from_record<F: Fn(&[u8]) -> Option<u32>>(record: &Record, tid_of: F) -> Self {
+        let mut segments = vec![AlignmentSegment::from_record(record)];
+        if let Some(sa_tag) = Self::sa_tag(record) {
+            segments.extend(Self::parse_sa_tag(&sa_tag, tid_of));
+        }
+        Self::new(segments)
+    }
+
+    /// The SA tag of a record, if it has one
+    pub fn sa_tag(record: &Record) -> Option<String> {
+        match record.aux(b"SA") {
+            Ok(Aux::String(sa_tag)) => Some(sa_tag.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The segments of an SA tag, a `;` terminated list of entries. Malformed entries are skipped
+    pub fn parse_sa_tag<F: Fn(&[u8]) -> Option<u32>>(
+        sa_tag: &str,
+        tid_of: F,
+    ) -> Vec<AlignmentSegment> {
+        sa_tag
+            .split(';')
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| AlignmentSegment::from_sa_entry(entry, &tid_of))
+            .collect()
+    }
+
+    pub fn is_split(&self) -> bool {
+        self.segments.len() > 1
+    }
+
+    /// The contigs any segment of the read aligned to
+    pub fn tids(&self) -> BTreeSet<u32> {
+        self.segments
+            .iter()
+            .filter_map(|segment| segment.tid)
+            .collect()
+    }
+
+    /// Pairs of segments that are consecutive along the read
+    pub fn adjacent_segments(
+        &self,
+    ) -> impl Iterator<Item = (&AlignmentSegment, &AlignmentSegment)> {
+        self.segments.windows(2).map(|pair| (&pair[0], &pair[1]))
+    }
+}
@@ -4,22 +4,45 @@ use lorikeet_genome::external_command_checker;
 use lorikeet_genome::utils::utils::*;
 use lorikeet_genome::bam_parsing::bam_generator::*;
 use lorikeet_genome::processing::lorikeet_engine::{
-    run_summarize, start_lorikeet_engine, ReadType
+    run_apply_model, run_combine, run_concordance, run_gather, run_graph_inspect, run_inspect,
+    run_merge_vcfs, run_phylo, run_summarize, start_lorikeet_engine, ReadType
 };
+use lorikeet_genome::haplotype::event_map::EventMap;
+use lorikeet_genome::pair_hmm::gpu_pair_hmm::PairHMMBackend;
+use lorikeet_genome::processing::bam_reference_check::BamReferenceCheck;
+use lorikeet_genome::processing::dry_run::DryRun;
+use lorikeet_genome::processing::output_layout::OutputLayout;
+use lorikeet_genome::processing::run_config::RunConfig;
+use lorikeet_genome::processing::sample_addition::SampleAddition;
+use lorikeet_genome::processing::scatter_gather::ScatterShard;
+use lorikeet_genome::reads::read_group_profiles::ReadGroupProfiles;
+use lorikeet_genome::reference::reference_cache::ConcatenatedReference;
+use lorikeet_genome::reference::reference_mask::ReferenceMask;
 use lorikeet_genome::reference::reference_reader_utils::{ReferenceReaderUtils, GenomesAndContigs};
 use lorikeet_genome::utils::errors::BirdToolError;
+use lorikeet_genome::utils::exit_status::ExitStatus;
+use lorikeet_genome::utils::log_events::{LogEvents, LogFormat};
+use lorikeet_genome::utils::run_rng::RunRng;
+use lorikeet_genome::utils::temp_resources::{TempResource, TempResources};
+use lorikeet_genome::utils::thread_budget::ThreadBudget;
 use lorikeet_genome::bam_parsing::FlagFilter;
 
 use log::{info, warn};
 use std::env;
-use tempfile::NamedTempFile;
 use clap_complete::{generate, Shell};
 use log::LevelFilter;
 use env_logger::Builder;
 
 fn main() {
     let mut app = build_cli();
-    let matches = app.clone().get_matches();
+    let args = match RunConfig::expand_args(&app, env::args_os().collect()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Invalid config: {}", e);
+            ExitStatus::from_error(&e).exit();
+        }
+    };
+    let matches = app.clone().get_matches_from(args);
     set_log_level(&matches, false);
 
     match matches.subcommand_name() {
@@ -37,30 +60,98 @@ fn main() {
             bird_tool_utils::clap_utils::print_full_help_if_needed(m, genotype_full_help());
             let mode = "genotype";
 
-            match prepare_pileup(m, mode) {
-                Ok(_) => info!("Genotype complete."),
-                Err(e) => warn!("Genotype failed with error: {:?}", e),
-            };
+            finish("Genotype", prepare_pileup(m, mode));
         }
         Some("call") => {
             let m = matches.subcommand_matches("call").unwrap();
             bird_tool_utils::clap_utils::print_full_help_if_needed(m, call_full_help());
             let mode = "call";
 
-            match prepare_pileup(m, mode) {
-                Ok(_) => info!("Call complete."),
-                Err(e) => warn!("Call failed with error: {:?}", e),
-            };
+            finish("Call", prepare_pileup(m, mode));
         }
         Some("consensus") => {
             let m = matches.subcommand_matches("consensus").unwrap();
             bird_tool_utils::clap_utils::print_full_help_if_needed(m, consensus_full_help());
             let mode = "consensus";
 
-            match prepare_pileup(m, mode) {
-                Ok(_) => info!("Consensus complete."),
-                Err(e) => warn!("Consensus failed with error: {:?}", e),
-            };
+            finish("Consensus", prepare_pileup(m, mode));
+        }
+        Some("all") => {
+            let m = matches.subcommand_matches("all").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, all_full_help());
+            let mode = "all";
+
+            finish("All outputs", prepare_pileup(m, mode));
+        }
+        Some("ani") => {
+            let m = matches.subcommand_matches("ani").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, ani_full_help());
+            let mode = "ani";
+
+            finish("ANI calculation", prepare_pileup(m, mode));
+        }
+        Some("concordance") => {
+            let m = matches.subcommand_matches("concordance").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, concordance_full_help());
+            set_log_level(m, true);
+
+            finish("Concordance", run_concordance(m).map(|_| ExitStatus::Success));
+        }
+        Some("combine") => {
+            let m = matches.subcommand_matches("combine").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, combine_full_help());
+            set_log_level(m, true);
+
+            finish("Combine", run_combine(m).map(|_| ExitStatus::Success));
+        }
+        Some("merge-vcfs") => {
+            let m = matches.subcommand_matches("merge-vcfs").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, merge_vcfs_full_help());
+            set_log_level(m, true);
+
+            finish("Merge", run_merge_vcfs(m).map(|_| ExitStatus::Success));
+        }
+        Some("add-sample") => {
+            let m = matches.subcommand_matches("add-sample").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, add_sample_full_help());
+
+            // logging is set up by the call run on the new samples
+            finish("Add sample", add_samples(&app, m));
+        }
+        Some("gather") => {
+            let m = matches.subcommand_matches("gather").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, gather_full_help());
+            set_log_level(m, true);
+
+            finish("Gather", run_gather(m).map(|_| ExitStatus::Success));
+        }
+        Some("phylo") => {
+            let m = matches.subcommand_matches("phylo").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, phylo_full_help());
+            set_log_level(m, true);
+
+            finish("Phylo", run_phylo(m).map(|_| ExitStatus::Success));
+        }
+        Some("graph-inspect") => {
+            let m = matches.subcommand_matches("graph-inspect").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, graph_inspect_full_help());
+            set_log_level(m, true);
+
+            finish("Graph inspect", run_graph_inspect(m).map(|_| ExitStatus::Success));
+        }
+        Some("inspect") => {
+            let m = matches.subcommand_matches("inspect").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, inspect_full_help());
+            set_log_level(m, true);
+
+            finish("Inspect", run_inspect(m).map(|_| ExitStatus::Success));
+        }
+        Some("apply-model") => {
+            let m = matches.subcommand_matches("apply-model").unwrap();
+            bird_tool_utils::clap_utils::print_full_help_if_needed(m, apply_model_full_help());
+            set_log_level(m, true);
+
+            finish("Apply model", run_apply_model(m).map(|_| ExitStatus::Success));
         }
         Some("shell-completion") => {
             let m = matches.subcommand_matches("shell-completion").unwrap();
@@ -81,16 +172,67 @@ fn main() {
     }
 }
 
-fn prepare_pileup(m: &clap::ArgMatches, mode: &str) -> Result<(), BirdToolError> {
+/// Logs how a subcommand finished and exits with the matching exit status
+fn finish(name: &str, result: Result<ExitStatus, BirdToolError>) -> ! {
+    let status = match result {
+        Ok(ExitStatus::Success) => {
+            info!("{} complete.", name);
+            ExitStatus::Success
+        }
+        Ok(ExitStatus::PartialSuccess) => {
+            warn!("{} complete, but some genomes failed and were skipped.", name);
+            ExitStatus::PartialSuccess
+        }
+        Ok(status) => {
+            warn!("{} failed for every genome.", name);
+            status
+        }
+        Err(e) => {
+            warn!("{} failed with error: {}", name, e);
+            ExitStatus::from_error(&e)
+        }
+    };
+    status.exit()
+}
+
+/// Calls the new samples against the genomes of an existing output directory and merges the
+/// results into it
+fn add_samples(app: &clap::Command, m: &clap::ArgMatches) -> Result<ExitStatus, BirdToolError> {
+    let references = m
+        .get_many::<String>("genome-fasta-files")
+        .unwrap()
+        .cloned()
+        .collect::<Vec<String>>();
+    let sample_addition =
+        SampleAddition::new(m.get_one::<String>("output-directory").unwrap(), &references)?;
+
+    let call_matches = app
+        .clone()
+        .try_get_matches_from(sample_addition.call_command_line(m))
+        .map_err(|e| {
+            BirdToolError::ConfigError(format!("Invalid arguments for lorikeet call: {}", e))
+        })?;
+    let call_m = call_matches.subcommand_matches("call").unwrap();
+    let status = prepare_pileup(call_m, "call")?;
+
+    sample_addition.merge_results(call_m)?;
+    Ok(status)
+}
+
+fn prepare_pileup(m: &clap::ArgMatches, mode: &str) -> Result<ExitStatus, BirdToolError> {
     // This function is amazingly painful. It handles every combination of longread and short read
     // mapping or bam file reading. Could not make it smaller using dynamic or static dispatch
     set_log_level(m, true);
+    if m.get_flag("dry-run") {
+        return DryRun::new(m, mode).run().map(|_| ExitStatus::Success);
+    }
+    OutputLayout::validate_template(m.get_one::<String>("output-template").unwrap())?;
+    let config_path = RunConfig::write(m, mode, m.get_one::<String>("output-directory").unwrap())?;
+    info!("Run options written to {}", config_path.display());
+    TempResources::from_args(m)?;
+    info!("Random seed {}", RunRng::from_args(m));
     let filter_params = FilterParameters::generate_from_clap(m);
-    let threads = *m.get_one::<usize>("threads").unwrap();
-    rayon::ThreadPoolBuilder::new()
-        .num_threads(threads)
-        .build_global()
-        .unwrap();
+    ThreadBudget::from_args(m).build_global_pool();
 
     let references = ReferenceReaderUtils::parse_references(m);
     let references = references.iter().map(|p| &**p).collect::<Vec<&str>>();
@@ -98,8 +240,7 @@ fn prepare_pileup(m: &clap::ArgMatches, mode: &str) -> Result<(), BirdToolError>
     // Temp directory that will house all cached bams for variant calling
     let tmp_dir = match m.contains_id("bam-file-cache-directory") {
         false => {
-            let tmp_direct = tempdir::TempDir::new("lorikeet_fifo")
-                .expect("Unable to create temporary directory");
+            let tmp_direct = TempResources::create_dir("lorikeet_fifo")?;
             // debug!("Temp directory {}", tmp_direct.as_ref().to_str().unwrap());
             std::fs::create_dir(format!("{}/long", &tmp_direct.as_ref().to_str().unwrap()))
                 .unwrap();
@@ -118,6 +259,17 @@ fn prepare_pileup(m: &clap::ArgMatches, mode: &str) -> Result<(), BirdToolError>
 
     let (concatenated_genomes, genomes_and_contigs_option) =
         ReferenceReaderUtils::setup_genome_fasta_files(m);
+    // BAM files aligned to another version of a reference would give subtly wrong positions
+    let separator = genomes_and_contigs_option
+        .as_ref()
+        .map(|genomes_and_contigs| genomes_and_contigs.separator)
+        .unwrap_or_default();
+    BamReferenceCheck::check_args(m, &references, separator)?;
+    ReferenceMask::check_args(m)?;
+    PairHMMBackend::check_args(m)?;
+    ReadGroupProfiles::check_args(m)?;
+    ScatterShard::check_args(m)?;
+    EventMap::check_args(m)?;
     // debug!("Found genomes_and_contigs {:?}", genomes_and_contigs_option);
     if m.contains_id("bam-files") {
         let bam_files: Vec<&str> = m.get_many::<String>("bam-files").unwrap().map(|s| &**s).collect();
@@ -382,9 +534,9 @@ fn run_pileup<
     flag_filters: FlagFilter,
     long_readers: Option<Vec<U>>,
     genomes_and_contigs_option: Option<GenomesAndContigs>,
-    tmp_bam_file_cache: Option<tempdir::TempDir>,
-    concatenated_genomes: Option<NamedTempFile>,
-) -> Result<(), BirdToolError> {
+    tmp_bam_file_cache: Option<TempResource>,
+    concatenated_genomes: Option<ConcatenatedReference>,
+) -> Result<ExitStatus, BirdToolError> {
     let genomes_and_contigs = genomes_and_contigs_option.unwrap();
 
     start_lorikeet_engine(
@@ -396,8 +548,7 @@ fn run_pileup<
         genomes_and_contigs,
         tmp_bam_file_cache,
         concatenated_genomes,
-    )?;
-    Ok(())
+    )
 }
 
 fn set_log_level(matches: &clap::ArgMatches, is_last: bool) {
@@ -411,7 +562,17 @@ fn set_log_level(matches: &clap::ArgMatches, is_last: bool) {
         specified = true;
         log_level = LevelFilter::Error;
     }
-    if specified || is_last {
+    if is_last && LogFormat::from_args(matches) == LogFormat::Json {
+        let filters = env::var("RUST_LOG").ok();
+        let log_file = matches
+            .try_get_one::<String>("log-file")
+            .ok()
+            .flatten()
+            .map(|s| s.as_str());
+        if let Err(e) = LogEvents::init_json(log_level, filters.as_deref(), log_file) {
+            panic!("Failed to set up JSON logging: {}", e)
+        }
+    } else if specified || is_last {
         let mut builder = Builder::new();
         builder.filter_level(log_level);
         if env::var("RUST_LOG").is_ok() {
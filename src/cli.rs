@@ -6,6 +6,7 @@ use clap_complete::*;
 use roff::bold as roff_bold;
 use roff::Roff;
 use crate::utils::utils::table_roff;
+use crate::evolve::codon_structs::GeneticCodes;
 
 // See https://github.com/rust-cli/roff-rs/issues/19
 fn bold(s: &str) -> String {
@@ -189,6 +190,16 @@ fn add_thresholding_options(manual: Manual) -> Manual {
                     .long("--include-secondary")
                     .help("Include secondary alignments. [default: not set] \n"),
             )
+            .option(Opt::new("POLICY").long("--split-alignment-policy").help(
+                "How secondary and supplementary alignments that pass the filters above \
+                are used during variant calling. count-all uses and counts every \
+                alignment, skip only uses primary alignments, count-once-per-fragment uses \
+                a single alignment of each read per region, preferring the primary \
+                alignment, and weight-by-mapq uses every alignment with its likelihoods \
+                and AD count weighted by its share of the read's mapping qualities. All \
+                but count-all prevent split long reads being counted more than once in \
+                AD. [default: count-all] \n",
+            ))
             .option(Opt::new("INT").long("--contig-end-exclusion").help(
                 "Exclude bases at the ends of reference \
                          sequences from calculation [default: 0]",
@@ -205,7 +216,24 @@ fn add_thresholding_options(manual: Manual) -> Manual {
                 "Split the mapped read files up per reference.
                          Useful if you think run time is being hampered
                          by I/O. Most of the time this will not improve
-                         performance and instead just increase disk usage.",
+                         performance and instead just increase disk usage.
+                         The unique and shared read counts of each genome
+                         are written to competitive_mapping.tsv and the
+                         reads shared between each pair of genomes to
+                         read_sharing_matrix.tsv in the output directory.",
+            ))
+            .flag(Flag::new().long("--reassign-multimapped-reads").help(
+                "When splitting BAM files with --split-bams, redistribute
+                         MAPQ 0 reads that align equally well to several genomes
+                         using EM estimates of genome abundance. Reads are written
+                         to each genome with a fractional weight that is carried
+                         into allele depths. Requires the mapper to report
+                         secondary alignments.",
+            ))
+            .option(Opt::new("FLOAT").long("--min-reassignment-weight").help(
+                "Minimum share of a multi-mapped read a genome must receive
+                         for the read to be written to it when using
+                         --reassign-multimapped-reads [default: 0.1]",
             )),
     )
 }
@@ -271,6 +299,12 @@ fn read_mapping_params_section() -> Section {
                     monospace_roff("samtools sort -n"),
                 )),
         )
+        .flag(Flag::new().long("--skip-reference-check").help(
+            "Do not check that the contig names, lengths and, where the BAM header \
+            gives M5 checksums, sequences of the BAM files match the reference genomes. \
+            By default any difference stops the run with a per-contig report, as BAM files \
+            aligned to another version of a reference give subtly wrong positions. \n",
+        ))
 }
 
 fn reference_options() -> Section {
@@ -281,7 +315,8 @@ fn reference_options() -> Section {
                 .long("--reference,--genome-fasta-files")
                 .help(&format!(
                     "FASTA files of contigs e.g. concatenated \
-                    genomes or metagenome assembly
+                    genomes or metagenome assembly. Glob patterns e.g. 'genomes/*.fna' \
+                    and gzip, bzip2 or xz compressed files are accepted.
                     [required unless {} is specified] \n",
                     monospace_roff("-d/--genome-fasta-directory")
                 )),
@@ -302,10 +337,34 @@ fn reference_options() -> Section {
                 .short("-x")
                 .long("--genome-fasta-extension")
                 .help(&format!(
-                    "FASTA file extension in --genome-fasta-directory \
-                        [default \"fna\"] \n"
+                    "FASTA file extension in --genome-fasta-directory. \
+                        Compressed files with this extension followed by \
+                        .gz, .bz2 or .xz are also used. [default \"fna\"] \n"
                 )),
         )
+        .option(
+            Opt::new("CHAR")
+                .long("--genome-separator")
+                .help(
+                    "Character joining genome and contig names in the concatenated \
+                    reference, e.g. genome~contig. auto uses ~ unless a genome name \
+                    contains it, in which case the first of ^ @ that is not in any \
+                    genome name is used. A separator or % in a genome name is written \
+                    as % and its hex code, e.g. genome%7E1~contig. [default: auto] \n",
+                ),
+        )
+        .option(
+            Opt::new("PATH")
+                .long("--genome-definition")
+                .help(
+                    "Tab separated file of genome name, contig name, and optionally the \
+                    replicon type of the contig: chromosome, plasmid, or mobile_element. \
+                    Variants on plasmids and mobile elements are clustered separately from \
+                    chromosomal variants, and the copy number of each plasmid and mobile \
+                    element relative to the chromosome is reported per strain. Contigs not \
+                    listed are treated as chromosomal. \n",
+                ),
+        )
 }
 
 fn threads_options() -> Section {
@@ -322,7 +381,21 @@ fn threads_options() -> Section {
                      Thread usage qill not exceed the value \
                      provided by --threads [default 1] \n",
         ))
-}
+        .option(Opt::new("INT").long("--io-threads").help(
+            "Number of the threads provided by --threads reserved for BAM \
+                     decompression. These threads are shared between all open BAM \
+                     files and the remaining threads are used for variant calling. [default 0] \n",
+        ))
+        .flag(Flag::new().long("--pin-threads").help(
+            "Pin each variant calling thread to its own core. Can improve \
+                     throughput on machines with many cores or NUMA nodes. [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--max-contexts-in-memory").help(
+            "Maximum number of variants of a genome sorted in memory. Larger \
+                     sets are sorted in runs of this size written to the output \
+                     directory and merged back, bounding memory usage on genomes \
+                     with millions of variants. [default: 1000000] \n",
+        ))
 
 // fn add_help_options(manual: Manual) -> Manual {
 //     manual
@@ -375,6 +448,21 @@ fn sharding_section() -> Section {
     )))
 }
 
+fn scatter_section() -> Section {
+    Section::new("Scatter/gather options")
+        .option(Opt::new("INT").long("--scatter").help(
+            "Split the variant calling of each genome into this many shards, so that \
+            the shards can be run as separate jobs e.g. on a cluster. Each shard \
+            writes a partial VCF named <genome>.shard_<index>_of_<scatter>.vcf and the \
+            shards are merged with lorikeet gather. ANI tables written by a shard only \
+            describe the variants of that shard. [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--shard-index").help(
+            "The 0-based index of the shard to call when --scatter is set. \
+            [default: 0] \n",
+        ))
+}
+
 fn faq_section() -> Section {
     Section::new("Frequently asked questions (FAQ)").paragraph(&format!(
         "{} Lorikeet makes use of \
@@ -419,24 +507,202 @@ fn variant_calling_section_basic() -> Section {
             "Sets the default ploidy for the analysis to N. \
                     [default: 2] \n",
         ))
+        .flag(Flag::new().long("--estimate-ploidy").help(
+            "Estimate the number of strains of each genome from the allele fractions \
+            of a pileup of each sample before variant calling. The largest estimate \
+            across samples is used as the ploidy of the genome, unless the genome has \
+            a ploidy in --per-genome-config, and potential strains are merged down to \
+            it, so strains never found in the same sample may be merged. \
+            Estimates are written to <genome>_strain_count.tsv \n",
+        ))
+        .option(Opt::new("INT").long("--max-estimated-ploidy").help(
+            "Largest strain count considered by --estimate-ploidy. [default: 6] \n",
+        ))
         .flag(
             Flag::new()
                 .long("--calculate-fst")
-                .help("Calculate Fst values between samples and variants. \n"),
+                .help(
+                    "Calculate Fst values between samples and variants. \
+                    Nucleotide diversity within each sample, in total and per \
+                    window, and Hudson Fst between samples are also written, \
+                    normalised by the bases passing the depth filters in each \
+                    sample or pair of samples rather than by the genome size. \n",
+                ),
         )
+        .flag(Flag::new().long("--sample-dendrogram").help(
+            "Also write a UPGMA dendrogram of the samples of each genome in \
+            Newick format, <genome>_sample_dendrogram.nwk, built from the SNVs at \
+            which the consensus alleles of each pair of samples differ per compared \
+            base. The SNV counts are always written to <genome>_snv_distances.tsv \n",
+        ))
+        .flag(Flag::new().long("--calculate-sfs").help(
+            "Calculate the folded and unfolded site frequency spectra \
+                    of each genome across samples from the final genotypes, \
+                    with each sample contributing ploidy chromosomes. Only \
+                    biallelic sites genotyped in every sample are counted. \
+                    Writes <genome>_site_frequency_spectrum.tsv and \
+                    <genome>_sfs_summary.tsv with Watterson's theta, pi, \
+                    Tajima's D and Fay and Wu's H. The unfolded spectrum \
+                    treats the reference allele as ancestral. \n",
+        ))
+        .flag(Flag::new().long("--reference-bias").help(
+            "Measure the reference bias of the allele depths of each \
+                    sample at heterozygous-like biallelic SNVs, where both \
+                    alleles have --depth-per-sample-filter reads and an allele \
+                    fraction within --reference-bias-min-fraction of either \
+                    end. Writes <genome>_reference_bias.tsv with the mean \
+                    reference fraction, the expected reference to alternate \
+                    read ratio and whether the bias is significant. \n",
+        ))
+        .flag(Flag::new().long("--correct-reference-bias").help(
+            "As --reference-bias, and divide the reference depths of \
+                    significantly biased samples by their bias ratio when \
+                    estimating strain abundances and frequencies. \n",
+        ))
+        .option(Opt::new("FLOAT").long("--reference-bias-min-fraction").help(
+            "Minimum alternate and reference allele fraction of a \
+                    heterozygous-like site used to measure reference bias. \
+                    [default: 0.2] \n",
+        ))
+        .option(Opt::new("STRING").long("--allele-fraction-denominator").help(
+            "Reads counted in the denominator of the per-sample allele \
+                    fractions written to the AF FORMAT field. 'informative' \
+                    counts the reads assigned to an allele, i.e. the sum of AD. \
+                    'all' also counts the reads overlapping the variant that \
+                    support no allele better than the others. \
+                    [default: informative] \n",
+        ))
+        .flag(Flag::new().long("--callable-sites").help(
+            "Write <genome>_callable_sites.bed with the stretches of the \
+                    genome where each sample reaches --depth-per-sample-filter, \
+                    i.e. where a variant would have been called had one \
+                    existed. Masked regions are excluded. Use as the \
+                    denominator when comparing variant counts. \n",
+        ))
+        .option(Opt::new("INT").long("--callable-sites-min-samples").help(
+            "Minimum number of samples a position must be callable in \
+                    to be written to the callable sites BED file. [default: 1] \n",
+        ))
         .flag(Flag::new().long("--calculate-dnds").help(
             "Calculate coding regions and perform dN/dS calculations \
-                    along them using called variants. *Microbial only*. \n",
+                    along them using called variants. *Microbial only*. \
+                    In genotype mode dN/dS is also calculated for each strain \
+                    from the variants assigned to it and written as a genes \
+                    by strains matrix to <genome>_strain_dnds.tsv. \n",
+        ))
+        .option(Opt::new("CODE ..").long("--genetic-code").help(
+            "NCBI translation table used to call genes and calculate \
+                    dN/dS. Give a table id, e.g. 4 for Mycoplasma, to set the \
+                    code for all genomes, or GENOME=ID to set it for a single \
+                    genome. A transl_table attribute on a GFF record takes \
+                    precedence for that gene. Supported tables: 1-6, 9-14, 25. \
+                    [default: 11] \n",
+        ))
+        .option(Opt::new("PATH").long("--marker-catalog").help(
+            "TSV of resistance or marker mutations to summarise, with a \
+                    gene and a mutation on each line. Genes are matched to the \
+                    ID, Name, gene or locus_tag attributes of the genome's GFF \
+                    file and mutations are given as amino acid changes, e.g. \
+                    S450L, or coding sequence changes, e.g. c.1349C>T. Writes \
+                    the frequency of each mutation in every sample and, in \
+                    genotype mode, its presence in every strain to \
+                    <genome>_marker_mutations.tsv. \n",
         ))
-        .option(Opt::new("PATH").short("-f").long("--features-vcf").help(
+        .option(Opt::new("PATH ..").short("-f").long("--features-vcf").help(
             "The set of alleles to force-call regardless \
-                     of evidence. Note: The sight containing these alleles \
+                     of evidence. Can provide one or more, e.g. one per genome. Note: The sight containing these alleles \
                      has to be called as 'active' in order for them to appear \
                      in the final VCF. Addtionally, Provided file must be \
                      compressed using bgzip and indexed using bcftools index. If no index \
                      is present, and index will be attempted to be created. \
                      If the file is not properly compressed, Lorikeet will \
-                     unfortunately SEGFAULT with no error message. \n",
+                     unfortunately SEGFAULT with no error message. Also \
+                     accepted as --feature-vcf, e.g. to provide structural \
+                     variants called beforehand by svim, sniffles2 or cuteSV, \
+                     whose SVTYPE and SVLEN values are normalised on reading. \n",
+        ))
+        .option(Opt::new("PATH ..").long("--features-tsv").help(
+            "Tab separated files of positions to genotype regardless of \
+                     evidence, e.g. known AMR SNPs. Columns are contig, 1-based \
+                     position, reference allele and comma separated alternate \
+                     alleles. Contig names may include or omit the genome prefix. \
+                     Unlike --features-vcf, an active region is always created \
+                     around each position and the files do not need to be \
+                     compressed or indexed. \n",
+        ))
+        .option(Opt::new("PATH ..").long("--hotspots").help(
+            "BED or VCF files of known polymorphic loci, e.g. of a \
+                     surveillance panel, to always assemble. The activity of \
+                     every position within a hotspot that has reads in any \
+                     sample is raised to --hotspot-activity, so an active \
+                     region is created around it even at marginal coverage. \
+                     Files ending in .vcf, .vcf.gz or .bcf are read as VCF. \
+                     Contig names may include or omit the genome prefix. \n",
+        ))
+        .option(Opt::new("FLOAT").long("--hotspot-activity").help(
+            "Minimum activity probability of covered positions within \
+                     --hotspots. Values above --active-probability-threshold \
+                     make them active. [default: 1.0] \n",
+        ))
+        .option(Opt::new("PATH").long("--mask-bed").help(
+            "BED file of low mappability regions to mask. Variants \
+                     falling inside these regions are given the MASKED filter \
+                     and are excluded from ANI, Fst and dN/dS calculations. \
+                     Contig names may include or omit the genome prefix. \n",
+        ))
+        .option(Opt::new("INT").long("--mask-kmer-size").help(
+            "Instead of a BED file, mask every reference position covered \
+                     by a k-mer of this size that is not unique within the \
+                     genome (both strands). Must be between 1 and 32. \
+                     Conflicts with --mask-bed. [default: not_set] \n",
+        ))
+        .option(Opt::new("INT").long("--homopolymer-indel-filter").help(
+            "Give indels touching a reference homopolymer of at least \
+                     this length the HomopolymerIndel filter and exclude them \
+                     from ANI, Fst and dN/dS calculations. Useful for ONT \
+                     reads, which produce frequent false positive indels in \
+                     long homopolymers. All variants are annotated with HRUN, \
+                     and tandem repeat indels with RU and RPA, regardless. \
+                     [default: not_set] \n",
+        ))
+        .option(Opt::new("INT").long("--complexity-window-size").help(
+            "Size of the reference window centred on each variant used to \
+                     annotate its sequence entropy (ENTROPY) and DUST score \
+                     (DUST). [default: 64] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--low-complexity-filter").help(
+            "Give variants whose reference window has a DUST score of at \
+                     least this value the LowComplexity filter and exclude them \
+                     from ANI, Fst and dN/dS calculations. Low complexity sequence \
+                     is a major source of false strain discriminating variants. \
+                     20 matches the default threshold of sdust. \
+                     [default: not_set] \n",
+        ))
+        .option(Opt::new("INT").long("--coverage-window-size").help(
+            "Size of the windows in which read depth is measured to annotate \
+                     each variant with its copy number ratio (CNR), the depth \
+                     around the variant relative to the median window depth of \
+                     the genome. [default: 1000] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--min-copy-number-ratio").help(
+            "Variants with a copy number ratio below this value, i.e. in a \
+                     putative deletion, are given the CopyNumber filter as their \
+                     allele fractions may not reflect strain frequencies. \
+                     [default: 0.5] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--max-copy-number-ratio").help(
+            "Variants with a copy number ratio above this value, i.e. in a \
+                     putative duplication, are given the CopyNumber filter. \
+                     [default: 1.5] \n",
+        ))
+        .flag(Flag::new().long("--write-features").help(
+            "Also write <genome>_features.tsv next to each VCF, with one \
+                     row per ALT allele of every record, filtered records \
+                     included, giving QUAL, QD, DP, MQ, BQ, strand counts and \
+                     strand odds ratio, repeat and sequence complexity, and \
+                     per sample depths, for training custom filtering models. \
+                     Models can be applied with lorikeet apply-model. \
+                     [default: not set] \n",
         ))
         .option(Opt::new("INT").long("--qual-by-depth-filter").help(
             "The minimum QD value for a variant to have for it to be \
@@ -451,6 +717,23 @@ fn variant_calling_section_basic() -> Section {
                      sample to be included in ANI & Fst calculations for that \
                      variant. [default: 5] \n",
         ))
+        .flag(Flag::new().long("--hard-filter").help(
+            "Remove variants failing the --qual-by-depth-filter, \
+                     --depth-per-sample-filter or \
+                     --min-variant-depth-for-genotyping thresholds from the \
+                     VCF. By default they are kept and given the LowQD or \
+                     LowDepth filter. \n",
+        ))
+        .option(Opt::new("NAMES ..").long("--replicates").help(
+            "Groups of samples that are technical replicates of each other, \
+                     each given as comma separated sample names e.g. \
+                     --replicates sampleA_1,sampleA_2 sampleB_1,sampleB_2. \
+                     Variants called in one replicate but not another are used \
+                     to estimate the false positive rate of the run and to \
+                     calibrate variant QUALs, keeping the original QUAL in \
+                     OQUAL. The calibration is written to \
+                     <genome>_replicate_calibration.tsv. [default: not_set] \n",
+        ))
         .option(Opt::new("INT").long("--min-long-read-size").help(
             "The minimum size for long reads to be used for analysis \
                     [default: 1500] \n",
@@ -488,17 +771,99 @@ fn variant_calling_section_basic() -> Section {
                     mostly represent noise. Call variants on them can often \
                     be slow and not produce anything fruitful. [default: 0] \n",
         ))
+        .option(Opt::new("STR").long("--sv-caller").help(
+            "The program used to call structural variants from \
+                     longreads: svim, sniffles2 or cutesv. The calls of \
+                     every longread sample are merged into \
+                     structural_variants.vcf.gz and used as feature \
+                     variants to guide the assembly of the regions they \
+                     fall in. [default: svim] \n",
+        ))
         .option(Opt::new("INT").long("--min-sv-qual").help(
-            "Minimum structural variants quality returned by svim \
-                     and used by lorikeet. Not PHRED-scaled quality, value \
-                     determined by number of supporting reads. Consult \
-                     svim documentation for details. [default: 3] \n",
+            "Minimum structural variants quality returned by the \
+                     structural variant caller and used by lorikeet. For svim \
+                     this is not PHRED-scaled quality, value determined by \
+                     number of supporting reads. Consult the documentation \
+                     of the caller for details. [default: 3] \n",
         ))
         .flag(Flag::new().long("--do-not-call-svs").help(
-            "Opts not to use svim to call structural variants \
+            "Opts not to call structural variants \
                      using provided longreads. If no longreads are provided \
                      this has no effect. \n",
         ))
+        .flag(Flag::new().long("--short-read-sv-evidence").help(
+            "Estimate the fragment length distribution of each short read \
+                     sample and report deletions and tandem duplications \
+                     supported by discordant read pairs, split reads whose \
+                     alignments are chained through their SA tags, or read \
+                     depth change points in short_read_structural_variants.vcf. \
+                     Allows basic structural variant detection without \
+                     longreads. \n",
+        ))
+        .option(Opt::new("INT").long("--min-discordant-pairs").help(
+            "Minimum number of discordant read pairs or split reads, \
+                     pooled across samples, required to report a structural variant \
+                     when using --short-read-sv-evidence. [default: 3] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--discordant-insert-size-stdevs").help(
+            "Number of robust standard deviations above the median \
+                     insert size at which a read pair is considered \
+                     discordant. [default: 4.0] \n",
+        ))
+        .flag(Flag::new().long("--soft-clip-rescue").help(
+            "Collect soft clipped read ends before assembly and build a \
+                     consensus of the clipped sequence at shared breakpoints. \
+                     Insertions resolved from these clips are added to the \
+                     assembly as candidate haplotypes, recovering insertions \
+                     too long for reads to align across. \n",
+        ))
+        .option(Opt::new("INT").long("--min-rescue-clip-length").help(
+            "Minimum number of high quality soft clipped bases for a \
+                     read to count towards a breakpoint when using \
+                     --soft-clip-rescue. [default: 10] \n",
+        ))
+        .option(Opt::new("INT").long("--min-rescue-clipped-reads").help(
+            "Minimum number of soft clipped reads, pooled across samples, \
+                     supporting a breakpoint before an insertion is rescued \
+                     from it. [default: 3] \n",
+        ))
+        .flag(Flag::new().long("--soft-clip-profile").help(
+            "Write the rate and length distribution of soft clipped \
+                     read ends of each sample before assembly to \
+                     <genome>_soft_clipping.tsv. \n",
+        ))
+        .option(Opt::new("FLOAT").long("--end-trim-mismatch-rate").help(
+            "Trim read ends whose rate of mismatches and insertions \
+                     against the reference exceeds this rate before assembly, \
+                     removing adapters and chimeric tails of long reads \
+                     without discarding the reads. Each end is trimmed by at \
+                     most half of the read. Trimmed bases are reported in \
+                     <genome>_soft_clipping.tsv. [default: off] \n",
+        ))
+        .option(Opt::new("INT").long("--end-trim-min-length").help(
+            "Minimum length of a read end trimmed when using \
+                     --end-trim-mismatch-rate. [default: 10] \n",
+        ))
+        .flag(Flag::new().long("--minimizer-prefilter").help(
+            "Sketch the reference window of each active region with \
+                     minimizers and discard reads sharing too few minimizers \
+                     with it before assembly. Speeds up assembly of very deep \
+                     long read data. The number of discarded reads is reported \
+                     for each genome. \n",
+        ))
+        .option(Opt::new("INT").long("--minimizer-kmer-size").help(
+            "Kmer size of the minimizers used by --minimizer-prefilter. \
+                     Maximum of 32. [default: 15] \n",
+        ))
+        .option(Opt::new("INT").long("--minimizer-window-size").help(
+            "Number of consecutive kmers each minimizer is chosen from \
+                     when using --minimizer-prefilter. [default: 10] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--min-shared-minimizer-fraction").help(
+            "Minimum fraction of a read's minimizers that must be found \
+                     in the reference window of the region for the read to be \
+                     kept by --minimizer-prefilter. [default: 0.05] \n",
+        ))
 }
 
 fn variant_calling_options_advanced() -> Section {
@@ -516,6 +881,39 @@ fn variant_calling_options_advanced() -> Section {
                     [default: 10] \n",
                 ),
         )
+        .option(
+            Opt::new("INT")
+                .long("--pair-hmm-batch-size")
+                .help(
+                    "Number of read and haplotype pairs computed together by the \
+                    AVX Pair HMM. The reads of every sample in an active region are \
+                    queued together and split into batches of this size which are \
+                    processed in parallel. Set to 0 to compute each sample in turn. \
+                    [default: 4096] \n",
+                ),
+        )
+        .flag(Flag::new().long("--pack-read-bases").help(
+            "Pack the bases of the reads waiting to be evaluated by the Pair HMM \
+            into two bits per base, unpacking each read as it is evaluated. \
+            Reduces memory use in deep regions at a small cost in speed. \n",
+        ))
+        .flag(Flag::new().long("--bin-base-qualities").help(
+            "Bin the base qualities used by the Pair HMM into the 8 levels used \
+            by Illumina sequencers. \n",
+        ))
+        .option(
+            Opt::new("STR")
+                .long("--pairhmm-backend")
+                .help(
+                    "Implementation used to compute the Pair HMM likelihoods, one of \
+                    auto, avx, or gpu. The gpu backend uses OpenCL and requires lorikeet \
+                    to be built with the gpu feature. auto uses the GPU when one is \
+                    available. If the GPU cannot be initialized or fails on a batch, \
+                    the AVX Pair HMM is used instead. auto uses the scalar Pair HMM \
+                    with --disable-avx, and gpu can not be combined with it. \
+                    [default: auto] \n",
+                ),
+        )
         .option(
             Opt::new("STR")
                 .long("--pcr-indel-model")
@@ -534,6 +932,29 @@ fn variant_calling_options_advanced() -> Section {
                 .long("--indel-heterozygosity")
                 .help("Heterozygosity for indel calling. [default: 0.000125] \n"),
         )
+        .option(
+            Opt::new("FILE")
+                .long("--heterozygosity-priors")
+                .help(
+                    "Tab separated table of per genome heterozygosity priors with the \
+                    columns genome, SNP heterozygosity, and indel heterozygosity. Genomes \
+                    in the table use these priors in place of --snp-heterozygosity and \
+                    --indel-heterozygosity, e.g. to give viral and bacterial genomes \
+                    different expected diversity. [default: not set] \n",
+                ),
+        )
+        .option(
+            Opt::new("FILE")
+                .long("--per-genome-config")
+                .help(
+                    "TOML file with a table of parameter overrides for each listed genome, \
+                    named by the genome or its fasta file. The supported keys are kmer-sizes, \
+                    ploidy, active-probability-threshold, min-assembly-region-size, \
+                    max-assembly-region-size and depth-per-sample-filter, which replace the \
+                    command line values for that genome only, e.g. \
+                    [genome_1] kmer-sizes = [17, 25] ploidy = 2. [default: not set] \n",
+                ),
+        )
         .option(
             Opt::new("FLOAT")
                 .long("--standard-min-confidence-threshold-for-calling")
@@ -613,6 +1034,30 @@ fn variant_calling_options_advanced() -> Section {
             For faster performance, set this to 2 or 3 at a cost to recall. \
                      [default: 1] \n",
         ))
+        .flag(Flag::new().long("--hybrid-assembly").help(
+            "Build assembly graphs in a technology aware manner. Short \
+                     reads create the kmers and edge weights of the graph, \
+                     while long reads are threaded afterwards and can only \
+                     connect kmers already present in the graph. Helps \
+                     resolve haplotypes in low complexity regions without \
+                     long read errors adding spurious branches. Only has an \
+                     effect when both short and long reads are provided. \n",
+        ))
+        .flag(Flag::new().long("--base-quality-recalibration").help(
+            "Recalibrate base qualities before calculating read likelihoods. \
+                     An error model of reported quality, sequencing cycle and \
+                     dinucleotide context is learned for each sample from \
+                     sites that appear invariant, and the recalibrated qualities \
+                     are used in the PairHMM. Helps reduce false positives \
+                     caused by overconfident base qualities, e.g. nanopore reads. \n",
+        ))
+        .option(Opt::new("STR").long("--read-group-weights").help(
+            "Comma separated weights between 0 and 1 applied to the read likelihoods of each \
+            sequencing platform, e.g. ILLUMINA=1.0,ONT=0.5,PACBIO=0.8. The platform of a \
+            read is taken from the PL field of its @RG header line. Reads without one are \
+            treated as ILLUMINA if they come from a short read BAM and ONT otherwise. Stops \
+            abundant long reads from outweighing short read evidence in mixed samples. \n",
+        ))
         .flag(Flag::new().long("--use-adaptive-pruning").help(
             "Use more advanced pruning algorithm to prune paths in \
                      graph. Better suited when performing variant calling \
@@ -628,6 +1073,17 @@ fn variant_calling_options_advanced() -> Section {
                 .long("--graph-output")
                 .help("Write debug assembly graph information to this file. \n"),
         )
+        .option(Opt::new("DIRECTORY").long("--dump-assembly-graphs").help(
+            "Write the cleaned read threading graph and sequence graph of each \
+            assembled region and kmer size to this directory in GFA format, with \
+            edge multiplicities and the reference path recorded as tags. The files \
+            can be summarised with lorikeet graph-inspect. [default: not set] \n",
+        ))
+        .option(Opt::new("STR ..").long("--dump-graph-intervals").help(
+            "Only dump the graphs of regions overlapping these spans, given in the \
+            same start-end format as --limiting-interval and applied to every contig. \
+            [default: all regions] \n",
+        ))
         .flag(
             Flag::new()
                 .long("--dont-use-soft-clipped-bases")
@@ -650,6 +1106,36 @@ fn variant_calling_options_advanced() -> Section {
             "Maximum number of variants in graph the \
                      adaptive pruner will allow. [default: 100] \n",
         ))
+        .option(Opt::new("STR").long("--chain-pruner").help(
+            "Chain pruner used to remove likely sequencing errors \
+                     from assembly graphs, overriding --use-adaptive-pruning. \
+                     Options are adaptive, low-weight and sample-support, \
+                     plus coverage-normalized when built with the \
+                     experimental-pruners feature. [default: not set] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--pruning-coverage-fraction").help(
+            "Fraction of the median reference edge multiplicity below \
+                     which the coverage-normalized pruner removes chains. \
+                     [default: 0.05] \n",
+        ))
+        .option(Opt::new("INT").long("--pruning-min-sample-reads").help(
+            "Reads a sample must contribute to an edge for the \
+                     sample-support pruner to count the sample as supporting \
+                     it. [default: 2] \n",
+        ))
+        .option(Opt::new("INT").long("--pruning-min-samples").help(
+            "The sample-support pruner removes chains without an edge \
+                     supported by --pruning-min-sample-reads reads in at least \
+                     this many samples, so variation private to low depth \
+                     samples is not drowned out by a single deep sample. \
+                     [default: 1] \n",
+        ))
+        .option(Opt::new("PATH").long("--export-chain-features").help(
+            "Write the features of every chain of each read threading \
+                     graph, and whether the chain pruner removed it, to this \
+                     TSV file for training new chain pruners. \
+                     [default: not set] \n",
+        ))
         .option(
             Opt::new("INT")
                 .long("--max-prob-propagation-distance")
@@ -663,26 +1149,145 @@ fn variant_calling_options_advanced() -> Section {
             "Two or more phased substitutions separated by \
                      this distance or less are merged into MNPs. [default: 0] \n",
         ))
+        .option(Opt::new("INT").long("--max-alternate-alleles").help(
+            "Maximum number of alternate alleles genotyped at a single site. \
+            Sites with more alleles keep those on the highest scoring haplotypes, \
+            breaking ties by the read support of each allele within a single sample \
+            and then across all samples. Dropped alleles are written to \
+            <genome>_dropped_alleles.tsv in the output directory. [default: 180] \n",
+        ))
+        .flag(Flag::new().long("--emit-complex-events").help(
+            "Report phased substitutions and indels on the same haplotype that are separated \
+            by --max-mnp-distance or less as a single complex allele instead of decomposing \
+            them into separate records. Reads supporting the haplotype are then counted once \
+            towards the complex allele. Requires --max-mnp-distance greater than 0. \n",
+        ))
+        .option(Opt::new("INT").long("--sv-info-min-indel-length").help(
+            "Insertions and deletions that change the length of the reference by at least \
+            this many bases are written with END, SVLEN, and SVTYPE INFO fields. Records with \
+            symbolic alleles, e.g. <DEL>, always have these fields. Set to 0 to only describe \
+            symbolic records. [default: 50] \n",
+        ))
+        .option(Opt::new("MODEL").long("--haplotype-scoring-model").help(
+            "How assembled haplotypes are ranked when a site has too many alleles to \
+            genotype and alleles carried only by low ranking haplotypes are dropped. \
+            path-weight uses the score of the haplotype's path through the assembly graph, \
+            read-support the number of reads supporting the haplotype, and sample-likelihood \
+            the sum over samples of the best per read likelihood margin of the haplotype. \
+            The latter two help keep real alleles of low abundance strains at multi-allelic \
+            sites. [default: path-weight] \n",
+        ))
         .flag(
             Flag::new()
                 .long("--disable-optimizations")
                 .help("Don't skip calculations in ActiveRegions with no variants \n"),
         )
+        .flag(Flag::new().long("--disable-assembly-fallback").help(
+            "Emit nothing for active regions whose local assembly fails, e.g. because \
+            every graph has cycles or is of low complexity. By default SNVs in such \
+            regions are called from a pileup of their reads and tagged with the \
+            ASSEMBLY_FALLBACK INFO flag, with allelic depths but missing genotypes. \n",
+        ))
         .flag(Flag::new().long("--disable-avx").help(
             "Disable the use of the GKL-rs AVX acceleration components \
                      for PairHMM and Smith-Waterman calculations. \n",
         ))
+        .option(Opt::new("STR").long("--sw-preset").help(
+            "Smith-Waterman parameters used to recover dangling ends and to align \
+            haplotypes to the reference. 'short-read' uses the GATK parameters, \
+            'long-read' opens gaps more cheaply to suit the indels of long reads and \
+            of indel rich genomes. [default: short-read] \n",
+        ))
+        .option(Opt::new("INT").long("--sw-match").help(
+            "Smith-Waterman match score, replacing that of --sw-preset for both \
+            alignments. Must be >= 0 [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--sw-mismatch").help(
+            "Smith-Waterman mismatch penalty, replacing that of --sw-preset for both \
+            alignments. Must be <= 0 [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--sw-gap-open").help(
+            "Smith-Waterman gap open penalty, replacing that of --sw-preset for both \
+            alignments. Must be <= 0 [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--sw-gap-extend").help(
+            "Smith-Waterman gap extend penalty, replacing that of --sw-preset for both \
+            alignments. Must be <= 0 [default: not set] \n",
+        ))
         .option(Opt::new("STR").long("--limiting-interval").help(
             "Mainly used for debugging purposes. Only call variants \
                      within this given span on all contigs. E.g. providing \
                      '1000-2000' would only call variants between the 1000 \
                      and 2000 bp span on each provided contig. \n",
         ))
+        .option(Opt::new("FLOAT").long("--subsample-fraction").help(
+            "Only assemble this fraction of the active regions of each genome, \
+            chosen at random, to quickly produce an approximate call set \
+            e.g. when exploring thresholds. The same regions are chosen by runs \
+            with the same --seed. [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--subsample-regions").help(
+            "Only profile and call this many randomly chosen windows of each \
+            genome. Windows are the chunks of contig the activity profile is \
+            computed in, so the variants within each window are called as in a \
+            full run. Can be combined with --subsample-fraction. \
+            [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--seed").help(
+            "Seed for every random number generator used by the run, e.g. \
+            for QD jittering and strain clustering. Runs with the same seed \
+            give the same results. If not set a random seed is drawn, logged \
+            and recorded in the VCF header. [default: not set] \n",
+        ))
+        .option(Opt::new("STR").long("--normalize-vcf").help(
+            "Normalise records against the reference before they are \
+            written to the VCF. 'left-align' shifts indels to their leftmost \
+            position and trims the alleles to a single shared padding base, \
+            as 'bcftools norm' does. 'atomize' also splits biallelic MNPs \
+            into one SNP per differing base. 'none' writes records as called. \
+            [default: none] \n",
+        ))
         .flag(
             Flag::new()
                 .long("--force")
                 .help("Forcefully overwrite previous runs. \n"),
         )
+        .flag(Flag::new().long("--dry-run").help(
+            "Validate the reference genomes, BAM files, read files and \
+            external tools required by this run, print the stages and \
+            outputs planned for each reference genome, then exit without \
+            doing any of the work. \n",
+        ))
+        .option(Opt::new("FILE").long("--config").help(
+            "TOML file of options for this run, keyed by their long names, \
+            e.g. threads = 16 and kmer-sizes = [17, 25]. Flags are set with \
+            true. Options given on the command line override the file. The \
+            options of every run are written to lorikeet_config.toml in the \
+            output directory so the run can be repeated. [default: not set] \n",
+        ))
+        .option(Opt::new("PATH").long("--tmp-dir").help(
+            "Directory for temporary files, such as cached BAM files, the \
+            concatenated genomes and mapping indices, instead of the system \
+            default, e.g. a scratch disk. Also used by the external programs \
+            lorikeet runs. Temporary files are removed when the run finishes, \
+            panics or is interrupted. [default: $TMPDIR or /tmp] \n",
+        ))
+        .flag(Flag::new().long("--keep-temp").help(
+            "Keep temporary files after the run for debugging and log where \
+            they are. \n",
+        ))
+        .option(Opt::new("STR").long("--log-format").help(
+            "Format of log messages. 'text' prints human readable log \
+            messages and progress bars. 'json' instead emits one JSON \
+            object per line for each log message and for the start and end \
+            of each processing stage of a genome, suitable for cluster logs. \
+            Progress bars are not shown when using 'json' or when stderr is \
+            not a terminal. [default: text] \n",
+        ))
+        .option(Opt::new("FILE").long("--log-file").help(
+            "Write JSON log events to this file instead of stderr. \
+            Only used with --log-format json. \n",
+        ))
 }
 
 // fn add_verbosity_flags_to_section(section: Section) -> Section {
@@ -699,6 +1304,101 @@ fn variant_calling_options_advanced() -> Section {
 //         ))
 // }
 
+fn strain_genotyping_options() -> Section {
+    Section::new("Strain genotyping options").option(
+        Opt::new("FLOAT").long("--min-strain-divergence").help(
+            "Potential strains whose reconstructed genomes differ by \
+            less than this fraction of the genome are merged into a single \
+            strain before abundances are calculated. E.g. 0.0001 merges \
+            strains that share more than 99.99% ANI. [default: 0.0, disabled] \n",
+        ),
+    )
+    .option(
+        Opt::new("INT").long("--min-linked-reads").help(
+            "Potential strains connected by at least this many reads spanning \
+            a variant in each strain are merged into a single strain. \
+            The number of such reads supporting each variant is reported in \
+            the LINKED_READS INFO field. [default: 0, disabled] \n",
+        ),
+    )
+    .flag(
+        Flag::new().long("--export-linkage-graph").help(
+            "Write the read linkage graph of each genome, with a node for each \
+            variant and an edge weighted by the number of reads shared between two \
+            variants. Written as <genome>_linkage_nodes.tsv and \
+            <genome>_linkage_edges.tsv, for import into Cytoscape or Gephi, and as \
+            <genome>_linkage_graph.graphml. [default: not set] \n",
+        ),
+    )
+    .flag(
+        Flag::new().long("--bin-long-reads-by-strain").help(
+            "Write the long reads of each sample assigned to each strain to \
+            <genome>_strain_<strain>_<sample>.bam, for reassembly or polishing of \
+            each strain. Reads are assigned using the likelihood of the alleles they \
+            carry at strain variants, with the strain frequencies of the sample as \
+            prior. [default: not set] \n",
+        ),
+    )
+    .option(
+        Opt::new("FLOAT").long("--min-strain-read-posterior").help(
+            "Minimum posterior probability of a strain for a long read to be \
+            written to the BAM file of that strain by --bin-long-reads-by-strain. \
+            [default: 0.9] \n",
+        ),
+    )
+    .flag(
+        Flag::new().long("--polish-strains").help(
+            "Polish each strain genome with the long reads binned to it by \
+            --bin-long-reads-by-strain, replacing each window of the genome with the \
+            consensus of a partial order alignment of the window and the reads \
+            covering it. Written to <genome>_strain_<strain>_polished.fna, with the \
+            number of corrections made to each strain in \
+            <genome>_strain_polishing.tsv. [default: not set] \n",
+        ),
+    )
+    .option(
+        Opt::new("INT").long("--polish-window-length").help(
+            "Length of the windows strain genomes are polished in by \
+            --polish-strains. [default: 500] \n",
+        ),
+    )
+    .option(
+        Opt::new("INT").long("--polish-min-coverage").help(
+            "Minimum number of reads covering a window for it to be polished by \
+            --polish-strains. [default: 3] \n",
+        ),
+    )
+}
+
+fn consensus_options() -> Section {
+    Section::new("Consensus options")
+        .option(Opt::new("STR").long("--consensus-ambiguity").help(
+            "How to represent positions with uncertain allele support in the \
+            consensus genomes. 'none' always uses the allele with the highest depth, \
+            'iupac' uses IUPAC ambiguity codes at uncertain SNP positions and N at \
+            other uncertain positions, and 'n' uses N at all uncertain positions. \
+            Masked positions are written to a BED file alongside each consensus genome. \
+            [default: none] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--consensus-min-allele-fraction").help(
+            "Positions where the most supported allele makes up less than this \
+            fraction of the depth are considered uncertain. \
+            Only used with --consensus-ambiguity. [default: 0.75] \n",
+        ))
+        .option(Opt::new("INT").long("--consensus-min-depth").help(
+            "Positions with less depth than this are masked with N, including \
+            positions without a variant or without any reads. \
+            Only used with --consensus-ambiguity. [default: 3] \n",
+        ))
+        .option(Opt::new("STR").long("--consensus-samples").help(
+            "Only generate consensus genomes for these samples. Samples are named \
+            by their read or BAM file names. A consensus genome is written for each \
+            selected sample along with a majority consensus built from the summed \
+            allele depths of all selected samples, which is written to \
+            <genome>_majority_consensus.fna. [default: all samples] \n",
+        ))
+}
+
 pub fn genotype_full_help() -> Manual {
     let mut manual = Manual::new("lorikeet genotype")
         .about(
@@ -729,6 +1429,7 @@ pub fn genotype_full_help() -> Manual {
     manual = add_thresholding_options(manual);
     manual = manual.custom(variant_calling_section_basic());
     manual = manual.custom(variant_calling_options_advanced());
+    manual = manual.custom(strain_genotyping_options());
     manual = manual.custom(
         Section::new("Output options")
             .option(
@@ -740,6 +1441,26 @@ pub fn genotype_full_help() -> Manual {
                 [default: ./]",
                     ),
             )
+            .option(Opt::new("TEMPLATE").long("--output-template").help(
+                "Layout of the output directory of each genome, relative to \
+                --output-directory. May contain the {genome}, {mode} and \
+                {sample} placeholders and must contain {genome}. {sample} is \
+                the sample name when a single sample is given, otherwise \
+                all_samples. A manifest of every output and its type is \
+                written to lorikeet_manifest.tsv in the output directory. \
+                [default: {genome}] \n",
+            ))
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--reference-cache-directory")
+                    .help(
+                        "Keep the concatenated reference genomes, their .fai and the \
+                mapper indexes built from them in this directory, keyed by the checksums of \
+                the genome files. Later runs on the same genomes reuse them instead of \
+                concatenating and indexing the genomes again. The directory may or may not \
+                exist. [default: not used] \n",
+                    ),
+            )
             .option(
                 Opt::new("DIRECTORY")
                     .long("--bam-file-cache-directory")
@@ -756,6 +1477,18 @@ pub fn genotype_full_help() -> Manual {
                 Flag::new()
                     .long("--keep-unmapped")
                     .help("Include unmapped reads from cached BAM files. [default: not set] \n"),
+            )
+            .option(
+                Opt::new("FORMAT")
+                    .long("--abundance-formats")
+                    .help(
+                        "Also write the relative strain abundances in these formats. Either or \
+                both of: \n\
+                - cami: one CAMI profile per sample across every genome, \
+                <sample>_strain_abundances.profile in the output directory \n\
+                - biom: BIOM classic TSV table, <genome>_strain_abundances.biom.tsv \n\
+                [default: not set] \n",
+                    ),
             ),
     );
 
@@ -807,6 +1540,7 @@ pub fn call_full_help() -> Manual {
     manual = manual.custom(reference_options());
     manual = manual.custom(read_mapping_params_section());
     manual = manual.custom(sharding_section());
+    manual = manual.custom(scatter_section());
     manual = add_mapping_options(manual);
     manual = add_thresholding_options(manual);
     manual = manual.custom(variant_calling_section_basic());
@@ -822,6 +1556,26 @@ pub fn call_full_help() -> Manual {
                 [default: ./]",
                     ),
             )
+            .option(Opt::new("TEMPLATE").long("--output-template").help(
+                "Layout of the output directory of each genome, relative to \
+                --output-directory. May contain the {genome}, {mode} and \
+                {sample} placeholders and must contain {genome}. {sample} is \
+                the sample name when a single sample is given, otherwise \
+                all_samples. A manifest of every output and its type is \
+                written to lorikeet_manifest.tsv in the output directory. \
+                [default: {genome}] \n",
+            ))
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--reference-cache-directory")
+                    .help(
+                        "Keep the concatenated reference genomes, their .fai and the \
+                mapper indexes built from them in this directory, keyed by the checksums of \
+                the genome files. Later runs on the same genomes reuse them instead of \
+                concatenating and indexing the genomes again. The directory may or may not \
+                exist. [default: not used] \n",
+                    ),
+            )
             .option(
                 Opt::new("DIRECTORY")
                     .long("--bam-file-cache-directory")
@@ -838,6 +1592,32 @@ pub fn call_full_help() -> Manual {
                 Flag::new()
                     .long("--keep-unmapped")
                     .help("Include unmapped reads from cached BAM files. [default: not set]"),
+            )
+            .flag(Flag::new().long("--haplotype-vcf").help(
+                "Emit one record per assembled haplotype in each active region \
+                instead of site level records. REF is the reference haplotype, \
+                ALT the assembled haplotype and AD the reads best supporting each \
+                of them, so local haplotypes can be compared between samples \
+                without re-phasing. ANI, Fst and dN/dS are not calculated. \
+                [default: not set] \n",
+            ))
+            .flag(Flag::new().long("--emit-haplotype-records").help(
+                "Add a symbolic <HAP> record for each assembled non-reference \
+                haplotype alongside the site level records, even when genotyping \
+                filtered or dropped the variants it is made of. Records span the \
+                active region to END, carry the haplotype sequence in HAPSEQ and \
+                the reads best supporting the reference and the haplotype in AD. \
+                They are not used for ANI, Fst, dN/dS or strain abundances. \
+                [default: not set]",
+            ))
+            .option(
+                Opt::new("FLOAT")
+                    .long("--haplotype-record-min-qual")
+                    .help(
+                        "Minimum QUAL, the phred scaled evidence summed over samples, \
+                        of a haplotype written with --emit-haplotype-records. \
+                        [default: 30.0]",
+                    ),
             ),
     );
 
@@ -896,6 +1676,7 @@ pub fn consensus_full_help() -> Manual {
     manual = add_thresholding_options(manual);
     manual = manual.custom(variant_calling_section_basic());
     manual = manual.custom(variant_calling_options_advanced());
+    manual = manual.custom(consensus_options());
     manual = manual.custom(
         Section::new("Output options")
             .option(
@@ -907,6 +1688,26 @@ pub fn consensus_full_help() -> Manual {
                 [default: ./] \n",
                     ),
             )
+            .option(Opt::new("TEMPLATE").long("--output-template").help(
+                "Layout of the output directory of each genome, relative to \
+                --output-directory. May contain the {genome}, {mode} and \
+                {sample} placeholders and must contain {genome}. {sample} is \
+                the sample name when a single sample is given, otherwise \
+                all_samples. A manifest of every output and its type is \
+                written to lorikeet_manifest.tsv in the output directory. \
+                [default: {genome}] \n",
+            ))
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--reference-cache-directory")
+                    .help(
+                        "Keep the concatenated reference genomes, their .fai and the \
+                mapper indexes built from them in this directory, keyed by the checksums of \
+                the genome files. Later runs on the same genomes reuse them instead of \
+                concatenating and indexing the genomes again. The directory may or may not \
+                exist. [default: not used] \n",
+                    ),
+            )
             .option(
                 Opt::new("DIRECTORY")
                     .long("--bam-file-cache-directory")
@@ -952,67 +1753,696 @@ pub fn consensus_full_help() -> Manual {
     return manual;
 }
 
-pub fn summarise_full_help() -> Manual {
-    let mut manual = Manual::new("lorikeet summarise")
+pub fn all_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet all")
         .about(
             &format!(
-                "Calculate ANI and Fst metrics on a given set of VCF files (version {})",
+                "Call variants, genotype strains and generate consensus genomes in a single pass (version {})",
                 crate_version!()
             )
         )
         .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
         .description(
-            "lorikeet summarise uses a set of VCF files as input and calculates conANI, popANI, \
-            subpopANI, and Fst metrics for the variants in each file. \
+            "lorikeet all maps reads, finds active regions and assembles and genotypes them once \
+            for each genome, then produces the outputs of lorikeet call, lorikeet genotype and \
+            lorikeet consensus from the same variants. This avoids repeating the read mapping and \
+            variant calling that running each subcommand separately would require. \
             \n\
-            ANI metrics require coverage information to determine the number of shared bases \
-            in each sample. VCF files do not provide this information, so the shared base size is just \
-            the total size of the genome. In our experience, this doesn't really matter that much as \
-            the ANI metrics are quite insensitive when provided low coverage samples anyway. \
-            Fst tends to perform better for low and high coverage samples and does not require whole \
-            genome coverage information."
+            The outputs of each subcommand are written to call/, genotype/ and consensus/ \
+            subdirectories of the output directory of each genome."
         );
 
-    manual = manual
-        .option(
-            Opt::new("PATH ..")
-                .short("-i")
-                .long("--vcfs")
-                .help("Paths to input VCF files. Can provide one or more. \n"),
-        )
-        .option(
-            Opt::new("DIRECTORY")
-                .short("-i")
-                .long("--vcfs")
-                .help("Paths to input VCF files. Can provide one or more. \n"),
-        )
-        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
-            "Output directory. Folder will contain subfolders for each input VCF \
-             [default: ./] \n",
-        ))
-        .option(
-            Opt::new("INT")
-                .long("--threads")
-                .short("-t")
-                .help("Maximum number of threads used. [default: 8] \n"),
-        )
-        .option(Opt::new("INT").long("--qual-by-depth-filter").help(
-            "The minimum QD value for a variant to have for it to be \
-                     included in the genotyping or ANI analyses. [default: 25] \n",
-        ))
-        .option(Opt::new("INT").long("--qual-threshold").help(
-            "The PHRED-scaled quality score threshold for use \
-                     with ANI calculations. [default: 150] \n",
-        ))
-        .option(Opt::new("INT").long("--depth-per-sample-filter").help(
-            "Minimum depth of a variant in a sample for that \
-                     sample to be included in ANI & Fst calculations for that \
-                     variant. [default: 5] \n",
-        ));
+    manual = manual.custom(threads_options());
+    manual = manual.custom(reference_options());
+    manual = manual.custom(read_mapping_params_section());
+    manual = manual.custom(sharding_section());
+    manual = add_mapping_options(manual);
+    manual = add_thresholding_options(manual);
+    manual = manual.custom(variant_calling_section_basic());
+    manual = manual.custom(variant_calling_options_advanced());
+    manual = manual.custom(strain_genotyping_options());
+    manual = manual.custom(consensus_options());
+    manual = manual.custom(
+        Section::new("Output options")
+            .option(
+                Opt::new("DIRECTORY")
+                    .short("-o")
+                    .long("--output-directory")
+                    .help(
+                        "Output directory. Folder will contain subfolders for each input genome \
+                [default: ./]",
+                    ),
+            )
+            .option(Opt::new("OUTPUT").long("--outputs").help(
+                "The outputs to produce from the called variants. Any of: \n\
+                - call: the VCF, ANI and marker outputs of lorikeet call \n\
+                - genotype: the strain VCF, abundances and genomes of lorikeet genotype \n\
+                - consensus: the VCF and consensus genomes of lorikeet consensus \n\
+                [default: call genotype consensus] \n",
+            ))
+            .option(Opt::new("TEMPLATE").long("--output-template").help(
+                "Layout of the output directory of each genome, relative to \
+                --output-directory. May contain the {genome}, {mode} and \
+                {sample} placeholders and must contain {genome}. {mode} is \
+                all, the outputs of each subcommand are written to subdirectories. \
+                A manifest of every output and its type is \
+                written to lorikeet_manifest.tsv in the output directory. \
+                [default: {genome}] \n",
+            ))
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--reference-cache-directory")
+                    .help(
+                        "Keep the concatenated reference genomes, their .fai and the \
+                mapper indexes built from them in this directory, keyed by the checksums of \
+                the genome files. Later runs on the same genomes reuse them instead of \
+                concatenating and indexing the genomes again. The directory may or may not \
+                exist. [default: not used] \n",
+                    ),
+            )
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--bam-file-cache-directory")
+                    .help(
+                        "Output BAM files generated during \
+                alignment to this directory. The directory may or may not exist. \
+                [default: not used] \n",
+                    ),
+            )
+            .flag(
+                Flag::new()
+                    .long("--keep-unmapped")
+                    .help("Include unmapped reads from cached BAM files. [default: not set] \n"),
+            )
+            .option(
+                Opt::new("FORMAT")
+                    .long("--abundance-formats")
+                    .help(
+                        "Also write the relative strain abundances in these formats. Either or \
+                both of: cami (one profile per sample), biom (one table per genome). \
+                [default: not set] \n",
+                    ),
+            ),
+    );
 
-    manual = add_verbosity_flags(manual);
-    return manual;
-}
+    manual = manual.example(
+        Example::new()
+            .text("Map paired reads to a reference and produce every output")
+            .command(
+                "lorikeet all --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10",
+            ),
+    );
+    manual = manual.example(
+        Example::new()
+            .text("Only produce the call and consensus outputs from a sorted BAM file")
+            .command(
+                "lorikeet all --bam-files my.bam --genome-fasta-directory genomes/ -x fna \
+                --output-directory lorikeet_out/ --threads 10 --outputs call consensus",
+            ),
+    );
+
+    manual = add_verbosity_flags(manual);
+
+    manual = manual.custom(faq_section());
+
+    return manual;
+}
+
+pub fn ani_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet ani")
+        .about(
+            &format!(
+                "Calculate ANI between samples from pileup base counts, without assembly (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet ani maps reads and counts the bases of every sample at every position of \
+            each genome, skipping active region detection, local reassembly and the PairHMM. \
+            Positions at which any sample carries a non-reference base become SNV sites, from \
+            which conANI, popANI and subpopANI are calculated as in lorikeet call. This is an \
+            order of magnitude faster than lorikeet call when only the ANI tables are needed. \
+            \n\
+            Indels are not counted, so ANI values can be slightly higher than those of \
+            lorikeet call. No VCF is written."
+        );
+
+    manual = manual.custom(threads_options());
+    manual = manual.custom(reference_options());
+    manual = manual.custom(read_mapping_params_section());
+    manual = add_mapping_options(manual);
+    manual = add_thresholding_options(manual);
+    manual = manual.custom(
+        Section::new("Pileup options")
+            .option(Opt::new("FLOAT").long("--min-snv-fraction").help(
+                "Minimum fraction of the depth of a sample a non-reference base needs \
+                to be counted in that sample. Bases below it are treated as sequencing errors. \
+                The base also needs --depth-per-sample-filter reads. [default: 0.05] \n",
+            )),
+    );
+    manual = manual.custom(
+        Section::new("Output options")
+            .option(
+                Opt::new("DIRECTORY")
+                    .short("-o")
+                    .long("--output-directory")
+                    .help(
+                        "Output directory. Folder will contain subfolders for each input genome \
+                [default: ./]",
+                    ),
+            )
+            .option(
+                Opt::new("DIRECTORY")
+                    .long("--bam-file-cache-directory")
+                    .help(
+                        "Output BAM files generated during \
+                alignment to this directory. The directory may or may not exist. \
+                [default: not used] \n",
+                    ),
+            )
+            .flag(Flag::new().long("--sample-dendrogram").help(
+                "Also write a UPGMA dendrogram of the samples of each genome in \
+                Newick format, <genome>_sample_dendrogram.nwk, built from the SNVs at \
+                which the consensus alleles of each pair of samples differ per compared \
+                base. The SNV counts are always written to <genome>_snv_distances.tsv \n",
+            )),
+    );
+
+    manual = manual.example(
+        Example::new()
+            .text("Map paired reads to a reference and calculate ANI between samples")
+            .command(
+                "lorikeet ani --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10",
+            ),
+    );
+
+    manual = add_verbosity_flags(manual);
+
+    return manual;
+}
+
+pub fn summarise_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet summarise")
+        .about(
+            &format!(
+                "Calculate ANI and Fst metrics on a given set of VCF files (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet summarise uses a set of VCF files as input and calculates conANI, popANI, \
+            subpopANI, and Fst metrics for the variants in each file. \
+            \n\
+            ANI metrics require coverage information to determine the number of shared bases \
+            in each sample. VCF files do not provide this information, so the shared base size is just \
+            the total size of the genome. In our experience, this doesn't really matter that much as \
+            the ANI metrics are quite insensitive when provided low coverage samples anyway. \
+            Fst tends to perform better for low and high coverage samples and does not require whole \
+            genome coverage information."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(
+            Opt::new("DIRECTORY")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory. Folder will contain subfolders for each input VCF \
+             [default: ./] \n",
+        ))
+        .option(
+            Opt::new("INT")
+                .long("--threads")
+                .short("-t")
+                .help("Maximum number of threads used. [default: 8] \n"),
+        )
+        .option(Opt::new("INT").long("--qual-by-depth-filter").help(
+            "The minimum QD value for a variant to have for it to be \
+                     included in the genotyping or ANI analyses. [default: 25] \n",
+        ))
+        .option(Opt::new("INT").long("--qual-threshold").help(
+            "The PHRED-scaled quality score threshold for use \
+                     with ANI calculations. [default: 150] \n",
+        ))
+        .option(Opt::new("INT").long("--depth-per-sample-filter").help(
+            "Minimum depth of a variant in a sample for that \
+                     sample to be included in ANI & Fst calculations for that \
+                     variant. [default: 5] \n",
+        ))
+        .flag(Flag::new().long("--sample-dendrogram").help(
+            "Also write a UPGMA dendrogram of the samples of each genome in \
+            Newick format, <genome>_sample_dendrogram.nwk, built from the SNVs at \
+            which the consensus alleles of each pair of samples differ per compared \
+            base. The SNV counts are always written to <genome>_snv_distances.tsv \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn concordance_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet concordance")
+        .about(
+            &format!(
+                "Calculate pairwise genotype concordance between samples (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet concordance uses a set of VCF files produced by lorikeet as input and \
+            calculates the fraction of variant sites at which each pair of samples shares the same \
+            consensus allele, i.e. the allele with the highest depth. Pairs of samples within a VCF \
+            that share nearly all consensus alleles are flagged as probable duplicates. \
+            \n\
+            If a comparison VCF is provided, each sample is also compared against every sample in \
+            the comparison VCF. Samples with the same name are expected to match, and a sample \
+            that does not match its same named sample but does match another sample is flagged \
+            as a probable sample swap."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(Opt::new("PATH").long("--comparison-vcf").help(
+            "VCF file, such as from a previous run, to compare the samples of each input \
+            VCF against. [default: not set] \n",
+        ))
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory. A concordance table is written for each input VCF \
+             [default: ./] \n",
+        ))
+        .option(Opt::new("INT").long("--depth-per-sample-filter").help(
+            "Minimum depth of a variant in a sample for that \
+                     sample's consensus allele to be compared. [default: 5] \n",
+        ))
+        .option(Opt::new("INT").long("--min-compared-sites").help(
+            "Minimum number of sites called in both samples for a pair \
+                     of samples to be flagged. [default: 20] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--concordance-threshold").help(
+            "Pairs of samples sharing at least this fraction of consensus \
+                     alleles are considered to be the same sample. [default: 0.99] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn phylo_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet phylo")
+        .about(
+            &format!(
+                "Build core SNP alignments and trees from lorikeet VCF files (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet phylo uses a set of VCF files produced by lorikeet as input and writes an \
+            alignment of the SNP sites that differ between samples, or between strains, in FASTA \
+            and relaxed PHYLIP format, along with the position of each alignment column. \
+            \n\
+            In samples mode each sample is represented by its consensus allele, i.e. the allele \
+            with the highest depth, and samples without the depth to make a call are written as N. \
+            In strains mode each strain reported in the ST INFO field of a lorikeet genotype VCF \
+            carries the alternate allele of the sites it is listed at. A neighbor joining tree of \
+            the pairwise SNP distances can also be written in Newick format."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory. An alignment is written for each input VCF \
+             [default: ./] \n",
+        ))
+        .option(Opt::new("MODE").long("--mode").help(
+            "Align the consensus alleles of each sample (samples) or the strains \
+            reported by lorikeet genotype (strains). [default: samples] \n",
+        ))
+        .option(Opt::new("INT").long("--depth-per-sample-filter").help(
+            "Minimum depth of a variant in a sample for that \
+                     sample's consensus allele to be used. [default: 5] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--max-missing").help(
+            "Maximum fraction of samples that can be missing a call at a site \
+            for the site to be included. 0 only includes sites called in every \
+            sample. [default: 0.0] \n",
+        ))
+        .flag(Flag::new().long("--tree").help(
+            "Also write a neighbor joining tree of the pairwise SNP distances \
+            in Newick format. \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn graph_inspect_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet graph-inspect")
+        .about(
+            &format!(
+                "Summarise assembly graphs written by --dump-assembly-graphs (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet graph-inspect loads the GFA files written by lorikeet call, genotype or \
+            consensus with --dump-assembly-graphs and writes a table with one row per graph \
+            giving its region, kmer size, number of vertices and edges, the size of the \
+            reference path, the number of sources and sinks, and the edge multiplicities. \
+            The GFA files themselves can be loaded into graph viewers such as Bandage."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--graphs")
+                .help("GFA files or directories containing them. Can provide one or more. \n"),
+        )
+        .option(Opt::new("PATH").short("-o").long("--output-file").help(
+            "Write the summary table to this file instead of stdout. [default: not set] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn inspect_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet inspect")
+        .about(
+            &format!(
+                "Show the reads and haplotypes supporting the alleles of a variant (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet inspect prints a text pileup of a single variant so that a call can be \
+            checked without loading the BAM files into a genome browser. The alleles are taken \
+            from the record of --vcf overlapping the position, or from the bases the reads carry \
+            at the position when no VCF file is given. The haplotype of each allele is printed \
+            under the reference, followed by the reads of each sample grouped by the allele they \
+            support and aligned to the reference, with matches shown as '.' and ',' on the \
+            forward and reverse strands. A table gives the number of reads supporting each allele \
+            in each sample on either strand. Records written with --haplotype-vcf span a whole \
+            assembly region, so inspecting them shows the assembled haplotypes of the region."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH")
+                .short("-r")
+                .long("--reference")
+                .help("FASTA file of the reference the BAM files were mapped to. \n"),
+        )
+        .option(
+            Opt::new("PATH ..")
+                .short("-b")
+                .long("--bam-files")
+                .help("Sorted and indexed BAM files to show the reads of, one per sample. \n"),
+        )
+        .option(Opt::new("STR").long("--region").help(
+            "Variant to inspect, given as contig:position with a 1-based position. The contig \
+            must be named as in the BAM headers. \n",
+        ))
+        .option(Opt::new("PATH").short("-v").long("--vcf").help(
+            "VCF file to take the alleles of the variant from, e.g. the output of lorikeet call. \
+            [default: not set] \n",
+        ))
+        .option(Opt::new("INT").long("--flank").help(
+            "Number of reference bases shown either side of the variant. [default: 20] \n",
+        ))
+        .option(Opt::new("INT").long("--max-reads").help(
+            "Maximum number of reads shown for each allele of each sample. Every read is \
+            counted in the allele table. [default: 50] \n",
+        ))
+        .option(Opt::new("INT").long("--min-mapq").help(
+            "Minimum mapping quality of the reads shown and counted. [default: 0] \n",
+        ))
+        .option(Opt::new("PATH").short("-o").long("--output-file").help(
+            "Write the pileup to this file instead of stdout. [default: not set] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn apply_model_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet apply-model")
+        .about(
+            &format!(
+                "Score and filter the records of a VCF file with a logistic model (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet apply-model scores each record of a lorikeet VCF file with a logistic \
+            regression model trained on the tables written by --write-features. The model is a \
+            text file with one feature name, as in the header of the features table, and weight \
+            per line separated by whitespace, plus an optional intercept line, e.g. \
+            'intercept -2.5' and 'QD 0.3'. Lines starting with # are ignored. The score of an ALT \
+            allele is 1 / (1 + exp(-(intercept + sum of weight * feature))), with features the \
+            record does not carry contributing nothing, and the score of a record is the highest \
+            score of its ALT alleles. Every record is written with its score in the MLSCORE INFO \
+            field, and records scoring below --threshold are given the LowModelScore filter."
+        );
+
+    manual = manual
+        .option(Opt::new("PATH").short("-v").long("--vcf").help(
+            "VCF file written by lorikeet call, genotype or consensus. \n",
+        ))
+        .option(Opt::new("PATH").short("-m").long("--model").help(
+            "Model file of feature weights. \n",
+        ))
+        .option(Opt::new("PATH").short("-o").long("--output-file").help(
+            "Path of the scored VCF file. \n",
+        ))
+        .option(Opt::new("FLOAT").long("--threshold").help(
+            "Records scoring below this value are filtered. [default: 0.5] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn gather_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet gather")
+        .about(
+            &format!(
+                "Merge the shard VCF files of a scattered lorikeet call run (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet gather finds the partial VCF files written by lorikeet call --scatter in \
+            each genome folder of an output directory and merges the shards of each genome into \
+            a single position sorted VCF file, <genome>.vcf. Every shard of a genome must be \
+            present before it is gathered."
+        );
+
+    manual = manual
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory of the scattered lorikeet call runs. [default: ./] \n",
+        ))
+        .flag(Flag::new().long("--remove-shards").help(
+            "Delete the shard VCF files once they have been gathered. \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn add_sample_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet add-sample")
+        .about(
+            &format!(
+                "Add samples to the output of a previous lorikeet run (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet add-sample takes the output directory of a previous lorikeet call or genotype \
+            run and the BAM files of one or more new samples, and updates the VCF file, ANI tables, \
+            and strain abundances of each genome without reprocessing the existing samples. \
+            \n\
+            The new samples are called with lorikeet call using the alleles of the existing VCF \
+            files as feature variants, so the previously discovered alleles are genotyped in the \
+            new samples along with any regions that are only active in the new samples. The \
+            results are then jointly genotyped with the existing samples as in lorikeet combine. \
+            Sites that were only discovered in the new samples are written as missing for the \
+            existing samples. \
+            \n\
+            ANI values between pairs of existing samples and between pairs of new samples are \
+            kept from their own runs. ANI values between an existing and a new sample are \
+            calculated from the combined VCF, treating every base of the genome as comparable. \
+            Strain abundances are recalculated using the strains assigned by the existing run. \
+            \n\
+            Any arguments given after -- are passed on to lorikeet call, e.g. \
+            lorikeet add-sample -o output -r genome.fna -b new.bam -- --ploidy 1"
+        );
+
+    manual = manual
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory of the existing lorikeet run. Updated in place. [default: ./] \n",
+        ))
+        .option(
+            Opt::new("PATH ..")
+                .short("-r")
+                .long("--genome-fasta-files")
+                .help("The reference genomes of the existing run to add the samples to. \n"),
+        )
+        .option(
+            Opt::new("PATH ..")
+                .short("-b")
+                .long("--bam-files")
+                .help("Short read BAM files of the new samples. \n"),
+        )
+        .option(
+            Opt::new("PATH ..")
+                .short("-l")
+                .long("--longread-bam-files")
+                .help("Long read BAM files of the new samples. \n"),
+        )
+        .option(Opt::new("INT").short("-t").long("--threads").help(
+            "Number of threads used. [default: 10] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn combine_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet combine")
+        .about(
+            &format!(
+                "Jointly genotype the samples of multiple lorikeet runs (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet combine takes VCF files produced by separate lorikeet call or genotype runs \
+            against the same reference genome and jointly genotypes every sample, so that new samples \
+            can be added to a cohort without reprocessing the BAM files of previous runs. \
+            The input VCF files must contain the PL and AD format fields. gVCF files with \
+            <NON_REF> reference blocks are not supported. \
+            \n\
+            Sites are matched by contig, position, and reference allele and their alternate alleles \
+            are merged. Samples from a run that did not report a site are treated as uninformative \
+            at that site and do not contribute to the call."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory. [default: ./] \n",
+        ))
+        .option(Opt::new("NAME").long("--output-name").help(
+            "Name of the combined VCF file, without extension. [default: combined] \n",
+        ))
+        .option(Opt::new("INT").long("--ploidy").help(
+            "Sets the default ploidy for the analysis to N. [default: 2] \n",
+        ))
+        .option(Opt::new("FLOAT").short("-C").long("--standard-min-confidence-threshold-for-calling").help(
+            "The minimum phred-scaled confidence threshold at which \
+                     variants should be called. [default: 25.0] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--snp-heterozygosity").help(
+            "Heterozygosity prior for SNPs. [default: 0.001] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--indel-heterozygosity").help(
+            "Heterozygosity prior for indels. [default: 0.000125] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--heterozygosity-stdev").help(
+            "Standard deviation of heterozygosity for SNP and indel calling. [default: 0.01] \n",
+        ))
+        .flag(Flag::new().long("--use-posteriors-to-calculate-qual").help(
+            "If provided, we will use the genotype posteriors to calculate QUAL. \n",
+        ))
+        .flag(Flag::new().long("--annotate-with-num-discovered-alleles").help(
+            "If provided, we will annotate records with the number of alternate alleles \
+            that were discovered (but not necessarily genotyped) at a given site. \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
+
+pub fn merge_vcfs_full_help() -> Manual {
+    let mut manual = Manual::new("lorikeet merge-vcfs")
+        .about(
+            &format!(
+                "Merge the VCF files of multiple lorikeet runs on the same genome (version {})",
+                crate_version!()
+            )
+        )
+        .author(Author::new(crate::AUTHOR).email("rhys.newell94 near gmail.com"))
+        .description(
+            "lorikeet merge-vcfs takes VCF files produced by separate lorikeet runs against the same \
+            genome and writes the union of their sites, without genotyping the samples again. \
+            Sites are matched by contig, position, and reference allele and their alternate alleles \
+            are merged. Samples are matched by name, and samples missing from the runs reporting a \
+            site are written as missing. \
+            \n\
+            Each run numbers its strains (ST) and variant groups (VG) independently, so the IDs of \
+            later runs are matched to the IDs of earlier runs by the overlap of the alleles they \
+            contain. IDs without a sufficiently overlapping match are given new IDs."
+        );
+
+    manual = manual
+        .option(
+            Opt::new("PATH ..")
+                .short("-i")
+                .long("--vcfs")
+                .help("Paths to input VCF files. Can provide one or more. \n"),
+        )
+        .option(Opt::new("DIRECTORY").short("-o").long("--output-directory").help(
+            "Output directory. [default: ./] \n",
+        ))
+        .option(Opt::new("NAME").long("--output-name").help(
+            "Name of the merged VCF file, without extension. [default: merged] \n",
+        ))
+        .option(Opt::new("INT").long("--ploidy").help(
+            "Ploidy of samples missing from every run reporting a site. [default: 2] \n",
+        ))
+        .option(Opt::new("FLOAT").long("--min-id-overlap").help(
+            "Minimum Jaccard index between the alleles of a strain or variant group of one run \
+            and those of an earlier run for the two to be given the same ID. [default: 0.5] \n",
+        ));
+
+    manual = add_verbosity_flags(manual);
+    return manual;
+}
 
 pub fn build_cli() -> Command {
     // specify _2 lazily because need to define it at runtime.
@@ -1111,44 +2541,1450 @@ See lorikeet summarise --full-help for further options and further detail.
   lorikeet genotype --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
     --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10
 
-See lorikeet genotype --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet genotype"),
-            ansi_term::Colour::Green.paint(
-                "*EXPERIMENTAL* Report strain-level genotypes and abundances based on variant read mappings"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Map paired reads to a reference and generate genotypes"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Generate strain-level genotypes from read mappings compared to reference from a sorted BAM file and plots the results:"),
+See lorikeet genotype --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet genotype"),
+            ansi_term::Colour::Green.paint(
+                "*EXPERIMENTAL* Report strain-level genotypes and abundances based on variant read mappings"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and generate genotypes"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Generate strain-level genotypes from read mappings compared to reference from a sorted BAM file and plots the results:"),
+        );
+
+        static ref CALL_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet call --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10 --kmer-sizes 17 25
+
+{}
+
+  lorikeet genotype --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
+    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10 --kmer-sizes 17 25
+
+See lorikeet genotype --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet call"),
+            ansi_term::Colour::Green.paint(
+                "Perform read mapping and variant calling using local reassembly of active regions"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and generate genotypes"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Perform read read mapping and variant calling on an entire directory of genomes and save the bam files:"),
+        );
+
+        static ref ANI_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet ani --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
+
+{}
+
+  lorikeet ani --bam-files my.bam --genome-fasta-directory genomes/ -x fna
+    --output-directory lorikeet_out/ --threads 10
+
+See lorikeet ani --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet ani"),
+            ansi_term::Colour::Green.paint(
+                "Calculate ANI between samples from pileup base counts without local reassembly"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and calculate ANI"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Calculate ANI for every genome in a directory from a sorted BAM file:"),
+        );
+
+        static ref ALL_HELP: String = format!(
+            "
+                            {}
+              {}
+
+{}
+
+  lorikeet all --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10
+
+{}
+
+  lorikeet all --bam-files my.bam --genome-fasta-directory genomes/ -x fna
+    --output-directory lorikeet_out/ --threads 10 --outputs call consensus
+
+See lorikeet all --full-help for further options and further detail.
+",
+            ansi_term::Colour::Green.paint(
+                "lorikeet all"),
+            ansi_term::Colour::Green.paint(
+                "Call variants, genotype strains and generate consensus genomes in a single pass"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Map paired reads to a reference and produce the outputs of call, genotype and consensus"),
+            ansi_term::Colour::Purple.paint(
+                "Example: Only produce the call and consensus outputs for every genome in a directory:"),
+        );
+    }
+
+    let genotype_command = Command::new("genotype")
+        .about("Perform variant calling analysis and then binning")
+        .override_help(GENOTYPE_HELP.as_str())
+        .arg(
+            Arg::new("full-help")
+                .long("full-help")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("full-help-roff")
+                .long("full-help-roff")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bam-files")
+                .short('b')
+                .long("bam-files")
+                .action(ArgAction::Append)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("sharded")
+                .long("sharded")
+                .required(false)
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("exclude-genomes-from-deshard")
+                .long("exclude-genomes-from-deshard")
+                .requires("sharded"),
+        )
+        .arg(
+            Arg::new("read1")
+                .short('1')
+                .long("read1")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .requires("read2")
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "single",
+                    "coupled",
+                    "interleaved",
+                    "longreads",
+                    "longread-bam-files",
+                    "full-help",
+                    "full-help-roff",
+                ])
+                .conflicts_with("bam-files"),
+        )
+        .arg(
+            Arg::new("read2")
+                .short('2')
+                .long("read2")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .requires("read1")
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "single",
+                    "coupled",
+                    "interleaved",
+                    "longreads",
+                    "longread-bam-files",
+                    "full-help",
+                    "full-help-roff",
+                ])
+                .conflicts_with("bam-files"),
+        )
+        .arg(
+            Arg::new("coupled")
+                .short('c')
+                .long("coupled")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "read1",
+                    "coupled",
+                    "interleaved",
+                    "single",
+                    "longreads",
+                    "longread-bam-files",
+                    "full-help",
+                    "full-help-roff",
+                ])
+                .conflicts_with("bam-files"),
+        )
+        .arg(
+            Arg::new("interleaved")
+                .long("interleaved")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "read1",
+                    "coupled",
+                    "single",
+                    "interleaved",
+                    "longreads",
+                    "longread-bam-files",
+                    "full-help",
+                    "full-help-roff",
+                ])
+                .conflicts_with("bam-files"),
+        )
+        .arg(
+            Arg::new("single")
+                .long("single")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "read1",
+                    "coupled",
+                    "interleaved",
+                    "longreads",
+                    "longread-bam-files",
+                    "full-help",
+                    "full-help-roff",
+                ])
+                .conflicts_with("bam-files"),
+        )
+        .arg(
+            Arg::new("longreads")
+                .long("longreads")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "read1",
+                    "coupled",
+                    "interleaved",
+                    "single",
+                    "full-help", "full-help-roff",
+                    "longread-bam-files"
+                ])
+                .conflicts_with_all(&["longread-bam-files"]),
+        )
+        .arg(
+            Arg::new("longread-bam-files")
+                .short('l').long("longread-bam-files")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&[
+                    "bam-files",
+                    "read1",
+                    "coupled",
+                    "interleaved",
+                    "single",
+                    "full-help", "full-help-roff",
+                    "longreads",
+                ])
+                .conflicts_with_all(&["longreads"]),
+        )
+        .arg(
+            Arg::new("genome-fasta-files")
+                .short('f')
+                .short_alias('r')
+                .alias("reference")
+                .long("genome-fasta-files")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required_unless_present_any(&["genome-fasta-directory", "full-help", "full-help-roff"]),
+        )
+        .arg(
+            Arg::new("genome-fasta-directory")
+                .long("genome-fasta-directory")
+                .short('d')
+                .required_unless_present_any(&["genome-fasta-files", "full-help", "full-help-roff"]),
+        )
+        .arg(
+            Arg::new("genome-fasta-extension")
+                .long("genome-fasta-extension")
+                .short('x')
+                .default_value("fna"),
+        )
+        .arg(
+            Arg::new("genome-separator")
+                .long("genome-separator")
+                .value_parser(["auto", "~", "^", "@"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("genome-definition")
+                .long("genome-definition"),
+        )
+        .arg(
+            Arg::new("bam-file-cache-directory")
+                .long("bam-file-cache-directory"),
+        )
+        .arg(
+            Arg::new("output-directory")
+                .long("output-directory")
+                .short('o')
+                .default_value("./"),
+        )
+        .arg(
+            Arg::new("output-template")
+                .long("output-template")
+                .default_value("{genome}"),
+        )
+        .arg(
+            Arg::new("reference-cache-directory")
+                .long("reference-cache-directory"),
+        )
+        .arg(
+            Arg::new("features-vcf")
+                .long("features-vcf")
+                .alias("feature-vcf")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required(false),
+        )
+        .arg(
+            Arg::new("features-tsv")
+                .long("features-tsv")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required(false),
+        )
+        .arg(
+            Arg::new("hotspots")
+                .long("hotspots")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .required(false),
+        )
+        .arg(
+            Arg::new("hotspot-activity")
+                .long("hotspot-activity")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("write-features")
+                .long("write-features")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("threads")
+                .short('t').long("threads")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("parallel-genomes")
+                .short('P').long("parallel-genomes")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("max-contexts-in-memory")
+                .long("max-contexts-in-memory")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1000000"),
+        )
+        .arg(
+            Arg::new("io-threads")
+                .long("io-threads")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("pin-threads")
+                .long("pin-threads")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("mapper")
+                .short('p')
+                .long("mapper")
+                .value_parser(MAPPING_SOFTWARE_LIST.iter().collect::<Vec<_>>())
+                .default_value(DEFAULT_MAPPING_SOFTWARE),
+        )
+        .arg(
+            Arg::new("longread-mapper")
+                .long("longread-mapper")
+                .value_parser(LONGREAD_MAPPING_SOFTWARE_LIST.iter().collect::<Vec<_>>())
+                .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+        )
+        .arg(
+            Arg::new("minimap2-params")
+                .long("minimap2-params")
+                .long("minimap2-parameters")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("minimap2-reference-is-index")
+                .long("minimap2-reference-is-index"),
+        )
+        .arg(
+            Arg::new("bwa-params")
+                .long("bwa-params")
+                .long("bwa-parameters")
+                .allow_hyphen_values(true),
+        )
+        .arg(
+            Arg::new("high-memory")
+                .long("high-memory")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("keep-unmapped")
+                .long("keep-unmapped")
+                .action(clap::ArgAction::SetTrue)
+                .requires("bam-file-cache-directory"),
+        )
+        .arg(Arg::new("split-bams").long("split-bams").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("reassign-multimapped-reads")
+                .long("reassign-multimapped-reads")
+                .action(clap::ArgAction::SetTrue)
+                .requires("split-bams"),
+        )
+        .arg(
+            Arg::new("min-reassignment-weight")
+                .long("min-reassignment-weight")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.1"),
+        )
+        .arg(
+            Arg::new("min-read-aligned-length")
+                .long("min-read-aligned-length")
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("min-read-percent-identity")
+                .long("min-read-percent-identity")
+                .value_parser(clap::value_parser!(f32)),
+        )
+        .arg(
+            Arg::new("min-read-aligned-percent")
+                .long("min-read-aligned-percent")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::new("min-read-aligned-length-pair")
+                .long("min-read-aligned-length-pair")
+                .value_parser(clap::value_parser!(u32))
+                .conflicts_with("allow-improper-pairs"),
+        )
+        .arg(
+            Arg::new("min-read-percent-identity-pair")
+                .long("min-read-percent-identity-pair")
+                .value_parser(clap::value_parser!(f32))
+                .conflicts_with("allow-improper-pairs"),
+        )
+        .arg(
+            Arg::new("min-read-aligned-percent-pair")
+                .long("min-read-aligned-percent-pair")
+                .value_parser(clap::value_parser!(f32))
+                .conflicts_with("allow-improper-pairs"),
+        )
+        .arg(
+            Arg::new("min-covered-fraction")
+                .long("min-covered-fraction")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::new("min-contig-size")
+                .long("min-contig-size")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("phred-scaled-global-read-mismapping-rate")
+                .long("phred-scaled-global-read-mismapping-rate")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("45"),
+        )
+        .arg(
+            Arg::new("pair-hmm-gap-continuation-penalty")
+                .long("pair-hmm-gap-continuation-penalty")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("pair-hmm-batch-size")
+                .long("pair-hmm-batch-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("4096"),
+        )
+        .arg(
+            Arg::new("pack-read-bases")
+                .long("pack-read-bases")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bin-base-qualities")
+                .long("bin-base-qualities")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("pairhmm-backend")
+                .long("pairhmm-backend")
+                .value_parser(["auto", "avx", "gpu"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::new("pcr-indel-model")
+                .long("pcr-indel-model")
+                .default_value("conservative")
+                .value_parser(vec![
+                    "none",
+                    "None",
+                    "NONE",
+                    "hostile",
+                    "Hostile",
+                    "HOSTILE",
+                    "aggresive",
+                    "Agressive",
+                    "AGGRESSIVE",
+                    "conservative",
+                    "Conservative",
+                    "CONSERVATIVE",
+                ]),
+        )
+        .arg(
+            Arg::new("heterozygosity-stdev")
+                .long("heterozygosity-stdev")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.01"),
+        )
+        .arg(
+            Arg::new("snp-heterozygosity")
+                .long("snp-heterozygosity")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.001"),
+        )
+        .arg(
+            Arg::new("indel-heterozygosity")
+                .long("indel-heterozygosity")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.000125"),
+        )
+        .arg(
+            Arg::new("heterozygosity-priors")
+                .long("heterozygosity-priors"),
+        )
+        .arg(
+            Arg::new("per-genome-config")
+                .long("per-genome-config"),
+        )
+        .arg(
+            Arg::new("standard-min-confidence-threshold-for-calling")
+                .long("standard-min-confidence-threshold-for-calling")
+                .short('C')
+                .value_parser(clap::value_parser!(f64))
+                .default_value("25.0"),
+        )
+        .arg(
+            Arg::new("genotype-assignment-method")
+                .long("genotype-assignment-method")
+                .default_value("UsePLsToAssign")
+                .value_parser(vec![
+                    "UsePLsToAssign",
+                    "UsePosteriorProbabilities",
+                    "BestMatchToOriginal",
+                    "DoNotAssignGenotypes",
+                ])
+                .hide(true),
+        )
+        .arg(
+            Arg::new("use-posteriors-to-calculate-qual")
+                .long("use-posteriors-to-calculate-qual")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("annotate-with-num-discovered-alleles")
+                .long("annotate-with-num-discovered-alleles")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("active-probability-threshold")
+                .long("active-probability-threshold")
+                .value_parser(clap::value_parser!(f32))
+                .default_value("0.002"),
+        )
+        .arg(
+            Arg::new("min-assembly-region-size")
+                .long("min-assembly-region-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("50"),
+        )
+        .arg(
+            Arg::new("max-assembly-region-size")
+                .long("max-assembly-region-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("300"),
+        )
+        .arg(
+            Arg::new("kmer-sizes")
+                .long("kmer-sizes")
+                .short('k')
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .value_parser(clap::value_parser!(usize))
+                .default_values(&["21", "33"]),
+        )
+        .arg(
+            Arg::new("disable-automatic-kmer-adjustment")
+                .long("disable-automatic-kmer-adjustment")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-allowed-path-for-read-threading-assembler")
+                .long("max-allowed-path-for-read-threading-assembler")
+                .value_parser(clap::value_parser!(i32))
+                .default_value("128")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("dont-increase-kmer-sizes-for-cycles")
+                .long("dont-increase-kmer-sizes-for-cycles")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allow-non-unique-kmers-in-ref")
+                .long("allow-non-unique-kmers-in-ref")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("debug-graph-transformations")
+                .long("debug-graph-transformations")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("do-not-recover-dangling-branches")
+                .long("do-not-recover-dangling-branches")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("do-not-run-physical-phasing")
+                .long("do-not-run-physical-phasing")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("recover-all-dangling-branches")
+                .long("recover-all-dangling-branches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-dangling-branch-length")
+                .long("min-dangling-branch-length")
+                .value_parser(clap::value_parser!(i32))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("graph-output")
+                .long("graph-output")
+                .default_value("lorikeet_haplotype_caller"),
+        )
+        .arg(
+            Arg::new("debug-graph-output")
+                .long("debug-graph-output")
+                .default_value("lorikeet_haplotype_caller_debug")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("dump-assembly-graphs")
+                .long("dump-assembly-graphs")
+                .required(false),
+        )
+        .arg(
+            Arg::new("dump-graph-intervals")
+                .long("dump-graph-intervals")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .requires("dump-assembly-graphs"),
+        )
+        .arg(
+            Arg::new("num-pruning-samples")
+                .long("num-pruning-samples")
+                .value_parser(clap::value_parser!(i32))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("min-prune-factor")
+                .long("min-prune-factor")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("disable-prune-factor-correction")
+                .long("disable-prune-factor-correction")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(Arg::new("use-adaptive-pruning").long("use-adaptive-pruning").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("dont-use-soft-clipped-bases")
+                .long("dont-use-soft-clipped-bases")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("initial-error-rate-for-pruning")
+                .long("initial-error-rate-for-pruning")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.001"),
+        )
+        .arg(
+            Arg::new("pruning-seeding-log-odds-threshold")
+                .long("pruning-seeding-log-odds-threshold")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("4.0")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("pruning-log-odds-threshold")
+                .long("pruning-log-odds-threshold")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1.0"),
+        )
+        .arg(
+            Arg::new("max-unpruned-variants")
+                .long("max-unpruned-variants")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("chain-pruner")
+                .long("chain-pruner"),
+        )
+        .arg(
+            Arg::new("pruning-coverage-fraction")
+                .long("pruning-coverage-fraction")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.05"),
+        )
+        .arg(
+            Arg::new("pruning-min-sample-reads")
+                .long("pruning-min-sample-reads")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("2"),
+        )
+        .arg(
+            Arg::new("pruning-min-samples")
+                .long("pruning-min-samples")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("export-chain-features")
+                .long("export-chain-features"),
+        )
+        .arg(
+            Arg::new("max-input-depth")
+                .long("max-input-depth")
+                .short('i')
+                .value_parser(clap::value_parser!(usize))
+                .default_value("200000"),
+        )
+        .arg(
+            Arg::new("normalize-vcf")
+                .long("normalize-vcf")
+                .value_parser(["none", "left-align", "atomize"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::new("subsample-fraction")
+                .long("subsample-fraction")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("subsample-regions")
+                .long("subsample-regions")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("min-variant-depth-for-genotyping")
+                .long("min-variant-depth-for-genotyping")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("min-strain-divergence")
+                .long("min-strain-divergence")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.0"),
+        )
+        .arg(
+            Arg::new("min-linked-reads")
+                .long("min-linked-reads")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("export-linkage-graph")
+                .long("export-linkage-graph")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("bin-long-reads-by-strain")
+                .long("bin-long-reads-by-strain")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-strain-read-posterior")
+                .long("min-strain-read-posterior")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.9"),
+        )
+        .arg(
+            Arg::new("polish-strains")
+                .long("polish-strains")
+                .action(ArgAction::SetTrue)
+                .requires("bin-long-reads-by-strain"),
+        )
+        .arg(
+            Arg::new("polish-window-length")
+                .long("polish-window-length")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("500"),
+        )
+        .arg(
+            Arg::new("polish-min-coverage")
+                .long("polish-min-coverage")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("contig-end-exclusion")
+                .long("contig-end-exclusion")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("max-prob-propagation-distance")
+                .long("max-prob-propagation-distance")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("50"),
+        )
+        .arg(
+            Arg::new("use-linked-debruijn-graph")
+                .long("use-linked-debruijn-graph")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("error-correct-reads")
+                .long("error-correct-reads")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("kmer-length-for-read-error-correction")
+                .long("kmer-length-for-read-error-correction")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("25")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("min-observations-for-kmers-to-be-solid")
+                .long("min-observations-for-kmers-to-be-solid")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("max-mnp-distance")
+                .long("max-mnp-distance")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("0"),
+        )
+        .arg(
+            Arg::new("max-alternate-alleles")
+                .long("max-alternate-alleles")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("180"),
+        )
+        .arg(
+            Arg::new("emit-complex-events")
+                .long("emit-complex-events")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sv-info-min-indel-length")
+                .long("sv-info-min-indel-length")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("50"),
+        )
+        .arg(
+            Arg::new("haplotype-scoring-model")
+                .long("haplotype-scoring-model")
+                .value_parser(["path-weight", "read-support", "sample-likelihood"])
+                .default_value("path-weight"),
+        )
+        .arg(
+            Arg::new("min-observation-for-kmer-to-be-solid")
+                .long("min-observation-for-kmer-to-be-solid")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("enable-legacy-graph-cycle-detection")
+                .long("enable-legacy-graph-cycle-detection")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("min-matching-bases-to-dangling-end-recovery")
+                .long("min-matching-bases-to-dangling-end-recovery")
+                .value_parser(clap::value_parser!(i32))
+                .default_value("-1")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("assembly-region-padding")
+                .long("assembly-region-padding")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("100"),
+        )
+        .arg(
+            Arg::new("indel-padding-for-genotyping")
+                .long("indel-padding-for-genotyping")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("75")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("str-padding-for-genotyping")
+                .long("str-padding-for-genotyping")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("75")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("snp-padding-for-genotyping")
+                .long("snp-padding-for-genotyping")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("max-extension-into-region-padding")
+                .long("max-extension-into-region-padding")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("25")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("soft-clip-low-quality-ends")
+                .long("soft-clip-low-quality-ends")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("trim-min")
+                .long("trim-min")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.00"),
+        )
+        .arg(
+            Arg::new("trim-max")
+                .long("trim-max")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1.00"),
+        )
+        .arg(
+            Arg::new("mapping-quality-threshold-for-genotyping")
+                .long("mapping-quality-threshold-for-genotyping")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("min-sv-qual")
+                .long("min-sv-qual")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("3"),
+        )
+        .arg(Arg::new("do-not-call-svs").long("do-not-call-svs").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("sv-caller")
+                .long("sv-caller")
+                .value_parser(["svim", "sniffles2", "cutesv"])
+                .default_value("svim"),
+        )
+        .arg(
+            Arg::new("short-read-sv-evidence")
+                .long("short-read-sv-evidence")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-discordant-pairs")
+                .long("min-discordant-pairs")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("discordant-insert-size-stdevs")
+                .long("discordant-insert-size-stdevs")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("4.0"),
+        )
+        .arg(
+            Arg::new("soft-clip-rescue")
+                .long("soft-clip-rescue")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("min-rescue-clip-length")
+                .long("min-rescue-clip-length")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("min-rescue-clipped-reads")
+                .long("min-rescue-clipped-reads")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("soft-clip-profile")
+                .long("soft-clip-profile")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("end-trim-mismatch-rate")
+                .long("end-trim-mismatch-rate")
+                .value_parser(clap::value_parser!(f64)),
+        )
+        .arg(
+            Arg::new("end-trim-min-length")
+                .long("end-trim-min-length")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("minimizer-prefilter")
+                .long("minimizer-prefilter")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("minimizer-kmer-size")
+                .long("minimizer-kmer-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("15"),
+        )
+        .arg(
+            Arg::new("minimizer-window-size")
+                .long("minimizer-window-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("min-shared-minimizer-fraction")
+                .long("min-shared-minimizer-fraction")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.05"),
+        )
+        .arg(
+            Arg::new("min-mapq")
+                .long("min-mapq")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("min-long-read-size")
+                .long("min-long-read-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1500"),
+        )
+        .arg(
+            Arg::new("min-long-read-average-base-qual")
+                .long("min-long-read-average-base-qual")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("20"),
+        )
+        .arg(
+            Arg::new("min-base-quality")
+                .long("min-base-quality")
+                .short('q')
+                .value_parser(clap::value_parser!(u8))
+                .default_value("10"),
+        )
+        .arg(
+            Arg::new("base-quality-score-threshold")
+                .long("base-quality-score-threshold")
+                .value_parser(clap::value_parser!(u8))
+                .default_value("18"),
+        )
+        .arg(
+            Arg::new("qual-by-depth-filter")
+                .long("qual-by-depth-filter")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("25.0"),
+        )
+        .arg(
+            Arg::new("hard-filter")
+                .long("hard-filter")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("replicates")
+                .long("replicates")
+                .action(ArgAction::Append)
+                .num_args(1..),
+        )
+        .arg(
+            Arg::new("qual-threshold")
+                .long("qual-threshold")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("150.0"),
+        )
+        .arg(
+            Arg::new("depth-per-sample-filter")
+                .long("depth-per-sample-filter")
+                .value_parser(clap::value_parser!(i64))
+                .default_value("5"),
+        )
+        .arg(
+            Arg::new("disable-dynamic-read-disqualification-for-genotyping")
+                .long("disable-dynamic-read-disqualification-for-genotyping")
+                .action(clap::ArgAction::SetTrue)
+                .hide(false),
+        )
+        .arg(
+            Arg::new("dynamic-read-disqualification-threshold")
+                .long("dynamic-read-disqualification-threshold")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1.0")
+                .hide(false),
+        )
+        .arg(
+            Arg::new("expected-mismatch-rate-for-read-disqualification")
+                .long("expected-mismatch-rate-for-read-disqualification")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.02")
+                .hide(false),
+        )
+        .arg(
+            Arg::new("allele-informative-reads-overlap-margin")
+                .long("allele-informative-reads-overlap-margin")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("2")
+                .hide(true),
+        )
+        .arg(
+            Arg::new("disable-symmetric-hmm-normalizing")
+                .long("disable-symmetric-hmm-normalizing")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("disable-cap-base-qualities-to-map-quality")
+                .long("disable-cap-base-qualities-to-map-quality")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(
+            Arg::new("disable-spanning-event-genotyping")
+                .long("disable-spanning-event-genotyping")
+                .action(clap::ArgAction::SetTrue)
+                .hide(true),
+        )
+        .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("disable-assembly-fallback")
+                .long("disable-assembly-fallback")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("skip-reference-check")
+                .long("skip-reference-check")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("sw-preset")
+                .long("sw-preset")
+                .value_parser(["short-read", "long-read"])
+                .default_value("short-read"),
+        )
+        .arg(
+            Arg::new("sw-match")
+                .long("sw-match")
+                .value_parser(clap::value_parser!(i32).range(0..)),
+        )
+        .arg(
+            Arg::new("sw-mismatch")
+                .long("sw-mismatch")
+                .value_parser(clap::value_parser!(i32).range(..=0))
+                .allow_negative_numbers(true),
+        )
+        .arg(
+            Arg::new("sw-gap-open")
+                .long("sw-gap-open")
+                .value_parser(clap::value_parser!(i32).range(..=0))
+                .allow_negative_numbers(true),
+        )
+        .arg(
+            Arg::new("sw-gap-extend")
+                .long("sw-gap-extend")
+                .value_parser(clap::value_parser!(i32).range(..=0))
+                .allow_negative_numbers(true),
+        )
+        .arg(Arg::new("no-zeros").long("no-zeros").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("allow-improper-pairs").long("allow-improper-pairs").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("include-secondary").long("include-secondary").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("exclude-supplementary").long("exclude-supplementary").action(clap::ArgAction::SetTrue))
+        .arg(
+            Arg::new("split-alignment-policy")
+                .long("split-alignment-policy")
+                .value_parser(["count-all", "skip", "count-once-per-fragment", "weight-by-mapq"])
+                .default_value("count-all"),
+        )
+        .arg(
+            Arg::new("ploidy")
+                .long("ploidy")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("2"),
+        )
+        .arg(
+            Arg::new("estimate-ploidy")
+                .long("estimate-ploidy")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("max-estimated-ploidy")
+                .long("max-estimated-ploidy")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("6"),
+        )
+        .arg(
+            Arg::new("calculate-dnds")
+                .long("calculate-dnds")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allele-fraction-denominator")
+                .long("allele-fraction-denominator")
+                .value_parser(["informative", "all"])
+                .default_value("informative"),
+        )
+        .arg(
+            Arg::new("callable-sites")
+                .long("callable-sites")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("callable-sites-min-samples")
+                .long("callable-sites-min-samples")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1"),
+        )
+        .arg(
+            Arg::new("calculate-sfs")
+                .long("calculate-sfs")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("reference-bias")
+                .long("reference-bias")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("correct-reference-bias")
+                .long("correct-reference-bias")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("reference-bias-min-fraction")
+                .long("reference-bias-min-fraction")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.2"),
+        )
+        .arg(
+            Arg::new("calculate-fst")
+                .long("calculate-fst")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sample-dendrogram")
+                .long("sample-dendrogram")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("prodigal-params")
+                .long("prodigal-params")
+                .default_value("-p meta"),
+        )
+        .arg(
+            Arg::new("genetic-code")
+                .long("genetic-code")
+                .action(clap::ArgAction::Append)
+                .num_args(1..)
+                .value_parser(GeneticCodes::validate),
+        )
+        .arg(
+            Arg::new("marker-catalog")
+                .long("marker-catalog")
+                .required(false),
+        )
+        .arg(
+            Arg::new("limiting-interval")
+                .long("limiting-interval")
+                .required(false),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(clap::value_parser!(u64))
+                .required(false),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_parser(["fast", "very-fast", "sensitive", "precise", "super-sensitive"])
+                .required(false)
+        )
+        .arg(
+            Arg::new("mask-bed")
+                .long("mask-bed")
+                .required(false),
+        )
+        .arg(
+            Arg::new("mask-kmer-size")
+                .long("mask-kmer-size")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("mask-bed")
+                .required(false),
+        )
+        .arg(
+            Arg::new("homopolymer-indel-filter")
+                .long("homopolymer-indel-filter")
+                .value_parser(clap::value_parser!(usize))
+                .required(false),
+        )
+        .arg(
+            Arg::new("complexity-window-size")
+                .long("complexity-window-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("64"),
+        )
+        .arg(
+            Arg::new("low-complexity-filter")
+                .long("low-complexity-filter")
+                .value_parser(clap::value_parser!(f64))
+                .required(false),
+        )
+        .arg(
+            Arg::new("coverage-window-size")
+                .long("coverage-window-size")
+                .value_parser(clap::value_parser!(usize))
+                .default_value("1000"),
+        )
+        .arg(
+            Arg::new("min-copy-number-ratio")
+                .long("min-copy-number-ratio")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.5"),
+        )
+        .arg(
+            Arg::new("max-copy-number-ratio")
+                .long("max-copy-number-ratio")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("1.5"),
+        )
+        .arg(
+            Arg::new("hybrid-assembly")
+                .long("hybrid-assembly")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("log-format")
+                .long("log-format")
+                .value_parser(["text", "json"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .required(false),
+        )
+        .arg(
+            Arg::new("base-quality-recalibration")
+                .long("base-quality-recalibration")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("read-group-weights")
+                .long("read-group-weights"),
+        )
+        .arg(
+            Arg::new("abundance-formats")
+                .long("abundance-formats")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .value_parser(["cami", "biom"]),
+        )
+        .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("dry-run").long("dry-run").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("config").long("config"))
+        .arg(Arg::new("tmp-dir").long("tmp-dir"))
+        .arg(Arg::new("keep-temp").long("keep-temp").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("verbose").short('v').long("verbose").action(clap::ArgAction::SetTrue))
+        .arg(Arg::new("quiet").long("quiet").action(clap::ArgAction::SetTrue));
+
+    // the combined subcommand takes every genotype option along with the consensus options
+    let all_command = genotype_command
+        .clone()
+        .name("all")
+        .about("Call variants, genotype strains and generate consensus genomes in a single pass")
+        .override_help(ALL_HELP.as_str())
+        .arg(
+            Arg::new("outputs")
+                .long("outputs")
+                .action(ArgAction::Append)
+                .num_args(1..)
+                .value_parser(["call", "genotype", "consensus"])
+                .default_values(["call", "genotype", "consensus"]),
+        )
+        .arg(
+            Arg::new("consensus-ambiguity")
+                .long("consensus-ambiguity")
+                .value_parser(["none", "iupac", "n"])
+                .default_value("none"),
+        )
+        .arg(
+            Arg::new("consensus-min-allele-fraction")
+                .long("consensus-min-allele-fraction")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.75"),
+        )
+        .arg(
+            Arg::new("consensus-min-depth")
+                .long("consensus-min-depth")
+                .value_parser(clap::value_parser!(i32))
+                .default_value("3"),
+        )
+        .arg(
+            Arg::new("consensus-samples")
+                .long("consensus-samples")
+                .num_args(1..),
         );
 
-        static ref CALL_HELP: String = format!(
-            "
-                            {}
-              {}
-
-{}
-
-  lorikeet call --coupled read1.fastq.gz read2.fastq.gz --reference assembly.fna --threads 10 --kmer-sizes 17 25
-
-{}
-
-  lorikeet genotype --bam-files my.bam --longread-bam-files my-longread.bam --genome-fasta-directory genomes/ -x fna
-    --bam-file-cache-directory saved_bam_files --output-directory lorikeet_out/ --threads 10 --kmer-sizes 17 25
-
-See lorikeet genotype --full-help for further options and further detail.
-",
-            ansi_term::Colour::Green.paint(
-                "lorikeet call"),
-            ansi_term::Colour::Green.paint(
-                "Perform read mapping and variant calling using local reassembly of active regions"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Map paired reads to a reference and generate genotypes"),
-            ansi_term::Colour::Purple.paint(
-                "Example: Perform read read mapping and variant calling on an entire directory of genomes and save the bam files:"),
+    // the fast ANI subcommand reads and filters reads like genotype but only counts bases
+    let ani_command = genotype_command
+        .clone()
+        .name("ani")
+        .about("Calculate ANI between samples from pileup base counts without local reassembly")
+        .override_help(ANI_HELP.as_str())
+        .arg(
+            Arg::new("min-snv-fraction")
+                .long("min-snv-fraction")
+                .value_parser(clap::value_parser!(f64))
+                .default_value("0.05"),
         );
-    }
 
     return Command::new("lorikeet")
         .version(crate_version!())
@@ -1167,9 +4003,20 @@ Usage: lorikeet <subcommand> ...
 Main subcommands:
 \tcall      \tPerforms variant calling on the provides genomes
 \tconsensus \tCreates consensus genomes for each input reference and for each sample
+\tall       \tProduces the outputs of call, genotype and consensus in a single pass
+\tani       \tFast ANI between samples from pileup base counts, without assembly
 
 Utility subcommands:
 \tsummarise \tCalculate microdiversity statistics for a given set of VCF files
+\tconcordance \tFlag duplicate or swapped samples using genotype concordance
+\tcombine   \tJointly genotype the samples of multiple lorikeet VCF files
+\tmerge-vcfs\tMerge the sites and samples of lorikeet VCF files of one genome
+\tadd-sample\tAdd new samples to the output of a previous lorikeet run
+\tgather    \tMerge the shard VCF files of a scattered lorikeet call run
+\tphylo     \tBuild core SNP alignments and trees from lorikeet VCF files
+\tgraph-inspect\tSummarise assembly graphs written by --dump-assembly-graphs
+\tinspect   \tShow the reads and haplotypes supporting the alleles of a variant
+\tapply-model\tScore and filter VCF records with a model trained on --write-features
 \tshell-completion  \tGenerate shell completion scripts
 
 Experimental subcommands:
@@ -1182,10 +4029,11 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
 ",
         )
         .arg_required_else_help(true)
+        .subcommand(genotype_command)
         .subcommand(
-            Command::new("genotype")
-                .about("Perform variant calling analysis and then binning")
-                .override_help(GENOTYPE_HELP.as_str())
+            Command::new("call")
+                .about("Perform variant calling across the given genomes and samples")
+                .override_help(CALL_HELP.as_str())
                 .arg(
                     Arg::new("full-help")
                         .long("full-help")
@@ -1360,6 +4208,16 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .short('x')
                         .default_value("fna"),
                 )
+                .arg(
+                    Arg::new("genome-separator")
+                        .long("genome-separator")
+                        .value_parser(["auto", "~", "^", "@"])
+                        .default_value("auto"),
+                )
+                .arg(
+                    Arg::new("genome-definition")
+                        .long("genome-definition"),
+                )
                 .arg(
                     Arg::new("bam-file-cache-directory")
                         .long("bam-file-cache-directory"),
@@ -1370,11 +4228,48 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .short('o')
                         .default_value("./"),
                 )
+                .arg(
+                    Arg::new("output-template")
+                        .long("output-template")
+                        .default_value("{genome}"),
+                )
+                .arg(
+                    Arg::new("reference-cache-directory")
+                        .long("reference-cache-directory"),
+                )
                 .arg(
                     Arg::new("features-vcf")
                         .long("features-vcf")
+                        .alias("feature-vcf")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("features-tsv")
+                        .long("features-tsv")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
                         .required(false),
                 )
+                .arg(
+                    Arg::new("hotspots")
+                        .long("hotspots")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("hotspot-activity")
+                        .long("hotspot-activity")
+                        .value_parser(clap::value_parser!(f32))
+                        .default_value("1.0"),
+                )
+                .arg(
+                    Arg::new("write-features")
+                        .long("write-features")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("threads")
                         .short('t').long("threads")
@@ -1387,6 +4282,23 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("1"),
                 )
+                .arg(
+                    Arg::new("max-contexts-in-memory")
+                        .long("max-contexts-in-memory")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000000"),
+                )
+                .arg(
+                    Arg::new("io-threads")
+                        .long("io-threads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("pin-threads")
+                        .long("pin-threads")
+                        .action(clap::ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("mapper")
                         .short('p')
@@ -1428,6 +4340,18 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .requires("bam-file-cache-directory"),
                 )
                 .arg(Arg::new("split-bams").long("split-bams").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("reassign-multimapped-reads")
+                        .long("reassign-multimapped-reads")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("split-bams"),
+                )
+                .arg(
+                    Arg::new("min-reassignment-weight")
+                        .long("min-reassignment-weight")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.1"),
+                )
                 .arg(
                     Arg::new("min-read-aligned-length")
                         .long("min-read-aligned-length")
@@ -1486,6 +4410,28 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(u8))
                         .default_value("10"),
                 )
+                .arg(
+                    Arg::new("pair-hmm-batch-size")
+                        .long("pair-hmm-batch-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4096"),
+                )
+                .arg(
+                    Arg::new("pack-read-bases")
+                        .long("pack-read-bases")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("bin-base-qualities")
+                        .long("bin-base-qualities")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("pairhmm-backend")
+                        .long("pairhmm-backend")
+                        .value_parser(["auto", "avx", "gpu"])
+                        .default_value("auto"),
+                )
                 .arg(
                     Arg::new("pcr-indel-model")
                         .long("pcr-indel-model")
@@ -1523,6 +4469,14 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(f64))
                         .default_value("0.000125"),
                 )
+                .arg(
+                    Arg::new("heterozygosity-priors")
+                        .long("heterozygosity-priors"),
+                )
+                .arg(
+                    Arg::new("per-genome-config")
+                        .long("per-genome-config"),
+                )
                 .arg(
                     Arg::new("standard-min-confidence-threshold-for-calling")
                         .long("standard-min-confidence-threshold-for-calling")
@@ -1640,6 +4594,18 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("lorikeet_haplotype_caller_debug")
                         .hide(true),
                 )
+                .arg(
+                    Arg::new("dump-assembly-graphs")
+                        .long("dump-assembly-graphs")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("dump-graph-intervals")
+                        .long("dump-graph-intervals")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .requires("dump-assembly-graphs"),
+                )
                 .arg(
                     Arg::new("num-pruning-samples")
                         .long("num-pruning-samples")
@@ -1688,6 +4654,32 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("100"),
                 )
+                .arg(
+                    Arg::new("chain-pruner")
+                        .long("chain-pruner"),
+                )
+                .arg(
+                    Arg::new("pruning-coverage-fraction")
+                        .long("pruning-coverage-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.05"),
+                )
+                .arg(
+                    Arg::new("pruning-min-sample-reads")
+                        .long("pruning-min-sample-reads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("pruning-min-samples")
+                        .long("pruning-min-samples")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("export-chain-features")
+                        .long("export-chain-features"),
+                )
                 .arg(
                     Arg::new("max-input-depth")
                         .long("max-input-depth")
@@ -1696,10 +4688,20 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("200000"),
                 )
                 .arg(
-                    Arg::new("min-variant-depth-for-genotyping")
-                        .long("min-variant-depth-for-genotyping")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("10"),
+                    Arg::new("normalize-vcf")
+                        .long("normalize-vcf")
+                        .value_parser(["none", "left-align", "atomize"])
+                        .default_value("none"),
+                )
+                .arg(
+                    Arg::new("subsample-fraction")
+                        .long("subsample-fraction")
+                        .value_parser(clap::value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("subsample-regions")
+                        .long("subsample-regions")
+                        .value_parser(clap::value_parser!(usize)),
                 )
                 .arg(
                     Arg::new("contig-end-exclusion")
@@ -1745,6 +4747,29 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("0"),
                 )
+                .arg(
+                    Arg::new("max-alternate-alleles")
+                        .long("max-alternate-alleles")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("180"),
+                )
+                .arg(
+                    Arg::new("emit-complex-events")
+                        .long("emit-complex-events")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sv-info-min-indel-length")
+                        .long("sv-info-min-indel-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50"),
+                )
+                .arg(
+                    Arg::new("haplotype-scoring-model")
+                        .long("haplotype-scoring-model")
+                        .value_parser(["path-weight", "read-support", "sample-likelihood"])
+                        .default_value("path-weight"),
+                )
                 .arg(
                     Arg::new("min-observation-for-kmer-to-be-solid")
                         .long("min-observation-for-kmer-to-be-solid")
@@ -1830,6 +4855,85 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("3"),
                 )
                 .arg(Arg::new("do-not-call-svs").long("do-not-call-svs").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("sv-caller")
+                        .long("sv-caller")
+                        .value_parser(["svim", "sniffles2", "cutesv"])
+                        .default_value("svim"),
+                )
+                .arg(
+                    Arg::new("short-read-sv-evidence")
+                        .long("short-read-sv-evidence")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("min-discordant-pairs")
+                        .long("min-discordant-pairs")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("discordant-insert-size-stdevs")
+                        .long("discordant-insert-size-stdevs")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("4.0"),
+                )
+                .arg(
+                    Arg::new("soft-clip-rescue")
+                        .long("soft-clip-rescue")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("min-rescue-clip-length")
+                        .long("min-rescue-clip-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("min-rescue-clipped-reads")
+                        .long("min-rescue-clipped-reads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("soft-clip-profile")
+                        .long("soft-clip-profile")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("end-trim-mismatch-rate")
+                        .long("end-trim-mismatch-rate")
+                        .value_parser(clap::value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("end-trim-min-length")
+                        .long("end-trim-min-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("minimizer-prefilter")
+                        .long("minimizer-prefilter")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("minimizer-kmer-size")
+                        .long("minimizer-kmer-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("15"),
+                )
+                .arg(
+                    Arg::new("minimizer-window-size")
+                        .long("minimizer-window-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("min-shared-minimizer-fraction")
+                        .long("min-shared-minimizer-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.05"),
+                )
                 .arg(
                     Arg::new("min-mapq")
                         .long("min-mapq")
@@ -1867,6 +4971,34 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(f64))
                         .default_value("25.0"),
                 )
+                .arg(
+                    Arg::new("hard-filter")
+                        .long("hard-filter")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("haplotype-vcf")
+                        .long("haplotype-vcf")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("emit-haplotype-records")
+                        .long("emit-haplotype-records")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with("haplotype-vcf"),
+                )
+                .arg(
+                    Arg::new("haplotype-record-min-qual")
+                        .long("haplotype-record-min-qual")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("30.0"),
+                )
+                .arg(
+                    Arg::new("replicates")
+                        .long("replicates")
+                        .action(ArgAction::Append)
+                        .num_args(1..),
+                )
                 .arg(
                     Arg::new("qual-threshold")
                         .long("qual-threshold")
@@ -1913,63 +5045,274 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .hide(true),
                 )
                 .arg(
-                    Arg::new("disable-cap-base-qualities-to-map-quality")
-                        .long("disable-cap-base-qualities-to-map-quality")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("disable-cap-base-qualities-to-map-quality")
+                        .long("disable-cap-base-qualities-to-map-quality")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true),
+                )
+                .arg(
+                    Arg::new("disable-spanning-event-genotyping")
+                        .long("disable-spanning-event-genotyping")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true),
+                )
+                .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("disable-assembly-fallback")
+                        .long("disable-assembly-fallback")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("skip-reference-check")
+                        .long("skip-reference-check")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("sw-preset")
+                        .long("sw-preset")
+                        .value_parser(["short-read", "long-read"])
+                        .default_value("short-read"),
+                )
+                .arg(
+                    Arg::new("sw-match")
+                        .long("sw-match")
+                        .value_parser(clap::value_parser!(i32).range(0..)),
+                )
+                .arg(
+                    Arg::new("sw-mismatch")
+                        .long("sw-mismatch")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
                 )
                 .arg(
-                    Arg::new("disable-spanning-event-genotyping")
-                        .long("disable-spanning-event-genotyping")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("sw-gap-open")
+                        .long("sw-gap-open")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
+                )
+                .arg(
+                    Arg::new("sw-gap-extend")
+                        .long("sw-gap-extend")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
                 )
-                .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("no-zeros").long("no-zeros").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("allow-improper-pairs").long("allow-improper-pairs").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("include-secondary").long("include-secondary").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("exclude-supplementary").long("exclude-supplementary").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("split-alignment-policy")
+                        .long("split-alignment-policy")
+                        .value_parser([
+                            "count-all",
+                            "skip",
+                            "count-once-per-fragment",
+                            "weight-by-mapq",
+                        ])
+                        .default_value("count-all"),
+                )
                 .arg(
                     Arg::new("ploidy")
                         .long("ploidy")
                         .value_parser(clap::value_parser!(usize))
                         .default_value("2"),
                 )
+                .arg(
+                    Arg::new("estimate-ploidy")
+                        .long("estimate-ploidy")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max-estimated-ploidy")
+                        .long("max-estimated-ploidy")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("6"),
+                )
                 .arg(
                     Arg::new("calculate-dnds")
                         .long("calculate-dnds")
                         .action(clap::ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("allele-fraction-denominator")
+                        .long("allele-fraction-denominator")
+                        .value_parser(["informative", "all"])
+                        .default_value("informative"),
+                )
+                .arg(
+                    Arg::new("callable-sites")
+                        .long("callable-sites")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("callable-sites-min-samples")
+                        .long("callable-sites-min-samples")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("calculate-sfs")
+                        .long("calculate-sfs")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("reference-bias")
+                        .long("reference-bias")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("correct-reference-bias")
+                        .long("correct-reference-bias")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("reference-bias-min-fraction")
+                        .long("reference-bias-min-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.2"),
+                )
                 .arg(
                     Arg::new("calculate-fst")
                         .long("calculate-fst")
                         .action(clap::ArgAction::SetTrue),
                 )
+                .arg(
+                    Arg::new("sample-dendrogram")
+                        .long("sample-dendrogram")
+                        .action(clap::ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("prodigal-params")
                         .long("prodigal-params")
                         .default_value("-p meta"),
                 )
+                .arg(
+                    Arg::new("genetic-code")
+                        .long("genetic-code")
+                        .action(clap::ArgAction::Append)
+                        .num_args(1..)
+                        .value_parser(GeneticCodes::validate),
+                )
+                .arg(
+                    Arg::new("marker-catalog")
+                        .long("marker-catalog")
+                        .required(false),
+                )
                 .arg(
                     Arg::new("limiting-interval")
                         .long("limiting-interval")
                         .required(false),
                 )
+                .arg(
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(clap::value_parser!(u64))
+                        .required(false),
+                )
                 .arg(
                     Arg::new("profile")
                         .long("profile")
                         .value_parser(["fast", "very-fast", "sensitive", "precise", "super-sensitive"])
                         .required(false)
                 )
+                .arg(
+                    Arg::new("mask-bed")
+                        .long("mask-bed")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("mask-kmer-size")
+                        .long("mask-kmer-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .conflicts_with("mask-bed")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("homopolymer-indel-filter")
+                        .long("homopolymer-indel-filter")
+                        .value_parser(clap::value_parser!(usize))
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("complexity-window-size")
+                        .long("complexity-window-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("64"),
+                )
+                .arg(
+                    Arg::new("low-complexity-filter")
+                        .long("low-complexity-filter")
+                        .value_parser(clap::value_parser!(f64))
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("coverage-window-size")
+                        .long("coverage-window-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000"),
+                )
+                .arg(
+                    Arg::new("min-copy-number-ratio")
+                        .long("min-copy-number-ratio")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.5"),
+                )
+                .arg(
+                    Arg::new("max-copy-number-ratio")
+                        .long("max-copy-number-ratio")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("1.5"),
+                )
+                .arg(
+                    Arg::new("hybrid-assembly")
+                        .long("hybrid-assembly")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("log-format")
+                        .long("log-format")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::new("log-file")
+                        .long("log-file")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("base-quality-recalibration")
+                        .long("base-quality-recalibration")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("read-group-weights")
+                        .long("read-group-weights"),
+                )
+                .arg(
+                    Arg::new("scatter")
+                        .long("scatter")
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("shard-index")
+                        .long("shard-index")
+                        .value_parser(clap::value_parser!(usize))
+                        .requires("scatter")
+                        .default_value("0"),
+                )
                 .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("dry-run").long("dry-run").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("config").long("config"))
+                .arg(Arg::new("tmp-dir").long("tmp-dir"))
+                .arg(Arg::new("keep-temp").long("keep-temp").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("verbose").short('v').long("verbose").action(clap::ArgAction::SetTrue))
                 .arg(Arg::new("quiet").long("quiet").action(clap::ArgAction::SetTrue)),
         )
         .subcommand(
-            Command::new("call")
-                .about("Perform variant calling across the given genomes and samples")
-                .override_help(CALL_HELP.as_str())
+            Command::new("consensus")
+                .about("Generate consensus genomes across all provided samples")
+                .override_help(CONSENSUS_HELP.as_str())
                 .arg(
                     Arg::new("full-help")
                         .long("full-help")
@@ -1985,6 +5328,7 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .short('b')
                         .long("bam-files")
                         .action(ArgAction::Append)
+                        .num_args(1..)
                         .num_args(1..),
                 )
                 .arg(
@@ -2144,6 +5488,16 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .short('x')
                         .default_value("fna"),
                 )
+                .arg(
+                    Arg::new("genome-separator")
+                        .long("genome-separator")
+                        .value_parser(["auto", "~", "^", "@"])
+                        .default_value("auto"),
+                )
+                .arg(
+                    Arg::new("genome-definition")
+                        .long("genome-definition"),
+                )
                 .arg(
                     Arg::new("bam-file-cache-directory")
                         .long("bam-file-cache-directory"),
@@ -2154,11 +5508,48 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .short('o')
                         .default_value("./"),
                 )
+                .arg(
+                    Arg::new("output-template")
+                        .long("output-template")
+                        .default_value("{genome}"),
+                )
+                .arg(
+                    Arg::new("reference-cache-directory")
+                        .long("reference-cache-directory"),
+                )
                 .arg(
                     Arg::new("features-vcf")
                         .long("features-vcf")
+                        .alias("feature-vcf")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("features-tsv")
+                        .long("features-tsv")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("hotspots")
+                        .long("hotspots")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
                         .required(false),
                 )
+                .arg(
+                    Arg::new("hotspot-activity")
+                        .long("hotspot-activity")
+                        .value_parser(clap::value_parser!(f32))
+                        .default_value("1.0"),
+                )
+                .arg(
+                    Arg::new("write-features")
+                        .long("write-features")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("threads")
                         .short('t').long("threads")
@@ -2171,6 +5562,23 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("1"),
                 )
+                .arg(
+                    Arg::new("max-contexts-in-memory")
+                        .long("max-contexts-in-memory")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000000"),
+                )
+                .arg(
+                    Arg::new("io-threads")
+                        .long("io-threads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("0"),
+                )
+                .arg(
+                    Arg::new("pin-threads")
+                        .long("pin-threads")
+                        .action(clap::ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("mapper")
                         .short('p')
@@ -2212,6 +5620,18 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .requires("bam-file-cache-directory"),
                 )
                 .arg(Arg::new("split-bams").long("split-bams").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("reassign-multimapped-reads")
+                        .long("reassign-multimapped-reads")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("split-bams"),
+                )
+                .arg(
+                    Arg::new("min-reassignment-weight")
+                        .long("min-reassignment-weight")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.1"),
+                )
                 .arg(
                     Arg::new("min-read-aligned-length")
                         .long("min-read-aligned-length")
@@ -2270,6 +5690,28 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(u8))
                         .default_value("10"),
                 )
+                .arg(
+                    Arg::new("pair-hmm-batch-size")
+                        .long("pair-hmm-batch-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4096"),
+                )
+                .arg(
+                    Arg::new("pack-read-bases")
+                        .long("pack-read-bases")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("bin-base-qualities")
+                        .long("bin-base-qualities")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("pairhmm-backend")
+                        .long("pairhmm-backend")
+                        .value_parser(["auto", "avx", "gpu"])
+                        .default_value("auto"),
+                )
                 .arg(
                     Arg::new("pcr-indel-model")
                         .long("pcr-indel-model")
@@ -2307,6 +5749,14 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(f64))
                         .default_value("0.000125"),
                 )
+                .arg(
+                    Arg::new("heterozygosity-priors")
+                        .long("heterozygosity-priors"),
+                )
+                .arg(
+                    Arg::new("per-genome-config")
+                        .long("per-genome-config"),
+                )
                 .arg(
                     Arg::new("standard-min-confidence-threshold-for-calling")
                         .long("standard-min-confidence-threshold-for-calling")
@@ -2424,6 +5874,18 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("lorikeet_haplotype_caller_debug")
                         .hide(true),
                 )
+                .arg(
+                    Arg::new("dump-assembly-graphs")
+                        .long("dump-assembly-graphs")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("dump-graph-intervals")
+                        .long("dump-graph-intervals")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .requires("dump-assembly-graphs"),
+                )
                 .arg(
                     Arg::new("num-pruning-samples")
                         .long("num-pruning-samples")
@@ -2472,6 +5934,32 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("100"),
                 )
+                .arg(
+                    Arg::new("chain-pruner")
+                        .long("chain-pruner"),
+                )
+                .arg(
+                    Arg::new("pruning-coverage-fraction")
+                        .long("pruning-coverage-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.05"),
+                )
+                .arg(
+                    Arg::new("pruning-min-sample-reads")
+                        .long("pruning-min-sample-reads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("2"),
+                )
+                .arg(
+                    Arg::new("pruning-min-samples")
+                        .long("pruning-min-samples")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::new("export-chain-features")
+                        .long("export-chain-features"),
+                )
                 .arg(
                     Arg::new("max-input-depth")
                         .long("max-input-depth")
@@ -2479,6 +5967,22 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("200000"),
                 )
+                .arg(
+                    Arg::new("normalize-vcf")
+                        .long("normalize-vcf")
+                        .value_parser(["none", "left-align", "atomize"])
+                        .default_value("none"),
+                )
+                .arg(
+                    Arg::new("subsample-fraction")
+                        .long("subsample-fraction")
+                        .value_parser(clap::value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("subsample-regions")
+                        .long("subsample-regions")
+                        .value_parser(clap::value_parser!(usize)),
+                )
                 .arg(
                     Arg::new("contig-end-exclusion")
                         .long("contig-end-exclusion")
@@ -2523,6 +6027,29 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(usize))
                         .default_value("0"),
                 )
+                .arg(
+                    Arg::new("max-alternate-alleles")
+                        .long("max-alternate-alleles")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("180"),
+                )
+                .arg(
+                    Arg::new("emit-complex-events")
+                        .long("emit-complex-events")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sv-info-min-indel-length")
+                        .long("sv-info-min-indel-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50"),
+                )
+                .arg(
+                    Arg::new("haplotype-scoring-model")
+                        .long("haplotype-scoring-model")
+                        .value_parser(["path-weight", "read-support", "sample-likelihood"])
+                        .default_value("path-weight"),
+                )
                 .arg(
                     Arg::new("min-observation-for-kmer-to-be-solid")
                         .long("min-observation-for-kmer-to-be-solid")
@@ -2596,18 +6123,97 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("1.00"),
                 )
                 .arg(
-                    Arg::new("mapping-quality-threshold-for-genotyping")
-                        .long("mapping-quality-threshold-for-genotyping")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("20"),
+                    Arg::new("mapping-quality-threshold-for-genotyping")
+                        .long("mapping-quality-threshold-for-genotyping")
+                        .value_parser(clap::value_parser!(u8))
+                        .default_value("20"),
+                )
+                .arg(
+                    Arg::new("min-sv-qual")
+                        .long("min-sv-qual")
+                        .value_parser(clap::value_parser!(u8))
+                        .default_value("3"),
+                )
+                .arg(Arg::new("do-not-call-svs").long("do-not-call-svs").action(clap::ArgAction::SetTrue))
+                .arg(
+                    Arg::new("sv-caller")
+                        .long("sv-caller")
+                        .value_parser(["svim", "sniffles2", "cutesv"])
+                        .default_value("svim"),
+                )
+                .arg(
+                    Arg::new("short-read-sv-evidence")
+                        .long("short-read-sv-evidence")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("min-discordant-pairs")
+                        .long("min-discordant-pairs")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("discordant-insert-size-stdevs")
+                        .long("discordant-insert-size-stdevs")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("4.0"),
+                )
+                .arg(
+                    Arg::new("soft-clip-rescue")
+                        .long("soft-clip-rescue")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("min-rescue-clip-length")
+                        .long("min-rescue-clip-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("min-rescue-clipped-reads")
+                        .long("min-rescue-clipped-reads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::new("soft-clip-profile")
+                        .long("soft-clip-profile")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("end-trim-mismatch-rate")
+                        .long("end-trim-mismatch-rate")
+                        .value_parser(clap::value_parser!(f64)),
+                )
+                .arg(
+                    Arg::new("end-trim-min-length")
+                        .long("end-trim-min-length")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::new("minimizer-prefilter")
+                        .long("minimizer-prefilter")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("minimizer-kmer-size")
+                        .long("minimizer-kmer-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("15"),
+                )
+                .arg(
+                    Arg::new("minimizer-window-size")
+                        .long("minimizer-window-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
                 )
                 .arg(
-                    Arg::new("min-sv-qual")
-                        .long("min-sv-qual")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("3"),
+                    Arg::new("min-shared-minimizer-fraction")
+                        .long("min-shared-minimizer-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.05"),
                 )
-                .arg(Arg::new("do-not-call-svs").long("do-not-call-svs").action(clap::ArgAction::SetTrue))
                 .arg(
                     Arg::new("min-mapq")
                         .long("min-mapq")
@@ -2645,6 +6251,17 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .value_parser(clap::value_parser!(f64))
                         .default_value("25.0"),
                 )
+                .arg(
+                    Arg::new("hard-filter")
+                        .long("hard-filter")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("replicates")
+                        .long("replicates")
+                        .action(ArgAction::Append)
+                        .num_args(1..),
+                )
                 .arg(
                     Arg::new("qual-threshold")
                         .long("qual-threshold")
@@ -2674,819 +6291,689 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                     Arg::new("expected-mismatch-rate-for-read-disqualification")
                         .long("expected-mismatch-rate-for-read-disqualification")
                         .value_parser(clap::value_parser!(f64))
-                        .default_value("0.02")
-                        .hide(false),
-                )
-                .arg(
-                    Arg::new("allele-informative-reads-overlap-margin")
-                        .long("allele-informative-reads-overlap-margin")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("2")
-                        .hide(true),
-                )
-                .arg(
-                    Arg::new("disable-symmetric-hmm-normalizing")
-                        .long("disable-symmetric-hmm-normalizing")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
-                )
-                .arg(
-                    Arg::new("disable-cap-base-qualities-to-map-quality")
-                        .long("disable-cap-base-qualities-to-map-quality")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
-                )
-                .arg(
-                    Arg::new("disable-spanning-event-genotyping")
-                        .long("disable-spanning-event-genotyping")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
-                )
-                .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("no-zeros").long("no-zeros").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("allow-improper-pairs").long("allow-improper-pairs").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("include-secondary").long("include-secondary").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("exclude-supplementary").long("exclude-supplementary").action(clap::ArgAction::SetTrue))
-                .arg(
-                    Arg::new("ploidy")
-                        .long("ploidy")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("2"),
-                )
-                .arg(
-                    Arg::new("calculate-dnds")
-                        .long("calculate-dnds")
-                        .action(clap::ArgAction::SetTrue),
-                )
-                .arg(
-                    Arg::new("calculate-fst")
-                        .long("calculate-fst")
-                        .action(clap::ArgAction::SetTrue),
-                )
-                .arg(
-                    Arg::new("prodigal-params")
-                        .long("prodigal-params")
-                        .default_value("-p meta"),
-                )
-                .arg(
-                    Arg::new("limiting-interval")
-                        .long("limiting-interval")
-                        .required(false),
-                )
-                .arg(
-                    Arg::new("profile")
-                        .long("profile")
-                        .value_parser(["fast", "very-fast", "sensitive", "precise", "super-sensitive"])
-                        .required(false)
-                )
-                .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("verbose").short('v').long("verbose").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("quiet").long("quiet").action(clap::ArgAction::SetTrue)),
-        )
-        .subcommand(
-            Command::new("consensus")
-                .about("Generate consensus genomes across all provided samples")
-                .override_help(CONSENSUS_HELP.as_str())
-                .arg(
-                    Arg::new("full-help")
-                        .long("full-help")
-                        .action(clap::ArgAction::SetTrue),
-                )
-                .arg(
-                    Arg::new("full-help-roff")
-                        .long("full-help-roff")
-                        .action(clap::ArgAction::SetTrue),
-                )
-                .arg(
-                    Arg::new("bam-files")
-                        .short('b')
-                        .long("bam-files")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .num_args(1..),
-                )
-                .arg(
-                    Arg::new("sharded")
-                        .long("sharded")
-                        .required(false)
-                        .action(clap::ArgAction::SetTrue),
-                )
-                .arg(
-                    Arg::new("exclude-genomes-from-deshard")
-                        .long("exclude-genomes-from-deshard")
-                        .requires("sharded"),
-                )
-                .arg(
-                    Arg::new("read1")
-                        .short('1')
-                        .long("read1")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .requires("read2")
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "single",
-                            "coupled",
-                            "interleaved",
-                            "longreads",
-                            "longread-bam-files",
-                            "full-help",
-                            "full-help-roff",
-                        ])
-                        .conflicts_with("bam-files"),
-                )
-                .arg(
-                    Arg::new("read2")
-                        .short('2')
-                        .long("read2")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .requires("read1")
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "single",
-                            "coupled",
-                            "interleaved",
-                            "longreads",
-                            "longread-bam-files",
-                            "full-help",
-                            "full-help-roff",
-                        ])
-                        .conflicts_with("bam-files"),
-                )
-                .arg(
-                    Arg::new("coupled")
-                        .short('c')
-                        .long("coupled")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "read1",
-                            "coupled",
-                            "interleaved",
-                            "single",
-                            "longreads",
-                            "longread-bam-files",
-                            "full-help",
-                            "full-help-roff",
-                        ])
-                        .conflicts_with("bam-files"),
-                )
-                .arg(
-                    Arg::new("interleaved")
-                        .long("interleaved")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "read1",
-                            "coupled",
-                            "single",
-                            "interleaved",
-                            "longreads",
-                            "longread-bam-files",
-                            "full-help",
-                            "full-help-roff",
-                        ])
-                        .conflicts_with("bam-files"),
-                )
-                .arg(
-                    Arg::new("single")
-                        .long("single")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "read1",
-                            "coupled",
-                            "interleaved",
-                            "longreads",
-                            "longread-bam-files",
-                            "full-help",
-                            "full-help-roff",
-                        ])
-                        .conflicts_with("bam-files"),
-                )
-                .arg(
-                    Arg::new("longreads")
-                        .long("longreads")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "read1",
-                            "coupled",
-                            "interleaved",
-                            "single",
-                            "full-help", "full-help-roff",
-                            "longread-bam-files"
-                        ])
-                        .conflicts_with_all(&["longread-bam-files"]),
-                )
-                .arg(
-                    Arg::new("longread-bam-files")
-                        .short('l').long("longread-bam-files")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&[
-                            "bam-files",
-                            "read1",
-                            "coupled",
-                            "interleaved",
-                            "single",
-                            "full-help", "full-help-roff",
-                            "longreads",
-                        ])
-                        .conflicts_with_all(&["longreads"]),
-                )
-                .arg(
-                    Arg::new("genome-fasta-files")
-                        .short('f')
-                        .short_alias('r')
-                        .alias("reference")
-                        .long("genome-fasta-files")
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .required_unless_present_any(&["genome-fasta-directory", "full-help", "full-help-roff"]),
+                        .default_value("0.02")
+                        .hide(false),
                 )
                 .arg(
-                    Arg::new("genome-fasta-directory")
-                        .long("genome-fasta-directory")
-                        .short('d')
-                        .required_unless_present_any(&["genome-fasta-files", "full-help", "full-help-roff"]),
+                    Arg::new("allele-informative-reads-overlap-margin")
+                        .long("allele-informative-reads-overlap-margin")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("2")
+                        .hide(true),
                 )
                 .arg(
-                    Arg::new("genome-fasta-extension")
-                        .long("genome-fasta-extension")
-                        .short('x')
-                        .default_value("fna"),
+                    Arg::new("disable-symmetric-hmm-normalizing")
+                        .long("disable-symmetric-hmm-normalizing")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true),
                 )
                 .arg(
-                    Arg::new("bam-file-cache-directory")
-                        .long("bam-file-cache-directory"),
+                    Arg::new("disable-cap-base-qualities-to-map-quality")
+                        .long("disable-cap-base-qualities-to-map-quality")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true),
                 )
                 .arg(
-                    Arg::new("output-directory")
-                        .long("output-directory")
-                        .short('o')
-                        .default_value("./"),
+                    Arg::new("disable-spanning-event-genotyping")
+                        .long("disable-spanning-event-genotyping")
+                        .action(clap::ArgAction::SetTrue)
+                        .hide(true),
                 )
+                .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
                 .arg(
-                    Arg::new("features-vcf")
-                        .long("features-vcf")
-                        .required(false),
+                    Arg::new("disable-assembly-fallback")
+                        .long("disable-assembly-fallback")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("threads")
-                        .short('t').long("threads")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("10"),
+                    Arg::new("skip-reference-check")
+                        .long("skip-reference-check")
+                        .action(clap::ArgAction::SetTrue),
                 )
+                .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
                 .arg(
-                    Arg::new("parallel-genomes")
-                        .short('P').long("parallel-genomes")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("1"),
+                    Arg::new("sw-preset")
+                        .long("sw-preset")
+                        .value_parser(["short-read", "long-read"])
+                        .default_value("short-read"),
                 )
                 .arg(
-                    Arg::new("mapper")
-                        .short('p')
-                        .long("mapper")
-                        .value_parser(MAPPING_SOFTWARE_LIST.iter().collect::<Vec<_>>())
-                        .default_value(DEFAULT_MAPPING_SOFTWARE),
+                    Arg::new("sw-match")
+                        .long("sw-match")
+                        .value_parser(clap::value_parser!(i32).range(0..)),
                 )
                 .arg(
-                    Arg::new("longread-mapper")
-                        .long("longread-mapper")
-                        .value_parser(LONGREAD_MAPPING_SOFTWARE_LIST.iter().collect::<Vec<_>>())
-                        .default_value(DEFAULT_LONGREAD_MAPPING_SOFTWARE),
+                    Arg::new("sw-mismatch")
+                        .long("sw-mismatch")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
                 )
                 .arg(
-                    Arg::new("minimap2-params")
-                        .long("minimap2-params")
-                        .long("minimap2-parameters")
-                        .allow_hyphen_values(true),
+                    Arg::new("sw-gap-open")
+                        .long("sw-gap-open")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
                 )
                 .arg(
-                    Arg::new("minimap2-reference-is-index")
-                        .long("minimap2-reference-is-index"),
+                    Arg::new("sw-gap-extend")
+                        .long("sw-gap-extend")
+                        .value_parser(clap::value_parser!(i32).range(..=0))
+                        .allow_negative_numbers(true),
                 )
+                .arg(Arg::new("no-zeros").long("no-zeros").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("allow-improper-pairs").long("allow-improper-pairs").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("include-secondary").long("include-secondary").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("exclude-supplementary").long("exclude-supplementary").action(clap::ArgAction::SetTrue))
                 .arg(
-                    Arg::new("bwa-params")
-                        .long("bwa-params")
-                        .long("bwa-parameters")
-                        .allow_hyphen_values(true),
+                    Arg::new("split-alignment-policy")
+                        .long("split-alignment-policy")
+                        .value_parser([
+                            "count-all",
+                            "skip",
+                            "count-once-per-fragment",
+                            "weight-by-mapq",
+                        ])
+                        .default_value("count-all"),
                 )
                 .arg(
-                    Arg::new("high-memory")
-                        .long("high-memory")
-                        .hide(true),
+                    Arg::new("ploidy")
+                        .long("ploidy")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("2"),
                 )
                 .arg(
-                    Arg::new("keep-unmapped")
-                        .long("keep-unmapped")
-                        .action(clap::ArgAction::SetTrue)
-                        .requires("bam-file-cache-directory"),
+                    Arg::new("estimate-ploidy")
+                        .long("estimate-ploidy")
+                        .action(clap::ArgAction::SetTrue),
                 )
-                .arg(Arg::new("split-bams").long("split-bams").action(clap::ArgAction::SetTrue))
                 .arg(
-                    Arg::new("min-read-aligned-length")
-                        .long("min-read-aligned-length")
-                        .value_parser(clap::value_parser!(u32)),
+                    Arg::new("max-estimated-ploidy")
+                        .long("max-estimated-ploidy")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("6"),
                 )
                 .arg(
-                    Arg::new("min-read-percent-identity")
-                        .long("min-read-percent-identity")
-                        .value_parser(clap::value_parser!(f32)),
+                    Arg::new("calculate-dnds")
+                        .long("calculate-dnds")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-read-aligned-percent")
-                        .long("min-read-aligned-percent")
-                        .value_parser(clap::value_parser!(f32))
-                        .default_value("0.0"),
+                    Arg::new("allele-fraction-denominator")
+                        .long("allele-fraction-denominator")
+                        .value_parser(["informative", "all"])
+                        .default_value("informative"),
                 )
                 .arg(
-                    Arg::new("min-read-aligned-length-pair")
-                        .long("min-read-aligned-length-pair")
-                        .value_parser(clap::value_parser!(u32))
-                        .conflicts_with("allow-improper-pairs"),
+                    Arg::new("callable-sites")
+                        .long("callable-sites")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-read-percent-identity-pair")
-                        .long("min-read-percent-identity-pair")
-                        .value_parser(clap::value_parser!(f32))
-                        .conflicts_with("allow-improper-pairs"),
+                    Arg::new("callable-sites-min-samples")
+                        .long("callable-sites-min-samples")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1"),
                 )
                 .arg(
-                    Arg::new("min-read-aligned-percent-pair")
-                        .long("min-read-aligned-percent-pair")
-                        .value_parser(clap::value_parser!(f32))
-                        .conflicts_with("allow-improper-pairs"),
+                    Arg::new("calculate-sfs")
+                        .long("calculate-sfs")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-covered-fraction")
-                        .long("min-covered-fraction")
-                        .value_parser(clap::value_parser!(f32))
-                        .default_value("0.0"),
+                    Arg::new("reference-bias")
+                        .long("reference-bias")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-contig-size")
-                        .long("min-contig-size")
-                        .value_parser(clap::value_parser!(u64))
-                        .default_value("0"),
+                    Arg::new("correct-reference-bias")
+                        .long("correct-reference-bias")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("phred-scaled-global-read-mismapping-rate")
-                        .long("phred-scaled-global-read-mismapping-rate")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("45"),
+                    Arg::new("reference-bias-min-fraction")
+                        .long("reference-bias-min-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.2"),
                 )
                 .arg(
-                    Arg::new("pair-hmm-gap-continuation-penalty")
-                        .long("pair-hmm-gap-continuation-penalty")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("10"),
+                    Arg::new("calculate-fst")
+                        .long("calculate-fst")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("pcr-indel-model")
-                        .long("pcr-indel-model")
-                        .default_value("conservative")
-                        .value_parser(vec![
-                            "none",
-                            "None",
-                            "NONE",
-                            "hostile",
-                            "Hostile",
-                            "HOSTILE",
-                            "aggresive",
-                            "Agressive",
-                            "AGGRESSIVE",
-                            "conservative",
-                            "Conservative",
-                            "CONSERVATIVE",
-                        ]),
+                    Arg::new("sample-dendrogram")
+                        .long("sample-dendrogram")
+                        .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("heterozygosity-stdev")
-                        .long("heterozygosity-stdev")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("0.01"),
+                    Arg::new("prodigal-params")
+                        .long("prodigal-params")
+                        .default_value("-p meta"),
                 )
                 .arg(
-                    Arg::new("snp-heterozygosity")
-                        .long("snp-heterozygosity")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("0.001"),
+                    Arg::new("genetic-code")
+                        .long("genetic-code")
+                        .action(clap::ArgAction::Append)
+                        .num_args(1..)
+                        .value_parser(GeneticCodes::validate),
                 )
                 .arg(
-                    Arg::new("indel-heterozygosity")
-                        .long("indel-heterozygosity")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("0.000125"),
+                    Arg::new("marker-catalog")
+                        .long("marker-catalog")
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("standard-min-confidence-threshold-for-calling")
-                        .long("standard-min-confidence-threshold-for-calling")
-                        .short('C')
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("25.0"),
+                    Arg::new("limiting-interval")
+                        .long("limiting-interval")
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("genotype-assignment-method")
-                        .long("genotype-assignment-method")
-                        .default_value("UsePLsToAssign")
-                        .value_parser(vec![
-                            "UsePLsToAssign",
-                            "UsePosteriorProbabilities",
-                            "BestMatchToOriginal",
-                            "DoNotAssignGenotypes",
-                        ])
-                        .hide(true),
+                    Arg::new("seed")
+                        .long("seed")
+                        .value_parser(clap::value_parser!(u64))
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("use-posteriors-to-calculate-qual")
-                        .long("use-posteriors-to-calculate-qual")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("profile")
+                        .long("profile")
+                        .value_parser(["fast", "very-fast", "sensitive", "precise", "super-sensitive"])
+                        .required(false)
                 )
                 .arg(
-                    Arg::new("annotate-with-num-discovered-alleles")
-                        .long("annotate-with-num-discovered-alleles")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("mask-bed")
+                        .long("mask-bed")
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("active-probability-threshold")
-                        .long("active-probability-threshold")
-                        .value_parser(clap::value_parser!(f32))
-                        .default_value("0.002"),
+                    Arg::new("mask-kmer-size")
+                        .long("mask-kmer-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .conflicts_with("mask-bed")
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("min-assembly-region-size")
-                        .long("min-assembly-region-size")
+                    Arg::new("homopolymer-indel-filter")
+                        .long("homopolymer-indel-filter")
                         .value_parser(clap::value_parser!(usize))
-                        .default_value("50"),
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("max-assembly-region-size")
-                        .long("max-assembly-region-size")
+                    Arg::new("complexity-window-size")
+                        .long("complexity-window-size")
                         .value_parser(clap::value_parser!(usize))
-                        .default_value("300"),
+                        .default_value("64"),
                 )
                 .arg(
-                    Arg::new("kmer-sizes")
-                        .long("kmer-sizes")
-                        .short('k')
-                        .action(ArgAction::Append)
-                        .num_args(1..)
-                        .value_parser(clap::value_parser!(usize))
-                        .default_values(&["21", "33"]),
+                    Arg::new("low-complexity-filter")
+                        .long("low-complexity-filter")
+                        .value_parser(clap::value_parser!(f64))
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("disable-automatic-kmer-adjustment")
-                        .long("disable-automatic-kmer-adjustment")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("coverage-window-size")
+                        .long("coverage-window-size")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("1000"),
                 )
                 .arg(
-                    Arg::new("max-allowed-path-for-read-threading-assembler")
-                        .long("max-allowed-path-for-read-threading-assembler")
-                        .value_parser(clap::value_parser!(i32))
-                        .default_value("128")
-                        .hide(true),
+                    Arg::new("min-copy-number-ratio")
+                        .long("min-copy-number-ratio")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.5"),
                 )
                 .arg(
-                    Arg::new("dont-increase-kmer-sizes-for-cycles")
-                        .long("dont-increase-kmer-sizes-for-cycles")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("max-copy-number-ratio")
+                        .long("max-copy-number-ratio")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("1.5"),
                 )
                 .arg(
-                    Arg::new("allow-non-unique-kmers-in-ref")
-                        .long("allow-non-unique-kmers-in-ref")
+                    Arg::new("hybrid-assembly")
+                        .long("hybrid-assembly")
                         .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("debug-graph-transformations")
-                        .long("debug-graph-transformations")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("log-format")
+                        .long("log-format")
+                        .value_parser(["text", "json"])
+                        .default_value("text"),
                 )
                 .arg(
-                    Arg::new("do-not-recover-dangling-branches")
-                        .long("do-not-recover-dangling-branches")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("log-file")
+                        .long("log-file")
+                        .required(false),
                 )
                 .arg(
-                    Arg::new("do-not-run-physical-phasing")
-                        .long("do-not-run-physical-phasing")
+                    Arg::new("base-quality-recalibration")
+                        .long("base-quality-recalibration")
                         .action(clap::ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("recover-all-dangling-branches")
-                        .long("recover-all-dangling-branches")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("read-group-weights")
+                        .long("read-group-weights"),
+                )
+                .arg(
+                    Arg::new("consensus-ambiguity")
+                        .long("consensus-ambiguity")
+                        .value_parser(["none", "iupac", "n"])
+                        .default_value("none"),
+                )
+                .arg(
+                    Arg::new("consensus-min-allele-fraction")
+                        .long("consensus-min-allele-fraction")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.75"),
                 )
                 .arg(
-                    Arg::new("min-dangling-branch-length")
-                        .long("min-dangling-branch-length")
+                    Arg::new("consensus-min-depth")
+                        .long("consensus-min-depth")
                         .value_parser(clap::value_parser!(i32))
-                        .default_value("1"),
+                        .default_value("3"),
                 )
                 .arg(
-                    Arg::new("graph-output")
-                        .long("graph-output")
-                        .default_value("lorikeet_haplotype_caller"),
+                    Arg::new("consensus-samples")
+                        .long("consensus-samples")
+                        .num_args(1..),
                 )
+                .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("dry-run").long("dry-run").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("config").long("config"))
+                .arg(Arg::new("tmp-dir").long("tmp-dir"))
+                .arg(Arg::new("keep-temp").long("keep-temp").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("verbose").short('v').long("verbose").action(clap::ArgAction::SetTrue))
+                .arg(Arg::new("quiet").long("quiet").action(clap::ArgAction::SetTrue)),
+        )
+        .subcommand(all_command)
+        .subcommand(ani_command)
+        .subcommand(
+            Command::new("summarise")
+                .about("Summarizes ANI values of a given set of VCF files")
                 .arg(
-                    Arg::new("debug-graph-output")
-                        .long("debug-graph-output")
-                        .default_value("lorikeet_haplotype_caller_debug")
-                        .hide(true),
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("num-pruning-samples")
-                        .long("num-pruning-samples")
-                        .value_parser(clap::value_parser!(i32))
-                        .default_value("1"),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-prune-factor")
-                        .long("min-prune-factor")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("1"),
+                    Arg::new("vcfs")
+                        .long("vcfs")
+                        .short('i')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("disable-prune-factor-correction")
-                        .long("disable-prune-factor-correction")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("output")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
                 )
-                .arg(Arg::new("use-adaptive-pruning").long("use-adaptive-pruning").action(clap::ArgAction::SetTrue))
                 .arg(
-                    Arg::new("dont-use-soft-clipped-bases")
-                        .long("dont-use-soft-clipped-bases")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("threads")
+                        .long("threads")
+                        .short('t')
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("8"),
                 )
                 .arg(
-                    Arg::new("initial-error-rate-for-pruning")
-                        .long("initial-error-rate-for-pruning")
+                    Arg::new("qual-by-depth-filter")
+                        .long("qual-by-depth-filter")
                         .value_parser(clap::value_parser!(f64))
-                        .default_value("0.001"),
+                        .default_value("25.0"),
                 )
                 .arg(
-                    Arg::new("pruning-seeding-log-odds-threshold")
-                        .long("pruning-seeding-log-odds-threshold")
+                    Arg::new("qual-threshold")
+                        .long("qual-threshold")
                         .value_parser(clap::value_parser!(f64))
-                        .default_value("4.0")
-                        .hide(true),
+                        .default_value("150.0"),
                 )
                 .arg(
-                    Arg::new("pruning-log-odds-threshold")
-                        .long("pruning-log-odds-threshold")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("1.0"),
+                    Arg::new("depth-per-sample-filter")
+                        .long("depth-per-sample-filter")
+                        .value_parser(clap::value_parser!(i64))
+                        .default_value("5"),
                 )
                 .arg(
-                    Arg::new("max-unpruned-variants")
-                        .long("max-unpruned-variants")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("100"),
+                    Arg::new("sample-dendrogram")
+                        .long("sample-dendrogram")
+                        .action(ArgAction::SetTrue),
                 )
+                .arg(Arg::new("verbose").short('v').long("verbose").action(ArgAction::SetTrue)),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("concordance"))
+                .about("Calculates pairwise genotype concordance between samples of VCF files")
                 .arg(
-                    Arg::new("max-input-depth")
-                        .long("max-input-depth")
-                        .short('i')
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("200000"),
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("contig-end-exclusion")
-                        .long("contig-end-exclusion")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("0"),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("max-prob-propagation-distance")
-                        .long("max-prob-propagation-distance")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("50"),
+                    Arg::new("vcfs")
+                        .long("vcfs")
+                        .short('i')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
+                .arg(Arg::new("comparison-vcf").long("comparison-vcf"))
                 .arg(
-                    Arg::new("use-linked-debruijn-graph")
-                        .long("use-linked-debruijn-graph")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("output")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
                 )
                 .arg(
-                    Arg::new("error-correct-reads")
-                        .long("error-correct-reads")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("depth-per-sample-filter")
+                        .long("depth-per-sample-filter")
+                        .value_parser(clap::value_parser!(i32))
+                        .default_value("5"),
                 )
                 .arg(
-                    Arg::new("kmer-length-for-read-error-correction")
-                        .long("kmer-length-for-read-error-correction")
+                    Arg::new("min-compared-sites")
+                        .long("min-compared-sites")
                         .value_parser(clap::value_parser!(usize))
-                        .default_value("25")
-                        .hide(true),
+                        .default_value("20"),
                 )
                 .arg(
-                    Arg::new("min-observations-for-kmers-to-be-solid")
-                        .long("min-observations-for-kmers-to-be-solid")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("20")
-                        .hide(true),
+                    Arg::new("concordance-threshold")
+                        .long("concordance-threshold")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.99"),
+                ),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("phylo"))
+                .about("Builds core SNP alignments and neighbor joining trees from lorikeet VCF files")
+                .arg(
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("max-mnp-distance")
-                        .long("max-mnp-distance")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("0"),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-observation-for-kmer-to-be-solid")
-                        .long("min-observation-for-kmer-to-be-solid")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("20")
-                        .hide(true),
+                    Arg::new("vcfs")
+                        .long("vcfs")
+                        .short('i')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("enable-legacy-graph-cycle-detection")
-                        .long("enable-legacy-graph-cycle-detection")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("output")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
                 )
                 .arg(
-                    Arg::new("min-matching-bases-to-dangling-end-recovery")
-                        .long("min-matching-bases-to-dangling-end-recovery")
+                    Arg::new("mode")
+                        .long("mode")
+                        .value_parser(["samples", "strains"])
+                        .default_value("samples"),
+                )
+                .arg(
+                    Arg::new("depth-per-sample-filter")
+                        .long("depth-per-sample-filter")
                         .value_parser(clap::value_parser!(i32))
-                        .default_value("-1")
-                        .hide(true),
+                        .default_value("5"),
                 )
                 .arg(
-                    Arg::new("assembly-region-padding")
-                        .long("assembly-region-padding")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("100"),
+                    Arg::new("max-missing")
+                        .long("max-missing")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.0"),
                 )
                 .arg(
-                    Arg::new("indel-padding-for-genotyping")
-                        .long("indel-padding-for-genotyping")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("75")
-                        .hide(true),
+                    Arg::new("tree")
+                        .long("tree")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("graph-inspect"))
+                .about("Summarises assembly graphs written by --dump-assembly-graphs")
+                .arg(
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("str-padding-for-genotyping")
-                        .long("str-padding-for-genotyping")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("75")
-                        .hide(true),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("snp-padding-for-genotyping")
-                        .long("snp-padding-for-genotyping")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("20")
-                        .hide(true),
+                    Arg::new("graphs")
+                        .long("graphs")
+                        .short('i')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
+                .arg(Arg::new("output-file").long("output-file").short('o')),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("inspect"))
+                .about("Shows the reads and haplotypes supporting the alleles of a variant")
                 .arg(
-                    Arg::new("max-extension-into-region-padding")
-                        .long("max-extension-into-region-padding")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("25")
-                        .hide(true),
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("soft-clip-low-quality-ends")
-                        .long("soft-clip-low-quality-ends")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("trim-min")
-                        .long("trim-min")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("0.00"),
+                    Arg::new("reference")
+                        .long("reference")
+                        .short('r')
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("trim-max")
-                        .long("trim-max")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("1.00"),
+                    Arg::new("bam-files")
+                        .long("bam-files")
+                        .short('b')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("mapping-quality-threshold-for-genotyping")
-                        .long("mapping-quality-threshold-for-genotyping")
-                        .value_parser(clap::value_parser!(u8))
+                    Arg::new("region")
+                        .long("region")
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
+                )
+                .arg(Arg::new("vcf").long("vcf").short('v'))
+                .arg(
+                    Arg::new("flank")
+                        .long("flank")
+                        .value_parser(clap::value_parser!(usize))
                         .default_value("20"),
                 )
                 .arg(
-                    Arg::new("min-sv-qual")
-                        .long("min-sv-qual")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("3"),
+                    Arg::new("max-reads")
+                        .long("max-reads")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("50"),
                 )
-                .arg(Arg::new("do-not-call-svs").long("do-not-call-svs").action(clap::ArgAction::SetTrue))
                 .arg(
                     Arg::new("min-mapq")
                         .long("min-mapq")
                         .value_parser(clap::value_parser!(u8))
-                        .default_value("20"),
+                        .default_value("0"),
                 )
+                .arg(Arg::new("output-file").long("output-file").short('o')),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("apply-model"))
+                .about("Scores and filters the records of a VCF file with a logistic model")
                 .arg(
-                    Arg::new("min-long-read-size")
-                        .long("min-long-read-size")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("1500"),
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-long-read-average-base-qual")
-                        .long("min-long-read-average-base-qual")
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("20"),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("min-base-quality")
-                        .long("min-base-quality")
-                        .short('q')
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("10"),
+                    Arg::new("vcf")
+                        .long("vcf")
+                        .short('v')
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("base-quality-score-threshold")
-                        .long("base-quality-score-threshold")
-                        .value_parser(clap::value_parser!(u8))
-                        .default_value("18"),
+                    Arg::new("model")
+                        .long("model")
+                        .short('m')
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("qual-by-depth-filter")
-                        .long("qual-by-depth-filter")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("25.0"),
+                    Arg::new("output-file")
+                        .long("output-file")
+                        .short('o')
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
                 )
                 .arg(
-                    Arg::new("qual-threshold")
-                        .long("qual-threshold")
+                    Arg::new("threshold")
+                        .long("threshold")
                         .value_parser(clap::value_parser!(f64))
-                        .default_value("150.0"),
+                        .default_value("0.5"),
+                ),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("gather"))
+                .about("Merges the shard VCF files of a scattered lorikeet call run")
+                .arg(
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("depth-per-sample-filter")
-                        .long("depth-per-sample-filter")
-                        .value_parser(clap::value_parser!(i64))
-                        .default_value("5"),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("disable-dynamic-read-disqualification-for-genotyping")
-                        .long("disable-dynamic-read-disqualification-for-genotyping")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(false),
+                    Arg::new("output")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
                 )
                 .arg(
-                    Arg::new("dynamic-read-disqualification-threshold")
-                        .long("dynamic-read-disqualification-threshold")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("1.0")
-                        .hide(false),
+                    Arg::new("remove-shards")
+                        .long("remove-shards")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("add-sample"))
+                .about("Adds new samples to the output of a previous lorikeet run")
+                .arg(
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("expected-mismatch-rate-for-read-disqualification")
-                        .long("expected-mismatch-rate-for-read-disqualification")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("0.02")
-                        .hide(false),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("allele-informative-reads-overlap-margin")
-                        .long("allele-informative-reads-overlap-margin")
+                    Arg::new("output-directory")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
+                )
+                .arg(
+                    Arg::new("genome-fasta-files")
+                        .long("genome-fasta-files")
+                        .short('r')
+                        .alias("reference")
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
+                )
+                .arg(
+                    Arg::new("bam-files")
+                        .long("bam-files")
+                        .short('b')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&[
+                            "longread-bam-files",
+                            "full-help",
+                            "full-help-roff",
+                        ]),
+                )
+                .arg(
+                    Arg::new("longread-bam-files")
+                        .long("longread-bam-files")
+                        .short('l')
+                        .action(ArgAction::Append)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("threads")
+                        .long("threads")
+                        .short('t')
                         .value_parser(clap::value_parser!(usize))
-                        .default_value("2")
-                        .hide(true),
+                        .default_value("10"),
                 )
                 .arg(
-                    Arg::new("disable-symmetric-hmm-normalizing")
-                        .long("disable-symmetric-hmm-normalizing")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("call-args")
+                        .action(ArgAction::Append)
+                        .num_args(0..)
+                        .last(true),
+                ),
+        )
+        .subcommand(
+            add_clap_verbosity_flags(Command::new("combine"))
+                .about("Jointly genotypes the samples of multiple lorikeet VCF files")
+                .arg(
+                    Arg::new("full-help")
+                        .long("full-help")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("disable-cap-base-qualities-to-map-quality")
-                        .long("disable-cap-base-qualities-to-map-quality")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("full-help-roff")
+                        .long("full-help-roff")
+                        .action(ArgAction::SetTrue),
                 )
                 .arg(
-                    Arg::new("disable-spanning-event-genotyping")
-                        .long("disable-spanning-event-genotyping")
-                        .action(clap::ArgAction::SetTrue)
-                        .hide(true),
+                    Arg::new("vcfs")
+                        .long("vcfs")
+                        .short('i')
+                        .action(ArgAction::Append)
+                        .num_args(1..)
+                        .required_unless_present_any(&["full-help", "full-help-roff"]),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output-directory")
+                        .short('o')
+                        .default_value("./"),
+                )
+                .arg(
+                    Arg::new("output-name")
+                        .long("output-name")
+                        .default_value("combined"),
                 )
-                .arg(Arg::new("disable-optimizations").long("disable-optimizations").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("disable-avx").long("disable-avx").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("no-zeros").long("no-zeros").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("allow-improper-pairs").long("allow-improper-pairs").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("include-secondary").long("include-secondary").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("exclude-supplementary").long("exclude-supplementary").action(clap::ArgAction::SetTrue))
                 .arg(
                     Arg::new("ploidy")
                         .long("ploidy")
@@ -3494,38 +6981,56 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("2"),
                 )
                 .arg(
-                    Arg::new("calculate-dnds")
-                        .long("calculate-dnds")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("standard-min-confidence-threshold-for-calling")
+                        .long("standard-min-confidence-threshold-for-calling")
+                        .short('C')
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("25.0"),
                 )
                 .arg(
-                    Arg::new("calculate-fst")
-                        .long("calculate-fst")
-                        .action(clap::ArgAction::SetTrue),
+                    Arg::new("heterozygosity-stdev")
+                        .long("heterozygosity-stdev")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.01"),
                 )
                 .arg(
-                    Arg::new("prodigal-params")
-                        .long("prodigal-params")
-                        .default_value("-p meta"),
+                    Arg::new("snp-heterozygosity")
+                        .long("snp-heterozygosity")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.001"),
                 )
                 .arg(
-                    Arg::new("limiting-interval")
-                        .long("limiting-interval")
-                        .required(false),
+                    Arg::new("indel-heterozygosity")
+                        .long("indel-heterozygosity")
+                        .value_parser(clap::value_parser!(f64))
+                        .default_value("0.000125"),
                 )
                 .arg(
-                    Arg::new("profile")
-                        .long("profile")
-                        .value_parser(["fast", "very-fast", "sensitive", "precise", "super-sensitive"])
-                        .required(false)
+                    Arg::new("genotype-assignment-method")
+                        .long("genotype-assignment-method")
+                        .default_value("UsePLsToAssign")
+                        .value_parser(vec![
+                            "UsePLsToAssign",
+                            "UsePosteriorProbabilities",
+                            "BestMatchToOriginal",
+                            "DoNotAssignGenotypes",
+                        ])
+                        .hide(true),
                 )
-                .arg(Arg::new("force").long("force").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("verbose").short('v').long("verbose").action(clap::ArgAction::SetTrue))
-                .arg(Arg::new("quiet").long("quiet").action(clap::ArgAction::SetTrue)),
+                .arg(
+                    Arg::new("use-posteriors-to-calculate-qual")
+                        .long("use-posteriors-to-calculate-qual")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("annotate-with-num-discovered-alleles")
+                        .long("annotate-with-num-discovered-alleles")
+                        .action(ArgAction::SetTrue),
+                ),
         )
         .subcommand(
-            Command::new("summarise")
-                .about("Summarizes ANI values of a given set of VCF files")
+            add_clap_verbosity_flags(Command::new("merge-vcfs"))
+                .about("Merges the sites and samples of lorikeet VCF files of the same genome")
                 .arg(
                     Arg::new("full-help")
                         .long("full-help")
@@ -3551,31 +7056,22 @@ Rhys J. P. Newell <rhys.newell near hdr.qut.edu.au>
                         .default_value("./"),
                 )
                 .arg(
-                    Arg::new("threads")
-                        .long("threads")
-                        .short('t')
-                        .value_parser(clap::value_parser!(usize))
-                        .default_value("8"),
+                    Arg::new("output-name")
+                        .long("output-name")
+                        .default_value("merged"),
                 )
                 .arg(
-                    Arg::new("qual-by-depth-filter")
-                        .long("qual-by-depth-filter")
-                        .value_parser(clap::value_parser!(f64))
-                        .default_value("25.0"),
+                    Arg::new("ploidy")
+                        .long("ploidy")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("2"),
                 )
                 .arg(
-                    Arg::new("qual-threshold")
-                        .long("qual-threshold")
+                    Arg::new("min-id-overlap")
+                        .long("min-id-overlap")
                         .value_parser(clap::value_parser!(f64))
-                        .default_value("150.0"),
-                )
-                .arg(
-                    Arg::new("depth-per-sample-filter")
-                        .long("depth-per-sample-filter")
-                        .value_parser(clap::value_parser!(i64))
-                        .default_value("5"),
-                )
-                .arg(Arg::new("verbose").short('v').long("verbose").action(ArgAction::SetTrue)),
+                        .default_value("0.5"),
+                ),
         )
         .subcommand(
             add_clap_verbosity_flags(Command::new("shell-completion"))
@@ -0,0 +1,286 @@
+use ndarray::Array2;
+use rust_htslib::bcf::Read;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// A variant site identified by contig name, start position, and reference allele so that
+/// sites can be matched between VCF files with differing contig ids
+pub type SiteKey = (String, usize, Vec<u8>);
+
+/// The consensus genotype of each sample at each variant site. The consensus genotype is the
+/// allele with the highest depth in the sample, or None if the sample lacks the depth to make
+/// a confident call
+#[derive(Debug, Clone)]
+pub struct SiteGenotypes {
+    pub sample_names: Vec<String>,
+    pub sites: HashMap<SiteKey, Vec<Option<Vec<u8>>>>,
+}
+
+impl SiteGenotypes {
+    pub fn from_contexts(
+        contexts: &[VariantContext],
+        contig_names: &[String],
+        sample_names: Vec<String>,
+        min_depth: i32,
+    ) -> Self {
+        let mut sites = HashMap::with_capacity(contexts.len());
+        for vc in contexts {
+            let genotypes = (0..sample_names.len())
+                .map(|sample_index| {
+                    let depth: i32 = vc.genotypes.genotypes()[sample_index].ad.iter().sum();
+                    if depth < min_depth {
+                        None
+                    } else {
+                        vc.get_consensus_allele(sample_index)
+                            .map(|allele| allele.bases)
+                    }
+                })
+                .collect::<Vec<Option<Vec<u8>>>>();
+
+            sites.insert(
+                (
+                    contig_names[vc.loc.tid].clone(),
+                    vc.loc.start,
+                    vc.get_reference().bases.clone(),
+                ),
+                genotypes,
+            );
+        }
+
+        Self {
+            sample_names,
+            sites,
+        }
+    }
+
+    /// Reads the consensus genotypes from a VCF file. The VCF must provide the AD format field,
+    /// as produced by lorikeet
+    pub fn from_vcf(vcf_path: &str, min_depth: i32) -> Result<Self, BirdToolError> {
+        let reader = VcfInput::open(vcf_path)?;
+        let header = reader.header();
+        let sample_names = header
+            .samples()
+            .into_iter()
+            .map(|s| String::from_utf8_lossy(s).to_string())
+            .collect::<Vec<String>>();
+        let contig_names = (0..header.contig_count())
+            .map(|rid| String::from_utf8_lossy(header.rid2name(rid).unwrap()).to_string())
+            .collect::<Vec<String>>();
+
+        let contexts = VariantContext::process_vcf_from_path(vcf_path, true);
+        Ok(Self::from_contexts(
+            &contexts,
+            &contig_names,
+            sample_names,
+            min_depth,
+        ))
+    }
+}
+
+/// How a pair of samples was flagged after comparing their genotypes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcordanceFlag {
+    /// Nothing notable about this pair
+    Unflagged,
+    /// Too few sites were called in both samples to compare them
+    InsufficientSites,
+    /// Two samples that are expected to differ share nearly all genotypes
+    ProbableDuplicate,
+    /// Samples with the same name that were expected to match do not
+    Discordant,
+    /// Samples with the same name that match as expected
+    Match,
+    /// A sample matches a differently named sample while its same named sample is discordant
+    ProbableSwap,
+}
+
+impl ConcordanceFlag {
+    pub fn to_key(&self) -> &str {
+        match self {
+            Self::Unflagged => ".",
+            Self::InsufficientSites => "insufficient_sites",
+            Self::ProbableDuplicate => "probable_duplicate",
+            Self::Discordant => "discordant",
+            Self::Match => "match",
+            Self::ProbableSwap => "probable_swap",
+        }
+    }
+}
+
+/// Pairwise genotype concordance between two sets of samples. When comparing the samples
+/// within a single run, both sets are the same
+pub struct GenotypeConcordance {
+    pub query_names: Vec<String>,
+    pub target_names: Vec<String>,
+    pub compared_sites: Array2<usize>,
+    pub concordant_sites: Array2<usize>,
+    within_run: bool,
+}
+
+impl GenotypeConcordance {
+    /// Compares every sample within a single run against each other
+    pub fn within_run(genotypes: &SiteGenotypes) -> Self {
+        let mut concordance = Self::compare(genotypes, genotypes);
+        concordance.within_run = true;
+        concordance
+    }
+
+    /// Compares the samples of the query against the samples of the target. Only sites present
+    /// in both with a confident genotype in both samples are compared
+    pub fn compare(query: &SiteGenotypes, target: &SiteGenotypes) -> Self {
+        let n_query = query.sample_names.len();
+        let n_target = target.sample_names.len();
+        let mut compared_sites = Array2::<usize>::zeros((n_query, n_target));
+        let mut concordant_sites = Array2::<usize>::zeros((n_query, n_target));
+
+        for (site, query_genotypes) in query.sites.iter() {
+            let target_genotypes = match target.sites.get(site) {
+                Some(target_genotypes) => target_genotypes,
+                None => continue,
+            };
+
+            for (i, query_genotype) in query_genotypes.iter().enumerate() {
+                let query_genotype = match query_genotype {
+                    Some(query_genotype) => query_genotype,
+                    None => continue,
+                };
+                for (j, target_genotype) in target_genotypes.iter().enumerate() {
+                    if let Some(target_genotype) = target_genotype {
+                        compared_sites[[i, j]] += 1;
+                        if query_genotype == target_genotype {
+                            concordant_sites[[i, j]] += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            query_names: query.sample_names.clone(),
+            target_names: target.sample_names.clone(),
+            compared_sites,
+            concordant_sites,
+            within_run: false,
+        }
+    }
+
+    /// Fraction of compared sites at which both samples share the same consensus genotype
+    pub fn concordance(&self, i: usize, j: usize) -> Option<f64> {
+        let compared = self.compared_sites[[i, j]];
+        if compared == 0 {
+            None
+        } else {
+            Some(self.concordant_sites[[i, j]] as f64 / compared as f64)
+        }
+    }
+
+    fn is_concordant(&self, i: usize, j: usize, min_sites: usize, threshold: f64) -> bool {
+        self.compared_sites[[i, j]] >= min_sites
+            && self.concordance(i, j).unwrap_or(0.0) >= threshold
+    }
+
+    /// Flags each pair of samples. Within a run, any two samples sharing at least `threshold`
+    /// of their genotypes are probable duplicates. When comparing against another set of samples,
+    /// samples with the same name are expected to match and a sample whose same named partner is
+    /// discordant but matches a different sample is a probable swap
+    pub fn flag_pairs(&self, min_sites: usize, threshold: f64) -> Vec<(usize, usize, ConcordanceFlag)> {
+        let mut flags = Vec::new();
+        for i in 0..self.query_names.len() {
+            let expected_partner = if self.within_run {
+                None
+            } else {
+                self.target_names
+                    .iter()
+                    .position(|name| name == &self.query_names[i])
+            };
+            let expected_partner_discordant = match expected_partner {
+                Some(j) => !self.is_concordant(i, j, min_sites, threshold),
+                None => false,
+            };
+
+            let first_target = if self.within_run { i + 1 } else { 0 };
+            for j in first_target..self.target_names.len() {
+                let flag = if self.compared_sites[[i, j]] < min_sites {
+                    ConcordanceFlag::InsufficientSites
+                } else if Some(j) == expected_partner {
+                    if expected_partner_discordant {
+                        ConcordanceFlag::Discordant
+                    } else {
+                        ConcordanceFlag::Match
+                    }
+                } else if self.is_concordant(i, j, min_sites, threshold) {
+                    if expected_partner_discordant {
+                        ConcordanceFlag::ProbableSwap
+                    } else {
+                        ConcordanceFlag::ProbableDuplicate
+                    }
+                } else {
+                    ConcordanceFlag::Unflagged
+                };
+                flags.push((i, j, flag));
+            }
+        }
+
+        flags
+    }
+
+    /// Writes the pairwise concordance table and warns about any probable duplicates or swaps
+    pub fn write_tsv(
+        &self,
+        file_name: &str,
+        min_sites: usize,
+        threshold: f64,
+    ) -> Result<(), BirdToolError> {
+        let mut file = File::create(file_name).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", file_name, e))
+        })?;
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", file_name, e))
+        };
+        writeln!(file, "##source=lorikeet-v{}", env!("CARGO_PKG_VERSION")).map_err(write_error)?;
+        writeln!(
+            file,
+            "sample_1\tsample_2\tcompared_sites\tconcordant_sites\tconcordance\tflag"
+        )
+        .map_err(write_error)?;
+
+        for (i, j, flag) in self.flag_pairs(min_sites, threshold) {
+            let concordance = match self.concordance(i, j) {
+                Some(concordance) => format!("{:.4}", concordance),
+                None => "NA".to_string(),
+            };
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                self.query_names[i],
+                self.target_names[j],
+                self.compared_sites[[i, j]],
+                self.concordant_sites[[i, j]],
+                concordance,
+                flag.to_key()
+            )
+            .map_err(write_error)?;
+
+            match flag {
+                ConcordanceFlag::ProbableDuplicate
+                | ConcordanceFlag::ProbableSwap
+                | ConcordanceFlag::Discordant => warn!(
+                    "Samples {} and {} flagged as {} with concordance {}",
+                    self.query_names[i],
+                    self.target_names[j],
+                    flag.to_key(),
+                    concordance
+                ),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,2 @@
+pub mod genotype_concordance;
+pub mod replicate_calibration;
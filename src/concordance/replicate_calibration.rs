@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// Alternate allele calls and the calls not made in the paired replicate, for variants within a
+/// range of QUAL values
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CalibrationBin {
+    pub calls: usize,
+    pub discordant: usize,
+}
+
+impl CalibrationBin {
+    /// Fraction of calls not made in the other replicate. A pseudocount keeps the rate above
+    /// zero for bins without discordant calls
+    pub fn error_rate(&self) -> f64 {
+        (self.discordant as f64 + 0.5) / (self.calls as f64 + 1.0)
+    }
+}
+
+/// Empirical calibration of variant QUALs from technical replicates. A call made in one replicate
+/// of a pair but not the other counts as a false positive, and each QUAL bin is given the QUAL of
+/// its false positive rate. The original QUAL is kept in OQUAL, and bins with fewer than
+/// `MIN_CALLS` calls are left as they are.
+#[derive(Debug, Clone)]
+pub struct ReplicateCalibration {
+    pub replicate_groups: Vec<Vec<usize>>,
+    pub min_depth: i32,
+    pub bins: Vec<CalibrationBin>,
+}
+
+impl ReplicateCalibration {
+    pub const BIN_WIDTH: f64 = 10.0;
+    pub const N_BINS: usize = 50;
+    pub const MIN_CALLS: usize = 20;
+
+    pub fn new(replicate_groups: Vec<Vec<usize>>, min_depth: i32) -> Self {
+        Self {
+            replicate_groups,
+            min_depth,
+            bins: vec![CalibrationBin::default(); Self::N_BINS],
+        }
+    }
+
+    /// Replicate calibration requested on the command line, or None if no replicates were given.
+    /// Each value of --replicates is a comma separated group of sample names
+    pub fn from_args(
+        args: &clap::ArgMatches,
+        sample_names: &[&str],
+    ) -> Result<Option<Self>, BirdToolError> {
+        let groups = match args.try_get_many::<String>("replicates").ok().flatten() {
+            Some(groups) => groups.cloned().collect::<Vec<String>>(),
+            None => return Ok(None),
+        };
+        let replicate_groups = Self::parse_groups(&groups, sample_names)?;
+        let min_depth = *args.get_one::<i64>("depth-per-sample-filter").unwrap() as i32;
+        Ok(Some(Self::new(replicate_groups, min_depth)))
+    }
+
+    /// Converts groups of comma separated sample names into groups of sample indices
+    pub fn parse_groups(
+        groups: &[String],
+        sample_names: &[&str],
+    ) -> Result<Vec<Vec<usize>>, BirdToolError> {
+        groups
+            .iter()
+            .map(|group| {
+                let indices = group
+                    .split(',')
+                    .map(|name| name.trim())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        sample_names
+                            .iter()
+                            .position(|sample_name| *sample_name == name)
+                            .ok_or_else(|| {
+                                BirdToolError::DebugError(format!(
+                                    "Replicate sample {} is not one of the samples {:?}",
+                                    name, sample_names
+                                ))
+                            })
+                    })
+                    .collect::<Result<Vec<usize>, BirdToolError>>()?;
+                if indices.len() < 2 {
+                    return Err(BirdToolError::DebugError(format!(
+                        "Replicate group {} must contain at least two samples",
+                        group
+                    )));
+                }
+                Ok(indices)
+            })
+            .collect()
+    }
+
+    fn bin_index(qual: f64) -> usize {
+        ((qual.max(0.0) / Self::BIN_WIDTH) as usize).min(Self::N_BINS - 1)
+    }
+
+    /// Whether a sample has enough depth to be compared and whether it calls an alternate allele
+    fn sample_call(&self, vc: &VariantContext, sample_index: usize) -> Option<bool> {
+        let genotype = vc.genotypes.genotypes().get(sample_index)?;
+        let depth: i32 = genotype.ad.iter().sum();
+        if depth < self.min_depth {
+            return None;
+        }
+        Some(genotype.ad.iter().skip(1).any(|ad| *ad >= self.min_depth))
+    }
+
+    /// Counts the calls and discordant calls of every replicate pair at a site
+    pub fn add_context(&mut self, vc: &VariantContext) {
+        let bin = Self::bin_index(vc.get_phred_scaled_qual());
+        for group in self.replicate_groups.iter() {
+            for (i, first) in group.iter().enumerate() {
+                for second in group.iter().skip(i + 1) {
+                    let (first_call, second_call) =
+                        match (self.sample_call(vc, *first), self.sample_call(vc, *second)) {
+                            (Some(first_call), Some(second_call)) => (first_call, second_call),
+                            _ => continue,
+                        };
+                    self.bins[bin].calls += first_call as usize + second_call as usize;
+                    if first_call != second_call {
+                        self.bins[bin].discordant += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn estimate(&mut self, contexts: &[VariantContext]) {
+        for vc in contexts.iter().filter(|vc| !vc.is_masked()) {
+            self.add_context(vc);
+        }
+    }
+
+    /// The false positive rate of the run across all QUALs, or None if no calls were compared
+    pub fn false_positive_rate(&self) -> Option<f64> {
+        let calls = self.bins.iter().map(|bin| bin.calls).sum::<usize>();
+        let discordant = self.bins.iter().map(|bin| bin.discordant).sum::<usize>();
+        if calls == 0 {
+            None
+        } else {
+            Some(discordant as f64 / calls as f64)
+        }
+    }
+
+    /// The empirical QUAL of variants with the given QUAL, or None if there are too few calls
+    /// in its bin
+    pub fn calibrated_qual(&self, qual: f64) -> Option<f64> {
+        let bin = &self.bins[Self::bin_index(qual)];
+        if bin.calls < Self::MIN_CALLS {
+            None
+        } else {
+            Some(-10.0 * bin.error_rate().log10())
+        }
+    }
+
+    /// Replaces the QUAL of each variant with its empirical QUAL, keeping the original in the
+    /// OQUAL INFO field. Returns the number of calibrated variants
+    pub fn calibrate(&self, contexts: &mut [VariantContext]) -> usize {
+        let mut calibrated = 0;
+        for vc in contexts.iter_mut() {
+            let qual = vc.get_phred_scaled_qual();
+            if let Some(calibrated_qual) = self.calibrated_qual(qual) {
+                vc.set_attribute(
+                    VariantAnnotations::OriginalQual.to_key().to_string(),
+                    AttributeObject::f64(qual),
+                );
+                vc.log10_p_error(calibrated_qual / -10.0);
+                calibrated += 1;
+            }
+        }
+        calibrated
+    }
+
+    /// Writes the calls, discordant calls, error rate and empirical QUAL of each QUAL bin
+    pub fn write_report(&self, path: &str) -> Result<(), BirdToolError> {
+        let file = File::create(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", path, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+        let write_error =
+            |e: std::io::Error| BirdToolError::IOError(format!("Unable to write to {}: {}", path, e));
+
+        writeln!(
+            writer,
+            "qual_start\tqual_end\tcalls\tdiscordant_calls\terror_rate\tcalibrated_qual"
+        )
+        .map_err(write_error)?;
+        for (index, bin) in self.bins.iter().enumerate() {
+            if bin.calls == 0 && bin.discordant == 0 {
+                continue;
+            }
+            let qual_start = index as f64 * Self::BIN_WIDTH;
+            let qual_end = if index == Self::N_BINS - 1 {
+                "inf".to_string()
+            } else {
+                format!("{}", qual_start + Self::BIN_WIDTH)
+            };
+            let calibrated_qual = match self.calibrated_qual(qual_start) {
+                Some(qual) => format!("{:.2}", qual),
+                None => "NA".to_string(),
+            };
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{:.6}\t{}",
+                qual_start,
+                qual_end,
+                bin.calls,
+                bin.discordant,
+                bin.error_rate(),
+                calibrated_qual
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
@@ -1,13 +1,17 @@
 use bio::alphabets::dna;
 use bio_types::strand::Strand;
 use itertools::{izip, Itertools};
-use rust_htslib::bcf::Read;
-use std::collections::HashMap;
+use rust_htslib::bcf::record::Numeric;
+use rust_htslib::bcf::{Read, Record};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use crate::model::variant_context::VariantContext;
 use crate::model::variant_context_utils::VariantContextUtils;
+use crate::reference::genome_separator::GenomeSeparator;
 use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
 use crate::utils::utils::{mean, std_deviation};
+use crate::utils::vcf_input::VcfInput;
 
 #[allow(dead_code)]
 pub struct GeneInfo {
@@ -31,36 +35,187 @@ pub struct NCBITable {
 }
 
 impl NCBITable {
+    const BASE1: &'static str = "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG";
+    const BASE2: &'static str = "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG";
+    const BASE3: &'static str = "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG";
+
+    /// NCBI translation table ids that lorikeet can use
+    pub const SUPPORTED_TABLES: [usize; 13] = [1, 2, 3, 4, 5, 6, 9, 10, 11, 12, 13, 14, 25];
+
+    pub fn is_supported(table_id: usize) -> bool {
+        Self::SUPPORTED_TABLES.contains(&table_id)
+    }
+
     // get translation tables in NCBI format
     // Kind of lazy storing and then converting every time but would take way too much time
     // to write out each table into CodonTable format by hand
     fn get_translation_table(table_id: usize) -> NCBITable {
-        match table_id {
-            1 => NCBITable {
-                aas: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG".to_owned(),
-                starts: "---M------**--*----M---------------M----------------------------"
-                    .to_owned(),
-                base1: "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG"
-                    .to_owned(),
-                base2: "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG"
-                    .to_owned(),
-                base3: "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG"
-                    .to_owned(),
-            },
-            11 => NCBITable {
-                aas: "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG".to_owned(),
-                starts: "---M------**--*----M------------MMMM---------------M------------"
-                    .to_owned(),
-                base1: "TTTTTTTTTTTTTTTTCCCCCCCCCCCCCCCCAAAAAAAAAAAAAAAAGGGGGGGGGGGGGGGG"
-                    .to_owned(),
-                base2: "TTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGGTTTTCCCCAAAAGGGG"
-                    .to_owned(),
-                base3: "TCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAGTCAG"
-                    .to_owned(),
-            },
+        let (aas, starts) = match table_id {
+            // Standard
+            1 => (
+                "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "---M------**--*----M---------------M----------------------------",
+            ),
+            // Vertebrate mitochondrial
+            2 => (
+                "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG",
+                "----------**--------------------MMMM----------**---M------------",
+            ),
+            // Yeast mitochondrial
+            3 => (
+                "FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "----------**----------------------MM---------------M------------",
+            ),
+            // Mold, protozoan and coelenterate mitochondrial, Mycoplasma and Spiroplasma
+            4 => (
+                "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "--MM------**-------M------------MMMM---------------M------------",
+            ),
+            // Invertebrate mitochondrial
+            5 => (
+                "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG",
+                "---M------**--------------------MMMM---------------M------------",
+            ),
+            // Ciliate, dasycladacean and hexamita nuclear
+            6 => (
+                "FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "--------------*--------------------M----------------------------",
+            ),
+            // Echinoderm and flatworm mitochondrial
+            9 => (
+                "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+                "----------**-----------------------M---------------M------------",
+            ),
+            // Euplotid nuclear
+            10 => (
+                "FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "----------**-----------------------M----------------------------",
+            ),
+            // Bacterial, archaeal and plant plastid
+            11 => (
+                "FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "---M------**--*----M------------MMMM---------------M------------",
+            ),
+            // Alternative yeast nuclear
+            12 => (
+                "FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "----------**--*----M---------------M----------------------------",
+            ),
+            // Ascidian mitochondrial
+            13 => (
+                "FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG",
+                "---M------**----------------------MM---------------M------------",
+            ),
+            // Alternative flatworm mitochondrial
+            14 => (
+                "FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG",
+                "-----------*-----------------------M----------------------------",
+            ),
+            // Candidate division SR1 and Gracilibacteria
+            25 => (
+                "FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG",
+                "---M------**-----------------------M---------------M------------",
+            ),
             _ => {
                 panic!("Translation table {} not yet implemented", table_id);
             }
+        };
+
+        NCBITable {
+            aas: aas.to_owned(),
+            starts: starts.to_owned(),
+            base1: Self::BASE1.to_owned(),
+            base2: Self::BASE2.to_owned(),
+            base3: Self::BASE3.to_owned(),
+        }
+    }
+}
+
+/// The genetic code used to translate the genes of each genome. Given as NCBI translation
+/// table ids through `--genetic-code`, either bare to set the code for every genome or as
+/// `GENOME=ID` to set the code of a single genome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneticCodes {
+    pub default: usize,
+    pub per_genome: HashMap<String, usize>,
+}
+
+impl GeneticCodes {
+    pub const DEFAULT_TABLE: usize = 11;
+
+    /// Checks a single `--genetic-code` value, for use as a clap value parser
+    pub fn validate(value: &str) -> Result<String, String> {
+        Self::parse_value(value).map(|_| value.to_string())
+    }
+
+    fn parse_value(value: &str) -> Result<(Option<String>, usize), String> {
+        let (genome, table) = match value.rsplit_once('=') {
+            Some((genome, table)) => (Some(genome.to_string()), table),
+            None => (None, value),
+        };
+        match table.trim().parse::<usize>() {
+            Ok(table_id) if NCBITable::is_supported(table_id) => Ok((genome, table_id)),
+            _ => Err(format!(
+                "Unsupported genetic code '{}', expected one of the NCBI translation tables {}",
+                value,
+                NCBITable::SUPPORTED_TABLES.iter().join(", ")
+            )),
+        }
+    }
+
+    pub fn parse<S: AsRef<str>>(values: &[S]) -> Result<Self, BirdToolError> {
+        let mut codes = Self {
+            default: Self::DEFAULT_TABLE,
+            per_genome: HashMap::new(),
+        };
+        for value in values {
+            match Self::parse_value(value.as_ref()).map_err(BirdToolError::DebugError)? {
+                (Some(genome), table_id) => {
+                    codes.per_genome.insert(genome, table_id);
+                }
+                (None, table_id) => codes.default = table_id,
+            }
+        }
+        Ok(codes)
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Result<Self, BirdToolError> {
+        match args.try_get_many::<String>("genetic-code").ok().flatten() {
+            Some(values) => Self::parse(&values.collect::<Vec<&String>>()),
+            None => Self::parse::<String>(&[]),
+        }
+    }
+
+    /// The translation table for a genome, matched on its name or the file stem of its path
+    pub fn for_genome(&self, genome: &str) -> usize {
+        let stem = std::path::Path::new(genome)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(genome);
+        self.per_genome
+            .get(genome)
+            .or_else(|| self.per_genome.get(stem))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// The translation table for a gene, preferring a `transl_table` attribute in the GFF record
+    /// over the genome wide code
+    pub fn for_gene(gene: &bio::io::gff::Record, genome_table: usize) -> usize {
+        match gene.attributes().get("transl_table") {
+            Some(value) => match value.parse::<usize>() {
+                Ok(table_id) if NCBITable::is_supported(table_id) => table_id,
+                _ => {
+                    debug!(
+                        "Ignoring unsupported transl_table {} for gene at {}:{}",
+                        value,
+                        gene.seqname(),
+                        gene.start()
+                    );
+                    genome_table
+                }
+            },
+            None => genome_table,
         }
     }
 }
@@ -73,6 +228,341 @@ impl CodonTable {
             ns_sites: HashMap::new(),
         }
     }
+
+    /// The amino acid a codon encodes under the loaded table, '*' for stop codons
+    pub fn amino_acid(&self, codon: &[u8]) -> Option<char> {
+        self.aminos.get(codon).copied()
+    }
+
+    /// The non-synonymous and synonymous differences between two codons, averaged over every
+    /// order in which the differing bases could have been substituted. None if the codons are
+    /// identical or not in the loaded table
+    pub fn substitution_pathways(&self, reference: &[u8], alternate: &[u8]) -> Option<(f64, f64)> {
+        if reference == alternate
+            || !self.aminos.contains_key(reference)
+            || !self.aminos.contains_key(alternate)
+        {
+            return None;
+        }
+
+        let diffs = reference
+            .iter()
+            .zip(alternate.iter())
+            .enumerate()
+            .filter(|(_, (c1, c2))| c1 != c2)
+            .map(|(pos, _)| pos)
+            .collect::<Vec<usize>>();
+        let permutations = diffs
+            .iter()
+            .permutations(diffs.len())
+            .collect::<Vec<Vec<&usize>>>();
+
+        let mut ns = 0;
+        let mut ss = 0;
+        for permutation in permutations.iter() {
+            let mut shifting = reference.to_vec();
+            for pos in permutation {
+                // Check if one amino acid change causes an syn or non-syn
+                let old_shift = shifting.clone();
+                shifting[**pos] = alternate[**pos];
+                if self.aminos[&old_shift] != self.aminos[&shifting] {
+                    ns += 1;
+                } else {
+                    ss += 1;
+                }
+            }
+        }
+
+        Some((
+            ns as f64 / permutations.len() as f64,
+            ss as f64 / permutations.len() as f64,
+        ))
+    }
+
+    /// The non-synonymous and synonymous sites, N and S, of the codons of a gene. Incomplete
+    /// codons and codons containing N are skipped
+    pub fn count_sites(&self, codon_sequence: &[Vec<u8>]) -> (f64, f64) {
+        let mut big_n = 0.0;
+        let mut big_s = 0.0;
+        for codon in codon_sequence.iter() {
+            if codon.len() != 3 || codon.contains(&b'N') {
+                continue;
+            }
+            if let Some(n) = self.ns_sites.get(codon) {
+                big_n += n;
+                big_s += 3.0 - n;
+            }
+        }
+        (big_n, big_s)
+    }
+
+    /// The non-synonymous and synonymous differences, Nd and Sd, given the substitutions within
+    /// each codon of a gene, by codon index, along with the number of codons changed at more than
+    /// one base
+    pub fn count_differences(
+        &self,
+        codon_sequence: &[Vec<u8>],
+        codon_substitutions: &BTreeMap<usize, Vec<CodonSubstitution>>,
+    ) -> (f64, f64, usize) {
+        let mut big_nd = 0.0;
+        let mut big_sd = 0.0;
+        let mut multi_nucleotide_codons = 0;
+        for (codon_idx, substitutions) in codon_substitutions.iter() {
+            let codon = match codon_sequence.get(*codon_idx) {
+                Some(codon) if codon.len() == 3 && !codon.contains(&b'N') => codon,
+                _ => continue,
+            };
+            for new_codon in CodonSubstitution::merge(codon, substitutions) {
+                if let Some((nd, sd)) = self.substitution_pathways(codon, &new_codon) {
+                    big_nd += nd;
+                    big_sd += sd;
+                    if codon
+                        .iter()
+                        .zip(new_codon.iter())
+                        .filter(|(c1, c2)| c1 != c2)
+                        .count()
+                        > 1
+                    {
+                        multi_nucleotide_codons += 1;
+                    }
+                }
+            }
+        }
+        (big_nd, big_sd, multi_nucleotide_codons)
+    }
+
+    /// The dN/dS ratio under the Jukes-Cantor model given the non-synonymous and synonymous
+    /// differences and sites. 1.0 when there are no synonymous differences and never negative
+    pub fn jukes_cantor_dnds(big_nd: f64, big_n: f64, big_sd: f64, big_s: f64) -> f64 {
+        let mut pn = big_nd / big_n;
+        let mut ps = big_sd / big_s;
+        // Weirdly in the Jukes-Cantor model if pn or ps are 0.75 then the nat log does not resolve
+        // No one talks about this in the literature for some reason
+        if pn == 0.75 {
+            pn = 0.7499
+        }
+        if ps == 0.75 {
+            ps = 0.7499
+        }
+        let d_n = -(3.0 / 4.0) * (1.0 - (4.0 * pn) / 3.0).ln();
+        let d_s = -(3.0 / 4.0) * (1.0 - (4.0 * ps) / 3.0).ln();
+        let dnds = d_n / d_s;
+
+        // negative dnds values make no sense, but occur nonetheless
+        // Just make them 0.0
+        if dnds.is_nan() || d_s - 0.0 <= f64::EPSILON {
+            1.
+        } else if dnds.is_sign_negative() {
+            0.0
+        } else {
+            dnds
+        }
+    }
+}
+
+/// A group of substitutions known to lie on the same haplotype
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PhaseSet {
+    // variants clustered into the same variant group (VG)
+    VariantGroup(i32),
+    // variants assigned to the same strains (ST)
+    Strains(Vec<usize>),
+    // the bases of a single multi-nucleotide allele, by record and allele index
+    Allele(usize, usize),
+}
+
+/// A substituted base within a codon of a gene, oriented along the gene
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodonSubstitution {
+    pub codon_position: usize,
+    pub base: u8,
+    pub phase_set: Option<PhaseSet>,
+}
+
+impl CodonSubstitution {
+    /// The alternate codons given by the substitutions within a codon. Substitutions in the same
+    /// phase set are applied to the reference codon together, giving a multi-nucleotide codon
+    /// change, whereas unphased substitutions each change the reference codon on their own
+    pub fn merge(reference: &[u8], substitutions: &[CodonSubstitution]) -> Vec<Vec<u8>> {
+        let mut phased: Vec<(&PhaseSet, Vec<u8>)> = Vec::new();
+        let mut codons = Vec::new();
+        for substitution in substitutions {
+            if substitution.codon_position >= reference.len() {
+                continue;
+            }
+            match &substitution.phase_set {
+                Some(phase_set) => {
+                    match phased
+                        .iter_mut()
+                        .find(|(existing, _)| *existing == phase_set)
+                    {
+                        Some((_, codon)) => codon[substitution.codon_position] = substitution.base,
+                        None => {
+                            let mut codon = reference.to_vec();
+                            codon[substitution.codon_position] = substitution.base;
+                            phased.push((phase_set, codon));
+                        }
+                    }
+                }
+                None => {
+                    let mut codon = reference.to_vec();
+                    codon[substitution.codon_position] = substitution.base;
+                    codons.push(codon);
+                }
+            }
+        }
+        phased
+            .into_iter()
+            .map(|(_, codon)| codon)
+            .chain(codons.into_iter())
+            .collect()
+    }
+}
+
+/// A gene along with its codons, with the variants over it fetched from a VCF
+struct GeneRegion {
+    start: usize,
+    end: usize,
+    frame: usize,
+    strand: Strand,
+    codon_sequence: Vec<Vec<u8>>,
+}
+
+impl GeneRegion {
+    /// Fetches the reference sequence of a gene and the variants over it. None if the gene has
+    /// no strand or there are no variants on its contig
+    fn fetch(
+        gene: &bio::io::gff::Record,
+        variants: &mut rust_htslib::bcf::IndexedReader,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+    ) -> Option<Self> {
+        let strand = gene.strand()?;
+        // create concatenated contig name format
+        let separator = reference_reader.genomes_and_contigs.separator;
+        let contig_name = separator.join(
+            &reference_reader.retrieve_reference_stem(ref_idx),
+            gene.seqname(),
+        );
+        // no variants on this contig so skip
+        let rid = VariantContext::get_contig_vcf_tid(
+            variants.header(),
+            contig_name.as_bytes(),
+            &separator,
+        )?;
+
+        reference_reader
+            .fetch_contig_from_reference_by_contig_name(contig_name.as_bytes(), ref_idx);
+        reference_reader.read_sequence_to_vec();
+        // bio::gff documentation says start and end positions are 1-based, so we minus 1
+        // Additionally, end position is non-inclusive so do minus 1
+        let start = *gene.start() as usize - 1;
+        let end = *gene.end() as usize - 1;
+        // fetch variants in this window
+        variants.fetch(rid, start as u64, Some(end as u64)).ok()?;
+
+        let frame: usize = gene.frame().parse().unwrap_or(0);
+        let gene_sequence = &reference_reader.current_sequence[start..=end];
+        let codon_sequence = get_codons(gene_sequence, frame, strand);
+        Some(Self {
+            start,
+            end,
+            frame,
+            strand,
+            codon_sequence,
+        })
+    }
+
+    /// Adds the bases substituted by an alternate allele of the same length as the reference
+    /// allele at `position` to the substitutions of the codons of the gene. Returns the number of
+    /// substituted bases
+    fn add_substitutions(
+        &self,
+        codon_substitutions: &mut BTreeMap<usize, Vec<CodonSubstitution>>,
+        position: usize,
+        ref_bases: &[u8],
+        alt_bases: &[u8],
+        phase_set: &Option<PhaseSet>,
+    ) -> usize {
+        let mut substituted = 0;
+        for (offset, (ref_base, alt_base)) in ref_bases.iter().zip(alt_bases.iter()).enumerate() {
+            if ref_base == alt_base {
+                continue;
+            }
+            substituted += 1;
+            if let Some((codon_idx, codon_position)) = codon_position(
+                self.start,
+                self.end,
+                self.frame,
+                self.strand,
+                position + offset,
+            ) {
+                let base = match self.strand {
+                    Strand::Reverse => dna::complement(*alt_base),
+                    _ => *alt_base,
+                };
+                codon_substitutions
+                    .entry(codon_idx)
+                    .or_insert_with(Vec::new)
+                    .push(CodonSubstitution {
+                        codon_position,
+                        base,
+                        phase_set: phase_set.clone(),
+                    });
+            }
+        }
+        substituted
+    }
+}
+
+/// The phasing information of a VCF record, read from its VG and ST INFO fields
+struct RecordPhasing {
+    variant_group: Option<i32>,
+    strains: Option<Vec<usize>>,
+}
+
+impl RecordPhasing {
+    fn from_record(record: &Record) -> Self {
+        let variant_group = match record.info(b"VG").integer() {
+            Ok(Some(values)) => values.iter().find(|value| !value.is_missing()).copied(),
+            _ => None,
+        };
+        let strains = match record.info(b"ST").integer() {
+            Ok(Some(values)) => {
+                let mut strains = values
+                    .iter()
+                    .filter(|value| !value.is_missing() && **value >= 0)
+                    .map(|value| *value as usize)
+                    .collect::<Vec<usize>>();
+                strains.sort_unstable();
+                Some(strains).filter(|strains| !strains.is_empty())
+            }
+            _ => None,
+        };
+        Self {
+            variant_group,
+            strains,
+        }
+    }
+
+    /// The phase set of an alternate allele of `n_bases` bases of the record at `record_idx`.
+    /// The bases of a multi-nucleotide allele are always phased with one another
+    fn phase_set(
+        &self,
+        record_idx: usize,
+        allele_index: usize,
+        n_bases: usize,
+    ) -> Option<PhaseSet> {
+        if let Some(variant_group) = self.variant_group {
+            Some(PhaseSet::VariantGroup(variant_group))
+        } else if let Some(strains) = &self.strains {
+            Some(PhaseSet::Strains(strains.clone()))
+        } else if n_bases > 1 {
+            Some(PhaseSet::Allele(record_idx, allele_index))
+        } else {
+            None
+        }
+    }
 }
 
 pub trait Translations {
@@ -87,7 +577,17 @@ pub trait Translations {
         qual_by_depth_filter: f64,
         qual_threshold: f64,
         depth_per_sample_filter: i64,
-    ) -> (Vec<usize>, Vec<usize>, Vec<f64>);
+    ) -> (Vec<usize>, Vec<usize>, Vec<usize>, Vec<f64>);
+    fn find_strain_mutations(
+        &self,
+        gene: &bio::io::gff::Record,
+        variants: &mut rust_htslib::bcf::IndexedReader,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        strain_ids: &[usize],
+        qual_by_depth_filter: f64,
+        qual_threshold: f64,
+    ) -> (Vec<usize>, Vec<f64>);
     fn calculate_gene_coverage(
         &self,
         gene: &bio::io::gff::Record,
@@ -144,7 +644,10 @@ impl Translations for CodonTable {
     /// Finds all associate mutations within a gene region in the form of a gff record
     /// If there are associated variants in this gene attempts to calculate dN/dS ratios for
     /// the given sample
-    /// Returns a tuple of the number of frameshifts and dN/dS ratio
+    /// Substituted bases within the same codon are merged into a single codon change when they
+    /// are phased, i.e. share a variant group, a strain assignment or a multi-nucleotide allele.
+    /// Returns a tuple of the number of substituted bases, frameshifts, codons changed at more
+    /// than one base and the dN/dS ratio of each sample
     /// TODO: Refactor so calculates for all samples at once without having to re-read the variant
     ///       region each time.
     fn find_mutations(
@@ -157,363 +660,185 @@ impl Translations for CodonTable {
         qual_by_depth_filter: f64,
         qual_threshold: f64,
         depth_per_sample_filter: i64,
-    ) -> (Vec<usize>, Vec<usize>, Vec<f64>) {
-        match gene.strand() {
-            Some(strand) => {
-                let contig_name = format!(
-                    "{}~{}",
-                    reference_reader.retrieve_reference_stem(ref_idx),
-                    gene.seqname()
-                ); // create concatenated contig name format
-                let rid = if let Some(rid) =
-                    VariantContext::get_contig_vcf_tid(variants.header(), contig_name.as_bytes())
-                {
-                    rid
-                } else {
-                    // no variants on this contig so skip
-                    return (vec![0; n_samples], vec![0; n_samples], vec![1.0; n_samples]);
-                };
-
-                reference_reader
-                    .fetch_contig_from_reference_by_contig_name(contig_name.as_bytes(), ref_idx);
-                reference_reader.read_sequence_to_vec();
-                // bio::gff documentation says start and end positions are 1-based, so we minus 1
-                // Additionally, end position is non-inclusive so do minus 1
-                let start = *gene.start() as usize - 1;
-                let end = *gene.end() as usize - 1;
-                // debug!("Start {} End {}", start, end);
-                // fetch variants in this window
-                match variants.fetch(rid, start as u64, Some(end as u64)) {
-                    Ok(_) => {}
-                    Err(_e) => {
-                        return (vec![0; n_samples], vec![0; n_samples], vec![1.0; n_samples]);
-                    }
-                };
-
-                // VariantContext::process_vcf_in_region()
-
-                let frame: usize = match gene.frame().parse() {
-                    Ok(frame_val) => frame_val,
-                    Err(_) => 0,
-                };
-                let gene_sequence = &reference_reader.current_sequence[start..=end];
-                // debug!("Gene Seq {:?}", String::from_utf8_lossy(gene_sequence));
-                let codon_sequence = get_codons(&gene_sequence, frame, strand);
-                // debug!("Codon Sequence {:?}", codon_sequence);
-
-                // Calculate N and S
-                let mut big_n: Vec<f64> = vec![0.0; n_samples];
-                let mut big_s: Vec<f64> = vec![0.0; n_samples];
-                for codon in codon_sequence.iter() {
-                    if std::str::from_utf8(codon)
-                        .expect("Unable to interpret codon")
-                        .contains('N')
-                        || codon.len() != 3
-                    {
-                        continue;
-                    } else {
-                        match self.ns_sites.get(codon) {
-                            Some(n) => {
-                                for sample_idx in 0..n_samples {
-                                    big_n[sample_idx] += n;
-                                    big_s[sample_idx] += 3.0 - n;
-                                }
-                            }
-                            None => {
-                                // debug!(
-                                    // "Codon {:?} not found",
-                                    // std::str::from_utf8(codon.as_slice())
-                                // );
+    ) -> (Vec<usize>, Vec<usize>, Vec<usize>, Vec<f64>) {
+        let region = match GeneRegion::fetch(gene, variants, reference_reader, ref_idx) {
+            Some(region) => region,
+            None => {
+                return (
+                    vec![0; n_samples],
+                    vec![0; n_samples],
+                    vec![0; n_samples],
+                    vec![1.0; n_samples],
+                )
+            }
+        };
+        let (big_n, big_s) = self.count_sites(&region.codon_sequence);
+
+        // dN/dS calculations when using NGS reads outlined here:
+        // http://bioinformatics.cvr.ac.uk/blog/calculating-dnds-for-ngs-datasets/
+        // Note, we don't normalize for depth here and instead just use Jukes-Cantor model
+        // Substituted bases are first collected per codon so that bases phased onto the
+        // same haplotype are classified together as a single codon change
+        let mut codon_substitutions: Vec<BTreeMap<usize, Vec<CodonSubstitution>>> =
+            vec![BTreeMap::new(); n_samples];
+        let mut frameshifts = vec![0; n_samples];
+        let mut snps = vec![0; n_samples];
+        for (record_idx, record) in variants.records().into_iter().enumerate() {
+            match record {
+                Ok(mut record) => {
+                    let phasing = RecordPhasing::from_record(&record);
+                    match VariantContext::from_vcf_record(&mut record, true) {
+                        Some(mut context) => {
+                            let passes = VariantContextUtils::passes_thresholds(
+                                &mut context,
+                                qual_by_depth_filter,
+                                qual_threshold,
+                            );
+
+                            if !passes {
                                 continue;
                             }
-                        }
-                    }
-                }
 
-                // debug!("getting ns_sites N {:?} S {:?}", &big_n, &big_s);
-
-                // Create Nd and Sd values
-                let mut big_nd: Vec<f64> = vec![0.0; n_samples];
-                let mut big_sd: Vec<f64> = vec![0.0; n_samples];
-
-                // dN/dS calculations when using NGS reads outlined here:
-                // http://bioinformatics.cvr.ac.uk/blog/calculating-dnds-for-ngs-datasets/
-                // Note, we don't normalize for depth here and instead just use Jukes-Cantor model
-                let mut codon: &Vec<u8> = &codon_sequence[0];
-                let mut previous_codon: &Vec<u8> = &codon_sequence[0];
-                let mut new_codons: Vec<Vec<Vec<u8>>> = vec![vec![]; n_samples];
-                let mut positionals = vec![0; n_samples];
-                let mut total_variants = vec![0; n_samples];
-                let mut gene_cursor = 0; // index inside gene, i.e, position of variant
-                let mut reference_cursor = start; // index of reference sequence
-                let mut frameshifts = vec![0; n_samples];
-                let mut snps = vec![0; n_samples];
-                let mut old_codon_idx = None;
-                for record in variants.records().into_iter() {
-                    match record {
-                        Ok(mut record) => {
-                            match VariantContext::from_vcf_record(&mut record, true) {
-                                Some(mut context) => {
-                                    let passes = VariantContextUtils::passes_thresholds(
-                                        &mut context,
-                                        qual_by_depth_filter,
-                                        qual_threshold,
-                                    );
+                            let ref_allele = context.get_reference().clone();
+                            for sample_idx in 0..n_samples {
+                                let which_are_present = context.alleles_present_in_sample(
+                                    sample_idx,
+                                    depth_per_sample_filter as i32,
+                                );
 
-                                    if !passes {
-                                        continue;
-                                    }
+                                if !which_are_present[1..].iter().any(|v| *v) {
+                                    continue; // no alt alleles are present
+                                }
 
-                                    // gained bases is the difference between current and previous
-                                    // position
-                                    let gained_bases =
-                                        context.loc.start.saturating_sub(reference_cursor);
-                                    // update previous position to current position
-                                    gene_cursor += gained_bases;
-                                    // add gained bases to reference cursor
-                                    reference_cursor = context.loc.start;
-
-                                    // index of current codon in gene
-                                    // number of codons is gene size divided by three
-                                    let codon_idx = gene_cursor / 3 as usize;
-                                    let process_previous_codon = match old_codon_idx {
-                                        Some(old_idx) => {
-                                            if old_idx != codon_idx {
-                                                old_codon_idx = Some(codon_idx);
-                                                previous_codon = codon;
-                                                true
-                                            } else {
-                                                false
-                                            }
-                                        }
-                                        None => {
-                                            old_codon_idx = Some(codon_idx);
-                                            false
-                                        }
-                                    };
-
-                                    // index of current position in the current codon
-                                    // all codons have size == 3
-                                    let codon_cursor = gene_cursor % 3;
-                                    // debug!(
-                                    //     "reference cursor {} gained_bases {} Gene cursor {} codon idx {} codon cursor {}",
-                                    //     reference_cursor, gained_bases, gene_cursor, codon_idx, codon_cursor
-                                    // );
-
-                                    if codon_idx >= codon_sequence.len() {
+                                // iterate through non reference alleles
+                                // if those alleles are present in this sample then
+                                // increment appropriate values
+                                for (allele_index, allele) in
+                                    context.get_alternate_alleles_with_index()
+                                {
+                                    if !which_are_present[allele_index] {
                                         continue;
                                     }
-                                    codon = &codon_sequence[codon_idx];
-
-                                    if std::str::from_utf8(codon.as_slice())
-                                        .expect("Unable to interpret codon")
-                                        .contains('N')
-                                        || codon.len() != 3
-                                    {
+                                    if allele.bases.len() != ref_allele.bases.len() {
+                                        frameshifts[sample_idx] += 1;
                                         continue;
                                     }
 
-                                    for sample_idx in 0..n_samples {
-                                        if process_previous_codon {
-                                            for new_codon in new_codons[sample_idx].iter() {
-                                                // debug!("Positionals start {:?}", &positionals);
-                                                // debug!(
-                                                //     "Sample {} new_codon {:?} ref_codon {:?}",
-                                                //     sample_idx, new_codon, &previous_codon
-                                                // );
-                                                if (previous_codon.len() == 3)
-                                                    && (new_codon.len() == 3)
-                                                    && (previous_codon != new_codon)
-                                                {
-                                                    // get indices of different locations
-                                                    let mut pos = 0 as usize;
-                                                    let mut diffs = vec![];
-                                                    for (c1, c2) in
-                                                        previous_codon.iter().zip(new_codon.iter())
-                                                    {
-                                                        if c1 != c2 {
-                                                            diffs.push(pos);
-                                                        }
-                                                        pos += 1;
-                                                    }
-                                                    total_variants[sample_idx] += diffs.len();
-                                                    // get permuations of positions
-                                                    let permutations: Vec<Vec<&usize>> = diffs
-                                                        .iter()
-                                                        .permutations(diffs.len())
-                                                        .collect();
-
-                                                    // calculate synonymous and non-synonymous for each permutation
-                                                    let mut ns = 0;
-                                                    let mut ss = 0;
-                                                    // debug!(
-                                                    //     "positional difference {:?} permutations {:?}",
-                                                    //     diffs,
-                                                    //     permutations.len()
-                                                    // );
-                                                    positionals[sample_idx] += permutations.len();
-                                                    for permutation in permutations.iter() {
-                                                        let mut shifting = codon.clone();
-                                                        let mut old_shift;
-                                                        for pos in permutation {
-                                                            // Check if one amino acid change causes an syn or non-syn
-                                                            old_shift = shifting.clone();
-                                                            shifting[**pos] = new_codon[**pos];
-                                                            // debug!("Old shift {:?}, new {:?}", old_shift, shifting);
-                                                            if self.aminos[&old_shift]
-                                                                != self.aminos[&shifting]
-                                                            {
-                                                                ns += 1;
-                                                            } else {
-                                                                ss += 1;
-                                                            }
-                                                        }
-                                                    }
-                                                    let nd = ns as f64 / permutations.len() as f64;
-                                                    let sd = ss as f64 / permutations.len() as f64;
-                                                    big_nd[sample_idx] += nd;
-                                                    big_sd[sample_idx] += sd;
-                                                }
-                                                // debug!("Positionals end {:?}", &positionals);
-                                                // debug!(
-                                                //     "Codon idx {} gene cursor {} codons {} gene length {} new_codons {:?}",
-                                                //     codon_idx,
-                                                //     gene_cursor,
-                                                //     codon_sequence.len(),
-                                                //     gene_sequence.len(),
-                                                //     new_codons[sample_idx]
-                                                // );
-                                            }
-                                            // begin working on new codon
-                                            new_codons[sample_idx] = Vec::new();
-                                        }
-
-                                        let which_are_present = context
-                                            .alleles_present_in_sample(
-                                                sample_idx,
-                                                depth_per_sample_filter as i32,
-                                            );
-
-                                        if !which_are_present[1..].iter().any(|v| *v) {
-                                            continue; // no alt alleles are present
-                                        }
-
-                                        let ref_allele = context.get_reference();
-                                        let mut snp_count = 0;
-
-                                        // iterate through non reference alleles
-                                        // if those alleles are present in this sample then
-                                        // increment appropriate values
-                                        for (allele_index, allele) in
-                                            context.get_alternate_alleles_with_index()
-                                        {
-                                            if new_codons[sample_idx].len() == 0 {
-                                                new_codons[sample_idx] = vec![codon.clone()];
-                                            }
-                                            if allele.bases.len() > 1
-                                                || allele.bases.len() != ref_allele.bases.len()
-                                            {
-                                                if which_are_present[allele_index] {
-                                                    frameshifts[sample_idx] += 1;
-                                                }
-                                                continue;
-                                            }
-
-                                            if which_are_present[allele_index] {
-                                                snps[sample_idx] += 1;
-                                                if snp_count >= 1 {
-                                                    // Create a copy of codon up to this point
-                                                    // Not sure if reusing previous variants is bad, but
-                                                    // not doing so can cause randomness to dN/dS values
-                                                    // debug!(
-                                                    //     "before pushing {:?}",
-                                                    //     new_codons[sample_idx]
-                                                    // );
-                                                    new_codons[sample_idx].push(codon.clone());
-                                                    // debug!(
-                                                    //     "Gene cursor {} sample idx {} snp count {} codon cursor {}",
-                                                    //     gene_cursor, sample_idx, snp_count, codon_cursor
-                                                    // );
-                                                    // debug!(
-                                                    //     "new_codons length {}",
-                                                    //     new_codons[sample_idx].len()
-                                                    // );
-                                                    new_codons[sample_idx][snp_count]
-                                                        [codon_cursor] = allele.bases[0];
-
-                                                    // debug!(
-                                                    //     "multi snp codon {:?}",
-                                                    //     new_codons[sample_idx]
-                                                    // );
-                                                } else {
-                                                    for var_idx in 0..new_codons[sample_idx].len() {
-                                                        // debug!(
-                                                        //     "s {} v {} c {}. Size {}",
-                                                        //     sample_idx,
-                                                        //     var_idx,
-                                                        //     codon_cursor,
-                                                        //     new_codons[sample_idx].len()
-                                                        // );
-                                                        new_codons[sample_idx][var_idx]
-                                                            [codon_cursor] = allele.bases[0];
-                                                    }
-                                                }
-                                                snp_count += 1;
-                                            }
-                                        }
-                                    }
+                                    let phase_set = phasing.phase_set(
+                                        record_idx,
+                                        allele_index,
+                                        allele.bases.len(),
+                                    );
+                                    snps[sample_idx] += region.add_substitutions(
+                                        &mut codon_substitutions[sample_idx],
+                                        context.loc.start,
+                                        &ref_allele.bases,
+                                        &allele.bases,
+                                        &phase_set,
+                                    );
                                 }
-                                None => continue,
                             }
                         }
-                        Err(_) => {
-                            // Skip error record
-                            continue;
-                        }
+                        None => continue,
                     }
                 }
-
-                let mut dnds_values = vec![1.0; n_samples];
-                for sample_idx in 0..n_samples {
-                    // debug!(
-                    //     "Nd {} N {}, Sd {} S {} total permutations {} variants {}",
-                    //     big_nd[sample_idx],
-                    //     big_n[sample_idx],
-                    //     big_sd[sample_idx],
-                    //     big_s[sample_idx],
-                    //     positionals[sample_idx],
-                    //     total_variants[sample_idx]
-                    // );
-                    let mut pn = big_nd[sample_idx] / big_n[sample_idx];
-                    let mut ps = big_sd[sample_idx] / big_s[sample_idx];
-                    // debug!("pn {} ps {}", pn, ps);
-                    // Weirdly in the Jukes-Cantor model if pn or ps are 0.75 then the nat log does not resolve
-                    // No one talks about this in the literature for some reason
-                    if pn == 0.75 {
-                        pn = 0.7499
-                    }
-                    if ps == 0.75 {
-                        ps = 0.7499
-                    }
-                    let d_n = -(3.0 / 4.0) * (1.0 - (4.0 * pn) / 3.0).ln();
-                    let d_s = -(3.0 / 4.0) * (1.0 - (4.0 * ps) / 3.0).ln();
-                    // debug!("dN {} dS {}", d_n, d_s);
-                    let mut dnds = d_n / d_s;
-
-                    // negative dnds values make no sense, but occur nonetheless
-                    // Just make them 0.0
-                    if dnds.is_nan() || d_s - 0.0 <= f64::EPSILON {
-                        dnds = 1.
-                    } else if dnds.is_sign_negative() {
-                        dnds = 0.0
-                    }
-                    dnds_values[sample_idx] = dnds
+                Err(_) => {
+                    // Skip error record
+                    continue;
                 }
+            }
+        }
 
-                return (snps, frameshifts, dnds_values);
+        let mut multi_nucleotide_codons = vec![0; n_samples];
+        let mut dnds_values = vec![1.0; n_samples];
+        for sample_idx in 0..n_samples {
+            let (nd, sd, multi_nucleotide) =
+                self.count_differences(&region.codon_sequence, &codon_substitutions[sample_idx]);
+            multi_nucleotide_codons[sample_idx] = multi_nucleotide;
+            dnds_values[sample_idx] = Self::jukes_cantor_dnds(nd, big_n, sd, big_s);
+        }
+
+        (snps, frameshifts, multi_nucleotide_codons, dnds_values)
+    }
+
+    /// Finds the substitutions each strain carries within a gene, i.e. the first alternate allele
+    /// of every variant assigned to the strain by the ST INFO field, and calculates the dN/dS
+    /// ratio of each strain from them. Every substitution of a strain lies on the same haplotype,
+    /// so substitutions within a codon are always merged into a single codon change.
+    /// Returns the number of substituted bases and the dN/dS ratio of each of `strain_ids`
+    fn find_strain_mutations(
+        &self,
+        gene: &bio::io::gff::Record,
+        variants: &mut rust_htslib::bcf::IndexedReader,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        strain_ids: &[usize],
+        qual_by_depth_filter: f64,
+        qual_threshold: f64,
+    ) -> (Vec<usize>, Vec<f64>) {
+        let n_strains = strain_ids.len();
+        let region = match GeneRegion::fetch(gene, variants, reference_reader, ref_idx) {
+            Some(region) => region,
+            None => return (vec![0; n_strains], vec![1.0; n_strains]),
+        };
+        let (big_n, big_s) = self.count_sites(&region.codon_sequence);
+
+        let mut codon_substitutions: Vec<BTreeMap<usize, Vec<CodonSubstitution>>> =
+            vec![BTreeMap::new(); n_strains];
+        let mut snps = vec![0; n_strains];
+        for record in variants.records() {
+            let mut record = match record {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            let strains = match RecordPhasing::from_record(&record).strains {
+                Some(strains) => strains,
+                None => continue,
+            };
+            let mut context = match VariantContext::from_vcf_record(&mut record, true) {
+                Some(context) => context,
+                None => continue,
+            };
+            if !VariantContextUtils::passes_thresholds(
+                &mut context,
+                qual_by_depth_filter,
+                qual_threshold,
+            ) {
+                continue;
+            }
+
+            // strain IDs refer to the first alternate allele
+            let (ref_allele, alt_allele) = match (context.alleles.first(), context.alleles.get(1)) {
+                (Some(ref_allele), Some(alt_allele))
+                    if ref_allele.bases.len() == alt_allele.bases.len() =>
+                {
+                    (ref_allele, alt_allele)
+                }
+                _ => continue,
+            };
+            for (strain_idx, strain_id) in strain_ids.iter().enumerate() {
+                if !strains.contains(strain_id) {
+                    continue;
+                }
+                snps[strain_idx] += region.add_substitutions(
+                    &mut codon_substitutions[strain_idx],
+                    context.loc.start,
+                    &ref_allele.bases,
+                    &alt_allele.bases,
+                    &Some(PhaseSet::Strains(vec![*strain_id])),
+                );
             }
-            _ => return (vec![0; n_samples], vec![0; n_samples], vec![1.0; n_samples]),
         }
+
+        let dnds_values = codon_substitutions
+            .iter()
+            .map(|substitutions| {
+                let (nd, sd, _) = self.count_differences(&region.codon_sequence, substitutions);
+                Self::jukes_cantor_dnds(nd, big_n, sd, big_s)
+            })
+            .collect::<Vec<f64>>();
+
+        (snps, dnds_values)
     }
 
     fn calculate_gene_coverage(
@@ -546,3 +871,40 @@ pub fn get_codons<'a>(sequence: &'a [u8], frame: usize, strandedness: Strand) ->
         }
     }
 }
+
+/// The strains variants are assigned to by the ST INFO field anywhere in a VCF, in ascending
+/// order. Empty for VCFs that were not genotyped into strains
+pub fn strain_ids_in_vcf(path: &str) -> Result<Vec<usize>, BirdToolError> {
+    let mut reader = VcfInput::open(path)?;
+    let mut strain_ids = BTreeSet::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read record of {}: {}", path, e))
+        })?;
+        if let Some(strains) = RecordPhasing::from_record(&record).strains {
+            strain_ids.extend(strains);
+        }
+    }
+    Ok(strain_ids.into_iter().collect())
+}
+
+/// The index of the codon containing a 0-based reference position of a gene spanning `gene_start`
+/// to `gene_end` inclusive, and the position within that codon, in the orientation of the gene.
+/// None for positions outside of the gene or before its reading frame begins
+pub fn codon_position(
+    gene_start: usize,
+    gene_end: usize,
+    frame: usize,
+    strandedness: Strand,
+    position: usize,
+) -> Option<(usize, usize)> {
+    if position < gene_start || position > gene_end {
+        return None;
+    }
+    let gene_offset = match strandedness {
+        Strand::Forward | Strand::Unknown => position - gene_start,
+        Strand::Reverse => gene_end - position,
+    };
+    let frame_offset = gene_offset.checked_sub(frame)?;
+    Some((frame_offset / 3, frame_offset % 3))
+}
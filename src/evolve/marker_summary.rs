@@ -0,0 +1,442 @@
+use bio::alphabets::dna;
+use bio_types::strand::Strand;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::evolve::codon_structs::CodonTable;
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// A resistance or marker mutation within a gene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerMutation {
+    /// Amino acid substitution at a 1-based codon number, e.g. S450L or p.S450L
+    Protein {
+        ref_aa: char,
+        codon: usize,
+        alt_aa: char,
+    },
+    /// Substitution at a 1-based position of the coding sequence, e.g. c.1349C>T
+    Nucleotide {
+        position: usize,
+        ref_base: u8,
+        alt_base: u8,
+    },
+}
+
+impl MarkerMutation {
+    pub fn parse(mutation: &str) -> Result<Self, String> {
+        let mutation = mutation.trim();
+        if let Some(nucleotide) = mutation.strip_prefix("c.") {
+            let (position, change) = nucleotide.split_at(
+                nucleotide
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(nucleotide.len()),
+            );
+            let change = change.to_ascii_uppercase().into_bytes();
+            return match (position.parse::<usize>(), change.as_slice()) {
+                (Ok(position), [ref_base, b'>', alt_base])
+                    if position > 0
+                        && b"ACGT".contains(ref_base)
+                        && b"ACGT".contains(alt_base) =>
+                {
+                    Ok(Self::Nucleotide {
+                        position,
+                        ref_base: *ref_base,
+                        alt_base: *alt_base,
+                    })
+                }
+                _ => Err(format!("Invalid nucleotide mutation {}", mutation)),
+            };
+        }
+
+        let protein = mutation.strip_prefix("p.").unwrap_or(mutation);
+        let chars = protein.to_ascii_uppercase().chars().collect::<Vec<char>>();
+        let is_amino_acid = |c: &char| c.is_ascii_alphabetic() || *c == '*';
+        match (chars.first(), chars.last()) {
+            (Some(ref_aa), Some(alt_aa))
+                if chars.len() > 2 && is_amino_acid(ref_aa) && is_amino_acid(alt_aa) =>
+            {
+                match chars[1..chars.len() - 1]
+                    .iter()
+                    .collect::<String>()
+                    .parse::<usize>()
+                {
+                    Ok(codon) if codon > 0 => Ok(Self::Protein {
+                        ref_aa: *ref_aa,
+                        codon,
+                        alt_aa: *alt_aa,
+                    }),
+                    _ => Err(format!("Invalid amino acid mutation {}", mutation)),
+                }
+            }
+            _ => Err(format!("Invalid amino acid mutation {}", mutation)),
+        }
+    }
+
+    /// 0-based positions of the mutation within the coding sequence
+    pub fn cds_positions(&self) -> Vec<usize> {
+        match self {
+            Self::Protein { codon, .. } => {
+                let first = (codon - 1) * 3;
+                vec![first, first + 1, first + 2]
+            }
+            Self::Nucleotide { position, .. } => vec![position - 1],
+        }
+    }
+}
+
+/// A single entry of a marker catalog
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerEntry {
+    pub gene: String,
+    pub label: String,
+    pub mutation: MarkerMutation,
+}
+
+/// Location of a catalog gene on the reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerGene {
+    pub tid: usize,
+    pub contig: String,
+    // 0-based, inclusive
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+    pub frame: usize,
+}
+
+impl MarkerGene {
+    /// Attributes of a GFF record that can hold the name of a gene
+    pub const NAME_ATTRIBUTES: [&'static str; 4] = ["ID", "Name", "gene", "locus_tag"];
+
+    pub fn from_gff(record: &bio::io::gff::Record, tid: usize) -> Self {
+        Self {
+            tid,
+            contig: record.seqname().to_string(),
+            // GFF positions are 1-based and inclusive
+            start: *record.start() as usize - 1,
+            end: *record.end() as usize - 1,
+            strand: record.strand().unwrap_or(Strand::Forward),
+            frame: record.frame().parse().unwrap_or(0),
+        }
+    }
+
+    pub fn matches(record: &bio::io::gff::Record, gene: &str) -> bool {
+        Self::NAME_ATTRIBUTES
+            .iter()
+            .any(|attribute| record.attributes().get(*attribute).map(|v| v.as_str()) == Some(gene))
+    }
+
+    /// Reference position of a 0-based coding sequence position, or None if it lies outside
+    /// of the gene
+    pub fn reference_position(&self, cds_position: usize) -> Option<usize> {
+        let offset = self.frame + cds_position;
+        if offset > self.end - self.start {
+            return None;
+        }
+        match self.strand {
+            Strand::Reverse => Some(self.end - offset),
+            _ => Some(self.start + offset),
+        }
+    }
+
+    /// A reference base as read along the coding strand
+    fn coding_base(&self, base: u8) -> u8 {
+        match self.strand {
+            Strand::Reverse => dna::complement(base.to_ascii_uppercase()),
+            _ => base.to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Frequency and strain presence of a catalog mutation in the called variants
+#[derive(Debug, Clone, PartialEq)]
+pub struct MarkerCall {
+    pub gene: String,
+    pub label: String,
+    pub contig: String,
+    // 1-based position of the first reference base of the mutation
+    pub position: usize,
+    // reference codon or base along the coding strand
+    pub reference: String,
+    // None when a sample does not have enough depth at a matching variant
+    pub sample_frequencies: Vec<Option<f64>>,
+    pub strains: Vec<bool>,
+}
+
+impl MarkerCall {
+    pub fn samples_present(&self) -> usize {
+        self.sample_frequencies
+            .iter()
+            .filter(|frequency| frequency.map(|f| f > 0.0).unwrap_or(false))
+            .count()
+    }
+}
+
+/// Catalog of resistance or marker mutations, read from a TSV of genes and mutations such as S450L
+/// or c.1349C>T. Genes are matched against the GFF attributes of each genome, and a variant carries
+/// a mutation when its alternate allele produces the mutated codon or base.
+#[derive(Debug, Clone, Default)]
+pub struct MarkerCatalog {
+    pub entries: Vec<MarkerEntry>,
+}
+
+impl MarkerCatalog {
+    pub fn from_args(args: &clap::ArgMatches) -> Result<Option<Self>, BirdToolError> {
+        match args.try_get_one::<String>("marker-catalog").ok().flatten() {
+            Some(path) => Ok(Some(Self::from_tsv(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn from_tsv(path: &str) -> Result<Self, BirdToolError> {
+        let file = File::open(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {}", path, e)))?;
+
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line =
+                line.map_err(|e| BirdToolError::IOError(format!("Unable to read {}: {}", path, e)))?;
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            if fields.len() < 2 {
+                return Err(BirdToolError::IOError(format!(
+                    "Malformed marker catalog line in {}: {}",
+                    path, line
+                )));
+            }
+            if fields[1].eq_ignore_ascii_case("mutation") {
+                continue;
+            }
+
+            let mutation = MarkerMutation::parse(fields[1])
+                .map_err(|e| BirdToolError::IOError(format!("{} in {}", e, path)))?;
+            entries.push(MarkerEntry {
+                gene: fields[0].trim().to_string(),
+                label: fields[1].trim().to_string(),
+                mutation,
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// The mutated codon or base along the coding strand, if the alternate allele at
+    /// `allele_index` changes any of the given reference positions
+    fn mutated_sequence(
+        gene: &MarkerGene,
+        positions: &[usize],
+        reference_sequence: &[u8],
+        vc: &VariantContext,
+        allele_index: usize,
+    ) -> Option<Vec<u8>> {
+        let ref_bases = &vc.alleles[0].bases;
+        let alt_bases = &vc.alleles[allele_index].bases;
+        if ref_bases.len() != alt_bases.len() {
+            // only substitutions can carry a catalog mutation
+            return None;
+        }
+
+        let mut sequence = positions
+            .iter()
+            .map(|position| gene.coding_base(reference_sequence[*position]))
+            .collect::<Vec<u8>>();
+        let mut changed = false;
+        for (offset, (ref_base, alt_base)) in ref_bases.iter().zip(alt_bases.iter()).enumerate() {
+            if ref_base == alt_base {
+                continue;
+            }
+            if let Some(index) = positions
+                .iter()
+                .position(|position| *position == vc.loc.start + offset)
+            {
+                sequence[index] = gene.coding_base(*alt_base);
+                changed = true;
+            }
+        }
+
+        if changed {
+            Some(sequence)
+        } else {
+            None
+        }
+    }
+
+    /// Finds the variants carrying a catalog mutation in a gene and summarises their allele
+    /// frequencies in each sample and their presence in each strain. `reference_sequence` is
+    /// the sequence of the contig the gene is on
+    pub fn evaluate(
+        entry: &MarkerEntry,
+        gene: &MarkerGene,
+        reference_sequence: &[u8],
+        contexts: &[VariantContext],
+        codon_table: &CodonTable,
+        n_samples: usize,
+        min_depth: i32,
+        strain_ids: &[usize],
+    ) -> Option<MarkerCall> {
+        let positions = entry
+            .mutation
+            .cds_positions()
+            .into_iter()
+            .map(|cds_position| gene.reference_position(cds_position))
+            .collect::<Option<Vec<usize>>>()?;
+        if positions.iter().any(|position| *position >= reference_sequence.len()) {
+            return None;
+        }
+        let first = *positions.iter().min()?;
+        let last = *positions.iter().max()?;
+
+        let reference = positions
+            .iter()
+            .map(|position| gene.coding_base(reference_sequence[*position]))
+            .collect::<Vec<u8>>();
+        if let MarkerMutation::Protein { ref_aa, .. } = entry.mutation {
+            if codon_table.amino_acid(&reference) != Some(ref_aa) {
+                debug!(
+                    "Reference codon {} of {} does not encode {} for {}",
+                    String::from_utf8_lossy(&reference),
+                    &entry.gene,
+                    ref_aa,
+                    &entry.label
+                );
+            }
+        }
+
+        let mut frequencies = vec![0.0; n_samples];
+        let mut depths = vec![None; n_samples];
+        let mut strains = vec![false; strain_ids.len()];
+        for vc in contexts.iter().filter(|vc| {
+            vc.loc.tid == gene.tid
+                && !vc.is_masked()
+                && vc.loc.start <= last
+                && vc.loc.start + vc.alleles[0].bases.len() > first
+        }) {
+            for allele_index in 1..vc.alleles.len() {
+                let mutated = match Self::mutated_sequence(
+                    gene,
+                    &positions,
+                    reference_sequence,
+                    vc,
+                    allele_index,
+                ) {
+                    Some(mutated) => mutated,
+                    None => continue,
+                };
+                let carries_mutation = match entry.mutation {
+                    MarkerMutation::Protein { alt_aa, .. } => {
+                        codon_table.amino_acid(&mutated) == Some(alt_aa)
+                    }
+                    MarkerMutation::Nucleotide { alt_base, .. } => mutated[0] == alt_base,
+                };
+                if !carries_mutation {
+                    continue;
+                }
+
+                for (sample_index, genotype) in
+                    vc.genotypes.genotypes().iter().enumerate().take(n_samples)
+                {
+                    let depth: i32 = genotype.ad.iter().sum();
+                    let alt_depth = genotype.ad.get(allele_index).copied().unwrap_or(0);
+                    if depth > 0 {
+                        frequencies[sample_index] += alt_depth as f64 / depth as f64;
+                    }
+                    depths[sample_index] =
+                        Some(depths[sample_index].map_or(depth, |d: i32| d.max(depth)));
+                }
+
+                // strains carry the first alternate allele of the contexts they are part of
+                if allele_index == 1 {
+                    for (strain, strain_id) in strains.iter_mut().zip(strain_ids.iter()) {
+                        *strain |= vc.part_of_strain(*strain_id);
+                    }
+                }
+            }
+        }
+
+        let sample_frequencies = frequencies
+            .into_iter()
+            .zip(depths.into_iter())
+            .map(|(frequency, depth)| match depth {
+                Some(depth) if depth < min_depth => None,
+                _ => Some(frequency.min(1.0)),
+            })
+            .collect();
+
+        Some(MarkerCall {
+            gene: entry.gene.clone(),
+            label: entry.label.clone(),
+            contig: gene.contig.clone(),
+            position: first + 1,
+            reference: String::from_utf8_lossy(&reference).to_string(),
+            sample_frequencies,
+            strains,
+        })
+    }
+
+    /// Catalog entries grouped by gene name
+    pub fn entries_by_gene(&self) -> HashMap<&str, Vec<&MarkerEntry>> {
+        let mut by_gene: HashMap<&str, Vec<&MarkerEntry>> = HashMap::new();
+        for entry in self.entries.iter() {
+            by_gene.entry(entry.gene.as_str()).or_default().push(entry);
+        }
+        by_gene
+    }
+
+    /// Writes the frequency of each catalog mutation in every sample and whether each strain
+    /// carries it
+    pub fn write_summary(
+        path: &str,
+        calls: &[MarkerCall],
+        sample_names: &[&str],
+        strain_ids: &[usize],
+    ) -> Result<(), BirdToolError> {
+        let file = File::create(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", path, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+        let write_error =
+            |e: std::io::Error| BirdToolError::IOError(format!("Unable to write to {}: {}", path, e));
+
+        write!(writer, "gene\tmutation\tcontig\tpos\tref\tsamples_present").map_err(write_error)?;
+        for sample_name in sample_names {
+            write!(writer, "\t{}", sample_name).map_err(write_error)?;
+        }
+        for strain_id in strain_ids {
+            write!(writer, "\tstrain_{}", strain_id).map_err(write_error)?;
+        }
+        writeln!(writer).map_err(write_error)?;
+
+        for call in calls {
+            write!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                &call.gene,
+                &call.label,
+                &call.contig,
+                call.position,
+                &call.reference,
+                call.samples_present()
+            )
+            .map_err(write_error)?;
+            for frequency in call.sample_frequencies.iter() {
+                match frequency {
+                    Some(frequency) => write!(writer, "\t{:.4}", frequency),
+                    None => write!(writer, "\tNA"),
+                }
+                .map_err(write_error)?;
+            }
+            for present in call.strains.iter() {
+                write!(writer, "\t{}", *present as u8).map_err(write_error)?;
+            }
+            writeln!(writer).map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
@@ -1 +1,2 @@
-pub mod codon_structs;
+pub mod codon_structs;
+pub mod marker_summary;
@@ -38,6 +38,16 @@ pub fn check_for_svim_asm() {
         .expect("Failed to find installed svim-asm");
 }
 
+pub fn check_for_sniffles() {
+    check_for_external_command_presence("sniffles", "which sniffles")
+        .expect("Failed to find installed sniffles");
+}
+
+pub fn check_for_cutesv() {
+    check_for_external_command_presence("cuteSV", "which cuteSV")
+        .expect("Failed to find installed cuteSV");
+}
+
 pub fn check_for_minimap2() {
     check_for_external_command_presence("minimap2", "which minimap2")
         .expect("Failed to find installed minimap2");
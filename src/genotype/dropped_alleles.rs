@@ -0,0 +1,154 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::model::byte_array_allele::{Allele, ByteArrayAllele};
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
+
+/// The step of genotyping at which an alternate allele was dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    // too many alleles at a site found in the assembled haplotypes
+    HaplotypeAlleles,
+    // too many alternate alleles left in the variant context being genotyped
+    GenotypedAlleles,
+}
+
+impl DropReason {
+    pub fn to_key(&self) -> &'static str {
+        match self {
+            Self::HaplotypeAlleles => "haplotype_alleles",
+            Self::GenotypedAlleles => "genotyped_alleles",
+        }
+    }
+}
+
+/// An alternate allele removed from a site because the site had more alleles than could be
+/// genotyped, along with the evidence it had when it was ranked against the other alleles
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedAllele {
+    pub tid: usize,
+    pub position: usize,
+    pub ref_allele: Vec<u8>,
+    pub alt_allele: Vec<u8>,
+    pub reason: DropReason,
+    // NaN when the allele was not ranked by haplotype score
+    pub best_haplotype_score: f64,
+    // the highest number of reads supporting the allele in a single sample
+    pub max_sample_support: usize,
+    pub total_support: usize,
+}
+
+impl DroppedAllele {
+    pub fn new(
+        tid: usize,
+        position: usize,
+        ref_allele: &ByteArrayAllele,
+        alt_allele: &ByteArrayAllele,
+        reason: DropReason,
+        best_haplotype_score: f64,
+        max_sample_support: usize,
+        total_support: usize,
+    ) -> Self {
+        Self {
+            tid,
+            position,
+            ref_allele: ref_allele.get_bases().to_vec(),
+            alt_allele: alt_allele.get_bases().to_vec(),
+            reason,
+            best_haplotype_score,
+            max_sample_support,
+            total_support,
+        }
+    }
+}
+
+/// The alternate alleles dropped at sites with too many alleles to genotype. Every clone shares the
+/// same records, so they can be written once a reference has been called.
+#[derive(Debug, Clone, Default)]
+pub struct DroppedAlleleLog {
+    alleles: Arc<Mutex<Vec<DroppedAllele>>>,
+}
+
+impl DroppedAlleleLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, allele: DroppedAllele) {
+        self.alleles.lock().unwrap().push(allele);
+    }
+
+    pub fn len(&self) -> usize {
+        self.alleles.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The dropped alleles sorted by position
+    pub fn alleles(&self) -> Vec<DroppedAllele> {
+        let mut alleles = self.alleles.lock().unwrap().clone();
+        alleles.sort_by(|a, b| {
+            (a.tid, a.position, &a.alt_allele).cmp(&(b.tid, b.position, &b.alt_allele))
+        });
+        alleles
+    }
+
+    /// The path of the dropped allele file of a reference
+    pub fn file_path(output_prefix: &str, reference_name: &str) -> String {
+        format!("{}/{}_dropped_alleles.tsv", output_prefix, reference_name)
+    }
+
+    /// Writes the dropped alleles to {output_prefix}/{reference_name}_dropped_alleles.tsv
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        reference_reader: &ReferenceReader,
+    ) -> Result<(), BirdToolError> {
+        let file_name = Self::file_path(output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(
+            writer,
+            "contig\tposition\tref\talt\treason\tbest_haplotype_score\tmax_sample_support\ttotal_support"
+        )
+        .map_err(write_error)?;
+        for allele in self.alleles() {
+            let contig = reference_reader
+                .retrieve_contig_name_from_tid(allele.tid)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .unwrap_or_else(|| allele.tid.to_string());
+            let best_haplotype_score = if allele.best_haplotype_score.is_finite() {
+                format!("{:.4}", allele.best_haplotype_score)
+            } else {
+                "NA".to_string()
+            };
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                contig,
+                allele.position + 1,
+                String::from_utf8_lossy(&allele.ref_allele),
+                String::from_utf8_lossy(&allele.alt_allele),
+                allele.reason.to_key(),
+                best_haplotype_score,
+                allele.max_sample_support,
+                allele.total_support
+            )
+            .map_err(write_error)?;
+        }
+
+        writer.flush().map_err(write_error)
+    }
+}
@@ -30,15 +30,20 @@ impl GenotypeAssignmentMethod {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Serialized as `{"type": <variant>, "value": <contents>}` so the JSON representation stays
+/// unambiguous between variants holding the same underlying type. Non-finite floats are written
+/// as strings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value")]
 pub enum AttributeObject {
-    f64(f64),
-    Vecf64(Vec<f64>),
+    f64(#[serde(with = "crate::model::variant_context_json::non_finite_f64")] f64),
+    Vecf64(#[serde(with = "crate::model::variant_context_json::non_finite_vec_f64")] Vec<f64>),
     String(String),
     UnsizedInteger(usize),
     VecUnsize(Vec<usize>),
     VecU8(Vec<u8>),
     I32(i32),
+    VecI32(Vec<i32>),
     None,
 }
 
@@ -60,7 +65,7 @@ impl AttributeObject {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Genotype {
     pub ploidy: usize,
     pub pl: Vec<i32>,
@@ -71,6 +76,8 @@ pub struct Genotype {
     pub is_phased: bool,
     pub sample_name: usize, // we change this to usize to save on memory
     pub attributes: HashMap<String, AttributeObject>,
+    // cached on first use, so it is not part of the serialized representation
+    #[serde(skip)]
     pub genotype_type: Option<GenotypeType>,
 }
 
@@ -441,7 +448,7 @@ impl Genotype {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct GenotypesContext {
     // sample_names_in_order: Vec<String>,
     genotypes: Vec<Genotype>,
@@ -314,6 +314,12 @@ impl GenotypeLikelihoodCalculator {
         // debug!("Single likelihoods array {:?}", likelihoods);
         // debug!("Number of evidences {}", number_of_evidences);
         // debug!("permutation {:?}", permutation);
+        if self.ploidy == 1 {
+            return GenotypeLikelihoods::from_log10_likelihoods(
+                self.haploid_genotype_likelihoods(likelihoods, permutation, number_of_evidences),
+            );
+        }
+
         let read_likelihoods_by_genotype_index = self
             .get_read_raw_read_likelihoods_by_genotype_index(
                 likelihoods,
@@ -326,6 +332,35 @@ impl GenotypeLikelihoodCalculator {
         return GenotypeLikelihoods::from_log10_likelihoods(read_likelihoods_by_genotype_index);
     }
 
+    /**
+     * Haploid genotype likelihoods. Each genotype is a single allele so the likelihood of a genotype
+     * is the product of the read likelihoods of its allele, and there are no mixture components or
+     * ploidy normalisation to compute.
+     */
+    fn haploid_genotype_likelihoods<A: Allele>(
+        &self,
+        likelihoods: &Array2<f64>,
+        permutation: &AlleleLikelihoodMatrixMapper<A>,
+        number_of_evidences: usize,
+    ) -> Vec<f64> {
+        assert!(
+            permutation.permutation.number_of_alleles() == self.allele_count,
+            "Mismatch between likelihood matrix and allele_count {} -> {}",
+            permutation.permutation.number_of_alleles(),
+            self.allele_count
+        );
+
+        (0..self.allele_count)
+            .map(|a| {
+                likelihoods
+                    .row(permutation.permutation.from_index(a))
+                    .iter()
+                    .take(number_of_evidences)
+                    .sum::<f64>()
+            })
+            .collect()
+    }
+
     /**
      * A helper method that actually does the matrix operations but returns the raw values.
      *
@@ -47,6 +47,10 @@ impl GenotypeLikelihoodCalculators {
     }
 
     pub fn get_instance(ploidy: usize, allele_count: usize) -> GenotypeLikelihoodCalculator {
+        if ploidy == 1 {
+            return Self::get_haploid_instance(allele_count);
+        }
+
         let allele_first_offset_by_ploidy =
             GenotypeLikelihoodCalculators::calculate_genotype_counts_using_table_and_validate(
                 ploidy,
@@ -66,6 +70,36 @@ impl GenotypeLikelihoodCalculators {
         );
     }
 
+    /**
+     * Haploid genotypes are single alleles, so there are no heterozygous genotypes to enumerate.
+     * The genotype with index i is allele i and the offset of the first genotype containing allele a
+     * is a, so the tables are filled in directly rather than through the general recurrence.
+     */
+    fn get_haploid_instance(allele_count: usize) -> GenotypeLikelihoodCalculator {
+        let mut allele_first_offset_by_ploidy: Array2<i32> = Array::zeros([2, allele_count + 1]);
+        for a in 1..=allele_count {
+            allele_first_offset_by_ploidy[[0, a]] = 1;
+            allele_first_offset_by_ploidy[[1, a]] = a as i32;
+        }
+
+        let mut haploid_genotypes = Vec::with_capacity(allele_count);
+        if allele_count > 0 {
+            haploid_genotypes.push(GenotypeAlleleCounts::first(1));
+            for genotype_index in 1..allele_count {
+                let next = haploid_genotypes[genotype_index - 1].next();
+                haploid_genotypes.push(next);
+            }
+        }
+        let genotype_table_by_ploidy = vec![vec![GenotypeAlleleCounts::first(0)], haploid_genotypes];
+
+        return GenotypeLikelihoodCalculator::new(
+            1,
+            allele_count,
+            allele_first_offset_by_ploidy,
+            genotype_table_by_ploidy,
+        );
+    }
+
     fn calculate_genotype_counts_using_table_and_validate(
         ploidy: usize,
         allele_count: usize,
@@ -3,6 +3,7 @@ use ordered_float::OrderedFloat;
 use std::collections::{BinaryHeap, HashSet};
 
 use crate::assembly::assembly_based_caller_utils::AssemblyBasedCallerUtils;
+use crate::genotype::dropped_alleles::{DropReason, DroppedAllele, DroppedAlleleLog};
 use crate::genotype::genotype_builder::{
     AttributeObject, Genotype, GenotypeAssignmentMethod, GenotypesContext,
 };
@@ -33,6 +34,8 @@ pub struct GenotypingEngine {
     genotype_assignment_method: GenotypeAssignmentMethod,
     use_posterior_probabilities_to_calculate_qual: bool,
     annotate_number_of_alleles_discovered: bool,
+    pub(crate) max_alternate_alleles: usize,
+    pub(crate) dropped_alleles: DroppedAlleleLog,
 }
 
 impl GenotypingEngine {
@@ -65,9 +68,57 @@ impl GenotypingEngine {
                 .get_flag("use-posteriors-to-calculate-qual"),
             annotate_number_of_alleles_discovered: args
                 .get_flag("annotate-with-num-discovered-alleles"),
+            max_alternate_alleles: args
+                .try_get_one::<usize>("max-alternate-alleles")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(VariantContext::MAX_ALTERNATE_ALLELES),
+            dropped_alleles: DroppedAlleleLog::new(),
         }
     }
 
+    /// The alternate alleles dropped by this engine and all of its clones
+    pub fn dropped_alleles(&self) -> &DroppedAlleleLog {
+        &self.dropped_alleles
+    }
+
+    fn record_dropped_alleles(&self, vc: &VariantContext, alleles_to_keep: &[ByteArrayAllele]) {
+        for (allele_index, allele) in vc.alleles.iter().enumerate() {
+            if allele.is_reference() || alleles_to_keep.contains(allele) {
+                continue;
+            }
+            let support = vc
+                .genotypes
+                .genotypes()
+                .iter()
+                .map(|genotype| {
+                    genotype.ad.get(allele_index).copied().unwrap_or(0).max(0) as usize
+                })
+                .collect::<Vec<usize>>();
+            self.dropped_alleles.record(DroppedAllele::new(
+                vc.loc.tid,
+                vc.loc.start,
+                vc.get_reference(),
+                allele,
+                DropReason::GenotypedAlleles,
+                f64::NAN,
+                support.iter().copied().max().unwrap_or(0),
+                support.iter().sum(),
+            ));
+        }
+    }
+
+    /// Replaces the heterozygosity priors used by the allele frequency calculator
+    pub fn set_heterozygosity(&mut self, snp_het: f64, ind_het: f64, het_std: f64) {
+        self.allele_frequency_calculator = AlleleFrequencyCalculator::from_heterozygosity(
+            snp_het,
+            ind_het,
+            het_std,
+            self.allele_frequency_calculator.default_ploidy,
+        );
+    }
+
     /**
      * Main entry function to calculate genotypes of a given VC with corresponding GL's that is shared across genotypers (namely UG and HC).
      *
@@ -90,12 +141,13 @@ impl GenotypingEngine {
         }
 
         let mut reduced_vc: VariantContext;
-        if VariantContext::MAX_ALTERNATE_ALLELES < (vc.get_alternate_alleles().len()) {
+        if self.max_alternate_alleles < (vc.get_alternate_alleles().len()) {
             let alleles_to_keep = AlleleSubsettingUtils::calculate_most_likely_alleles(
                 &vc,
                 ploidy,
-                VariantContext::MAX_ALTERNATE_ALLELES,
+                self.max_alternate_alleles,
             );
+            self.record_dropped_alleles(&vc, &alleles_to_keep);
 
             let reduced_genotypes = if alleles_to_keep.len() == 1 {
                 VariantContext::subset_to_ref_only(&vc, ploidy)
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::utils::errors::BirdToolError;
+
+/// Per genome SNP and indel heterozygosity priors, overriding `--snp-heterozygosity` and
+/// `--indel-heterozygosity` for the listed genomes. Expected diversity varies by orders of
+/// magnitude between e.g. viral and bacterial genomes, so a single global prior fits none of them.
+///
+/// The table is tab separated with the columns genome, SNP heterozygosity, and indel
+/// heterozygosity. Lines starting with `#` and a header line starting with `genome` are skipped.
+/// Genomes can be given by name or by the path of their fasta file.
+#[derive(Debug, Clone, Default)]
+pub struct HeterozygosityPriors {
+    priors: HashMap<String, (f64, f64)>,
+}
+
+impl HeterozygosityPriors {
+    pub fn new() -> Self {
+        Self {
+            priors: HashMap::new(),
+        }
+    }
+
+    /// The prior table given on the command line, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let path = args
+            .try_get_one::<String>("heterozygosity-priors")
+            .ok()
+            .flatten()?;
+        match Self::from_file(path) {
+            Ok(priors) => Some(priors),
+            Err(BirdToolError::IOError(message)) | Err(BirdToolError::DebugError(message)) => {
+                panic!("{}", message)
+            }
+            Err(_) => None,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, BirdToolError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read heterozygosity priors {}: {}", path, e))
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BirdToolError> {
+        let mut priors = Self::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("genome\t") {
+                continue;
+            }
+
+            let invalid = || {
+                BirdToolError::DebugError(format!(
+                    "Invalid heterozygosity prior line '{}', expected GENOME<TAB>SNP<TAB>INDEL",
+                    line
+                ))
+            };
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            if fields.len() != 3 {
+                return Err(invalid());
+            }
+            let snp_heterozygosity = fields[1].trim().parse::<f64>().map_err(|_| invalid())?;
+            let indel_heterozygosity = fields[2].trim().parse::<f64>().map_err(|_| invalid())?;
+            if !(snp_heterozygosity > 0.0 && snp_heterozygosity < 1.0)
+                || !(indel_heterozygosity > 0.0 && indel_heterozygosity < 1.0)
+            {
+                return Err(invalid());
+            }
+
+            priors.insert(fields[0].trim(), snp_heterozygosity, indel_heterozygosity);
+        }
+
+        Ok(priors)
+    }
+
+    pub fn insert(&mut self, genome: &str, snp_heterozygosity: f64, indel_heterozygosity: f64) {
+        self.priors.insert(
+            Self::genome_name(genome).to_string(),
+            (snp_heterozygosity, indel_heterozygosity),
+        );
+    }
+
+    /// The (SNP, indel) heterozygosity priors of a genome, if it is in the table
+    pub fn get(&self, genome: &str) -> Option<(f64, f64)> {
+        self.priors.get(Self::genome_name(genome)).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.priors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.priors.is_empty()
+    }
+
+    /// Genomes are named by their fasta file without its directory and extension
+    pub(crate) fn genome_name(genome: &str) -> &str {
+        let file_name = Path::new(genome)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(genome);
+        let file_name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+        [".fna", ".fasta", ".fa", ".fas"]
+            .iter()
+            .find_map(|extension| file_name.strip_suffix(extension))
+            .unwrap_or(file_name)
+    }
+}
@@ -1,3 +1,4 @@
+pub mod dropped_alleles;
 pub mod genotype_allele_counts;
 pub mod genotype_builder;
 pub mod genotype_likelihood_calculator;
@@ -6,3 +7,4 @@ pub mod genotype_likelihoods;
 pub mod genotype_prior_calculator;
 pub mod genotyping_engine;
 pub mod genotyping_likelihoods;
+pub mod heterozygosity_priors;
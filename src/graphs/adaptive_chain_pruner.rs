@@ -1,12 +1,14 @@
 use multimap::MultiMap;
 use ordered_float::OrderedFloat;
 use petgraph::Direction;
+use rayon::prelude::*;
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 use crate::graphs::base_edge::BaseEdge;
 use crate::graphs::base_graph::BaseGraph;
 use crate::graphs::base_vertex::BaseVertex;
+use crate::graphs::chain_pruner::ChainPruner;
 use crate::graphs::path::{Chain, Path};
 use crate::haplotype::haplotype_caller_engine::HaplotypeCallerEngine;
 use crate::utils::base_utils::BaseUtils;
@@ -278,3 +280,72 @@ impl AdaptiveChainPruner {
             .unwrap()
     }
 }
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> ChainPruner<V, E>
+    for AdaptiveChainPruner
+{
+    fn name(&self) -> &'static str {
+        "adaptive"
+    }
+
+    fn is_adaptive(&self) -> bool {
+        true
+    }
+
+    fn chains_to_remove<'a>(
+        &self,
+        chains: &'a VecDeque<Path>,
+        graph: &BaseGraph<V, E>,
+    ) -> Vec<&'a Path> {
+        if chains.is_empty() {
+            return Vec::new();
+        }
+        let probable_error_chains =
+            self.likely_error_chains(&chains, graph, self.initial_error_probability);
+        // debug!("Probable error chains {}", probable_error_chains.len());
+
+        let error_count = probable_error_chains
+            .into_par_iter()
+            .map(|chain| {
+                // chain
+                //     .get_edges()
+                //     .par_iter()
+                //     .map(|e| graph.graph.edge_weight(*e).unwrap().get_multiplicity())
+                //     .sum::<usize>()
+                graph
+                    .graph
+                    .edge_weight(chain.get_last_edge())
+                    .unwrap()
+                    .get_multiplicity()
+            })
+            .sum::<usize>();
+        // debug!("Error count {}", error_count);
+
+        let total_bases = chains
+            .par_iter()
+            .map(|chain| {
+                chain
+                    .get_edges()
+                    .iter()
+                    .map(|e| graph.graph.edge_weight(*e).unwrap().get_multiplicity())
+                    .sum::<usize>()
+            })
+            .sum::<usize>();
+
+        // debug!("Total bases {}", total_bases);
+        let error_rate = error_count as f64 / total_bases as f64;
+        // debug!("Error rate {}", error_rate);
+        self.likely_error_chains(&chains, graph, error_rate)
+            .into_par_iter()
+            .filter(|c| {
+                !c.get_edges()
+                    .iter()
+                    .any(|e| graph.graph.edge_weight(*e).unwrap().is_ref())
+            })
+            .collect::<Vec<&Path>>()
+    }
+
+    fn box_clone(&self) -> Box<dyn ChainPruner<V, E>> {
+        Box::new(self.clone())
+    }
+}
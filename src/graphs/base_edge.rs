@@ -18,6 +18,12 @@ pub trait BaseEdge: Debug + Clone + Send + Sync + Eq + PartialEq + Hash {
 
     fn get_pruning_multiplicity(&self) -> usize;
 
+    /// The number of samples with at least min_multiplicity observations of this edge. Edges that
+    /// do not track samples count as a single sample
+    fn samples_with_multiplicity(&self, min_multiplicity: usize) -> usize {
+        (self.get_multiplicity() > 0 && self.get_multiplicity() >= min_multiplicity) as usize
+    }
+
     fn set_multiplicity(&mut self, value: usize);
 
     fn is_ref(&self) -> bool;
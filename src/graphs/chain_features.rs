@@ -0,0 +1,165 @@
+use petgraph::Direction;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::graphs::base_edge::BaseEdge;
+use crate::graphs::base_graph::BaseGraph;
+use crate::graphs::base_vertex::BaseVertex;
+use crate::graphs::path::Path;
+use crate::utils::errors::BirdToolError;
+use crate::utils::simple_interval::{Locatable, SimpleInterval};
+
+/// Features of a single chain of an assembly graph, computed before pruning
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainFeatures {
+    pub edges: usize,
+    pub is_reference: bool,
+    pub first_multiplicity: usize,
+    pub last_multiplicity: usize,
+    pub min_multiplicity: usize,
+    pub max_multiplicity: usize,
+    pub mean_multiplicity: f64,
+    // summed multiplicity of the edges leaving the first vertex and entering the last vertex
+    pub left_total_multiplicity: usize,
+    pub right_total_multiplicity: usize,
+    pub starts_at_source: bool,
+    pub ends_at_sink: bool,
+}
+
+impl ChainFeatures {
+    pub const HEADER: &'static str = "edges\tis_reference\tfirst_multiplicity\t\
+        last_multiplicity\tmin_multiplicity\tmax_multiplicity\tmean_multiplicity\t\
+        left_total_multiplicity\tright_total_multiplicity\tstarts_at_source\tends_at_sink";
+
+    pub fn from_chain<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>(
+        chain: &Path,
+        graph: &BaseGraph<V, E>,
+    ) -> Self {
+        let multiplicities = chain
+            .get_edges()
+            .iter()
+            .map(|e| graph.graph.edge_weight(*e).unwrap().get_multiplicity())
+            .collect::<Vec<usize>>();
+        let first_vertex = chain.get_first_vertex(graph);
+        let last_vertex = chain.get_last_vertex();
+        let total_multiplicity = |vertex, direction| {
+            graph
+                .graph
+                .edges_directed(vertex, direction)
+                .map(|e| e.weight().get_multiplicity())
+                .sum::<usize>()
+        };
+
+        Self {
+            edges: chain.len(),
+            is_reference: chain.get_edges().iter().any(|e| graph.edge_is_ref(*e)),
+            first_multiplicity: multiplicities.first().copied().unwrap_or(0),
+            last_multiplicity: multiplicities.last().copied().unwrap_or(0),
+            min_multiplicity: multiplicities.iter().copied().min().unwrap_or(0),
+            max_multiplicity: multiplicities.iter().copied().max().unwrap_or(0),
+            mean_multiplicity: if multiplicities.is_empty() {
+                0.0
+            } else {
+                multiplicities.iter().sum::<usize>() as f64 / multiplicities.len() as f64
+            },
+            left_total_multiplicity: total_multiplicity(first_vertex, Direction::Outgoing),
+            right_total_multiplicity: total_multiplicity(last_vertex, Direction::Incoming),
+            starts_at_source: graph.is_source(first_vertex),
+            ends_at_sink: graph.is_sink(last_vertex),
+        }
+    }
+
+    pub fn to_row(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{:.3}\t{}\t{}\t{}\t{}",
+            self.edges,
+            self.is_reference as u8,
+            self.first_multiplicity,
+            self.last_multiplicity,
+            self.min_multiplicity,
+            self.max_multiplicity,
+            self.mean_multiplicity,
+            self.left_total_multiplicity,
+            self.right_total_multiplicity,
+            self.starts_at_source as u8,
+            self.ends_at_sink as u8
+        )
+    }
+}
+
+/// Writes the features of every chain of the read threading graphs, labelled with whether the
+/// active pruner removed it, to the TSV given by --export-chain-features. Rows are not ordered by
+/// position.
+#[derive(Debug, Clone)]
+pub struct ChainFeatureExport {
+    writer: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl ChainFeatureExport {
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let path = args
+            .try_get_one::<String>("export-chain-features")
+            .ok()
+            .flatten()?;
+        match Self::new(path) {
+            Ok(export) => Some(export),
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    pub fn new(path: &str) -> Result<Self, BirdToolError> {
+        let file = File::create(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to create {}: {}", path, e)))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(
+            writer,
+            "tid\tstart\tend\tkmer_size\tpruner\t{}\tpruned",
+            ChainFeatures::HEADER
+        )
+        .map_err(|e| BirdToolError::IOError(format!("Unable to write to {}: {}", path, e)))?;
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+        })
+    }
+
+    /// Writes a row for every chain of a graph, logging rather than failing the assembly when the
+    /// rows can't be written
+    pub fn write<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>(
+        &self,
+        location: &SimpleInterval,
+        pruner: &str,
+        chains: &VecDeque<Path>,
+        chains_to_remove: &[&Path],
+        graph: &BaseGraph<V, E>,
+    ) {
+        let removed = chains_to_remove.iter().copied().collect::<HashSet<&Path>>();
+        let rows = chains
+            .iter()
+            .map(|chain| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    location.tid(),
+                    location.get_start(),
+                    location.get_end(),
+                    graph.get_kmer_size(),
+                    pruner,
+                    ChainFeatures::from_chain(chain, graph).to_row(),
+                    removed.contains(chain) as u8
+                )
+            })
+            .collect::<String>();
+
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Err(e) = writer
+            .write_all(rows.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            warn!("Unable to write chain features: {}", e);
+        }
+    }
+}
@@ -2,59 +2,68 @@ use petgraph::stable_graph::EdgeIndex;
 use petgraph::stable_graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use petgraph::Direction;
-use rayon::prelude::*;
-use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet};
 
 use crate::graphs::adaptive_chain_pruner::AdaptiveChainPruner;
 use crate::graphs::base_edge::BaseEdge;
 use crate::graphs::base_graph::BaseGraph;
 use crate::graphs::base_vertex::BaseVertex;
+#[cfg(feature = "experimental-pruners")]
+use crate::graphs::coverage_normalized_chain_pruner::CoverageNormalizedChainPruner;
 use crate::graphs::low_weight_chain_pruner::LowWeightChainPruner;
 use crate::graphs::path::Path;
+use crate::graphs::sample_support_chain_pruner::SampleSupportChainPruner;
+use crate::utils::errors::BirdToolError;
 
-#[derive(Debug, Clone)]
-pub enum ChainPruner {
-    AdaptiveChainPruner(AdaptiveChainPruner),
-    LowWeightChainPruner(LowWeightChainPruner),
-}
+/// Removes chains, i.e. maximal linear paths, that are likely to be sequencing errors from an
+/// assembly graph. Pruners only choose the chains to remove, and can be boxed and chosen at runtime
+/// from a [`ChainPrunerRegistry`].
+pub trait ChainPruner<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>:
+    std::fmt::Debug + Send + Sync
+{
+    /// The name the pruner is registered under
+    fn name(&self) -> &'static str;
 
-impl ChainPruner {
-    pub fn is_adaptive(&self) -> bool {
-        match self {
-            ChainPruner::AdaptiveChainPruner(_) => true,
-            ChainPruner::LowWeightChainPruner(_) => false,
-        }
+    /// Whether the pruner adapts to the error rate of the graph, in which case the prune factor
+    /// is not corrected for coverage
+    fn is_adaptive(&self) -> bool {
+        false
     }
 
-    pub fn set_prune_factor(&mut self, new_prune_factor: usize) {
-        match self {
-            ChainPruner::LowWeightChainPruner(pruner) => pruner.prune_factor = new_prune_factor,
-            _ => {
-                // do nothing
-            }
-        }
+    fn set_prune_factor(&mut self, _prune_factor: usize) {
+        // do nothing
     }
 
-    pub fn prune_low_weight_chains<
-        V: BaseVertex + std::marker::Sync,
-        E: BaseEdge + std::marker::Sync,
-    >(
+    fn chains_to_remove<'a>(
         &self,
-        graph: &mut BaseGraph<V, E>,
-    ) {
-        let chains = Self::find_all_chains(&graph);
+        chains: &'a VecDeque<Path>,
+        graph: &BaseGraph<V, E>,
+    ) -> Vec<&'a Path>;
+
+    fn box_clone(&self) -> Box<dyn ChainPruner<V, E>>;
+
+    fn prune_low_weight_chains(&self, graph: &mut BaseGraph<V, E>) {
+        let chains = ChainUtils::find_all_chains(&graph);
         // debug!("Chains {}", chains.len());
 
         let chains_to_remove = self.chains_to_remove(&chains, &graph);
         // debug!("Chains to remove {}", chains_to_remove.len());
-        chains_to_remove
-            .into_iter()
-            .for_each(|chain| graph.remove_all_edges(chain.get_edges()));
+        ChainUtils::remove_chains(graph, chains_to_remove);
+    }
+}
 
-        graph.remove_singleton_orphan_vertices();
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> Clone
+    for Box<dyn ChainPruner<V, E>>
+{
+    fn clone(&self) -> Self {
+        self.box_clone()
     }
+}
+
+pub struct ChainUtils {}
 
+impl ChainUtils {
     pub fn find_all_chains<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>(
         graph: &BaseGraph<V, E>,
     ) -> VecDeque<Path> {
@@ -117,72 +126,107 @@ impl ChainPruner {
         Path::new(last_vertex_id, edges)
     }
 
-    pub fn chains_to_remove<
-        'a,
-        V: BaseVertex + std::marker::Sync,
-        E: BaseEdge + std::marker::Sync,
-    >(
+    /// Removes the edges of the given chains and the vertices left without any edges
+    pub fn remove_chains<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>(
+        graph: &mut BaseGraph<V, E>,
+        chains_to_remove: Vec<&Path>,
+    ) {
+        chains_to_remove
+            .into_iter()
+            .for_each(|chain| graph.remove_all_edges(chain.get_edges()));
+
+        graph.remove_singleton_orphan_vertices();
+    }
+}
+
+/// The command line parameters chain pruners are built from
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruningParameters {
+    pub prune_factor: usize,
+    pub initial_error_rate: f64,
+    pub log_odds_threshold: f64,
+    pub seeding_log_odds_threshold: f64,
+    pub max_unpruned_variants: usize,
+    pub coverage_fraction: f64,
+    pub min_sample_reads: usize,
+    pub min_samples: usize,
+}
+
+pub type ChainPrunerConstructor<V, E> = fn(&PruningParameters) -> Box<dyn ChainPruner<V, E>>;
+
+/// Chain pruners by name, as given to --chain-pruner. Experimental pruners are only registered with
+/// the experimental-pruners feature.
+pub struct ChainPrunerRegistry<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> {
+    constructors: HashMap<&'static str, ChainPrunerConstructor<V, E>>,
+}
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> Default
+    for ChainPrunerRegistry<V, E>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> ChainPrunerRegistry<V, E> {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            constructors: HashMap::new(),
+        };
+        registry.register("adaptive", |parameters| {
+            Box::new(AdaptiveChainPruner::new(
+                parameters.initial_error_rate,
+                parameters.log_odds_threshold,
+                parameters.seeding_log_odds_threshold,
+                parameters.max_unpruned_variants,
+            ))
+        });
+        registry.register("low-weight", |parameters| {
+            Box::new(LowWeightChainPruner::new(parameters.prune_factor))
+        });
+        registry.register("sample-support", |parameters| {
+            Box::new(SampleSupportChainPruner::new(
+                parameters.min_sample_reads,
+                parameters.min_samples,
+            ))
+        });
+        #[cfg(feature = "experimental-pruners")]
+        registry.register("coverage-normalized", |parameters| {
+            Box::new(CoverageNormalizedChainPruner::new(
+                parameters.coverage_fraction,
+            ))
+        });
+        registry
+    }
+
+    /// Registers a pruner, replacing any pruner already registered under the name
+    pub fn register(&mut self, name: &'static str, constructor: ChainPrunerConstructor<V, E>) {
+        self.constructors.insert(name, constructor);
+    }
+
+    /// The names of the registered pruners, sorted
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = self
+            .constructors
+            .keys()
+            .copied()
+            .collect::<Vec<&'static str>>();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn create(
         &self,
-        chains: &'a VecDeque<Path>,
-        graph: &BaseGraph<V, E>,
-    ) -> Vec<&'a Path> {
-        match self {
-            ChainPruner::AdaptiveChainPruner(adaptive) => {
-                if chains.is_empty() {
-                    return Vec::new();
-                }
-                let probable_error_chains = adaptive.likely_error_chains(
-                    &chains,
-                    graph,
-                    adaptive.initial_error_probability,
-                );
-                // debug!("Probable error chains {}", probable_error_chains.len());
-
-                let error_count = probable_error_chains
-                    .into_par_iter()
-                    .map(|chain| {
-                        // chain
-                        //     .get_edges()
-                        //     .par_iter()
-                        //     .map(|e| graph.graph.edge_weight(*e).unwrap().get_multiplicity())
-                        //     .sum::<usize>()
-                        graph
-                            .graph
-                            .edge_weight(chain.get_last_edge())
-                            .unwrap()
-                            .get_multiplicity()
-                    })
-                    .sum::<usize>();
-                // debug!("Error count {}", error_count);
-
-                let total_bases = chains
-                    .par_iter()
-                    .map(|chain| {
-                        chain
-                            .get_edges()
-                            .iter()
-                            .map(|e| graph.graph.edge_weight(*e).unwrap().get_multiplicity())
-                            .sum::<usize>()
-                    })
-                    .sum::<usize>();
-
-                // debug!("Total bases {}", total_bases);
-                let error_rate = error_count as f64 / total_bases as f64;
-                // debug!("Error rate {}", error_rate);
-                adaptive
-                    .likely_error_chains(&chains, graph, error_rate)
-                    .into_par_iter()
-                    .filter(|c| {
-                        !c.get_edges()
-                            .iter()
-                            .any(|e| graph.graph.edge_weight(*e).unwrap().is_ref())
-                    })
-                    .collect::<Vec<&Path>>()
-            }
-            ChainPruner::LowWeightChainPruner(low_weight) => chains
-                .into_par_iter()
-                .filter(|chain| low_weight.needs_pruning(graph, chain))
-                .collect(),
+        name: &str,
+        parameters: &PruningParameters,
+    ) -> Result<Box<dyn ChainPruner<V, E>>, BirdToolError> {
+        match self.constructors.get(name) {
+            Some(constructor) => Ok(constructor(parameters)),
+            None => Err(BirdToolError::DebugError(format!(
+                "Unknown chain pruner {}. Options are {}",
+                name,
+                self.names().join(", ")
+            ))),
         }
     }
 }
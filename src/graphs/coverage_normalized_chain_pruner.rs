@@ -0,0 +1,89 @@
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+use crate::graphs::base_edge::BaseEdge;
+use crate::graphs::base_graph::BaseGraph;
+use crate::graphs::base_vertex::BaseVertex;
+use crate::graphs::chain_pruner::ChainPruner;
+use crate::graphs::path::Path;
+
+/// Experimental pruner removing non-reference chains whose heaviest edge is below
+/// `coverage_fraction` of the median reference edge multiplicity. Only built with the
+/// experimental-pruners feature.
+#[derive(Debug, Clone)]
+pub struct CoverageNormalizedChainPruner {
+    pub coverage_fraction: f64,
+}
+
+impl CoverageNormalizedChainPruner {
+    pub fn new(coverage_fraction: f64) -> Self {
+        Self {
+            coverage_fraction: coverage_fraction.clamp(0.0, 1.0),
+        }
+    }
+
+    /// The median multiplicity of the reference edges, falling back to every edge
+    pub fn reference_coverage<
+        V: BaseVertex + std::marker::Sync,
+        E: BaseEdge + std::marker::Sync,
+    >(
+        graph: &BaseGraph<V, E>,
+    ) -> usize {
+        let mut multiplicities = graph
+            .graph
+            .edge_weights()
+            .filter(|edge| edge.is_ref())
+            .map(|edge| edge.get_multiplicity())
+            .collect::<Vec<usize>>();
+        if multiplicities.is_empty() {
+            multiplicities = graph
+                .graph
+                .edge_weights()
+                .map(|edge| edge.get_multiplicity())
+                .collect::<Vec<usize>>();
+        }
+        if multiplicities.is_empty() {
+            return 0;
+        }
+        multiplicities.sort_unstable();
+        multiplicities[multiplicities.len() / 2]
+    }
+
+    /// The multiplicity below which a chain is pruned, at least 1
+    pub fn threshold(&self, coverage: usize) -> usize {
+        ((coverage as f64 * self.coverage_fraction).ceil() as usize).max(1)
+    }
+}
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> ChainPruner<V, E>
+    for CoverageNormalizedChainPruner
+{
+    fn name(&self) -> &'static str {
+        "coverage-normalized"
+    }
+
+    fn is_adaptive(&self) -> bool {
+        true
+    }
+
+    fn chains_to_remove<'a>(
+        &self,
+        chains: &'a VecDeque<Path>,
+        graph: &BaseGraph<V, E>,
+    ) -> Vec<&'a Path> {
+        let threshold = self.threshold(Self::reference_coverage(graph));
+        chains
+            .into_par_iter()
+            .filter(|chain| {
+                chain.get_edges().iter().all(|e| {
+                    let edge = graph.graph.edge_weight(*e).unwrap();
+                    edge.get_pruning_multiplicity() < threshold && !edge.is_ref()
+                })
+            })
+            .collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn ChainPruner<V, E>> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use std::fs::{create_dir_all, File};
+use std::hash::Hash;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+use crate::graphs::base_edge::BaseEdge;
+use crate::graphs::base_graph::BaseGraph;
+use crate::graphs::base_vertex::BaseVertex;
+use crate::utils::errors::BirdToolError;
+use crate::utils::simple_interval::{Locatable, SimpleInterval};
+
+/// Writes the assembly graphs of selected regions to GFA files, one segment per vertex and one link
+/// per edge, tagged with the reference path and multiplicities.
+#[derive(Debug, Clone)]
+pub struct GraphDump {
+    directory: String,
+    // inclusive start-end spans, applied to every contig. Empty dumps every region
+    intervals: Vec<(usize, usize)>,
+}
+
+impl GraphDump {
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let directory = args
+            .try_get_one::<String>("dump-assembly-graphs")
+            .ok()
+            .flatten()?
+            .clone();
+
+        let intervals = args
+            .try_get_many::<String>("dump-graph-intervals")
+            .ok()
+            .flatten()
+            .map(|intervals| {
+                intervals
+                    .map(|interval| match Self::parse_interval(interval) {
+                        Ok(interval) => interval,
+                        Err(e) => panic!("Invalid --dump-graph-intervals value: {}", e),
+                    })
+                    .collect::<Vec<(usize, usize)>>()
+            })
+            .unwrap_or_default();
+
+        if let Err(e) = create_dir_all(&directory) {
+            panic!("Unable to create graph dump directory {}: {}", directory, e);
+        }
+
+        Some(Self {
+            directory,
+            intervals,
+        })
+    }
+
+    pub fn new(directory: &str, intervals: Vec<(usize, usize)>) -> Self {
+        Self {
+            directory: directory.to_string(),
+            intervals,
+        }
+    }
+
+    /// Parses a span given in the same start-end format as --limiting-interval
+    pub fn parse_interval(interval: &str) -> Result<(usize, usize), String> {
+        let mut split = interval.splitn(2, '-');
+        let start = split.next().and_then(|start| start.trim().parse::<usize>().ok());
+        let end = split.next().and_then(|end| end.trim().parse::<usize>().ok());
+        match (start, end) {
+            (Some(start), Some(end)) if start <= end => Ok((start, end)),
+            _ => Err(format!("{} is not of the form start-end", interval)),
+        }
+    }
+
+    /// Whether the graphs of the region at this location should be written
+    pub fn should_dump(&self, location: &SimpleInterval) -> bool {
+        self.intervals.is_empty()
+            || self.intervals.iter().any(|(start, end)| {
+                location.get_start() <= *end && location.get_end() >= *start
+            })
+    }
+
+    pub fn gfa_path(&self, location: &SimpleInterval, kmer_size: usize, graph_type: &str) -> String {
+        format!(
+            "{}/{}_{}-{}.k{}.{}.gfa",
+            &self.directory,
+            location.tid(),
+            location.get_start(),
+            location.get_end(),
+            kmer_size,
+            graph_type
+        )
+    }
+
+    /// Writes the graph of a region if the region was selected, logging rather than failing the
+    /// assembly when the file can't be written
+    pub fn dump<V: BaseVertex + Hash, E: BaseEdge>(
+        &self,
+        graph: &BaseGraph<V, E>,
+        location: &SimpleInterval,
+        graph_type: &str,
+        overlap: usize,
+    ) {
+        if !self.should_dump(location) {
+            return;
+        }
+
+        let path = self.gfa_path(location, graph.get_kmer_size(), graph_type);
+        if let Err(e) = Self::write_gfa(graph, location, graph_type, overlap, &path) {
+            warn!("Unable to write assembly graph {}: {:?}", path, e);
+        }
+    }
+
+    pub fn write_gfa<V: BaseVertex + Hash, E: BaseEdge>(
+        graph: &BaseGraph<V, E>,
+        location: &SimpleInterval,
+        graph_type: &str,
+        overlap: usize,
+        path: &str,
+    ) -> Result<(), BirdToolError> {
+        let file = File::create(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to create {}: {}", path, e)))?;
+        let mut writer = BufWriter::new(file);
+
+        let mut lines = vec![format!(
+            "H\tVN:Z:1.0\tRG:Z:{}:{}-{}\tKM:i:{}\tGT:Z:{}",
+            location.tid(),
+            location.get_start(),
+            location.get_end(),
+            graph.get_kmer_size(),
+            graph_type
+        )];
+        for v in graph.graph.node_indices() {
+            let sequence = graph.graph.node_weight(v).unwrap().get_sequence();
+            lines.push(format!(
+                "S\t{}\t{}\tLN:i:{}\tRF:i:{}",
+                v.index(),
+                std::str::from_utf8(sequence).unwrap_or("*"),
+                sequence.len(),
+                graph.is_reference_node(v) as u8
+            ));
+        }
+        for e in graph.graph.edge_indices() {
+            let edge = graph.graph.edge_weight(e).unwrap();
+            lines.push(format!(
+                "L\t{}\t+\t{}\t+\t{}M\tRC:i:{}\tRF:i:{}",
+                graph.get_edge_source(e).index(),
+                graph.get_edge_target(e).index(),
+                overlap,
+                edge.get_multiplicity(),
+                edge.is_ref() as u8
+            ));
+        }
+
+        for line in lines {
+            writeln!(writer, "{}", line)
+                .map_err(|e| BirdToolError::IOError(format!("Unable to write {}: {}", path, e)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Summary of a dumped assembly graph, as reported by lorikeet graph-inspect
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GraphSummary {
+    pub region: String,
+    pub kmer_size: usize,
+    pub graph_type: String,
+    pub segments: usize,
+    pub links: usize,
+    pub reference_segments: usize,
+    pub reference_links: usize,
+    pub sources: usize,
+    pub sinks: usize,
+    pub total_sequence_length: usize,
+    pub max_multiplicity: usize,
+    pub mean_multiplicity: f64,
+}
+
+impl GraphSummary {
+    pub const HEADER: &'static str = "file\tregion\tkmer_size\tgraph_type\tsegments\tlinks\treference_segments\treference_links\tsources\tsinks\ttotal_sequence_length\tmax_multiplicity\tmean_multiplicity";
+
+    pub fn from_gfa(path: &str) -> Result<Self, BirdToolError> {
+        let file = File::open(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {}", path, e)))?;
+
+        let mut summary = Self::default();
+        let mut segments = HashSet::new();
+        let mut with_incoming = HashSet::new();
+        let mut with_outgoing = HashSet::new();
+        let mut total_multiplicity = 0;
+        for line in BufReader::new(file).lines() {
+            let line = line
+                .map_err(|e| BirdToolError::IOError(format!("Unable to read {}: {}", path, e)))?;
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            match fields[0] {
+                "H" => {
+                    summary.region = Self::tag(&fields, "RG:Z:").unwrap_or("").to_string();
+                    summary.kmer_size = Self::integer_tag(&fields, "KM:i:");
+                    summary.graph_type = Self::tag(&fields, "GT:Z:").unwrap_or("").to_string();
+                }
+                "S" if fields.len() >= 3 => {
+                    segments.insert(fields[1].to_string());
+                    summary.total_sequence_length += Self::integer_tag(&fields, "LN:i:");
+                    summary.reference_segments += Self::integer_tag(&fields, "RF:i:");
+                }
+                "L" if fields.len() >= 6 => {
+                    summary.links += 1;
+                    with_outgoing.insert(fields[1].to_string());
+                    with_incoming.insert(fields[3].to_string());
+                    let multiplicity = Self::integer_tag(&fields, "RC:i:");
+                    summary.max_multiplicity = summary.max_multiplicity.max(multiplicity);
+                    total_multiplicity += multiplicity;
+                    summary.reference_links += Self::integer_tag(&fields, "RF:i:");
+                }
+                _ => {}
+            }
+        }
+
+        summary.segments = segments.len();
+        summary.sources = segments.difference(&with_incoming).count();
+        summary.sinks = segments.difference(&with_outgoing).count();
+        if summary.links > 0 {
+            summary.mean_multiplicity = total_multiplicity as f64 / summary.links as f64;
+        }
+        Ok(summary)
+    }
+
+    fn tag<'a>(fields: &[&'a str], prefix: &str) -> Option<&'a str> {
+        fields
+            .iter()
+            .skip(1)
+            .find_map(|field| field.strip_prefix(prefix))
+    }
+
+    fn integer_tag(fields: &[&str], prefix: &str) -> usize {
+        Self::tag(fields, prefix)
+            .and_then(|value| value.parse::<usize>().ok())
+            .unwrap_or(0)
+    }
+
+    pub fn to_row(&self, path: &str) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.2}",
+            path,
+            self.region,
+            self.kmer_size,
+            self.graph_type,
+            self.segments,
+            self.links,
+            self.reference_segments,
+            self.reference_links,
+            self.sources,
+            self.sinks,
+            self.total_sequence_length,
+            self.max_multiplicity,
+            self.mean_multiplicity
+        )
+    }
+}
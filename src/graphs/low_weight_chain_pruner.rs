@@ -1,6 +1,10 @@
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
 use crate::graphs::base_edge::BaseEdge;
 use crate::graphs::base_graph::BaseGraph;
 use crate::graphs::base_vertex::BaseVertex;
+use crate::graphs::chain_pruner::ChainPruner;
 use crate::graphs::path::Path;
 
 /**
@@ -35,3 +39,30 @@ impl LowWeightChainPruner {
             })
     }
 }
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> ChainPruner<V, E>
+    for LowWeightChainPruner
+{
+    fn name(&self) -> &'static str {
+        "low-weight"
+    }
+
+    fn set_prune_factor(&mut self, prune_factor: usize) {
+        self.prune_factor = prune_factor;
+    }
+
+    fn chains_to_remove<'a>(
+        &self,
+        chains: &'a VecDeque<Path>,
+        graph: &BaseGraph<V, E>,
+    ) -> Vec<&'a Path> {
+        chains
+            .into_par_iter()
+            .filter(|chain| self.needs_pruning(graph, chain))
+            .collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn ChainPruner<V, E>> {
+        Box::new(self.clone())
+    }
+}
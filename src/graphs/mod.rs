@@ -2,9 +2,13 @@ pub mod adaptive_chain_pruner;
 pub mod base_edge;
 pub mod base_graph;
 pub mod base_vertex;
+pub mod chain_features;
 pub mod chain_pruner;
 pub mod common_suffix_splitter;
+#[cfg(feature = "experimental-pruners")]
+pub mod coverage_normalized_chain_pruner;
 pub mod graph_based_k_best_haplotype_finder;
+pub mod graph_dump;
 pub mod graph_utils;
 pub mod k_best_haplotype;
 pub mod k_best_haplotype_finder;
@@ -12,6 +16,7 @@ pub mod kmer_searchable_graph;
 pub mod low_weight_chain_pruner;
 pub mod multi_sample_edge;
 pub mod path;
+pub mod sample_support_chain_pruner;
 pub mod seq_graph;
 pub mod seq_vertex;
 pub mod shared_sequence_merger;
@@ -23,12 +23,17 @@ use crate::graphs::base_edge::BaseEdge;
  *      e.getPruningMultiplicity()        // = 3
  * }
  * </pre>
+ * <p>
+ * The multiplicity of every sample is also kept, by the index the sample was flushed with, so
+ * that pruners can ask how many samples support an edge rather than how many reads.
+ * </p>
  */
 #[derive(Debug, Clone)]
 pub struct MultiSampleEdge {
     current_single_sample_multiplicity: usize,
     single_sample_capacity: usize,
     single_sample_multiplicities: BinaryHeap<Reverse<usize>>,
+    sample_multiplicities: Vec<usize>,
     reference_path_indexes: Vec<usize>,
     pub(crate) multiplicity: usize,
     pub(crate) is_ref: bool,
@@ -63,6 +68,7 @@ impl MultiSampleEdge {
         self.single_sample_capacity = single_sample_capacity;
         self.single_sample_multiplicities = single_sample_multiplicities;
         self.current_single_sample_multiplicity = multiplicity;
+        self.sample_multiplicities = Vec::new();
         self.reference_path_indexes = Vec::with_capacity(2);
     }
 
@@ -86,6 +92,21 @@ impl MultiSampleEdge {
         self.current_single_sample_multiplicity = 0;
     }
 
+    /**
+     * As flush_single_sample_multiplicity, also adding the current single sample multiplicity to
+     * the multiplicity of the given sample. A sample may be flushed more than once, e.g. once for
+     * its short reads and once for its long reads.
+     */
+    pub fn flush_sample_multiplicity(&mut self, sample: usize) {
+        if self.current_single_sample_multiplicity > 0 {
+            if self.sample_multiplicities.len() <= sample {
+                self.sample_multiplicities.resize(sample + 1, 0);
+            }
+            self.sample_multiplicities[sample] += self.current_single_sample_multiplicity;
+        }
+        self.flush_single_sample_multiplicity();
+    }
+
     pub fn inc_multiplicity(&mut self, incr: usize) {
         self.multiplicity += incr;
         self.current_single_sample_multiplicity += incr;
@@ -106,6 +127,11 @@ impl MultiSampleEdge {
     pub fn get_current_single_sample_multiplicity(&self) -> usize {
         self.current_single_sample_multiplicity
     }
+
+    /// The multiplicity of this edge in each flushed sample
+    pub fn get_sample_multiplicities(&self) -> &[usize] {
+        &self.sample_multiplicities
+    }
 }
 
 impl BaseEdge for MultiSampleEdge {
@@ -126,6 +152,7 @@ impl BaseEdge for MultiSampleEdge {
             single_sample_multiplicities,
             single_sample_capacity,
             current_single_sample_multiplicity: multiplicity,
+            sample_multiplicities: Vec::new(),
             reference_path_indexes: Vec::with_capacity(2),
         }
     }
@@ -167,6 +194,19 @@ impl BaseEdge for MultiSampleEdge {
         self.single_sample_multiplicities.peek().unwrap().0
     }
 
+    /**
+     * The number of samples with at least min_multiplicity observations of this edge. The
+     * observations of the sample currently being threaded count as a sample of their own.
+     */
+    fn samples_with_multiplicity(&self, min_multiplicity: usize) -> usize {
+        let current = self.current_single_sample_multiplicity;
+        self.sample_multiplicities
+            .iter()
+            .filter(|multiplicity| **multiplicity > 0 && **multiplicity >= min_multiplicity)
+            .count()
+            + (current > 0 && current >= min_multiplicity) as usize
+    }
+
     /**
      * Set the multiplicity of this edge to value
      * @param value an integer >= 0
@@ -203,6 +243,14 @@ impl BaseEdge for MultiSampleEdge {
     fn add(&mut self, edge: Self) {
         self.multiplicity += edge.multiplicity;
         self.is_ref = self.is_ref || edge.is_ref;
+        if self.sample_multiplicities.len() < edge.sample_multiplicities.len() {
+            self.sample_multiplicities
+                .resize(edge.sample_multiplicities.len(), 0);
+        }
+        self.sample_multiplicities
+            .iter_mut()
+            .zip(edge.sample_multiplicities.iter())
+            .for_each(|(multiplicity, other)| *multiplicity += other);
     }
 
     /**
@@ -0,0 +1,69 @@
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+use crate::graphs::base_edge::BaseEdge;
+use crate::graphs::base_graph::BaseGraph;
+use crate::graphs::base_vertex::BaseVertex;
+use crate::graphs::chain_pruner::ChainPruner;
+use crate::graphs::path::Path;
+
+/// Prune all chains from this graph where no edge is supported by at least min_reads reads in at
+/// least min_samples samples, so variants seen in several shallow samples outlive the errors of a
+/// single deep one. Edges that do not track samples count as a single sample.
+///
+/// For A -[1, 1]> B -[1, 1]> C with min_reads 1 and min_samples 2 the chain is kept, but
+/// A -[5, 0]> B -[5, 0]> C is removed.
+#[derive(Debug, Clone)]
+pub struct SampleSupportChainPruner {
+    pub(crate) min_reads: usize,
+    pub(crate) min_samples: usize,
+}
+
+impl SampleSupportChainPruner {
+    pub fn new(min_reads: usize, min_samples: usize) -> Self {
+        Self {
+            min_reads: min_reads.max(1),
+            min_samples,
+        }
+    }
+
+    pub fn needs_pruning<'a, V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync>(
+        &self,
+        graph: &BaseGraph<V, E>,
+        chain: &Path,
+    ) -> bool {
+        chain
+            .get_edges()
+            .iter()
+            .all(|e| match graph.graph.edge_weight(*e) {
+                None => panic!("Edge index not in graph"),
+                Some(edge) => {
+                    !edge.is_ref()
+                        && edge.samples_with_multiplicity(self.min_reads) < self.min_samples
+                }
+            })
+    }
+}
+
+impl<V: BaseVertex + std::marker::Sync, E: BaseEdge + std::marker::Sync> ChainPruner<V, E>
+    for SampleSupportChainPruner
+{
+    fn name(&self) -> &'static str {
+        "sample-support"
+    }
+
+    fn chains_to_remove<'a>(
+        &self,
+        chains: &'a VecDeque<Path>,
+        graph: &BaseGraph<V, E>,
+    ) -> Vec<&'a Path> {
+        chains
+            .into_par_iter()
+            .filter(|chain| self.needs_pruning(graph, chain))
+            .collect()
+    }
+
+    fn box_clone(&self) -> Box<dyn ChainPruner<V, E>> {
+        Box::new(self.clone())
+    }
+}
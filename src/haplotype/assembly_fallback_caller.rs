@@ -0,0 +1,114 @@
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::genotype::genotype_likelihood_calculators::GenotypeLikelihoodCalculators;
+use crate::model::variant_context::VariantContext;
+use crate::processing::pileup_ani::{base_index, PileupAni, SampleBaseCounts};
+use crate::reads::bird_tool_reads::BirdToolRead;
+use crate::utils::simple_interval::{Locatable, SimpleInterval};
+
+/// Calls SNVs from a pileup of the reads of an assembly region whose local assembly failed, using
+/// the thresholds of lorikeet ani. Records carry AD but missing genotypes and the ASSEMBLY_FALLBACK
+/// flag. Disabled by --disable-assembly-fallback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssemblyFallbackCaller {
+    pileup: PileupAni,
+}
+
+impl AssemblyFallbackCaller {
+    pub fn new(pileup: PileupAni) -> Self {
+        Self { pileup }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        if args
+            .try_get_one::<bool>("disable-assembly-fallback")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        Some(Self::new(PileupAni::from_args(args)))
+    }
+
+    /// The base counts of each sample over `reference`, the bases of the contig from
+    /// `reference_start`
+    pub fn count_reads(
+        &self,
+        reads: &[BirdToolRead],
+        reference: &[u8],
+        reference_start: usize,
+        n_samples: usize,
+    ) -> Vec<SampleBaseCounts> {
+        let mut samples = vec![SampleBaseCounts::new(reference.len()); n_samples];
+        for read in reads.iter() {
+            if let Some(counts) = samples.get_mut(read.sample_index) {
+                self.pileup
+                    .count_record_from(&read.read, reference, reference_start, counts);
+            }
+        }
+        samples
+    }
+
+    /// SNVs within `active_span` of a failed region, given its reads and the reference bases of
+    /// `reference_loc`
+    pub fn call(
+        &self,
+        reads: &[BirdToolRead],
+        reference: &[u8],
+        reference_loc: &SimpleInterval,
+        active_span: &SimpleInterval,
+        n_samples: usize,
+        ploidy: usize,
+    ) -> Vec<VariantContext> {
+        let reference_start = reference_loc.get_start();
+        let samples = self.count_reads(reads, reference, reference_start, n_samples);
+
+        let mut positions = samples
+            .iter()
+            .flat_map(|sample| sample.alternate_positions().copied())
+            .filter(|position| {
+                let position = position + reference_start;
+                position >= active_span.get_start() && position <= active_span.get_end()
+            })
+            .collect::<Vec<usize>>();
+        positions.sort_unstable();
+        positions.dedup();
+
+        positions
+            .into_iter()
+            .filter_map(|position| {
+                let reference_base = *reference.get(position)?;
+                let reference_index = base_index(reference_base)?;
+                let counts = samples
+                    .iter()
+                    .map(|sample| sample.counts(position, reference_index))
+                    .collect::<Vec<[u32; 4]>>();
+                let mut context = self.pileup.site(
+                    reference_loc.get_contig(),
+                    position + reference_start,
+                    reference_base,
+                    &counts,
+                )?;
+                Self::tag(&mut context, ploidy);
+                Some(context)
+            })
+            .collect()
+    }
+
+    /// Gives the genotypes of a pileup site the ploidy of the genome and flat likelihoods, so
+    /// they are written as missing, and flags the site as a fallback call
+    pub fn tag(context: &mut VariantContext, ploidy: usize) {
+        let n_alleles = context.alleles.len();
+        for genotype in context.get_genotypes_mut().genotypes_mut().iter_mut() {
+            genotype.ploidy = ploidy;
+            genotype.pl =
+                vec![0; GenotypeLikelihoodCalculators::genotype_count(ploidy, n_alleles) as usize];
+        }
+        context.set_attribute(
+            VariantAnnotations::AssemblyFallback.to_key().to_string(),
+            AttributeObject::None,
+        );
+    }
+}
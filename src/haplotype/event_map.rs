@@ -343,6 +343,114 @@ impl EventMap {
         return b;
     }
 
+    /// Checks that --emit-complex-events is given a distance to combine events over, as it would
+    /// otherwise do nothing at the default --max-mnp-distance of 0
+    pub fn check_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        let emit_complex_events = args
+            .try_get_one::<bool>("emit-complex-events")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        let max_mnp_distance = args
+            .try_get_one::<usize>("max-mnp-distance")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(0);
+        if emit_complex_events && max_mnp_distance == 0 {
+            return Err(BirdToolError::ConfigError(
+                "--emit-complex-events combines events separated by --max-mnp-distance or less, \
+                set --max-mnp-distance to a distance greater than 0"
+                    .to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /**
+     * Combine events on this haplotype that lie within max_complex_distance bases of each other
+     * into single complex events whose alleles are the reference and haplotype bases spanning the
+     * combined events. Unlike MNP merging this also combines substitutions with neighbouring
+     * indels and events in different cigar elements, so that codon level changes are reported as
+     * a single allele and reads supporting the haplotype are counted once towards it.
+     *
+     * @param reference the reference bases this event map was built against
+     * @param max_complex_distance events separated by this distance or less are combined. 0 disables combining
+     */
+    pub fn merge_complex_events(&mut self, reference: &[u8], max_complex_distance: usize) {
+        if max_complex_distance == 0 || self.map.len() < 2 {
+            return;
+        }
+
+        let events = std::mem::take(&mut self.map)
+            .into_iter()
+            .map(|(_, vc)| vc)
+            .collect::<Vec<VariantContext>>();
+
+        let mut group: Vec<VariantContext> = Vec::new();
+        for vc in events {
+            let combine = match group.last() {
+                Some(last) => {
+                    vc.loc.start > last.loc.end
+                        && vc.loc.start - last.loc.end <= max_complex_distance
+                }
+                None => true,
+            };
+
+            if !combine {
+                let merged = self.make_complex_event(std::mem::take(&mut group), reference);
+                self.map.insert(merged.loc.start, merged);
+            }
+            group.push(vc);
+        }
+
+        if !group.is_empty() {
+            let merged = self.make_complex_event(group, reference);
+            self.map.insert(merged.loc.start, merged);
+        }
+    }
+
+    /**
+     * Replace the reference bases spanned by a group of ordered, non-overlapping events with the
+     * alternate alleles of those events. A group containing a single event is returned unchanged
+     */
+    fn make_complex_event(
+        &self,
+        mut events: Vec<VariantContext>,
+        reference: &[u8],
+    ) -> VariantContext {
+        if events.len() == 1 {
+            return events.pop().unwrap();
+        }
+
+        let offset = |pos: usize| pos - self.reference_loc.start;
+        let start = events[0].loc.start;
+        let end = events.last().unwrap().loc.end;
+
+        let mut alt_bases = Vec::new();
+        let mut cursor = start;
+        for event in events.iter() {
+            alt_bases.extend_from_slice(&reference[offset(cursor)..offset(event.loc.start)]);
+            alt_bases.extend_from_slice(event.get_alternate_alleles()[0].get_bases());
+            cursor = event.loc.end + 1;
+        }
+
+        let mut complex_event = VariantContext::build(
+            self.reference_loc.tid,
+            start,
+            end,
+            vec![
+                ByteArrayAllele::new(&reference[offset(start)..=offset(end)], true),
+                ByteArrayAllele::new(alt_bases.as_slice(), false),
+            ],
+        );
+        complex_event.source = self.source_name_to_add.clone();
+        complex_event.get_type();
+
+        complex_event
+    }
+
     /**
      * Build event maps for each haplotype, returning the sorted set of all of the starting positions of all
      * events across all haplotypes
@@ -356,6 +464,8 @@ impl EventMap {
      *                       are merged until a substitution is separated from the previous one by a greater distance.
      *                       That is, if maxMnpDistance = 1, substitutions at 10,11,12,14,15,17 are partitioned into a MNP
      *                       at 10-12, a MNP at 14-15, and a SNP at 17.  May not be negative.
+     * @param emit_complex_events if true, events of any type separated by maxMnpDistance or less are
+     *                            combined into a single complex event per haplotype
      * @return a sorted set of start positions of all events among all haplotypes
      */
     pub fn build_event_maps_for_haplotypes<'a, L: 'a + Locatable, I>(
@@ -363,6 +473,7 @@ impl EventMap {
         reference: &[u8],
         ref_loc: &SimpleInterval,
         max_mnp_distance: usize,
+        emit_complex_events: bool,
     ) -> Result<BTreeSet<usize>, BirdToolError>
     where
         I: IntoIterator<Item = &'a mut Haplotype<L>>,
@@ -374,13 +485,17 @@ impl EventMap {
         let mut start_pos_key_set = BTreeSet::new();
         for h in haplotypes.into_iter() {
             // Walk along the alignment and turn any difference from the reference into an event
-            h.event_map = Some(EventMap::new(
+            let mut event_map = EventMap::new(
                 &h,
                 reference,
                 ref_loc.clone(),
                 format!("HC{}", hap_number),
                 max_mnp_distance,
-            ));
+            );
+            if emit_complex_events {
+                event_map.merge_complex_events(reference, max_mnp_distance);
+            }
+            h.event_map = Some(event_map);
             hap_number += 1;
             start_pos_key_set.extend(h.event_map.as_ref().unwrap().get_start_positions());
             // Assert that all of the events discovered have 2 alleles
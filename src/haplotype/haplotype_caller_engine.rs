@@ -6,6 +6,7 @@ use rayon::prelude::*;
 use rust_htslib::bam::{record::Cigar, Record};
 use rust_htslib::bcf::record::GenotypeAllele;
 use rust_htslib::bcf::{Format, Header, Writer};
+use std::borrow::Borrow;
 use std::cmp::{max, min};
 use std::collections::{HashSet, HashMap};
 use std::fs::{create_dir_all, File};
@@ -14,41 +15,68 @@ use std::sync::{Arc, Mutex};
 
 use crate::ani_calculator::ani_calculator::ANICalculator;
 use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::model::accessible_genome::{AccessibleGenome, AccessibleWindow};
+use crate::model::callable_sites::{CallableSites, CallableWindow};
+use crate::model::consensus_depths::{ConsensusDepths, DepthWindow};
 use crate::model::allele_likelihoods::AlleleLikelihoods;
 use crate::model::byte_array_allele::ByteArrayAllele;
 use crate::model::variant_context::VariantContext;
+use crate::model::variant_normalizer::VariantNormalizer;
+use crate::model::variants::Filter;
 use crate::activity_profile::activity_profile::Profile;
 use crate::activity_profile::activity_profile_state::{ActivityProfileState, ActivityProfileDataType};
 use crate::activity_profile::band_pass_activity_profile::BandPassActivityProfile;
 use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
+use crate::assembly::active_region_log::{ActiveRegionLog, RegionOutcome, RegionStatus};
 use crate::assembly::assembly_based_caller_utils::AssemblyBasedCallerUtils;
 use crate::assembly::assembly_region::AssemblyRegion;
 use crate::assembly::assembly_region_trimmer::AssemblyRegionTrimmer;
 use crate::assembly::assembly_region_walker::AssemblyRegionWalker;
 use crate::assembly::assembly_result_set::AssemblyResultSet;
+use crate::assembly::forced_alleles::{ForcedAlleles, ForcedPosition};
+use crate::assembly::hotspots::Hotspots;
+use crate::assembly::minimizer_filter::MinimizerFilter;
+use crate::reads::read_end_profile::ReadEndProfile;
 use crate::reference::reference_reader_utils::GenomesAndContigs;
 use crate::bam_parsing::{FlagFilter, bam_generator::*};
+use crate::genotype::dropped_alleles::DroppedAlleleLog;
 use crate::genotype::genotype_builder::Genotype;
 use crate::genotype::genotype_prior_calculator::GenotypePriorCalculator;
 use crate::genotype::genotyping_engine::GenotypingEngine;
+use crate::graphs::chain_features::ChainFeatureExport;
+use crate::graphs::chain_pruner::{ChainPrunerRegistry, PruningParameters};
+use crate::graphs::graph_dump::GraphDump;
+use crate::haplotype::assembly_fallback_caller::AssemblyFallbackCaller;
 use crate::haplotype::haplotype::Haplotype;
 use crate::haplotype::haplotype_caller_genotyping_engine::HaplotypeCallerGenotypingEngine;
+use crate::haplotype::haplotype_records::{HaplotypeRecords, SymbolicHaplotypeRecords};
 use crate::haplotype::ref_vs_any_result::RefVsAnyResult;
 use crate::processing::lorikeet_engine::{ReadType, Elem};
+use crate::processing::per_genome_config::GenomeOverrides;
+use crate::processing::scatter_gather::ScatterShard;
+use crate::processing::subsampler::Subsampler;
+use crate::processing::variant_features::VariantFeatures;
 use crate::read_orientation::beta_distribution_shape::BetaDistributionShape;
 use crate::read_threading::read_threading_assembler::ReadThreadingAssembler;
 use crate::read_threading::read_threading_graph::ReadThreadingGraph;
 use crate::reads::alignment_utils::AlignmentUtils;
+use crate::reads::base_recalibration::BaseRecalibrationTable;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::cigar_utils::CigarUtils;
+use crate::reads::read_group_profiles::ReadGroupProfiles;
 use crate::reads::read_utils::ReadUtils;
 use crate::reference::reference_reader::ReferenceReader;
+use crate::reference::reference_writer::ConsensusOptions;
 use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_provenance::VcfProvenance;
 use crate::utils::interval_utils::IntervalUtils;
 use crate::utils::math_utils::{MathUtils, RunningAverage};
 use crate::utils::natural_log_utils::NaturalLogUtils;
+use crate::utils::partial_output::PartialOutput;
 use crate::utils::quality_utils::QualityUtils;
 use crate::utils::simple_interval::{Locatable, SimpleInterval};
+use crate::utils::thread_budget::ThreadBudget;
+use crate::pair_hmm::gpu_pair_hmm::GpuPairHMM;
 use crate::pair_hmm::pair_hmm_likelihood_calculation_engine::{
     AVXMode, PairHMMLikelihoodCalculationEngine,
 };
@@ -64,6 +92,23 @@ pub struct HaplotypeCallerEngine {
     ref_idx: usize,
     stand_min_conf: f64,
     mapping_quality_threshold: u8,
+    scatter_shard: Option<ScatterShard>,
+    subsampler: Option<Subsampler>,
+    provenance: VcfProvenance,
+    minimizer_filter: Option<MinimizerFilter>,
+    read_end_profile: Option<ReadEndProfile>,
+    forced_alleles: Option<ForcedAlleles>,
+    hotspots: Option<Hotspots>,
+    haplotype_records: bool,
+    write_features: bool,
+    symbolic_haplotype_records: Option<SymbolicHaplotypeRecords>,
+    assembly_fallback: Option<AssemblyFallbackCaller>,
+    vcf_normalizer: Option<VariantNormalizer>,
+    accessible_genome: AccessibleGenome,
+    callable_sites: CallableSites,
+    consensus_depths: Option<ConsensusDepths>,
+    active_regions: ActiveRegionLog,
+    genome_overrides: GenomeOverrides,
 }
 
 impl HaplotypeCallerEngine {
@@ -124,6 +169,7 @@ impl HaplotypeCallerEngine {
         samples: Vec<String>,
         do_allele_specific_calcs: bool,
         sample_ploidy: usize,
+        genome_overrides: &GenomeOverrides,
     ) -> HaplotypeCallerEngine {
         let mut kmer_sizes = match args.get_many::<usize>("kmer-sizes") {
             Some(vals) => vals
@@ -148,6 +194,10 @@ impl HaplotypeCallerEngine {
             &mut allow_non_unique_kmers_in_ref, 
             &mut recover_all_dangling_branches
         );
+        if let Some(genome_kmer_sizes) = &genome_overrides.kmer_sizes {
+            kmer_sizes = genome_kmer_sizes.clone();
+        }
+        ReadThreadingAssembler::validate_kmer_sizes(&kmer_sizes);
 
         let mut assembly_engine = ReadThreadingAssembler::new(
             *args.get_one::<i32>("max-allowed-path-for-read-threading-assembler")
@@ -195,8 +245,31 @@ impl HaplotypeCallerEngine {
             Some(path) => Some(path.to_string()),
             None => None,
         };
+        assembly_engine.graph_dump = GraphDump::from_args(args);
+        assembly_engine.chain_feature_export = ChainFeatureExport::from_args(args);
+        if let Some(chain_pruner) = args.try_get_one::<String>("chain-pruner").ok().flatten() {
+            let parameters = PruningParameters {
+                prune_factor,
+                initial_error_rate: *args.get_one::<f64>("initial-error-rate-for-pruning").unwrap(),
+                log_odds_threshold: MathUtils::log10_to_log(
+                    *args.get_one::<f64>("pruning-log-odds-threshold").unwrap(),
+                ),
+                seeding_log_odds_threshold: MathUtils::log10_to_log(
+                    *args.get_one::<f64>("pruning-seeding-log-odds-threshold").unwrap(),
+                ),
+                max_unpruned_variants: *args.get_one::<usize>("max-unpruned-variants").unwrap(),
+                coverage_fraction: *args.get_one::<f64>("pruning-coverage-fraction").unwrap(),
+                min_sample_reads: *args.get_one::<usize>("pruning-min-sample-reads").unwrap(),
+                min_samples: *args.get_one::<usize>("pruning-min-samples").unwrap(),
+            };
+            match ChainPrunerRegistry::new().create(chain_pruner, &parameters) {
+                Ok(chain_pruner) => assembly_engine.set_chain_pruner(chain_pruner),
+                Err(e) => panic!("{:?}", e),
+            }
+        }
         assembly_engine.min_base_quality_to_use_in_assembly =
             *args.get_one::<u8>("min-base-quality").unwrap();
+        assembly_engine.hybrid_assembly = args.get_flag("hybrid-assembly");
 
         HaplotypeCallerEngine {
             active_region_evaluation_genotyper_engine: GenotypingEngine::make(
@@ -240,9 +313,43 @@ impl HaplotypeCallerEngine {
             mapping_quality_threshold: *args
                 .get_one::<u8>("mapping-quality-threshold-for-genotyping")
                 .unwrap(),
+            // checked at startup
+            scatter_shard: ScatterShard::from_args(args).ok().flatten(),
+            subsampler: Subsampler::from_args(args),
+            provenance: VcfProvenance::from_args(args),
+            minimizer_filter: MinimizerFilter::from_args(args),
+            read_end_profile: ReadEndProfile::from_args(args, samples.len()),
+            forced_alleles: ForcedAlleles::from_args(args),
+            hotspots: Hotspots::from_args(args),
+            haplotype_records: Self::haplotype_records_requested(args),
+            write_features: args
+                .try_get_one::<bool>("write-features")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false),
+            symbolic_haplotype_records: SymbolicHaplotypeRecords::from_args(args),
+            assembly_fallback: AssemblyFallbackCaller::from_args(args),
+            vcf_normalizer: VariantNormalizer::from_args(args),
+            accessible_genome: AccessibleGenome::new(),
+            callable_sites: CallableSites::new(),
+            consensus_depths: if ConsensusOptions::masking_requested(args) {
+                Some(ConsensusDepths::new())
+            } else {
+                None
+            },
+            active_regions: ActiveRegionLog::new(),
+            genome_overrides: genome_overrides.clone(),
         }
     }
 
+    /// The ploidy of the genome, which may be overridden by --per-genome-config
+    fn ploidy(&self, args: &clap::ArgMatches) -> usize {
+        self.genome_overrides
+            .ploidy
+            .unwrap_or_else(|| *args.get_one::<usize>("ploidy").unwrap())
+    }
+
     fn set_assembly_profile(
         args: &clap::ArgMatches, 
         kmer_sizes: &mut Vec<usize>, 
@@ -301,6 +408,114 @@ impl HaplotypeCallerEngine {
         self.stand_min_conf
     }
 
+    pub fn minimizer_filter(&self) -> Option<&MinimizerFilter> {
+        self.minimizer_filter.as_ref()
+    }
+
+    pub fn read_end_profile(&self) -> Option<&ReadEndProfile> {
+        self.read_end_profile.as_ref()
+    }
+
+    pub fn dropped_alleles(&self) -> &DroppedAlleleLog {
+        self.genotyping_engine.dropped_alleles()
+    }
+
+    /// The bases passing the depth filters in each window profiled for activity
+    pub fn accessible_genome(&self) -> &AccessibleGenome {
+        &self.accessible_genome
+    }
+
+    /// The positions passing the depth filter in each sample of each window profiled for activity
+    pub fn callable_sites(&self) -> &CallableSites {
+        &self.callable_sites
+    }
+
+    /// The depth of each sample in each window profiled for activity, recorded only when
+    /// consensus genomes mask uncertain positions
+    pub fn consensus_depths(&self) -> Option<&ConsensusDepths> {
+        self.consensus_depths.as_ref()
+    }
+
+    /// The outcome of every assembly region called so far
+    pub fn active_regions(&self) -> &ActiveRegionLog {
+        &self.active_regions
+    }
+
+    /// The random subset of the genome to call, if subsampling was requested
+    pub fn subsampler(&self) -> Option<&Subsampler> {
+        self.subsampler.as_ref()
+    }
+
+    pub fn forced_alleles(&self) -> Option<&ForcedAlleles> {
+        self.forced_alleles.as_ref()
+    }
+
+    /// Whether one record per assembled haplotype should be emitted instead of site level
+    /// records. Only available in call mode
+    pub fn haplotype_records_requested(args: &clap::ArgMatches) -> bool {
+        args.try_get_one::<bool>("haplotype-vcf")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn emits_haplotype_records(&self) -> bool {
+        self.haplotype_records
+    }
+
+    /// The symbolic haplotype records added to the VCF, if --emit-haplotype-records was given
+    pub fn symbolic_haplotype_records(&self) -> Option<&SymbolicHaplotypeRecords> {
+        self.symbolic_haplotype_records.as_ref()
+    }
+
+    /// Overrides the SNP and indel heterozygosity priors of every genotyper used for this genome
+    pub fn set_heterozygosity(&mut self, snp_het: f64, ind_het: f64, het_std: f64) {
+        self.genotype_prior_calculator =
+            GenotypePriorCalculator::assuming_hw(snp_het.log10(), ind_het.log10(), None);
+        self.active_region_evaluation_genotyper_engine
+            .set_heterozygosity(snp_het, ind_het, het_std);
+        self.genotyping_engine
+            .set_heterozygosity(snp_het, ind_het, het_std);
+    }
+
+    /// Computes read likelihoods on the GPU initialized for the run, if there is one
+    pub fn set_gpu_pair_hmm(&mut self, gpu_pair_hmm: Option<Arc<GpuPairHMM>>) {
+        self.likelihood_calculation_engine
+            .set_gpu_pair_hmm(gpu_pair_hmm);
+    }
+
+    /// The file stem of the VCF written for this genome, which names the shard when only one
+    /// shard of the genome is being called
+    pub fn vcf_file_stem(&self, reference_reader: &ReferenceReader) -> String {
+        let genome = &reference_reader.genomes_and_contigs.genomes[self.ref_idx];
+        match &self.scatter_shard {
+            Some(shard) => shard.file_stem(genome),
+            None => genome.to_string(),
+        }
+    }
+
+    /// The bases of the contigs of a genome that will be profiled. Progress is measured in these
+    /// bases, so the ETA reflects the size of the genome rather than the number of chunks
+    pub fn profiled_bases<I: IntoIterator<Item = u64>>(
+        target_lens: I,
+        min_contig_length: u64,
+    ) -> u64 {
+        target_lens
+            .into_iter()
+            .filter(|t_length| *t_length >= min_contig_length)
+            .sum::<u64>()
+    }
+
+    /// Bar over the processed bases of a genome with an estimate of the time remaining
+    pub fn profile_progress_style() -> ProgressStyle {
+        ProgressStyle::default_bar()
+            .template(
+                "[{elapsed_precise}] {bar:40.cyan/blue} {human_pos}/{human_len} bp (ETA {eta}) {msg}",
+            )
+            .unwrap()
+    }
+
     pub fn collect_activity_profile(
         &mut self,
         indexed_bam_readers: &[String],
@@ -332,9 +547,12 @@ impl HaplotypeCallerEngine {
             .get_one::<usize>("max-prob-propagation-distance")
             .unwrap();
 
-        let active_prob_thresh = *m
-            .get_one::<f32>("active-probability-threshold")
-            .unwrap();
+        let reader_threads = ThreadBudget::from_args(m).reader_threads();
+
+        let active_prob_thresh = self
+            .genome_overrides
+            .active_probability_threshold
+            .unwrap_or_else(|| *m.get_one::<f32>("active-probability-threshold").unwrap());
 
         let min_contig_length = *m
             .get_one::<u64>("min-contig-size")
@@ -352,7 +570,7 @@ impl HaplotypeCallerEngine {
         // debug!("Limiting {:?}", &limiting_interval);
 
         let ploidy: usize = max(
-            *m.get_one::<usize>("ploidy").unwrap(),
+            self.ploidy(m),
             Self::MINIMUM_PUTATIVE_PLOIDY_FOR_ACTIVE_REGION_DISCOVERY,
         );
 
@@ -360,7 +578,11 @@ impl HaplotypeCallerEngine {
         let mut tids: HashSet<usize> = HashSet::new();
         let mut found_contigs = HashMap::new();
         let reference = reference_reader.retrieve_reference_stem(ref_idx);
-
+        let separator = reference_reader.genomes_and_contigs.separator;
+        // --read-group-weights is checked at startup, and the read groups of each sample are
+        // taken from the BAM headers read here for the contigs of this genome
+        let read_group_weights = ReadGroupProfiles::weights_from_args(m).ok().flatten();
+        let mut read_groups_by_sample = Vec::with_capacity(indexed_bam_readers.len());
 
         indexed_bam_readers
             .iter()
@@ -377,14 +599,18 @@ impl HaplotypeCallerEngine {
                 // get reference stats
                 // let bam_generator = bam_generator.start();
                 let header = bam_generator.header(); // bam header
+                if read_group_weights.is_some() {
+                    read_groups_by_sample
+                        .push(ReadGroupProfiles::read_groups_from_header(header.as_bytes()));
+                }
                 let header_names = header.target_names();
                 header_names
                     .into_iter()
                     .enumerate()
                     .for_each(|(tid, contig_name)| {
                         let target_name = std::str::from_utf8(contig_name).unwrap();
-                        let target_match = if target_name.contains("~") {
-                            target_name.split_once("~").unwrap().0 == reference.as_str()
+                        let target_match = if let Some((genome, _)) = separator.split(target_name) {
+                            genome == reference.as_str()
                         } else {
                             target_name.contains(&reference)
                         };
@@ -413,31 +639,85 @@ impl HaplotypeCallerEngine {
                     })
             });
         
+        if m.get_flag("base-quality-recalibration") {
+            {
+                let pb = pb_tree.lock().unwrap();
+                pb[pb_index].set_stage("Learning base quality error model");
+            }
+            let mut sorted_tids = tids.iter().copied().collect::<Vec<usize>>();
+            sorted_tids.sort_unstable();
+            let recalibration_table = BaseRecalibrationTable::learn_from_bams(
+                indexed_bam_readers,
+                &sorted_tids,
+                reference_reader,
+                ref_idx,
+                flag_filters,
+                min_mapq,
+                short_sample_count,
+                if m.contains_id("longreads") || m.contains_id("longread-bam-files") {
+                    long_sample_count
+                } else {
+                    0
+                },
+                min_long_read_size,
+                min_long_read_average_base_qual,
+                n_threads,
+                reader_threads,
+                BaseRecalibrationTable::DEFAULT_MAX_OBSERVATIONS_PER_SAMPLE,
+            );
+            self.likelihood_calculation_engine
+                .set_base_recalibration_table(recalibration_table);
+        }
+
+        if let Some(weights) = read_group_weights {
+            self.likelihood_calculation_engine
+                .set_read_group_profiles(ReadGroupProfiles::new(read_groups_by_sample, weights));
+        }
+
         let total_sample_count = short_sample_count + long_sample_count;
         let chunk_size = max(250000 / total_sample_count, max_assembly_region_size * 5);
         let genome_size = reference_reader.target_lens.values().sum::<u64>();
 
-        // how many chunks are there going to be? for each contig, divide it's length by chunk size and count the ceiling
-        let mut n_chunks = 0;
-        for t_length in reference_reader.target_lens.values() {
-            if *t_length < min_contig_length {
-                continue;
-            }
+        let genome_bases = Self::profiled_bases(
+            tids.iter().map(|tid| reference_reader.target_lens[tid]),
+            min_contig_length,
+        );
 
-            n_chunks += (t_length / chunk_size as u64) as usize;
-            if t_length % chunk_size as u64 != 0 {
-                n_chunks += 1;
-            }
+        let chunk_offsets = ScatterShard::chunk_offsets(
+            &tids
+                .iter()
+                .map(|tid| (*tid, reference_reader.target_lens[tid]))
+                .collect::<Vec<(usize, u64)>>(),
+            chunk_size,
+            min_contig_length,
+        );
+        let n_chunks = tids
+            .iter()
+            .map(|tid| reference_reader.target_lens[tid])
+            .filter(|t_length| *t_length >= min_contig_length)
+            .map(|t_length| (t_length as usize + chunk_size - 1) / chunk_size)
+            .sum::<usize>();
+        let selected_chunks = self
+            .subsampler
+            .and_then(|subsampler| subsampler.selected_windows(n_chunks, ref_idx));
+        if let Some(selected_chunks) = &selected_chunks {
+            info!(
+                "{}: Subsampling {} of {} windows",
+                &reference,
+                selected_chunks.len(),
+                n_chunks
+            );
         }
 
         {
             let pb = pb_tree.lock().unwrap();
-            // simple spinner with a counter
-            let new_style = ProgressStyle::default_bar()
-                .template("[{elapsed_precise}] {spinner:.green} {msg} {pos:>7}/{len:7}").unwrap();
-            pb[pb_index].progress_bar.set_style(new_style);
-            pb[pb_index].progress_bar.set_length(n_chunks as u64);
-            pb[pb_index].progress_bar.set_message(format!("{}: Generating activity profile...", &pb[pb_index].key));
+            pb[pb_index]
+                .progress_bar
+                .set_style(Self::profile_progress_style());
+            pb[pb_index].progress_bar.set_length(genome_bases);
+            pb[pb_index].progress_bar.set_position(0);
+            pb[pb_index].progress_bar.reset_eta();
+            pb[pb_index].set_stage("Generating activity profile");
         }
 
         let contexts = tids
@@ -478,6 +758,14 @@ impl HaplotypeCallerEngine {
                                         position_limit.overlaps(limit)
                                     }
                                     None => true,
+                                } && match &self.scatter_shard {
+                                    Some(shard) => shard.contains(chunk_offsets[&tid] + chunk_idx),
+                                    None => true,
+                                } && match &selected_chunks {
+                                    Some(selected) => {
+                                        selected.contains(&(chunk_offsets[&tid] + chunk_idx))
+                                    }
+                                    None => true,
                                 };
 
                                 if within_bounds {
@@ -509,6 +797,7 @@ impl HaplotypeCallerEngine {
                                                 .next()
                                                 .unwrap();
                                             let mut bam_generated = bam_generator.start();
+                                            bam_generated.set_threads(reader_threads);
 
                                             let mut read_type = ReadType::Short;
 
@@ -582,9 +871,15 @@ impl HaplotypeCallerEngine {
                                             debug!("N. depth counts {:?}", val.1.shape());
                                             {
                                                 let pb = pb_tree.lock().unwrap();
-                                                pb[pb_index].progress_bar.inc(1);
+                                                pb[pb_index].progress_bar.inc(positions.len() as u64);
                                             }
                                             let (vc_vec, concatenated_array) = val;
+                                            self.accessible_genome.record(AccessibleWindow::new(
+                                                tid,
+                                                first,
+                                                last,
+                                                concatenated_array.clone(),
+                                            ));
                                             consolidator.0.extend(vc_vec);
                                             (consolidator.0, consolidator.1 + &concatenated_array)
                                         },
@@ -593,6 +888,9 @@ impl HaplotypeCallerEngine {
                                         }
                                     }
                                 } else {
+                                    // skipped chunks still count towards the bases of the genome
+                                    let pb = pb_tree.lock().unwrap();
+                                    pb[pb_index].progress_bar.inc(positions.len() as u64);
                                     consolidator
                                 }
                             })
@@ -619,7 +917,7 @@ impl HaplotypeCallerEngine {
             });
         {
             let pb = pb_tree.lock().unwrap();
-            pb[pb_index].progress_bar.finish_with_message(format!("{}: Finished generating activity profile.", &pb[pb_index].key));
+            pb[pb_index].finish_with_message(format!("{}: Finished generating activity profile.", &pb[pb_index].key));
         }
         (contexts.0, contexts.1)
     }
@@ -937,6 +1235,27 @@ impl HaplotypeCallerEngine {
         let depth_per_sample_filter = *args
             .get_one::<i64>("depth-per-sample-filter")
             .unwrap() as i32;
+
+        // positions given in a positions TSV are always made active so that they are genotyped
+        let forced_positions: &[ForcedPosition] = match (
+            &self.forced_alleles,
+            reference_reader.retrieve_contig_name_from_tid(tid),
+        ) {
+            (Some(forced_alleles), Some(contig_name)) => forced_alleles.positions_for_contig(
+                contig_name,
+                &reference_reader.genomes_and_contigs.separator,
+            ),
+            _ => &[],
+        };
+        // known polymorphic loci are boosted so that they are assembled at marginal coverage
+        let hotspot_intervals: &[(usize, usize)] = match (
+            &self.hotspots,
+            reference_reader.retrieve_contig_name_from_tid(tid),
+        ) {
+            (Some(hotspots), Some(contig_name)) => hotspots
+                .intervals_for_contig(contig_name, &reference_reader.genomes_and_contigs.separator),
+            _ => &[],
+        };
         
         // the total sample count will increase the number of RAM we will be using
         // each sample adds a "Genotype" struct which is a large struct with many fields
@@ -993,6 +1312,11 @@ impl HaplotypeCallerEngine {
                     let mut depth_of_position =
                         vec![Vec::with_capacity(n_positions); sample_names.len()];
                     let mut depths_counters = vec![0; sample_names.len()];
+                    let mut consensus_depth_window = self
+                        .consensus_depths
+                        .as_ref()
+                        .map(|_| DepthWindow::new(tid, 0, sample_names.len()));
+                    let mut first_position = None;
                     for pos in positions {
                         match &limiting_interval {
                             Some(limit) => {
@@ -1009,8 +1333,10 @@ impl HaplotypeCallerEngine {
                                 //
                             }
                         }
+                        first_position.get_or_insert(pos);
                         let mut genotypes = Vec::new();
                         let hq_soft_clips = per_contig_per_base_hq_soft_clips[pos];
+                        let mut covered = false;
 
                         // ANI should only be performed on "compared bases", that is bases that were >= depth per sample filter in both sample.
                         // First we need to store the number of bases in a sample above the require depth, for a contig of length 10:
@@ -1026,6 +1352,10 @@ impl HaplotypeCallerEngine {
                         // - `[3, -2, 4, -1]`
                         for (idx, sample_likelihoods) in genotype_likelihoods.iter().enumerate() {
                             let ref_v_any = &sample_likelihoods[pos];
+                            covered |= ref_v_any.get_dp() > 0;
+                            if let Some(depth_window) = consensus_depth_window.as_mut() {
+                                depth_window.push(idx, ref_v_any.get_dp());
+                            }
                             
                             // create compressed array of bases passing the depth filter.
                             // Used during ANI calculations to determine number of comparable
@@ -1077,13 +1407,30 @@ impl HaplotypeCallerEngine {
                             self.stand_min_conf,
                         );
 
-                        let is_active_prob = match vc_out {
+                        let mut is_active_prob = match vc_out {
                             Some(vc) => {
                                 QualityUtils::qual_to_prob(vc.get_phred_scaled_qual() as u8)
                             }
                             None => 0.0,
                         };
 
+                        if ForcedAlleles::positions_in(
+                            forced_positions,
+                            contig_position,
+                            contig_position,
+                        )
+                        .next()
+                        .is_some()
+                        {
+                            is_active_prob = ForcedAlleles::ACTIVE_PROBABILITY as f64;
+                        }
+
+                        if let Some(hotspots) = &self.hotspots {
+                            if covered && Hotspots::contains(hotspot_intervals, contig_position) {
+                                is_active_prob = hotspots.boost(is_active_prob);
+                            }
+                        }
+
                         // debug!(
                         //     "{}-{} Active Prob {}",
                         //     chunk_location.start + pos,
@@ -1109,6 +1456,19 @@ impl HaplotypeCallerEngine {
                     for (idx, sample_depth) in depths_counters.into_iter().enumerate() {
                         depth_of_position[idx].push(sample_depth);
                     }
+                    if let Some(first_position) = first_position {
+                        self.callable_sites.record(CallableWindow::new(
+                            tid,
+                            chunk_location.start + first_position,
+                            depth_of_position.clone(),
+                        ));
+                        if let (Some(consensus_depths), Some(mut depth_window)) =
+                            (&self.consensus_depths, consensus_depth_window)
+                        {
+                            depth_window.start = chunk_location.start + first_position;
+                            consensus_depths.record(depth_window);
+                        }
+                    }
                     
                     let comparable_bases = ANICalculator::calculate_compared_bases(Some(depth_of_position), n_positions as u64, total_sample_count);
 
@@ -1161,21 +1521,80 @@ impl HaplotypeCallerEngine {
      */
     pub fn call_region<'b>(
         &mut self,
-        mut region: AssemblyRegion,
+        region: AssemblyRegion,
         reference_reader: &'b mut ReferenceReader,
         given_alleles: Vec<VariantContext>,
         args: &'b clap::ArgMatches,
         sample_names: &'b [String],
         flag_filters: &'b FlagFilter,
     ) -> Vec<VariantContext> {
+        let (tid, start, end, reads) = (
+            region.get_contig(),
+            region.get_start(),
+            region.get_end(),
+            region.len(),
+        );
+        let (calls, status, haplotypes) = self.call_region_with_outcome(
+            region,
+            reference_reader,
+            given_alleles,
+            args,
+            sample_names,
+            flag_filters,
+        );
+
+        // reads beyond the maximum input depth were discarded while filling the region
+        let max_input_depth = args
+            .try_get_one::<usize>("max-input-depth")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(usize::MAX);
+        let status = if reads >= max_input_depth && status != RegionStatus::Failed {
+            RegionStatus::Downsampled
+        } else {
+            status
+        };
+        self.active_regions.record(RegionOutcome::new(
+            tid,
+            start,
+            end,
+            status,
+            reads,
+            haplotypes,
+            calls.len(),
+        ));
+
+        calls
+    }
+
+    /// Calls variants in a region, returning the calls along with the status of the region and
+    /// the number of assembled haplotypes
+    fn call_region_with_outcome<'b>(
+        &mut self,
+        mut region: AssemblyRegion,
+        reference_reader: &'b mut ReferenceReader,
+        given_alleles: Vec<VariantContext>,
+        args: &'b clap::ArgMatches,
+        sample_names: &'b [String],
+        flag_filters: &'b FlagFilter,
+    ) -> (Vec<VariantContext>, RegionStatus, usize) {
         let vc_priors = Vec::new();
 
         if !region.is_active() && given_alleles.is_empty() {
-            return self.reference_model_for_no_variation(&mut region, true, &vc_priors);
+            return (
+                self.reference_model_for_no_variation(&mut region, true, &vc_priors),
+                RegionStatus::ReferenceOnly,
+                0,
+            );
         }
 
         if given_alleles.is_empty() && region.len() == 0 {
-            return self.reference_model_for_no_variation(&mut region, true, &vc_priors);
+            return (
+                self.reference_model_for_no_variation(&mut region, true, &vc_priors),
+                RegionStatus::ReferenceOnly,
+                0,
+            );
         }
 
         let region_without_reads = region.clone_without_reads();
@@ -1189,17 +1608,30 @@ impl HaplotypeCallerEngine {
             &mut self.assembly_engine,
             true,
             sample_names,
+            self.minimizer_filter.as_ref(),
+            self.read_end_profile.as_ref(),
         );
+        let n_haplotypes = untrimmed_assembly_result.haplotypes.len();
+
+        if untrimmed_assembly_result.assembly_failed {
+            return self.call_failed_region(
+                &mut untrimmed_assembly_result,
+                args,
+                sample_names,
+                n_haplotypes,
+            );
+        }
 
         let all_variation_events = match untrimmed_assembly_result
             .get_variation_events(*args.get_one::<usize>("max-mnp-distance").unwrap())
         {
             Ok(result) => result,
             Err(_) => {
-                return self.reference_model_for_no_variation(
-                    &mut untrimmed_assembly_result.region_for_genotyping,
-                    true,
-                    &vc_priors,
+                return self.call_failed_region(
+                    &mut untrimmed_assembly_result,
+                    args,
+                    sample_names,
+                    n_haplotypes,
                 )
             }
         };
@@ -1225,10 +1657,14 @@ impl HaplotypeCallerEngine {
         // debug!("Trim complete!");
 
         if !trimming_result.is_variation_present() && !args.get_flag("disable-optimizations") {
-            return self.reference_model_for_no_variation(
-                &mut trimming_result.original_region,
-                false,
-                &vc_priors,
+            return (
+                self.reference_model_for_no_variation(
+                    &mut trimming_result.original_region,
+                    false,
+                    &vc_priors,
+                ),
+                RegionStatus::ReferenceOnly,
+                n_haplotypes,
             );
         }
 
@@ -1281,10 +1717,14 @@ impl HaplotypeCallerEngine {
             AssemblyBasedCallerUtils::split_reads_by_sample(filtered_reads);
 
         if !assembly_result.variation_present || assembly_result.region_for_genotyping.len() == 0 {
-            return self.reference_model_for_no_variation(
-                &mut assembly_result.region_for_genotyping,
-                false,
-                &vc_priors,
+            return (
+                self.reference_model_for_no_variation(
+                    &mut assembly_result.region_for_genotyping,
+                    false,
+                    &vc_priors,
+                ),
+                RegionStatus::ReferenceOnly,
+                n_haplotypes,
             );
         };
 
@@ -1338,10 +1778,14 @@ impl HaplotypeCallerEngine {
         //         .collect::<Vec<&str>>()
         // );
         if read_likelihoods.alleles.len() == 1 {
-            return self.reference_model_for_no_variation(
-                &mut assembly_result.region_for_genotyping,
-                false,
-                &vc_priors,
+            return (
+                self.reference_model_for_no_variation(
+                    &mut assembly_result.region_for_genotyping,
+                    false,
+                    &vc_priors,
+                ),
+                RegionStatus::ReferenceOnly,
+                n_haplotypes,
             );
         };
         // Realign reads to their best haplotype.
@@ -1357,6 +1801,18 @@ impl HaplotypeCallerEngine {
         );
         read_likelihoods.change_evidence(read_alignments);
 
+        if self.haplotype_records {
+            return (
+                HaplotypeRecords::from_likelihoods(&read_likelihoods),
+                RegionStatus::AssembledVariation,
+                n_haplotypes,
+            );
+        }
+
+        if let Some(symbolic_haplotype_records) = &self.symbolic_haplotype_records {
+            symbolic_haplotype_records.record(&read_likelihoods);
+        }
+
         // if debug {
         // debug!(
         //     "After change {:?}",
@@ -1376,6 +1832,7 @@ impl HaplotypeCallerEngine {
         //  GLs.  In particular, for samples that are heterozygous non-reference (B/C) the marginalization for B treats the
         //  haplotype containing C as reference (and vice versa).  Now this is fine if all possible haplotypes are included
         //  in the genotyping, but we lose information if we select down to a few haplotypes.  [EB]
+        let ploidy = self.ploidy(args);
         let called_haplotypes = match self.genotyping_engine.assign_genotype_likelihoods(
             assembly_result.haplotypes.clone(),
             read_likelihoods,
@@ -1387,17 +1844,21 @@ impl HaplotypeCallerEngine {
             false,
             *args.get_one::<usize>("max-mnp-distance").unwrap(),
             sample_names,
-            *args.get_one::<usize>("ploidy").unwrap(),
+            ploidy,
             args,
             &reference_reader,
             self.stand_min_conf,
         ) {
             Ok(result) => result,
             Err(_) => {
-                return self.reference_model_for_no_variation(
-                    &mut assembly_result.region_for_genotyping,
-                    false,
-                    &vc_priors,
+                return (
+                    self.reference_model_for_no_variation(
+                        &mut assembly_result.region_for_genotyping,
+                        false,
+                        &vc_priors,
+                    ),
+                    RegionStatus::Failed,
+                    n_haplotypes,
                 );
             }
         };
@@ -1406,7 +1867,46 @@ impl HaplotypeCallerEngine {
         //       Emit reference confidence? Maybe
         //
 
-        return called_haplotypes.calls;
+        return (
+            called_haplotypes.calls,
+            RegionStatus::AssembledVariation,
+            n_haplotypes,
+        );
+    }
+
+    /// Calls the SNVs of a region whose assembly failed from a pileup of its reads, unless the
+    /// fallback caller is disabled, in which case the region is emitted as reference only
+    fn call_failed_region(
+        &mut self,
+        assembly_result: &mut AssemblyResultSet<ReadThreadingGraph>,
+        args: &clap::ArgMatches,
+        sample_names: &[String],
+        n_haplotypes: usize,
+    ) -> (Vec<VariantContext>, RegionStatus, usize) {
+        let ploidy = self.ploidy(args);
+        let calls = match &self.assembly_fallback {
+            Some(assembly_fallback) if !self.haplotype_records => assembly_fallback.call(
+                &assembly_result.region_for_genotyping.reads,
+                &assembly_result.full_reference_with_padding,
+                &assembly_result.padded_reference_loc,
+                &assembly_result.region_for_genotyping.active_span,
+                sample_names.len(),
+                ploidy,
+            ),
+            _ => Vec::new(),
+        };
+        if calls.is_empty() {
+            return (
+                self.reference_model_for_no_variation(
+                    &mut assembly_result.region_for_genotyping,
+                    true,
+                    &Vec::new(),
+                ),
+                RegionStatus::Failed,
+                n_haplotypes,
+            );
+        }
+        (calls, RegionStatus::Failed, n_haplotypes)
     }
 
     fn filter_non_passing_reads(
@@ -1830,13 +2330,13 @@ impl HaplotypeCallerEngine {
         // ensure path exists
         create_dir_all(output_prefix).expect("Unable to create output directory");
 
-        // Initiate writer
+        // Initiate writer, the VCF is only moved into place once every record is written
+        let vcf_output = PartialOutput::new(format!(
+            "{}/{}.vcf",
+            output_prefix, self.vcf_file_stem(reference_reader),
+        ));
         let mut bcf_writer = Writer::from_path(
-            format!(
-                "{}/{}.vcf",
-                output_prefix, &reference_reader.genomes_and_contigs.genomes[self.ref_idx],
-            )
-            .as_str(),
+            vcf_output.partial_path(),
             &header,
             true,
             Format::Vcf, // uncompressed. Bcf compression seems busted?
@@ -1888,6 +2388,8 @@ impl HaplotypeCallerEngine {
             .expect("Unable to push format tag");
 
         bcf_writer.write(&record).expect("Unable to write empty record");
+        drop(bcf_writer);
+        vcf_output.commit().expect("Unable to move VCF output into place");
     }
 
     /// Takes a vector of VariantContexts and writes them to a single VCF4 file
@@ -1899,7 +2401,86 @@ impl HaplotypeCallerEngine {
         reference_reader: &ReferenceReader,
         strain_info: bool,
     ) {
-        if variant_contexts.len() == 0 {
+        // haplotype records span the whole active region, so are written as assembled
+        let normalized;
+        let variant_contexts = match &self.vcf_normalizer {
+            Some(normalizer) if !self.haplotype_records => {
+                normalized = normalizer.normalize_contexts(
+                    variant_contexts,
+                    &mut reference_reader.clone(),
+                    self.ref_idx,
+                );
+                &normalized
+            }
+            _ => variant_contexts,
+        };
+
+        // symbolic haplotype records only join the site level records here, so that nothing
+        // else downstream counts them as variants
+        let merged;
+        let variant_contexts = match &self.symbolic_haplotype_records {
+            Some(records) if !records.is_empty() => {
+                merged = records.merge(variant_contexts);
+                &merged
+            }
+            _ => variant_contexts,
+        };
+
+        self.write_sorted_vcf(
+            output_prefix,
+            variant_contexts.iter(),
+            sample_names,
+            reference_reader,
+            strain_info,
+        );
+    }
+
+    /// Normalises the contexts and adds the symbolic haplotype records as `write_vcf` does, for
+    /// contexts that are then sorted and streamed to `write_sorted_vcf`
+    pub fn prepare_vcf_contexts(
+        &self,
+        variant_contexts: Vec<VariantContext>,
+        reference_reader: &ReferenceReader,
+    ) -> Vec<VariantContext> {
+        let mut variant_contexts = self.normalize_vcf_contexts(variant_contexts, reference_reader);
+        if let Some(records) = &self.symbolic_haplotype_records {
+            records.append_to(&mut variant_contexts);
+        }
+        variant_contexts
+    }
+
+    /// Normalises the contexts as `write_vcf` does, without adding the symbolic haplotype records.
+    /// Sorted contexts stay sorted
+    pub fn normalize_vcf_contexts(
+        &self,
+        variant_contexts: Vec<VariantContext>,
+        reference_reader: &ReferenceReader,
+    ) -> Vec<VariantContext> {
+        match &self.vcf_normalizer {
+            Some(normalizer) if !self.haplotype_records => normalizer.normalize_contexts(
+                &variant_contexts,
+                &mut reference_reader.clone(),
+                self.ref_idx,
+            ),
+            _ => variant_contexts,
+        }
+    }
+
+    /// Writes VariantContexts that are already normalised and sorted to a single VCF4 file, one
+    /// at a time, so a sorted stream of contexts never needs to be held in memory at once
+    pub fn write_sorted_vcf<I, V>(
+        &self,
+        output_prefix: &str,
+        variant_contexts: I,
+        sample_names: &[&str],
+        reference_reader: &ReferenceReader,
+        strain_info: bool,
+    ) where
+        I: IntoIterator<Item = V>,
+        V: Borrow<VariantContext>,
+    {
+        let mut variant_contexts = variant_contexts.into_iter().peekable();
+        if variant_contexts.peek().is_none() {
 
             self.write_empty_vcf(
                 output_prefix,
@@ -1909,11 +2490,11 @@ impl HaplotypeCallerEngine {
             );
             let out_file_name = format!(
                 "{}/{}.vcf",
-                output_prefix, &reference_reader.genomes_and_contigs.genomes[self.ref_idx],
+                output_prefix, self.vcf_file_stem(reference_reader),
             );
             let out_file_name_tmp = format!(
                 "{}/{}.vcf.tmp",
-                output_prefix, &reference_reader.genomes_and_contigs.genomes[self.ref_idx],
+                output_prefix, self.vcf_file_stem(reference_reader),
             );
 
             {
@@ -1932,6 +2513,7 @@ impl HaplotypeCallerEngine {
                 }
             }
             std::fs::rename(out_file_name_tmp.as_str(), out_file_name.as_str()).expect("Unable to rename VCF file");
+            self.write_features_table(output_prefix, reference_reader);
 
             return;
         }
@@ -1944,22 +2526,40 @@ impl HaplotypeCallerEngine {
         // ensure path exists
         create_dir_all(output_prefix).expect("Unable to create output directory");
 
-        // Initiate writer
+        // Initiate writer, the VCF is only moved into place once every record is written
+        let vcf_output = PartialOutput::new(format!(
+            "{}/{}.vcf",
+            output_prefix, self.vcf_file_stem(reference_reader),
+        ));
         let mut bcf_writer = Writer::from_path(
-            format!(
-                "{}/{}.vcf",
-                output_prefix, &reference_reader.genomes_and_contigs.genomes[self.ref_idx],
-            )
-            .as_str(),
+            vcf_output.partial_path(),
             &header,
             true,
             Format::Vcf, // uncompressed. Bcf compression seems busted?
         )
         .unwrap_or_else(|_| panic!("Unable to create VCF output: {}.vcf", output_prefix));
 
-
         for vc in variant_contexts {
-            vc.write_as_vcf_record(&mut bcf_writer, reference_reader, sample_names.len());
+            vc.borrow()
+                .write_as_vcf_record(&mut bcf_writer, reference_reader, sample_names.len());
+        }
+        drop(bcf_writer);
+        vcf_output.commit().expect("Unable to move VCF output into place");
+        self.write_features_table(output_prefix, reference_reader);
+    }
+
+    /// Writes the features of every record of the VCF just written, filtered records included,
+    /// when --write-features was given
+    fn write_features_table(&self, output_prefix: &str, reference_reader: &ReferenceReader) {
+        if !self.write_features {
+            return;
+        }
+        let file_stem = self.vcf_file_stem(reference_reader);
+        let vcf_path = format!("{}/{}.vcf", output_prefix, file_stem);
+        let features_path = format!("{}/{}_features.tsv", output_prefix, file_stem);
+        match VariantFeatures::write_table(&vcf_path, &features_path) {
+            Ok(rows) => debug!("Wrote {} feature rows to {}", rows, features_path),
+            Err(e) => warn!("Unable to write features of {}: {:?}", vcf_path, e),
         }
     }
 
@@ -1971,6 +2571,9 @@ impl HaplotypeCallerEngine {
         strain_info: bool,
     ) {
         header.push_record(format!("##source=lorikeet-v{}", env!("CARGO_PKG_VERSION")).as_bytes());
+        for record in self.provenance.header_records() {
+            header.push_record(record.as_bytes());
+        }
 
         // debug!("samples {:?}", &sample_names);
         for sample_idx in 0..sample_names.len() {
@@ -1986,22 +2589,80 @@ impl HaplotypeCallerEngine {
             header.push_sample(format!("{}", sample_idx + 1).as_bytes());
         }
 
-        // Add contig info
+        // Add contig info, with the checksum of each contig so the reference can be verified
+        let mut contig_reader = reference_reader.clone();
         for tid in reference_reader
             .retrieve_tids_for_ref_index(self.ref_idx)
             .unwrap()
             .iter()
         {
+            let md5 = match contig_reader.fetch_contig_from_reference_by_tid(*tid, self.ref_idx) {
+                Ok(_) => {
+                    contig_reader.read_sequence_to_vec();
+                    format!(
+                        ", md5={}",
+                        VcfProvenance::contig_md5(&contig_reader.current_sequence)
+                    )
+                }
+                Err(_) => String::new(),
+            };
             header.push_record(
                 format!(
-                    "##contig=<ID={}, length={}>",
+                    "##contig=<ID={}, length={}{}>",
                     std::str::from_utf8(reference_reader.get_target_name(*tid)).unwrap(),
-                    reference_reader.target_lens.get(&tid).unwrap()
+                    reference_reader.target_lens.get(&tid).unwrap(),
+                    md5
                 )
                 .as_bytes(),
             );
         }
 
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Variant overlaps a masked low mappability region\">",
+                Filter::Masked.to_key()
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Indel in a long reference homopolymer run\">",
+                Filter::HomopolymerIndel.to_key()
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Variant in low complexity reference sequence, with a DUST score above the low-complexity-filter\">",
+                Filter::LowComplexity.to_key()
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Read depth around the variant suggests a duplication or deletion, allele fractions may not reflect strain frequencies\">",
+                Filter::CopyNumber.to_key()
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Variant QD is below the qual-by-depth-filter, excluded from genotyping and ANI\">",
+                Filter::LowQD.to_key()
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"No sample supports an alternate allele with at least depth-per-sample-filter reads, or too few reads support it for genotyping\">",
+                Filter::LowDepth.to_key()
+            )
+            .as_bytes(),
+        );
+        if self.symbolic_haplotype_records.is_some() {
+            header.push_record(SymbolicHaplotypeRecords::header_record().as_bytes());
+        }
+
         VariantAnnotationEngine::populate_vcf_header(header, strain_info);
     }
 }
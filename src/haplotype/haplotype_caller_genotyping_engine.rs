@@ -1,13 +1,14 @@
 use hashlink::linked_hash_map::LinkedHashMap;
 use hashlink::LinkedHashSet;
 use ordered_float::OrderedFloat;
-use std::cmp::Ordering;
+use std::cmp::{Ordering, Reverse};
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::annotator::variant_annotation::Annotation;
 use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
 use crate::assembly::assembly_based_caller_utils::AssemblyBasedCallerUtils;
+use crate::genotype::dropped_alleles::{DropReason, DroppedAllele, DroppedAlleleLog};
 use crate::genotype::genotype_builder::{Genotype, GenotypesContext};
 use crate::genotype::genotype_likelihood_calculators::GenotypeLikelihoodCalculators;
 use crate::genotype::genotype_prior_calculator::GenotypePriorCalculator;
@@ -15,6 +16,7 @@ use crate::genotype::genotyping_engine::GenotypingEngine;
 use crate::haplotype::called_haplotypes::CalledHaplotypes;
 use crate::haplotype::event_map::EventMap;
 use crate::haplotype::haplotype::Haplotype;
+use crate::haplotype::haplotype_scoring::HaplotypeScoringModel;
 use crate::haplotype::homogenous_ploidy_model::HomogeneousPloidyModel;
 use crate::haplotype::independent_samples_genotype_model::IndependentSamplesGenotypesModel;
 use crate::model::allele_likelihoods::AlleleLikelihoods;
@@ -37,6 +39,8 @@ pub struct HaplotypeCallerGenotypingEngine {
     max_genotype_count_to_enumerate: usize,
     practical_allele_count_for_ploidy: HashMap<usize, usize>,
     do_physical_phasing: bool,
+    sv_info_min_indel_length: usize,
+    haplotype_scoring_model: HaplotypeScoringModel,
 }
 
 impl HaplotypeCallerGenotypingEngine {
@@ -71,9 +75,29 @@ impl HaplotypeCallerGenotypingEngine {
                 .get_one::<f64>("indel-heterozygosity")
                 .unwrap(),
             practical_allele_count_for_ploidy: HashMap::new(),
+            sv_info_min_indel_length: args
+                .try_get_one::<usize>("sv-info-min-indel-length")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(0),
+            haplotype_scoring_model: HaplotypeScoringModel::from_args(args),
         }
     }
 
+    /// The alternate alleles dropped at sites with too many alleles to genotype
+    pub fn dropped_alleles(&self) -> &DroppedAlleleLog {
+        self.genotyping_engine.dropped_alleles()
+    }
+
+    /// Overrides the SNP and indel heterozygosity priors given on the command line
+    pub fn set_heterozygosity(&mut self, snp_het: f64, ind_het: f64, het_std: f64) {
+        self.snp_heterozygosity = snp_het;
+        self.indel_heterozygosity = ind_het;
+        self.genotyping_engine
+            .set_heterozygosity(snp_het, ind_het, het_std);
+    }
+
     /**
      * Main entry point of class - given a particular set of haplotypes, samples and reference context, compute
      * genotype likelihoods and assemble into a list of variant contexts and genomic events ready for calling
@@ -127,11 +151,20 @@ impl HaplotypeCallerGenotypingEngine {
             ref_bases,
             &ref_loc,
             max_mnp_distance,
+            args.get_flag("emit-complex-events"),
         ) {
             Ok(result) => result,
             Err(error) => return Err(error),
         };
 
+        // rescore the haplotypes used to choose which alleles to keep at sites with too many
+        self.haplotype_scoring_model
+            .score_haplotypes(&mut haplotypes, &read_likelihoods);
+
+        // number of reads best supporting each haplotype in each sample, only calculated once a
+        // site has too many alleles
+        let mut haplotype_support = None;
+
         // Walk along each position in the key set and create each event to be outputted
         let mut called_haplotypes = HashSet::new();
         let mut return_calls = Vec::new();
@@ -192,6 +225,8 @@ impl HaplotypeCallerGenotypingEngine {
                         ploidy,
                         &mut allele_mapper,
                         &mut merged_vc,
+                        &read_likelihoods,
+                        &mut haplotype_support,
                     ) {
                         Ok(_) => (),
                         Err(error) => {
@@ -479,13 +514,16 @@ impl HaplotypeCallerGenotypingEngine {
         //     merged_alleles_list_size_before_possible_trimming
         // );
 
-        if untrimmed_result.get_alleles_ref().len()
+        let mut result = if untrimmed_result.get_alleles_ref().len()
             == merged_alleles_list_size_before_possible_trimming
         {
-            return untrimmed_result;
+            untrimmed_result
         } else {
-            return VariantContextUtils::reverse_trim_alleles(&untrimmed_result);
-        }
+            VariantContextUtils::reverse_trim_alleles(&untrimmed_result)
+        };
+        result.add_structural_variant_info(self.sv_info_min_indel_length);
+
+        return result;
     }
 
     fn resolve_genotype_prior_calculator(
@@ -557,9 +595,9 @@ impl HaplotypeCallerGenotypingEngine {
 
     /**
      * If the number of alleles is so high that enumerating all possible genotypes is impractical, as determined by
-     * {@link #maxGenotypeCountToEnumerate}, remove alt alleles from the input {@code alleleMapper} that are
-     * not well supported by good-scored haplotypes.
-     * Otherwise do nothing.
+     * {@link #maxGenotypeCountToEnumerate}, or there are more alt alleles than --max-alternate-alleles, remove alt
+     * alleles from the input {@code alleleMapper} that are not well supported by good-scored haplotypes or reads.
+     * Otherwise do nothing. Removed alleles are recorded in the dropped allele log.
      *
      * Alleles kept are guaranteed to have higher precedence than those removed, where precedence is determined by
      * {@link AlleleScoredByHaplotypeScores}.
@@ -568,12 +606,15 @@ impl HaplotypeCallerGenotypingEngine {
      * that is, entries will be only be removed but not not shifted relative to each other.
      *  @param ploidy        ploidy of the sample
      * @param alleleMapper  original allele to haplotype map
+     * @param haplotypeSupport  per sample read support of each haplotype, calculated on first use
      */
     fn remove_alt_alleles_if_too_many_genotypes<'b>(
         &mut self,
         ploidy: usize,
         allele_mapper: &mut LinkedHashMap<usize, Vec<&'b Haplotype<SimpleInterval>>>,
         merged_vc: &mut VariantContext,
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        haplotype_support: &mut Option<Vec<Vec<usize>>>,
     ) -> anyhow::Result<()> {
         let original_allele_count = allele_mapper.len();
         let max_genotype_count_to_enumerate = self.max_genotype_count_to_enumerate;
@@ -586,14 +627,25 @@ impl HaplotypeCallerGenotypingEngine {
                     max_genotype_count_to_enumerate,
                 )
             });
-
-        if original_allele_count > *practical_allele_count {
-            let alleles_to_keep = Self::which_alleles_to_keep_based_on_hap_scores(
-                allele_mapper,
-                merged_vc,
-                *practical_allele_count,
-            )?;
+        // the reference allele is not counted towards the maximum number of alternate alleles
+        let practical_allele_count =
+            (*practical_allele_count).min(self.genotyping_engine.max_alternate_alleles + 1);
+
+        if original_allele_count > practical_allele_count {
+            let haplotype_support = haplotype_support
+                .get_or_insert_with(|| Self::haplotype_read_support(read_likelihoods));
+            let (alleles_to_keep, dropped_alleles) =
+                Self::which_alleles_to_keep_based_on_hap_scores(
+                    allele_mapper,
+                    merged_vc,
+                    practical_allele_count,
+                    read_likelihoods,
+                    haplotype_support,
+                )?;
             allele_mapper.retain(|allele, _| alleles_to_keep.contains(&allele));
+            for dropped_allele in dropped_alleles {
+                self.genotyping_engine.dropped_alleles.record(dropped_allele);
+            }
 
             // debug!(
             //     "At position {:?} removed alt alleles where ploidy is {} and original allele count \
@@ -646,25 +698,83 @@ impl HaplotypeCallerGenotypingEngine {
         Ok(())
     }
 
+    /**
+     * Counts the informative reads of each sample best explained by each haplotype.
+     *
+     * @return the read count of each haplotype, indexed by sample then haplotype
+     */
+    fn haplotype_read_support(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> Vec<Vec<usize>> {
+        (0..read_likelihoods.number_of_samples())
+            .map(|sample_index| {
+                let mut support = vec![0; read_likelihoods.number_of_alleles()];
+                let best_alleles = read_likelihoods.best_alleles_breaking_ties_for_sample(sample_index);
+                for best_allele in best_alleles {
+                    if let Some(haplotype_index) = best_allele.allele_index {
+                        if best_allele.is_informative() {
+                            support[haplotype_index] += 1;
+                        }
+                    }
+                }
+                support
+            })
+            .collect::<Vec<Vec<usize>>>()
+    }
+
+    /**
+     * The read support of an allele, the reads best explained by any of its haplotypes.
+     *
+     * @return the highest support within a single sample and the support summed across samples
+     */
+    fn allele_read_support(
+        haplotypes: &[&Haplotype<SimpleInterval>],
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        haplotype_support: &[Vec<usize>],
+    ) -> (usize, usize) {
+        let haplotype_indices = haplotypes
+            .iter()
+            .filter_map(|haplotype| read_likelihoods.index_of_allele(haplotype))
+            .collect::<Vec<usize>>();
+        haplotype_support
+            .iter()
+            .map(|sample_support| {
+                haplotype_indices
+                    .iter()
+                    .map(|index| sample_support[*index])
+                    .sum::<usize>()
+            })
+            .fold((0, 0), |(max, total), support| {
+                (max.max(support), total + support)
+            })
+    }
+
     /**
      * Returns a list of alleles that is a subset of the key set of input map {@code alleleMapper}.
      * The size of the returned list is min({@code desiredNumOfAlleles}, alleleMapper.size()).
      *
      * Alleles kept are guaranteed to have higher precedence than those removed, where precedence is determined by
-     * {@link AlleleScoredByHaplotypeScores}.
+     * {@link AlleleScoredByHaplotypeScores}. Alleles with tied haplotype scores are ranked by their read support
+     * within a single sample, so that alleles private to one sample are not lost to alleles weakly present in many.
      *
      * Entries in the returned list are guaranteed to have the same relative order as they were in the input map.
      *
      * @param alleleMapper          original allele to haplotype map
      * @param desiredNumOfAlleles   desired allele count, including ref allele
+     * @return the alleles to keep and the alleles dropped
      */
     fn which_alleles_to_keep_based_on_hap_scores<'b>(
         allele_mapper: &mut LinkedHashMap<usize, Vec<&'b Haplotype<SimpleInterval>>>,
-        merged_vc: &mut VariantContext,
+        merged_vc: &VariantContext,
         desired_num_of_alleles: usize,
-    ) -> anyhow::Result<Vec<usize>> {
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        haplotype_support: &[Vec<usize>],
+    ) -> anyhow::Result<(Vec<usize>, Vec<DroppedAllele>)> {
         if allele_mapper.len() <= desired_num_of_alleles {
-            return Ok(allele_mapper.keys().map(|a| *a).collect::<Vec<usize>>());
+            return Ok((
+                allele_mapper.keys().map(|a| *a).collect::<Vec<usize>>(),
+                Vec::new(),
+            ));
         }
 
         let mut allele_max_priority_q = BinaryHeap::new();
@@ -688,21 +798,48 @@ impl HaplotypeCallerGenotypingEngine {
                 f64::NEG_INFINITY
             };
 
-            allele_max_priority_q.push(AlleleScoredByHaplotype::new(
+            let (max_sample_support, total_support) = Self::allele_read_support(
+                allele_mapper.get(allele).unwrap(),
+                read_likelihoods,
+                haplotype_support,
+            );
+
+            // alleles of highest precedence compare as least, so the heap is reversed to pop them first
+            allele_max_priority_q.push(Reverse(AlleleScoredByHaplotype::new(
                 &merged_vc.alleles[*allele],
                 highest_score,
                 second_highest_score,
+                max_sample_support,
+                total_support,
                 *allele,
-            ));
+            )));
         }
 
         let mut alleles_to_retain = LinkedHashSet::new();
         let mut current_allele;
         while alleles_to_retain.len() < desired_num_of_alleles && allele_max_priority_q.len() > 0 {
-            current_allele = allele_max_priority_q.pop().unwrap().get_allele_index();
+            current_allele = allele_max_priority_q.pop().unwrap().0.get_allele_index();
             alleles_to_retain.insert(current_allele);
         }
 
+        let ref_allele = merged_vc.get_reference();
+        let dropped_alleles = allele_max_priority_q
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(scored_allele)| {
+                DroppedAllele::new(
+                    merged_vc.loc.tid,
+                    merged_vc.loc.start,
+                    ref_allele,
+                    scored_allele.allele,
+                    DropReason::HaplotypeAlleles,
+                    scored_allele.best_haplotype_score,
+                    scored_allele.max_sample_support,
+                    scored_allele.total_support,
+                )
+            })
+            .collect::<Vec<DroppedAllele>>();
+
         // get the reference allele index and ensure that it remains in the 
         // alleles to retain set
         if let Some(ref_allele_index) = merged_vc.alleles
@@ -716,11 +853,14 @@ impl HaplotypeCallerGenotypingEngine {
             anyhow::bail!("Reference allele not found in merged VC alleles!")
         }
 
-        return Ok(allele_mapper
-            .keys()
-            .filter(|a| alleles_to_retain.contains(a))
-            .map(|a| *a)
-            .collect::<Vec<usize>>());
+        return Ok((
+            allele_mapper
+                .keys()
+                .filter(|a| alleles_to_retain.contains(a))
+                .map(|a| *a)
+                .collect::<Vec<usize>>(),
+            dropped_alleles,
+        ));
     }
 
     fn replace_span_dels(
@@ -770,18 +910,22 @@ impl HaplotypeCallerGenotypingEngine {
 
 /**
  * A utility class that provides ordering information, given best and second best haplotype scores.
- * If there's a tie between the two alleles when comparing their best haplotype score, the second best haplotype score
- * is used for breaking the tie. In the case that one allele doesn't have a second best allele, i.e. it has only one
- * supportive haplotype, its second best score is set as {@link Double#NEGATIVE_INFINITY}.
- * In the extremely unlikely cases that two alleles, having the same best haplotype score, neither have a second
- * best haplotype score, or the same second best haplotype score, the order is exactly the same as determined by
- * {@link Allele#compareTo(Allele)}.
+ * If there's a tie between the two alleles when comparing their best haplotype score, the allele supported by the
+ * most reads within a single sample comes first, then the allele supported by the most reads across all samples.
+ * If the read support also ties, the second best haplotype score is used for breaking the tie. In the case that one
+ * allele doesn't have a second best allele, i.e. it has only one supportive haplotype, its second best score is set
+ * as {@link Double#NEGATIVE_INFINITY}.
+ * In the extremely unlikely cases that two alleles, having the same best haplotype score and read support, neither
+ * have a second best haplotype score, or the same second best haplotype score, the order is exactly the same as
+ * determined by {@link Allele#compareTo(Allele)}.
  */
 #[derive(Debug, Clone)]
 struct AlleleScoredByHaplotype<'a> {
     allele: &'a ByteArrayAllele,
     best_haplotype_score: f64,
     second_best_haplotype_score: f64,
+    max_sample_support: usize,
+    total_support: usize,
     allele_index: usize,
 }
 
@@ -790,12 +934,16 @@ impl<'a> AlleleScoredByHaplotype<'a> {
         allele: &'a ByteArrayAllele,
         best_haplotype_score: f64,
         second_best_haplotype_score: f64,
+        max_sample_support: usize,
+        total_support: usize,
         allele_index: usize,
     ) -> AlleleScoredByHaplotype<'a> {
         Self {
             allele,
             best_haplotype_score,
             second_best_haplotype_score,
+            max_sample_support,
+            total_support,
             allele_index,
         }
     }
@@ -819,6 +967,10 @@ impl<'a> Ord for AlleleScoredByHaplotype<'a> {
             return Ordering::Less;
         } else if self.best_haplotype_score < other.best_haplotype_score {
             return Ordering::Greater;
+        } else if self.max_sample_support != other.max_sample_support {
+            return other.max_sample_support.cmp(&self.max_sample_support);
+        } else if self.total_support != other.total_support {
+            return other.total_support.cmp(&self.total_support);
         } else if (self.second_best_haplotype_score - other.second_best_haplotype_score).abs()
             > f64::EPSILON
         {
@@ -1,10 +1,10 @@
-use bird_tool_utils::command::finish_command_safely;
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use ndarray::{Array, Array1, Array2};
 use ndarray_npy::{read_npy, write_npy};
 use rayon::prelude::*;
 use std::collections::{HashMap, HashSet};
-use std::fs::create_dir_all;
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
 use std::sync::{Arc, Mutex};
 
 use crate::bam_parsing::FlagFilter;
@@ -13,27 +13,44 @@ use crate::genotype::genotype_builder::AttributeObject;
 use crate::linkage::linkage_engine::LinkageEngine;
 use crate::model::variant_context::VariantContext;
 use crate::processing::lorikeet_engine::Elem;
+use crate::reads::read_group_samples::ReadGroupSamples;
 use crate::reference::reference_reader::ReferenceReader;
+use crate::reference::reference_reader_utils::RepliconType;
+use crate::utils::external_command::ExternalCommand;
+use crate::utils::run_rng::RunRng;
 use crate::utils::simple_interval::Locatable;
 
 /// HaplotypeClusteringEngine provides a suite of functions that takes a list of VariantContexts
 /// And clusters them using the flight python module. It will then read in the results of flight
 /// and modify the variant contexts to contain their allocated strain.
+///
+/// When contigs are tagged as plasmids or mobile elements in the genome definition file, the
+/// variants of each replicon type are clustered separately, as their depths reflect the copy
+/// number of the replicon as well as the abundance of the strain.
 pub struct HaplotypeClusteringEngine<'a> {
     output_prefix: &'a str,
     variants: Vec<VariantContext>,
+    reference_reader: &'a ReferenceReader,
     ref_idx: usize,
     ref_name: &'a str,
     n_samples: usize,
     allowed_threads: usize,
+    genome_size: u64,
+    min_strain_divergence: f64,
+    min_linked_reads: usize,
     labels: Array1<i32>,
     labels_set: HashSet<i32>,
     cluster_separation: Array2<f64>,
     previous_groups: HashMap<i32, i32>,
     exclusive_groups: HashMap<i32, HashSet<i32>>,
+    export_linkage_graph: bool,
+    expected_strains: Option<usize>,
 }
 
 impl<'a> HaplotypeClusteringEngine<'a> {
+    /// Replicon types with fewer variants than this are not clustered by flight
+    const MIN_VARIANTS_TO_CLUSTER: usize = 10;
+
     pub fn new(
         output_prefix: &'a str,
         variants: Vec<VariantContext>,
@@ -41,22 +58,49 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         ref_idx: usize,
         n_samples: usize,
         allowed_threads: usize,
+        min_strain_divergence: f64,
+        min_linked_reads: usize,
     ) -> HaplotypeClusteringEngine<'a> {
+        let genome_size = match reference_reader.retrieve_tids_for_ref_index(ref_idx) {
+            Some(tids) => tids
+                .iter()
+                .map(|tid| reference_reader.get_contig_length(*tid))
+                .sum::<u64>(),
+            None => reference_reader.target_lens.values().sum::<u64>(),
+        };
+
         Self {
             output_prefix,
             variants,
+            reference_reader,
             ref_idx,
             ref_name: &reference_reader.genomes_and_contigs.genomes[ref_idx],
             n_samples,
             allowed_threads,
+            genome_size,
+            min_strain_divergence,
+            min_linked_reads,
             labels: Array::default(0),
             labels_set: HashSet::new(),
             cluster_separation: Array::default((0, 0)),
             previous_groups: HashMap::new(),
             exclusive_groups: HashMap::new(),
+            export_linkage_graph: false,
+            expected_strains: None,
         }
     }
 
+    /// Write the variant read linkage graph as node and edge TSV files and as GraphML
+    pub fn set_export_linkage_graph(&mut self, export_linkage_graph: bool) {
+        self.export_linkage_graph = export_linkage_graph;
+    }
+
+    /// The number of strains estimated before variant calling. Potential strains beyond this
+    /// are merged into the strains most similar to them
+    pub fn set_expected_strains(&mut self, expected_strains: Option<usize>) {
+        self.expected_strains = expected_strains;
+    }
+
     /// Runs the clustering engine, linkage engine, and genotype abundances engine
     /// Returns a tuple containing the number of found strains and a `Vec<VariantContext>` with
     /// each context tagged with one or more strains.
@@ -67,14 +111,20 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         n_threads: usize,
         tree: &Arc<Mutex<Vec<&Elem>>>,
     ) -> (usize, Vec<VariantContext>) {
-        // Creates the depth array used by flight
-        let file_name = self.prepare_depth_file();
         {
             let pb = &tree.lock().unwrap()[self.ref_idx + 2];
-            pb.progress_bar
-                .set_message(format!("{}: Running UMAP and HDBSCAN...", self.ref_name,));
+            pb.set_stage("Running UMAP and HDBSCAN");
+        }
+        let replicon_partitions = self.replicon_partitions();
+        if replicon_partitions.len() > 1 {
+            self.cluster_replicons_separately(&replicon_partitions);
+        } else {
+            // Creates the depth array used by flight
+            let all_variants = (0..self.variants.len()).collect::<Vec<usize>>();
+            let file_name = self.prepare_depth_file(&all_variants, None);
+            let (labels, cluster_separation) = self.run_flight(file_name);
+            self.set_clusters(labels, cluster_separation);
         }
-        self.run_flight(file_name);
         // debug!("Flight complete.");
         self.apply_clusters();
         // debug!("Variant groups tagged.");
@@ -82,14 +132,13 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         // variant groups organized into potential strains
         {
             let pb = &tree.lock().unwrap()[self.ref_idx + 2];
-            pb.progress_bar
-                .set_message(format!("{}: Linking variant groups...", self.ref_name,));
+            pb.set_stage("Linking variant groups");
         }
 
         // debug!("separation {:?}", &self.cluster_separation);
         let grouped_contexts = self.group_contexts();
 
-        let linkage_engine = LinkageEngine::new(
+        let mut linkage_engine = LinkageEngine::new(
             grouped_contexts,
             // sample_names,
             &self.cluster_separation,
@@ -101,18 +150,75 @@ impl<'a> HaplotypeClusteringEngine<'a> {
             n_threads,
             &format!("{}/{}", self.output_prefix, self.ref_name),
             flag_filters,
+            self.min_linked_reads,
         );
         // debug!("Potential strains {:?}", potential_strains);
 
+        let potential_strains = if self.min_strain_divergence > 0.0 {
+            let strain_count = potential_strains.len();
+            let merged_strains = Self::merge_similar_strains(
+                potential_strains,
+                &self.variants_per_group(),
+                self.genome_size,
+                self.min_strain_divergence,
+            );
+            debug!(
+                "{}: Merged {} potential strains into {}",
+                self.ref_name,
+                strain_count,
+                merged_strains.len()
+            );
+            merged_strains
+        } else {
+            potential_strains
+        };
+
+        let potential_strains = match self.expected_strains {
+            Some(expected_strains) if potential_strains.len() > expected_strains => {
+                let strain_count = potential_strains.len();
+                let merged_strains = Self::merge_to_expected_strains(
+                    potential_strains,
+                    &self.variants_per_group(),
+                    expected_strains,
+                );
+                debug!(
+                    "{}: Merged {} potential strains into the {} expected",
+                    self.ref_name,
+                    strain_count,
+                    merged_strains.len()
+                );
+                merged_strains
+            }
+            _ => potential_strains,
+        };
+        let linked_read_counts = linkage_engine.linked_read_counts(&potential_strains);
+
+        if replicon_partitions.len() > 1 {
+            self.write_replicon_copy_numbers(&potential_strains, &replicon_partitions, sample_names);
+        }
+
+        if self.export_linkage_graph {
+            let reference_reader = self.reference_reader;
+            linkage_engine
+                .variant_linkage_graph(&potential_strains, |tid| {
+                    reference_reader
+                        .retrieve_contig_name_from_tid(tid)
+                        .map(|name| String::from_utf8_lossy(name).to_string())
+                        .unwrap_or_else(|| tid.to_string())
+                })
+                .write_files(&format!("{}/{}", self.output_prefix, self.ref_name));
+        }
+
         (
             potential_strains.len(),
-            self.annotate_variant_contexts_with_strains(potential_strains),
+            self.annotate_variant_contexts_with_strains(potential_strains, linked_read_counts),
         )
     }
 
     fn annotate_variant_contexts_with_strains(
         self,
         potential_strains: Vec<LinkedHashSet<i32>>,
+        linked_read_counts: LinkedHashMap<i32, Vec<usize>>,
     ) -> Vec<VariantContext> {
         // regroup contexts but owned
         let mut grouped_contexts = LinkedHashMap::with_capacity(self.labels_set.len());
@@ -129,6 +235,18 @@ impl<'a> HaplotypeClusteringEngine<'a> {
 
         // debug!("Number of groups {}", grouped_contexts.len());
 
+        // the linked read counts are in the same order as the contexts within each group
+        for (group, counts) in linked_read_counts {
+            if let Some(variant_contexts) = grouped_contexts.get_mut(&group) {
+                for (vc, count) in variant_contexts.iter_mut().zip(counts) {
+                    vc.attributes.insert(
+                        VariantAnnotations::LinkedReads.to_key().to_string(),
+                        AttributeObject::I32(count as i32),
+                    );
+                }
+            }
+        }
+
         for (strain_idx, groups_in_strain) in potential_strains.into_iter().enumerate() {
             // debug!(
             //     "Strain index {} groups in strain {:?}",
@@ -158,6 +276,110 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         return_contexts
     }
 
+    /// Merges potential strains whose reconstructed genomes are too similar to be considered
+    /// separate strains. The divergence between two strains is the number of variants carried by
+    /// only one of the strains divided by the genome size. Strains are merged greedily in order,
+    /// with each strain being merged into the first previous strain it is within
+    /// `min_strain_divergence` of. Merged strains contain the variant groups of both strains.
+    pub fn merge_similar_strains(
+        potential_strains: Vec<LinkedHashSet<i32>>,
+        variants_per_group: &HashMap<i32, HashSet<usize>>,
+        genome_size: u64,
+        min_strain_divergence: f64,
+    ) -> Vec<LinkedHashSet<i32>> {
+        let mut merged_strains: Vec<(LinkedHashSet<i32>, HashSet<usize>)> =
+            Vec::with_capacity(potential_strains.len());
+
+        for groups_in_strain in potential_strains {
+            let strain_variants = groups_in_strain
+                .iter()
+                .filter_map(|group| variants_per_group.get(group))
+                .flat_map(|variants| variants.iter().copied())
+                .collect::<HashSet<usize>>();
+
+            let similar_strain = merged_strains.iter().position(|(_, other_variants)| {
+                let differing_variants =
+                    strain_variants.symmetric_difference(other_variants).count();
+                (differing_variants as f64 / genome_size.max(1) as f64) < min_strain_divergence
+            });
+
+            match similar_strain {
+                Some(strain_idx) => {
+                    let (groups, variants) = &mut merged_strains[strain_idx];
+                    groups.extend(groups_in_strain);
+                    variants.extend(strain_variants);
+                }
+                None => merged_strains.push((groups_in_strain, strain_variants)),
+            }
+        }
+
+        merged_strains
+            .into_iter()
+            .map(|(groups, _)| groups)
+            .collect()
+    }
+
+    /// Repeatedly merges the two potential strains differing at the fewest variants until no more
+    /// than `expected_strains` remain. Merged strains contain the variant groups of both strains.
+    pub fn merge_to_expected_strains(
+        potential_strains: Vec<LinkedHashSet<i32>>,
+        variants_per_group: &HashMap<i32, HashSet<usize>>,
+        expected_strains: usize,
+    ) -> Vec<LinkedHashSet<i32>> {
+        let mut merged_strains = potential_strains
+            .into_iter()
+            .map(|groups_in_strain| {
+                let strain_variants = groups_in_strain
+                    .iter()
+                    .filter_map(|group| variants_per_group.get(group))
+                    .flat_map(|variants| variants.iter().copied())
+                    .collect::<HashSet<usize>>();
+                (groups_in_strain, strain_variants)
+            })
+            .collect::<Vec<(LinkedHashSet<i32>, HashSet<usize>)>>();
+
+        while merged_strains.len() > expected_strains.max(1) {
+            let mut closest = (0, 1, usize::MAX);
+            for first in 0..merged_strains.len() {
+                for second in (first + 1)..merged_strains.len() {
+                    let differing_variants = merged_strains[first]
+                        .1
+                        .symmetric_difference(&merged_strains[second].1)
+                        .count();
+                    if differing_variants < closest.2 {
+                        closest = (first, second, differing_variants);
+                    }
+                }
+            }
+            let (groups, variants) = merged_strains.remove(closest.1);
+            let (merged_groups, merged_variants) = &mut merged_strains[closest.0];
+            merged_groups.extend(groups);
+            merged_variants.extend(variants);
+        }
+
+        merged_strains
+            .into_iter()
+            .map(|(groups, _)| groups)
+            .collect()
+    }
+
+    /// The indices of the variants belonging to each variant group
+    fn variants_per_group(&self) -> HashMap<i32, HashSet<usize>> {
+        let mut variants_per_group = HashMap::new();
+        for (idx, context) in self.variants.iter().enumerate() {
+            if let Some(AttributeObject::I32(group)) = context
+                .attributes
+                .get(VariantAnnotations::VariantGroup.to_key())
+            {
+                variants_per_group
+                    .entry(*group)
+                    .or_insert_with(HashSet::new)
+                    .insert(idx);
+            }
+        }
+        variants_per_group
+    }
+
     /// Group contexts by their variant group and return a HashMap
     /// key is variant group, value is  vector reference the variant context
     /// The variant context should be sorted by location if they have been generated by
@@ -205,10 +427,260 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         }
     }
 
+    /// The replicon type of the contig of each variant
+    fn variant_replicon_type(&self, variant: &VariantContext) -> RepliconType {
+        match self
+            .reference_reader
+            .retrieve_contig_name_from_tid(variant.loc.tid() as usize)
+        {
+            Some(contig_name) => self
+                .reference_reader
+                .genomes_and_contigs
+                .replicon_type(&String::from_utf8_lossy(contig_name)),
+            None => RepliconType::Chromosome,
+        }
+    }
+
+    /// The indices of the variants on each replicon type, chromosomes first
+    fn replicon_partitions(&self) -> Vec<(RepliconType, Vec<usize>)> {
+        if self.reference_reader.genomes_and_contigs.replicon_types.is_empty() {
+            return vec![(RepliconType::Chromosome, (0..self.variants.len()).collect())];
+        }
+
+        Self::partition_by_replicon_type(
+            self.variants
+                .iter()
+                .map(|variant| self.variant_replicon_type(variant)),
+        )
+    }
+
+    /// Groups the indices of the given replicon types by type, chromosomes first
+    pub fn partition_by_replicon_type<I: IntoIterator<Item = RepliconType>>(
+        replicon_types: I,
+    ) -> Vec<(RepliconType, Vec<usize>)> {
+        let mut partitions: HashMap<RepliconType, Vec<usize>> = HashMap::new();
+        for (idx, replicon_type) in replicon_types.into_iter().enumerate() {
+            partitions
+                .entry(replicon_type)
+                .or_insert_with(Vec::new)
+                .push(idx);
+        }
+        let mut partitions = partitions.into_iter().collect::<Vec<_>>();
+        partitions.sort_unstable_by_key(|(replicon_type, _)| *replicon_type);
+        partitions
+    }
+
+    /// Runs flight on the variants of each replicon type and combines the results. Labels of
+    /// later replicon types are offset past those of earlier ones and variant groups of different
+    /// replicon types are never considered close, so they can only be linked by reads. Replicon
+    /// types with too few variants for UMAP form a single variant group.
+    fn cluster_replicons_separately(&mut self, partitions: &[(RepliconType, Vec<usize>)]) {
+        let mut labels = Array1::from_elem(self.variants.len(), -1);
+        let mut separations = Vec::with_capacity(partitions.len());
+        let mut offset = 0;
+        for (replicon_type, variant_indices) in partitions {
+            let (partition_labels, separation) =
+                if variant_indices.len() < Self::MIN_VARIANTS_TO_CLUSTER {
+                    (
+                        Array1::from_elem(variant_indices.len(), 0),
+                        Array2::zeros((1, 1)),
+                    )
+                } else {
+                    let file_name =
+                        self.prepare_depth_file(variant_indices, Some(replicon_type.to_key()));
+                    self.run_flight(file_name)
+                };
+            debug!(
+                "{}: {} variants on {} contigs",
+                self.ref_name,
+                variant_indices.len(),
+                replicon_type.to_key()
+            );
+
+            for (idx, label) in variant_indices.iter().zip(partition_labels.iter()) {
+                if *label >= 0 {
+                    labels[[*idx]] = *label + offset as i32;
+                }
+            }
+            let n_labels = separation
+                .nrows()
+                .max(partition_labels.iter().map(|l| *l + 1).max().unwrap_or(0).max(0) as usize);
+            separations.push((offset, n_labels, separation));
+            offset += n_labels;
+        }
+
+        let mut cluster_separation = Array2::from_elem((offset, offset), f64::INFINITY);
+        for (offset, n_labels, separation) in separations {
+            for i in 0..separation.nrows().min(n_labels) {
+                for j in 0..separation.ncols().min(n_labels) {
+                    cluster_separation[[offset + i, offset + j]] = separation[[i, j]];
+                }
+            }
+        }
+
+        self.set_clusters(labels, cluster_separation);
+    }
+
+    /// Writes the copy number of each plasmid and mobile element contig relative to the
+    /// chromosome for each strain and sample. The copy number is the mean alternate allele depth
+    /// of the strain's variants on the contig divided by the mean alternate allele depth of its
+    /// chromosomal variants. Strains without chromosomal variants are compared to the mean depth
+    /// of all chromosomal variants of the genome.
+    fn write_replicon_copy_numbers(
+        &self,
+        potential_strains: &[LinkedHashSet<i32>],
+        partitions: &[(RepliconType, Vec<usize>)],
+        sample_names: &[String],
+    ) {
+        let chromosome_variants = partitions
+            .iter()
+            .filter(|(replicon_type, _)| *replicon_type == RepliconType::Chromosome)
+            .flat_map(|(_, variant_indices)| variant_indices.iter().copied())
+            .collect::<HashSet<usize>>();
+        let chromosome_depths =
+            self.mean_depths(chromosome_variants.iter().copied(), |ad| ad.iter().sum());
+
+        let variants_per_group = self.variants_per_group();
+        let path = format!("{}/{}_replicon_copy_number.tsv", self.output_prefix, self.ref_name);
+        let mut writer = BufWriter::new(
+            File::create(&path).unwrap_or_else(|_| panic!("Unable to create {}", &path)),
+        );
+        let cleaned_sample_names = ReadGroupSamples::from_bams(sample_names);
+        writeln!(
+            writer,
+            "strain_id\tcontig\treplicon_type\t{}",
+            cleaned_sample_names.join("\t")
+        )
+        .expect("Unable to write replicon copy numbers");
+
+        for (strain_idx, groups_in_strain) in potential_strains.iter().enumerate() {
+            let strain_variants = groups_in_strain
+                .iter()
+                .filter_map(|group| variants_per_group.get(group))
+                .flat_map(|variants| variants.iter().copied())
+                .collect::<HashSet<usize>>();
+            let strain_chromosome_variants = strain_variants
+                .iter()
+                .copied()
+                .filter(|idx| chromosome_variants.contains(idx))
+                .collect::<Vec<usize>>();
+            let denominators = if strain_chromosome_variants.is_empty() {
+                chromosome_depths.clone()
+            } else {
+                self.mean_depths(strain_chromosome_variants.into_iter(), Self::alt_depth)
+            };
+
+            let mut variants_per_contig: LinkedHashMap<i32, Vec<usize>> = LinkedHashMap::new();
+            let mut strain_variants = strain_variants
+                .into_iter()
+                .filter(|idx| !chromosome_variants.contains(idx))
+                .collect::<Vec<usize>>();
+            strain_variants.sort_unstable();
+            for idx in strain_variants {
+                variants_per_contig
+                    .entry(self.variants[idx].loc.tid())
+                    .or_insert_with(Vec::new)
+                    .push(idx);
+            }
+
+            for (tid, variant_indices) in variants_per_contig {
+                let contig_name = self
+                    .reference_reader
+                    .retrieve_contig_name_from_tid(tid as usize)
+                    .map(|name| String::from_utf8_lossy(name).to_string())
+                    .unwrap_or_else(|| tid.to_string());
+                let copy_numbers = Self::copy_numbers(
+                    &self.mean_depths(variant_indices.iter().copied(), Self::alt_depth),
+                    &denominators,
+                )
+                .into_iter()
+                .map(|copy_number| match copy_number {
+                    Some(copy_number) => format!("{:.3}", copy_number),
+                    None => "NA".to_string(),
+                })
+                .collect::<Vec<String>>();
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    strain_idx,
+                    contig_name,
+                    self.variant_replicon_type(&self.variants[variant_indices[0]])
+                        .to_key(),
+                    copy_numbers.join("\t")
+                )
+                .expect("Unable to write replicon copy numbers");
+            }
+        }
+    }
+
+    /// The mean per sample depth of a set of variants, using `depth` to summarise the allele
+    /// depths of each genotype
+    fn mean_depths<I: Iterator<Item = usize>, F: Fn(&[i32]) -> i32>(
+        &self,
+        variant_indices: I,
+        depth: F,
+    ) -> Vec<f64> {
+        Self::mean_sample_depths(
+            variant_indices.map(|idx| &self.variants[idx]),
+            self.n_samples,
+            depth,
+        )
+    }
+
+    /// The mean depth of each of the first `n_samples` samples across the given variants
+    pub fn mean_sample_depths<'b, I: Iterator<Item = &'b VariantContext>, F: Fn(&[i32]) -> i32>(
+        variants: I,
+        n_samples: usize,
+        depth: F,
+    ) -> Vec<f64> {
+        let mut totals = vec![0.0; n_samples];
+        let mut count = 0;
+        for variant in variants {
+            count += 1;
+            for (sample_index, genotype) in variant
+                .genotypes
+                .genotypes()
+                .into_iter()
+                .enumerate()
+                .take(n_samples)
+            {
+                totals[sample_index] += depth(&genotype.ad_i32()) as f64;
+            }
+        }
+        if count > 0 {
+            totals.iter_mut().for_each(|total| *total /= count as f64);
+        }
+        totals
+    }
+
+    /// The depth of each sample relative to its chromosomal depth, or None for samples without
+    /// chromosomal depth
+    pub fn copy_numbers(depths: &[f64], chromosome_depths: &[f64]) -> Vec<Option<f64>> {
+        depths
+            .iter()
+            .zip(chromosome_depths.iter())
+            .map(|(depth, chromosome_depth)| {
+                if *chromosome_depth > 0.0 {
+                    Some(depth / chromosome_depth)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The summed depth of the alternate alleles
+    pub fn alt_depth(ad: &[i32]) -> i32 {
+        ad.iter().skip(1).sum()
+    }
+
     /// Writes out a variant by sample depth array from the provided collection of variant contexts
-    fn prepare_depth_file(&self) -> String {
+    fn prepare_depth_file(&self, variant_indices: &[usize], suffix: Option<&str>) -> String {
         // debug!("Writing depth file...");
-        let file_name = format!("{}/{}", self.output_prefix, self.ref_name,);
+        let file_name = match suffix {
+            Some(suffix) => format!("{}/{}_{}", self.output_prefix, self.ref_name, suffix),
+            None => format!("{}/{}", self.output_prefix, self.ref_name,),
+        };
         // ensure path exists
         create_dir_all(self.output_prefix).expect("Unable to create output directory");
 
@@ -217,9 +689,9 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         // information for the reference and alternate alleles. Thus each sample is represented by two
         // columns. The reference allele always comes first.
         let mut var_depth_array: Array2<i32> =
-            Array::from_elem((self.variants.len(), self.n_samples * 2 + 2), 0);
+            Array::from_elem((variant_indices.len(), self.n_samples * 2 + 2), 0);
 
-        for (row_id, var) in self.variants.iter().enumerate() {
+        for (row_id, var) in variant_indices.iter().map(|idx| &self.variants[*idx]).enumerate() {
             var_depth_array[[row_id, 0]] = var.loc.tid();
             var_depth_array[[row_id, 1]] = var.loc.start as i32;
             for (sample_index, genotype) in var.genotypes.genotypes().into_iter().enumerate() {
@@ -237,7 +709,15 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         return file_name;
     }
 
-    fn run_flight<S: AsRef<str>>(&mut self, file_name: S) {
+    fn set_clusters(&mut self, labels: Array1<i32>, cluster_separation: Array2<f64>) {
+        self.labels_set = labels.iter().map(|l| *l).collect::<HashSet<i32>>();
+        self.labels = labels;
+        self.cluster_separation = cluster_separation;
+    }
+
+    /// Runs flight on a depth file, returning the variant group of each variant and the
+    /// separation between each pair of variant groups
+    fn run_flight<S: AsRef<str>>(&self, file_name: S) -> (Array1<i32>, Array2<f64>) {
         let cmd_string = format!(
             "flight fit --input {}.npy --cores {}",
             file_name.as_ref(),
@@ -245,27 +725,21 @@ impl<'a> HaplotypeClusteringEngine<'a> {
         );
 
         // Run the flight command
-        finish_command_safely(
+        ExternalCommand::run_or_exit(
             std::process::Command::new("bash")
                 .arg("-c")
                 .arg(&cmd_string)
-                .stderr(std::process::Stdio::piped())
-                // .stdout(std::process::Stdio::piped())
-                .spawn()
-                .expect("Unable to execute bash"),
+                .env("PYTHONHASHSEED", (RunRng::seed() % (u32::MAX as u64 + 1)).to_string()),
             "flight",
         );
 
         // Read in the results
         let labels: Array1<i32> =
             read_npy(format!("{}_labels.npy", file_name.as_ref())).expect("Unable to read npy");
-        let labels_set = labels.iter().map(|l| *l).collect::<HashSet<i32>>();
 
         let cluster_separation: Array2<f64> =
             read_npy(format!("{}_separation.npy", file_name.as_ref())).expect("Unable to read npy");
 
-        self.labels = labels;
-        self.labels_set = labels_set;
-        self.cluster_separation = cluster_separation;
+        (labels, cluster_separation)
     }
 }
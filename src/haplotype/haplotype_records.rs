@@ -0,0 +1,331 @@
+use std::sync::{Arc, Mutex};
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::{AttributeObject, Genotype};
+use crate::haplotype::haplotype::Haplotype;
+use crate::model::allele_likelihoods::{AlleleLikelihoods, BestAllele};
+use crate::model::byte_array_allele::{Allele, ByteArrayAllele};
+use crate::model::variant_context::VariantContext;
+use crate::utils::simple_interval::{Locatable, SimpleInterval};
+
+/// Haplotype level records of an assembly region: one haploid record per non-reference haplotype,
+/// with the reference haplotype as REF and the reads best explained by each haplotype in AD.
+/// Haplotypes without informative reads are not emitted.
+pub struct HaplotypeRecords {}
+
+impl HaplotypeRecords {
+    const MAX_GENOTYPE_QUALITY: i32 = 99;
+
+    /// Number of reads best explained by each haplotype, given the informative best haplotypes
+    /// of the reads of a sample
+    pub fn supporting_reads(best_alleles: &[BestAllele], n_haplotypes: usize) -> Vec<i32> {
+        let mut counts = vec![0; n_haplotypes];
+        for best_allele in best_alleles {
+            if let Some(allele_index) = best_allele.allele_index {
+                counts[allele_index] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Phred scaled likelihoods of a sample carrying the reference or the alternate haplotype,
+    /// using the reads best explained by either of them
+    fn haploid_pls(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        best_alleles: &[BestAllele],
+        sample_index: usize,
+        ref_index: usize,
+        alt_index: usize,
+    ) -> Vec<i32> {
+        let values = &read_likelihoods.values_by_sample_index[sample_index];
+        let scale = if read_likelihoods.is_natural_log {
+            std::f64::consts::LN_10
+        } else {
+            1.0
+        };
+
+        let mut log10_likelihoods = [0.0, 0.0];
+        for best_allele in best_alleles.iter().filter(|best_allele| {
+            best_allele.allele_index == Some(ref_index)
+                || best_allele.allele_index == Some(alt_index)
+        }) {
+            log10_likelihoods[0] += values[[ref_index, best_allele.evidence_index]] / scale;
+            log10_likelihoods[1] += values[[alt_index, best_allele.evidence_index]] / scale;
+        }
+
+        let max_likelihood = log10_likelihoods[0].max(log10_likelihoods[1]);
+        log10_likelihoods
+            .iter()
+            .map(|likelihood| (-10.0 * (likelihood - max_likelihood)).round() as i32)
+            .collect()
+    }
+
+    /// The informative best haplotype of every read of each sample, along with the number of
+    /// reads best supporting each haplotype in each sample
+    fn best_alleles_and_support(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> (Vec<Vec<BestAllele>>, Vec<Vec<i32>>) {
+        let best_alleles = (0..read_likelihoods.number_of_samples())
+            .map(|sample_index| {
+                read_likelihoods
+                    .best_alleles_breaking_ties_for_sample(sample_index)
+                    .into_iter()
+                    .filter(|best_allele| best_allele.is_informative())
+                    .collect::<Vec<BestAllele>>()
+            })
+            .collect::<Vec<Vec<BestAllele>>>();
+        let supporting_reads = best_alleles
+            .iter()
+            .map(|sample_best_alleles| {
+                Self::supporting_reads(sample_best_alleles, read_likelihoods.alleles.len())
+            })
+            .collect::<Vec<Vec<i32>>>();
+        (best_alleles, supporting_reads)
+    }
+
+    /// The haploid genotype of each sample for the reference haplotype against an alternate
+    /// haplotype, written with the given alleles
+    fn genotypes(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        best_alleles: &[Vec<BestAllele>],
+        supporting_reads: &[Vec<i32>],
+        ref_index: usize,
+        alt_index: usize,
+        ref_allele: &ByteArrayAllele,
+        alt_allele: &ByteArrayAllele,
+    ) -> Vec<Genotype> {
+        supporting_reads
+            .iter()
+            .enumerate()
+            .map(|(sample_index, counts)| {
+                let pls = Self::haploid_pls(
+                    read_likelihoods,
+                    &best_alleles[sample_index],
+                    sample_index,
+                    ref_index,
+                    alt_index,
+                );
+                let called = if pls[1] == 0 && pls[0] > 0 {
+                    alt_allele.clone()
+                } else {
+                    ref_allele.clone()
+                };
+                let mut genotype = Genotype::build_from_alleles(
+                    vec![called],
+                    read_likelihoods.samples()[sample_index],
+                );
+                genotype.ad = vec![counts[ref_index], counts[alt_index]];
+                genotype.dp = counts[ref_index] + counts[alt_index];
+                genotype.gq = pls[0].max(pls[1]).min(Self::MAX_GENOTYPE_QUALITY);
+                genotype.pl = pls;
+                genotype
+            })
+            .collect::<Vec<Genotype>>()
+    }
+
+    /// The index and location of the reference haplotype
+    fn reference_haplotype(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> Option<(usize, SimpleInterval)> {
+        let haplotypes = &read_likelihoods.alleles.list;
+        let ref_index = haplotypes.iter().position(|haplotype| haplotype.is_ref())?;
+        let location = haplotypes[ref_index].get_genome_location()?.clone();
+        Some((ref_index, location))
+    }
+
+    /// The indices of the non-reference haplotypes with informative reads in any sample
+    fn alternate_haplotypes(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        supporting_reads: &[Vec<i32>],
+        ref_index: usize,
+    ) -> Vec<usize> {
+        let haplotypes = &read_likelihoods.alleles.list;
+        (0..haplotypes.len())
+            .filter(|alt_index| {
+                *alt_index != ref_index
+                    && haplotypes[*alt_index].get_bases() != haplotypes[ref_index].get_bases()
+                    && supporting_reads.iter().any(|counts| counts[*alt_index] > 0)
+            })
+            .collect()
+    }
+
+    /// One record for each non-reference haplotype with informative reads in any sample
+    pub fn from_likelihoods(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> Vec<VariantContext> {
+        let (ref_index, location) = match Self::reference_haplotype(read_likelihoods) {
+            Some(reference_haplotype) => reference_haplotype,
+            None => return Vec::new(),
+        };
+        let haplotypes = &read_likelihoods.alleles.list;
+        let ref_allele = ByteArrayAllele::new(haplotypes[ref_index].get_bases(), true);
+
+        let (best_alleles, supporting_reads) = Self::best_alleles_and_support(read_likelihoods);
+        let mut records = Vec::new();
+        for alt_index in Self::alternate_haplotypes(read_likelihoods, &supporting_reads, ref_index)
+        {
+            let alt_allele = ByteArrayAllele::new(haplotypes[alt_index].get_bases(), false);
+            let genotypes = Self::genotypes(
+                read_likelihoods,
+                &best_alleles,
+                &supporting_reads,
+                ref_index,
+                alt_index,
+                &ref_allele,
+                &alt_allele,
+            );
+
+            let mut vc = VariantContext::build(
+                location.get_contig(),
+                location.get_start(),
+                location.get_start() + ref_allele.length() - 1,
+                vec![ref_allele.clone(), alt_allele],
+            );
+            vc.add_genotypes(genotypes);
+            records.push(vc);
+        }
+
+        records
+    }
+
+    /// One symbolic record for each non-reference haplotype whose QUAL, the summed phred scaled
+    /// evidence of every sample for the haplotype over the reference, reaches `min_qual`
+    pub fn symbolic_from_likelihoods(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        min_qual: f64,
+    ) -> Vec<VariantContext> {
+        let (ref_index, location) = match Self::reference_haplotype(read_likelihoods) {
+            Some(reference_haplotype) => reference_haplotype,
+            None => return Vec::new(),
+        };
+        let haplotypes = &read_likelihoods.alleles.list;
+        let ref_bases = haplotypes[ref_index].get_bases();
+        if ref_bases.is_empty() {
+            return Vec::new();
+        }
+        let ref_allele = ByteArrayAllele::new(&ref_bases[..1], true);
+        let alt_allele = ByteArrayAllele::new(SymbolicHaplotypeRecords::SYMBOLIC_ALLELE, false);
+
+        let (best_alleles, supporting_reads) = Self::best_alleles_and_support(read_likelihoods);
+        let mut records = Vec::new();
+        for alt_index in Self::alternate_haplotypes(read_likelihoods, &supporting_reads, ref_index)
+        {
+            let genotypes = Self::genotypes(
+                read_likelihoods,
+                &best_alleles,
+                &supporting_reads,
+                ref_index,
+                alt_index,
+                &ref_allele,
+                &alt_allele,
+            );
+            let qual = genotypes
+                .iter()
+                .map(|genotype| genotype.pl[0] as f64)
+                .sum::<f64>();
+            if qual < min_qual {
+                continue;
+            }
+
+            let end = location.get_start() + ref_bases.len() - 1;
+            let mut vc = VariantContext::build(
+                location.get_contig(),
+                location.get_start(),
+                end,
+                vec![ref_allele.clone(), alt_allele.clone()],
+            );
+            vc.log10_p_error(-qual / 10.0);
+            vc.attributes.insert(
+                VariantAnnotations::End.to_key().to_string(),
+                AttributeObject::I32(end as i32 + 1),
+            );
+            vc.attributes.insert(
+                VariantAnnotations::HaplotypeSequence.to_key().to_string(),
+                AttributeObject::String(
+                    String::from_utf8_lossy(haplotypes[alt_index].get_bases()).to_string(),
+                ),
+            );
+            vc.add_genotypes(genotypes);
+            records.push(vc);
+        }
+
+        records
+    }
+}
+
+/// `<HAP>` records of the assembled haplotypes reaching --haplotype-record-min-qual, kept even when
+/// their site level variants were filtered. They are only merged in when the VCF is written, and
+/// every clone shares the same records.
+#[derive(Debug, Clone)]
+pub struct SymbolicHaplotypeRecords {
+    min_qual: f64,
+    records: Arc<Mutex<Vec<VariantContext>>>,
+}
+
+impl SymbolicHaplotypeRecords {
+    pub const SYMBOLIC_ALLELE: &'static [u8] = b"<HAP>";
+
+    pub fn new(min_qual: f64) -> Self {
+        Self {
+            min_qual,
+            records: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        if !args
+            .try_get_one::<bool>("emit-haplotype-records")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+        {
+            return None;
+        }
+        Some(Self::new(
+            args.try_get_one::<f64>("haplotype-record-min-qual")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(30.0),
+        ))
+    }
+
+    /// The ALT header line describing the symbolic allele
+    pub fn header_record() -> String {
+        "##ALT=<ID=HAP,Description=\"Assembled non-reference haplotype spanning POS to END\">"
+            .to_string()
+    }
+
+    /// Adds the symbolic records of the haplotypes of an assembly region
+    pub fn record(&self, read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>) {
+        let records = HaplotypeRecords::symbolic_from_likelihoods(read_likelihoods, self.min_qual);
+        if !records.is_empty() {
+            self.records.lock().unwrap().extend(records);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds the haplotype records to owned site level contexts, which are left for the caller to
+    /// sort
+    pub fn append_to(&self, contexts: &mut Vec<VariantContext>) {
+        contexts.extend(self.records.lock().unwrap().iter().cloned());
+    }
+
+    /// The site level contexts together with the haplotype records, sorted by position
+    pub fn merge(&self, contexts: &[VariantContext]) -> Vec<VariantContext> {
+        let records = self.records.lock().unwrap();
+        let mut merged = Vec::with_capacity(contexts.len() + records.len());
+        merged.extend_from_slice(contexts);
+        merged.extend(records.iter().cloned());
+        merged.sort();
+        merged
+    }
+}
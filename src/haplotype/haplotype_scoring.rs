@@ -0,0 +1,150 @@
+use ndarray::Array2;
+
+use crate::haplotype::haplotype::Haplotype;
+use crate::model::allele_likelihoods::AlleleLikelihoods;
+use crate::utils::simple_interval::SimpleInterval;
+
+/// How assembled haplotypes are ranked when a site has too many alleles to genotype and the
+/// alleles carried only by low scoring haplotypes are dropped. The path weight of the assembly
+/// graph favours the most abundant strain, so a real allele of a low abundance strain can lose
+/// out to assembly noise; scoring haplotypes by their read evidence avoids that.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaplotypeScoringModel {
+    /// The score of the haplotype's path through the assembly graph
+    PathWeight,
+    /// The number of reads that support the haplotype at least as well as any other haplotype
+    ReadSupport,
+    /// The sum over samples of the largest margin by which a read of the sample favours the
+    /// haplotype over every other haplotype
+    SampleLikelihood,
+}
+
+impl HaplotypeScoringModel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "path-weight" => Some(Self::PathWeight),
+            "read-support" => Some(Self::ReadSupport),
+            "sample-likelihood" => Some(Self::SampleLikelihood),
+            _ => None,
+        }
+    }
+
+    /// The model given with --haplotype-scoring-model, whose value parser only accepts the names
+    /// of known models
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        args.try_get_one::<String>("haplotype-scoring-model")
+            .ok()
+            .flatten()
+            .and_then(|name| Self::from_name(name))
+            .unwrap_or(Self::PathWeight)
+    }
+
+    /// Sets the score of each haplotype that has likelihoods. Haplotypes keep their path weight
+    /// under the default model
+    pub fn score_haplotypes(
+        &self,
+        haplotypes: &mut [Haplotype<SimpleInterval>],
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) {
+        let scores = match self {
+            Self::PathWeight => return,
+            Self::ReadSupport => Self::read_support_scores(
+                &read_likelihoods.values_by_sample_index,
+                &Self::evidence_counts(read_likelihoods),
+                read_likelihoods.get_informative_threshold(),
+            ),
+            Self::SampleLikelihood => Self::sample_likelihood_scores(
+                &read_likelihoods.values_by_sample_index,
+                &Self::evidence_counts(read_likelihoods),
+            ),
+        };
+
+        for haplotype in haplotypes.iter_mut() {
+            if let Some(index) = read_likelihoods.index_of_allele(haplotype) {
+                haplotype.score = scores[index].into();
+            }
+        }
+    }
+
+    /// For each allele, the number of reads across all samples whose likelihood under the allele
+    /// is within `informative_threshold` of their best likelihood. Matrices are indexed by
+    /// allele then read
+    pub fn read_support_scores(
+        sample_matrices: &[Array2<f64>],
+        evidence_counts: &[usize],
+        informative_threshold: f64,
+    ) -> Vec<f64> {
+        let allele_count = sample_matrices.first().map(|m| m.nrows()).unwrap_or(0);
+        let mut scores = vec![0.0; allele_count];
+        for (matrix, evidence_count) in sample_matrices.iter().zip(evidence_counts.iter()) {
+            for read in 0..(*evidence_count).min(matrix.ncols()) {
+                let column = matrix.column(read);
+                let best = column.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                if best == f64::NEG_INFINITY {
+                    continue;
+                }
+                column
+                    .iter()
+                    .zip(scores.iter_mut())
+                    .filter(|(likelihood, _)| best - **likelihood <= informative_threshold)
+                    .for_each(|(_, score)| *score += 1.0);
+            }
+        }
+        scores
+    }
+
+    /// For each allele, the sum over samples of the largest margin between a read's likelihood
+    /// under the allele and its best likelihood under any other allele. Matrices are indexed by
+    /// allele then read
+    pub fn sample_likelihood_scores(
+        sample_matrices: &[Array2<f64>],
+        evidence_counts: &[usize],
+    ) -> Vec<f64> {
+        let allele_count = sample_matrices.first().map(|m| m.nrows()).unwrap_or(0);
+        let mut scores = vec![0.0; allele_count];
+        if allele_count < 2 {
+            return scores;
+        }
+
+        for (matrix, evidence_count) in sample_matrices.iter().zip(evidence_counts.iter()) {
+            let mut best_margins = vec![f64::NEG_INFINITY; allele_count];
+            for read in 0..(*evidence_count).min(matrix.ncols()) {
+                // the best other allele is the second best allele for the best allele and the
+                // best allele for every other allele
+                let column = matrix.column(read);
+                let (mut best, mut second_best) = (0, usize::MAX);
+                for allele in 1..allele_count {
+                    if column[allele] > column[best] {
+                        second_best = best;
+                        best = allele;
+                    } else if second_best == usize::MAX || column[allele] > column[second_best] {
+                        second_best = allele;
+                    }
+                }
+
+                for (allele, best_margin) in best_margins.iter_mut().enumerate() {
+                    let other = if allele == best { second_best } else { best };
+                    let margin = column[allele] - column[other];
+                    if margin.is_finite() && margin > *best_margin {
+                        *best_margin = margin;
+                    }
+                }
+            }
+
+            scores
+                .iter_mut()
+                .zip(best_margins.into_iter())
+                .filter(|(_, best_margin)| best_margin.is_finite())
+                .for_each(|(score, best_margin)| *score += best_margin);
+        }
+        scores
+    }
+
+    fn evidence_counts(
+        read_likelihoods: &AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> Vec<usize> {
+        (0..read_likelihoods.number_of_samples())
+            .map(|sample_index| read_likelihoods.sample_evidence_count(sample_index))
+            .collect()
+    }
+}
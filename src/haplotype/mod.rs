@@ -1,9 +1,12 @@
 pub mod called_haplotypes;
 pub mod event_map;
+pub mod assembly_fallback_caller;
 pub mod haplotype;
 pub mod haplotype_caller_engine;
 pub mod haplotype_caller_genotyping_engine;
 pub mod haplotype_clustering_engine;
+pub mod haplotype_records;
+pub mod haplotype_scoring;
 pub mod homogenous_ploidy_model;
 pub mod independent_samples_genotype_model;
 pub mod location_and_alleles;
@@ -13,6 +13,7 @@ pub mod annotator;
 pub mod assembly;
 pub mod bam_parsing;
 pub mod cli;
+pub mod concordance;
 pub mod evolve;
 pub mod external_command_checker;
 pub mod genotype;
@@ -21,6 +22,7 @@ pub mod haplotype;
 pub mod linkage;
 pub mod model;
 pub mod pair_hmm;
+pub mod phylogeny;
 pub mod processing;
 pub mod read_error_corrector;
 pub mod read_orientation;
@@ -46,7 +48,7 @@ extern crate serde_derive;
 #[macro_use]
 extern crate approx;
 
-use std::process;
+use crate::utils::exit_status::ExitStatus;
 
 pub const AUTHOR: &str =
     "Rhys J. P. Newell, Centre for Microbiome Research, School of Biomedical Sciences, Faculty of Health, Queensland University of Technology";
@@ -61,7 +63,7 @@ pub fn parse_percentage(m: &clap::ArgMatches, parameter: &str) -> f32 {
                 percentage = percentage / 100.0;
             } else if percentage < 0.0 || percentage > 100.0 {
                 error!("Invalid alignment percentage: '{}'", percentage);
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
             info!("Using {} {}%", parameter, percentage * 100.0);
             percentage
@@ -24,6 +24,7 @@ use crate::bam_parsing::{
         NamedBamReaderGenerator,
     },
 };
+use crate::linkage::variant_linkage_graph::{VariantLinkageGraph, VariantNode};
 use crate::model::byte_array_allele::Allele;
 use crate::model::variant_context::VariantContext;
 
@@ -35,6 +36,9 @@ use crate::model::variant_context::VariantContext;
 pub struct LinkageEngine<'a> {
     grouped_contexts: LinkedHashMap<i32, Vec<&'a VariantContext>>,
     grouped_mean_read_depth: LinkedHashMap<i32, f64>,
+    // reads supporting the alternate allele of each variant, in the same order as grouped_contexts
+    variant_reads: LinkedHashMap<i32, Vec<HashSet<String>>>,
+    grouped_reads: LinkedHashMap<i32, HashSet<String>>,
     // samples: &'a [String],
     cluster_separations: &'a Array2<f64>,
     previous_groups: &'a HashMap<i32, i32>,
@@ -54,6 +58,8 @@ impl<'a> LinkageEngine<'a> {
         Self {
             grouped_contexts,
             grouped_mean_read_depth: LinkedHashMap::new(),
+            variant_reads: LinkedHashMap::new(),
+            grouped_reads: LinkedHashMap::new(),
             // samples,
             cluster_separations,
             previous_groups,
@@ -65,22 +71,35 @@ impl<'a> LinkageEngine<'a> {
         self.grouped_contexts
     }
 
+    /// Sets the reads supporting each variant of each variant group, in the same order as the
+    /// grouped contexts, instead of collecting them from the BAM files. The reads of each group
+    /// are the reads of its variants
+    pub fn set_variant_reads(&mut self, variant_reads: LinkedHashMap<i32, Vec<HashSet<String>>>) {
+        self.grouped_reads = variant_reads
+            .iter()
+            .map(|(group, reads)| (*group, reads.iter().flatten().cloned().collect()))
+            .collect();
+        self.variant_reads = variant_reads;
+    }
+
     /// Runs the linkage algorithm on the grouped variant contexts and returns
     /// an ordered vector of length n where n is number of variant contexts. Each index of the vector
     /// corresponds to the index of the variant contexts present in the HaplotypeClusteringEngine.
     /// Each element of the vector is another vector containing the strain indices
-    /// that are associated with the corresponding variant context
+    /// that are associated with the corresponding variant context.
+    /// Strains connected by at least `min_linked_reads` reads are merged, 0 disables merging
     pub fn run_linkage(
-        mut self,
+        &mut self,
         indexed_bam_readers: &[String],
         n_threads: usize,
         output_path: &str,
         flag_filters: &FlagFilter,
+        min_linked_reads: usize,
     ) -> Vec<LinkedHashSet<i32>> {
-        let read_ids_in_groups =
+        self.grouped_reads =
             self.get_reads_for_groups(indexed_bam_readers, flag_filters, n_threads);
         // debug!("group mean read depths {:?}", &self.grouped_mean_read_depth);
-        let graph = self.build_graph(read_ids_in_groups);
+        let graph = self.build_graph(&self.grouped_reads);
         // debug!("Graph {} {}", graph.node_count(), graph.edge_count());
         if log_enabled!(Level::Debug) {
             let output_dot = format!("{}_vg_graph.dot", output_path);
@@ -91,20 +110,156 @@ impl<'a> LinkageEngine<'a> {
 
             writeln!(file_open, "{:?}", Dot::new(&graph)).expect("Unable to write dot file");
         }
-        if graph.edge_count() == 0 {
+        let strains = if graph.edge_count() == 0 {
             // no connection formed, so each variant group is its own strain
-            return graph
+            graph
                 .node_weights()
                 .map(|n| {
                     let mut new_strain = LinkedHashSet::with_capacity(1);
                     new_strain.insert(*n);
                     new_strain
                 })
-                .collect();
+                .collect()
+        } else {
+            // let connected_components = self.extract_connected_components(graph);
+            self.compute_strain_denominations(vec![graph], output_path)
+        };
+
+        if min_linked_reads > 0 {
+            self.merge_linked_strains(strains, min_linked_reads)
+        } else {
+            strains
+        }
+    }
+
+    /// The reads supporting any of the given variant groups
+    fn reads_for_groups<'b, I: IntoIterator<Item = &'b i32>>(&self, groups: I) -> HashSet<&str> {
+        groups
+            .into_iter()
+            .filter_map(|group| self.grouped_reads.get(group))
+            .flat_map(|reads| reads.iter().map(|read| read.as_str()))
+            .collect()
+    }
+
+    /// Merges strains that are connected by at least `min_linked_reads` reads spanning a variant
+    /// in each strain. Variant groups that both strains share are ignored, as are strains
+    /// containing mutually exclusive groups. Strains are merged greedily in order, each strain
+    /// being merged into the first previous strain it is linked to.
+    pub fn merge_linked_strains(
+        &self,
+        strains: Vec<LinkedHashSet<i32>>,
+        min_linked_reads: usize,
+    ) -> Vec<LinkedHashSet<i32>> {
+        let mut merged_strains: Vec<LinkedHashSet<i32>> = Vec::with_capacity(strains.len());
+
+        for strain in strains {
+            let linked_strain = merged_strains.iter().position(|other| {
+                if strain
+                    .iter()
+                    .any(|g1| other.iter().any(|g2| self.check_exclusion(g1, g2)))
+                {
+                    return false;
+                }
+                let strain_reads =
+                    self.reads_for_groups(strain.iter().filter(|g| !other.contains(*g)));
+                let other_reads =
+                    self.reads_for_groups(other.iter().filter(|g| !strain.contains(*g)));
+                strain_reads.intersection(&other_reads).count() >= min_linked_reads
+            });
+
+            match linked_strain {
+                Some(strain_idx) => merged_strains[strain_idx].extend(strain),
+                None => merged_strains.push(strain),
+            }
         }
-        // let connected_components = self.extract_connected_components(graph);
 
-        self.compute_strain_denominations(vec![graph], output_path)
+        merged_strains
+    }
+
+    /// For each variant in each variant group, the number of reads supporting the variant that
+    /// also support a variant in another variant group of the same strain. Counts are in the
+    /// same order as the variants in the grouped contexts
+    pub fn linked_read_counts(
+        &self,
+        strains: &[LinkedHashSet<i32>],
+    ) -> LinkedHashMap<i32, Vec<usize>> {
+        let mut linked_groups: HashMap<i32, HashSet<i32>> = HashMap::new();
+        for strain in strains {
+            for group in strain.iter() {
+                linked_groups
+                    .entry(*group)
+                    .or_insert_with(HashSet::new)
+                    .extend(strain.iter().filter(|other| *other != group));
+            }
+        }
+
+        self.variant_reads
+            .iter()
+            .map(|(group, variant_reads)| {
+                let linked_reads = match linked_groups.get(group) {
+                    Some(groups) => self.reads_for_groups(groups.iter()),
+                    None => HashSet::new(),
+                };
+                let counts = variant_reads
+                    .iter()
+                    .map(|reads| {
+                        reads
+                            .iter()
+                            .filter(|read| linked_reads.contains(read.as_str()))
+                            .count()
+                    })
+                    .collect::<Vec<usize>>();
+                (*group, counts)
+            })
+            .collect()
+    }
+
+    /// The read linkage graph between the individual variants of every variant group, with each
+    /// variant labelled by the strains its group was assigned to
+    pub fn variant_linkage_graph<F: Fn(usize) -> String>(
+        &self,
+        strains: &[LinkedHashSet<i32>],
+        contig_name: F,
+    ) -> VariantLinkageGraph {
+        let mut group_strains: HashMap<i32, Vec<usize>> = HashMap::new();
+        for (strain_idx, strain) in strains.iter().enumerate() {
+            for group in strain.iter() {
+                group_strains
+                    .entry(*group)
+                    .or_insert_with(Vec::new)
+                    .push(strain_idx);
+            }
+        }
+
+        let no_reads = HashSet::new();
+        let mut nodes = Vec::new();
+        let mut node_reads = Vec::new();
+        for (group, variants) in self.grouped_contexts.iter() {
+            let group_reads = self.variant_reads.get(group);
+            for (variant_idx, variant) in variants.iter().enumerate() {
+                let reads = group_reads
+                    .and_then(|reads| reads.get(variant_idx))
+                    .unwrap_or(&no_reads);
+                nodes.push(VariantNode {
+                    contig: contig_name(variant.loc.tid),
+                    position: variant.loc.start + 1,
+                    reference: String::from_utf8_lossy(variant.get_reference().get_display_bases())
+                        .to_string(),
+                    alternate: variant
+                        .get_alternate_alleles()
+                        .iter()
+                        .map(|allele| String::from_utf8_lossy(allele.get_display_bases()).to_string())
+                        .collect::<Vec<String>>()
+                        .join(","),
+                    variant_group: *group,
+                    strains: group_strains.get(group).cloned().unwrap_or_default(),
+                    supporting_reads: reads.len(),
+                });
+                node_reads.push(reads);
+            }
+        }
+
+        VariantLinkageGraph::new(nodes, &node_reads)
     }
 
     /// Compute the different denominations of strain groupings from a given component.
@@ -915,9 +1070,13 @@ impl<'a> LinkageEngine<'a> {
                 let mut grouped_reads = LinkedHashMap::with_capacity(self.grouped_contexts.len());
                 let mut grouped_read_counts =
                     LinkedHashMap::with_capacity(self.grouped_contexts.len());
+                let mut variant_reads = LinkedHashMap::with_capacity(self.grouped_contexts.len());
                 let mut record = Record::new();
                 for (group, variants) in self.grouped_contexts.iter() {
-                    for variant in variants {
+                    let group_variant_reads = variant_reads
+                        .entry(*group)
+                        .or_insert_with(|| vec![HashSet::new(); variants.len()]);
+                    for (variant_idx, variant) in variants.iter().enumerate() {
                         bam_generated
                             .fetch((
                                 variant.loc.tid as i32,
@@ -975,6 +1134,7 @@ impl<'a> LinkageEngine<'a> {
                                         sample_idx,
                                         std::str::from_utf8(record.qname()).unwrap()
                                     );
+                                    group_variant_reads[variant_idx].insert(read_id.clone());
                                     records.insert(read_id);
                                     read_count += 1.0;
                                 }
@@ -992,6 +1152,7 @@ impl<'a> LinkageEngine<'a> {
                                         sample_idx,
                                         std::str::from_utf8(record.qname()).unwrap()
                                     );
+                                    group_variant_reads[variant_idx].insert(read_id.clone());
                                     records.insert(read_id);
                                     read_count += 1.0;
                                 }
@@ -1006,11 +1167,25 @@ impl<'a> LinkageEngine<'a> {
                     }
                 }
 
-                (grouped_reads, grouped_read_counts)
+                (grouped_reads, grouped_read_counts, variant_reads)
             })
-            .collect::<Vec<(LinkedHashMap<i32, HashSet<String>>, LinkedHashMap<i32, f64>)>>()
+            .collect::<Vec<(
+                LinkedHashMap<i32, HashSet<String>>,
+                LinkedHashMap<i32, f64>,
+                LinkedHashMap<i32, Vec<HashSet<String>>>,
+            )>>()
             .into_iter()
-            .for_each(|(sample_grouping, sample_counts)| {
+            .for_each(|(sample_grouping, sample_counts, sample_variant_reads)| {
+                for (vg, reads) in sample_variant_reads {
+                    let all_result = self
+                        .variant_reads
+                        .entry(vg)
+                        .or_insert_with(|| vec![HashSet::new(); reads.len()]);
+                    for (all_reads, reads) in all_result.iter_mut().zip(reads) {
+                        all_reads.extend(reads);
+                    }
+                }
+
                 for (vg, reads) in sample_grouping {
                     let all_result = all_grouped_reads.entry(vg).or_insert(HashSet::new());
                     all_result.par_extend(reads);
@@ -1037,10 +1212,7 @@ impl<'a> LinkageEngine<'a> {
     /// Builds a variant group graph. This graph is directed by read depth.
     /// Nodes with lower mean read depth are connected to high read depth nodes via incoming edges.
     /// Thus, low depth nodes are sinks, high depth are sources
-    fn build_graph(
-        &mut self,
-        grouped_reads: LinkedHashMap<i32, HashSet<String>>,
-    ) -> Graph<i32, f64> {
+    fn build_graph(&self, grouped_reads: &LinkedHashMap<i32, HashSet<String>>) -> Graph<i32, f64> {
         let mut graph = Graph::new();
         let mut node_indices = LinkedHashMap::with_capacity(grouped_reads.len());
         for (group1, reads1) in grouped_reads.iter() {
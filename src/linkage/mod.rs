@@ -1 +1,4 @@
 pub mod linkage_engine;
+pub mod phasing_statistics;
+pub mod strain_read_binning;
+pub mod variant_linkage_graph;
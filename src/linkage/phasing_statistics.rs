@@ -0,0 +1,292 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// The strain assignment of a variant, as used to decide whether it is phased with its neighbour
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhasedVariant {
+    pub tid: usize,
+    pub start: usize,
+    pub end: usize,
+    pub group: Option<i32>,
+    pub strains: Option<Vec<usize>>,
+    pub linked_reads: i32,
+}
+
+impl PhasedVariant {
+    pub fn from_context(vc: &VariantContext) -> Self {
+        let group = match vc.attributes.get(VariantAnnotations::VariantGroup.to_key()) {
+            Some(AttributeObject::I32(group)) => Some(*group),
+            _ => None,
+        };
+        let strains = match vc.attributes.get(VariantAnnotations::Strain.to_key()) {
+            Some(AttributeObject::VecUnsize(strains)) => Some(strains.clone()),
+            _ => None,
+        };
+        let linked_reads = match vc.attributes.get(VariantAnnotations::LinkedReads.to_key()) {
+            Some(AttributeObject::I32(linked_reads)) => *linked_reads,
+            _ => 0,
+        };
+        Self {
+            tid: vc.loc.tid,
+            start: vc.loc.start,
+            end: vc.loc.end,
+            group,
+            strains,
+            linked_reads,
+        }
+    }
+
+    /// Two neighbouring variants are phased if both were assigned to strains and they either
+    /// belong to the same variant group or are both linked by reads to a strain they share
+    pub fn is_phased_with(&self, other: &PhasedVariant) -> bool {
+        if self.tid != other.tid {
+            return false;
+        }
+        match (&self.strains, &other.strains) {
+            (Some(strains), Some(other_strains)) => {
+                (self.group.is_some() && self.group == other.group)
+                    || (self.linked_reads > 0
+                        && other.linked_reads > 0
+                        && strains.iter().any(|strain| other_strains.contains(strain)))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Haplotype block statistics of a sample, or of a whole genome
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhasingSummary {
+    pub variants: usize,
+    // pairs of neighbouring variants on the same contig
+    pub variant_pairs: usize,
+    pub phased_pairs: usize,
+    // blocks of at least two phased variants
+    pub blocks: usize,
+    pub block_n50: usize,
+    pub largest_block: usize,
+}
+
+impl PhasingSummary {
+    pub fn fraction_phased(&self) -> f64 {
+        if self.variant_pairs > 0 {
+            self.phased_pairs as f64 / self.variant_pairs as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Haplotype block statistics of each sample: the number, N50 and largest of the blocks of
+/// neighbouring heterozygous variants that strain assignment phases together, and the fraction of
+/// neighbouring pairs that were phased.
+pub struct PhasingStatistics {
+    depth_per_sample_filter: i32,
+}
+
+impl PhasingStatistics {
+    pub const GENOME_ROW: &'static str = "all_samples";
+    pub const RUN_REPORT_NAME: &'static str = "lorikeet_phasing_report.tsv";
+
+    pub fn new(depth_per_sample_filter: i64) -> Self {
+        Self {
+            depth_per_sample_filter: depth_per_sample_filter.max(1) as i32,
+        }
+    }
+
+    /// The length-weighted median of the block lengths
+    pub fn n50(block_lengths: &[usize]) -> usize {
+        let mut lengths = block_lengths.to_vec();
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        let half = (lengths.iter().sum::<usize>() + 1) / 2;
+        let mut cumulative = 0;
+        for length in lengths {
+            cumulative += length;
+            if cumulative >= half {
+                return length;
+            }
+        }
+        0
+    }
+
+    /// Summarises variants sorted by position
+    pub fn summarise(variants: &[PhasedVariant]) -> PhasingSummary {
+        let mut variant_pairs = 0;
+        let mut phased_pairs = 0;
+        let mut block_lengths = Vec::new();
+        // start of the current block and the number of variants in it
+        let mut block: Option<(usize, usize)> = None;
+
+        for (idx, variant) in variants.iter().enumerate() {
+            let previous = if idx > 0 { variants.get(idx - 1) } else { None };
+            match previous {
+                Some(previous) if previous.tid == variant.tid => {
+                    variant_pairs += 1;
+                    if previous.is_phased_with(variant) {
+                        phased_pairs += 1;
+                        block = block.map(|(start, count)| (start, count + 1));
+                    } else {
+                        Self::close_block(block, previous, &mut block_lengths);
+                        block = Some((variant.start, 1));
+                    }
+                }
+                Some(previous) => {
+                    Self::close_block(block, previous, &mut block_lengths);
+                    block = Some((variant.start, 1));
+                }
+                None => block = Some((variant.start, 1)),
+            }
+        }
+        if let Some(last) = variants.last() {
+            Self::close_block(block, last, &mut block_lengths);
+        }
+
+        PhasingSummary {
+            variants: variants.len(),
+            variant_pairs,
+            phased_pairs,
+            blocks: block_lengths.len(),
+            block_n50: Self::n50(&block_lengths),
+            largest_block: block_lengths.iter().copied().max().unwrap_or(0),
+        }
+    }
+
+    fn close_block(
+        block: Option<(usize, usize)>,
+        last_variant: &PhasedVariant,
+        block_lengths: &mut Vec<usize>,
+    ) {
+        if let Some((start, count)) = block {
+            if count >= 2 {
+                block_lengths.push(last_variant.end.saturating_sub(start) + 1);
+            }
+        }
+    }
+
+    /// The summary of each sample followed by the summary of the genome
+    pub fn summaries(&self, contexts: &[VariantContext], n_samples: usize) -> Vec<PhasingSummary> {
+        let mut sorted = contexts
+            .iter()
+            .filter(|vc| !vc.is_filtered())
+            .collect::<Vec<&VariantContext>>();
+        sorted.sort_by_key(|vc| (vc.loc.tid, vc.loc.start));
+
+        let mut summaries = (0..n_samples)
+            .map(|sample_index| {
+                let variants = sorted
+                    .iter()
+                    .filter(|vc| {
+                        vc.genotypes
+                            .genotypes()
+                            .get(sample_index)
+                            .map(|genotype| {
+                                genotype
+                                    .ad
+                                    .iter()
+                                    .filter(|ad| **ad >= self.depth_per_sample_filter)
+                                    .count()
+                                    >= 2
+                            })
+                            .unwrap_or(false)
+                    })
+                    .map(|vc| PhasedVariant::from_context(vc))
+                    .collect::<Vec<PhasedVariant>>();
+                Self::summarise(&variants)
+            })
+            .collect::<Vec<PhasingSummary>>();
+
+        let variants = sorted
+            .iter()
+            .map(|vc| PhasedVariant::from_context(vc))
+            .collect::<Vec<PhasedVariant>>();
+        summaries.push(Self::summarise(&variants));
+        summaries
+    }
+
+    /// Writes the summary of each sample and of the genome to
+    /// {output_prefix}/{reference_name}_phasing.tsv
+    pub fn write(
+        &self,
+        contexts: &[VariantContext],
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+    ) -> Result<(), BirdToolError> {
+        let file_name = format!("{}/{}_phasing.tsv", output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(writer, "{}", Self::HEADER).map_err(write_error)?;
+        let summaries = self.summaries(contexts, sample_names.len());
+        for (idx, summary) in summaries.iter().enumerate() {
+            let sample = sample_names.get(idx).copied().unwrap_or(Self::GENOME_ROW);
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{:.4}\t{}\t{}\t{}",
+                sample,
+                summary.variants,
+                summary.variant_pairs,
+                summary.phased_pairs,
+                summary.fraction_phased(),
+                summary.blocks,
+                summary.block_n50,
+                summary.largest_block
+            )
+            .map_err(write_error)?;
+        }
+
+        writer.flush().map_err(write_error)
+    }
+
+    const HEADER: &'static str = "sample\theterozygous_variants\tvariant_pairs\tphased_pairs\t\
+        fraction_phased\tblocks\tblock_n50\tlargest_block";
+
+    /// Combines the phasing tables of each genome into RUN_REPORT_NAME in the output directory,
+    /// prefixing each row with its genome. Returns the path of the report
+    pub fn write_run_report(
+        output_directory: &str,
+        genome_tables: &[(String, PathBuf)],
+    ) -> Result<PathBuf, BirdToolError> {
+        let report_path = Path::new(output_directory).join(Self::RUN_REPORT_NAME);
+        let file = File::create(&report_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", report_path.display(), e))
+        })?;
+        let mut writer = BufWriter::new(file);
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!(
+                "Unable to write to {}: {}",
+                report_path.display(),
+                e
+            ))
+        };
+
+        writeln!(writer, "genome\t{}", Self::HEADER).map_err(write_error)?;
+        for (genome, table) in genome_tables {
+            let reader = BufReader::new(File::open(table).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to open {}: {}", table.display(), e))
+            })?);
+            for line in reader.lines().skip(1) {
+                let line = line.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read {}: {}", table.display(), e))
+                })?;
+                if !line.is_empty() {
+                    writeln!(writer, "{}\t{}", genome, line).map_err(write_error)?;
+                }
+            }
+        }
+        writer.flush().map_err(write_error)?;
+
+        Ok(report_path)
+    }
+}
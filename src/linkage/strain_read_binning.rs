@@ -0,0 +1,371 @@
+use rust_htslib::bam::{self, Read, Record};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::abundance::strain_frequencies::StrainFrequencyEstimator;
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::bam_parsing::split_alignment::SplitAlignmentChain;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::byte_array_allele::Allele;
+use crate::model::variant_context::VariantContext;
+use crate::processing::output_layout::OutputLayout;
+use crate::utils::errors::BirdToolError;
+
+/// Bins long reads by the strain they most likely came from, from the alleles they support at
+/// strain assigned variants and the strain frequencies of their sample. Reads reaching
+/// `min_posterior` for a single strain are written, with every alignment, to the BAM file of that
+/// strain.
+pub struct StrainReadBinner {
+    strain_ids: Vec<usize>,
+    min_posterior: f64,
+}
+
+/// The allele likelihoods of a read, summed over the variants it spans
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadStrainLikelihoods {
+    pub log_likelihoods: Vec<f64>,
+    pub sites: usize,
+}
+
+impl StrainReadBinner {
+    // long read base qualities are often missing or uninformative
+    const DEFAULT_ERROR_RATE: f64 = 0.05;
+    const MIN_ERROR_RATE: f64 = 1e-4;
+
+    pub fn new(strain_ids: &[usize], min_posterior: f64) -> Self {
+        Self {
+            strain_ids: strain_ids.to_vec(),
+            min_posterior,
+        }
+    }
+
+    /// The binner requested by --bin-long-reads-by-strain, if any
+    pub fn from_args(args: &clap::ArgMatches, strain_ids: &[usize]) -> Option<Self> {
+        let requested = args
+            .try_get_one::<bool>("bin-long-reads-by-strain")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        if !requested {
+            return None;
+        }
+
+        let min_posterior = args
+            .try_get_one::<f64>("min-strain-read-posterior")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(0.9);
+        Some(Self::new(strain_ids, min_posterior))
+    }
+
+    /// Normalised posterior probability of each strain given the log likelihoods of a read
+    pub fn posteriors(log_likelihoods: &[f64], priors: &[f64]) -> Vec<f64> {
+        let log_posteriors = log_likelihoods
+            .iter()
+            .zip(priors.iter())
+            .map(|(log_likelihood, prior)| {
+                if *prior > 0.0 {
+                    log_likelihood + prior.ln()
+                } else {
+                    f64::NEG_INFINITY
+                }
+            })
+            .collect::<Vec<f64>>();
+        let max = log_posteriors
+            .iter()
+            .cloned()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max == f64::NEG_INFINITY {
+            return vec![0.0; log_posteriors.len()];
+        }
+
+        let unnormalised = log_posteriors
+            .iter()
+            .map(|log_posterior| (log_posterior - max).exp())
+            .collect::<Vec<f64>>();
+        let total = unnormalised.iter().sum::<f64>();
+        unnormalised.into_iter().map(|p| p / total).collect()
+    }
+
+    /// The index of the strain a read is binned to along with its posterior, or None if no strain
+    /// reaches the minimum posterior
+    pub fn assign(
+        &self,
+        likelihoods: &ReadStrainLikelihoods,
+        priors: &[f64],
+    ) -> Option<(usize, f64)> {
+        if likelihoods.sites == 0 {
+            return None;
+        }
+        Self::posteriors(&likelihoods.log_likelihoods, priors)
+            .into_iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+            .filter(|(_, posterior)| *posterior >= self.min_posterior)
+    }
+
+    /// The path of the BAM file of reads binned to a strain
+    pub fn bam_path(
+        output_prefix: &str,
+        reference_name: &str,
+        sample_name: &str,
+        strain_id: usize,
+    ) -> String {
+        format!(
+            "{}/{}",
+            output_prefix,
+            OutputLayout::strain_bam_name(reference_name, strain_id, sample_name)
+        )
+    }
+
+    /// The key identifying the alignments of a split read, i.e. a read with an SA tag or a
+    /// supplementary alignment, so that they are counted once at a variant. The mates of a pair
+    /// have different keys, and reads that are not split have none so they are always counted
+    pub fn split_read_key(record: &Record) -> Option<(Vec<u8>, bool)> {
+        if record.is_supplementary() || SplitAlignmentChain::sa_tag(record).is_some() {
+            Some((record.qname().to_vec(), record.is_last_in_template()))
+        } else {
+            None
+        }
+    }
+
+    /// Bins the reads of a sample and writes the reads binned to each strain to its own indexed
+    /// BAM file. Returns the number of reads binned to each strain
+    pub fn bin_reads(
+        &self,
+        contexts: &[VariantContext],
+        bam_path: &str,
+        sample_index: usize,
+        output_prefix: &str,
+        reference_name: &str,
+        sample_name: &str,
+        n_threads: usize,
+    ) -> Result<Vec<usize>, BirdToolError> {
+        let (read_likelihoods, split_tids) = self.read_likelihoods(contexts, bam_path)?;
+
+        // strain frequencies of the sample are the prior of each read
+        let estimator = StrainFrequencyEstimator::new(&self.strain_ids);
+        let priors = estimator
+            .estimate(&estimator.sample_sites(contexts, sample_index))
+            .into_iter()
+            .map(|estimate| estimate.frequency)
+            .collect::<Vec<f64>>();
+
+        let assignments = read_likelihoods
+            .into_iter()
+            .filter_map(|(read_name, likelihoods)| {
+                self.assign(&likelihoods, &priors)
+                    .map(|(strain, _)| (read_name, strain))
+            })
+            .collect::<HashMap<Vec<u8>, usize>>();
+
+        // contigs are written in order so the binned reads stay sorted
+        let mut tids = contexts
+            .iter()
+            .map(|vc| vc.loc.tid as u32)
+            .collect::<BTreeSet<u32>>();
+        for (read_name, read_tids) in split_tids.iter() {
+            if assignments.contains_key(read_name) {
+                tids.extend(read_tids);
+            }
+        }
+
+        self.write_binned_reads(
+            &tids,
+            bam_path,
+            &assignments,
+            output_prefix,
+            reference_name,
+            sample_name,
+            n_threads,
+        )
+    }
+
+    /// The likelihoods of every read spanning a strain assigned variant, along with the contigs
+    /// each split read has segments on
+    fn read_likelihoods(
+        &self,
+        contexts: &[VariantContext],
+        bam_path: &str,
+    ) -> Result<
+        (
+            HashMap<Vec<u8>, ReadStrainLikelihoods>,
+            HashMap<Vec<u8>, BTreeSet<u32>>,
+        ),
+        BirdToolError,
+    > {
+        let mut reader = bam::IndexedReader::from_path(bam_path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {:?}", bam_path, e)))?;
+        let header = reader.header().clone();
+        let mut read_likelihoods: HashMap<Vec<u8>, ReadStrainLikelihoods> = HashMap::new();
+        let mut split_tids: HashMap<Vec<u8>, BTreeSet<u32>> = HashMap::new();
+        let mut record = Record::new();
+
+        for vc in contexts.iter() {
+            let strains = match vc.attributes.get(VariantAnnotations::Strain.to_key()) {
+                Some(AttributeObject::VecUnsize(strains)) => strains,
+                _ => continue,
+            };
+            let carries_alt = self
+                .strain_ids
+                .iter()
+                .map(|strain_id| strains.contains(strain_id))
+                .collect::<Vec<bool>>();
+            let ref_bases = vc.get_reference().get_bases();
+            let alternate_alleles = vc.get_alternate_alleles();
+            let alt_bases = match alternate_alleles.first() {
+                Some(alt) => alt.get_bases(),
+                None => continue,
+            };
+
+            reader
+                .fetch((
+                    vc.loc.tid as i32,
+                    vc.loc.start as i64,
+                    vc.loc.end as i64 + 1,
+                ))
+                .map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Unable to fetch {}:{}-{} from {}: {:?}",
+                        vc.loc.tid, vc.loc.start, vc.loc.end, bam_path, e
+                    ))
+                })?;
+            // split reads observed at this variant, so that each is only counted once
+            let mut observed: HashSet<(Vec<u8>, bool)> = HashSet::new();
+            while let Some(result) = reader.read(&mut record) {
+                result.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read {}: {:?}", bam_path, e))
+                })?;
+                if record.is_unmapped() || record.is_secondary() || record.is_duplicate() {
+                    continue;
+                }
+                if SplitAlignmentChain::sa_tag(&record).is_some()
+                    && !split_tids.contains_key(record.qname())
+                {
+                    let chain = SplitAlignmentChain::from_record(&record, |contig_name| {
+                        header.tid(contig_name)
+                    });
+                    split_tids.insert(record.qname().to_vec(), chain.tids());
+                }
+
+                let read_pos = match record.cigar().read_pos(vc.loc.start as u32, false, false) {
+                    Ok(Some(read_pos)) => read_pos as usize,
+                    _ => continue,
+                };
+                let seq = record.seq().as_bytes();
+                let matches = |allele: &[u8]| {
+                    seq.len() >= read_pos + allele.len()
+                        && &seq[read_pos..read_pos + allele.len()] == allele
+                };
+                let supports_alt = match (matches(alt_bases), matches(ref_bases)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    // reads matching both or neither allele do not tell the strains apart
+                    _ => continue,
+                };
+                if let Some(key) = Self::split_read_key(&record) {
+                    if !observed.insert(key) {
+                        continue;
+                    }
+                }
+
+                let error_rate = match record.qual().get(read_pos) {
+                    Some(qual) if *qual != 255 => 10.0_f64
+                        .powf(-(*qual as f64) / 10.0)
+                        .max(Self::MIN_ERROR_RATE),
+                    _ => Self::DEFAULT_ERROR_RATE,
+                };
+                let likelihoods = read_likelihoods
+                    .entry(record.qname().to_vec())
+                    .or_insert_with(|| ReadStrainLikelihoods {
+                        log_likelihoods: vec![0.0; self.strain_ids.len()],
+                        sites: 0,
+                    });
+                for (log_likelihood, strain_carries_alt) in likelihoods
+                    .log_likelihoods
+                    .iter_mut()
+                    .zip(carries_alt.iter())
+                {
+                    *log_likelihood += if *strain_carries_alt == supports_alt {
+                        (1.0 - error_rate).ln()
+                    } else {
+                        error_rate.ln()
+                    };
+                }
+                likelihoods.sites += 1;
+            }
+        }
+
+        Ok((read_likelihoods, split_tids))
+    }
+
+    fn write_binned_reads(
+        &self,
+        tids: &BTreeSet<u32>,
+        bam_path: &str,
+        assignments: &HashMap<Vec<u8>, usize>,
+        output_prefix: &str,
+        reference_name: &str,
+        sample_name: &str,
+        n_threads: usize,
+    ) -> Result<Vec<usize>, BirdToolError> {
+        let mut reader = bam::IndexedReader::from_path(bam_path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {:?}", bam_path, e)))?;
+        let header = bam::Header::from_template(reader.header());
+
+        let paths = self
+            .strain_ids
+            .iter()
+            .map(|strain_id| Self::bam_path(output_prefix, reference_name, sample_name, *strain_id))
+            .collect::<Vec<String>>();
+        let mut writers = paths
+            .iter()
+            .map(|path| {
+                bam::Writer::from_path(path, &header, bam::Format::Bam).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to write bam at {}: {:?}", path, e))
+                })
+            })
+            .collect::<Result<Vec<bam::Writer>, BirdToolError>>()?;
+
+        let mut read_counts = vec![0; self.strain_ids.len()];
+        let mut record = Record::new();
+        for tid in tids {
+            reader.fetch(*tid as i32).map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to fetch contig {} from {}: {:?}",
+                    tid, bam_path, e
+                ))
+            })?;
+            while let Some(result) = reader.read(&mut record) {
+                result.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read {}: {:?}", bam_path, e))
+                })?;
+                if let Some(strain) = assignments.get(record.qname()) {
+                    writers[*strain].write(&record).map_err(|e| {
+                        BirdToolError::IOError(format!("Unable to write binned read {:?}", e))
+                    })?;
+                    if !record.is_secondary() && !record.is_supplementary() {
+                        read_counts[*strain] += 1;
+                    }
+                }
+            }
+        }
+
+        // writers must be closed before the BAM files can be indexed
+        drop(writers);
+        for path in paths.iter() {
+            bam::index::build(
+                path,
+                Some(&format!("{}.bai", path)),
+                bam::index::Type::Bai,
+                n_threads as u32,
+            )
+            .map_err(|e| {
+                BirdToolError::IOError(format!("Unable to index bam at {}: {:?}", path, e))
+            })?;
+        }
+
+        Ok(read_counts)
+    }
+}
@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// A variant in the read linkage graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantNode {
+    pub contig: String,
+    // 1-based position, as in the VCF
+    pub position: usize,
+    pub reference: String,
+    pub alternate: String,
+    pub variant_group: i32,
+    pub strains: Vec<usize>,
+    // reads supporting the alternate allele across all samples
+    pub supporting_reads: usize,
+}
+
+impl VariantNode {
+    pub fn id(&self) -> String {
+        format!(
+            "{}:{}:{}>{}",
+            self.contig, self.position, self.reference, self.alternate
+        )
+    }
+}
+
+/// The read linkage graph of a genome at the level of single variants. Each node is a variant
+/// and each edge connects two variants whose alternate alleles are supported by the same
+/// read(s), weighted by the number of shared reads. Exported so that strain separation can be
+/// inspected in tools like Cytoscape or Gephi.
+#[derive(Debug, Clone)]
+pub struct VariantLinkageGraph {
+    pub nodes: Vec<VariantNode>,
+    // (node index, node index, shared reads), with the smaller node index first
+    pub edges: Vec<(usize, usize, usize)>,
+}
+
+impl VariantLinkageGraph {
+    /// Builds the graph from the reads supporting each node, in the same order as `nodes`
+    pub fn new(nodes: Vec<VariantNode>, node_reads: &[&HashSet<String>]) -> Self {
+        // invert the read sets so only pairs of variants that actually share reads are visited
+        let mut read_nodes: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (node_idx, reads) in node_reads.iter().enumerate() {
+            for read in reads.iter() {
+                read_nodes
+                    .entry(read.as_str())
+                    .or_insert_with(Vec::new)
+                    .push(node_idx);
+            }
+        }
+
+        let mut shared_reads: HashMap<(usize, usize), usize> = HashMap::new();
+        for (_, linked_nodes) in read_nodes {
+            for (i, node_1) in linked_nodes.iter().enumerate() {
+                for node_2 in linked_nodes[i + 1..].iter() {
+                    *shared_reads.entry((*node_1, *node_2)).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut edges = shared_reads
+            .into_iter()
+            .map(|((node_1, node_2), count)| (node_1, node_2, count))
+            .collect::<Vec<(usize, usize, usize)>>();
+        edges.sort_unstable();
+
+        Self { nodes, edges }
+    }
+
+    /// Writes `<output_path>_linkage_nodes.tsv`, `<output_path>_linkage_edges.tsv`, and
+    /// `<output_path>_linkage_graph.graphml`
+    pub fn write_files(&self, output_path: &str) {
+        let create = |suffix: &str| {
+            let file_name = format!("{}_{}", output_path, suffix);
+            BufWriter::new(
+                File::create(Path::new(&file_name))
+                    .unwrap_or_else(|_| panic!("Unable to create file: {}", file_name)),
+            )
+        };
+
+        self.write_nodes_tsv(&mut create("linkage_nodes.tsv"))
+            .expect("Unable to write linkage nodes");
+        self.write_edges_tsv(&mut create("linkage_edges.tsv"))
+            .expect("Unable to write linkage edges");
+        self.write_graphml(&mut create("linkage_graph.graphml"))
+            .expect("Unable to write linkage graph");
+    }
+
+    pub fn write_nodes_tsv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(
+            writer,
+            "id\tcontig\tposition\tref\talt\tvariant_group\tstrains\tsupporting_reads"
+        )?;
+        for node in self.nodes.iter() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                node.id(),
+                node.contig,
+                node.position,
+                node.reference,
+                node.alternate,
+                node.variant_group,
+                Self::format_strains(&node.strains),
+                node.supporting_reads
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_edges_tsv<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "source\ttarget\tshared_reads")?;
+        for (node_1, node_2, shared_reads) in self.edges.iter() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                self.nodes[*node_1].id(),
+                self.nodes[*node_2].id(),
+                shared_reads
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn write_graphml<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        writeln!(
+            writer,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )?;
+        for (key, attr_type) in [
+            ("contig", "string"),
+            ("position", "long"),
+            ("ref", "string"),
+            ("alt", "string"),
+            ("variant_group", "int"),
+            ("strains", "string"),
+            ("supporting_reads", "long"),
+        ] {
+            writeln!(
+                writer,
+                "  <key id=\"{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>",
+                key, key, attr_type
+            )?;
+        }
+        writeln!(
+            writer,
+            "  <key id=\"shared_reads\" for=\"edge\" attr.name=\"shared_reads\" attr.type=\"long\"/>"
+        )?;
+        writeln!(writer, "  <graph id=\"linkage\" edgedefault=\"undirected\">")?;
+
+        for (node_idx, node) in self.nodes.iter().enumerate() {
+            writeln!(writer, "    <node id=\"n{}\">", node_idx)?;
+            for (key, value) in [
+                ("contig", Self::escape(&node.contig)),
+                ("position", node.position.to_string()),
+                ("ref", Self::escape(&node.reference)),
+                ("alt", Self::escape(&node.alternate)),
+                ("variant_group", node.variant_group.to_string()),
+                ("strains", Self::format_strains(&node.strains)),
+                ("supporting_reads", node.supporting_reads.to_string()),
+            ] {
+                writeln!(writer, "      <data key=\"{}\">{}</data>", key, value)?;
+            }
+            writeln!(writer, "    </node>")?;
+        }
+
+        for (node_1, node_2, shared_reads) in self.edges.iter() {
+            writeln!(
+                writer,
+                "    <edge source=\"n{}\" target=\"n{}\"><data key=\"shared_reads\">{}</data></edge>",
+                node_1, node_2, shared_reads
+            )?;
+        }
+
+        writeln!(writer, "  </graph>")?;
+        writeln!(writer, "</graphml>")?;
+        Ok(())
+    }
+
+    fn format_strains(strains: &[usize]) -> String {
+        if strains.is_empty() {
+            ".".to_string()
+        } else {
+            strains
+                .iter()
+                .map(|strain| strain.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        }
+    }
+
+    fn escape(value: &str) -> String {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+}
@@ -0,0 +1,95 @@
+use ndarray::Array2;
+use std::sync::{Arc, Mutex};
+
+use crate::reference::reference_mask::ReferenceMask;
+
+/// The bases of a window of a contig passing the depth filters, within each sample (diagonal)
+/// and within each pair of samples (off diagonal)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessibleWindow {
+    pub tid: usize,
+    // 0-based, inclusive
+    pub start: usize,
+    pub end: usize,
+    pub compared_bases: Array2<f32>,
+}
+
+impl AccessibleWindow {
+    pub fn new(tid: usize, start: usize, end: usize, compared_bases: Array2<f32>) -> Self {
+        Self {
+            tid,
+            start,
+            end,
+            compared_bases,
+        }
+    }
+
+    pub fn contains(&self, tid: usize, position: usize) -> bool {
+        self.tid == tid && self.start <= position && position <= self.end
+    }
+
+    /// Bases passing the depth filters in both samples, or within a single sample when the
+    /// samples are the same
+    pub fn accessible_bases(&self, sample_1: usize, sample_2: usize) -> f64 {
+        self.compared_bases[[sample_1, sample_2]].max(0.0) as f64
+    }
+}
+
+/// The bases passing the depth filters in each sample and pair of samples, recorded one window at a
+/// time while calling and used as the denominators of per base statistics. Every clone shares the
+/// same windows.
+#[derive(Debug, Clone, Default)]
+pub struct AccessibleGenome {
+    windows: Arc<Mutex<Vec<AccessibleWindow>>>,
+}
+
+impl AccessibleGenome {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, window: AccessibleWindow) {
+        self.windows.lock().unwrap().push(window);
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The recorded windows sorted by position
+    pub fn windows(&self) -> Vec<AccessibleWindow> {
+        let mut windows = self.windows.lock().unwrap().clone();
+        windows.sort_by_key(|window| (window.tid, window.start));
+        windows
+    }
+
+    /// Removes the masked bases of each window from its accessible bases
+    pub fn apply_mask(&self, reference_mask: &ReferenceMask) {
+        for window in self.windows.lock().unwrap().iter_mut() {
+            let masked_bases =
+                reference_mask.masked_bases_within(window.tid, window.start, window.end) as f32;
+            window
+                .compared_bases
+                .iter_mut()
+                .for_each(|val| *val = (*val - masked_bases).max(0.0));
+        }
+    }
+
+    /// The index of the window of sorted `windows` containing a position
+    pub fn window_index(
+        windows: &[AccessibleWindow],
+        tid: usize,
+        position: usize,
+    ) -> Option<usize> {
+        let idx = windows.partition_point(|window| (window.tid, window.end) < (tid, position));
+        if idx < windows.len() && windows[idx].contains(tid, position) {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+}
@@ -62,6 +62,15 @@ impl AlleleFrequencyCalculator {
             .unwrap();
         let ploidy: usize = *args.get_one::<usize>("ploidy").unwrap();
 
+        Self::from_heterozygosity(snp_het, ind_het, het_std, ploidy)
+    }
+
+    pub fn from_heterozygosity(
+        snp_het: f64,
+        ind_het: f64,
+        het_std: f64,
+        ploidy: usize,
+    ) -> AlleleFrequencyCalculator {
         let ref_pseudo_count = snp_het / (het_std.powf(2.));
         let snp_pseudo_count = snp_het * ref_pseudo_count;
         let indel_pseudo_count = ind_het * ref_pseudo_count;
@@ -140,6 +149,25 @@ impl AlleleFrequencyCalculator {
         return MathUtils::normalize_log10(log10_posteriors, true);
     }
 
+    /**
+     * Haploid genotypes are single alleles, so the genotype index is the allele index and every
+     * genotype has a combination count of one. The posteriors are then just the likelihoods weighted
+     * by the allele frequencies and no genotype likelihood calculator needs to be built.
+     */
+    fn log10_normalized_haploid_posteriors(
+        g: &Genotype,
+        log10_allele_frequencies: &[f64],
+    ) -> Vec<f64> {
+        let log10_posteriors = g
+            .get_likelihoods()
+            .get_as_vector()
+            .iter()
+            .zip(log10_allele_frequencies.iter())
+            .map(|(likelihood, frequency)| likelihood + frequency)
+            .collect::<Vec<f64>>();
+        return MathUtils::normalize_log10(log10_posteriors, true);
+    }
+
     /**
      * Calculate the posterior probability that a single biallelic genotype is non-ref
      *
@@ -263,13 +291,20 @@ impl AlleleFrequencyCalculator {
                 g.get_ploidy()
             };
 
-            let mut gl_calc = GenotypeLikelihoodCalculators::get_instance(ploidy, num_alleles);
+            let mut gl_calc = if ploidy == 1 && g.has_likelihoods() {
+                None
+            } else {
+                Some(GenotypeLikelihoodCalculators::get_instance(ploidy, num_alleles))
+            };
 
-            let log10_genotype_posteriors = self.log10_normalized_genotype_posteriors(
-                &g,
-                &mut gl_calc,
-                &mut log10_allele_frequencies,
-            );
+            let log10_genotype_posteriors = match gl_calc.as_mut() {
+                Some(gl_calc) => self.log10_normalized_genotype_posteriors(
+                    &g,
+                    gl_calc,
+                    &mut log10_allele_frequencies,
+                ),
+                None => Self::log10_normalized_haploid_posteriors(&g, &log10_allele_frequencies),
+            };
 
             if !spanning_deletion_present {
                 log10_p_no_variant +=
@@ -314,19 +349,34 @@ impl AlleleFrequencyCalculator {
                     .iter_mut()
                     .for_each(|arr| arr.clear());
             }
-            for genotype in (0..gl_calc.genotype_count as usize).into_iter() {
-                let log10_genotype_posterior = log10_genotype_posteriors[genotype];
-                let log10_absent_posteriors = &log10_absent_posteriors;
-                gl_calc
-                    .genotype_allele_counts_at(genotype)
-                    .for_each_absent_allele_index(
-                        |a| {
-                            let mut log10_absent_posteriors = log10_absent_posteriors.borrow_mut();
-                            log10_absent_posteriors[a].push(log10_genotype_posterior)
-                        },
-                        num_alleles,
-                    );
-
+            match gl_calc.as_mut() {
+                Some(gl_calc) => {
+                    for genotype in (0..gl_calc.genotype_count as usize).into_iter() {
+                        let log10_genotype_posterior = log10_genotype_posteriors[genotype];
+                        let log10_absent_posteriors = &log10_absent_posteriors;
+                        gl_calc
+                            .genotype_allele_counts_at(genotype)
+                            .for_each_absent_allele_index(
+                                |a| {
+                                    let mut log10_absent_posteriors =
+                                        log10_absent_posteriors.borrow_mut();
+                                    log10_absent_posteriors[a].push(log10_genotype_posterior)
+                                },
+                                num_alleles,
+                            );
+                    }
+                }
+                None => {
+                    // a haploid genotype is missing every allele but its own
+                    let mut log10_absent_posteriors = log10_absent_posteriors.borrow_mut();
+                    for (genotype, log10_genotype_posterior) in
+                        log10_genotype_posteriors.iter().enumerate()
+                    {
+                        for a in (0..num_alleles).filter(|a| *a != genotype) {
+                            log10_absent_posteriors[a].push(*log10_genotype_posterior);
+                        }
+                    }
+                }
             }
 
             let log10_absent_posteriors = log10_absent_posteriors.borrow_mut();
@@ -419,6 +469,20 @@ impl AlleleFrequencyCalculator {
             if !g.genotype_usable_for_af_calculation() {
                 continue;
             }
+            if g.get_ploidy() == 1 && g.has_likelihoods() {
+                let log10_genotype_posteriors =
+                    Self::log10_normalized_haploid_posteriors(g, log10_allele_frequencies);
+                // each haploid genotype holds a single copy of its allele
+                let mut log10_result = log10_result.borrow_mut();
+                for (allele_index, log10_posterior) in log10_genotype_posteriors.iter().enumerate() {
+                    log10_result[allele_index] = MathUtils::log10_sum_log10_two_values(
+                        log10_result[allele_index],
+                        *log10_posterior,
+                    );
+                }
+                continue;
+            }
+
             let mut gl_calc =
                 GenotypeLikelihoodCalculators::get_instance(g.get_ploidy(), num_alleles);
 
@@ -306,7 +306,7 @@ impl<A: Allele> AlleleLikelihoods<A> {
     //     let sample_count = samples.len();
     // }
 
-    fn get_informative_threshold(&self) -> f64 {
+    pub(crate) fn get_informative_threshold(&self) -> f64 {
         if self.is_natural_log {
             return *NATURAL_LOG_INFORMATIVE_THRESHOLD;
         } else {
@@ -366,6 +366,26 @@ impl<A: Allele> AlleleLikelihoods<A> {
         &mut self.values_by_sample_index[sample_index]
     }
 
+    /// Multiplies the likelihoods of each unit of evidence by the weight returned for it, so that
+    /// evidence with a weight below 1 contributes less to the genotype likelihoods
+    pub fn apply_evidence_weights<F: Fn(&BirdToolRead) -> f64>(&mut self, weight: F) {
+        for sample_index in 0..self.samples.len() {
+            let sample_evidence = match self.evidence_by_sample_index.get(&sample_index) {
+                Some(sample_evidence) => sample_evidence,
+                None => continue,
+            };
+            let sample_values = &mut self.values_by_sample_index[sample_index];
+            for (evidence_index, evidence) in sample_evidence.iter().enumerate() {
+                let evidence_weight = weight(evidence);
+                if evidence_weight != 1.0 {
+                    sample_values
+                        .column_mut(evidence_index)
+                        .mapv_inplace(|value| value * evidence_weight);
+                }
+            }
+        }
+    }
+
     /**
      * Adjusts likelihoods so that for each unit of evidence, the best allele likelihood is 0 and caps the minimum likelihood
      * of any allele for each unit of evidence based on the maximum alternative allele likelihood.
@@ -1083,8 +1103,7 @@ impl<A: Allele> AlleleLikelihoods<A> {
         let evidence_count = std::cmp::min(
             self.evidence_by_sample_index
                 .get(&sample_index)
-                .unwrap()
-                .len(),
+                .map_or(0, |evidence| evidence.len()),
             self.values_by_sample_index[sample_index].ncols(),
         );
 
@@ -3,7 +3,7 @@ use std::hash::{Hash, Hasher};
 use crate::model::variants;
 use crate::utils::vcf_constants::VCFConstants;
 
-#[derive(Debug, Clone, Ord, PartialOrd)]
+#[derive(Debug, Clone, Ord, PartialOrd, Serialize, Deserialize)]
 pub struct ByteArrayAllele {
     pub(crate) is_ref: bool,
     pub(crate) is_no_call: bool,
@@ -85,6 +85,12 @@ impl ByteArrayAllele {
         self.bases.as_slice() == b"*"
     }
 
+    /// The bases of this allele as they are written in a VCF record. Unlike `get_bases`, symbolic
+    /// alleles such as `<DEL>` and breakends keep their full representation
+    pub fn get_display_bases(&self) -> &[u8] {
+        self.bases.as_slice()
+    }
+
     pub fn len(&self) -> usize {
         return if self.is_symbolic {
             0
@@ -0,0 +1,229 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::reference::reference_mask::ReferenceMask;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
+
+/// The positions of a window of a contig passing --depth-per-sample-filter in each sample, stored
+/// as run lengths: positive runs of passing positions and negative runs of failing positions
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallableWindow {
+    pub tid: usize,
+    // 0-based position of the first base of the runs
+    pub start: usize,
+    pub runs: Vec<Vec<i32>>,
+}
+
+impl CallableWindow {
+    pub fn new(tid: usize, start: usize, runs: Vec<Vec<i32>>) -> Self {
+        Self { tid, start, runs }
+    }
+
+    /// The number of positions covered by the runs
+    pub fn len(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|sample_runs| {
+                sample_runs
+                    .iter()
+                    .map(|run| run.unsigned_abs() as usize)
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A stretch of a contig callable in the same set of samples
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallableInterval {
+    pub tid: usize,
+    // 0-based, half open
+    pub start: usize,
+    pub end: usize,
+    pub samples: Vec<usize>,
+}
+
+/// The positions whose depth reaches --depth-per-sample-filter in each sample, recorded one window
+/// at a time and written by --callable-sites as a BED file. Masked positions are never callable,
+/// and every clone shares the same windows.
+#[derive(Debug, Clone, Default)]
+pub struct CallableSites {
+    windows: Arc<Mutex<Vec<CallableWindow>>>,
+    mask: Arc<Mutex<Option<ReferenceMask>>>,
+}
+
+impl CallableSites {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the callable sites should be written, i.e. whether --callable-sites was given
+    pub fn requested(args: &clap::ArgMatches) -> bool {
+        args.try_get_one::<bool>("callable-sites")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn min_samples_from_args(args: &clap::ArgMatches) -> usize {
+        args.try_get_one::<usize>("callable-sites-min-samples")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(1)
+    }
+
+    pub fn record(&self, window: CallableWindow) {
+        self.windows.lock().unwrap().push(window);
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The recorded windows sorted by position
+    pub fn windows(&self) -> Vec<CallableWindow> {
+        let mut windows = self.windows.lock().unwrap().clone();
+        windows.sort_by_key(|window| (window.tid, window.start));
+        windows
+    }
+
+    /// Excludes the masked positions from the callable sites
+    pub fn apply_mask(&self, reference_mask: &ReferenceMask) {
+        *self.mask.lock().unwrap() = Some(reference_mask.clone());
+    }
+
+    /// The stretches of the genome callable in the same set of at least `min_samples` samples.
+    /// Adjacent stretches callable in the same samples are merged across windows
+    pub fn intervals(&self, min_samples: usize) -> Vec<CallableInterval> {
+        let min_samples = min_samples.max(1);
+        let mask = self.mask.lock().unwrap().clone();
+        let mut intervals: Vec<CallableInterval> = Vec::new();
+        let mut current: Option<CallableInterval> = None;
+        let mut samples = Vec::new();
+
+        for window in self.windows() {
+            // (index of the current run, positions of it left) of each sample
+            let mut cursors = vec![(0, 0); window.runs.len()];
+            for offset in 0..window.len() {
+                let position = window.start + offset;
+                samples.clear();
+                for (sample_idx, sample_runs) in window.runs.iter().enumerate() {
+                    let (run_idx, remaining) = &mut cursors[sample_idx];
+                    while *remaining == 0 && *run_idx < sample_runs.len() {
+                        *remaining = sample_runs[*run_idx].unsigned_abs() as usize;
+                        *run_idx += 1;
+                    }
+                    if *remaining == 0 {
+                        continue;
+                    }
+                    *remaining -= 1;
+                    if sample_runs[*run_idx - 1] > 0 {
+                        samples.push(sample_idx);
+                    }
+                }
+                if let Some(mask) = &mask {
+                    if mask.is_masked(window.tid, position) {
+                        samples.clear();
+                    }
+                }
+
+                match &mut current {
+                    Some(interval)
+                        if interval.tid == window.tid
+                            && interval.end == position
+                            && interval.samples == samples =>
+                    {
+                        interval.end += 1;
+                    }
+                    _ => {
+                        if let Some(interval) = current.take() {
+                            intervals.push(interval);
+                        }
+                        current = Some(CallableInterval {
+                            tid: window.tid,
+                            start: position,
+                            end: position + 1,
+                            samples: samples.clone(),
+                        });
+                    }
+                }
+            }
+        }
+        if let Some(interval) = current.take() {
+            intervals.push(interval);
+        }
+
+        intervals.retain(|interval| interval.samples.len() >= min_samples);
+        intervals
+    }
+
+    /// The path of the callable sites file of a reference
+    pub fn file_path(output_prefix: &str, reference_name: &str) -> String {
+        format!("{}/{}_callable_sites.bed", output_prefix, reference_name)
+    }
+
+    /// Writes the stretches callable in at least `min_samples` samples to
+    /// {output_prefix}/{reference_name}_callable_sites.bed. Returns the number of callable bases
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        reference_reader: &ReferenceReader,
+        sample_names: &[&str],
+        min_samples: usize,
+    ) -> Result<u64, BirdToolError> {
+        let file_name = Self::file_path(output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        writeln!(writer, "#contig\tstart\tend\tcallable_samples\tsamples").map_err(write_error)?;
+        let mut callable_bases = 0;
+        for interval in self.intervals(min_samples) {
+            let contig = reference_reader
+                .retrieve_contig_name_from_tid(interval.tid)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .unwrap_or_else(|| interval.tid.to_string());
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                contig,
+                interval.start,
+                interval.end,
+                interval.samples.len(),
+                interval
+                    .samples
+                    .iter()
+                    .map(|sample_idx| sample_names
+                        .get(*sample_idx)
+                        .map(|name| name.to_string())
+                        .unwrap_or_else(|| sample_idx.to_string()))
+                    .collect::<Vec<String>>()
+                    .join(",")
+            )
+            .map_err(write_error)?;
+            callable_bases += (interval.end - interval.start) as u64;
+        }
+
+        writer.flush().map_err(write_error)?;
+        Ok(callable_bases)
+    }
+}
@@ -0,0 +1,126 @@
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+/// The read depth of each sample over a window of a contig, stored as runs of (depth, length)
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthWindow {
+    pub tid: usize,
+    // 0-based position of the first base of the runs
+    pub start: usize,
+    pub runs: Vec<Vec<(i32, u32)>>,
+}
+
+impl DepthWindow {
+    pub fn new(tid: usize, start: usize, n_samples: usize) -> Self {
+        Self {
+            tid,
+            start,
+            runs: vec![Vec::new(); n_samples],
+        }
+    }
+
+    /// Adds the depth of a sample at the next position of the window
+    pub fn push(&mut self, sample_index: usize, depth: i32) {
+        let sample_runs = &mut self.runs[sample_index];
+        match sample_runs.last_mut() {
+            Some((run_depth, length)) if *run_depth == depth => *length += 1,
+            _ => sample_runs.push((depth, 1)),
+        }
+    }
+
+    /// The number of positions covered by the runs
+    pub fn len(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|sample_runs| {
+                sample_runs
+                    .iter()
+                    .map(|(_, length)| *length as usize)
+                    .sum::<usize>()
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The depth at each position of the window summed across the given samples
+    pub fn summed_depths(&self, sample_indices: &[usize]) -> Vec<i32> {
+        let mut depths = vec![0; self.len()];
+        for sample_index in sample_indices {
+            let mut position = 0;
+            for (depth, length) in self.runs[*sample_index].iter() {
+                for summed in depths[position..position + *length as usize].iter_mut() {
+                    *summed += *depth;
+                }
+                position += *length as usize;
+            }
+        }
+        depths
+    }
+}
+
+/// The read depth of every window profiled for activity, recorded when consensus genomes mask
+/// uncertain positions so that positions without a variant can be masked for low depth too.
+/// Every clone shares the same windows, so the chunks profiled in parallel can each record their
+/// own window.
+#[derive(Debug, Clone, Default)]
+pub struct ConsensusDepths {
+    windows: Arc<Mutex<Vec<DepthWindow>>>,
+}
+
+impl ConsensusDepths {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, window: DepthWindow) {
+        self.windows.lock().unwrap().push(window);
+    }
+
+    /// The half open stretches of a contig where the depth summed across the given samples is
+    /// below `min_depth`. Positions without a recorded depth have no reads, so they are included
+    pub fn low_depth_ranges(
+        &self,
+        tid: usize,
+        contig_length: usize,
+        sample_indices: &[usize],
+        min_depth: i32,
+    ) -> Vec<Range<usize>> {
+        let mut windows = self
+            .windows
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|window| window.tid == tid)
+            .cloned()
+            .collect::<Vec<DepthWindow>>();
+        windows.sort_by_key(|window| window.start);
+
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut add_position = |position: usize| match ranges.last_mut() {
+            Some(range) if range.end == position => range.end += 1,
+            _ => ranges.push(position..position + 1),
+        };
+        let mut next_position = 0;
+        for window in windows.iter() {
+            for position in next_position..window.start.min(contig_length) {
+                add_position(position);
+            }
+            for (offset, depth) in window.summed_depths(sample_indices).into_iter().enumerate() {
+                let position = window.start + offset;
+                if position >= next_position && position < contig_length && depth < min_depth {
+                    add_position(position);
+                }
+            }
+            next_position = next_position.max(window.start + window.len());
+        }
+        for position in next_position..contig_length {
+            add_position(position);
+        }
+
+        ranges
+    }
+}
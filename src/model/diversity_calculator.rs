@@ -0,0 +1,339 @@
+use ndarray::Array2;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::accessible_genome::{AccessibleGenome, AccessibleWindow};
+use crate::model::variant_context::VariantContext;
+use crate::model::variant_context_utils::VariantContextUtils;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::errors::BirdToolError;
+
+/// The nucleotide diversity of a sample over its accessible bases
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleDiversity {
+    pub sample: usize,
+    pub accessible_bases: f64,
+    pub segregating_sites: usize,
+    pub pi: f64,
+}
+
+/// The divergence and Hudson Fst of a pair of samples over the bases accessible in both
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairwiseDivergence {
+    pub sample_1: usize,
+    pub sample_2: usize,
+    pub accessible_bases: f64,
+    pub dxy: f64,
+    pub fst: f64,
+}
+
+/// Nucleotide diversity, dxy and Hudson's Fst, divided by the bases passing the depth filters in
+/// each sample or pair of samples rather than by the genome size.
+pub struct DiversityCalculator {
+    n_samples: usize,
+    depth_per_sample_filter: i64,
+    windows: Vec<AccessibleWindow>,
+    compared_bases: Array2<f32>,
+    // summed diversity and segregating sites of each window and sample
+    window_pi: Vec<Vec<f64>>,
+    window_sites: Vec<Vec<usize>>,
+    pi: Vec<f64>,
+    segregating_sites: Vec<usize>,
+    // summed mean within sample diversity and divergence of each pair of samples
+    pair_pi: Array2<f64>,
+    dxy: Array2<f64>,
+}
+
+impl DiversityCalculator {
+    pub fn new(
+        n_samples: usize,
+        depth_per_sample_filter: i64,
+        accessible_genome: &AccessibleGenome,
+        compared_bases: Array2<f32>,
+    ) -> Self {
+        let windows = accessible_genome.windows();
+        let n_windows = windows.len();
+        Self {
+            n_samples,
+            depth_per_sample_filter,
+            windows,
+            compared_bases,
+            window_pi: vec![vec![0.0; n_samples]; n_windows],
+            window_sites: vec![vec![0; n_samples]; n_windows],
+            pi: vec![0.0; n_samples],
+            segregating_sites: vec![0; n_samples],
+            pair_pi: Array2::zeros((n_samples, n_samples)),
+            dxy: Array2::zeros((n_samples, n_samples)),
+        }
+    }
+
+    /// Probability that two reads drawn without replacement carry different alleles
+    pub fn within_sample_diversity(allele_depths: &[i32]) -> f64 {
+        let depth = allele_depths.iter().map(|ad| (*ad).max(0)).sum::<i32>() as f64;
+        if depth < 2.0 {
+            return 0.0;
+        }
+        let homozygosity = allele_depths
+            .iter()
+            .map(|ad| {
+                let frequency = (*ad).max(0) as f64 / depth;
+                frequency * frequency
+            })
+            .sum::<f64>();
+        depth / (depth - 1.0) * (1.0 - homozygosity)
+    }
+
+    /// Probability that a read from each sample carries different alleles
+    pub fn between_sample_divergence(allele_depths_1: &[i32], allele_depths_2: &[i32]) -> f64 {
+        let depth_1 = allele_depths_1.iter().map(|ad| (*ad).max(0)).sum::<i32>() as f64;
+        let depth_2 = allele_depths_2.iter().map(|ad| (*ad).max(0)).sum::<i32>() as f64;
+        if depth_1 <= 0.0 || depth_2 <= 0.0 {
+            return 0.0;
+        }
+        let shared = allele_depths_1
+            .iter()
+            .zip(allele_depths_2.iter())
+            .map(|(ad_1, ad_2)| {
+                ((*ad_1).max(0) as f64 / depth_1) * ((*ad_2).max(0) as f64 / depth_2)
+            })
+            .sum::<f64>();
+        1.0 - shared
+    }
+
+    /// Adds the allele depths of each sample at a site within the window at `window`
+    pub fn add_site(&mut self, window: Option<usize>, allele_depths: &[Vec<i32>]) {
+        let passing = allele_depths
+            .iter()
+            .map(|ads| {
+                ads.iter().map(|ad| (*ad).max(0) as i64).sum::<i64>()
+                    >= self.depth_per_sample_filter.max(1)
+            })
+            .collect::<Vec<bool>>();
+        let within = allele_depths
+            .iter()
+            .map(|ads| Self::within_sample_diversity(ads))
+            .collect::<Vec<f64>>();
+
+        for sample_1 in 0..self.n_samples.min(allele_depths.len()) {
+            if !passing[sample_1] {
+                continue;
+            }
+            self.pi[sample_1] += within[sample_1];
+            if within[sample_1] > 0.0 {
+                self.segregating_sites[sample_1] += 1;
+            }
+            if let Some(window) = window {
+                self.window_pi[window][sample_1] += within[sample_1];
+                if within[sample_1] > 0.0 {
+                    self.window_sites[window][sample_1] += 1;
+                }
+            }
+
+            for sample_2 in (sample_1 + 1)..self.n_samples.min(allele_depths.len()) {
+                if !passing[sample_2] {
+                    continue;
+                }
+                let pair_pi = (within[sample_1] + within[sample_2]) / 2.0;
+                let dxy = Self::between_sample_divergence(
+                    &allele_depths[sample_1],
+                    &allele_depths[sample_2],
+                );
+                self.pair_pi[[sample_1, sample_2]] += pair_pi;
+                self.pair_pi[[sample_2, sample_1]] += pair_pi;
+                self.dxy[[sample_1, sample_2]] += dxy;
+                self.dxy[[sample_2, sample_1]] += dxy;
+            }
+        }
+    }
+
+    /// Adds every variant passing the QD and QUAL thresholds
+    pub fn calculate(
+        &mut self,
+        contexts: &mut [VariantContext],
+        qual_by_depth_filter: f64,
+        qual_threshold: f64,
+    ) {
+        for context in contexts.iter_mut() {
+            if context.is_filtered()
+                || !VariantContextUtils::passes_thresholds(
+                    context,
+                    qual_by_depth_filter,
+                    qual_threshold,
+                )
+            {
+                continue;
+            }
+            let allele_depths = context
+                .genotypes
+                .genotypes()
+                .iter()
+                .map(|genotype| genotype.ad.clone())
+                .collect::<Vec<Vec<i32>>>();
+            let window =
+                AccessibleGenome::window_index(&self.windows, context.loc.tid, context.loc.start);
+            self.add_site(window, &allele_depths);
+        }
+    }
+
+    pub fn sample_diversity(&self) -> Vec<SampleDiversity> {
+        (0..self.n_samples)
+            .map(|sample| {
+                let accessible_bases = self.compared_bases[[sample, sample]].max(0.0) as f64;
+                SampleDiversity {
+                    sample,
+                    accessible_bases,
+                    segregating_sites: self.segregating_sites[sample],
+                    pi: Self::per_base(self.pi[sample], accessible_bases),
+                }
+            })
+            .collect()
+    }
+
+    /// The diversity of each sample within each window, in window order
+    pub fn window_diversity(&self) -> Vec<(&AccessibleWindow, Vec<SampleDiversity>)> {
+        self.windows
+            .iter()
+            .enumerate()
+            .map(|(window_idx, window)| {
+                let diversity = (0..self.n_samples)
+                    .map(|sample| {
+                        let accessible_bases = window.accessible_bases(sample, sample);
+                        SampleDiversity {
+                            sample,
+                            accessible_bases,
+                            segregating_sites: self.window_sites[window_idx][sample],
+                            pi: Self::per_base(
+                                self.window_pi[window_idx][sample],
+                                accessible_bases,
+                            ),
+                        }
+                    })
+                    .collect::<Vec<SampleDiversity>>();
+                (window, diversity)
+            })
+            .collect()
+    }
+
+    pub fn pairwise_divergence(&self) -> Vec<PairwiseDivergence> {
+        let mut pairs = Vec::new();
+        for sample_1 in 0..self.n_samples {
+            for sample_2 in (sample_1 + 1)..self.n_samples {
+                let accessible_bases = self.compared_bases[[sample_1, sample_2]].max(0.0) as f64;
+                let dxy = self.dxy[[sample_1, sample_2]];
+                let fst = if dxy > 0.0 {
+                    (1.0 - self.pair_pi[[sample_1, sample_2]] / dxy).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                pairs.push(PairwiseDivergence {
+                    sample_1,
+                    sample_2,
+                    accessible_bases,
+                    dxy: Self::per_base(dxy, accessible_bases),
+                    fst,
+                });
+            }
+        }
+        pairs
+    }
+
+    fn per_base(value: f64, accessible_bases: f64) -> f64 {
+        if accessible_bases > 0.0 {
+            value / accessible_bases
+        } else {
+            0.0
+        }
+    }
+
+    /// Writes {reference_name}_nucleotide_diversity.tsv, {reference_name}_window_diversity.tsv
+    /// and {reference_name}_pairwise_fst.tsv to the output prefix
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+        reference_reader: &ReferenceReader,
+    ) -> Result<(), BirdToolError> {
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        let sample_name = |sample: usize| {
+            sample_names
+                .get(sample)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| (sample + 1).to_string())
+        };
+
+        let mut writer = Self::create(output_prefix, reference_name, "nucleotide_diversity")?;
+        writeln!(writer, "sample\taccessible_bases\tsegregating_sites\tpi").map_err(write_error)?;
+        for diversity in self.sample_diversity() {
+            writeln!(
+                writer,
+                "{}\t{:.0}\t{}\t{:.8}",
+                sample_name(diversity.sample),
+                diversity.accessible_bases,
+                diversity.segregating_sites,
+                diversity.pi
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)?;
+
+        let mut writer = Self::create(output_prefix, reference_name, "window_diversity")?;
+        writeln!(
+            writer,
+            "contig\tstart\tend\tsample\taccessible_bases\tsegregating_sites\tpi"
+        )
+        .map_err(write_error)?;
+        for (window, window_diversity) in self.window_diversity() {
+            let contig = reference_reader
+                .retrieve_contig_name_from_tid(window.tid)
+                .map(|name| String::from_utf8_lossy(name).to_string())
+                .unwrap_or_else(|| window.tid.to_string());
+            for diversity in window_diversity {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{:.0}\t{}\t{:.8}",
+                    contig,
+                    window.start + 1,
+                    window.end + 1,
+                    sample_name(diversity.sample),
+                    diversity.accessible_bases,
+                    diversity.segregating_sites,
+                    diversity.pi
+                )
+                .map_err(write_error)?;
+            }
+        }
+        writer.flush().map_err(write_error)?;
+
+        let mut writer = Self::create(output_prefix, reference_name, "pairwise_fst")?;
+        writeln!(writer, "sample_1\tsample_2\taccessible_bases\tdxy\tfst").map_err(write_error)?;
+        for pair in self.pairwise_divergence() {
+            writeln!(
+                writer,
+                "{}\t{}\t{:.0}\t{:.8}\t{:.6}",
+                sample_name(pair.sample_1),
+                sample_name(pair.sample_2),
+                pair.accessible_bases,
+                pair.dxy,
+                pair.fst
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+
+    fn create(
+        output_prefix: &str,
+        reference_name: &str,
+        suffix: &str,
+    ) -> Result<BufWriter<File>, BirdToolError> {
+        let file_name = format!("{}/{}_{}.tsv", output_prefix, reference_name, suffix);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        Ok(BufWriter::new(file))
+    }
+}
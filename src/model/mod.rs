@@ -1,3 +1,4 @@
+pub mod accessible_genome;
 pub mod allele_frequency_calculator;
 pub mod allele_frequency_calculator_result;
 pub mod allele_likelihood_matrix_mapper;
@@ -5,9 +6,17 @@ pub mod allele_likelihoods;
 pub mod allele_list;
 pub mod allele_subsetting_utils;
 pub mod byte_array_allele;
+pub mod callable_sites;
+pub mod consensus_depths;
+pub mod diversity_calculator;
 pub mod location_and_alleles;
+pub mod site_frequency_spectrum;
 pub mod variant_context;
+pub mod variant_context_json;
 pub mod variant_context_utils;
+pub mod variant_normalizer;
+pub mod variant_sorter;
+pub mod variant_store;
 pub mod variants;
 
 #[cfg(feature = "fst")]
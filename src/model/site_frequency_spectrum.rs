@@ -0,0 +1,223 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::model::byte_array_allele::Allele;
+use crate::model::variant_context::VariantContext;
+use crate::model::variant_context_utils::VariantContextUtils;
+use crate::utils::errors::BirdToolError;
+
+/// Population genetic summary statistics of a site frequency spectrum. The theta estimators are
+/// totals over the genome rather than per base
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumSummary {
+    pub segregating_sites: usize,
+    pub singletons: usize,
+    pub watterson_theta: f64,
+    pub pi: f64,
+    pub theta_h: f64,
+    pub tajimas_d: f64,
+    pub fay_and_wus_h: f64,
+}
+
+/// Folded and unfolded site frequency spectrum of the biallelic sites where every sample has a
+/// complete genotype, along with Watterson's theta, Tajima's pi and D, and Fay and Wu's H.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteFrequencySpectrum {
+    n_chromosomes: usize,
+    unfolded: Vec<usize>,
+    skipped_sites: usize,
+}
+
+impl SiteFrequencySpectrum {
+    pub fn new(n_chromosomes: usize) -> Self {
+        Self {
+            n_chromosomes,
+            unfolded: vec![0; n_chromosomes + 1],
+            skipped_sites: 0,
+        }
+    }
+
+    pub fn n_chromosomes(&self) -> usize {
+        self.n_chromosomes
+    }
+
+    /// The number of sites at which 0 to n chromosomes carry the alternate allele
+    pub fn unfolded(&self) -> &[usize] {
+        &self.unfolded
+    }
+
+    /// The number of sites at which 0 to n / 2 chromosomes carry the minor allele
+    pub fn folded(&self) -> Vec<usize> {
+        let n = self.n_chromosomes;
+        (0..=n / 2)
+            .map(|count| {
+                if 2 * count == n {
+                    self.unfolded[count]
+                } else {
+                    self.unfolded[count] + self.unfolded[n - count]
+                }
+            })
+            .collect()
+    }
+
+    pub fn skipped_sites(&self) -> usize {
+        self.skipped_sites
+    }
+
+    pub fn counted_sites(&self) -> usize {
+        self.unfolded.iter().sum()
+    }
+
+    /// Counts a site at which `alternate_count` of the chromosomes carry the alternate allele
+    pub fn add_site(&mut self, alternate_count: usize) {
+        match self.unfolded.get_mut(alternate_count) {
+            Some(sites) => *sites += 1,
+            None => self.skipped_sites += 1,
+        }
+    }
+
+    /// The number of alternate alleles and of called chromosomes of a biallelic site. None if the
+    /// site has more than one alternate allele or any sample is missing a genotype call
+    pub fn alternate_allele_count(context: &VariantContext) -> Option<(usize, usize)> {
+        if context.alleles.len() != 2 {
+            return None;
+        }
+        let mut alternate_count = 0;
+        let mut chromosomes = 0;
+        for genotype in context.genotypes.genotypes() {
+            if genotype.alleles.is_empty() || genotype.alleles.len() != genotype.ploidy {
+                return None;
+            }
+            for allele in genotype.alleles.iter() {
+                if allele.is_no_call() {
+                    return None;
+                }
+                if !allele.is_reference() {
+                    alternate_count += 1;
+                }
+            }
+            chromosomes += genotype.alleles.len();
+        }
+        Some((alternate_count, chromosomes))
+    }
+
+    /// Adds every unfiltered site passing the quality thresholds
+    pub fn calculate(
+        &mut self,
+        contexts: &mut [VariantContext],
+        qual_by_depth_filter: f64,
+        qual_threshold: f64,
+    ) {
+        for context in contexts.iter_mut() {
+            if context.is_filtered()
+                || !VariantContextUtils::passes_thresholds(
+                    context,
+                    qual_by_depth_filter,
+                    qual_threshold,
+                )
+            {
+                continue;
+            }
+            match Self::alternate_allele_count(context) {
+                Some((alternate_count, chromosomes)) if chromosomes == self.n_chromosomes => {
+                    self.add_site(alternate_count)
+                }
+                _ => self.skipped_sites += 1,
+            }
+        }
+    }
+
+    pub fn summary(&self) -> SpectrumSummary {
+        let n = self.n_chromosomes;
+        let segregating: &[usize] = if n > 1 { &self.unfolded[1..n] } else { &[] };
+        let segregating_sites = segregating.iter().sum::<usize>();
+        let singletons = if n > 1 { self.folded()[1] } else { 0 };
+
+        let a1 = (1..n).map(|i| 1.0 / i as f64).sum::<f64>();
+        let a2 = (1..n).map(|i| 1.0 / (i * i) as f64).sum::<f64>();
+        let pairs = (n * n.saturating_sub(1)) as f64 / 2.0;
+        let weighted = |weight: &dyn Fn(usize) -> f64| {
+            segregating
+                .iter()
+                .enumerate()
+                .map(|(idx, sites)| weight(idx + 1) * *sites as f64)
+                .sum::<f64>()
+                / pairs
+        };
+
+        let s = segregating_sites as f64;
+        let watterson_theta = if a1 > 0.0 { s / a1 } else { f64::NAN };
+        let pi = weighted(&|i| (i * (n - i)) as f64);
+        let theta_h = weighted(&|i| (i * i) as f64);
+
+        let tajimas_d = if n >= 3 && segregating_sites > 0 {
+            let n = n as f64;
+            let b1 = (n + 1.0) / (3.0 * (n - 1.0));
+            let b2 = 2.0 * (n * n + n + 3.0) / (9.0 * n * (n - 1.0));
+            let c1 = b1 - 1.0 / a1;
+            let c2 = b2 - (n + 2.0) / (a1 * n) + a2 / (a1 * a1);
+            let e1 = c1 / a1;
+            let e2 = c2 / (a1 * a1 + a2);
+            (pi - watterson_theta) / (e1 * s + e2 * s * (s - 1.0)).sqrt()
+        } else {
+            f64::NAN
+        };
+
+        SpectrumSummary {
+            segregating_sites,
+            singletons,
+            watterson_theta,
+            pi,
+            theta_h,
+            tajimas_d,
+            fay_and_wus_h: pi - theta_h,
+        }
+    }
+
+    /// Writes the spectra to `<reference>_site_frequency_spectrum.tsv` and their summary
+    /// statistics to `<reference>_sfs_summary.tsv`
+    pub fn write(&self, output_prefix: &str, reference_name: &str) -> Result<(), BirdToolError> {
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+
+        let mut writer = Self::create(output_prefix, reference_name, "site_frequency_spectrum")?;
+        writeln!(writer, "spectrum\tallele_count\tsites").map_err(write_error)?;
+        for (count, sites) in self.unfolded.iter().enumerate() {
+            writeln!(writer, "unfolded\t{}\t{}", count, sites).map_err(write_error)?;
+        }
+        for (count, sites) in self.folded().iter().enumerate() {
+            writeln!(writer, "folded\t{}\t{}", count, sites).map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)?;
+
+        let summary = self.summary();
+        let mut writer = Self::create(output_prefix, reference_name, "sfs_summary")?;
+        writeln!(writer, "statistic\tvalue").map_err(write_error)?;
+        writeln!(writer, "chromosomes\t{}", self.n_chromosomes).map_err(write_error)?;
+        writeln!(writer, "counted_sites\t{}", self.counted_sites()).map_err(write_error)?;
+        writeln!(writer, "skipped_sites\t{}", self.skipped_sites).map_err(write_error)?;
+        writeln!(writer, "segregating_sites\t{}", summary.segregating_sites)
+            .map_err(write_error)?;
+        writeln!(writer, "singletons\t{}", summary.singletons).map_err(write_error)?;
+        writeln!(writer, "watterson_theta\t{:.6}", summary.watterson_theta).map_err(write_error)?;
+        writeln!(writer, "pi\t{:.6}", summary.pi).map_err(write_error)?;
+        writeln!(writer, "theta_h\t{:.6}", summary.theta_h).map_err(write_error)?;
+        writeln!(writer, "tajimas_d\t{:.6}", summary.tajimas_d).map_err(write_error)?;
+        writeln!(writer, "fay_and_wus_h\t{:.6}", summary.fay_and_wus_h).map_err(write_error)?;
+        writer.flush().map_err(write_error)
+    }
+
+    fn create(
+        output_prefix: &str,
+        reference_name: &str,
+        suffix: &str,
+    ) -> Result<BufWriter<File>, BirdToolError> {
+        let file_name = format!("{}/{}_{}.tsv", output_prefix, reference_name, suffix);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        Ok(BufWriter::new(file))
+    }
+}
@@ -1,32 +1,33 @@
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
-use std::process::Stdio;
 use rust_htslib::bcf::header::HeaderView;
 use rust_htslib::bcf::record::{GenotypeAllele, Numeric};
-use rust_htslib::bcf::{IndexedReader, Read, Reader, Record, Writer};
+use rust_htslib::bcf::{IndexedReader, Read, Record, Writer};
 use std::cmp::{min, Ordering};
 use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 use std::ops::Range;
-use std::path::Path;
 
 use crate::genotype::genotype_builder::{
     AttributeObject, Genotype, GenotypeAssignmentMethod, GenotypesContext,
 };
+use crate::annotator::allele_fraction::AlleleFractionDenominator;
 use crate::annotator::variant_annotation::VariantAnnotations;
-use crate::external_command_checker::check_for_bcftools;
 use crate::genotype::genotype_likelihood_calculators::GenotypeLikelihoodCalculators;
 use crate::genotype::genotype_likelihoods::GenotypeLikelihoods;
 use crate::genotype::genotype_prior_calculator::GenotypePriorCalculator;
 use crate::model::byte_array_allele::{Allele, ByteArrayAllele};
 use crate::model::variants::{Filter, NON_REF_ALLELE};
+use crate::processing::structural_variant_caller::StructuralVariantCaller;
+use crate::reference::genome_separator::GenomeSeparator;
 use crate::reference::reference_reader::ReferenceReader;
 use crate::utils::math_utils::MathUtils;
 use crate::utils::simple_interval::SimpleInterval;
 use crate::utils::vcf_constants::*;
+use crate::utils::vcf_input::VcfInput;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VariantContext {
     pub loc: SimpleInterval,
     // variant alleles
@@ -34,13 +35,16 @@ pub struct VariantContext {
     // per sample likelihoods
     pub genotypes: GenotypesContext,
     pub source: String,
+    #[serde(with = "crate::model::variant_context_json::non_finite_f64")]
     pub log10_p_error: f64,
     pub filters: HashSet<Filter>,
     pub attributes: LinkedHashMap<String, AttributeObject>,
+    // cached on first use, so it is not part of the serialized representation
+    #[serde(skip)]
     pub variant_type: Option<VariantType>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum VariantType {
     NoVariation,
     Snp,
@@ -227,6 +231,26 @@ impl VariantContext {
         self.filters.insert(filter);
     }
 
+    /// Adds the `MASKED` filter to this context and marks it as unqualified so that it is
+    /// excluded from ANI, Fst and dN/dS calculations
+    pub fn mask(&mut self) {
+        self.filter_unqualified(Filter::Masked);
+    }
+
+    /// Adds a filter to this context and marks it as unqualified so that it is excluded from
+    /// ANI, Fst and dN/dS calculations
+    pub fn filter_unqualified(&mut self, filter: Filter) {
+        self.filter(filter);
+        self.set_attribute(
+            VariantAnnotations::Qualified.to_key().to_string(),
+            AttributeObject::String("false".to_string()),
+        );
+    }
+
+    pub fn is_masked(&self) -> bool {
+        self.filters.contains(&Filter::Masked)
+    }
+
     pub fn has_log10_p_error(&self) -> bool {
         (self.log10_p_error - 1.0).abs() > f64::EPSILON
     }
@@ -332,13 +356,18 @@ impl VariantContext {
                                 0,
                                 genotype_likelihoods.len(),
                             );
-                            let mut gl_calc = GenotypeLikelihoodCalculators::get_instance(
-                                ploidy,
-                                alleles_to_use.len(),
-                            );
-                            let allele_counts =
-                                gl_calc.genotype_allele_counts_at(max_likelihood_index);
-                            let final_alleles = allele_counts.as_allele_list(alleles_to_use);
+                            let final_alleles = if ploidy == 1 {
+                                // haploid genotype indices are allele indices
+                                vec![alleles_to_use[max_likelihood_index].clone()]
+                            } else {
+                                let mut gl_calc = GenotypeLikelihoodCalculators::get_instance(
+                                    ploidy,
+                                    alleles_to_use.len(),
+                                );
+                                gl_calc
+                                    .genotype_allele_counts_at(max_likelihood_index)
+                                    .as_allele_list(alleles_to_use)
+                            };
                             if final_alleles.contains(&*NON_REF_ALLELE) {
                                 gb.no_call_alleles(ploidy);
                                 gb.pl = GenotypeLikelihoods::from_log10_likelihoods(
@@ -451,27 +480,21 @@ impl VariantContext {
     /// Returns and owned representation of the consensus allele at this position,
     /// that is the allele with highest sequencing depth in the specified sample index.
     pub fn get_consensus_allele(&self, sample_index: usize) -> Option<ByteArrayAllele> {
+        self.get_consensus_allele_from_depths(&self.genotypes.genotypes()[sample_index].ad)
+    }
+
+    /// Returns the allele with the highest of the given allele depths, e.g. depths summed across
+    /// several samples, or None if no allele has any depth
+    pub fn get_consensus_allele_from_depths(&self, depths: &[i32]) -> Option<ByteArrayAllele> {
         let mut current_max_depth = std::i32::MIN;
         let mut current_consensus = None;
-        for (i, dp) in self.genotypes.genotypes()[sample_index]
-            .ad
-            .iter()
-            .enumerate()
-        {
+        for (i, dp) in depths.iter().enumerate() {
             if dp > &current_max_depth {
                 current_max_depth = *dp;
                 current_consensus = Some(self.alleles[i].clone());
             }
         }
 
-        // debug!(
-        //     "Max Depth {} All depths {:?} Consensus {:?} genotypes {:?}",
-        //     current_max_depth,
-        //     self.genotypes.genotypes()[sample_index],
-        //     &current_consensus,
-        //     self.genotypes.genotypes()
-        // );
-
         if current_max_depth == 0 {
             // no variant was found in this sample
             return None;
@@ -700,7 +723,7 @@ impl VariantContext {
     }
 
     pub fn process_vcf_from_path(vcf_path: &str, with_depth: bool) -> Vec<VariantContext> {
-        let mut vcf_reader = Reader::from_path(vcf_path);
+        let mut vcf_reader = VcfInput::open(vcf_path);
         match vcf_reader {
             Ok(ref mut reader) => {
                 let variant_contexts = reader
@@ -715,72 +738,20 @@ impl VariantContext {
 
                 return variant_contexts;
             }
-            Err(_) => {
-                // debug!("No VCF records found for {}", vcf_path);
+            Err(e) => {
+                warn!("No VCF records read: {:?}", e);
                 return Vec::new();
             }
         }
     }
 
     pub fn retrieve_indexed_vcf_file(file: &str) -> IndexedReader {
-        match IndexedReader::from_path(file) {
-            Ok(vcf_reader) => {
-                // debug!("Found readable VCF file");
-                return vcf_reader;
-            }
-            Err(_e) => {
-                // vcf file likely not indexed
-                Self::generate_vcf_index(file)
-            }
+        match VcfInput::open_indexed(file) {
+            Ok(vcf_reader) => vcf_reader,
+            Err(e) => panic!("{:?}", e),
         }
     }
 
-    pub fn get_vcf_reader<S: AsRef<str>>(vcf_path: S) -> IndexedReader {
-        match IndexedReader::from_path(format!("{}.gz", vcf_path.as_ref())) {
-            Ok(vcf_reader) => {
-                // debug!("Found readable VCF file");
-                return vcf_reader;
-            }
-            Err(_e) => {
-                // vcf file likely not indexed
-                Self::generate_vcf_index(vcf_path)
-            }
-        }
-    }
-
-    pub fn generate_vcf_index<S: AsRef<str>>(vcf_path: S) -> IndexedReader {
-        check_for_bcftools();
-        // debug!("Generating VCF index");
-        let gzip_path = format!("{}.gz", vcf_path.as_ref());
-        let cmd_string = if !Path::new(&gzip_path).exists() {
-            format!(
-                "set -e -o pipefail; \
-            bgzip {}; bcftools index {}.gz",
-                vcf_path.as_ref(),
-                vcf_path.as_ref(),
-            )
-        } else {
-            format!(
-                "set -e -o pipefail; \
-                bcftools index {}.gz",
-                vcf_path.as_ref(),
-            )
-        };
-
-        // debug!("Queuing cmd_string: {}", cmd_string);
-
-        std::process::Command::new("bash")
-            .arg("-c")
-            .arg(&cmd_string)
-            .stdout(Stdio::piped())
-            .output()
-            .expect("Unable to execute bash");
-
-        return IndexedReader::from_path(&gzip_path).expect(
-            "Unable to generate index. Please ensure you have gzipped your file using 'bgzip -c'.",
-        );
-    }
-
     pub fn from_vcf_record(record: &mut Record, with_depths: bool) -> Option<VariantContext> {
         // debug!("Found VCF record with {:?} alleles", record.allele_count());
         let variants = Self::collect_variants(record, false, false, None);
@@ -795,6 +766,7 @@ impl VariantContext {
             record.pos() as usize,
             variants,
         );
+        vc.read_structural_variant_info(record);
         if with_depths {
             let allele_depths = record.format(b"AD").integer().unwrap();
             let genotype_tags = record.format(b"GT").string().unwrap();
@@ -809,7 +781,7 @@ impl VariantContext {
             //     &allele_depths,
             //     record.format(b"AD").integer().unwrap()
             // );
-            let genotypes = allele_depths
+            let mut genotypes = allele_depths
                 .iter()
                 .map(|depths| {
                     let mut depths = depths.into_iter().map(|d| *d as i32).collect::<Vec<i32>>();
@@ -821,6 +793,14 @@ impl VariantContext {
                 })
                 .collect::<Vec<Genotype>>();
 
+            // PLs are optional, but are required if the genotypes are to be recalculated
+            if let Ok(likelihoods) = record.format(b"PL").integer() {
+                for (genotype, pls) in genotypes.iter_mut().zip(likelihoods.iter()) {
+                    // missing and vector end values are negative
+                    genotype.pl = pls.iter().filter(|pl| **pl >= 0).copied().collect();
+                }
+            }
+
             let genotype_context = GenotypesContext::new(genotypes);
             vc.genotypes = genotype_context;
 
@@ -844,6 +824,177 @@ impl VariantContext {
         Some(vc)
     }
 
+    /// Reads the END, SVLEN, and SVTYPE INFO fields of a record, extending the end of this
+    /// context to END when it is present. SVTYPE and SVLEN are normalised, so that the records
+    /// of svim, sniffles2, cuteSV and lorikeet itself share one representation
+    fn read_structural_variant_info(&mut self, record: &Record) {
+        let sv_type = match record.info(b"SVTYPE").string() {
+            Ok(Some(sv_type)) if !sv_type.is_empty() => Some(
+                StructuralVariantCaller::normalize_sv_type(&String::from_utf8_lossy(sv_type[0])),
+            ),
+            _ => None,
+        };
+
+        if let Ok(Some(end)) = record.info(b"END").integer() {
+            // END is 1-based and inclusive
+            if !end.is_empty() && !end[0].is_missing() && end[0] as usize > self.loc.start {
+                self.loc.end = end[0] as usize - 1;
+                self.attributes.insert(
+                    VariantAnnotations::End.to_key().to_string(),
+                    AttributeObject::I32(end[0]),
+                );
+            }
+        }
+
+        if let Ok(Some(sv_lengths)) = record.info(b"SVLEN").integer() {
+            let sv_lengths = sv_lengths
+                .iter()
+                .filter(|l| !l.is_missing())
+                .map(|l| match &sv_type {
+                    Some(sv_type) => StructuralVariantCaller::normalize_sv_length(sv_type, *l),
+                    None => *l,
+                })
+                .collect::<Vec<i32>>();
+            if !sv_lengths.is_empty() {
+                self.attributes.insert(
+                    VariantAnnotations::StructuralVariantLength.to_key().to_string(),
+                    AttributeObject::VecI32(sv_lengths),
+                );
+            }
+        }
+
+        if let Some(sv_type) = sv_type {
+            self.attributes.insert(
+                VariantAnnotations::StructuralVariantType.to_key().to_string(),
+                AttributeObject::String(sv_type),
+            );
+        }
+    }
+
+    /// Reads the strain and variant group IDs of a record written by lorikeet, which
+    /// `from_vcf_record` leaves out
+    pub fn read_strain_info(&mut self, record: &Record) {
+        if let Ok(Some(strain_ids)) = record
+            .info(VariantAnnotations::Strain.to_key().as_bytes())
+            .integer()
+        {
+            let strain_ids = strain_ids
+                .iter()
+                .filter(|strain| **strain >= 0)
+                .map(|strain| *strain as usize)
+                .collect::<Vec<usize>>();
+            if !strain_ids.is_empty() {
+                self.attributes.insert(
+                    VariantAnnotations::Strain.to_key().to_string(),
+                    AttributeObject::VecUnsize(strain_ids),
+                );
+            }
+        }
+
+        if let Ok(Some(group)) = record
+            .info(VariantAnnotations::VariantGroup.to_key().as_bytes())
+            .integer()
+        {
+            if !group.is_empty() && group[0] >= 0 {
+                self.attributes.insert(
+                    VariantAnnotations::VariantGroup.to_key().to_string(),
+                    AttributeObject::I32(group[0]),
+                );
+            }
+        }
+    }
+
+    /// Sets the END, SVLEN, and SVTYPE attributes of this context if it has a symbolic alternate
+    /// allele, or an indel changing the length of the reference by at least `min_indel_length`
+    /// bases. Indels are left as is when `min_indel_length` is 0. Values that were read in from
+    /// a VCF file are kept.
+    pub fn add_structural_variant_info(&mut self, min_indel_length: usize) {
+        let ref_length = self.get_reference().length();
+        let symbolic_type = self
+            .get_alternate_alleles()
+            .into_iter()
+            .find(|allele| allele.is_symbolic())
+            .map(|allele| Self::symbolic_allele_type(allele.get_display_bases()));
+
+        let (sv_type, sv_lengths) = match symbolic_type {
+            Some(sv_type) => (sv_type, None),
+            None => {
+                if min_indel_length == 0 {
+                    return;
+                }
+                let sv_lengths = self
+                    .get_alternate_alleles()
+                    .into_iter()
+                    .map(|allele| {
+                        if allele.is_span_del() {
+                            0
+                        } else {
+                            allele.length() as i32 - ref_length as i32
+                        }
+                    })
+                    .collect::<Vec<i32>>();
+                let longest = match sv_lengths.iter().max_by_key(|length| length.abs()) {
+                    Some(longest) => *longest,
+                    None => return,
+                };
+                if (longest.unsigned_abs() as usize) < min_indel_length || longest == 0 {
+                    return;
+                }
+                let sv_type = if longest < 0 { "DEL" } else { "INS" };
+                (sv_type.to_string(), Some(sv_lengths))
+            }
+        };
+
+        let end = std::cmp::max(self.loc.end, self.loc.start + ref_length.saturating_sub(1));
+        if !self.attributes.contains_key(VariantAnnotations::End.to_key()) {
+            self.attributes.insert(
+                VariantAnnotations::End.to_key().to_string(),
+                AttributeObject::I32(end as i32 + 1),
+            );
+        }
+
+        if !self
+            .attributes
+            .contains_key(VariantAnnotations::StructuralVariantLength.to_key())
+        {
+            let sv_lengths = match sv_lengths {
+                Some(sv_lengths) => Some(sv_lengths),
+                // the length of a symbolic deletion is implied by its end
+                None if sv_type == "DEL" && end > self.loc.start => {
+                    Some(vec![-((end - self.loc.start) as i32)])
+                }
+                None => None,
+            };
+            if let Some(sv_lengths) = sv_lengths {
+                self.attributes.insert(
+                    VariantAnnotations::StructuralVariantLength.to_key().to_string(),
+                    AttributeObject::VecI32(sv_lengths),
+                );
+            }
+        }
+
+        if !self
+            .attributes
+            .contains_key(VariantAnnotations::StructuralVariantType.to_key())
+        {
+            self.attributes.insert(
+                VariantAnnotations::StructuralVariantType.to_key().to_string(),
+                AttributeObject::String(sv_type),
+            );
+        }
+    }
+
+    /// The SVTYPE of a symbolic allele, e.g. DUP for <DUP:TANDEM> and BND for breakends
+    fn symbolic_allele_type(bases: &[u8]) -> String {
+        if bases.len() > 2 && bases[0] == b'<' && bases[bases.len() - 1] == b'>' {
+            let id = &bases[1..bases.len() - 1];
+            let id = id.split(|base| *base == b':').next().unwrap_or(id);
+            String::from_utf8_lossy(id).to_string()
+        } else {
+            "BND".to_string()
+        }
+    }
+
     /// Collect variants from a given ´bcf::Record`.
     pub fn collect_variants(
         record: &mut Record,
@@ -852,30 +1003,6 @@ impl VariantContext {
         _indel_len_range: Option<Range<u32>>,
     ) -> Vec<ByteArrayAllele> {
         let _pos = record.pos();
-        let _svlens = match record.info(b"SVLEN").integer() {
-            // Gets value from SVLEN tag in VCF record
-            Ok(Some(svlens)) => Some(
-                svlens
-                    .into_iter()
-                    .map(|l| {
-                        if !l.is_missing() {
-                            Some(l.abs() as u32)
-                        } else {
-                            None
-                        }
-                    })
-                    .collect_vec(),
-            ),
-            _ => None,
-        };
-        let _end = match record.info(b"END").integer() {
-            Ok(Some(end)) => {
-                let end = end[0] as u32 - 1;
-                Some(end)
-            }
-            _ => None,
-        };
-
         // check if len is within the given range
         // let is_valid_len = |svlen| {
         //     if let Some(ref len_range) = indel_len_range {
@@ -913,30 +1040,14 @@ impl VariantContext {
             let mut variant_vec = vec![];
             alleles.iter().enumerate().for_each(|(i, alt_allele)| {
                 let is_reference = i == 0;
-                // if alt_allele == b"*" {
-                //     // dummy non-ref allele, signifying potential homozygous reference site
-                //     if omit_snvs {
-                //         variant_vec.push(ByteArrayAllele::new(".".as_bytes(), is_reference))
-                //     } else {
-                //         variant_vec.push(ByteArrayAllele::new(".".as_bytes(), is_reference))
-                //     }
-                // } else
-                if alt_allele == b"<DEL>" {
-                    // if let Some(ref svlens) = svlens {
-                    //     if let Some(svlen) = svlens[i] {
-                    //         variant_vec.push(ByteArrayAllele::new("*".as_bytes(), is_reference))
-                    //     } else {
-                    //         // TODO fail with an error in this case
-                    //         variant_vec.push(ByteArrayAllele::new(".".as_bytes(), is_reference))
-                    //     }
-                    // } else {
-                    //     // TODO fail with an error in this case
-                    //     variant_vec.push(ByteArrayAllele::new(".".as_bytes(), is_reference))
-                    // }
-                } else if alt_allele[0] == b'<' {
-                    // TODO Catch <DUP> structural variants here
-                    // skip any other special alleles
-                    // variant_vec.push(ByteArrayAllele::new(".".as_bytes(), is_reference))
+                if !is_reference
+                    && (ByteArrayAllele::would_be_symbolic_allele(alt_allele)
+                        || ByteArrayAllele::would_be_star_allele(alt_allele))
+                {
+                    // Symbolic alleles, e.g. <DEL>, <DUP>, <INS>, breakends, and spanning
+                    // deletions are kept as is so the allele indices of the genotype fields
+                    // still line up with the alleles of the record
+                    variant_vec.push(ByteArrayAllele::new(alt_allele, is_reference))
                 } else if alt_allele.len() == 1 && ref_allele.len() == 1 {
                     // SNV
                     if omit_snvs {
@@ -975,9 +1086,13 @@ impl VariantContext {
     }
 
     /// Attempts to retrieve a VCF rid given a contig name
-    /// If the provided contig name contains a `~` symbol, then this function attempts to remove
+    /// If the provided contig name contains the genome separator, then this function attempts to remove
     /// and find the rid from a split in the contig name
-    pub fn get_contig_vcf_tid(vcf_header: &HeaderView, contig_name: &[u8]) -> Option<u32> {
+    pub fn get_contig_vcf_tid(
+        vcf_header: &HeaderView,
+        contig_name: &[u8],
+        separator: &GenomeSeparator,
+    ) -> Option<u32> {
         match vcf_header.name2rid(contig_name) {
             Ok(rid) => Some(rid),
             Err(_) => {
@@ -987,7 +1102,10 @@ impl VariantContext {
                 //     std::str::from_utf8(ReferenceReader::split_contig_name(contig_name, '~' as u8))
                 // );
                 match vcf_header
-                    .name2rid(ReferenceReader::split_contig_name(contig_name, '~' as u8))
+                    .name2rid(ReferenceReader::split_contig_name(
+                        contig_name,
+                        separator.as_char() as u8,
+                    ))
                 {
                     Ok(rid) => Some(rid),
                     Err(_) => match vcf_header.name2rid(contig_name) {
@@ -1163,7 +1281,7 @@ impl VariantContext {
     pub fn get_alleles_as_bytes(&self) -> Vec<&[u8]> {
         self.get_alleles()
             .into_iter()
-            .map(|a| a.get_bases())
+            .map(|a| a.get_display_bases())
             .collect::<Vec<&[u8]>>()
     }
 
@@ -1191,11 +1309,26 @@ impl VariantContext {
         bcf_writer: &mut Writer,
         reference_reader: &ReferenceReader,
         n_samples: usize,
+    ) {
+        self.write_as_vcf_record_on_contig(
+            bcf_writer,
+            reference_reader.get_target_name(self.loc.get_contig()),
+            n_samples,
+        )
+    }
+
+    /// writes this VariantContext as a VCF4 record on the named contig, ignoring the tid of
+    /// this context. Used when the contexts were not generated from a `ReferenceReader`
+    pub fn write_as_vcf_record_on_contig(
+        &self,
+        bcf_writer: &mut Writer,
+        contig_name: &[u8],
+        n_samples: usize,
     ) {
         let mut record = bcf_writer.empty_record();
         let rid = bcf_writer
             .header()
-            .name2rid(reference_reader.get_target_name(self.loc.get_contig()))
+            .name2rid(contig_name)
             .expect("Contig name not present in BCF header");
         record.set_rid(Some(rid));
         record.set_pos(self.loc.start as i64); // 0-based
@@ -1262,6 +1395,15 @@ impl VariantContext {
                 }
             }
         }
+
+        if let Some(AttributeObject::VecI32(val)) = self
+            .attributes
+            .get_mut(VariantAnnotations::StrandCounts.to_key())
+        {
+            if alt_index * 2 + 1 < val.len() {
+                *val = vec![val[0], val[1], val[alt_index * 2], val[alt_index * 2 + 1]];
+            }
+        }
     }
 
     fn add_variant_info(&self, record: &mut Record) {
@@ -1352,6 +1494,15 @@ impl VariantContext {
             }
         }
 
+        if let Some(AttributeObject::VecI32(val)) = self
+            .attributes
+            .get(VariantAnnotations::StrandCounts.to_key())
+        {
+            record
+                .push_info_integer(VariantAnnotations::StrandCounts.to_key().as_bytes(), val)
+                .expect("Cannot push info tag");
+        }
+
         if self
             .attributes
             .contains_key(VariantAnnotations::VariantGroup.to_key())
@@ -1386,6 +1537,27 @@ impl VariantContext {
             }
         }
 
+        if let Some(AttributeObject::I32(val)) = self
+            .attributes
+            .get(VariantAnnotations::LinkedReads.to_key())
+        {
+            record
+                .push_info_integer(VariantAnnotations::LinkedReads.to_key().as_bytes(), &[*val])
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::f64(val)) = self
+            .attributes
+            .get(VariantAnnotations::StrainDiscriminatingPower.to_key())
+        {
+            record
+                .push_info_float(
+                    VariantAnnotations::StrainDiscriminatingPower.to_key().as_bytes(),
+                    &[*val as f32],
+                )
+                .expect("Cannot push info tag");
+        }
+
         if self
             .attributes
             .contains_key(VariantAnnotations::Qualified.to_key())
@@ -1401,6 +1573,117 @@ impl VariantContext {
                     .expect("Cannot push info tag");
             }
         }
+
+        if let Some(AttributeObject::I32(val)) =
+            self.attributes.get(VariantAnnotations::End.to_key())
+        {
+            record
+                .push_info_integer(VariantAnnotations::End.to_key().as_bytes(), &[*val])
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::VecI32(val)) = self
+            .attributes
+            .get(VariantAnnotations::StructuralVariantLength.to_key())
+        {
+            record
+                .push_info_integer(
+                    VariantAnnotations::StructuralVariantLength.to_key().as_bytes(),
+                    val,
+                )
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::String(val)) = self
+            .attributes
+            .get(VariantAnnotations::StructuralVariantType.to_key())
+        {
+            record
+                .push_info_string(
+                    VariantAnnotations::StructuralVariantType.to_key().as_bytes(),
+                    &[val.as_bytes()],
+                )
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::I32(val)) =
+            self.attributes.get(VariantAnnotations::HomopolymerRun.to_key())
+        {
+            record
+                .push_info_integer(VariantAnnotations::HomopolymerRun.to_key().as_bytes(), &[*val])
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::String(val)) =
+            self.attributes.get(VariantAnnotations::RepeatUnit.to_key())
+        {
+            record
+                .push_info_string(
+                    VariantAnnotations::RepeatUnit.to_key().as_bytes(),
+                    &[val.as_bytes()],
+                )
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::VecI32(val)) = self
+            .attributes
+            .get(VariantAnnotations::RepeatsPerAllele.to_key())
+        {
+            record
+                .push_info_integer(VariantAnnotations::RepeatsPerAllele.to_key().as_bytes(), val)
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::f64(val)) =
+            self.attributes.get(VariantAnnotations::CopyNumberRatio.to_key())
+        {
+            record
+                .push_info_float(
+                    VariantAnnotations::CopyNumberRatio.to_key().as_bytes(),
+                    &[*val as f32],
+                )
+                .expect("Cannot push info tag");
+        }
+
+        for annotation in [VariantAnnotations::SequenceEntropy, VariantAnnotations::DustScore] {
+            if let Some(AttributeObject::f64(val)) = self.attributes.get(annotation.to_key()) {
+                record
+                    .push_info_float(annotation.to_key().as_bytes(), &[*val as f32])
+                    .expect("Cannot push info tag");
+            }
+        }
+
+        if let Some(AttributeObject::f64(val)) =
+            self.attributes.get(VariantAnnotations::OriginalQual.to_key())
+        {
+            record
+                .push_info_float(
+                    VariantAnnotations::OriginalQual.to_key().as_bytes(),
+                    &[*val as f32],
+                )
+                .expect("Cannot push info tag");
+        }
+
+        if let Some(AttributeObject::String(val)) = self
+            .attributes
+            .get(VariantAnnotations::HaplotypeSequence.to_key())
+        {
+            record
+                .push_info_string(
+                    VariantAnnotations::HaplotypeSequence.to_key().as_bytes(),
+                    &[val.as_bytes()],
+                )
+                .expect("Cannot push info tag");
+        }
+
+        if self
+            .attributes
+            .contains_key(VariantAnnotations::AssemblyFallback.to_key())
+        {
+            record
+                .push_info_flag(VariantAnnotations::AssemblyFallback.to_key().as_bytes())
+                .expect("Cannot push info tag");
+        }
     }
 
     fn add_genotype_format(&self, record: &mut Record, _n_samples: usize) {
@@ -1480,6 +1763,73 @@ impl VariantContext {
         record
             .push_format_integer(VariantAnnotations::Depth.to_key().as_bytes(), &dps)
             .expect("Unable to push format tag");
+
+        self.add_allele_fraction_format(record);
+
+        for annotation in [
+            VariantAnnotations::SampleMappingQuality,
+            VariantAnnotations::SampleBaseQuality,
+        ] {
+            self.add_per_allele_format_integer(record, annotation.to_key());
+        }
+    }
+
+    /// Pushes the fraction of the reads of each sample supporting each alternate allele. Samples
+    /// without an AF attribute, e.g. those read from a VCF file, get fractions from their allele
+    /// depths and samples without reads get missing values
+    fn add_allele_fraction_format(&self, record: &mut Record) {
+        let n_alternate = self.alleles.len().saturating_sub(1);
+        if n_alternate == 0 {
+            return;
+        }
+
+        let key = VariantAnnotations::AlleleFraction.to_key();
+        let mut values = Vec::with_capacity(n_alternate * self.genotypes.len());
+        for genotype in self.genotypes.genotypes() {
+            let fractions = match genotype.attributes.get(key) {
+                Some(AttributeObject::Vecf64(fractions)) => Some(fractions.clone()),
+                _ => AlleleFractionDenominator::Informative.allele_fractions(genotype),
+            };
+            match fractions {
+                Some(fractions) if fractions.len() == n_alternate => {
+                    values.extend(fractions.into_iter().map(|fraction| fraction as f32))
+                }
+                _ => values.extend(vec![f32::missing(); n_alternate]),
+            }
+        }
+
+        record
+            .push_format_float(key.as_bytes(), &values)
+            .expect("Unable to push format tag");
+    }
+
+    /// Pushes a FORMAT field holding one integer per allele for each sample. Samples without the
+    /// attribute get missing values, and the field is left out if no sample has it
+    fn add_per_allele_format_integer(&self, record: &mut Record, key: &str) {
+        let key_string = key.to_string();
+        if !self
+            .genotypes
+            .genotypes()
+            .iter()
+            .any(|genotype| genotype.has_attribute(&key_string))
+        {
+            return;
+        }
+
+        let n_alleles = self.alleles.len();
+        let mut values = Vec::with_capacity(n_alleles * self.genotypes.len());
+        for genotype in self.genotypes.genotypes() {
+            match genotype.get_attribute(&key_string) {
+                Some(AttributeObject::VecI32(vals)) if vals.len() == n_alleles => {
+                    values.extend(vals.iter().copied())
+                }
+                _ => values.extend(vec![i32::missing(); n_alleles]),
+            }
+        }
+
+        record
+            .push_format_integer(key.as_bytes(), &values)
+            .expect("Unable to push format tag");
     }
 
     /// Given the most likely index from a set of likelihoods i.e. for phred scaled [10, 0, 20],
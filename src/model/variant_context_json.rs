@@ -0,0 +1,157 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// Version of the JSON representation of variant contexts. Bump this whenever a change to
+/// `VariantContext`, `Genotype`, or `AttributeObject` alters their serialized form so that
+/// stale dumps are rejected rather than silently misread
+pub const VARIANT_CONTEXT_JSON_VERSION: u32 = 1;
+
+/// A set of variant contexts along with the information required to interpret them, used to
+/// dump intermediate calling results to disk and reload them in a later stage or run.
+/// Genotypes refer to samples by index, so the sample names are stored in the same order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VariantContextDump {
+    pub version: u32,
+    pub lorikeet_version: String,
+    pub samples: Vec<String>,
+    pub contexts: Vec<VariantContext>,
+}
+
+impl VariantContextDump {
+    pub fn new(samples: Vec<String>, contexts: Vec<VariantContext>) -> Self {
+        Self {
+            version: VARIANT_CONTEXT_JSON_VERSION,
+            lorikeet_version: env!("CARGO_PKG_VERSION").to_string(),
+            samples,
+            contexts,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, BirdToolError> {
+        serde_json::to_string(self).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to serialize variant contexts: {}", e))
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, BirdToolError> {
+        let dump: Self = serde_json::from_str(json).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to parse variant contexts: {}", e))
+        })?;
+        dump.check_version()
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), BirdToolError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", path.display(), e))
+        })?;
+        serde_json::to_writer(BufWriter::new(file), self).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to write variant contexts to {}: {}",
+                path.display(),
+                e
+            ))
+        })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, BirdToolError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to open {}: {}", path.display(), e))
+        })?;
+        let dump: Self = serde_json::from_reader(BufReader::new(file)).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to parse variant contexts in {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        dump.check_version()
+    }
+
+    fn check_version(self) -> Result<Self, BirdToolError> {
+        if self.version != VARIANT_CONTEXT_JSON_VERSION {
+            return Err(BirdToolError::IOError(format!(
+                "Variant context dump has version {} but version {} is required. It was written by lorikeet v{}",
+                self.version, VARIANT_CONTEXT_JSON_VERSION, self.lorikeet_version
+            )));
+        }
+        Ok(self)
+    }
+}
+
+/// (De)serializes an f64 that may be NaN or infinite, which JSON has no numbers for, e.g. the
+/// `log10_p_error` of a record without a QUAL. Non-finite values are written as the strings
+/// "NaN", "inf" and "-inf". Use with `#[serde(with = "...")]`
+pub mod non_finite_f64 {
+    use serde::de::{self, Deserializer, Visitor};
+    use serde::Serializer;
+    use std::fmt;
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&value.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        deserializer.deserialize_any(NonFiniteF64Visitor)
+    }
+
+    struct NonFiniteF64Visitor;
+
+    impl<'de> Visitor<'de> for NonFiniteF64Visitor {
+        type Value = f64;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number or one of \"NaN\", \"inf\" or \"-inf\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+            value
+                .parse::<f64>()
+                .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+        }
+
+        // serde_json writes non-finite values as null by default
+        fn visit_unit<E: de::Error>(self) -> Result<f64, E> {
+            Ok(f64::NAN)
+        }
+    }
+}
+
+/// As `non_finite_f64`, for every value of a vector
+pub mod non_finite_vec_f64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(transparent)]
+    struct NonFiniteF64(#[serde(with = "super::non_finite_f64")] f64);
+
+    pub fn serialize<S: Serializer>(values: &[f64], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(values.iter().map(|value| NonFiniteF64(*value)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f64>, D::Error> {
+        Vec::<NonFiniteF64>::deserialize(deserializer)
+            .map(|values| values.into_iter().map(|value| value.0).collect())
+    }
+}
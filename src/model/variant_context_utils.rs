@@ -1,6 +1,8 @@
 use hashlink::{LinkedHashMap, LinkedHashSet};
 use rayon::prelude::*;
+use rust_htslib::bcf::Read;
 use std::cmp::min;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::hash_map::DefaultHasher;
@@ -10,15 +12,34 @@ use std::ops::Range;
 
 use crate::annotator::variant_annotation::VariantAnnotations;
 use crate::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
+use crate::genotype::genotype_likelihood_calculators::GenotypeLikelihoodCalculators;
 use crate::model::byte_array_allele::{Allele, ByteArrayAllele};
 use crate::model::variant_context::VariantContext;
-use crate::model::variants::SPAN_DEL_ALLELE;
+use crate::model::variants::{Filter, SPAN_DEL_ALLELE};
+use crate::processing::vcf_combiner::{CombineInput, VcfCombiner};
 use crate::reads::alignment_utils::AlignmentUtils;
+use crate::utils::errors::BirdToolError;
 use crate::utils::simple_interval::{Locatable, SimpleInterval};
 use crate::utils::vcf_constants::*;
+use crate::utils::vcf_input::VcfInput;
 
 pub struct VariantContextUtils {}
 
+/// An allele of a site, as contig name, position, reference bases and alternate bases
+pub type SiteAllele = (String, usize, Vec<u8>, Vec<u8>);
+
+/// The union of the sites of several lorikeet VCF files of the same genome
+pub struct MergedVcfs {
+    pub sample_names: Vec<String>,
+    /// Contig names and lengths, indexed by the tid of the contexts
+    pub contigs: Vec<(String, Option<u64>)>,
+    pub contexts: Vec<VariantContext>,
+    /// The merged strain ID of each strain ID of each input, in the order of the inputs
+    pub strain_ids: Vec<HashMap<usize, usize>>,
+    /// The merged variant group ID of each variant group ID of each input
+    pub variant_group_ids: Vec<HashMap<usize, usize>>,
+}
+
 impl VariantContextUtils {
     /**
      *
@@ -46,7 +67,8 @@ impl VariantContextUtils {
         let mut lengths = Vec::new();
         for allele in vc.get_alternate_alleles() {
             let allele_bases = allele.get_bases();
-            if allele_bases.len() > 1 {
+            // the alternate allele of a deletion is only its padding base
+            if !allele_bases.is_empty() {
                 let result = Self::get_num_tandem_repeat_units_main(
                     ref_allele_bases,
                     &allele_bases[1..allele_bases.len()],
@@ -71,11 +93,7 @@ impl VariantContextUtils {
                 return None;
             }
         }
-        if lengths.is_empty() {
-            return Some((lengths, repeat_unit));
-        } else {
-            return None;
-        }
+        return Some((lengths, repeat_unit));
     }
 
     pub fn passes_thresholds(
@@ -83,6 +101,10 @@ impl VariantContextUtils {
         qual_by_depth_filter: f64,
         qual_threshold: f64,
     ) -> bool {
+        if context.is_masked() {
+            return false;
+        }
+
         match context.attributes.get("QF").cloned() {
             Some(attribute) => match attribute {
                 AttributeObject::String(passes) => match passes.as_str() {
@@ -203,7 +225,7 @@ impl VariantContextUtils {
      *                              be represented as one, it will be just the length of the input string)
      */
     pub fn find_repeated_substring(bases: &[u8]) -> usize {
-        for rep_length in 1..bases.len() {
+        for rep_length in 1..=bases.len() {
             let candidate_repeat_unit = &bases[0..rep_length];
             let mut all_bases_match = true;
             for start in rep_length..bases.len() {
@@ -608,10 +630,17 @@ impl VariantContextUtils {
         vcs: Vec<VariantContext>,
         min_qual_by_depth: f64,
         min_variant_depth: i32,
+        hard_filter: bool,
     ) -> (Vec<VariantContext>, Vec<VariantContext>) {
         let mut split_vcs = Vec::new();
         let mut filtered_vcs = Vec::new();
         for mut vc in vcs {
+            if vc.is_masked() {
+                // masked variants are kept for output but never genotyped
+                filtered_vcs.push(vc);
+                continue;
+            }
+
             match vc
                 .attributes
                 .get(VariantAnnotations::QualByDepth.to_key())
@@ -625,6 +654,9 @@ impl VariantContextUtils {
                             VariantAnnotations::Qualified.to_key().to_string(),
                             AttributeObject::String(format!("{}", result)),
                         );
+                        if qbd < min_qual_by_depth {
+                            vc.filter(Filter::LowQD);
+                        }
                         if qbd >= min_qual_by_depth && vc.log10_p_error <= -15.0 {
                             let n_alts = vc.get_alternate_alleles().len();
                             if n_alts == 1 {
@@ -635,6 +667,9 @@ impl VariantContextUtils {
                                 // for genotyping
                                 if depth_sum >= min_variant_depth {
                                     split_vcs.push(vc)
+                                } else if !hard_filter {
+                                    vc.filter(Filter::LowDepth);
+                                    filtered_vcs.push(vc)
                                 }
                             } else if n_alts > 1 {
                                 let ref_allele = vc.get_reference();
@@ -683,10 +718,30 @@ impl VariantContextUtils {
                                             // debug!("New genotype {:?}", &new_genotype);
                                         }
 
+                                        for annotation in [
+                                            VariantAnnotations::SampleMappingQuality,
+                                            VariantAnnotations::SampleBaseQuality,
+                                        ] {
+                                            let key = annotation.to_key().to_string();
+                                            if let Some(AttributeObject::VecI32(vals)) =
+                                                old_genotype.get_attribute(&key)
+                                            {
+                                                if alt_index + 1 < vals.len() {
+                                                    new_genotype.attribute(
+                                                        key,
+                                                        AttributeObject::VecI32(vec![
+                                                            vals[0],
+                                                            vals[alt_index + 1],
+                                                        ]),
+                                                    );
+                                                }
+                                            }
+                                        }
+
                                         per_sample_genotypes.push(new_genotype);
                                     }
 
-                                    if variant_depth >= min_variant_depth {
+                                    if variant_depth >= min_variant_depth || !hard_filter {
                                         let mut new_vc = VariantContext::build(
                                             vc.loc.tid,
                                             vc.loc.start,
@@ -704,8 +759,14 @@ impl VariantContextUtils {
                                         new_vc.genotypes =
                                             GenotypesContext::new(per_sample_genotypes);
                                         new_vc.log10_p_error = vc.log10_p_error;
+                                        new_vc.filters = vc.filters.clone();
 
-                                        new_vcs.push(new_vc);
+                                        if variant_depth >= min_variant_depth {
+                                            new_vcs.push(new_vc);
+                                        } else {
+                                            new_vc.filter(Filter::LowDepth);
+                                            filtered_vcs.push(new_vc);
+                                        }
                                     }
                                 }
 
@@ -723,6 +784,58 @@ impl VariantContextUtils {
         return (split_vcs, filtered_vcs);
     }
 
+    /// Whether any sample supports an alternate allele with at least `depth_per_sample_filter`
+    /// reads
+    pub fn alternate_allele_has_depth(vc: &VariantContext, depth_per_sample_filter: i32) -> bool {
+        vc.genotypes
+            .genotypes()
+            .iter()
+            .any(|genotype| genotype.ad.iter().skip(1).any(|ad| *ad >= depth_per_sample_filter))
+    }
+
+    /// Gives contexts with a QD below `qual_by_depth_filter` the LowQD filter and contexts where no
+    /// sample supports an alternate allele with at least `depth_per_sample_filter` reads the
+    /// LowDepth filter. These variants are already excluded from genotyping and ANI, the filters
+    /// make that visible in the VCF. With `hard_filter` the variants are removed instead.
+    /// Returns the number of filtered contexts
+    pub fn apply_quality_filters(
+        contexts: &mut Vec<VariantContext>,
+        qual_by_depth_filter: f64,
+        depth_per_sample_filter: i64,
+        hard_filter: bool,
+    ) -> usize {
+        let mut filtered = 0;
+        for vc in contexts.iter_mut() {
+            let mut passes = true;
+            if let Some(AttributeObject::f64(qd)) =
+                vc.attributes.get(VariantAnnotations::QualByDepth.to_key())
+            {
+                if *qd < qual_by_depth_filter {
+                    vc.filter(Filter::LowQD);
+                    passes = false;
+                }
+            }
+
+            if vc.genotypes.genotypes().iter().any(|genotype| !genotype.ad.is_empty())
+                && !Self::alternate_allele_has_depth(vc, depth_per_sample_filter as i32)
+            {
+                vc.filter(Filter::LowDepth);
+                passes = false;
+            }
+
+            if !passes {
+                filtered += 1;
+            }
+        }
+
+        if hard_filter {
+            contexts.retain(|vc| {
+                !vc.filters.contains(&Filter::LowQD) && !vc.filters.contains(&Filter::LowDepth)
+            });
+        }
+        filtered
+    }
+
     pub fn strip_pls_and_ad(genotypes: &mut GenotypesContext) {
         genotypes.genotypes_mut().iter_mut().for_each(|g| {
             g.pl = Vec::new();
@@ -844,7 +957,8 @@ impl VariantContextUtils {
             if Self::is_non_symbolic_extendable_allele(a) {
                 let extended = ByteArrayAllele::extend(a, extra_bases);
                 map.insert(a, extended);
-            } else if a == &*SPAN_DEL_ALLELE {
+            } else if a == &*SPAN_DEL_ALLELE || a.is_symbolic {
+                // symbolic alleles carry no bases to extend
                 map.insert(a, a.clone());
             };
         }
@@ -1197,6 +1311,401 @@ impl VariantContextUtils {
             allele.clone()
         }
     }
+
+    /// Merges the VCF files of separate lorikeet runs against the same genome into the union
+    /// of their sites. Samples are matched by name, so a sample present in several runs gets a
+    /// single column, taken from the run with the most depth at each site. Samples from runs
+    /// without a record at a site are written as missing. The strain (ST) and variant group (VG)
+    /// IDs of each run are numbered independently, so they are reconciled by matching the alleles
+    /// they contain, see `reconcile_ids`
+    pub fn merge_from_paths(
+        vcf_paths: &[&str],
+        default_ploidy: usize,
+        min_id_overlap: f64,
+    ) -> Result<MergedVcfs, BirdToolError> {
+        let mut runs = Vec::with_capacity(vcf_paths.len());
+        for vcf_path in vcf_paths.iter() {
+            runs.push(Self::read_lorikeet_vcf(vcf_path)?);
+        }
+
+        let mut sample_names: Vec<String> = Vec::new();
+        let mut sample_indices: HashMap<String, usize> = HashMap::new();
+        let mut contigs: Vec<(String, Option<u64>)> = Vec::new();
+        let mut contig_indices: HashMap<String, usize> = HashMap::new();
+        let mut sample_maps = Vec::with_capacity(runs.len());
+        let mut strain_sites = Vec::with_capacity(runs.len());
+        let mut group_sites = Vec::with_capacity(runs.len());
+        let mut sites: LinkedHashMap<(usize, usize, Vec<u8>), Vec<(usize, VariantContext)>> =
+            LinkedHashMap::new();
+
+        for (run_index, run) in runs.into_iter().enumerate() {
+            sample_maps.push(
+                run.sample_names
+                    .iter()
+                    .map(|name| {
+                        *sample_indices.entry(name.clone()).or_insert_with(|| {
+                            sample_names.push(name.clone());
+                            sample_names.len() - 1
+                        })
+                    })
+                    .collect::<Vec<usize>>(),
+            );
+            let tid_map = run
+                .contigs
+                .iter()
+                .map(|(name, length)| {
+                    *contig_indices.entry(name.clone()).or_insert_with(|| {
+                        contigs.push((name.clone(), *length));
+                        contigs.len() - 1
+                    })
+                })
+                .collect::<Vec<usize>>();
+
+            let mut run_strains: HashMap<usize, HashSet<SiteAllele>> = HashMap::new();
+            let mut run_groups: HashMap<usize, HashSet<SiteAllele>> = HashMap::new();
+            for vc in run.contexts {
+                if vc.alleles.len() > 1 {
+                    let site_allele = (
+                        run.contigs[vc.loc.tid].0.clone(),
+                        vc.loc.start,
+                        vc.alleles[0].get_bases().to_vec(),
+                        vc.alleles[1].get_bases().to_vec(),
+                    );
+                    if let Some(AttributeObject::VecUnsize(strain_ids)) =
+                        vc.attributes.get(VariantAnnotations::Strain.to_key())
+                    {
+                        for strain_id in strain_ids.iter() {
+                            run_strains
+                                .entry(*strain_id)
+                                .or_insert_with(HashSet::new)
+                                .insert(site_allele.clone());
+                        }
+                    }
+                    if let Some(AttributeObject::I32(group)) =
+                        vc.attributes.get(VariantAnnotations::VariantGroup.to_key())
+                    {
+                        run_groups
+                            .entry(*group as usize)
+                            .or_insert_with(HashSet::new)
+                            .insert(site_allele);
+                    }
+                }
+
+                let key = (
+                    tid_map[vc.loc.tid],
+                    vc.loc.start,
+                    vc.get_reference().get_bases().to_vec(),
+                );
+                sites
+                    .entry(key)
+                    .or_insert_with(Vec::new)
+                    .push((run_index, vc));
+            }
+            strain_sites.push(run_strains);
+            group_sites.push(run_groups);
+        }
+
+        let strain_ids = Self::reconcile_ids(&strain_sites, min_id_overlap);
+        let variant_group_ids = Self::reconcile_ids(&group_sites, min_id_overlap);
+
+        let mut contexts = sites
+            .into_iter()
+            .map(|((tid, start, ref_bases), site_contexts)| {
+                Self::merge_lorikeet_site(
+                    tid,
+                    start,
+                    ref_bases,
+                    site_contexts,
+                    &sample_maps,
+                    sample_names.len(),
+                    default_ploidy,
+                    &strain_ids,
+                    &variant_group_ids,
+                )
+            })
+            .collect::<Vec<VariantContext>>();
+        contexts.sort_by(|a, b| (a.loc.tid, a.loc.start).cmp(&(b.loc.tid, b.loc.start)));
+
+        Ok(MergedVcfs {
+            sample_names,
+            contigs,
+            contexts,
+            strain_ids,
+            variant_group_ids,
+        })
+    }
+
+    /// Reads a VCF written by lorikeet along with the strain and variant group IDs of its
+    /// records
+    fn read_lorikeet_vcf(vcf_path: &str) -> Result<CombineInput, BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let header = reader.header().clone();
+        let mut contexts = Vec::new();
+        for record in reader.records() {
+            let mut record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            if let Some(mut vc) = VariantContext::from_vcf_record(&mut record, true) {
+                vc.read_strain_info(&record);
+                contexts.push(vc);
+            }
+        }
+
+        Ok(CombineInput {
+            sample_names: CombineInput::sample_names_from_header(&header),
+            contigs: CombineInput::contigs_from_header(&header),
+            contexts,
+        })
+    }
+
+    /// Gives the IDs of each run an ID shared by every run, given the site alleles each ID
+    /// contains. The IDs of the first run are kept. Each ID of a later run takes the shared ID,
+    /// not already taken in that run, whose site alleles have the highest Jaccard index with its
+    /// own, provided it is at least `min_overlap`. Pairs are assigned greedily from the highest
+    /// index down. IDs without a match keep their value if it is unused, or are given the next
+    /// unused ID. Returns the shared ID of each ID of each run
+    pub fn reconcile_ids(
+        runs: &[HashMap<usize, HashSet<SiteAllele>>],
+        min_overlap: f64,
+    ) -> Vec<HashMap<usize, usize>> {
+        let mut shared: BTreeMap<usize, HashSet<SiteAllele>> = BTreeMap::new();
+        let mut mappings = Vec::with_capacity(runs.len());
+        for run in runs.iter() {
+            let mut ids = run.keys().copied().collect::<Vec<usize>>();
+            ids.sort_unstable();
+
+            let mut candidates = Vec::new();
+            for id in ids.iter() {
+                for (shared_id, shared_sites) in shared.iter() {
+                    let overlap = Self::jaccard_index(&run[id], shared_sites);
+                    if overlap > 0.0 && overlap >= min_overlap {
+                        candidates.push((overlap, *id, *shared_id));
+                    }
+                }
+            }
+            candidates.sort_by(|a, b| {
+                b.0.partial_cmp(&a.0)
+                    .unwrap()
+                    .then(a.1.cmp(&b.1))
+                    .then(a.2.cmp(&b.2))
+            });
+
+            let mut mapping = HashMap::new();
+            let mut taken = HashSet::new();
+            for (_, id, shared_id) in candidates {
+                if mapping.contains_key(&id) || taken.contains(&shared_id) {
+                    continue;
+                }
+                mapping.insert(id, shared_id);
+                taken.insert(shared_id);
+            }
+
+            for id in ids {
+                match mapping.get(&id) {
+                    Some(shared_id) => {
+                        shared
+                            .get_mut(shared_id)
+                            .unwrap()
+                            .extend(run[&id].iter().cloned());
+                    }
+                    None => {
+                        let shared_id = if shared.contains_key(&id) {
+                            shared.keys().next_back().unwrap() + 1
+                        } else {
+                            id
+                        };
+                        shared.insert(shared_id, run[&id].clone());
+                        mapping.insert(id, shared_id);
+                    }
+                }
+            }
+            mappings.push(mapping);
+        }
+
+        mappings
+    }
+
+    fn jaccard_index(first: &HashSet<SiteAllele>, second: &HashSet<SiteAllele>) -> f64 {
+        let intersection = first.intersection(second).count();
+        let union = first.len() + second.len() - intersection;
+        if union == 0 {
+            0.0
+        } else {
+            intersection as f64 / union as f64
+        }
+    }
+
+    fn merge_lorikeet_site(
+        tid: usize,
+        start: usize,
+        ref_bases: Vec<u8>,
+        site_contexts: Vec<(usize, VariantContext)>,
+        sample_maps: &[Vec<usize>],
+        n_samples: usize,
+        default_ploidy: usize,
+        strain_ids: &[HashMap<usize, usize>],
+        variant_group_ids: &[HashMap<usize, usize>],
+    ) -> VariantContext {
+        let mut alleles = vec![ByteArrayAllele::new(&ref_bases, true)];
+        for (_, vc) in site_contexts.iter() {
+            for allele in vc.alleles.iter() {
+                if !alleles.contains(allele) {
+                    alleles.push(allele.clone());
+                }
+            }
+        }
+
+        let mut genotypes: Vec<Option<Genotype>> = vec![None; n_samples];
+        for (run_index, vc) in site_contexts.iter() {
+            let old_to_new = vc
+                .alleles
+                .iter()
+                .map(|allele| alleles.iter().position(|a| a == allele).unwrap())
+                .collect::<Vec<usize>>();
+
+            for (sample_index, genotype) in vc.genotypes.genotypes().iter().enumerate() {
+                let merged_index = sample_maps[*run_index][sample_index];
+                if let Some(existing) = &genotypes[merged_index] {
+                    if existing.dp >= genotype.dp {
+                        continue;
+                    }
+                }
+
+                let mut ad = vec![0; alleles.len()];
+                for (old_index, depth) in genotype.ad.iter().enumerate() {
+                    if old_index < old_to_new.len() {
+                        ad[old_to_new[old_index]] = *depth;
+                    }
+                }
+                let mut merged_genotype = Genotype::build_from_ads(genotype.ploidy, ad);
+                merged_genotype.pl = VcfCombiner::remap_likelihoods(
+                    &genotype.pl,
+                    genotype.ploidy,
+                    &old_to_new,
+                    alleles.len(),
+                );
+                Self::call_merged_genotype(&mut merged_genotype, &alleles);
+                merged_genotype.sample_name = merged_index;
+                genotypes[merged_index] = Some(merged_genotype);
+            }
+        }
+
+        let genotypes = genotypes
+            .into_iter()
+            .enumerate()
+            .map(|(sample_index, genotype)| {
+                genotype.unwrap_or_else(|| {
+                    let mut genotype =
+                        Genotype::build_from_ads(default_ploidy, vec![0; alleles.len()]);
+                    Self::call_merged_genotype(&mut genotype, &alleles);
+                    genotype.sample_name = sample_index;
+                    genotype
+                })
+            })
+            .collect::<Vec<Genotype>>();
+
+        // the most confident record provides the quality and INFO fields of the site
+        let (_, best) = site_contexts
+            .iter()
+            .max_by(|(_, a), (_, b)| {
+                a.get_phred_scaled_qual()
+                    .partial_cmp(&b.get_phred_scaled_qual())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap();
+        let end = site_contexts
+            .iter()
+            .map(|(_, vc)| vc.loc.end)
+            .max()
+            .unwrap();
+        let mut merged = VariantContext::build(tid, start, end, alleles);
+        merged.genotypes = GenotypesContext::new(genotypes);
+        merged.log10_p_error(best.get_log10_p_error());
+        merged.attributes = best.attributes.clone();
+        merged
+            .attributes
+            .remove(VariantAnnotations::Strain.to_key());
+        merged
+            .attributes
+            .remove(VariantAnnotations::VariantGroup.to_key());
+        if best.alleles.len() != merged.alleles.len() {
+            // lengths are given per alternate allele
+            merged
+                .attributes
+                .remove(VariantAnnotations::StructuralVariantLength.to_key());
+        }
+        // a site is only filtered if every run filtered it
+        if site_contexts.iter().all(|(_, vc)| vc.is_filtered()) {
+            merged.filters = best.filters.clone();
+        }
+
+        // strain IDs refer to the first alternate allele, which may differ between runs
+        let mut merged_strains = site_contexts
+            .iter()
+            .filter(|(_, vc)| vc.alleles.get(1) == merged.alleles.get(1))
+            .filter_map(|(run_index, vc)| {
+                match vc.attributes.get(VariantAnnotations::Strain.to_key()) {
+                    Some(AttributeObject::VecUnsize(ids)) => Some(
+                        ids.iter()
+                            .filter_map(|id| strain_ids[*run_index].get(id).copied())
+                            .collect::<Vec<usize>>(),
+                    ),
+                    _ => None,
+                }
+            })
+            .flatten()
+            .collect::<Vec<usize>>();
+        merged_strains.sort_unstable();
+        merged_strains.dedup();
+        if !merged_strains.is_empty() {
+            merged.attributes.insert(
+                VariantAnnotations::Strain.to_key().to_string(),
+                AttributeObject::VecUnsize(merged_strains),
+            );
+        }
+
+        let merged_group = site_contexts.iter().find_map(|(run_index, vc)| {
+            match vc.attributes.get(VariantAnnotations::VariantGroup.to_key()) {
+                Some(AttributeObject::I32(group)) => variant_group_ids[*run_index]
+                    .get(&(*group as usize))
+                    .copied(),
+                _ => None,
+            }
+        });
+        if let Some(group) = merged_group {
+            merged.attributes.insert(
+                VariantAnnotations::VariantGroup.to_key().to_string(),
+                AttributeObject::I32(group as i32),
+            );
+        }
+
+        merged
+    }
+
+    /// Sets the called alleles of a merged genotype from its most likely PL. Genotypes without
+    /// PLs are given flat likelihoods and no alleles, so they are written as missing
+    fn call_merged_genotype(genotype: &mut Genotype, alleles: &Vec<ByteArrayAllele>) {
+        if genotype.pl.is_empty() {
+            genotype.pl = vec![
+                0;
+                GenotypeLikelihoodCalculators::genotype_count(genotype.ploidy, alleles.len())
+                    as usize
+            ];
+            return;
+        }
+        let most_likely = genotype
+            .pl
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, pl)| **pl)
+            .map(|(index, _)| index)
+            .unwrap();
+        let mut calculator =
+            GenotypeLikelihoodCalculators::get_instance(genotype.ploidy, alleles.len());
+        genotype.alleles = calculator
+            .genotype_allele_counts_at(most_likely)
+            .as_allele_list(alleles);
+    }
 }
 
 #[derive(Debug, Clone, Ord, PartialOrd, PartialEq, Eq)]
@@ -0,0 +1,215 @@
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::model::byte_array_allele::{Allele, ByteArrayAllele};
+use crate::model::variant_context::VariantContext;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::simple_interval::SimpleInterval;
+
+/// Left aligns and trims variants as `bcftools norm` does, optionally splitting biallelic MNPs into
+/// SNPs. Symbolic records and records not matching the reference are left unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VariantNormalizer {
+    atomize: bool,
+}
+
+impl VariantNormalizer {
+    pub fn new(atomize: bool) -> Self {
+        Self { atomize }
+    }
+
+    /// The normaliser requested by --normalize-vcf, or None if records are written as called
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        match args
+            .try_get_one::<String>("normalize-vcf")
+            .ok()
+            .flatten()
+            .map(|mode| mode.as_str())
+        {
+            Some("left-align") => Some(Self::new(false)),
+            Some("atomize") => Some(Self::new(true)),
+            _ => None,
+        }
+    }
+
+    /// Left aligns and trims alleles, the first of which is the reference allele, starting at the
+    /// 0-based `start` of `contig`. Returns the new start and alleles
+    pub fn left_align(alleles: &[Vec<u8>], start: usize, contig: &[u8]) -> (usize, Vec<Vec<u8>>) {
+        let mut start = start;
+        let mut alleles = alleles.to_vec();
+        if alleles.is_empty() || alleles.iter().any(|allele| allele.is_empty()) {
+            return (start, alleles);
+        }
+
+        // shift left while every allele ends with the same base, extending the alleles with the
+        // preceding reference base whenever one of them would become empty
+        loop {
+            let last_base = alleles[0][alleles[0].len() - 1];
+            if !alleles
+                .iter()
+                .all(|allele| allele[allele.len() - 1] == last_base)
+            {
+                break;
+            }
+            let extend = alleles.iter().any(|allele| allele.len() == 1);
+            if extend && start == 0 {
+                break;
+            }
+            alleles.iter_mut().for_each(|allele| {
+                allele.pop();
+            });
+            if extend {
+                start -= 1;
+                let base = contig[start].to_ascii_uppercase();
+                alleles.iter_mut().for_each(|allele| allele.insert(0, base));
+            }
+        }
+
+        // then remove shared leading bases, keeping at least one base in every allele
+        while alleles.iter().all(|allele| allele.len() >= 2)
+            && alleles.iter().all(|allele| allele[0] == alleles[0][0])
+        {
+            alleles.iter_mut().for_each(|allele| {
+                allele.remove(0);
+            });
+            start += 1;
+        }
+
+        (start, alleles)
+    }
+
+    /// Splits a biallelic MNP starting at `start` into the position, reference and alternate base
+    /// of each differing base. None if the alleles are not a biallelic MNP
+    pub fn atomize_alleles(alleles: &[Vec<u8>], start: usize) -> Option<Vec<(usize, u8, u8)>> {
+        match alleles {
+            [ref_bases, alt_bases] if ref_bases.len() == alt_bases.len() && ref_bases.len() > 1 => {
+                Some(
+                    ref_bases
+                        .iter()
+                        .zip(alt_bases.iter())
+                        .enumerate()
+                        .filter(|(_, (ref_base, alt_base))| ref_base != alt_base)
+                        .map(|(offset, (ref_base, alt_base))| {
+                            (start + offset, *ref_base, *alt_base)
+                        })
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    /// The normalised records of a variant given the sequence of its contig
+    pub fn normalize(&self, vc: &VariantContext, contig: &[u8]) -> Vec<VariantContext> {
+        if vc.alleles.len() < 2
+            || vc
+                .alleles
+                .iter()
+                .any(|allele| allele.is_symbolic() || allele.is_no_call() || allele.is_span_del())
+        {
+            return vec![vc.clone()];
+        }
+
+        let alleles = vc
+            .alleles
+            .iter()
+            .map(|allele| allele.get_display_bases().to_vec())
+            .collect::<Vec<Vec<u8>>>();
+        let ref_matches = contig
+            .get(vc.loc.start..vc.loc.start + alleles[0].len())
+            .map(|bases| bases.eq_ignore_ascii_case(&alleles[0]))
+            .unwrap_or(false);
+        if !ref_matches {
+            return vec![vc.clone()];
+        }
+
+        let (start, aligned) = Self::left_align(&alleles, vc.loc.start, contig);
+        if self.atomize {
+            if let Some(snps) = Self::atomize_alleles(&aligned, start) {
+                return snps
+                    .into_iter()
+                    .map(|(position, ref_base, alt_base)| {
+                        Self::with_alleles(vc, position, vec![vec![ref_base], vec![alt_base]])
+                    })
+                    .collect();
+            }
+        }
+
+        if start == vc.loc.start && aligned == alleles {
+            vec![vc.clone()]
+        } else {
+            vec![Self::with_alleles(vc, start, aligned)]
+        }
+    }
+
+    /// Copy of a variant at a new start with new alleles, in the order of its current alleles.
+    /// Genotype alleles are replaced by the new allele at the same index, and the END, SVLEN and
+    /// SVTYPE of long indels are recomputed for the new position and alleles
+    fn with_alleles(vc: &VariantContext, start: usize, alleles: Vec<Vec<u8>>) -> VariantContext {
+        let new_alleles = alleles
+            .iter()
+            .enumerate()
+            .map(|(idx, bases)| ByteArrayAllele::new(bases, idx == 0))
+            .collect::<Vec<ByteArrayAllele>>();
+
+        let mut normalized = vc.clone();
+        normalized.loc = SimpleInterval::new(vc.loc.tid, start, start + alleles[0].len() - 1);
+        for genotype in normalized.genotypes.genotypes_mut() {
+            for allele in genotype.alleles.iter_mut() {
+                if let Some(idx) = vc.alleles.iter().position(|old| old == allele) {
+                    *allele = new_alleles[idx].clone();
+                }
+            }
+        }
+        normalized.alleles = new_alleles;
+        normalized.variant_type = None;
+
+        let mut had_sv_info = false;
+        for annotation in [
+            VariantAnnotations::End,
+            VariantAnnotations::StructuralVariantLength,
+            VariantAnnotations::StructuralVariantType,
+        ] {
+            had_sv_info |= normalized.attributes.remove(annotation.to_key()).is_some();
+        }
+        if had_sv_info {
+            // the indel was already long enough to be described
+            normalized.add_structural_variant_info(1);
+        }
+        normalized
+    }
+
+    /// Normalises every variant of a reference, fetching each contig once. Variants on contigs
+    /// that cannot be fetched are kept as they are. Returns the normalised variants in order
+    pub fn normalize_contexts(
+        &self,
+        contexts: &[VariantContext],
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+    ) -> Vec<VariantContext> {
+        let mut order = (0..contexts.len()).collect::<Vec<usize>>();
+        order.sort_by_key(|i| contexts[*i].loc.tid);
+
+        let mut normalized = Vec::with_capacity(contexts.len());
+        let mut current_tid = None;
+        for i in order {
+            let tid = contexts[i].loc.tid;
+            if current_tid != Some(tid) {
+                match reference_reader.fetch_contig_from_reference_by_tid(tid, ref_idx) {
+                    Ok(_) => {
+                        reference_reader.read_sequence_to_vec();
+                        current_tid = Some(tid);
+                    }
+                    Err(_) => {
+                        debug!("Unable to fetch contig {} for VCF normalisation", tid);
+                        current_tid = None;
+                        normalized.push(contexts[i].clone());
+                        continue;
+                    }
+                }
+            }
+
+            normalized.extend(self.normalize(&contexts[i], &reference_reader.current_sequence));
+        }
+        normalized.sort_by_key(|vc| (vc.loc.tid, vc.loc.start, vc.loc.end));
+        normalized
+    }
+}
@@ -0,0 +1,210 @@
+use rayon::prelude::*;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{create_dir_all, File};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+
+/// Sorts variant contexts by their `Ord`, spilling runs of `max_in_memory` contexts to a temporary
+/// directory inside of `parent` and merging them back a context at a time.
+#[derive(Debug, Clone)]
+pub struct VariantSorter {
+    max_in_memory: usize,
+    parent: PathBuf,
+}
+
+impl VariantSorter {
+    pub const DEFAULT_MAX_IN_MEMORY: usize = 1_000_000;
+
+    pub fn new<P: AsRef<Path>>(parent: P, max_in_memory: usize) -> Self {
+        Self {
+            max_in_memory: max_in_memory.max(1),
+            parent: parent.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn from_args<P: AsRef<Path>>(args: &clap::ArgMatches, parent: P) -> Self {
+        Self::new(
+            parent,
+            args.try_get_one::<usize>("max-contexts-in-memory")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(Self::DEFAULT_MAX_IN_MEMORY),
+        )
+    }
+
+    pub fn max_in_memory(&self) -> usize {
+        self.max_in_memory
+    }
+
+    /// Sorts the contexts, spilling them to disk if there are more than `max_in_memory`. Only
+    /// for callers that need every sorted context in memory, others should use `sort_iter`
+    pub fn sort(
+        &self,
+        mut contexts: Vec<VariantContext>,
+    ) -> Result<Vec<VariantContext>, BirdToolError> {
+        if contexts.len() <= self.max_in_memory {
+            contexts.par_sort_unstable();
+            return Ok(contexts);
+        }
+
+        let n_contexts = contexts.len();
+        let sorted_contexts = self.sort_iter(contexts)?;
+        let mut sorted = Vec::with_capacity(n_contexts);
+        for context in sorted_contexts {
+            sorted.push(context?);
+        }
+        Ok(sorted)
+    }
+
+    /// Sorts the contexts, returning an iterator over them in order, so that callers consuming
+    /// the contexts one at a time never hold all of them in memory
+    pub fn sort_iter(
+        &self,
+        mut contexts: Vec<VariantContext>,
+    ) -> Result<SortedContexts, BirdToolError> {
+        if contexts.len() <= self.max_in_memory {
+            contexts.par_sort_unstable();
+            return Ok(SortedContexts::in_memory(contexts));
+        }
+
+        create_dir_all(&self.parent).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create directory {}: {}",
+                self.parent.display(),
+                e
+            ))
+        })?;
+        let directory = tempfile::Builder::new()
+            .prefix("lorikeet_variant_sort")
+            .tempdir_in(&self.parent)
+            .map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to create sort directory in {}: {}",
+                    self.parent.display(),
+                    e
+                ))
+            })?;
+
+        // runs are drained from the end of the input so that it can shrink as each is spilled
+        let mut runs = Vec::new();
+        while !contexts.is_empty() {
+            let run_start = contexts.len().saturating_sub(self.max_in_memory);
+            let mut run = contexts.split_off(run_start);
+            contexts.shrink_to_fit();
+            run.par_sort_unstable();
+            let path = directory.path().join(format!("run_{}.jsonl", runs.len()));
+            Self::write_run(&path, &run)?;
+            runs.push(path);
+        }
+        // ties between runs are broken by the order of the input
+        runs.reverse();
+        debug!("Merging {} sorted runs of variants", runs.len());
+
+        SortedContexts::merge(directory, runs)
+    }
+
+    fn write_run(path: &Path, contexts: &[VariantContext]) -> Result<(), BirdToolError> {
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", path.display(), e))
+        };
+        let file = File::create(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", path.display(), e))
+        })?;
+        let mut writer = BufWriter::new(file);
+        for context in contexts {
+            serde_json::to_writer(&mut writer, context).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to serialize variant context: {}", e))
+            })?;
+            writer.write_all(b"\n").map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
+
+/// Sorted iterator over the contexts of a [`VariantSorter`]. Ties between runs are broken by the
+/// order of the runs
+pub struct SortedContexts {
+    // kept so that the runs are only removed once they have been read
+    _directory: Option<TempDir>,
+    in_memory: std::vec::IntoIter<VariantContext>,
+    runs: Vec<(PathBuf, Lines<BufReader<File>>)>,
+    heap: BinaryHeap<Reverse<(VariantContext, usize)>>,
+}
+
+impl SortedContexts {
+    fn in_memory(contexts: Vec<VariantContext>) -> Self {
+        Self {
+            _directory: None,
+            in_memory: contexts.into_iter(),
+            runs: Vec::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    fn merge(directory: TempDir, paths: Vec<PathBuf>) -> Result<Self, BirdToolError> {
+        let mut runs = Vec::with_capacity(paths.len());
+        for path in paths {
+            let file = File::open(&path).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to open {}: {}", path.display(), e))
+            })?;
+            runs.push((path, BufReader::new(file).lines()));
+        }
+
+        let mut sorted = Self {
+            _directory: Some(directory),
+            in_memory: Vec::new().into_iter(),
+            runs,
+            heap: BinaryHeap::new(),
+        };
+        for run in 0..sorted.runs.len() {
+            if let Some(context) = sorted.read_next(run)? {
+                sorted.heap.push(Reverse((context, run)));
+            }
+        }
+        Ok(sorted)
+    }
+
+    fn read_next(&mut self, run: usize) -> Result<Option<VariantContext>, BirdToolError> {
+        let (path, lines) = &mut self.runs[run];
+        match lines.next() {
+            None => Ok(None),
+            Some(line) => {
+                let line = line.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read {}: {}", path.display(), e))
+                })?;
+                let context = serde_json::from_str(&line).map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Unable to parse variant context in {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })?;
+                Ok(Some(context))
+            }
+        }
+    }
+}
+
+impl Iterator for SortedContexts {
+    type Item = Result<VariantContext, BirdToolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(context) = self.in_memory.next() {
+            return Some(Ok(context));
+        }
+
+        let Reverse((context, run)) = self.heap.pop()?;
+        match self.read_next(run) {
+            Ok(Some(next)) => self.heap.push(Reverse((next, run))),
+            Ok(None) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(context))
+    }
+}
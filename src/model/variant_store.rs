@@ -0,0 +1,287 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::iter::Peekable;
+use std::path::PathBuf;
+
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+use crate::utils::temp_resources::{TempResource, TempResources};
+
+/// Location and extent of a single chunk on disk. Every chunk holds the contexts of one contig
+/// sorted by position
+#[derive(Debug, Clone)]
+struct StoreChunk {
+    tid: usize,
+    start: usize,
+    end: usize,
+    len: usize,
+    path: PathBuf,
+}
+
+impl StoreChunk {
+    fn overlaps(&self, tid: usize, start: usize, end: usize) -> bool {
+        self.tid == tid && self.start <= end && self.end >= start
+    }
+}
+
+/// An on-disk store of variant contexts that can be queried by region. Contexts are buffered in
+/// memory until `chunk_size` of them have been added, at which point the buffer is sorted,
+/// split by contig and written out as one chunk per contig. Only the extent of each chunk is kept
+/// in memory, so stages that hold large numbers of intermediate contexts can keep them on disk
+/// and read back the contexts of a region or contig only when needed.
+///
+/// Chunks are written as one JSON encoded context per line inside of a registered temporary
+/// directory, so under --tmp-dir if given, which is removed when the store is dropped or the run
+/// is interrupted.
+pub struct VariantStore {
+    directory: TempResource,
+    chunk_size: usize,
+    buffer: Vec<VariantContext>,
+    chunks: Vec<StoreChunk>,
+    len: usize,
+}
+
+impl VariantStore {
+    pub const DEFAULT_CHUNK_SIZE: usize = 50_000;
+
+    /// Creates an empty store in a new temporary directory
+    pub fn new(chunk_size: usize) -> Result<Self, BirdToolError> {
+        let directory = TempResources::create_dir("lorikeet_variant_store")?;
+
+        Ok(Self {
+            directory,
+            chunk_size: chunk_size.max(1),
+            buffer: Vec::new(),
+            chunks: Vec::new(),
+            len: 0,
+        })
+    }
+
+    /// Total number of contexts in the store
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of chunks that have been written to disk
+    pub fn n_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn push(&mut self, context: VariantContext) -> Result<(), BirdToolError> {
+        self.buffer.push(context);
+        self.len += 1;
+        if self.buffer.len() >= self.chunk_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn extend<I: IntoIterator<Item = VariantContext>>(
+        &mut self,
+        contexts: I,
+    ) -> Result<(), BirdToolError> {
+        for context in contexts {
+            self.push(context)?;
+        }
+        Ok(())
+    }
+
+    /// Writes any buffered contexts to disk
+    pub fn flush(&mut self) -> Result<(), BirdToolError> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        let mut buffer = std::mem::take(&mut self.buffer);
+        buffer.sort_unstable();
+
+        let mut contig_start = 0;
+        while contig_start < buffer.len() {
+            let tid = buffer[contig_start].loc.tid;
+            let contig_end = contig_start
+                + buffer[contig_start..]
+                    .iter()
+                    .take_while(|context| context.loc.tid == tid)
+                    .count();
+            self.write_chunk(&buffer[contig_start..contig_end])?;
+            contig_start = contig_end;
+        }
+
+        Ok(())
+    }
+
+    fn write_chunk(&mut self, contexts: &[VariantContext]) -> Result<(), BirdToolError> {
+        let path = self
+            .directory
+            .path()
+            .join(format!("chunk_{}.jsonl", self.chunks.len()));
+        let file = File::create(&path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", path.display(), e))
+        })?;
+        let mut writer = BufWriter::new(file);
+        for context in contexts {
+            serde_json::to_writer(&mut writer, context).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to serialize variant context: {}", e))
+            })?;
+            writer.write_all(b"\n").map_err(|e| {
+                BirdToolError::IOError(format!("Unable to write to {}: {}", path.display(), e))
+            })?;
+        }
+        writer.flush().map_err(|e| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", path.display(), e))
+        })?;
+
+        self.chunks.push(StoreChunk {
+            tid: contexts[0].loc.tid,
+            start: contexts.iter().map(|c| c.loc.start).min().unwrap(),
+            end: contexts.iter().map(|c| c.loc.end).max().unwrap(),
+            len: contexts.len(),
+            path,
+        });
+        Ok(())
+    }
+
+    fn read_chunk(chunk: &StoreChunk) -> Result<Vec<VariantContext>, BirdToolError> {
+        let file = File::open(&chunk.path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to open {}: {}", chunk.path.display(), e))
+        })?;
+        let mut contexts = Vec::with_capacity(chunk.len);
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read {}: {}", chunk.path.display(), e))
+            })?;
+            let context: VariantContext = serde_json::from_str(&line).map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to parse variant context in {}: {}",
+                    chunk.path.display(),
+                    e
+                ))
+            })?;
+            contexts.push(context);
+        }
+        Ok(contexts)
+    }
+
+    /// The contigs that have contexts in the store, in ascending order
+    pub fn contigs(&self) -> Vec<usize> {
+        let mut tids = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.tid)
+            .chain(self.buffer.iter().map(|context| context.loc.tid))
+            .collect::<Vec<usize>>();
+        tids.sort_unstable();
+        tids.dedup();
+        tids
+    }
+
+    /// All contexts overlapping the 0-based inclusive interval `start..=end` of contig `tid`,
+    /// sorted by position. Only the chunks overlapping the interval are read from disk
+    pub fn query(
+        &self,
+        tid: usize,
+        start: usize,
+        end: usize,
+    ) -> Result<Vec<VariantContext>, BirdToolError> {
+        let overlaps = |context: &VariantContext| {
+            context.loc.tid == tid && context.loc.start <= end && context.loc.end >= start
+        };
+
+        let mut contexts = Vec::new();
+        for chunk in self.chunks.iter().filter(|c| c.overlaps(tid, start, end)) {
+            contexts.extend(Self::read_chunk(chunk)?.into_iter().filter(|c| overlaps(c)));
+        }
+        contexts.extend(self.buffer.iter().filter(|c| overlaps(c)).cloned());
+        contexts.sort_unstable();
+        Ok(contexts)
+    }
+
+    /// All contexts on contig `tid`, sorted by position
+    pub fn query_contig(&self, tid: usize) -> Result<Vec<VariantContext>, BirdToolError> {
+        self.query(tid, 0, usize::MAX)
+    }
+
+    /// Iterates over every context in sorted order, reading one contig at a time from disk
+    pub fn iter(&self) -> VariantStoreIter<'_> {
+        VariantStoreIter {
+            store: self,
+            contigs: self.contigs().into_iter(),
+            current: Vec::new().into_iter(),
+        }
+    }
+
+    /// Merges two streams of contexts that are each sorted by position, such as the contexts of
+    /// a store and those that were kept in memory, into one sorted stream
+    pub fn merge_sorted<I, J>(left: I, right: J) -> MergedContexts<I::IntoIter, J::IntoIter>
+    where
+        I: IntoIterator<Item = Result<VariantContext, BirdToolError>>,
+        J: IntoIterator<Item = Result<VariantContext, BirdToolError>>,
+    {
+        MergedContexts {
+            left: left.into_iter().peekable(),
+            right: right.into_iter().peekable(),
+        }
+    }
+}
+
+/// Sorted iterator over the contexts of a [`VariantStore`]. Only the contexts of the current
+/// contig are held in memory
+pub struct VariantStoreIter<'a> {
+    store: &'a VariantStore,
+    contigs: std::vec::IntoIter<usize>,
+    current: std::vec::IntoIter<VariantContext>,
+}
+
+impl<'a> Iterator for VariantStoreIter<'a> {
+    type Item = Result<VariantContext, BirdToolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(context) = self.current.next() {
+                return Some(Ok(context));
+            }
+
+            let tid = self.contigs.next()?;
+            match self.store.query_contig(tid) {
+                Ok(contexts) => self.current = contexts.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Sorted merge of two sorted streams of contexts, see [`VariantStore::merge_sorted`]. Errors are
+/// passed on as soon as they are reached
+pub struct MergedContexts<I, J>
+where
+    I: Iterator<Item = Result<VariantContext, BirdToolError>>,
+    J: Iterator<Item = Result<VariantContext, BirdToolError>>,
+{
+    left: Peekable<I>,
+    right: Peekable<J>,
+}
+
+impl<I, J> Iterator for MergedContexts<I, J>
+where
+    I: Iterator<Item = Result<VariantContext, BirdToolError>>,
+    J: Iterator<Item = Result<VariantContext, BirdToolError>>,
+{
+    type Item = Result<VariantContext, BirdToolError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let take_left = match (self.left.peek(), self.right.peek()) {
+            (Some(Err(_)), _) | (Some(_), None) => true,
+            (Some(Ok(left)), Some(Ok(right))) => left <= right,
+            _ => false,
+        };
+        if take_left {
+            self.left.next()
+        } else {
+            self.right.next()
+        }
+    }
+}
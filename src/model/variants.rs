@@ -446,11 +446,17 @@ impl Variant {
 }
 
 /// The filter tag given to the locus
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum Filter {
     LowCov,
     Amb,
     Del,
+    Masked,
+    HomopolymerIndel,
+    LowComplexity,
+    CopyNumber,
+    LowQD,
+    LowDepth,
     PASS,
     None,
 }
@@ -462,6 +468,12 @@ impl Filter {
             "LowCov" => Filter::LowCov,
             "Amb" => Filter::Amb,
             "Del" => Filter::Del,
+            "MASKED" => Filter::Masked,
+            "HomopolymerIndel" => Filter::HomopolymerIndel,
+            "LowComplexity" => Filter::LowComplexity,
+            "CopyNumber" => Filter::CopyNumber,
+            "LowQD" => Filter::LowQD,
+            "LowDepth" => Filter::LowDepth,
             _ => Filter::None,
         }
     }
@@ -472,6 +484,12 @@ impl Filter {
             Ok("LowCov") => Filter::LowCov,
             Ok("Amb") => Filter::Amb,
             Ok("Del") => Filter::Del,
+            Ok("MASKED") => Filter::Masked,
+            Ok("HomopolymerIndel") => Filter::HomopolymerIndel,
+            Ok("LowComplexity") => Filter::LowComplexity,
+            Ok("CopyNumber") => Filter::CopyNumber,
+            Ok("LowQD") => Filter::LowQD,
+            Ok("LowDepth") => Filter::LowDepth,
             _ => Filter::None,
         }
     }
@@ -482,6 +500,12 @@ impl Filter {
             Self::LowCov => "LowCov",
             Self::Amb => "Amb",
             Self::Del => "Del",
+            Self::Masked => "MASKED",
+            Self::HomopolymerIndel => "HomopolymerIndel",
+            Self::LowComplexity => "LowComplexity",
+            Self::CopyNumber => "CopyNumber",
+            Self::LowQD => "LowQD",
+            Self::LowDepth => "LowDepth",
             Self::PASS => "PASS",
         }
     }
@@ -0,0 +1,417 @@
+use crate::haplotype::haplotype::Haplotype;
+use crate::model::allele_likelihoods::AlleleLikelihoods;
+use crate::model::byte_array_allele::Allele;
+use crate::pair_hmm::pair_hmm_likelihood_calculation_engine::PairHMMInputScoreImputator;
+use crate::pair_hmm::pair_hmm_model::PairHMMModel;
+use crate::reads::bird_tool_reads::BirdToolRead;
+use crate::utils::errors::BirdToolError;
+use crate::utils::quality_utils::QualityUtils;
+use crate::utils::simple_interval::SimpleInterval;
+use std::sync::Arc;
+
+/// The implementation used to compute read likelihoods with the PairHMM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PairHMMBackend {
+    /// Use the GPU if lorikeet was built with the `gpu` feature and a device is available,
+    /// otherwise use AVX
+    Auto,
+    /// Use the AVX accelerated PairHMM, or the scalar PairHMM if AVX is unavailable
+    Avx,
+    /// Use the GPU, falling back to AVX if no device can be initialized
+    Gpu,
+}
+
+impl PairHMMBackend {
+    /// The requested backend. --disable-avx keeps the PairHMM on the CPU, so only the scalar
+    /// PairHMM is used with it
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let disable_avx = args
+            .try_get_one::<bool>("disable-avx")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        match args
+            .try_get_one::<String>("pairhmm-backend")
+            .ok()
+            .flatten()
+            .map(|s| s.as_str())
+        {
+            _ if disable_avx => Self::Avx,
+            Some("gpu") => Self::Gpu,
+            Some("avx") => Self::Avx,
+            _ => Self::Auto,
+        }
+    }
+
+    /// Fails when the GPU is requested along with --disable-avx, which would otherwise ignore it
+    pub fn check_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        let disable_avx = args
+            .try_get_one::<bool>("disable-avx")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        match args.try_get_one::<String>("pairhmm-backend").ok().flatten() {
+            Some(backend) if disable_avx && backend == "gpu" => Err(BirdToolError::ConfigError(
+                "--pairhmm-backend gpu can not be used with --disable-avx".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Initializes the GPU used by every genome of a run. None if the backend does not use the
+    /// GPU or it can not be initialized, in which case the AVX or scalar PairHMM is used
+    pub fn initialize(&self) -> Option<Arc<GpuPairHMM>> {
+        match self {
+            Self::Avx => None,
+            Self::Auto | Self::Gpu => match GpuPairHMM::new() {
+                Ok(gpu_pair_hmm) => {
+                    info!("Using GPU PairHMM backend");
+                    Some(Arc::new(gpu_pair_hmm))
+                }
+                Err(e) if *self == Self::Gpu => {
+                    warn!("{}, falling back to AVX PairHMM", e);
+                    None
+                }
+                Err(e) => {
+                    info!("{}, using AVX PairHMM", e);
+                    None
+                }
+            },
+        }
+    }
+}
+
+/// The reads and haplotypes of one batch flattened into the arrays consumed by the GPU kernel.
+/// Per base priors and transition probabilities are precomputed on the host so that the kernel
+/// only has to perform the forward recursion.
+#[derive(Debug, Default)]
+pub struct GpuPairHMMBatch {
+    pub read_bases: Vec<u8>,
+    pub read_offsets: Vec<u32>,
+    pub read_lengths: Vec<u32>,
+    /// match and mismatch probability of each read base
+    pub priors: Vec<f64>,
+    /// `PairHMMModel::TRANS_PROB_ARRAY_LENGTH` transition probabilities for each read base
+    pub transitions: Vec<f64>,
+    pub haplotype_bases: Vec<u8>,
+    pub haplotype_offsets: Vec<u32>,
+    pub haplotype_lengths: Vec<u32>,
+    pub max_read_length: usize,
+}
+
+impl GpuPairHMMBatch {
+    const TRISTATE_CORRECTION: f64 = 3.0;
+
+    pub fn new(haplotypes: &[&[u8]]) -> Self {
+        let mut batch = Self::default();
+        for haplotype in haplotypes {
+            batch.haplotype_offsets.push(batch.haplotype_bases.len() as u32);
+            batch.haplotype_lengths.push(haplotype.len() as u32);
+            batch.haplotype_bases.extend_from_slice(haplotype);
+        }
+        batch
+    }
+
+    pub fn add_read(
+        &mut self,
+        model: &PairHMMModel,
+        read_bases: &[u8],
+        read_quals: &[u8],
+        insertion_gop: &[u8],
+        deletion_gop: &[u8],
+        overall_gcp: &[u8],
+    ) {
+        self.read_offsets.push(self.read_bases.len() as u32);
+        self.read_lengths.push(read_bases.len() as u32);
+        self.read_bases.extend_from_slice(read_bases);
+        self.max_read_length = std::cmp::max(self.max_read_length, read_bases.len());
+
+        for i in 0..read_bases.len() {
+            self.priors.push(QualityUtils::qual_to_prob(read_quals[i]));
+            self.priors
+                .push(QualityUtils::qual_to_error_prob(read_quals[i]) / Self::TRISTATE_CORRECTION);
+            self.transitions.extend(model.qual_to_trans_probs_return_vec(
+                insertion_gop[i],
+                deletion_gop[i],
+                overall_gcp[i],
+            ));
+        }
+    }
+
+    pub fn n_reads(&self) -> usize {
+        self.read_lengths.len()
+    }
+
+    pub fn n_haplotypes(&self) -> usize {
+        self.haplotype_lengths.len()
+    }
+}
+
+/// PairHMM likelihood computation on a GPU via OpenCL. Each read and haplotype pair is computed
+/// by a separate work item, which walks the haplotype one column at a time keeping only the
+/// previous column of the match, insertion and deletion matrices. The compiled kernel is shared
+/// by every thread, and each batch is sent to the device on a command queue of its own.
+#[derive(Debug)]
+pub struct GpuPairHMM {
+    #[cfg(feature = "gpu")]
+    pro_que: ocl::ProQue,
+}
+
+#[cfg(feature = "gpu")]
+static PAIR_HMM_KERNEL: &str = r#"
+#pragma OPENCL EXTENSION cl_khr_fp64 : enable
+
+__kernel void pair_hmm_forward(
+    __global const uchar* read_bases,
+    __global const uint* read_offsets,
+    __global const uint* read_lengths,
+    __global const double* priors,
+    __global const double* transitions,
+    __global const uchar* haplotype_bases,
+    __global const uint* haplotype_offsets,
+    __global const uint* haplotype_lengths,
+    const uint n_haplotypes,
+    const uint scratch_stride,
+    const double initial_condition,
+    __global double* scratch,
+    __global double* results)
+{
+    const uint pair = get_global_id(0);
+    const uint r = pair / n_haplotypes;
+    const uint h = pair % n_haplotypes;
+    const uint read_length = read_lengths[r];
+    const uint haplotype_length = haplotype_lengths[h];
+
+    __global const uchar* rb = read_bases + read_offsets[r];
+    __global const double* pr = priors + 2 * read_offsets[r];
+    __global const double* tr = transitions + 6 * read_offsets[r];
+    __global const uchar* hb = haplotype_bases + haplotype_offsets[h];
+
+    const uint column = read_length + 1;
+    __global double* m_prev = scratch + (size_t)pair * scratch_stride;
+    __global double* i_prev = m_prev + column;
+    __global double* d_prev = i_prev + column;
+    __global double* m_curr = d_prev + column;
+    __global double* i_curr = m_curr + column;
+    __global double* d_curr = i_curr + column;
+
+    const double initial_value = initial_condition / haplotype_length;
+    for (uint i = 0; i <= read_length; i++) {
+        m_prev[i] = 0.0;
+        i_prev[i] = 0.0;
+        d_prev[i] = 0.0;
+    }
+    d_prev[0] = initial_value;
+
+    double final_sum = 0.0;
+    for (uint j = 1; j <= haplotype_length; j++) {
+        const uchar y = hb[j - 1];
+        m_curr[0] = 0.0;
+        i_curr[0] = 0.0;
+        d_curr[0] = initial_value;
+        for (uint i = 1; i <= read_length; i++) {
+            const uchar x = rb[i - 1];
+            const double prior = (x == y || x == 'N' || y == 'N') ? pr[2 * (i - 1)] : pr[2 * (i - 1) + 1];
+            __global const double* t = tr + 6 * (i - 1);
+            // transition order follows PairHMMModel: mm, im, mi, ii, md, dd
+            m_curr[i] = prior * (m_prev[i - 1] * t[0] + i_prev[i - 1] * t[1] + d_prev[i - 1] * t[1]);
+            i_curr[i] = m_curr[i - 1] * t[2] + i_curr[i - 1] * t[3];
+            d_curr[i] = m_prev[i] * t[4] + d_prev[i] * t[5];
+        }
+        final_sum += m_curr[read_length] + i_curr[read_length];
+
+        __global double* tmp;
+        tmp = m_prev; m_prev = m_curr; m_curr = tmp;
+        tmp = i_prev; i_prev = i_curr; i_curr = tmp;
+        tmp = d_prev; d_prev = d_curr; d_curr = tmp;
+    }
+
+    results[pair] = log10(final_sum) - log10(initial_condition);
+}
+"#;
+
+impl GpuPairHMM {
+    /// The scaling applied to the first row of the deletion matrix to avoid underflow, matching
+    /// the scalar PairHMM
+    const INITIAL_CONDITION: f64 = 8.98846567431158e307; // 2^1020
+
+    /// Initializes the first available OpenCL device. Fails if lorikeet was built without the
+    /// `gpu` feature, no device is available, or the device lacks double precision support
+    #[cfg(feature = "gpu")]
+    pub fn new() -> Result<Self, BirdToolError> {
+        let pro_que = ocl::ProQue::builder()
+            .src(PAIR_HMM_KERNEL)
+            .dims(1)
+            .build()
+            .map_err(|e| {
+                BirdToolError::GpuError(format!("Unable to initialize OpenCL device: {}", e))
+            })?;
+        Ok(Self { pro_que })
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    pub fn new() -> Result<Self, BirdToolError> {
+        Err(BirdToolError::GpuError(
+            "lorikeet was built without the gpu feature".to_string(),
+        ))
+    }
+
+    /// Computes the log10 likelihood of every read in the batch given every haplotype.
+    /// Returns a read major vector with one value per haplotype for each read
+    #[cfg(feature = "gpu")]
+    pub fn compute_batch(&self, batch: &GpuPairHMMBatch) -> Result<Vec<f64>, BirdToolError> {
+        use ocl::{flags::MemFlags, Buffer};
+
+        let n_pairs = batch.n_reads() * batch.n_haplotypes();
+        if n_pairs == 0 {
+            return Ok(Vec::new());
+        }
+        let gpu_error =
+            |e: ocl::Error| BirdToolError::GpuError(format!("OpenCL PairHMM failed: {}", e));
+        // a queue per batch so that threads do not wait for the transfers of other batches
+        let queue = ocl::Queue::new(self.pro_que.context(), self.pro_que.device(), None)
+            .map_err(gpu_error)?;
+
+        macro_rules! input_buffer {
+            ($data:expr) => {
+                Buffer::builder()
+                    .queue(queue.clone())
+                    .flags(MemFlags::new().read_only())
+                    .len($data.len())
+                    .copy_host_slice(&$data[..])
+                    .build()
+                    .map_err(gpu_error)?
+            };
+        }
+
+        let read_bases = input_buffer!(batch.read_bases);
+        let read_offsets = input_buffer!(batch.read_offsets);
+        let read_lengths = input_buffer!(batch.read_lengths);
+        let priors = input_buffer!(batch.priors);
+        let transitions = input_buffer!(batch.transitions);
+        let haplotype_bases = input_buffer!(batch.haplotype_bases);
+        let haplotype_offsets = input_buffer!(batch.haplotype_offsets);
+        let haplotype_lengths = input_buffer!(batch.haplotype_lengths);
+
+        // two columns each of the match, insertion, and deletion matrices per pair
+        let scratch_stride = 6 * (batch.max_read_length + 1);
+        let scratch = Buffer::<f64>::builder()
+            .queue(queue.clone())
+            .len(n_pairs * scratch_stride)
+            .build()
+            .map_err(gpu_error)?;
+        let results = Buffer::<f64>::builder()
+            .queue(queue.clone())
+            .flags(MemFlags::new().write_only())
+            .len(n_pairs)
+            .build()
+            .map_err(gpu_error)?;
+
+        let kernel = self
+            .pro_que
+            .kernel_builder("pair_hmm_forward")
+            .queue(queue.clone())
+            .global_work_size(n_pairs)
+            .arg(&read_bases)
+            .arg(&read_offsets)
+            .arg(&read_lengths)
+            .arg(&priors)
+            .arg(&transitions)
+            .arg(&haplotype_bases)
+            .arg(&haplotype_offsets)
+            .arg(&haplotype_lengths)
+            .arg(batch.n_haplotypes() as u32)
+            .arg(scratch_stride as u32)
+            .arg(Self::INITIAL_CONDITION)
+            .arg(&scratch)
+            .arg(&results)
+            .build()
+            .map_err(gpu_error)?;
+
+        unsafe {
+            kernel.enq().map_err(gpu_error)?;
+        }
+
+        let mut likelihoods = vec![0.0; n_pairs];
+        results.read(&mut likelihoods).enq().map_err(gpu_error)?;
+
+        Ok(likelihoods)
+    }
+
+    #[cfg(not(feature = "gpu"))]
+    pub fn compute_batch(&self, _batch: &GpuPairHMMBatch) -> Result<Vec<f64>, BirdToolError> {
+        Err(BirdToolError::GpuError(
+            "lorikeet was built without the gpu feature".to_string(),
+        ))
+    }
+
+    /// Computes the likelihoods of the reads of every sample and stores them in the likelihoods
+    /// matrix. Reads are sent to the device in batches of at most `batch_size` read and
+    /// haplotype pairs to bound device memory use
+    pub fn compute_log10_likelihoods(
+        &self,
+        haplotypes: &[Haplotype<SimpleInterval>],
+        allele_likelihoods: &mut AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        processed_reads_by_sample: &[(usize, Vec<BirdToolRead>)],
+        input_score_imputator: &PairHMMInputScoreImputator,
+        batch_size: usize,
+    ) -> Result<(), BirdToolError> {
+        let haplotype_bases = haplotypes
+            .iter()
+            .map(|haplotype| haplotype.get_bases())
+            .collect::<Vec<&[u8]>>();
+        let n_haplotypes = haplotype_bases.len();
+        if n_haplotypes == 0 {
+            return Ok(());
+        }
+
+        // haplotype index of each allele in the likelihoods matrix
+        let allele_to_haplotype_index = (0..allele_likelihoods.number_of_alleles())
+            .map(|allele_index| {
+                let allele = allele_likelihoods.alleles.get_allele(allele_index).unwrap();
+                haplotypes.iter().position(|haplotype| haplotype == allele).unwrap()
+            })
+            .collect::<Vec<usize>>();
+
+        let model = PairHMMModel::new();
+        let reads_per_batch = std::cmp::max(batch_size / n_haplotypes, 1);
+        let queued_reads = processed_reads_by_sample
+            .iter()
+            .flat_map(|(sample_index, reads)| {
+                reads
+                    .iter()
+                    .enumerate()
+                    .map(move |(read_index, read)| (*sample_index, read_index, read))
+            })
+            .collect::<Vec<(usize, usize, &BirdToolRead)>>();
+
+        for queued_batch in queued_reads.chunks(reads_per_batch) {
+            let mut batch = GpuPairHMMBatch::new(&haplotype_bases);
+            for (_, _, read) in queued_batch {
+                batch.add_read(
+                    &model,
+                    &read.unpacked_bases(),
+                    read.read.qual(),
+                    &input_score_imputator.ins_open_penalties(read),
+                    &input_score_imputator.del_open_penalties(read),
+                    &input_score_imputator.gap_continuation_penalties(read),
+                );
+            }
+
+            let likelihoods = self.compute_batch(&batch)?;
+            for ((sample_index, read_index, _), read_likelihoods) in
+                queued_batch.iter().zip(likelihoods.chunks(n_haplotypes))
+            {
+                for (allele_index, haplotype_index) in allele_to_haplotype_index.iter().enumerate()
+                {
+                    allele_likelihoods.values_by_sample_index[*sample_index]
+                        [[allele_index, *read_index]] = read_likelihoods[*haplotype_index];
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
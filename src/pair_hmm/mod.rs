@@ -1,3 +1,4 @@
+pub mod gpu_pair_hmm;
 pub mod pair_hmm;
 pub mod pair_hmm_likelihood_calculation_engine;
 pub mod pair_hmm_model;
@@ -1,5 +1,6 @@
 use ndarray::{Array2, Zip};
 use rayon::prelude::*;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use gkl::pairhmm::forward;
 
@@ -230,7 +231,7 @@ impl<'a> PairHMM<'a> {
 
                     for read in processed_reads.iter() {
                         read_data_array.push(ReadDataHolder::new(
-                            &read.bases,
+                            read.unpacked_bases(),
                             &read.read.qual(),
                             input_score_imputator.ins_open_penalties(read),
                             input_score_imputator.del_open_penalties(read),
@@ -290,7 +291,7 @@ impl<'a> PairHMM<'a> {
                     let mut idx = 0;
                     let mut read_index = 0;
                     for read in processed_reads {
-                        let read_bases = read.bases.as_slice();
+                        let read_bases = read.unpacked_bases();
                         let read_quals = read.read.qual();
                         let read_ins_quals = input_score_imputator.ins_open_penalties(&read);
                         let read_del_quals = input_score_imputator.del_open_penalties(&read);
@@ -316,7 +317,7 @@ impl<'a> PairHMM<'a> {
                             };
                             let lk = self.compute_read_likelihood_given_haplotype_log10(
                                 allele_bases,
-                                read_bases,
+                                &read_bases,
                                 read_quals,
                                 &read_ins_quals,
                                 &read_del_quals,
@@ -340,6 +341,108 @@ impl<'a> PairHMM<'a> {
         }
     }
 
+    /// Computes the likelihoods of the reads of every sample in a single work queue rather than
+    /// sample by sample. Read and haplotype pairs are split into batches of roughly `batch_size`
+    /// pairs which are processed in parallel, keeping the vector units busy for regions where
+    /// individual samples only contribute a handful of reads.
+    /// Only available in AVX mode, other modes compute each sample in turn.
+    pub fn compute_log10_likelihoods_batched(
+        &mut self,
+        allele_likelihoods: &mut AlleleLikelihoods<Haplotype<SimpleInterval>>,
+        processed_reads_by_sample: Vec<(usize, Vec<BirdToolRead>)>,
+        input_score_imputator: &PairHMMInputScoreImputator,
+        batch_size: usize,
+    ) {
+        match self.avx_mode {
+            AVXMode::AVX => {
+                let num_haplotypes = self.m_haplotype_data_array.len();
+                if num_haplotypes == 0 {
+                    return;
+                }
+
+                // the work queue, each read is paired with every haplotype
+                let mut queued_reads = Vec::new();
+                for (sample_index, processed_reads) in processed_reads_by_sample.iter() {
+                    for (read_index, read) in processed_reads.iter().enumerate() {
+                        queued_reads.push((
+                            *sample_index,
+                            read_index,
+                            ReadDataHolder::new(
+                                read.unpacked_bases(),
+                                &read.read.qual(),
+                                input_score_imputator.ins_open_penalties(read),
+                                input_score_imputator.del_open_penalties(read),
+                                input_score_imputator.gap_continuation_penalties(read),
+                            ),
+                        ));
+                    }
+                }
+
+                let reads_per_batch = std::cmp::max(batch_size / num_haplotypes, 1);
+                let haplotype_data = &self.m_haplotype_data_array;
+                let batch_likelihoods = queued_reads
+                    .par_chunks(reads_per_batch)
+                    .map(|batch| {
+                        let avx_function = &forward().unwrap();
+                        batch
+                            .iter()
+                            .flat_map(|(_, _, read)| {
+                                haplotype_data.iter().map(move |hap_bases| {
+                                    avx_function(
+                                        hap_bases,
+                                        &read.read_bases,
+                                        read.read_quals,
+                                        &read.insertion_gop,
+                                        &read.deletion_gop,
+                                        &read.overall_gcp,
+                                    )
+                                })
+                            })
+                            .collect::<Vec<f64>>()
+                    })
+                    .collect::<Vec<Vec<f64>>>();
+
+                // haplotype list index of each allele in the likelihoods matrix
+                let allele_to_haplotype_index = (0..allele_likelihoods.number_of_alleles())
+                    .map(|allele_index| {
+                        *self
+                            .haplotype_to_haplotype_list_index_map
+                            .get(
+                                &match allele_likelihoods.alleles.get_allele(allele_index) {
+                                    Some(new_order) => new_order,
+                                    None => panic!("Could not map new order {} to old order as new index was not present in new list", allele_index)
+                                }
+                            )
+                            .unwrap()
+                    })
+                    .collect::<Vec<usize>>();
+
+                for ((sample_index, read_index, _), likelihoods) in queued_reads.iter().zip(
+                    batch_likelihoods
+                        .iter()
+                        .flat_map(|batch| batch.chunks(num_haplotypes)),
+                ) {
+                    for (allele_index, haplotype_index) in
+                        allele_to_haplotype_index.iter().enumerate()
+                    {
+                        allele_likelihoods.values_by_sample_index[*sample_index]
+                            [[allele_index, *read_index]] = likelihoods[*haplotype_index];
+                    }
+                }
+            }
+            _ => {
+                for (sample_index, processed_reads) in processed_reads_by_sample {
+                    self.compute_log10_likelihoods(
+                        sample_index,
+                        allele_likelihoods,
+                        processed_reads,
+                        input_score_imputator,
+                    );
+                }
+            }
+        }
+    }
+
     /// Computes the per read per allele likelihoods using AVX accelerated computation
     /// Values are collected into m_log_likelihood_array
     fn compute_likelihoods(&mut self, read_data_array: Vec<ReadDataHolder>) {
@@ -357,7 +460,7 @@ impl<'a> PairHMM<'a> {
                                 //println!("quals {:?} i {:?} c {:?}", read.read_quals, &read.insertion_gop, &read.overall_gcp);
                                 avx_function(
                                     hap_bases,
-                                    read.read_bases,
+                                    &read.read_bases,
                                     read.read_quals,
                                     &read.insertion_gop,
                                     &read.deletion_gop,
@@ -719,7 +822,8 @@ impl<'a> PairHMM<'a> {
 
 #[derive(Debug)]
 struct ReadDataHolder<'a> {
-    read_bases: &'a [u8],
+    // owned when the bases of a packed read had to be unpacked
+    read_bases: Cow<'a, [u8]>,
     read_quals: &'a [u8],
     insertion_gop: Vec<u8>,
     deletion_gop: Vec<u8>,
@@ -728,7 +832,7 @@ struct ReadDataHolder<'a> {
 
 impl<'a> ReadDataHolder<'a> {
     fn new(
-        read_bases: &'a [u8],
+        read_bases: Cow<'a, [u8]>,
         read_quals: &'a [u8],
         insertion_gop: Vec<u8>,
         deletion_gop: Vec<u8>,
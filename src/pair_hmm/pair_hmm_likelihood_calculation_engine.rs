@@ -1,6 +1,7 @@
 use rayon::prelude::*;
 use std::cmp::{max, min};
 use std::collections::HashMap;
+use std::sync::Arc;
 use ordered_float::OrderedFloat;
 
 use crate::utils::quality_utils::QualityUtils;
@@ -9,10 +10,15 @@ use crate::assembly::assembly_result_set::AssemblyResultSet;
 use crate::haplotype::haplotype::Haplotype;
 use crate::model::allele_likelihoods::AlleleLikelihoods;
 use crate::model::variant_context_utils::VariantContextUtils;
+use crate::pair_hmm::gpu_pair_hmm::GpuPairHMM;
 use crate::pair_hmm::pair_hmm::PairHMM;
 use crate::read_threading::abstract_read_threading_graph::AbstractReadThreadingGraph;
+use crate::reads::base_recalibration::BaseRecalibrationTable;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::read_clipper::ReadClipper;
+use crate::reads::read_compression::ReadCompression;
+use crate::reads::read_group_profiles::ReadGroupProfiles;
+use crate::reads::split_alignment_policy::SplitAlignmentPolicy;
 use crate::reads::read_utils::ReadUtils;
 
 lazy_static! {
@@ -55,6 +61,11 @@ pub struct PairHMMLikelihoodCalculationEngine {
     pcr_indel_error_model_cache: Vec<u8>,
     input_score_imputator: PairHMMInputScoreImputator,
     avx_mode: AVXMode,
+    base_recalibration_table: Option<Arc<BaseRecalibrationTable>>,
+    pair_hmm_batch_size: usize,
+    gpu_pair_hmm: Option<Arc<GpuPairHMM>>,
+    read_group_profiles: Option<Arc<ReadGroupProfiles>>,
+    read_compression: ReadCompression,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -159,6 +170,11 @@ impl PairHMMLikelihoodCalculationEngine {
             pcr_indel_error_model_cache: Vec::new(),
             input_score_imputator: PairHMMInputScoreImputator::new(constant_gcp),
             avx_mode,
+            base_recalibration_table: None,
+            pair_hmm_batch_size: 0,
+            gpu_pair_hmm: None,
+            read_group_profiles: None,
+            read_compression: ReadCompression::default(),
         };
 
         result.initialize_pcr_error_model();
@@ -166,6 +182,51 @@ impl PairHMMLikelihoodCalculationEngine {
         return result;
     }
 
+    /// Use the given error model to recalibrate read base qualities before they are passed to the
+    /// PairHMM
+    pub fn set_base_recalibration_table(&mut self, table: BaseRecalibrationTable) {
+        self.base_recalibration_table = Some(Arc::new(table));
+    }
+
+    /// Weight the likelihoods of each read by the sequencing platform of its read group
+    pub fn set_read_group_profiles(&mut self, profiles: ReadGroupProfiles) {
+        self.read_group_profiles = Some(Arc::new(profiles));
+    }
+
+    /// Compute the likelihoods of the reads of all samples together in batches of roughly this
+    /// many read and haplotype pairs. 0 computes the likelihoods one sample at a time
+    pub fn set_pair_hmm_batch_size(&mut self, batch_size: usize) {
+        self.pair_hmm_batch_size = batch_size;
+    }
+
+    /// Bin the qualities and pack the bases of the quality modified copies of reads that are
+    /// held until the PairHMM has processed them
+    pub fn set_read_compression(&mut self, read_compression: ReadCompression) {
+        self.read_compression = read_compression;
+    }
+
+    /// Compute read likelihoods on the GPU initialized for the run by
+    /// `PairHMMBackend::initialize`, or with the AVX or scalar PairHMM when there is none
+    pub fn set_gpu_pair_hmm(&mut self, gpu_pair_hmm: Option<Arc<GpuPairHMM>>) {
+        self.gpu_pair_hmm = gpu_pair_hmm;
+    }
+
+    /// The base qualities of a read, recalibrated if an error model is available. Reads clipped
+    /// to the region keep their hard clips, so cycles are measured from the original read start
+    fn recalibrated_base_qualities(&self, read: &BirdToolRead) -> Vec<u8> {
+        let mut read_quals = read.read.qual().to_vec();
+        if let Some(table) = &self.base_recalibration_table {
+            table.recalibrate(
+                read.sample_index,
+                &read.bases[..],
+                &mut read_quals,
+                BaseRecalibrationTable::hard_clips(&read.read.cigar()),
+                read.read.is_reverse(),
+            );
+        }
+        read_quals
+    }
+
     fn initialize_pcr_error_model(&mut self) {
         self.pcr_indel_error_model_cache = vec![0; Self::MAX_REPEAT_LENGTH + 1];
 
@@ -216,11 +277,51 @@ impl PairHMMLikelihoodCalculationEngine {
         // clone so we can borrow haplotypes in pair_hmm
         let mut result = AlleleLikelihoods::new(haplotypes.clone(), samples, per_sample_read_list);
 
-        for i in 0..sample_count {
-            if !result.evidence_by_sample_index.contains_key(&i) {
-                continue;
+        let gpu_pair_hmm = self.gpu_pair_hmm.clone();
+        let cpu_reads_by_sample = match gpu_pair_hmm {
+            Some(gpu_pair_hmm) => {
+                let processed_reads_by_sample =
+                    self.modify_read_qualities_by_sample(sample_count, &mut result);
+                let batch_size = if self.pair_hmm_batch_size == 0 {
+                    usize::MAX
+                } else {
+                    self.pair_hmm_batch_size
+                };
+                match gpu_pair_hmm.compute_log10_likelihoods(
+                    &haplotypes,
+                    &mut result,
+                    &processed_reads_by_sample,
+                    &self.input_score_imputator,
+                    batch_size,
+                ) {
+                    Ok(_) => None,
+                    Err(error) => {
+                        warn!("{}, falling back to AVX PairHMM", error);
+                        Some(processed_reads_by_sample)
+                    }
+                }
             }
-            self.compute_read_likelihoods_in_matrix(i, &mut result, &mut pair_hmm);
+            None if self.pair_hmm_batch_size > 0 => {
+                Some(self.modify_read_qualities_by_sample(sample_count, &mut result))
+            }
+            None => {
+                for i in 0..sample_count {
+                    if !result.evidence_by_sample_index.contains_key(&i) {
+                        continue;
+                    }
+                    self.compute_read_likelihoods_in_matrix(i, &mut result, &mut pair_hmm);
+                }
+                None
+            }
+        };
+
+        if let Some(processed_reads_by_sample) = cpu_reads_by_sample {
+            pair_hmm.compute_log10_likelihoods_batched(
+                &mut result,
+                processed_reads_by_sample,
+                &self.input_score_imputator,
+                self.pair_hmm_batch_size,
+            );
         }
         result.normalize_likelihoods(
             self.log10_global_read_mismapping_rate,
@@ -238,6 +339,12 @@ impl PairHMMLikelihoodCalculationEngine {
             ));
         };
 
+        // weights are applied last so that disqualification only depends on the read itself
+        if let Some(profiles) = &self.read_group_profiles {
+            result.apply_evidence_weights(|read| profiles.weight(read));
+        }
+        result.apply_evidence_weights(SplitAlignmentPolicy::alignment_weight);
+
         return result;
     }
 
@@ -341,6 +448,20 @@ impl PairHMMLikelihoodCalculationEngine {
         );
     }
 
+    /// Applies `modify_read_qualities` to the reads of each sample in the likelihoods
+    fn modify_read_qualities_by_sample(
+        &self,
+        sample_count: usize,
+        likelihoods: &mut AlleleLikelihoods<Haplotype<SimpleInterval>>,
+    ) -> Vec<(usize, Vec<BirdToolRead>)> {
+        (0..sample_count)
+            .filter_map(|i| {
+                let reads = likelihoods.evidence_by_sample_index.get_mut(&i)?;
+                Some((i, self.modify_read_qualities(reads)))
+            })
+            .collect()
+    }
+
     /**
      * Pre-processing of the reads to be evaluated at the current location from the current sample.
      * We apply the PCR Error Model, and cap the minimum base, insertion, and deletion qualities of each read.
@@ -355,7 +476,7 @@ impl PairHMMLikelihoodCalculationEngine {
             .map(|read| {
                 if self.modify_soft_clipped_bases {
                     // let bases = &read.bases[..];
-                    let mut read_quals = read.read.qual().to_vec();
+                    let mut read_quals = self.recalibrated_base_qualities(read);
                     let mut read_ins_quals = ReadUtils::get_base_insertion_qualities(read);
                     let mut read_del_quals = ReadUtils::get_base_deletion_qualities(read);
                     self.apply_pcr_error_model(
@@ -379,19 +500,21 @@ impl PairHMMLikelihoodCalculationEngine {
                         read_quals.clone(),
                     );
                     // Create a new copy of the read and sets its base qualities to the modified versions.
-                    Self::create_quality_modified_read(
+                    let mut processed_read = Self::create_quality_modified_read(
                         &read,
                         &read.bases[..],
                         read_quals,
                         read_ins_quals,
                         read_del_quals,
-                    )
+                    );
+                    processed_read.compress(&self.read_compression);
+                    processed_read
                 } else {
                     let maybe_unclipped =
                         ReadClipper::new(read.clone()).hard_clip_soft_clipped_bases();
                     let bases = maybe_unclipped.seq();
 
-                    let mut read_quals = maybe_unclipped.read.qual().to_vec();
+                    let mut read_quals = self.recalibrated_base_qualities(&maybe_unclipped);
                     let mut read_ins_quals =
                         ReadUtils::get_base_insertion_qualities(&maybe_unclipped);
                     let mut read_del_quals =
@@ -413,13 +536,15 @@ impl PairHMMLikelihoodCalculationEngine {
                         read_quals.clone(),
                     );
                     // Create a new copy of the read and sets its base qualities to the modified versions.
-                    Self::create_quality_modified_read(
+                    let mut processed_read = Self::create_quality_modified_read(
                         &read,
                         bases,
                         read_quals,
                         read_ins_quals,
                         read_del_quals,
-                    )
+                    );
+                    processed_read.compress(&self.read_compression);
+                    processed_read
                 }
             })
             .collect()
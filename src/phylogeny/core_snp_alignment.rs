@@ -0,0 +1,234 @@
+use ndarray::Array2;
+use rust_htslib::bcf::Read;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::concordance::genotype_concordance::SiteGenotypes;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// An alignment of the bases at SNP sites that are variable between samples or strains. Sites
+/// where a taxon has no confident call hold `N`, and only sites where at most `max_missing` of
+/// the taxa are missing are kept, so the default of 0 gives a strict core SNP alignment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreSnpAlignment {
+    pub taxa: Vec<String>,
+    // contig name and 0-based position of each column
+    pub sites: Vec<(String, usize)>,
+    pub sequences: Vec<Vec<u8>>,
+}
+
+impl CoreSnpAlignment {
+    pub const MISSING: u8 = b'N';
+
+    pub fn new(taxa: Vec<String>) -> Self {
+        let sequences = vec![Vec::new(); taxa.len()];
+        Self {
+            taxa,
+            sites: Vec::new(),
+            sequences,
+        }
+    }
+
+    /// Adds a column if it is a variable SNP with few enough missing taxa. Returns whether the
+    /// column was added
+    pub fn add_site(
+        &mut self,
+        contig: &str,
+        position: usize,
+        bases: &[Option<u8>],
+        max_missing: f64,
+    ) -> bool {
+        let called = bases.iter().flatten().collect::<Vec<&u8>>();
+        let missing = bases.len() - called.len();
+        if called.is_empty()
+            || missing as f64 > max_missing * bases.len() as f64
+            || called.iter().all(|base| *base == called[0])
+        {
+            return false;
+        }
+
+        for (sequence, base) in self.sequences.iter_mut().zip(bases.iter()) {
+            sequence.push(base.unwrap_or(Self::MISSING));
+        }
+        self.sites.push((contig.to_string(), position));
+        true
+    }
+
+    /// Aligns the consensus base of each sample. Indels and sites where a sample's consensus
+    /// allele is not a single base are not SNPs, and samples without the depth to make a call
+    /// are missing
+    pub fn from_sample_genotypes(genotypes: &SiteGenotypes, max_missing: f64) -> Self {
+        let mut alignment = Self::new(genotypes.sample_names.clone());
+        let sites = genotypes.sites.iter().collect::<BTreeMap<_, _>>();
+        for ((contig, position, reference), sample_alleles) in sites {
+            if reference.len() != 1 {
+                continue;
+            }
+            let bases = sample_alleles
+                .iter()
+                .map(|allele| match allele {
+                    Some(bases) if bases.len() == 1 => Some(bases[0].to_ascii_uppercase()),
+                    _ => None,
+                })
+                .collect::<Vec<Option<u8>>>();
+            alignment.add_site(contig, *position, &bases, max_missing);
+        }
+        alignment
+    }
+
+    /// Aligns the strains reported in the ST INFO field of a lorikeet genotype VCF. A strain
+    /// carries the first alternate allele of a record if it is listed in the record's ST field
+    /// and the reference allele otherwise
+    pub fn from_strain_vcf(vcf_path: &str) -> Result<Self, BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let header = reader.header().clone();
+
+        let mut records = Vec::new();
+        let mut n_strains = 0;
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            let alleles = record.alleles();
+            if alleles.len() < 2 || alleles[0].len() != 1 || alleles[1].len() != 1 {
+                continue;
+            }
+            let strains = match record
+                .info(VariantAnnotations::Strain.to_key().as_bytes())
+                .integer()
+            {
+                Ok(Some(strains)) => strains
+                    .iter()
+                    .filter(|strain| **strain >= 0)
+                    .map(|strain| *strain as usize)
+                    .collect::<Vec<usize>>(),
+                _ => Vec::new(),
+            };
+            n_strains = strains.iter().map(|strain| strain + 1).fold(n_strains, usize::max);
+
+            let contig = match record.rid() {
+                Some(rid) => String::from_utf8_lossy(header.rid2name(rid).map_err(|e| {
+                    BirdToolError::IOError(format!("Invalid contig in {}: {}", vcf_path, e))
+                })?)
+                .to_string(),
+                None => continue,
+            };
+            records.push((
+                contig,
+                record.pos() as usize,
+                alleles[0][0].to_ascii_uppercase(),
+                alleles[1][0].to_ascii_uppercase(),
+                strains,
+            ));
+        }
+
+        let mut alignment = Self::new(
+            (0..n_strains)
+                .map(|strain| format!("strain_{}", strain))
+                .collect(),
+        );
+        for (contig, position, reference, alternate, strains) in records {
+            let bases = (0..n_strains)
+                .map(|strain| {
+                    if strains.contains(&strain) {
+                        Some(alternate)
+                    } else {
+                        Some(reference)
+                    }
+                })
+                .collect::<Vec<Option<u8>>>();
+            alignment.add_site(&contig, position, &bases, 0.0);
+        }
+        Ok(alignment)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// The proportion of differing bases between each pair of taxa, over the columns where
+    /// neither taxon is missing. Pairs without any such columns are given a distance of 1
+    pub fn distance_matrix(&self) -> Array2<f64> {
+        let n_taxa = self.taxa.len();
+        let mut distances = Array2::<f64>::zeros((n_taxa, n_taxa));
+        for i in 0..n_taxa {
+            for j in (i + 1)..n_taxa {
+                let (compared, differing) = self.sequences[i]
+                    .iter()
+                    .zip(self.sequences[j].iter())
+                    .filter(|(a, b)| **a != Self::MISSING && **b != Self::MISSING)
+                    .fold((0, 0), |(compared, differing), (a, b)| {
+                        (compared + 1, differing + (a != b) as usize)
+                    });
+                let distance = if compared == 0 {
+                    1.0
+                } else {
+                    differing as f64 / compared as f64
+                };
+                distances[[i, j]] = distance;
+                distances[[j, i]] = distance;
+            }
+        }
+        distances
+    }
+
+    pub fn write_fasta(&self, file_name: &str) -> Result<(), BirdToolError> {
+        let (mut writer, write_error) = Self::create(file_name)?;
+        for (taxon, sequence) in self.taxa.iter().zip(self.sequences.iter()) {
+            writeln!(writer, ">{}", taxon).map_err(write_error)?;
+            writer.write_all(sequence).map_err(write_error)?;
+            writeln!(writer).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the alignment in relaxed sequential PHYLIP format, which allows taxon names longer
+    /// than 10 characters as long as they contain no whitespace
+    pub fn write_phylip(&self, file_name: &str) -> Result<(), BirdToolError> {
+        let (mut writer, write_error) = Self::create(file_name)?;
+        writeln!(writer, "{} {}", self.taxa.len(), self.len()).map_err(write_error)?;
+        for (taxon, sequence) in self.taxa.iter().zip(self.sequences.iter()) {
+            write!(writer, "{} ", Self::phylip_name(taxon)).map_err(write_error)?;
+            writer.write_all(sequence).map_err(write_error)?;
+            writeln!(writer).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the contig and 1-based position of each column of the alignment
+    pub fn write_sites(&self, file_name: &str) -> Result<(), BirdToolError> {
+        let (mut writer, write_error) = Self::create(file_name)?;
+        writeln!(writer, "column\tcontig\tposition").map_err(write_error)?;
+        for (column, (contig, position)) in self.sites.iter().enumerate() {
+            writeln!(writer, "{}\t{}\t{}", column + 1, contig, position + 1).map_err(write_error)?;
+        }
+        Ok(())
+    }
+
+    fn phylip_name(taxon: &str) -> String {
+        taxon
+            .chars()
+            .map(|c| if c.is_whitespace() { '_' } else { c })
+            .collect()
+    }
+
+    fn create(
+        file_name: &str,
+    ) -> Result<(BufWriter<File>, impl Fn(std::io::Error) -> BirdToolError + '_), BirdToolError>
+    {
+        let file = File::create(file_name).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {}", file_name, e))
+        })?;
+        let write_error = move |e: std::io::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", file_name, e))
+        };
+        Ok((BufWriter::new(file), write_error))
+    }
+}
@@ -0,0 +1,3 @@
+pub mod core_snp_alignment;
+pub mod neighbor_joining;
+pub mod upgma;
@@ -0,0 +1,81 @@
+use ndarray::Array2;
+
+/// Builds a tree from a distance matrix with the neighbor joining algorithm of Saitou and Nei
+/// (1987) and returns it in Newick format. Negative branch lengths are set to 0. The tree is
+/// unrooted; the final two nodes are joined at the midpoint of the distance between them.
+pub fn neighbor_joining(names: &[String], distances: &Array2<f64>) -> String {
+    let mut nodes = names
+        .iter()
+        .map(|name| newick_name(name))
+        .collect::<Vec<String>>();
+    let mut d = (0..nodes.len())
+        .map(|i| (0..nodes.len()).map(|j| distances[[i, j]]).collect::<Vec<f64>>())
+        .collect::<Vec<Vec<f64>>>();
+
+    while nodes.len() > 2 {
+        let n = nodes.len();
+        let row_sums = d.iter().map(|row| row.iter().sum::<f64>()).collect::<Vec<f64>>();
+
+        let (mut best_i, mut best_j, mut best_q) = (0, 1, f64::INFINITY);
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let q = (n - 2) as f64 * d[i][j] - row_sums[i] - row_sums[j];
+                if q < best_q {
+                    best_i = i;
+                    best_j = j;
+                    best_q = q;
+                }
+            }
+        }
+
+        let distance = d[best_i][best_j];
+        let length_i = (0.5 * distance
+            + (row_sums[best_i] - row_sums[best_j]) / (2.0 * (n - 2) as f64))
+            .max(0.0);
+        let length_j = (distance - length_i).max(0.0);
+        let joined = format!(
+            "({}:{:.6},{}:{:.6})",
+            nodes[best_i], length_i, nodes[best_j], length_j
+        );
+        let joined_distances = (0..n)
+            .filter(|k| *k != best_i && *k != best_j)
+            .map(|k| (0.5 * (d[best_i][k] + d[best_j][k] - distance)).max(0.0))
+            .collect::<Vec<f64>>();
+
+        // best_j > best_i, so removing it first leaves best_i in place
+        for index in [best_j, best_i] {
+            nodes.remove(index);
+            d.remove(index);
+            d.iter_mut().for_each(|row| {
+                row.remove(index);
+            });
+        }
+        for (row, joined_distance) in d.iter_mut().zip(joined_distances.iter()) {
+            row.push(*joined_distance);
+        }
+        let mut joined_row = joined_distances;
+        joined_row.push(0.0);
+        d.push(joined_row);
+        nodes.push(joined);
+    }
+
+    match nodes.len() {
+        0 => ";".to_string(),
+        1 => format!("{};", nodes[0]),
+        _ => {
+            let half = d[0][1].max(0.0) / 2.0;
+            format!("({}:{:.6},{}:{:.6});", nodes[0], half, nodes[1], half)
+        }
+    }
+}
+
+/// Replaces the characters with special meaning in Newick format
+pub(crate) fn newick_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '(' | ')' | '[' | ']' | ':' | ';' | ',' | '\'' => '_',
+            c if c.is_whitespace() => '_',
+            c => c,
+        })
+        .collect()
+}
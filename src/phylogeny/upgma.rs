@@ -0,0 +1,74 @@
+use ndarray::Array2;
+
+use crate::phylogeny::neighbor_joining::newick_name;
+
+/// Builds a rooted, ultrametric tree from a distance matrix with UPGMA, joining the closest pair
+/// of clusters and averaging their distances to every other cluster by cluster size, and returns
+/// it in Newick format. Of tied pairs the first found is joined, so the tree is stable between
+/// runs.
+pub fn upgma(names: &[String], distances: &Array2<f64>) -> String {
+    // the Newick string, number of leaves and height of each cluster
+    let mut clusters = names
+        .iter()
+        .map(|name| (newick_name(name), 1usize, 0.0f64))
+        .collect::<Vec<(String, usize, f64)>>();
+    let mut d = (0..clusters.len())
+        .map(|i| {
+            (0..clusters.len())
+                .map(|j| distances[[i, j]])
+                .collect::<Vec<f64>>()
+        })
+        .collect::<Vec<Vec<f64>>>();
+
+    while clusters.len() > 1 {
+        let n = clusters.len();
+        let (mut best_i, mut best_j, mut best_d) = (0, 1, f64::INFINITY);
+        for i in 0..n {
+            for j in (i + 1)..n {
+                if d[i][j] < best_d {
+                    best_i = i;
+                    best_j = j;
+                    best_d = d[i][j];
+                }
+            }
+        }
+
+        let height = (best_d / 2.0).max(0.0);
+        let (node_i, size_i, height_i) = &clusters[best_i];
+        let (node_j, size_j, height_j) = &clusters[best_j];
+        let joined = format!(
+            "({}:{:.6},{}:{:.6})",
+            node_i,
+            (height - height_i).max(0.0),
+            node_j,
+            (height - height_j).max(0.0)
+        );
+        let (size_i, size_j) = (*size_i as f64, *size_j as f64);
+        let joined_distances = (0..n)
+            .filter(|k| *k != best_i && *k != best_j)
+            .map(|k| (size_i * d[best_i][k] + size_j * d[best_j][k]) / (size_i + size_j))
+            .collect::<Vec<f64>>();
+        let joined_size = clusters[best_i].1 + clusters[best_j].1;
+
+        // best_j > best_i, so removing it first leaves best_i in place
+        for index in [best_j, best_i] {
+            clusters.remove(index);
+            d.remove(index);
+            d.iter_mut().for_each(|row| {
+                row.remove(index);
+            });
+        }
+        for (row, joined_distance) in d.iter_mut().zip(joined_distances.iter()) {
+            row.push(*joined_distance);
+        }
+        let mut joined_row = joined_distances;
+        joined_row.push(0.0);
+        d.push(joined_row);
+        clusters.push((joined, joined_size, height));
+    }
+
+    match clusters.pop() {
+        Some((node, _, _)) => format!("{};", node),
+        None => ";".to_string(),
+    }
+}
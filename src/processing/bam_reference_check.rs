@@ -0,0 +1,351 @@
+use needletail::parse_fastx_file;
+use rust_htslib::bam::{self, Read};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::processing::dry_run::ReferenceContigs;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_provenance::VcfProvenance;
+
+/// A reference sequence listed in the header of a BAM file
+#[derive(Debug, Clone, PartialEq)]
+pub struct BamContig {
+    pub name: String,
+    pub length: u64,
+    // the M5 checksum of the @SQ line, if given
+    pub md5: Option<String>,
+}
+
+impl BamContig {
+    pub fn new(name: &str, length: u64, md5: Option<&str>) -> Self {
+        Self {
+            name: name.to_string(),
+            length,
+            md5: md5.map(|md5| md5.to_ascii_lowercase()),
+        }
+    }
+
+    /// The reference sequences of the header of a BAM file, in header order
+    pub fn read_header(bam_file: &str) -> Result<Vec<Self>, BirdToolError> {
+        let reader = bam::Reader::from_path(bam_file).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to open BAM file {}: {}", bam_file, e))
+        })?;
+        let header = reader.header();
+        // M5 is only available from the text of the header
+        let checksums = bam::Header::from_template(header)
+            .to_hashmap()
+            .remove("SQ")
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|record| Some((record.get("SN")?.clone(), record.get("M5")?.clone())))
+            .collect::<HashMap<String, String>>();
+
+        Ok((0..header.target_count())
+            .map(|tid| {
+                let name = String::from_utf8_lossy(header.tid2name(tid)).to_string();
+                let md5 = checksums.get(&name).map(|md5| md5.as_str());
+                Self::new(&name, header.target_len(tid).unwrap_or(0), md5)
+            })
+            .collect())
+    }
+}
+
+/// A difference between the reference sequences of a BAM file and the reference genomes
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContigMismatch {
+    // a contig of a genome aligned to in the BAM file is not in the BAM header
+    MissingFromBam {
+        contig: String,
+    },
+    // a contig named after a reference genome is not in that genome
+    MissingFromReference {
+        contig: String,
+    },
+    Length {
+        contig: String,
+        bam_length: u64,
+        reference_length: u64,
+    },
+    Checksum {
+        contig: String,
+        bam_md5: String,
+        reference_md5: String,
+    },
+}
+
+impl ContigMismatch {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::MissingFromBam { contig } => {
+                format!("{}: in the reference but not in the BAM header", contig)
+            }
+            Self::MissingFromReference { contig } => {
+                format!("{}: in the BAM header but not in the reference", contig)
+            }
+            Self::Length {
+                contig,
+                bam_length,
+                reference_length,
+            } => format!(
+                "{}: {} bp in the BAM header but {} bp in the reference",
+                contig, bam_length, reference_length
+            ),
+            Self::Checksum {
+                contig,
+                bam_md5,
+                reference_md5,
+            } => format!(
+                "{}: M5 {} in the BAM header but {} in the reference",
+                contig, bam_md5, reference_md5
+            ),
+        }
+    }
+}
+
+/// How the reference sequences of one BAM file compare to the reference genomes
+#[derive(Debug, Clone, PartialEq)]
+pub struct BamReferenceReport {
+    pub bam_file: String,
+    // reference contigs found in the BAM header
+    pub shared: usize,
+    pub mismatches: Vec<ContigMismatch>,
+}
+
+impl BamReferenceReport {
+    pub fn is_consistent(&self) -> bool {
+        self.shared > 0 && self.mismatches.is_empty()
+    }
+
+    /// One line per problem, for reporting to the user
+    pub fn describe(&self) -> Vec<String> {
+        if self.shared == 0 {
+            return vec![format!(
+                "BAM file {} shares no contigs with the reference genomes",
+                self.bam_file
+            )];
+        }
+        self.mismatches
+            .iter()
+            .map(|mismatch| format!("{}: {}", self.bam_file, mismatch.describe()))
+            .collect()
+    }
+}
+
+/// Checks that the @SQ lines of the input BAM files match the reference genomes in length and, when
+/// given, MD5. Contigs match by their own name or their name in the concatenated reference. Any
+/// difference is a configuration error. Skipped with --skip-reference-check.
+#[derive(Debug, Clone)]
+pub struct BamReferenceCheck {
+    references: Vec<ReferenceContigs>,
+    // joins genome and contig names in the concatenated reference
+    separator: GenomeSeparator,
+    // MD5 of each contig by its name in the concatenated reference, computed on first use
+    checksums: Option<HashMap<String, String>>,
+}
+
+impl BamReferenceCheck {
+    pub fn new(references: Vec<ReferenceContigs>, separator: GenomeSeparator) -> Self {
+        Self {
+            references,
+            separator,
+            checksums: None,
+        }
+    }
+
+    /// Checks the BAM files of a run, unless --skip-reference-check is given
+    pub fn check_args(
+        args: &clap::ArgMatches,
+        references: &[&str],
+        separator: GenomeSeparator,
+    ) -> Result<(), BirdToolError> {
+        if args
+            .try_get_one::<bool>("skip-reference-check")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        let mut bam_files = Vec::new();
+        for id in ["bam-files", "longread-bam-files"] {
+            if let Some(paths) = args.try_get_many::<String>(id).ok().flatten() {
+                bam_files.extend(paths.cloned());
+            }
+        }
+        if bam_files.is_empty() {
+            return Ok(());
+        }
+
+        let references = references
+            .iter()
+            .map(|path| ReferenceContigs::read(path))
+            .collect::<Result<Vec<ReferenceContigs>, BirdToolError>>()?;
+        let mut check = Self::new(references, separator);
+        let mut problems = Vec::new();
+        for bam_file in bam_files.iter() {
+            let report = check.check_bam(bam_file)?;
+            if report.is_consistent() {
+                debug!("{} matches the reference genomes", bam_file);
+            } else {
+                problems.extend(report.describe());
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        Err(BirdToolError::ConfigError(format!(
+            "BAM files do not match the reference genomes, they may have been aligned to a \
+            different version of the reference. Use --skip-reference-check to run anyway.\n{}",
+            problems.join("\n")
+        )))
+    }
+
+    pub fn check_bam(&mut self, bam_file: &str) -> Result<BamReferenceReport, BirdToolError> {
+        if !Path::new(bam_file).exists() {
+            return Err(BirdToolError::ConfigError(format!(
+                "BAM file {} does not exist",
+                bam_file
+            )));
+        }
+        let bam_contigs = BamContig::read_header(bam_file)?;
+        if bam_contigs.iter().any(|contig| contig.md5.is_some()) && self.checksums.is_none() {
+            self.checksums = Some(self.reference_checksums()?);
+        }
+        let empty = HashMap::new();
+        let checksums = self.checksums.as_ref().unwrap_or(&empty);
+        Ok(Self::compare(
+            bam_file,
+            &self.references,
+            checksums,
+            &bam_contigs,
+            &self.separator,
+        ))
+    }
+
+    /// The MD5 of every contig of the reference genomes, by its name in the concatenated
+    /// reference
+    fn reference_checksums(&self) -> Result<HashMap<String, String>, BirdToolError> {
+        let mut checksums = HashMap::new();
+        for reference in self.references.iter() {
+            let mut reader = parse_fastx_file(Path::new(&reference.path)).map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to read reference {}: {}",
+                    reference.path, e
+                ))
+            })?;
+            while let Some(record) = reader.next() {
+                let record = record.map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Failed to parse record in {}: {}",
+                        reference.path, e
+                    ))
+                })?;
+                let contig_name = String::from_utf8_lossy(record.id())
+                    .split_whitespace()
+                    .next()
+                    .unwrap_or("")
+                    .to_string();
+                checksums.insert(
+                    self.separator.join(&reference.genome_name, &contig_name),
+                    VcfProvenance::contig_md5(&record.seq()),
+                );
+            }
+        }
+        Ok(checksums)
+    }
+
+    /// Compares the reference sequences of a BAM header to the reference genomes. `checksums`
+    /// holds the MD5 of each reference contig by its name in the concatenated reference
+    pub fn compare(
+        bam_file: &str,
+        references: &[ReferenceContigs],
+        checksums: &HashMap<String, String>,
+        bam_contigs: &[BamContig],
+        separator: &GenomeSeparator,
+    ) -> BamReferenceReport {
+        // (genome, contig, length) by the plain and the concatenated name of each contig
+        let mut contigs = HashMap::new();
+        for reference in references.iter() {
+            for (contig_name, length) in reference.contigs.iter() {
+                let contig = (
+                    reference.genome_name.as_str(),
+                    contig_name.as_str(),
+                    *length,
+                );
+                contigs.entry(contig_name.clone()).or_insert(contig);
+                contigs.insert(
+                    separator.join(&reference.genome_name, contig_name),
+                    contig,
+                );
+            }
+        }
+        let genome_names = references
+            .iter()
+            .map(|reference| reference.genome_name.as_str())
+            .collect::<HashSet<&str>>();
+
+        let mut mismatches = Vec::new();
+        let mut found = HashSet::new();
+        for bam_contig in bam_contigs.iter() {
+            let (genome_name, contig_name, length) = match contigs.get(&bam_contig.name) {
+                Some(contig) => *contig,
+                None => {
+                    if let Some((genome_name, _)) = separator.split(&bam_contig.name) {
+                        if genome_names.contains(genome_name.as_ref()) {
+                            mismatches.push(ContigMismatch::MissingFromReference {
+                                contig: bam_contig.name.clone(),
+                            });
+                        }
+                    }
+                    continue;
+                }
+            };
+            found.insert((genome_name, contig_name));
+
+            if bam_contig.length != length {
+                mismatches.push(ContigMismatch::Length {
+                    contig: bam_contig.name.clone(),
+                    bam_length: bam_contig.length,
+                    reference_length: length,
+                });
+                continue;
+            }
+            let reference_md5 = checksums.get(&separator.join(genome_name, contig_name));
+            if let (Some(bam_md5), Some(reference_md5)) = (&bam_contig.md5, reference_md5) {
+                if bam_md5 != reference_md5 {
+                    mismatches.push(ContigMismatch::Checksum {
+                        contig: bam_contig.name.clone(),
+                        bam_md5: bam_md5.clone(),
+                        reference_md5: reference_md5.clone(),
+                    });
+                }
+            }
+        }
+
+        // the genomes the BAM file was aligned to should have all of their contigs in it
+        let aligned_genomes = found
+            .iter()
+            .map(|(genome_name, _)| *genome_name)
+            .collect::<HashSet<&str>>();
+        for reference in references.iter() {
+            if !aligned_genomes.contains(reference.genome_name.as_str()) {
+                continue;
+            }
+            for (contig_name, _) in reference.contigs.iter() {
+                if !found.contains(&(reference.genome_name.as_str(), contig_name.as_str())) {
+                    mismatches.push(ContigMismatch::MissingFromBam {
+                        contig: separator.join(&reference.genome_name, contig_name),
+                    });
+                }
+            }
+        }
+
+        BamReferenceReport {
+            bam_file: bam_file.to_string(),
+            shared: found.len(),
+            mismatches,
+        }
+    }
+}
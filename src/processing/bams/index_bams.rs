@@ -1,27 +1,36 @@
 use glob::glob;
 use indicatif::{ProgressBar, ProgressStyle};
 // use rayon::prelude::*;
-use rust_htslib::bam;
+use rust_htslib::bam::{self, Read};
 use std::collections::HashMap;
 use std::path::Path;
 use std::result::Result::Err;
 use std::time::Duration;
 
 use crate::bam_parsing::bam_generator::*;
+use crate::processing::bams::multi_mapping::{MultiMappingEm, MultiMappingReassignment};
+use crate::processing::bams::read_sharing::{
+    CompetitiveMappingReport, ReadSharingCounter, SampleReadSharing,
+};
 use crate::reference::reference_reader_utils::GenomesAndContigs;
 use crate::utils::errors::BirdToolError;
 
 /// Ensures mapping is completed for provided bams. If multiple references are provided and
 /// the user has asked to run genomes in parallel, then the bams are split per reference to
-/// avoid file locking when reading bams in parallel.
+/// avoid file locking when reading bams in parallel. Reads mapping equally well to several
+/// genomes are redistributed between them while splitting if `reassignment` is given.
+/// Returns the reads shared between genomes in each split BAM, which is empty if the BAMs
+/// were not split.
 pub fn finish_bams<R: NamedBamReader, G: NamedBamReaderGenerator<R>>(
     bams: Vec<G>,
     n_threads: usize,
     references: &GenomesAndContigs,
     split_bams: bool,
     mapping: bool,
-) -> Result<(), BirdToolError> {
+    reassignment: Option<&MultiMappingReassignment>,
+) -> Result<CompetitiveMappingReport, BirdToolError> {
     let mut record: bam::Record = bam::Record::new();
+    let mut competitive_mapping = CompetitiveMappingReport::new(&references.genomes);
 
     // progress bar
     let sty = match ProgressStyle::default_bar()
@@ -45,16 +54,22 @@ pub fn finish_bams<R: NamedBamReader, G: NamedBamReaderGenerator<R>>(
         let path = bam.path().to_string();
         let stoit_name = bam.name().to_string().replace("/", ".");
 
-        pb1.set_message(format!(
-            "Processing sample: {}",
-            match &stoit_name[..4] {
-                ".tmp" => &stoit_name[15..],
-                _ => &stoit_name,
-            },
-        ));
+        let sample_name = match &stoit_name[..4] {
+            ".tmp" => &stoit_name[15..],
+            _ => &stoit_name,
+        };
+        pb1.set_message(format!("Processing sample: {}", sample_name));
 
         if split_bams {
-            split_bams_to_references(bam, references, &path, n_threads)?;
+            let read_sharing = split_bams_to_references(
+                bam,
+                references,
+                &path,
+                n_threads,
+                reassignment,
+                sample_name,
+            )?;
+            competitive_mapping.add_sample(read_sharing);
         } else if mapping {
             while bam.read(&mut record).is_some() {
                 continue;
@@ -76,17 +91,20 @@ pub fn finish_bams<R: NamedBamReader, G: NamedBamReaderGenerator<R>>(
         pb1.inc(1);
     }
     pb1.finish_with_message(format!("Reads and BAM files processed..."));
-    Ok(())
+    Ok(competitive_mapping)
 }
 
 /// Splits bams by reference if the user has requested split bams.
 /// This is done to avoid file locking when reading bams in parallel.
+/// Returns the reads of the sample shared between genomes.
 fn split_bams_to_references<R: NamedBamReader>(
     mut bam_generator: R,
     references: &GenomesAndContigs,
     bam_path: &str,
-    n_threads: usize
-) -> Result<(), BirdToolError> {
+    n_threads: usize,
+    reassignment: Option<&MultiMappingReassignment>,
+    sample_name: &str,
+) -> Result<SampleReadSharing, BirdToolError> {
     let mut bam_writer_map: HashMap<String, bam::Writer> = HashMap::with_capacity(references.genomes.len());
     let bam_header = bam_generator.header();
     let new_header = bam::Header::from_template(bam_header);
@@ -115,18 +133,54 @@ fn split_bams_to_references<R: NamedBamReader>(
 
     let mut record: bam::Record = bam::Record::new();
 
+    // MAPQ 0 alignments are held back until every uniquely mapped read has been counted
+    let mut em = MultiMappingEm::new(references.genomes.len());
+    let mut read_sharing = ReadSharingCounter::new(references.genomes.len());
+    let mut held_back: HashMap<(Vec<u8>, bool), Vec<(usize, bam::Record)>> = HashMap::new();
+    let genome_indices = references
+        .genomes
+        .iter()
+        .enumerate()
+        .map(|(index, genome)| (genome.as_str(), index))
+        .collect::<HashMap<&str, usize>>();
+
     while bam_generator.read(&mut record).is_some() {
         let record_tid = record.tid();
         if record_tid < 0 {
             continue;
         }
-        let ref_name = std::str::from_utf8(bam_generator.header().tid2name(record_tid as u32)).expect("Cannot read reference name from bam file").split('~').next().unwrap();
+        let ref_name = std::str::from_utf8(bam_generator.header().tid2name(record_tid as u32)).expect("Cannot read reference name from bam file");
+        let ref_name = references.separator.genome(ref_name);
+        let genome_index = genome_indices[ref_name.as_ref()];
+        read_sharing.add(&record, genome_index);
+
+        if reassignment.is_some() {
+            if MultiMappingReassignment::is_candidate(&record) {
+                held_back
+                    .entry(MultiMappingReassignment::segment_key(&record))
+                    .or_insert_with(Vec::new)
+                    .push((genome_index, record.clone()));
+                continue;
+            } else if !record.is_secondary() && !record.is_supplementary() {
+                em.add_unique(genome_index);
+            }
+        }
 
         let writer = bam_writer_map.get_mut(ref_name).unwrap();
         writer.write(&record).unwrap();
     }
 
     bam_generator.finish();
+
+    // reassigned reads are merged into the sorted output once the writers are closed
+    let mut reassigned: Vec<Vec<bam::Record>> = vec![Vec::new(); references.genomes.len()];
+    if let Some(reassignment) = reassignment {
+        let n_held_back = held_back.len();
+        for (genome_index, record) in reassignment.reassign(held_back, em) {
+            reassigned[genome_index].push(record);
+        }
+        debug!("Resolved {} reads with MAPQ 0 alignments", n_held_back);
+    }
     
     let mut paths_to_index = Vec::with_capacity(bam_writer_map.len());
     // build indices for writers in two loops
@@ -141,22 +195,71 @@ fn split_bams_to_references<R: NamedBamReader>(
             "{}/{}/{}",
             path_prefix, ref_name, path_suffix
         );
-        paths_to_index.push(path);
-        
+        paths_to_index.push((genome_indices[ref_name.as_str()], path));
+
     }
 
-    // build indices for writers
-    for path in paths_to_index.into_iter() {
+    // merge in the reassigned reads and build indices for writers
+    for (genome_index, path) in paths_to_index.into_iter() {
+        merge_into_sorted_bam(&path, std::mem::take(&mut reassigned[genome_index]))?;
         bam::index::build(
             &path,
             Some(&format!("{}.bai", path)),
             bam::index::Type::Bai,
             n_threads as u32,
         )
-        .unwrap_or_else(|_| panic!("Unable to index bam at {}", &path));
+        .map_err(|_| BirdToolError::IOError(format!("Unable to index bam at {}", &path)))?;
     }
 
-    Ok(())
+    Ok(read_sharing.summarise(sample_name))
+}
+
+/// Merges records into a coordinate sorted BAM file, so that records added after the file was
+/// written still leave it sorted and it can be indexed. The file is replaced by the merged file
+pub fn merge_into_sorted_bam(
+    path: &str,
+    mut records: Vec<bam::Record>,
+) -> Result<(), BirdToolError> {
+    if records.is_empty() {
+        return Ok(());
+    }
+    records.sort_by_key(coordinate_key);
+
+    let merged_path = format!("{}.merged", path);
+    {
+        let mut reader = bam::Reader::from_path(path)
+            .map_err(|_| BirdToolError::IOError(format!("Unable to read bam at {}", path)))?;
+        let header = bam::Header::from_template(reader.header());
+        let mut writer =
+            bam::Writer::from_path(&merged_path, &header, bam::Format::Bam).map_err(|_| {
+                BirdToolError::IOError(format!("Unable to write bam at {}", &merged_path))
+            })?;
+        let write_error =
+            |_| BirdToolError::IOError(format!("Unable to write bam at {}", &merged_path));
+
+        let mut pending = records.into_iter().peekable();
+        let mut record = bam::Record::new();
+        while let Some(result) = reader.read(&mut record) {
+            result
+                .map_err(|_| BirdToolError::IOError(format!("Unable to read bam at {}", path)))?;
+            let key = coordinate_key(&record);
+            while let Some(next) = pending.next_if(|next| coordinate_key(next) <= key) {
+                writer.write(&next).map_err(write_error)?;
+            }
+            writer.write(&record).map_err(write_error)?;
+        }
+        for next in pending {
+            writer.write(&next).map_err(write_error)?;
+        }
+    }
+
+    std::fs::rename(&merged_path, path)
+        .map_err(|_| BirdToolError::IOError(format!("Unable to replace bam at {}", path)))
+}
+
+// unmapped reads without a reference come last in coordinate sorted files
+fn coordinate_key(record: &bam::Record) -> (u32, i64) {
+    (record.tid() as u32, record.pos())
 }
 
 pub fn recover_bams(
@@ -1 +1,3 @@
 pub mod index_bams;
+pub mod multi_mapping;
+pub mod read_sharing;
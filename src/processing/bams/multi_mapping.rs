@@ -0,0 +1,259 @@
+use bio::alphabets::dna;
+use rust_htslib::bam::record::{Aux, Record};
+use std::collections::HashMap;
+
+use crate::reads::cigar_utils::CigarUtils;
+
+/// Expectation maximisation of genome abundances from reads that map uniquely to one genome and
+/// reads that map equally well to several. Multi-mapped reads are grouped into equivalence classes
+/// by the set of genomes they map to, so the cost of each iteration depends on the number of
+/// distinct genome sets rather than the number of reads.
+#[derive(Debug, Clone)]
+pub struct MultiMappingEm {
+    unique_counts: Vec<f64>,
+    classes: HashMap<Vec<usize>, f64>,
+}
+
+impl MultiMappingEm {
+    pub fn new(n_genomes: usize) -> Self {
+        Self {
+            unique_counts: vec![0.0; n_genomes],
+            classes: HashMap::new(),
+        }
+    }
+
+    pub fn add_unique(&mut self, genome: usize) {
+        self.unique_counts[genome] += 1.0;
+    }
+
+    /// Adds a read that maps equally well to each of the given genomes
+    pub fn add_multi(&mut self, mut genomes: Vec<usize>) {
+        genomes.sort_unstable();
+        genomes.dedup();
+        if genomes.len() == 1 {
+            self.add_unique(genomes[0]);
+        } else if !genomes.is_empty() {
+            *self.classes.entry(genomes).or_insert(0.0) += 1.0;
+        }
+    }
+
+    /// Estimates the relative abundance of each genome. Abundances start from the unique counts,
+    /// with a pseudocount so that genomes without unique reads can still take a share of the
+    /// multi-mapped reads, and are iterated until no abundance changes by more than `tolerance`
+    pub fn estimate(&self, max_iterations: usize, tolerance: f64) -> Vec<f64> {
+        let n_genomes = self.unique_counts.len();
+        let total = self.unique_counts.iter().sum::<f64>() + self.classes.values().sum::<f64>();
+        if n_genomes == 0 || total == 0.0 {
+            return vec![1.0 / n_genomes.max(1) as f64; n_genomes];
+        }
+
+        let pseudo_total = self.unique_counts.iter().sum::<f64>() + n_genomes as f64;
+        let mut abundances = self
+            .unique_counts
+            .iter()
+            .map(|count| (count + 1.0) / pseudo_total)
+            .collect::<Vec<f64>>();
+
+        for _ in 0..max_iterations {
+            let mut counts = self.unique_counts.clone();
+            for (genomes, n_reads) in self.classes.iter() {
+                for (genome, weight) in genomes
+                    .iter()
+                    .zip(Self::weights(&abundances, genomes).into_iter())
+                {
+                    counts[*genome] += n_reads * weight;
+                }
+            }
+
+            let mut max_difference: f64 = 0.0;
+            for (abundance, count) in abundances.iter_mut().zip(counts.into_iter()) {
+                let updated = count / total;
+                max_difference = max_difference.max((updated - *abundance).abs());
+                *abundance = updated;
+            }
+
+            if max_difference < tolerance {
+                break;
+            }
+        }
+
+        abundances
+    }
+
+    /// The share of a read mapping to each of `genomes` given the genome abundances
+    pub fn weights(abundances: &[f64], genomes: &[usize]) -> Vec<f64> {
+        let total = genomes.iter().map(|g| abundances[*g]).sum::<f64>();
+        if total > 0.0 {
+            genomes.iter().map(|g| abundances[*g] / total).collect()
+        } else {
+            vec![1.0 / genomes.len() as f64; genomes.len()]
+        }
+    }
+}
+
+/// Reassigns MAPQ 0 reads that map equally well to several genomes, by EM over genome abundances.
+/// Each genome receiving at least `min_weight` of a read gets it as a primary alignment with MAPQ
+/// `REASSIGNED_MAPQ` and its share in the `WEIGHT_TAG` aux tag.
+#[derive(Debug, Clone)]
+pub struct MultiMappingReassignment {
+    pub min_weight: f64,
+    pub max_iterations: usize,
+    pub tolerance: f64,
+}
+
+impl MultiMappingReassignment {
+    pub const WEIGHT_TAG: &'static [u8] = b"XW";
+    pub const REASSIGNED_MAPQ: u8 = 60;
+    const DEFAULT_MAX_ITERATIONS: usize = 1000;
+    const DEFAULT_TOLERANCE: f64 = 1e-6;
+    const SECONDARY_FLAG: u16 = 0x100;
+
+    pub fn new(min_weight: f64) -> Self {
+        Self {
+            min_weight,
+            max_iterations: Self::DEFAULT_MAX_ITERATIONS,
+            tolerance: Self::DEFAULT_TOLERANCE,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        if !args.get_flag("reassign-multimapped-reads") {
+            return None;
+        }
+
+        Some(Self::new(
+            *args.get_one::<f64>("min-reassignment-weight").unwrap(),
+        ))
+    }
+
+    /// Whether an alignment should be held back for reassignment
+    pub fn is_candidate(record: &Record) -> bool {
+        record.mapq() == 0 && !record.is_supplementary() && !record.is_unmapped()
+    }
+
+    /// Alignments of the same sequenced read share a name and, for pairs, a mate flag
+    pub fn segment_key(record: &Record) -> (Vec<u8>, bool) {
+        (record.qname().to_vec(), record.is_last_in_template())
+    }
+
+    /// The weight given to a reassigned alignment, or None if it was not reassigned
+    pub fn read_weight(record: &Record) -> Option<f64> {
+        match record.aux(Self::WEIGHT_TAG) {
+            Ok(Aux::Float(weight)) => Some(weight as f64),
+            Ok(Aux::Double(weight)) => Some(weight),
+            _ => None,
+        }
+    }
+
+    /// Resolves the held back alignments, each tagged with the index of the genome it aligns to.
+    /// Alignments of reads that only map to one genome are returned unchanged. `em` must already
+    /// hold the uniquely mapped reads. Returns the alignments to write along with their genome
+    pub fn reassign(
+        &self,
+        held_back: HashMap<(Vec<u8>, bool), Vec<(usize, Record)>>,
+        mut em: MultiMappingEm,
+    ) -> Vec<(usize, Record)> {
+        let mut resolved = Vec::new();
+        let mut multi_mapped = Vec::new();
+        for (_, alignments) in held_back.into_iter() {
+            let mut genomes = alignments.iter().map(|(g, _)| *g).collect::<Vec<usize>>();
+            genomes.sort_unstable();
+            genomes.dedup();
+            if genomes.len() < 2 {
+                resolved.extend(alignments);
+            } else {
+                em.add_multi(genomes.clone());
+                multi_mapped.push((genomes, alignments));
+            }
+        }
+
+        let abundances = em.estimate(self.max_iterations, self.tolerance);
+        debug!(
+            "Reassigning {} multi-mapped reads using genome abundances {:?}",
+            multi_mapped.len(),
+            &abundances
+        );
+
+        for (genomes, alignments) in multi_mapped.into_iter() {
+            let weights = MultiMappingEm::weights(&abundances, &genomes);
+            let primary = alignments
+                .iter()
+                .find(|(_, record)| !record.is_secondary())
+                .or_else(|| alignments.iter().find(|(_, record)| record.seq_len() > 0))
+                .map(|(_, record)| record.clone());
+
+            for (genome, weight) in genomes.into_iter().zip(weights.into_iter()) {
+                if weight < self.min_weight {
+                    continue;
+                }
+
+                let best = alignments
+                    .iter()
+                    .filter(|(g, _)| *g == genome)
+                    .max_by_key(|(_, record)| {
+                        (
+                            Self::alignment_score(record),
+                            !record.is_secondary(),
+                        )
+                    })
+                    .map(|(_, record)| record);
+                let reassigned = match (best, primary.as_ref()) {
+                    (Some(best), Some(primary)) => Self::as_primary(best, primary),
+                    _ => None,
+                };
+
+                if let Some(mut reassigned) = reassigned {
+                    reassigned.set_mapq(Self::REASSIGNED_MAPQ);
+                    let _ = reassigned.remove_aux(Self::WEIGHT_TAG);
+                    if reassigned
+                        .push_aux(Self::WEIGHT_TAG, Aux::Float(weight as f32))
+                        .is_ok()
+                    {
+                        resolved.push((genome, reassigned));
+                    }
+                }
+            }
+        }
+
+        resolved
+    }
+
+    fn alignment_score(record: &Record) -> i64 {
+        match record.aux(b"AS") {
+            Ok(Aux::I8(score)) => score as i64,
+            Ok(Aux::U8(score)) => score as i64,
+            Ok(Aux::I16(score)) => score as i64,
+            Ok(Aux::U16(score)) => score as i64,
+            Ok(Aux::I32(score)) => score as i64,
+            Ok(Aux::U32(score)) => score as i64,
+            _ => 0,
+        }
+    }
+
+    /// Turns an alignment into a primary alignment. Secondary alignments are often written without
+    /// their sequence, in which case it is taken from the primary alignment of the read, reverse
+    /// complemented if the alignments are on opposite strands. Returns None if the sequence can not
+    /// be recovered, e.g. because the alignment is hard clipped
+    fn as_primary(record: &Record, primary: &Record) -> Option<Record> {
+        let mut record = record.clone();
+        if record.seq_len() == 0 {
+            let cigar = record.cigar().take();
+            if CigarUtils::get_read_length(&cigar) as usize != primary.seq_len() {
+                return None;
+            }
+
+            let (seq, qual) = if record.is_reverse() == primary.is_reverse() {
+                (primary.seq().as_bytes(), primary.qual().to_vec())
+            } else {
+                (
+                    dna::revcomp(primary.seq().as_bytes()),
+                    primary.qual().iter().rev().copied().collect::<Vec<u8>>(),
+                )
+            };
+            let qname = record.qname().to_vec();
+            record.set(&qname, Some(&cigar), &seq, &qual);
+        }
+        record.set_flags(record.flags() & !Self::SECONDARY_FLAG);
+        Some(record)
+    }
+}
@@ -0,0 +1,243 @@
+use rust_htslib::bam::Record;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::utils::errors::BirdToolError;
+
+/// Unique and shared read counts of the genomes in one sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleReadSharing {
+    pub sample: String,
+    // reads aligning only to each genome
+    pub unique: Vec<u64>,
+    // reads aligning to each genome and at least one other genome
+    pub shared: Vec<u64>,
+    // reads aligning to both genomes of each pair, with the total reads of each genome on the
+    // diagonal
+    pub matrix: Vec<Vec<u64>>,
+}
+
+impl SampleReadSharing {
+    pub fn total(&self, genome: usize) -> u64 {
+        self.unique[genome] + self.shared[genome]
+    }
+}
+
+/// The genomes the reads of one sample align to, collected while a BAM file is split per genome.
+/// Reads are keyed by a hash of their name and mate, and a read is only given a set of genomes once
+/// it has been seen aligned to a second genome, so most reads cost a single map entry
+#[derive(Debug, Clone)]
+pub struct ReadSharingCounter {
+    n_genomes: usize,
+    single: HashMap<u64, usize>,
+    multiple: HashMap<u64, Vec<usize>>,
+}
+
+impl ReadSharingCounter {
+    pub fn new(n_genomes: usize) -> Self {
+        Self {
+            n_genomes,
+            single: HashMap::new(),
+            multiple: HashMap::new(),
+        }
+    }
+
+    fn segment_hash(record: &Record) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        record.qname().hash(&mut hasher);
+        record.is_last_in_template().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Adds an alignment of a read to a genome. Unmapped and supplementary records are skipped,
+    /// as supplementary alignments come from chimeric reads rather than similar genomes
+    pub fn add(&mut self, record: &Record, genome: usize) {
+        if record.is_unmapped() || record.is_supplementary() {
+            return;
+        }
+        let key = Self::segment_hash(record);
+        if let Some(genomes) = self.multiple.get_mut(&key) {
+            if !genomes.contains(&genome) {
+                genomes.push(genome);
+            }
+            return;
+        }
+        match self.single.get(&key).copied() {
+            None => {
+                self.single.insert(key, genome);
+            }
+            Some(previous) if previous != genome => {
+                self.single.remove(&key);
+                self.multiple.insert(key, vec![previous, genome]);
+            }
+            Some(_) => {}
+        }
+    }
+
+    pub fn summarise(&self, sample: &str) -> SampleReadSharing {
+        let mut unique = vec![0; self.n_genomes];
+        let mut shared = vec![0; self.n_genomes];
+        let mut matrix = vec![vec![0; self.n_genomes]; self.n_genomes];
+        for genome in self.single.values() {
+            unique[*genome] += 1;
+            matrix[*genome][*genome] += 1;
+        }
+        for genomes in self.multiple.values() {
+            for genome in genomes.iter() {
+                shared[*genome] += 1;
+                for other in genomes.iter() {
+                    matrix[*genome][*other] += 1;
+                }
+            }
+        }
+        SampleReadSharing {
+            sample: sample.to_string(),
+            unique,
+            shared,
+            matrix,
+        }
+    }
+}
+
+/// Unique and shared read counts of every genome in every sample, with a warning for each pair of
+/// genomes sharing more than `SIMILARITY_WARNING_FRACTION` of the reads of the smaller one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompetitiveMappingReport {
+    pub genomes: Vec<String>,
+    pub samples: Vec<SampleReadSharing>,
+}
+
+impl CompetitiveMappingReport {
+    pub const SIMILARITY_WARNING_FRACTION: f64 = 0.2;
+
+    pub fn new(genomes: &[String]) -> Self {
+        Self {
+            genomes: genomes.to_vec(),
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn add_sample(&mut self, sample: SampleReadSharing) {
+        self.samples.push(sample);
+    }
+
+    pub fn extend(&mut self, other: CompetitiveMappingReport) {
+        self.samples.extend(other.samples);
+    }
+
+    /// The genome by genome matrix of shared reads summed over samples
+    pub fn sharing_matrix(&self) -> Vec<Vec<u64>> {
+        let n_genomes = self.genomes.len();
+        let mut matrix = vec![vec![0; n_genomes]; n_genomes];
+        for sample in self.samples.iter() {
+            for (row, sample_row) in matrix.iter_mut().zip(sample.matrix.iter()) {
+                for (count, sample_count) in row.iter_mut().zip(sample_row.iter()) {
+                    *count += sample_count;
+                }
+            }
+        }
+        matrix
+    }
+
+    /// Pairs of genomes sharing at least `fraction` of the reads of the genome with fewer reads,
+    /// along with the shared fraction
+    pub fn similar_genomes(&self, fraction: f64) -> Vec<(usize, usize, f64)> {
+        let matrix = self.sharing_matrix();
+        let mut pairs = Vec::new();
+        for first in 0..matrix.len() {
+            for second in (first + 1)..matrix.len() {
+                let smaller = matrix[first][first].min(matrix[second][second]);
+                if smaller == 0 {
+                    continue;
+                }
+                let shared_fraction = matrix[first][second] as f64 / smaller as f64;
+                if shared_fraction >= fraction {
+                    pairs.push((first, second, shared_fraction));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Writes `competitive_mapping.tsv` and `read_sharing_matrix.tsv` to `output_directory` and
+    /// warns about genomes that are too similar for reads to be attributed reliably
+    pub fn write(&self, output_directory: &str) -> Result<(), BirdToolError> {
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+
+        let mut writer = Self::create(output_directory, "competitive_mapping.tsv")?;
+        writeln!(
+            writer,
+            "sample\tgenome\ttotal_reads\tunique_reads\tshared_reads\tshared_fraction"
+        )
+        .map_err(write_error)?;
+        for sample in self.samples.iter() {
+            for (genome_index, genome) in self.genomes.iter().enumerate() {
+                let total = sample.total(genome_index);
+                let shared_fraction = if total > 0 {
+                    format!("{:.6}", sample.shared[genome_index] as f64 / total as f64)
+                } else {
+                    "NA".to_string()
+                };
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    sample.sample,
+                    genome,
+                    total,
+                    sample.unique[genome_index],
+                    sample.shared[genome_index],
+                    shared_fraction
+                )
+                .map_err(write_error)?;
+            }
+        }
+        writer.flush().map_err(write_error)?;
+
+        let mut writer = Self::create(output_directory, "read_sharing_matrix.tsv")?;
+        writeln!(writer, "genome\t{}", self.genomes.join("\t")).map_err(write_error)?;
+        for (genome, row) in self.genomes.iter().zip(self.sharing_matrix().iter()) {
+            writeln!(
+                writer,
+                "{}\t{}",
+                genome,
+                row.iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<String>>()
+                    .join("\t")
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)?;
+
+        for (first, second, shared_fraction) in
+            self.similar_genomes(Self::SIMILARITY_WARNING_FRACTION)
+        {
+            warn!(
+                "Genomes {} and {} share {:.1}% of their reads, so reads can not be reliably \
+                attributed to either of them",
+                self.genomes[first],
+                self.genomes[second],
+                shared_fraction * 100.0
+            );
+        }
+        Ok(())
+    }
+
+    fn create(output_directory: &str, file_name: &str) -> Result<BufWriter<File>, BirdToolError> {
+        let file_name = format!("{}/{}", output_directory, file_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        Ok(BufWriter::new(file))
+    }
+}
@@ -0,0 +1,504 @@
+use needletail::parse_fastx_file;
+use std::path::Path;
+use std::process::Command;
+
+use crate::ani_calculator::ani_calculator::ANICalculator;
+use crate::assembly::forced_alleles::ForcedAlleles;
+use crate::assembly::hotspots::Hotspots;
+use crate::processing::bam_reference_check::BamReferenceCheck;
+use crate::processing::output_layout::OutputLayout;
+use crate::processing::per_genome_config::PerGenomeConfig;
+use crate::processing::run_outputs::RunOutputs;
+use crate::processing::structural_variant_caller::StructuralVariantCaller;
+use crate::processing::subsampler::Subsampler;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::reference::reference_reader_utils::ReferenceReaderUtils;
+use crate::utils::errors::BirdToolError;
+
+/// Name and length of every contig in a reference genome
+#[derive(Debug, Clone)]
+pub struct ReferenceContigs {
+    pub genome_name: String,
+    pub path: String,
+    pub contigs: Vec<(String, u64)>,
+}
+
+impl ReferenceContigs {
+    pub fn read(path: &str) -> Result<Self, BirdToolError> {
+        let mut reader = parse_fastx_file(Path::new(path)).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read reference {}: {}", path, e))
+        })?;
+
+        let mut contigs = Vec::new();
+        while let Some(record) = reader.next() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Failed to parse record in {}: {}", path, e))
+            })?;
+            let contig_name = std::str::from_utf8(record.id())
+                .map_err(|_| {
+                    BirdToolError::IOError(format!("Contig name in {} is not valid UTF-8", path))
+                })?
+                .split_whitespace()
+                .next()
+                .unwrap_or("")
+                .to_string();
+            contigs.push((contig_name, record.seq().len() as u64));
+        }
+
+        Ok(Self {
+            genome_name: ReferenceReaderUtils::genome_name_from_path(path),
+            path: path.to_string(),
+            contigs,
+        })
+    }
+
+    pub fn length(&self) -> u64 {
+        self.contigs.iter().map(|(_, length)| *length).sum()
+    }
+
+    /// Upper bound on the number of assembly regions, reached when every position is active
+    pub fn estimated_regions(&self, max_assembly_region_size: usize) -> u64 {
+        let max_assembly_region_size = max_assembly_region_size.max(1) as u64;
+        self.contigs
+            .iter()
+            .map(|(_, length)| (length + max_assembly_region_size - 1) / max_assembly_region_size)
+            .sum()
+    }
+}
+
+/// Checks the references, BAM files, read files and external tools of a run and prints what would
+/// be done, reporting every problem rather than stopping at the first.
+pub struct DryRun<'a> {
+    args: &'a clap::ArgMatches,
+    mode: &'a str,
+    problems: Vec<String>,
+}
+
+impl<'a> DryRun<'a> {
+    pub fn new(args: &'a clap::ArgMatches, mode: &'a str) -> Self {
+        Self {
+            args,
+            mode,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Validates the inputs and prints the execution plan. Returns an error listing every
+    /// problem found
+    pub fn run(mut self) -> Result<(), BirdToolError> {
+        let references = self.check_inputs()?;
+        self.print_plan(&references);
+
+        if self.problems.is_empty() {
+            println!("Dry run found no problems");
+            Ok(())
+        } else {
+            for problem in self.problems.iter() {
+                println!("PROBLEM: {}", problem);
+            }
+            Err(BirdToolError::DebugError(format!(
+                "Dry run found {} problem(s)",
+                self.problems.len()
+            )))
+        }
+    }
+
+    /// Checks the references, BAM files, read files, feature files and external tools of the
+    /// run, returning the references that could be read. Problems are collected in `problems`
+    pub fn check_inputs(&mut self) -> Result<Vec<ReferenceContigs>, BirdToolError> {
+        let (references, separator) = self.check_references()?;
+        self.check_bam_files(&references, separator);
+        self.check_read_files();
+        self.check_feature_files();
+        self.check_external_tools();
+        Ok(references)
+    }
+
+    /// Every problem found so far
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    fn strings(&self, id: &str) -> Vec<String> {
+        self.args
+            .try_get_many::<String>(id)
+            .ok()
+            .flatten()
+            .map(|values| values.cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn check_references(
+        &mut self,
+    ) -> Result<(Vec<ReferenceContigs>, GenomeSeparator), BirdToolError> {
+        let paths = ReferenceReaderUtils::try_parse_references(self.args)?;
+        let mut references = Vec::with_capacity(paths.len());
+        for path in paths {
+            if !Path::new(&path).exists() {
+                self.problems
+                    .push(format!("Reference genome {} does not exist", &path));
+                continue;
+            }
+
+            match ReferenceContigs::read(&path) {
+                Ok(reference) if reference.contigs.is_empty() => self
+                    .problems
+                    .push(format!("Reference genome {} contains no sequences", &path)),
+                Ok(reference) => {
+                    for (contig_name, _) in reference.contigs.iter() {
+                        if let Err(BirdToolError::DebugError(e)) =
+                            GenomeSeparator::validate_contig_name(contig_name)
+                        {
+                            self.problems.push(format!("{}: {}", &path, e));
+                        }
+                    }
+                    references.push(reference);
+                }
+                Err(BirdToolError::IOError(e)) | Err(BirdToolError::DebugError(e)) => {
+                    self.problems.push(e)
+                }
+                Err(e) => self.problems.push(format!("{:?}", e)),
+            }
+        }
+
+        let genome_names = references
+            .iter()
+            .map(|reference| reference.genome_name.clone())
+            .collect::<Vec<String>>();
+        let separator = match GenomeSeparator::from_args(self.args, &genome_names) {
+            Ok(separator) => separator,
+            Err(e) => {
+                self.problems.push(e.to_string());
+                GenomeSeparator::default()
+            }
+        };
+
+        Ok((references, separator))
+    }
+
+    fn check_bam_files(&mut self, references: &[ReferenceContigs], separator: GenomeSeparator) {
+        let skip_reference_check = self
+            .args
+            .try_get_one::<bool>("skip-reference-check")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        let mut reference_check = BamReferenceCheck::new(references.to_vec(), separator);
+
+        let mut bam_files = self.strings("bam-files");
+        bam_files.extend(self.strings("longread-bam-files"));
+        for bam_file in bam_files {
+            if !Path::new(&bam_file).exists() {
+                self.problems
+                    .push(format!("BAM file {} does not exist", &bam_file));
+                continue;
+            }
+
+            if !skip_reference_check {
+                match reference_check.check_bam(&bam_file) {
+                    Ok(report) => self.problems.extend(report.describe()),
+                    Err(e) => self.problems.push(format!("{:?}", e)),
+                }
+            }
+
+            if !Path::new(&format!("{}.bai", &bam_file)).exists()
+                && !Path::new(&format!("{}.csi", &bam_file)).exists()
+            {
+                println!("BAM file {} is not indexed, an index will be created", &bam_file);
+            }
+        }
+    }
+
+    fn check_read_files(&mut self) {
+        for id in ["read1", "read2", "coupled", "interleaved", "single", "longreads"] {
+            for read_file in self.strings(id) {
+                if !Path::new(&read_file).exists() {
+                    self.problems
+                        .push(format!("--{} file {} does not exist", id, &read_file));
+                }
+            }
+        }
+    }
+
+    fn check_feature_files(&mut self) {
+        for feature_vcf in self.strings("features-vcf") {
+            if !Path::new(&feature_vcf).exists() {
+                self.problems
+                    .push(format!("--features-vcf file {} does not exist", &feature_vcf));
+            }
+        }
+
+        let features_tsvs = self.strings("features-tsv");
+        if !features_tsvs.is_empty() {
+            if let Err(e) = ForcedAlleles::from_tsvs(&features_tsvs) {
+                self.problems.push(format!("{:?}", e));
+            }
+        }
+
+        let hotspots = self.strings("hotspots");
+        if !hotspots.is_empty() {
+            if let Err(e) = Hotspots::from_files(&hotspots, Hotspots::DEFAULT_ACTIVITY) {
+                self.problems.push(format!("{:?}", e));
+            }
+        }
+    }
+
+    fn has_short_reads(&self) -> bool {
+        ["read1", "coupled", "interleaved", "single"]
+            .iter()
+            .any(|id| self.args.contains_id(id))
+    }
+
+    fn has_long_reads(&self) -> bool {
+        self.args.contains_id("longreads") || self.args.contains_id("longread-bam-files")
+    }
+
+    fn calling_svs(&self) -> bool {
+        // lorikeet ani only counts bases
+        self.mode != "ani"
+            && self.has_long_reads()
+            && !self
+                .args
+                .try_get_one::<bool>("do-not-call-svs")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false)
+    }
+
+    /// The external programs needed by the selected inputs and mode
+    pub fn required_tools(&self) -> Vec<&'static str> {
+        let mapper_tool = |mapper: Option<&String>| match mapper.map(|m| m.as_str()) {
+            Some("bwa-mem") => "bwa",
+            Some("bwa-mem2") => "bwa-mem2",
+            _ => "minimap2",
+        };
+
+        let mut tools = Vec::new();
+        if self.has_short_reads() {
+            tools.push(mapper_tool(self.args.get_one::<String>("mapper")));
+            tools.push("samtools");
+        }
+        if self.args.contains_id("longreads") {
+            tools.push(mapper_tool(self.args.get_one::<String>("longread-mapper")));
+            tools.push("samtools");
+        }
+        if self.calling_svs() {
+            tools.push(StructuralVariantCaller::from_args(self.args).executable());
+            tools.push("bcftools");
+        }
+        if self.args.get_flag("calculate-dnds") {
+            tools.push("prodigal");
+        }
+        tools.sort_unstable();
+        tools.dedup();
+        tools
+    }
+
+    fn check_external_tools(&mut self) {
+        for tool in self.required_tools() {
+            let found = Command::new("which")
+                .arg(tool)
+                .output()
+                .map(|output| output.status.success())
+                .unwrap_or(false);
+            if !found {
+                self.problems.push(format!(
+                    "{} is required for this run but was not found on the PATH",
+                    tool
+                ));
+            }
+        }
+    }
+
+    fn stages(&self) -> Vec<&'static str> {
+        let mut stages = Vec::new();
+        if self.has_short_reads() || self.args.contains_id("longreads") {
+            stages.push("read mapping");
+        }
+        if self.mode == "ani" {
+            stages.push("pileup base counting and ANI calculation");
+            return stages;
+        }
+        if self.calling_svs() {
+            stages.push("structural variant calling");
+        }
+        stages.push("activity profile");
+        if self.args.contains_id("subsample-fraction")
+            || self.args.contains_id("subsample-regions")
+        {
+            stages.push("subsampling of the genome");
+        }
+        stages.push("assembly and genotyping of active regions");
+        for pathway in RunOutputs::from_args(self.args, self.mode).pathways() {
+            match pathway {
+                "call" => stages.push("ANI calculation and VCF writing"),
+                "genotype" => stages.push("strain abundance estimation"),
+                "consensus" => stages.push("strain abundance estimation and consensus writing"),
+                _ => {}
+            }
+        }
+        if self.args.get_flag("calculate-dnds") {
+            stages.push("dN/dS calculation");
+        }
+        if self.args.get_flag("calculate-fst") {
+            stages.push("Fst calculation");
+        }
+        stages
+    }
+
+    fn outputs(&self, genome_prefix: &str, genome_name: &str) -> Vec<String> {
+        if self.mode == "ani" {
+            let mut outputs = ANICalculator::TABLE_NAMES
+                .iter()
+                .chain([ANICalculator::DISTANCE_TABLE_NAME].iter())
+                .map(|table_name| format!("{}/{}_{}.tsv", genome_prefix, genome_name, table_name))
+                .collect::<Vec<String>>();
+            if self.args.get_flag("sample-dendrogram") {
+                outputs.push(format!(
+                    "{}/{}_{}.nwk",
+                    genome_prefix,
+                    genome_name,
+                    ANICalculator::DENDROGRAM_NAME
+                ));
+            }
+            return outputs;
+        }
+        let run_outputs = RunOutputs::from_args(self.args, self.mode);
+        let mut outputs = Vec::new();
+        for pathway in run_outputs.pathways() {
+            let output_prefix = run_outputs.output_prefix(genome_prefix, pathway);
+            outputs.push(format!("{}/{}.vcf", output_prefix, genome_name));
+            if self.args.get_flag("write-features") {
+                outputs.push(format!("{}/{}_features.tsv", output_prefix, genome_name));
+            }
+            match pathway {
+                "genotype" => {
+                    outputs.push(format!("{}/{}_strain_coverages.tsv", output_prefix, genome_name));
+                    outputs.push(format!(
+                        "{}/{}_strain_frequencies.tsv",
+                        output_prefix, genome_name
+                    ));
+                    outputs.push(format!("{}/{}_phasing.tsv", output_prefix, genome_name));
+                    if self.args.get_flag("calculate-dnds") {
+                        outputs.push(format!("{}/{}_strain_dnds.tsv", output_prefix, genome_name));
+                    }
+                    if self
+                        .args
+                        .try_get_one::<bool>("polish-strains")
+                        .ok()
+                        .flatten()
+                        .copied()
+                        .unwrap_or(false)
+                    {
+                        outputs.push(format!(
+                            "{}/{}_strain_<strain>_polished.fna",
+                            output_prefix, genome_name
+                        ));
+                        outputs.push(format!(
+                            "{}/{}_strain_polishing.tsv",
+                            output_prefix, genome_name
+                        ));
+                    }
+                }
+                "consensus" => {
+                    outputs.push(format!("{}/{}_strain_coverages.tsv", output_prefix, genome_name));
+                    outputs.push(format!(
+                        "{}/{}_consensus_<strain>.fna",
+                        output_prefix, genome_name
+                    ));
+                }
+                _ => {}
+            }
+            if self.args.get_flag("calculate-dnds") {
+                outputs.push(format!("{}/{}_dnds.tsv", output_prefix, genome_name));
+            }
+        }
+        outputs.push(format!("{}/{}_active_regions.bed", genome_prefix, genome_name));
+        if self.args.get_flag("calculate-fst") {
+            for suffix in ["nucleotide_diversity", "window_diversity", "pairwise_fst"] {
+                outputs.push(format!("{}/{}_{}.tsv", genome_prefix, genome_name, suffix));
+            }
+        }
+        outputs
+    }
+
+    fn print_plan(&mut self, references: &[ReferenceContigs]) {
+        let output_layout = OutputLayout::from_args(self.args, self.mode);
+        if let Err(BirdToolError::ConfigError(e)) =
+            OutputLayout::validate_template(&output_layout.template)
+        {
+            self.problems.push(e);
+        }
+        if let Err(BirdToolError::DebugError(e)) = Subsampler::new(
+            self.args
+                .try_get_one::<f64>("subsample-fraction")
+                .ok()
+                .flatten()
+                .copied(),
+            self.args
+                .try_get_one::<usize>("subsample-regions")
+                .ok()
+                .flatten()
+                .copied(),
+        ) {
+            self.problems.push(e);
+        }
+        let per_genome_config = match self
+            .args
+            .try_get_one::<String>("per-genome-config")
+            .ok()
+            .flatten()
+        {
+            Some(path) => match PerGenomeConfig::from_file(path) {
+                Ok(config) => Some(config),
+                Err(BirdToolError::IOError(e)) | Err(BirdToolError::DebugError(e)) => {
+                    self.problems.push(e);
+                    None
+                }
+                Err(_) => None,
+            },
+            None => None,
+        };
+        let max_assembly_region_size = *self
+            .args
+            .get_one::<usize>("max-assembly-region-size")
+            .unwrap();
+        let stages = self.stages();
+
+        println!("Execution plan for lorikeet {}:", self.mode);
+        for reference in references {
+            let output_prefix = output_layout.genome_prefix(&reference.genome_name);
+            println!(
+                "{} ({}): {} contigs, {} bp, at most {} assembly regions",
+                &reference.genome_name,
+                &reference.path,
+                reference.contigs.len(),
+                reference.length(),
+                reference.estimated_regions(
+                    per_genome_config
+                        .as_ref()
+                        .and_then(|config| config.get(&reference.genome_name))
+                        .and_then(|overrides| overrides.max_assembly_region_size)
+                        .unwrap_or(max_assembly_region_size)
+                )
+            );
+            println!("\tStages: {}", stages.join(" -> "));
+            if Path::new(&output_prefix).exists() && !self.args.get_flag("force") {
+                println!(
+                    "\tOutput directory {} exists, cached results will be reused unless --force is given",
+                    &output_prefix
+                );
+            }
+            for output in self.outputs(&output_prefix, &reference.genome_name) {
+                println!("\tWrites {}", output);
+            }
+        }
+        println!(
+            "Output manifest: {}/{}",
+            &output_layout.output_directory,
+            OutputLayout::MANIFEST_NAME
+        );
+    }
+}
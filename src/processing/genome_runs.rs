@@ -0,0 +1,99 @@
+use std::any::Any;
+use std::fs::create_dir_all;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::utils::errors::BirdToolError;
+use crate::utils::exit_status::ExitStatus;
+
+/// Runs each genome so that a failing genome does not stop the others. A `lorikeet.incomplete`
+/// marker stays in the output directory of a genome until every output is written, so failed
+/// genomes are not taken as cached when the run is retried.
+#[derive(Debug, Clone, Default)]
+pub struct GenomeRuns {
+    failed: Arc<Mutex<Vec<String>>>,
+}
+
+impl GenomeRuns {
+    pub const INCOMPLETE_MARKER: &'static str = "lorikeet.incomplete";
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the outputs in a genome output directory were left incomplete by an earlier run
+    pub fn is_incomplete(output_prefix: &str) -> bool {
+        Path::new(output_prefix)
+            .join(Self::INCOMPLETE_MARKER)
+            .exists()
+    }
+
+    /// Runs the steps of a genome, marking its output directory as incomplete until they finish.
+    /// An error returned by the steps, or a panic in them, records the genome as failed
+    pub fn run<F: FnOnce() -> Result<(), BirdToolError>>(
+        &self,
+        genome: &str,
+        output_prefix: &str,
+        steps: F,
+    ) {
+        let marker = Path::new(output_prefix).join(Self::INCOMPLETE_MARKER);
+        if let Err(e) = create_dir_all(output_prefix).and_then(|_| {
+            std::fs::write(
+                &marker,
+                format!(
+                    "The outputs of {} have not been written completely\n",
+                    genome
+                ),
+            )
+        }) {
+            warn!("{}: Unable to mark outputs as incomplete {:?}", genome, e);
+        }
+
+        let failure = match catch_unwind(AssertUnwindSafe(steps)) {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(e.to_string()),
+            Err(cause) => Some(Self::panic_message(cause.as_ref())),
+        };
+        match failure {
+            None => {
+                if marker.exists() {
+                    if let Err(e) = std::fs::remove_file(&marker) {
+                        warn!("{}: Unable to remove {} {:?}", genome, marker.display(), e);
+                    }
+                }
+            }
+            Some(reason) => {
+                error!("{}: Skipping genome after failure: {}", genome, reason);
+                let _ = std::fs::write(
+                    &marker,
+                    format!(
+                        "The outputs of {} have not been written completely\nFailed: {}\n",
+                        genome, reason
+                    ),
+                );
+                self.failed.lock().unwrap().push(genome.to_string());
+            }
+        }
+    }
+
+    fn panic_message(cause: &(dyn Any + Send)) -> String {
+        if let Some(message) = cause.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = cause.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "unknown error".to_string()
+        }
+    }
+
+    /// The genomes that failed, in the order they failed
+    pub fn failed(&self) -> Vec<String> {
+        self.failed.lock().unwrap().clone()
+    }
+
+    /// The exit status of a run of `n_genomes` genomes
+    pub fn status(&self, n_genomes: usize) -> ExitStatus {
+        ExitStatus::from_genomes(n_genomes, self.failed.lock().unwrap().len())
+    }
+}
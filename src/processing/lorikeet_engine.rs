@@ -1,39 +1,85 @@
-use bird_tool_utils::command::finish_command_safely;
-use indicatif::{style::TemplateError, MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{
+    style::TemplateError, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle,
+};
 use itertools::Itertools;
 use rayon::prelude::*;
 use rust_htslib::bcf::Read;
 use scoped_threadpool::Pool;
-use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File, OpenOptions};
 use std::io::{BufWriter, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tempdir::TempDir;
-use tempfile::NamedTempFile;
 
 use crate::bam_parsing::{
     FlagFilter,
     bam_generator::*
 };
-use crate::evolve::codon_structs::{CodonTable, Translations};
+use crate::evolve::codon_structs::{strain_ids_in_vcf, CodonTable, GeneticCodes, Translations};
+use crate::evolve::marker_summary::{MarkerCatalog, MarkerGene};
 use crate::abundance::abundance_calculator_engine::AbundanceCalculatorEngine;
+use crate::abundance::abundance_formats::{AbundanceFormat, CamiProfiles};
+use crate::abundance::reference_bias::ReferenceBias;
+use crate::abundance::strain_count::StrainCountEstimator;
+use crate::abundance::strain_discrimination::StrainDiscrimination;
+use crate::abundance::strain_frequencies::StrainFrequencyEstimator;
+use crate::genotype::heterozygosity_priors::HeterozygosityPriors;
+use crate::graphs::graph_dump::GraphSummary;
 use crate::ani_calculator::ani_calculator::ANICalculator;
+use crate::annotator::allele_fraction::AlleleFractionDenominator;
+use crate::annotator::coverage_context::CoverageContext;
+use crate::annotator::repeat_context::RepeatContext;
+use crate::annotator::sequence_complexity::SequenceComplexity;
+use crate::assembly::active_region_log::RegionStatus;
 use crate::assembly::assembly_region_walker::AssemblyRegionWalker;
+use crate::concordance::genotype_concordance::{GenotypeConcordance, SiteGenotypes};
+use crate::concordance::replicate_calibration::ReplicateCalibration;
 use crate::reference::reference_reader_utils::GenomesAndContigs;
-use crate::external_command_checker::{check_for_bcftools, check_for_svim};
+use crate::external_command_checker::check_for_bcftools;
+use crate::haplotype::haplotype_caller_engine::HaplotypeCallerEngine;
 use crate::haplotype::haplotype_clustering_engine::HaplotypeClusteringEngine;
+use crate::linkage::phasing_statistics::PhasingStatistics;
+use crate::linkage::strain_read_binning::StrainReadBinner;
+use crate::model::callable_sites::CallableSites;
+use crate::model::diversity_calculator::DiversityCalculator;
+use crate::model::site_frequency_spectrum::SiteFrequencySpectrum;
 use crate::model::variant_context::VariantContext;
 use crate::model::variant_context_utils::VariantContextUtils;
+use crate::model::variant_sorter::VariantSorter;
+use crate::model::variant_store::VariantStore;
+use crate::pair_hmm::gpu_pair_hmm::PairHMMBackend;
+use crate::phylogeny::core_snp_alignment::CoreSnpAlignment;
+use crate::phylogeny::neighbor_joining::neighbor_joining;
+use crate::processing::genome_runs::GenomeRuns;
+use crate::processing::output_layout::OutputLayout;
+use crate::processing::per_genome_config::PerGenomeConfig;
+use crate::processing::pileup_ani::PileupAni;
+use crate::processing::run_outputs::RunOutputs;
+use crate::processing::scatter_gather::{ScatterShard, ShardGatherer};
+use crate::processing::structural_variant_caller::StructuralVariantCaller;
+use crate::processing::vcf_combiner::{CombineInput, VcfCombiner};
+use crate::processing::sv_evidence::SvEvidenceCollector;
+use crate::processing::variant_inspector::{parse_region, VariantInspector};
+use crate::processing::variant_features::FeatureModel;
 use crate::processing::bams::index_bams::*;
+use crate::processing::bams::multi_mapping::MultiMappingReassignment;
+use crate::processing::bams::read_sharing::CompetitiveMappingReport;
+use crate::reads::read_group_samples::ReadGroupSamples;
+use crate::reference::reference_mask::ReferenceMask;
+use crate::reference::reference_cache::ConcatenatedReference;
 use crate::reference::reference_reader::ReferenceReader;
 use crate::reference::reference_reader_utils::ReferenceReaderUtils;
-use crate::reference::reference_writer::ReferenceWriter;
+use crate::reference::reference_writer::{ConsensusOptions, ReferenceWriter};
+use crate::reference::strain_polisher::StrainPolisher;
 use crate::utils::errors::BirdToolError;
-use crate::utils::utils::get_cleaned_sample_names;
+use crate::utils::exit_status::ExitStatus;
+use crate::utils::external_command::ExternalCommand;
+use crate::utils::log_events::LogEvents;
+use crate::utils::temp_resources::{TempResource, TempResources};
+use crate::utils::thread_budget::ThreadBudget;
+use crate::utils::vcf_input::VcfInput;
 #[cfg(feature = "fst")]
 use crate::model::fst_calculator::calculate_fst;
 
@@ -50,6 +96,31 @@ pub struct Elem {
     pub progress_bar: ProgressBar,
 }
 
+impl Elem {
+    /// Shows the stage this bar's genome has reached. When JSON logging is enabled the stage is
+    /// also emitted as a stage-start event for the genome
+    pub fn set_stage(&self, stage: &str) {
+        LogEvents::stage_start(&self.key, stage);
+        self.progress_bar
+            .set_message(format!("{}: {}...", self.key, stage));
+    }
+
+    /// Updates the message shown on this progress bar without starting a new stage
+    pub fn set_message(&self, message: String) {
+        self.progress_bar.set_message(message);
+    }
+
+    pub fn finish_and_clear(&self) {
+        LogEvents::stage_end(&self.key);
+        self.progress_bar.finish_and_clear();
+    }
+
+    pub fn finish_with_message(&self, message: String) {
+        LogEvents::stage_end(&self.key);
+        self.progress_bar.finish_with_message(message);
+    }
+}
+
 /// The main lorikeet engine, takes any number of reference genomes and reads/bam files and performs
 /// read mapping, variant calling, consensus genome calling, and strain genotyping
 ///
@@ -60,8 +131,8 @@ pub struct LorikeetEngine<'a> {
     long_read_bam_count: usize,
     flag_filters: FlagFilter,
     genomes_and_contigs: GenomesAndContigs,
-    concatenated_genomes: Option<NamedTempFile>,
-    tmp_bam_file_cache: Option<TempDir>,
+    concatenated_genomes: Option<ConcatenatedReference>,
+    tmp_bam_file_cache: Option<TempResource>,
     reference_map: HashMap<usize, String>,
     references: Vec<&'a str>,
     // multi: Arc<MultiProgress>,
@@ -74,28 +145,31 @@ pub struct LorikeetEngine<'a> {
 }
 
 impl<'a> LorikeetEngine<'a> {
-    pub fn apply_per_reference(&self) {
+    /// Runs every genome, returning whether all of them, some of them or none of them finished
+    pub fn apply_per_reference(&self) -> ExitStatus {
         let parallel_genomes = *self
             .args
             .get_one::<usize>("parallel-genomes")
             .unwrap() as u32;
         let mut pool = Pool::new(parallel_genomes);
-        let n_threads = std::cmp::max(
-            self.threads / min(parallel_genomes as usize, self.references.len()),
-            2,
-        );
-        let output_prefix = match self.args.contains_id("output-directory") {
-            true => {
-                match std::fs::create_dir_all(
-                    self.args.get_one::<String>("output-directory").unwrap(),
-                ) {
-                    Ok(_) => {}
-                    Err(err) => panic!("Unable to create output directory {:?}", err),
-                };
-                self.args.get_one::<String>("output-directory").unwrap()
-            }
-            false => "./",
-        };
+        let n_threads = ThreadBudget::from_args(self.args).threads_per_genome(self.references.len());
+        if self.args.contains_id("output-directory") {
+            match std::fs::create_dir_all(
+                self.args.get_one::<String>("output-directory").unwrap(),
+            ) {
+                Ok(_) => {}
+                Err(err) => panic!("Unable to create output directory {:?}", err),
+            };
+        }
+        let output_layout = OutputLayout::from_args(self.args, self.mode);
+        let output_layout = &output_layout;
+        let genome_runs = GenomeRuns::new();
+        let genome_runs = &genome_runs;
+        let cami_profiles = CamiProfiles::new();
+        let cami_profiles = &cami_profiles;
+        // the GPU is initialized once and shared by the genomes called in parallel
+        let gpu_pair_hmm = PairHMMBackend::from_args(self.args).initialize();
+        let gpu_pair_hmm = &gpu_pair_hmm;
 
         pool.scoped(|scope| {
             Self::begin_tick(0, &self.progress_bars, &self.multi_inner, "");
@@ -103,6 +177,7 @@ impl<'a> LorikeetEngine<'a> {
 
             for (ref_idx, reference_stem) in self.reference_map.clone().into_iter() {
                 let mode = self.mode;
+                let run_outputs = RunOutputs::from_args(self.args, mode);
                 let multi_inner = &self.multi_inner;
                 let tree = &self.tree;
                 let progress_bars = &self.progress_bars;
@@ -122,9 +197,7 @@ impl<'a> LorikeetEngine<'a> {
                 #[cfg(feature = "fst")]
                 let ploidy = *self.args.get_one::<usize>("ploidy").unwrap();
 
-                let output_prefix = format!(
-                    "{}/{}",
-                    &output_prefix,
+                let output_prefix = output_layout.genome_prefix(
                     Path::new(&reference_stem)
                         .file_stem()
                         .unwrap()
@@ -132,20 +205,41 @@ impl<'a> LorikeetEngine<'a> {
                         .unwrap(),
                 );
 
-                if Path::new(&output_prefix).exists() && !self.args.get_flag("force") {
-                    let cache = glob::glob(&format!(
-                        "{}/*{}",
-                        &output_prefix,
-                        if mode == "call" {
-                            ".vcf*"
-                        } else if mode == "genotype" {
-                            "strain_coverages.tsv"
-                        } else if mode == "consensus" {
-                            "consensus_*.fna"
-                        } else {
-                            ".vcf*"
-                        }
-                    ))
+                // outputs left incomplete by a failed or killed run are never cached
+                if Path::new(&output_prefix).exists()
+                    && !self.args.get_flag("force")
+                    && !GenomeRuns::is_incomplete(&output_prefix)
+                {
+                    // a shard only counts its own VCF as cached
+                    let call_cache = match ScatterShard::from_args(self.args).ok().flatten() {
+                        Some(shard) => format!("{}.vcf*", shard.file_stem("")),
+                        None => ".vcf*".to_string(),
+                    };
+                    let cache_pattern = if run_outputs.is_combined() {
+                        // pathways are produced in order, so the last one is written last
+                        let last_pathway = run_outputs.pathways().last().copied().unwrap_or("call");
+                        format!(
+                            "{}/*.vcf*",
+                            run_outputs.output_prefix(&output_prefix, last_pathway)
+                        )
+                    } else {
+                        format!(
+                            "{}/*{}",
+                            &output_prefix,
+                            if mode == "call" {
+                                call_cache.as_str()
+                            } else if mode == "genotype" {
+                                "strain_coverages.tsv"
+                            } else if mode == "consensus" {
+                                "consensus_*.fna"
+                            } else if mode == "ani" {
+                                "consensus_ani.tsv"
+                            } else {
+                                ".vcf*"
+                            }
+                        )
+                    };
+                    let cache = glob::glob(&cache_pattern)
                     .expect("failed to interpret glob")
                     .map(|p| {
                         p.expect("Failed to read cached vcf path")
@@ -158,6 +252,10 @@ impl<'a> LorikeetEngine<'a> {
                         if self.args.get_flag("calculate-dnds")
                             || self.args.get_flag("calculate-fst")
                         {
+                            let output_prefix = run_outputs.output_prefix(
+                                &output_prefix,
+                                run_outputs.pathways().first().copied().unwrap_or("call"),
+                            );
                             scope.execute(move || {
                                 // This is here to calculate dnds values if calculate dnds is
                                 // specified but not force. Kind of an edge case, but I think
@@ -187,30 +285,18 @@ impl<'a> LorikeetEngine<'a> {
                                 if self.args.get_flag("calculate-fst") {
                                     {
                                         let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                        pb.progress_bar.set_message(format!(
-                                            "{}: Calculating Fst values...",
-                                            &reference_reader.genomes_and_contigs.genomes[ref_idx],
-                                        ));
+                                        pb.set_stage("Calculating Fst values");
                                     }
                                     
 
-                                    let mut vcf_path = format!(
+                                    let vcf_path = match VcfInput::resolve(&format!(
                                         "{}/{}.vcf",
                                         &output_prefix,
                                         &reference_reader.genomes_and_contigs.genomes[ref_idx]
-                                    );
-
-                                    // check if we should be using gzipped vcf
-                                    if !Path::new(&vcf_path).exists() {
-                                        vcf_path = format!(
-                                            "{}.gz",
-                                            vcf_path
-                                        );
-
-                                        if !Path::new(&vcf_path).exists() {
-                                            panic!("Unable to find vcf file for Fst calculation: {}", vcf_path);
-                                        }
-                                    }
+                                    )) {
+                                        Ok(vcf_path) => vcf_path,
+                                        Err(e) => panic!("Fst calculation failed: {:?}", e),
+                                    };
 
                                     match calculate_fst(
                                         &output_prefix,
@@ -231,10 +317,7 @@ impl<'a> LorikeetEngine<'a> {
                                 if self.args.get_flag("calculate-dnds") {
                                     {
                                         let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                        pb.progress_bar.set_message(format!(
-                                            "{}: Calculating evolutionary rates...",
-                                            &reference_reader.genomes_and_contigs.genomes[ref_idx],
-                                        ));
+                                        pb.set_stage("Calculating evolutionary rates");
                                     }
                                     calculate_dnds(
                                         self.args,
@@ -248,11 +331,11 @@ impl<'a> LorikeetEngine<'a> {
 
                                 {
                                     let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                    pb.progress_bar.set_message(format!(
+                                    pb.set_message(format!(
                                         "{}: All steps completed {}",
                                         &reference, "✔",
                                     ));
-                                    pb.progress_bar.finish_and_clear();
+                                    pb.finish_and_clear();
                                 }
                                 {
                                     let pb = &tree.lock().unwrap()[1];
@@ -260,7 +343,7 @@ impl<'a> LorikeetEngine<'a> {
                                     let pos = pb.progress_bar.position();
                                     let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                                     if pos >= len {
-                                        pb.progress_bar.finish_with_message(format!(
+                                        pb.finish_with_message(format!(
                                             "All genomes analyzed {}",
                                             "✔",
                                         ));
@@ -272,7 +355,7 @@ impl<'a> LorikeetEngine<'a> {
                                     let pos = pb.progress_bar.position();
                                     let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                                     if pos >= len {
-                                        pb.progress_bar.finish_with_message(format!(
+                                        pb.finish_with_message(format!(
                                             "All steps completed {}",
                                             "✔",
                                         ));
@@ -288,11 +371,11 @@ impl<'a> LorikeetEngine<'a> {
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
 
-                                pb.progress_bar.set_message(format!(
+                                pb.set_message(format!(
                                     "{}: Output already present. Run with --force to overwrite",
                                     &genomes_and_contigs.genomes[ref_idx]
                                 ));
-                                pb.progress_bar.finish_and_clear();
+                                pb.finish_and_clear();
                             }
                             {
                                 let pb = &tree.lock().unwrap()[1];
@@ -301,7 +384,7 @@ impl<'a> LorikeetEngine<'a> {
                                 let pos = pb.progress_bar.position();
                                 let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                                 if pos >= len {
-                                    pb.progress_bar.finish_with_message(format!(
+                                    pb.finish_with_message(format!(
                                         "All genomes analyzed {}",
                                         "✔",
                                     ));
@@ -314,7 +397,7 @@ impl<'a> LorikeetEngine<'a> {
                                 let pos = pb.progress_bar.position();
                                 let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                                 if pos >= len {
-                                    pb.progress_bar.finish_with_message(format!(
+                                    pb.finish_with_message(format!(
                                         "All steps completed {}",
                                         "✔",
                                     ));
@@ -325,7 +408,9 @@ impl<'a> LorikeetEngine<'a> {
                     }
                 }
 
-                scope.execute(move || {
+                let genome = genomes_and_contigs.genomes[ref_idx].clone();
+                let genome_output_prefix = output_prefix.clone();
+                scope.execute(move || genome_runs.run(&genome, &genome_output_prefix, move || {
                     let reference = &genomes_and_contigs.genomes[ref_idx];
                     Self::begin_tick(
                         ref_idx + 2,
@@ -367,11 +452,67 @@ impl<'a> LorikeetEngine<'a> {
                     let _per_reference_samples = 0;
                     let _per_reference_short_samples = 0;
 
+                    if mode == "ani" {
+                        // no active regions, assembly or PairHMM, only pileup base counts
+                        {
+                            let pb = &tree.lock().unwrap()[ref_idx + 2];
+                            pb.set_stage("Counting bases and running ANI calculations");
+                        }
+                        let sample_names = ReadGroupSamples::from_bams(&indexed_bam_readers);
+                        let cleaned_sample_names = sample_names
+                            .iter()
+                            .map(|sample_name| sample_name.as_str())
+                            .collect::<Vec<&str>>();
+                        create_dir_all(&output_prefix).expect("Unable to create output directory");
+
+                        let n_sites = PileupAni::from_args(self.args).run(
+                            self.args,
+                            &indexed_bam_readers,
+                            self.short_read_bam_count,
+                            &mut reference_reader,
+                            ref_idx,
+                            flag_filters,
+                            &output_prefix,
+                            &cleaned_sample_names,
+                        );
+                        debug!("{}: {} pileup sites", &reference, n_sites);
+
+                        {
+                            let pb = &tree.lock().unwrap()[ref_idx + 2];
+                            pb.set_message(format!(
+                                "{}: All steps completed {}",
+                                &reference, "✔",
+                            ));
+                            pb.finish_and_clear();
+                        }
+                        {
+                            let pb = &tree.lock().unwrap()[1];
+                            pb.progress_bar.inc(1);
+                            let pos = pb.progress_bar.position();
+                            let len = pb.progress_bar.length().unwrap_or_else(|| 0);
+                            if pos >= len {
+                                pb.finish_with_message(format!("All genomes analyzed {}", "✔",));
+                            }
+                        }
+                        {
+                            let pb = &tree.lock().unwrap()[0];
+                            pb.progress_bar.inc(1);
+                            let pos = pb.progress_bar.position();
+                            let len = pb.progress_bar.length().unwrap_or_else(|| 0);
+                            if pos >= len {
+                                pb.finish_with_message(format!("All steps completed {}", "✔",));
+                            }
+                        }
+                        return Ok(());
+                    }
+
                     if !self.args.get_flag("do-not-call-svs") && self.long_read_bam_count > 0 {
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar
-                                .set_message(format!("{}: Collecting SVs using svim...", pb.key));
+                            pb.set_stage(&format!(
+                                "Collecting SVs using {}",
+                                StructuralVariantCaller::from_args(self.args).name()
+                            ));
                         }
 
                         Self::call_structural_variants(
@@ -387,24 +528,98 @@ impl<'a> LorikeetEngine<'a> {
                         indexed_bam_readers.len()
                     );
 
+                    let mut genome_overrides = PerGenomeConfig::overrides_from_args(
+                        self.args,
+                        &genomes_and_contigs.genomes[ref_idx],
+                    );
+
+                    // the ploidy of the genome in the per genome config takes precedence
+                    let mut expected_strains = None;
+                    if StrainCountEstimator::requested(self.args)
+                        && genome_overrides.ploidy.is_none()
+                    {
+                        {
+                            let pb = &tree.lock().unwrap()[ref_idx + 2];
+                            pb.set_stage("Estimating strain count");
+                        }
+                        // counted on a copy so the contigs are not added twice to the reader
+                        let mut pileup_reader = reference_reader.clone();
+                        let (pileup_contexts, _) = PileupAni::from_args(self.args).calculate(
+                            self.args,
+                            &indexed_bam_readers,
+                            self.short_read_bam_count,
+                            &mut pileup_reader,
+                            ref_idx,
+                            flag_filters,
+                        );
+                        let pileup_genome_size = pileup_reader.target_lens.values().sum::<u64>();
+                        let sample_counts = StrainCountEstimator::from_args(self.args).estimate(
+                            &pileup_contexts,
+                            indexed_bam_readers.len(),
+                            pileup_genome_size,
+                        );
+                        let strain_count = StrainCountEstimator::genome_estimate(&sample_counts);
+                        info!(
+                            "{}: Estimated {} strains, genotyping with ploidy {}",
+                            &reference, strain_count, strain_count
+                        );
+
+                        let sample_names = ReadGroupSamples::from_bams(&indexed_bam_readers);
+                        let cleaned_sample_names = sample_names
+                            .iter()
+                            .map(|sample_name| sample_name.as_str())
+                            .collect::<Vec<&str>>();
+                        create_dir_all(&output_prefix).expect("Unable to create output directory");
+                        if let Err(e) = StrainCountEstimator::write(
+                            &output_prefix,
+                            &reference,
+                            &cleaned_sample_names,
+                            &sample_counts,
+                        ) {
+                            warn!("{}: Unable to write strain counts {:?}", &reference, e);
+                        }
+
+                        genome_overrides.ploidy = Some(strain_count);
+                        expected_strains = Some(strain_count);
+                    }
+                    if !genome_overrides.is_empty() {
+                        debug!(
+                            "{}: Using per genome overrides {:?}",
+                            &genomes_and_contigs.genomes[ref_idx], &genome_overrides
+                        );
+                    }
+
                     let mut assembly_engine = AssemblyRegionWalker::start(
                         self.args,
                         ref_idx,
                         self.short_read_bam_count,
                         self.long_read_bam_count,
                         &indexed_bam_readers,
+                        &genome_overrides,
                         // n_threads,
                     );
+                    assembly_engine.evaluator.set_gpu_pair_hmm(gpu_pair_hmm.clone());
+
+                    if let Some((snp_het, ind_het)) = HeterozygosityPriors::from_args(self.args)
+                        .and_then(|priors| priors.get(&genomes_and_contigs.genomes[ref_idx]))
+                    {
+                        debug!(
+                            "{}: Using heterozygosity priors SNP {} indel {}",
+                            &genomes_and_contigs.genomes[ref_idx], snp_het, ind_het
+                        );
+                        assembly_engine.evaluator.set_heterozygosity(
+                            snp_het,
+                            ind_het,
+                            *self.args.get_one::<f64>("heterozygosity-stdev").unwrap(),
+                        );
+                    }
 
                     {
                         let pb = &tree.lock().unwrap()[ref_idx + 2];
-                        pb.progress_bar.set_message(format!(
-                            "{}: Performing variant calling on active regions...",
-                            pb.key
-                        ));
+                        pb.set_stage("Performing variant calling on active regions");
                     }
 
-                    let (mut contexts, passing_sites) = assembly_engine.collect_shards(
+                    let (mut contexts, mut passing_sites) = assembly_engine.collect_shards(
                         self.args,
                         &indexed_bam_readers,
                         &genomes_and_contigs,
@@ -417,30 +632,231 @@ impl<'a> LorikeetEngine<'a> {
                         &tree
                     );
 
+                    if let Some(minimizer_filter) = assembly_engine.evaluator.minimizer_filter() {
+                        let (seen, discarded) = minimizer_filter.counts();
+                        info!(
+                            "{}: Minimizer pre-filter discarded {} of {} reads ({:.2}%)",
+                            &reference,
+                            discarded,
+                            seen,
+                            minimizer_filter.discard_rate()
+                        );
+                    }
+
+                    let dropped_alleles = assembly_engine.evaluator.dropped_alleles();
+                    if !dropped_alleles.is_empty() {
+                        info!(
+                            "{}: Dropped {} alternate alleles at sites with too many alleles",
+                            &reference,
+                            dropped_alleles.len()
+                        );
+                        if let Err(e) =
+                            dropped_alleles.write(&output_prefix, &reference, &reference_reader)
+                        {
+                            warn!("{}: Unable to write dropped alleles {:?}", &reference, e);
+                        }
+                    }
+
+                    let active_regions = assembly_engine.evaluator.active_regions();
+                    if !active_regions.is_empty() {
+                        debug!(
+                            "{}: {} assembly regions, {} with assembled variation, {} failed, {} downsampled",
+                            &reference,
+                            active_regions.len(),
+                            active_regions.count(RegionStatus::AssembledVariation),
+                            active_regions.count(RegionStatus::Failed),
+                            active_regions.count(RegionStatus::Downsampled)
+                        );
+                        if let Err(e) =
+                            active_regions.write(&output_prefix, &reference, &reference_reader)
+                        {
+                            warn!("{}: Unable to write active regions {:?}", &reference, e);
+                        }
+                    }
+
                     let genome_size = reference_reader
                         .target_lens
                         .iter()
                         .map(|(_, length)| length)
                         .sum::<u64>();
 
+                    // Flag variants in low mappability regions and remove those regions from
+                    // the comparable bases so they do not skew ANI
+                    let genome_size = match ReferenceMask::from_args(
+                        self.args,
+                        &mut reference_reader,
+                        ref_idx,
+                    )? {
+                        Some(reference_mask) => {
+                            let masked_contexts = reference_mask.apply_to_contexts(&mut contexts);
+                            debug!(
+                                "{}: {} masked bases, {} masked variants",
+                                &reference,
+                                reference_mask.masked_bases(),
+                                masked_contexts
+                            );
+                            // per window, before the windows themselves are masked
+                            reference_mask.adjust_compared_bases(
+                                &mut passing_sites,
+                                &assembly_engine.evaluator.accessible_genome().windows(),
+                            );
+                            assembly_engine
+                                .evaluator
+                                .accessible_genome()
+                                .apply_mask(&reference_mask);
+                            assembly_engine
+                                .evaluator
+                                .callable_sites()
+                                .apply_mask(&reference_mask);
+                            genome_size.saturating_sub(reference_mask.masked_bases())
+                        }
+                        None => genome_size,
+                    };
+
+                    // Annotate homopolymer and tandem repeat context, filtering indels in long
+                    // homopolymers if requested
+                    let repeat_filtered = RepeatContext::from_args(self.args).annotate_contexts(
+                        &mut contexts,
+                        &mut reference_reader,
+                        ref_idx,
+                    );
+                    debug!(
+                        "{}: {} indels filtered in long homopolymers",
+                        &reference, repeat_filtered
+                    );
+
+                    // Annotate the entropy and DUST score of the reference around each variant,
+                    // filtering variants in low complexity sequence if requested
+                    let complexity_filtered = SequenceComplexity::from_args(self.args)
+                        .annotate_contexts(&mut contexts, &mut reference_reader, ref_idx);
+                    debug!(
+                        "{}: {} variants filtered in low complexity sequence",
+                        &reference, complexity_filtered
+                    );
+
+                    // Annotate the local copy number of each variant from the read depth
+                    // around it, flagging variants in putative duplications and deletions
+                    match CoverageContext::from_args(self.args).annotate_contexts(
+                        &mut contexts,
+                        &indexed_bam_readers,
+                        &reference_reader,
+                        ref_idx,
+                    ) {
+                        Ok(copy_number_filtered) => debug!(
+                            "{}: {} variants flagged with anomalous copy number",
+                            &reference, copy_number_filtered
+                        ),
+                        Err(e) => warn!(
+                            "{}: Unable to annotate copy number context {:?}",
+                            &reference, e
+                        ),
+                    }
+
+                    // every output below needs the contexts in memory, so they are sorted in
+                    // place. The external sort is kept for the VCF written last, where the sorted
+                    // contexts are streamed rather than held
                     contexts.par_sort_unstable();
+                    AlleleFractionDenominator::from_args(self.args)
+                        .annotate_contexts(&mut contexts);
                     // contexts.reverse();
                     debug!("example variant {:?}", &contexts.first());
 
-                    let cleaned_sample_names = get_cleaned_sample_names(&indexed_bam_readers);
+                    // read group sample names take precedence over the names of the BAM files
+                    let sample_names = ReadGroupSamples::from_bams(&indexed_bam_readers);
+                    let cleaned_sample_names = sample_names
+                        .iter()
+                        .map(|sample_name| sample_name.as_str())
+                        .collect::<Vec<&str>>();
 
                     // ensure output path exists
                     create_dir_all(&output_prefix).expect("Unable to create output directory");
 
+                    if CallableSites::requested(self.args) {
+                        match assembly_engine.evaluator.callable_sites().write(
+                            &output_prefix,
+                            &reference,
+                            &reference_reader,
+                            &cleaned_sample_names,
+                            CallableSites::min_samples_from_args(self.args),
+                        ) {
+                            Ok(callable_bases) => {
+                                debug!("{}: {} callable bases", &reference, callable_bases)
+                            }
+                            Err(e) => {
+                                warn!("{}: Unable to write callable sites {:?}", &reference, e)
+                            }
+                        }
+                    }
+
+                    if let Some(read_end_profile) = assembly_engine.evaluator.read_end_profile() {
+                        if let Err(e) = read_end_profile.write(
+                            &output_prefix,
+                            &reference,
+                            &cleaned_sample_names,
+                        ) {
+                            warn!("{}: Unable to write soft clipping profile {:?}", &reference, e);
+                        }
+                    }
+
+                    // Calibrate QUALs against the discordance between technical replicates
+                    match ReplicateCalibration::from_args(self.args, &cleaned_sample_names) {
+                        Ok(Some(mut calibration)) => {
+                            calibration.estimate(&contexts);
+                            match calibration.false_positive_rate() {
+                                Some(rate) => info!(
+                                    "{}: Estimated false positive rate from replicates {:.4}",
+                                    &reference, rate
+                                ),
+                                None => warn!(
+                                    "{}: No variants were covered in both samples of a replicate pair",
+                                    &reference
+                                ),
+                            }
+                            let calibrated = calibration.calibrate(&mut contexts);
+                            debug!("{}: {} variant QUALs calibrated", &reference, calibrated);
+                            if let Err(e) = calibration.write_report(&format!(
+                                "{}/{}_replicate_calibration.tsv",
+                                &output_prefix, &reference
+                            )) {
+                                warn!("{}: Unable to write replicate calibration {:?}", &reference, e);
+                            }
+                        }
+                        Ok(None) => {}
+                        Err(e) => warn!("{}: Replicate calibration skipped {:?}", &reference, e),
+                    }
+
+                    if self.args.get_flag("short-read-sv-evidence")
+                        && self.short_read_bam_count > 0
+                    {
+                        let sv_evidence = SvEvidenceCollector::from_args(&self.args);
+                        match sv_evidence.run(
+                            &indexed_bam_readers[..self.short_read_bam_count],
+                            &cleaned_sample_names[..self.short_read_bam_count],
+                            reference,
+                            &reference_reader.genomes_and_contigs.separator,
+                            &output_prefix,
+                        ) {
+                            Ok(n_candidates) => debug!(
+                                "{}: {} short read structural variant candidates",
+                                reference, n_candidates
+                            ),
+                            Err(e) => warn!(
+                                "{}: Unable to collect short read structural variant evidence {:?}",
+                                reference, e
+                            ),
+                        }
+                    }
+
                     let qual_by_depth_filter: f64 = *self
                         .args
                         .get_one::<f64>("qual-by-depth-filter")
                         .unwrap();
 
-                    let depth_per_sample_filter: i64 = *self
-                        .args
-                        .get_one::<i64>("depth-per-sample-filter")
-                        .unwrap();
+                    let depth_per_sample_filter: i64 = genome_overrides
+                        .depth_per_sample_filter
+                        .unwrap_or_else(|| {
+                            *self.args.get_one::<i64>("depth-per-sample-filter").unwrap()
+                        });
 
                     let qual_filter = *self
                         .args
@@ -448,115 +864,257 @@ impl<'a> LorikeetEngine<'a> {
                         .unwrap()
                         / -10.0;
 
-                    #[cfg(feature = "fst")]
-                    let vcf_path = format!(
-                        "{}/{}.vcf",
-                        &output_prefix, &reference_reader.genomes_and_contigs.genomes[ref_idx]
+                    // Variants failing the QD and depth filters are kept in the VCF with a
+                    // FILTER tag unless hard filtering was requested
+                    let hard_filter = self.args.get_flag("hard-filter");
+                    let quality_filtered = VariantContextUtils::apply_quality_filters(
+                        &mut contexts,
+                        qual_by_depth_filter,
+                        depth_per_sample_filter,
+                        hard_filter,
+                    );
+                    debug!(
+                        "{}: {} variants failed the QD or depth filters",
+                        &reference, quality_filtered
                     );
-                    if mode == "call" {
-                        // calculate ANI statistics for short reads only
+
+                    // reference bias of the allele depths of each sample at heterozygous-like
+                    // sites, optionally corrected for when estimating strain abundances
+                    let reference_bias_correction = if ReferenceBias::requested(self.args) {
+                        let mut reference_bias = ReferenceBias::from_args(
+                            self.args,
+                            cleaned_sample_names.len(),
+                            depth_per_sample_filter,
+                        );
+                        reference_bias.add_contexts(&contexts);
+                        for (sample_index, sample_name) in cleaned_sample_names.iter().enumerate() {
+                            if reference_bias.is_biased(sample_index) {
+                                warn!(
+                                    "{}: Allele depths of sample {} are biased towards the \
+                                    reference, mean reference fraction {:.3}",
+                                    &reference,
+                                    sample_name,
+                                    reference_bias.samples()[sample_index]
+                                        .mean_reference_fraction()
+                                );
+                            }
+                        }
+                        if let Err(e) =
+                            reference_bias.write(&output_prefix, &reference, &cleaned_sample_names)
+                        {
+                            warn!("{}: Unable to write reference bias {:?}", &reference, e);
+                        }
+                        if self.args.get_flag("correct-reference-bias") {
+                            reference_bias.correction_factors()
+                        } else {
+                            Vec::new()
+                        }
+                    } else {
+                        Vec::new()
+                    };
+
+                    // nucleotide diversity and Fst over the bases passing the depth filters
+                    let accessible_genome = assembly_engine.evaluator.accessible_genome();
+                    if self.args.get_flag("calculate-fst") && !accessible_genome.is_empty() {
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar.set_message(format!(
-                                "{}: Running ANI calculations...",
-                                pb.key
-                            ));
+                            pb.set_stage("Calculating nucleotide diversity");
                         }
-                        let mut ani_calculator = ANICalculator::new(
-                            self.short_read_bam_count + self.long_read_bam_count,
+                        let mut diversity_calculator = DiversityCalculator::new(
+                            cleaned_sample_names.len(),
+                            depth_per_sample_filter,
+                            accessible_genome,
+                            passing_sites.clone(),
                         );
-                        ani_calculator.run_calculator(
+                        diversity_calculator.calculate(
                             &mut contexts,
-                            &output_prefix,
-                            &cleaned_sample_names,
-                            reference,
-                            genome_size,
-                            Some(passing_sites),
                             qual_by_depth_filter,
                             qual_filter,
-                            depth_per_sample_filter,
                         );
-
-                        {
-                            let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar.set_message(format!(
-                                "{}: Generating VCF file of {} variant positions...",
-                                &reference,
-                                contexts.len()
-                            ));
-                        }
-                        assembly_engine.evaluator.write_vcf(
+                        if let Err(e) = diversity_calculator.write(
                             &output_prefix,
-                            &contexts,
+                            &reference,
                             &cleaned_sample_names,
                             &reference_reader,
-                            false,
+                        ) {
+                            warn!("{}: Unable to write nucleotide diversity {:?}", &reference, e);
+                        }
+                    }
+
+                    // folded and unfolded site frequency spectra across samples
+                    if self.args.get_flag("calculate-sfs") {
+                        let ploidy = genome_overrides
+                            .ploidy
+                            .unwrap_or_else(|| *self.args.get_one::<usize>("ploidy").unwrap());
+                        let mut site_frequency_spectrum =
+                            SiteFrequencySpectrum::new(ploidy * cleaned_sample_names.len());
+                        site_frequency_spectrum.calculate(
+                            &mut contexts,
+                            qual_by_depth_filter,
+                            qual_filter,
                         );
+                        debug!(
+                            "{}: Site frequency spectrum of {} sites, skipped {} sites",
+                            &reference,
+                            site_frequency_spectrum.counted_sites(),
+                            site_frequency_spectrum.skipped_sites()
+                        );
+                        if let Err(e) = site_frequency_spectrum.write(&output_prefix, &reference) {
+                            warn!(
+                                "{}: Unable to write site frequency spectrum {:?}",
+                                &reference, e
+                            );
+                        }
+                    }
 
+                    #[cfg(feature = "fst")]
+                    let vcf_file_stem = assembly_engine.evaluator.vcf_file_stem(&reference_reader);
+                    if run_outputs.call {
+                        let output_prefix = run_outputs.output_prefix(&output_prefix, "call");
+                        create_dir_all(&output_prefix).expect("Unable to create output directory");
                         #[cfg(feature = "fst")]
-                        if self.args.get_flag("calculate-fst") {
+                        let vcf_path = format!("{}/{}.vcf", &output_prefix, &vcf_file_stem);
+                        if assembly_engine.evaluator.emits_haplotype_records() {
+                            // haplotype records span whole regions, so site level statistics
+                            // can not be calculated from them
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Calculating Fst values...",
-                                    &reference,
+                                pb.set_stage(&format!(
+                                    "Generating VCF file of {} haplotypes",
+                                    contexts.len()
                                 ));
                             }
-                            match calculate_fst(
+                            assembly_engine.evaluator.write_vcf(
                                 &output_prefix,
-                                &reference_reader.genomes_and_contigs.genomes[ref_idx],
-                                vcf_path.as_str(),
-                                ploidy,
-                                depth_per_sample_filter,
-                            ) {
-                                Ok(_) => {
-                                    //
-                                }
-                                Err(e) => {
-                                    warn!("Python error {:?}", e);
-                                }
+                                &contexts,
+                                &cleaned_sample_names,
+                                &reference_reader,
+                                false,
+                            );
+                        } else {
+                            // calculate ANI statistics for short reads only
+                            {
+                                let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                pb.set_stage("Running ANI calculations");
                             }
-                        }
+                            let mut ani_calculator = ANICalculator::from_args(
+                                self.args,
+                                self.short_read_bam_count + self.long_read_bam_count,
+                            );
+                            ani_calculator.run_calculator(
+                                &mut contexts,
+                                &output_prefix,
+                                &cleaned_sample_names,
+                                &assembly_engine.evaluator.vcf_file_stem(&reference_reader),
+                                genome_size,
+                                Some(passing_sites.clone()),
+                                qual_by_depth_filter,
+                                qual_filter,
+                                depth_per_sample_filter,
+                            );
 
-                        if self.args.get_flag("calculate-dnds") {
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Calculating evolutionary rates...",
-                                    &reference,
+                                pb.set_stage(&format!(
+                                    "Generating VCF file of {} variant positions",
+                                    contexts.len()
                                 ));
                             }
-                            calculate_dnds(
+                            assembly_engine.evaluator.write_vcf(
+                                &output_prefix,
+                                &contexts,
+                                &cleaned_sample_names,
+                                &reference_reader,
+                                false,
+                            );
+
+                            #[cfg(feature = "fst")]
+                            if self.args.get_flag("calculate-fst") {
+                                {
+                                    let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                    pb.set_stage("Calculating Fst values");
+                                }
+                                match calculate_fst(
+                                    &output_prefix,
+                                    &reference_reader.genomes_and_contigs.genomes[ref_idx],
+                                    vcf_path.as_str(),
+                                    ploidy,
+                                    depth_per_sample_filter,
+                                ) {
+                                    Ok(_) => {
+                                        //
+                                    }
+                                    Err(e) => {
+                                        warn!("Python error {:?}", e);
+                                    }
+                                }
+                            }
+
+                            if self.args.get_flag("calculate-dnds") {
+                                {
+                                    let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                    pb.set_stage("Calculating evolutionary rates");
+                                }
+                                calculate_dnds(
+                                    self.args,
+                                    &reference_stem,
+                                    output_prefix.as_str(),
+                                    &mut reference_reader,
+                                    ref_idx,
+                                    cleaned_sample_names.len(),
+                                );
+                            }
+
+                            summarise_markers(
                                 self.args,
                                 &reference_stem,
                                 output_prefix.as_str(),
                                 &mut reference_reader,
                                 ref_idx,
-                                cleaned_sample_names.len(),
+                                &contexts,
+                                None,
+                                &cleaned_sample_names,
+                                &[],
                             );
                         }
-                    } else if mode == "genotype" {
+                    }
+                    if run_outputs.genotype {
+                        let output_prefix = run_outputs.output_prefix(&output_prefix, "genotype");
+                        create_dir_all(&output_prefix).expect("Unable to create output directory");
+                        #[cfg(feature = "fst")]
+                        let vcf_path = format!("{}/{}.vcf", &output_prefix, &vcf_file_stem);
                         // If a variant context contains more than one allele, we need to split
                         // this context into n different contexts, where n is number of variant
                         // alleles
                         let (mut split_contexts, filtered_contexts) =
                             VariantContextUtils::split_contexts(
-                                contexts,
+                                if run_outputs.needed_after("genotype") {
+                                    contexts.clone()
+                                } else {
+                                    std::mem::take(&mut contexts)
+                                },
                                 qual_by_depth_filter,
                                 *self.args
                                     .get_one::<i64>("min-variant-depth-for-genotyping")
                                     .unwrap() as i32,
+                                hard_filter,
                             );
+
+                        // Filtered contexts take no part in clustering, abundances or strains, so
+                        // they are kept on disk and only read back by region for the marker
+                        // summaries and one contig at a time for the VCF
+                        let mut filtered_store =
+                            VariantStore::new(VariantStore::DEFAULT_CHUNK_SIZE)?;
+                        filtered_store.extend(filtered_contexts)?;
+                        filtered_store.flush()?;
                         
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar.set_message(format!(
-                                "{}: Running ANI calculations...",
-                                pb.key
-                            ));
+                            pb.set_stage("Running ANI calculations");
                         }
                         // calculate ANI statistics
-                        let mut ani_calculator = ANICalculator::new(
+                        let mut ani_calculator = ANICalculator::from_args(
+                            self.args,
                             self.short_read_bam_count + self.long_read_bam_count,
                         );
                         ani_calculator.run_calculator(
@@ -565,7 +1123,7 @@ impl<'a> LorikeetEngine<'a> {
                             &cleaned_sample_names,
                             reference,
                             genome_size,
-                            Some(passing_sites),
+                            Some(passing_sites.clone()),
                             qual_by_depth_filter,
                             qual_filter,
                             depth_per_sample_filter,
@@ -574,14 +1132,25 @@ impl<'a> LorikeetEngine<'a> {
                         if split_contexts.len() >= 1 {
                             // Perform UMAP and HDBSCAN clustering followed by variant group
                             // read linkage clustering.
-                            let clustering_engine = HaplotypeClusteringEngine::new(
+                            let mut clustering_engine = HaplotypeClusteringEngine::new(
                                 output_prefix.as_str(),
                                 split_contexts,
                                 &reference_reader,
                                 ref_idx,
                                 indexed_bam_readers.len(),
                                 n_threads,
+                                *self
+                                    .args
+                                    .get_one::<f64>("min-strain-divergence")
+                                    .unwrap(),
+                                *self
+                                    .args
+                                    .get_one::<usize>("min-linked-reads")
+                                    .unwrap(),
                             );
+                            clustering_engine
+                                .set_export_linkage_graph(self.args.get_flag("export-linkage-graph"));
+                            clustering_engine.set_expected_strains(expected_strains);
                             let (n_strains, split_contexts) = clustering_engine.perform_clustering(
                                 &indexed_bam_readers,
                                 flag_filters,
@@ -596,51 +1165,191 @@ impl<'a> LorikeetEngine<'a> {
                             // Get strain abundances
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Calculating genotype abundances...",
-                                    &reference,
-                                ));
+                                pb.set_stage("Calculating genotype abundances");
                             }
-                            let abundance_calculator_engine = AbundanceCalculatorEngine::new(
+                            let mut abundance_calculator_engine = AbundanceCalculatorEngine::new(
                                 split_contexts,
                                 &reference_reader.genomes_and_contigs.genomes[ref_idx],
                                 &output_prefix,
                                 &cleaned_sample_names,
                             );
+                            abundance_calculator_engine
+                                .set_output_formats(AbundanceFormat::from_args(self.args)?);
+                            abundance_calculator_engine.set_cami_profiles(cami_profiles.clone());
+                            abundance_calculator_engine
+                                .set_reference_bias_correction(reference_bias_correction.clone());
+
+                            let (strain_ids_present, mut split_contexts) =
+                                abundance_calculator_engine.run_abundance_calculator(
+                                    n_strains,
+                                    cleaned_sample_names.len(),
+                                );
+                            if !strain_ids_present.is_empty() {
+                                let annotated = StrainDiscrimination::from_contexts(
+                                    &split_contexts,
+                                    &strain_ids_present,
+                                    cleaned_sample_names.len(),
+                                )
+                                .annotate_contexts(&mut split_contexts);
+                                debug!(
+                                    "{}: {} variants scored for strain discriminating power",
+                                    &reference, annotated
+                                );
+
+                                let mut strain_frequency_estimator =
+                                    StrainFrequencyEstimator::new(&strain_ids_present);
+                                strain_frequency_estimator.set_reference_bias_correction(
+                                    reference_bias_correction.clone(),
+                                );
+                                if let Err(e) = strain_frequency_estimator
+                                    .write_strain_frequencies(
+                                        &split_contexts,
+                                        &output_prefix,
+                                        &reference_reader.genomes_and_contigs.genomes[ref_idx],
+                                        &cleaned_sample_names,
+                                    )
+                                {
+                                    warn!(
+                                        "{}: Unable to write strain frequencies {:?}",
+                                        &reference, e
+                                    );
+                                }
+
+                                if let Err(e) = PhasingStatistics::new(depth_per_sample_filter)
+                                    .write(
+                                        &split_contexts,
+                                        &output_prefix,
+                                        &reference_reader.genomes_and_contigs.genomes[ref_idx],
+                                        &cleaned_sample_names,
+                                    )
+                                {
+                                    warn!(
+                                        "{}: Unable to write phasing statistics {:?}",
+                                        &reference, e
+                                    );
+                                }
+
+                                if let Some(binner) =
+                                    StrainReadBinner::from_args(self.args, &strain_ids_present)
+                                {
+                                    {
+                                        let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                        pb.set_stage("Binning long reads by strain");
+                                    }
+                                    for (sample_index, bam_path) in indexed_bam_readers
+                                        .iter()
+                                        .enumerate()
+                                        .skip(self.short_read_bam_count)
+                                    {
+                                        match binner.bin_reads(
+                                            &split_contexts,
+                                            bam_path,
+                                            sample_index,
+                                            &output_prefix,
+                                            &reference_reader.genomes_and_contigs.genomes[ref_idx],
+                                            cleaned_sample_names[sample_index],
+                                            n_threads,
+                                        ) {
+                                            Ok(read_counts) => debug!(
+                                                "{}: Long reads of {} binned by strain {:?}",
+                                                &reference,
+                                                cleaned_sample_names[sample_index],
+                                                read_counts
+                                            ),
+                                            Err(e) => warn!(
+                                                "{}: Unable to bin long reads of {} by strain {:?}",
+                                                &reference, cleaned_sample_names[sample_index], e
+                                            ),
+                                        }
+                                    }
+                                }
+                            }
+
+                            // let strain_ids_present = (0..n_strains).into_iter().collect::<Vec<usize>>();
+                            split_contexts.par_sort_unstable();
+
+                            // Write genotypes to disk, reference specific
+                            {
+                                let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                pb.set_stage("Writing strains");
+                            }
+                            let strain_ids_present = if strain_ids_present.len() > 0 {
+                                strain_ids_present
+                            } else {
+                                vec![0]
+                            };
+                            summarise_markers(
+                                self.args,
+                                &reference_stem,
+                                output_prefix.as_str(),
+                                &mut reference_reader,
+                                ref_idx,
+                                &split_contexts,
+                                Some(&filtered_store),
+                                &cleaned_sample_names,
+                                &strain_ids_present,
+                            );
+                            let mut reference_writer =
+                                ReferenceWriter::new(reference_reader.clone(), &output_prefix);
+                            reference_writer.write_strain_allele_matrix(
+                                &split_contexts,
+                                ref_idx,
+                                &strain_ids_present,
+                            );
+                            let strain_coordinates = reference_writer.generate_strains(
+                                &mut split_contexts,
+                                ref_idx,
+                                strain_ids_present,
+                            );
 
-                            let (strain_ids_present, mut split_contexts) =
-                                abundance_calculator_engine.run_abundance_calculator(
-                                    n_strains,
-                                    cleaned_sample_names.len(),
-                                );
+                            if let Some(polisher) = StrainPolisher::from_args(self.args) {
+                                {
+                                    let pb = &tree.lock().unwrap()[ref_idx + 2];
+                                    pb.set_stage("Polishing strains");
+                                }
+                                match polisher.polish_strains(
+                                    &output_prefix,
+                                    &reference_reader.genomes_and_contigs.genomes[ref_idx],
+                                    &strain_coordinates,
+                                    &cleaned_sample_names[self.short_read_bam_count..],
+                                ) {
+                                    Ok(summaries) => {
+                                        for (strain_id, summary) in summaries {
+                                            info!(
+                                                "{}: {} corrections to strain {} from {} reads",
+                                                &reference,
+                                                summary.corrections,
+                                                strain_id,
+                                                summary.reads
+                                            );
+                                        }
+                                    }
+                                    Err(e) => warn!(
+                                        "{}: Unable to polish strains {:?}",
+                                        &reference, e
+                                    ),
+                                }
+                            }
 
-                            // let strain_ids_present = (0..n_strains).into_iter().collect::<Vec<usize>>();
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar
-                                    .set_message(
-                                        format!("{}: Generating VCF file...", &reference,),
-                                    );
+                                pb.set_stage("Generating VCF file");
                             }
-
-                            split_contexts.extend(filtered_contexts);
-                            split_contexts.par_sort_unstable();
-                            assembly_engine.evaluator.write_vcf(
+                            write_genotype_vcf(
+                                self.args,
+                                &assembly_engine.evaluator,
                                 &output_prefix,
-                                &split_contexts,
+                                split_contexts,
+                                &filtered_store,
                                 &cleaned_sample_names,
                                 &reference_reader,
-                                true,
-                            );
+                            )?;
 
                             #[cfg(feature = "fst")]
                             if self.args.get_flag("calculate-fst") {
                                 {
                                     let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                    pb.progress_bar.set_message(format!(
-                                        "{}: Calculating Fst values...",
-                                        &reference,
-                                    ));
+                                    pb.set_stage("Calculating Fst values");
                                 }
                                 match calculate_fst(
                                     &output_prefix,
@@ -661,10 +1370,7 @@ impl<'a> LorikeetEngine<'a> {
                             if self.args.get_flag("calculate-dnds") {
                                 {
                                     let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                    pb.progress_bar.set_message(format!(
-                                        "{}: Calculating evolutionary rates...",
-                                        &reference,
-                                    ));
+                                    pb.set_stage("Calculating evolutionary rates");
                                 }
                                 calculate_dnds(
                                     self.args,
@@ -675,42 +1381,45 @@ impl<'a> LorikeetEngine<'a> {
                                     cleaned_sample_names.len(),
                                 );
                             }
-
+                        } else {
+                            summarise_markers(
+                                self.args,
+                                &reference_stem,
+                                output_prefix.as_str(),
+                                &mut reference_reader,
+                                ref_idx,
+                                &split_contexts,
+                                Some(&filtered_store),
+                                &cleaned_sample_names,
+                                &[],
+                            );
                             // Write genotypes to disk, reference specific
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar
-                                    .set_message(format!("{}: Writing strains...", &reference,));
+                                pb.set_stage("Writing reference strain");
                             }
                             let mut reference_writer =
-                                ReferenceWriter::new(reference_reader, &output_prefix);
+                                ReferenceWriter::new(reference_reader.clone(), &output_prefix);
                             reference_writer.generate_strains(
-                                split_contexts,
+                                &mut split_contexts,
                                 ref_idx,
-                                if strain_ids_present.len() > 0 {
-                                    strain_ids_present
-                                } else {
-                                    vec![0]
-                                },
+                                vec![0],
                             );
-                        } else {
-                            split_contexts.extend(filtered_contexts);
-                            assembly_engine.evaluator.write_vcf(
+                            write_genotype_vcf(
+                                self.args,
+                                &assembly_engine.evaluator,
                                 &output_prefix,
-                                &split_contexts,
+                                split_contexts,
+                                &filtered_store,
                                 &cleaned_sample_names,
                                 &reference_reader,
-                                true,
-                            );
+                            )?;
 
                             #[cfg(feature = "fst")]
                             if self.args.get_flag("calculate-fst") {
                                 {
                                     let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                    pb.progress_bar.set_message(format!(
-                                        "{}: Calculating Fst values...",
-                                        &reference,
-                                    ));
+                                    pb.set_stage("Calculating Fst values");
                                 }
                                 match calculate_fst(
                                     &output_prefix,
@@ -731,10 +1440,7 @@ impl<'a> LorikeetEngine<'a> {
                             if self.args.get_flag("calculate-dnds") {
                                 {
                                     let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                    pb.progress_bar.set_message(format!(
-                                        "{}: Calculating evolutionary rates...",
-                                        &reference,
-                                    ));
+                                    pb.set_stage("Calculating evolutionary rates");
                                 }
                                 calculate_dnds(
                                     self.args,
@@ -745,28 +1451,20 @@ impl<'a> LorikeetEngine<'a> {
                                     cleaned_sample_names.len(),
                                 );
                             }
-                            // Write genotypes to disk, reference specific
-                            {
-                                let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Writing reference strain...",
-                                    &reference,
-                                ));
-                            }
-                            let mut reference_writer =
-                                ReferenceWriter::new(reference_reader, &output_prefix);
-                            reference_writer.generate_strains(split_contexts, ref_idx, vec![0]);
                         }
-                    } else if mode == "consensus" {
+                    }
+                    if run_outputs.consensus {
+                        let output_prefix = run_outputs.output_prefix(&output_prefix, "consensus");
+                        create_dir_all(&output_prefix).expect("Unable to create output directory");
+                        #[cfg(feature = "fst")]
+                        let vcf_path = format!("{}/{}.vcf", &output_prefix, &vcf_file_stem);
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar.set_message(format!(
-                                "{}: Running ANI calculations...",
-                                pb.key
-                            ));
+                            pb.set_stage("Running ANI calculations");
                         }
                         // calculate ANI statistics
-                        let mut ani_calculator = ANICalculator::new(
+                        let mut ani_calculator = ANICalculator::from_args(
+                            self.args,
                             self.short_read_bam_count + self.long_read_bam_count,
                         );
                         ani_calculator.run_calculator(
@@ -783,8 +1481,7 @@ impl<'a> LorikeetEngine<'a> {
                         // Get sample distances
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar
-                                .set_message(format!("{}: Generating VCF file...", &reference,));
+                            pb.set_stage("Generating VCF file");
                         }
                         assembly_engine.evaluator.write_vcf(
                             &output_prefix,
@@ -798,10 +1495,7 @@ impl<'a> LorikeetEngine<'a> {
                         if self.args.get_flag("calculate-fst") {
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Calculating Fst values...",
-                                    &reference,
-                                ));
+                                pb.set_stage("Calculating Fst values");
                             }
                             match calculate_fst(
                                 &output_prefix,
@@ -822,10 +1516,7 @@ impl<'a> LorikeetEngine<'a> {
                         if self.args.get_flag("calculate-dnds") {
                             {
                                 let pb = &tree.lock().unwrap()[ref_idx + 2];
-                                pb.progress_bar.set_message(format!(
-                                    "{}: Calculating evolutionary rates...",
-                                    &reference,
-                                ));
+                                pb.set_stage("Calculating evolutionary rates");
                             }
                             calculate_dnds(
                                 self.args,
@@ -839,10 +1530,7 @@ impl<'a> LorikeetEngine<'a> {
 
                         {
                             let pb = &tree.lock().unwrap()[ref_idx + 2];
-                            pb.progress_bar.set_message(format!(
-                                "{}: Generating consensus genomes...",
-                                &reference,
-                            ));
+                            pb.set_stage("Generating consensus genomes");
                         }
                         // variant_matrix.generate_distances();
                         let mut reference_writer =
@@ -851,14 +1539,15 @@ impl<'a> LorikeetEngine<'a> {
                             contexts,
                             ref_idx,
                             &cleaned_sample_names,
+                            &ConsensusOptions::from_args(self.args),
+                            assembly_engine.evaluator.consensus_depths(),
                         );
-                    };
+                    }
 
                     {
                         let pb = &tree.lock().unwrap()[ref_idx + 2];
-                        pb.progress_bar
-                            .set_message(format!("{}: All steps completed {}", &reference, "✔",));
-                        pb.progress_bar.finish_and_clear();
+                        pb.set_message(format!("{}: All steps completed {}", &reference, "✔",));
+                        pb.finish_and_clear();
                     }
                     {
                         let pb = &tree.lock().unwrap()[1];
@@ -866,8 +1555,7 @@ impl<'a> LorikeetEngine<'a> {
                         let pos = pb.progress_bar.position();
                         let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                         if pos >= len {
-                            pb.progress_bar
-                                .finish_with_message(format!("All genomes analyzed {}", "✔",));
+                            pb.finish_with_message(format!("All genomes analyzed {}", "✔",));
                         }
                     }
                     {
@@ -876,115 +1564,161 @@ impl<'a> LorikeetEngine<'a> {
                         let pos = pb.progress_bar.position();
                         let len = pb.progress_bar.length().unwrap_or_else(|| 0);
                         if pos >= len {
-                            pb.progress_bar
-                                .finish_with_message(format!("All steps completed {}", "✔",));
+                            pb.finish_with_message(format!("All steps completed {}", "✔",));
                         }
                     }
-                });
+                    Ok(())
+                }));
             }
 
             // self.multi.join().unwrap();
         });
+
+        let genomes = self
+            .reference_map
+            .values()
+            .map(|reference_stem| {
+                Path::new(reference_stem)
+                    .file_stem()
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string()
+            })
+            .collect::<Vec<String>>();
+        let phasing_tables = genomes
+            .iter()
+            .flat_map(|genome| {
+                output_layout
+                    .outputs_of_type(genome, "phasing")
+                    .into_iter()
+                    .map(move |path| (genome.clone(), path))
+            })
+            .collect::<Vec<(String, PathBuf)>>();
+        if !phasing_tables.is_empty() {
+            match PhasingStatistics::write_run_report(
+                &output_layout.output_directory,
+                &phasing_tables,
+            ) {
+                Ok(report_path) => info!("Phasing report written to {}", report_path.display()),
+                Err(e) => warn!("Unable to write phasing report {:?}", e),
+            }
+        }
+        if !cami_profiles.is_empty() {
+            match cami_profiles.write(&output_layout.output_directory) {
+                Ok(paths) => info!(
+                    "CAMI profiles of {} samples written to {}",
+                    paths.len(),
+                    &output_layout.output_directory
+                ),
+                Err(e) => warn!("Unable to write CAMI profiles {:?}", e),
+            }
+        }
+        match output_layout.write_manifest(&genomes) {
+            Ok(manifest_path) => info!("Output manifest written to {}", manifest_path.display()),
+            Err(e) => warn!("Unable to write output manifest {:?}", e),
+        }
+
+        let failed_genomes = genome_runs.failed();
+        if !failed_genomes.is_empty() {
+            warn!(
+                "{} of {} genomes failed and were skipped: {}",
+                failed_genomes.len(),
+                genomes.len(),
+                failed_genomes.join(", ")
+            );
+        }
+        genome_runs.status(genomes.len())
     }
 
-    /// Uses svim to call potential structural variants along the current reference genome
-    /// Any retrieved structural variants are stored in their own VCF file but also
-    /// used as `feature` variants to guide potential short read calls of these variants
+    /// Uses the caller chosen by --sv-caller to call potential structural variants along the
+    /// current reference genome. Any retrieved structural variants are stored in their own VCF
+    /// file but also used as `feature` variants to guide potential short read calls of these
+    /// variants
     fn call_structural_variants(
         indexed_longread_bam_readers: &[String],
         output_prefix: &str,
         reference: &str,
         args: &clap::ArgMatches,
     ) {
-        check_for_svim();
+        let sv_caller = StructuralVariantCaller::from_args(args);
+        sv_caller.check();
         check_for_bcftools();
         let min_mapq = args.get_one::<u8>("min-mapq").unwrap();
         let min_sv_qual = args.get_one::<u8>("min-sv-qual").unwrap();
+        let threads = std::cmp::max(
+            *args.get_one::<usize>("threads").unwrap()
+                / std::cmp::max(indexed_longread_bam_readers.len(), 1),
+            1,
+        );
         debug!("bam readers {:?}", indexed_longread_bam_readers);
-        // use svim on each longread sample
+        // call SVs on each longread sample
         indexed_longread_bam_readers
             .into_par_iter()
             .enumerate()
             .for_each(|(idx, bam_reader)| {
 
-                // svim path is just output prefix with numbered svim
-                let svim_path = format!("{}/svim_{}", output_prefix, idx);
+                // working directory is just output prefix with the numbered caller
+                let sv_path = sv_caller.working_directory(output_prefix, idx);
 
                 let cmd_string = format!(
                     "set -e -o pipefail; \
-                    svim alignment \
-                    --skip_genotyping \
-                    --min_mapq {} --sequence_alleles \
-                    {} {} {}; \
+                    {}; \
                     bcftools sort {}/variants.vcf | bcftools view -i 'QUAL >= {}' > {}/variants_filtered_sorted.vcf; \
-                    bgzip {}/variants_filtered_sorted.vcf; bcftools index {}/variants_filtered_sorted.vcf.gz",
-                    min_mapq,
-                    &svim_path,
-                    bam_reader,
-                    reference,
-                    &svim_path,
+                    bgzip -f {}/variants_filtered_sorted.vcf; bcftools index -f {}/variants_filtered_sorted.vcf.gz",
+                    sv_caller.command(bam_reader, reference, &sv_path, *min_mapq, threads),
+                    &sv_path,
                     &min_sv_qual,
-                    &svim_path,
-                    &svim_path,
-                    &svim_path,
+                    &sv_path,
+                    &sv_path,
+                    &sv_path,
                 );
 
                 debug!("Queuing cmd string {}", &cmd_string);
 
-                // We do not want to capture any stdio from svim as it produces too much
-                // and we can't clear the buffer before it starts hanging: https://github.com/rust-lang/rust/issues/45572
-                finish_command_safely(
-                    Command::new("bash")
-                        .arg("-c")
-                        .arg(&cmd_string)
-                        .stderr(Stdio::null())
-                        .spawn()
-                        .expect("Unable to execute svim command"),
-                    "svim"
+                // The caller writes too much to stderr to buffer, so only its tail is kept while
+                // it is drained: https://github.com/rust-lang/rust/issues/45572
+                ExternalCommand::run_or_exit(
+                    Command::new("bash").arg("-c").arg(&cmd_string),
+                    sv_caller.executable(),
                 );
         });
 
         if indexed_longread_bam_readers.len() > 1 {
-            // once svim has run on each sample, we need to merge the VCF files together
-            // the easiest way to do this is bcftools merge
+            // once the caller has run on each sample, we need to merge the VCF files together
+            // the easiest way to do this is bcftools merge. Callers name every sample the same
+            // or after the BAM file, so duplicate sample names are allowed
             let cmd_string = format!(
                 "set -e -o pipefail; \
-                bcftools merge {}/svim_*/variants_filtered_sorted.vcf.gz | bcftools sort > {}/structural_variants.vcf; \
-                bgzip {}/structural_variants.vcf; bcftools index {}/structural_variants.vcf.gz",
+                bcftools merge --force-samples {}/{}_*/variants_filtered_sorted.vcf.gz | bcftools sort > {}/structural_variants.vcf; \
+                bgzip -f {}/structural_variants.vcf; bcftools index -f {}/structural_variants.vcf.gz",
                 output_prefix,
+                sv_caller.name(),
                 output_prefix,
                 output_prefix,
                 output_prefix
             );
 
             debug!("Queuing cmd string {}", &cmd_string);
-            finish_command_safely(
-                Command::new("bash")
-                    .arg("-c")
-                    .arg(&cmd_string)
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .expect("Unable to execute bcftools command"),
+            ExternalCommand::run_or_exit(
+                Command::new("bash").arg("-c").arg(&cmd_string),
                 "bcftools",
             );
         } else {
             // if there is only one longread sample just use that one
             let cmd_string = format!(
                 "set -e -o pipefail; \
-                mv {}/svim_0/variants_filtered_sorted.vcf.gz {}/structural_variants.vcf.gz; \
-                bcftools index {}/structural_variants.vcf.gz",
-                output_prefix, output_prefix, output_prefix
+                mv {}/variants_filtered_sorted.vcf.gz {}/structural_variants.vcf.gz; \
+                bcftools index -f {}/structural_variants.vcf.gz",
+                sv_caller.working_directory(output_prefix, 0),
+                output_prefix,
+                output_prefix
             );
 
             debug!("Queuing cmd string {}", &cmd_string);
-            finish_command_safely(
-                Command::new("bash")
-                    .arg("-c")
-                    .arg(&cmd_string)
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .expect("Unable to execute bcftools command"),
-                "mv",
+            ExternalCommand::run_or_exit(
+                Command::new("bash").arg("-c").arg(&cmd_string),
+                "bcftools",
             );
         }
     }
@@ -1068,7 +1802,7 @@ impl<'a> LorikeetEngine<'a> {
 
         pb.enable_steady_tick(Duration::from_millis(200));
 
-        pb.set_message(format!("{}: {}...", &elem.key, message));
+        elem.set_stage(message);
     }
 }
 
@@ -1084,9 +1818,9 @@ pub fn start_lorikeet_engine<
     mode: &str,
     flag_filters: FlagFilter,
     genomes_and_contigs: GenomesAndContigs,
-    tmp_bam_file_cache: Option<TempDir>,
-    concatenated_genomes: Option<NamedTempFile>,
-) -> Result<(), BirdToolError> {
+    tmp_bam_file_cache: Option<TempResource>,
+    concatenated_genomes: Option<ConcatenatedReference>,
+) -> Result<ExitStatus, BirdToolError> {
     let threads = match m.get_one::<usize>("threads") {
         Some(val) => *val,
         None => {
@@ -1097,6 +1831,9 @@ pub fn start_lorikeet_engine<
     };
     debug!("Parsing reference info...");
     let references = ReferenceReaderUtils::parse_references(&m);
+    // Compressed references need to be decompressed before they can be indexed
+    let (references, _decompressed_references) =
+        ReferenceReaderUtils::decompress_references(references);
     debug!("Parsing reference info...done. ({} references)", references.len());
     let references = references.par_iter().map(|p| &**p).collect::<Vec<&str>>();
     debug!("Retrieving references...");
@@ -1104,6 +1841,11 @@ pub fn start_lorikeet_engine<
         Some(ref file) => file.path().to_str().unwrap().to_string(),
         None => "".to_string(),
     };
+    // the index of a cached reference is kept for later runs
+    let reference_is_cached = concatenated_genomes
+        .as_ref()
+        .map(|reference| reference.is_cached())
+        .unwrap_or(false);
     ReferenceReaderUtils::retrieve_reference(&Some(
         concatenated_temp_file_name.clone(),
     ));
@@ -1123,16 +1865,19 @@ pub fn start_lorikeet_engine<
 
 
     // Finish each BAM source
+    let mut competitive_mapping = CompetitiveMappingReport::new(&genomes_and_contigs.genomes);
     if m.contains_id("longreads") || m.contains_id("longread-bam-files") {
         info!("Processing long reads...");
-        finish_bams(
+        let long_read_sharing = finish_bams(
             longreads,
             threads,
             &genomes_and_contigs,
             // run_in_parallel,
             m.get_flag("split-bams"),
             !m.contains_id("longread-bam-files"),
+            MultiMappingReassignment::from_args(m).as_ref(),
         ).expect("Failed to finish BAMs");
+        competitive_mapping.extend(long_read_sharing);
     }
 
     if m.contains_id("coupled")
@@ -1143,20 +1888,45 @@ pub fn start_lorikeet_engine<
         || m.contains_id("bam-files")
     {
         info!("Processing short reads...");
-        finish_bams(
+        let short_read_sharing = finish_bams(
             bam_readers,
             threads,
             &genomes_and_contigs,
             // run_in_parallel,
             m.get_flag("split-bams"),
             !m.contains_id("bam-files"),
+            MultiMappingReassignment::from_args(m).as_ref(),
         ).expect("Failed to finish BAMs");
+        competitive_mapping.extend(short_read_sharing);
+    }
+
+    // reads shared between genomes when mapping competitively against all of them
+    if !competitive_mapping.is_empty() {
+        let output_directory = OutputLayout::from_args(m, mode).output_directory;
+        match std::fs::create_dir_all(&output_directory)
+            .map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to create output directory {}: {}",
+                    output_directory, e
+                ))
+            })
+            .and_then(|_| competitive_mapping.write(&output_directory))
+        {
+            Ok(_) => info!("Competitive mapping diagnostics written to {}", &output_directory),
+            Err(e) => warn!("Unable to write competitive mapping diagnostics {:?}", e),
+        }
     }
 
     let mut reference_map = HashMap::new();
 
-    // Set up multi progress bars
-    let multi = Arc::new(MultiProgress::new());
+    // Set up multi progress bars. These are hidden when not attached to a terminal or when
+    // structured log events are requested
+    let show_progress_bars = LogEvents::show_progress_bars();
+    let multi = Arc::new(if show_progress_bars {
+        MultiProgress::new()
+    } else {
+        MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+    });
 
     let multi_inner = Arc::clone(&multi);
     let progress_bars = match LorikeetEngine::setup_progress_bars(
@@ -1171,6 +1941,11 @@ pub fn start_lorikeet_engine<
         Ok(val) => val,
         Err(e) => return Err(BirdToolError::DebugError(e.to_string())),
     };
+    if !show_progress_bars {
+        progress_bars
+            .iter()
+            .for_each(|pb| pb.progress_bar.set_draw_target(ProgressDrawTarget::hidden()));
+    }
 
     let tree: Arc<Mutex<Vec<&Elem>>> =
         Arc::new(Mutex::new(Vec::with_capacity(progress_bars.len())));
@@ -1189,7 +1964,7 @@ pub fn start_lorikeet_engine<
         reference_count
     );
 
-    {
+    let status = {
         let lorikeet_engine = LorikeetEngine {
             args: m,
             short_read_bam_count,
@@ -1209,16 +1984,15 @@ pub fn start_lorikeet_engine<
             run_in_parallel: m.get_flag("split-bams"),
         };
 
-        lorikeet_engine.apply_per_reference();
-    }
+        lorikeet_engine.apply_per_reference()
+    };
 
     // cleanup temp files .fai index file
-    if Path::new(format!("{}.fai", concatenated_temp_file_name).as_str()).exists() {
-        std::fs::remove_file(format!("{}.fai", concatenated_temp_file_name).as_str())
-            .expect("Failed to remove temp file");
+    if !reference_is_cached {
+        TempResources::release(format!("{}.fai", concatenated_temp_file_name));
     }
 
-    Ok(())
+    Ok(status)
 }
 
 pub fn run_summarize(args: &clap::ArgMatches) {
@@ -1246,9 +2020,19 @@ pub fn run_summarize(args: &clap::ArgMatches) {
     };
 
     vcf_files.into_iter().for_each(|vcf_path| {
-        let reader = rust_htslib::bcf::Reader::from_path(vcf_path).unwrap();
+        let reader = match VcfInput::open(vcf_path) {
+            Ok(reader) => reader,
+            Err(e) => {
+                warn!("Skipping {}: {:?}", vcf_path, e);
+                return;
+            }
+        };
         let header = reader.header();
         let mut variant_contexts = VariantContext::process_vcf_from_path(vcf_path, true);
+        if variant_contexts.is_empty() {
+            warn!("Skipping {} as it contains no variants", vcf_path);
+            return;
+        }
 
         #[cfg(feature = "fst")]
         let mut ploidy = 2;
@@ -1278,12 +2062,13 @@ pub fn run_summarize(args: &clap::ArgMatches) {
             })
             .sum();
         // calculate ANI statistics
-        let mut ani_calculator = ANICalculator::new(variant_contexts[0].genotypes.len());
+        let mut ani_calculator =
+            ANICalculator::from_args(args, variant_contexts[0].genotypes.len());
         ani_calculator.run_calculator(
             &mut variant_contexts,
             output_prefix,
             samples.as_slice(),
-            Path::new(vcf_path).file_stem().unwrap().to_str().unwrap(),
+            &VcfInput::stem(vcf_path),
             genome_size,
             None,
             qual_by_depth_filter,
@@ -1294,7 +2079,7 @@ pub fn run_summarize(args: &clap::ArgMatches) {
         #[cfg(feature = "fst")]
         calculate_fst(
             output_prefix,
-            Path::new(vcf_path).file_stem().unwrap().to_str().unwrap(),
+            &VcfInput::stem(vcf_path),
             vcf_path,
             ploidy as usize,
             depth_per_sample_filter,
@@ -1302,12 +2087,303 @@ pub fn run_summarize(args: &clap::ArgMatches) {
     })
 }
 
+/// Calculates the pairwise genotype concordance between the samples within each VCF file and,
+/// if provided, between the samples of each VCF file and a comparison VCF file
+pub fn run_concordance(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let vcf_files = args.get_many::<String>("vcfs").unwrap().map(|s| &**s).collect::<Vec<&str>>();
+    let min_depth = *args.get_one::<i32>("depth-per-sample-filter").unwrap();
+    let min_sites = *args.get_one::<usize>("min-compared-sites").unwrap();
+    let threshold = *args.get_one::<f64>("concordance-threshold").unwrap();
+    let output_prefix = args.get_one::<String>("output").unwrap();
+    create_dir_all(output_prefix).map_err(|e| {
+        BirdToolError::IOError(format!(
+            "Unable to create output directory {}: {}",
+            output_prefix, e
+        ))
+    })?;
+
+    let comparison = match args.get_one::<String>("comparison-vcf") {
+        Some(comparison_vcf) => Some((
+            VcfInput::stem(comparison_vcf),
+            SiteGenotypes::from_vcf(comparison_vcf, min_depth)?,
+        )),
+        None => None,
+    };
+
+    for vcf_path in vcf_files {
+        let vcf_stem = VcfInput::stem(vcf_path);
+        let genotypes = SiteGenotypes::from_vcf(vcf_path, min_depth)?;
+
+        GenotypeConcordance::within_run(&genotypes).write_tsv(
+            &format!("{}/{}_concordance.tsv", output_prefix, vcf_stem),
+            min_sites,
+            threshold,
+        )?;
+
+        if let Some((comparison_stem, comparison_genotypes)) = &comparison {
+            GenotypeConcordance::compare(&genotypes, comparison_genotypes).write_tsv(
+                &format!(
+                    "{}/{}_vs_{}_concordance.tsv",
+                    output_prefix, vcf_stem, comparison_stem
+                ),
+                min_sites,
+                threshold,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a core SNP alignment, and optionally a neighbor joining tree, of the samples or
+/// strains of each VCF file
+pub fn run_phylo(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let vcf_files = args.get_many::<String>("vcfs").unwrap().map(|s| &**s).collect::<Vec<&str>>();
+    let min_depth = *args.get_one::<i32>("depth-per-sample-filter").unwrap();
+    let max_missing = *args.get_one::<f64>("max-missing").unwrap();
+    let mode = args.get_one::<String>("mode").unwrap().as_str();
+    let output_prefix = args.get_one::<String>("output").unwrap();
+    create_dir_all(output_prefix).map_err(|e| {
+        BirdToolError::IOError(format!(
+            "Unable to create output directory {}: {}",
+            output_prefix, e
+        ))
+    })?;
+
+    for vcf_path in vcf_files {
+        let vcf_stem = Path::new(vcf_path).file_stem().unwrap().to_str().unwrap();
+        let alignment = match mode {
+            "strains" => CoreSnpAlignment::from_strain_vcf(vcf_path)?,
+            _ => CoreSnpAlignment::from_sample_genotypes(
+                &SiteGenotypes::from_vcf(vcf_path, min_depth)?,
+                max_missing,
+            ),
+        };
+        if alignment.taxa.len() < 2 {
+            warn!(
+                "{} contains fewer than two {}, skipping alignment",
+                vcf_path, mode
+            );
+            continue;
+        }
+        info!(
+            "Aligned {} SNP sites across {} {} of {}",
+            alignment.len(),
+            alignment.taxa.len(),
+            mode,
+            vcf_path
+        );
+
+        let alignment_prefix = format!("{}/{}_core_snps", output_prefix, vcf_stem);
+        alignment.write_fasta(&format!("{}.fasta", alignment_prefix))?;
+        alignment.write_phylip(&format!("{}.phy", alignment_prefix))?;
+        alignment.write_sites(&format!("{}_sites.tsv", alignment_prefix))?;
+
+        if args.get_flag("tree") {
+            let tree_path = format!("{}.nwk", alignment_prefix);
+            let tree = neighbor_joining(&alignment.taxa, &alignment.distance_matrix());
+            std::fs::write(&tree_path, format!("{}\n", tree)).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to write to {}: {}", tree_path, e))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Summarises the assembly graphs written with --dump-assembly-graphs, one row per GFA file
+pub fn run_graph_inspect(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let mut gfa_paths = Vec::new();
+    for path in args.get_many::<String>("graphs").unwrap() {
+        if Path::new(path).is_dir() {
+            let entries = std::fs::read_dir(path).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read directory {}: {}", path, e))
+            })?;
+            let mut dir_paths = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| entry.extension().map_or(false, |ext| ext == "gfa"))
+                .map(|entry| entry.to_string_lossy().to_string())
+                .collect::<Vec<String>>();
+            dir_paths.sort();
+            gfa_paths.extend(dir_paths);
+        } else {
+            gfa_paths.push(path.clone());
+        }
+    }
+
+    let mut rows = vec![GraphSummary::HEADER.to_string()];
+    for gfa_path in gfa_paths.iter() {
+        rows.push(GraphSummary::from_gfa(gfa_path)?.to_row(gfa_path));
+    }
+    info!("Summarised {} assembly graphs", gfa_paths.len());
+
+    match args.get_one::<String>("output-file") {
+        Some(output_file) => {
+            std::fs::write(output_file, format!("{}\n", rows.join("\n"))).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to write to {}: {}", output_file, e))
+            })
+        }
+        None => {
+            let stdout = std::io::stdout();
+            let mut handle = stdout.lock();
+            for row in rows {
+                writeln!(handle, "{}", row).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to write to stdout: {}", e))
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Prints the reads and haplotypes supporting each allele of the variant at --region
+pub fn run_inspect(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let (contig, position) = parse_region(args.get_one::<String>("region").unwrap())?;
+    let lines = VariantInspector::from_args(args).inspect(&contig, position)?;
+
+    match args.get_one::<String>("output-file") {
+        Some(output_file) => {
+            std::fs::write(output_file, format!("{}\n", lines.join("\n"))).map_err(|e| {
+                BirdToolError::IOError(format!("Unable to write to {}: {}", output_file, e))
+            })
+        }
+        None => {
+            let stdout = std::io::stdout();
+            let mut handle = stdout.lock();
+            for line in lines {
+                writeln!(handle, "{}", line).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to write to stdout: {}", e))
+                })?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Scores the records of a VCF file with a model trained on the tables of --write-features
+pub fn run_apply_model(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let vcf_path = args.get_one::<String>("vcf").unwrap();
+    let output_path = args.get_one::<String>("output-file").unwrap();
+    let threshold = *args.get_one::<f64>("threshold").unwrap();
+    let model = FeatureModel::from_file(args.get_one::<String>("model").unwrap())?;
+
+    let (written, filtered) = model.apply(vcf_path, output_path, threshold)?;
+    info!(
+        "Scored {} records of {}, {} of which scored below {} and were filtered",
+        written, vcf_path, filtered, threshold
+    );
+    Ok(())
+}
+
+/// Merges the shard VCF files written by `lorikeet call --scatter` into one VCF per genome
+pub fn run_gather(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let output_dir = args.get_one::<String>("output").unwrap();
+    ShardGatherer::run(output_dir, args.get_flag("remove-shards"))
+}
+
+/// Jointly genotypes the samples of multiple lorikeet VCF files called against the same reference
+pub fn run_combine(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let vcf_files = args.get_many::<String>("vcfs").unwrap().map(|s| &**s).collect::<Vec<&str>>();
+    let ploidy = *args.get_one::<usize>("ploidy").unwrap();
+    let output_prefix = args.get_one::<String>("output").unwrap();
+    let output_name = args.get_one::<String>("output-name").unwrap();
+    create_dir_all(output_prefix).map_err(|e| {
+        BirdToolError::IOError(format!(
+            "Unable to create output directory {}: {}",
+            output_prefix, e
+        ))
+    })?;
+
+    let mut inputs = Vec::with_capacity(vcf_files.len());
+    for vcf_path in vcf_files.iter() {
+        let input = CombineInput::from_vcf(vcf_path)?;
+        info!(
+            "Read {} sites across {} samples from {}",
+            input.contexts.len(),
+            input.sample_names.len(),
+            vcf_path
+        );
+        inputs.push(input);
+    }
+
+    let sample_names = inputs
+        .iter()
+        .flat_map(|input| input.sample_names.iter().cloned())
+        .collect::<Vec<String>>();
+    let (contigs, merged) = VcfCombiner::merge_inputs(inputs, ploidy);
+    info!(
+        "Jointly genotyping {} sites across {} samples",
+        merged.len(),
+        sample_names.len()
+    );
+    let genotyped = VcfCombiner::joint_genotype(args, merged, &sample_names);
+
+    let output_path = format!("{}/{}.vcf", output_prefix, output_name);
+    VcfCombiner::write_vcf(
+        &output_path,
+        vcf_files[0],
+        &contigs,
+        &sample_names,
+        &genotyped,
+    )?;
+    info!("Wrote {} jointly genotyped sites to {}", genotyped.len(), output_path);
+
+    Ok(())
+}
+
+/// Merges the sites and samples of lorikeet VCF files of the same genome, reconciling the strain
+/// and variant group IDs of each run
+pub fn run_merge_vcfs(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+    let vcf_files = args.get_many::<String>("vcfs").unwrap().map(|s| &**s).collect::<Vec<&str>>();
+    let ploidy = *args.get_one::<usize>("ploidy").unwrap();
+    let min_id_overlap = *args.get_one::<f64>("min-id-overlap").unwrap();
+    let output_prefix = args.get_one::<String>("output").unwrap();
+    let output_name = args.get_one::<String>("output-name").unwrap();
+    create_dir_all(output_prefix).map_err(|e| {
+        BirdToolError::IOError(format!(
+            "Unable to create output directory {}: {}",
+            output_prefix, e
+        ))
+    })?;
+
+    let merged = VariantContextUtils::merge_from_paths(&vcf_files, ploidy, min_id_overlap)?;
+    for (vcf_path, strain_ids) in vcf_files.iter().zip(merged.strain_ids.iter()).skip(1) {
+        let mut renamed = strain_ids
+            .iter()
+            .filter(|(id, merged_id)| id != merged_id)
+            .map(|(id, merged_id)| (*id, *merged_id))
+            .collect::<Vec<(usize, usize)>>();
+        renamed.sort_unstable();
+        if !renamed.is_empty() {
+            info!("Strain IDs of {} renamed to {:?}", vcf_path, renamed);
+        }
+    }
+
+    let output_path = format!("{}/{}.vcf", output_prefix, output_name);
+    VcfCombiner::write_vcf(
+        &output_path,
+        vcf_files[0],
+        &merged.contigs,
+        &merged.sample_names,
+        &merged.contexts,
+    )?;
+    info!(
+        "Wrote {} merged sites across {} samples to {}",
+        merged.contexts.len(),
+        merged.sample_names.len(),
+        output_path
+    );
+
+    Ok(())
+}
+
 /// Checks for the presence of gff file in the output directory for the current reference
 /// If none is present then generate one
 fn check_for_gff(
     reference: &str,
     output_prefix: &str,
     m: &clap::ArgMatches,
+    genetic_code: usize,
 ) -> Option<bio::io::gff::Reader<File>> {
     let cache = glob::glob(&format!("{}/*.gff", &output_prefix))
         .expect("failed to interpret glob")
@@ -1330,23 +2406,32 @@ fn check_for_gff(
         Some(gff_reader)
     } else {
         let gff_path = format!("{}/genes.gff", output_prefix);
+        let prodigal_params = m
+            .get_one::<String>("prodigal-params")
+            .map(|s| &**s)
+            .unwrap_or_else(|| "");
+        // gene calls depend on which codons are stops, so pass on the genetic code unless the
+        // user has already chosen one
+        let translation_table = if prodigal_params.split_whitespace().any(|p| p == "-g") {
+            String::new()
+        } else {
+            format!("-g {}", genetic_code)
+        };
         let cmd_string = format!(
             "set -e -o pipefail; \
-            prodigal -o {} -i {} -f gff {}",
+            prodigal -o {} -i {} -f gff {} {}",
             // prodigal
             &gff_path,
             &reference,
-            m.get_one::<String>("prodigal-params").map(|s| &**s).unwrap_or_else(|| ""),
+            translation_table,
+            prodigal_params,
         );
         // debug!("Queuing cmd_string: {}", cmd_string);
-        finish_command_safely(
+        ExternalCommand::run_or_exit(
             std::process::Command::new("bash")
                 .arg("-c")
                 .arg(&cmd_string)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .expect("Unable to execute bash"),
+                .stdout(Stdio::piped()),
             "prodigal",
         );
 
@@ -1378,34 +2463,38 @@ fn calculate_dnds(
         .unwrap()
         / -10.0;
 
-    match check_for_gff(reference, output_prefix, args) {
+    let genetic_codes = GeneticCodes::from_args(args).expect("Invalid genetic code");
+    let genome_table = genetic_codes
+        .for_genome(&reference_reader.genomes_and_contigs.genomes[ref_idx]);
+
+    match check_for_gff(reference, output_prefix, args, genome_table) {
         Some(mut genes) => {
 
-            let mut vcf_prefix = format!(
+            let vcf_path = format!(
                 "{}/{}.vcf",
                 &output_prefix,
                 &reference_reader.genomes_and_contigs.genomes[ref_idx]
             );
 
-            // check if we should be using gzipped vcf
-            if !Path::new(&vcf_prefix).exists() {
-                vcf_prefix = format!(
-                    "{}.gz",
-                    vcf_prefix
-                );
-
-                if !Path::new(&vcf_prefix).exists() {
-                    panic!("Unable to find vcf file for Fst calculation: {}", vcf_prefix);
-                }
-            }
-
-            debug!("Reading VCF: {}", &vcf_prefix);
-            let mut variants = VariantContext::get_vcf_reader(vcf_prefix.as_str());
+            debug!("Reading VCF: {}", &vcf_path);
+            let mut variants = match VcfInput::open_indexed(&vcf_path) {
+                Ok(variants) => variants,
+                Err(e) => panic!("dN/dS calculation failed: {:?}", e),
+            };
             debug!("Success!");
-            let mut dnds_calculator = CodonTable::setup();
-            dnds_calculator.get_codon_table(11);
+            // genotyped VCFs assign variants to strains, so selection is also estimated per strain
+            let strain_ids = match strain_ids_in_vcf(&vcf_path) {
+                Ok(strain_ids) => strain_ids,
+                Err(e) => {
+                    warn!("Unable to read strains of {}: {:?}", &vcf_path, e);
+                    Vec::new()
+                }
+            };
+            // genes can override the genome's genetic code with a transl_table attribute, so
+            // keep one table per code seen
+            let mut codon_tables: HashMap<usize, CodonTable> = HashMap::new();
 
-            // create new TSV file that will contain gene\tSNPs\tindels\tdN/dS
+            // create new TSV file that will contain gene\tSNPs\tindels\tMNP_codons\tdN/dS
             let tsv_file = OpenOptions::new()
                 .create(true)
                 .write(true)
@@ -1418,24 +2507,69 @@ fn calculate_dnds(
             tsv_writer
                 .write_all(
                     format!(
-                        "contig\tID\tstart\tstop\tSNPs\tindels\tdN/dS\n",
+                        "contig\tID\tstart\tstop\tSNPs\tindels\tMNP_codons\tdN/dS\n",
                     ).as_bytes(),
                 ).expect("Unable to write to TSV file");
 
+            // genes x strains matrix of dN/dS values
+            let mut strain_tsv_writer = if strain_ids.is_empty() {
+                None
+            } else {
+                let strain_tsv_file = File::create(format!(
+                    "{}/{}_strain_dnds.tsv",
+                    output_prefix, &reference_reader.genomes_and_contigs.genomes[ref_idx]
+                ))
+                .expect("Unable to create strain dN/dS TSV file");
+                let mut strain_tsv_writer = BufWriter::new(strain_tsv_file);
+                writeln!(
+                    strain_tsv_writer,
+                    "contig\tID\tstart\tstop\t{}",
+                    strain_ids
+                        .iter()
+                        .map(|strain_id| format!("strain_{}", strain_id))
+                        .join("\t")
+                )
+                .expect("Unable to write to TSV file");
+                Some(strain_tsv_writer)
+            };
+
             for gene in genes.records() {
                 match gene {
                     Ok(gene) => {
-                        let (snps, frameshifts, dnds_values) = dnds_calculator.find_mutations(
-                            &gene,
-                            &mut variants,
-                            reference_reader,
-                            ref_idx,
-                            sample_count,
-                            qual_by_depth_filter,
-                            qual_filter,
-                            depth_per_sample_filter,
-                        );
-                        if snps.iter().sum::<usize>() == 0 && frameshifts.iter().sum::<usize>() == 0 {
+                        let table_id = GeneticCodes::for_gene(&gene, genome_table);
+                        let dnds_calculator = codon_tables.entry(table_id).or_insert_with(|| {
+                            let mut codon_table = CodonTable::setup();
+                            codon_table.get_codon_table(table_id);
+                            codon_table
+                        });
+                        let (snps, frameshifts, multi_nucleotide_codons, dnds_values) =
+                            dnds_calculator.find_mutations(
+                                &gene,
+                                &mut variants,
+                                reference_reader,
+                                ref_idx,
+                                sample_count,
+                                qual_by_depth_filter,
+                                qual_filter,
+                                depth_per_sample_filter,
+                            );
+                        let (strain_snps, strain_dnds_values) = if strain_tsv_writer.is_some() {
+                            dnds_calculator.find_strain_mutations(
+                                &gene,
+                                &mut variants,
+                                reference_reader,
+                                ref_idx,
+                                &strain_ids,
+                                qual_by_depth_filter,
+                                qual_filter,
+                            )
+                        } else {
+                            (Vec::new(), Vec::new())
+                        };
+                        let strain_mutations = strain_snps.iter().sum::<usize>() > 0;
+                        let sample_mutations = snps.iter().sum::<usize>() > 0
+                            || frameshifts.iter().sum::<usize>() > 0;
+                        if !sample_mutations && !strain_mutations {
                             continue;
                         }
 
@@ -1446,17 +2580,42 @@ fn calculate_dnds(
                             .expect("Unable to get ID from GFF file")
                             .to_string();
 
+                        if let Some(strain_tsv_writer) =
+                            strain_tsv_writer.as_mut().filter(|_| strain_mutations)
+                        {
+                            writeln!(
+                                strain_tsv_writer,
+                                "{}\t{}\t{}\t{}\t{}",
+                                gene.seqname(),
+                                id,
+                                gene.start(),
+                                gene.end(),
+                                strain_dnds_values
+                                    .into_iter()
+                                    .map(|s| format!("{}", s))
+                                    .join("\t"),
+                            )
+                            .expect("Unable to write to TSV file");
+                        }
+                        if !sample_mutations {
+                            continue;
+                        }
+
                         // write to TSV file
                         tsv_writer
                             .write_all(
                                 format!(
-                                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
                                     gene.seqname(),
                                     id,
                                     gene.start(),
                                     gene.end(),
                                     snps.into_iter().map(|s| format!("{}", s)).join(","),
                                     frameshifts.into_iter().map(|s| format!("{}", s)).join(","),
+                                    multi_nucleotide_codons
+                                        .into_iter()
+                                        .map(|s| format!("{}", s))
+                                        .join(","),
                                     dnds_values.into_iter().map(|s| format!("{}", s)).join(","),
                                 ).as_bytes(),
                             ).expect("Unable to write to TSV file");
@@ -1465,6 +2624,11 @@ fn calculate_dnds(
                 }
             }
             tsv_writer.flush().expect("Unable to flush TSV writer");
+            if let Some(mut strain_tsv_writer) = strain_tsv_writer {
+                strain_tsv_writer
+                    .flush()
+                    .expect("Unable to flush TSV writer");
+            }
         }
         None => {
             // too many GFF files in output folder, abort this genome
@@ -1477,3 +2641,215 @@ fn calculate_dnds(
     //     std::fs::remove_file(&placeholder_gene_file).expect("Unable to remove placeholder gene file");
     // }
 }
+
+/// Writes the genotype VCF, streaming the sorted contexts to it and merging in the filtered
+/// contexts of the store one contig at a time, so neither set is held in memory twice
+fn write_genotype_vcf(
+    args: &clap::ArgMatches,
+    evaluator: &HaplotypeCallerEngine,
+    output_prefix: &str,
+    contexts: Vec<VariantContext>,
+    filtered_store: &VariantStore,
+    sample_names: &[&str],
+    reference_reader: &ReferenceReader,
+) -> Result<(), BirdToolError> {
+    let contexts = evaluator.prepare_vcf_contexts(contexts, reference_reader);
+    let sorted_contexts = VariantSorter::from_args(args, output_prefix).sort_iter(contexts)?;
+    let filtered_contexts = filtered_store
+        .contigs()
+        .into_iter()
+        .flat_map(|tid| match filtered_store.query_contig(tid) {
+            Ok(filtered) => evaluator
+                .normalize_vcf_contexts(filtered, reference_reader)
+                .into_iter()
+                .map(Ok)
+                .collect::<Vec<_>>(),
+            Err(e) => vec![Err(e)],
+        });
+
+    let mut read_error = None;
+    evaluator.write_sorted_vcf(
+        output_prefix,
+        VariantStore::merge_sorted(sorted_contexts, filtered_contexts).map_while(|context| {
+            context.map_err(|e| read_error = Some(e)).ok()
+        }),
+        sample_names,
+        reference_reader,
+        true,
+    );
+    match read_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Summarises the mutations of the marker catalog, e.g. known AMR mutations, across the samples
+/// and strains of a genome. Does nothing if no catalog was given
+fn summarise_markers(
+    args: &clap::ArgMatches,
+    reference: &str,
+    output_prefix: &str,
+    reference_reader: &mut ReferenceReader,
+    ref_idx: usize,
+    contexts: &[VariantContext],
+    filtered_store: Option<&VariantStore>,
+    sample_names: &[&str],
+    strain_ids: &[usize],
+) {
+    let genome = reference_reader.genomes_and_contigs.genomes[ref_idx].clone();
+    let catalog = match MarkerCatalog::from_args(args) {
+        Ok(Some(catalog)) => catalog,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("{}: Unable to read marker catalog {:?}", &genome, e);
+            return;
+        }
+    };
+
+    let genome_table = match GeneticCodes::from_args(args) {
+        Ok(genetic_codes) => genetic_codes.for_genome(&genome),
+        Err(e) => {
+            warn!("{}: Invalid genetic code {:?}", &genome, e);
+            return;
+        }
+    };
+    let mut genes = match check_for_gff(reference, output_prefix, args, genome_table) {
+        Some(genes) => genes,
+        None => {
+            warn!(
+                "{}: Not summarising marker mutations as there are too many GFF files in {}",
+                &genome, output_prefix
+            );
+            return;
+        }
+    };
+
+    // GFF contig names may include or omit the genome prefix
+    let mut name_to_tid = HashMap::new();
+    if let Some(tids) = reference_reader.retrieve_tids_for_ref_index(ref_idx) {
+        for tid in tids.iter() {
+            let target_name = reference_reader.get_target_name(*tid).to_vec();
+            name_to_tid.insert(
+                ReferenceReaderUtils::split_contig_name(
+                    &target_name,
+                    &reference_reader.genomes_and_contigs.separator,
+                ),
+                *tid,
+            );
+            name_to_tid.insert(String::from_utf8(target_name).unwrap(), *tid);
+        }
+    }
+
+    let min_depth = *args.get_one::<i64>("depth-per-sample-filter").unwrap() as i32;
+    let entries_by_gene = catalog.entries_by_gene();
+    let mut found_genes = HashSet::new();
+    let mut codon_tables: HashMap<usize, CodonTable> = HashMap::new();
+    let mut current_tid = None;
+    let mut calls = Vec::with_capacity(catalog.entries.len());
+    for record in genes.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if record.feature_type() != "CDS" && record.feature_type() != "gene" {
+            continue;
+        }
+
+        // a gene is only summarised once, even if both its gene and CDS records match
+        let (gene_name, entries) = match MarkerGene::NAME_ATTRIBUTES.iter().find_map(|attribute| {
+            record
+                .attributes()
+                .get(*attribute)
+                .and_then(|name| entries_by_gene.get_key_value(name.as_str()))
+        }) {
+            Some((gene_name, entries)) if !found_genes.contains(gene_name) => (*gene_name, entries),
+            _ => continue,
+        };
+        let tid = match name_to_tid.get(record.seqname()) {
+            Some(tid) => *tid,
+            None => continue,
+        };
+        found_genes.insert(gene_name);
+
+        if current_tid != Some(tid) {
+            if reference_reader
+                .fetch_contig_from_reference_by_tid(tid, ref_idx)
+                .is_err()
+            {
+                warn!("{}: Unable to read contig {}", &genome, record.seqname());
+                continue;
+            }
+            reference_reader.read_sequence_to_vec();
+            current_tid = Some(tid);
+        }
+
+        let gene = MarkerGene::from_gff(&record, tid);
+        // filtered contexts still count towards the marker frequencies, so those in the gene are
+        // read back from the store alongside the others
+        let gene_contexts;
+        let contexts = match filtered_store {
+            Some(filtered_store) => match filtered_store.query(tid, gene.start, gene.end) {
+                Ok(mut filtered) => {
+                    filtered.extend(
+                        contexts
+                            .iter()
+                            .filter(|vc| {
+                                vc.loc.tid == tid
+                                    && vc.loc.start <= gene.end
+                                    && vc.loc.end >= gene.start
+                            })
+                            .cloned(),
+                    );
+                    gene_contexts = filtered;
+                    &gene_contexts[..]
+                }
+                Err(e) => {
+                    warn!("{}: Unable to read filtered variants {:?}", &genome, e);
+                    return;
+                }
+            },
+            None => contexts,
+        };
+        let table_id = GeneticCodes::for_gene(&record, genome_table);
+        let codon_table = codon_tables.entry(table_id).or_insert_with(|| {
+            let mut codon_table = CodonTable::setup();
+            codon_table.get_codon_table(table_id);
+            codon_table
+        });
+        for entry in entries {
+            match MarkerCatalog::evaluate(
+                entry,
+                &gene,
+                &reference_reader.current_sequence,
+                contexts,
+                codon_table,
+                sample_names.len(),
+                min_depth,
+                strain_ids,
+            ) {
+                Some(call) => calls.push(call),
+                None => warn!(
+                    "{}: Marker mutation {} lies outside of gene {}",
+                    &genome, &entry.label, gene_name
+                ),
+            }
+        }
+    }
+
+    for gene_name in entries_by_gene.keys() {
+        if !found_genes.contains(gene_name) {
+            debug!("{}: Marker gene {} not found in GFF", &genome, gene_name);
+        }
+    }
+
+    let summary_path = format!("{}/{}_marker_mutations.tsv", output_prefix, &genome);
+    match MarkerCatalog::write_summary(&summary_path, &calls, sample_names, strain_ids) {
+        Ok(_) => info!(
+            "{}: Summarised {} marker mutations to {}",
+            &genome,
+            calls.len(),
+            &summary_path
+        ),
+        Err(e) => warn!("{}: Unable to write marker summary {:?}", &genome, e),
+    }
+}
@@ -1,2 +1,18 @@
+pub mod bam_reference_check;
 pub mod bams;
+pub mod dry_run;
+pub mod genome_runs;
 pub mod lorikeet_engine;
+pub mod output_layout;
+pub mod per_genome_config;
+pub mod pileup_ani;
+pub mod run_config;
+pub mod run_outputs;
+pub mod sample_addition;
+pub mod scatter_gather;
+pub mod structural_variant_caller;
+pub mod subsampler;
+pub mod sv_evidence;
+pub mod variant_features;
+pub mod variant_inspector;
+pub mod vcf_combiner;
@@ -0,0 +1,258 @@
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::utils::errors::BirdToolError;
+use crate::utils::partial_output::PartialOutput;
+
+/// Output directory layout given by a template with {genome}, {mode} and {sample} placeholders.
+/// `MANIFEST_NAME` lists every output file with its genome and type.
+#[derive(Debug, Clone)]
+pub struct OutputLayout {
+    pub output_directory: String,
+    pub template: String,
+    pub mode: String,
+    pub sample: String,
+}
+
+impl OutputLayout {
+    pub const DEFAULT_TEMPLATE: &'static str = "{genome}";
+    pub const MANIFEST_NAME: &'static str = "lorikeet_manifest.tsv";
+    const PLACEHOLDERS: [&'static str; 3] = ["{genome}", "{mode}", "{sample}"];
+    const MULTIPLE_SAMPLES: &'static str = "all_samples";
+    const STRAIN_BAM_INFIX: &'static str = "_strain_";
+
+    pub fn new(output_directory: &str, template: &str, mode: &str, samples: &[String]) -> Self {
+        let sample = match samples {
+            [sample] => sample.clone(),
+            _ => Self::MULTIPLE_SAMPLES.to_string(),
+        };
+        Self {
+            output_directory: output_directory.to_string(),
+            template: template.to_string(),
+            mode: mode.to_string(),
+            sample,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches, mode: &str) -> Self {
+        let output_directory = args
+            .try_get_one::<String>("output-directory")
+            .ok()
+            .flatten()
+            .map(|directory| directory.as_str())
+            .unwrap_or("./");
+        let template = args
+            .try_get_one::<String>("output-template")
+            .ok()
+            .flatten()
+            .map(|template| template.as_str())
+            .unwrap_or(Self::DEFAULT_TEMPLATE);
+        Self::new(output_directory, template, mode, &Self::sample_names(args))
+    }
+
+    /// The names of the samples given on the command line, taken from the file stems of the
+    /// BAM or read files. Paired read files count as a single sample
+    fn sample_names(args: &clap::ArgMatches) -> Vec<String> {
+        let values = |id: &str| {
+            args.try_get_many::<String>(id)
+                .ok()
+                .flatten()
+                .map(|values| values.cloned().collect::<Vec<String>>())
+                .unwrap_or_default()
+        };
+
+        let mut files = values("bam-files");
+        files.extend(values("longread-bam-files"));
+        files.extend(values("read1"));
+        files.extend(values("coupled").into_iter().step_by(2));
+        files.extend(values("interleaved"));
+        files.extend(values("single"));
+        files.extend(values("longreads"));
+        files
+            .iter()
+            .filter_map(|file| Path::new(file).file_stem())
+            .map(|stem| stem.to_string_lossy().to_string())
+            .collect()
+    }
+
+    /// Checks that the template only contains known placeholders and includes {genome}, so
+    /// that genomes do not overwrite the outputs of each other
+    pub fn validate_template(template: &str) -> Result<(), BirdToolError> {
+        if !template.contains("{genome}") {
+            return Err(BirdToolError::ConfigError(format!(
+                "Output template {} must contain {{genome}}",
+                template
+            )));
+        }
+
+        let mut remaining = template.to_string();
+        for placeholder in Self::PLACEHOLDERS.iter() {
+            remaining = remaining.replace(placeholder, "");
+        }
+        if remaining.contains('{') || remaining.contains('}') {
+            return Err(BirdToolError::ConfigError(format!(
+                "Output template {} contains an unknown placeholder, expected any of {:?}",
+                template,
+                Self::PLACEHOLDERS
+            )));
+        }
+
+        if Path::new(template).is_absolute() {
+            return Err(BirdToolError::ConfigError(format!(
+                "Output template {} must be relative to the output directory",
+                template
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// The directory the outputs of a genome are written to
+    pub fn genome_prefix(&self, genome: &str) -> String {
+        let relative = self
+            .template
+            .replace("{genome}", genome)
+            .replace("{mode}", &self.mode)
+            .replace("{sample}", &self.sample);
+        format!("{}/{}", self.output_directory, relative.trim_matches('/'))
+    }
+
+    /// The file name of the BAM file of the reads of a sample binned to a strain of a genome
+    pub fn strain_bam_name(genome: &str, strain_id: usize, sample: &str) -> String {
+        format!(
+            "{}{}{}_{}.bam",
+            genome,
+            Self::STRAIN_BAM_INFIX,
+            strain_id,
+            sample
+        )
+    }
+
+    /// Whether a file name is one given by [`OutputLayout::strain_bam_name`] for the genome
+    fn is_strain_bam_name(name: &str, genome: &str) -> bool {
+        name.strip_prefix(genome)
+            .and_then(|name| name.strip_prefix(Self::STRAIN_BAM_INFIX))
+            .and_then(|name| name.strip_suffix(".bam"))
+            .and_then(|name| name.split_once('_'))
+            .map_or(false, |(strain_id, sample)| {
+                strain_id.parse::<usize>().is_ok() && !sample.is_empty()
+            })
+    }
+
+    /// The type of an output file of a genome, judging by its name. None for files that are not
+    /// outputs
+    pub fn output_type(path: &Path, genome: &str) -> Option<&'static str> {
+        let name = path.file_name()?.to_str()?;
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        let output_type = if name.ends_with(".vcf") {
+            "vcf"
+        } else if name.ends_with("_strain_coverages.tsv") {
+            "strain_coverages"
+        } else if name.ends_with("_strain_frequencies.tsv") {
+            "strain_frequencies"
+        } else if name.ends_with("_dnds.tsv") {
+            "dnds"
+        } else if name.ends_with("_marker_mutations.tsv") {
+            "marker_mutations"
+        } else if name.ends_with("_concordance.tsv") {
+            "concordance"
+        } else if name.ends_with("_dropped_alleles.tsv") {
+            "dropped_alleles"
+        } else if name.ends_with("_nucleotide_diversity.tsv")
+            || name.ends_with("_window_diversity.tsv")
+        {
+            "diversity"
+        } else if name.ends_with("_pairwise_fst.tsv") {
+            "fst"
+        } else if name.ends_with("_phasing.tsv") {
+            "phasing"
+        } else if name.ends_with(".tsv") {
+            "table"
+        } else if name.ends_with(".fna") || name.ends_with(".fasta") {
+            "fasta"
+        } else if name.ends_with(".gff") {
+            "gff"
+        } else if Self::is_strain_bam_name(name, genome) {
+            "strain_bam"
+        } else if name.ends_with("_active_regions.bed") {
+            "active_regions"
+        } else if name.ends_with(".bed") {
+            "bed"
+        } else if name.ends_with(".nwk") {
+            "newick"
+        } else if name.ends_with(".biom") {
+            "biom"
+        } else if name.ends_with(".json") {
+            "json"
+        } else {
+            return None;
+        };
+        Some(output_type)
+    }
+
+    fn collect_outputs(directory: &Path, genome: &str, outputs: &mut Vec<(PathBuf, &'static str)>) {
+        let entries = match std::fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        for path in paths {
+            if path.is_dir() {
+                Self::collect_outputs(&path, genome, outputs);
+            } else if let Some(output_type) = Self::output_type(&path, genome) {
+                outputs.push((path, output_type));
+            }
+        }
+    }
+
+    /// The output files of a genome of the given type
+    pub fn outputs_of_type(&self, genome: &str, output_type: &str) -> Vec<PathBuf> {
+        let mut outputs = Vec::new();
+        Self::collect_outputs(Path::new(&self.genome_prefix(genome)), genome, &mut outputs);
+        outputs
+            .into_iter()
+            .filter(|(_, path_type)| *path_type == output_type)
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Writes the manifest of the outputs of the given genomes as a TSV with the genome, mode,
+    /// output type and path of each file. Returns the path of the manifest
+    pub fn write_manifest(&self, genomes: &[String]) -> Result<PathBuf, BirdToolError> {
+        let manifest_path = Path::new(&self.output_directory).join(Self::MANIFEST_NAME);
+        // only appears once every output is listed
+        let manifest = PartialOutput::new(&manifest_path);
+        let mut writer = BufWriter::new(manifest.create()?);
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!(
+                "Unable to write to {}: {}",
+                manifest_path.display(),
+                e
+            ))
+        };
+
+        writeln!(writer, "genome\tmode\ttype\tpath").map_err(write_error)?;
+        for genome in genomes {
+            let mut outputs = Vec::new();
+            Self::collect_outputs(Path::new(&self.genome_prefix(genome)), genome, &mut outputs);
+            for (path, output_type) in outputs {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    genome,
+                    &self.mode,
+                    output_type,
+                    path.display()
+                )
+                .map_err(write_error)?;
+            }
+        }
+        writer.flush().map_err(write_error)?;
+        drop(writer);
+
+        manifest.commit()
+    }
+}
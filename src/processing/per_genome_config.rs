@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use crate::genotype::heterozygosity_priors::HeterozygosityPriors;
+use crate::utils::errors::BirdToolError;
+
+/// Parameters of a single genome overriding the values given on the command line. Keys are
+/// named after the command line options they replace
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct GenomeOverrides {
+    pub kmer_sizes: Option<Vec<usize>>,
+    pub ploidy: Option<usize>,
+    pub active_probability_threshold: Option<f32>,
+    pub min_assembly_region_size: Option<usize>,
+    pub max_assembly_region_size: Option<usize>,
+    pub depth_per_sample_filter: Option<i64>,
+}
+
+impl GenomeOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn validate(&self, genome: &str) -> Result<(), BirdToolError> {
+        let invalid = |message: &str| {
+            Err(BirdToolError::DebugError(format!(
+                "Invalid per genome config for {}: {}",
+                genome, message
+            )))
+        };
+        if let Some(kmer_sizes) = &self.kmer_sizes {
+            if kmer_sizes.is_empty() || kmer_sizes.iter().any(|kmer_size| *kmer_size == 0) {
+                return invalid("kmer-sizes must list at least one kmer size above 0");
+            }
+        }
+        if self.ploidy == Some(0) {
+            return invalid("ploidy must be at least 1");
+        }
+        if let Some(threshold) = self.active_probability_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return invalid("active-probability-threshold must be between 0 and 1");
+            }
+        }
+        if let (Some(min_size), Some(max_size)) =
+            (self.min_assembly_region_size, self.max_assembly_region_size)
+        {
+            if min_size > max_size {
+                return invalid(
+                    "min-assembly-region-size must not exceed max-assembly-region-size",
+                );
+            }
+        }
+        if let Some(depth) = self.depth_per_sample_filter {
+            if depth < 0 {
+                return invalid("depth-per-sample-filter must not be negative");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Per genome parameter overrides read from the TOML file given by --per-genome-config. Each table
+/// is named by a genome, or the path of its fasta file, and its keys are the options it overrides,
+/// e.g. `[genome_1]` with `kmer-sizes = [17, 25]` and `ploidy = 2`.
+#[derive(Debug, Clone, Default)]
+pub struct PerGenomeConfig {
+    genomes: HashMap<String, GenomeOverrides>,
+}
+
+impl PerGenomeConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The config given on the command line, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let path = args
+            .try_get_one::<String>("per-genome-config")
+            .ok()
+            .flatten()?;
+        match Self::from_file(path) {
+            Ok(config) => Some(config),
+            Err(BirdToolError::IOError(message)) | Err(BirdToolError::DebugError(message)) => {
+                panic!("{}", message)
+            }
+            Err(_) => None,
+        }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, BirdToolError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read per genome config {}: {}", path, e))
+        })?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, BirdToolError> {
+        let tables: HashMap<String, GenomeOverrides> = toml::from_str(text).map_err(|e| {
+            BirdToolError::DebugError(format!("Unable to parse per genome config: {}", e))
+        })?;
+
+        let mut config = Self::new();
+        for (genome, overrides) in tables {
+            overrides.validate(&genome)?;
+            config.insert(&genome, overrides);
+        }
+        Ok(config)
+    }
+
+    pub fn insert(&mut self, genome: &str, overrides: GenomeOverrides) {
+        self.genomes.insert(
+            HeterozygosityPriors::genome_name(genome).to_string(),
+            overrides,
+        );
+    }
+
+    /// The overrides of a genome, if it is in the config
+    pub fn get(&self, genome: &str) -> Option<&GenomeOverrides> {
+        self.genomes.get(HeterozygosityPriors::genome_name(genome))
+    }
+
+    /// The overrides of a genome, which are empty if no config was given or the genome is not
+    /// in it
+    pub fn overrides_from_args(args: &clap::ArgMatches, genome: &str) -> GenomeOverrides {
+        Self::from_args(args)
+            .and_then(|config| config.get(genome).cloned())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.genomes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genomes.is_empty()
+    }
+}
@@ -0,0 +1,473 @@
+use ndarray::Array2;
+use rayon::prelude::*;
+use rust_htslib::bam::record::{Cigar, Record};
+use std::collections::HashMap;
+
+use crate::ani_calculator::ani_calculator::ANICalculator;
+use crate::bam_parsing::bam_generator::*;
+use crate::bam_parsing::FlagFilter;
+use crate::genotype::genotype_builder::{AttributeObject, Genotype};
+use crate::model::byte_array_allele::ByteArrayAllele;
+use crate::model::variant_context::VariantContext;
+use crate::processing::lorikeet_engine::ReadType;
+use crate::reads::read_utils::ReadUtils;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::utils::thread_budget::ThreadBudget;
+
+const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+/// The index of a base in ACGT order, None for ambiguous bases
+pub fn base_index(base: u8) -> Option<usize> {
+    match base.to_ascii_uppercase() {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+/// Base counts of one sample along a contig. The depth is kept for every position, while the
+/// counts of non-reference bases are only kept at the positions they were seen at
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleBaseCounts {
+    depths: Vec<u32>,
+    alternate_counts: HashMap<usize, [u32; 4]>,
+}
+
+impl SampleBaseCounts {
+    pub fn new(length: usize) -> Self {
+        Self {
+            depths: vec![0; length],
+            alternate_counts: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.depths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.depths.is_empty()
+    }
+
+    /// Counts a base observed at a 0-based position of a contig with the given sequence
+    pub fn add_base(&mut self, position: usize, base: u8, reference: &[u8]) {
+        let index = match base_index(base) {
+            Some(index) => index,
+            None => return,
+        };
+        if position >= self.depths.len() {
+            return;
+        }
+        self.depths[position] += 1;
+        if reference.get(position).and_then(|b| base_index(*b)) != Some(index) {
+            self.alternate_counts.entry(position).or_insert([0; 4])[index] += 1;
+        }
+    }
+
+    pub fn depth(&self, position: usize) -> u32 {
+        self.depths.get(position).copied().unwrap_or(0)
+    }
+
+    /// Positions at which a non-reference base was seen
+    pub fn alternate_positions(&self) -> impl Iterator<Item = &usize> {
+        self.alternate_counts.keys()
+    }
+
+    /// The counts of each base at a position whose reference base has `reference_index`
+    pub fn counts(&self, position: usize, reference_index: usize) -> [u32; 4] {
+        let mut counts = self
+            .alternate_counts
+            .get(&position)
+            .copied()
+            .unwrap_or([0; 4]);
+        let alternate_depth = counts.iter().sum::<u32>();
+        counts[reference_index] = self.depth(position).saturating_sub(alternate_depth);
+        counts
+    }
+
+    /// Run length encoding of the positions covered by at least `threshold` bases, in the format
+    /// taken by `ANICalculator::calculate_compared_bases`: positive runs are covered and negative
+    /// runs are not
+    pub fn depth_runs(&self, threshold: u32) -> Vec<i32> {
+        let mut runs = Vec::new();
+        let mut current: i32 = 0;
+        for depth in self.depths.iter() {
+            if *depth >= threshold {
+                if current < 0 {
+                    runs.push(current);
+                    current = 0;
+                }
+                current += 1;
+            } else {
+                if current > 0 {
+                    runs.push(current);
+                    current = 0;
+                }
+                current -= 1;
+            }
+        }
+        runs.push(current);
+        runs
+    }
+}
+
+/// ANI from pileup base counts, for lorikeet ani. Every position where a sample carries a
+/// non-reference base passing --depth-per-sample-filter and --min-snv-fraction becomes a site, so
+/// only SNVs are counted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PileupAni {
+    pub min_base_quality: u8,
+    pub min_mapq: u8,
+    pub depth_per_sample_filter: u32,
+    pub min_allele_fraction: f64,
+}
+
+impl PileupAni {
+    pub fn new(
+        min_base_quality: u8,
+        min_mapq: u8,
+        depth_per_sample_filter: u32,
+        min_allele_fraction: f64,
+    ) -> Self {
+        Self {
+            min_base_quality,
+            min_mapq,
+            depth_per_sample_filter: depth_per_sample_filter.max(1),
+            min_allele_fraction: min_allele_fraction.clamp(0.0, 1.0),
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            *args.get_one::<u8>("min-base-quality").unwrap(),
+            *args.get_one::<u8>("min-mapq").unwrap(),
+            (*args.get_one::<i64>("depth-per-sample-filter").unwrap()).max(0) as u32,
+            args.try_get_one::<f64>("min-snv-fraction")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(0.05),
+        )
+    }
+
+    /// Adds the aligned bases of a record passing the base quality threshold
+    pub fn count_record(&self, record: &Record, reference: &[u8], counts: &mut SampleBaseCounts) {
+        self.count_record_from(record, reference, 0, counts)
+    }
+
+    /// Adds the aligned bases of a record passing the base quality threshold, where `reference`
+    /// and `counts` start at `reference_start` of the contig rather than at its first base.
+    /// Bases aligned before `reference_start` are skipped
+    pub fn count_record_from(
+        &self,
+        record: &Record,
+        reference: &[u8],
+        reference_start: usize,
+        counts: &mut SampleBaseCounts,
+    ) {
+        let sequence = record.seq().as_bytes();
+        let qualities = record.qual();
+        let mut read_position = 0;
+        let mut reference_position = record.pos().max(0) as usize;
+        for op in record.cigar().iter() {
+            match op {
+                Cigar::Match(length) | Cigar::Equal(length) | Cigar::Diff(length) => {
+                    for _ in 0..*length {
+                        if qualities[read_position] >= self.min_base_quality
+                            && reference_position >= reference_start
+                        {
+                            counts.add_base(
+                                reference_position - reference_start,
+                                sequence[read_position],
+                                reference,
+                            );
+                        }
+                        read_position += 1;
+                        reference_position += 1;
+                    }
+                }
+                Cigar::Ins(length) | Cigar::SoftClip(length) => read_position += *length as usize,
+                Cigar::Del(length) | Cigar::RefSkip(length) => {
+                    reference_position += *length as usize
+                }
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+    }
+
+    /// Counts the bases of the reads of a sample aligned to a contig
+    pub fn count_sample(
+        &self,
+        bam_path: &str,
+        tid: usize,
+        reference: &[u8],
+        flag_filters: &FlagFilter,
+        read_type: ReadType,
+        args: &clap::ArgMatches,
+    ) -> SampleBaseCounts {
+        let min_long_read_size = *args.get_one::<usize>("min-long-read-size").unwrap();
+        let min_long_read_average_base_qual = *args
+            .get_one::<usize>("min-long-read-average-base-qual")
+            .unwrap();
+
+        let mut counts = SampleBaseCounts::new(reference.len());
+        let bam_generator = generate_indexed_named_bam_readers_from_bam_files(vec![bam_path], 1)
+            .into_iter()
+            .next()
+            .unwrap();
+        let mut bam_generated = bam_generator.start();
+        bam_generated.set_threads(ThreadBudget::from_args(args).reader_threads());
+        if bam_generated.fetch(tid as i32).is_err() {
+            warn!("Unable to fetch contig {} from {}", tid, bam_path);
+            return counts;
+        }
+
+        let mut record = Record::new();
+        while bam_generated.read(&mut record) {
+            if ReadUtils::read_is_filtered(
+                &record,
+                flag_filters,
+                self.min_mapq,
+                read_type,
+                &None,
+                min_long_read_size,
+                min_long_read_average_base_qual,
+            ) {
+                continue;
+            }
+            self.count_record(&record, reference, &mut counts);
+        }
+        counts
+    }
+
+    /// The site at a position given the base counts of every sample, None if no sample carries a
+    /// non-reference base at a high enough depth and fraction
+    pub fn site(
+        &self,
+        tid: usize,
+        position: usize,
+        reference_base: u8,
+        counts: &[[u32; 4]],
+    ) -> Option<VariantContext> {
+        let reference_index = base_index(reference_base)?;
+        let passes = |count: u32, depth: u32| {
+            count >= self.depth_per_sample_filter
+                && count as f64 >= self.min_allele_fraction * depth as f64
+        };
+        let alternate_indices = (0..BASES.len())
+            .filter(|index| *index != reference_index)
+            .filter(|index| {
+                counts
+                    .iter()
+                    .any(|sample| passes(sample[*index], sample.iter().sum::<u32>()))
+            })
+            .collect::<Vec<usize>>();
+        if alternate_indices.is_empty() {
+            return None;
+        }
+
+        let allele_indices = std::iter::once(reference_index)
+            .chain(alternate_indices.into_iter())
+            .collect::<Vec<usize>>();
+        let alleles = allele_indices
+            .iter()
+            .map(|index| ByteArrayAllele::new(&[BASES[*index]], *index == reference_index))
+            .collect::<Vec<ByteArrayAllele>>();
+        let mut context = VariantContext::build(tid, position, position, alleles);
+
+        let genotypes = counts
+            .iter()
+            .enumerate()
+            .map(|(sample_index, sample)| {
+                let depth = sample.iter().sum::<u32>();
+                let ad = allele_indices
+                    .iter()
+                    .map(|index| {
+                        if passes(sample[*index], depth) {
+                            sample[*index] as i32
+                        } else {
+                            0
+                        }
+                    })
+                    .collect::<Vec<i32>>();
+                let mut genotype = Genotype::build_from_ads(1, ad);
+                genotype.dp = depth as i32;
+                genotype.sample_name = sample_index;
+                genotype
+            })
+            .collect::<Vec<Genotype>>();
+        context.add_genotypes(genotypes);
+        // sites are not scored, so they always pass the quality thresholds
+        context.set_attribute(
+            "QF".to_string(),
+            AttributeObject::String("true".to_string()),
+        );
+        Some(context)
+    }
+
+    /// The sites of a contig and the number of bases compared between each pair of samples
+    pub fn calculate_contig(
+        &self,
+        tid: usize,
+        reference: &[u8],
+        samples: &[SampleBaseCounts],
+    ) -> (Vec<VariantContext>, Array2<f32>) {
+        let mut positions = samples
+            .iter()
+            .flat_map(|sample| sample.alternate_positions().copied())
+            .collect::<Vec<usize>>();
+        positions.sort_unstable();
+        positions.dedup();
+
+        let contexts = positions
+            .into_iter()
+            .filter_map(|position| {
+                let reference_base = *reference.get(position)?;
+                let reference_index = base_index(reference_base)?;
+                let counts = samples
+                    .iter()
+                    .map(|sample| sample.counts(position, reference_index))
+                    .collect::<Vec<[u32; 4]>>();
+                self.site(tid, position, reference_base, &counts)
+            })
+            .collect::<Vec<VariantContext>>();
+
+        let compared_bases = ANICalculator::calculate_compared_bases(
+            Some(
+                samples
+                    .iter()
+                    .map(|sample| sample.depth_runs(self.depth_per_sample_filter))
+                    .collect::<Vec<Vec<i32>>>(),
+            ),
+            reference.len() as u64,
+            samples.len(),
+        );
+        (contexts, compared_bases)
+    }
+
+    /// The sites of a genome and the number of bases compared between each pair of samples
+    /// across its contigs
+    pub fn calculate(
+        &self,
+        args: &clap::ArgMatches,
+        indexed_bam_readers: &[String],
+        short_sample_count: usize,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        flag_filters: &FlagFilter,
+    ) -> (Vec<VariantContext>, Array2<f32>) {
+        let n_samples = indexed_bam_readers.len();
+        let min_contig_length = *args.get_one::<u64>("min-contig-size").unwrap();
+        let reference = reference_reader.retrieve_reference_stem(ref_idx);
+        let separator = reference_reader.genomes_and_contigs.separator;
+
+        // the contigs of the genome, which are in the same order in every BAM file
+        let bam_generator = generate_indexed_named_bam_readers_from_bam_files(
+            vec![indexed_bam_readers[0].as_str()],
+            1,
+        )
+        .into_iter()
+        .next()
+        .unwrap();
+        let header = bam_generator.header();
+        let mut tids = Vec::new();
+        for (tid, contig_name) in header.target_names().into_iter().enumerate() {
+            let target_name = std::str::from_utf8(contig_name).unwrap();
+            let target_match = match separator.split(target_name) {
+                Some((genome, _)) => genome == reference.as_str(),
+                None => target_name.contains(&reference),
+            };
+            let target_len = header.target_len(tid as u32).unwrap();
+            if target_match && target_len >= min_contig_length {
+                reference_reader.update_ref_index_tids(ref_idx, tid);
+                reference_reader.add_target(contig_name, tid);
+                reference_reader.add_length(tid, target_len);
+                tids.push(tid);
+            }
+        }
+
+        let long_reads = args.contains_id("longreads") || args.contains_id("longread-bam-files");
+        let reference_reader = &*reference_reader;
+        tids.into_par_iter()
+            .map(|tid| {
+                let mut reference_reader = reference_reader.clone();
+                reference_reader.update_current_sequence_capacity(
+                    reference_reader.get_contig_length(tid) as usize,
+                );
+                if reference_reader
+                    .fetch_contig_from_reference_by_tid(tid, ref_idx)
+                    .is_err()
+                {
+                    warn!(
+                        "{}: Unable to read the sequence of contig {}",
+                        &reference, tid
+                    );
+                    return (Vec::new(), Array2::default((n_samples, n_samples)));
+                }
+                reference_reader.read_sequence_to_vec();
+                let sequence = std::mem::take(&mut reference_reader.current_sequence);
+
+                let samples = indexed_bam_readers
+                    .par_iter()
+                    .enumerate()
+                    .map(|(sample_idx, bam_path)| {
+                        let read_type = if long_reads && sample_idx >= short_sample_count {
+                            ReadType::Long
+                        } else {
+                            ReadType::Short
+                        };
+                        self.count_sample(bam_path, tid, &sequence, flag_filters, read_type, args)
+                    })
+                    .collect::<Vec<SampleBaseCounts>>();
+                self.calculate_contig(tid, &sequence, &samples)
+            })
+            .reduce(
+                || (Vec::new(), Array2::default((n_samples, n_samples))),
+                |mut a, b| {
+                    a.0.extend(b.0);
+                    (a.0, a.1 + &b.1)
+                },
+            )
+    }
+
+    /// Calculates the sites of a genome and writes the ANI tables lorikeet call would write.
+    /// Returns the number of sites found
+    pub fn run(
+        &self,
+        args: &clap::ArgMatches,
+        indexed_bam_readers: &[String],
+        short_sample_count: usize,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        flag_filters: &FlagFilter,
+        output_prefix: &str,
+        sample_names: &[&str],
+    ) -> usize {
+        let reference = reference_reader.retrieve_reference_stem(ref_idx);
+        let (mut contexts, compared_bases) = self.calculate(
+            args,
+            indexed_bam_readers,
+            short_sample_count,
+            reference_reader,
+            ref_idx,
+            flag_filters,
+        );
+        let genome_size = reference_reader.target_lens.values().sum::<u64>();
+
+        let mut ani_calculator = ANICalculator::from_args(args, indexed_bam_readers.len());
+        ani_calculator.run_calculator(
+            &mut contexts,
+            output_prefix,
+            sample_names,
+            &reference,
+            genome_size,
+            Some(compared_bases),
+            0.0,
+            0.0,
+            self.depth_per_sample_filter as i64,
+        );
+        contexts.len()
+    }
+}
@@ -0,0 +1,259 @@
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, Command};
+use std::ffi::OsString;
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+use toml::value::{Table, Value};
+
+use crate::utils::errors::BirdToolError;
+use crate::utils::partial_output::PartialOutput;
+
+/// Run options read from the TOML file given by --config, keyed by their long option names, e.g.
+/// `kmer-sizes = [17, 25]` or `calculate-fst = true`. Options given on the command line take
+/// precedence. The merged options are written to `lorikeet_config.toml` in the output directory.
+pub struct RunConfig {}
+
+impl RunConfig {
+    pub const FILE_NAME: &'static str = "lorikeet_config.toml";
+    // options that describe how lorikeet was invoked rather than the run itself
+    const EXCLUDED_IDS: [&'static str; 3] = ["config", "full-help", "full-help-roff"];
+
+    /// The command line with the options of the config given by --config, if any, inserted after
+    /// the subcommand. Returned unchanged if no config was given
+    pub fn expand_args(app: &Command, args: Vec<OsString>) -> Result<Vec<OsString>, BirdToolError> {
+        // only global flags can come before the subcommand
+        let subcommand_index = match args
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, arg)| !arg.to_string_lossy().starts_with('-'))
+        {
+            Some((index, _)) => index,
+            None => return Ok(args),
+        };
+        let subcommand = match app.find_subcommand(&args[subcommand_index]) {
+            Some(subcommand) => subcommand,
+            None => return Ok(args),
+        };
+        let command_line = &args[subcommand_index + 1..];
+        let path = match Self::config_path(command_line) {
+            Some(path) => path,
+            None => return Ok(args),
+        };
+
+        let text = std::fs::read_to_string(&path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read config {}: {}", path, e))
+        })?;
+        let table = Self::parse(&text)?;
+        let config_args = Self::config_args(subcommand, &table, command_line)?;
+
+        let mut expanded = args[..=subcommand_index].to_vec();
+        expanded.extend(config_args);
+        expanded.extend(command_line.iter().cloned());
+        Ok(expanded)
+    }
+
+    /// The path given to --config on a command line
+    fn config_path(command_line: &[OsString]) -> Option<String> {
+        let mut tokens = command_line.iter().map(|token| token.to_string_lossy());
+        while let Some(token) = tokens.next() {
+            if token == "--config" {
+                return tokens.next().map(|path| path.to_string());
+            } else if let Some(path) = token.strip_prefix("--config=") {
+                return Some(path.to_string());
+            }
+        }
+        None
+    }
+
+    pub fn parse(text: &str) -> Result<Table, BirdToolError> {
+        toml::from_str(text)
+            .map_err(|e| BirdToolError::ConfigError(format!("Unable to parse config: {}", e)))
+    }
+
+    /// Whether an option was given on a command line, by its long or short name
+    fn given_on_command_line(arg: &Arg, command_line: &[OsString]) -> bool {
+        command_line.iter().any(|token| {
+            let token = token.to_string_lossy();
+            if let Some(long) = token.strip_prefix("--") {
+                arg.get_long() == long.split('=').next()
+            } else if let Some(short) = token.strip_prefix('-') {
+                arg.get_short().is_some() && short.chars().next() == arg.get_short()
+            } else {
+                false
+            }
+        })
+    }
+
+    /// A single value of an option as it would be typed on the command line
+    fn scalar(key: &str, value: &Value) -> Result<String, BirdToolError> {
+        match value {
+            Value::String(value) => Ok(value.clone()),
+            Value::Integer(value) => Ok(value.to_string()),
+            Value::Float(value) => Ok(value.to_string()),
+            Value::Boolean(value) => Ok(value.to_string()),
+            Value::Datetime(value) => Ok(value.to_string()),
+            Value::Array(_) | Value::Table(_) => Err(BirdToolError::ConfigError(format!(
+                "Config option {} must be a single value or a list of values",
+                key
+            ))),
+        }
+    }
+
+    /// The command line options of the subcommand set by a config, skipping those given on the
+    /// command line
+    pub fn config_args(
+        command: &Command,
+        table: &Table,
+        command_line: &[OsString],
+    ) -> Result<Vec<OsString>, BirdToolError> {
+        let mut config_args = Vec::new();
+        for (key, value) in table.iter() {
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == key.as_str())
+                .filter(|_| !Self::EXCLUDED_IDS.contains(&key.as_str()))
+                .ok_or_else(|| {
+                    BirdToolError::ConfigError(format!(
+                        "Unknown option {} in config for lorikeet {}",
+                        key,
+                        command.get_name()
+                    ))
+                })?;
+            let long = match arg.get_long() {
+                Some(long) => format!("--{}", long),
+                None => {
+                    return Err(BirdToolError::ConfigError(format!(
+                        "Option {} can not be set in a config",
+                        key
+                    )))
+                }
+            };
+            if Self::given_on_command_line(arg, command_line) {
+                continue;
+            }
+
+            match (value, arg.get_action()) {
+                (Value::Boolean(set), ArgAction::SetTrue) => {
+                    if *set {
+                        config_args.push(long.into());
+                    }
+                }
+                (Value::Boolean(set), ArgAction::SetFalse) => {
+                    if !*set {
+                        config_args.push(long.into());
+                    }
+                }
+                (Value::Array(values), action) => {
+                    let values = values
+                        .iter()
+                        .map(|value| Self::scalar(key, value))
+                        .collect::<Result<Vec<String>, BirdToolError>>()?;
+                    if matches!(action, ArgAction::Append) {
+                        for value in values {
+                            config_args.push(long.clone().into());
+                            config_args.push(value.into());
+                        }
+                    } else {
+                        config_args.push(long.into());
+                        config_args.extend(values.into_iter().map(OsString::from));
+                    }
+                }
+                (value, _) => {
+                    config_args.push(long.into());
+                    config_args.push(Self::scalar(key, value)?.into());
+                }
+            }
+        }
+        Ok(config_args)
+    }
+
+    /// A single parsed value, typed as it would most naturally be written in TOML
+    fn toml_value(raw: &str) -> Value {
+        if let Ok(value) = raw.parse::<bool>() {
+            return Value::Boolean(value);
+        }
+        // values such as 007 keep their leading zeros
+        if let Ok(value) = raw.parse::<i64>() {
+            if value.to_string() == raw {
+                return Value::Integer(value);
+            }
+        }
+        if raw.contains('.') {
+            if let Ok(value) = raw.parse::<f64>() {
+                if value.is_finite() {
+                    return Value::Float(value);
+                }
+            }
+        }
+        Value::String(raw.to_string())
+    }
+
+    /// The options given to a run, from the command line or a config. Defaults are left out, so
+    /// that rerunning with the config never sets options that conflict with each other
+    pub fn to_toml(args: &clap::ArgMatches) -> Table {
+        let mut table = Table::new();
+        for id in args.ids() {
+            let id = id.as_str();
+            if Self::EXCLUDED_IDS.contains(&id)
+                || !matches!(
+                    args.value_source(id),
+                    Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+                )
+            {
+                continue;
+            }
+            let raw = match args.get_raw(id) {
+                Ok(Some(raw)) => raw
+                    .map(|value| value.to_string_lossy().to_string())
+                    .collect::<Vec<String>>(),
+                _ => continue,
+            };
+            let value = match raw.len() {
+                0 => continue,
+                1 => Self::toml_value(&raw[0]),
+                _ => Value::Array(raw.iter().map(|value| Self::toml_value(value)).collect()),
+            };
+            table.insert(id.to_string(), value);
+        }
+        table
+    }
+
+    /// Writes the options of a run to `lorikeet_config.toml` in the output directory. Returns the
+    /// path of the config
+    pub fn write(
+        args: &clap::ArgMatches,
+        mode: &str,
+        output_directory: &str,
+    ) -> Result<PathBuf, BirdToolError> {
+        create_dir_all(output_directory).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create directory {}: {}",
+                output_directory, e
+            ))
+        })?;
+        let config = PartialOutput::new(Path::new(output_directory).join(Self::FILE_NAME));
+        let text = toml::to_string(&Self::to_toml(args)).map_err(|e| {
+            BirdToolError::DebugError(format!("Unable to serialize run config: {}", e))
+        })?;
+        std::fs::write(
+            config.partial_path(),
+            format!(
+                "# Options of lorikeet {} v{}, rerun with lorikeet {} --config {}\n{}",
+                mode,
+                env!("CARGO_PKG_VERSION"),
+                mode,
+                Self::FILE_NAME,
+                text
+            ),
+        )
+        .map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to write {}: {}",
+                config.partial_path().display(),
+                e
+            ))
+        })?;
+        config.commit()
+    }
+}
@@ -0,0 +1,85 @@
+/// The output pathways of each genome. The all subcommand writes each pathway chosen with --outputs
+/// to its own subdirectory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutputs {
+    pub call: bool,
+    pub genotype: bool,
+    pub consensus: bool,
+    combined: bool,
+}
+
+impl RunOutputs {
+    pub const COMBINED_MODE: &'static str = "all";
+    pub const PATHWAYS: [&'static str; 3] = ["call", "genotype", "consensus"];
+
+    pub fn new(call: bool, genotype: bool, consensus: bool, combined: bool) -> Self {
+        Self {
+            call,
+            genotype,
+            consensus,
+            combined,
+        }
+    }
+
+    /// The outputs of a single subcommand
+    pub fn for_mode(mode: &str) -> Self {
+        Self::new(
+            mode == "call",
+            mode == "genotype",
+            mode == "consensus",
+            false,
+        )
+    }
+
+    pub fn from_args(args: &clap::ArgMatches, mode: &str) -> Self {
+        if mode != Self::COMBINED_MODE {
+            return Self::for_mode(mode);
+        }
+
+        let outputs = args
+            .try_get_many::<String>("outputs")
+            .ok()
+            .flatten()
+            .map(|outputs| outputs.map(|output| output.as_str()).collect::<Vec<&str>>())
+            .unwrap_or_else(|| Self::PATHWAYS.to_vec());
+        Self::new(
+            outputs.contains(&"call"),
+            outputs.contains(&"genotype"),
+            outputs.contains(&"consensus"),
+            true,
+        )
+    }
+
+    pub fn is_combined(&self) -> bool {
+        self.combined
+    }
+
+    /// The selected pathways, in the order they are produced
+    pub fn pathways(&self) -> Vec<&'static str> {
+        Self::PATHWAYS
+            .iter()
+            .zip([self.call, self.genotype, self.consensus])
+            .filter(|(_, selected)| *selected)
+            .map(|(pathway, _)| *pathway)
+            .collect()
+    }
+
+    /// Whether the contexts are needed by a pathway produced after `pathway`
+    pub fn needed_after(&self, pathway: &str) -> bool {
+        self.pathways()
+            .into_iter()
+            .skip_while(|selected| *selected != pathway)
+            .nth(1)
+            .is_some()
+    }
+
+    /// The directory the outputs of a pathway are written to, given the output directory of the
+    /// genome
+    pub fn output_prefix(&self, genome_prefix: &str, pathway: &str) -> String {
+        if self.combined {
+            format!("{}/{}", genome_prefix, pathway)
+        } else {
+            genome_prefix.to_string()
+        }
+    }
+}
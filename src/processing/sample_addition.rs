@@ -0,0 +1,421 @@
+use ndarray::s;
+use rust_htslib::bcf::Read;
+use std::collections::HashMap;
+use std::fs::{copy, remove_file, rename};
+use std::path::Path;
+use tempfile::TempDir;
+
+use crate::abundance::abundance_calculator_engine::AbundanceCalculatorEngine;
+use crate::abundance::abundance_formats::{AbundanceFormat, CamiProfiles};
+use crate::abundance::strain_frequencies::StrainFrequencyEstimator;
+use crate::ani_calculator::ani_calculator::ANICalculator;
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::genotype::genotype_builder::AttributeObject;
+use crate::model::variant_context::VariantContext;
+use crate::processing::vcf_combiner::{CombineInput, VcfCombiner};
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// A genome of a previous lorikeet run that new samples are being added to
+#[derive(Debug, Clone)]
+pub struct ExistingGenome {
+    pub name: String,
+    pub reference: String,
+    pub vcf_path: String,
+}
+
+impl ExistingGenome {
+    fn output_prefix(&self, output_directory: &str) -> String {
+        format!("{}/{}", output_directory, self.name)
+    }
+}
+
+/// Adds samples to the output directory of a previous `lorikeet call` or `lorikeet genotype` run
+/// without reprocessing the BAM files of the existing samples.
+///
+/// The new samples are called on their own with the alleles of each existing VCF provided as
+/// feature variants, so previously discovered alleles are genotyped in the new samples alongside
+/// any regions that are only active in the new samples. The results are then combined with the
+/// existing VCF in the same way as `lorikeet combine`. ANI values between pairs of existing
+/// samples and pairs of new samples are kept from their own runs, while ANI values between an
+/// existing and a new sample are calculated from the combined VCF assuming every base of the
+/// genome is comparable. Strain abundances are recalculated for genomes that were genotyped,
+/// using the strains assigned to each variant by the existing run.
+pub struct SampleAddition {
+    output_directory: String,
+    genomes: Vec<ExistingGenome>,
+    feature_vcfs: Vec<String>,
+    working_directory: TempDir,
+}
+
+impl SampleAddition {
+    const CALL_DIRECTORY: &'static str = "calls";
+
+    /// Finds the existing output of each reference in `output_directory`. References without
+    /// a VCF file from a previous run are skipped
+    pub fn new(output_directory: &str, references: &[String]) -> Result<Self, BirdToolError> {
+        let genomes = references
+            .iter()
+            .filter_map(|reference| {
+                let name = Path::new(reference).file_stem()?.to_str()?.to_string();
+                match Self::find_vcf(&format!("{}/{}", output_directory, name), &name) {
+                    Some(vcf_path) => Some(ExistingGenome {
+                        name,
+                        reference: reference.clone(),
+                        vcf_path,
+                    }),
+                    None => {
+                        warn!(
+                            "No existing VCF file found for {} in {}, skipping",
+                            name, output_directory
+                        );
+                        None
+                    }
+                }
+            })
+            .collect::<Vec<ExistingGenome>>();
+        if genomes.is_empty() {
+            return Err(BirdToolError::IOError(format!(
+                "No existing lorikeet output found in {} for the provided references",
+                output_directory
+            )));
+        }
+
+        let working_directory = tempfile::Builder::new()
+            .prefix("lorikeet_add_sample")
+            .tempdir_in(output_directory)
+            .map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to create working directory in {}: {}",
+                    output_directory, e
+                ))
+            })?;
+
+        let mut sample_addition = Self {
+            output_directory: output_directory.to_string(),
+            genomes,
+            feature_vcfs: Vec::new(),
+            working_directory,
+        };
+        sample_addition.prepare_feature_vcfs()?;
+        Ok(sample_addition)
+    }
+
+    pub fn genomes(&self) -> &[ExistingGenome] {
+        &self.genomes
+    }
+
+    /// The VCF file written for a genome by lorikeet, either uncompressed or bgzipped
+    pub fn find_vcf(output_prefix: &str, name: &str) -> Option<String> {
+        [
+            format!("{}/{}.vcf", output_prefix, name),
+            format!("{}/{}.vcf.gz", output_prefix, name),
+        ]
+        .into_iter()
+        .find(|path| Path::new(path).exists())
+    }
+
+    // Indexing a plain VCF writes a bgzipped copy next to it, so the existing VCFs are copied to
+    // the working directory before being used as feature variants
+    fn prepare_feature_vcfs(&mut self) -> Result<(), BirdToolError> {
+        let mut feature_vcfs = Vec::with_capacity(self.genomes.len());
+        for genome in self.genomes.iter() {
+            let feature_vcf = format!("{}/{}_features.vcf", self.working_path(), genome.name);
+            let copy_path = if genome.vcf_path.ends_with(".gz") {
+                format!("{}.gz", feature_vcf)
+            } else {
+                feature_vcf.clone()
+            };
+            copy(&genome.vcf_path, &copy_path).map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to copy {} to {}: {}",
+                    genome.vcf_path, copy_path, e
+                ))
+            })?;
+            VcfInput::open_indexed(&feature_vcf)?;
+            feature_vcfs.push(format!("{}.gz", feature_vcf));
+        }
+        self.feature_vcfs = feature_vcfs;
+        Ok(())
+    }
+
+    fn working_path(&self) -> &str {
+        self.working_directory.path().to_str().unwrap()
+    }
+
+    fn call_output_directory(&self) -> String {
+        format!("{}/{}", self.working_path(), Self::CALL_DIRECTORY)
+    }
+
+    /// The command line of the `lorikeet call` run on the new samples. Any arguments given after
+    /// `--` are passed on to `lorikeet call`
+    pub fn call_command_line(&self, args: &clap::ArgMatches) -> Vec<String> {
+        let mut command_line = vec![
+            "lorikeet".to_string(),
+            "call".to_string(),
+            "--genome-fasta-files".to_string(),
+        ];
+        command_line.extend(self.genomes.iter().map(|genome| genome.reference.clone()));
+        for bam_argument in ["bam-files", "longread-bam-files"] {
+            if let Some(bam_files) = args.get_many::<String>(bam_argument) {
+                command_line.push(format!("--{}", bam_argument));
+                command_line.extend(bam_files.cloned());
+            }
+        }
+        command_line.push("--features-vcf".to_string());
+        command_line.extend(self.feature_vcfs.iter().cloned());
+        command_line.extend([
+            "--threads".to_string(),
+            args.get_one::<usize>("threads").unwrap().to_string(),
+            "--output-directory".to_string(),
+            self.call_output_directory(),
+            "--force".to_string(),
+        ]);
+        if args.get_flag("verbose") {
+            command_line.push("--verbose".to_string());
+        }
+        if args.get_flag("quiet") {
+            command_line.push("--quiet".to_string());
+        }
+        if let Some(call_args) = args.get_many::<String>("call-args") {
+            command_line.extend(call_args.cloned());
+        }
+        command_line
+    }
+
+    /// Combines the results of the new samples with the existing output of every genome
+    pub fn merge_results(&self, call_args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        let cami_profiles = CamiProfiles::new();
+        for genome in self.genomes.iter() {
+            self.merge_genome(genome, call_args, &cami_profiles)?;
+        }
+        if !cami_profiles.is_empty() {
+            cami_profiles.write(&self.output_directory)?;
+        }
+        Ok(())
+    }
+
+    fn merge_genome(
+        &self,
+        genome: &ExistingGenome,
+        call_args: &clap::ArgMatches,
+        cami_profiles: &CamiProfiles,
+    ) -> Result<(), BirdToolError> {
+        let output_prefix = genome.output_prefix(&self.output_directory);
+        let call_prefix = genome.output_prefix(&self.call_output_directory());
+        let new_vcf = match Self::find_vcf(&call_prefix, &genome.name) {
+            Some(new_vcf) => new_vcf,
+            None => {
+                warn!("{}: No VCF file was produced for the new samples", genome.name);
+                return Ok(());
+            }
+        };
+
+        let existing = CombineInput::from_vcf(&genome.vcf_path)?;
+        let new = CombineInput::from_vcf(&new_vcf)?;
+        if let Some(duplicate) = new
+            .sample_names
+            .iter()
+            .find(|sample| existing.sample_names.contains(sample))
+        {
+            return Err(BirdToolError::DebugError(format!(
+                "Sample {} is already present in {}",
+                duplicate, genome.vcf_path
+            )));
+        }
+        let n_existing = existing.sample_names.len();
+        let n_new = new.sample_names.len();
+        let sample_names = existing
+            .sample_names
+            .iter()
+            .chain(new.sample_names.iter())
+            .cloned()
+            .collect::<Vec<String>>();
+        info!(
+            "{}: Adding {} samples to {} existing samples",
+            genome.name, n_new, n_existing
+        );
+
+        let ploidy = *call_args.get_one::<usize>("ploidy").unwrap();
+        let (contigs, merged) = VcfCombiner::merge_inputs(vec![existing, new], ploidy);
+        let mut contexts = VcfCombiner::joint_genotype(call_args, merged, &sample_names);
+        let strains = Self::existing_strains(&genome.vcf_path)?;
+        let n_strains = Self::apply_strains(&mut contexts, &contigs, &strains);
+
+        let names = sample_names.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
+        let genome_size = contigs
+            .iter()
+            .filter_map(|(_, length)| *length)
+            .sum::<u64>();
+        if genome_size > 0 {
+            let mut ani_calculator = ANICalculator::from_args(call_args, sample_names.len());
+            ani_calculator.calculate(
+                &mut contexts,
+                genome_size,
+                None,
+                *call_args.get_one::<f64>("qual-by-depth-filter").unwrap(),
+                *call_args.get_one::<f64>("qual-threshold").unwrap() / -10.0,
+                *call_args.get_one::<i64>("depth-per-sample-filter").unwrap(),
+            );
+            for table_name in ANICalculator::TABLE_NAMES {
+                let table = ani_calculator.table_mut(table_name).unwrap();
+                for (offset, n_samples, prefix) in [
+                    (0, n_existing, &output_prefix),
+                    (n_existing, n_new, &call_prefix),
+                ] {
+                    let path = format!("{}/{}_{}.tsv", prefix, genome.name, table_name);
+                    match ANICalculator::read_ani_table(&path) {
+                        Ok(block) if block.nrows() == n_samples => {
+                            table
+                                .slice_mut(s![
+                                    offset..offset + n_samples,
+                                    offset..offset + n_samples
+                                ])
+                                .assign(&block);
+                        }
+                        Ok(block) => warn!(
+                            "{}: Expected {} samples in {} but found {}",
+                            genome.name,
+                            n_samples,
+                            path,
+                            block.nrows()
+                        ),
+                        Err(e) => debug!("{}: Unable to reuse ANI table {:?}", genome.name, e),
+                    }
+                }
+            }
+            ani_calculator.write_tables(&output_prefix, &names, &genome.name);
+        } else {
+            warn!(
+                "{}: Contig lengths are missing from {}, ANI tables were not updated",
+                genome.name, genome.vcf_path
+            );
+        }
+
+        let strain_coverages = format!("{}/{}_strain_coverages.tsv", output_prefix, genome.name);
+        if n_strains > 0 && Path::new(&strain_coverages).exists() {
+            let mut abundance_calculator_engine = AbundanceCalculatorEngine::new(
+                contexts.clone(),
+                &genome.name,
+                &output_prefix,
+                &names,
+            );
+            abundance_calculator_engine.set_output_formats(AbundanceFormat::from_args(call_args)?);
+            abundance_calculator_engine.set_cami_profiles(cami_profiles.clone());
+            let (strain_ids_present, contexts) = abundance_calculator_engine
+                .run_abundance_calculator(n_strains, sample_names.len());
+            if !strain_ids_present.is_empty() {
+                if let Err(e) = StrainFrequencyEstimator::new(&strain_ids_present)
+                    .write_strain_frequencies(&contexts, &output_prefix, &genome.name, &names)
+                {
+                    warn!("{}: Unable to write strain frequencies {:?}", genome.name, e);
+                }
+            }
+        }
+
+        let merged_vcf = format!("{}/{}.vcf", self.working_path(), genome.name);
+        VcfCombiner::write_vcf(&merged_vcf, &genome.vcf_path, &contigs, &sample_names, &contexts)?;
+        let output_vcf = format!("{}/{}.vcf", output_prefix, genome.name);
+        rename(&merged_vcf, &output_vcf).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to move {} to {}: {}",
+                merged_vcf, output_vcf, e
+            ))
+        })?;
+        // compressed copies and indices of the previous VCF are now out of date
+        for suffix in [".gz", ".gz.csi", ".gz.tbi"] {
+            let stale = format!("{}{}", output_vcf, suffix);
+            if Path::new(&stale).exists() {
+                remove_file(&stale).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to remove {}: {}", stale, e))
+                })?;
+            }
+        }
+        info!(
+            "{}: Wrote {} sites across {} samples to {}",
+            genome.name,
+            contexts.len(),
+            sample_names.len(),
+            output_vcf
+        );
+
+        Ok(())
+    }
+
+    /// The strains assigned to each variant of a genotyped VCF, keyed by contig, position and
+    /// reference allele, along with the alternate allele the strains carry
+    pub fn existing_strains(
+        vcf_path: &str,
+    ) -> Result<HashMap<(String, usize, Vec<u8>), (Vec<u8>, Vec<usize>)>, BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let header = reader.header().clone();
+
+        let mut strains = HashMap::new();
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            let alleles = record.alleles();
+            let strain_ids = match record
+                .info(VariantAnnotations::Strain.to_key().as_bytes())
+                .integer()
+            {
+                Ok(Some(strain_ids)) => strain_ids
+                    .iter()
+                    .filter(|strain| **strain >= 0)
+                    .map(|strain| *strain as usize)
+                    .collect::<Vec<usize>>(),
+                _ => continue,
+            };
+            if alleles.len() < 2 || strain_ids.is_empty() {
+                continue;
+            }
+            let contig = match record.rid() {
+                Some(rid) => match header.rid2name(rid) {
+                    Ok(name) => String::from_utf8_lossy(name).to_string(),
+                    Err(_) => continue,
+                },
+                None => continue,
+            };
+            strains.insert(
+                (contig, record.pos() as usize, alleles[0].to_vec()),
+                (alleles[1].to_vec(), strain_ids),
+            );
+        }
+        Ok(strains)
+    }
+
+    /// Annotates the contexts whose first alternate allele was assigned to strains in the
+    /// existing run. Returns the number of strains
+    pub fn apply_strains(
+        contexts: &mut [VariantContext],
+        contigs: &[(String, Option<u64>)],
+        strains: &HashMap<(String, usize, Vec<u8>), (Vec<u8>, Vec<usize>)>,
+    ) -> usize {
+        let mut n_strains = 0;
+        for vc in contexts.iter_mut() {
+            if vc.alleles.len() < 2 {
+                continue;
+            }
+            let key = (
+                contigs[vc.loc.tid].0.clone(),
+                vc.loc.start,
+                vc.get_reference().bases.clone(),
+            );
+            if let Some((alternate, strain_ids)) = strains.get(&key) {
+                if &vc.alleles[1].bases != alternate {
+                    continue;
+                }
+                n_strains = strain_ids
+                    .iter()
+                    .map(|strain| strain + 1)
+                    .fold(n_strains, usize::max);
+                vc.set_attribute(
+                    VariantAnnotations::Strain.to_key().to_string(),
+                    AttributeObject::VecUnsize(strain_ids.clone()),
+                );
+            }
+        }
+        n_strains
+    }
+}
@@ -0,0 +1,295 @@
+use hashlink::LinkedHashMap;
+use rust_htslib::bcf::{Format, Header, Read, Reader, Record, Writer};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::path::Path;
+
+use crate::utils::errors::BirdToolError;
+
+/// One of `scatter_count` deterministic shards of the activity profile chunks of a genome.
+/// Chunks are numbered across the contigs of the genome in tid order and dealt out to the
+/// shards in turn, so every shard receives a similar amount of sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScatterShard {
+    pub scatter_count: usize,
+    pub shard_index: usize,
+}
+
+impl ScatterShard {
+    const SHARD_SEPARATOR: &'static str = ".shard_";
+
+    pub fn new(scatter_count: usize, shard_index: usize) -> Result<Self, BirdToolError> {
+        if scatter_count == 0 || shard_index >= scatter_count {
+            return Err(BirdToolError::ConfigError(format!(
+                "--shard-index must be less than --scatter, found shard {} of {}",
+                shard_index, scatter_count
+            )));
+        }
+        Ok(Self {
+            scatter_count,
+            shard_index,
+        })
+    }
+
+    /// The shard requested on the command line, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Result<Option<Self>, BirdToolError> {
+        let scatter_count = match args.try_get_one::<usize>("scatter").ok().flatten() {
+            Some(scatter_count) => *scatter_count,
+            None => return Ok(None),
+        };
+        let shard_index = args
+            .try_get_one::<usize>("shard-index")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(0);
+        Self::new(scatter_count, shard_index).map(Some)
+    }
+
+    /// Fails on a --shard-index outside of --scatter before any genome is run
+    pub fn check_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        Self::from_args(args).map(|_| ())
+    }
+
+    /// Whether the chunk with the given genome wide ordinal belongs to this shard
+    pub fn contains(&self, chunk_ordinal: usize) -> bool {
+        chunk_ordinal % self.scatter_count == self.shard_index
+    }
+
+    /// The ordinal of the first chunk of each contig. Contigs shorter than `min_contig_length`
+    /// have no chunks
+    pub fn chunk_offsets(
+        target_lens: &[(usize, u64)],
+        chunk_size: usize,
+        min_contig_length: u64,
+    ) -> HashMap<usize, usize> {
+        let mut target_lens = target_lens.to_vec();
+        target_lens.sort_unstable();
+
+        let mut offsets = HashMap::with_capacity(target_lens.len());
+        let mut n_chunks = 0;
+        for (tid, target_len) in target_lens {
+            offsets.insert(tid, n_chunks);
+            if target_len >= min_contig_length {
+                n_chunks += (target_len as usize + chunk_size - 1) / chunk_size;
+            }
+        }
+        offsets
+    }
+
+    /// The file stem used for the partial outputs of this shard
+    pub fn file_stem(&self, genome: &str) -> String {
+        format!(
+            "{}{}{}_of_{}",
+            genome,
+            Self::SHARD_SEPARATOR,
+            self.shard_index,
+            self.scatter_count
+        )
+    }
+
+    /// Splits a shard file stem into the genome and the shard it was produced by
+    pub fn parse_file_stem(file_stem: &str) -> Option<(String, Self)> {
+        let (genome, shard) = file_stem.rsplit_once(Self::SHARD_SEPARATOR)?;
+        let (shard_index, scatter_count) = shard.split_once("_of_")?;
+        let shard = Self::new(scatter_count.parse().ok()?, shard_index.parse().ok()?).ok()?;
+        Some((genome.to_string(), shard))
+    }
+}
+
+/// Merges the partial VCF files written by each shard of `lorikeet call --scatter`
+pub struct ShardGatherer;
+
+impl ShardGatherer {
+    /// Extensions of the shard VCF files, which the gathered VCF file keeps
+    const VCF_EXTENSIONS: [&'static str; 2] = [".vcf.gz", ".vcf"];
+
+    /// Finds the shard VCF files in each genome directory below `output_dir`, grouped by the
+    /// VCF file they are to be gathered into
+    pub fn find_shards(
+        output_dir: &str,
+    ) -> Result<LinkedHashMap<String, Vec<(ScatterShard, String)>>, BirdToolError> {
+        let mut shard_paths = Vec::new();
+        for extension in Self::VCF_EXTENSIONS {
+            let pattern = format!(
+                "{}/*/*{}*{}",
+                output_dir,
+                ScatterShard::SHARD_SEPARATOR,
+                extension
+            );
+            shard_paths.extend(
+                glob::glob(&pattern)
+                    .map_err(|e| {
+                        BirdToolError::IOError(format!("Invalid output directory: {}", e))
+                    })?
+                    .filter_map(|path| path.ok()),
+            );
+        }
+        shard_paths.sort();
+
+        let mut shards: LinkedHashMap<String, Vec<(ScatterShard, String)>> = LinkedHashMap::new();
+        for path in shard_paths {
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(file_name) => file_name,
+                None => continue,
+            };
+            let (file_stem, extension) = match Self::VCF_EXTENSIONS.iter().find_map(|extension| {
+                file_name
+                    .strip_suffix(extension)
+                    .map(|file_stem| (file_stem, extension))
+            }) {
+                Some(split) => split,
+                None => continue,
+            };
+            if let Some((genome, shard)) = ScatterShard::parse_file_stem(file_stem) {
+                let gathered_path = path
+                    .with_file_name(format!("{}{}", genome, extension))
+                    .to_string_lossy()
+                    .to_string();
+                shards
+                    .entry(gathered_path)
+                    .or_insert_with(Vec::new)
+                    .push((shard, path.to_string_lossy().to_string()));
+            }
+        }
+
+        Ok(shards)
+    }
+
+    /// Checks that exactly one file was found for every shard of the scatter
+    pub fn check_complete(
+        gathered_path: &str,
+        shards: &[(ScatterShard, String)],
+    ) -> Result<(), BirdToolError> {
+        let scatter_count = shards[0].0.scatter_count;
+        let mut found = vec![false; scatter_count];
+        for (shard, path) in shards {
+            if shard.scatter_count != scatter_count || found[shard.shard_index] {
+                return Err(BirdToolError::IOError(format!(
+                    "Shard {} does not match the other shards of {}",
+                    path, gathered_path
+                )));
+            }
+            found[shard.shard_index] = true;
+        }
+
+        match found.iter().position(|found| !found) {
+            Some(missing) => Err(BirdToolError::IOError(format!(
+                "Shard {} of {} is missing for {}",
+                missing, scatter_count, gathered_path
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the records of every shard, sorted by position, to `output_path`. Each shard is
+    /// sorted by position, so the shards are merged a record at a time rather than read into
+    /// memory. The header is taken from the first shard and the output is compressed when it
+    /// ends with `.gz`. Returns the number of records written
+    pub fn gather(shard_paths: &[String], output_path: &str) -> Result<usize, BirdToolError> {
+        let mut readers = shard_paths
+            .iter()
+            .map(|shard_path| {
+                Reader::from_path(shard_path).map_err(|e| Self::read_error(shard_path, e))
+            })
+            .collect::<Result<Vec<Reader>, BirdToolError>>()?;
+        let header = Header::from_template(readers[0].header());
+        let mut bcf_writer = Writer::from_path(
+            output_path,
+            &header,
+            !output_path.ends_with(".gz"),
+            Format::Vcf,
+        )
+        .map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create VCF output {}: {}",
+                output_path, e
+            ))
+        })?;
+
+        // the next record of each shard, ordered by position and then by shard
+        let mut next_records = Vec::with_capacity(readers.len());
+        let mut positions = BinaryHeap::with_capacity(readers.len());
+        for (shard, reader) in readers.iter_mut().enumerate() {
+            let record = Self::next_record(reader, &shard_paths[shard])?;
+            if let Some(record) = &record {
+                positions.push(Reverse((record.rid(), record.pos(), shard)));
+            }
+            next_records.push(record);
+        }
+
+        let mut n_records = 0;
+        while let Some(Reverse((_, _, shard))) = positions.pop() {
+            if let Some(mut record) = next_records[shard].take() {
+                bcf_writer.translate(&mut record);
+                bcf_writer.write(&record).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to write to {}: {}", output_path, e))
+                })?;
+                n_records += 1;
+            }
+
+            let record = Self::next_record(&mut readers[shard], &shard_paths[shard])?;
+            if let Some(record) = &record {
+                positions.push(Reverse((record.rid(), record.pos(), shard)));
+            }
+            next_records[shard] = record;
+        }
+
+        Ok(n_records)
+    }
+
+    /// The next record of a shard, or None once every record has been read
+    fn next_record(reader: &mut Reader, shard_path: &str) -> Result<Option<Record>, BirdToolError> {
+        let mut record = reader.empty_record();
+        match reader.read(&mut record) {
+            Some(Ok(())) => Ok(Some(record)),
+            Some(Err(e)) => Err(Self::read_error(shard_path, e)),
+            None => Ok(None),
+        }
+    }
+
+    fn read_error(path: &str, e: rust_htslib::errors::Error) -> BirdToolError {
+        BirdToolError::IOError(format!("Unable to read VCF file {}: {}", path, e))
+    }
+
+    /// Gathers every set of shards found below `output_dir`, optionally removing the shard
+    /// files once they have been merged
+    pub fn run(output_dir: &str, remove_shards: bool) -> Result<(), BirdToolError> {
+        let shards = Self::find_shards(output_dir)?;
+        if shards.is_empty() {
+            return Err(BirdToolError::IOError(format!(
+                "No shard VCF files found in {}",
+                output_dir
+            )));
+        }
+
+        for (gathered_path, mut genome_shards) in shards {
+            Self::check_complete(&gathered_path, &genome_shards)?;
+            genome_shards.sort_by_key(|(shard, _)| shard.shard_index);
+            let shard_paths = genome_shards
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect::<Vec<String>>();
+
+            let n_records = Self::gather(&shard_paths, &gathered_path)?;
+            info!(
+                "Gathered {} records from {} shards into {}",
+                n_records,
+                shard_paths.len(),
+                gathered_path
+            );
+
+            if remove_shards {
+                for shard_path in shard_paths {
+                    if Path::new(&shard_path).exists() {
+                        std::fs::remove_file(&shard_path).map_err(|e| {
+                            BirdToolError::IOError(format!("Unable to remove {}: {}", shard_path, e))
+                        })?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use crate::external_command_checker::{check_for_cutesv, check_for_sniffles, check_for_svim};
+use crate::utils::errors::BirdToolError;
+
+/// The program used to call structural variants from long reads, chosen with --sv-caller. Records
+/// of every caller are normalised to the SVTYPE and SVLEN lorikeet writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StructuralVariantCaller {
+    Svim,
+    Sniffles2,
+    CuteSV,
+}
+
+impl StructuralVariantCaller {
+    pub fn from_name(name: &str) -> Result<Self, BirdToolError> {
+        match name.to_ascii_lowercase().as_str() {
+            "svim" => Ok(Self::Svim),
+            "sniffles2" | "sniffles" => Ok(Self::Sniffles2),
+            "cutesv" => Ok(Self::CuteSV),
+            _ => Err(BirdToolError::DebugError(format!(
+                "Unknown structural variant caller {}. Options are svim, sniffles2 and cutesv",
+                name
+            ))),
+        }
+    }
+
+    /// The caller given by --sv-caller, svim if it is not set
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        match args.try_get_one::<String>("sv-caller").ok().flatten() {
+            Some(name) => match Self::from_name(name) {
+                Ok(caller) => caller,
+                Err(BirdToolError::DebugError(message)) => panic!("{}", message),
+                Err(_) => Self::Svim,
+            },
+            None => Self::Svim,
+        }
+    }
+
+    /// The name of the caller as given to --sv-caller
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Svim => "svim",
+            Self::Sniffles2 => "sniffles2",
+            Self::CuteSV => "cutesv",
+        }
+    }
+
+    /// The executable of the caller, as searched for on the PATH
+    pub fn executable(&self) -> &'static str {
+        match self {
+            Self::Svim => "svim",
+            Self::Sniffles2 => "sniffles",
+            Self::CuteSV => "cuteSV",
+        }
+    }
+
+    /// Panics if the caller is not installed
+    pub fn check(&self) {
+        match self {
+            Self::Svim => check_for_svim(),
+            Self::Sniffles2 => check_for_sniffles(),
+            Self::CuteSV => check_for_cutesv(),
+        }
+    }
+
+    /// The working directory of the caller for the long read sample at `idx`
+    pub fn working_directory(&self, output_prefix: &str, idx: usize) -> String {
+        format!("{}/{}_{}", output_prefix, self.name(), idx)
+    }
+
+    /// The command calling structural variants from `bam` against `reference` that writes
+    /// `<working_directory>/variants.vcf`
+    pub fn command(
+        &self,
+        bam: &str,
+        reference: &str,
+        working_directory: &str,
+        min_mapq: u8,
+        threads: usize,
+    ) -> String {
+        match self {
+            Self::Svim => format!(
+                "svim alignment --skip_genotyping --min_mapq {} --sequence_alleles {} {} {}",
+                min_mapq, working_directory, bam, reference
+            ),
+            Self::Sniffles2 => format!(
+                "mkdir -p {}; sniffles --input {} --reference {} --vcf {}/variants.vcf \
+                --mapq {} --threads {} --allow-overwrite",
+                working_directory, bam, reference, working_directory, min_mapq, threads
+            ),
+            Self::CuteSV => format!(
+                "mkdir -p {}/work; cuteSV --min_mapq {} --threads {} \
+                {} {} {}/variants.vcf {}/work",
+                working_directory,
+                min_mapq,
+                threads,
+                bam,
+                reference,
+                working_directory,
+                working_directory
+            ),
+        }
+    }
+
+    /// The SVTYPE lorikeet uses for the SVTYPE written by any caller. Subtypes such as DUP:TANDEM
+    /// and DUP_INT are reduced to their type and translocations are reported as breakends
+    pub fn normalize_sv_type(sv_type: &str) -> String {
+        let sv_type = sv_type.trim().to_ascii_uppercase();
+        let base_type = sv_type
+            .split(|c| c == ':' || c == '_')
+            .next()
+            .unwrap_or(&sv_type);
+        match base_type {
+            "TRA" | "TRANS" | "TRANSLOCATION" => "BND".to_string(),
+            "DELETION" => "DEL".to_string(),
+            "INSERTION" => "INS".to_string(),
+            "DUPLICATION" => "DUP".to_string(),
+            "INVERSION" => "INV".to_string(),
+            base_type => base_type.to_string(),
+        }
+    }
+
+    /// The SVLEN lorikeet uses for a length written by any caller: negative for deletions and
+    /// positive for every other type
+    pub fn normalize_sv_length(sv_type: &str, length: i32) -> i32 {
+        if sv_type == "DEL" {
+            -length.abs()
+        } else {
+            length.abs()
+        }
+    }
+}
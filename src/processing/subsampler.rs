@@ -0,0 +1,79 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+use crate::utils::errors::BirdToolError;
+use crate::utils::run_rng::RunRng;
+
+/// Random subset of a genome to call, given by --subsample-fraction or --subsample-regions. Draws
+/// depend only on the run seed and the region, so the subset does not change with the thread count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Subsampler {
+    pub fraction: Option<f64>,
+    pub windows: Option<usize>,
+}
+
+impl Subsampler {
+    pub fn new(fraction: Option<f64>, windows: Option<usize>) -> Result<Self, BirdToolError> {
+        if let Some(fraction) = fraction {
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                return Err(BirdToolError::DebugError(format!(
+                    "--subsample-fraction must be greater than 0 and at most 1, found {}",
+                    fraction
+                )));
+            }
+        }
+        if windows == Some(0) {
+            return Err(BirdToolError::DebugError(
+                "--subsample-regions must be greater than 0".to_string(),
+            ));
+        }
+        Ok(Self { fraction, windows })
+    }
+
+    /// The subsampling requested on the command line, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let fraction = args
+            .try_get_one::<f64>("subsample-fraction")
+            .ok()
+            .flatten()
+            .copied();
+        let windows = args
+            .try_get_one::<usize>("subsample-regions")
+            .ok()
+            .flatten()
+            .copied();
+        if fraction.is_none() && windows.is_none() {
+            return None;
+        }
+        match Self::new(fraction, windows) {
+            Ok(subsampler) => Some(subsampler),
+            Err(BirdToolError::DebugError(message)) => panic!("{}", message),
+            Err(_) => None,
+        }
+    }
+
+    /// The ordinals of the activity profile windows of a genome to profile, out of `n_windows`.
+    /// Every window is profiled unless --subsample-regions is set
+    pub fn selected_windows(&self, n_windows: usize, ref_idx: usize) -> Option<HashSet<usize>> {
+        let windows = self.windows?;
+        if windows >= n_windows {
+            return None;
+        }
+        let mut ordinals = (0..n_windows).collect::<Vec<usize>>();
+        let mut rng = RunRng::for_component("subsample_windows", ref_idx as u64);
+        ordinals.shuffle(&mut rng);
+        Some(ordinals.into_iter().take(windows).collect())
+    }
+
+    /// Whether the active region starting at `start` of contig `tid` is assembled
+    pub fn keeps_region(&self, tid: usize, start: usize) -> bool {
+        match self.fraction {
+            Some(fraction) if fraction < 1.0 => {
+                let key = ((tid as u64) << 32) ^ start as u64;
+                RunRng::for_component("subsample_regions", key).gen::<f64>() < fraction
+            }
+            _ => true,
+        }
+    }
+}
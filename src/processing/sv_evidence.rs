@@ -0,0 +1,853 @@
+use rayon::prelude::*;
+use rust_htslib::bam::{self, Read};
+use rust_htslib::bcf::{Format, Header, Writer};
+
+use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
+use crate::bam_parsing::split_alignment::SplitAlignmentChain;
+use crate::reads::insert_size_distribution::InsertSizeDistribution;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::utils::errors::BirdToolError;
+
+/// The kinds of structural variant that short read pairs and read depth can reveal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SvEvidenceType {
+    Deletion,
+    Duplication,
+}
+
+impl SvEvidenceType {
+    pub fn to_key(&self) -> &'static str {
+        match self {
+            Self::Deletion => "DEL",
+            Self::Duplication => "DUP",
+        }
+    }
+
+    pub fn symbolic_allele(&self) -> &'static [u8] {
+        match self {
+            Self::Deletion => b"<DEL>",
+            Self::Duplication => b"<DUP>",
+        }
+    }
+}
+
+/// A read pair whose orientation or insert size is inconsistent with the reference. The
+/// interval is the part of the reference between the reads for a deletion, and the part
+/// spanned by both reads for a tandem duplication
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscordantPair {
+    pub tid: u32,
+    pub sample_index: usize,
+    pub sv_type: SvEvidenceType,
+    pub start: u64,
+    pub end: u64,
+    pub insert_size: i64,
+}
+
+/// A read whose split alignments, followed along the read, skip over part of the reference
+/// (a deletion) or jump back to align to part of it again (a tandem duplication). The interval is
+/// the deleted or duplicated part of the reference, with breakpoints at base resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitRead {
+    pub tid: u32,
+    pub sample_index: usize,
+    pub sv_type: SvEvidenceType,
+    pub start: u64,
+    pub end: u64,
+}
+
+/// A potential deletion or duplication, with the evidence supporting it in each sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvCandidate {
+    pub tid: u32,
+    pub sv_type: SvEvidenceType,
+    // 0-based, end exclusive
+    pub start: u64,
+    pub end: u64,
+    pub discordant_pairs: Vec<usize>,
+    pub split_reads: Vec<usize>,
+    pub depth_ratios: Vec<f64>,
+    pub depth_change: bool,
+}
+
+impl SvCandidate {
+    pub fn evidence(&self) -> Vec<&'static str> {
+        let mut evidence = Vec::with_capacity(3);
+        if self.discordant_pairs.iter().any(|count| *count > 0) {
+            evidence.push("PE");
+        }
+        if self.split_reads.iter().any(|count| *count > 0) {
+            evidence.push("SR");
+        }
+        if self.depth_change {
+            evidence.push("RD");
+        }
+        evidence
+    }
+
+    fn reciprocal_overlap(&self, tid: u32, start: u64, end: u64) -> f64 {
+        if self.tid != tid || self.end <= start || end <= self.start {
+            return 0.0;
+        }
+        let overlap = (self.end.min(end) - self.start.max(start)) as f64;
+        let longest = (self.end - self.start).max(end - start) as f64;
+        overlap / longest
+    }
+}
+
+/// Read depth of a contig in fixed size windows
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthProfile {
+    pub window_size: u64,
+    // aligned bases per window, divided by the window size once complete
+    pub depths: Vec<f64>,
+}
+
+impl DepthProfile {
+    pub fn new(contig_length: u64, window_size: u64) -> Self {
+        Self {
+            window_size,
+            depths: vec![0.0; ((contig_length + window_size - 1) / window_size) as usize],
+        }
+    }
+
+    /// Adds the aligned bases of a read covering `start..end`
+    pub fn add_alignment(&mut self, start: u64, end: u64) {
+        let mut position = start;
+        while position < end {
+            let window = (position / self.window_size) as usize;
+            if window >= self.depths.len() {
+                break;
+            }
+            let window_end = ((window as u64 + 1) * self.window_size).min(end);
+            self.depths[window] += (window_end - position) as f64 / self.window_size as f64;
+            position = window_end;
+        }
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.depths
+            .iter_mut()
+            .zip(other.depths.iter())
+            .for_each(|(depth, other_depth)| *depth += other_depth);
+    }
+
+    /// Mean depth of the windows overlapping `start..end`
+    pub fn mean_depth(&self, start: u64, end: u64) -> f64 {
+        let first = (start / self.window_size) as usize;
+        let last = (((end.max(start + 1)) + self.window_size - 1) / self.window_size) as usize;
+        let last = last.min(self.depths.len());
+        if first >= last {
+            return 0.0;
+        }
+        self.depths[first..last].iter().sum::<f64>() / (last - first) as f64
+    }
+
+    /// Depth within `start..end` divided by the depth of flanks of the same length, up to
+    /// `max_flank` bases, on either side
+    pub fn depth_ratio(&self, start: u64, end: u64, max_flank: u64) -> f64 {
+        let flank = (end - start).min(max_flank).max(self.window_size);
+        let contig_end = self.depths.len() as u64 * self.window_size;
+        let left = self.mean_depth(start.saturating_sub(flank), start);
+        let right = self.mean_depth(end, (end + flank).min(contig_end));
+        let flanks = if start < flank {
+            right
+        } else if end + flank > contig_end {
+            left
+        } else {
+            (left + right) / 2.0
+        };
+        if flanks <= f64::EPSILON {
+            return 0.0;
+        }
+        self.mean_depth(start, end) / flanks
+    }
+
+    /// Segments between a change point where depth falls by at least `min_fold` and the next
+    /// change point where it rises again are potential deletions, and the reverse potential
+    /// duplications. Each change point compares the mean depth of `flank_windows` windows
+    /// either side and must be the strongest change within that distance.
+    pub fn change_point_segments(
+        &self,
+        min_fold: f64,
+        flank_windows: usize,
+        min_depth: f64,
+    ) -> Vec<(u64, u64, SvEvidenceType)> {
+        let n_windows = self.depths.len();
+        if n_windows < 2 * flank_windows + 1 || flank_windows == 0 {
+            return Vec::new();
+        }
+
+        let mean = |from: usize, to: usize| self.depths[from..to].iter().sum::<f64>() / (to - from) as f64;
+        let log_ratios = (0..n_windows)
+            .map(|boundary| {
+                if boundary < flank_windows || boundary + flank_windows > n_windows {
+                    return 0.0;
+                }
+                let left = mean(boundary - flank_windows, boundary);
+                let right = mean(boundary, boundary + flank_windows);
+                if left.max(right) < min_depth {
+                    return 0.0;
+                }
+                ((right + 1.0) / (left + 1.0)).ln()
+            })
+            .collect::<Vec<f64>>();
+
+        let threshold = min_fold.ln();
+        let change_points = (0..n_windows)
+            .filter(|boundary| {
+                let strength = log_ratios[*boundary].abs();
+                strength >= threshold
+                    && (boundary.saturating_sub(flank_windows)
+                        ..(boundary + flank_windows + 1).min(n_windows))
+                        .all(|other| {
+                            let other_strength = log_ratios[other].abs();
+                            other_strength < strength
+                                || (other_strength == strength && other >= *boundary)
+                        })
+            })
+            .collect::<Vec<usize>>();
+
+        change_points
+            .windows(2)
+            .filter_map(|pair| {
+                let (first, second) = (pair[0], pair[1]);
+                let start = first as u64 * self.window_size;
+                let end = second as u64 * self.window_size;
+                if log_ratios[first] < 0.0 && log_ratios[second] > 0.0 {
+                    Some((start, end, SvEvidenceType::Deletion))
+                } else if log_ratios[first] > 0.0 && log_ratios[second] < 0.0 {
+                    Some((start, end, SvEvidenceType::Duplication))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Collects deletion and duplication evidence from short read pairs. Discordant pairs are
+/// reads whose insert size is too long for the fragment length distribution of their sample
+/// (deletions) or whose orientation is reversed (tandem duplications). These are clustered
+/// across samples and combined with split reads, whose alignment chains are reconstructed from
+/// the SA tag of their primary alignment, and with change points in the read depth, so that runs
+/// without long reads still produce basic structural variant calls.
+pub struct SvEvidenceCollector {
+    pub min_mapq: u8,
+    pub min_discordant_pairs: usize,
+    pub insert_size_stdevs: f64,
+    pub window_size: u64,
+    pub min_depth_fold: f64,
+}
+
+impl SvEvidenceCollector {
+    pub const DEFAULT_WINDOW_SIZE: u64 = 100;
+    const FLANK_WINDOWS: usize = 5;
+    const MIN_CHANGE_POINT_DEPTH: f64 = 5.0;
+    const MAX_FLANK: u64 = 10_000;
+    // a depth segment and a discordant pair cluster describe the same event when they overlap
+    // by at least this fraction of the longer of the two
+    const MIN_RECIPROCAL_OVERLAP: f64 = 0.5;
+    // split reads are only evidence of events at least this long, as shorter ones are aligned
+    // as indels
+    const MIN_SPLIT_READ_SV_LENGTH: u64 = 50;
+    // split reads of the same event have breakpoints within this many bases of each other
+    const MAX_SPLIT_READ_DISTANCE: u64 = 20;
+
+    pub fn new(min_mapq: u8, min_discordant_pairs: usize, insert_size_stdevs: f64) -> Self {
+        Self {
+            min_mapq,
+            min_discordant_pairs,
+            insert_size_stdevs,
+            window_size: Self::DEFAULT_WINDOW_SIZE,
+            min_depth_fold: 1.8,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            *args.get_one::<u8>("min-mapq").unwrap(),
+            *args.get_one::<usize>("min-discordant-pairs").unwrap(),
+            *args.get_one::<f64>("discordant-insert-size-stdevs").unwrap(),
+        )
+    }
+
+    /// Classifies a read pair, seen from its leftmost read, as evidence for a structural variant
+    pub fn classify_pair(
+        &self,
+        record: &bam::Record,
+        sample_index: usize,
+        distribution: &InsertSizeDistribution,
+    ) -> Option<DiscordantPair> {
+        if !record.is_paired()
+            || record.is_unmapped()
+            || record.is_mate_unmapped()
+            || record.is_secondary()
+            || record.is_supplementary()
+            || record.is_duplicate()
+            || record.mapq() < self.min_mapq
+            || record.tid() != record.mtid()
+            || record.pos() > record.mpos()
+            || (record.pos() == record.mpos() && !record.is_first_in_template())
+        {
+            return None;
+        }
+
+        let start = record.pos() as u64;
+        let end = record.cigar().end_pos() as u64;
+        let mate_start = record.mpos() as u64;
+        let insert_size = record.insert_size().abs();
+        match (record.is_reverse(), record.is_mate_reverse()) {
+            (false, true) if distribution.is_too_long(insert_size, self.insert_size_stdevs) => {
+                Some(DiscordantPair {
+                    tid: record.tid() as u32,
+                    sample_index,
+                    sv_type: SvEvidenceType::Deletion,
+                    start: end.min(mate_start),
+                    end: mate_start.max(end),
+                    insert_size,
+                })
+            }
+            (true, false) if mate_start > start => Some(DiscordantPair {
+                tid: record.tid() as u32,
+                sample_index,
+                sv_type: SvEvidenceType::Duplication,
+                start,
+                end: start + insert_size.max((end - start) as i64) as u64,
+                insert_size,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The deletions and duplications implied by the alignment chain of a read. Only segments
+    /// that are consecutive along the read, on the same contig and strand and with at least the
+    /// minimum MAPQ are compared, so inversions and translocations are not reported
+    pub fn classify_split_read(
+        &self,
+        chain: &SplitAlignmentChain,
+        sample_index: usize,
+    ) -> Vec<SplitRead> {
+        chain
+            .adjacent_segments()
+            .filter_map(|(a, b)| {
+                let tid = match (a.tid, b.tid) {
+                    (Some(a_tid), Some(b_tid)) if a_tid == b_tid => a_tid,
+                    _ => return None,
+                };
+                if a.reverse != b.reverse || a.mapq < self.min_mapq || b.mapq < self.min_mapq {
+                    return None;
+                }
+
+                // the segments in the order they align along the forward strand of the reference
+                let (first, second) = if a.reverse { (b, a) } else { (a, b) };
+                let (sv_type, start, end) = if second.ref_start >= first.ref_end {
+                    (SvEvidenceType::Deletion, first.ref_end, second.ref_start)
+                } else {
+                    (SvEvidenceType::Duplication, second.ref_start, first.ref_end)
+                };
+                if ((end - start) as u64) < Self::MIN_SPLIT_READ_SV_LENGTH {
+                    return None;
+                }
+
+                Some(SplitRead {
+                    tid,
+                    sample_index,
+                    sv_type,
+                    start: start as u64,
+                    end: end as u64,
+                })
+            })
+            .collect()
+    }
+
+    /// Groups discordant pairs of the same type whose intervals start and end within
+    /// `max_distance` of each other. Clusters supported by fewer than `min_discordant_pairs`
+    /// pairs are dropped
+    pub fn cluster_pairs(
+        &self,
+        mut pairs: Vec<DiscordantPair>,
+        max_distance: u64,
+        median_insert_size: f64,
+        n_samples: usize,
+    ) -> Vec<SvCandidate> {
+        pairs.sort_unstable_by_key(|pair| (pair.tid, pair.sv_type, pair.start, pair.end));
+
+        let mut clusters: Vec<Vec<DiscordantPair>> = Vec::new();
+        for pair in pairs {
+            match clusters.last_mut() {
+                Some(cluster)
+                    if cluster[0].tid == pair.tid
+                        && cluster[0].sv_type == pair.sv_type
+                        && pair.start <= cluster[0].start + max_distance
+                        && Self::mean_end(cluster).abs_diff(pair.end) <= max_distance =>
+                {
+                    cluster.push(pair)
+                }
+                _ => clusters.push(vec![pair]),
+            }
+        }
+
+        clusters
+            .into_iter()
+            .filter(|cluster| cluster.len() >= self.min_discordant_pairs)
+            .map(|cluster| {
+                let mut discordant_pairs = vec![0; n_samples];
+                cluster
+                    .iter()
+                    .for_each(|pair| discordant_pairs[pair.sample_index] += 1);
+
+                let (start, end) = match cluster[0].sv_type {
+                    SvEvidenceType::Deletion => {
+                        // the deletion lies between the reads and is as long as the extra
+                        // insert size
+                        let inner_start = cluster.iter().map(|pair| pair.start).max().unwrap();
+                        let inner_end = cluster.iter().map(|pair| pair.end).min().unwrap();
+                        let mean_insert = cluster.iter().map(|pair| pair.insert_size).sum::<i64>()
+                            as f64
+                            / cluster.len() as f64;
+                        let length = (mean_insert - median_insert_size).max(1.0) as u64;
+                        (inner_start, (inner_start + length).min(inner_end.max(inner_start + 1)))
+                    }
+                    SvEvidenceType::Duplication => (
+                        cluster.iter().map(|pair| pair.start).min().unwrap(),
+                        cluster.iter().map(|pair| pair.end).max().unwrap(),
+                    ),
+                };
+
+                SvCandidate {
+                    tid: cluster[0].tid,
+                    sv_type: cluster[0].sv_type,
+                    start,
+                    end,
+                    discordant_pairs,
+                    split_reads: vec![0; n_samples],
+                    depth_ratios: vec![0.0; n_samples],
+                    depth_change: false,
+                }
+            })
+            .collect()
+    }
+
+    /// Groups split reads of the same type whose breakpoints lie within a few bases of each
+    /// other. Candidates that a group agrees with take its breakpoints, which are more precise
+    /// than those of discordant pairs, and groups matching no candidate become candidates when
+    /// they contain at least `min_discordant_pairs` reads
+    pub fn combine_with_split_reads(
+        &self,
+        mut candidates: Vec<SvCandidate>,
+        mut split_reads: Vec<SplitRead>,
+        n_samples: usize,
+    ) -> Vec<SvCandidate> {
+        split_reads.sort_unstable_by_key(|read| (read.tid, read.sv_type, read.start, read.end));
+
+        let mut clusters: Vec<Vec<SplitRead>> = Vec::new();
+        for read in split_reads {
+            match clusters.last_mut() {
+                Some(cluster)
+                    if cluster[0].tid == read.tid
+                        && cluster[0].sv_type == read.sv_type
+                        && read.start <= cluster[0].start + Self::MAX_SPLIT_READ_DISTANCE
+                        && cluster[0].end.abs_diff(read.end) <= Self::MAX_SPLIT_READ_DISTANCE =>
+                {
+                    cluster.push(read)
+                }
+                _ => clusters.push(vec![read]),
+            }
+        }
+
+        for cluster in clusters {
+            let mut counts = vec![0; n_samples];
+            cluster.iter().for_each(|read| counts[read.sample_index] += 1);
+            // the breakpoints of most reads
+            let median = |mut positions: Vec<u64>| {
+                positions.sort_unstable();
+                positions[positions.len() / 2]
+            };
+            let start = median(cluster.iter().map(|read| read.start).collect());
+            let end = median(cluster.iter().map(|read| read.end).collect());
+            let (tid, sv_type) = (cluster[0].tid, cluster[0].sv_type);
+
+            match candidates.iter_mut().find(|candidate| {
+                candidate.sv_type == sv_type
+                    && candidate.reciprocal_overlap(tid, start, end) >= Self::MIN_RECIPROCAL_OVERLAP
+            }) {
+                Some(candidate) => {
+                    candidate
+                        .split_reads
+                        .iter_mut()
+                        .zip(counts.iter())
+                        .for_each(|(total, count)| *total += count);
+                    candidate.start = start;
+                    candidate.end = end;
+                }
+                None if cluster.len() >= self.min_discordant_pairs => {
+                    candidates.push(SvCandidate {
+                        tid,
+                        sv_type,
+                        start,
+                        end,
+                        discordant_pairs: vec![0; n_samples],
+                        split_reads: counts,
+                        depth_ratios: vec![0.0; n_samples],
+                        depth_change: false,
+                    })
+                }
+                None => {}
+            }
+        }
+
+        candidates.sort_unstable_by_key(|candidate| (candidate.tid, candidate.start, candidate.end));
+        candidates
+    }
+
+    fn mean_end(cluster: &[DiscordantPair]) -> u64 {
+        cluster.iter().map(|pair| pair.end).sum::<u64>() / cluster.len() as u64
+    }
+
+    /// Adds the depth change point segments to the candidates, marking discordant pair clusters
+    /// that they agree with, and records the depth ratio of each candidate in each sample
+    pub fn combine_with_depth(
+        &self,
+        mut candidates: Vec<SvCandidate>,
+        tid: u32,
+        sample_depths: &[DepthProfile],
+    ) -> Vec<SvCandidate> {
+        if sample_depths.is_empty() {
+            return candidates;
+        }
+
+        let mut pooled = DepthProfile {
+            window_size: sample_depths[0].window_size,
+            depths: vec![0.0; sample_depths[0].depths.len()],
+        };
+        sample_depths.iter().for_each(|depths| pooled.add(depths));
+
+        for (start, end, sv_type) in pooled.change_point_segments(
+            self.min_depth_fold,
+            Self::FLANK_WINDOWS,
+            Self::MIN_CHANGE_POINT_DEPTH,
+        ) {
+            match candidates.iter_mut().find(|candidate| {
+                candidate.sv_type == sv_type
+                    && candidate.reciprocal_overlap(tid, start, end) >= Self::MIN_RECIPROCAL_OVERLAP
+            }) {
+                Some(candidate) => candidate.depth_change = true,
+                None => candidates.push(SvCandidate {
+                    tid,
+                    sv_type,
+                    start,
+                    end,
+                    discordant_pairs: vec![0; sample_depths.len()],
+                    split_reads: vec![0; sample_depths.len()],
+                    depth_ratios: vec![0.0; sample_depths.len()],
+                    depth_change: true,
+                }),
+            }
+        }
+
+        for candidate in candidates.iter_mut().filter(|candidate| candidate.tid == tid) {
+            candidate.depth_ratios = sample_depths
+                .iter()
+                .map(|depths| depths.depth_ratio(candidate.start, candidate.end, Self::MAX_FLANK))
+                .collect();
+        }
+
+        candidates.sort_unstable_by_key(|candidate| (candidate.tid, candidate.start, candidate.end));
+        candidates
+    }
+
+    /// Collects the discordant pairs, split reads and read depth of one sample on the given
+    /// contigs. Split reads are found from the SA tags of primary alignments, so each read is
+    /// counted once however many supplementary alignments it has
+    pub fn collect_sample(
+        &self,
+        bam_path: &str,
+        sample_index: usize,
+        contigs: &[(u32, u64)],
+    ) -> Result<
+        (
+            Option<InsertSizeDistribution>,
+            Vec<DiscordantPair>,
+            Vec<SplitRead>,
+            Vec<DepthProfile>,
+        ),
+        BirdToolError,
+    > {
+        let distribution = InsertSizeDistribution::from_bam(bam_path, contigs, self.min_mapq)?;
+        let mut reader = bam::IndexedReader::from_path(bam_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read BAM file {}: {}", bam_path, e))
+        })?;
+
+        let header = reader.header().clone();
+        let tid_of = |contig_name: &[u8]| header.tid(contig_name);
+
+        let mut pairs = Vec::new();
+        let mut split_reads = Vec::new();
+        let mut depths = Vec::with_capacity(contigs.len());
+        let mut record = bam::Record::new();
+        for (tid, length) in contigs.iter() {
+            let mut depth = DepthProfile::new(*length, self.window_size);
+            reader
+                .fetch((*tid as i32, 0, *length as i64))
+                .map_err(|e| BirdToolError::IOError(format!("Unable to fetch tid {}: {}", tid, e)))?;
+            while let Some(result) = reader.read(&mut record) {
+                result.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read record of {}: {}", bam_path, e))
+                })?;
+                if record.is_unmapped()
+                    || record.is_secondary()
+                    || record.is_supplementary()
+                    || record.is_duplicate()
+                    || record.mapq() < self.min_mapq
+                {
+                    continue;
+                }
+                depth.add_alignment(record.pos() as u64, record.cigar().end_pos() as u64);
+                if let Some(distribution) = &distribution {
+                    if let Some(pair) = self.classify_pair(&record, sample_index, distribution) {
+                        pairs.push(pair);
+                    }
+                }
+                if SplitAlignmentChain::sa_tag(&record).is_some() {
+                    let chain = SplitAlignmentChain::from_record(&record, tid_of);
+                    split_reads.extend(self.classify_split_read(&chain, sample_index));
+                }
+            }
+            depths.push(depth);
+        }
+
+        Ok((distribution, pairs, split_reads, depths))
+    }
+
+    /// Collects the evidence of every short read sample for the contigs of `genome`, named
+    /// `genome<separator>contig` in the BAM files, and writes the candidate events to
+    /// `<output_prefix>/short_read_structural_variants.vcf`
+    pub fn run(
+        &self,
+        short_read_bams: &[String],
+        sample_names: &[&str],
+        genome: &str,
+        separator: &GenomeSeparator,
+        output_prefix: &str,
+    ) -> Result<usize, BirdToolError> {
+        if short_read_bams.is_empty() {
+            return Ok(0);
+        }
+
+        let header_view = bam::IndexedReader::from_path(&short_read_bams[0])
+            .map_err(|e| {
+                BirdToolError::IOError(format!(
+                    "Unable to read BAM file {}: {}",
+                    &short_read_bams[0], e
+                ))
+            })?
+            .header()
+            .clone();
+        let contigs = (0..header_view.target_count())
+            .filter(|tid| {
+                separator.genome(&String::from_utf8_lossy(header_view.tid2name(*tid))) == genome
+            })
+            .filter_map(|tid| header_view.target_len(tid).map(|length| (tid, length)))
+            .collect::<Vec<(u32, u64)>>();
+
+        let samples = short_read_bams
+            .par_iter()
+            .enumerate()
+            .map(|(sample_index, bam_path)| self.collect_sample(bam_path, sample_index, &contigs))
+            .collect::<Result<Vec<_>, BirdToolError>>()?;
+
+        let distributions = samples
+            .iter()
+            .filter_map(|(distribution, _, _, _)| *distribution)
+            .collect::<Vec<InsertSizeDistribution>>();
+        for (sample_index, (distribution, _, _, _)) in samples.iter().enumerate() {
+            match distribution {
+                Some(distribution) => debug!(
+                    "{}: sample {} insert size median {} stdev {:.1} from {} pairs",
+                    genome, sample_index, distribution.median, distribution.stdev, distribution.n_pairs
+                ),
+                None => debug!(
+                    "{}: sample {} has no proper pairs, skipping discordant pairs",
+                    genome, sample_index
+                ),
+            }
+        }
+
+        // clusters are formed across samples, so use the widest distribution to join them
+        let max_distance = distributions
+            .iter()
+            .map(|distribution| distribution.upper_bound(self.insert_size_stdevs))
+            .fold(0.0, f64::max) as u64;
+        let median_insert_size = if distributions.is_empty() {
+            0.0
+        } else {
+            distributions.iter().map(|distribution| distribution.median).sum::<f64>()
+                / distributions.len() as f64
+        };
+
+        let n_samples = short_read_bams.len();
+        let mut all_pairs = Vec::new();
+        let mut all_split_reads = Vec::new();
+        let mut sample_depths: Vec<Vec<DepthProfile>> = vec![Vec::new(); contigs.len()];
+        for (_, pairs, split_reads, depths) in samples {
+            all_pairs.extend(pairs);
+            all_split_reads.extend(split_reads);
+            for (contig_index, depth) in depths.into_iter().enumerate() {
+                sample_depths[contig_index].push(depth);
+            }
+        }
+
+        let mut candidates =
+            self.cluster_pairs(all_pairs, max_distance, median_insert_size, n_samples);
+        candidates = self.combine_with_split_reads(candidates, all_split_reads, n_samples);
+        for ((tid, _), depths) in contigs.iter().zip(sample_depths.iter()) {
+            candidates = self.combine_with_depth(candidates, *tid, depths);
+        }
+
+        Self::write_vcf(
+            &format!("{}/short_read_structural_variants.vcf", output_prefix),
+            &header_view,
+            &contigs,
+            sample_names,
+            &candidates,
+        )?;
+
+        Ok(candidates.len())
+    }
+
+    fn write_vcf(
+        output_path: &str,
+        bam_header: &bam::HeaderView,
+        contigs: &[(u32, u64)],
+        sample_names: &[&str],
+        candidates: &[SvCandidate],
+    ) -> Result<(), BirdToolError> {
+        let mut header = Header::new();
+        header.push_record(format!("##source=lorikeet-v{}", env!("CARGO_PKG_VERSION")).as_bytes());
+        for (tid, length) in contigs.iter() {
+            header.push_record(
+                format!(
+                    "##contig=<ID={},length={}>",
+                    String::from_utf8_lossy(bam_header.tid2name(*tid)),
+                    length
+                )
+                .as_bytes(),
+            );
+        }
+        header.push_record(b"##ALT=<ID=DEL,Description=\"Deletion\">");
+        header.push_record(b"##ALT=<ID=DUP,Description=\"Tandem duplication\">");
+        for annotation in VariantAnnotationEngine::structural_variant_annotations() {
+            header.push_record(annotation.generate_header_record().as_bytes());
+        }
+        header.push_record(
+            b"##INFO=<ID=EVIDENCE,Number=.,Type=String,Description=\"Evidence supporting the event: PE discordant read pairs, SR split reads, RD read depth change points\">",
+        );
+        header.push_record(
+            b"##INFO=<ID=PE,Number=1,Type=Integer,Description=\"Discordant read pairs supporting the event across all samples\">",
+        );
+        header.push_record(
+            b"##INFO=<ID=SR,Number=1,Type=Integer,Description=\"Split reads supporting the event across all samples\">",
+        );
+        header.push_record(
+            b"##FORMAT=<ID=PE,Number=1,Type=Integer,Description=\"Discordant read pairs supporting the event\">",
+        );
+        header.push_record(
+            b"##FORMAT=<ID=SR,Number=1,Type=Integer,Description=\"Split reads supporting the event\">",
+        );
+        header.push_record(
+            b"##FORMAT=<ID=DR,Number=1,Type=Float,Description=\"Mean read depth within the event divided by the mean read depth of its flanks\">",
+        );
+        for (sample_idx, sample_name) in sample_names.iter().enumerate() {
+            header.push_record(
+                format!("##sample=<ID={}, name={}>", sample_idx + 1, sample_name).as_bytes(),
+            );
+            header.push_sample(format!("{}", sample_idx + 1).as_bytes());
+        }
+
+        let write_error = |e: rust_htslib::errors::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", output_path, e))
+        };
+        let mut writer = Writer::from_path(output_path, &header, true, Format::Vcf)
+            .map_err(write_error)?;
+
+        for candidate in candidates.iter() {
+            let contig_name = bam_header.tid2name(candidate.tid);
+            let rid = writer.header().name2rid(contig_name).map_err(write_error)?;
+            let length = (candidate.end - candidate.start) as i32;
+            let mut record = writer.empty_record();
+            record.set_rid(Some(rid));
+            record.set_pos(candidate.start as i64);
+            record
+                .set_alleles(&[b"N" as &[u8], candidate.sv_type.symbolic_allele()])
+                .map_err(write_error)?;
+            record
+                .push_info_integer(b"END", &[candidate.end as i32])
+                .map_err(write_error)?;
+            record
+                .push_info_integer(
+                    b"SVLEN",
+                    &[match candidate.sv_type {
+                        SvEvidenceType::Deletion => -length,
+                        SvEvidenceType::Duplication => length,
+                    }],
+                )
+                .map_err(write_error)?;
+            record
+                .push_info_string(b"SVTYPE", &[candidate.sv_type.to_key().as_bytes()])
+                .map_err(write_error)?;
+            record
+                .push_info_string(
+                    b"EVIDENCE",
+                    &candidate
+                        .evidence()
+                        .iter()
+                        .map(|evidence| evidence.as_bytes())
+                        .collect::<Vec<&[u8]>>(),
+                )
+                .map_err(write_error)?;
+            record
+                .push_info_integer(
+                    b"PE",
+                    &[candidate.discordant_pairs.iter().sum::<usize>() as i32],
+                )
+                .map_err(write_error)?;
+            record
+                .push_info_integer(b"SR", &[candidate.split_reads.iter().sum::<usize>() as i32])
+                .map_err(write_error)?;
+            record
+                .push_format_integer(
+                    b"PE",
+                    &candidate
+                        .discordant_pairs
+                        .iter()
+                        .map(|count| *count as i32)
+                        .collect::<Vec<i32>>(),
+                )
+                .map_err(write_error)?;
+            record
+                .push_format_integer(
+                    b"SR",
+                    &candidate
+                        .split_reads
+                        .iter()
+                        .map(|count| *count as i32)
+                        .collect::<Vec<i32>>(),
+                )
+                .map_err(write_error)?;
+            record
+                .push_format_float(
+                    b"DR",
+                    &candidate
+                        .depth_ratios
+                        .iter()
+                        .map(|ratio| *ratio as f32)
+                        .collect::<Vec<f32>>(),
+                )
+                .map_err(write_error)?;
+            writer.write(&record).map_err(write_error)?;
+        }
+
+        Ok(())
+    }
+}
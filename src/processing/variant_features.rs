@@ -0,0 +1,451 @@
+use rust_htslib::bcf::record::Numeric;
+use rust_htslib::bcf::{Format, Header, Read, Record, Writer};
+use std::collections::HashSet;
+use std::io::{BufWriter, Write};
+
+use crate::annotator::variant_annotation::VariantAnnotations;
+use crate::utils::errors::BirdToolError;
+use crate::utils::partial_output::PartialOutput;
+use crate::utils::vcf_input::VcfInput;
+
+/// The features of one ALT allele of a VCF record, read back from the fields lorikeet writes, as a
+/// row of the --write-features table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantFeatures {
+    pub contig: String,
+    // 1-based
+    pub position: usize,
+    pub ref_allele: String,
+    pub alt_allele: String,
+    pub filter: String,
+    // in the order of FEATURE_NAMES
+    pub values: Vec<Option<f64>>,
+}
+
+impl VariantFeatures {
+    pub const FEATURE_NAMES: [&'static str; 25] = [
+        "QUAL",
+        "QD",
+        "DP",
+        "MQ_REF",
+        "MQ_ALT",
+        "BQ_REF",
+        "BQ_ALT",
+        "SC_REF_FWD",
+        "SC_REF_REV",
+        "SC_ALT_FWD",
+        "SC_ALT_REV",
+        "SOR",
+        "LENGTH_DIFF",
+        "HRUN",
+        "RPA_REF",
+        "RPA_ALT",
+        "ENTROPY",
+        "DUST",
+        "CNR",
+        "SDP",
+        "SAMPLE_DEPTH_MEAN",
+        "SAMPLE_DEPTH_MIN",
+        "SAMPLE_DEPTH_MAX",
+        "ALT_SAMPLES",
+        "ALT_FRACTION_MAX",
+    ];
+
+    /// Index of a feature in FEATURE_NAMES
+    pub fn feature_index(name: &str) -> Option<usize> {
+        Self::FEATURE_NAMES
+            .iter()
+            .position(|feature| feature.eq_ignore_ascii_case(name))
+    }
+
+    pub fn value(&self, name: &str) -> Option<f64> {
+        Self::feature_index(name).and_then(|index| self.values[index])
+    }
+
+    /// The features of every ALT allele of a record
+    pub fn from_record(record: &Record) -> Vec<Self> {
+        let header = record.header();
+        let contig = record
+            .rid()
+            .and_then(|rid| header.rid2name(rid).ok())
+            .map(|name| String::from_utf8_lossy(name).to_string())
+            .unwrap_or_else(|| ".".to_string());
+        let filter = {
+            let filters = record
+                .filters()
+                .map(|id| String::from_utf8_lossy(&header.id_to_name(id)).to_string())
+                .collect::<Vec<String>>();
+            if filters.is_empty() {
+                ".".to_string()
+            } else {
+                filters.join(";")
+            }
+        };
+        let alleles = record
+            .alleles()
+            .into_iter()
+            .map(|allele| String::from_utf8_lossy(allele).to_string())
+            .collect::<Vec<String>>();
+
+        let qual = record.qual();
+        let qual = if qual.is_missing() || qual.is_nan() {
+            None
+        } else {
+            Some(qual as f64)
+        };
+        let first = |key: &str| Self::info_values(record, key).first().copied().flatten();
+        let mapping_quality =
+            Self::info_values(record, VariantAnnotations::MappingQuality.to_key());
+        let base_quality = Self::info_values(record, VariantAnnotations::BaseQuality.to_key());
+        let strand_counts = Self::info_values(record, VariantAnnotations::StrandCounts.to_key());
+        let repeats = Self::info_values(record, VariantAnnotations::RepeatsPerAllele.to_key());
+        let allele_depths = match record.format(b"AD").integer() {
+            Ok(depths) => depths
+                .iter()
+                .map(|depths| {
+                    depths
+                        .iter()
+                        .map(|depth| if *depth < 0 { 0.0 } else { *depth as f64 })
+                        .collect::<Vec<f64>>()
+                })
+                .collect::<Vec<Vec<f64>>>(),
+            Err(_) => Vec::new(),
+        };
+        let sample_depths = allele_depths
+            .iter()
+            .map(|depths| depths.iter().sum::<f64>())
+            .collect::<Vec<f64>>();
+
+        (1..alleles.len())
+            .map(|alt_index| {
+                let at =
+                    |values: &[Option<f64>], index: usize| values.get(index).copied().flatten();
+                let sc = |index: usize| at(&strand_counts, index);
+                let strand_odds_ratio =
+                    match (sc(0), sc(1), sc(alt_index * 2), sc(alt_index * 2 + 1)) {
+                        (Some(ref_fwd), Some(ref_rev), Some(alt_fwd), Some(alt_rev)) => {
+                            Some(Self::strand_odds_ratio(ref_fwd, ref_rev, alt_fwd, alt_rev))
+                        }
+                        _ => None,
+                    };
+                let alt_depths = allele_depths
+                    .iter()
+                    .map(|depths| depths.get(alt_index).copied().unwrap_or(0.0))
+                    .collect::<Vec<f64>>();
+                let alt_fractions = alt_depths
+                    .iter()
+                    .zip(sample_depths.iter())
+                    .filter(|(_, depth)| **depth > 0.0)
+                    .map(|(alt_depth, depth)| alt_depth / depth);
+
+                let values = vec![
+                    qual,
+                    first(VariantAnnotations::QualByDepth.to_key()),
+                    first(VariantAnnotations::Depth.to_key()),
+                    at(&mapping_quality, 0),
+                    at(&mapping_quality, alt_index),
+                    at(&base_quality, 0),
+                    at(&base_quality, alt_index),
+                    sc(0),
+                    sc(1),
+                    sc(alt_index * 2),
+                    sc(alt_index * 2 + 1),
+                    strand_odds_ratio,
+                    Some(alleles[alt_index].len() as f64 - alleles[0].len() as f64),
+                    first(VariantAnnotations::HomopolymerRun.to_key()),
+                    at(&repeats, 0),
+                    at(&repeats, alt_index),
+                    first(VariantAnnotations::SequenceEntropy.to_key()),
+                    first(VariantAnnotations::DustScore.to_key()),
+                    first(VariantAnnotations::CopyNumberRatio.to_key()),
+                    first(VariantAnnotations::StrainDiscriminatingPower.to_key()),
+                    if sample_depths.is_empty() {
+                        None
+                    } else {
+                        Some(sample_depths.iter().sum::<f64>() / sample_depths.len() as f64)
+                    },
+                    sample_depths.iter().copied().reduce(f64::min),
+                    sample_depths.iter().copied().reduce(f64::max),
+                    if allele_depths.is_empty() {
+                        None
+                    } else {
+                        Some(alt_depths.iter().filter(|depth| **depth > 0.0).count() as f64)
+                    },
+                    alt_fractions.reduce(f64::max),
+                ];
+
+                Self {
+                    contig: contig.clone(),
+                    position: record.pos() as usize + 1,
+                    ref_allele: alleles[0].clone(),
+                    alt_allele: alleles[alt_index].clone(),
+                    filter: filter.clone(),
+                    values,
+                }
+            })
+            .collect()
+    }
+
+    /// The values of an INFO field, which may be written as integers or floats. Missing values
+    /// are None, and fields absent from the header or the record give no values
+    fn info_values(record: &Record, key: &str) -> Vec<Option<f64>> {
+        if let Ok(Some(values)) = record.info(key.as_bytes()).float() {
+            return values
+                .iter()
+                .map(|value| {
+                    if value.is_missing() || value.is_nan() {
+                        None
+                    } else {
+                        Some(*value as f64)
+                    }
+                })
+                .collect();
+        }
+        if let Ok(Some(values)) = record.info(key.as_bytes()).integer() {
+            return values
+                .iter()
+                .map(|value| {
+                    if value.is_missing() {
+                        None
+                    } else {
+                        Some(*value as f64)
+                    }
+                })
+                .collect();
+        }
+        Vec::new()
+    }
+
+    /// The symmetric strand odds ratio of the reads supporting the reference and ALT alleles on
+    /// either strand, as calculated by GATK. Higher values indicate stronger strand bias
+    pub fn strand_odds_ratio(ref_fwd: f64, ref_rev: f64, alt_fwd: f64, alt_rev: f64) -> f64 {
+        // pseudocounts avoid dividing by zero
+        let (ref_fwd, ref_rev, alt_fwd, alt_rev) =
+            (ref_fwd + 1.0, ref_rev + 1.0, alt_fwd + 1.0, alt_rev + 1.0);
+        let ratio = (ref_fwd * alt_rev) / (ref_rev * alt_fwd);
+        let symmetrical_ratio = ratio + 1.0 / ratio;
+        let ref_ratio = ref_fwd.min(ref_rev) / ref_fwd.max(ref_rev);
+        let alt_ratio = alt_fwd.min(alt_rev) / alt_fwd.max(alt_rev);
+
+        symmetrical_ratio.ln() + ref_ratio.ln() - alt_ratio.ln()
+    }
+
+    pub fn header_line() -> String {
+        let mut columns = vec!["contig", "position", "ref", "alt", "filter"];
+        columns.extend(Self::FEATURE_NAMES.iter());
+        columns.join("\t")
+    }
+
+    pub fn to_line(&self) -> String {
+        let mut columns = vec![
+            self.contig.clone(),
+            self.position.to_string(),
+            self.ref_allele.clone(),
+            self.alt_allele.clone(),
+            self.filter.clone(),
+        ];
+        columns.extend(self.values.iter().map(|value| match value {
+            Some(value) => Self::format_value(*value),
+            None => "NA".to_string(),
+        }));
+        columns.join("\t")
+    }
+
+    /// Values are written with at most four decimal places, without trailing zeros
+    fn format_value(value: f64) -> String {
+        let formatted = format!("{:.4}", value);
+        let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+        match formatted {
+            "-0" | "" => "0".to_string(),
+            formatted => formatted.to_string(),
+        }
+    }
+
+    /// Writes the features of every ALT allele of every record of a VCF file to a TSV file.
+    /// Returns the number of rows written
+    pub fn write_table(vcf_path: &str, output_path: &str) -> Result<usize, BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let output = PartialOutput::new(output_path);
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", output_path, e))
+        };
+        let mut writer = BufWriter::new(output.create()?);
+        writeln!(writer, "{}", Self::header_line()).map_err(write_error)?;
+
+        let mut rows = 0;
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            for features in Self::from_record(&record) {
+                writeln!(writer, "{}", features.to_line()).map_err(write_error)?;
+                rows += 1;
+            }
+        }
+        writer.flush().map_err(write_error)?;
+        drop(writer);
+        output.commit()?;
+
+        Ok(rows)
+    }
+}
+
+/// Logistic regression model over `VariantFeatures` for `lorikeet apply-model`, read from lines of
+/// feature names and weights. Records whose best ALT allele scores below the threshold get the
+/// LowModelScore filter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureModel {
+    pub intercept: f64,
+    // index into VariantFeatures::FEATURE_NAMES and weight of each feature
+    pub weights: Vec<(usize, f64)>,
+}
+
+impl FeatureModel {
+    pub const SCORE_KEY: &'static str = "MLSCORE";
+    pub const FILTER_KEY: &'static str = "LowModelScore";
+
+    pub fn parse(text: &str) -> Result<Self, BirdToolError> {
+        let mut intercept = None;
+        let mut weights = Vec::new();
+        let mut seen = HashSet::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields = line.split_whitespace().collect::<Vec<&str>>();
+            if fields.len() != 2 {
+                return Err(BirdToolError::ConfigError(format!(
+                    "Expected a feature name and weight in model line: {}",
+                    line
+                )));
+            }
+            let weight = fields[1].parse::<f64>().map_err(|_| {
+                BirdToolError::ConfigError(format!("Invalid weight in model line: {}", line))
+            })?;
+            if !seen.insert(fields[0].to_ascii_uppercase()) {
+                return Err(BirdToolError::ConfigError(format!(
+                    "Feature {} is given more than once in the model",
+                    fields[0]
+                )));
+            }
+
+            if fields[0].eq_ignore_ascii_case("intercept") {
+                intercept = Some(weight);
+            } else {
+                match VariantFeatures::feature_index(fields[0]) {
+                    Some(index) => weights.push((index, weight)),
+                    None => {
+                        return Err(BirdToolError::ConfigError(format!(
+                            "Unknown feature {} in model, expected intercept or one of {}",
+                            fields[0],
+                            VariantFeatures::FEATURE_NAMES.join(", ")
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            intercept: intercept.unwrap_or(0.0),
+            weights,
+        })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, BirdToolError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to read model {}: {}", path, e)))?;
+        Self::parse(&text)
+    }
+
+    /// The score of a single ALT allele, between 0 and 1
+    pub fn score(&self, features: &VariantFeatures) -> f64 {
+        let linear = self
+            .weights
+            .iter()
+            .filter_map(|(index, weight)| features.values[*index].map(|value| value * weight))
+            .sum::<f64>()
+            + self.intercept;
+        1.0 / (1.0 + (-linear).exp())
+    }
+
+    /// The score of a record, the highest score of its ALT alleles
+    pub fn score_record(&self, record: &Record) -> Option<f64> {
+        VariantFeatures::from_record(record)
+            .iter()
+            .map(|features| self.score(features))
+            .reduce(f64::max)
+    }
+
+    /// Writes the records of `vcf_path` to `output_path` with their score added as an INFO
+    /// field, filtering those that score below `threshold`. Returns the number of records
+    /// written and the number of those filtered
+    pub fn apply(
+        &self,
+        vcf_path: &str,
+        output_path: &str,
+        threshold: f64,
+    ) -> Result<(usize, usize), BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let mut header = Header::from_template(reader.header());
+        header.push_record(
+            format!(
+                "##INFO=<ID={},Number=1,Type=Float,\
+                Description=\"Score given to the record by lorikeet apply-model\">",
+                Self::SCORE_KEY
+            )
+            .as_bytes(),
+        );
+        header.push_record(
+            format!(
+                "##FILTER=<ID={},Description=\"Score given by lorikeet apply-model is below {}\">",
+                Self::FILTER_KEY,
+                threshold
+            )
+            .as_bytes(),
+        );
+
+        let output = PartialOutput::new(output_path);
+        let write_error = |e: rust_htslib::errors::Error| {
+            BirdToolError::IOError(format!("Unable to write to {}: {}", output_path, e))
+        };
+        let mut bcf_writer = Writer::from_path(output.partial_path(), &header, true, Format::Vcf)
+            .map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create VCF output {}: {}",
+                output_path, e
+            ))
+        })?;
+
+        let (mut written, mut filtered) = (0, 0);
+        for record in reader.records() {
+            let mut record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            let score = self.score_record(&record);
+            bcf_writer.translate(&mut record);
+            if let Some(score) = score {
+                record
+                    .push_info_float(Self::SCORE_KEY.as_bytes(), &[score as f32])
+                    .map_err(write_error)?;
+                if score < threshold {
+                    if record.has_filter("PASS".as_bytes()) {
+                        record
+                            .remove_filter("PASS".as_bytes(), false)
+                            .map_err(write_error)?;
+                    }
+                    record
+                        .push_filter(Self::FILTER_KEY.as_bytes())
+                        .map_err(write_error)?;
+                    filtered += 1;
+                }
+            }
+            bcf_writer.write(&record).map_err(write_error)?;
+            written += 1;
+        }
+        drop(bcf_writer);
+        output.commit()?;
+
+        Ok((written, filtered))
+    }
+}
@@ -0,0 +1,650 @@
+use bio::io::fasta::IndexedReader as FastaReader;
+use rust_htslib::bam::{self, record::Cigar, Read as BamRead};
+use rust_htslib::bcf::Read as BcfRead;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::reference::reference_reader_utils::ReferenceReaderUtils;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// The reference positions shown by a pileup, 0-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PileupWindow {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl PileupWindow {
+    pub fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
+    }
+
+    pub fn contains(&self, position: usize) -> bool {
+        position >= self.start && position < self.start + self.len
+    }
+}
+
+/// The bases of a read, haplotype or the reference aligned to each position of a window
+#[derive(Debug, Clone, PartialEq)]
+pub struct PileupRow {
+    pub label: String,
+    // the aligned base of each reference position, DELETED for deleted positions and None for
+    // positions the row does not cover
+    pub bases: Vec<Option<u8>>,
+    // the bases inserted after each reference position
+    pub insertions: Vec<Vec<u8>>,
+    pub reverse: bool,
+}
+
+impl PileupRow {
+    pub const DELETED: u8 = b'*';
+
+    /// The row of the reference sequence of the window
+    pub fn from_reference(label: &str, reference: &[u8]) -> Self {
+        Self {
+            label: label.to_string(),
+            bases: reference
+                .iter()
+                .map(|base| Some(base.to_ascii_uppercase()))
+                .collect(),
+            insertions: vec![Vec::new(); reference.len()],
+            reverse: false,
+        }
+    }
+
+    /// The row of an alignment of `sequence` starting at reference position `alignment_start`
+    pub fn from_alignment(
+        label: &str,
+        window: PileupWindow,
+        alignment_start: usize,
+        cigar: &[Cigar],
+        sequence: &[u8],
+        reverse: bool,
+    ) -> Self {
+        let mut bases = vec![None; window.len];
+        let mut insertions = vec![Vec::new(); window.len];
+        let mut ref_pos = alignment_start;
+        let mut read_pos = 0;
+        for element in cigar {
+            match element {
+                Cigar::Match(length) | Cigar::Equal(length) | Cigar::Diff(length) => {
+                    for _ in 0..*length {
+                        if window.contains(ref_pos) {
+                            bases[ref_pos - window.start] =
+                                sequence.get(read_pos).map(|base| base.to_ascii_uppercase());
+                        }
+                        ref_pos += 1;
+                        read_pos += 1;
+                    }
+                }
+                Cigar::Ins(length) => {
+                    // insertions are placed after the preceding reference base
+                    if ref_pos > 0 && window.contains(ref_pos - 1) {
+                        let end = (read_pos + *length as usize).min(sequence.len());
+                        insertions[ref_pos - 1 - window.start].extend(
+                            sequence[read_pos.min(end)..end]
+                                .iter()
+                                .map(|base| base.to_ascii_uppercase()),
+                        );
+                    }
+                    read_pos += *length as usize;
+                }
+                Cigar::Del(length) => {
+                    for _ in 0..*length {
+                        if window.contains(ref_pos) {
+                            bases[ref_pos - window.start] = Some(Self::DELETED);
+                        }
+                        ref_pos += 1;
+                    }
+                }
+                Cigar::RefSkip(length) => ref_pos += *length as usize,
+                Cigar::SoftClip(length) => read_pos += *length as usize,
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+
+        Self {
+            label: label.to_string(),
+            bases,
+            insertions,
+            reverse,
+        }
+    }
+
+    /// The row of the haplotype carrying `alt_allele` in place of the `ref_length` reference bases
+    /// starting at `allele_start`. `reference` holds the reference bases from `reference_start`
+    /// and must cover the window and the allele. Alleles are aligned from their first base, as in
+    /// VCF records where indels share a leading padding base
+    pub fn from_allele(
+        label: &str,
+        window: PileupWindow,
+        reference: &[u8],
+        reference_start: usize,
+        allele_start: usize,
+        ref_length: usize,
+        alt_allele: &[u8],
+    ) -> Self {
+        let offset = allele_start
+            .saturating_sub(reference_start)
+            .min(reference.len());
+        let suffix = (offset + ref_length).min(reference.len());
+        let shared = ref_length.min(alt_allele.len());
+
+        let mut sequence = reference[..offset].to_vec();
+        sequence.extend_from_slice(alt_allele);
+        sequence.extend_from_slice(&reference[suffix..]);
+
+        let mut cigar = vec![Cigar::Match((offset + shared) as u32)];
+        if alt_allele.len() > ref_length {
+            cigar.push(Cigar::Ins((alt_allele.len() - ref_length) as u32));
+        } else if alt_allele.len() < ref_length {
+            cigar.push(Cigar::Del((ref_length - alt_allele.len()) as u32));
+        }
+        cigar.push(Cigar::Match((reference.len() - suffix) as u32));
+        cigar.retain(|element| element.len() > 0);
+
+        Self::from_alignment(label, window, reference_start, &cigar, &sequence, false)
+    }
+
+    /// The bases of the row over the inclusive reference span `start`-`end`, including the bases
+    /// inserted after each position of the span. None if the row does not cover the whole span
+    pub fn allele_bases(&self, window: PileupWindow, start: usize, end: usize) -> Option<Vec<u8>> {
+        if !window.contains(start) || !window.contains(end) || end < start {
+            return None;
+        }
+        let mut bases = Vec::new();
+        for offset in (start - window.start)..=(end - window.start) {
+            match self.bases[offset] {
+                Some(Self::DELETED) => {}
+                Some(base) => bases.push(base),
+                None => return None,
+            }
+            bases.extend_from_slice(&self.insertions[offset]);
+        }
+        Some(bases)
+    }
+
+    /// The window offset of the first and last position covered by the row
+    fn covered_span(&self) -> Option<(usize, usize)> {
+        let first = self.bases.iter().position(|base| base.is_some())?;
+        let last = self.bases.iter().rposition(|base| base.is_some())?;
+        Some((first, last))
+    }
+}
+
+/// The allele a read supports at the inspected variant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AlleleSupport {
+    Allele(usize),
+    // the read spans the variant but matches none of its alleles
+    Other,
+    // the read does not cover every base of the variant
+    NotSpanning,
+}
+
+impl AlleleSupport {
+    /// Matches the bases of a read over the variant against each allele, ignoring case
+    pub fn assign(bases: Option<&[u8]>, alleles: &[Vec<u8>]) -> Self {
+        match bases {
+            Some(bases) => alleles
+                .iter()
+                .position(|allele| allele.eq_ignore_ascii_case(bases))
+                .map(Self::Allele)
+                .unwrap_or(Self::Other),
+            None => Self::NotSpanning,
+        }
+    }
+
+    pub fn to_label(&self) -> String {
+        match self {
+            Self::Allele(0) => "REF".to_string(),
+            Self::Allele(idx) => format!("ALT{}", idx),
+            Self::Other => "other".to_string(),
+            Self::NotSpanning => "not_spanning".to_string(),
+        }
+    }
+}
+
+/// Parses a region of the form contig:position, with a 1-based position. Contig names may
+/// contain colons, and the position may contain thousands separators
+pub fn parse_region(region: &str) -> Result<(String, usize), BirdToolError> {
+    let error = || {
+        BirdToolError::DebugError(format!(
+            "--region {} is not of the form contig:position",
+            region
+        ))
+    };
+    let (contig, position) = region.rsplit_once(':').ok_or_else(error)?;
+    let position = position
+        .replace(',', "")
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| error())?;
+    if contig.is_empty() || position == 0 {
+        return Err(error());
+    }
+    Ok((contig.to_string(), position - 1))
+}
+
+/// Renders rows as text columns aligned to the reference, the first row being the reference
+/// itself. Insertions are shown after the base they follow, with the other rows padded by '-'.
+/// Bases matching the reference are shown as '.' on the forward strand and ',' on the reverse
+/// strand, mismatches in upper case on the forward strand and lower case on the reverse strand,
+/// deletions as '*' and positions not covered by a row as spaces
+pub fn render(rows: &[PileupRow]) -> Vec<String> {
+    let (label_width, insertion_widths) = layout(rows);
+    let reference = match rows.first() {
+        Some(reference) => reference,
+        None => return Vec::new(),
+    };
+
+    let stranded = |base: u8, reverse: bool| {
+        if reverse {
+            base.to_ascii_lowercase() as char
+        } else {
+            base.to_ascii_uppercase() as char
+        }
+    };
+    rows.iter()
+        .enumerate()
+        .map(|(row_idx, row)| {
+            let mut line = format!("{:<width$}  ", row.label, width = label_width);
+            let covered = row.covered_span();
+            for (offset, base) in row.bases.iter().enumerate() {
+                line.push(match base {
+                    Some(base) if row_idx == 0 => *base as char,
+                    Some(PileupRow::DELETED) => PileupRow::DELETED as char,
+                    Some(base) if Some(*base) == reference.bases[offset] => {
+                        if row.reverse {
+                            ','
+                        } else {
+                            '.'
+                        }
+                    }
+                    Some(base) => stranded(*base, row.reverse),
+                    None => ' ',
+                });
+
+                let inserted = &row.insertions[offset];
+                let padding = match covered {
+                    Some((first, last)) if offset >= first && offset < last => '-',
+                    _ => ' ',
+                };
+                line.extend(inserted.iter().map(|base| stranded(*base, row.reverse)));
+                line.extend(
+                    std::iter::repeat(padding).take(insertion_widths[offset] - inserted.len()),
+                );
+            }
+            line.trim_end().to_string()
+        })
+        .collect()
+}
+
+/// A line marking the column of the window offset `offset` in rows rendered by `render`
+pub fn marker(rows: &[PileupRow], offset: usize) -> String {
+    let (label_width, insertion_widths) = layout(rows);
+    let column = label_width + 2 + offset + insertion_widths.iter().take(offset).sum::<usize>();
+    format!("{}^", " ".repeat(column))
+}
+
+/// The width of the row labels and of the insertions after each position of the window
+fn layout(rows: &[PileupRow]) -> (usize, Vec<usize>) {
+    let label_width = rows.iter().map(|row| row.label.len()).max().unwrap_or(0);
+    let window_len = rows.first().map(|row| row.bases.len()).unwrap_or(0);
+    let insertion_widths = (0..window_len)
+        .map(|offset| {
+            rows.iter()
+                .map(|row| row.insertions[offset].len())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    (label_width, insertion_widths)
+}
+
+/// Text pileup of the reads supporting each allele of a single variant, aligned to the reference
+/// window with per strand counts.
+#[derive(Debug, Clone)]
+pub struct VariantInspector {
+    reference_path: String,
+    bam_paths: Vec<String>,
+    vcf_path: Option<String>,
+    flank: usize,
+    max_reads: usize,
+    min_mapq: u8,
+}
+
+impl VariantInspector {
+    const MAX_READ_NAME: usize = 32;
+
+    pub fn new(
+        reference_path: &str,
+        bam_paths: Vec<String>,
+        vcf_path: Option<String>,
+        flank: usize,
+        max_reads: usize,
+        min_mapq: u8,
+    ) -> Self {
+        Self {
+            reference_path: reference_path.to_string(),
+            bam_paths,
+            vcf_path,
+            flank,
+            max_reads,
+            min_mapq,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            args.get_one::<String>("reference").unwrap(),
+            args.get_many::<String>("bam-files")
+                .unwrap()
+                .cloned()
+                .collect(),
+            args.try_get_one::<String>("vcf").ok().flatten().cloned(),
+            *args.get_one::<usize>("flank").unwrap(),
+            *args.get_one::<usize>("max-reads").unwrap(),
+            *args.get_one::<u8>("min-mapq").unwrap(),
+        )
+    }
+
+    /// The start and alleles of the VCF record overlapping `position` of `contig`, if any
+    fn vcf_alleles(
+        vcf_path: &str,
+        contig: &str,
+        position: usize,
+    ) -> Result<Option<(usize, Vec<Vec<u8>>)>, BirdToolError> {
+        let mut reader = VcfInput::open(vcf_path)?;
+        let rid = (0..reader.header().contig_count()).find(|rid| {
+            reader
+                .header()
+                .rid2name(*rid)
+                .map(|name| name == contig.as_bytes())
+                .unwrap_or(false)
+        });
+        let rid = match rid {
+            Some(rid) => rid,
+            None => return Ok(None),
+        };
+
+        let mut overlapping = None;
+        for record in reader.records() {
+            let record = record.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", vcf_path, e))
+            })?;
+            if record.rid() != Some(rid) {
+                continue;
+            }
+            let start = record.pos() as usize;
+            let alleles = record
+                .alleles()
+                .into_iter()
+                .map(|allele| allele.to_vec())
+                .collect::<Vec<Vec<u8>>>();
+            let end = start + alleles[0].len().max(1) - 1;
+            if start == position {
+                return Ok(Some((start, alleles)));
+            } else if start <= position && end >= position && overlapping.is_none() {
+                overlapping = Some((start, alleles));
+            }
+        }
+        Ok(overlapping)
+    }
+
+    /// The reads of a BAM file overlapping the window, skipping unmapped, secondary,
+    /// supplementary, duplicate and QC failed reads and those below the mapping quality threshold
+    fn read_rows(
+        &self,
+        bam_path: &str,
+        contig: &str,
+        window: PileupWindow,
+    ) -> Result<Vec<(usize, PileupRow)>, BirdToolError> {
+        let mut reader = bam::IndexedReader::from_path(bam_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read BAM file {}: {}", bam_path, e))
+        })?;
+        let tid = reader.header().tid(contig.as_bytes()).ok_or_else(|| {
+            BirdToolError::DebugError(format!("Contig {} is not in {}", contig, bam_path))
+        })?;
+        reader
+            .fetch((
+                tid as i32,
+                window.start as i64,
+                (window.start + window.len) as i64,
+            ))
+            .map_err(|e| BirdToolError::IOError(format!("Unable to fetch {}: {}", contig, e)))?;
+
+        let mut rows = Vec::new();
+        let mut record = bam::Record::new();
+        while let Some(result) = reader.read(&mut record) {
+            result.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read record of {}: {}", bam_path, e))
+            })?;
+            if record.is_unmapped()
+                || record.is_secondary()
+                || record.is_supplementary()
+                || record.is_duplicate()
+                || record.is_quality_check_failed()
+                || record.mapq() < self.min_mapq
+            {
+                continue;
+            }
+            let name = String::from_utf8_lossy(record.qname());
+            let name = name.chars().take(Self::MAX_READ_NAME).collect::<String>();
+            let row = PileupRow::from_alignment(
+                &name,
+                window,
+                record.pos() as usize,
+                &record.cigar().0,
+                &record.seq().as_bytes(),
+                record.is_reverse(),
+            );
+            rows.push((record.pos() as usize, row));
+        }
+        Ok(rows)
+    }
+
+    /// The pileup of the variant at the 0-based `position` of `contig`, as lines of text
+    pub fn inspect(&self, contig: &str, position: usize) -> Result<Vec<String>, BirdToolError> {
+        let mut fasta = match FastaReader::from_file(&self.reference_path) {
+            Ok(reader) => reader,
+            Err(_) => ReferenceReaderUtils::generate_faidx(&self.reference_path),
+        };
+        let contig_length = fasta
+            .index
+            .sequences()
+            .into_iter()
+            .find(|sequence| sequence.name == contig)
+            .map(|sequence| sequence.len as usize)
+            .ok_or_else(|| {
+                BirdToolError::DebugError(format!(
+                    "Contig {} is not in {}",
+                    contig, self.reference_path
+                ))
+            })?;
+        if position >= contig_length {
+            return Err(BirdToolError::DebugError(format!(
+                "Position {} is past the end of {} ({} bp)",
+                position + 1,
+                contig,
+                contig_length
+            )));
+        }
+
+        let variant = match &self.vcf_path {
+            Some(vcf_path) => Self::vcf_alleles(vcf_path, contig, position)?,
+            None => None,
+        };
+        let (allele_start, allele_end) = match &variant {
+            Some((start, alleles)) => (*start, start + alleles[0].len().max(1) - 1),
+            None => (position, position),
+        };
+        let window_start = allele_start.min(position).saturating_sub(self.flank);
+        let window_end = (allele_end.max(position) + self.flank).min(contig_length - 1);
+        let window = PileupWindow::new(window_start, window_end - window_start + 1);
+
+        let mut reference = Vec::new();
+        fasta
+            .fetch(contig, window_start as u64, window_end as u64 + 1)
+            .and_then(|_| fasta.read(&mut reference))
+            .map_err(|e| {
+                BirdToolError::IOError(format!("Unable to fetch {} from reference: {}", contig, e))
+            })?;
+        let reference_row = PileupRow::from_reference("reference", &reference);
+
+        let mut samples = Vec::with_capacity(self.bam_paths.len());
+        for bam_path in self.bam_paths.iter() {
+            let sample = Path::new(bam_path)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| bam_path.clone());
+            let reads = self.read_rows(bam_path, contig, window)?;
+            samples.push((sample, reads));
+        }
+
+        // without a VCF record, the alleles are the bases observed at the position, with the
+        // reference base first and the rest by the number of reads carrying them
+        let (source, alleles) = match variant {
+            Some((_, alleles)) => (
+                format!(
+                    "record at {}:{} of {}",
+                    contig,
+                    allele_start + 1,
+                    self.vcf_path.as_ref().unwrap()
+                ),
+                alleles,
+            ),
+            None => {
+                let mut observed: HashMap<Vec<u8>, usize> = HashMap::new();
+                for (_, reads) in samples.iter() {
+                    for (_, read) in reads {
+                        if let Some(bases) = read.allele_bases(window, position, position) {
+                            *observed.entry(bases).or_insert(0) += 1;
+                        }
+                    }
+                }
+                let ref_allele = reference_row.bases[position - window.start]
+                    .map(|base| vec![base])
+                    .unwrap_or_default();
+                observed.remove(&ref_allele);
+                let mut observed = observed.into_iter().collect::<Vec<(Vec<u8>, usize)>>();
+                observed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                let mut alleles = vec![ref_allele];
+                alleles.extend(observed.into_iter().map(|(allele, _)| allele));
+                ("bases observed in the reads".to_string(), alleles)
+            }
+        };
+        let display = |allele: &[u8]| {
+            if allele.is_empty() {
+                "*".to_string()
+            } else {
+                String::from_utf8_lossy(allele).to_string()
+            }
+        };
+
+        let mut lines = vec![format!(
+            "# lorikeet inspect {}:{} window {}:{}-{}",
+            contig,
+            position + 1,
+            contig,
+            window_start + 1,
+            window_end + 1
+        )];
+        lines.push(format!("# alleles from {}", source));
+        for (idx, allele) in alleles.iter().enumerate() {
+            lines.push(format!(
+                "# {}\t{}",
+                AlleleSupport::Allele(idx).to_label(),
+                display(allele)
+            ));
+        }
+
+        let mut rows = vec![reference_row];
+        for (idx, allele) in alleles.iter().enumerate() {
+            if allele.iter().any(|base| *base == b'<' || *base == b'*') {
+                continue;
+            }
+            rows.push(PileupRow::from_allele(
+                &format!("haplotype {}", AlleleSupport::Allele(idx).to_label()),
+                window,
+                &reference,
+                window_start,
+                allele_start,
+                allele_end - allele_start + 1,
+                allele,
+            ));
+        }
+
+        lines.push("#sample\tallele\treads\tforward\treverse".to_string());
+        // the index of the first row of each sample
+        let mut sample_starts = Vec::with_capacity(samples.len());
+        for (sample, reads) in samples.into_iter() {
+            let mut supported = reads
+                .into_iter()
+                .map(|(start, read)| {
+                    let bases = read.allele_bases(window, allele_start, allele_end);
+                    (
+                        AlleleSupport::assign(bases.as_deref(), &alleles),
+                        start,
+                        read,
+                    )
+                })
+                .collect::<Vec<(AlleleSupport, usize, PileupRow)>>();
+            supported.sort_by_key(|(support, start, _)| (*support, *start));
+
+            let mut counts: Vec<(AlleleSupport, usize, usize)> = Vec::new();
+            for (support, _, read) in supported.iter() {
+                match counts.iter_mut().find(|(counted, _, _)| counted == support) {
+                    Some((_, forward, reverse)) => {
+                        if read.reverse {
+                            *reverse += 1
+                        } else {
+                            *forward += 1
+                        }
+                    }
+                    None => counts.push((*support, !read.reverse as usize, read.reverse as usize)),
+                }
+            }
+            for (support, forward, reverse) in counts {
+                lines.push(format!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    sample,
+                    support.to_label(),
+                    forward + reverse,
+                    forward,
+                    reverse
+                ));
+            }
+
+            sample_starts.push((rows.len(), sample));
+            let mut shown: HashMap<AlleleSupport, usize> = HashMap::new();
+            for (support, _, mut read) in supported.into_iter() {
+                let count = shown.entry(support).or_insert(0);
+                if support == AlleleSupport::NotSpanning || *count >= self.max_reads {
+                    continue;
+                }
+                *count += 1;
+                read.label = format!("{} {}", support.to_label(), read.label);
+                rows.push(read);
+            }
+        }
+
+        lines.push(String::new());
+        let sample_headers = |idx: usize, lines: &mut Vec<String>| {
+            for (_, sample) in sample_starts.iter().filter(|(start, _)| *start == idx) {
+                lines.push(format!("## {}", sample));
+            }
+        };
+        for (idx, line) in render(&rows).into_iter().enumerate() {
+            sample_headers(idx, &mut lines);
+            lines.push(line);
+            if idx == 0 {
+                lines.push(marker(&rows, position - window.start));
+            }
+        }
+        sample_headers(rows.len(), &mut lines);
+
+        Ok(lines)
+    }
+}
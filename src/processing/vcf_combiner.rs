@@ -0,0 +1,429 @@
+use hashlink::LinkedHashMap;
+use rust_htslib::bcf::header::{HeaderRecord, HeaderView};
+use rust_htslib::bcf::{Format, Header, Read, Writer};
+use std::collections::HashMap;
+
+use crate::annotator::variant_annotation::{Annotation, AnnotationType, VariantAnnotations};
+use crate::annotator::variant_annotator_engine::VariantAnnotationEngine;
+use crate::genotype::genotype_builder::{Genotype, GenotypesContext};
+use crate::genotype::genotype_likelihood_calculators::GenotypeLikelihoodCalculators;
+use crate::genotype::genotype_prior_calculator::GenotypePriorCalculator;
+use crate::genotype::genotyping_engine::GenotypingEngine;
+use crate::model::byte_array_allele::ByteArrayAllele;
+use crate::model::variant_context::VariantContext;
+use crate::utils::errors::BirdToolError;
+use crate::utils::vcf_input::VcfInput;
+
+/// The samples, contigs, and variant contexts of a single VCF file to be combined
+pub struct CombineInput {
+    pub sample_names: Vec<String>,
+    /// Contig names and lengths, indexed by the tid of the contexts
+    pub contigs: Vec<(String, Option<u64>)>,
+    pub contexts: Vec<VariantContext>,
+}
+
+impl CombineInput {
+    /// Reads a VCF produced by lorikeet. The AD and PL format fields are required for the
+    /// samples to be jointly genotyped
+    pub fn from_vcf(vcf_path: &str) -> Result<Self, BirdToolError> {
+        let reader = VcfInput::open(vcf_path)?;
+        let header = reader.header();
+        if Self::is_gvcf(header) {
+            return Err(Self::gvcf_error(vcf_path));
+        }
+        let sample_names = Self::sample_names_from_header(header);
+        let contigs = Self::contigs_from_header(header);
+
+        let contexts = VariantContext::process_vcf_from_path(vcf_path, true);
+        if contexts.iter().any(|vc| vc.has_non_ref_allele()) {
+            return Err(Self::gvcf_error(vcf_path));
+        }
+
+        Ok(Self {
+            sample_names,
+            contigs,
+            contexts,
+        })
+    }
+
+    /// Whether the header describes the `<NON_REF>` allele of a gVCF
+    pub fn is_gvcf(header: &HeaderView) -> bool {
+        header.header_records().iter().any(|record| {
+            matches!(record, HeaderRecord::Structured { key, values }
+                if key == "ALT" && values.get("ID").map(|id| id.as_str()) == Some("NON_REF"))
+        })
+    }
+
+    /// The reference confidence blocks of gVCFs are not read, so samples without a record at a
+    /// site would be given flat likelihoods rather than the hom-ref likelihoods of their block
+    fn gvcf_error(vcf_path: &str) -> BirdToolError {
+        BirdToolError::ConfigError(format!(
+            "{} is a gVCF, which can not be combined. Combine the VCF files of lorikeet call or \
+            genotype runs instead",
+            vcf_path
+        ))
+    }
+
+    /// lorikeet names the sample columns by index and records the actual sample names in
+    /// `##sample=<ID=..., name=...>` lines. Falls back to the column names if these are absent
+    pub fn sample_names_from_header(header: &HeaderView) -> Vec<String> {
+        let mut names_by_id = HashMap::new();
+        for record in header.header_records() {
+            if let HeaderRecord::Structured { key, values } = record {
+                if key != "sample" {
+                    continue;
+                }
+                let mut id = None;
+                let mut name = None;
+                for (field, value) in values.iter() {
+                    match field.trim() {
+                        "ID" => id = Some(value.trim().to_string()),
+                        "name" => name = Some(value.trim().to_string()),
+                        _ => {}
+                    }
+                }
+                if let (Some(id), Some(name)) = (id, name) {
+                    names_by_id.insert(id, name);
+                }
+            }
+        }
+
+        header
+            .samples()
+            .into_iter()
+            .map(|sample| {
+                let id = String::from_utf8_lossy(sample).to_string();
+                names_by_id.get(&id).cloned().unwrap_or(id)
+            })
+            .collect()
+    }
+
+    /// Contig names and lengths, indexed by the rid of the records
+    pub fn contigs_from_header(header: &HeaderView) -> Vec<(String, Option<u64>)> {
+        let contig_lengths = Self::contig_lengths_from_header(header);
+        (0..header.contig_count())
+            .map(|rid| {
+                let name = String::from_utf8_lossy(header.rid2name(rid).unwrap()).to_string();
+                let length = contig_lengths.get(&name).copied();
+                (name, length)
+            })
+            .collect()
+    }
+
+    fn contig_lengths_from_header(header: &HeaderView) -> HashMap<String, u64> {
+        header
+            .header_records()
+            .into_iter()
+            .filter_map(|record| match record {
+                HeaderRecord::Contig { values, .. } => {
+                    let name = values.get("ID")?.clone();
+                    let length = values.get("length")?.parse::<u64>().ok()?;
+                    Some((name, length))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Combines the per-sample results of separate lorikeet runs against the same reference and
+/// jointly genotypes every sample with the `GenotypingEngine`, allowing a cohort to grow without
+/// reprocessing the BAM files of previous runs.
+///
+/// Sites are matched by contig name, position, and reference allele. When a run did not consider
+/// an allele seen in another run, genotypes containing that allele are given the least likely
+/// PL of the sample. Samples from runs without a record at a site are given flat likelihoods
+/// so that they do not contribute to the call and are written as missing. gVCF input is rejected
+/// by `CombineInput::from_vcf`, as the hom-ref likelihoods of its reference blocks are not used.
+pub struct VcfCombiner {}
+
+impl VcfCombiner {
+    /// Merges the contexts of each input into a single set of contexts containing the samples of
+    /// every input, in the order of the inputs. Returns the combined contig names, which the tids
+    /// of the returned contexts refer to, alongside the coordinate sorted contexts
+    pub fn merge_inputs(
+        inputs: Vec<CombineInput>,
+        default_ploidy: usize,
+    ) -> (Vec<(String, Option<u64>)>, Vec<VariantContext>) {
+        let mut contigs: Vec<(String, Option<u64>)> = Vec::new();
+        let mut contig_indices: HashMap<String, usize> = HashMap::new();
+        let mut sample_offsets = Vec::with_capacity(inputs.len());
+        let mut sample_counts = Vec::with_capacity(inputs.len());
+        let mut total_samples = 0;
+        let mut sites: LinkedHashMap<(usize, usize, Vec<u8>), Vec<(usize, VariantContext)>> =
+            LinkedHashMap::new();
+
+        for (input_index, input) in inputs.into_iter().enumerate() {
+            sample_offsets.push(total_samples);
+            sample_counts.push(input.sample_names.len());
+            total_samples += input.sample_names.len();
+
+            let tid_map = input
+                .contigs
+                .iter()
+                .map(|(name, length)| {
+                    *contig_indices.entry(name.clone()).or_insert_with(|| {
+                        contigs.push((name.clone(), *length));
+                        contigs.len() - 1
+                    })
+                })
+                .collect::<Vec<usize>>();
+
+            for vc in input.contexts {
+                let key = (
+                    tid_map[vc.loc.tid],
+                    vc.loc.start,
+                    vc.get_reference().bases.clone(),
+                );
+                sites.entry(key).or_insert_with(Vec::new).push((input_index, vc));
+            }
+        }
+
+        let mut merged = sites
+            .into_iter()
+            .map(|((tid, start, ref_bases), site_contexts)| {
+                Self::merge_site(
+                    tid,
+                    start,
+                    ref_bases,
+                    site_contexts,
+                    &sample_offsets,
+                    &sample_counts,
+                    total_samples,
+                    default_ploidy,
+                )
+            })
+            .collect::<Vec<VariantContext>>();
+        merged.sort_by(|a, b| (a.loc.tid, a.loc.start).cmp(&(b.loc.tid, b.loc.start)));
+
+        (contigs, merged)
+    }
+
+    fn merge_site(
+        tid: usize,
+        start: usize,
+        ref_bases: Vec<u8>,
+        site_contexts: Vec<(usize, VariantContext)>,
+        sample_offsets: &[usize],
+        sample_counts: &[usize],
+        total_samples: usize,
+        default_ploidy: usize,
+    ) -> VariantContext {
+        let mut alleles = vec![ByteArrayAllele::new(&ref_bases, true)];
+        for (_, vc) in site_contexts.iter() {
+            for allele in vc.alleles.iter() {
+                if !alleles.contains(allele) {
+                    alleles.push(allele.clone());
+                }
+            }
+        }
+
+        let mut genotypes: Vec<Option<Genotype>> = vec![None; total_samples];
+        for (input_index, vc) in site_contexts.iter() {
+            let old_to_new = vc
+                .alleles
+                .iter()
+                .map(|allele| alleles.iter().position(|a| a == allele).unwrap())
+                .collect::<Vec<usize>>();
+
+            for (sample_index, genotype) in vc.genotypes.genotypes().iter().enumerate() {
+                let mut ad = vec![0; alleles.len()];
+                for (old_index, depth) in genotype.ad.iter().enumerate() {
+                    if old_index < old_to_new.len() {
+                        ad[old_to_new[old_index]] = *depth;
+                    }
+                }
+
+                let mut merged_genotype = Genotype::build_from_ads(genotype.ploidy, ad);
+                merged_genotype.pl = Self::remap_likelihoods(
+                    &genotype.pl,
+                    genotype.ploidy,
+                    &old_to_new,
+                    alleles.len(),
+                );
+                if merged_genotype.pl.is_empty() {
+                    merged_genotype.pl = Self::flat_likelihoods(genotype.ploidy, alleles.len());
+                }
+                merged_genotype.sample_name = sample_offsets[*input_index] + sample_index;
+                genotypes[merged_genotype.sample_name] = Some(merged_genotype);
+            }
+        }
+
+        // samples from runs that did not report this site
+        for (input_index, offset) in sample_offsets.iter().enumerate() {
+            for sample_index in *offset..(*offset + sample_counts[input_index]) {
+                if genotypes[sample_index].is_none() {
+                    let mut genotype =
+                        Genotype::build_from_ads(default_ploidy, vec![0; alleles.len()]);
+                    genotype.pl = Self::flat_likelihoods(default_ploidy, alleles.len());
+                    genotype.sample_name = sample_index;
+                    genotypes[sample_index] = Some(genotype);
+                }
+            }
+        }
+
+        let end = start + ref_bases.len() - 1;
+        let mut vc = VariantContext::build(tid, start, end, alleles);
+        vc.genotypes = GenotypesContext::new(genotypes.into_iter().map(|g| g.unwrap()).collect());
+        vc
+    }
+
+    /// Moves phred scaled likelihoods calculated over one set of alleles onto a larger set of
+    /// alleles. `old_to_new` gives the index in the new alleles of each of the old alleles.
+    /// Genotypes containing alleles that were not part of the old set are given the least likely
+    /// PL. Returns an empty vector if the PLs do not match the ploidy and old allele count
+    pub fn remap_likelihoods(
+        pls: &[i32],
+        ploidy: usize,
+        old_to_new: &[usize],
+        n_new_alleles: usize,
+    ) -> Vec<i32> {
+        let n_old_alleles = old_to_new.len();
+        if pls.is_empty()
+            || pls.len()
+                != GenotypeLikelihoodCalculators::genotype_count(ploidy, n_old_alleles) as usize
+        {
+            return Vec::new();
+        }
+
+        let mut old_calculator = GenotypeLikelihoodCalculators::get_instance(ploidy, n_old_alleles);
+        let mut new_calculator = GenotypeLikelihoodCalculators::get_instance(ploidy, n_new_alleles);
+        let least_likely = *pls.iter().max().unwrap();
+        let mut remapped = vec![
+            least_likely;
+            GenotypeLikelihoodCalculators::genotype_count(ploidy, n_new_alleles) as usize
+        ];
+
+        for (old_index, pl) in pls.iter().enumerate() {
+            let mut allele_indices = Vec::with_capacity(ploidy);
+            let counts = old_calculator.genotype_allele_counts_at(old_index);
+            for rank in 0..counts.distinct_allele_count() {
+                let new_allele_index = old_to_new[counts.allele_index_at(rank)];
+                for _ in 0..counts.allele_count_at(rank) {
+                    allele_indices.push(new_allele_index);
+                }
+            }
+            remapped[new_calculator.alleles_to_index(&allele_indices)] = *pl;
+        }
+
+        remapped
+    }
+
+    fn flat_likelihoods(ploidy: usize, n_alleles: usize) -> Vec<i32> {
+        vec![0; GenotypeLikelihoodCalculators::genotype_count(ploidy, n_alleles) as usize]
+    }
+
+    /// Recalculates the genotypes, allele frequencies, and quality of each merged context.
+    /// Contexts that no longer pass the calling threshold are removed
+    pub fn joint_genotype(
+        args: &clap::ArgMatches,
+        contexts: Vec<VariantContext>,
+        sample_names: &[String],
+    ) -> Vec<VariantContext> {
+        let ploidy = *args.get_one::<usize>("ploidy").unwrap();
+        let stand_min_conf = *args
+            .get_one::<f64>("standard-min-confidence-threshold-for-calling")
+            .unwrap();
+        let gpc = GenotypePriorCalculator::make(args);
+        let given_alleles = Vec::new();
+        let mut genotyping_engine =
+            GenotypingEngine::make(args, sample_names.to_vec(), false, ploidy);
+
+        contexts
+            .into_iter()
+            .filter(|vc| vc.alleles.len() > 1)
+            .filter_map(|vc| {
+                genotyping_engine.calculate_genotypes(
+                    vc,
+                    ploidy,
+                    &gpc,
+                    &given_alleles,
+                    stand_min_conf,
+                )
+            })
+            .collect()
+    }
+
+    /// Writes the combined contexts to a VCF using the header of `template_vcf`, which should be
+    /// the first of the combined VCF files, with the samples and contigs of every input
+    pub fn write_vcf(
+        output_path: &str,
+        template_vcf: &str,
+        contigs: &[(String, Option<u64>)],
+        sample_names: &[String],
+        contexts: &[VariantContext],
+    ) -> Result<(), BirdToolError> {
+        let reader = VcfInput::open(template_vcf)?;
+        let template = reader.header();
+        let template_samples = template.sample_count() as usize;
+        let mut header = Header::from_template_subset(template, &[]).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to copy header of {}: {}", template_vcf, e))
+        })?;
+
+        for (name, length) in contigs.iter() {
+            if template.name2rid(name.as_bytes()).is_err() {
+                match length {
+                    Some(length) => header.push_record(
+                        format!("##contig=<ID={},length={}>", name, length).as_bytes(),
+                    ),
+                    None => header.push_record(format!("##contig=<ID={}>", name).as_bytes()),
+                };
+            }
+        }
+
+        // inputs may describe deletions and symbolic alleles the template lacks
+        for annotation in VariantAnnotationEngine::structural_variant_annotations() {
+            if template.info_type(annotation.get_key().as_bytes()).is_err() {
+                header.push_record(annotation.generate_header_record().as_bytes());
+            }
+        }
+        // VCF files of older runs lack the per-sample allele fractions
+        let allele_fraction_key = VariantAnnotations::AlleleFraction.to_key();
+        let has_allele_fractions = template.header_records().iter().any(|record| {
+            matches!(record, HeaderRecord::Format { values, .. }
+                if values.get("ID").map(|id| id.as_str()) == Some(allele_fraction_key))
+        });
+        if !has_allele_fractions {
+            header.push_record(
+                Annotation::new(VariantAnnotations::AlleleFraction, AnnotationType::Format)
+                    .generate_header_record()
+                    .as_bytes(),
+            );
+        }
+
+        // as may the strain IDs of merged lorikeet genotype runs
+        for annotation in VariantAnnotationEngine::strain_annotations() {
+            let key = annotation.get_key();
+            if template.info_type(key.as_bytes()).is_err()
+                && contexts.iter().any(|vc| vc.attributes.contains_key(key))
+            {
+                header.push_record(annotation.generate_header_record().as_bytes());
+            }
+        }
+
+        // the template already describes its own samples, which come first
+        for (sample_idx, sample_name) in sample_names.iter().enumerate().skip(template_samples) {
+            header.push_record(
+                format!("##sample=<ID={}, name={}>", sample_idx + 1, sample_name).as_bytes(),
+            );
+        }
+        for sample_idx in 0..sample_names.len() {
+            header.push_sample(format!("{}", sample_idx + 1).as_bytes());
+        }
+
+        let mut bcf_writer = Writer::from_path(output_path, &header, true, Format::Vcf)
+            .map_err(|e| {
+                BirdToolError::IOError(format!("Unable to create VCF output {}: {}", output_path, e))
+            })?;
+
+        for vc in contexts {
+            vc.write_as_vcf_record_on_contig(
+                &mut bcf_writer,
+                contigs[vc.loc.tid].0.as_bytes(),
+                sample_names.len(),
+            );
+        }
+
+        Ok(())
+    }
+}
@@ -578,6 +578,11 @@ pub struct SequenceForKmers<'a> {
     pub stop: usize,
     pub count: usize,
     pub is_ref: bool,
+    /**
+     * If true, this sequence may only add connections between kmers that already exist in the
+     * graph. Used for long reads during hybrid assembly
+     */
+    pub junction_only: bool,
 }
 
 impl<'a> SequenceForKmers<'a> {
@@ -599,6 +604,7 @@ impl<'a> SequenceForKmers<'a> {
             stop,
             count,
             is_ref,
+            junction_only: false,
         }
     }
 }
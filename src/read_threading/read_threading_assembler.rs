@@ -11,9 +11,12 @@ use crate::graphs::adaptive_chain_pruner::AdaptiveChainPruner;
 use crate::graphs::base_edge::{BaseEdge, BaseEdgeStruct};
 use crate::graphs::base_graph::BaseGraph;
 use crate::graphs::base_vertex::BaseVertex;
-use crate::graphs::chain_pruner::ChainPruner;
+use crate::graphs::chain_features::ChainFeatureExport;
+use crate::graphs::chain_pruner::{ChainPruner, ChainUtils};
 use crate::graphs::graph_based_k_best_haplotype_finder::GraphBasedKBestHaplotypeFinder;
+use crate::graphs::graph_dump::GraphDump;
 use crate::graphs::k_best_haplotype::KBestHaplotype;
+use crate::graphs::multi_sample_edge::MultiSampleEdge;
 use crate::graphs::seq_graph::SeqGraph;
 use crate::graphs::seq_vertex::SeqVertex;
 use crate::haplotype::haplotype::Haplotype;
@@ -21,6 +24,7 @@ use crate::model::byte_array_allele::Allele;
 use crate::pair_hmm::pair_hmm_likelihood_calculation_engine::AVXMode;
 use crate::graphs::low_weight_chain_pruner::LowWeightChainPruner;
 use crate::read_threading::abstract_read_threading_graph::{AbstractReadThreadingGraph, SequenceForKmers};
+use crate::read_threading::multi_debruijn_vertex::MultiDeBruijnVertex;
 use crate::read_threading::read_threading_graph::ReadThreadingGraph;
 use crate::reads::bird_tool_reads::BirdToolRead;
 use crate::reads::cigar_utils::CigarUtils;
@@ -29,6 +33,26 @@ use crate::utils::simple_interval::{Locatable, SimpleInterval};
 
 const PRUNE_FACTOR_COVERAGE_THRESHOLD: f64 = 10.0;
 
+/// Reasons a kmer size can be rejected when building a graph for a region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KmerRejection {
+    NonUniqueReferenceKmers,
+    Cycles,
+    LowComplexity,
+    DanglingBranchCycles,
+}
+
+impl KmerRejection {
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::NonUniqueReferenceKmers => "reference contains non-unique kmers",
+            Self::Cycles => "graph contains a cycle",
+            Self::LowComplexity => "graph does not have enough complexity",
+            Self::DanglingBranchCycles => "recovering dangling branches created a cycle",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ReadThreadingAssembler {
     pub(crate) kmer_sizes: Vec<usize>,
@@ -46,13 +70,16 @@ pub struct ReadThreadingAssembler {
     pub(crate) recover_all_dangling_branches: bool,
     pub(crate) min_dangling_branch_length: i32,
     pub(crate) min_base_quality_to_use_in_assembly: u8,
+    pub(crate) hybrid_assembly: bool,
     prune_factor: usize,
     min_matching_bases_to_dangling_end_recovery: i32,
-    chain_pruner: ChainPruner,
+    chain_pruner: Box<dyn ChainPruner<MultiDeBruijnVertex, MultiSampleEdge>>,
     pub(crate) debug_graph_transformations: bool,
     pub(crate) debug_graph_output_path: Option<String>,
     // graph_haplotype_histogram_path: Option<String>,
     pub(crate) graph_output_path: Option<String>,
+    pub(crate) graph_dump: Option<GraphDump>,
+    pub(crate) chain_feature_export: Option<ChainFeatureExport>,
 }
 
 impl ReadThreadingAssembler {
@@ -92,14 +119,14 @@ impl ReadThreadingAssembler {
         kmer_sizes.sort_unstable();
 
         let chain_pruner = if use_adaptive_pruning {
-            ChainPruner::AdaptiveChainPruner(AdaptiveChainPruner::new(
+            Box::new(AdaptiveChainPruner::new(
                 initial_error_rate_for_pruning,
                 pruning_log_odds_threshold,
                 pruning_seeding_log_odds_threshold,
                 max_unpruned_variants,
-            ))
+            )) as Box<dyn ChainPruner<MultiDeBruijnVertex, MultiSampleEdge>>
         } else {
-            ChainPruner::LowWeightChainPruner(LowWeightChainPruner::new(prune_factor))
+            Box::new(LowWeightChainPruner::new(prune_factor))
         };
 
         // TODO: //!use_linked_debruijn_graphs should be used for generate_seq_graph
@@ -122,14 +149,126 @@ impl ReadThreadingAssembler {
             min_matching_bases_to_dangling_end_recovery: min_matching_bases_to_dangle_end_recovery,
             // recover_haplotypes_from_edges_not_covered_in_junction_trees: true,
             min_base_quality_to_use_in_assembly: Self::DEFAULT_MIN_BASE_QUALITY_TO_USE,
+            hybrid_assembly: false,
             debug_graph_transformations: false,
             debug_graph_output_path: Some(format!("graph_debugging")),
             // graph_haplotype_histogram_path: None,
             graph_output_path: None,
+            graph_dump: None,
+            chain_feature_export: None,
             disable_prune_factor_correction
         }
     }
 
+    /**
+     * Warns about even kmer sizes, which are used as given. An odd length kmer can never be its
+     * own reverse complement, so odd kmer sizes avoid palindromic kmers in the graph.
+     * Returns the even kmer sizes.
+     */
+    pub fn validate_kmer_sizes(kmer_sizes: &[usize]) -> Vec<usize> {
+        let even_kmer_sizes = kmer_sizes
+            .iter()
+            .copied()
+            .filter(|kmer_size| kmer_size % 2 == 0)
+            .collect::<Vec<usize>>();
+        if !even_kmer_sizes.is_empty() {
+            warn!(
+                "Kmer sizes {:?} are even and can form palindromic kmers, odd kmer sizes are \
+                recommended",
+                &even_kmer_sizes
+            );
+        }
+        even_kmer_sizes
+    }
+
+    /// Whether every kmer of the given size occurs only once in the reference bases
+    pub fn has_unique_reference_kmers(ref_bases: &[u8], kmer_size: usize) -> bool {
+        ReadThreadingGraph::determine_non_unique_kmers(
+            &SequenceForKmers::new("ref".to_string(), ref_bases, 0, ref_bases.len(), 1, true),
+            kmer_size,
+        )
+        .is_empty()
+    }
+
+    /**
+     * The smallest odd kmer size between min_kmer_size and max_kmer_size (inclusive) for which every
+     * reference kmer is unique, or None if there is no such kmer size.
+     *
+     * If all kmers of size k are unique then so are all kmers of size k + 1, so the odd sizes in the
+     * range can be binary searched.
+     */
+    pub fn smallest_unique_kmer_size(
+        ref_bases: &[u8],
+        min_kmer_size: usize,
+        max_kmer_size: usize,
+    ) -> Option<usize> {
+        let min_kmer_size = min_kmer_size.max(1) | 1;
+        if min_kmer_size > max_kmer_size {
+            return None;
+        }
+
+        // candidates are min_kmer_size + 2 * i for i in 0..n_candidates
+        let n_candidates = (max_kmer_size - min_kmer_size) / 2 + 1;
+        let (mut low, mut high) = (0, n_candidates);
+        while low < high {
+            let mid = (low + high) / 2;
+            if Self::has_unique_reference_kmers(ref_bases, min_kmer_size + 2 * mid) {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+
+        if low < n_candidates {
+            Some(min_kmer_size + 2 * low)
+        } else {
+            None
+        }
+    }
+
+    /**
+     * The first kmer size to try once the requested kmer sizes have failed. Rather than stepping
+     * KMER_SIZE_ITERATION_INCREASE past the largest requested kmer size, jump straight to the
+     * smallest odd kmer size giving unique reference kmers if it lies within the range the
+     * expansion would have covered.
+     */
+    fn first_expanded_kmer_size(&self, ref_bases: &[u8]) -> usize {
+        let max_kmer_size = *self.kmer_sizes.iter().max().unwrap();
+        let ladder_start = (max_kmer_size + Self::KMER_SIZE_ITERATION_INCREASE) | 1;
+        if self.allow_non_unique_kmers_in_ref {
+            return ladder_start;
+        }
+
+        let ladder_end = max_kmer_size
+            + Self::KMER_SIZE_ITERATION_INCREASE * Self::MAX_KMER_ITERATIONS_TO_ATTEMPT;
+        match Self::smallest_unique_kmer_size(ref_bases, max_kmer_size + 1, ladder_end) {
+            Some(kmer_size) => kmer_size,
+            None => ladder_start,
+        }
+    }
+
+    fn log_kmer_rejection(
+        ref_haplotype: &Haplotype<SimpleInterval>,
+        kmer_size: usize,
+        reason: KmerRejection,
+    ) {
+        match ref_haplotype.genome_location.as_ref() {
+            Some(location) => debug!(
+                "Not using kmer size of {} in region {}:{}-{} because {}",
+                kmer_size,
+                location.tid(),
+                location.get_start(),
+                location.get_end(),
+                reason.description()
+            ),
+            None => debug!(
+                "Not using kmer size of {} because {}",
+                kmer_size,
+                reason.description()
+            ),
+        }
+    }
+
     pub fn default() -> Self {
         Self::new(
             Self::DEFAULT_NUM_PATHS_PER_GRAPH as i32,
@@ -190,6 +329,40 @@ impl ReadThreadingAssembler {
         self.prune_factor = value;
         self.chain_pruner.set_prune_factor(value);
     }
+
+    /// Replaces the pruner chosen by --use-adaptive-pruning, e.g. with one from the
+    /// ChainPrunerRegistry
+    pub fn set_chain_pruner(
+        &mut self,
+        mut chain_pruner: Box<dyn ChainPruner<MultiDeBruijnVertex, MultiSampleEdge>>,
+    ) {
+        chain_pruner.set_prune_factor(self.prune_factor);
+        self.chain_pruner = chain_pruner;
+    }
+
+    /// Prunes the chains of a read threading graph, writing the features of every chain first if
+    /// --export-chain-features was given
+    fn prune_chains(
+        &self,
+        ref_haplotype: &Haplotype<SimpleInterval>,
+        graph: &mut BaseGraph<MultiDeBruijnVertex, MultiSampleEdge>,
+    ) {
+        match &self.chain_feature_export {
+            Some(chain_feature_export) => {
+                let chains = ChainUtils::find_all_chains(graph);
+                let chains_to_remove = self.chain_pruner.chains_to_remove(&chains, graph);
+                chain_feature_export.write(
+                    ref_haplotype.genome_location.as_ref().unwrap(),
+                    self.chain_pruner.name(),
+                    &chains,
+                    &chains_to_remove,
+                    graph,
+                );
+                ChainUtils::remove_chains(graph, chains_to_remove);
+            }
+            None => self.chain_pruner.prune_low_weight_chains(graph),
+        }
+    }
     /**
      * Main entry point into the assembly engine. Build a set of deBruijn graphs out of the provided reference sequence and list of reads
      * @param assemblyRegion              AssemblyRegion object holding the reads which are to be used during assembly
@@ -328,6 +501,7 @@ impl ReadThreadingAssembler {
         avx_mode: AVXMode,
         additional_kmer_sizes: Option<Vec<usize>>
     ) {
+        let mut assembled_any = false;
         // create the graphs by calling our subclass assemble method
         self.assemble(
             &corrected_reads,
@@ -339,6 +513,9 @@ impl ReadThreadingAssembler {
         )
         .into_iter()
         .for_each(|mut result| {
+            if result.status != Status::Failed {
+                assembled_any = true;
+            }
             // debug!("graph after assembly {:?}", &result.graph.as_ref().unwrap().base_graph);
             // debug!(
             //     "Result loc {:?} Status {:?} haps {:?}",
@@ -362,6 +539,7 @@ impl ReadThreadingAssembler {
                 // result_set.add_haplotype(result);
             }
         });
+        result_set.assembly_failed = !assembled_any;
     }
 
     /**
@@ -417,12 +595,11 @@ impl ReadThreadingAssembler {
         
 
         if results.is_empty() && !self.dont_increase_kmer_sizes_for_cycles {
-            let mut kmer_size =
-                *self.kmer_sizes.iter().max().unwrap() + Self::KMER_SIZE_ITERATION_INCREASE;
-            // if kmer_size is even, add 1 to make it odd
-            if kmer_size % 2 == 0 {
-                kmer_size += 1;
-            }
+            let mut kmer_size = self.first_expanded_kmer_size(ref_haplotype.get_bases());
+            debug!(
+                "No graph could be built with kmer sizes {:?} in region {:?}, expanding from kmer size {}",
+                &kmer_sizes, ref_haplotype.genome_location, kmer_size
+            );
             let mut num_iterations = 1;
             while results.is_empty() && num_iterations <= Self::MAX_KMER_ITERATIONS_TO_ATTEMPT {
                 // on the last attempt we will allow low complexity graphs
@@ -472,7 +649,8 @@ impl ReadThreadingAssembler {
         let mut saved_assembly_results = Vec::new();
 
         let mut has_adequately_assembled_graph = false;
-        let kmers_to_try = self.get_expanded_kmer_list(additional_kmer_sizes);
+        let kmers_to_try =
+            self.get_expanded_kmer_list(ref_haplotype.get_bases(), additional_kmer_sizes);
         // first, try using the requested kmer sizes
         for i in 0..kmers_to_try.len() {
             let kmer_size = kmers_to_try[i];
@@ -563,8 +741,11 @@ impl ReadThreadingAssembler {
             // In this case we prefer the last meaningful kmer size if possible
             // for result in saved_assembly_results.
             saved_assembly_results.reverse();
+            // assembly failed unless an earlier graph can be used after all
+            result_set.assembly_failed = true;
             for result in saved_assembly_results {
                 if result.discovered_haplotypes.len() > 1 {
+                    result_set.assembly_failed = false;
                     // let mut result_set = result_set.lock().unwrap();
                     let ar_index = result_set.add_assembly_result(result);
                     for h in result_set.assembly_results[ar_index]
@@ -630,12 +811,15 @@ impl ReadThreadingAssembler {
      * Method for getting a list of all of the specified kmer sizes to test for the graph including kmer expansions
      * @return
      */
-    fn get_expanded_kmer_list(&self, additional_kmer_sizes: Option<Vec<usize>>) -> Vec<usize> {
+    fn get_expanded_kmer_list(
+        &self,
+        ref_bases: &[u8],
+        additional_kmer_sizes: Option<Vec<usize>>,
+    ) -> Vec<usize> {
         let mut return_list = Vec::new();
         return_list.extend(self.kmer_sizes.iter());
         if !self.dont_increase_kmer_sizes_for_cycles {
-            let mut kmer_size =
-                self.kmer_sizes.iter().max().unwrap() + Self::KMER_SIZE_ITERATION_INCREASE;
+            let mut kmer_size = self.first_expanded_kmer_size(ref_bases);
             let mut num_iterations = 1;
             while num_iterations <= Self::MAX_KMER_ITERATIONS_TO_ATTEMPT {
                 return_list.push(kmer_size);
@@ -938,20 +1122,13 @@ impl ReadThreadingAssembler {
         }
 
         if !self.allow_non_unique_kmers_in_ref
-            && !ReadThreadingGraph::determine_non_unique_kmers(
-                &SequenceForKmers::new(
-                    "ref".to_string(),
-                    ref_haplotype.get_bases(),
-                    0,
-                    ref_haplotype.get_bases().len(),
-                    1,
-                    true,
-                ),
-                kmer_size,
-            )
-            .is_empty()
+            && !Self::has_unique_reference_kmers(ref_haplotype.get_bases(), kmer_size)
         {
-            // debug!("Not using kmer size of {kmer_size} in read threading assembler because reference contains non-unique kmers");
+            Self::log_kmer_rejection(
+                ref_haplotype,
+                kmer_size,
+                KmerRejection::NonUniqueReferenceKmers,
+            );
             return None;
         }
 
@@ -978,6 +1155,7 @@ impl ReadThreadingAssembler {
         // };
 
         rt_graph.set_threading_start_only_at_existing_vertex(!self.recover_dangling_branches);
+        rt_graph.set_hybrid_assembly(self.hybrid_assembly);
 
         // add the reference sequence to the graph
         let mut pending = LinkedHashMap::new();
@@ -1042,8 +1220,7 @@ impl ReadThreadingAssembler {
         // It's also important to prune before checking for cycles so that sequencing errors don't create false cycles
         // and unnecessarily abort assembly
         if self.prune_before_cycle_counting {
-            self.chain_pruner
-                .prune_low_weight_chains(rt_graph.get_base_graph_mut());
+            self.prune_chains(ref_haplotype, rt_graph.get_base_graph_mut());
         }
         // debug!(
         //     "3 - Graph Kmer {} Edges {} Nodes {}",
@@ -1054,21 +1231,13 @@ impl ReadThreadingAssembler {
 
         // sanity check: make sure there are no cycles in the graph, unless we are in experimental mode
         if self.generate_seq_graph && rt_graph.has_cycles() {
-            // debug!(
-            //     "Not using kmer size of {}  in read threading assembler \
-            //         because it contains a cycle",
-            //     kmer_size
-            // );
+            Self::log_kmer_rejection(ref_haplotype, kmer_size, KmerRejection::Cycles);
             return None;
         }
 
         // sanity check: make sure the graph had enough complexity with the given kmer
         if !allow_low_complexity_graphs && rt_graph.is_low_quality_graph() {
-            // debug!(
-            //     "Not using kmer size of {} in read threading assembler because it does not \
-            //         produce a graph with enough complexity",
-            //     kmer_size
-            // );
+            Self::log_kmer_rejection(ref_haplotype, kmer_size, KmerRejection::LowComplexity);
             return None;
         }
 
@@ -1082,6 +1251,11 @@ impl ReadThreadingAssembler {
         if self.recover_all_dangling_branches
             && result.threading_graph.as_ref().unwrap().has_cycles()
         {
+            Self::log_kmer_rejection(
+                ref_haplotype,
+                kmer_size,
+                KmerRejection::DanglingBranchCycles,
+            );
             return None;
         }
 
@@ -1096,8 +1270,7 @@ impl ReadThreadingAssembler {
         dangling_end_sw_parameters: &Parameters,
     ) -> AssemblyResult<SimpleInterval, A> {
         if !self.prune_before_cycle_counting {
-            self.chain_pruner
-                .prune_low_weight_chains(rt_graph.get_base_graph_mut())
+            self.prune_chains(ref_haplotype, rt_graph.get_base_graph_mut())
         }
 
         if self.debug_graph_transformations {
@@ -1135,6 +1308,15 @@ impl ReadThreadingAssembler {
             rt_graph.remove_paths_not_connected_to_ref()
         }
 
+        if let Some(graph_dump) = &self.graph_dump {
+            graph_dump.dump(
+                rt_graph.get_base_graph(),
+                ref_haplotype.genome_location.as_ref().unwrap(),
+                "read_threading",
+                kmer_size - 1,
+            );
+        }
+
         if self.debug_graph_transformations {
             self.print_debug_graph_transform_abstract(
                 &rt_graph,
@@ -1196,6 +1378,14 @@ impl ReadThreadingAssembler {
             initial_seq_graph.base_graph.clean_non_ref_paths();
             let cleaned: AssemblyResult<SimpleInterval, ReadThreadingGraph> =
                 self.clean_up_seq_graph(initial_seq_graph, &ref_haplotype);
+            if let (Some(graph_dump), Some(seq_graph)) = (&self.graph_dump, &cleaned.graph) {
+                graph_dump.dump(
+                    &seq_graph.base_graph,
+                    ref_haplotype.genome_location.as_ref().unwrap(),
+                    "seq_graph",
+                    0,
+                );
+            }
             let status = cleaned.status;
             return AssemblyResult::new(status, cleaned.graph, Some(rt_graph));
         } else {
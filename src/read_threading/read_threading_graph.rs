@@ -9,7 +9,7 @@ use rust_htslib::bam::record::Cigar;
 use std::cmp::{max, min};
 use std::collections::HashSet;
 
-use crate::assembly::kmer::Kmer;
+use crate::assembly::kmer::{Kmer, KmerHashBuilder};
 use crate::graphs::base_edge::BaseEdge;
 use crate::graphs::base_edge::BaseEdgeStruct;
 use crate::graphs::base_graph::BaseGraph;
@@ -17,6 +17,7 @@ use crate::graphs::base_vertex::BaseVertex;
 use crate::graphs::multi_sample_edge::MultiSampleEdge;
 use crate::graphs::seq_graph::SeqGraph;
 use crate::pair_hmm::pair_hmm_likelihood_calculation_engine::AVXMode;
+use crate::processing::lorikeet_engine::ReadType;
 use crate::read_threading::abstract_read_threading_graph::{
     AbstractReadThreadingGraph, DanglingChainMergeHelper, SequenceForKmers, TraversalDirection,
 };
@@ -35,7 +36,7 @@ pub struct ReadThreadingGraph {
     /**
      * A set of non-unique kmers that cannot be used as merge points in the graph
      */
-    non_unique_kmers: HashSet<Kmer>,
+    non_unique_kmers: HashSet<Kmer, KmerHashBuilder>,
     min_matching_bases_to_dangling_end_recovery: i32,
     counter: usize,
     /**
@@ -45,7 +46,7 @@ pub struct ReadThreadingGraph {
     /**
      * A map from kmers -> their corresponding vertex in the graph
      */
-    kmer_to_vertex_map: LinkedHashMap<Kmer, NodeIndex>,
+    kmer_to_vertex_map: LinkedHashMap<Kmer, NodeIndex, KmerHashBuilder>,
     debug_graph_transformations: bool,
     min_base_quality_to_use_in_assembly: u8,
     pub reference_path: Vec<NodeIndex>,
@@ -58,6 +59,14 @@ pub struct ReadThreadingGraph {
     max_mismatches_in_dangling_head: i32,
     increase_counts_through_branches: bool,
     num_pruning_samples: usize,
+    /**
+     * When true, long reads are only used to connect kmers observed in short reads
+     */
+    hybrid_assembly: bool,
+    /**
+     * Edges introduced by junction only sequences, the only edges those sequences add weight to
+     */
+    junction_edges: HashSet<EdgeIndex>,
     pub(crate) base_graph: BaseGraph<MultiDeBruijnVertex, MultiSampleEdge>,
     avx_mode: AVXMode,
 }
@@ -79,11 +88,11 @@ impl ReadThreadingGraph {
     ) -> Self {
         let base_graph = BaseGraph::new(kmer_size);
         Self {
-            non_unique_kmers: HashSet::new(),
+            non_unique_kmers: HashSet::default(),
             min_matching_bases_to_dangling_end_recovery,
             counter: 0,
             // pending: LinkedHashMap::new(),
-            kmer_to_vertex_map: LinkedHashMap::new(),
+            kmer_to_vertex_map: LinkedHashMap::default(),
             debug_graph_transformations: true,
             min_base_quality_to_use_in_assembly,
             reference_path: Vec::new(),
@@ -93,6 +102,8 @@ impl ReadThreadingGraph {
             max_mismatches_in_dangling_head: -1,
             increase_counts_through_branches: false,
             num_pruning_samples: min_pruning_samples,
+            hybrid_assembly: false,
+            junction_edges: HashSet::new(),
             base_graph,
             avx_mode,
         }
@@ -102,6 +113,71 @@ impl ReadThreadingGraph {
         Self::new(kmer_size, false, 6, 1, -1, AVXMode::detect_mode())
     }
 
+    /**
+     * Enables technology aware graph construction. Short reads create the kmer vertices and edge
+     * weights of the graph, while long reads are threaded afterwards and may only add edges
+     * between kmers that already exist. This lets long reads resolve junctions in low complexity
+     * regions without their per-base errors introducing spurious branches.
+     */
+    pub fn set_hybrid_assembly(&mut self, value: bool) {
+        self.hybrid_assembly = value;
+    }
+
+    /**
+     * Thread a sequence through the graph using only vertices that already exist. Consecutive
+     * unique kmers that are both present in the graph are connected. No new vertices are ever
+     * created.
+     *
+     * Edges threaded by other sequences keep their weights, so a long read spanning thousands of
+     * kmers does not outweigh the short reads that built them. Only the edges introduced by
+     * junction sequences are weighted, by the number of junction sequences supporting them.
+     *
+     * @param seqForKmers a non-null sequence
+     */
+    fn thread_junction_sequence(&mut self, seq_for_kmers: &SequenceForKmers) {
+        let kmer_size = self.base_graph.get_kmer_size();
+        if seq_for_kmers.stop < seq_for_kmers.start + kmer_size {
+            return;
+        }
+
+        let mut previous_vertex: Option<NodeIndex> = None;
+        for (_, kmer) in Kmer::rolling(
+            seq_for_kmers.sequence,
+            seq_for_kmers.start,
+            seq_for_kmers.stop - kmer_size,
+            kmer_size,
+        ) {
+            let vertex = self.get_kmer_vertex(&kmer, false).copied();
+
+            if let (Some(prev), Some(next)) = (previous_vertex, vertex) {
+                match self.base_graph.graph.find_edge(prev, next) {
+                    Some(edge) => {
+                        if self.junction_edges.contains(&edge) {
+                            self.base_graph
+                                .graph
+                                .edge_weight_mut(edge)
+                                .unwrap()
+                                .inc_multiplicity(seq_for_kmers.count);
+                        }
+                    }
+                    None => {
+                        let edge = self.base_graph.graph.add_edge(
+                            prev,
+                            next,
+                            MultiSampleEdge::new(
+                                false,
+                                seq_for_kmers.count,
+                                self.num_pruning_samples,
+                            ),
+                        );
+                        self.junction_edges.insert(edge);
+                    }
+                }
+            }
+            previous_vertex = vertex;
+        }
+    }
+
     /**
      * Get the collection of non-unique kmers from sequence for kmer size kmerSize
      * @param seqForKmers a sequence to get kmers from
@@ -113,7 +189,6 @@ impl ReadThreadingGraph {
         kmer_size: usize,
     ) -> Vec<Kmer> {
         // count up occurrences of kmers within each read
-        let mut all_kmers = HashSet::new();
         let mut non_unique_kmers = Vec::new();
 
         let stop_position = seq_for_kmers.stop.checked_sub(kmer_size);
@@ -122,12 +197,10 @@ impl ReadThreadingGraph {
                 // pass
             }
             Some(stop_position) => {
-                for i in 0..=stop_position {
-                    // if seq_for_kmers.sequence.len() <= i + kmer_size {
-                    //     break;
-                    // }
-                    let kmer =
-                        Kmer::new_with_start_and_length(seq_for_kmers.sequence, i, kmer_size);
+                let mut all_kmers =
+                    HashSet::with_capacity_and_hasher(stop_position + 1, KmerHashBuilder::new());
+                for (_, kmer) in Kmer::rolling(seq_for_kmers.sequence, 0, stop_position, kmer_size)
+                {
                     if all_kmers.contains(&kmer) {
                         non_unique_kmers.push(kmer);
                     } else {
@@ -165,7 +238,7 @@ impl ReadThreadingGraph {
         &self,
         kmer_size: usize,
         with_non_uniques: Vec<&SequenceForKmers>,
-    ) -> HashSet<Kmer> {
+    ) -> HashSet<Kmer, KmerHashBuilder> {
         // loop over all sequences that have non-unique kmers in them from the previous iterator
         with_non_uniques
             .iter()
@@ -183,7 +256,7 @@ impl ReadThreadingGraph {
                 }
             })
             .flat_map(|non_uniques| non_uniques.into_iter())
-            .collect::<HashSet<Kmer>>()
+            .collect::<HashSet<Kmer, KmerHashBuilder>>()
     }
 
     // /**
@@ -203,7 +276,7 @@ impl ReadThreadingGraph {
      * Get the set of non-unique kmers in this graph.  For debugging purposes
      * @return a non-null set of kmers
      */
-    pub fn get_non_uniques(&mut self) -> &HashSet<Kmer> {
+    pub fn get_non_uniques(&mut self) -> &HashSet<Kmer, KmerHashBuilder> {
         &self.non_unique_kmers
     }
 }
@@ -380,6 +453,14 @@ impl AbstractReadThreadingGraph for ReadThreadingGraph {
                         1,
                         false,
                     );
+                    if self.hybrid_assembly && read.read_type == ReadType::Long {
+                        if let Some(sequence_for_kmers) = pending
+                            .get_mut(&read.sample_index)
+                            .and_then(|sequences| sequences.last_mut())
+                        {
+                            sequence_for_kmers.junction_only = true;
+                        }
+                    }
                     *count += 1;
                 }
                 last_good = -1;
@@ -425,34 +506,80 @@ impl AbstractReadThreadingGraph for ReadThreadingGraph {
         // Capture the set of non-unique kmers for the given kmer size (if applicable)
         self.preprocess_reads(&pending);
 
+        // Every kmer of the reference gets a vertex, and most reads add at least one more through
+        // a sequencing error or variant, so size the kmer map up front rather than rehashing it
+        let expected_vertices = pending
+            .values()
+            .flatten()
+            .map(|sequence_for_kmers| {
+                if sequence_for_kmers.is_ref {
+                    sequence_for_kmers
+                        .stop
+                        .saturating_sub(sequence_for_kmers.start)
+                } else {
+                    1
+                }
+            })
+            .sum::<usize>();
+        self.kmer_to_vertex_map.reserve(expected_vertices);
+
+        // Long reads can only be restricted to existing kmers if some short reads are present
+        let restrict_junction_sequences = pending
+            .values()
+            .flatten()
+            .any(|sequence_for_kmers| {
+                !sequence_for_kmers.is_ref && !sequence_for_kmers.junction_only
+            });
+
         // let pending = self.get_pending();
-        // go through the pending sequences, and add them to the graph
-        for (_name, sequences_for_samples) in pending.iter() {
-            // debug!("Sample {} reads {}", *name, sequences_for_samples.len());
-            for sequence_for_kmers in sequences_for_samples.iter() {
-                self.thread_sequence(sequence_for_kmers);
-                if Self::WRITE_GRAPH {
-                    self.base_graph.print_graph(
-                        &format!(
-                            "threading.{}.{}.dot",
-                            self.counter,
-                            sequence_for_kmers.name.replace(" ", "_")
-                        ),
-                        true,
-                        0,
-                    );
+        // go through the pending sequences, and add them to the graph. Junction only sequences
+        // are threaded last so that they can connect the kmers provided by every other sequence
+        for junction_pass in [false, true] {
+            for (name, sequences_for_samples) in pending.iter() {
+                // debug!("Sample {} reads {}", *name, sequences_for_samples.len());
+                let mut threaded = false;
+                for sequence_for_kmers in sequences_for_samples
+                    .iter()
+                    .filter(|sequence_for_kmers| sequence_for_kmers.junction_only == junction_pass)
+                {
+                    if junction_pass && restrict_junction_sequences {
+                        self.thread_junction_sequence(sequence_for_kmers);
+                    } else {
+                        self.thread_sequence(sequence_for_kmers);
+                    }
+                    if Self::WRITE_GRAPH {
+                        self.base_graph.print_graph(
+                            &format!(
+                                "threading.{}.{}.dot",
+                                self.counter,
+                                sequence_for_kmers.name.replace(" ", "_")
+                            ),
+                            true,
+                            0,
+                        );
+                    }
+                    self.counter += 1;
+                    threaded = true;
+                }
+
+                if !threaded {
+                    continue;
+                }
+                // debug!(
+                //     "Threaded {} Nodes {} Edges {}",
+                //     self.counter,
+                //     self.base_graph.graph.node_count(),
+                //     self.base_graph.graph.edge_count()
+                // );
+                // flush the single sample edge values from the graph
+                for e in self.base_graph.graph.edge_weights_mut() {
+                    // the reference is threaded under usize::MAX and is not a sample
+                    if *name == std::usize::MAX {
+                        e.flush_single_sample_multiplicity()
+                    } else {
+                        e.flush_sample_multiplicity(*name)
+                    }
                 }
-                self.counter += 1;
-            }
-            // debug!(
-            //     "Threaded {} Nodes {} Edges {}",
-            //     self.counter,
-            //     self.base_graph.graph.node_count(),
-            //     self.base_graph.graph.edge_count()
-            // );
-            // flush the single sample edge values from the graph
-            for e in self.base_graph.graph.edge_weights_mut() {
-                e.flush_single_sample_multiplicity()
             }
             // debug!(
             //     "Flushed {} Nodes {} Edges {}",
@@ -571,13 +698,16 @@ impl AbstractReadThreadingGraph for ReadThreadingGraph {
             return Some(0);
         } else {
             let stop = seq_for_kmers.stop.saturating_sub(self.base_graph.get_kmer_size());
+            if stop <= seq_for_kmers.start {
+                return None;
+            }
 
-            for i in seq_for_kmers.start..stop {
-                let kmer1 = Kmer::new_with_start_and_length(
-                    seq_for_kmers.sequence,
-                    i,
-                    self.base_graph.get_kmer_size(),
-                );
+            for (i, kmer1) in Kmer::rolling(
+                seq_for_kmers.sequence,
+                seq_for_kmers.start,
+                stop - 1,
+                self.base_graph.get_kmer_size(),
+            ) {
                 if self.is_threading_start(&kmer1) {
                     return Some(i);
                 }
@@ -0,0 +1,490 @@
+use rayon::prelude::*;
+use rust_htslib::bam::record::{Cigar, CigarStringView, Record};
+use std::cmp::min;
+
+use crate::bam_parsing::bam_generator::{
+    generate_indexed_named_bam_readers_from_bam_files, IndexedNamedBamReader,
+};
+use crate::bam_parsing::FlagFilter;
+use crate::processing::lorikeet_engine::ReadType;
+use crate::reads::read_utils::ReadUtils;
+use crate::reference::reference_reader::ReferenceReader;
+
+/// Observed and expected error counts for a single covariate bin
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecalDatum {
+    pub observations: u64,
+    pub errors: u64,
+    /// Sum of the error probabilities implied by the reported base qualities
+    pub expected_errors: f64,
+}
+
+impl RecalDatum {
+    pub fn add(&mut self, reported_quality: u8, is_error: bool) {
+        self.observations += 1;
+        if is_error {
+            self.errors += 1;
+        }
+        self.expected_errors += 10.0_f64.powf(-(reported_quality as f64) / 10.0);
+    }
+
+    pub fn combine(&mut self, other: &RecalDatum) {
+        self.observations += other.observations;
+        self.errors += other.errors;
+        self.expected_errors += other.expected_errors;
+    }
+
+    /// Phred scaled observed error rate. A pseudocount is used so that bins without any observed
+    /// errors do not produce infinite qualities
+    pub fn empirical_quality(&self) -> f64 {
+        let error_rate = (self.errors as f64 + 1.0) / (self.observations as f64 + 2.0);
+        (-10.0 * error_rate.log10()).min(BaseRecalibrationTable::MAX_RECALIBRATED_QUALITY as f64)
+    }
+
+    /// Phred scaled mean error rate implied by the reported qualities
+    pub fn reported_quality(&self) -> f64 {
+        if self.observations == 0 || self.expected_errors <= 0.0 {
+            return 0.0;
+        }
+        -10.0 * (self.expected_errors / self.observations as f64).log10()
+    }
+
+    fn is_trusted(&self) -> bool {
+        self.observations >= BaseRecalibrationTable::MIN_OBSERVATIONS_PER_BIN
+    }
+}
+
+/// Error model for a single sample. Errors are binned by reported quality, by reported quality and
+/// sequencing cycle, and by reported quality and dinucleotide context
+#[derive(Debug, Clone)]
+pub struct SampleRecalibrationTable {
+    global: RecalDatum,
+    by_quality: Vec<RecalDatum>,
+    by_cycle: Vec<RecalDatum>,
+    by_context: Vec<RecalDatum>,
+}
+
+impl SampleRecalibrationTable {
+    pub fn new() -> Self {
+        let n_qualities = BaseRecalibrationTable::MAX_REPORTED_QUALITY as usize + 1;
+        Self {
+            global: RecalDatum::default(),
+            by_quality: vec![RecalDatum::default(); n_qualities],
+            by_cycle: vec![RecalDatum::default(); n_qualities * BaseRecalibrationTable::N_CYCLE_BINS],
+            by_context: vec![RecalDatum::default(); n_qualities * BaseRecalibrationTable::N_CONTEXTS],
+        }
+    }
+
+    pub fn observations(&self) -> u64 {
+        self.global.observations
+    }
+
+    pub fn add_observation(
+        &mut self,
+        reported_quality: u8,
+        cycle_bin: usize,
+        context: usize,
+        is_error: bool,
+    ) {
+        let quality = min(reported_quality, BaseRecalibrationTable::MAX_REPORTED_QUALITY);
+        let q = quality as usize;
+        self.global.add(quality, is_error);
+        self.by_quality[q].add(quality, is_error);
+        self.by_cycle[q * BaseRecalibrationTable::N_CYCLE_BINS + cycle_bin].add(quality, is_error);
+        self.by_context[q * BaseRecalibrationTable::N_CONTEXTS + context].add(quality, is_error);
+    }
+
+    pub fn combine(&mut self, other: &SampleRecalibrationTable) {
+        self.global.combine(&other.global);
+        for (datum, other) in self
+            .by_quality
+            .iter_mut()
+            .chain(self.by_cycle.iter_mut())
+            .chain(self.by_context.iter_mut())
+            .zip(
+                other
+                    .by_quality
+                    .iter()
+                    .chain(other.by_cycle.iter())
+                    .chain(other.by_context.iter()),
+            )
+        {
+            datum.combine(other);
+        }
+    }
+
+    /// Recalibrates a single base quality. The global shift is applied first, followed by the shift
+    /// for the reported quality, and finally the cycle and context shifts which are both relative to
+    /// the quality adjusted value. Bins with too few observations do not contribute a shift.
+    pub fn recalibrate_quality(&self, reported_quality: u8, cycle_bin: usize, context: usize) -> u8 {
+        if !self.global.is_trusted() {
+            return reported_quality;
+        }
+
+        let q = min(reported_quality, BaseRecalibrationTable::MAX_REPORTED_QUALITY) as usize;
+        let mut expected = reported_quality as f64 + self.global.empirical_quality()
+            - self.global.reported_quality();
+
+        let quality_datum = &self.by_quality[q];
+        if quality_datum.is_trusted() {
+            expected = quality_datum.empirical_quality();
+        }
+
+        let cycle_datum = &self.by_cycle[q * BaseRecalibrationTable::N_CYCLE_BINS + cycle_bin];
+        let cycle_delta = if cycle_datum.is_trusted() {
+            cycle_datum.empirical_quality() - expected
+        } else {
+            0.0
+        };
+
+        let context_datum = &self.by_context[q * BaseRecalibrationTable::N_CONTEXTS + context];
+        let context_delta = if context_datum.is_trusted() {
+            context_datum.empirical_quality() - expected
+        } else {
+            0.0
+        };
+
+        (expected + cycle_delta + context_delta).round().max(1.0).min(
+            BaseRecalibrationTable::MAX_RECALIBRATED_QUALITY as f64,
+        ) as u8
+    }
+}
+
+/// A BQSR-like base quality error model learned from putatively invariant sites of the reference.
+/// Each sample has its own model, and the recalibrated qualities are used in place of the reported
+/// qualities when calculating read likelihoods in the PairHMM.
+#[derive(Debug, Clone)]
+pub struct BaseRecalibrationTable {
+    samples: Vec<SampleRecalibrationTable>,
+}
+
+impl BaseRecalibrationTable {
+    pub const MAX_REPORTED_QUALITY: u8 = 93;
+    pub const MAX_RECALIBRATED_QUALITY: u8 = 60;
+    pub const MIN_OBSERVATIONS_PER_BIN: u64 = 100;
+    pub const CYCLE_BIN_SIZE: usize = 10;
+    // Cycles past N_CYCLE_BINS * CYCLE_BIN_SIZE share the final bin, which keeps long reads bounded
+    pub const N_CYCLE_BINS: usize = 50;
+    // 16 dinucleotides plus one bin for the first base of a read or ambiguous bases
+    pub const N_CONTEXTS: usize = 17;
+
+    /// Reference positions where the fraction of mismatching bases is greater than this are
+    /// treated as potential variants and are not used to learn the error model
+    const MAX_MISMATCH_FRACTION: f64 = 0.2;
+    const MIN_SITE_DEPTH: u32 = 3;
+    const WINDOW_SIZE: usize = 50000;
+    pub const DEFAULT_MAX_OBSERVATIONS_PER_SAMPLE: u64 = 10_000_000;
+
+    pub fn from_sample_tables(samples: Vec<SampleRecalibrationTable>) -> Self {
+        Self { samples }
+    }
+
+    pub fn sample_table(&self, sample_index: usize) -> Option<&SampleRecalibrationTable> {
+        self.samples.get(sample_index)
+    }
+
+    /// Replaces the qualities of a read, given in the orientation stored in the BAM record, with
+    /// their recalibrated values. Reads that have been hard clipped, e.g. to an assembly region,
+    /// keep the length of their clips in `hard_clips` so that cycles are counted from the start of
+    /// the original read. Samples without a model are left unchanged.
+    pub fn recalibrate(
+        &self,
+        sample_index: usize,
+        bases: &[u8],
+        quals: &mut [u8],
+        hard_clips: (usize, usize),
+        is_reverse: bool,
+    ) {
+        let table = match self.samples.get(sample_index) {
+            Some(table) => table,
+            None => return,
+        };
+
+        let read_length = quals.len();
+        for offset in 0..read_length {
+            quals[offset] = table.recalibrate_quality(
+                quals[offset],
+                Self::cycle_bin(offset, read_length, hard_clips, is_reverse),
+                Self::context(bases, offset, is_reverse),
+            );
+        }
+    }
+
+    /// The binned position of a base in the order that it was sequenced. `offset` and
+    /// `read_length` refer to the bases stored in the record, which include soft clips, and
+    /// `hard_clips` are the bases hard clipped from the start and end of the record. Reverse
+    /// strand reads were sequenced from the end of the record
+    pub fn cycle_bin(
+        offset: usize,
+        read_length: usize,
+        hard_clips: (usize, usize),
+        is_reverse: bool,
+    ) -> usize {
+        let (leading_hard_clips, trailing_hard_clips) = hard_clips;
+        let cycle = if is_reverse {
+            trailing_hard_clips + read_length - 1 - offset
+        } else {
+            leading_hard_clips + offset
+        };
+        min(cycle / Self::CYCLE_BIN_SIZE, Self::N_CYCLE_BINS - 1)
+    }
+
+    /// The number of bases hard clipped from the start and end of a record
+    pub fn hard_clips(cigar: &CigarStringView) -> (usize, usize) {
+        let leading = match cigar.first() {
+            Some(Cigar::HardClip(len)) => *len as usize,
+            _ => 0,
+        };
+        let trailing = match cigar.last() {
+            Some(Cigar::HardClip(len)) if cigar.len() > 1 => *len as usize,
+            _ => 0,
+        };
+        (leading, trailing)
+    }
+
+    /// The dinucleotide made up of the previously sequenced base and the current base. Reverse
+    /// strand reads are stored reverse complemented, so their previous base is the next base in
+    /// the record and both bases are complemented
+    pub fn context(bases: &[u8], offset: usize, is_reverse: bool) -> usize {
+        let no_context = Self::N_CONTEXTS - 1;
+        let (previous, current) = if is_reverse {
+            if offset + 1 >= bases.len() {
+                return no_context;
+            }
+            (
+                Self::complement_index(bases[offset + 1]),
+                Self::complement_index(bases[offset]),
+            )
+        } else {
+            if offset == 0 || offset >= bases.len() {
+                return no_context;
+            }
+            (Self::base_index(bases[offset - 1]), Self::base_index(bases[offset]))
+        };
+
+        match (previous, current) {
+            (Some(previous), Some(current)) => previous * 4 + current,
+            _ => no_context,
+        }
+    }
+
+    fn base_index(base: u8) -> Option<usize> {
+        match base.to_ascii_uppercase() {
+            b'A' => Some(0),
+            b'C' => Some(1),
+            b'G' => Some(2),
+            b'T' => Some(3),
+            _ => None,
+        }
+    }
+
+    fn complement_index(base: u8) -> Option<usize> {
+        Self::base_index(base).map(|index| 3 - index)
+    }
+
+    /// First pass over the BAM files of each sample. Reads are compared to the reference at
+    /// sites where the pileup shows little evidence of variation, and every mismatch at these
+    /// sites is counted as a sequencing error. At most `max_observations_per_sample` bases are
+    /// observed per sample.
+    pub fn learn_from_bams(
+        indexed_bam_readers: &[String],
+        tids: &[usize],
+        reference_reader: &ReferenceReader,
+        ref_idx: usize,
+        flag_filters: &FlagFilter,
+        min_mapq: u8,
+        short_sample_count: usize,
+        long_sample_count: usize,
+        min_long_read_size: usize,
+        min_long_read_average_base_qual: usize,
+        n_threads: usize,
+        reader_threads: usize,
+        max_observations_per_sample: u64,
+    ) -> Self {
+        let samples = indexed_bam_readers
+            .par_iter()
+            .enumerate()
+            .map(|(sample_idx, bam_path)| {
+                let read_type = if sample_idx >= short_sample_count
+                    && sample_idx < short_sample_count + long_sample_count
+                {
+                    ReadType::Long
+                } else {
+                    ReadType::Short
+                };
+
+                let mut bam_reader = generate_indexed_named_bam_readers_from_bam_files(
+                    vec![bam_path.as_str()],
+                    n_threads as u32,
+                )
+                .into_iter()
+                .next()
+                .unwrap();
+                bam_reader.set_threads(reader_threads);
+                let mut reference_reader = reference_reader.clone();
+                let mut table = SampleRecalibrationTable::new();
+
+                'contigs: for tid in tids.iter() {
+                    if reference_reader
+                        .fetch_contig_from_reference_by_tid(*tid, ref_idx)
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    reference_reader.read_sequence_to_vec();
+                    let contig_length = reference_reader.current_sequence.len();
+
+                    let mut window_start = 0;
+                    while window_start < contig_length {
+                        let window_end = min(window_start + Self::WINDOW_SIZE, contig_length);
+                        Self::learn_window(
+                            &mut bam_reader,
+                            &mut table,
+                            &reference_reader.current_sequence,
+                            *tid,
+                            window_start,
+                            window_end,
+                            flag_filters,
+                            min_mapq,
+                            read_type,
+                            min_long_read_size,
+                            min_long_read_average_base_qual,
+                        );
+
+                        if table.observations() >= max_observations_per_sample {
+                            break 'contigs;
+                        }
+                        window_start = window_end;
+                    }
+                }
+
+                debug!(
+                    "Learned base quality error model for sample {} from {} bases",
+                    sample_idx,
+                    table.observations()
+                );
+                table
+            })
+            .collect::<Vec<SampleRecalibrationTable>>();
+
+        Self::from_sample_tables(samples)
+    }
+
+    fn learn_window<R: IndexedNamedBamReader>(
+        bam_reader: &mut R,
+        table: &mut SampleRecalibrationTable,
+        reference: &[u8],
+        tid: usize,
+        window_start: usize,
+        window_end: usize,
+        flag_filters: &FlagFilter,
+        min_mapq: u8,
+        read_type: ReadType,
+        min_long_read_size: usize,
+        min_long_read_average_base_qual: usize,
+    ) {
+        if bam_reader
+            .fetch((tid as i32, window_start as i64, window_end as i64))
+            .is_err()
+        {
+            warn!(
+                "Unable to fetch {}:{}-{} while learning base quality error model",
+                tid, window_start, window_end
+            );
+            return;
+        }
+
+        let mut records = Vec::new();
+        let mut record = Record::new();
+        while bam_reader.read(&mut record) {
+            if ReadUtils::read_is_filtered(
+                &record,
+                flag_filters,
+                min_mapq,
+                read_type,
+                &None,
+                min_long_read_size,
+                min_long_read_average_base_qual,
+            ) {
+                continue;
+            }
+            records.push(record.clone());
+        }
+
+        // First find the sites that look invariant within this window
+        let window_length = window_end - window_start;
+        let mut depth = vec![0u32; window_length];
+        let mut mismatches = vec![0u32; window_length];
+        for record in records.iter() {
+            let bases = record.seq().as_bytes();
+            Self::for_each_aligned_base(record, |read_offset, ref_pos| {
+                if ref_pos < window_start || ref_pos >= window_end {
+                    return;
+                }
+                depth[ref_pos - window_start] += 1;
+                if bases[read_offset].to_ascii_uppercase()
+                    != reference[ref_pos].to_ascii_uppercase()
+                {
+                    mismatches[ref_pos - window_start] += 1;
+                }
+            });
+        }
+
+        // Then count the errors at those sites
+        for record in records.iter() {
+            let bases = record.seq().as_bytes();
+            let quals = record.qual();
+            let is_reverse = record.is_reverse();
+            let hard_clips = Self::hard_clips(&record.cigar());
+            Self::for_each_aligned_base(record, |read_offset, ref_pos| {
+                if ref_pos < window_start || ref_pos >= window_end {
+                    return;
+                }
+                let site = ref_pos - window_start;
+                if depth[site] < Self::MIN_SITE_DEPTH
+                    || mismatches[site] as f64 / depth[site] as f64 > Self::MAX_MISMATCH_FRACTION
+                {
+                    return;
+                }
+                let (read_base, ref_base) = match (
+                    Self::base_index(bases[read_offset]),
+                    Self::base_index(reference[ref_pos]),
+                ) {
+                    (Some(read_base), Some(ref_base)) => (read_base, ref_base),
+                    _ => return,
+                };
+
+                table.add_observation(
+                    quals[read_offset],
+                    Self::cycle_bin(read_offset, bases.len(), hard_clips, is_reverse),
+                    Self::context(&bases, read_offset, is_reverse),
+                    read_base != ref_base,
+                );
+            });
+        }
+    }
+
+    /// Calls `f` with the read offset and reference position of every aligned base of the record
+    fn for_each_aligned_base<F: FnMut(usize, usize)>(record: &Record, mut f: F) {
+        let mut read_cursor = 0;
+        let mut ref_cursor = record.pos() as usize;
+        for cig in record.cigar().iter() {
+            match cig {
+                Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) => {
+                    for i in 0..*len as usize {
+                        f(read_cursor + i, ref_cursor + i);
+                    }
+                    read_cursor += *len as usize;
+                    ref_cursor += *len as usize;
+                }
+                Cigar::Ins(len) | Cigar::SoftClip(len) => {
+                    read_cursor += *len as usize;
+                }
+                Cigar::Del(len) | Cigar::RefSkip(len) => {
+                    ref_cursor += *len as usize;
+                }
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
@@ -7,6 +8,7 @@ use std::ops::Deref;
 
 use crate::processing::lorikeet_engine::ReadType;
 use crate::reads::cigar_utils::CigarUtils;
+use crate::reads::read_compression::{PackedBases, ReadCompression};
 use crate::reads::read_utils::ReadUtils;
 use crate::utils::simple_interval::Locatable;
 
@@ -30,6 +32,8 @@ pub struct BirdToolRead {
     pub read_type: ReadType,
     pub transient_attributes: HashMap<String, Vec<u8>>,
     pub bases: Vec<u8>,
+    // set once the bases have been packed, in which case `bases` is empty
+    pub packed_bases: Option<PackedBases>,
 }
 
 impl BirdToolRead {
@@ -41,6 +45,7 @@ impl BirdToolRead {
             read_type,
             transient_attributes: HashMap::new(),
             bases,
+            packed_bases: None,
         }
     }
 
@@ -53,12 +58,50 @@ impl BirdToolRead {
     ) {
         self.read.set(name, cigar, &bases, quals);
         self.bases = bases;
+        self.packed_bases = None;
     }
 
     pub fn seq(&self) -> &[u8] {
+        debug_assert!(
+            self.packed_bases.is_none(),
+            "Bases of a packed read must be accessed through unpacked_bases"
+        );
         self.bases.as_slice()
     }
 
+    /// The bases of the read, unpacking them if the read has been compressed
+    pub fn unpacked_bases(&self) -> Cow<'_, [u8]> {
+        match &self.packed_bases {
+            Some(packed_bases) => Cow::Owned(packed_bases.unpack()),
+            None => Cow::Borrowed(self.bases.as_slice()),
+        }
+    }
+
+    pub fn is_packed(&self) -> bool {
+        self.packed_bases.is_some()
+    }
+
+    /**
+     * Bins the base qualities and packs the bases of the read to reduce the memory it holds.
+     * Packed reads can only be used where bases are read through unpacked_bases, i.e. the copies
+     * of reads handed to the PairHMM.
+     */
+    pub fn compress(&mut self, compression: &ReadCompression) {
+        if let Some(quality_bins) = &compression.quality_bins {
+            let mut quals = self.read.qual().to_vec();
+            quality_bins.bin_all(&mut quals);
+            let qname = self.read.qname().to_vec();
+            let cigar = CigarString(self.read.cigar().0.clone());
+            let bases = self.read.seq().as_bytes();
+            self.read.set(&qname, Some(&cigar), &bases, &quals);
+        }
+
+        if compression.pack_bases && self.packed_bases.is_none() {
+            self.packed_bases = Some(PackedBases::pack(&self.bases));
+            self.bases = Vec::new();
+        }
+    }
+
     pub fn get_contig(&self) -> usize {
         self.read.tid() as usize
     }
@@ -227,7 +270,7 @@ impl Hash for BirdToolRead {
         self.read.qname().hash(state);
         self.read.qual().hash(state);
         self.sample_index.hash(state);
-        self.bases.hash(state);
+        self.unpacked_bases().hash(state);
     }
 }
 
@@ -0,0 +1,97 @@
+use rust_htslib::bam::{self, Read};
+
+use crate::utils::errors::BirdToolError;
+
+/// Robust estimate of the fragment length distribution of a paired end sample, using the median
+/// and the median absolute deviation of the insert sizes of properly paired reads so that the
+/// discordant pairs being searched for do not inflate the spread.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InsertSizeDistribution {
+    pub median: f64,
+    // MAD scaled to be consistent with the standard deviation of a normal distribution
+    pub stdev: f64,
+    pub n_pairs: usize,
+}
+
+impl InsertSizeDistribution {
+    /// Number of pairs sampled from each BAM file
+    pub const MAX_PAIRS: usize = 100_000;
+    const MAD_SCALE: f64 = 1.4826;
+
+    pub fn from_insert_sizes(mut insert_sizes: Vec<i64>) -> Option<Self> {
+        insert_sizes.retain(|insert_size| *insert_size > 0);
+        if insert_sizes.is_empty() {
+            return None;
+        }
+
+        let median = Self::median(&mut insert_sizes);
+        let mut deviations = insert_sizes
+            .iter()
+            .map(|insert_size| (*insert_size as f64 - median).abs().round() as i64)
+            .collect::<Vec<i64>>();
+        let stdev = Self::median(&mut deviations) * Self::MAD_SCALE;
+
+        Some(Self {
+            median,
+            stdev: stdev.max(1.0),
+            n_pairs: insert_sizes.len(),
+        })
+    }
+
+    /// Samples the insert sizes of the first properly paired reads on the given contigs
+    pub fn from_bam(
+        bam_path: &str,
+        tids: &[(u32, u64)],
+        min_mapq: u8,
+    ) -> Result<Option<Self>, BirdToolError> {
+        let mut reader = bam::IndexedReader::from_path(bam_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read BAM file {}: {}", bam_path, e))
+        })?;
+
+        let mut insert_sizes = Vec::new();
+        let mut record = bam::Record::new();
+        'contigs: for (tid, length) in tids.iter() {
+            reader
+                .fetch((*tid as i32, 0, *length as i64))
+                .map_err(|e| BirdToolError::IOError(format!("Unable to fetch tid {}: {}", tid, e)))?;
+            while let Some(result) = reader.read(&mut record) {
+                result.map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to read record of {}: {}", bam_path, e))
+                })?;
+                if record.is_proper_pair()
+                    && !record.is_secondary()
+                    && !record.is_supplementary()
+                    && !record.is_duplicate()
+                    && record.mapq() >= min_mapq
+                    && record.insert_size() > 0
+                {
+                    insert_sizes.push(record.insert_size());
+                    if insert_sizes.len() >= Self::MAX_PAIRS {
+                        break 'contigs;
+                    }
+                }
+            }
+        }
+
+        Ok(Self::from_insert_sizes(insert_sizes))
+    }
+
+    /// The largest insert size still considered concordant
+    pub fn upper_bound(&self, n_stdevs: f64) -> f64 {
+        self.median + n_stdevs * self.stdev
+    }
+
+    pub fn is_too_long(&self, insert_size: i64, n_stdevs: f64) -> bool {
+        insert_size.abs() as f64 > self.upper_bound(n_stdevs)
+    }
+
+    fn median(values: &mut [i64]) -> f64 {
+        values.sort_unstable();
+        let middle = values.len() / 2;
+        if values.len() % 2 == 0 {
+            (values[middle - 1] + values[middle]) as f64 / 2.0
+        } else {
+            values[middle] as f64
+        }
+    }
+}
@@ -1,7 +1,14 @@
 pub mod alignment_utils;
+pub mod base_recalibration;
 pub mod bird_tool_reads;
 pub mod cigar_builder;
 pub mod cigar_utils;
 pub mod clipping_op;
+pub mod insert_size_distribution;
 pub mod read_clipper;
+pub mod read_compression;
+pub mod read_end_profile;
+pub mod read_group_profiles;
+pub mod read_group_samples;
 pub mod read_utils;
+pub mod split_alignment_policy;
@@ -0,0 +1,136 @@
+/// Read bases packed four to a byte. Bases other than A, C, G and T are kept on the side.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct PackedBases {
+    packed: Vec<u8>,
+    len: usize,
+    // position and value of each base that is not A, C, G or T
+    exceptions: Vec<(u32, u8)>,
+}
+
+impl PackedBases {
+    const BASES: [u8; 4] = [b'A', b'C', b'G', b'T'];
+
+    pub fn pack(bases: &[u8]) -> Self {
+        let mut packed = vec![0u8; (bases.len() + 3) / 4];
+        let mut exceptions = Vec::new();
+        for (i, base) in bases.iter().enumerate() {
+            let code = match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => {
+                    exceptions.push((i as u32, *base));
+                    0
+                }
+            };
+            packed[i / 4] |= code << ((i % 4) * 2);
+        }
+
+        Self {
+            packed,
+            len: bases.len(),
+            exceptions,
+        }
+    }
+
+    pub fn unpack(&self) -> Vec<u8> {
+        let mut bases = (0..self.len)
+            .map(|i| Self::BASES[((self.packed[i / 4] >> ((i % 4) * 2)) & 0b11) as usize])
+            .collect::<Vec<u8>>();
+        for (position, base) in self.exceptions.iter() {
+            bases[*position as usize] = *base;
+        }
+        bases
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Bins base qualities into a small number of representative values, following the 8 level
+/// scheme used by Illumina sequencers. Binned qualities barely change read likelihoods but
+/// make reads sharing a sequence far more likely to share their qualities as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityBins {
+    // inclusive upper bound and representative quality of each bin, the last bin is open ended
+    bins: Vec<(u8, u8)>,
+}
+
+impl Default for QualityBins {
+    fn default() -> Self {
+        Self {
+            bins: vec![
+                (2, 2),
+                (9, 6),
+                (19, 15),
+                (24, 22),
+                (29, 27),
+                (34, 33),
+                (39, 37),
+                (u8::MAX, 40),
+            ],
+        }
+    }
+}
+
+impl QualityBins {
+    pub fn bin(&self, qual: u8) -> u8 {
+        self.bins
+            .iter()
+            .find(|(upper_bound, _)| qual <= *upper_bound)
+            .map(|(_, representative)| *representative)
+            .unwrap_or(qual)
+    }
+
+    pub fn bin_all(&self, quals: &mut [u8]) {
+        for qual in quals.iter_mut() {
+            *qual = self.bin(*qual);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+}
+
+/// How the quality modified copies of reads handed to the PairHMM are stored
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadCompression {
+    pub pack_bases: bool,
+    pub quality_bins: Option<QualityBins>,
+}
+
+impl ReadCompression {
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let flag = |id: &str| {
+            args.try_get_one::<bool>(id)
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false)
+        };
+
+        Self {
+            pack_bases: flag("pack-read-bases"),
+            quality_bins: if flag("bin-base-qualities") {
+                Some(QualityBins::default())
+            } else {
+                None
+            },
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.pack_bases || self.quality_bins.is_some()
+    }
+}
@@ -0,0 +1,385 @@
+use rust_htslib::bam::record::Cigar;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::reads::bird_tool_reads::BirdToolRead;
+use crate::reads::clipping_op::ClippingOp;
+use crate::reads::read_clipper::{ClippingRepresentation, ReadClipper};
+use crate::utils::errors::BirdToolError;
+use crate::utils::simple_interval::Locatable;
+
+/// Upper bounds of the soft clip length bins of a [`ReadEndProfile`]. Longer clips fall in a final
+/// open ended bin
+pub const CLIP_LENGTH_BINS: [usize; 3] = [10, 100, 1000];
+const N_CLIP_LENGTH_BINS: usize = CLIP_LENGTH_BINS.len() + 1;
+
+/// Soft clipping and trimming counts of a single sample
+#[derive(Debug, Default)]
+struct SampleEndCounts {
+    reads: AtomicUsize,
+    bases: AtomicUsize,
+    soft_clipped_reads: AtomicUsize,
+    soft_clipped_bases: AtomicUsize,
+    // soft clips at the left and right end of the alignment, binned by length
+    left_clips: [AtomicUsize; N_CLIP_LENGTH_BINS],
+    right_clips: [AtomicUsize; N_CLIP_LENGTH_BINS],
+    trimmed_reads: AtomicUsize,
+    trimmed_bases: AtomicUsize,
+}
+
+/// The soft clipping profile of a sample
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleEndProfile {
+    pub sample: usize,
+    pub reads: usize,
+    pub bases: usize,
+    pub soft_clipped_reads: usize,
+    pub soft_clipped_bases: usize,
+    pub left_clips: Vec<usize>,
+    pub right_clips: Vec<usize>,
+    pub trimmed_reads: usize,
+    pub trimmed_bases: usize,
+}
+
+impl SampleEndProfile {
+    pub fn soft_clipped_read_rate(&self) -> f64 {
+        Self::rate(self.soft_clipped_reads, self.reads)
+    }
+
+    pub fn soft_clipped_base_rate(&self) -> f64 {
+        Self::rate(self.soft_clipped_bases, self.bases)
+    }
+
+    fn rate(count: usize, total: usize) -> f64 {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    }
+}
+
+/// Soft clipping and end trimming of the reads of each sample as they enter assembly, counted once
+/// per active region. Every clone shares the same counts.
+#[derive(Debug, Clone)]
+pub struct ReadEndProfile {
+    counts: Arc<Vec<SampleEndCounts>>,
+}
+
+impl ReadEndProfile {
+    pub fn new(n_samples: usize) -> Self {
+        Self {
+            counts: Arc::new((0..n_samples).map(|_| SampleEndCounts::default()).collect()),
+        }
+    }
+
+    /// A profile if --soft-clip-profile or adaptive end trimming was requested
+    pub fn from_args(args: &clap::ArgMatches, n_samples: usize) -> Option<Self> {
+        let profile = args
+            .try_get_one::<bool>("soft-clip-profile")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        if profile || AdaptiveEndTrimmer::from_args(args).is_some() {
+            Some(Self::new(n_samples))
+        } else {
+            None
+        }
+    }
+
+    /// The bin of a soft clip of the given length
+    pub fn clip_length_bin(length: usize) -> usize {
+        CLIP_LENGTH_BINS
+            .iter()
+            .position(|upper| length <= *upper)
+            .unwrap_or(N_CLIP_LENGTH_BINS - 1)
+    }
+
+    /// The lengths of the soft clips at the left and right end of an alignment
+    pub fn soft_clip_lengths(cigar: &[Cigar]) -> (usize, usize) {
+        let clip_length = |op: Option<&Cigar>| match op {
+            Some(Cigar::SoftClip(length)) => *length as usize,
+            _ => 0,
+        };
+        let unclipped = cigar
+            .iter()
+            .filter(|op| !matches!(op, Cigar::HardClip(_)))
+            .collect::<Vec<&Cigar>>();
+        match unclipped.as_slice() {
+            [] => (0, 0),
+            [only] => (clip_length(Some(only)), 0),
+            [first, .., last] => (clip_length(Some(first)), clip_length(Some(last))),
+        }
+    }
+
+    /// Counts a read entering assembly
+    pub fn record_read(&self, read: &BirdToolRead) {
+        let counts = match self.counts.get(read.sample_index) {
+            Some(counts) => counts,
+            None => return,
+        };
+        let (left, right) = Self::soft_clip_lengths(&read.read.cigar().0);
+        counts.reads.fetch_add(1, Ordering::Relaxed);
+        counts.bases.fetch_add(read.len(), Ordering::Relaxed);
+        if left + right > 0 {
+            counts.soft_clipped_reads.fetch_add(1, Ordering::Relaxed);
+            counts
+                .soft_clipped_bases
+                .fetch_add(left + right, Ordering::Relaxed);
+        }
+        if left > 0 {
+            counts.left_clips[Self::clip_length_bin(left)].fetch_add(1, Ordering::Relaxed);
+        }
+        if right > 0 {
+            counts.right_clips[Self::clip_length_bin(right)].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts the bases trimmed from a read of a sample
+    pub fn record_trim(&self, sample: usize, trimmed_bases: usize) {
+        if trimmed_bases == 0 {
+            return;
+        }
+        if let Some(counts) = self.counts.get(sample) {
+            counts.trimmed_reads.fetch_add(1, Ordering::Relaxed);
+            counts
+                .trimmed_bases
+                .fetch_add(trimmed_bases, Ordering::Relaxed);
+        }
+    }
+
+    pub fn samples(&self) -> Vec<SampleEndProfile> {
+        let load = |counter: &AtomicUsize| counter.load(Ordering::Relaxed);
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(sample, counts)| SampleEndProfile {
+                sample,
+                reads: load(&counts.reads),
+                bases: load(&counts.bases),
+                soft_clipped_reads: load(&counts.soft_clipped_reads),
+                soft_clipped_bases: load(&counts.soft_clipped_bases),
+                left_clips: counts.left_clips.iter().map(load).collect(),
+                right_clips: counts.right_clips.iter().map(load).collect(),
+                trimmed_reads: load(&counts.trimmed_reads),
+                trimmed_bases: load(&counts.trimmed_bases),
+            })
+            .collect()
+    }
+
+    /// Writes the profile of every sample to `<reference>_soft_clipping.tsv`
+    pub fn write(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        sample_names: &[&str],
+    ) -> Result<(), BirdToolError> {
+        let write_error = |e: std::io::Error| {
+            BirdToolError::DebugError(format!("Unable to write to file {:?}", e))
+        };
+        let file_name = format!("{}/{}_soft_clipping.tsv", output_prefix, reference_name);
+        let file = File::create(Path::new(&file_name)).map_err(|e| {
+            BirdToolError::DebugError(format!("Cannot create file {}: {:?}", file_name, e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let mut bin_names = Vec::with_capacity(N_CLIP_LENGTH_BINS);
+        let mut lower = 1;
+        for upper in CLIP_LENGTH_BINS.iter() {
+            bin_names.push(format!("{}_{}", lower, upper));
+            lower = upper + 1;
+        }
+        bin_names.push(format!("over_{}", lower - 1));
+        let bin_header = |end: &str| {
+            bin_names
+                .iter()
+                .map(|bin| format!("{}_clips_{}", end, bin))
+                .collect::<Vec<String>>()
+                .join("\t")
+        };
+        writeln!(
+            writer,
+            "sample\treads\tsoft_clipped_reads\tsoft_clipped_read_rate\tbases\t\
+            soft_clipped_bases\tsoft_clipped_base_rate\t{}\t{}\ttrimmed_reads\ttrimmed_bases",
+            bin_header("left"),
+            bin_header("right")
+        )
+        .map_err(write_error)?;
+
+        let join = |counts: &[usize]| {
+            counts
+                .iter()
+                .map(|count| count.to_string())
+                .collect::<Vec<String>>()
+                .join("\t")
+        };
+        for profile in self.samples() {
+            let sample_name = sample_names
+                .get(profile.sample)
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| (profile.sample + 1).to_string());
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{:.6}\t{}\t{}\t{:.6}\t{}\t{}\t{}\t{}",
+                sample_name,
+                profile.reads,
+                profile.soft_clipped_reads,
+                profile.soft_clipped_read_rate(),
+                profile.bases,
+                profile.soft_clipped_bases,
+                profile.soft_clipped_base_rate(),
+                join(&profile.left_clips),
+                join(&profile.right_clips),
+                profile.trimmed_reads,
+                profile.trimmed_bases
+            )
+            .map_err(write_error)?;
+        }
+        writer.flush().map_err(write_error)
+    }
+}
+
+/// Trims read ends whose mismatch rate exceeds `max_mismatch_rate` before assembly, considering
+/// tails of at least `min_tail_length` bases and at most half of the read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptiveEndTrimmer {
+    pub max_mismatch_rate: f64,
+    pub min_tail_length: usize,
+}
+
+impl AdaptiveEndTrimmer {
+    pub fn new(max_mismatch_rate: f64, min_tail_length: usize) -> Self {
+        Self {
+            max_mismatch_rate: max_mismatch_rate.clamp(0.0, 1.0),
+            min_tail_length: min_tail_length.max(1),
+        }
+    }
+
+    /// The trimmer requested by --end-trim-mismatch-rate, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let max_mismatch_rate = *args
+            .try_get_one::<f64>("end-trim-mismatch-rate")
+            .ok()
+            .flatten()?;
+        let min_tail_length = args
+            .try_get_one::<usize>("end-trim-min-length")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(10);
+        Some(Self::new(max_mismatch_rate, min_tail_length))
+    }
+
+    /// Whether each base of a read, in read order, mismatches the reference starting at the
+    /// 0-based `reference_start`. Inserted bases count as mismatches, while soft clipped bases,
+    /// ambiguous bases and bases outside of the reference are None
+    pub fn mismatch_flags(
+        cigar: &[Cigar],
+        read_bases: &[u8],
+        alignment_start: usize,
+        reference: &[u8],
+        reference_start: usize,
+    ) -> Vec<Option<bool>> {
+        let mut flags = Vec::with_capacity(read_bases.len());
+        let mut reference_position = alignment_start;
+        for op in cigar.iter() {
+            match op {
+                Cigar::Match(length) | Cigar::Equal(length) | Cigar::Diff(length) => {
+                    for _ in 0..*length {
+                        let read_base = read_bases.get(flags.len()).map(|b| b.to_ascii_uppercase());
+                        let reference_base = reference_position
+                            .checked_sub(reference_start)
+                            .and_then(|offset| reference.get(offset))
+                            .map(|b| b.to_ascii_uppercase());
+                        flags.push(match (read_base, reference_base) {
+                            (Some(b'N'), _) | (_, Some(b'N')) => None,
+                            (Some(read_base), Some(reference_base)) => {
+                                Some(read_base != reference_base)
+                            }
+                            _ => None,
+                        });
+                        reference_position += 1;
+                    }
+                }
+                Cigar::Ins(length) => flags.extend((0..*length).map(|_| Some(true))),
+                Cigar::SoftClip(length) => flags.extend((0..*length).map(|_| None)),
+                Cigar::Del(length) | Cigar::RefSkip(length) => {
+                    reference_position += *length as usize
+                }
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+        flags.truncate(read_bases.len());
+        flags
+    }
+
+    /// The number of bases to trim from the start and end of a read given its mismatch flags
+    pub fn tail_lengths(&self, flags: &[Option<bool>]) -> (usize, usize) {
+        let max_trim = flags.len() / 2;
+        let best_tail = |tail: &mut dyn Iterator<Item = &Option<bool>>| {
+            let mut score = 0.0;
+            let mut informative = 0;
+            let mut best = (0.0, 0);
+            for (idx, flag) in tail.take(max_trim).enumerate() {
+                if let Some(mismatch) = flag {
+                    informative += 1;
+                    score += if *mismatch {
+                        1.0 - self.max_mismatch_rate
+                    } else {
+                        -self.max_mismatch_rate
+                    };
+                }
+                if informative >= self.min_tail_length && score > best.0 {
+                    best = (score, idx + 1);
+                }
+            }
+            best.1
+        };
+        (
+            best_tail(&mut flags.iter()),
+            best_tail(&mut flags.iter().rev()),
+        )
+    }
+
+    /// Trims the ends of a read aligned to `reference`, which starts at the 0-based
+    /// `reference_start`. Returns the read and the number of bases trimmed from it
+    pub fn trim(
+        &self,
+        read: BirdToolRead,
+        reference: &[u8],
+        reference_start: usize,
+    ) -> (BirdToolRead, usize) {
+        if read.is_empty() || read.read.is_unmapped() {
+            return (read, 0);
+        }
+        let flags = Self::mismatch_flags(
+            &read.read.cigar().0,
+            &read.unpacked_bases(),
+            read.get_start(),
+            reference,
+            reference_start,
+        );
+        let (left, right) = self.tail_lengths(&flags);
+        if left + right == 0 {
+            return (read, 0);
+        }
+
+        let read_length = read.len();
+        let mut clipper = ReadClipper::new(read);
+        // the end is clipped first so that the read coordinates of the start do not change
+        if right > 0 {
+            clipper.add_op(ClippingOp::new(read_length - right, read_length - 1));
+        }
+        if left > 0 {
+            clipper.add_op(ClippingOp::new(0, left - 1));
+        }
+        (
+            clipper.clip_read(ClippingRepresentation::HardclipBases),
+            left + right,
+        )
+    }
+}
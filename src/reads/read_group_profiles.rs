@@ -0,0 +1,166 @@
+use rust_htslib::bam::record::Aux;
+use std::collections::HashMap;
+
+use crate::processing::lorikeet_engine::ReadType;
+use crate::reads::bird_tool_reads::BirdToolRead;
+use crate::utils::errors::BirdToolError;
+
+/// Sequencing platform given by the PL field of a read group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SequencingPlatform {
+    Illumina,
+    OxfordNanopore,
+    PacBio,
+    Unknown,
+}
+
+impl SequencingPlatform {
+    pub fn from_pl(platform: &str) -> Self {
+        match platform.to_ascii_uppercase().as_str() {
+            "ILLUMINA" => Self::Illumina,
+            "ONT" | "NANOPORE" | "OXFORDNANOPORE" => Self::OxfordNanopore,
+            "PACBIO" => Self::PacBio,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The platform assumed for reads that do not belong to a read group with a known platform
+    pub fn from_read_type(read_type: ReadType) -> Self {
+        match read_type {
+            ReadType::Short => Self::Illumina,
+            ReadType::Long => Self::OxfordNanopore,
+        }
+    }
+
+    pub fn to_key(&self) -> &str {
+        match self {
+            Self::Illumina => "ILLUMINA",
+            Self::OxfordNanopore => "ONT",
+            Self::PacBio => "PACBIO",
+            Self::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// The fields of a @RG header line that determine how its reads are treated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadGroupProfile {
+    pub platform: SequencingPlatform,
+    pub platform_unit: Option<String>,
+}
+
+/// Read group profiles of each sample, used to weight the likelihoods of reads by the sequencing
+/// platform that produced them. Without weighting, a sample mixing short and long reads lets the
+/// abundant but noisy long reads outvote the short reads at SNVs.
+#[derive(Debug, Clone)]
+pub struct ReadGroupProfiles {
+    profiles_by_sample: Vec<HashMap<Vec<u8>, ReadGroupProfile>>,
+    weights: HashMap<SequencingPlatform, f64>,
+}
+
+impl ReadGroupProfiles {
+    pub fn new(
+        profiles_by_sample: Vec<HashMap<Vec<u8>, ReadGroupProfile>>,
+        weights: HashMap<SequencingPlatform, f64>,
+    ) -> Self {
+        Self {
+            profiles_by_sample,
+            weights,
+        }
+    }
+
+    /// The platform weights given with --read-group-weights, if any
+    pub fn weights_from_args(
+        args: &clap::ArgMatches,
+    ) -> Result<Option<HashMap<SequencingPlatform, f64>>, BirdToolError> {
+        args.try_get_one::<String>("read-group-weights")
+            .ok()
+            .flatten()
+            .map(|weights| Self::parse_weights(weights))
+            .transpose()
+    }
+
+    /// Fails on malformed --read-group-weights before any genome is run
+    pub fn check_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        Self::weights_from_args(args).map(|_| ())
+    }
+
+    /// Parses the platform weights given as a comma separated list, e.g. `ILLUMINA=1.0,ONT=0.5`
+    pub fn parse_weights(weights: &str) -> Result<HashMap<SequencingPlatform, f64>, BirdToolError> {
+        weights
+            .split(',')
+            .filter(|entry| !entry.trim().is_empty())
+            .map(|entry| {
+                let invalid = || {
+                    BirdToolError::ConfigError(format!(
+                        "Invalid read group weight '{}', expected PLATFORM=WEIGHT",
+                        entry
+                    ))
+                };
+                let (platform, weight) = entry.split_once('=').ok_or_else(invalid)?;
+                let weight = weight.trim().parse::<f64>().map_err(|_| invalid())?;
+                if !(0.0..=1.0).contains(&weight) {
+                    return Err(invalid());
+                }
+                Ok((SequencingPlatform::from_pl(platform.trim()), weight))
+            })
+            .collect()
+    }
+
+    /// Collects the ID, PL, and PU fields of every @RG line of a SAM header
+    pub fn read_groups_from_header(header_text: &[u8]) -> HashMap<Vec<u8>, ReadGroupProfile> {
+        let header_text = String::from_utf8_lossy(header_text);
+        header_text
+            .lines()
+            .filter(|line| line.starts_with("@RG"))
+            .filter_map(|line| {
+                let mut id = None;
+                let mut platform = SequencingPlatform::Unknown;
+                let mut platform_unit = None;
+                for field in line.split('\t').skip(1) {
+                    match field.split_once(':') {
+                        Some(("ID", value)) => id = Some(value.as_bytes().to_vec()),
+                        Some(("PL", value)) => platform = SequencingPlatform::from_pl(value),
+                        Some(("PU", value)) => platform_unit = Some(value.to_string()),
+                        _ => continue,
+                    }
+                }
+
+                id.map(|id| {
+                    (
+                        id,
+                        ReadGroupProfile {
+                            platform,
+                            platform_unit,
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// The platform of the read group of a read. Reads outside of any read group, or in a read
+    /// group without a known platform, are assumed to come from the platform of their read type
+    pub fn platform(&self, read: &BirdToolRead) -> SequencingPlatform {
+        let platform = match read.read.aux(b"RG") {
+            Ok(Aux::String(read_group)) => self
+                .profiles_by_sample
+                .get(read.sample_index)
+                .and_then(|profiles| profiles.get(read_group.as_bytes()))
+                .map(|profile| profile.platform)
+                .unwrap_or(SequencingPlatform::Unknown),
+            _ => SequencingPlatform::Unknown,
+        };
+
+        match platform {
+            SequencingPlatform::Unknown => SequencingPlatform::from_read_type(read.read_type),
+            platform => platform,
+        }
+    }
+
+    /// The factor applied to the log likelihoods of a read. Platforms without a weight are
+    /// left as is
+    pub fn weight(&self, read: &BirdToolRead) -> f64 {
+        *self.weights.get(&self.platform(read)).unwrap_or(&1.0)
+    }
+}
@@ -0,0 +1,96 @@
+use rust_htslib::bam::{self, Read};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::utils::utils::clean_sample_name;
+
+/// Sample names of the BAM files of a run, taken from the SM field of their read groups when it
+/// names a single sample, otherwise from the file path. Colliding names are made unique.
+pub struct ReadGroupSamples;
+
+impl ReadGroupSamples {
+    /// The sample named by the SM fields of the @RG lines of a SAM header, or None if there are
+    /// no SM fields or they name more than one sample
+    pub fn sample_from_header(header_text: &[u8]) -> Option<String> {
+        let header_text = String::from_utf8_lossy(header_text);
+        let samples = header_text
+            .lines()
+            .filter(|line| line.starts_with("@RG"))
+            .filter_map(|line| {
+                line.split('\t')
+                    .skip(1)
+                    .find_map(|field| field.strip_prefix("SM:"))
+                    .map(|sample| sample.trim().to_string())
+            })
+            .filter(|sample| !sample.is_empty())
+            .collect::<BTreeSet<String>>();
+
+        if samples.len() == 1 {
+            samples.into_iter().next()
+        } else {
+            if samples.len() > 1 {
+                debug!("Read groups name multiple samples {:?}", &samples);
+            }
+            None
+        }
+    }
+
+    /// The names of the samples of the given BAM files, in sample order. BAM files that can not
+    /// be read fall back to their file names
+    pub fn from_bams(bam_paths: &[String]) -> Vec<String> {
+        let read_group_samples = bam_paths
+            .iter()
+            .map(|bam_path| match bam::Reader::from_path(bam_path) {
+                Ok(reader) => Self::sample_from_header(reader.header().as_bytes()),
+                Err(e) => {
+                    debug!("Unable to read header of {}: {:?}", bam_path, e);
+                    None
+                }
+            })
+            .collect::<Vec<Option<String>>>();
+
+        Self::resolve(bam_paths, &read_group_samples)
+    }
+
+    /// Combines the read group sample of each BAM file with the names derived from the file paths,
+    /// ensuring that every sample ends up with a unique name
+    pub fn resolve(bam_paths: &[String], read_group_samples: &[Option<String>]) -> Vec<String> {
+        let file_names = (0..bam_paths.len())
+            .map(|sample_idx| clean_sample_name(sample_idx, bam_paths).to_string())
+            .collect::<Vec<String>>();
+
+        let mut names = file_names
+            .iter()
+            .zip(read_group_samples.iter())
+            .map(|(file_name, sample)| sample.clone().unwrap_or_else(|| file_name.clone()))
+            .collect::<Vec<String>>();
+
+        let counts = Self::name_counts(&names);
+        for (sample_idx, name) in names.iter_mut().enumerate() {
+            if counts[name.as_str()] > 1 && read_group_samples[sample_idx].is_some() {
+                warn!(
+                    "Sample name {} from the read groups of {} is shared with another BAM file, \
+                    using the file name instead",
+                    name, &bam_paths[sample_idx]
+                );
+                *name = file_names[sample_idx].clone();
+            }
+        }
+
+        let counts = Self::name_counts(&names);
+        for (sample_idx, name) in names.iter_mut().enumerate() {
+            if counts[name.as_str()] > 1 {
+                *name = format!("{}_{}", name, sample_idx + 1);
+            }
+        }
+
+        names
+    }
+
+    fn name_counts(names: &[String]) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for name in names {
+            *counts.entry(name.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
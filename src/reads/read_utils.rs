@@ -41,7 +41,7 @@ impl ReadUtils {
 
         let cigar = record.cigar();
 
-        let mut result = record.is_secondary()
+        let mut result = (!flag_filters.include_secondary && record.is_secondary())
             || (!flag_filters.include_supplementary
                 && record.is_supplementary())
             || (record.is_paired()
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use crate::reads::bird_tool_reads::BirdToolRead;
+
+/// How secondary and supplementary alignments of a read are treated once they pass the flag
+/// filters. A long read split across a structural variant, or a read placed in several copies of
+/// a repeat, contributes one observation per alignment and is counted more than once towards
+/// the allele depths of a sample unless another policy than `CountAll` is used. Weights set by
+/// `WeightByMapq` scale both the likelihoods of an alignment and its count in AD.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitAlignmentPolicy {
+    /// Every alignment is used and counted, the behaviour before split alignments were handled
+    CountAll,
+    /// Only primary alignments are used
+    Skip,
+    /// All alignments are used, but each read contributes at most one alignment to a region,
+    /// preferring the primary alignment and otherwise the one with the highest MAPQ
+    CountOncePerFragment,
+    /// All alignments are used, with the likelihoods of each weighted by the probability that it
+    /// is the correct placement of the read given the MAPQs of all of its alignments in the region
+    WeightByMapq,
+}
+
+impl SplitAlignmentPolicy {
+    /// Transient attribute holding the weight of an alignment under `WeightByMapq`
+    pub const WEIGHT_ATTRIBUTE: &'static str = "AlignmentWeight";
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count-all" => Some(Self::CountAll),
+            "skip" => Some(Self::Skip),
+            "count-once-per-fragment" => Some(Self::CountOncePerFragment),
+            "weight-by-mapq" => Some(Self::WeightByMapq),
+            _ => None,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        match args
+            .try_get_one::<String>("split-alignment-policy")
+            .ok()
+            .flatten()
+        {
+            Some(name) => Self::from_name(name)
+                .unwrap_or_else(|| panic!("Unknown split alignment policy {}", name)),
+            None => Self::CountAll,
+        }
+    }
+
+    /// Applies the policy to the reads of one sample in a region
+    pub fn apply(&self, reads: &mut Vec<BirdToolRead>) {
+        match self {
+            Self::CountAll => {}
+            Self::Skip => reads.retain(|read| Self::is_primary(read)),
+            Self::CountOncePerFragment => {
+                let mut best_alignments: HashMap<(Vec<u8>, bool), usize> = HashMap::new();
+                for (index, read) in reads.iter().enumerate() {
+                    best_alignments
+                        .entry(Self::segment_key(read))
+                        .and_modify(|best| {
+                            if Self::preference(read) > Self::preference(&reads[*best]) {
+                                *best = index;
+                            }
+                        })
+                        .or_insert(index);
+                }
+
+                let mut index = 0;
+                reads.retain(|read| {
+                    let retain = best_alignments[&Self::segment_key(read)] == index;
+                    index += 1;
+                    retain
+                });
+            }
+            Self::WeightByMapq => {
+                let mut totals: HashMap<(Vec<u8>, bool), (usize, f64)> = HashMap::new();
+                for read in reads.iter() {
+                    let total = totals.entry(Self::segment_key(read)).or_insert((0, 0.0));
+                    total.0 += 1;
+                    total.1 += Self::placement_probability(read.read.mapq());
+                }
+
+                for read in reads.iter_mut() {
+                    let (count, total) = totals[&Self::segment_key(read)];
+                    if count < 2 {
+                        continue;
+                    }
+                    let placement_weight = if total > 0.0 {
+                        Self::placement_probability(read.read.mapq()) / total
+                    } else {
+                        1.0 / count as f64
+                    };
+                    // keep any weight given to the read when it was redistributed between genomes
+                    let weight = placement_weight * Self::alignment_weight(read);
+                    read.set_transient_attribute(
+                        Self::WEIGHT_ATTRIBUTE.to_string(),
+                        weight.to_le_bytes().to_vec(),
+                    );
+                }
+            }
+        }
+    }
+
+    /// The weight given to a read's likelihoods and allele depths, 1 unless it was set by
+    /// `WeightByMapq` or when the read was redistributed between genomes
+    pub fn alignment_weight(read: &BirdToolRead) -> f64 {
+        read.transient_attributes
+            .get(Self::WEIGHT_ATTRIBUTE)
+            .and_then(|bytes| bytes.as_slice().try_into().ok())
+            .map(f64::from_le_bytes)
+            .unwrap_or(1.0)
+    }
+
+    fn is_primary(read: &BirdToolRead) -> bool {
+        !read.read.is_secondary() && !read.read.is_supplementary()
+    }
+
+    // alignments of the same sequenced read share a name and, for pairs, a mate flag
+    fn segment_key(read: &BirdToolRead) -> (Vec<u8>, bool) {
+        (read.name().to_vec(), read.read.is_last_in_template())
+    }
+
+    fn preference(read: &BirdToolRead) -> (bool, u8) {
+        (Self::is_primary(read), read.read.mapq())
+    }
+
+    fn placement_probability(mapq: u8) -> f64 {
+        1.0 - 10.0_f64.powf(-(mapq as f64) / 10.0)
+    }
+}
@@ -0,0 +1,197 @@
+use std::borrow::Cow;
+
+use crate::bam_parsing::CONCATENATED_FASTA_FILE_SEPARATOR;
+use crate::utils::errors::BirdToolError;
+
+/// The character joining genome and contig names in the concatenated reference, i.e.
+/// `genome~contig`. It is chosen once per run and carried with the genomes of the run in
+/// `GenomesAndContigs`. Names are split on the first separator, so contig names can contain the
+/// separator. Genome names containing the separator or the escape character have them written
+/// as `%` and two hex digits, e.g. `genome%7E1~contig`, and read back unescaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GenomeSeparator {
+    separator: char,
+}
+
+impl Default for GenomeSeparator {
+    fn default() -> Self {
+        Self { separator: '~' }
+    }
+}
+
+impl GenomeSeparator {
+    /// Separators that can be used, all of which are valid in SAM reference names and VCF
+    /// contig IDs. '|' is left out as it joins contig and genome names in consensus FASTA headers
+    pub const CANDIDATES: [char; 3] = ['~', '^', '@'];
+
+    /// Starts an escaped character in a genome name
+    pub const ESCAPE: char = '%';
+
+    /// Characters that are not allowed in VCF contig IDs
+    const INVALID_VCF_CONTIG_CHARACTERS: [char; 12] =
+        [',', '<', '>', '[', ']', '{', '}', '(', ')', '\'', '"', '\\'];
+
+    pub fn new(separator: char) -> Self {
+        Self { separator }
+    }
+
+    pub fn as_char(&self) -> char {
+        self.separator
+    }
+
+    /// Chooses the separator for a set of genomes. With `auto` the first candidate that is in no
+    /// genome name is used, falling back to the default and escaping it in genome names when
+    /// every candidate is taken. A requested separator is used as is
+    pub fn select(requested: &str, genome_names: &[String]) -> Result<Self, BirdToolError> {
+        if requested == "auto" {
+            let separator = Self::CANDIDATES
+                .iter()
+                .copied()
+                .find(|separator| {
+                    !genome_names
+                        .iter()
+                        .any(|genome_name| genome_name.contains(*separator))
+                })
+                .map(Self::new)
+                .unwrap_or_default();
+            return Ok(separator);
+        }
+
+        let mut chars = requested.chars();
+        match (chars.next(), chars.next()) {
+            (Some(separator), None) if Self::CANDIDATES.contains(&separator) => {
+                Ok(Self::new(separator))
+            }
+            _ => Err(BirdToolError::ConfigError(format!(
+                "Invalid genome separator '{}', expected auto or one of {:?}",
+                requested,
+                Self::CANDIDATES
+            ))),
+        }
+    }
+
+    /// Selects the separator requested with --genome-separator for the given genomes
+    pub fn from_args(
+        args: &clap::ArgMatches,
+        genome_names: &[String],
+    ) -> Result<Self, BirdToolError> {
+        let requested = args
+            .try_get_one::<String>("genome-separator")
+            .ok()
+            .flatten()
+            .map(|requested| requested.as_str())
+            .unwrap_or("auto");
+        let separator = Self::select(requested, genome_names)?;
+        if requested == "auto"
+            && separator.separator.to_string() != CONCATENATED_FASTA_FILE_SEPARATOR
+        {
+            warn!(
+                "Genome names contain '{}', using '{}' to separate genome and contig names",
+                CONCATENATED_FASTA_FILE_SEPARATOR, separator.separator
+            );
+        }
+        if let Some(genome_name) = genome_names
+            .iter()
+            .find(|genome_name| genome_name.contains(separator.separator))
+        {
+            warn!(
+                "Genome name {} contains the genome separator '{}', it is written as {} in \
+                the concatenated reference",
+                genome_name,
+                separator.separator,
+                separator.escape(genome_name)
+            );
+        }
+        Ok(separator)
+    }
+
+    /// Checks a contig name can be written to SAM and VCF files unchanged
+    pub fn validate_contig_name(contig_name: &str) -> Result<(), BirdToolError> {
+        if contig_name.is_empty()
+            || contig_name.starts_with('*')
+            || contig_name.starts_with('=')
+            || contig_name.contains(|c: char| {
+                !c.is_ascii_graphic() || Self::INVALID_VCF_CONTIG_CHARACTERS.contains(&c)
+            })
+        {
+            return Err(BirdToolError::DebugError(format!(
+                "Contig name '{}' contains characters that are not allowed in SAM or VCF files",
+                contig_name
+            )));
+        }
+        Ok(())
+    }
+
+    /// A genome name with the separator and escape character escaped
+    pub fn escape<'a>(&self, genome_name: &'a str) -> Cow<'a, str> {
+        if !genome_name.contains(|c| c == self.separator || c == Self::ESCAPE) {
+            return Cow::Borrowed(genome_name);
+        }
+        let mut escaped = String::with_capacity(genome_name.len() + 4);
+        for c in genome_name.chars() {
+            if c == self.separator || c == Self::ESCAPE {
+                escaped.push_str(&format!("{}{:02X}", Self::ESCAPE, c as u32));
+            } else {
+                escaped.push(c);
+            }
+        }
+        Cow::Owned(escaped)
+    }
+
+    /// Reverses `escape`. Escape characters that are not followed by two hex digits are kept
+    pub fn unescape(genome_name: &str) -> Cow<'_, str> {
+        if !genome_name.contains(Self::ESCAPE) {
+            return Cow::Borrowed(genome_name);
+        }
+        let mut unescaped = String::with_capacity(genome_name.len());
+        let mut rest = genome_name;
+        while let Some(position) = rest.find(Self::ESCAPE) {
+            unescaped.push_str(&rest[..position]);
+            let code = rest
+                .get(position + 1..position + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            match code {
+                Some(code) => {
+                    unescaped.push(code as char);
+                    rest = &rest[position + 3..];
+                }
+                None => {
+                    unescaped.push(Self::ESCAPE);
+                    rest = &rest[position + 1..];
+                }
+            }
+        }
+        unescaped.push_str(rest);
+        Cow::Owned(unescaped)
+    }
+
+    /// The name of a contig in the concatenated reference
+    pub fn join(&self, genome_name: &str, contig_name: &str) -> String {
+        format!(
+            "{}{}{}",
+            self.escape(genome_name),
+            self.separator,
+            contig_name
+        )
+    }
+
+    /// Splits a concatenated reference name into its unescaped genome name and contig name
+    pub fn split<'a>(&self, name: &'a str) -> Option<(Cow<'a, str>, &'a str)> {
+        name.split_once(self.separator)
+            .map(|(genome, contig)| (Self::unescape(genome), contig))
+    }
+
+    /// The genome of a concatenated reference name, or the whole name if it has no separator
+    pub fn genome<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        self.split(name)
+            .map(|(genome, _)| genome)
+            .unwrap_or(Cow::Borrowed(name))
+    }
+
+    /// The contig of a concatenated reference name, or the whole name if it has no separator
+    pub fn contig<'a>(&self, name: &'a str) -> &'a str {
+        name.split_once(self.separator)
+            .map(|(_, contig)| contig)
+            .unwrap_or(name)
+    }
+}
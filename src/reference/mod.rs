@@ -1,3 +1,8 @@
+pub mod genome_separator;
+pub mod partial_order_alignment;
+pub mod reference_cache;
+pub mod reference_mask;
 pub mod reference_reader;
 pub mod reference_reader_utils;
 pub mod reference_writer;
+pub mod strain_polisher;
@@ -0,0 +1,305 @@
+const MATCH_SCORE: i32 = 3;
+const MISMATCH_SCORE: i32 = -5;
+const GAP_SCORE: i32 = -4;
+// low enough that adding gap scores to it never overflows
+const UNREACHABLE: i32 = i32::MIN / 2;
+
+#[derive(Debug, Clone)]
+struct PoaNode {
+    base: u8,
+    // predecessors and the number of sequences through each edge
+    in_edges: Vec<(usize, u32)>,
+    out_edges: Vec<usize>,
+    // nodes with other bases at the same position of the alignment
+    aligned: Vec<usize>,
+    // position of the node in the backbone sequence, if it is part of it
+    backbone: Option<usize>,
+}
+
+/// Partial order alignment graph of a backbone sequence and the sequences aligned to it. The
+/// consensus is the heaviest bundle between the ends of the backbone.
+#[derive(Debug, Clone)]
+pub struct PartialOrderGraph {
+    nodes: Vec<PoaNode>,
+    order: Vec<usize>,
+    backbone_length: usize,
+}
+
+impl PartialOrderGraph {
+    pub fn new(backbone: &[u8]) -> Self {
+        let mut graph = Self {
+            nodes: Vec::with_capacity(backbone.len()),
+            order: Vec::new(),
+            backbone_length: backbone.len(),
+        };
+        let mut previous = None;
+        for (position, base) in backbone.iter().enumerate() {
+            let node = graph.add_node(*base, Some(position));
+            if let Some(previous) = previous {
+                graph.add_edge(previous, node);
+            }
+            previous = Some(node);
+        }
+        graph.order = (0..graph.nodes.len()).collect();
+        graph
+    }
+
+    fn add_node(&mut self, base: u8, backbone: Option<usize>) -> usize {
+        self.nodes.push(PoaNode {
+            base,
+            in_edges: Vec::new(),
+            out_edges: Vec::new(),
+            aligned: Vec::new(),
+            backbone,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn add_edge(&mut self, from: usize, to: usize) {
+        match self.nodes[to]
+            .in_edges
+            .iter_mut()
+            .find(|(predecessor, _)| *predecessor == from)
+        {
+            Some(edge) => edge.1 += 1,
+            None => {
+                self.nodes[to].in_edges.push((from, 1));
+                self.nodes[from].out_edges.push(to);
+            }
+        }
+    }
+
+    /// Node of the given base at the alignment position of `node`, created if needed
+    fn node_for_base(&mut self, node: usize, base: u8) -> usize {
+        if self.nodes[node].base == base {
+            return node;
+        }
+        if let Some(aligned) = self.nodes[node]
+            .aligned
+            .iter()
+            .find(|aligned| self.nodes[**aligned].base == base)
+        {
+            return *aligned;
+        }
+
+        let new_node = self.add_node(base, None);
+        let mut aligned = self.nodes[node].aligned.clone();
+        aligned.push(node);
+        for other in aligned.iter() {
+            self.nodes[*other].aligned.push(new_node);
+        }
+        self.nodes[new_node].aligned = aligned;
+        new_node
+    }
+
+    /// Kahn's algorithm over the edges of the graph. Nodes left over by a cycle are appended so
+    /// that every node still has a rank
+    fn topological_order(&self) -> Vec<usize> {
+        let mut in_degree = self
+            .nodes
+            .iter()
+            .map(|node| node.in_edges.len())
+            .collect::<Vec<usize>>();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        let mut stack = (0..self.nodes.len())
+            .rev()
+            .filter(|node| in_degree[*node] == 0)
+            .collect::<Vec<usize>>();
+        while let Some(node) = stack.pop() {
+            order.push(node);
+            for successor in self.nodes[node].out_edges.iter().rev() {
+                in_degree[*successor] -= 1;
+                if in_degree[*successor] == 0 {
+                    stack.push(*successor);
+                }
+            }
+        }
+        if order.len() < self.nodes.len() {
+            let mut placed = vec![false; self.nodes.len()];
+            for node in order.iter() {
+                placed[*node] = true;
+            }
+            order.extend((0..self.nodes.len()).filter(|node| !placed[*node]));
+        }
+        order
+    }
+
+    /// Aligns a sequence to the graph, returning the node and sequence index of each column of the
+    /// alignment. Bases inserted relative to the graph have no node
+    pub fn align(&self, sequence: &[u8]) -> Vec<(Option<usize>, Option<usize>)> {
+        let n = self.order.len();
+        let m = sequence.len();
+        let width = m + 1;
+        let mut rank = vec![0; self.nodes.len()];
+        for (index, node) in self.order.iter().enumerate() {
+            rank[*node] = index;
+        }
+
+        // row 0 is a virtual start preceding every node, so alignments can start anywhere
+        let mut scores = vec![UNREACHABLE; (n + 1) * width];
+        // the row of the previous cell and whether the move was diagonal, up or left
+        let mut moves = vec![(0_usize, 0_u8); (n + 1) * width];
+        for j in 0..=m {
+            scores[j] = GAP_SCORE * j as i32;
+        }
+
+        for (index, node) in self.order.iter().enumerate() {
+            let row = index + 1;
+            let node = &self.nodes[*node];
+            let predecessors = std::iter::once(0)
+                .chain(
+                    node.in_edges
+                        .iter()
+                        .map(|(predecessor, _)| rank[*predecessor] + 1)
+                        .filter(|predecessor_row| *predecessor_row < row),
+                )
+                .collect::<Vec<usize>>();
+            for j in 0..=m {
+                let mut best = (UNREACHABLE, (0, 0));
+                for predecessor_row in predecessors.iter() {
+                    if j > 0 {
+                        let substitution = if node.base == sequence[j - 1] {
+                            MATCH_SCORE
+                        } else {
+                            MISMATCH_SCORE
+                        };
+                        let diagonal = scores[predecessor_row * width + j - 1] + substitution;
+                        if diagonal > best.0 {
+                            best = (diagonal, (*predecessor_row, 0));
+                        }
+                    }
+                    let up = scores[predecessor_row * width + j] + GAP_SCORE;
+                    if up > best.0 {
+                        best = (up, (*predecessor_row, 1));
+                    }
+                }
+                if j > 0 {
+                    let left = scores[row * width + j - 1] + GAP_SCORE;
+                    if left > best.0 {
+                        best = (left, (row, 2));
+                    }
+                }
+                scores[row * width + j] = best.0;
+                moves[row * width + j] = best.1;
+            }
+        }
+
+        // the alignment can also end anywhere along the graph
+        let mut row = (1..=n)
+            .max_by_key(|row| (scores[row * width + m], std::cmp::Reverse(*row)))
+            .unwrap_or(0);
+        let mut j = m;
+        let mut alignment = Vec::new();
+        while row > 0 {
+            let (previous_row, direction) = moves[row * width + j];
+            let node = self.order[row - 1];
+            match direction {
+                0 => {
+                    alignment.push((Some(node), Some(j - 1)));
+                    j -= 1;
+                }
+                1 => alignment.push((Some(node), None)),
+                _ => {
+                    alignment.push((None, Some(j - 1)));
+                    j -= 1;
+                }
+            }
+            row = previous_row;
+        }
+        while j > 0 {
+            alignment.push((None, Some(j - 1)));
+            j -= 1;
+        }
+        alignment.reverse();
+        alignment
+    }
+
+    /// Aligns a sequence to the graph and adds its bases and edges
+    pub fn add_sequence(&mut self, sequence: &[u8]) {
+        if sequence.is_empty() {
+            return;
+        }
+        let alignment = self.align(sequence);
+        let mut previous = None;
+        for (node, index) in alignment {
+            let index = match index {
+                Some(index) => index,
+                // bases of the graph deleted from the sequence
+                None => continue,
+            };
+            let base = sequence[index];
+            let node = match node {
+                Some(node) => self.node_for_base(node, base),
+                None => self.add_node(base, None),
+            };
+            if let Some(previous) = previous {
+                self.add_edge(previous, node);
+            }
+            previous = Some(node);
+        }
+        self.order = self.topological_order();
+    }
+
+    /// The heaviest bundle between the first and last backbone bases, along with the number of
+    /// corrections it makes to the backbone. A substitution, or an insertion or deletion of any
+    /// length, counts as one correction per base of the longer side
+    pub fn consensus(&self) -> (Vec<u8>, usize) {
+        if self.backbone_length == 0 {
+            return (Vec::new(), 0);
+        }
+        // the backbone occupies the first nodes of the graph
+        let (start, end) = (0, self.backbone_length - 1);
+        let mut scores = vec![i64::MIN; self.nodes.len()];
+        let mut predecessors = vec![None; self.nodes.len()];
+        scores[start] = 0;
+        for node in self.order.iter() {
+            if *node == start {
+                continue;
+            }
+            let heaviest = self.nodes[*node]
+                .in_edges
+                .iter()
+                .filter(|(predecessor, _)| scores[*predecessor] > i64::MIN)
+                .max_by_key(|(predecessor, weight)| (*weight, scores[*predecessor]));
+            if let Some((predecessor, weight)) = heaviest {
+                scores[*node] = scores[*predecessor] + *weight as i64;
+                predecessors[*node] = Some(*predecessor);
+            }
+        }
+
+        let mut path = vec![end];
+        let mut node = end;
+        while node != start {
+            match predecessors[node] {
+                Some(predecessor) if path.len() <= self.nodes.len() => {
+                    path.push(predecessor);
+                    node = predecessor;
+                }
+                // the backbone end is unreachable by the heaviest edges, so keep the backbone
+                _ => {
+                    let backbone = (0..self.backbone_length)
+                        .map(|position| self.nodes[position].base)
+                        .collect();
+                    return (backbone, 0);
+                }
+            }
+        }
+        path.reverse();
+
+        let mut corrections = 0;
+        let mut last_position = 0;
+        let mut novel = 0;
+        for node in path.iter().skip(1) {
+            match self.nodes[*node].backbone {
+                Some(position) => {
+                    corrections += novel.max(position.saturating_sub(last_position + 1));
+                    last_position = position;
+                    novel = 0;
+                }
+                None => novel += 1,
+            }
+        }
+        let bases = path.iter().map(|node| self.nodes[*node].base).collect();
+        (bases, corrections)
+    }
+}
@@ -0,0 +1,156 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::bam_parsing::mapping_index_maintenance::generate_concatenated_fasta_file;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::reference::reference_reader_utils::ReferenceReaderUtils;
+use crate::utils::errors::BirdToolError;
+use crate::utils::temp_resources::TempResource;
+
+/// The concatenated reference genomes of a run, either a temporary file removed at the end of the
+/// run or a file kept in the reference cache
+#[derive(Debug)]
+pub enum ConcatenatedReference {
+    Temporary(TempResource),
+    Cached(PathBuf),
+}
+
+impl ConcatenatedReference {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Temporary(file) => file.path(),
+            Self::Cached(path) => path.as_path(),
+        }
+    }
+
+    pub fn is_cached(&self) -> bool {
+        matches!(self, Self::Cached(_))
+    }
+}
+
+/// Persistent cache of concatenated references and their indexes, keyed by the checksums of the
+/// genome files and the genome separator. An entry is used once `COMPLETE_MARKER` is written.
+#[derive(Debug, Clone)]
+pub struct ReferenceCache {
+    pub directory: PathBuf,
+}
+
+impl ReferenceCache {
+    pub const REFERENCE_NAME: &'static str = "concatenated_genomes.fna";
+    const COMPLETE_MARKER: &'static str = "complete";
+
+    pub fn new(directory: &str) -> Self {
+        Self {
+            directory: PathBuf::from(directory),
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        args.try_get_one::<String>("reference-cache-directory")
+            .ok()
+            .flatten()
+            .map(|directory| Self::new(directory))
+    }
+
+    /// The md5 checksum of the contents of a file
+    pub fn file_checksum(path: &str) -> Result<String, BirdToolError> {
+        let mut file = File::open(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to open {}: {:?}", path, e)))?;
+        let mut context = md5::Context::new();
+        let mut buffer = vec![0u8; 1 << 16];
+        loop {
+            let read = file
+                .read(&mut buffer)
+                .map_err(|e| BirdToolError::IOError(format!("Unable to read {}: {:?}", path, e)))?;
+            if read == 0 {
+                break;
+            }
+            context.consume(&buffer[..read]);
+        }
+        Ok(format!("{:x}", context.compute()))
+    }
+
+    /// The key of the cache entry of the given genomes. Genome names are part of the contig names
+    /// of the concatenated reference, so they are part of the key along with the separator
+    /// joining them to contig names and the checksums
+    pub fn cache_key(
+        genome_paths: &[String],
+        separator: GenomeSeparator,
+    ) -> Result<String, BirdToolError> {
+        let mut context = md5::Context::new();
+        context.consume(separator.as_char().to_string().as_bytes());
+        for path in genome_paths {
+            context.consume(ReferenceReaderUtils::genome_name_from_path(path).as_bytes());
+            context.consume(b"\t");
+            context.consume(Self::file_checksum(path)?.as_bytes());
+            context.consume(b"\n");
+        }
+        Ok(format!("{:x}", context.compute()))
+    }
+
+    pub fn entry_directory(&self, key: &str) -> PathBuf {
+        self.directory.join(key)
+    }
+
+    /// The cached concatenated reference of the genomes if there is one
+    pub fn lookup(&self, key: &str) -> Option<PathBuf> {
+        let entry = self.entry_directory(key);
+        let reference = entry.join(Self::REFERENCE_NAME);
+        if entry.join(Self::COMPLETE_MARKER).exists() && reference.exists() {
+            Some(reference)
+        } else {
+            None
+        }
+    }
+
+    /// The concatenated reference of the genomes, taken from the cache or concatenated and added
+    /// to the cache if it is missing
+    pub fn concatenated_reference(
+        &self,
+        genome_paths: &[String],
+        separator: GenomeSeparator,
+    ) -> Result<ConcatenatedReference, BirdToolError> {
+        let key = Self::cache_key(genome_paths, separator)?;
+        if let Some(reference) = self.lookup(&key) {
+            info!(
+                "Using cached concatenated reference {}",
+                reference.display()
+            );
+            return Ok(ConcatenatedReference::Cached(reference));
+        }
+
+        let entry = self.entry_directory(&key);
+        std::fs::create_dir_all(&entry).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create reference cache directory {}: {:?}",
+                entry.display(),
+                e
+            ))
+        })?;
+
+        let concatenated = generate_concatenated_fasta_file(&genome_paths.to_vec(), separator);
+        let reference = entry.join(Self::REFERENCE_NAME);
+        std::fs::copy(concatenated.path(), &reference).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to add concatenated reference to cache {}: {:?}",
+                reference.display(),
+                e
+            ))
+        })?;
+        ReferenceReaderUtils::generate_faidx(reference.to_str().unwrap());
+        File::create(entry.join(Self::COMPLETE_MARKER)).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to mark reference cache entry {} as complete: {:?}",
+                entry.display(),
+                e
+            ))
+        })?;
+        info!(
+            "Added concatenated reference to cache {}",
+            reference.display()
+        );
+
+        Ok(ConcatenatedReference::Cached(reference))
+    }
+}
@@ -0,0 +1,379 @@
+use ndarray::Array2;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::model::accessible_genome::AccessibleWindow;
+use crate::model::variant_context::VariantContext;
+use crate::reference::reference_reader::ReferenceReader;
+use crate::reference::reference_reader_utils::ReferenceReaderUtils;
+use crate::utils::errors::BirdToolError;
+
+/// Holds the low mappability regions of a reference genome. Regions are stored per tid as sorted,
+/// non-overlapping 0-based half open intervals.
+///
+/// Variants that fall inside of a masked region are given the `MASKED` filter tag and are removed
+/// from the ANI, Fst and dN/dS calculations. The masked bases are also removed from the number of
+/// compared bases so that they do not inflate the ANI values.
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceMask {
+    intervals: HashMap<usize, Vec<(usize, usize)>>,
+}
+
+impl ReferenceMask {
+    pub fn new() -> Self {
+        Self {
+            intervals: HashMap::new(),
+        }
+    }
+
+    /// Builds the mask for the reference at `ref_idx` using the user provided arguments.
+    /// A BED file takes precedence over kmer uniqueness. Returns None if no masking was requested
+    /// and a config error if the BED file can not be read.
+    pub fn from_args(
+        args: &clap::ArgMatches,
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+    ) -> Result<Option<Self>, BirdToolError> {
+        if let Some(bed_path) = args.try_get_one::<String>("mask-bed").ok().flatten() {
+            Self::from_bed(bed_path, reference_reader, ref_idx).map(Some)
+        } else if let Some(kmer_size) = args.try_get_one::<usize>("mask-kmer-size").ok().flatten() {
+            Ok(Some(Self::from_kmer_uniqueness(
+                reference_reader,
+                ref_idx,
+                *kmer_size,
+            )))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reads the --mask-bed file once before any genome is run, so that a missing or malformed
+    /// file stops the run instead of each genome being called without its mask
+    pub fn check_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        match args.try_get_one::<String>("mask-bed").ok().flatten() {
+            Some(bed_path) => Self::read_bed(bed_path).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads a BED file and keeps the intervals that land on contigs of the given reference.
+    /// Contig names can be provided either with or without the genome prefix
+    pub fn from_bed(
+        path: &str,
+        reference_reader: &ReferenceReader,
+        ref_idx: usize,
+    ) -> Result<Self, BirdToolError> {
+        let mut name_to_tid = HashMap::new();
+        if let Some(tids) = reference_reader.retrieve_tids_for_ref_index(ref_idx) {
+            for tid in tids.iter() {
+                let target_name = reference_reader.get_target_name(*tid).to_vec();
+                name_to_tid.insert(
+                    ReferenceReaderUtils::split_contig_name(
+                        &target_name,
+                        &reference_reader.genomes_and_contigs.separator,
+                    ),
+                    *tid,
+                );
+                name_to_tid.insert(String::from_utf8(target_name).unwrap(), *tid);
+            }
+        }
+
+        let mut mask = Self::new();
+        for (contig, start, end) in Self::read_bed(path)? {
+            if let Some(tid) = name_to_tid.get(&contig) {
+                mask.add_interval(*tid, start, end);
+            }
+        }
+        mask.merge();
+
+        debug!(
+            "Loaded {} masked bases from {}",
+            mask.masked_bases(),
+            path
+        );
+        Ok(mask)
+    }
+
+    /// The contig, start and end of every interval in a BED file
+    fn read_bed(path: &str) -> Result<Vec<(String, usize, usize)>, BirdToolError> {
+        let file = File::open(path).map_err(|e| {
+            BirdToolError::ConfigError(format!("Unable to open mask BED file {}: {}", path, e))
+        })?;
+
+        let mut intervals = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| {
+                BirdToolError::ConfigError(format!("Unable to read mask BED file {}: {}", path, e))
+            })?;
+            if line.is_empty()
+                || line.starts_with('#')
+                || line.starts_with("track")
+                || line.starts_with("browser")
+            {
+                continue;
+            }
+
+            let fields = line.split('\t').collect::<Vec<&str>>();
+            if fields.len() < 3 {
+                return Err(BirdToolError::ConfigError(format!(
+                    "Malformed BED line in {}: {}",
+                    path, line
+                )));
+            }
+
+            let start = fields[1].parse::<usize>().map_err(|_| {
+                BirdToolError::ConfigError(format!("Invalid BED start in {}: {}", path, line))
+            })?;
+            let end = fields[2].parse::<usize>().map_err(|_| {
+                BirdToolError::ConfigError(format!("Invalid BED end in {}: {}", path, line))
+            })?;
+
+            intervals.push((fields[0].to_string(), start, end));
+        }
+
+        Ok(intervals)
+    }
+
+    /// Masks all positions of the reference covered by a kmer that occurs more than once in the
+    /// reference genome, considering both strands.
+    pub fn from_kmer_uniqueness(
+        reference_reader: &mut ReferenceReader,
+        ref_idx: usize,
+        kmer_size: usize,
+    ) -> Self {
+        let tids = match reference_reader.retrieve_tids_for_ref_index(ref_idx) {
+            Some(tids) => tids.iter().copied().collect::<Vec<usize>>(),
+            None => return Self::new(),
+        };
+
+        let mut sequences = Vec::with_capacity(tids.len());
+        for tid in tids.iter() {
+            if reference_reader
+                .fetch_contig_from_reference_by_tid(*tid, ref_idx)
+                .is_err()
+            {
+                sequences.push(Vec::new());
+                continue;
+            }
+            reference_reader.read_sequence_to_vec();
+            sequences.push(std::mem::take(&mut reference_reader.current_sequence));
+        }
+
+        let mut mask = Self::new();
+        for (tid, intervals) in tids
+            .into_iter()
+            .zip(Self::find_non_unique_kmer_intervals(&sequences, kmer_size))
+        {
+            for (start, end) in intervals {
+                mask.add_interval(tid, start, end);
+            }
+        }
+        mask.merge();
+        mask
+    }
+
+    /// For each provided sequence, returns the merged 0-based half open intervals that are covered
+    /// by a canonical kmer observed more than once across all sequences. Kmers containing
+    /// non-ACGT bases are ignored. Kmer size must be between 1 and 32.
+    pub fn find_non_unique_kmer_intervals(
+        sequences: &[Vec<u8>],
+        kmer_size: usize,
+    ) -> Vec<Vec<(usize, usize)>> {
+        assert!(
+            kmer_size > 0 && kmer_size <= 32,
+            "Mask kmer size must be between 1 and 32"
+        );
+
+        let mut counts: HashMap<u64, u32> = HashMap::new();
+        let canonical_kmers = sequences
+            .iter()
+            .map(|sequence| Self::canonical_kmers(sequence, kmer_size))
+            .collect::<Vec<Vec<Option<u64>>>>();
+
+        for kmers in canonical_kmers.iter() {
+            for kmer in kmers.iter().flatten() {
+                *counts.entry(*kmer).or_insert(0) += 1;
+            }
+        }
+
+        canonical_kmers
+            .iter()
+            .map(|kmers| {
+                let mut intervals: Vec<(usize, usize)> = Vec::new();
+                for (pos, kmer) in kmers.iter().enumerate() {
+                    if let Some(kmer) = kmer {
+                        if counts[kmer] > 1 {
+                            match intervals.last_mut() {
+                                Some(last) if last.1 >= pos => last.1 = pos + kmer_size,
+                                _ => intervals.push((pos, pos + kmer_size)),
+                            }
+                        }
+                    }
+                }
+                intervals
+            })
+            .collect()
+    }
+
+    /// 2-bit encodes every kmer in the sequence and returns the smaller of the forward and reverse
+    /// complement encodings. Positions whose kmer contains an ambiguous base are None
+    fn canonical_kmers(sequence: &[u8], kmer_size: usize) -> Vec<Option<u64>> {
+        if sequence.len() < kmer_size {
+            return Vec::new();
+        }
+
+        let mask = if kmer_size == 32 {
+            u64::MAX
+        } else {
+            (1u64 << (2 * kmer_size)) - 1
+        };
+        let shift = 2 * (kmer_size as u64 - 1);
+
+        let mut forward = 0u64;
+        let mut reverse = 0u64;
+        let mut valid_run = 0;
+        let mut kmers = Vec::with_capacity(sequence.len() - kmer_size + 1);
+        for (pos, base) in sequence.iter().enumerate() {
+            let code = match base.to_ascii_uppercase() {
+                b'A' => Some(0u64),
+                b'C' => Some(1u64),
+                b'G' => Some(2u64),
+                b'T' => Some(3u64),
+                _ => None,
+            };
+
+            match code {
+                Some(code) => {
+                    forward = ((forward << 2) | code) & mask;
+                    reverse = (reverse >> 2) | ((3 - code) << shift);
+                    valid_run += 1;
+                }
+                None => {
+                    valid_run = 0;
+                }
+            }
+
+            if pos + 1 >= kmer_size {
+                if valid_run >= kmer_size {
+                    kmers.push(Some(std::cmp::min(forward, reverse)));
+                } else {
+                    kmers.push(None);
+                }
+            }
+        }
+
+        kmers
+    }
+
+    /// Adds a 0-based half open interval to the mask. Call `merge` once all intervals are added
+    pub fn add_interval(&mut self, tid: usize, start: usize, end: usize) {
+        if end <= start {
+            return;
+        }
+        self.intervals
+            .entry(tid)
+            .or_insert_with(Vec::new)
+            .push((start, end));
+    }
+
+    /// Sorts and merges overlapping or adjacent intervals on each contig
+    pub fn merge(&mut self) {
+        for intervals in self.intervals.values_mut() {
+            intervals.sort_unstable();
+            let mut merged: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+            for (start, end) in intervals.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if last.1 >= start => last.1 = std::cmp::max(last.1, end),
+                    _ => merged.push((start, end)),
+                }
+            }
+            *intervals = merged;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.values().all(|intervals| intervals.is_empty())
+    }
+
+    /// Whether the 0-based position on the given tid is masked
+    pub fn is_masked(&self, tid: usize, pos: usize) -> bool {
+        self.overlaps(tid, pos, pos)
+    }
+
+    /// Whether any position within the 0-based closed interval [start, end] is masked
+    pub fn overlaps(&self, tid: usize, start: usize, end: usize) -> bool {
+        match self.intervals.get(&tid) {
+            Some(intervals) => {
+                // first interval whose end is past the start of the query
+                let idx = intervals.partition_point(|(_, interval_end)| *interval_end <= start);
+                idx < intervals.len() && intervals[idx].0 <= end
+            }
+            None => false,
+        }
+    }
+
+    /// Total number of bases covered by the mask
+    pub fn masked_bases(&self) -> u64 {
+        self.intervals
+            .values()
+            .flat_map(|intervals| intervals.iter())
+            .map(|(start, end)| (end - start) as u64)
+            .sum()
+    }
+
+    /// Number of masked bases within the 0-based closed interval [start, end]
+    pub fn masked_bases_within(&self, tid: usize, start: usize, end: usize) -> u64 {
+        match self.intervals.get(&tid) {
+            Some(intervals) => {
+                let first = intervals.partition_point(|(_, interval_end)| *interval_end <= start);
+                intervals[first..]
+                    .iter()
+                    .take_while(|(interval_start, _)| *interval_start <= end)
+                    .map(|(interval_start, interval_end)| {
+                        (std::cmp::min(*interval_end, end + 1)
+                            - std::cmp::max(*interval_start, start)) as u64
+                    })
+                    .sum()
+            }
+            None => 0,
+        }
+    }
+
+    /// Tags any variant context that overlaps the mask with the `MASKED` filter.
+    /// These contexts are also marked as unqualified so that downstream statistics ignore them.
+    /// Returns the number of contexts that were masked
+    pub fn apply_to_contexts(&self, contexts: &mut [VariantContext]) -> usize {
+        let mut masked = 0;
+        for context in contexts.iter_mut() {
+            if self.overlaps(context.loc.tid, context.loc.start, context.loc.end) {
+                context.mask();
+                masked += 1;
+            }
+        }
+        masked
+    }
+
+    /// Removes the masked bases from a matrix of compared bases between samples. Each window
+    /// only gives up the bases masked within it, and never more than it compared, so a pair of
+    /// samples loses nothing for masked regions neither of them covered
+    pub fn adjust_compared_bases(
+        &self,
+        compared_bases: &mut Array2<f32>,
+        windows: &[AccessibleWindow],
+    ) {
+        for window in windows.iter() {
+            let masked_bases =
+                self.masked_bases_within(window.tid, window.start, window.end) as f32;
+            if masked_bases == 0.0 {
+                continue;
+            }
+            compared_bases
+                .iter_mut()
+                .zip(window.compared_bases.iter())
+                .for_each(|(val, window_bases)| {
+                    *val = (*val - window_bases.max(0.0).min(masked_bases)).max(0.0);
+                });
+        }
+    }
+}
@@ -215,16 +215,15 @@ impl ReferenceReader {
                 "{}",
                 std::str::from_utf8(contig_name)
                     .unwrap()
-                    .splitn(2, '~')
+                    .splitn(2, self.genomes_and_contigs.separator.as_char())
                     .nth(1)
                     .unwrap()
             )) {
                 Ok(reference) => reference,
                 Err(_e) => {
-                    match self.indexed_reader.fetch_all(&format!(
-                        "{}~{}",
-                        self.genomes_and_contigs.genomes[ref_idx],
-                        std::str::from_utf8(contig_name).unwrap()
+                    match self.indexed_reader.fetch_all(&self.genomes_and_contigs.separator.join(
+                        &self.genomes_and_contigs.genomes[ref_idx],
+                        std::str::from_utf8(contig_name).unwrap(),
                     )) {
                         Ok(reference) => reference,
                         Err(e) => {
@@ -254,7 +253,7 @@ impl ReferenceReader {
                 "{}",
                 match std::str::from_utf8(&self.target_names[&tid])
                     .unwrap()
-                    .splitn(2, '~')
+                    .splitn(2, self.genomes_and_contigs.separator.as_char())
                     .nth(1)
                 {
                     None => std::str::from_utf8(&self.target_names[&tid]).unwrap(),
@@ -263,10 +262,9 @@ impl ReferenceReader {
             )) {
                 Ok(reference) => Ok(reference),
                 Err(_e) => {
-                    match self.indexed_reader.fetch_all(&format!(
-                        "{}~{}",
-                        self.genomes_and_contigs.genomes[ref_idx],
-                        std::str::from_utf8(&self.target_names[&tid]).unwrap()
+                    match self.indexed_reader.fetch_all(&self.genomes_and_contigs.separator.join(
+                        &self.genomes_and_contigs.genomes[ref_idx],
+                        std::str::from_utf8(&self.target_names[&tid]).unwrap(),
                     )) {
                         Ok(reference) => Ok(reference),
                         Err(e) => {
@@ -300,7 +298,7 @@ impl ReferenceReader {
                     "{}",
                     std::str::from_utf8(&self.target_names[&interval.get_contig()])
                         .unwrap()
-                        .splitn(2, '~')
+                        .splitn(2, self.genomes_and_contigs.separator.as_char())
                         .nth(1)
                         .unwrap()
                 ),
@@ -312,10 +310,9 @@ impl ReferenceReader {
             ) {
                 Ok(reference) => reference,
                 Err(_e) => match self.indexed_reader.fetch(
-                    &format!(
-                        "{}~{}",
-                        self.genomes_and_contigs.genomes[ref_idx],
-                        std::str::from_utf8(&self.target_names[&interval.get_contig()]).unwrap()
+                    &self.genomes_and_contigs.separator.join(
+                        &self.genomes_and_contigs.genomes[ref_idx],
+                        std::str::from_utf8(&self.target_names[&interval.get_contig()]).unwrap(),
                     ),
                     interval.get_start() as u64,
                     min(
@@ -327,11 +324,10 @@ impl ReferenceReader {
                     Err(e) => {
                         panic!(
                             "Cannot read sequence from reference {} {:?}",
-                            format!(
-                                "{}~{}",
+                            self.genomes_and_contigs.separator.join(
                                 &self.genomes_and_contigs.genomes[ref_idx],
                                 std::str::from_utf8(&self.target_names[&interval.get_contig()])
-                                    .unwrap()
+                                    .unwrap(),
                             ),
                             e,
                         );
@@ -1,17 +1,24 @@
 use bio::io::fasta::IndexedReader;
 use glob::glob;
 use needletail::parse_fastx_file;
-use std::process::{Stdio, exit, self};
+use std::process::Stdio;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufRead;
 use std::path::Path;
-use tempfile::NamedTempFile;
 
 use crate::external_command_checker;
 use crate::bam_parsing::mapping_index_maintenance::generate_concatenated_fasta_file;
+use crate::reference::genome_separator::GenomeSeparator;
+use crate::reference::reference_cache::{ConcatenatedReference, ReferenceCache};
+use crate::utils::errors::BirdToolError;
+use crate::utils::exit_status::ExitStatus;
+use crate::utils::temp_resources::{TempResource, TempResources};
 use crate::utils::utils::find_first;
 
+/// Compression formats that can be read for reference FASTA files
+pub const COMPRESSED_FASTA_EXTENSIONS: [&str; 3] = ["gz", "bz2", "xz"];
+
 // lazy_static! {
 //     static ref GALAH_COMMAND_DEFINITION: GalahClustererCommandDefinition = {
 //         GalahClustererCommandDefinition {
@@ -55,25 +62,22 @@ impl ReferenceReaderUtils {
         return &target_name[0..offset];
     }
 
-    // Splits a contig name based on the ~
-    pub fn split_contig_name(target_name: &Vec<u8>) -> String {
-        String::from_utf8(target_name.clone())
-            .unwrap()
-            .splitn(2, '~')
-            .nth(1)
-            .unwrap_or_else(|| std::str::from_utf8(&target_name).unwrap())
+    // Splits a contig name based on the genome separator
+    pub fn split_contig_name(target_name: &Vec<u8>, separator: &GenomeSeparator) -> String {
+        separator
+            .contig(std::str::from_utf8(target_name).unwrap())
             .to_string()
     }
 
     pub fn setup_genome_fasta_files(
         m: &clap::ArgMatches,
-    ) -> (Option<NamedTempFile>, Option<GenomesAndContigs>) {
+    ) -> (Option<ConcatenatedReference>, Option<GenomesAndContigs>) {
         let genome_fasta_files_opt = {
-            match bird_tool_utils::clap_utils::parse_list_of_genome_fasta_files(m, false) {
+            match Self::try_parse_references(m) {
                 Ok(paths) => {
                     if paths.len() == 0 {
                         error!("Genome paths were described, but ultimately none were found");
-                        exit(1);
+                        ExitStatus::ConfigurationError.exit();
                     }
                     // if m.is_present("checkm-tab-table") || m.is_present("genome-info") {
                     //     let genomes_after_filtering =
@@ -107,17 +111,32 @@ impl ReferenceReaderUtils {
 
         // debug!("Found paths {:?}", &genome_fasta_files_opt);
 
+        // the separator used in the concatenated reference is chosen to avoid the genome names
+        let separator = match &genome_fasta_files_opt {
+            Some(genome_paths) => {
+                let genome_names = genome_paths
+                    .iter()
+                    .map(|path| Self::genome_name_from_path(path))
+                    .collect::<Vec<String>>();
+                match GenomeSeparator::from_args(m, &genome_names) {
+                    Ok(separator) => separator,
+                    Err(e) => {
+                        error!("{}", e);
+                        ExitStatus::ConfigurationError.exit();
+                    }
+                }
+            }
+            None => GenomeSeparator::default(),
+        };
+
         let (concatenated_genomes, genomes_and_contigs_option) = match m.contains_id("genome-fasta-files") {
             true => match genome_fasta_files_opt {
                 Some(genome_paths) => (
-                    Some(
-                        generate_concatenated_fasta_file(
-                            &genome_paths,
-                        ),
-                    ),
+                    Some(Self::concatenate_genomes(m, &genome_paths, separator)),
                     Self::extract_genomes_and_contigs_option(
                         m,
                         &genome_paths.iter().map(|s| s.as_str()).collect(),
+                        separator,
                     ),
                 ),
                 None => (None, None),
@@ -136,11 +155,11 @@ impl ReferenceReaderUtils {
                 let list_of_genome_fasta_files = &dereplicated_genomes;
 
                 (
-                    Some(
-                        generate_concatenated_fasta_file(
-                            list_of_genome_fasta_files,
-                        ),
-                    ),
+                    Some(Self::concatenate_genomes(
+                        m,
+                        list_of_genome_fasta_files,
+                        separator,
+                    )),
                     Self::extract_genomes_and_contigs_option(
                         m,
                         &dereplicated_genomes
@@ -148,6 +167,7 @@ impl ReferenceReaderUtils {
                             .iter()
                             .map(|s| s.as_str())
                             .collect(),
+                        separator,
                     ),
                 )
             }
@@ -157,48 +177,206 @@ impl ReferenceReaderUtils {
         return (concatenated_genomes, genomes_and_contigs_option);
     }
 
+    /// Concatenates the genomes into a single reference, reusing the concatenated reference in
+    /// --reference-cache-directory when one is given
+    fn concatenate_genomes(
+        m: &clap::ArgMatches,
+        genome_paths: &Vec<String>,
+        separator: GenomeSeparator,
+    ) -> ConcatenatedReference {
+        if let Some(reference_cache) = ReferenceCache::from_args(m) {
+            match reference_cache.concatenated_reference(genome_paths, separator) {
+                Ok(reference) => return reference,
+                Err(e) => warn!(
+                    "Unable to use reference cache {}, concatenating genomes for this run only: {:?}",
+                    reference_cache.directory.display(),
+                    e
+                ),
+            }
+        }
+        let concatenated = generate_concatenated_fasta_file(genome_paths, separator)
+            .keep()
+            .expect("Unable to keep concatenated reference")
+            .1;
+        // the .fai index written next to the reference is removed with it on interruption
+        TempResources::register(format!("{}.fai", concatenated.display()));
+        ConcatenatedReference::Temporary(TempResources::adopt(concatenated))
+    }
+
     pub fn parse_references(m: &clap::ArgMatches) -> Vec<String> {
+        match Self::try_parse_references(m) {
+            Ok(references) => references,
+            Err(e) => panic!("Can't find suitable references for variant calling: {:?}", e),
+        }
+    }
+
+    /// Collects the reference genome paths provided by the user. Paths given to
+    /// --genome-fasta-files may be glob patterns, and --genome-fasta-directory also picks up
+    /// compressed FASTA files (e.g. genome.fna.gz) with the given extension. Compressed references
+    /// are decompressed into the concatenated reference file, so do not need to be decompressed
+    /// by the user.
+    pub fn try_parse_references(m: &clap::ArgMatches) -> Result<Vec<String>, BirdToolError> {
         let references = match m.get_many::<String>("genome-fasta-files") {
             Some(vec) => {
-                let reference_paths = vec.map(|p| p.to_string()).collect::<Vec<String>>();
-                // debug!("Reference files {:?}", reference_paths);
+                let mut reference_paths = Vec::new();
+                for path in vec {
+                    if Path::new(path).exists() || !Self::is_glob_pattern(path) {
+                        reference_paths.push(path.to_string());
+                    } else {
+                        let expanded = Self::glob_paths(path)?;
+                        if expanded.is_empty() {
+                            return Err(BirdToolError::IOError(format!(
+                                "No genome FASTA files matched {}",
+                                path
+                            )));
+                        }
+                        reference_paths.extend(expanded);
+                    }
+                }
                 reference_paths
             }
             None => match m.get_one::<String>("genome-fasta-directory") {
                 Some(path) => {
                     let ext = m.get_one::<String>("genome-fasta-extension").unwrap();
-                    let reference_glob = format!("{}/*.{}", path, ext);
-                    let reference_paths = glob(&reference_glob)
-                        .expect("Failed to read cache")
-                        .map(|p| {
-                            p.expect("Failed to read cached bam path")
-                                .to_str()
-                                .unwrap()
-                                .to_string()
-                        })
-                        .collect::<Vec<String>>();
-                    // debug!("Reference files {:?}", reference_paths);
+                    let mut reference_paths = Self::glob_paths(&format!("{}/*.{}", path, ext))?;
+                    for compression in COMPRESSED_FASTA_EXTENSIONS.iter() {
+                        reference_paths.extend(Self::glob_paths(&format!(
+                            "{}/*.{}.{}",
+                            path, ext, compression
+                        ))?);
+                    }
+                    reference_paths.sort();
                     reference_paths
                 }
-                None => panic!("Can't find suitable references for variant calling"),
+                None => {
+                    return Err(BirdToolError::IOError(
+                        "No genome FASTA files or directory provided".to_string(),
+                    ))
+                }
             },
         };
-        return references;
+        return Ok(references);
+    }
+
+    fn is_glob_pattern(path: &str) -> bool {
+        path.contains(|c| c == '*' || c == '?' || c == '[')
+    }
+
+    fn glob_paths(pattern: &str) -> Result<Vec<String>, BirdToolError> {
+        let paths = glob(pattern).map_err(|e| {
+            BirdToolError::IOError(format!("Invalid genome path pattern {}: {}", pattern, e))
+        })?;
+
+        let mut reference_paths = Vec::new();
+        for path in paths {
+            let path = path.map_err(|e| {
+                BirdToolError::IOError(format!("Failed to read genome path: {}", e))
+            })?;
+            reference_paths.push(path.to_str().unwrap().to_string());
+        }
+        Ok(reference_paths)
+    }
+
+    /// Whether the FASTA file at the given path is compressed, judging by its extension
+    pub fn is_compressed_fasta(path: &str) -> bool {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => COMPRESSED_FASTA_EXTENSIONS.contains(&ext),
+            None => false,
+        }
+    }
+
+    /// The genome name derived from a FASTA file path. This is the file stem, ignoring any
+    /// compression extension so that genome.fna and genome.fna.gz both produce "genome"
+    pub fn genome_name_from_path(path: &str) -> String {
+        let path = if Self::is_compressed_fasta(path) {
+            Path::new(path).file_stem().map(Path::new)
+        } else {
+            Some(Path::new(path))
+        };
+
+        path.and_then(|p| p.file_stem())
+            .expect("Problem while determining file stem")
+            .to_str()
+            .expect("File name string conversion problem")
+            .to_string()
+    }
+
+    /// Indexed FASTA readers can not read gzip, bzip2 or xz compressed files. Any compressed
+    /// references are decompressed into a temporary directory, keeping their genome name, and
+    /// the returned paths point to the decompressed copies. The returned TempResource must be
+    /// kept alive for as long as the references are in use.
+    pub fn decompress_references(
+        references: Vec<String>,
+    ) -> (Vec<String>, Option<TempResource>) {
+        if !references.iter().any(|r| Self::is_compressed_fasta(r)) {
+            return (references, None);
+        }
+
+        let tmp_dir = TempResources::create_dir("lorikeet_references")
+            .expect("Unable to create temporary directory for decompressed references");
+        let references = references
+            .into_iter()
+            .map(|reference| {
+                if !Self::is_compressed_fasta(&reference) {
+                    return reference;
+                }
+
+                let decompressed_path = format!(
+                    "{}/{}.fna",
+                    tmp_dir.path().to_str().unwrap(),
+                    Self::genome_name_from_path(&reference)
+                );
+                debug!("Decompressing {} to {}", &reference, &decompressed_path);
+                let mut reader = parse_fastx_file(Path::new(&reference))
+                    .expect(&format!("Unable to read fasta file {}", &reference));
+                let mut writer = bio::io::fasta::Writer::to_file(&decompressed_path)
+                    .expect(&format!("Unable to create {}", &decompressed_path));
+                while let Some(record) = reader.next() {
+                    let record = record.expect(&format!(
+                        "Failed to parse record in fasta file {}",
+                        &reference
+                    ));
+                    let contig_name = std::str::from_utf8(record.id())
+                        .expect("UTF-8 conversion problem in contig name");
+                    writer
+                        .write(contig_name, None, &record.seq())
+                        .expect("Failed to write decompressed reference");
+                }
+                writer.flush().expect("Failed to flush decompressed reference");
+                decompressed_path
+            })
+            .collect::<Vec<String>>();
+
+        (references, Some(tmp_dir))
     }
 
     pub fn extract_genomes_and_contigs_option(
         m: &clap::ArgMatches,
         genome_fasta_files: &Vec<&str>,
+        separator: GenomeSeparator,
     ) -> Option<GenomesAndContigs> {
-        match m.contains_id("genome-definition") {
-            true => Some(read_genome_definition_file(
-                m.get_one::<String>("genome-definition").unwrap(),
-            )),
-            false => Some(read_genome_fasta_files(
-                &genome_fasta_files,
-                false,
-            )),
+        let mut genomes_and_contigs = read_genome_fasta_files(&genome_fasta_files, false);
+        genomes_and_contigs.separator = separator;
+        // genomes are always named after their fasta files, the definition file only adds the
+        // replicon type of each contig
+        if let Some(definition_file_path) = m
+            .try_get_one::<String>("genome-definition")
+            .ok()
+            .flatten()
+        {
+            let definition = read_genome_definition_file(definition_file_path, separator);
+            for genome in definition.genomes.iter() {
+                if !genomes_and_contigs.genomes.contains(genome) {
+                    warn!(
+                        "Genome {} in the genome definition file does not match any genome \
+                        fasta file",
+                        genome
+                    );
+                }
+            }
+            genomes_and_contigs.replicon_types = definition.replicon_types;
         }
+        Some(genomes_and_contigs)
     }
 
     pub fn generate_faidx(reference_path: &str) -> IndexedReader<File> {
@@ -280,10 +458,43 @@ impl ReferenceReaderUtils {
     // }
 }
 
+/// The kind of replicon a contig belongs to. Plasmids and mobile elements can be present at a
+/// different copy number to the chromosome, so their variants have different depths to the
+/// chromosomal variants of the same strain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum RepliconType {
+    Chromosome,
+    Plasmid,
+    MobileElement,
+}
+
+impl RepliconType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "chromosome" => Some(Self::Chromosome),
+            "plasmid" => Some(Self::Plasmid),
+            "mobile_element" | "mobile-element" => Some(Self::MobileElement),
+            _ => None,
+        }
+    }
+
+    pub fn to_key(&self) -> &'static str {
+        match self {
+            Self::Chromosome => "chromosome",
+            Self::Plasmid => "plasmid",
+            Self::MobileElement => "mobile_element",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GenomesAndContigs {
     pub genomes: Vec<String>,
     pub contigs: usize,
+    // replicon types given in the genome definition file, keyed by concatenated reference name
+    pub replicon_types: HashMap<String, RepliconType>,
+    // joins genome and contig names in the concatenated reference
+    pub separator: GenomeSeparator,
 }
 
 impl GenomesAndContigs {
@@ -291,9 +502,20 @@ impl GenomesAndContigs {
         GenomesAndContigs {
             genomes: Vec::new(),
             contigs: 0,
+            replicon_types: HashMap::new(),
+            separator: GenomeSeparator::default(),
         }
     }
 
+    /// The replicon type of a contig in the concatenated reference, chromosomal unless the
+    /// genome definition file says otherwise
+    pub fn replicon_type(&self, contig_name: &str) -> RepliconType {
+        self.replicon_types
+            .get(contig_name)
+            .copied()
+            .unwrap_or(RepliconType::Chromosome)
+    }
+
     pub fn establish_genome(&mut self, genome_name: String) -> usize {
         let index = self.genomes.len();
         self.genomes.push(genome_name);
@@ -323,32 +545,11 @@ pub fn read_genome_fasta_files(
         let mut reader =
             parse_fastx_file(path).expect(&format!("Unable to read fasta file {}", file));
 
-        // Remove .gz .bz .xz from file names if present
-        let genome_name1 =
-            String::from(path.to_str().expect("File name string conversion problem"));
-        // if genome_name1 ends with .gz, .bz, or .xz, the Rust HTSLIB indexed reader
-        // will not be able to parse it. So we need to gracefully exit and inform the user
-        // that they need to decompress the file first. Additionally, if they have provided BAM
-        // files the BAM files will need to be regenerated with decompressed input or reheadered
-        // to remove the .gz, .bz, or .xz extension.
-        if genome_name1.ends_with(".gz") || genome_name1.ends_with(".bz") || genome_name1.ends_with(".xz") {
-            error!("The genome file {} is compressed. Please decompress the file before running lorikeet.", genome_name1);
-            error!("If you are using BAM files, please regenerate the BAM files with decompressed input");
-            error!("Or reheader the BAM files to remove the .gz, .bz, or .xz extension from genome names");
-            process::exit(1);
-        }
-        let path1 = Path::new(&genome_name1);
-
-        let genome_name = String::from(
-            path1
-                .file_stem()
-                .expect("Problem while determining file stem")
-                .to_str()
-                .expect("File name string conversion problem"),
-        );
+        // Compressed genomes are named without their compression extension
+        let genome_name = ReferenceReaderUtils::genome_name_from_path(file);
         if contig_to_genome.genome_index(&genome_name).is_some() {
             error!("The genome name {} was derived from >1 file", genome_name);
-            exit(1);
+            ExitStatus::ConfigurationError.exit();
         }
         let _genome_index = contig_to_genome.establish_genome(genome_name);
         while let Some(record) = reader.next() {
@@ -369,7 +570,10 @@ pub fn read_genome_fasta_files(
     return contig_to_genome;
 }
 
-pub fn read_genome_definition_file(definition_file_path: &str) -> GenomesAndContigs {
+pub fn read_genome_definition_file(
+    definition_file_path: &str,
+    separator: GenomeSeparator,
+) -> GenomesAndContigs {
     let f = std::fs::File::open(definition_file_path).expect(&format!(
         "Unable to find/read genome definition file {}",
         definition_file_path
@@ -379,10 +583,11 @@ pub fn read_genome_definition_file(definition_file_path: &str) -> GenomesAndCont
     // Maintain the same order as the input file.
     let mut genome_order: Vec<String> = vec![];
     let mut contig_to_genome = GenomesAndContigs::new();
+    contig_to_genome.separator = separator;
     for line_res in file.lines() {
         let line = line_res.expect("Read error on genome definition file");
         let v: Vec<&str> = line.split("\t").collect();
-        if v.len() == 2 {
+        if v.len() == 2 || v.len() == 3 {
             let genome = v[0].trim();
             let contig = v[1]
                 .split_ascii_whitespace()
@@ -390,6 +595,24 @@ pub fn read_genome_definition_file(definition_file_path: &str) -> GenomesAndCont
                 .expect("Failed to split contig name by whitespace in genome definition file");
             contig_to_genome.contigs += 1;
 
+            if v.len() == 3 {
+                match RepliconType::from_name(v[2].trim()) {
+                    Some(replicon_type) => {
+                        contig_to_genome
+                            .replicon_types
+                            .insert(separator.join(genome, contig), replicon_type);
+                    }
+                    None => {
+                        error!(
+                            "Unknown replicon type \"{}\" in the genome definition file, \
+                                expected chromosome, plasmid, or mobile_element",
+                            v[2].trim()
+                        );
+                        ExitStatus::ConfigurationError.exit();
+                    }
+                }
+            }
+
             if genome_to_contig.contains_key(genome) {
                 genome_to_contig
                     .get_mut(genome)
@@ -404,10 +627,10 @@ pub fn read_genome_definition_file(definition_file_path: &str) -> GenomesAndCont
         } else {
             error!(
                 "The line \"{}\" in the genome definition file is not a \
-                    genome name and contig name separated by a tab",
+                    genome name, contig name, and optional replicon type separated by tabs",
                 line
             );
-            exit(1);
+            ExitStatus::ConfigurationError.exit();
         }
     }
 
@@ -1,13 +1,176 @@
+use rayon::prelude::*;
 use std::collections::{BTreeMap, BinaryHeap};
 use std::fs::{create_dir_all, File};
 use std::io::Write;
+use std::ops::Range;
 use std::path::Path;
 
+use crate::annotator::variant_annotation::VariantAnnotations;
 use crate::model::byte_array_allele::ByteArrayAllele;
+use crate::model::consensus_depths::ConsensusDepths;
 use crate::model::variant_context::{VariantContext, VariantType};
 use crate::reference::reference_reader::ReferenceReader;
+use crate::reference::strain_polisher::StrainCoordinates;
+use crate::utils::base_utils::BaseUtils;
 use crate::utils::simple_interval::Locatable;
 
+/// How consensus genomes represent positions with uncertain allele support
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsensusAmbiguity {
+    /// Always use the allele with the highest depth
+    None,
+    /// Use IUPAC ambiguity codes at uncertain SNP positions and N at other uncertain positions
+    Iupac,
+    /// Use N at all uncertain positions
+    N,
+}
+
+/// Thresholds used to decide whether a consensus position is uncertain
+#[derive(Debug, Clone)]
+pub struct ConsensusOptions {
+    pub ambiguity: ConsensusAmbiguity,
+    pub min_major_allele_fraction: f64,
+    pub min_depth: i32,
+    /// Only generate consensus genomes for these samples. All samples are used when None
+    pub samples: Option<Vec<String>>,
+}
+
+impl ConsensusOptions {
+    pub fn new(
+        ambiguity: ConsensusAmbiguity,
+        min_major_allele_fraction: f64,
+        min_depth: i32,
+    ) -> Self {
+        Self {
+            ambiguity,
+            min_major_allele_fraction,
+            min_depth,
+            samples: None,
+        }
+    }
+
+    /// Restricts consensus generation to the provided samples
+    pub fn with_samples(mut self, samples: Vec<String>) -> Self {
+        self.samples = Some(samples);
+        self
+    }
+
+    /// Returns the indices of the samples that consensus genomes should be generated for.
+    /// Samples can be requested by their full name or by their file name without any directories
+    pub fn selected_sample_indices(&self, samples: &[&str]) -> Vec<usize> {
+        match &self.samples {
+            None => (0..samples.len()).collect(),
+            Some(requested) => samples
+                .iter()
+                .enumerate()
+                .filter(|(_, sample_name)| {
+                    requested.iter().any(|requested_name| {
+                        requested_name == *sample_name
+                            || requested_name.as_str() == short_sample_name(sample_name)
+                    })
+                })
+                .map(|(sample_index, _)| sample_index)
+                .collect(),
+        }
+    }
+
+    /// Whether consensus genomes should mask uncertain positions, i.e. whether
+    /// --consensus-ambiguity was given and is not none
+    pub fn masking_requested(args: &clap::ArgMatches) -> bool {
+        match args
+            .try_get_one::<String>("consensus-ambiguity")
+            .ok()
+            .flatten()
+        {
+            Some(ambiguity) => ambiguity != "none",
+            None => false,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let ambiguity = match args
+            .get_one::<String>("consensus-ambiguity")
+            .unwrap()
+            .as_str()
+        {
+            "iupac" => ConsensusAmbiguity::Iupac,
+            "n" => ConsensusAmbiguity::N,
+            _ => ConsensusAmbiguity::None,
+        };
+
+        let options = Self::new(
+            ambiguity,
+            *args
+                .get_one::<f64>("consensus-min-allele-fraction")
+                .unwrap(),
+            *args.get_one::<i32>("consensus-min-depth").unwrap(),
+        );
+
+        match args.get_many::<String>("consensus-samples") {
+            Some(samples) => options.with_samples(samples.cloned().collect()),
+            None => options,
+        }
+    }
+
+    /// If the position of the given variant context is uncertain in the given sample, returns the
+    /// bases that should replace the reference allele in the consensus along with the reason the
+    /// position was masked. Positions with depth below the minimum are always masked with N, while
+    /// positions where the major allele fraction is too low use IUPAC codes for SNPs if requested.
+    pub fn masked_bases(
+        &self,
+        vc: &mut VariantContext,
+        sample_index: usize,
+    ) -> Option<(Vec<u8>, &'static str)> {
+        let ad = vc.genotypes.genotypes()[sample_index].ad.clone();
+        self.masked_bases_for_depths(vc, &ad)
+    }
+
+    /// As `masked_bases` but using the provided allele depths, e.g. depths summed across samples
+    pub fn masked_bases_for_depths(
+        &self,
+        vc: &mut VariantContext,
+        ad: &[i32],
+    ) -> Option<(Vec<u8>, &'static str)> {
+        if self.ambiguity == ConsensusAmbiguity::None {
+            return None;
+        }
+
+        let ref_length = vc.get_reference().bases.len();
+        let total_depth: i32 = ad.iter().sum();
+        if total_depth < self.min_depth {
+            return Some((vec![b'N'; ref_length], "low_depth"));
+        }
+
+        let major_depth = *ad.iter().max().unwrap_or(&0);
+        if total_depth > 0
+            && (major_depth as f64 / total_depth as f64) < self.min_major_allele_fraction
+        {
+            let is_snp = *vc.get_type() == VariantType::Snp;
+            let bases = match (self.ambiguity, is_snp) {
+                (ConsensusAmbiguity::Iupac, true) => {
+                    let supported_bases = vc
+                        .alleles
+                        .iter()
+                        .zip(ad.iter())
+                        .filter(|(_, depth)| **depth > 0)
+                        .map(|(allele, _)| allele.bases[0])
+                        .collect::<Vec<u8>>();
+                    vec![BaseUtils::iupac_code(&supported_bases)]
+                }
+                _ => vec![b'N'; ref_length],
+            };
+            return Some((bases, "ambiguous"));
+        }
+
+        None
+    }
+}
+
+/// Sample names may be full paths to the read or BAM files, so strip any directories
+fn short_sample_name(sample_name: &str) -> &str {
+    sample_name.rsplitn(2, '/').next().unwrap()
+}
+
 /// Struct housing methods for writing out genomes when given specific variant information
 /// Basically a wrapper for reference reader
 pub struct ReferenceWriter<'a> {
@@ -27,21 +190,27 @@ impl<'a> ReferenceWriter<'a> {
 
     /// Generates the potential strain genomes calculated by Lorikeet. The VariantContexts are expected
     /// To be tagged with one or more strain genomes in their `attributes` with `VariantAnnotation::Strain`
-    /// tag
+    /// tag. The contexts are sorted in place and left to the caller, e.g. to be written to the VCF
+    /// file afterwards. Returns where the reference positions ended up in each strain genome
     pub fn generate_strains(
         &mut self,
-        variant_contexts: Vec<VariantContext>,
+        variant_contexts: &mut [VariantContext],
         ref_idx: usize,
         strain_ids_present: Vec<usize>,
-    ) {
-        let mut grouped_variant_contexts = Self::split_variant_contexts_by_tid(variant_contexts);
+    ) -> BTreeMap<usize, StrainCoordinates> {
+        variant_contexts.par_sort_unstable();
+        let contig_ranges = Self::contig_ranges(variant_contexts);
         let tids = self
             .reference_reader
             .retrieve_tids_for_ref_index(ref_idx)
             .unwrap()
             .clone();
 
+        let mut strain_coordinates = BTreeMap::new();
         for strain_idx in strain_ids_present {
+            let coordinates = strain_coordinates
+                .entry(strain_idx)
+                .or_insert_with(StrainCoordinates::new);
             let file_name = format!(
                 "{}/{}_strain_{}.fna",
                 self.output_prefix,
@@ -68,7 +237,9 @@ impl<'a> ReferenceWriter<'a> {
                 let old_length = new_bases.len();
                 // This value holds how far right or left the vc location has shifted as we add indels
                 let mut offset = 0;
-                let variant_contexts_of_contig = grouped_variant_contexts.get_mut(&tid);
+                let variant_contexts_of_contig = contig_ranges
+                    .get(tid)
+                    .map(|range| &mut variant_contexts[range.clone()]);
                 let mut variations = 0;
                 match variant_contexts_of_contig {
                     Some(variant_contexts_of_contig) => {
@@ -84,6 +255,7 @@ impl<'a> ReferenceWriter<'a> {
                                     variant_type,
                                     &mut offset,
                                 );
+                                coordinates.add_shift(*tid, vc.loc.end + 1, offset);
                                 variations += if is_ref { 0 } else { 1 };
                             }
                         }
@@ -112,110 +284,349 @@ impl<'a> ReferenceWriter<'a> {
                 }
             }
         }
+
+        strain_coordinates
+    }
+
+    /// Writes a TSV with one row per strain annotated variant and one column per strain. Each cell
+    /// contains the allele carried by that strain at the variant, i.e. the alternate allele if
+    /// the variant is part of the strain and the reference allele otherwise.
+    /// Positions are 1-based to match the VCF output.
+    pub fn write_strain_allele_matrix(
+        &self,
+        variant_contexts: &[VariantContext],
+        ref_idx: usize,
+        strain_ids_present: &[usize],
+    ) {
+        let file_name = format!(
+            "{}/{}_strain_alleles.tsv",
+            self.output_prefix, self.reference_reader.genomes_and_contigs.genomes[ref_idx],
+        );
+        let file_path = Path::new(&file_name);
+        debug!("File path {}", &file_name);
+        let mut file_open =
+            File::create(file_path).expect("No Read or Write Permission in current directory");
+
+        writeln!(
+            file_open,
+            "##source=lorikeet-v{}",
+            env!("CARGO_PKG_VERSION")
+        )
+        .expect("Unable to write to file");
+        write!(file_open, "contig\tpos\tref").expect("Unable to write to file");
+        for strain_id in strain_ids_present {
+            write!(file_open, "\tstrain_{}", strain_id).expect("Unable to write to file");
+        }
+        writeln!(file_open).expect("Unable to write to file");
+
+        for vc in variant_contexts {
+            if !vc
+                .attributes
+                .contains_key(VariantAnnotations::Strain.to_key())
+            {
+                continue;
+            }
+
+            write!(
+                file_open,
+                "{}\t{}\t{}",
+                std::str::from_utf8(self.reference_reader.get_target_name(vc.loc.get_contig()))
+                    .unwrap(),
+                vc.loc.start + 1,
+                String::from_utf8_lossy(&vc.get_reference().bases),
+            )
+            .expect("Unable to write to file");
+            for allele in Self::strain_alleles(vc, strain_ids_present) {
+                write!(file_open, "\t{}", String::from_utf8_lossy(&allele.bases))
+                    .expect("Unable to write to file");
+            }
+            writeln!(file_open).expect("Unable to write to file");
+        }
+    }
+
+    /// The allele carried by each of the given strains at this variant context. Strains carry
+    /// the first alternate allele if the context is part of the strain, otherwise the reference.
+    pub fn strain_alleles<'b>(
+        vc: &'b VariantContext,
+        strain_ids: &[usize],
+    ) -> Vec<&'b ByteArrayAllele> {
+        let reference = vc.get_reference();
+        let alternate = vc.get_alternate_alleles().first().copied().unwrap_or(reference);
+        strain_ids
+            .iter()
+            .map(|strain_id| {
+                if vc.part_of_strain(*strain_id) {
+                    alternate
+                } else {
+                    reference
+                }
+            })
+            .collect()
     }
 
     /// Generates the per sample consensus genomes based on the provided variant contexts.
     /// The consensus is defined as the most dominant variant at a given position on the reference
-    /// genome measured by read depth.
+    /// genome measured by read depth. A majority consensus is also generated using the allele
+    /// depths summed across all selected samples. Positions deemed uncertain by the
+    /// `ConsensusOptions` are masked and written to a BED file alongside each consensus, in
+    /// consensus coordinates. Positions where `consensus_depths` is below the minimum depth are
+    /// masked whether or not they have a variant.
     pub fn generate_consensus(
         &mut self,
         variant_contexts: Vec<VariantContext>,
         ref_idx: usize,
         samples: &[&str],
+        options: &ConsensusOptions,
+        consensus_depths: Option<&ConsensusDepths>,
     ) {
         let mut grouped_variant_contexts = Self::split_variant_contexts_by_tid(variant_contexts);
+        let selected_samples = options.selected_sample_indices(samples);
+        if selected_samples.is_empty() {
+            warn!(
+                "None of the samples requested by --consensus-samples were found, skipping consensus generation for {}",
+                &self.reference_reader.genomes_and_contigs.genomes[ref_idx]
+            );
+            return;
+        }
+
+        let genome_name = self.reference_reader.genomes_and_contigs.genomes[ref_idx].clone();
+        for sample_index in selected_samples.iter() {
+            let sample_name = short_sample_name(samples[*sample_index]);
+            self.write_consensus_genome(
+                &mut grouped_variant_contexts,
+                ref_idx,
+                sample_name,
+                &format!("{}_consensus_{}", genome_name, sample_name),
+                options,
+                consensus_depths,
+                &[*sample_index],
+                |vc| vc.genotypes.genotypes()[*sample_index].ad.clone(),
+            );
+        }
+
+        self.write_consensus_genome(
+            &mut grouped_variant_contexts,
+            ref_idx,
+            "majority",
+            // named apart from the sample consensus files, so a sample can be called majority
+            &format!("{}_majority_consensus", genome_name),
+            options,
+            consensus_depths,
+            &selected_samples,
+            |vc| Self::summed_allele_depths(vc, &selected_samples),
+        );
+    }
+
+    /// Sums the allele depths of the given samples
+    pub fn summed_allele_depths(vc: &VariantContext, sample_indices: &[usize]) -> Vec<i32> {
+        let mut depths = vec![0; vc.alleles.len()];
+        for sample_index in sample_indices {
+            for (depth, sample_depth) in depths
+                .iter_mut()
+                .zip(vc.genotypes.genotypes()[*sample_index].ad.iter())
+            {
+                *depth += *sample_depth;
+            }
+        }
+        depths
+    }
+
+    /// Writes out a single consensus genome for the given reference. `allele_depths` provides
+    /// the depth of each allele of a variant context used to pick the consensus allele, and the
+    /// depth of the samples in `sample_indices` is used to mask positions without a variant. The
+    /// consensus name is embedded in each FASTA header as `{contig}|{name}`, and the FASTA and
+    /// masked BED files are named after `file_stem`
+    fn write_consensus_genome<F: Fn(&VariantContext) -> Vec<i32>>(
+        &mut self,
+        grouped_variant_contexts: &mut BTreeMap<usize, Vec<VariantContext>>,
+        ref_idx: usize,
+        consensus_name: &str,
+        file_stem: &str,
+        options: &ConsensusOptions,
+        consensus_depths: Option<&ConsensusDepths>,
+        sample_indices: &[usize],
+        allele_depths: F,
+    ) {
         let tids = self
             .reference_reader
             .retrieve_tids_for_ref_index(ref_idx)
             .unwrap()
             .clone();
-        for (sample_index, sample_name) in samples.into_iter().enumerate() {
-            let file_name = format!(
-                "{}/{}_consensus_{}.fna",
-                self.output_prefix,
-                self.reference_reader.genomes_and_contigs.genomes[ref_idx],
-                &sample_name.rsplitn(2, '/').next().unwrap(),
-            );
-            let file_path = Path::new(&file_name);
-            debug!("File path {}", &file_name);
-            // Open new reference file or create one
-            let mut file_open = File::create(file_path).unwrap_or_else(|_| {
+        let file_name = format!("{}/{}.fna", self.output_prefix, file_stem);
+        let file_path = Path::new(&file_name);
+        debug!("File path {}", &file_name);
+        // Open new reference file or create one
+        let mut file_open = File::create(file_path).unwrap_or_else(|_| {
+            panic!(
+                "No Read or Write Permission in current directory: {:?}",
+                file_path
+            )
+        });
+        let mut masked_file = if options.ambiguity != ConsensusAmbiguity::None {
+            let masked_file_name = format!("{}/{}_masked.bed", self.output_prefix, file_stem);
+            Some(File::create(&masked_file_name).unwrap_or_else(|_| {
                 panic!(
                     "No Read or Write Permission in current directory: {:?}",
-                    file_path
+                    &masked_file_name
                 )
-            });
-            for tid in tids.iter() {
-                if self
-                    .reference_reader
-                    .fetch_contig_from_reference_by_tid(*tid, ref_idx)
-                    .is_err()
-                {
-                    continue;
-                };
-                self.reference_reader.read_sequence_to_vec();
-                debug!(
-                    "Fetched length {} tid {} ref_idx {} ",
-                    self.reference_reader.current_sequence.len(),
-                    *tid,
-                    ref_idx
-                );
-                let mut new_bases = std::mem::take(&mut self.reference_reader.current_sequence);
-                let old_length = new_bases.len();
-                debug!("Contig length {}", old_length);
-                // This value holds how far right or left the vc location has shifted as we add indels
-                let mut offset = 0;
-                let variant_contexts_of_contig = grouped_variant_contexts.get_mut(&tid);
-                let mut variations = 0;
-                match variant_contexts_of_contig {
-                    Some(variant_contexts_of_contig) => {
-                        for mut vc in variant_contexts_of_contig.iter_mut() {
-                            let consensus_allele = vc.get_consensus_allele(sample_index);
-                            match consensus_allele {
-                                Some(consensus_allele) => {
-                                    let variant_type = vc.get_type().clone();
-                                    let is_ref = consensus_allele.is_ref;
-                                    Self::modify_reference_bases_based_on_variant_type(
-                                        &mut new_bases,
-                                        consensus_allele,
-                                        &mut vc,
-                                        variant_type,
-                                        &mut offset,
-                                    );
-                                    variations += if is_ref { 0 } else { 1 };
-                                }
-                                None => continue,
+            }))
+        } else {
+            None
+        };
+        for tid in tids.iter() {
+            if self
+                .reference_reader
+                .fetch_contig_from_reference_by_tid(*tid, ref_idx)
+                .is_err()
+            {
+                continue;
+            };
+            self.reference_reader.read_sequence_to_vec();
+            debug!(
+                "Fetched length {} tid {} ref_idx {} ",
+                self.reference_reader.current_sequence.len(),
+                *tid,
+                ref_idx
+            );
+            let mut new_bases = std::mem::take(&mut self.reference_reader.current_sequence);
+            let old_length = new_bases.len();
+            debug!("Contig length {}", old_length);
+            // This value holds how far right or left the vc location has shifted as we add indels
+            let mut offset = 0;
+            let variant_contexts_of_contig = grouped_variant_contexts.get_mut(tid);
+            let mut variations = 0;
+            let mut masked_positions = Vec::new();
+
+            // positions without enough reads are masked whether or not there is a variant there,
+            // while the bases are still in reference coordinates
+            let low_depth_ranges = match consensus_depths {
+                Some(consensus_depths) if options.ambiguity != ConsensusAmbiguity::None => {
+                    consensus_depths.low_depth_ranges(
+                        *tid,
+                        old_length,
+                        sample_indices,
+                        options.min_depth,
+                    )
+                }
+                _ => Vec::new(),
+            };
+            for range in low_depth_ranges.iter() {
+                new_bases[range.clone()]
+                    .iter_mut()
+                    .for_each(|base| *base = b'N');
+            }
+            let mut low_depth_ranges = low_depth_ranges.into_iter().peekable();
+            match variant_contexts_of_contig {
+                Some(variant_contexts_of_contig) => {
+                    for mut vc in variant_contexts_of_contig.iter_mut() {
+                        // stretches before the variant are shifted by the variants before them
+                        while let Some(range) =
+                            low_depth_ranges.next_if(|range| range.start <= vc.loc.start)
+                        {
+                            masked_positions.push((
+                                (range.start as i64 + offset) as usize,
+                                (range.end as i64 + offset) as usize,
+                                "low_depth",
+                            ));
+                        }
+
+                        let depths = allele_depths(&*vc);
+                        // a variant without any depth is masked too, so this comes first
+                        if let Some((masked_bases, reason)) =
+                            options.masked_bases_for_depths(&mut vc, &depths)
+                        {
+                            // masked bases are the same length as the reference allele, so the
+                            // offset does not change
+                            let start = (vc.loc.start as i64 + offset) as usize;
+                            let end = start + masked_bases.len();
+                            let already_masked = reason == "low_depth"
+                                && new_bases[start..end].iter().all(|base| *base == b'N');
+                            new_bases.splice(start..end, masked_bases.into_iter());
+                            if !already_masked {
+                                masked_positions.push((start, end, reason));
                             }
+                            continue;
+                        }
+
+                        let consensus_allele = vc.get_consensus_allele_from_depths(&depths);
+                        match consensus_allele {
+                            Some(consensus_allele) => {
+                                let variant_type = vc.get_type().clone();
+                                let is_ref = consensus_allele.is_ref;
+                                Self::modify_reference_bases_based_on_variant_type(
+                                    &mut new_bases,
+                                    consensus_allele,
+                                    &mut vc,
+                                    variant_type,
+                                    &mut offset,
+                                );
+                                variations += if is_ref { 0 } else { 1 };
+                            }
+                            None => continue,
                         }
-                    }
-                    None => {
-                        // pass
                     }
                 }
+                None => {
+                    // pass
+                }
+            }
+            for range in low_depth_ranges {
+                masked_positions.push((
+                    (range.start as i64 + offset) as usize,
+                    (range.end as i64 + offset) as usize,
+                    "low_depth",
+                ));
+            }
 
-                debug!(
-                    "Writing contig {}",
-                    std::str::from_utf8(self.reference_reader.get_target_name(*tid)).unwrap()
-                );
-                // write the contig header
-                writeln!(
-                    file_open,
-                    ">{} sample_consensus={} old_length={} new_length={} variations={}",
-                    std::str::from_utf8(self.reference_reader.get_target_name(*tid)).unwrap(),
-                    sample_name,
-                    old_length,
-                    new_bases.len(),
-                    variations
-                )
-                .expect("Unable to write to file");
+            let contig_name =
+                std::str::from_utf8(self.reference_reader.get_target_name(*tid)).unwrap();
+            debug!("Writing contig {}", contig_name);
+            // write the contig header
+            writeln!(
+                file_open,
+                ">{}|{} sample_consensus={} old_length={} new_length={} variations={}",
+                contig_name,
+                consensus_name,
+                consensus_name,
+                old_length,
+                new_bases.len(),
+                variations
+            )
+            .expect("Unable to write to file");
 
-                // write out the actual contig
-                for line in new_bases[..].chunks(60).into_iter() {
-                    file_open.write_all(line).unwrap();
-                    file_open.write_all(b"\n").unwrap();
+            if let Some(masked_file) = masked_file.as_mut() {
+                for (start, end, reason) in masked_positions {
+                    writeln!(
+                        masked_file,
+                        "{}\t{}\t{}\t{}",
+                        contig_name, start, end, reason
+                    )
+                    .expect("Unable to write to file");
                 }
             }
+
+            // write out the actual contig
+            for line in new_bases[..].chunks(60).into_iter() {
+                file_open.write_all(line).unwrap();
+                file_open.write_all(b"\n").unwrap();
+            }
+        }
+    }
+
+    /// The range of the contexts of each contig in contexts sorted by position
+    fn contig_ranges(variant_contexts: &[VariantContext]) -> BTreeMap<usize, Range<usize>> {
+        let mut contig_ranges = BTreeMap::new();
+        let mut start = 0;
+        for (index, vc) in variant_contexts.iter().enumerate() {
+            let tid = vc.loc.get_contig();
+            if index + 1 == variant_contexts.len()
+                || variant_contexts[index + 1].loc.get_contig() != tid
+            {
+                contig_ranges.insert(tid, start..index + 1);
+                start = index + 1;
+            }
         }
+        contig_ranges
     }
 
     /// Takes a list of variant contexts and returns a BTreeMap with contexts grouped by which
@@ -0,0 +1,395 @@
+use rayon::prelude::*;
+use rust_htslib::bam::{self, record::Cigar, Read, Record};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::linkage::strain_read_binning::StrainReadBinner;
+use crate::reference::partial_order_alignment::PartialOrderGraph;
+use crate::utils::errors::BirdToolError;
+
+/// Where the positions of a reference genome ended up in a strain genome generated from it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StrainCoordinates {
+    // for each tid, the reference positions at which the shift of the following positions
+    // changes, sorted by position
+    shifts: HashMap<usize, Vec<(usize, i64)>>,
+}
+
+impl StrainCoordinates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that reference positions from `ref_pos` onwards are shifted by `shift` in the strain
+    pub fn add_shift(&mut self, tid: usize, ref_pos: usize, shift: i64) {
+        let shifts = self.shifts.entry(tid).or_default();
+        let current = shifts.last().map(|(_, shift)| *shift).unwrap_or(0);
+        if shift != current {
+            shifts.push((ref_pos, shift));
+        }
+    }
+
+    /// The position in the strain of a reference position
+    pub fn to_strain(&self, tid: usize, ref_pos: usize) -> i64 {
+        let shift = match self.shifts.get(&tid) {
+            Some(shifts) => {
+                let index = shifts.partition_point(|(pos, _)| *pos <= ref_pos);
+                if index == 0 {
+                    0
+                } else {
+                    shifts[index - 1].1
+                }
+            }
+            None => 0,
+        };
+        ref_pos as i64 + shift
+    }
+}
+
+/// Number of reads used and corrections made when polishing a strain genome
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolishingSummary {
+    pub reads: usize,
+    pub windows: usize,
+    pub polished_windows: usize,
+    pub corrections: usize,
+}
+
+/// Polishes strain genomes with the long reads binned to each strain, replacing each window with
+/// the partial order alignment consensus of its fragments, as racon does.
+pub struct StrainPolisher {
+    window_length: usize,
+    min_coverage: usize,
+}
+
+impl StrainPolisher {
+    pub const DEFAULT_WINDOW_LENGTH: usize = 500;
+    pub const DEFAULT_MIN_COVERAGE: usize = 3;
+    // fragments shorter than this fraction of their window are too short to place reliably
+    const MIN_FRAGMENT_FRACTION: f64 = 0.1;
+
+    pub fn new(window_length: usize, min_coverage: usize) -> Self {
+        Self {
+            window_length: window_length.max(1),
+            min_coverage,
+        }
+    }
+
+    /// The polisher requested by --polish-strains, if any
+    pub fn from_args(args: &clap::ArgMatches) -> Option<Self> {
+        let requested = args
+            .try_get_one::<bool>("polish-strains")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        if !requested {
+            return None;
+        }
+
+        let window_length = args
+            .try_get_one::<usize>("polish-window-length")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(Self::DEFAULT_WINDOW_LENGTH);
+        let min_coverage = args
+            .try_get_one::<usize>("polish-min-coverage")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(Self::DEFAULT_MIN_COVERAGE);
+        Some(Self::new(window_length, min_coverage))
+    }
+
+    /// The path of the polished genome of a strain
+    pub fn polished_path(output_prefix: &str, reference_name: &str, strain_id: usize) -> String {
+        format!(
+            "{}/{}_strain_{}_polished.fna",
+            output_prefix, reference_name, strain_id
+        )
+    }
+
+    /// Polishes every strain genome of a reference with the reads binned to it from the given
+    /// samples and writes the summary table. Returns the summary of each strain
+    pub fn polish_strains(
+        &self,
+        output_prefix: &str,
+        reference_name: &str,
+        strain_coordinates: &BTreeMap<usize, StrainCoordinates>,
+        sample_names: &[&str],
+    ) -> Result<Vec<(usize, PolishingSummary)>, BirdToolError> {
+        let mut summaries = Vec::with_capacity(strain_coordinates.len());
+        for (strain_id, coordinates) in strain_coordinates.iter() {
+            let bam_paths = sample_names
+                .iter()
+                .map(|sample_name| {
+                    StrainReadBinner::bam_path(
+                        output_prefix,
+                        reference_name,
+                        sample_name,
+                        *strain_id,
+                    )
+                })
+                .filter(|bam_path| Path::new(bam_path).exists())
+                .collect::<Vec<String>>();
+            let summary = self.polish_strain(
+                &format!(
+                    "{}/{}_strain_{}.fna",
+                    output_prefix, reference_name, strain_id
+                ),
+                &Self::polished_path(output_prefix, reference_name, *strain_id),
+                *strain_id,
+                &bam_paths,
+                coordinates,
+            )?;
+            summaries.push((*strain_id, summary));
+        }
+
+        Self::write_summary(
+            &format!("{}/{}_strain_polishing.tsv", output_prefix, reference_name),
+            &summaries,
+        )?;
+        Ok(summaries)
+    }
+
+    /// Polishes each contig of a strain genome with the reads of the given BAM files, writing the
+    /// polished genome to `polished_path`
+    pub fn polish_strain(
+        &self,
+        strain_path: &str,
+        polished_path: &str,
+        strain_id: usize,
+        bam_paths: &[String],
+        coordinates: &StrainCoordinates,
+    ) -> Result<PolishingSummary, BirdToolError> {
+        let strain_reader = bio::io::fasta::Reader::from_file(strain_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to open {}: {:?}", strain_path, e))
+        })?;
+        let mut bam_readers = bam_paths
+            .iter()
+            .map(|bam_path| {
+                bam::IndexedReader::from_path(bam_path).map_err(|e| {
+                    BirdToolError::IOError(format!("Unable to open {}: {:?}", bam_path, e))
+                })
+            })
+            .collect::<Result<Vec<bam::IndexedReader>, BirdToolError>>()?;
+        let mut polished_file = File::create(polished_path).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to create {}: {:?}", polished_path, e))
+        })?;
+
+        let mut summary = PolishingSummary::default();
+        for contig in strain_reader.records() {
+            let contig = contig.map_err(|e| {
+                BirdToolError::IOError(format!("Unable to read {}: {:?}", strain_path, e))
+            })?;
+            let backbone = contig.seq();
+            let n_windows = backbone.len().div_ceil(self.window_length);
+            let mut fragments = vec![Vec::new(); n_windows];
+
+            for (bam_reader, bam_path) in bam_readers.iter_mut().zip(bam_paths.iter()) {
+                let tid = match bam_reader.header().tid(contig.id().as_bytes()) {
+                    Some(tid) => tid,
+                    None => continue,
+                };
+                bam_reader.fetch(tid as i32).map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Unable to fetch {} from {}: {:?}",
+                        contig.id(),
+                        bam_path,
+                        e
+                    ))
+                })?;
+                let mut record = Record::new();
+                while let Some(result) = bam_reader.read(&mut record) {
+                    result.map_err(|e| {
+                        BirdToolError::IOError(format!("Unable to read {}: {:?}", bam_path, e))
+                    })?;
+                    if record.is_unmapped() || record.is_secondary() || record.is_duplicate() {
+                        continue;
+                    }
+                    let read_fragments = self.split_read(
+                        record.pos(),
+                        &record.cigar(),
+                        &record.seq().as_bytes(),
+                        |ref_pos| coordinates.to_strain(tid as usize, ref_pos),
+                        backbone.len(),
+                    );
+                    if !read_fragments.is_empty() {
+                        summary.reads += 1;
+                    }
+                    for (window, fragment) in read_fragments {
+                        fragments[window].push(fragment);
+                    }
+                }
+            }
+
+            let windows = fragments
+                .into_par_iter()
+                .enumerate()
+                .map(|(window, window_fragments)| {
+                    let start = window * self.window_length;
+                    let end = (start + self.window_length).min(backbone.len());
+                    self.polish_window(&backbone[start..end], &window_fragments)
+                })
+                .collect::<Vec<Option<(Vec<u8>, usize)>>>();
+
+            let mut polished = Vec::with_capacity(backbone.len());
+            let (mut contig_corrections, mut polished_windows) = (0, 0);
+            for (window, polished_window) in windows.into_iter().enumerate() {
+                match polished_window {
+                    Some((bases, corrections)) => {
+                        polished.extend(bases);
+                        contig_corrections += corrections;
+                        polished_windows += 1;
+                    }
+                    None => {
+                        let start = window * self.window_length;
+                        let end = (start + self.window_length).min(backbone.len());
+                        polished.extend_from_slice(&backbone[start..end]);
+                    }
+                }
+            }
+            summary.windows += n_windows;
+            summary.polished_windows += polished_windows;
+            summary.corrections += contig_corrections;
+
+            let write_error = |e: std::io::Error| {
+                BirdToolError::IOError(format!("Unable to write {}: {:?}", polished_path, e))
+            };
+            writeln!(
+                polished_file,
+                ">{} strain_id={} old_length={} new_length={} corrections={}",
+                contig.id(),
+                strain_id,
+                backbone.len(),
+                polished.len(),
+                contig_corrections
+            )
+            .map_err(write_error)?;
+            for line in polished.chunks(60) {
+                polished_file.write_all(line).map_err(write_error)?;
+                polished_file.write_all(b"\n").map_err(write_error)?;
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Splits the aligned bases of a read into the fragments falling in each window of a strain
+    /// contig of the given length. `to_strain` gives the strain position of a reference position,
+    /// and inserted bases belong to the window of the aligned base before them
+    pub fn split_read<F: Fn(usize) -> i64>(
+        &self,
+        ref_start: i64,
+        cigar: &[Cigar],
+        seq: &[u8],
+        to_strain: F,
+        contig_length: usize,
+    ) -> Vec<(usize, Vec<u8>)> {
+        // the window of each aligned read base
+        let mut read_windows: Vec<(usize, usize)> = Vec::new();
+        let mut current_window = None;
+        let (mut query_pos, mut ref_pos) = (0, ref_start.max(0) as usize);
+        for op in cigar {
+            match op {
+                Cigar::Match(len) | Cigar::Equal(len) | Cigar::Diff(len) => {
+                    for _ in 0..*len {
+                        let strain_pos = to_strain(ref_pos);
+                        current_window = if strain_pos >= 0 && (strain_pos as usize) < contig_length
+                        {
+                            Some(strain_pos as usize / self.window_length)
+                        } else {
+                            None
+                        };
+                        if let Some(window) = current_window {
+                            read_windows.push((query_pos, window));
+                        }
+                        query_pos += 1;
+                        ref_pos += 1;
+                    }
+                }
+                Cigar::Ins(len) => {
+                    if let Some(window) = current_window {
+                        read_windows.extend((0..*len as usize).map(|i| (query_pos + i, window)));
+                    }
+                    query_pos += *len as usize;
+                }
+                Cigar::SoftClip(len) => query_pos += *len as usize,
+                Cigar::Del(len) | Cigar::RefSkip(len) => ref_pos += *len as usize,
+                Cigar::HardClip(_) | Cigar::Pad(_) => {}
+            }
+        }
+
+        let mut fragments: Vec<(usize, usize, usize)> = Vec::new();
+        for (query_pos, window) in read_windows {
+            match fragments.last_mut() {
+                Some((last_window, _, end)) if *last_window == window && *end == query_pos => {
+                    *end += 1
+                }
+                _ => fragments.push((window, query_pos, query_pos + 1)),
+            }
+        }
+
+        fragments
+            .into_iter()
+            .filter(|(window, start, end)| {
+                let window_start = window * self.window_length;
+                let window_length =
+                    (window_start + self.window_length).min(contig_length) - window_start;
+                (end - start) as f64 >= window_length as f64 * Self::MIN_FRAGMENT_FRACTION
+                    && *end <= seq.len()
+            })
+            .map(|(window, start, end)| (window, seq[start..end].to_vec()))
+            .collect()
+    }
+
+    /// The consensus of a window and the number of corrections made to it, or None when the
+    /// window has too few fragments to be polished
+    pub fn polish_window(
+        &self,
+        backbone: &[u8],
+        fragments: &[Vec<u8>],
+    ) -> Option<(Vec<u8>, usize)> {
+        if fragments.is_empty() || fragments.len() < self.min_coverage {
+            return None;
+        }
+        let mut graph = PartialOrderGraph::new(backbone);
+        for fragment in fragments {
+            graph.add_sequence(fragment);
+        }
+        Some(graph.consensus())
+    }
+
+    /// Writes the number of reads used and corrections made for each strain
+    pub fn write_summary(
+        path: &str,
+        summaries: &[(usize, PolishingSummary)],
+    ) -> Result<(), BirdToolError> {
+        let mut file = File::create(path)
+            .map_err(|e| BirdToolError::IOError(format!("Unable to create {}: {:?}", path, e)))?;
+        let write_error = |e: std::io::Error| {
+            BirdToolError::IOError(format!("Unable to write {}: {:?}", path, e))
+        };
+        writeln!(
+            file,
+            "strain_id\treads\twindows\tpolished_windows\tcorrections"
+        )
+        .map_err(write_error)?;
+        for (strain_id, summary) in summaries {
+            writeln!(
+                file,
+                "{}\t{}\t{}\t{}\t{}",
+                strain_id,
+                summary.reads,
+                summary.windows,
+                summary.polished_windows,
+                summary.corrections
+            )
+            .map_err(write_error)?;
+        }
+        Ok(())
+    }
+}
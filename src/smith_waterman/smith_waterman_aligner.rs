@@ -7,6 +7,7 @@ use std::convert::TryFrom;
 // use crate::smith_waterman::bindings::*;
 use crate::reads::alignment_utils::AlignmentUtils;
 use crate::pair_hmm::pair_hmm_likelihood_calculation_engine::AVXMode;
+use crate::smith_waterman::bindings::SWParameters;
 
 lazy_static! {
     pub static ref ORIGINAL_DEFAULT: Parameters = Parameters::new(3, -1, -4, -3);
@@ -25,6 +26,96 @@ lazy_static! {
     pub static ref ALIGNMENT_TO_BEST_HAPLOTYPE_SW_PARAMETERS: Parameters = Parameters::new(10, -15, -30, -5);
 }
 
+/// The Smith-Waterman parameters used to recover dangling ends and to align haplotypes to the
+/// reference. The long read preset makes gaps cheaper, and --sw-match, --sw-mismatch, --sw-gap-open
+/// and --sw-gap-extend override both sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssemblySWParameters {
+    pub dangling_end: SWParameters,
+    pub haplotype_to_reference: SWParameters,
+}
+
+impl AssemblySWParameters {
+    pub fn short_read() -> Self {
+        Self {
+            dangling_end: SWParameters::new(25, -50, -110, -6),
+            haplotype_to_reference: SWParameters::new(200, -150, -260, -11),
+        }
+    }
+
+    pub fn long_read() -> Self {
+        Self {
+            dangling_end: SWParameters::new(25, -50, -70, -4),
+            haplotype_to_reference: SWParameters::new(200, -150, -180, -8),
+        }
+    }
+
+    /// The parameters of a named preset, if it exists
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "short-read" => Some(Self::short_read()),
+            "long-read" => Some(Self::long_read()),
+            _ => None,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let preset = args
+            .try_get_one::<String>("sw-preset")
+            .ok()
+            .flatten()
+            .and_then(|preset| Self::preset(preset.as_str()))
+            .unwrap_or_else(Self::short_read);
+        let weight = |name: &str| args.try_get_one::<i32>(name).ok().flatten().copied();
+
+        preset.with_weights(
+            weight("sw-match"),
+            weight("sw-mismatch"),
+            weight("sw-gap-open"),
+            weight("sw-gap-extend"),
+        )
+    }
+
+    /// Replaces the given weights in both parameter sets
+    pub fn with_weights(
+        self,
+        match_value: Option<i32>,
+        mismatch_penalty: Option<i32>,
+        gap_open_penalty: Option<i32>,
+        gap_extend_penalty: Option<i32>,
+    ) -> Self {
+        let replace = |parameters: SWParameters| {
+            SWParameters::new(
+                match_value.unwrap_or(parameters.match_value),
+                mismatch_penalty.unwrap_or(parameters.mismatch_penalty),
+                gap_open_penalty.unwrap_or(parameters.gap_open_penalty),
+                gap_extend_penalty.unwrap_or(parameters.gap_extend_penalty),
+            )
+        };
+        Self {
+            dangling_end: replace(self.dangling_end),
+            haplotype_to_reference: replace(self.haplotype_to_reference),
+        }
+    }
+
+    pub fn dangling_end_parameters(&self) -> Parameters {
+        Self::gkl_parameters(&self.dangling_end)
+    }
+
+    pub fn haplotype_to_reference_parameters(&self) -> Parameters {
+        Self::gkl_parameters(&self.haplotype_to_reference)
+    }
+
+    fn gkl_parameters(parameters: &SWParameters) -> Parameters {
+        Parameters::new(
+            parameters.match_value,
+            parameters.mismatch_penalty,
+            parameters.gap_open_penalty,
+            parameters.gap_extend_penalty,
+        )
+    }
+}
+
 pub struct SmithWatermanAligner {}
 
 impl SmithWatermanAligner {
@@ -120,4 +120,39 @@ impl BaseUtils {
             _ => return b'.',
         }
     }
+
+    /**
+     * Returns the IUPAC ambiguity code representing all of the provided bases.
+     * Non ACGT bases are ignored, N is returned if no valid bases are provided
+     */
+    pub fn iupac_code(bases: &[u8]) -> u8 {
+        let mut mask = 0u8;
+        for base in bases.iter() {
+            mask |= match base.to_ascii_uppercase() {
+                b'A' => 1,
+                b'C' => 2,
+                b'G' => 4,
+                b'T' => 8,
+                _ => 0,
+            };
+        }
+
+        match mask {
+            1 => b'A',
+            2 => b'C',
+            4 => b'G',
+            8 => b'T',
+            3 => b'M',
+            5 => b'R',
+            9 => b'W',
+            6 => b'S',
+            10 => b'Y',
+            12 => b'K',
+            7 => b'V',
+            11 => b'H',
+            13 => b'D',
+            14 => b'B',
+            _ => b'N',
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use std::error::Error;
 use std::fmt;
 
-#[derive(Clone)]
+// Debug names the kind of error along with its message, e.g. ConfigError("...")
+#[derive(Clone, Debug)]
 pub enum BirdToolError {
     InvalidClip(String),
     CigarBuilderError(String),
@@ -12,24 +13,13 @@ pub enum BirdToolError {
     InvalidVariationEvent(String),
     ProcessPanicked(String),
     DebugError(String),
+    GpuError(String),
+    ConfigError(String),
 }
 
-// Implement std::fmt::Display for AppError
-impl fmt::Display for BirdToolError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "An Error Occurred, Please Try Again!") // user-facing output
-    }
-}
-
-// Implement std::fmt::Debug for AppError
-impl fmt::Debug for BirdToolError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{{ file: {}, line: {} }}", file!(), line!()) // programmer-facing output
-    }
-}
-
-impl Error for BirdToolError {
-    fn description(&self) -> &str {
+impl BirdToolError {
+    /// The message describing what went wrong
+    pub fn message(&self) -> &str {
         match self {
             BirdToolError::InvalidClip(val)
             | BirdToolError::IOError(val)
@@ -39,7 +29,22 @@ impl Error for BirdToolError {
             | BirdToolError::SkipException(val)
             | BirdToolError::InvalidVariationEvent(val)
             | BirdToolError::ProcessPanicked(val)
-            | BirdToolError::DebugError(val) => val,
+            | BirdToolError::DebugError(val)
+            | BirdToolError::GpuError(val)
+            | BirdToolError::ConfigError(val) => val,
         }
     }
 }
+
+// Implement std::fmt::Display for AppError
+impl fmt::Display for BirdToolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message()) // user-facing output
+    }
+}
+
+impl Error for BirdToolError {
+    fn description(&self) -> &str {
+        self.message()
+    }
+}
@@ -0,0 +1,50 @@
+use crate::utils::errors::BirdToolError;
+
+/// The exit status of a lorikeet run, so that workflow engines can tell from the exit code whether
+/// a retry can help: 0 success, 1 failure, 2 configuration error, 3 external tool error and 4
+/// partial success, when some genomes failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success,
+    Failure,
+    ConfigurationError,
+    ExternalToolError,
+    PartialSuccess,
+}
+
+impl ExitStatus {
+    pub fn code(&self) -> i32 {
+        match self {
+            Self::Success => 0,
+            Self::Failure => 1,
+            Self::ConfigurationError => 2,
+            Self::ExternalToolError => 3,
+            Self::PartialSuccess => 4,
+        }
+    }
+
+    /// The exit status of a run that ended with an error
+    pub fn from_error(error: &BirdToolError) -> Self {
+        match error {
+            BirdToolError::ConfigError(_) => Self::ConfigurationError,
+            BirdToolError::ProcessPanicked(_) => Self::ExternalToolError,
+            _ => Self::Failure,
+        }
+    }
+
+    /// The exit status of a run given how many of its genomes failed
+    pub fn from_genomes(n_genomes: usize, n_failed: usize) -> Self {
+        if n_failed == 0 {
+            Self::Success
+        } else if n_failed < n_genomes {
+            Self::PartialSuccess
+        } else {
+            Self::Failure
+        }
+    }
+
+    /// Exits the process with this status
+    pub fn exit(&self) -> ! {
+        std::process::exit(self.code())
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::Read;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+
+use crate::utils::errors::BirdToolError;
+use crate::utils::log_events::LogEvents;
+
+/// The likely cause of a failed external command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFailureKind {
+    /// The program, or a program run by its shell command, is not installed or not on the PATH
+    MissingBinary,
+    /// The program was found but could not be executed
+    NotExecutable,
+    /// The program was killed with SIGKILL or reported an allocation failure, which is almost
+    /// always the out of memory killer or a memory limit of the scheduler
+    OutOfMemory,
+    /// The program was killed by any other signal
+    Signal(i32),
+    /// The program ran and exited with an error
+    RuntimeError,
+}
+
+impl CommandFailureKind {
+    // bash exits with 128 + n when a command it runs is killed by signal n
+    const SIGNAL_EXIT_OFFSET: i32 = 128;
+    const SIGKILL: i32 = 9;
+    // messages that name memory exhaustion explicitly. A bare "killed" is not enough, as tools
+    // also report processes killed by other signals or by the user
+    const OUT_OF_MEMORY_MESSAGES: [&'static str; 6] = [
+        "out of memory",
+        "out-of-memory",
+        "oom-kill",
+        "cannot allocate memory",
+        "std::bad_alloc",
+        "memoryerror",
+    ];
+
+    pub fn to_key(&self) -> &'static str {
+        match self {
+            Self::MissingBinary => "missing_binary",
+            Self::NotExecutable => "not_executable",
+            Self::OutOfMemory => "out_of_memory",
+            Self::Signal(_) => "signal",
+            Self::RuntimeError => "runtime_error",
+        }
+    }
+
+    /// Classifies a failure from the exit code or signal of the process and the tail of its
+    /// stderr
+    pub fn classify(exit_code: Option<i32>, signal: Option<i32>, stderr_tail: &str) -> Self {
+        let signal = signal.or_else(|| {
+            exit_code
+                .filter(|code| *code > Self::SIGNAL_EXIT_OFFSET)
+                .map(|code| code - Self::SIGNAL_EXIT_OFFSET)
+        });
+        let stderr_tail = stderr_tail.to_lowercase();
+        match (exit_code, signal) {
+            (Some(127), _) => Self::MissingBinary,
+            (Some(126), _) => Self::NotExecutable,
+            (_, Some(Self::SIGKILL)) => Self::OutOfMemory,
+            (_, Some(signal)) => Self::Signal(signal),
+            _ if stderr_tail.contains("command not found") => Self::MissingBinary,
+            _ if Self::OUT_OF_MEMORY_MESSAGES
+                .iter()
+                .any(|message| stderr_tail.contains(message)) =>
+            {
+                Self::OutOfMemory
+            }
+            _ => Self::RuntimeError,
+        }
+    }
+}
+
+/// A failed external command, with the last of what it wrote to stderr
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandFailure {
+    pub program: String,
+    pub kind: CommandFailureKind,
+    pub exit_code: Option<i32>,
+    pub signal: Option<i32>,
+    pub stderr_tail: String,
+}
+
+impl CommandFailure {
+    pub fn new(
+        program: &str,
+        exit_code: Option<i32>,
+        signal: Option<i32>,
+        stderr_tail: String,
+    ) -> Self {
+        Self {
+            program: program.to_string(),
+            kind: CommandFailureKind::classify(exit_code, signal, &stderr_tail),
+            exit_code,
+            signal,
+            stderr_tail,
+        }
+    }
+
+    fn from_status(program: &str, status: ExitStatus, stderr_tail: String) -> Self {
+        Self::new(program, status.code(), status.signal(), stderr_tail)
+    }
+
+    /// What went wrong and what can be done about it, followed by the tail of stderr
+    pub fn message(&self) -> String {
+        let mut message = match self.kind {
+            CommandFailureKind::MissingBinary => format!(
+                "{} could not be found. Check that it and the programs it runs are installed \
+                and on the PATH, e.g. by activating the lorikeet conda environment",
+                self.program
+            ),
+            CommandFailureKind::NotExecutable => format!(
+                "{} was found but could not be executed. Check its permissions",
+                self.program
+            ),
+            CommandFailureKind::OutOfMemory => format!(
+                "{} was killed, most likely for running out of memory. Retry with more memory \
+                or fewer threads",
+                self.program
+            ),
+            CommandFailureKind::Signal(signal) => {
+                format!("{} was killed by signal {}", self.program, signal)
+            }
+            CommandFailureKind::RuntimeError => match self.exit_code {
+                Some(code) => format!("{} failed with exit code {}", self.program, code),
+                None => format!("{} failed", self.program),
+            },
+        };
+        if !self.stderr_tail.is_empty() {
+            message.push_str(&format!("\nLast lines of stderr:\n{}", self.stderr_tail));
+        }
+        message
+    }
+
+    /// Reports the failure as an error, or as a `command_failed` event when JSON logging is
+    /// enabled
+    pub fn log(&self) {
+        if LogEvents::json_enabled() {
+            let exit_code = self.exit_code.map(|code| code.to_string());
+            let signal = self.signal.map(|signal| signal.to_string());
+            let message = self.message();
+            LogEvents::event(
+                "command_failed",
+                &[
+                    ("program", Some(self.program.as_str())),
+                    ("kind", Some(self.kind.to_key())),
+                    ("exit_code", exit_code.as_deref()),
+                    ("signal", signal.as_deref()),
+                    ("message", Some(message.as_str())),
+                    ("stderr_tail", Some(self.stderr_tail.as_str())),
+                ],
+            );
+        } else {
+            error!("{}", self.message());
+        }
+    }
+}
+
+impl From<CommandFailure> for BirdToolError {
+    fn from(failure: CommandFailure) -> Self {
+        BirdToolError::ProcessPanicked(failure.message())
+    }
+}
+
+/// Runs external programs to completion, capturing the last `STDERR_TAIL_BYTES` of stderr so that
+/// failures can be classified and reported.
+pub struct ExternalCommand {}
+
+impl ExternalCommand {
+    pub const STDERR_TAIL_BYTES: usize = 8 * 1024;
+
+    /// Runs a command to completion. `program` names the program in error messages
+    pub fn run(command: &mut Command, program: &str) -> Result<(), CommandFailure> {
+        let mut child = command.stderr(Stdio::piped()).spawn().map_err(|e| {
+            let exit_code = match e.kind() {
+                std::io::ErrorKind::NotFound => Some(127),
+                std::io::ErrorKind::PermissionDenied => Some(126),
+                _ => None,
+            };
+            CommandFailure::new(program, exit_code, None, e.to_string())
+        })?;
+
+        let stdout = child.stdout.take().map(|mut stdout| {
+            thread::spawn(move || {
+                let _ = std::io::copy(&mut stdout, &mut std::io::sink());
+            })
+        });
+        let stderr = child
+            .stderr
+            .take()
+            .map(|stderr| thread::spawn(move || Self::read_tail(stderr, Self::STDERR_TAIL_BYTES)));
+
+        let status = child.wait().map_err(|e| {
+            CommandFailure::new(
+                program,
+                None,
+                None,
+                format!("Unable to wait on process: {}", e),
+            )
+        })?;
+        if let Some(stdout) = stdout {
+            let _ = stdout.join();
+        }
+        let stderr_tail = stderr
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(CommandFailure::from_status(program, status, stderr_tail))
+        }
+    }
+
+    /// Runs a command to completion, logging the failure and exiting if it fails
+    pub fn run_or_exit(command: &mut Command, program: &str) {
+        if let Err(failure) = Self::run(command, program) {
+            failure.log();
+            error!("Cannot continue after {} failed.", program);
+            crate::utils::exit_status::ExitStatus::ExternalToolError.exit();
+        }
+    }
+
+    /// Reads a stream to its end, keeping at most the last `max_bytes`. A line cut by the limit is
+    /// dropped
+    pub fn read_tail<R: Read>(mut reader: R, max_bytes: usize) -> String {
+        let mut tail = Vec::new();
+        let mut buffer = [0; 8192];
+        let mut truncated = false;
+        loop {
+            match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    tail.extend_from_slice(&buffer[..n]);
+                    if tail.len() > 2 * max_bytes {
+                        tail.drain(..tail.len() - max_bytes);
+                        truncated = true;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        if tail.len() > max_bytes {
+            tail.drain(..tail.len() - max_bytes);
+            truncated = true;
+        }
+        if truncated {
+            if let Some(newline) = tail.iter().position(|byte| *byte == b'\n') {
+                tail.drain(..=newline);
+            }
+        }
+        String::from_utf8_lossy(&tail).trim().to_string()
+    }
+}
@@ -0,0 +1,237 @@
+use env_logger::filter::{Builder as FilterBuilder, Filter};
+use hashlink::LinkedHashMap;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    static ref EVENT_SINK: Mutex<Option<EventSink>> = Mutex::new(None);
+}
+
+static JSON_EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// The format used to report logging and progress information
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable log lines and interactive progress bars
+    Text,
+    /// One JSON object per line. Progress bars are replaced by stage events
+    Json,
+}
+
+impl LogFormat {
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        match args
+            .try_get_one::<String>("log-format")
+            .ok()
+            .flatten()
+            .map(|s| s.as_str())
+        {
+            Some("json") => Self::Json,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// A single event written as one line of JSON. The fields of the event follow the timestamp and
+/// event type in the order they were given
+#[derive(Debug, Serialize)]
+pub struct LogEvent<'a> {
+    pub timestamp: f64,
+    pub event: &'a str,
+    #[serde(flatten)]
+    pub fields: LinkedHashMap<&'a str, Option<&'a str>>,
+}
+
+/// Where JSON events are written, along with the stage each genome is currently in so that
+/// stage-end events can report how long the stage took
+struct EventSink {
+    writer: Box<dyn Write + Send>,
+    stages: HashMap<String, (String, Instant)>,
+}
+
+impl EventSink {
+    fn emit(&mut self, line: &str) {
+        // Failing to write a log line should never bring down a run
+        let _ = writeln!(self.writer, "{}", line);
+        let _ = self.writer.flush();
+    }
+}
+
+/// Logger used in place of env_logger when `--log-format json` is requested. Log records are
+/// written to the event sink as JSON lines, with warnings and errors given their own event type
+struct JsonLogger {
+    filter: Filter,
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+
+        let event = match record.level() {
+            log::Level::Error => "error",
+            log::Level::Warn => "warning",
+            _ => "log",
+        };
+        let line = LogEvents::format_event(
+            LogEvents::timestamp(),
+            event,
+            &[
+                ("level", Some(record.level().as_str())),
+                ("target", Some(record.target())),
+                ("message", Some(&record.args().to_string())),
+            ],
+        );
+        LogEvents::emit(&line);
+    }
+
+    fn flush(&self) {
+        if let Some(sink) = EVENT_SINK.lock().unwrap().as_mut() {
+            let _ = sink.writer.flush();
+        }
+    }
+}
+
+pub struct LogEvents {}
+
+impl LogEvents {
+    /// Installs the JSON logger and event sink. Events go to `log_file` if provided, otherwise to
+    /// stderr. `filters` follows the RUST_LOG syntax and is applied on top of `log_level`
+    pub fn init_json(
+        log_level: LevelFilter,
+        filters: Option<&str>,
+        log_file: Option<&str>,
+    ) -> Result<(), String> {
+        let writer: Box<dyn Write + Send> = match log_file {
+            Some(path) => Box::new(
+                File::create(path)
+                    .map_err(|e| format!("Unable to create log file {}: {}", path, e))?,
+            ),
+            None => Box::new(std::io::stderr()),
+        };
+
+        let mut filter = FilterBuilder::new();
+        filter.filter_level(log_level);
+        if let Some(filters) = filters {
+            filter.parse(filters);
+        }
+        let filter = filter.build();
+        let max_level = filter.filter();
+
+        log::set_boxed_logger(Box::new(JsonLogger { filter }))
+            .map_err(|e| format!("Failed to set logger: {}", e))?;
+        log::set_max_level(max_level);
+
+        *EVENT_SINK.lock().unwrap() = Some(EventSink {
+            writer,
+            stages: HashMap::new(),
+        });
+        JSON_EVENTS.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Whether structured events are being emitted
+    pub fn json_enabled() -> bool {
+        JSON_EVENTS.load(Ordering::SeqCst)
+    }
+
+    /// Interactive progress bars are only drawn when writing plain text to a terminal
+    pub fn show_progress_bars() -> bool {
+        !Self::json_enabled() && std::io::stderr().is_terminal()
+    }
+
+    /// Records that the given genome has started a new stage. Any stage the genome was previously
+    /// in is ended first
+    pub fn stage_start(genome: &str, stage: &str) {
+        if !Self::json_enabled() {
+            return;
+        }
+        let mut sink = EVENT_SINK.lock().unwrap();
+        if let Some(sink) = sink.as_mut() {
+            if let Some((previous, started)) = sink.stages.remove(genome) {
+                let line = Self::stage_end_line(genome, &previous, started);
+                sink.emit(&line);
+            }
+            let line = Self::format_event(
+                Self::timestamp(),
+                "stage_start",
+                &[("genome", Some(genome)), ("stage", Some(stage))],
+            );
+            sink.emit(&line);
+            sink.stages
+                .insert(genome.to_string(), (stage.to_string(), Instant::now()));
+        }
+    }
+
+    /// Ends the current stage of the given genome, if there is one
+    pub fn stage_end(genome: &str) {
+        if !Self::json_enabled() {
+            return;
+        }
+        let mut sink = EVENT_SINK.lock().unwrap();
+        if let Some(sink) = sink.as_mut() {
+            if let Some((stage, started)) = sink.stages.remove(genome) {
+                let line = Self::stage_end_line(genome, &stage, started);
+                sink.emit(&line);
+            }
+        }
+    }
+
+    fn stage_end_line(genome: &str, stage: &str, started: Instant) -> String {
+        let elapsed = format!("{:.3}", started.elapsed().as_secs_f64());
+        Self::format_event(
+            Self::timestamp(),
+            "stage_end",
+            &[
+                ("genome", Some(genome)),
+                ("stage", Some(stage)),
+                ("elapsed_seconds", Some(&elapsed)),
+            ],
+        )
+    }
+
+    /// Emits a single event with the given fields, if structured events are being emitted
+    pub fn event(event: &str, fields: &[(&str, Option<&str>)]) {
+        if !Self::json_enabled() {
+            return;
+        }
+        let line = Self::format_event(Self::timestamp(), event, fields);
+        Self::emit(&line);
+    }
+
+    fn emit(line: &str) {
+        match EVENT_SINK.lock().unwrap().as_mut() {
+            Some(sink) => sink.emit(line),
+            None => eprintln!("{}", line),
+        }
+    }
+
+    /// Seconds since the unix epoch with millisecond precision
+    pub fn timestamp() -> f64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_millis() as f64 / 1000.0,
+            Err(_) => 0.0,
+        }
+    }
+
+    /// Formats a single event as a JSON object on one line. Fields with a value of None are
+    /// written as null
+    pub fn format_event(timestamp: f64, event: &str, fields: &[(&str, Option<&str>)]) -> String {
+        let event = LogEvent {
+            timestamp,
+            event,
+            fields: fields.iter().copied().collect(),
+        };
+        serde_json::to_string(&event).expect("Log events are always valid JSON")
+    }
+}
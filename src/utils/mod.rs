@@ -2,12 +2,21 @@ pub mod artificial_read_utils;
 pub mod base_utils;
 pub mod dirichlet;
 pub mod errors;
+pub mod exit_status;
+pub mod external_command;
 pub mod fragment_collection;
 pub mod fragment_utils;
 pub mod interval_utils;
+pub mod log_events;
 pub mod math_utils;
 pub mod natural_log_utils;
+pub mod partial_output;
 pub mod quality_utils;
+pub mod run_rng;
 pub mod simple_interval;
+pub mod temp_resources;
+pub mod thread_budget;
 pub mod utils;
 pub mod vcf_constants;
+pub mod vcf_input;
+pub mod vcf_provenance;
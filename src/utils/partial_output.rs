@@ -0,0 +1,60 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use crate::utils::errors::BirdToolError;
+
+/// An output file written to its path with `.incomplete` appended and renamed into place by
+/// `commit`, so a killed run never leaves a truncated output behind.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartialOutput {
+    path: PathBuf,
+    partial_path: PathBuf,
+}
+
+impl PartialOutput {
+    pub const SUFFIX: &'static str = ".incomplete";
+
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let mut partial_path = path.clone().into_os_string();
+        partial_path.push(Self::SUFFIX);
+        Self {
+            path,
+            partial_path: PathBuf::from(partial_path),
+        }
+    }
+
+    /// The path the output is moved to once complete
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The path the output is written to
+    pub fn partial_path(&self) -> &Path {
+        &self.partial_path
+    }
+
+    /// Creates the file the output is written to
+    pub fn create(&self) -> Result<File, BirdToolError> {
+        File::create(&self.partial_path).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to create {}: {}",
+                self.partial_path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Moves the written output to its path, replacing any previous output. Returns the path
+    pub fn commit(self) -> Result<PathBuf, BirdToolError> {
+        std::fs::rename(&self.partial_path, &self.path).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to move {} to {}: {}",
+                self.partial_path.display(),
+                self.path.display(),
+                e
+            ))
+        })?;
+        Ok(self.path)
+    }
+}
@@ -0,0 +1,58 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref RUN_SEED: RwLock<Option<u64>> = RwLock::new(None);
+}
+
+/// The random number generators of a run, each derived from the run seed, a component name and a
+/// key, so a run with the same --seed gives the same output with any number of threads.
+pub struct RunRng {}
+
+impl RunRng {
+    /// Sets the run seed from --seed, or a random seed if it was not given, and returns it
+    pub fn from_args(args: &clap::ArgMatches) -> u64 {
+        let seed = match args.try_get_one::<u64>("seed").ok().flatten() {
+            Some(seed) => *seed,
+            None => rand::thread_rng().gen(),
+        };
+        Self::init(seed);
+        seed
+    }
+
+    pub fn init(seed: u64) {
+        *RUN_SEED.write().unwrap() = Some(seed);
+    }
+
+    /// The seed of this run. A random seed is drawn the first time this is called if none was set
+    pub fn seed() -> u64 {
+        if let Some(seed) = *RUN_SEED.read().unwrap() {
+            return seed;
+        }
+
+        let mut run_seed = RUN_SEED.write().unwrap();
+        *run_seed.get_or_insert_with(|| rand::thread_rng().gen())
+    }
+
+    /// Seed of the generator of a component for the given key, derived from the run seed
+    pub fn derive_seed(seed: u64, component: &str, key: u64) -> u64 {
+        // FNV-1a of the component name, mixed with the seed and key by splitmix64
+        let component_hash = component.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        Self::splitmix64(Self::splitmix64(seed ^ component_hash) ^ key)
+    }
+
+    /// Generator for a component of this run, e.g. `RunRng::for_component("qd_jitter", key)`
+    pub fn for_component(component: &str, key: u64) -> StdRng {
+        StdRng::seed_from_u64(Self::derive_seed(Self::seed(), component, key))
+    }
+
+    fn splitmix64(value: u64) -> u64 {
+        let mut z = value.wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
@@ -11,7 +11,7 @@ use crate::utils::interval_utils::IntervalUtils;
 *
 *@warning 0 length intervals are NOT currently allowed, but support may be added in the future
 */
-#[derive(Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Clone, Debug, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SimpleInterval {
     pub(crate) start: usize,
     pub(crate) end: usize,
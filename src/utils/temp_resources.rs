@@ -0,0 +1,226 @@
+use nix::libc;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, Once};
+
+use crate::utils::errors::BirdToolError;
+
+lazy_static! {
+    static ref TEMP_REGISTRY: Mutex<TempRegistry> = Mutex::new(TempRegistry::default());
+}
+
+static INSTALL_HANDLERS: Once = Once::new();
+
+#[derive(Debug, Default)]
+struct TempRegistry {
+    paths: Vec<PathBuf>,
+    directory: Option<PathBuf>,
+    keep: bool,
+}
+
+/// A temporary file or directory in the registry that is removed when it is dropped, unless
+/// --keep-temp was given
+#[derive(Debug)]
+pub struct TempResource {
+    path: PathBuf,
+}
+
+impl TempResource {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempResource {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempResource {
+    fn drop(&mut self) {
+        TempResources::release(&self.path);
+    }
+}
+
+/// Temporary files and directories of a run, removed when their owner is dropped, on panic and on
+/// SIGINT, SIGTERM or SIGHUP. --keep-temp leaves them in place.
+pub struct TempResources {}
+
+impl TempResources {
+    /// Sets up the registry from --tmp-dir and --keep-temp and installs the cleanup handlers
+    pub fn from_args(args: &clap::ArgMatches) -> Result<(), BirdToolError> {
+        let directory = args.try_get_one::<String>("tmp-dir").ok().flatten();
+        let keep = args
+            .try_get_one::<bool>("keep-temp")
+            .ok()
+            .flatten()
+            .copied()
+            .unwrap_or(false);
+        Self::configure(directory.map(|directory| directory.as_str()), keep)?;
+        Self::install_handlers();
+        Ok(())
+    }
+
+    pub fn configure(directory: Option<&str>, keep: bool) -> Result<(), BirdToolError> {
+        let directory = match directory {
+            Some(directory) => {
+                std::fs::create_dir_all(directory).map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Unable to create temporary directory {}: {}",
+                        directory, e
+                    ))
+                })?;
+                let directory = std::fs::canonicalize(directory).map_err(|e| {
+                    BirdToolError::IOError(format!(
+                        "Unable to resolve temporary directory {}: {}",
+                        directory, e
+                    ))
+                })?;
+                // tempfile, tempdir and external programs all honour TMPDIR
+                std::env::set_var("TMPDIR", &directory);
+                Some(directory)
+            }
+            None => None,
+        };
+
+        let mut registry = TEMP_REGISTRY.lock().unwrap();
+        registry.directory = directory;
+        registry.keep = keep;
+        Ok(())
+    }
+
+    /// The directory temporary resources are created in
+    pub fn directory() -> PathBuf {
+        TEMP_REGISTRY
+            .lock()
+            .unwrap()
+            .directory
+            .clone()
+            .unwrap_or_else(std::env::temp_dir)
+    }
+
+    /// Whether temporary resources are kept after the run
+    pub fn keep() -> bool {
+        TEMP_REGISTRY.lock().unwrap().keep
+    }
+
+    /// Creates and registers a temporary directory whose name starts with `prefix`
+    pub fn create_dir(prefix: &str) -> Result<TempResource, BirdToolError> {
+        let path = tempfile::Builder::new()
+            .prefix(prefix)
+            .tempdir_in(Self::directory())
+            .map_err(|e| {
+                BirdToolError::IOError(format!("Unable to create temporary directory: {}", e))
+            })?
+            .into_path();
+        Ok(Self::adopt(path))
+    }
+
+    /// Registers an existing temporary file or directory and takes ownership of removing it
+    pub fn adopt(path: PathBuf) -> TempResource {
+        Self::register(&path);
+        TempResource { path }
+    }
+
+    /// Tracks a path created elsewhere, e.g. a named temporary file or the indexes written next
+    /// to it, so that it is removed if the run is interrupted
+    pub fn register<P: AsRef<Path>>(path: P) {
+        let path = path.as_ref().to_path_buf();
+        let mut registry = TEMP_REGISTRY.lock().unwrap();
+        if !registry.paths.contains(&path) {
+            registry.paths.push(path);
+        }
+    }
+
+    /// Removes a registered path, unless temporary resources are kept, and forgets it
+    pub fn release<P: AsRef<Path>>(path: P) {
+        let path = path.as_ref();
+        let keep = {
+            let mut registry = match TEMP_REGISTRY.lock() {
+                Ok(registry) => registry,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            registry.paths.retain(|registered| registered != path);
+            registry.keep
+        };
+        if keep {
+            debug!("Keeping temporary resource {}", path.display());
+        } else {
+            Self::remove(path);
+        }
+    }
+
+    /// Removes every registered path, unless temporary resources are kept. Returns the number of
+    /// paths that were registered
+    pub fn cleanup() -> usize {
+        // a signal may arrive while the registry is locked, in which case nothing is removed
+        // rather than deadlocking
+        let (paths, keep) = match TEMP_REGISTRY.try_lock() {
+            Ok(mut registry) => (std::mem::take(&mut registry.paths), registry.keep),
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => {
+                let mut registry = poisoned.into_inner();
+                (std::mem::take(&mut registry.paths), registry.keep)
+            }
+            Err(std::sync::TryLockError::WouldBlock) => return 0,
+        };
+        for path in paths.iter() {
+            if keep {
+                eprintln!("Keeping temporary resource {}", path.display());
+            } else {
+                Self::remove(path);
+            }
+        }
+        paths.len()
+    }
+
+    fn remove(path: &Path) {
+        let result = if path.is_dir() {
+            std::fs::remove_dir_all(path)
+        } else {
+            std::fs::remove_file(path)
+        };
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => eprintln!(
+                "Unable to remove temporary resource {}: {}",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    /// Cleans up on panics and termination signals. Only installs the handlers once
+    pub fn install_handlers() {
+        INSTALL_HANDLERS.call_once(|| {
+            let default_hook = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                default_hook(info);
+                Self::cleanup();
+            }));
+
+            let action = SigAction::new(
+                SigHandler::Handler(handle_termination_signal),
+                SaFlags::empty(),
+                SigSet::empty(),
+            );
+            for termination_signal in [Signal::SIGINT, Signal::SIGTERM, Signal::SIGHUP] {
+                if let Err(e) = unsafe { signal::sigaction(termination_signal, &action) } {
+                    warn!("Unable to handle {}: {}", termination_signal, e);
+                }
+            }
+        });
+    }
+}
+
+extern "C" fn handle_termination_signal(signal_number: libc::c_int) {
+    TempResources::cleanup();
+    // restore the default action and raise the signal again so the process exits as it would have
+    if let Ok(termination_signal) = Signal::try_from(signal_number) {
+        unsafe {
+            let _ = signal::signal(termination_signal, SigHandler::SigDfl);
+        }
+        let _ = signal::raise(termination_signal);
+    }
+}
@@ -0,0 +1,122 @@
+use std::cmp::{max, min};
+
+/// Divides the threads provided by `--threads` between the compute pools and htslib's
+/// decompression threads so that the two do not oversubscribe the machine.
+///
+/// `--io-threads` are taken out of the total and shared between every BAM reader that can be
+/// open at once. As each compute thread holds at most one reader at a time, each reader gets
+/// `io_threads / compute_threads` extra decompression threads. The remaining threads are
+/// used by the rayon pool and divided between the genomes run in parallel.
+#[derive(Debug, Clone)]
+pub struct ThreadBudget {
+    pub threads: usize,
+    pub io_threads: usize,
+    pub parallel_genomes: usize,
+    pub pin_threads: bool,
+}
+
+impl ThreadBudget {
+    pub fn new(threads: usize, io_threads: usize, parallel_genomes: usize, pin_threads: bool) -> Self {
+        let threads = max(threads, 1);
+        Self {
+            threads,
+            // always leave at least one thread for computation
+            io_threads: min(io_threads, threads - 1),
+            parallel_genomes: max(parallel_genomes, 1),
+            pin_threads,
+        }
+    }
+
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        Self::new(
+            *args.get_one::<usize>("threads").unwrap(),
+            args.try_get_one::<usize>("io-threads")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(0),
+            args.try_get_one::<usize>("parallel-genomes")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(1),
+            args.try_get_one::<bool>("pin-threads")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or(false),
+        )
+    }
+
+    /// Threads available to the compute pools
+    pub fn compute_threads(&self) -> usize {
+        self.threads - self.io_threads
+    }
+
+    /// Compute threads given to each genome when `n_references` genomes are being processed.
+    /// Each genome gets at least two threads, even if that oversubscribes the compute threads
+    pub fn threads_per_genome(&self, n_references: usize) -> usize {
+        let concurrent_genomes = max(min(self.parallel_genomes, n_references), 1);
+        max(self.compute_threads() / concurrent_genomes, 2)
+    }
+
+    /// Extra decompression threads given to each BAM reader
+    pub fn io_threads_per_reader(&self) -> usize {
+        self.io_threads / self.compute_threads()
+    }
+
+    /// The value to pass to `set_threads` of a BAM reader, which counts the calling thread
+    pub fn reader_threads(&self) -> usize {
+        self.io_threads_per_reader() + 1
+    }
+
+    /// Builds the global rayon pool with the compute threads. When pinning is requested each
+    /// worker is bound to its own core, in order, so that neighbouring workers share a NUMA node
+    pub fn build_global_pool(&self) {
+        let mut builder = rayon::ThreadPoolBuilder::new().num_threads(self.compute_threads());
+        if self.pin_threads {
+            let cores = Self::available_cores();
+            if cores.is_empty() {
+                warn!("Unable to determine available cores, threads will not be pinned");
+            } else {
+                builder = builder.start_handler(move |thread_index| {
+                    Self::pin_current_thread(cores[thread_index % cores.len()])
+                });
+            }
+        }
+        builder.build_global().unwrap();
+    }
+
+    /// The cores this process is allowed to run on, in ascending order
+    #[cfg(target_os = "linux")]
+    pub fn available_cores() -> Vec<usize> {
+        use nix::sched::{sched_getaffinity, CpuSet};
+        use nix::unistd::Pid;
+
+        match sched_getaffinity(Pid::from_raw(0)) {
+            Ok(cpu_set) => (0..CpuSet::count())
+                .filter(|core| cpu_set.is_set(*core).unwrap_or(false))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn available_cores() -> Vec<usize> {
+        Vec::new()
+    }
+
+    #[cfg(target_os = "linux")]
+    fn pin_current_thread(core: usize) {
+        use nix::sched::{sched_setaffinity, CpuSet};
+        use nix::unistd::Pid;
+
+        let mut cpu_set = CpuSet::new();
+        if cpu_set.set(core).is_err() || sched_setaffinity(Pid::from_raw(0), &cpu_set).is_err() {
+            debug!("Failed to pin thread to core {}", core);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn pin_current_thread(_core: usize) {}
+}
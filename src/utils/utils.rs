@@ -1,7 +1,5 @@
 use rayon::prelude::*;
-use std::{str, process};
-use tempdir::TempDir;
-use tempfile::NamedTempFile;
+use std::str;
 
 use crate::external_command_checker;
 
@@ -10,12 +8,16 @@ use crate::{bam_parsing::{
     mapping_index_maintenance::{
         MappingIndex,
         generate_bwa_index,
+        generate_cached_index,
         generate_minimap2_index
     },
     mapping_parameters::*,
     bam_generator::*
 }, parse_percentage};
 use crate::processing::lorikeet_engine::ReadType;
+use crate::reference::reference_cache::ConcatenatedReference;
+use crate::utils::exit_status::ExitStatus;
+use crate::utils::temp_resources::TempResource;
 
 pub const NUMERICAL_EPSILON: f64 = 1e-3;
 pub const CONCATENATED_REFERENCE_CACHE_STEM: &str = "lorikeet-genome";
@@ -115,10 +117,10 @@ pub fn get_cleaned_sample_names(samples: &[String]) -> Vec<&str> {
 pub fn get_streamed_bam_readers<'a>(
     m: &'a clap::ArgMatches,
     mapping_program: MappingProgram,
-    reference_tempfile: &'a Option<NamedTempFile>,
+    reference_tempfile: &'a Option<ConcatenatedReference>,
     readtype: &ReadType,
     _references: &'a Option<Vec<&'a str>>,
-    tmp_bam_file_cache: &Option<TempDir>,
+    tmp_bam_file_cache: &Option<TempResource>,
 ) -> Vec<BamGeneratorSet<StreamingNamedBamReaderGenerator>> {
     // Check the output BAM directory actually exists and is writeable
     if m.contains_id("bam-file-cache-directory") {
@@ -158,7 +160,15 @@ pub fn get_streamed_bam_readers<'a>(
     for reference_wise_params in params {
         debug!("Ref Wise Params: {:?}", &reference_wise_params.len());
         let mut bam_readers = vec![];
-        let index = setup_mapping_index(&reference_wise_params, &m, mapping_program);
+        let index = setup_mapping_index(
+            &reference_wise_params,
+            &m,
+            mapping_program,
+            reference_tempfile
+                .as_ref()
+                .map(|reference| reference.is_cached())
+                .unwrap_or(false),
+        );
 
         let reference = reference_wise_params.reference;
         debug!("Reference file {:?}", &reference);
@@ -230,9 +240,9 @@ pub fn get_streamed_bam_readers<'a>(
 
 pub fn long_generator_setup(
     m: &clap::ArgMatches,
-    reference_tempfile: &Option<NamedTempFile>,
+    reference_tempfile: &Option<ConcatenatedReference>,
     references: &Option<Vec<&str>>,
-    tmp_bam_file_cache: &Option<TempDir>,
+    tmp_bam_file_cache: &Option<TempResource>,
 ) -> (
     Vec<StreamingNamedBamReaderGenerator>,
     Vec<Option<Box<dyn MappingIndex>>>,
@@ -294,11 +304,11 @@ pub fn parse_mapping_program(mapper: Option<&str>) -> MappingProgram {
 pub fn get_streamed_filtered_bam_readers(
     m: &clap::ArgMatches,
     mapping_program: MappingProgram,
-    reference_tempfile: &Option<NamedTempFile>,
+    reference_tempfile: &Option<ConcatenatedReference>,
     filter_params: &FilterParameters,
     readtype: &ReadType,
     _references: &Option<Vec<&str>>,
-    tmp_bam_file_cache: &Option<TempDir>,
+    tmp_bam_file_cache: &Option<TempResource>,
 ) -> Vec<BamGeneratorSet<StreamingFilteredNamedBamReaderGenerator>> {
     // Check the output BAM directory actually exists and is writeable
     if m.contains_id("bam-file-cache-directory") {
@@ -336,7 +346,15 @@ pub fn get_streamed_filtered_bam_readers(
     let mut generator_set = vec![];
     for reference_wise_params in params {
         let mut bam_readers = vec![];
-        let index = setup_mapping_index(&reference_wise_params, &m, mapping_program);
+        let index = setup_mapping_index(
+            &reference_wise_params,
+            &m,
+            mapping_program,
+            reference_tempfile
+                .as_ref()
+                .map(|reference| reference.is_cached())
+                .unwrap_or(false),
+        );
 
         let reference = reference_wise_params.reference;
         debug!("Reference file {:?}", &reference);
@@ -416,7 +434,22 @@ pub fn setup_mapping_index(
     reference_wise_params: &SingleReferenceMappingParameters,
     m: &clap::ArgMatches,
     mapping_program: MappingProgram,
+    reference_is_cached: bool,
 ) -> Option<Box<dyn MappingIndex>> {
+    // indexes of cached references are kept in the cache alongside the reference
+    if reference_is_cached && !m.contains_id("minimap2-reference-is-index") {
+        let index_creation_parameters = match mapping_program {
+            MappingProgram::BWA_MEM | MappingProgram::BWA_MEM2 => None,
+            _ => m.get_one::<String>("minimap2-params").map(|s| s.as_str()),
+        };
+        return Some(generate_cached_index(
+            reference_wise_params.reference,
+            Some(*m.get_one::<usize>("threads").unwrap() as u16),
+            index_creation_parameters,
+            mapping_program,
+        ));
+    }
+
     match mapping_program {
         MappingProgram::BWA_MEM | MappingProgram::BWA_MEM2 => {
             Some(generate_bwa_index(
@@ -465,7 +498,7 @@ pub fn setup_bam_cache_directory(cache_directory: &str) {
                 "Cache directory {} does not appear to be writeable, not continuing",
                 cache_directory
             );
-            process::exit(1);
+            ExitStatus::ConfigurationError.exit();
         } else {
             info!(
                 "Writing BAM files to already existing directory {}",
@@ -501,7 +534,7 @@ pub fn setup_bam_cache_directory(cache_directory: &str) {
                              cache directory {} is not writeable, not continuing",
                             cache_directory
                         );
-                        process::exit(1);
+                        ExitStatus::ConfigurationError.exit();
                     } else {
                         info!("Creating cache directory {}", cache_directory);
                         std::fs::create_dir(path).expect("Unable to create cache directory");
@@ -512,12 +545,12 @@ pub fn setup_bam_cache_directory(cache_directory: &str) {
                          yet exist, so not creating that cache directory, and not continuing.",
                         cache_directory
                     );
-                    process::exit(1);
+                    ExitStatus::ConfigurationError.exit();
                 }
             }
             None => {
                 error!("Cannot create root directory {}", cache_directory);
-                process::exit(1);
+                ExitStatus::ConfigurationError.exit();
             }
         }
     }
@@ -529,7 +562,7 @@ pub fn setup_bam_cache_directory(cache_directory: &str) {
             "Failed to create test file in bam cache directory: {}",
             tf_result.err().unwrap()
         );
-        process::exit(1);
+        ExitStatus::ConfigurationError.exit();
     }
 }
 
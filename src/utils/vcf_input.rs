@@ -0,0 +1,131 @@
+use rust_htslib::bcf::{IndexedReader, Reader};
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::external_command_checker::check_for_bcftools;
+use crate::utils::errors::BirdToolError;
+use crate::utils::external_command::ExternalCommand;
+
+/// Opens plain, bgzipped and remote VCF files, trying the path with and without .gz. Plain local
+/// files needing an index are compressed to a copy next to the original.
+pub struct VcfInput {}
+
+impl VcfInput {
+    const REMOTE_SCHEMES: [&'static str; 5] = ["http://", "https://", "ftp://", "s3://", "gs://"];
+
+    /// Whether the path is a URL that htslib reads remotely
+    pub fn is_remote(path: &str) -> bool {
+        Self::REMOTE_SCHEMES
+            .iter()
+            .any(|scheme| path.starts_with(scheme))
+    }
+
+    /// The file name of a VCF without its directory and its .vcf, .vcf.gz or .bcf extension
+    pub fn stem(path: &str) -> String {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let name = name.strip_suffix(".gz").unwrap_or(name);
+        name.strip_suffix(".vcf")
+            .or_else(|| name.strip_suffix(".bcf"))
+            .unwrap_or(name)
+            .to_string()
+    }
+
+    /// The path of an existing VCF, trying the path with a .gz suffix added or removed if the
+    /// path itself does not exist. Remote paths are returned as they are
+    pub fn resolve(path: &str) -> Result<String, BirdToolError> {
+        if Self::is_remote(path) || Path::new(path).exists() {
+            return Ok(path.to_string());
+        }
+        let alternative = match path.strip_suffix(".gz") {
+            Some(plain) => plain.to_string(),
+            None => format!("{}.gz", path),
+        };
+        if Path::new(&alternative).exists() {
+            Ok(alternative)
+        } else {
+            Err(BirdToolError::IOError(format!(
+                "Unable to find VCF file {} or {}",
+                path, alternative
+            )))
+        }
+    }
+
+    /// Opens a VCF to read its records in order
+    pub fn open(path: &str) -> Result<Reader, BirdToolError> {
+        let resolved = Self::resolve(path)?;
+        Reader::from_path(&resolved).map_err(|e| {
+            BirdToolError::IOError(format!("Unable to read VCF file {}: {}", resolved, e))
+        })
+    }
+
+    /// Opens a VCF to fetch the records of regions, preferring a bgzipped copy of a plain VCF
+    /// and indexing local files that are not yet indexed
+    pub fn open_indexed(path: &str) -> Result<IndexedReader, BirdToolError> {
+        let compressed = format!("{}.gz", path);
+        let resolved = if !Self::is_remote(path)
+            && !path.ends_with(".gz")
+            && Path::new(&compressed).exists()
+        {
+            compressed
+        } else {
+            Self::resolve(path)?
+        };
+
+        match IndexedReader::from_path(&resolved) {
+            Ok(reader) => return Ok(reader),
+            Err(e) if Self::is_remote(&resolved) => {
+                return Err(BirdToolError::IOError(format!(
+                    "Unable to read remote VCF file {} and its index: {}",
+                    resolved, e
+                )))
+            }
+            // most likely not indexed yet
+            Err(_) => {}
+        }
+
+        let indexed = Self::index(&resolved)?;
+        IndexedReader::from_path(&indexed).map_err(|e| {
+            BirdToolError::IOError(format!(
+                "Unable to read indexed VCF file {}: {}. Please ensure it was compressed with \
+                bgzip rather than gzip",
+                indexed, e
+            ))
+        })
+    }
+
+    /// Indexes a local VCF, writing a bgzipped copy next to it first if it is not compressed.
+    /// Returns the path of the indexed file
+    fn index(path: &str) -> Result<String, BirdToolError> {
+        check_for_bcftools();
+        let (gzip_path, cmd_string) = if path.ends_with(".gz") {
+            (
+                path.to_string(),
+                format!("set -e -o pipefail; bcftools index -f {}", path),
+            )
+        } else {
+            let gzip_path = format!("{}.gz", path);
+            let cmd_string = format!(
+                "set -e -o pipefail; bgzip -c {} > {}; bcftools index -f {}",
+                path, gzip_path, gzip_path
+            );
+            (gzip_path, cmd_string)
+        };
+
+        ExternalCommand::run(
+            std::process::Command::new("bash")
+                .arg("-c")
+                .arg(&cmd_string)
+                .stdout(Stdio::piped()),
+            "bcftools",
+        )
+        .map_err(|failure| {
+            failure.log();
+            BirdToolError::IOError(format!(
+                "Unable to index VCF file {}: {}",
+                path,
+                failure.message()
+            ))
+        })?;
+        Ok(gzip_path)
+    }
+}
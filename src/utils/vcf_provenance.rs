@@ -0,0 +1,92 @@
+use crate::utils::run_rng::RunRng;
+
+/// The command line, version, and parameters of a lorikeet run, written to the header of each
+/// VCF file as `##lorikeet_*` lines so that the results can be reproduced
+#[derive(Debug, Clone, Default)]
+pub struct VcfProvenance {
+    pub command_line: String,
+    pub version: String,
+    // parameter name and value, in the order the parameters are defined
+    pub parameters: Vec<(String, String)>,
+}
+
+impl VcfProvenance {
+    pub fn new(command_line: String, parameters: Vec<(String, String)>) -> Self {
+        Self {
+            command_line,
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            parameters,
+        }
+    }
+
+    /// The command line of this process and every parameter of the subcommand, including those
+    /// left at their default value
+    pub fn from_args(args: &clap::ArgMatches) -> Self {
+        let command_line = std::env::args()
+            .map(|arg| Self::quote_argument(&arg))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let mut parameters: Vec<(String, String)> = args
+            .ids()
+            .filter_map(|id| {
+                let values = args
+                    .get_raw(id.as_str())?
+                    .map(|value| value.to_string_lossy().to_string())
+                    .collect::<Vec<String>>();
+                if values.is_empty() {
+                    None
+                } else {
+                    Some((id.to_string(), values.join(" ")))
+                }
+            })
+            .collect();
+
+        // record the seed drawn for runs that were not given one so they can be repeated
+        if args.try_get_one::<u64>("seed").is_ok()
+            && !parameters.iter().any(|(name, _)| name == "seed")
+        {
+            parameters.push(("seed".to_string(), RunRng::seed().to_string()));
+        }
+
+        Self::new(command_line, parameters)
+    }
+
+    /// The header lines describing this run
+    pub fn header_records(&self) -> Vec<String> {
+        let mut records = Vec::with_capacity(self.parameters.len() + 2);
+        records.push(format!("##lorikeet_version={}", self.version));
+        records.push(format!("##lorikeet_command={}", self.command_line));
+        for (name, value) in self.parameters.iter() {
+            records.push(format!(
+                "##lorikeet_parameter=<ID={},Value=\"{}\">",
+                name,
+                Self::escape(value)
+            ));
+        }
+        records
+    }
+
+    /// The MD5 checksum of a contig as defined for the M5 field of SAM headers, i.e. of the
+    /// upper case sequence
+    pub fn contig_md5(sequence: &[u8]) -> String {
+        let sequence = sequence
+            .iter()
+            .filter(|base| !base.is_ascii_whitespace())
+            .map(|base| base.to_ascii_uppercase())
+            .collect::<Vec<u8>>();
+        format!("{:x}", md5::compute(&sequence))
+    }
+
+    fn quote_argument(arg: &str) -> String {
+        if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '\'' || c == '"') {
+            format!("'{}'", arg.replace('\'', "'\\''"))
+        } else {
+            arg.to_string()
+        }
+    }
+
+    fn escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+}
@@ -0,0 +1,123 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use hashlink::LinkedHashMap;
+use lorikeet_genome::abundance::abundance_formats::{AbundanceFormat, CamiProfiles};
+use lorikeet_genome::utils::errors::BirdToolError;
+
+fn test_abundances() -> LinkedHashMap<usize, Vec<f64>> {
+    let mut abundances = LinkedHashMap::new();
+    abundances.insert(0, vec![3.0, 0.0]);
+    abundances.insert(2, vec![1.0, 0.0]);
+    abundances
+}
+
+#[test]
+fn test_relative_abundances() {
+    let relative = AbundanceFormat::relative_abundances(&test_abundances(), 2);
+    assert_eq!(relative[&0], vec![0.75, 0.0]);
+    assert_eq!(relative[&2], vec![0.25, 0.0]);
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(AbundanceFormat::from_name("CAMI").unwrap(), AbundanceFormat::Cami);
+    assert_eq!(AbundanceFormat::from_name("biom").unwrap(), AbundanceFormat::Biom);
+    assert!(matches!(
+        AbundanceFormat::from_name("json"),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_write_cami() {
+    let mut other_abundances = LinkedHashMap::new();
+    other_abundances.insert(0, vec![4.0, 2.0]);
+    let genomes = vec![
+        ("genome".to_string(), test_abundances()),
+        ("other".to_string(), other_abundances),
+    ];
+
+    // percentages are relative to every strain of every genome in the sample
+    let mut output = Vec::new();
+    AbundanceFormat::write_cami(&mut output, "sample_1", 0, &genomes).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "@SampleID:sample_1");
+    assert_eq!(lines[4], "@@TAXID\tRANK\tTAXPATH\tTAXPATHSN\tPERCENTAGE");
+    assert_eq!(lines[5], "genome\tgenome\tgenome\tgenome\t50.00000");
+    assert_eq!(
+        lines[6],
+        "genome.strain_0\tstrain\tgenome|genome.strain_0\tgenome|genome.strain_0\t37.50000"
+    );
+    assert_eq!(
+        lines[7],
+        "genome.strain_2\tstrain\tgenome|genome.strain_2\tgenome|genome.strain_2\t12.50000"
+    );
+    assert_eq!(lines[8], "other\tgenome\tother\tother\t50.00000");
+    assert_eq!(
+        lines[9],
+        "other.strain_0\tstrain\tother|other.strain_0\tother|other.strain_0\t50.00000"
+    );
+    assert_eq!(lines.len(), 10);
+
+    // genomes absent from a sample are left out
+    let mut output = Vec::new();
+    AbundanceFormat::write_cami(&mut output, "sample_2", 1, &genomes).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let lines = output.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[5], "other\tgenome\tother\tother\t100.00000");
+    assert_eq!(lines.len(), 7);
+}
+
+#[test]
+fn test_cami_profiles() {
+    let directory = tempfile::tempdir().unwrap();
+    let profiles = CamiProfiles::new();
+    assert!(profiles.is_empty());
+
+    let mut other_abundances = LinkedHashMap::new();
+    other_abundances.insert(0, vec![4.0, 2.0]);
+    // genomes finish in any order, but are listed by name
+    profiles.add("other", &["sample_1", "sample_2"], &other_abundances);
+    profiles
+        .clone()
+        .add("genome", &["sample_1", "sample_2"], &test_abundances());
+
+    let paths = profiles.write(directory.path()).unwrap();
+    assert_eq!(
+        paths,
+        vec![
+            directory.path().join("sample_1_strain_abundances.profile"),
+            directory.path().join("sample_2_strain_abundances.profile"),
+        ]
+    );
+    let profile = std::fs::read_to_string(&paths[0]).unwrap();
+    let lines = profile.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "@SampleID:sample_1");
+    assert_eq!(lines[5], "genome\tgenome\tgenome\tgenome\t50.00000");
+    assert_eq!(lines[8], "other\tgenome\tother\tother\t50.00000");
+}
+
+#[test]
+fn test_write_biom() {
+    let mut output = Vec::new();
+    AbundanceFormat::write_biom(
+        &mut output,
+        "genome",
+        &["sample_1", "sample_2"],
+        &test_abundances(),
+    )
+    .unwrap();
+    let output = String::from_utf8(output).unwrap();
+
+    assert_eq!(
+        output,
+        "# Constructed from biom file\n\
+        #OTU ID\tsample_1\tsample_2\ttaxonomy\n\
+        genome.strain_0\t0.750000\t0.000000\tg__genome; s__genome.strain_0\n\
+        genome.strain_2\t0.250000\t0.000000\tg__genome; s__genome.strain_2\n"
+    );
+}
@@ -0,0 +1,58 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::haplotype::assembly_fallback_caller::AssemblyFallbackCaller;
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::processing::pileup_ani::PileupAni;
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+
+fn read(name: &str, position: i64, bases: &[u8], sample_index: usize) -> BirdToolRead {
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::Match(bases.len() as u32)]);
+    record.set(name.as_bytes(), Some(&cigar), bases, &vec![30; bases.len()]);
+    record.set_pos(position);
+    BirdToolRead::new(record, sample_index, ReadType::Short)
+}
+
+#[test]
+fn testCallFailedRegion() {
+    // the region covers positions 100 to 109 of the contig, of which 102 to 107 are active
+    let reference = b"ACGTACGTAC";
+    let reference_loc = SimpleInterval::new(0, 100, 109);
+    let active_span = SimpleInterval::new(0, 102, 107);
+
+    let mut reads = Vec::new();
+    for index in 0..4 {
+        // sample 0 carries a T at 104 and an A at 109, outside of the active span
+        reads.push(read(&format!("a{}", index), 100, b"ACGTTCGTAA", 0));
+        reads.push(read(&format!("b{}", index), 100, reference, 1));
+    }
+    // a read starting before the region only counts where it overlaps it
+    reads.push(read("c", 95, b"AAAAAACGTA", 1));
+
+    let caller = AssemblyFallbackCaller::new(PileupAni::new(10, 0, 2, 0.05));
+    let calls = caller.call(&reads, reference, &reference_loc, &active_span, 2, 2);
+
+    assert_eq!(calls.len(), 1);
+    let call = &calls[0];
+    assert_eq!(call.loc.get_start(), 104);
+    assert_eq!(call.get_n_alleles(), 2);
+    assert!(call
+        .attributes
+        .contains_key(VariantAnnotations::AssemblyFallback.to_key()));
+
+    let genotypes = call.genotypes.genotypes();
+    assert_eq!(genotypes[0].ad, vec![0, 4]);
+    assert_eq!(genotypes[1].ad, vec![5, 0]);
+    // genotypes are left uncalled with flat likelihoods over the diploid genotypes
+    assert!(genotypes.iter().all(|genotype| genotype.ploidy == 2));
+    assert!(genotypes
+        .iter()
+        .all(|genotype| genotype.pl == vec![0, 0, 0]));
+    assert!(genotypes.iter().all(|genotype| genotype.alleles.is_empty()));
+}
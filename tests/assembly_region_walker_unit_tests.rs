@@ -0,0 +1,212 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::activity_profile::activity_profile::Profile;
+use lorikeet_genome::activity_profile::activity_profile_state::{
+    ActivityProfileDataType, ActivityProfileState,
+};
+use lorikeet_genome::activity_profile::band_pass_activity_profile::BandPassActivityProfile;
+use lorikeet_genome::assembly::assembly_region::AssemblyRegion;
+use lorikeet_genome::assembly::assembly_region_walker::AssemblyRegionWalker;
+use lorikeet_genome::bam_parsing::FlagFilter;
+use lorikeet_genome::cli::build_cli;
+use lorikeet_genome::processing::per_genome_config::GenomeOverrides;
+use lorikeet_genome::reference::reference_reader::ReferenceReader;
+use lorikeet_genome::reference::reference_reader_utils::read_genome_fasta_files;
+use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
+
+static reference_path: &str = "tests/data/two_contigs.fna";
+static long_read_bam: &str = "tests/data/two_contigs_lr1.bam";
+// lengths of contig_9_pilon and seq2
+const contig_lengths: [usize; 2] = [5940, 1000];
+
+fn call_args(extra_args: &[&str]) -> clap::ArgMatches {
+    let mut args = vec![
+        "lorikeet",
+        "call",
+        "-r",
+        reference_path,
+        "-l",
+        long_read_bam,
+    ];
+    args.extend_from_slice(extra_args);
+    build_cli()
+        .get_matches_from(args)
+        .subcommand_matches("call")
+        .unwrap()
+        .clone()
+}
+
+fn test_walker(args: &clap::ArgMatches, bam_files: &[String]) -> AssemblyRegionWalker {
+    AssemblyRegionWalker::start(args, 0, 0, 1, bam_files, &GenomeOverrides::default())
+}
+
+fn test_reference_reader() -> ReferenceReader {
+    let genomes_and_contigs = read_genome_fasta_files(&vec![reference_path], false);
+    ReferenceReader::new(
+        &Some(reference_path.to_string()),
+        genomes_and_contigs,
+        contig_lengths.len(),
+    )
+}
+
+fn inactive_region(tid: usize, start: usize, end: usize) -> AssemblyRegion {
+    AssemblyRegion::new(
+        SimpleInterval::new(tid, start, end),
+        false,
+        0,
+        contig_lengths[tid],
+        tid,
+        0,
+        0.0,
+    )
+}
+
+fn flag_filters() -> FlagFilter {
+    FlagFilter {
+        include_improper_pairs: true,
+        include_secondary: false,
+        include_supplementary: false,
+    }
+}
+
+#[test]
+fn test_iter_regions_keeps_region_order() {
+    let args = call_args(&[]);
+    let bam_files = vec![long_read_bam.to_string()];
+    let walker = test_walker(&args, &bam_files);
+    let reference_reader = test_reference_reader();
+    let flag_filters = flag_filters();
+
+    // regions are called in the order given, not sorted by position
+    let regions = vec![
+        inactive_region(1, 0, 299),
+        inactive_region(0, 500, 799),
+        inactive_region(0, 100, 399),
+    ];
+    let expected_spans = regions
+        .iter()
+        .map(|region| region.get_span().clone())
+        .collect::<Vec<SimpleInterval>>();
+
+    let mut calls = walker.iter_regions(
+        regions,
+        &args,
+        &bam_files,
+        &flag_filters,
+        &reference_reader,
+        1,
+    );
+    assert_eq!(calls.remaining(), 3);
+    let (first_region, first_contexts) = calls.next().unwrap();
+    assert_eq!(calls.remaining(), 2);
+    assert_eq!(first_region.get_span(), &expected_spans[0]);
+    // inactive regions are not assembled, and are yielded without their reads
+    assert!(first_contexts.is_empty());
+    assert_eq!(first_region.len(), 0);
+
+    let spans = calls
+        .map(|(region, _)| region.get_span().clone())
+        .collect::<Vec<SimpleInterval>>();
+    assert_eq!(spans, expected_spans[1..].to_vec());
+}
+
+#[test]
+fn test_iter_regions_skips_regions_outside_limiting_interval() {
+    let args = call_args(&["--limiting-interval", "500-999"]);
+    let bam_files = vec![long_read_bam.to_string()];
+    let walker = test_walker(&args, &bam_files);
+    let reference_reader = test_reference_reader();
+    let flag_filters = flag_filters();
+
+    // the limiting interval applies to every contig
+    let regions = vec![
+        inactive_region(0, 0, 299),
+        inactive_region(0, 900, 1199),
+        inactive_region(0, 2000, 2299),
+        inactive_region(1, 400, 599),
+        inactive_region(1, 0, 299),
+    ];
+    let spans = walker
+        .iter_regions(
+            regions,
+            &args,
+            &bam_files,
+            &flag_filters,
+            &reference_reader,
+            1,
+        )
+        .map(|(region, _)| region.get_span().clone())
+        .collect::<Vec<SimpleInterval>>();
+    assert_eq!(
+        spans,
+        vec![
+            SimpleInterval::new(0, 900, 1199),
+            SimpleInterval::new(1, 400, 599),
+        ]
+    );
+}
+
+#[test]
+fn test_iter_shard_region_boundaries() {
+    let args = call_args(&[
+        "--min-assembly-region-size",
+        "50",
+        "--max-assembly-region-size",
+        "300",
+    ]);
+    let bam_files = vec![long_read_bam.to_string()];
+    let walker = test_walker(&args, &bam_files);
+    let reference_reader = test_reference_reader();
+    let flag_filters = flag_filters();
+
+    let shard_end = 1999;
+    let mut shard = BandPassActivityProfile::new(
+        50,
+        0.002,
+        BandPassActivityProfile::MAX_FILTER_SIZE,
+        BandPassActivityProfile::DEFAULT_SIGMA,
+        true,
+        0,
+        0,
+        contig_lengths[0],
+    );
+    for position in 0..=shard_end {
+        shard.add(ActivityProfileState::new(
+            SimpleInterval::new(0, position, position),
+            0.0,
+            ActivityProfileDataType::None,
+        ));
+    }
+
+    let spans = walker
+        .iter_shard(
+            shard,
+            &args,
+            &bam_files,
+            &flag_filters,
+            &reference_reader,
+            1,
+        )
+        .map(|(region, contexts)| {
+            assert!(!region.is_active());
+            assert!(contexts.is_empty());
+            region.get_span().clone()
+        })
+        .collect::<Vec<SimpleInterval>>();
+
+    // the regions tile the shard in order without gaps or overlaps, each within the maximum
+    // region size
+    assert!(!spans.is_empty());
+    assert_eq!(spans[0].get_start(), 0);
+    for span in spans.iter() {
+        assert_eq!(span.get_contig(), 0);
+        assert!(span.get_end() <= shard_end);
+        assert!(span.size() <= 300);
+    }
+    for pair in spans.windows(2) {
+        assert_eq!(pair[1].get_start(), pair[0].get_end() + 1);
+    }
+}
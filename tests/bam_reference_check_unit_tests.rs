@@ -0,0 +1,134 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::bam_reference_check::{
+    BamContig, BamReferenceCheck, ContigMismatch,
+};
+use lorikeet_genome::processing::dry_run::ReferenceContigs;
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use std::collections::HashMap;
+
+fn reference(genome_name: &str, contigs: &[(&str, u64)]) -> ReferenceContigs {
+    ReferenceContigs {
+        genome_name: genome_name.to_string(),
+        path: format!("{}.fna", genome_name),
+        contigs: contigs
+            .iter()
+            .map(|(name, length)| (name.to_string(), *length))
+            .collect(),
+    }
+}
+
+#[test]
+fn testCompareMatchingBam() {
+    let references = vec![
+        reference("genome_1", &[("contig_1", 1000), ("contig_2", 500)]),
+        reference("genome_2", &[("contig_3", 200)]),
+    ];
+    let checksums = HashMap::new();
+
+    // aligned to genome_1 alone, contigs of genome_2 are not expected
+    let bam_contigs = vec![
+        BamContig::new("contig_1", 1000, None),
+        BamContig::new("contig_2", 500, None),
+    ];
+    let report = BamReferenceCheck::compare(
+        "a.bam",
+        &references,
+        &checksums,
+        &bam_contigs,
+        &GenomeSeparator::default(),
+    );
+    assert!(report.is_consistent());
+    assert_eq!(report.shared, 2);
+
+    // aligned to the concatenated reference
+    let bam_contigs = vec![
+        BamContig::new("genome_1~contig_1", 1000, None),
+        BamContig::new("genome_1~contig_2", 500, None),
+        BamContig::new("genome_2~contig_3", 200, None),
+    ];
+    let report = BamReferenceCheck::compare(
+        "b.bam",
+        &references,
+        &checksums,
+        &bam_contigs,
+        &GenomeSeparator::default(),
+    );
+    assert!(report.is_consistent());
+    assert_eq!(report.shared, 3);
+}
+
+#[test]
+fn testCompareMismatchedBam() {
+    let references = vec![reference(
+        "genome_1",
+        &[("contig_1", 1000), ("contig_2", 500), ("contig_4", 50)],
+    )];
+    let mut checksums = HashMap::new();
+    checksums.insert(
+        "genome_1~contig_2".to_string(),
+        "0123456789abcdef0123456789abcdef".to_string(),
+    );
+
+    let bam_contigs = vec![
+        BamContig::new("contig_1", 1010, None),
+        BamContig::new("contig_2", 500, Some("FEDCBA9876543210FEDCBA9876543210")),
+        BamContig::new("genome_1~contig_5", 20, None),
+        BamContig::new("unrelated", 20, None),
+    ];
+    let report = BamReferenceCheck::compare(
+        "c.bam",
+        &references,
+        &checksums,
+        &bam_contigs,
+        &GenomeSeparator::default(),
+    );
+    assert!(!report.is_consistent());
+    assert_eq!(report.shared, 2);
+    assert_eq!(
+        report.mismatches,
+        vec![
+            ContigMismatch::Length {
+                contig: "contig_1".to_string(),
+                bam_length: 1010,
+                reference_length: 1000,
+            },
+            ContigMismatch::Checksum {
+                contig: "contig_2".to_string(),
+                bam_md5: "fedcba9876543210fedcba9876543210".to_string(),
+                reference_md5: "0123456789abcdef0123456789abcdef".to_string(),
+            },
+            ContigMismatch::MissingFromReference {
+                contig: "genome_1~contig_5".to_string(),
+            },
+            ContigMismatch::MissingFromBam {
+                contig: "genome_1~contig_4".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        report.describe()[0],
+        "c.bam: contig_1: 1010 bp in the BAM header but 1000 bp in the reference"
+    );
+}
+
+#[test]
+fn testCompareUnrelatedBam() {
+    let references = vec![reference("genome_1", &[("contig_1", 1000)])];
+    let bam_contigs = vec![BamContig::new("chr1", 248956422, None)];
+    let report = BamReferenceCheck::compare(
+        "d.bam",
+        &references,
+        &HashMap::new(),
+        &bam_contigs,
+        &GenomeSeparator::default(),
+    );
+    assert!(!report.is_consistent());
+    assert_eq!(
+        report.describe(),
+        vec!["BAM file d.bam shares no contigs with the reference genomes".to_string()]
+    );
+}
@@ -0,0 +1,126 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::reads::base_recalibration::{
+    BaseRecalibrationTable, SampleRecalibrationTable,
+};
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::reads::read_clipper::ReadClipper;
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+
+#[test]
+fn test_cycle_bins() {
+    assert_eq!(BaseRecalibrationTable::cycle_bin(5, 1000, (0, 0), false), 0);
+    assert_eq!(
+        BaseRecalibrationTable::cycle_bin(25, 1000, (0, 0), false),
+        2
+    );
+    // reverse strand reads are sequenced from the end of the record
+    assert_eq!(BaseRecalibrationTable::cycle_bin(0, 100, (0, 0), true), 9);
+    // hard clipped bases were sequenced too, before the start of a forward read and after the
+    // end of a reverse read
+    assert_eq!(BaseRecalibrationTable::cycle_bin(5, 10, (20, 0), false), 2);
+    assert_eq!(BaseRecalibrationTable::cycle_bin(5, 10, (0, 20), false), 0);
+    assert_eq!(BaseRecalibrationTable::cycle_bin(9, 10, (20, 0), true), 0);
+    assert_eq!(BaseRecalibrationTable::cycle_bin(9, 10, (0, 20), true), 2);
+    // long reads share the final bin
+    assert_eq!(
+        BaseRecalibrationTable::cycle_bin(999, 1000, (0, 0), false),
+        BaseRecalibrationTable::N_CYCLE_BINS - 1
+    );
+}
+
+#[test]
+fn test_clipped_reverse_strand_read_keeps_its_cycles() {
+    // bases in the first cycles are errors far more often than their reported quality says. The
+    // observations are in a context that the read does not have, so only the cycle shifts apply
+    let mut table = SampleRecalibrationTable::new();
+    for cycle_bin in 0..5 {
+        let error_rate = if cycle_bin == 0 { 10 } else { 1000 };
+        for i in 0..1000 {
+            table.add_observation(30, cycle_bin, 5, i % error_rate == 0);
+        }
+    }
+    let table = BaseRecalibrationTable::from_sample_tables(vec![table]);
+
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::SoftClip(3), Cigar::Match(27)]);
+    record.set(b"read", Some(&cigar), &[b'A'; 30], &[30; 30]);
+    record.set_pos(100);
+    record.set_reverse();
+    let read = BirdToolRead::new(record, 0, ReadType::Short);
+
+    let recalibrate = |read: &BirdToolRead| {
+        let mut quals = read.read.qual().to_vec();
+        table.recalibrate(
+            0,
+            read.seq(),
+            &mut quals,
+            BaseRecalibrationTable::hard_clips(&read.read.cigar()),
+            read.read.is_reverse(),
+        );
+        quals
+    };
+    let full_quals = recalibrate(&read);
+
+    // clip the soft clips and four aligned bases from the start, and the last seven bases
+    let clipped = ReadClipper::new(read).hard_clip_both_ends_by_reference_coordinates(103, 120);
+    assert_eq!(
+        BaseRecalibrationTable::hard_clips(&clipped.read.cigar()),
+        (7, 7)
+    );
+    let clipped_quals = recalibrate(&clipped);
+
+    assert_eq!(clipped_quals, full_quals[7..23].to_vec());
+    // the clipped read ends in the first cycles sequenced from the reverse strand
+    assert!(clipped_quals[15] < clipped_quals[0]);
+}
+
+#[test]
+fn test_dinucleotide_contexts() {
+    let bases = b"ACGT";
+    let no_context = BaseRecalibrationTable::N_CONTEXTS - 1;
+
+    assert_eq!(BaseRecalibrationTable::context(bases, 0, false), no_context);
+    // AC
+    assert_eq!(BaseRecalibrationTable::context(bases, 1, false), 1);
+    // reverse strand: complement of G then complement of C, i.e. CG
+    assert_eq!(BaseRecalibrationTable::context(bases, 1, true), 6);
+    assert_eq!(BaseRecalibrationTable::context(bases, 3, true), no_context);
+    assert_eq!(BaseRecalibrationTable::context(b"ANGT", 2, false), no_context);
+}
+
+#[test]
+fn test_overconfident_qualities_are_lowered() {
+    let mut table = SampleRecalibrationTable::new();
+    // reported Q30 but one in ten bases is an error
+    for i in 0..10000 {
+        table.add_observation(30, 0, 1, i % 10 == 0);
+    }
+
+    assert_eq!(table.recalibrate_quality(30, 0, 1), 10);
+
+    let table = BaseRecalibrationTable::from_sample_tables(vec![table]);
+    let mut quals = vec![30, 30, 30];
+    table.recalibrate(0, b"ACA", &mut quals, (0, 0), false);
+    // the first base has no context, but the quality shift still applies
+    assert_eq!(quals, vec![10, 10, 10]);
+
+    // samples without a model are unchanged
+    let mut quals = vec![30, 30, 30];
+    table.recalibrate(1, b"ACA", &mut quals, (0, 0), false);
+    assert_eq!(quals, vec![30, 30, 30]);
+}
+
+#[test]
+fn test_sparse_table_leaves_qualities_unchanged() {
+    let mut table = SampleRecalibrationTable::new();
+    for i in 0..10 {
+        table.add_observation(30, 0, 1, i % 2 == 0);
+    }
+
+    assert_eq!(table.recalibrate_quality(30, 0, 1), 30);
+}
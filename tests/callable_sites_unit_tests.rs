@@ -0,0 +1,72 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::model::callable_sites::{CallableInterval, CallableSites, CallableWindow};
+use lorikeet_genome::reference::reference_mask::ReferenceMask;
+
+fn interval(tid: usize, start: usize, end: usize, samples: Vec<usize>) -> CallableInterval {
+    CallableInterval {
+        tid,
+        start,
+        end,
+        samples,
+    }
+}
+
+#[test]
+fn test_runs_to_intervals() {
+    let callable_sites = CallableSites::new();
+    // recorded out of order, as parallel chunks would be
+    callable_sites.record(CallableWindow::new(0, 10, vec![vec![-2, 3], vec![5]]));
+    callable_sites.record(CallableWindow::new(
+        0,
+        0,
+        vec![vec![4, -3, 3], vec![3, -2, 4, -1]],
+    ));
+
+    assert_eq!(callable_sites.len(), 2);
+    assert_eq!(callable_sites.windows()[0].len(), 10);
+
+    assert_eq!(
+        callable_sites.intervals(1),
+        vec![
+            interval(0, 0, 3, vec![0, 1]),
+            interval(0, 3, 4, vec![0]),
+            interval(0, 5, 7, vec![1]),
+            interval(0, 7, 9, vec![0, 1]),
+            interval(0, 9, 10, vec![0]),
+            interval(0, 10, 12, vec![1]),
+            interval(0, 12, 15, vec![0, 1]),
+        ]
+    );
+    assert_eq!(
+        callable_sites.intervals(2),
+        vec![
+            interval(0, 0, 3, vec![0, 1]),
+            interval(0, 7, 9, vec![0, 1]),
+            interval(0, 12, 15, vec![0, 1]),
+        ]
+    );
+}
+
+#[test]
+fn test_masked_positions_are_not_callable() {
+    let callable_sites = CallableSites::new();
+    callable_sites.record(CallableWindow::new(1, 100, vec![vec![10]]));
+
+    let mut mask = ReferenceMask::new();
+    mask.add_interval(1, 103, 105);
+    mask.add_interval(0, 100, 110);
+    mask.merge();
+    callable_sites.apply_mask(&mask);
+
+    assert_eq!(
+        callable_sites.intervals(1),
+        vec![
+            interval(1, 100, 103, vec![0]),
+            interval(1, 105, 110, vec![0]),
+        ]
+    );
+}
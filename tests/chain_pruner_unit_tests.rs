@@ -9,7 +9,9 @@ extern crate approx;
 use hashlink::LinkedHashMap;
 use lorikeet_genome::graphs::adaptive_chain_pruner::AdaptiveChainPruner;
 use lorikeet_genome::graphs::base_edge::{BaseEdge, BaseEdgeStruct};
-use lorikeet_genome::graphs::chain_pruner::ChainPruner;
+use lorikeet_genome::graphs::chain_pruner::{
+    ChainPruner, ChainPrunerRegistry, ChainUtils, PruningParameters,
+};
 use lorikeet_genome::graphs::graph_based_k_best_haplotype_finder::GraphBasedKBestHaplotypeFinder;
 use lorikeet_genome::graphs::seq_graph::SeqGraph;
 use lorikeet_genome::graphs::seq_vertex::SeqVertex;
@@ -43,17 +45,17 @@ fn test_prune_low_weight_chains<E: BaseEdge>(
 ) {
     println!("remaining vertices {:?}", &remaining_vertices);
     let copy = remaining_vertices.clone();
-    let pruner = ChainPruner::AdaptiveChainPruner(AdaptiveChainPruner::new(
+    let pruner = AdaptiveChainPruner::new(
         0.001,
         MathUtils::log10_to_log(2.0),
         MathUtils::log10_to_log(4.0),
         50,
-    ));
+    );
 
     match chain_count_before_pruning {
         Some(chain_count_before_pruning) => {
             assert_eq!(
-                ChainPruner::find_all_chains(&graph.base_graph).len(),
+                ChainUtils::find_all_chains(&graph.base_graph).len(),
                 chain_count_before_pruning
             );
         }
@@ -173,12 +175,12 @@ fn test_adaptive_pruning_with_adjacent_bad_edges() {
             );
         };
 
-        let pruner = ChainPruner::AdaptiveChainPruner(AdaptiveChainPruner::new(
+        let pruner = AdaptiveChainPruner::new(
             0.01,
             2.0,
             MathUtils::log10_to_log(4.0),
             50,
-        ));
+        );
         pruner.prune_low_weight_chains(&mut graph.base_graph);
 
         assert!(!graph.base_graph.graph.contains_node(NodeIndex::new(4)));
@@ -258,12 +260,12 @@ fn test_adaptive_chain_pruning_with_bad_bubble() {
             );
         }
 
-        let pruner = ChainPruner::AdaptiveChainPruner(AdaptiveChainPruner::new(
+        let pruner = AdaptiveChainPruner::new(
             0.01,
             MathUtils::log10_to_log(1.0),
             MathUtils::log10_to_log(4.0),
             50,
-        ));
+        );
         pruner.prune_low_weight_chains(&mut graph.base_graph);
         assert!(!graph.base_graph.graph.contains_node(node_indices[4]));
         if variant_present {
@@ -360,12 +362,12 @@ fn test_adaptive_pruning(
             .count()
     );
 
-    let pruner = ChainPruner::AdaptiveChainPruner(AdaptiveChainPruner::new(
+    let pruner = AdaptiveChainPruner::new(
         0.001,
         log_odds_threshold,
         MathUtils::log10_to_log(4.0),
         50,
-    ));
+    );
     pruner.prune_low_weight_chains(graph.get_base_graph_mut());
     println!("Low weight chains pruned",);
     println!(
@@ -586,6 +588,64 @@ fn get_chain_pruner_data() {
     );
 }
 
+#[test]
+fn test_chain_pruner_registry() {
+    let parameters = PruningParameters {
+        prune_factor: 2,
+        initial_error_rate: 0.001,
+        log_odds_threshold: MathUtils::log10_to_log(1.0),
+        seeding_log_odds_threshold: MathUtils::log10_to_log(4.0),
+        max_unpruned_variants: 100,
+        coverage_fraction: 0.05,
+        min_sample_reads: 2,
+        min_samples: 1,
+    };
+    let registry = ChainPrunerRegistry::<SeqVertex, BaseEdgeStruct>::new();
+    assert!(registry.names().contains(&"adaptive"));
+    assert!(registry.names().contains(&"low-weight"));
+    assert!(registry.create("unknown", &parameters).is_err());
+
+    let adaptive = registry.create("adaptive", &parameters).unwrap();
+    assert_eq!(adaptive.name(), "adaptive");
+    assert!(adaptive.is_adaptive());
+
+    let source = SeqVertex::new("source".as_bytes().to_vec());
+    let sink = SeqVertex::new("sink".as_bytes().to_vec());
+    let A = SeqVertex::new("A".as_bytes().to_vec());
+    let B = SeqVertex::new("B".as_bytes().to_vec());
+    let C = SeqVertex::new("C".as_bytes().to_vec());
+    let D = SeqVertex::new("D".as_bytes().to_vec());
+
+    let mut graph = SeqGraph::new(20);
+    graph
+        .base_graph
+        .add_vertices(vec![&source, &A, &B, &C, &D, &sink]);
+    graph.base_graph.add_edges(
+        NodeIndex::new(0),
+        vec![
+            NodeIndex::new(1),
+            NodeIndex::new(2),
+            NodeIndex::new(3),
+            NodeIndex::new(5),
+        ],
+        BaseEdgeStruct::new(true, 1000, 0),
+    );
+    graph.base_graph.add_edges(
+        NodeIndex::new(1),
+        vec![NodeIndex::new(4), NodeIndex::new(3)],
+        BaseEdgeStruct::new(false, 1, 0),
+    );
+    assert_eq!(ChainUtils::find_all_chains(&graph.base_graph).len(), 4);
+
+    let low_weight = registry.create("low-weight", &parameters).unwrap();
+    assert_eq!(low_weight.name(), "low-weight");
+    assert!(!low_weight.is_adaptive());
+    low_weight.prune_low_weight_chains(&mut graph.base_graph);
+
+    assert!(!graph.base_graph.graph.contains_node(NodeIndex::new(4)));
+    assert_eq!(ChainUtils::find_all_chains(&graph.base_graph).len(), 1);
+}
+
 fn generate_read_with_errors(
     sequence: &[u8],
     start: usize,
@@ -0,0 +1,176 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use bio_types::strand::Strand;
+use lorikeet_genome::evolve::codon_structs::{
+    codon_position, CodonSubstitution, CodonTable, GeneticCodes, PhaseSet, Translations,
+};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_genetic_code_tables() {
+    let mut bacterial = CodonTable::setup();
+    bacterial.get_codon_table(11);
+    let mut mycoplasma = CodonTable::setup();
+    mycoplasma.get_codon_table(4);
+
+    assert_eq!(bacterial.amino_acid(b"TGA"), Some('*'));
+    assert_eq!(mycoplasma.amino_acid(b"TGA"), Some('W'));
+    for codon in [b"ATG", b"TAA", b"TAG", b"TGG", b"GCT"] {
+        assert_eq!(bacterial.amino_acid(codon), mycoplasma.amino_acid(codon));
+    }
+
+    let mut ciliate = CodonTable::setup();
+    ciliate.get_codon_table(6);
+    assert_eq!(ciliate.amino_acid(b"TAA"), Some('Q'));
+    assert_eq!(ciliate.amino_acid(b"TGA"), Some('*'));
+}
+
+#[test]
+fn test_genetic_codes_per_genome() {
+    let codes = GeneticCodes::parse(&["mycoplasma_pneumoniae=4", "25"]).unwrap();
+    assert_eq!(codes.default, 25);
+    assert_eq!(codes.for_genome("mycoplasma_pneumoniae"), 4);
+    assert_eq!(codes.for_genome("genomes/mycoplasma_pneumoniae.fna"), 4);
+    assert_eq!(codes.for_genome("ecoli"), 25);
+
+    let codes = GeneticCodes::parse::<&str>(&[]).unwrap();
+    assert_eq!(codes.for_genome("ecoli"), GeneticCodes::DEFAULT_TABLE);
+
+    assert!(GeneticCodes::parse(&["7"]).is_err());
+    assert!(GeneticCodes::parse(&["ecoli=eleven"]).is_err());
+    assert!(GeneticCodes::validate("ecoli=11").is_ok());
+}
+
+#[test]
+fn test_substitution_pathways() {
+    let mut table = CodonTable::setup();
+    table.get_codon_table(11);
+
+    // CTT (Leu) -> CTC (Leu) is synonymous, CTT -> TTT (Phe) is not
+    assert_eq!(
+        table.substitution_pathways(b"CTT", b"CTC"),
+        Some((0.0, 1.0))
+    );
+    assert_eq!(
+        table.substitution_pathways(b"CTT", b"TTT"),
+        Some((1.0, 0.0))
+    );
+    // CTT (Leu) -> TTA (Leu) via TTT (Phe) or CTA (Leu)
+    assert_eq!(
+        table.substitution_pathways(b"CTT", b"TTA"),
+        Some((1.0, 1.0))
+    );
+    assert_eq!(table.substitution_pathways(b"CTT", b"CTT"), None);
+}
+
+#[test]
+fn test_merge_codon_substitutions() {
+    let substitution =
+        |codon_position: usize, base: u8, phase_set: Option<PhaseSet>| CodonSubstitution {
+            codon_position,
+            base,
+            phase_set,
+        };
+
+    // phased substitutions give a single multi-nucleotide codon change
+    let phased = vec![
+        substitution(0, b'T', Some(PhaseSet::VariantGroup(1))),
+        substitution(2, b'A', Some(PhaseSet::VariantGroup(1))),
+    ];
+    assert_eq!(
+        CodonSubstitution::merge(b"CTT", &phased),
+        vec![b"TTA".to_vec()]
+    );
+
+    // unphased substitutions and other phase sets change the reference codon on their own
+    let unphased = vec![
+        substitution(0, b'T', None),
+        substitution(2, b'A', None),
+        substitution(1, b'G', Some(PhaseSet::Strains(vec![0]))),
+    ];
+    assert_eq!(
+        CodonSubstitution::merge(b"CTT", &unphased),
+        vec![b"CGT".to_vec(), b"TTT".to_vec(), b"CTA".to_vec()]
+    );
+}
+
+#[test]
+fn test_codon_position() {
+    // forward gene from 10 to 21 in frame 0
+    assert_eq!(codon_position(10, 21, 0, Strand::Forward, 10), Some((0, 0)));
+    assert_eq!(codon_position(10, 21, 0, Strand::Forward, 14), Some((1, 1)));
+    assert_eq!(codon_position(10, 21, 1, Strand::Forward, 14), Some((1, 0)));
+    assert_eq!(codon_position(10, 21, 1, Strand::Forward, 10), None);
+    assert_eq!(codon_position(10, 21, 0, Strand::Forward, 22), None);
+
+    // reverse genes are read from their end
+    assert_eq!(codon_position(10, 21, 0, Strand::Reverse, 21), Some((0, 0)));
+    assert_eq!(codon_position(10, 21, 0, Strand::Reverse, 10), Some((3, 2)));
+}
+
+#[test]
+fn test_strain_dnds() {
+    let mut table = CodonTable::setup();
+    table.get_codon_table(11);
+    let codon_sequence = vec![
+        b"ATG".to_vec(),
+        b"CTT".to_vec(),
+        b"GCT".to_vec(),
+        b"ANA".to_vec(),
+        b"TA".to_vec(),
+    ];
+
+    // incomplete codons and codons containing N have no sites
+    let (big_n, big_s) = table.count_sites(&codon_sequence);
+    assert!((big_n + big_s - 9.0).abs() < 1e-9);
+
+    // every substitution of a strain lies on the same haplotype, so the two substitutions of
+    // CTT give a single change to TTA, reached through TTT or through CTA
+    let strain = Some(PhaseSet::Strains(vec![0]));
+    let mut substitutions = BTreeMap::new();
+    substitutions.insert(
+        1,
+        vec![
+            CodonSubstitution {
+                codon_position: 0,
+                base: b'T',
+                phase_set: strain.clone(),
+            },
+            CodonSubstitution {
+                codon_position: 2,
+                base: b'A',
+                phase_set: strain.clone(),
+            },
+        ],
+    );
+    // GCT to ACT, alanine to threonine
+    substitutions.insert(
+        2,
+        vec![CodonSubstitution {
+            codon_position: 0,
+            base: b'A',
+            phase_set: strain.clone(),
+        }],
+    );
+    // codons containing N are skipped
+    substitutions.insert(
+        3,
+        vec![CodonSubstitution {
+            codon_position: 0,
+            base: b'T',
+            phase_set: strain,
+        }],
+    );
+    assert_eq!(
+        table.count_differences(&codon_sequence, &substitutions),
+        (2.0, 1.0, 1)
+    );
+
+    assert!((CodonTable::jukes_cantor_dnds(1.0, 10.0, 1.0, 5.0) - 0.461385).abs() < 1e-6);
+    // no synonymous differences
+    assert_eq!(CodonTable::jukes_cantor_dnds(1.0, 10.0, 0.0, 5.0), 1.0);
+    assert_eq!(CodonTable::jukes_cantor_dnds(0.0, 10.0, 0.0, 5.0), 1.0);
+}
@@ -0,0 +1,106 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::coverage_context::{CoverageContext, WindowDepths};
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::model::variants::Filter;
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use std::collections::HashMap;
+
+fn window_depths(depths: Vec<(usize, Vec<f64>)>) -> WindowDepths {
+    WindowDepths {
+        window_size: 100,
+        depths: depths.into_iter().collect(),
+    }
+}
+
+fn snp_context(pos: usize) -> VariantContext {
+    VariantContext::build(
+        0,
+        pos,
+        pos,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+        ],
+    )
+}
+
+#[test]
+fn test_add_read() {
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::Match(50), Cigar::Del(20), Cigar::Match(100)]);
+    record.set(b"read", Some(&cigar), &[b'A'; 150], &[30; 150]);
+    record.set_pos(60);
+
+    // the read covers 60-110 and 130-230, the deletion is not counted
+    let mut windows = vec![0.0; 3];
+    CoverageContext::add_read(&mut windows, 100, &record);
+    assert_eq!(windows, vec![40.0, 80.0, 30.0]);
+}
+
+#[test]
+fn test_median_window_depth() {
+    let depths = window_depths(vec![(0, vec![10.0, 30.0, 20.0, 1.0]), (1, vec![50.0])]);
+    // the partial last window of the first contig and the second contig are not used
+    let contig_lengths = HashMap::from([(0, 350), (1, 50)]);
+    assert_eq!(depths.median(&contig_lengths), 20.0);
+
+    let contig_lengths = HashMap::from([(0, 400), (1, 50)]);
+    assert_eq!(depths.median(&contig_lengths), 15.0);
+
+    assert_eq!(depths.depth_at(0, 250), Some(20.0));
+    assert_eq!(depths.depth_at(0, 450), None);
+    assert_eq!(depths.depth_at(2, 0), None);
+}
+
+#[test]
+fn test_copy_number_ratio() {
+    let samples = vec![
+        (window_depths(vec![(0, vec![10.0, 20.0, 30.0])]), 20.0),
+        (window_depths(vec![(0, vec![0.0, 40.0, 60.0])]), 40.0),
+        // samples without coverage are left out
+        (window_depths(vec![(0, vec![0.0, 0.0, 0.0])]), 0.0),
+    ];
+
+    // depths and medians are pooled across samples
+    assert_eq!(
+        CoverageContext::copy_number_ratio(&samples, 0, 250),
+        Some(1.5)
+    );
+    assert_eq!(
+        CoverageContext::copy_number_ratio(&samples, 0, 150),
+        Some(1.0)
+    );
+    assert_eq!(
+        CoverageContext::copy_number_ratio(&samples, 0, 50),
+        Some(10.0 / 60.0)
+    );
+    assert_eq!(CoverageContext::copy_number_ratio(&samples, 1, 50), None);
+}
+
+#[test]
+fn test_anomalous_coverage_is_filtered() {
+    let coverage_context = CoverageContext::new(100, 0.5, 2.0);
+    let key = VariantAnnotations::CopyNumberRatio.to_key().to_string();
+
+    let mut vc = snp_context(10);
+    assert!(!coverage_context.annotate(&mut vc, 1.5));
+    assert_eq!(vc.attributes.get(&key), Some(&AttributeObject::f64(1.5)));
+    assert!(!vc.filters.contains(&Filter::CopyNumber));
+
+    // a collapsed repeat with more than twice the median depth
+    let mut vc = snp_context(10);
+    assert!(coverage_context.annotate(&mut vc, 2.5));
+    assert_eq!(vc.attributes.get(&key), Some(&AttributeObject::f64(2.5)));
+    assert!(vc.filters.contains(&Filter::CopyNumber));
+
+    let mut vc = snp_context(10);
+    assert!(coverage_context.annotate(&mut vc, 0.25));
+    assert!(vc.filters.contains(&Filter::CopyNumber));
+}
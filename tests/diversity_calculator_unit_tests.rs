@@ -0,0 +1,123 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::model::accessible_genome::{AccessibleGenome, AccessibleWindow};
+use lorikeet_genome::model::diversity_calculator::DiversityCalculator;
+use lorikeet_genome::reference::reference_mask::ReferenceMask;
+use ndarray::Array2;
+
+fn compared_bases(within_1: f32, within_2: f32, between: f32) -> Array2<f32> {
+    let mut compared_bases = Array2::zeros((2, 2));
+    compared_bases[[0, 0]] = within_1;
+    compared_bases[[1, 1]] = within_2;
+    compared_bases[[0, 1]] = between;
+    compared_bases[[1, 0]] = between;
+    compared_bases
+}
+
+#[test]
+fn test_site_diversity() {
+    // half of the reads carry each allele
+    assert!(
+        (DiversityCalculator::within_sample_diversity(&[5, 5]) - 10.0 / 9.0 * 0.5).abs() < 1e-12
+    );
+    assert_eq!(DiversityCalculator::within_sample_diversity(&[10, 0]), 0.0);
+    assert_eq!(DiversityCalculator::within_sample_diversity(&[1, 0]), 0.0);
+
+    assert_eq!(
+        DiversityCalculator::between_sample_divergence(&[10, 0], &[0, 10]),
+        1.0
+    );
+    assert_eq!(
+        DiversityCalculator::between_sample_divergence(&[10, 0], &[10, 0]),
+        0.0
+    );
+    assert_eq!(
+        DiversityCalculator::between_sample_divergence(&[0, 0], &[10, 0]),
+        0.0
+    );
+}
+
+#[test]
+fn test_diversity_uses_accessible_bases() {
+    let accessible_genome = AccessibleGenome::new();
+    accessible_genome.record(AccessibleWindow::new(
+        0,
+        100,
+        199,
+        compared_bases(100.0, 50.0, 50.0),
+    ));
+    accessible_genome.record(AccessibleWindow::new(
+        0,
+        0,
+        99,
+        compared_bases(100.0, 100.0, 100.0),
+    ));
+
+    // the second sample is only covered over half of the genome
+    let mut calculator = DiversityCalculator::new(
+        2,
+        2,
+        &accessible_genome,
+        compared_bases(200.0, 150.0, 150.0),
+    );
+    calculator.add_site(Some(0), &[vec![10, 0], vec![0, 10]]);
+    calculator.add_site(Some(1), &[vec![5, 5], vec![10, 0]]);
+    // not enough depth in the second sample
+    calculator.add_site(Some(1), &[vec![10, 0], vec![1, 0]]);
+
+    let diversity = calculator.sample_diversity();
+    assert_eq!(diversity[0].accessible_bases, 200.0);
+    assert_eq!(diversity[0].segregating_sites, 1);
+    assert!((diversity[0].pi - 10.0 / 9.0 * 0.5 / 200.0).abs() < 1e-12);
+    assert_eq!(diversity[1].pi, 0.0);
+
+    let windows = calculator.window_diversity();
+    assert_eq!(windows.len(), 2);
+    assert_eq!(windows[0].0.start, 0);
+    assert_eq!(windows[1].1[1].accessible_bases, 50.0);
+    assert_eq!(windows[1].1[0].segregating_sites, 1);
+
+    let pairs = calculator.pairwise_divergence();
+    assert_eq!(pairs.len(), 1);
+    assert_eq!(pairs[0].accessible_bases, 150.0);
+    assert!((pairs[0].dxy - 1.5 / 150.0).abs() < 1e-12);
+    assert!(pairs[0].fst > 0.0 && pairs[0].fst <= 1.0);
+}
+
+#[test]
+fn test_accessible_windows() {
+    let accessible_genome = AccessibleGenome::new();
+    accessible_genome.record(AccessibleWindow::new(
+        1,
+        0,
+        99,
+        compared_bases(100.0, 100.0, 100.0),
+    ));
+    accessible_genome.record(AccessibleWindow::new(
+        0,
+        0,
+        99,
+        compared_bases(100.0, 80.0, 80.0),
+    ));
+
+    let mut mask = ReferenceMask::new();
+    mask.add_interval(0, 90, 120);
+    mask.merge();
+    assert_eq!(mask.masked_bases_within(0, 0, 99), 10);
+    assert_eq!(mask.masked_bases_within(0, 100, 109), 10);
+    assert_eq!(mask.masked_bases_within(1, 0, 99), 0);
+    accessible_genome.apply_mask(&mask);
+
+    let windows = accessible_genome.windows();
+    assert_eq!(windows[0].tid, 0);
+    assert_eq!(windows[0].accessible_bases(0, 0), 90.0);
+    assert_eq!(windows[0].accessible_bases(0, 1), 70.0);
+    assert_eq!(windows[1].accessible_bases(0, 0), 100.0);
+
+    assert_eq!(AccessibleGenome::window_index(&windows, 0, 50), Some(0));
+    assert_eq!(AccessibleGenome::window_index(&windows, 1, 99), Some(1));
+    assert_eq!(AccessibleGenome::window_index(&windows, 1, 100), None);
+}
@@ -0,0 +1,56 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::dropped_alleles::{DropReason, DroppedAllele, DroppedAlleleLog};
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+
+fn dropped(tid: usize, position: usize, alt: &[u8]) -> DroppedAllele {
+    DroppedAllele::new(
+        tid,
+        position,
+        &ByteArrayAllele::new(b"A", true),
+        &ByteArrayAllele::new(alt, false),
+        DropReason::HaplotypeAlleles,
+        -10.0,
+        3,
+        5,
+    )
+}
+
+#[test]
+fn test_clones_share_dropped_alleles() {
+    let log = DroppedAlleleLog::new();
+    let clone = log.clone();
+    assert!(log.is_empty());
+
+    clone.record(dropped(1, 50, b"T"));
+    log.record(dropped(0, 100, b"G"));
+    clone.record(dropped(0, 100, b"C"));
+    assert_eq!(log.len(), 3);
+    assert_eq!(clone.len(), 3);
+
+    let alleles = log.alleles();
+    assert_eq!(
+        alleles
+            .iter()
+            .map(|allele| (allele.tid, allele.position, allele.alt_allele.clone()))
+            .collect::<Vec<(usize, usize, Vec<u8>)>>(),
+        vec![
+            (0, 100, b"C".to_vec()),
+            (0, 100, b"G".to_vec()),
+            (1, 50, b"T".to_vec()),
+        ]
+    );
+    assert_eq!(alleles[0].ref_allele, b"A".to_vec());
+    assert_eq!(alleles[0].reason.to_key(), "haplotype_alleles");
+}
+
+#[test]
+fn test_file_path() {
+    assert_eq!(
+        DroppedAlleleLog::file_path("out/genome", "genome"),
+        "out/genome/genome_dropped_alleles.tsv"
+    );
+}
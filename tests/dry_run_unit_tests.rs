@@ -0,0 +1,56 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::cli::build_cli;
+use lorikeet_genome::processing::dry_run::DryRun;
+
+static reference_path: &str = "tests/data/two_contigs.fna";
+static bam_path: &str = "tests/data/two_contigs_lr1.bam";
+
+fn call_args(reference: &str, bam: &str) -> clap::ArgMatches {
+    build_cli()
+        .get_matches_from(vec![
+            "lorikeet",
+            "call",
+            "--dry-run",
+            "-r",
+            reference,
+            "-b",
+            bam,
+        ])
+        .subcommand_matches("call")
+        .unwrap()
+        .clone()
+}
+
+#[test]
+fn test_missing_reference() {
+    let args = call_args("tests/data/missing_genome.fna", bam_path);
+    let mut dry_run = DryRun::new(&args, "call");
+    let references = dry_run.check_inputs().unwrap();
+
+    assert!(references.is_empty());
+    assert!(dry_run
+        .problems()
+        .contains(&"Reference genome tests/data/missing_genome.fna does not exist".to_string()));
+}
+
+#[test]
+fn test_missing_bam() {
+    let args = call_args(reference_path, "tests/data/missing.bam");
+    let mut dry_run = DryRun::new(&args, "call");
+    let references = dry_run.check_inputs().unwrap();
+
+    // the reference is still read so the rest of the plan can be checked
+    assert_eq!(references.len(), 1);
+    assert_eq!(references[0].contigs.len(), 2);
+    assert_eq!(
+        dry_run.problems(),
+        &["BAM file tests/data/missing.bam does not exist".to_string()]
+    );
+
+    // every problem fails the dry run
+    assert!(DryRun::new(&args, "call").run().is_err());
+}
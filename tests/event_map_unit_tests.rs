@@ -9,6 +9,7 @@ use lorikeet_genome::haplotype::haplotype::Haplotype;
 use lorikeet_genome::model::byte_array_allele::{Allele, ByteArrayAllele};
 
 use lorikeet_genome::model::variant_context_utils::VariantContextUtils;
+use lorikeet_genome::utils::errors::BirdToolError;
 use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
 use rust_htslib::bam::record::CigarString;
 use std::convert::TryFrom;
@@ -297,3 +298,59 @@ fn run_test_blocks() {
     test_make_blocks(vec!["A", "ACGTA"], vec!["AG", "A"], vec!["AG", "ACGTA"]);
     test_make_blocks(vec!["A", "AC"], vec!["AGCGT", "A"], vec!["AGCGT", "AC"]);
 }
+
+#[test]
+fn run_complex_event_tests() {
+    let ref_bases = "AAAAACCCCCGGGGG";
+    let loc = SimpleInterval::new(0, 1, ref_bases.len());
+    // a SNP one base before a deletion
+    let mut hap = Haplotype::new("AAAAATCCCGGGGG".as_bytes(), false);
+    hap.set_cigar(CigarString::try_from("7M1D7M").unwrap().0);
+    hap.set_genome_location(loc.clone());
+
+    let mut event_map = EventMap::new(&hap, ref_bases.as_bytes(), loc.clone(), NAME.to_string(), 1);
+    assert_eq!(event_map.get_number_of_events(), 2);
+
+    // a distance of 0 leaves the events decomposed
+    event_map.merge_complex_events(ref_bases.as_bytes(), 0);
+    assert_eq!(event_map.get_number_of_events(), 2);
+
+    event_map.merge_complex_events(ref_bases.as_bytes(), 1);
+    assert_eq!(event_map.get_number_of_events(), 1);
+    let complex_event = event_map.get_variant_contexts()[0];
+    assert_eq!(complex_event.loc.get_start(), 6);
+    assert_eq!(complex_event.loc.get_end(), 8);
+    assert_eq!(complex_event.get_reference().get_bases(), b"CCC");
+    assert_eq!(complex_event.get_alternate_alleles()[0].get_bases(), b"TC");
+}
+
+#[test]
+fn test_complex_events_require_distance() {
+    let command = || {
+        clap::Command::new("call")
+            .arg(
+                clap::Arg::new("max-mnp-distance")
+                    .long("max-mnp-distance")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("0"),
+            )
+            .arg(
+                clap::Arg::new("emit-complex-events")
+                    .long("emit-complex-events")
+                    .action(clap::ArgAction::SetTrue),
+            )
+    };
+    let check = |args: &[&str]| {
+        let matches = command().try_get_matches_from(args).unwrap();
+        EventMap::check_args(&matches)
+    };
+
+    assert!(check(&["call"]).is_ok());
+    assert!(check(&["call", "--max-mnp-distance", "2"]).is_ok());
+    assert!(check(&["call", "--emit-complex-events", "--max-mnp-distance", "2"]).is_ok());
+    // complex events would never be combined
+    assert!(matches!(
+        check(&["call", "--emit-complex-events"]),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
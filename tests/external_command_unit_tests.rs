@@ -0,0 +1,124 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::external_command::{CommandFailureKind, ExternalCommand};
+use std::process::{Command, Stdio};
+
+#[test]
+fn test_classify_failures() {
+    assert_eq!(
+        CommandFailureKind::classify(Some(127), None, "bash: svim: command not found"),
+        CommandFailureKind::MissingBinary
+    );
+    assert_eq!(
+        CommandFailureKind::classify(Some(126), None, "Permission denied"),
+        CommandFailureKind::NotExecutable
+    );
+    // killed directly, or a command run by bash being killed
+    assert_eq!(
+        CommandFailureKind::classify(None, Some(9), ""),
+        CommandFailureKind::OutOfMemory
+    );
+    assert_eq!(
+        CommandFailureKind::classify(Some(137), None, ""),
+        CommandFailureKind::OutOfMemory
+    );
+    assert_eq!(
+        CommandFailureKind::classify(
+            Some(1),
+            None,
+            "terminate called after throwing an instance of 'std::bad_alloc'"
+        ),
+        CommandFailureKind::OutOfMemory
+    );
+    assert_eq!(
+        CommandFailureKind::classify(
+            Some(1),
+            None,
+            "slurmstepd: error: Detected 1 oom-kill event(s) in StepId=1.batch"
+        ),
+        CommandFailureKind::OutOfMemory
+    );
+    // being killed is only out of memory when the signal or the message says so
+    assert_eq!(
+        CommandFailureKind::classify(Some(1), None, "worker 3 killed by user request"),
+        CommandFailureKind::RuntimeError
+    );
+    assert_eq!(
+        CommandFailureKind::classify(None, Some(15), "Killed"),
+        CommandFailureKind::Signal(15)
+    );
+    assert_eq!(
+        CommandFailureKind::classify(Some(143), None, ""),
+        CommandFailureKind::Signal(15)
+    );
+    assert_eq!(
+        CommandFailureKind::classify(Some(1), None, "[E::bcf_hdr_read] Invalid header"),
+        CommandFailureKind::RuntimeError
+    );
+}
+
+#[test]
+fn test_read_tail() {
+    let text = (0..1000)
+        .map(|line| format!("line {}", line))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let tail = ExternalCommand::read_tail(text.as_bytes(), 100);
+    assert!(tail.len() <= 100);
+    assert!(tail.ends_with("line 999"));
+    // the line cut by the limit is dropped
+    assert!(tail.starts_with("line "));
+
+    assert_eq!(
+        ExternalCommand::read_tail("error\n".as_bytes(), 100),
+        "error"
+    );
+}
+
+#[test]
+fn test_run_external_command() {
+    assert!(ExternalCommand::run(Command::new("bash").arg("-c").arg("exit 0"), "bash").is_ok());
+
+    let failure = ExternalCommand::run(
+        Command::new("bash")
+            .arg("-c")
+            .arg("echo progress; echo 'bad input' >&2; exit 3")
+            .stdout(Stdio::piped()),
+        "tool",
+    )
+    .unwrap_err();
+    assert_eq!(failure.kind, CommandFailureKind::RuntimeError);
+    assert_eq!(failure.exit_code, Some(3));
+    assert_eq!(failure.stderr_tail, "bad input");
+    assert!(failure.message().contains("tool failed with exit code 3"));
+
+    let failure = ExternalCommand::run(
+        Command::new("bash")
+            .arg("-c")
+            .arg("lorikeet_missing_test_binary --version"),
+        "lorikeet_missing_test_binary",
+    )
+    .unwrap_err();
+    assert_eq!(failure.kind, CommandFailureKind::MissingBinary);
+
+    let failure =
+        ExternalCommand::run(&mut Command::new("lorikeet_missing_test_binary"), "missing")
+            .unwrap_err();
+    assert_eq!(failure.kind, CommandFailureKind::MissingBinary);
+
+    let failure =
+        ExternalCommand::run(Command::new("bash").arg("-c").arg("kill -9 $$"), "bash").unwrap_err();
+    assert_eq!(failure.kind, CommandFailureKind::OutOfMemory);
+
+    // chatty programs do not fill the pipe and hang
+    assert!(ExternalCommand::run(
+        Command::new("bash")
+            .arg("-c")
+            .arg("for i in $(seq 1 20000); do echo \"warning $i\" >&2; done"),
+        "bash",
+    )
+    .is_ok());
+}
@@ -0,0 +1,61 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::feature_context::{FeatureContext, FeatureWindow};
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::utils::simple_interval::Locatable;
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+
+fn feature(start: usize, end: usize) -> (u64, u64, VariantContext) {
+    let alleles = vec![
+        ByteArrayAllele::new(b"A", true),
+        ByteArrayAllele::new(b"<DEL>", false),
+    ];
+    (
+        start as u64,
+        end as u64,
+        VariantContext::build(0, start, end, alleles),
+    )
+}
+
+#[test]
+fn test_feature_window() {
+    // a deletion spanning into the window, a SNV and a feature past the queried region
+    let window = FeatureWindow::new(
+        0,
+        1000,
+        12000,
+        vec![feature(500, 1500), feature(2000, 2000), feature(9000, 9000)],
+    );
+    assert_eq!(window.len(), 3);
+
+    assert!(window.covers(0, 1000, 2500));
+    assert!(window.covers(0, 8000, 12000));
+    // regions before the window, past it or on another contig have to be fetched
+    assert!(!window.covers(0, 900, 2500));
+    assert!(!window.covers(0, 11000, 13000));
+    assert!(!window.covers(1, 1000, 2500));
+
+    let starts = |start: u64, end: u64| {
+        window
+            .overlapping(start, end)
+            .iter()
+            .map(|feature| feature.loc.get_start())
+            .collect::<Vec<usize>>()
+    };
+    assert_eq!(starts(1000, 2500), vec![500, 2000]);
+    assert_eq!(starts(1600, 1999), Vec::<usize>::new());
+    assert_eq!(starts(2000, 9000), vec![2000, 9000]);
+}
+
+#[test]
+fn test_empty_feature_context() {
+    let mut feature_context = FeatureContext::new(Vec::new(), FeatureContext::DEFAULT_LOOKAHEAD);
+    assert!(feature_context.is_empty());
+    let separator = GenomeSeparator::default();
+    assert!(feature_context.query(b"contig_1", &separator, 0, 1000).is_empty());
+    assert!(FeatureContext::check(&[]).is_ok());
+}
@@ -0,0 +1,58 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::forced_alleles::{ForcedAlleles, ForcedPosition};
+use lorikeet_genome::utils::simple_interval::Locatable;
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_parse_positions_line() {
+    assert_eq!(
+        ForcedAlleles::parse_line("contig_1\t10\ta\tG,T").unwrap(),
+        Some((
+            "contig_1".to_string(),
+            ForcedPosition {
+                pos: 9,
+                ref_allele: b"A".to_vec(),
+                alt_alleles: vec![b"G".to_vec(), b"T".to_vec()],
+            }
+        ))
+    );
+    assert_eq!(ForcedAlleles::parse_line("# comment").unwrap(), None);
+    assert_eq!(ForcedAlleles::parse_line("contig\tpos\tref\talt").unwrap(), None);
+    assert_eq!(ForcedAlleles::parse_line("").unwrap(), None);
+
+    assert!(ForcedAlleles::parse_line("contig_1\t0\tA\tG").is_err());
+    assert!(ForcedAlleles::parse_line("contig_1\t10\tA").is_err());
+    assert!(ForcedAlleles::parse_line("contig_1\t10\tA\tA").is_err());
+    assert!(ForcedAlleles::parse_line("contig_1\t10\tA\t<DEL>").is_err());
+}
+
+#[test]
+fn test_forced_alleles_in_region() {
+    let mut tsv = NamedTempFile::new().unwrap();
+    writeln!(tsv, "contig\tpos\tref\talt").unwrap();
+    writeln!(tsv, "contig_1\t500\tA\tG").unwrap();
+    writeln!(tsv, "contig_1\t100\tACGT\tA").unwrap();
+    writeln!(tsv, "contig_2\t100\tC\tT").unwrap();
+    let path = tsv.path().to_str().unwrap().to_string();
+
+    let forced_alleles = ForcedAlleles::from_tsvs(&[path]).unwrap();
+    assert_eq!(forced_alleles.len(), 3);
+    let separator = GenomeSeparator::default();
+
+    // the deletion starting before the interval still overlaps it
+    let variants = forced_alleles.variants_in(b"contig_1", &separator, 0, 101, 600);
+    assert_eq!(variants.len(), 2);
+    assert_eq!(variants[0].loc.get_start(), 99);
+    assert_eq!(variants[0].loc.get_end(), 102);
+    assert_eq!(variants[1].loc.get_start(), 499);
+
+    assert_eq!(forced_alleles.variants_in(b"contig_1", &separator, 0, 103, 400).len(), 0);
+    assert_eq!(forced_alleles.variants_in(b"genome~contig_2", &separator, 1, 0, 200).len(), 1);
+    assert_eq!(forced_alleles.variants_in(b"contig_3", &separator, 2, 0, 200).len(), 0);
+}
@@ -0,0 +1,102 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::genome_runs::GenomeRuns;
+use lorikeet_genome::utils::errors::BirdToolError;
+use lorikeet_genome::utils::exit_status::ExitStatus;
+use lorikeet_genome::utils::partial_output::PartialOutput;
+use std::io::Write;
+
+#[test]
+fn test_exit_status_codes() {
+    assert_eq!(ExitStatus::Success.code(), 0);
+    assert_eq!(
+        ExitStatus::from_error(&BirdToolError::ConfigError("bad option".to_string())),
+        ExitStatus::ConfigurationError
+    );
+    assert_eq!(
+        ExitStatus::from_error(&BirdToolError::ProcessPanicked(
+            "bcftools failed".to_string()
+        )),
+        ExitStatus::ExternalToolError
+    );
+    assert_eq!(
+        ExitStatus::from_error(&BirdToolError::IOError("disk full".to_string())),
+        ExitStatus::Failure
+    );
+
+    assert_eq!(ExitStatus::from_genomes(3, 0), ExitStatus::Success);
+    assert_eq!(ExitStatus::from_genomes(3, 1), ExitStatus::PartialSuccess);
+    assert_eq!(ExitStatus::from_genomes(3, 3), ExitStatus::Failure);
+}
+
+#[test]
+fn test_error_messages() {
+    // failures reach the user with their reason rather than where the error type is defined
+    let error = BirdToolError::ConfigError("--ploidy must be at least 1".to_string());
+    assert_eq!(error.to_string(), "--ploidy must be at least 1");
+    assert_eq!(error.message(), "--ploidy must be at least 1");
+    assert_eq!(
+        format!("{:?}", error),
+        "ConfigError(\"--ploidy must be at least 1\")"
+    );
+}
+
+#[test]
+fn test_partial_output() {
+    let directory = tempfile::tempdir().unwrap();
+    let path = directory.path().join("genome.vcf");
+    let output = PartialOutput::new(&path);
+    assert_eq!(
+        output.partial_path(),
+        directory.path().join("genome.vcf.incomplete")
+    );
+
+    let mut file = output.create().unwrap();
+    writeln!(file, "##fileformat=VCFv4.2").unwrap();
+    drop(file);
+    assert!(!path.exists());
+
+    assert_eq!(output.commit().unwrap(), path);
+    assert!(path.exists());
+    assert!(!directory.path().join("genome.vcf.incomplete").exists());
+}
+
+#[test]
+fn test_genome_runs() {
+    let directory = tempfile::tempdir().unwrap();
+    let finished = directory.path().join("genome_1");
+    let failed = directory.path().join("genome_2");
+    let genome_runs = GenomeRuns::new();
+
+    genome_runs.run("genome_1", finished.to_str().unwrap(), || {
+        assert!(GenomeRuns::is_incomplete(finished.to_str().unwrap()));
+        Ok(())
+    });
+    assert!(!GenomeRuns::is_incomplete(finished.to_str().unwrap()));
+
+    // a failing genome is recorded and leaves its outputs marked, without taking down the run
+    genome_runs.run("genome_2", failed.to_str().unwrap(), || {
+        panic!("no reads mapped");
+    });
+    assert!(GenomeRuns::is_incomplete(failed.to_str().unwrap()));
+    let marker = std::fs::read_to_string(failed.join(GenomeRuns::INCOMPLETE_MARKER)).unwrap();
+    assert!(marker.contains("no reads mapped"));
+
+    // as is one whose steps return an error
+    let errored = directory.path().join("genome_3");
+    genome_runs.run("genome_3", errored.to_str().unwrap(), || {
+        Err(BirdToolError::IOError("disk full".to_string()))
+    });
+    assert!(GenomeRuns::is_incomplete(errored.to_str().unwrap()));
+    let marker = std::fs::read_to_string(errored.join(GenomeRuns::INCOMPLETE_MARKER)).unwrap();
+    assert!(marker.contains("Failed: disk full"));
+
+    assert_eq!(
+        genome_runs.failed(),
+        vec!["genome_2".to_string(), "genome_3".to_string()]
+    );
+    assert_eq!(genome_runs.status(3), ExitStatus::PartialSuccess);
+}
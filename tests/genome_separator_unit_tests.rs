@@ -0,0 +1,93 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use std::borrow::Cow;
+
+fn genome_names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_auto_separator() {
+    assert_eq!(
+        GenomeSeparator::select("auto", &genome_names(&["genome_1", "genome_2"])).unwrap(),
+        GenomeSeparator::new('~')
+    );
+    assert_eq!(
+        GenomeSeparator::select("auto", &genome_names(&["genome~1", "genome_2"])).unwrap(),
+        GenomeSeparator::new('^')
+    );
+    assert_eq!(
+        GenomeSeparator::select("auto", &genome_names(&["genome~1", "genome^2"])).unwrap(),
+        GenomeSeparator::new('@')
+    );
+    // every candidate is taken, so the default is escaped in genome names
+    assert_eq!(
+        GenomeSeparator::select("auto", &genome_names(&["g~^@"])).unwrap(),
+        GenomeSeparator::default()
+    );
+}
+
+#[test]
+fn test_requested_separator() {
+    assert_eq!(
+        GenomeSeparator::select("^", &genome_names(&["genome~1"])).unwrap(),
+        GenomeSeparator::new('^')
+    );
+    assert_eq!(
+        GenomeSeparator::select("^", &genome_names(&["genome^1"])).unwrap(),
+        GenomeSeparator::new('^')
+    );
+    // | joins contig and genome names in consensus FASTA headers
+    assert!(GenomeSeparator::select("|", &genome_names(&["genome_1"])).is_err());
+    assert!(GenomeSeparator::select("%", &genome_names(&["genome_1"])).is_err());
+    assert!(GenomeSeparator::select("~~", &genome_names(&["genome_1"])).is_err());
+}
+
+#[test]
+fn test_split_contig_names() {
+    let separator = GenomeSeparator::default();
+    assert_eq!(
+        separator.split("genome_1~contig~1"),
+        Some((Cow::Borrowed("genome_1"), "contig~1"))
+    );
+    assert_eq!(separator.genome("contig_1"), "contig_1");
+    assert_eq!(separator.contig("genome_1~contig_1"), "contig_1");
+
+    let separator = GenomeSeparator::new('@');
+    assert_eq!(separator.join("genome_1", "contig~1"), "genome_1@contig~1");
+    assert_eq!(separator.genome("genome_1@contig~1"), "genome_1");
+    assert_eq!(separator.contig("genome_1~contig_1"), "genome_1~contig_1");
+}
+
+#[test]
+fn test_escaped_genome_names() {
+    let separator = GenomeSeparator::default();
+    assert_eq!(separator.escape("genome_1"), "genome_1");
+    assert_eq!(separator.escape("genome~1"), "genome%7E1");
+    assert_eq!(separator.escape("100%_genome"), "100%25_genome");
+
+    let name = separator.join("g~^@%", "contig~1");
+    assert_eq!(name, "g%7E^@%25~contig~1");
+    assert_eq!(
+        separator.split(&name),
+        Some((Cow::Owned("g~^@%".to_string()), "contig~1"))
+    );
+    assert_eq!(separator.contig(&name), "contig~1");
+
+    // escape characters that do not start an escaped character are kept
+    assert_eq!(GenomeSeparator::unescape("genome%zz"), "genome%zz");
+    assert_eq!(GenomeSeparator::unescape("genome%"), "genome%");
+}
+
+#[test]
+fn test_validate_contig_name() {
+    assert!(GenomeSeparator::validate_contig_name("NODE_1_length_100|x~y").is_ok());
+    assert!(GenomeSeparator::validate_contig_name("contig<1>").is_err());
+    assert!(GenomeSeparator::validate_contig_name("contig,1").is_err());
+    assert!(GenomeSeparator::validate_contig_name("*contig").is_err());
+    assert!(GenomeSeparator::validate_contig_name("").is_err());
+}
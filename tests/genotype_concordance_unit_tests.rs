@@ -0,0 +1,99 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::concordance::genotype_concordance::{
+    ConcordanceFlag, GenotypeConcordance, SiteGenotypes,
+};
+use std::collections::HashMap;
+
+fn site_genotypes(sample_names: &[&str], genotypes: Vec<Vec<Option<&[u8]>>>) -> SiteGenotypes {
+    let mut sites = HashMap::new();
+    for (position, site) in genotypes.into_iter().enumerate() {
+        sites.insert(
+            ("contig_1".to_string(), position, b"A".to_vec()),
+            site.into_iter()
+                .map(|genotype| genotype.map(|bases| bases.to_vec()))
+                .collect(),
+        );
+    }
+
+    SiteGenotypes {
+        sample_names: sample_names.iter().map(|name| name.to_string()).collect(),
+        sites,
+    }
+}
+
+#[test]
+fn test_within_run_duplicates() {
+    let genotypes = site_genotypes(
+        &["s1", "s2", "s3"],
+        vec![
+            vec![Some(b"A"), Some(b"A"), Some(b"T")],
+            vec![Some(b"T"), Some(b"T"), Some(b"A")],
+            vec![Some(b"A"), Some(b"A"), None],
+            vec![None, Some(b"T"), Some(b"T")],
+        ],
+    );
+
+    let concordance = GenotypeConcordance::within_run(&genotypes);
+    assert_eq!(concordance.compared_sites[[0, 1]], 3);
+    assert_eq!(concordance.concordance(0, 1), Some(1.0));
+    assert_eq!(concordance.compared_sites[[1, 2]], 3);
+    assert_eq!(concordance.concordance(1, 2), Some(1.0 / 3.0));
+
+    let flags = concordance.flag_pairs(2, 0.99);
+    assert_eq!(
+        flags,
+        vec![
+            (0, 1, ConcordanceFlag::ProbableDuplicate),
+            (0, 2, ConcordanceFlag::Unflagged),
+            (1, 2, ConcordanceFlag::Unflagged),
+        ]
+    );
+
+    // too few shared sites to say anything
+    let flags = concordance.flag_pairs(4, 0.99);
+    assert!(flags
+        .iter()
+        .all(|(_, _, flag)| *flag == ConcordanceFlag::InsufficientSites));
+}
+
+#[test]
+fn test_sample_swap_against_comparison() {
+    let query = site_genotypes(
+        &["s1", "s2"],
+        vec![
+            vec![Some(b"A"), Some(b"T")],
+            vec![Some(b"T"), Some(b"A")],
+            vec![Some(b"A"), Some(b"T")],
+        ],
+    );
+    // the labels of s1 and s2 have been swapped in the comparison
+    let target = site_genotypes(
+        &["s2", "s1"],
+        vec![
+            vec![Some(b"A"), Some(b"T")],
+            vec![Some(b"T"), Some(b"A")],
+            vec![Some(b"A"), Some(b"T")],
+        ],
+    );
+
+    let concordance = GenotypeConcordance::compare(&query, &target);
+    let flags = concordance.flag_pairs(2, 0.99);
+    assert_eq!(
+        flags,
+        vec![
+            (0, 0, ConcordanceFlag::ProbableSwap),
+            (0, 1, ConcordanceFlag::Discordant),
+            (1, 0, ConcordanceFlag::Discordant),
+            (1, 1, ConcordanceFlag::ProbableSwap),
+        ]
+    );
+
+    let concordance = GenotypeConcordance::compare(&query, &query);
+    let flags = concordance.flag_pairs(2, 0.99);
+    assert_eq!(flags[0], (0, 0, ConcordanceFlag::Match));
+    assert_eq!(flags[1], (0, 1, ConcordanceFlag::Unflagged));
+}
@@ -193,3 +193,23 @@ fn ploidy_and_maximum_allele_and_read_counts_data() {
         }
     }
 }
+
+#[test]
+fn haploid_instance_matches_general_tables() {
+    for allele_count in MAXIMUM_ALLELE.iter() {
+        let mut calculator = GenotypeLikelihoodCalculators::get_instance(1, *allele_count);
+        assert_eq!(calculator.genotype_count as usize, *allele_count);
+        assert_eq!(
+            calculator.allele_first_genotype_offset_by_ploidy,
+            GenotypeLikelihoodCalculators::build_allele_first_genotype_offset_table(
+                1,
+                *allele_count
+            )
+        );
+        for genotype_index in 0..*allele_count {
+            let genotype = calculator.genotype_allele_counts_at(genotype_index);
+            assert_eq!(genotype.distinct_allele_count(), 1);
+            assert_eq!(genotype.allele_index_at(0), genotype_index);
+        }
+    }
+}
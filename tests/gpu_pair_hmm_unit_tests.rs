@@ -0,0 +1,204 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::assembly_region::AssemblyRegion;
+use lorikeet_genome::assembly::assembly_result_set::AssemblyResultSet;
+use lorikeet_genome::haplotype::haplotype::Haplotype;
+use lorikeet_genome::pair_hmm::gpu_pair_hmm::PairHMMBackend;
+#[cfg(feature = "gpu")]
+use lorikeet_genome::pair_hmm::gpu_pair_hmm::{GpuPairHMM, GpuPairHMMBatch};
+#[cfg(feature = "gpu")]
+use lorikeet_genome::pair_hmm::pair_hmm::PairHMM;
+use lorikeet_genome::pair_hmm::pair_hmm_likelihood_calculation_engine::{
+    AVXMode, PCRErrorModel, PairHMMLikelihoodCalculationEngine,
+};
+#[cfg(feature = "gpu")]
+use lorikeet_genome::pair_hmm::pair_hmm_model::PairHMMModel;
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::read_threading::read_threading_graph::ReadThreadingGraph;
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::utils::artificial_read_utils::ArtificialReadUtils;
+use lorikeet_genome::utils::math_utils::MathUtils;
+use lorikeet_genome::utils::quality_utils::QualityUtils;
+use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
+use std::collections::HashMap;
+
+/// Likelihoods of a read matching the first of two haplotypes, computed with the GPU PairHMM
+/// of the given backend
+fn backend_likelihoods(backend: PairHMMBackend) -> Vec<f64> {
+    let mut lce = PairHMMLikelihoodCalculationEngine::new(
+        93,
+        MathUtils::log_to_log10(QualityUtils::qual_to_error_prob_log10(45)),
+        PCRErrorModel::Conservative,
+        16,
+        false,
+        1.0,
+        0.02,
+        true,
+        false,
+        true,
+        AVXMode::None,
+    );
+    lce.set_gpu_pair_hmm(backend.initialize());
+
+    let n = 10;
+    let mut read = BirdToolRead::new(
+        ArtificialReadUtils::create_artificial_read_default("test", 0, 0, 10, false),
+        0,
+        ReadType::Short,
+    );
+    read.read.set_mapq(60);
+    let location = SimpleInterval::new(read.get_contig(), read.get_start(), read.get_end());
+
+    let ref_bases = vec![b'A'; n + 1];
+    let mut ref_haplotype = Haplotype::new(ref_bases.as_slice(), true);
+    ref_haplotype.set_genome_location(location.clone());
+    let mut assembly_result_set = AssemblyResultSet::<ReadThreadingGraph>::new(
+        AssemblyRegion::new(SimpleInterval::new(0, 0, n + 1), true, 0, 100, 0, 0, 0.0),
+        ref_bases.clone(),
+        SimpleInterval::new(0, 0, n + 1),
+        ref_haplotype.clone(),
+    );
+    assembly_result_set.add_haplotype(ref_haplotype);
+
+    let mut alt_bases = ref_bases;
+    alt_bases[5] = b'C';
+    let mut alt_haplotype = Haplotype::new(alt_bases.as_slice(), false);
+    alt_haplotype.set_genome_location(location);
+    assembly_result_set.add_haplotype(alt_haplotype);
+
+    let mut per_sample_read_list = HashMap::new();
+    per_sample_read_list.insert(0, vec![read]);
+    let mut likelihoods =
+        lce.compute_read_likelihoods(&mut assembly_result_set, vec![0], per_sample_read_list);
+    let matrix = likelihoods.sample_matrix(0);
+    vec![matrix[[0, 0]], matrix[[1, 0]]]
+}
+
+#[cfg(not(feature = "gpu"))]
+#[test]
+fn test_gpu_backend_falls_back_to_cpu() {
+    // without the gpu feature no device can be initialized, so every backend uses the CPU
+    assert!(PairHMMBackend::Gpu.initialize().is_none());
+    assert!(PairHMMBackend::Auto.initialize().is_none());
+    assert!(PairHMMBackend::Avx.initialize().is_none());
+
+    let cpu_likelihoods = backend_likelihoods(PairHMMBackend::Avx);
+    assert!(cpu_likelihoods[0] > cpu_likelihoods[1]);
+    assert_eq!(backend_likelihoods(PairHMMBackend::Gpu), cpu_likelihoods);
+    assert_eq!(backend_likelihoods(PairHMMBackend::Auto), cpu_likelihoods);
+}
+
+#[test]
+fn test_gpu_backend_likelihoods_match_cpu() {
+    let cpu_likelihoods = backend_likelihoods(PairHMMBackend::Avx);
+    for (gpu, cpu) in backend_likelihoods(PairHMMBackend::Gpu)
+        .into_iter()
+        .zip(cpu_likelihoods)
+    {
+        assert!((gpu - cpu).abs() < 1e-6, "GPU {} CPU {}", gpu, cpu);
+    }
+}
+
+#[test]
+fn test_gpu_backend_with_disable_avx() {
+    let command = clap::Command::new("call")
+        .arg(
+            clap::Arg::new("disable-avx")
+                .long("disable-avx")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            clap::Arg::new("pairhmm-backend")
+                .long("pairhmm-backend")
+                .value_parser(["auto", "avx", "gpu"])
+                .default_value("auto"),
+        );
+    let args = |extra: &[&str]| {
+        let mut args = vec!["call"];
+        args.extend_from_slice(extra);
+        command.clone().try_get_matches_from(args).unwrap()
+    };
+
+    assert!(PairHMMBackend::check_args(&args(&["--pairhmm-backend", "gpu"])).is_ok());
+    assert_eq!(
+        PairHMMBackend::from_args(&args(&["--pairhmm-backend", "gpu"])),
+        PairHMMBackend::Gpu
+    );
+    // the GPU is not silently dropped when the CPU PairHMM is forced
+    assert!(
+        PairHMMBackend::check_args(&args(&["--pairhmm-backend", "gpu", "--disable-avx"])).is_err()
+    );
+    assert!(PairHMMBackend::check_args(&args(&["--disable-avx"])).is_ok());
+    assert_eq!(
+        PairHMMBackend::from_args(&args(&["--disable-avx"])),
+        PairHMMBackend::Avx
+    );
+}
+
+#[cfg(feature = "gpu")]
+#[test]
+fn test_gpu_pair_hmm_matches_cpu() {
+    let gpu_pair_hmm = match GpuPairHMM::new() {
+        Ok(gpu_pair_hmm) => gpu_pair_hmm,
+        // the feature can be built on machines without an OpenCL device
+        Err(_) => return,
+    };
+
+    let haplotypes: Vec<&[u8]> = vec![
+        &b"ACGTTGCAAGTCCTAGGATCCAGTTACGGA"[..],
+        &b"ACGTTGCAAGTCCTTGGATCCAGTTACGGA"[..],
+        &b"ACGTTGCAAGTCGGATCCAGTTACGGA"[..],
+    ];
+    let reads: Vec<(&[u8], u8)> = vec![
+        (&b"GCAAGTCCTAGGATCC"[..], 30),
+        (&b"GCAAGTCCTTGGATCCAG"[..], 20),
+        (&b"TTGCAAGTCGGATCCAGTTA"[..], 40),
+        (&b"ACGT"[..], 10),
+    ];
+
+    let model = PairHMMModel::new();
+    let mut batch = GpuPairHMMBatch::new(&haplotypes);
+    for (read_bases, qual) in reads.iter() {
+        batch.add_read(
+            &model,
+            read_bases,
+            &vec![*qual; read_bases.len()],
+            &vec![45; read_bases.len()],
+            &vec![45; read_bases.len()],
+            &vec![10; read_bases.len()],
+        );
+    }
+    let gpu_likelihoods = gpu_pair_hmm.compute_batch(&batch).unwrap();
+    assert_eq!(gpu_likelihoods.len(), reads.len() * haplotypes.len());
+
+    let max_read_length = reads.iter().map(|(bases, _)| bases.len()).max().unwrap();
+    let max_haplotype_length = haplotypes.iter().map(|bases| bases.len()).max().unwrap();
+    // results are read major, with one value per haplotype for each read
+    for (read_index, (read_bases, qual)) in reads.iter().enumerate() {
+        for (haplotype_index, haplotype_bases) in haplotypes.iter().enumerate() {
+            let mut cpu_pair_hmm = PairHMM::quick_initialize(max_read_length, max_haplotype_length);
+            let cpu_likelihood = cpu_pair_hmm.compute_read_likelihood_given_haplotype_log10(
+                haplotype_bases,
+                read_bases,
+                &vec![*qual; read_bases.len()],
+                &vec![45; read_bases.len()],
+                &vec![45; read_bases.len()],
+                &vec![10; read_bases.len()],
+                true,
+                None,
+            );
+            let gpu_likelihood = gpu_likelihoods[read_index * haplotypes.len() + haplotype_index];
+            assert!(
+                (cpu_likelihood - gpu_likelihood).abs() < 1e-6,
+                "read {} haplotype {}: CPU {} GPU {}",
+                read_index,
+                haplotype_index,
+                cpu_likelihood,
+                gpu_likelihood
+            );
+        }
+    }
+}
@@ -0,0 +1,66 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::graphs::base_edge::{BaseEdge, BaseEdgeStruct};
+use lorikeet_genome::graphs::graph_dump::{GraphDump, GraphSummary};
+use lorikeet_genome::graphs::seq_graph::SeqGraph;
+use lorikeet_genome::graphs::seq_vertex::SeqVertex;
+use lorikeet_genome::utils::simple_interval::SimpleInterval;
+
+#[test]
+fn test_parse_dump_intervals() {
+    assert_eq!(GraphDump::parse_interval("100-200").unwrap(), (100, 200));
+    assert!(GraphDump::parse_interval("200-100").is_err());
+    assert!(GraphDump::parse_interval("100").is_err());
+    assert!(GraphDump::parse_interval("a-b").is_err());
+
+    let graph_dump = GraphDump::new("graphs", vec![(100, 200)]);
+    assert!(graph_dump.should_dump(&SimpleInterval::new(0, 150, 300)));
+    assert!(graph_dump.should_dump(&SimpleInterval::new(3, 50, 100)));
+    assert!(!graph_dump.should_dump(&SimpleInterval::new(0, 201, 300)));
+    assert!(GraphDump::new("graphs", Vec::new()).should_dump(&SimpleInterval::new(0, 0, 10)));
+}
+
+#[test]
+fn test_write_and_summarise_gfa() {
+    // ref: ACT -> G -> TTA, alt bubble: ACT -> C -> TTA
+    let mut graph = SeqGraph::new(11);
+    let source = SeqVertex::new(b"ACT".to_vec());
+    let ref_middle = SeqVertex::new(b"G".to_vec());
+    let alt_middle = SeqVertex::new(b"C".to_vec());
+    let sink = SeqVertex::new(b"TTA".to_vec());
+    let ref_path = graph
+        .base_graph
+        .add_vertices(vec![&source, &ref_middle, &sink]);
+    let alt = graph.base_graph.add_node(&alt_middle);
+    graph.base_graph.add_edges(
+        ref_path[0],
+        vec![ref_path[1], ref_path[2]],
+        BaseEdgeStruct::new(true, 10, 0),
+    );
+    graph
+        .base_graph
+        .add_edges(ref_path[0], vec![alt, ref_path[2]], BaseEdgeStruct::new(false, 4, 0));
+
+    let directory = tempfile::tempdir().unwrap();
+    let location = SimpleInterval::new(2, 100, 250);
+    let graph_dump = GraphDump::new(directory.path().to_str().unwrap(), Vec::new());
+    graph_dump.dump(&graph.base_graph, &location, "seq_graph", 0);
+
+    let path = graph_dump.gfa_path(&location, 11, "seq_graph");
+    assert!(path.ends_with("2_100-250.k11.seq_graph.gfa"));
+    let summary = GraphSummary::from_gfa(&path).unwrap();
+    assert_eq!(summary.region, "2:100-250");
+    assert_eq!(summary.kmer_size, 11);
+    assert_eq!(summary.graph_type, "seq_graph");
+    assert_eq!(summary.segments, 4);
+    assert_eq!(summary.links, 4);
+    assert_eq!(summary.reference_links, 2);
+    assert_eq!(summary.sources, 1);
+    assert_eq!(summary.sinks, 1);
+    assert_eq!(summary.total_sequence_length, 8);
+    assert_eq!(summary.max_multiplicity, 10);
+    assert_eq!(summary.mean_multiplicity, 7.0);
+}
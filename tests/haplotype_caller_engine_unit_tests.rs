@@ -6,6 +6,7 @@
 #[macro_use]
 extern crate approx;
 
+use indicatif::ProgressBar;
 use lorikeet_genome::haplotype::haplotype_caller_engine::HaplotypeCallerEngine;
 use lorikeet_genome::utils::quality_utils::QualityUtils;
 
@@ -37,3 +38,30 @@ fn get_leading_order_data() {
     test_leading_order_in_error_rate(1000, 2, 0.00001);
     test_leading_order_in_error_rate(10000, 2, 0.000001);
 }
+
+#[test]
+fn test_progress_in_profiled_bases() {
+    let target_lens = vec![1000, 50, 2600];
+    let min_contig_length = 100;
+    let chunk_size = 500;
+
+    // contigs too short to be profiled are left out of the bar
+    let genome_bases =
+        HaplotypeCallerEngine::profiled_bases(target_lens.clone(), min_contig_length);
+    assert_eq!(genome_bases, 3600);
+
+    let progress_bar = ProgressBar::hidden();
+    progress_bar.set_style(HaplotypeCallerEngine::profile_progress_style());
+    progress_bar.set_length(genome_bases);
+    // every chunk of a profiled contig adds its bases, including the shorter last chunk
+    for target_len in target_lens
+        .into_iter()
+        .filter(|target_len| *target_len >= min_contig_length)
+    {
+        let positions = (0..target_len as usize).collect::<Vec<usize>>();
+        for chunk in positions.chunks(chunk_size) {
+            progress_bar.inc(chunk.len() as u64);
+        }
+    }
+    assert_eq!(progress_bar.position(), progress_bar.length().unwrap());
+}
@@ -0,0 +1,51 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use hashlink::LinkedHashSet;
+use lorikeet_genome::haplotype::haplotype_clustering_engine::HaplotypeClusteringEngine;
+use std::collections::{HashMap, HashSet};
+
+fn strain(groups: &[i32]) -> LinkedHashSet<i32> {
+    groups.iter().copied().collect()
+}
+
+fn variants_per_group() -> HashMap<i32, HashSet<usize>> {
+    let mut variants_per_group = HashMap::new();
+    variants_per_group.insert(0, (0..10).collect::<HashSet<usize>>());
+    variants_per_group.insert(1, (10..11).collect::<HashSet<usize>>());
+    variants_per_group.insert(2, (11..100).collect::<HashSet<usize>>());
+    variants_per_group
+}
+
+#[test]
+fn test_near_identical_strains_are_merged() {
+    // strain 0 and 1 differ by a single variant, strain 2 differs by 89 or 90 variants
+    let potential_strains = vec![strain(&[0]), strain(&[0, 1]), strain(&[2])];
+
+    let merged = HaplotypeClusteringEngine::merge_similar_strains(
+        potential_strains,
+        &variants_per_group(),
+        10_000,
+        0.001,
+    );
+
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0], strain(&[0, 1]));
+    assert_eq!(merged[1], strain(&[2]));
+}
+
+#[test]
+fn test_divergent_strains_are_kept() {
+    let potential_strains = vec![strain(&[0]), strain(&[0, 1]), strain(&[2])];
+
+    let merged = HaplotypeClusteringEngine::merge_similar_strains(
+        potential_strains.clone(),
+        &variants_per_group(),
+        10_000,
+        0.0001,
+    );
+
+    assert_eq!(merged, potential_strains);
+}
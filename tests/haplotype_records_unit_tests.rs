@@ -0,0 +1,214 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::haplotype::haplotype::Haplotype;
+use lorikeet_genome::haplotype::haplotype_records::{HaplotypeRecords, SymbolicHaplotypeRecords};
+use lorikeet_genome::model::allele_likelihoods::AlleleLikelihoods;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use std::collections::HashMap;
+
+const REF_BASES: &[u8] = b"ACGTACGTAC";
+const ALT_BASES: &[u8] = b"ACGTTCGTAC";
+// an assembled haplotype that no read supports
+const UNSUPPORTED_BASES: &[u8] = b"ACGTACCTAC";
+
+fn read(name: &str, sample_index: usize) -> BirdToolRead {
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::Match(10)]);
+    record.set(name.as_bytes(), Some(&cigar), REF_BASES, &[30; 10]);
+    record.set_pos(100);
+    record.set_mapq(60);
+    BirdToolRead::new(record, sample_index, ReadType::Short)
+}
+
+fn haplotype(bases: &[u8], is_ref: bool) -> Haplotype<SimpleInterval> {
+    let mut haplotype = Haplotype::new(bases, is_ref);
+    haplotype.set_genome_location(SimpleInterval::new(0, 100, 109));
+    haplotype
+}
+
+/// Likelihoods of reads that each clearly support the haplotype given for them, for the
+/// reference, alternate and unsupported haplotypes in that order
+fn likelihoods(
+    reads_by_sample: Vec<Vec<(&str, usize)>>,
+) -> AlleleLikelihoods<Haplotype<SimpleInterval>> {
+    let haplotypes = vec![
+        haplotype(REF_BASES, true),
+        haplotype(ALT_BASES, false),
+        haplotype(UNSUPPORTED_BASES, false),
+    ];
+    let samples = (0..reads_by_sample.len()).collect::<Vec<usize>>();
+    let mut evidence_by_sample = HashMap::new();
+    let mut supported_haplotypes = Vec::new();
+    for (sample_index, reads) in reads_by_sample.into_iter().enumerate() {
+        let (reads, haplotypes): (Vec<BirdToolRead>, Vec<usize>) = reads
+            .into_iter()
+            .map(|(name, haplotype_index)| (read(name, sample_index), haplotype_index))
+            .unzip();
+        evidence_by_sample.insert(sample_index, reads);
+        supported_haplotypes.push((sample_index, haplotypes));
+    }
+
+    let mut likelihoods = AlleleLikelihoods::new(haplotypes, samples, evidence_by_sample);
+    for (sample_index, haplotypes) in supported_haplotypes {
+        let matrix = likelihoods.sample_matrix(sample_index);
+        for (evidence_index, haplotype_index) in haplotypes.into_iter().enumerate() {
+            for other in 0..3 {
+                matrix[[other, evidence_index]] = if other == haplotype_index {
+                    -1.0
+                } else {
+                    -10.0
+                };
+            }
+        }
+    }
+    likelihoods
+}
+
+#[test]
+fn test_haplotype_records() {
+    let likelihoods = likelihoods(vec![
+        vec![("ref_1", 0), ("alt_1", 1), ("alt_2", 1), ("alt_3", 1)],
+        vec![("ref_2", 0), ("ref_3", 0)],
+    ]);
+    let records = HaplotypeRecords::from_likelihoods(&likelihoods);
+
+    // the unsupported haplotype is not emitted
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.loc, SimpleInterval::new(0, 100, 109));
+    assert_eq!(
+        record.get_alleles(),
+        &vec![
+            ByteArrayAllele::new(REF_BASES, true),
+            ByteArrayAllele::new(ALT_BASES, false),
+        ]
+    );
+
+    let genotypes = record.genotypes.genotypes();
+    assert_eq!(genotypes.len(), 2);
+    // the first sample carries the alternate haplotype
+    assert_eq!(
+        genotypes[0].alleles,
+        vec![ByteArrayAllele::new(ALT_BASES, false)]
+    );
+    assert_eq!(genotypes[0].ad, vec![1, 3]);
+    assert_eq!(genotypes[0].dp, 4);
+    assert_eq!(genotypes[0].pl, vec![180, 0]);
+    assert_eq!(genotypes[0].gq, 99);
+    // and the second the reference haplotype
+    assert_eq!(
+        genotypes[1].alleles,
+        vec![ByteArrayAllele::new(REF_BASES, true)]
+    );
+    assert_eq!(genotypes[1].ad, vec![2, 0]);
+    assert_eq!(genotypes[1].dp, 2);
+    assert_eq!(genotypes[1].pl, vec![0, 180]);
+    assert_eq!(genotypes[1].gq, 99);
+}
+
+#[test]
+fn test_haplotype_records_without_alternate_support() {
+    let likelihoods = likelihoods(vec![vec![("ref_1", 0), ("ref_2", 0)]]);
+    assert!(HaplotypeRecords::from_likelihoods(&likelihoods).is_empty());
+}
+
+#[test]
+fn test_supporting_reads() {
+    let likelihoods = likelihoods(vec![vec![("ref_1", 0), ("alt_1", 1), ("alt_2", 1)]]);
+    let best_alleles = likelihoods.best_alleles_breaking_ties_for_sample(0);
+    assert_eq!(
+        HaplotypeRecords::supporting_reads(&best_alleles, 3),
+        vec![1, 2, 0]
+    );
+}
+
+#[test]
+fn test_symbolic_haplotype_records() {
+    let likelihoods = likelihoods(vec![
+        vec![("ref_1", 0), ("alt_1", 1), ("alt_2", 1), ("alt_3", 1)],
+        vec![("ref_2", 0), ("ref_3", 0)],
+    ]);
+    let records = HaplotypeRecords::symbolic_from_likelihoods(&likelihoods, 30.0);
+
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    // the record is anchored on the first reference base and spans the reference haplotype
+    assert_eq!(record.loc, SimpleInterval::new(0, 100, 109));
+    assert_eq!(
+        record.get_alleles(),
+        &vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(SymbolicHaplotypeRecords::SYMBOLIC_ALLELE, false),
+        ]
+    );
+    // QUAL sums the evidence of every sample for the haplotype over the reference
+    assert_eq!(record.get_phred_scaled_qual(), 180.0);
+    assert_eq!(
+        record.attributes.get(VariantAnnotations::End.to_key()),
+        Some(&AttributeObject::I32(110))
+    );
+    assert_eq!(
+        record
+            .attributes
+            .get(VariantAnnotations::HaplotypeSequence.to_key()),
+        Some(&AttributeObject::String("ACGTTCGTAC".to_string()))
+    );
+
+    let genotypes = record.genotypes.genotypes();
+    assert_eq!(
+        genotypes[0].alleles,
+        vec![ByteArrayAllele::new(
+            SymbolicHaplotypeRecords::SYMBOLIC_ALLELE,
+            false
+        )]
+    );
+    assert_eq!(genotypes[0].ad, vec![1, 3]);
+    assert_eq!(genotypes[1].alleles, vec![ByteArrayAllele::new(b"A", true)]);
+    assert_eq!(genotypes[1].ad, vec![2, 0]);
+
+    // haplotypes below the minimum QUAL are not emitted
+    assert!(HaplotypeRecords::symbolic_from_likelihoods(&likelihoods, 200.0).is_empty());
+}
+
+#[test]
+fn test_merge_symbolic_haplotype_records() {
+    let likelihoods = likelihoods(vec![vec![("ref_1", 0), ("alt_1", 1), ("alt_2", 1)]]);
+    let symbolic_records = SymbolicHaplotypeRecords::new(30.0);
+    // clones share the records of the run
+    symbolic_records.clone().record(&likelihoods);
+    assert_eq!(symbolic_records.len(), 1);
+
+    let site = |position: usize| {
+        VariantContext::build(
+            0,
+            position,
+            position,
+            vec![
+                ByteArrayAllele::new(b"A", true),
+                ByteArrayAllele::new(b"T", false),
+            ],
+        )
+    };
+    let merged = symbolic_records.merge(&[site(104), site(50)]);
+    assert_eq!(
+        merged
+            .iter()
+            .map(|vc| vc.loc.get_start())
+            .collect::<Vec<usize>>(),
+        vec![50, 100, 104]
+    );
+    assert_eq!(
+        merged[1].get_alleles()[1],
+        ByteArrayAllele::new(SymbolicHaplotypeRecords::SYMBOLIC_ALLELE, false)
+    );
+}
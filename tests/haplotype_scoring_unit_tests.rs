@@ -0,0 +1,73 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::haplotype::haplotype_scoring::HaplotypeScoringModel;
+use ndarray::{arr2, Array2};
+
+/// Likelihoods of three alleles for the reads of two samples. The second sample's matrix has a
+/// column beyond its single read
+fn sample_matrices() -> Vec<Array2<f64>> {
+    vec![
+        arr2(&[[-1.0, -5.0, -2.0], [-1.5, -1.0, -9.0], [-9.0, -9.0, -2.0]]),
+        arr2(&[[-3.0, 0.0], [-1.0, -100.0], [-1.0, -100.0]]),
+    ]
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(
+        HaplotypeScoringModel::from_name("read-support"),
+        Some(HaplotypeScoringModel::ReadSupport)
+    );
+    assert_eq!(
+        HaplotypeScoringModel::from_name("sample-likelihood"),
+        Some(HaplotypeScoringModel::SampleLikelihood)
+    );
+    assert_eq!(HaplotypeScoringModel::from_name("unknown"), None);
+}
+
+#[test]
+fn test_read_support_scores() {
+    // reads support every allele within the threshold of their best likelihood, and columns
+    // beyond the evidence count of a sample are ignored
+    assert_eq!(
+        HaplotypeScoringModel::read_support_scores(&sample_matrices(), &[3, 1], 1.0),
+        vec![2.0, 3.0, 2.0]
+    );
+    assert_eq!(
+        HaplotypeScoringModel::read_support_scores(&sample_matrices(), &[3, 1], 0.1),
+        vec![2.0, 2.0, 2.0]
+    );
+
+    // reads without a finite likelihood support no allele
+    let matrices = vec![arr2(&[[f64::NEG_INFINITY], [f64::NEG_INFINITY]])];
+    assert_eq!(
+        HaplotypeScoringModel::read_support_scores(&matrices, &[1], 1.0),
+        vec![0.0, 0.0]
+    );
+    assert!(HaplotypeScoringModel::read_support_scores(&[], &[], 1.0).is_empty());
+}
+
+#[test]
+fn test_sample_likelihood_scores() {
+    // the first sample's best margins are 0.5, 4 and 0, the second sample's are -2, 0 and 0
+    assert_eq!(
+        HaplotypeScoringModel::sample_likelihood_scores(&sample_matrices(), &[3, 1]),
+        vec![-1.5, 4.0, 0.0]
+    );
+
+    // a sample without reads adds nothing
+    assert_eq!(
+        HaplotypeScoringModel::sample_likelihood_scores(&sample_matrices(), &[3, 0]),
+        vec![0.5, 4.0, 0.0]
+    );
+
+    // there is no other allele to compare against with a single allele
+    let matrices = vec![arr2(&[[-1.0, -2.0]])];
+    assert_eq!(
+        HaplotypeScoringModel::sample_likelihood_scores(&matrices, &[2]),
+        vec![0.0]
+    );
+}
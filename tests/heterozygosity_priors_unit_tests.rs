@@ -0,0 +1,32 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::heterozygosity_priors::HeterozygosityPriors;
+
+#[test]
+fn test_parse_priors() {
+    let priors = HeterozygosityPriors::parse(
+        "# priors\n\
+        genome\tsnp\tindel\n\
+        phage_1\t0.01\t0.001\n\
+        /data/genomes/GCF_000005845.2.fna\t0.0001\t0.00001\n",
+    )
+    .unwrap();
+
+    assert_eq!(priors.len(), 2);
+    assert_eq!(priors.get("phage_1"), Some((0.01, 0.001)));
+    assert_eq!(priors.get("phage_1.fasta"), Some((0.01, 0.001)));
+    assert_eq!(priors.get("GCF_000005845.2"), Some((0.0001, 0.00001)));
+    assert_eq!(priors.get("GCF_000005845"), None);
+    assert_eq!(priors.get("phage_2"), None);
+}
+
+#[test]
+fn test_invalid_priors() {
+    assert!(HeterozygosityPriors::parse("phage_1\t0.01\n").is_err());
+    assert!(HeterozygosityPriors::parse("phage_1\tmany\t0.001\n").is_err());
+    assert!(HeterozygosityPriors::parse("phage_1\t0.0\t0.001\n").is_err());
+    assert!(HeterozygosityPriors::parse("phage_1\t0.01\t1.5\n").is_err());
+}
@@ -0,0 +1,67 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::hotspots::Hotspots;
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+#[test]
+fn test_parse_bed_line() {
+    assert_eq!(
+        Hotspots::parse_bed_line("contig_1\t10\t20\tgyrA_S83").unwrap(),
+        Some(("contig_1".to_string(), 10, 20))
+    );
+    assert_eq!(Hotspots::parse_bed_line("# comment").unwrap(), None);
+    assert_eq!(Hotspots::parse_bed_line("track name=panel").unwrap(), None);
+    assert_eq!(Hotspots::parse_bed_line("").unwrap(), None);
+
+    assert!(Hotspots::parse_bed_line("contig_1\t10").is_err());
+    assert!(Hotspots::parse_bed_line("contig_1\t20\t10").is_err());
+    assert!(Hotspots::parse_bed_line("contig_1\tstart\t10").is_err());
+}
+
+#[test]
+fn test_hotspots_from_bed() {
+    let mut bed = NamedTempFile::new().unwrap();
+    writeln!(bed, "track name=panel").unwrap();
+    writeln!(bed, "contig_1\t500\t501").unwrap();
+    writeln!(bed, "contig_1\t100\t110").unwrap();
+    writeln!(bed, "contig_1\t105\t120").unwrap();
+    writeln!(bed, "contig_2\t10\t11").unwrap();
+    let path = bed.path().to_str().unwrap().to_string();
+
+    let hotspots = Hotspots::from_files(&[path], 0.5).unwrap();
+    // overlapping intervals are merged
+    assert_eq!(hotspots.len(), 3);
+    let separator = GenomeSeparator::default();
+    assert_eq!(
+        hotspots.intervals_for_contig(b"contig_1", &separator),
+        &[(100, 120), (500, 501)]
+    );
+    // contigs are found with or without the genome prefix
+    assert_eq!(
+        hotspots.intervals_for_contig(b"genome~contig_2", &separator),
+        &[(10, 11)]
+    );
+    assert_eq!(
+        hotspots.intervals_for_contig(b"genome^contig_2", &GenomeSeparator::new('^')),
+        &[(10, 11)]
+    );
+    assert!(hotspots.intervals_for_contig(b"contig_3", &separator).is_empty());
+
+    let intervals = hotspots.intervals_for_contig(b"contig_1", &separator);
+    assert!(!Hotspots::contains(intervals, 99));
+    assert!(Hotspots::contains(intervals, 100));
+    assert!(Hotspots::contains(intervals, 119));
+    assert!(!Hotspots::contains(intervals, 120));
+    assert!(Hotspots::contains(intervals, 500));
+    assert!(!Hotspots::contains(intervals, 501));
+    assert!(!Hotspots::contains(&[], 0));
+
+    // activity is only ever raised
+    assert_eq!(hotspots.boost(0.01), 0.5);
+    assert_eq!(hotspots.boost(0.9), 0.9);
+}
@@ -0,0 +1,58 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::kmer::Kmer;
+
+#[test]
+fn test_rolling_kmers_match_direct_kmers() {
+    let sequence = b"ACGTTGCANNACGTAcgtGGATCCAT";
+    for length in [1, 3, 5, 8] {
+        let last_start = sequence.len() - length;
+        let rolled = Kmer::rolling(sequence, 2, last_start, length).collect::<Vec<(usize, Kmer)>>();
+        assert_eq!(rolled.len(), last_start - 1);
+        for (start, kmer) in rolled {
+            assert_eq!(
+                kmer,
+                Kmer::new_with_start_and_length(sequence, start, length)
+            );
+            assert_eq!(kmer.bases(sequence), &sequence[start..start + length]);
+        }
+    }
+}
+
+#[test]
+fn test_rolling_long_kmers() {
+    let sequence = b"ACGTTGCAACGTAGGATCCATTAGGCATCAGGATTACAGATTACAGGACCA";
+    let length = Kmer::MAX_ENCODED_LENGTH + 4;
+    for (start, kmer) in Kmer::rolling(sequence, 0, sequence.len() - length, length) {
+        assert_eq!(
+            kmer,
+            Kmer::new_with_start_and_length(sequence, start, length)
+        );
+    }
+}
+
+#[test]
+fn test_encoded_kmers_are_exact() {
+    let sequence = b"AAAACCCCAAAAGGGGAAAA";
+    let kmers = Kmer::rolling(sequence, 0, sequence.len() - 4, 4)
+        .map(|(_, kmer)| kmer)
+        .collect::<Vec<Kmer>>();
+
+    // the same bases give the same kmer wherever they are, but case is significant
+    assert_eq!(kmers[0], kmers[8]);
+    assert_eq!(kmers[0], kmers[16]);
+    assert_eq!(kmers[0], Kmer::new(b"AAAA"));
+    assert_ne!(kmers[0], Kmer::new(b"aaaa"));
+    assert_ne!(Kmer::new(b"AAAA"), Kmer::new(b"AaAA"));
+    assert_eq!(Kmer::new(b"aaaa"), Kmer::new(b"aaaa"));
+    assert_ne!(kmers[0], kmers[4]);
+    assert_ne!(kmers[4], kmers[12]);
+    // kmers of different lengths never match, even when their encodings do
+    assert_ne!(Kmer::new(b"AAA"), Kmer::new(b"AAAA"));
+    // kmers with ambiguous bases are compared by hash
+    assert_eq!(Kmer::new(b"ANA"), Kmer::new(b"ANA"));
+    assert_ne!(Kmer::new(b"ANA"), Kmer::new(b"ANC"));
+}
@@ -0,0 +1,161 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use hashlink::{LinkedHashMap, LinkedHashSet};
+use lorikeet_genome::linkage::linkage_engine::LinkageEngine;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use ndarray::Array2;
+use std::collections::{HashMap, HashSet};
+
+fn variant(position: usize) -> VariantContext {
+    VariantContext::build(
+        0,
+        position,
+        position,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+        ],
+    )
+}
+
+fn reads(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn strain(groups: &[i32]) -> LinkedHashSet<i32> {
+    groups.iter().copied().collect()
+}
+
+// group 1 has one variant, group 2 two variants and group 3 one variant. Group 2 shares three
+// reads with group 1, group 3 shares one read with group 2
+fn test_contexts() -> Vec<VariantContext> {
+    vec![variant(10), variant(20), variant(30), variant(40)]
+}
+
+fn test_engine<'a>(
+    contexts: &'a [VariantContext],
+    cluster_separations: &'a Array2<f64>,
+    previous_groups: &'a HashMap<i32, i32>,
+    exclusive_groups: &'a HashMap<i32, HashSet<i32>>,
+) -> LinkageEngine<'a> {
+    let mut grouped_contexts = LinkedHashMap::new();
+    grouped_contexts.insert(1, vec![&contexts[0]]);
+    grouped_contexts.insert(2, vec![&contexts[1], &contexts[2]]);
+    grouped_contexts.insert(3, vec![&contexts[3]]);
+
+    let mut variant_reads = LinkedHashMap::new();
+    variant_reads.insert(1, vec![reads(&["0_r1", "0_r2", "1_r3"])]);
+    variant_reads.insert(
+        2,
+        vec![reads(&["0_r1", "0_r2", "0_r4"]), reads(&["1_r3", "0_r5"])],
+    );
+    variant_reads.insert(3, vec![reads(&["0_r5", "0_r6"])]);
+
+    let mut engine = LinkageEngine::new(
+        grouped_contexts,
+        cluster_separations,
+        previous_groups,
+        exclusive_groups,
+    );
+    engine.set_variant_reads(variant_reads);
+    engine
+}
+
+#[test]
+fn test_merge_linked_strains_threshold() {
+    let contexts = test_contexts();
+    let cluster_separations = Array2::zeros((3, 3));
+    let previous_groups = HashMap::new();
+    let exclusive_groups = HashMap::new();
+    let engine = test_engine(
+        &contexts,
+        &cluster_separations,
+        &previous_groups,
+        &exclusive_groups,
+    );
+    let strains = vec![strain(&[1]), strain(&[2]), strain(&[3])];
+
+    // groups 1 and 2 share exactly three reads
+    assert_eq!(
+        engine.merge_linked_strains(strains.clone(), 3),
+        vec![strain(&[1, 2]), strain(&[3])]
+    );
+    assert_eq!(
+        engine.merge_linked_strains(strains.clone(), 4),
+        strains.clone()
+    );
+    // once merged, group 3 links to the merged strain through group 2
+    assert_eq!(
+        engine.merge_linked_strains(strains, 1),
+        vec![strain(&[1, 2, 3])]
+    );
+
+    // reads of groups shared by both strains do not link them
+    let overlapping = vec![strain(&[1, 2]), strain(&[2, 3])];
+    assert_eq!(
+        engine.merge_linked_strains(overlapping.clone(), 1),
+        overlapping
+    );
+}
+
+#[test]
+fn test_merge_linked_strains_exclusion() {
+    let contexts = test_contexts();
+    let cluster_separations = Array2::zeros((3, 3));
+    let previous_groups = HashMap::new();
+    let mut exclusive_groups = HashMap::new();
+    exclusive_groups.insert(2, [1].iter().copied().collect::<HashSet<i32>>());
+    let engine = test_engine(
+        &contexts,
+        &cluster_separations,
+        &previous_groups,
+        &exclusive_groups,
+    );
+
+    // exclusion is checked in both directions, so group 2 can only join group 3
+    assert_eq!(
+        engine.merge_linked_strains(vec![strain(&[1]), strain(&[2]), strain(&[3])], 1),
+        vec![strain(&[1]), strain(&[2, 3])]
+    );
+    assert_eq!(
+        engine.merge_linked_strains(vec![strain(&[2]), strain(&[1]), strain(&[3])], 1),
+        vec![strain(&[2, 3]), strain(&[1])]
+    );
+}
+
+#[test]
+fn test_linked_read_counts_follow_grouped_contexts() {
+    let contexts = test_contexts();
+    let cluster_separations = Array2::zeros((3, 3));
+    let previous_groups = HashMap::new();
+    let exclusive_groups = HashMap::new();
+    let engine = test_engine(
+        &contexts,
+        &cluster_separations,
+        &previous_groups,
+        &exclusive_groups,
+    );
+
+    let counts = engine.linked_read_counts(&[strain(&[1, 2]), strain(&[3])]);
+    // one count per variant, in the order of the variants in each group
+    assert_eq!(counts.keys().copied().collect::<Vec<i32>>(), vec![1, 2, 3]);
+    assert_eq!(counts[&1], vec![3]);
+    assert_eq!(counts[&2], vec![2, 1]);
+    // a strain of a single group has nothing to link to
+    assert_eq!(counts[&3], vec![0]);
+
+    // a group in several strains links to the groups of all of them
+    let counts = engine.linked_read_counts(&[strain(&[1, 2]), strain(&[2, 3])]);
+    assert_eq!(counts[&2], vec![2, 2]);
+    assert_eq!(counts[&3], vec![1]);
+
+    let grouped_contexts = engine.retrieve_grouped_contexts();
+    for (group, group_counts) in counts.iter() {
+        assert_eq!(grouped_contexts[group].len(), group_counts.len());
+    }
+    assert_eq!(grouped_contexts[&2][1].loc.start, 30);
+}
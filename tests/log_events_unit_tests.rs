@@ -0,0 +1,88 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use indicatif::ProgressBar;
+use log::LevelFilter;
+use lorikeet_genome::processing::lorikeet_engine::Elem;
+use lorikeet_genome::utils::log_events::LogEvents;
+use serde_json::Value;
+use std::fs::read_to_string;
+
+#[test]
+fn test_format_event() {
+    let line = LogEvents::format_event(
+        12.5,
+        "log",
+        &[
+            ("level", Some("INFO")),
+            ("message", Some("quoted \"genome\": a\\b\nnext line\t\u{1}")),
+            ("signal", None),
+        ],
+    );
+    // one event per line, with the fields in the order they were given
+    assert!(!line.contains('\n'));
+    assert!(line.starts_with("{\"timestamp\":12.5,\"event\":\"log\",\"level\":\"INFO\""));
+
+    let event: Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(event["timestamp"], 12.5);
+    assert_eq!(event["event"], "log");
+    assert_eq!(
+        event["message"],
+        "quoted \"genome\": a\\b\nnext line\t\u{1}"
+    );
+    assert_eq!(event["signal"], Value::Null);
+}
+
+#[test]
+fn test_stage_events() {
+    let dir = tempfile::tempdir().unwrap();
+    let log_file = dir.path().join("events.jsonl");
+    LogEvents::init_json(LevelFilter::Off, None, log_file.to_str()).unwrap();
+    assert!(LogEvents::json_enabled());
+
+    let elem = Elem {
+        key: "genome_1".to_string(),
+        index: 0,
+        progress_bar: ProgressBar::hidden(),
+    };
+    // stages are given explicitly, so they can contain the separator of the bar message
+    elem.set_stage("Calling variants: pass 1");
+    elem.set_stage("Writing strains");
+    // messages that are not stages do not start one
+    elem.set_message("genome_1: All steps completed".to_string());
+    elem.finish_and_clear();
+
+    let events = read_to_string(&log_file)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<Value>(line).unwrap())
+        .collect::<Vec<Value>>();
+    let stages = events
+        .iter()
+        .map(|event| {
+            (
+                event["event"].as_str().unwrap().to_string(),
+                event["stage"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect::<Vec<(String, String)>>();
+    assert_eq!(
+        stages,
+        vec![
+            (
+                "stage_start".to_string(),
+                "Calling variants: pass 1".to_string()
+            ),
+            (
+                "stage_end".to_string(),
+                "Calling variants: pass 1".to_string()
+            ),
+            ("stage_start".to_string(), "Writing strains".to_string()),
+            ("stage_end".to_string(), "Writing strains".to_string()),
+        ]
+    );
+    assert!(events.iter().all(|event| event["genome"] == "genome_1"));
+    assert!(events[1]["elapsed_seconds"].is_string());
+}
@@ -0,0 +1,162 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use bio_types::strand::Strand;
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::evolve::codon_structs::{CodonTable, Translations};
+use lorikeet_genome::evolve::marker_summary::{
+    MarkerCatalog, MarkerEntry, MarkerGene, MarkerMutation,
+};
+use lorikeet_genome::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+
+fn snv(pos: usize, ref_base: &[u8], alt_base: &[u8], depths: &[[i32; 2]]) -> VariantContext {
+    let ref_allele = ByteArrayAllele::new(ref_base, true);
+    let alt_allele = ByteArrayAllele::new(alt_base, false);
+    let mut vc = VariantContext::build(0, pos, pos, vec![ref_allele.clone(), alt_allele]);
+    vc.genotypes = GenotypesContext::new(
+        depths
+            .iter()
+            .enumerate()
+            .map(|(sample_index, ad)| {
+                let mut genotype =
+                    Genotype::build_from_alleles(vec![ref_allele.clone()], sample_index);
+                genotype.ad = ad.to_vec();
+                genotype
+            })
+            .collect(),
+    );
+    vc
+}
+
+fn codon_table() -> CodonTable {
+    let mut codon_table = CodonTable::setup();
+    codon_table.get_codon_table(11);
+    codon_table
+}
+
+fn entry(gene: &str, mutation: &str) -> MarkerEntry {
+    MarkerEntry {
+        gene: gene.to_string(),
+        label: mutation.to_string(),
+        mutation: MarkerMutation::parse(mutation).unwrap(),
+    }
+}
+
+#[test]
+fn test_parse_marker_mutations() {
+    assert_eq!(
+        MarkerMutation::parse("S450L").unwrap(),
+        MarkerMutation::Protein {
+            ref_aa: 'S',
+            codon: 450,
+            alt_aa: 'L'
+        }
+    );
+    assert_eq!(
+        MarkerMutation::parse("p.W88*").unwrap(),
+        MarkerMutation::Protein {
+            ref_aa: 'W',
+            codon: 88,
+            alt_aa: '*'
+        }
+    );
+    assert_eq!(
+        MarkerMutation::parse("c.1349C>T").unwrap(),
+        MarkerMutation::Nucleotide {
+            position: 1349,
+            ref_base: b'C',
+            alt_base: b'T'
+        }
+    );
+    assert!(MarkerMutation::parse("S0L").is_err());
+    assert!(MarkerMutation::parse("SL").is_err());
+    assert!(MarkerMutation::parse("c.12C>").is_err());
+    assert!(MarkerMutation::parse("c.12C>Z").is_err());
+}
+
+#[test]
+fn test_marker_amino_acid_change() {
+    // ATG TCA AAA TAA starting at position 2, codon 2 encodes serine
+    let reference = b"CCATGTCAAAATAA";
+    let gene = MarkerGene {
+        tid: 0,
+        contig: "contig_1".to_string(),
+        start: 2,
+        end: 13,
+        strand: Strand::Forward,
+        frame: 0,
+    };
+
+    // TCA -> TTA is leucine, TCA -> TGA is a stop codon
+    let mut leucine = snv(6, b"C", b"T", &[[6, 4], [10, 0], [1, 1]]);
+    leucine.set_attribute(
+        VariantAnnotations::Strain.to_key().to_string(),
+        AttributeObject::VecUnsize(vec![1]),
+    );
+    let stop = snv(6, b"C", b"G", &[[5, 5], [5, 5], [5, 5]]);
+    let contexts = vec![leucine, stop];
+
+    let call = MarkerCatalog::evaluate(
+        &entry("geneA", "S2L"),
+        &gene,
+        reference,
+        &contexts,
+        &codon_table(),
+        3,
+        5,
+        &[0, 1],
+    )
+    .unwrap();
+    assert_eq!(call.position, 6);
+    assert_eq!(call.reference, "TCA");
+    assert_eq!(call.sample_frequencies, vec![Some(0.4), Some(0.0), None]);
+    assert_eq!(call.samples_present(), 1);
+    assert_eq!(call.strains, vec![false, true]);
+
+    // codons past the end of the gene can not be evaluated
+    assert!(MarkerCatalog::evaluate(
+        &entry("geneA", "K10R"),
+        &gene,
+        reference,
+        &contexts,
+        &codon_table(),
+        3,
+        5,
+        &[],
+    )
+    .is_none());
+}
+
+#[test]
+fn test_marker_reverse_strand() {
+    // ATGTAA on the reverse strand
+    let reference = b"TTACAT";
+    let gene = MarkerGene {
+        tid: 0,
+        contig: "contig_1".to_string(),
+        start: 0,
+        end: 5,
+        strand: Strand::Reverse,
+        frame: 0,
+    };
+    let contexts = vec![snv(4, b"A", b"G", &[[2, 8]])];
+
+    let call = MarkerCatalog::evaluate(
+        &entry("geneB", "c.2T>C"),
+        &gene,
+        reference,
+        &contexts,
+        &codon_table(),
+        1,
+        5,
+        &[],
+    )
+    .unwrap();
+    assert_eq!(call.position, 5);
+    assert_eq!(call.reference, "T");
+    assert_eq!(call.sample_frequencies, vec![Some(0.8)]);
+}
@@ -0,0 +1,38 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::minimizer_filter::MinimizerFilter;
+
+fn pseudo_random_sequence(length: usize, seed: u64) -> Vec<u8> {
+    let mut state = seed;
+    (0..length)
+        .map(|_| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            b"ACGT"[(state >> 62) as usize]
+        })
+        .collect()
+}
+
+#[test]
+fn test_minimizer_shared_fraction() {
+    let filter = MinimizerFilter::new(15, 10, 0.05);
+    let reference = pseudo_random_sequence(2000, 1);
+    let reference_sketch = filter.sketch(&reference);
+    assert!(!reference_sketch.is_empty());
+
+    // a read taken from the reference shares all of its minimizers
+    let read = &reference[500..1100];
+    assert_eq!(filter.shared_fraction(&reference_sketch, read), Some(1.0));
+
+    // a read from elsewhere shares almost none
+    let unrelated = pseudo_random_sequence(600, 2);
+    assert!(filter.shared_fraction(&reference_sketch, &unrelated).unwrap() < 0.05);
+
+    // reads too short for a full window of kmers and kmers containing Ns have no minimizers
+    assert_eq!(filter.shared_fraction(&reference_sketch, &reference[0..20]), None);
+    assert!(filter.sketch(&vec![b'N'; 100]).is_empty());
+}
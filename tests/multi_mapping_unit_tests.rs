@@ -0,0 +1,143 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+#[macro_use]
+extern crate approx;
+
+use lorikeet_genome::bam_parsing::bam_generator::generate_named_bam_readers_from_bam_files;
+use lorikeet_genome::processing::bams::index_bams::finish_bams;
+use lorikeet_genome::processing::bams::multi_mapping::{MultiMappingEm, MultiMappingReassignment};
+use lorikeet_genome::reference::reference_reader_utils::GenomesAndContigs;
+use rust_htslib::bam::record::{Cigar, CigarString};
+use rust_htslib::bam::{self, Read, Record};
+
+#[test]
+fn test_multi_mapping_em() {
+    let mut em = MultiMappingEm::new(3);
+    for _ in 0..90 {
+        em.add_unique(0);
+    }
+    for _ in 0..10 {
+        em.add_unique(1);
+    }
+    for _ in 0..100 {
+        em.add_multi(vec![1, 0]);
+    }
+    // a read mapping twice to the same genome is unique to it
+    em.add_multi(vec![2, 2]);
+
+    let abundances = em.estimate(1000, 1e-9);
+    assert_abs_diff_eq!(abundances.iter().sum::<f64>(), 1.0, epsilon = 1e-6);
+    assert_abs_diff_eq!(abundances[0], 180.0 / 201.0, epsilon = 1e-3);
+
+    let weights = MultiMappingEm::weights(&abundances, &[0, 1]);
+    assert_abs_diff_eq!(weights[0], 0.9, epsilon = 1e-3);
+    assert_abs_diff_eq!(weights[1], 0.1, epsilon = 1e-3);
+
+    // genomes without any abundance share reads evenly
+    assert_eq!(MultiMappingEm::weights(&[0.0, 0.0], &[0, 1]), vec![0.5, 0.5]);
+}
+
+fn alignment(name: &str, tid: i32, pos: i64, mapq: u8, secondary: bool) -> Record {
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::Match(10)]);
+    record.set(name.as_bytes(), Some(&cigar), b"ACGTACGTAC", &[30; 10]);
+    record.set_tid(tid);
+    record.set_pos(pos);
+    record.set_mtid(-1);
+    record.set_mpos(-1);
+    record.set_mapq(mapq);
+    record.set_flags(if secondary { 0x100 } else { 0 });
+    record
+}
+
+fn split_positions(path: &str) -> Vec<(String, i64, Option<f64>)> {
+    let mut reader = bam::Reader::from_path(path).unwrap();
+    reader
+        .records()
+        .map(|record| {
+            let record = record.unwrap();
+            (
+                String::from_utf8(record.qname().to_vec()).unwrap(),
+                record.pos(),
+                MultiMappingReassignment::read_weight(&record),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_split_multi_mapped_bam() {
+    let directory = tempfile::tempdir().unwrap();
+    let bam_path = format!("{}/sample.bam", directory.path().to_str().unwrap());
+    {
+        let header_view = bam::HeaderView::from_bytes(
+            b"@HD\tVN:1.6\tSO:coordinate\n\
+            @SQ\tSN:genome_a~contig_1\tLN:1000\n\
+            @SQ\tSN:genome_b~contig_1\tLN:1000\n",
+        );
+        let header = bam::Header::from_template(&header_view);
+        let mut writer = bam::Writer::from_path(&bam_path, &header, bam::Format::Bam).unwrap();
+        for record in [
+            alignment("unique_a1", 0, 100, 60, false),
+            alignment("multi", 0, 200, 0, false),
+            alignment("unique_a2", 0, 300, 60, false),
+            alignment("unique_a3", 0, 500, 60, false),
+            alignment("multi", 1, 50, 0, true),
+            alignment("unique_b1", 1, 400, 60, false),
+        ] {
+            writer.write(&record).unwrap();
+        }
+    }
+
+    let mut references = GenomesAndContigs::new();
+    references.establish_genome("genome_a".to_string());
+    references.establish_genome("genome_b".to_string());
+    let reassignment = MultiMappingReassignment::new(0.0);
+    finish_bams(
+        generate_named_bam_readers_from_bam_files(vec![bam_path.as_str()]),
+        1,
+        &references,
+        true,
+        false,
+        Some(&reassignment),
+    )
+    .unwrap();
+
+    // the reassigned read is merged in by position, so both split BAMs are sorted and indexed
+    let genome_a = format!("{}/genome_a/sample.bam", directory.path().to_str().unwrap());
+    let genome_b = format!("{}/genome_b/sample.bam", directory.path().to_str().unwrap());
+    assert!(std::path::Path::new(&format!("{}.bai", genome_a)).exists());
+    assert!(std::path::Path::new(&format!("{}.bai", genome_b)).exists());
+
+    let reads_a = split_positions(&genome_a);
+    let reads_b = split_positions(&genome_b);
+    assert_eq!(
+        reads_a
+            .iter()
+            .map(|(name, pos, _)| (name.as_str(), *pos))
+            .collect::<Vec<_>>(),
+        vec![
+            ("unique_a1", 100),
+            ("multi", 200),
+            ("unique_a2", 300),
+            ("unique_a3", 500)
+        ]
+    );
+    assert_eq!(
+        reads_b
+            .iter()
+            .map(|(name, pos, _)| (name.as_str(), *pos))
+            .collect::<Vec<_>>(),
+        vec![("multi", 50), ("unique_b1", 400)]
+    );
+
+    // the multi-mapped read is shared between the genomes by their abundance
+    let weight_a = reads_a[1].2.unwrap();
+    let weight_b = reads_b[0].2.unwrap();
+    assert_abs_diff_eq!(weight_a + weight_b, 1.0, epsilon = 1e-6);
+    assert!(weight_a > weight_b);
+    assert_eq!(reads_a[0].2, None);
+}
@@ -74,3 +74,28 @@ fn make_multiplicity_data() {
         }
     }
 }
+
+#[test]
+fn test_sample_multiplicities() {
+    let mut edge = MultiSampleEdge::new(false, 1, 2);
+    edge.inc_multiplicity(4);
+    edge.flush_sample_multiplicity(0);
+    edge.inc_multiplicity(1);
+    edge.flush_sample_multiplicity(2);
+    // a sample may be flushed again, e.g. for its long reads
+    edge.inc_multiplicity(1);
+    edge.flush_sample_multiplicity(2);
+
+    assert_eq!(edge.get_multiplicity(), 7);
+    assert_eq!(edge.get_sample_multiplicities(), &[5, 0, 2]);
+    assert_eq!(edge.samples_with_multiplicity(1), 2);
+    assert_eq!(edge.samples_with_multiplicity(2), 2);
+    assert_eq!(edge.samples_with_multiplicity(3), 1);
+    assert_eq!(edge.samples_with_multiplicity(6), 0);
+
+    // reads of the sample being threaded count before it is flushed
+    edge.inc_multiplicity(3);
+    assert_eq!(edge.samples_with_multiplicity(3), 2);
+    edge.flush_single_sample_multiplicity();
+    assert_eq!(edge.samples_with_multiplicity(3), 1);
+}
@@ -0,0 +1,103 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::output_layout::OutputLayout;
+use std::path::Path;
+
+#[test]
+fn test_output_template_expansion() {
+    let layout = OutputLayout::new(
+        "out",
+        "{mode}/{genome}/{sample}",
+        "call",
+        &["sample_1".to_string()],
+    );
+    assert_eq!(layout.genome_prefix("genome_a"), "out/call/genome_a/sample_1");
+
+    let layout = OutputLayout::new(
+        "out",
+        OutputLayout::DEFAULT_TEMPLATE,
+        "genotype",
+        &["sample_1".to_string(), "sample_2".to_string()],
+    );
+    assert_eq!(layout.genome_prefix("genome_a"), "out/genome_a");
+
+    let layout = OutputLayout::new("out", "{genome}/{sample}/", "call", &[]);
+    assert_eq!(layout.genome_prefix("genome_a"), "out/genome_a/all_samples");
+}
+
+#[test]
+fn test_output_template_validation() {
+    assert!(OutputLayout::validate_template("{genome}").is_ok());
+    assert!(OutputLayout::validate_template("{mode}/{genome}/{sample}").is_ok());
+    assert!(OutputLayout::validate_template("{mode}/{sample}").is_err());
+    assert!(OutputLayout::validate_template("{genome}/{strain}").is_err());
+    assert!(OutputLayout::validate_template("/{genome}").is_err());
+}
+
+#[test]
+fn test_output_types() {
+    assert_eq!(OutputLayout::output_type(Path::new("out/g/g.vcf.gz"), "g"), Some("vcf"));
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_strain_coverages.tsv"), "g"),
+        Some("strain_coverages")
+    );
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_strain_frequencies.tsv"), "g"),
+        Some("strain_frequencies")
+    );
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_dropped_alleles.tsv"), "g"),
+        Some("dropped_alleles")
+    );
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_active_regions.bed"), "g"),
+        Some("active_regions")
+    );
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/genotype/g_phasing.tsv"), "g"),
+        Some("phasing")
+    );
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_consensus_0.fna"), "g"),
+        Some("fasta")
+    );
+    assert_eq!(OutputLayout::output_type(Path::new("out/g/g.bam"), "g"), None);
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/g/g_strain_0_sample_1.bam"), "g"),
+        Some("strain_bam")
+    );
+}
+
+#[test]
+fn test_strain_bam_names() {
+    let name = OutputLayout::strain_bam_name("g", 3, "sample_1");
+    assert_eq!(name, "g_strain_3_sample_1.bam");
+    assert_eq!(
+        OutputLayout::output_type(Path::new(&format!("out/g/{}", name)), "g"),
+        Some("strain_bam")
+    );
+
+    // genome names containing _strain_ are not taken for strain BAM files
+    let genome = "e_coli_strain_k12";
+    assert_eq!(
+        OutputLayout::output_type(Path::new("out/e_coli_strain_k12/e_coli_strain_k12.bam"), genome),
+        None
+    );
+    assert_eq!(
+        OutputLayout::output_type(
+            Path::new("out/e_coli_strain_k12/e_coli_strain_k12_strain_coverages.tsv"),
+            genome
+        ),
+        Some("strain_coverages")
+    );
+    let name = OutputLayout::strain_bam_name(genome, 0, "sample_1");
+    assert_eq!(
+        OutputLayout::output_type(Path::new(&format!("out/{}/{}", genome, name)), genome),
+        Some("strain_bam")
+    );
+    // nor are the strain BAM files of other genomes
+    assert_eq!(OutputLayout::output_type(Path::new(&format!("out/g/{}", name)), "g"), None);
+}
@@ -86,3 +86,123 @@ fn test_compute_likelihoods() {
         v2
     );
 }
+
+/// Likelihoods of the reads of three samples against a reference haplotype, a SNP haplotype and
+/// a deletion haplotype, computed with the given PairHMM batch size
+fn sample_likelihoods(avx_mode: AVXMode, batch_size: usize) -> Vec<Vec<f64>> {
+    let mut lce = PairHMMLikelihoodCalculationEngine::new(
+        93,
+        MathUtils::log_to_log10(QualityUtils::qual_to_error_prob_log10(45)),
+        PCRErrorModel::Conservative,
+        16,
+        false,
+        1.0,
+        0.02,
+        true,
+        false,
+        true,
+        avx_mode,
+    );
+    lce.set_pair_hmm_batch_size(batch_size);
+
+    let ref_bases = b"ACGTTGCAAGTCCTAGGATCCAGTTACGGA".to_vec();
+    let location = SimpleInterval::new(0, 0, ref_bases.len() - 1);
+    let mut ref_haplotype = Haplotype::new(ref_bases.as_slice(), true);
+    ref_haplotype.set_genome_location(location.clone());
+    let mut assembly_result_set = AssemblyResultSet::<ReadThreadingGraph>::new(
+        AssemblyRegion::new(location.clone(), true, 0, 100, 0, 0, 0.0),
+        ref_bases.clone(),
+        location.clone(),
+        ref_haplotype.clone(),
+    );
+    assembly_result_set.add_haplotype(ref_haplotype);
+    for alt_bases in [
+        &b"ACGTTGCAAGTCCTTGGATCCAGTTACGGA"[..],
+        &b"ACGTTGCAAGTCGGATCCAGTTACGGA"[..],
+    ] {
+        let mut alt_haplotype = Haplotype::new(alt_bases, false);
+        alt_haplotype.set_genome_location(location.clone());
+        assembly_result_set.add_haplotype(alt_haplotype);
+    }
+
+    // samples with different numbers of reads, so batches span samples
+    let reads_by_sample: Vec<Vec<(&[u8], u8, i64)>> = vec![
+        vec![
+            (&b"GCAAGTCCTAGGATCC"[..], 30, 5),
+            (&b"GCAAGTCCTTGGATCCAG"[..], 20, 5),
+        ],
+        vec![(&b"CAAGTCCTTGGATCCAGTTA"[..], 40, 6)],
+        vec![
+            (&b"TTGCAAGTCGGATCCAG"[..], 35, 3),
+            (&b"ACGTTGCAAGTCCTAGG"[..], 25, 0),
+            (&b"GTCCTTGGATCCAGTTACGG"[..], 30, 9),
+        ],
+    ];
+    let mut per_sample_read_list = HashMap::new();
+    for (sample_index, reads) in reads_by_sample.iter().enumerate() {
+        let reads = reads
+            .iter()
+            .enumerate()
+            .map(|(read_index, (bases, qual, start))| {
+                let mut read = ArtificialReadUtils::create_artificial_read_with_name_and_pos(
+                    format!("read_{}_{}", sample_index, read_index),
+                    0,
+                    *start,
+                    bases,
+                    &vec![*qual; bases.len()],
+                    &format!("{}M", bases.len()),
+                    sample_index,
+                );
+                read.read.set_mapq(60);
+                read
+            })
+            .collect::<Vec<BirdToolRead>>();
+        per_sample_read_list.insert(sample_index, reads);
+    }
+
+    let mut likelihoods = lce.compute_read_likelihoods(
+        &mut assembly_result_set,
+        (0..reads_by_sample.len()).collect(),
+        per_sample_read_list,
+    );
+    (0..reads_by_sample.len())
+        .map(|sample_index| {
+            likelihoods
+                .sample_matrix(sample_index)
+                .iter()
+                .copied()
+                .collect::<Vec<f64>>()
+        })
+        .collect()
+}
+
+#[test]
+fn test_batched_likelihoods_match_per_sample() {
+    let avx_mode = AVXMode::detect_mode();
+    let per_sample = sample_likelihoods(avx_mode, 0);
+    assert_eq!(
+        per_sample.iter().map(|values| values.len()).sum::<usize>(),
+        18
+    );
+
+    // one read per batch, batches spanning samples, and a single batch
+    for batch_size in [1, 12, 1000] {
+        let batched = sample_likelihoods(avx_mode, batch_size);
+        assert_eq!(batched.len(), per_sample.len());
+        for (sample_index, (batched, per_sample)) in
+            batched.iter().zip(per_sample.iter()).enumerate()
+        {
+            assert_eq!(batched.len(), per_sample.len());
+            for (batched, per_sample) in batched.iter().zip(per_sample.iter()) {
+                assert!(
+                    (batched - per_sample).abs() < 1e-9,
+                    "sample {} with batch size {}: batched {} per sample {}",
+                    sample_index,
+                    batch_size,
+                    batched,
+                    per_sample
+                );
+            }
+        }
+    }
+}
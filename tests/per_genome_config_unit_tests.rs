@@ -0,0 +1,59 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::per_genome_config::{GenomeOverrides, PerGenomeConfig};
+
+#[test]
+fn test_parse_per_genome_config() {
+    let config = PerGenomeConfig::parse(
+        "# small viral genome\n\
+        [virus_1]\n\
+        kmer-sizes = [17, 25]\n\
+        ploidy = 4\n\
+        active-probability-threshold = 0.01\n\
+        \n\
+        [\"bacterium.2\"]\n\
+        max-assembly-region-size = 250\n\
+        depth-per-sample-filter = 5\n",
+    )
+    .unwrap();
+    assert_eq!(config.len(), 2);
+
+    let virus = config.get("virus_1").unwrap();
+    assert_eq!(virus.kmer_sizes, Some(vec![17, 25]));
+    assert_eq!(virus.ploidy, Some(4));
+    assert_eq!(virus.active_probability_threshold, Some(0.01));
+    assert_eq!(virus.max_assembly_region_size, None);
+    // genomes can be looked up by the path of their fasta file
+    assert_eq!(config.get("genomes/virus_1.fna"), Some(virus));
+
+    let bacterium = config.get("bacterium.2").unwrap();
+    assert_eq!(bacterium.max_assembly_region_size, Some(250));
+    assert_eq!(bacterium.depth_per_sample_filter, Some(5));
+    assert_eq!(bacterium.kmer_sizes, None);
+
+    assert!(config.get("unlisted").is_none());
+}
+
+#[test]
+fn test_empty_overrides() {
+    assert!(GenomeOverrides::default().is_empty());
+    let config = PerGenomeConfig::parse("[genome_1]\n").unwrap();
+    assert!(config.get("genome_1").unwrap().is_empty());
+}
+
+#[test]
+fn test_invalid_per_genome_config() {
+    // unknown options are rejected rather than silently ignored
+    assert!(PerGenomeConfig::parse("[genome_1]\nkmer-size = 21\n").is_err());
+    assert!(PerGenomeConfig::parse("[genome_1]\nploidy = 0\n").is_err());
+    assert!(PerGenomeConfig::parse("[genome_1]\nkmer-sizes = []\n").is_err());
+    assert!(PerGenomeConfig::parse("[genome_1]\nactive-probability-threshold = 1.5\n").is_err());
+    assert!(PerGenomeConfig::parse(
+        "[genome_1]\nmin-assembly-region-size = 300\nmax-assembly-region-size = 100\n"
+    )
+    .is_err());
+    assert!(PerGenomeConfig::parse("[genome_1\n").is_err());
+}
@@ -0,0 +1,81 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::linkage::phasing_statistics::{PhasedVariant, PhasingStatistics};
+
+fn variant(
+    tid: usize,
+    start: usize,
+    group: Option<i32>,
+    strains: Option<Vec<usize>>,
+    linked_reads: i32,
+) -> PhasedVariant {
+    PhasedVariant {
+        tid,
+        start,
+        end: start,
+        group,
+        strains,
+        linked_reads,
+    }
+}
+
+#[test]
+fn test_n50() {
+    assert_eq!(PhasingStatistics::n50(&[]), 0);
+    assert_eq!(PhasingStatistics::n50(&[100]), 100);
+    assert_eq!(PhasingStatistics::n50(&[10, 20, 30, 40]), 30);
+    assert_eq!(PhasingStatistics::n50(&[2, 2, 2, 2, 100]), 100);
+}
+
+#[test]
+fn test_variants_are_phased_by_group_or_shared_linked_strains() {
+    let grouped = variant(0, 10, Some(1), Some(vec![0]), 0);
+    assert!(grouped.is_phased_with(&variant(0, 20, Some(1), Some(vec![1]), 0)));
+    assert!(!grouped.is_phased_with(&variant(0, 20, Some(2), Some(vec![0]), 0)));
+    assert!(!grouped.is_phased_with(&variant(1, 20, Some(1), Some(vec![0]), 0)));
+    // unassigned variants are never phased
+    assert!(!grouped.is_phased_with(&variant(0, 20, Some(1), None, 0)));
+
+    let linked = variant(0, 10, None, Some(vec![0, 1]), 3);
+    assert!(linked.is_phased_with(&variant(0, 20, None, Some(vec![1]), 2)));
+    assert!(!linked.is_phased_with(&variant(0, 20, None, Some(vec![2]), 2)));
+    assert!(!linked.is_phased_with(&variant(0, 20, None, Some(vec![1]), 0)));
+}
+
+#[test]
+fn test_summarise_blocks() {
+    let variants = vec![
+        // block of 3 variants spanning 0..=20
+        variant(0, 0, Some(1), Some(vec![0]), 0),
+        variant(0, 10, Some(1), Some(vec![0]), 0),
+        variant(0, 20, Some(1), Some(vec![0]), 0),
+        // unphased singleton
+        variant(0, 30, None, None, 0),
+        // block of 2 variants spanning 40..=44
+        variant(0, 40, Some(2), Some(vec![1]), 0),
+        variant(0, 44, Some(2), Some(vec![1]), 0),
+        // same group on another contig breaks the block
+        variant(1, 5, Some(2), Some(vec![1]), 0),
+    ];
+    let summary = PhasingStatistics::summarise(&variants);
+
+    assert_eq!(summary.variants, 7);
+    assert_eq!(summary.variant_pairs, 5);
+    assert_eq!(summary.phased_pairs, 3);
+    assert_eq!(summary.blocks, 2);
+    assert_eq!(summary.block_n50, 21);
+    assert_eq!(summary.largest_block, 21);
+    assert!((summary.fraction_phased() - 0.6).abs() < 1e-9);
+}
+
+#[test]
+fn test_summarise_without_variants() {
+    let summary = PhasingStatistics::summarise(&[]);
+    assert_eq!(summary.variants, 0);
+    assert_eq!(summary.blocks, 0);
+    assert_eq!(summary.block_n50, 0);
+    assert_eq!(summary.fraction_phased(), 0.0);
+}
@@ -0,0 +1,80 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::phylogeny::core_snp_alignment::CoreSnpAlignment;
+use lorikeet_genome::phylogeny::neighbor_joining::neighbor_joining;
+use lorikeet_genome::phylogeny::upgma::upgma;
+use ndarray::Array2;
+
+fn taxa(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_add_core_snp_sites() {
+    let mut alignment = CoreSnpAlignment::new(taxa(&["a", "b", "c"]));
+    // invariant sites are not SNPs
+    assert!(!alignment.add_site("contig", 10, &[Some(b'A'), Some(b'A'), Some(b'A')], 0.0));
+    // a missing taxon excludes the site from a strict core alignment
+    assert!(!alignment.add_site("contig", 20, &[Some(b'A'), None, Some(b'C')], 0.0));
+    assert!(alignment.add_site("contig", 20, &[Some(b'A'), None, Some(b'C')], 0.5));
+    assert!(alignment.add_site("contig", 30, &[Some(b'G'), Some(b'T'), Some(b'T')], 0.0));
+
+    assert_eq!(alignment.len(), 2);
+    assert_eq!(
+        alignment.sites,
+        vec![("contig".to_string(), 20), ("contig".to_string(), 30)]
+    );
+    assert_eq!(
+        alignment.sequences,
+        vec![b"AG".to_vec(), b"NT".to_vec(), b"CT".to_vec()]
+    );
+
+    let distances = alignment.distance_matrix();
+    assert_eq!(distances[[0, 2]], 1.0);
+    assert_eq!(distances[[1, 2]], 0.0);
+    assert_eq!(distances[[0, 1]], distances[[1, 0]]);
+}
+
+#[test]
+fn test_neighbor_joining() {
+    let sequences = ["AGAA", "AGTC", "CTAA", "CTAA", "TCGG"];
+    let mut alignment = CoreSnpAlignment::new(taxa(&["a", "b", "c", "d", "e"]));
+    for column in 0..4 {
+        let bases = sequences
+            .iter()
+            .map(|sequence| Some(sequence.as_bytes()[column]))
+            .collect::<Vec<Option<u8>>>();
+        assert!(alignment.add_site("contig", column, &bases, 0.0));
+    }
+
+    let tree = neighbor_joining(&alignment.taxa, &alignment.distance_matrix());
+    assert_eq!(
+        tree,
+        "((a:0.125000,b:0.375000):0.062500,(e:0.625000,(c:0.000000,d:0.000000):0.375000):0.062500);"
+    );
+}
+
+#[test]
+fn test_upgma() {
+    let distances = Array2::from_shape_vec(
+        (5, 5),
+        vec![
+            0.0, 17.0, 21.0, 31.0, 23.0, //
+            17.0, 0.0, 30.0, 34.0, 21.0, //
+            21.0, 30.0, 0.0, 28.0, 39.0, //
+            31.0, 34.0, 28.0, 0.0, 43.0, //
+            23.0, 21.0, 39.0, 43.0, 0.0,
+        ],
+    )
+    .unwrap();
+
+    let tree = upgma(&taxa(&["a", "b", "c", "d", "e"]), &distances);
+    assert_eq!(
+        tree,
+        "((e:11.000000,(a:8.500000,b:8.500000):2.500000):5.500000,(c:14.000000,d:14.000000):2.500000);"
+    );
+    assert_eq!(upgma(&taxa(&["sample 1"]), &Array2::zeros((1, 1))), "sample_1;");
+}
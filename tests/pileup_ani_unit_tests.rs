@@ -0,0 +1,98 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::model::byte_array_allele::Allele;
+use lorikeet_genome::processing::pileup_ani::{PileupAni, SampleBaseCounts};
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+
+#[test]
+fn testDepthRuns() {
+    let reference = b"ACGTACGTAC";
+    let mut counts = SampleBaseCounts::new(reference.len());
+    for position in [0, 0, 1, 1, 2, 5, 5, 6, 6, 6] {
+        counts.add_base(position, reference[position], reference);
+    }
+    // depths are 2 2 1 0 0 2 3 0 0 0
+    assert_eq!(counts.depth_runs(2), vec![2, -3, 2, -3]);
+    assert_eq!(counts.depth_runs(1), vec![3, -2, 2, -3]);
+    assert_eq!(SampleBaseCounts::new(3).depth_runs(1), vec![-3]);
+}
+
+#[test]
+fn testCountsSeparateReferenceAndAlternateBases() {
+    let reference = b"ACGT";
+    let mut counts = SampleBaseCounts::new(reference.len());
+    counts.add_base(1, b'C', reference);
+    counts.add_base(1, b'C', reference);
+    counts.add_base(1, b't', reference);
+    // ambiguous bases and positions past the contig are ignored
+    counts.add_base(1, b'N', reference);
+    counts.add_base(4, b'A', reference);
+
+    assert_eq!(counts.depth(1), 3);
+    assert_eq!(counts.counts(1, 1), [0, 2, 0, 1]);
+    assert_eq!(
+        counts.alternate_positions().collect::<Vec<&usize>>(),
+        vec![&1]
+    );
+}
+
+#[test]
+fn testCountRecord() {
+    let reference = b"AAAAAAAAAA";
+    let mut record = Record::new();
+    // 2 soft clipped bases, 3 matches, a deleted base, 1 inserted base and 2 matches
+    let cigar = CigarString(vec![
+        Cigar::SoftClip(2),
+        Cigar::Match(3),
+        Cigar::Del(1),
+        Cigar::Ins(1),
+        Cigar::Match(2),
+    ]);
+    record.set(
+        b"read",
+        Some(&cigar),
+        b"GGACAGTA",
+        &[30, 30, 30, 30, 5, 30, 30, 30],
+    );
+    record.set_pos(2);
+
+    let pileup_ani = PileupAni::new(10, 0, 1, 0.05);
+    let mut counts = SampleBaseCounts::new(reference.len());
+    pileup_ani.count_record(&record, reference, &mut counts);
+
+    // position 4 has a low quality base and position 5 is deleted
+    let depths = (0..reference.len())
+        .map(|position| counts.depth(position))
+        .collect::<Vec<u32>>();
+    assert_eq!(depths, vec![0, 0, 1, 1, 0, 0, 1, 1, 0, 0]);
+    assert_eq!(counts.counts(3, 0), [0, 1, 0, 0]);
+    assert_eq!(counts.counts(7, 0), [1, 0, 0, 0]);
+    assert_eq!(counts.counts(6, 0), [0, 0, 0, 1]);
+}
+
+#[test]
+fn testSite() {
+    let pileup_ani = PileupAni::new(0, 0, 2, 0.1);
+
+    // only reference bases and errors below the thresholds
+    assert!(pileup_ani
+        .site(0, 10, b'A', &[[20, 1, 0, 0], [30, 0, 0, 0]])
+        .is_none());
+    // ambiguous reference bases are skipped
+    assert!(pileup_ani.site(0, 10, b'N', &[[0, 5, 5, 0]]).is_none());
+
+    let context = pileup_ani
+        .site(0, 10, b'A', &[[20, 1, 0, 0], [2, 18, 0, 0]])
+        .unwrap();
+    assert_eq!(context.get_n_alleles(), 2);
+    assert!(context.alleles[0].is_reference());
+    // the error in the first sample is dropped, so only the reference is present
+    assert_eq!(context.genotypes.genotypes()[0].ad, vec![20, 0]);
+    assert_eq!(context.genotypes.genotypes()[1].ad, vec![2, 18]);
+    assert_eq!(context.get_consensus_allele_index(0), Some(0));
+    assert_eq!(context.get_consensus_allele_index(1), Some(1));
+    assert_eq!(context.alleles_present_in_sample(1, 2), vec![true, true]);
+}
@@ -0,0 +1,36 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reads::read_compression::{PackedBases, QualityBins};
+
+#[test]
+fn test_pack_bases_round_trip() {
+    for bases in [
+        b"".to_vec(),
+        b"A".to_vec(),
+        b"ACGT".to_vec(),
+        b"TTGCAACGTAG".to_vec(),
+        b"ACNNGTRA".to_vec(),
+    ] {
+        let packed = PackedBases::pack(&bases);
+        assert_eq!(packed.len(), bases.len());
+        assert_eq!(packed.unpack(), bases);
+    }
+}
+
+#[test]
+fn test_quality_bins() {
+    let quality_bins = QualityBins::default();
+    assert_eq!(quality_bins.len(), 8);
+    assert_eq!(quality_bins.bin(0), 2);
+    assert_eq!(quality_bins.bin(9), 6);
+    assert_eq!(quality_bins.bin(10), 15);
+    assert_eq!(quality_bins.bin(30), 33);
+    assert_eq!(quality_bins.bin(41), 40);
+
+    let mut quals = vec![2, 12, 25, 38, 60];
+    quality_bins.bin_all(&mut quals);
+    assert_eq!(quals, vec![2, 15, 27, 37, 40]);
+}
@@ -0,0 +1,95 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reads::read_end_profile::{AdaptiveEndTrimmer, ReadEndProfile};
+use rust_htslib::bam::record::Cigar;
+
+#[test]
+fn testSoftClipLengths() {
+    assert_eq!(ReadEndProfile::soft_clip_lengths(&[]), (0, 0));
+    assert_eq!(
+        ReadEndProfile::soft_clip_lengths(&[Cigar::Match(10)]),
+        (0, 0)
+    );
+    assert_eq!(
+        ReadEndProfile::soft_clip_lengths(&[
+            Cigar::HardClip(5),
+            Cigar::SoftClip(3),
+            Cigar::Match(10),
+            Cigar::SoftClip(7),
+            Cigar::HardClip(2),
+        ]),
+        (3, 7)
+    );
+    assert_eq!(
+        ReadEndProfile::soft_clip_lengths(&[Cigar::Match(10), Cigar::SoftClip(4)]),
+        (0, 4)
+    );
+}
+
+#[test]
+fn testClipLengthBin() {
+    assert_eq!(ReadEndProfile::clip_length_bin(1), 0);
+    assert_eq!(ReadEndProfile::clip_length_bin(10), 0);
+    assert_eq!(ReadEndProfile::clip_length_bin(11), 1);
+    assert_eq!(ReadEndProfile::clip_length_bin(1000), 2);
+    assert_eq!(ReadEndProfile::clip_length_bin(5000), 3);
+}
+
+#[test]
+fn testProfileRecordsTrimmedBases() {
+    let profile = ReadEndProfile::new(2);
+    profile.record_trim(1, 12);
+    profile.record_trim(1, 0);
+    let samples = profile.samples();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0].trimmed_reads, 0);
+    assert_eq!(samples[1].trimmed_reads, 1);
+    assert_eq!(samples[1].trimmed_bases, 12);
+}
+
+#[test]
+fn testMismatchFlags() {
+    let cigar = [
+        Cigar::SoftClip(2),
+        Cigar::Match(4),
+        Cigar::Ins(1),
+        Cigar::Del(2),
+        Cigar::Match(2),
+    ];
+    let flags = AdaptiveEndTrimmer::mismatch_flags(&cigar, b"GGACGTCAT", 10, b"TTACGAGGAA", 8);
+    assert_eq!(
+        flags,
+        vec![
+            None,
+            None,
+            Some(false),
+            Some(false),
+            Some(false),
+            Some(true),
+            Some(true),
+            Some(false),
+            Some(true),
+        ]
+    );
+}
+
+#[test]
+fn testTailLengths() {
+    let trimmer = AdaptiveEndTrimmer::new(0.3, 5);
+
+    let mut flags = vec![Some(false); 20];
+    flags[..6].iter_mut().for_each(|flag| *flag = Some(true));
+    assert_eq!(trimmer.tail_lengths(&flags), (6, 0));
+
+    // a single variant near the end of a read is not trimmed
+    let mut flags = vec![Some(false); 20];
+    flags[19] = Some(true);
+    assert_eq!(trimmer.tail_lengths(&flags), (0, 0));
+
+    // ends are trimmed by at most half of the read
+    let flags = vec![Some(true); 20];
+    assert_eq!(trimmer.tail_lengths(&flags), (10, 10));
+}
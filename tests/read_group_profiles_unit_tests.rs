@@ -0,0 +1,68 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reads::read_group_profiles::{
+    ReadGroupProfile, ReadGroupProfiles, SequencingPlatform,
+};
+use lorikeet_genome::utils::errors::BirdToolError;
+
+#[test]
+fn test_read_groups_from_header() {
+    let header = b"@HD\tVN:1.6\tSO:coordinate\n\
+        @SQ\tSN:contig_1\tLN:1000\n\
+        @RG\tID:short\tSM:sample_1\tPL:ILLUMINA\tPU:FLOWCELL1.1\n\
+        @RG\tID:long\tSM:sample_1\tPL:ONT\n\
+        @RG\tID:other\tSM:sample_1\n";
+
+    let read_groups = ReadGroupProfiles::read_groups_from_header(header);
+    assert_eq!(read_groups.len(), 3);
+    assert_eq!(
+        read_groups.get(&b"short".to_vec()),
+        Some(&ReadGroupProfile {
+            platform: SequencingPlatform::Illumina,
+            platform_unit: Some("FLOWCELL1.1".to_string()),
+        })
+    );
+    assert_eq!(
+        read_groups.get(&b"long".to_vec()).unwrap().platform,
+        SequencingPlatform::OxfordNanopore
+    );
+    assert_eq!(
+        read_groups.get(&b"other".to_vec()).unwrap().platform,
+        SequencingPlatform::Unknown
+    );
+}
+
+#[test]
+fn test_parse_weights() {
+    let weights = ReadGroupProfiles::parse_weights("ILLUMINA=1.0, ONT=0.25,pacbio=0.5").unwrap();
+    assert_eq!(weights.get(&SequencingPlatform::Illumina), Some(&1.0));
+    assert_eq!(weights.get(&SequencingPlatform::OxfordNanopore), Some(&0.25));
+    assert_eq!(weights.get(&SequencingPlatform::PacBio), Some(&0.5));
+
+    assert!(ReadGroupProfiles::parse_weights("ONT").is_err());
+    assert!(ReadGroupProfiles::parse_weights("ONT=1.5").is_err());
+}
+
+#[test]
+fn test_check_read_group_weights() {
+    let command = clap::Command::new("call")
+        .arg(clap::Arg::new("read-group-weights").long("read-group-weights"));
+    let check = |args: Vec<&str>| {
+        ReadGroupProfiles::check_args(&command.clone().try_get_matches_from(args).unwrap())
+    };
+
+    assert!(check(vec!["call"]).is_ok());
+    assert!(check(vec!["call", "--read-group-weights", "ILLUMINA=1.0,ONT=0.5"]).is_ok());
+    // malformed weights stop the run with a configuration error before any genome is called
+    assert!(matches!(
+        check(vec!["call", "--read-group-weights", "ONT=2"]),
+        Err(BirdToolError::ConfigError(_))
+    ));
+    assert!(matches!(
+        check(vec!["call", "--read-group-weights", "ONT"]),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
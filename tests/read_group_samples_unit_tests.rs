@@ -0,0 +1,69 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reads::read_group_samples::ReadGroupSamples;
+
+#[test]
+fn test_sample_from_header() {
+    let header = b"@HD\tVN:1.6\tSO:coordinate\n\
+        @SQ\tSN:contig_1\tLN:1000\n\
+        @RG\tID:lane_1\tSM:sample_1\tPL:ILLUMINA\n\
+        @RG\tID:lane_2\tSM:sample_1\tPL:ILLUMINA\n";
+    assert_eq!(
+        ReadGroupSamples::sample_from_header(header),
+        Some("sample_1".to_string())
+    );
+
+    // read groups naming different samples do not name the BAM file
+    let header = b"@RG\tID:lane_1\tSM:sample_1\n@RG\tID:lane_2\tSM:sample_2\n";
+    assert_eq!(ReadGroupSamples::sample_from_header(header), None);
+
+    let header = b"@HD\tVN:1.6\n@RG\tID:lane_1\tPL:ONT\n";
+    assert_eq!(ReadGroupSamples::sample_from_header(header), None);
+}
+
+#[test]
+fn test_resolve_prefers_read_group_samples() {
+    let bam_paths = vec!["renamed_1.bam".to_string(), "renamed_2.bam".to_string()];
+    let names = ReadGroupSamples::resolve(&bam_paths, &[Some("sample_a".to_string()), None]);
+    assert_eq!(
+        names,
+        vec!["sample_a".to_string(), "renamed_2.bam".to_string()]
+    );
+}
+
+#[test]
+fn test_resolve_collisions() {
+    let bam_paths = vec![
+        "run_1.bam".to_string(),
+        "run_2.bam".to_string(),
+        "run_3.bam".to_string(),
+    ];
+    // colliding read group samples fall back to their file names
+    let names = ReadGroupSamples::resolve(
+        &bam_paths,
+        &[
+            Some("sample_a".to_string()),
+            Some("sample_a".to_string()),
+            Some("sample_b".to_string()),
+        ],
+    );
+    assert_eq!(
+        names,
+        vec![
+            "run_1.bam".to_string(),
+            "run_2.bam".to_string(),
+            "sample_b".to_string()
+        ]
+    );
+
+    // names that still collide are made unique
+    let bam_paths = vec!["run_1.bam".to_string(), "run_1.bam".to_string()];
+    let names = ReadGroupSamples::resolve(&bam_paths, &[None, None]);
+    assert_eq!(
+        names,
+        vec!["run_1.bam_1".to_string(), "run_1.bam_2".to_string()]
+    );
+}
@@ -0,0 +1,71 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::bams::read_sharing::{
+    CompetitiveMappingReport, ReadSharingCounter,
+};
+use rust_htslib::bam::Record;
+
+fn alignment(name: &[u8], flags: u16) -> Record {
+    let mut record = Record::new();
+    record.set(name, None, b"ACGT", &[30, 30, 30, 30]);
+    record.set_flags(flags);
+    record
+}
+
+#[test]
+fn testReadSharingCounter() {
+    let mut counter = ReadSharingCounter::new(3);
+    // read1 only aligns to genome 0, twice
+    counter.add(&alignment(b"read1", 0), 0);
+    counter.add(&alignment(b"read1", 0x100), 0);
+    // read2 aligns to genomes 0 and 1, read3 to all three genomes
+    counter.add(&alignment(b"read2", 0), 0);
+    counter.add(&alignment(b"read2", 0x100), 1);
+    counter.add(&alignment(b"read3", 0), 2);
+    counter.add(&alignment(b"read3", 0x100), 1);
+    counter.add(&alignment(b"read3", 0x100), 0);
+    // the mate of read1 is counted separately
+    counter.add(&alignment(b"read1", 0x80), 2);
+    // unmapped and supplementary records are skipped
+    counter.add(&alignment(b"read4", 0x4), 1);
+    counter.add(&alignment(b"read1", 0x800), 1);
+
+    let summary = counter.summarise("sample");
+    assert_eq!(summary.unique, vec![1, 0, 1]);
+    assert_eq!(summary.shared, vec![2, 2, 1]);
+    assert_eq!(
+        summary.matrix,
+        vec![vec![3, 2, 1], vec![2, 2, 1], vec![1, 1, 2]]
+    );
+    assert_eq!(summary.total(0), 3);
+}
+
+#[test]
+fn testSimilarGenomes() {
+    let genomes = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+    let mut report = CompetitiveMappingReport::new(&genomes);
+    assert!(report.is_empty());
+
+    for sample in ["s1", "s2"] {
+        let mut counter = ReadSharingCounter::new(3);
+        for read in 0..10 {
+            let name = format!("{}_{}", sample, read);
+            counter.add(&alignment(name.as_bytes(), 0), 0);
+            // 3 in 10 reads of genome a also align to genome b
+            if read < 3 {
+                counter.add(&alignment(name.as_bytes(), 0x100), 1);
+            }
+            counter.add(&alignment(format!("{}_c", name).as_bytes(), 0), 2);
+        }
+        report.add_sample(counter.summarise(sample));
+    }
+
+    let matrix = report.sharing_matrix();
+    assert_eq!(matrix[0], vec![20, 6, 0]);
+    assert_eq!(matrix[1][1], 6);
+    // b shares all of its reads with a
+    assert_eq!(report.similar_genomes(0.2), vec![(0, 1, 1.0)]);
+}
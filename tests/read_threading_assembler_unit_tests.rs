@@ -730,3 +730,32 @@ fn test_single_indel_as_double_indel_3_reads() {
 
     assert_eq!(paths.len(), 2);
 }
+
+#[test]
+fn test_smallest_unique_kmer_size() {
+    // every 13-mer of the repeated GATTACA is seen twice, kmers of 14 and above are unique
+    let reference = b"GATTACAGATTACAGATTACCTTGA";
+    assert!(!ReadThreadingAssembler::has_unique_reference_kmers(reference, 13));
+    assert!(ReadThreadingAssembler::has_unique_reference_kmers(reference, 14));
+
+    assert_eq!(
+        ReadThreadingAssembler::smallest_unique_kmer_size(reference, 1, 25),
+        Some(15)
+    );
+    assert_eq!(
+        ReadThreadingAssembler::smallest_unique_kmer_size(reference, 16, 30),
+        Some(17)
+    );
+    assert_eq!(
+        ReadThreadingAssembler::smallest_unique_kmer_size(reference, 3, 13),
+        None
+    );
+
+    // even kmer sizes are reported, but the requested kmer sizes are not rewritten
+    let kmer_sizes = vec![25, 10, 21, 11];
+    assert_eq!(
+        ReadThreadingAssembler::validate_kmer_sizes(&kmer_sizes),
+        vec![10]
+    );
+    assert!(ReadThreadingAssembler::validate_kmer_sizes(&[11, 21, 25]).is_empty());
+}
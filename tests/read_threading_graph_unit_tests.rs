@@ -13,6 +13,7 @@ use lorikeet_genome::assembly::kmer::Kmer;
 use lorikeet_genome::graphs::base_edge::BaseEdge;
 use lorikeet_genome::graphs::base_vertex::BaseVertex;
 use lorikeet_genome::graphs::graph_based_k_best_haplotype_finder::GraphBasedKBestHaplotypeFinder;
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
 use lorikeet_genome::read_threading::abstract_read_threading_graph::{
     AbstractReadThreadingGraph, SequenceForKmers,
 };
@@ -1151,3 +1152,136 @@ fn _generate_read_with_errors(
 
     result
 }
+
+fn build_hybrid_test_graph(hybrid_assembly: bool, include_short_read: bool) -> usize {
+    let reference = b"ACGTACCTGATTGCAGGTCAATGCCTTAGCAGTACGATCCAGTTGACCATGAGCTTAAC".to_vec();
+    let mut long_read_bases = reference.clone();
+    long_read_bases[30] = b'C';
+
+    let mut rtgraph = ReadThreadingGraph::default_with_kmer_size(11);
+    rtgraph.set_hybrid_assembly(hybrid_assembly);
+    let mut pending = LinkedHashMap::new();
+    rtgraph.add_sequence(
+        &mut pending,
+        "ref".to_string(),
+        std::usize::MAX,
+        reference.as_slice(),
+        0,
+        reference.len(),
+        1,
+        true,
+    );
+
+    let samples = vec!["short".to_string(), "long".to_string()];
+    let quals = vec![30; reference.len()];
+    let mut reads = Vec::new();
+    if include_short_read {
+        reads.push(ArtificialReadUtils::create_artificial_read(
+            &reference,
+            &quals,
+            CigarString::try_from("59M").unwrap(),
+        ));
+    }
+    let mut long_read = ArtificialReadUtils::create_artificial_read(
+        &long_read_bases,
+        &quals,
+        CigarString::try_from("59M").unwrap(),
+    );
+    long_read.sample_index = 1;
+    long_read.read_type = ReadType::Long;
+    reads.push(long_read);
+
+    let mut count = 0;
+    for read in reads.iter() {
+        rtgraph.add_read(read, &samples, &mut count, &mut pending);
+    }
+    rtgraph.build_graph_if_necessary(&mut pending);
+
+    rtgraph.get_base_graph().graph.node_count()
+}
+
+#[test]
+fn test_hybrid_assembly_long_reads_do_not_create_kmers() {
+    // 49 unique reference kmers, the long read SNP adds 11 more
+    assert_eq!(build_hybrid_test_graph(false, true), 60);
+    assert_eq!(build_hybrid_test_graph(true, true), 49);
+}
+
+#[test]
+fn test_hybrid_assembly_without_short_reads_threads_long_reads() {
+    assert_eq!(build_hybrid_test_graph(true, false), 60);
+}
+
+fn hybrid_edge_multiplicities(
+    hybrid_assembly: bool,
+    short_reads: &[(usize, usize)],
+    long_reads: usize,
+) -> Vec<usize> {
+    let reference = b"ACGTACCTGATTGCAGGTCAATGCCTTAGCAGTACGATCCAGTTGACCATGAGCTTAAC".to_vec();
+
+    let mut rtgraph = ReadThreadingGraph::default_with_kmer_size(11);
+    rtgraph.set_hybrid_assembly(hybrid_assembly);
+    let mut pending = LinkedHashMap::new();
+
+    let samples = vec!["short".to_string(), "long".to_string()];
+    let mut reads = Vec::new();
+    for (start, end) in short_reads.iter() {
+        reads.push(ArtificialReadUtils::create_artificial_read(
+            &reference[*start..*end],
+            &vec![30; end - start],
+            CigarString::try_from(format!("{}M", end - start).as_str()).unwrap(),
+        ));
+    }
+    for _ in 0..long_reads {
+        let mut long_read = ArtificialReadUtils::create_artificial_read(
+            &reference,
+            &vec![30; reference.len()],
+            CigarString::try_from("59M").unwrap(),
+        );
+        long_read.sample_index = 1;
+        long_read.read_type = ReadType::Long;
+        reads.push(long_read);
+    }
+
+    let mut count = 0;
+    for read in reads.iter() {
+        rtgraph.add_read(read, &samples, &mut count, &mut pending);
+    }
+    rtgraph.build_graph_if_necessary(&mut pending);
+
+    rtgraph
+        .get_base_graph()
+        .graph
+        .edge_weights()
+        .map(|edge| edge.get_multiplicity())
+        .sorted()
+        .collect()
+}
+
+#[test]
+fn test_hybrid_assembly_long_reads_do_not_reweight_short_read_edges() {
+    // the 48 edges of a read spanning the whole reference
+    let short_read_only = hybrid_edge_multiplicities(true, &[(0, 59)], 0);
+    assert_eq!(short_read_only, vec![1; 48]);
+
+    // without hybrid assembly a long read counts as much as a short read
+    assert_eq!(
+        hybrid_edge_multiplicities(false, &[(0, 59)], 1),
+        vec![2; 48]
+    );
+    assert_eq!(
+        hybrid_edge_multiplicities(true, &[(0, 59)], 1),
+        short_read_only
+    );
+}
+
+#[test]
+fn test_hybrid_assembly_weights_junctions_by_long_reads() {
+    // the short reads share no kmers, so only the long reads connect kmers 24 and 25
+    let mut expected = vec![1; 47];
+    expected.push(2);
+    assert_eq!(
+        hybrid_edge_multiplicities(true, &[(0, 35), (25, 59)], 2),
+        expected
+    );
+}
@@ -0,0 +1,84 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::abundance::reference_bias::{ReferenceBias, SampleReferenceBias};
+use lorikeet_genome::genotype::genotype_builder::Genotype;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+
+fn biallelic_site(position: usize, ads: Vec<Vec<i32>>) -> VariantContext {
+    let mut context = VariantContext::build(
+        0,
+        position,
+        position,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"C", false),
+        ],
+    );
+    context.add_genotypes(
+        ads.into_iter()
+            .map(|ad| Genotype::build_from_ads(1, ad))
+            .collect(),
+    );
+    context
+}
+
+#[test]
+fn testHeterozygousLike() {
+    let reference_bias = ReferenceBias::new(1, 2, 0.2);
+    assert_eq!(reference_bias.heterozygous_like(&[10, 10]), Some((10, 10)));
+    // below the depth filter, outside the fraction window or multiallelic
+    assert_eq!(reference_bias.heterozygous_like(&[10, 1]), None);
+    assert_eq!(reference_bias.heterozygous_like(&[90, 10]), None);
+    assert_eq!(reference_bias.heterozygous_like(&[10, 10, 10]), None);
+}
+
+#[test]
+fn testSampleReferenceBias() {
+    let mut sample = SampleReferenceBias::new();
+    assert!(sample.mean_reference_fraction().is_nan());
+    sample.add_site(6, 4);
+    sample.add_site(7, 3);
+    sample.add_site(0, 0);
+    assert_eq!(sample.sites(), 2);
+    assert_eq!((sample.reference_reads, sample.alternate_reads), (13, 7));
+    assert!((sample.mean_reference_fraction() - 0.65).abs() < 1e-9);
+    assert!((sample.median_reference_fraction() - 0.65).abs() < 1e-9);
+    assert!((sample.bias_ratio() - 0.65 / 0.35).abs() < 1e-9);
+}
+
+#[test]
+fn testCorrectionFactors() {
+    // the first sample is biased towards the reference at every site, the second is balanced
+    let contexts = (0..20)
+        .map(|position| {
+            let reference_reads = if position % 2 == 0 { 12 } else { 13 };
+            let balanced = if position % 2 == 0 { 9 } else { 11 };
+            biallelic_site(
+                position,
+                vec![
+                    vec![reference_reads, 20 - reference_reads],
+                    vec![balanced, 20 - balanced],
+                ],
+            )
+        })
+        .collect::<Vec<VariantContext>>();
+
+    let mut reference_bias = ReferenceBias::new(2, 2, 0.2);
+    reference_bias.add_contexts(&contexts);
+    assert_eq!(reference_bias.samples()[0].sites(), 20);
+    assert!(reference_bias.is_biased(0));
+    assert!(!reference_bias.is_biased(1));
+
+    let correction_factors = reference_bias.correction_factors();
+    assert!((correction_factors[0] - 0.625 / 0.375).abs() < 1e-9);
+    assert_eq!(correction_factors[1], 1.0);
+
+    // too few sites to call a bias
+    let mut reference_bias = ReferenceBias::new(2, 2, 0.2);
+    reference_bias.add_contexts(&contexts[..5]);
+    assert!(!reference_bias.is_biased(0));
+}
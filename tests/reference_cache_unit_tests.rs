@@ -0,0 +1,76 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use lorikeet_genome::reference::reference_cache::{ConcatenatedReference, ReferenceCache};
+use std::io::Write;
+use std::path::PathBuf;
+
+fn write_genome(directory: &tempfile::TempDir, name: &str, sequence: &str) -> String {
+    let path = directory.path().join(name);
+    let mut file = std::fs::File::create(&path).unwrap();
+    writeln!(file, ">contig_1\n{}", sequence).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_cache_key_follows_genome_contents() {
+    let directory = tempfile::tempdir().unwrap();
+    let genome_a = write_genome(&directory, "a.fna", "ACGTACGT");
+    let genome_b = write_genome(&directory, "b.fna", "TTGGCCAA");
+    let separator = GenomeSeparator::default();
+
+    let key = ReferenceCache::cache_key(&[genome_a.clone(), genome_b.clone()], separator).unwrap();
+    assert_eq!(
+        key,
+        ReferenceCache::cache_key(&[genome_a.clone(), genome_b.clone()], separator).unwrap()
+    );
+    // contigs are numbered in the order the genomes are given
+    assert_ne!(
+        key,
+        ReferenceCache::cache_key(&[genome_b.clone(), genome_a.clone()], separator).unwrap()
+    );
+    // the separator is part of every contig name
+    assert_ne!(
+        key,
+        ReferenceCache::cache_key(
+            &[genome_a.clone(), genome_b.clone()],
+            GenomeSeparator::new('^')
+        )
+        .unwrap()
+    );
+
+    write_genome(&directory, "b.fna", "TTGGCCAT");
+    assert_ne!(
+        key,
+        ReferenceCache::cache_key(&[genome_a, genome_b], separator).unwrap()
+    );
+
+    assert!(ReferenceCache::cache_key(&["missing.fna".to_string()], separator).is_err());
+}
+
+#[test]
+fn test_lookup_requires_complete_entry() {
+    let directory = tempfile::tempdir().unwrap();
+    let cache = ReferenceCache::new(directory.path().to_str().unwrap());
+    assert!(cache.lookup("abc").is_none());
+
+    // an entry without the completion marker is still being written
+    let entry = cache.entry_directory("abc");
+    std::fs::create_dir_all(&entry).unwrap();
+    std::fs::write(
+        entry.join(ReferenceCache::REFERENCE_NAME),
+        ">a~contig_1\nACGT\n",
+    )
+    .unwrap();
+    assert!(cache.lookup("abc").is_none());
+
+    let reference = ConcatenatedReference::Cached(PathBuf::from("cache/abc/genomes.fna"));
+    assert!(reference.is_cached());
+    assert_eq!(
+        reference.path(),
+        PathBuf::from("cache/abc/genomes.fna").as_path()
+    );
+}
@@ -0,0 +1,149 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::model::accessible_genome::AccessibleWindow;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::model::variant_context_utils::VariantContextUtils;
+use lorikeet_genome::reference::reference_mask::ReferenceMask;
+use lorikeet_genome::utils::errors::BirdToolError;
+use ndarray::Array2;
+
+#[test]
+fn test_interval_merging_and_overlaps() {
+    let mut mask = ReferenceMask::new();
+    mask.add_interval(0, 10, 20);
+    mask.add_interval(0, 15, 25);
+    mask.add_interval(0, 25, 30);
+    mask.add_interval(0, 50, 60);
+    mask.add_interval(1, 0, 5);
+    mask.add_interval(1, 5, 5); // empty, ignored
+    mask.merge();
+
+    assert_eq!(mask.masked_bases(), 20 + 10 + 5);
+    assert!(!mask.is_masked(0, 9));
+    assert!(mask.is_masked(0, 10));
+    assert!(mask.is_masked(0, 29));
+    assert!(!mask.is_masked(0, 30));
+    assert!(mask.overlaps(0, 40, 50));
+    assert!(!mask.overlaps(0, 30, 49));
+    assert!(mask.is_masked(1, 4));
+    assert!(!mask.is_masked(2, 4));
+}
+
+#[test]
+fn test_non_unique_kmers_are_masked() {
+    // "ACGTTGCA" appears in both contigs, the rest is unique
+    let contig_1 = b"ACGTTGCAGGGAAATCC".to_vec();
+    let contig_2 = b"TTTCCCACGTTGCA".to_vec();
+
+    let intervals =
+        ReferenceMask::find_non_unique_kmer_intervals(&[contig_1, contig_2], 8);
+
+    assert_eq!(intervals[0], vec![(0, 8)]);
+    assert_eq!(intervals[1], vec![(6, 14)]);
+}
+
+#[test]
+fn test_reverse_complement_kmers_are_masked() {
+    // AAACCCG reverse complement is CGGGTTT
+    let contig = b"AAACCCGATTAGCGGGTTT".to_vec();
+    let intervals = ReferenceMask::find_non_unique_kmer_intervals(&[contig], 7);
+
+    assert_eq!(intervals[0], vec![(0, 7), (12, 19)]);
+}
+
+#[test]
+fn test_ambiguous_bases_are_not_masked() {
+    let contig = b"ACGTNACGTNACGT".to_vec();
+    let intervals = ReferenceMask::find_non_unique_kmer_intervals(&[contig], 5);
+
+    assert!(intervals[0].is_empty());
+}
+
+#[test]
+fn test_masked_contexts_fail_thresholds() {
+    let mut mask = ReferenceMask::new();
+    mask.add_interval(0, 100, 200);
+    mask.merge();
+
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"T", false);
+    let mut contexts = vec![
+        VariantContext::build(0, 50, 50, vec![ref_allele.clone(), alt_allele.clone()]),
+        VariantContext::build(0, 150, 150, vec![ref_allele.clone(), alt_allele.clone()]),
+    ];
+
+    assert_eq!(mask.apply_to_contexts(&mut contexts), 1);
+    assert!(!contexts[0].is_masked());
+    assert!(contexts[1].is_masked());
+    assert!(!VariantContextUtils::passes_thresholds(
+        &mut contexts[1],
+        0.0,
+        0.0
+    ));
+}
+
+#[test]
+fn test_compared_bases_adjustment() {
+    let mut mask = ReferenceMask::new();
+    mask.add_interval(0, 0, 100);
+    mask.add_interval(1, 500, 520);
+    mask.merge();
+
+    let mut first_window = Array2::from_elem((2, 2), 500.0_f32);
+    first_window[[0, 1]] = 50.0;
+    first_window[[1, 0]] = 50.0;
+    let windows = vec![
+        AccessibleWindow::new(0, 0, 499, first_window),
+        AccessibleWindow::new(0, 500, 999, Array2::from_elem((2, 2), 500.0_f32)),
+        AccessibleWindow::new(1, 0, 999, Array2::from_elem((2, 2), 400.0_f32)),
+    ];
+    let mut compared_bases = windows
+        .iter()
+        .fold(Array2::<f32>::zeros((2, 2)), |total, window| {
+            total + &window.compared_bases
+        });
+    mask.adjust_compared_bases(&mut compared_bases, &windows);
+
+    // 120 bases are masked in total, but the pair only compared 50 of the masked 100 bases
+    // in the first window
+    assert_eq!(compared_bases[[0, 0]], 1280.0);
+    assert_eq!(compared_bases[[0, 1]], 880.0);
+    assert_eq!(compared_bases[[1, 0]], 880.0);
+}
+
+#[test]
+fn test_unreadable_mask_bed_is_a_config_error() {
+    let command = clap::Command::new("call").arg(clap::Arg::new("mask-bed").long("mask-bed"));
+    let directory = tempfile::tempdir().unwrap();
+    let check = |path: &std::path::Path| {
+        let args = command
+            .clone()
+            .try_get_matches_from(vec!["call", "--mask-bed", path.to_str().unwrap()])
+            .unwrap();
+        ReferenceMask::check_args(&args)
+    };
+
+    let valid = directory.path().join("valid.bed");
+    std::fs::write(&valid, "track name=mask\ncontig_1\t10\t20\n").unwrap();
+    assert!(check(&valid).is_ok());
+
+    let malformed = directory.path().join("malformed.bed");
+    std::fs::write(&malformed, "contig_1\tten\t20\n").unwrap();
+    assert!(matches!(
+        check(&malformed),
+        Err(BirdToolError::ConfigError(_))
+    ));
+
+    assert!(matches!(
+        check(&directory.path().join("missing.bed")),
+        Err(BirdToolError::ConfigError(_))
+    ));
+
+    // no BED file means nothing to check
+    let args = command.try_get_matches_from(vec!["call"]).unwrap();
+    assert!(ReferenceMask::check_args(&args).is_ok());
+}
@@ -0,0 +1,168 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use clap::{Arg, Command};
+use lorikeet_genome::reference::reference_reader_utils::ReferenceReaderUtils;
+use std::io::Write;
+
+fn test_command() -> Command {
+    Command::new("lorikeet")
+        .arg(
+            Arg::new("genome-fasta-files")
+                .long("genome-fasta-files")
+                .num_args(1..),
+        )
+        .arg(Arg::new("genome-fasta-directory").long("genome-fasta-directory"))
+        .arg(
+            Arg::new("genome-fasta-extension")
+                .long("genome-fasta-extension")
+                .default_value("fna"),
+        )
+}
+
+fn write_genome(directory: &tempfile::TempDir, name: &str, contigs: &[(&str, &str)]) -> String {
+    let path = directory.path().join(name);
+    let mut file = std::fs::File::create(&path).unwrap();
+    for (contig_name, sequence) in contigs.iter() {
+        writeln!(file, ">{}\n{}", contig_name, sequence).unwrap();
+    }
+    path.to_str().unwrap().to_string()
+}
+
+fn gzip(path: &str) -> String {
+    let status = std::process::Command::new("gzip")
+        .arg(path)
+        .status()
+        .expect("Unable to run gzip");
+    assert!(status.success());
+    format!("{}.gz", path)
+}
+
+#[test]
+fn test_genome_name_from_path() {
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path("genomes/genome_1.fna"),
+        "genome_1"
+    );
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path("genomes/genome_1.fna.gz"),
+        "genome_1"
+    );
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path("genomes/genome_1.fna.xz"),
+        "genome_1"
+    );
+    // only the last extension is removed from uncompressed files
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path("genome.v2.fasta"),
+        "genome.v2"
+    );
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path("genome"),
+        "genome"
+    );
+
+    assert!(ReferenceReaderUtils::is_compressed_fasta("genome.fna.bz2"));
+    assert!(!ReferenceReaderUtils::is_compressed_fasta("genome.fna"));
+    assert!(!ReferenceReaderUtils::is_compressed_fasta("genome.zip"));
+}
+
+#[test]
+fn test_parse_reference_globs() {
+    let directory = tempfile::tempdir().unwrap();
+    let genome_a = write_genome(&directory, "a.fna", &[("contig_1", "ACGTACGT")]);
+    let genome_b = write_genome(&directory, "b.fna", &[("contig_1", "TTGGCCAA")]);
+    write_genome(&directory, "c.fasta", &[("contig_1", "GGGGCCCC")]);
+
+    let pattern = format!("{}/*.fna", directory.path().to_str().unwrap());
+    let matches =
+        test_command().get_matches_from(vec!["lorikeet", "--genome-fasta-files", &pattern]);
+    let mut references = ReferenceReaderUtils::try_parse_references(&matches).unwrap();
+    references.sort();
+    assert_eq!(references, vec![genome_a.clone(), genome_b.clone()]);
+
+    // paths that are not patterns are kept as given, even if they do not exist yet
+    let matches = test_command().get_matches_from(vec![
+        "lorikeet",
+        "--genome-fasta-files",
+        &genome_a,
+        "missing.fna",
+    ]);
+    assert_eq!(
+        ReferenceReaderUtils::try_parse_references(&matches).unwrap(),
+        vec![genome_a, "missing.fna".to_string()]
+    );
+
+    let pattern = format!("{}/*.fa", directory.path().to_str().unwrap());
+    let matches =
+        test_command().get_matches_from(vec!["lorikeet", "--genome-fasta-files", &pattern]);
+    assert!(ReferenceReaderUtils::try_parse_references(&matches).is_err());
+
+    let matches = test_command().get_matches_from(vec!["lorikeet"]);
+    assert!(ReferenceReaderUtils::try_parse_references(&matches).is_err());
+}
+
+#[test]
+fn test_parse_reference_directory_with_compressed_genomes() {
+    let directory = tempfile::tempdir().unwrap();
+    let genome_a = write_genome(&directory, "a.fna", &[("contig_1", "ACGTACGT")]);
+    let genome_b = gzip(&write_genome(
+        &directory,
+        "b.fna",
+        &[("contig_1", "TTGGCCAA")],
+    ));
+    write_genome(&directory, "c.fasta", &[("contig_1", "GGGGCCCC")]);
+
+    let matches = test_command().get_matches_from(vec![
+        "lorikeet",
+        "--genome-fasta-directory",
+        directory.path().to_str().unwrap(),
+    ]);
+    assert_eq!(
+        ReferenceReaderUtils::try_parse_references(&matches).unwrap(),
+        vec![genome_a, genome_b]
+    );
+}
+
+#[test]
+fn test_decompress_references() {
+    let directory = tempfile::tempdir().unwrap();
+    let genome_a = write_genome(&directory, "a.fna", &[("contig_1", "ACGTACGT")]);
+    let genome_b = gzip(&write_genome(
+        &directory,
+        "b.fna",
+        &[("contig_1", "TTGGCCAA"), ("contig_2", "GATTACA")],
+    ));
+
+    // nothing is decompressed when no reference is compressed
+    let (references, tmp_dir) = ReferenceReaderUtils::decompress_references(vec![genome_a.clone()]);
+    assert_eq!(references, vec![genome_a.clone()]);
+    assert!(tmp_dir.is_none());
+
+    let (references, tmp_dir) =
+        ReferenceReaderUtils::decompress_references(vec![genome_a.clone(), genome_b]);
+    let tmp_dir = tmp_dir.unwrap();
+    assert_eq!(references[0], genome_a);
+    assert!(references[1].starts_with(tmp_dir.path().to_str().unwrap()));
+    // the decompressed copy keeps the genome name of the compressed file
+    assert_eq!(
+        ReferenceReaderUtils::genome_name_from_path(&references[1]),
+        "b"
+    );
+
+    let decompressed = std::fs::read_to_string(&references[1]).unwrap();
+    assert_eq!(
+        decompressed
+            .lines()
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<&str>>(),
+        vec![">contig_1", "TTGGCCAA", ">contig_2", "GATTACA"]
+    );
+
+    // the copies are removed along with the temporary directory
+    let decompressed_path = references[1].clone();
+    drop(tmp_dir);
+    assert!(!std::path::Path::new(&decompressed_path).exists());
+}
@@ -3,9 +3,16 @@
     non_snake_case
 )]
 
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
 use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::consensus_depths::{ConsensusDepths, DepthWindow};
 use lorikeet_genome::model::variant_context::{VariantContext, VariantType};
-use lorikeet_genome::reference::reference_writer::ReferenceWriter;
+use lorikeet_genome::reference::reference_reader::ReferenceReader;
+use lorikeet_genome::reference::reference_reader_utils::read_genome_fasta_files;
+use lorikeet_genome::reference::reference_writer::{
+    ConsensusAmbiguity, ConsensusOptions, ReferenceWriter,
+};
 
 #[test]
 fn test_indel_offsetting() {
@@ -106,3 +113,208 @@ fn test_indel_offsetting() {
 
 // TTTTTCGGTAATAAAATGATGATCGTTATTTGTATCTAACGACCCGTTA
 // TTTTTCGGTAATAAAATGATGACCCCCCCTCCGTATCTAACGACCCGTTA
+
+#[test]
+fn test_strain_alleles() {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"T", false);
+    let mut vc = VariantContext::build(0, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    vc.set_attribute(
+        VariantAnnotations::Strain.to_key().to_string(),
+        AttributeObject::VecUnsize(vec![0, 2]),
+    );
+
+    let alleles = ReferenceWriter::strain_alleles(&vc, &[0, 1, 2]);
+    assert_eq!(alleles, vec![&alt_allele, &ref_allele, &alt_allele]);
+
+    // contexts without any strain information carry the reference for every strain
+    let vc = VariantContext::build(0, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    let alleles = ReferenceWriter::strain_alleles(&vc, &[0, 1]);
+    assert_eq!(alleles, vec![&ref_allele, &ref_allele]);
+}
+
+fn snp_context_with_depths(ad: Vec<i32>) -> VariantContext {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"G", false);
+    let mut vc = VariantContext::build(0, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    let mut genotype = Genotype::build_from_alleles(vec![ref_allele, alt_allele], 0);
+    genotype.ad = ad;
+    vc.genotypes = GenotypesContext::new(vec![genotype]);
+    vc
+}
+
+#[test]
+fn test_consensus_masking() {
+    let iupac = ConsensusOptions::new(ConsensusAmbiguity::Iupac, 0.75, 3);
+    let n = ConsensusOptions::new(ConsensusAmbiguity::N, 0.75, 3);
+    let none = ConsensusOptions::new(ConsensusAmbiguity::None, 0.75, 3);
+
+    // A/G split evenly is ambiguous
+    let mut vc = snp_context_with_depths(vec![5, 5]);
+    assert_eq!(
+        iupac.masked_bases(&mut vc, 0),
+        Some((vec![b'R'], "ambiguous"))
+    );
+    assert_eq!(n.masked_bases(&mut vc, 0), Some((vec![b'N'], "ambiguous")));
+    assert_eq!(none.masked_bases(&mut vc, 0), None);
+
+    // confident positions are left alone
+    let mut vc = snp_context_with_depths(vec![1, 9]);
+    assert_eq!(iupac.masked_bases(&mut vc, 0), None);
+
+    // too little depth is always N
+    let mut vc = snp_context_with_depths(vec![1, 1]);
+    assert_eq!(
+        iupac.masked_bases(&mut vc, 0),
+        Some((vec![b'N'], "low_depth"))
+    );
+}
+
+#[test]
+fn test_majority_consensus_depths() {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"G", false);
+    let mut vc = VariantContext::build(0, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    let mut first = Genotype::build_from_alleles(vec![ref_allele.clone()], 0);
+    first.ad = vec![6, 0];
+    let mut second = Genotype::build_from_alleles(vec![alt_allele.clone()], 0);
+    second.ad = vec![1, 4];
+    let mut third = Genotype::build_from_alleles(vec![alt_allele.clone()], 0);
+    third.ad = vec![0, 4];
+    vc.genotypes = GenotypesContext::new(vec![first, second, third]);
+
+    let depths = ReferenceWriter::summed_allele_depths(&vc, &[0, 1, 2]);
+    assert_eq!(depths, vec![7, 8]);
+    assert_eq!(
+        vc.get_consensus_allele_from_depths(&depths),
+        Some(alt_allele)
+    );
+
+    let depths = ReferenceWriter::summed_allele_depths(&vc, &[0, 1]);
+    assert_eq!(depths, vec![7, 4]);
+    assert_eq!(
+        vc.get_consensus_allele_from_depths(&depths),
+        Some(ref_allele)
+    );
+    assert_eq!(vc.get_consensus_allele_from_depths(&[0, 0]), None);
+}
+
+#[test]
+fn test_consensus_sample_selection() {
+    let samples = vec!["reads/sample_1.bam", "reads/sample_2.bam", "sample_3"];
+    let options = ConsensusOptions::new(ConsensusAmbiguity::None, 0.75, 3);
+    assert_eq!(options.selected_sample_indices(&samples), vec![0, 1, 2]);
+
+    let options = options.with_samples(vec![
+        "sample_2.bam".to_string(),
+        "sample_3".to_string(),
+        "missing".to_string(),
+    ]);
+    assert_eq!(options.selected_sample_indices(&samples), vec![1, 2]);
+}
+
+fn depth_window(tid: usize, start: usize, depths: &[(i32, usize)]) -> DepthWindow {
+    let mut window = DepthWindow::new(tid, start, 1);
+    for (depth, length) in depths {
+        for _ in 0..*length {
+            window.push(0, *depth);
+        }
+    }
+    window
+}
+
+/// The sequence of each record of a FASTA file
+fn fasta_sequences(path: &str) -> Vec<Vec<u8>> {
+    let mut sequences: Vec<Vec<u8>> = Vec::new();
+    for line in std::fs::read_to_string(path).unwrap().lines() {
+        if line.starts_with('>') {
+            sequences.push(Vec::new());
+        } else {
+            sequences.last_mut().unwrap().extend(line.bytes());
+        }
+    }
+    sequences
+}
+
+#[test]
+fn test_low_depth_ranges() {
+    let depths = ConsensusDepths::new();
+    depths.record(depth_window(0, 10, &[(5, 10), (2, 5), (5, 5)]));
+    depths.record(depth_window(0, 40, &[(1, 5), (3, 5)]));
+
+    // positions before, between and after the windows have no reads
+    assert_eq!(
+        depths.low_depth_ranges(0, 60, &[0], 3),
+        vec![0..10, 20..25, 30..45, 50..60]
+    );
+    assert_eq!(depths.low_depth_ranges(1, 20, &[0], 3), vec![0..20]);
+}
+
+#[test]
+fn test_consensus_masks_uncovered_positions() {
+    let reference_path = "tests/data/two_contigs.fna";
+    let genomes_and_contigs = read_genome_fasta_files(&vec![reference_path], false);
+    let mut reference_reader =
+        ReferenceReader::new(&Some(reference_path.to_string()), genomes_and_contigs, 2);
+    for (tid, contig_name) in [b"contig_9_pilon".as_slice(), b"seq2".as_slice()]
+        .into_iter()
+        .enumerate()
+    {
+        reference_reader.update_ref_index_tids(0, tid);
+        reference_reader.add_target(contig_name, tid);
+    }
+    let genome_name = reference_reader.genomes_and_contigs.genomes[0].clone();
+    let reference = fasta_sequences(reference_path);
+
+    // the first contig is covered apart from a stretch at 100-150 and everything after 200,
+    // while the second contig has no reads at all
+    let depths = ConsensusDepths::new();
+    depths.record(depth_window(0, 0, &[(5, 100), (1, 50), (5, 50)]));
+
+    let directory = tempfile::tempdir().unwrap();
+    let output_prefix = directory.path().to_str().unwrap().to_string();
+    let mut reference_writer = ReferenceWriter::new(reference_reader, &output_prefix);
+    reference_writer.generate_consensus(
+        Vec::new(),
+        0,
+        &["sample"],
+        &ConsensusOptions::new(ConsensusAmbiguity::N, 0.75, 3),
+        Some(&depths),
+    );
+
+    let consensus = fasta_sequences(&format!(
+        "{}/{}_consensus_sample.fna",
+        output_prefix, genome_name
+    ));
+    assert_eq!(consensus.len(), 2);
+    assert_eq!(consensus[0].len(), reference[0].len());
+    assert_eq!(&consensus[0][..100], &reference[0][..100]);
+    assert!(consensus[0][100..150].iter().all(|base| *base == b'N'));
+    assert_eq!(&consensus[0][150..200], &reference[0][150..200]);
+    assert!(consensus[0][200..].iter().all(|base| *base == b'N'));
+    assert!(consensus[1].iter().all(|base| *base == b'N'));
+
+    let masked = std::fs::read_to_string(format!(
+        "{}/{}_consensus_sample_masked.bed",
+        output_prefix, genome_name
+    ))
+    .unwrap()
+    .lines()
+    .map(|line| line.splitn(2, '\t').nth(1).unwrap().to_string())
+    .collect::<Vec<String>>();
+    assert_eq!(
+        masked,
+        vec![
+            "100\t150\tlow_depth".to_string(),
+            format!("200\t{}\tlow_depth", reference[0].len()),
+            format!("0\t{}\tlow_depth", reference[1].len()),
+        ]
+    );
+
+    // the majority consensus of the selected samples is named apart from the samples
+    let majority = fasta_sequences(&format!(
+        "{}/{}_majority_consensus.fna",
+        output_prefix, genome_name
+    ));
+    assert_eq!(majority, consensus);
+}
@@ -0,0 +1,86 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::repeat_context::RepeatContext;
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+
+fn variant(start: usize, ref_allele: &[u8], alt_allele: &[u8]) -> VariantContext {
+    VariantContext::build(
+        0,
+        start,
+        start + ref_allele.len() - 1,
+        vec![
+            ByteArrayAllele::new(ref_allele, true),
+            ByteArrayAllele::new(alt_allele, false),
+        ],
+    )
+}
+
+#[test]
+fn test_run_length_at() {
+    let reference = b"GAAATcc";
+    assert_eq!(RepeatContext::run_length_at(reference, 0), 1);
+    assert_eq!(RepeatContext::run_length_at(reference, 2), 3);
+    // runs are case insensitive
+    assert_eq!(RepeatContext::run_length_at(b"GAaAT", 1), 3);
+    assert_eq!(RepeatContext::run_length_at(reference, 5), 2);
+    assert_eq!(RepeatContext::run_length_at(reference, 7), 0);
+}
+
+#[test]
+fn test_repeat_unit_of_a_deletion() {
+    // one copy of CA is deleted from (CA)4
+    let reference = b"GCACACACAT";
+    let mut vc = variant(0, b"GCA", b"G");
+
+    assert!(!RepeatContext::new(None).annotate(&mut vc, reference));
+    assert_eq!(vc.attributes.get("HRUN"), Some(&AttributeObject::I32(1)));
+    assert_eq!(
+        vc.attributes.get("RU"),
+        Some(&AttributeObject::String("CA".to_string()))
+    );
+    assert_eq!(
+        vc.attributes.get("RPA"),
+        Some(&AttributeObject::VecI32(vec![4, 3]))
+    );
+}
+
+#[test]
+fn test_repeat_unit_of_a_homopolymer_insertion() {
+    // an A is inserted into a run of three
+    let reference = b"GAAATC";
+    let mut vc = variant(0, b"G", b"GA");
+
+    assert!(!RepeatContext::new(Some(4)).annotate(&mut vc, reference));
+    assert_eq!(vc.attributes.get("HRUN"), Some(&AttributeObject::I32(3)));
+    assert_eq!(
+        vc.attributes.get("RU"),
+        Some(&AttributeObject::String("A".to_string()))
+    );
+    assert_eq!(
+        vc.attributes.get("RPA"),
+        Some(&AttributeObject::VecI32(vec![3, 4]))
+    );
+    assert!(!vc.is_filtered());
+
+    // runs as long as the threshold are filtered
+    let mut vc = variant(0, b"G", b"GA");
+    assert!(RepeatContext::new(Some(3)).annotate(&mut vc, reference));
+    assert!(vc.is_filtered());
+}
+
+#[test]
+fn test_snvs_have_no_repeat_unit() {
+    let reference = b"GAAATC";
+    let mut vc = variant(4, b"T", b"A");
+
+    // SNVs are never filtered, however long the run next to them
+    assert!(!RepeatContext::new(Some(2)).annotate(&mut vc, reference));
+    assert_eq!(vc.attributes.get("HRUN"), Some(&AttributeObject::I32(3)));
+    assert_eq!(vc.attributes.get("RU"), None);
+    assert_eq!(vc.attributes.get("RPA"), None);
+}
@@ -0,0 +1,71 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::concordance::replicate_calibration::ReplicateCalibration;
+use lorikeet_genome::genotype::genotype_builder::{Genotype, GenotypesContext};
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+
+fn site(pos: usize, qual: f64, depths: &[[i32; 2]]) -> VariantContext {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"G", false);
+    let mut vc = VariantContext::build(0, pos, pos, vec![ref_allele.clone(), alt_allele]);
+    vc.log10_p_error(qual / -10.0);
+    vc.genotypes = GenotypesContext::new(
+        depths
+            .iter()
+            .enumerate()
+            .map(|(sample_index, ad)| {
+                let mut genotype =
+                    Genotype::build_from_alleles(vec![ref_allele.clone()], sample_index);
+                genotype.ad = ad.to_vec();
+                genotype
+            })
+            .collect(),
+    );
+    vc
+}
+
+#[test]
+fn test_parse_replicate_groups() {
+    let samples = ["a_1", "a_2", "b_1", "b_2"];
+    let groups = ["a_1,a_2".to_string(), "b_2,b_1".to_string()];
+    assert_eq!(
+        ReplicateCalibration::parse_groups(&groups, &samples).unwrap(),
+        vec![vec![0, 1], vec![3, 2]]
+    );
+    assert!(ReplicateCalibration::parse_groups(&["a_1".to_string()], &samples).is_err());
+    assert!(ReplicateCalibration::parse_groups(&["a_1,c_1".to_string()], &samples).is_err());
+}
+
+#[test]
+fn test_replicate_calibration() {
+    let mut calibration = ReplicateCalibration::new(vec![vec![0, 1]], 5);
+    let mut contexts = Vec::new();
+    // concordant high quality calls
+    for pos in 0..30 {
+        contexts.push(site(pos, 105.0, &[[10, 10], [12, 8]]));
+    }
+    // low quality calls that are only seen in one replicate half of the time
+    for pos in 30..50 {
+        let second = if pos % 2 == 0 { [20, 0] } else { [10, 10] };
+        contexts.push(site(pos, 15.0, &[[10, 10], second]));
+    }
+    // sites without coverage in the second replicate are not compared
+    contexts.push(site(50, 15.0, &[[10, 10], [1, 1]]));
+
+    calibration.estimate(&contexts);
+    assert_eq!(calibration.bins[10].calls, 60);
+    assert_eq!(calibration.bins[10].discordant, 0);
+    assert_eq!(calibration.bins[1].calls, 30);
+    assert_eq!(calibration.bins[1].discordant, 10);
+    assert!((calibration.false_positive_rate().unwrap() - 10.0 / 90.0).abs() < 1e-9);
+
+    let calibrated = calibration.calibrate(&mut contexts);
+    assert_eq!(calibrated, 51);
+    let expected = -10.0 * (10.5f64 / 31.0).log10();
+    assert!((contexts[30].get_phred_scaled_qual() - expected).abs() < 1e-9);
+    assert!(contexts[0].get_phred_scaled_qual() > contexts[30].get_phred_scaled_qual());
+}
@@ -0,0 +1,129 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::genotype_builder::{Genotype, GenotypesContext};
+use lorikeet_genome::haplotype::haplotype_clustering_engine::HaplotypeClusteringEngine;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::reference::genome_separator::GenomeSeparator;
+use lorikeet_genome::reference::reference_reader_utils::{
+    read_genome_definition_file, RepliconType,
+};
+use std::io::Write;
+
+/// A SNP with the given reference and alternate depths in each sample
+fn snp_with_depths(depths: &[(i32, i32)]) -> VariantContext {
+    let mut vc = VariantContext::build(
+        0,
+        10,
+        10,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+        ],
+    );
+    vc.genotypes = GenotypesContext::new(
+        depths
+            .iter()
+            .map(|(ref_depth, alt_depth)| Genotype::build_from_ads(1, vec![*ref_depth, *alt_depth]))
+            .collect(),
+    );
+    vc
+}
+
+#[test]
+fn test_replicon_types_from_genome_definition() {
+    let mut definition = tempfile::NamedTempFile::new().unwrap();
+    writeln!(definition, "genome_1\tcontig_1").unwrap();
+    writeln!(definition, "genome_1\tcontig_2\tchromosome").unwrap();
+    writeln!(definition, "genome_1\tplasmid_1\tplasmid").unwrap();
+    writeln!(definition, "genome_2\tphage_1\tmobile-element").unwrap();
+
+    let genomes_and_contigs = read_genome_definition_file(
+        definition.path().to_str().unwrap(),
+        GenomeSeparator::default(),
+    );
+    assert_eq!(genomes_and_contigs.contigs, 4);
+    assert_eq!(
+        genomes_and_contigs.replicon_type("genome_1~plasmid_1"),
+        RepliconType::Plasmid
+    );
+    assert_eq!(
+        genomes_and_contigs.replicon_type("genome_2~phage_1"),
+        RepliconType::MobileElement
+    );
+    assert_eq!(
+        genomes_and_contigs.replicon_type("genome_1~contig_2"),
+        RepliconType::Chromosome
+    );
+    // contigs without a replicon type are chromosomal
+    assert_eq!(
+        genomes_and_contigs.replicon_type("genome_1~contig_1"),
+        RepliconType::Chromosome
+    );
+}
+
+#[test]
+fn test_partition_by_replicon_type() {
+    let partitions = HaplotypeClusteringEngine::partition_by_replicon_type(vec![
+        RepliconType::Plasmid,
+        RepliconType::Chromosome,
+        RepliconType::MobileElement,
+        RepliconType::Chromosome,
+        RepliconType::Plasmid,
+    ]);
+    // chromosomes come first, and each partition keeps the order of the variants
+    assert_eq!(
+        partitions,
+        vec![
+            (RepliconType::Chromosome, vec![1, 3]),
+            (RepliconType::Plasmid, vec![0, 4]),
+            (RepliconType::MobileElement, vec![2]),
+        ]
+    );
+
+    assert!(HaplotypeClusteringEngine::partition_by_replicon_type(Vec::new()).is_empty());
+}
+
+#[test]
+fn test_replicon_copy_numbers() {
+    // the second sample has no reads on the chromosome
+    let chromosome_variants = vec![
+        snp_with_depths(&[(10, 10), (0, 0)]),
+        snp_with_depths(&[(5, 30), (0, 0)]),
+    ];
+    let plasmid_variants = vec![
+        snp_with_depths(&[(0, 60), (2, 8)]),
+        snp_with_depths(&[(0, 40), (2, 12)]),
+    ];
+
+    let chromosome_depths = HaplotypeClusteringEngine::mean_sample_depths(
+        chromosome_variants.iter(),
+        2,
+        HaplotypeClusteringEngine::alt_depth,
+    );
+    assert_eq!(chromosome_depths, vec![20.0, 0.0]);
+    let plasmid_depths = HaplotypeClusteringEngine::mean_sample_depths(
+        plasmid_variants.iter(),
+        2,
+        HaplotypeClusteringEngine::alt_depth,
+    );
+    assert_eq!(plasmid_depths, vec![50.0, 10.0]);
+
+    // the plasmid is present at two and a half copies per chromosome
+    assert_eq!(
+        HaplotypeClusteringEngine::copy_numbers(&plasmid_depths, &chromosome_depths),
+        vec![Some(2.5), None]
+    );
+
+    assert_eq!(
+        HaplotypeClusteringEngine::mean_sample_depths(
+            std::iter::empty::<&VariantContext>(),
+            2,
+            HaplotypeClusteringEngine::alt_depth
+        ),
+        vec![0.0, 0.0]
+    );
+}
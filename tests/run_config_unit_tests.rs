@@ -0,0 +1,177 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use clap::{Arg, ArgAction, Command};
+use lorikeet_genome::processing::run_config::RunConfig;
+use std::ffi::OsString;
+use toml::value::Value;
+
+fn test_command() -> Command {
+    Command::new("lorikeet").subcommand(
+        Command::new("call")
+            .arg(
+                Arg::new("bam-files")
+                    .short('b')
+                    .long("bam-files")
+                    .num_args(1..),
+            )
+            .arg(
+                Arg::new("kmer-sizes")
+                    .long("kmer-sizes")
+                    .action(ArgAction::Append)
+                    .value_parser(clap::value_parser!(usize)),
+            )
+            .arg(
+                Arg::new("threads")
+                    .short('t')
+                    .long("threads")
+                    .value_parser(clap::value_parser!(usize))
+                    .default_value("8"),
+            )
+            .arg(
+                Arg::new("min-qual")
+                    .long("min-qual")
+                    .value_parser(clap::value_parser!(f64)),
+            )
+            .arg(
+                Arg::new("calculate-fst")
+                    .long("calculate-fst")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(Arg::new("config").long("config")),
+    )
+}
+
+fn os_args(args: &[&str]) -> Vec<OsString> {
+    args.iter().map(OsString::from).collect()
+}
+
+#[test]
+fn test_config_args() {
+    let command = test_command();
+    let call = command.find_subcommand("call").unwrap();
+    let table = RunConfig::parse(
+        "bam-files = [\"a.bam\", \"b.bam\"]\n\
+        kmer-sizes = [17, 25]\n\
+        threads = 16\n\
+        calculate-fst = true\n",
+    )
+    .unwrap();
+
+    let config_args = RunConfig::config_args(call, &table, &[]).unwrap();
+    let matches = call
+        .clone()
+        .try_get_matches_from(
+            std::iter::once(OsString::from("call")).chain(config_args.into_iter()),
+        )
+        .unwrap();
+    assert_eq!(
+        matches
+            .get_many::<String>("bam-files")
+            .unwrap()
+            .cloned()
+            .collect::<Vec<String>>(),
+        vec!["a.bam".to_string(), "b.bam".to_string()]
+    );
+    assert_eq!(
+        matches
+            .get_many::<usize>("kmer-sizes")
+            .unwrap()
+            .copied()
+            .collect::<Vec<usize>>(),
+        vec![17, 25]
+    );
+    assert_eq!(*matches.get_one::<usize>("threads").unwrap(), 16);
+    assert!(matches.get_flag("calculate-fst"));
+
+    // flags set to false are left unset
+    let table = RunConfig::parse("calculate-fst = false\n").unwrap();
+    assert!(RunConfig::config_args(call, &table, &[])
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_command_line_overrides_config() {
+    let command = test_command();
+    let directory = tempfile::tempdir().unwrap();
+    let config = directory.path().join("run.toml");
+    std::fs::write(&config, "threads = 16\nmin-qual = 30.5\n").unwrap();
+
+    let args = RunConfig::expand_args(
+        &command,
+        os_args(&[
+            "lorikeet",
+            "call",
+            "--config",
+            config.to_str().unwrap(),
+            "-t",
+            "4",
+        ]),
+    )
+    .unwrap();
+    let matches = command.clone().try_get_matches_from(args).unwrap();
+    let call = matches.subcommand_matches("call").unwrap();
+    assert_eq!(*call.get_one::<usize>("threads").unwrap(), 4);
+    assert_eq!(*call.get_one::<f64>("min-qual").unwrap(), 30.5);
+
+    // without a config the command line is unchanged
+    let args = os_args(&["lorikeet", "call", "-t", "4"]);
+    assert_eq!(
+        RunConfig::expand_args(&command, args.clone()).unwrap(),
+        args
+    );
+}
+
+#[test]
+fn test_invalid_config() {
+    let command = test_command();
+    let call = command.find_subcommand("call").unwrap();
+    // unknown options are rejected rather than silently ignored
+    let table = RunConfig::parse("kmer-size = 21\n").unwrap();
+    assert!(RunConfig::config_args(call, &table, &[]).is_err());
+    let table = RunConfig::parse("[call]\nthreads = 4\n").unwrap();
+    assert!(RunConfig::config_args(call, &table, &[]).is_err());
+    let table = RunConfig::parse("config = \"other.toml\"\n").unwrap();
+    assert!(RunConfig::config_args(call, &table, &[]).is_err());
+    assert!(RunConfig::parse("threads = \n").is_err());
+}
+
+#[test]
+fn test_write_run_config() {
+    let command = test_command();
+    let matches = command
+        .try_get_matches_from(os_args(&[
+            "lorikeet",
+            "call",
+            "-b",
+            "a.bam",
+            "b.bam",
+            "--min-qual",
+            "30.5",
+            "--calculate-fst",
+        ]))
+        .unwrap();
+    let call = matches.subcommand_matches("call").unwrap();
+
+    let table = RunConfig::to_toml(call);
+    assert_eq!(
+        table.get("bam-files"),
+        Some(&Value::Array(vec![
+            Value::String("a.bam".to_string()),
+            Value::String("b.bam".to_string())
+        ]))
+    );
+    assert_eq!(table.get("min-qual"), Some(&Value::Float(30.5)));
+    assert_eq!(table.get("calculate-fst"), Some(&Value::Boolean(true)));
+    // defaults are not written
+    assert!(table.get("threads").is_none());
+
+    let directory = tempfile::tempdir().unwrap();
+    let path = RunConfig::write(call, "call", directory.path().to_str().unwrap()).unwrap();
+    assert_eq!(path.file_name().unwrap(), RunConfig::FILE_NAME);
+    let written = RunConfig::parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written, table);
+}
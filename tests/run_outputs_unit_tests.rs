@@ -0,0 +1,35 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::run_outputs::RunOutputs;
+
+#[test]
+fn test_single_mode_outputs() {
+    let outputs = RunOutputs::for_mode("genotype");
+    assert!(!outputs.is_combined());
+    assert_eq!(outputs.pathways(), vec!["genotype"]);
+    assert_eq!(
+        outputs.output_prefix("out/genome", "genotype"),
+        "out/genome"
+    );
+    assert!(!outputs.needed_after("genotype"));
+}
+
+#[test]
+fn test_combined_outputs() {
+    let outputs = RunOutputs::new(true, true, true, true);
+    assert_eq!(outputs.pathways(), vec!["call", "genotype", "consensus"]);
+    assert_eq!(
+        outputs.output_prefix("out/genome", "consensus"),
+        "out/genome/consensus"
+    );
+    assert!(outputs.needed_after("call"));
+    assert!(outputs.needed_after("genotype"));
+    assert!(!outputs.needed_after("consensus"));
+
+    let outputs = RunOutputs::new(true, true, false, true);
+    assert_eq!(outputs.pathways(), vec!["call", "genotype"]);
+    assert!(!outputs.needed_after("genotype"));
+}
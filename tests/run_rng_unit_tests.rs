@@ -0,0 +1,43 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::run_rng::RunRng;
+use rand::Rng;
+
+#[test]
+fn test_derived_seeds() {
+    assert_eq!(
+        RunRng::derive_seed(42, "qd_jitter", 7),
+        RunRng::derive_seed(42, "qd_jitter", 7)
+    );
+    assert_ne!(
+        RunRng::derive_seed(42, "qd_jitter", 7),
+        RunRng::derive_seed(43, "qd_jitter", 7)
+    );
+    assert_ne!(
+        RunRng::derive_seed(42, "qd_jitter", 7),
+        RunRng::derive_seed(42, "qd_jitter", 8)
+    );
+    assert_ne!(
+        RunRng::derive_seed(42, "qd_jitter", 7),
+        RunRng::derive_seed(42, "downsampling", 7)
+    );
+}
+
+#[test]
+fn test_component_generators_follow_run_seed() {
+    RunRng::init(1234);
+    assert_eq!(RunRng::seed(), 1234);
+
+    let first = (0..5)
+        .map(|_| RunRng::for_component("test", 1).gen::<u64>())
+        .collect::<Vec<u64>>();
+    assert!(first.iter().all(|value| *value == first[0]));
+
+    let mut rng = RunRng::for_component("test", 1);
+    let draws = (0..5).map(|_| rng.gen::<u64>()).collect::<Vec<u64>>();
+    let mut rng = RunRng::for_component("test", 1);
+    assert_eq!(draws, (0..5).map(|_| rng.gen::<u64>()).collect::<Vec<u64>>());
+}
@@ -0,0 +1,81 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::ani_calculator::ani_calculator::ANICalculator;
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::processing::sample_addition::SampleAddition;
+use std::collections::HashMap;
+
+fn context(tid: usize, start: usize, reference: &[u8], alternate: &[u8]) -> VariantContext {
+    VariantContext::build(
+        tid,
+        start,
+        start + reference.len() - 1,
+        vec![
+            ByteArrayAllele::new(reference, true),
+            ByteArrayAllele::new(alternate, false),
+        ],
+    )
+}
+
+#[test]
+fn test_ani_tables_round_trip() {
+    let output = tempfile::tempdir().unwrap();
+    let output_prefix = output.path().to_str().unwrap();
+    let mut ani_calculator = ANICalculator::new(2);
+    ani_calculator.calculate(&mut [], 1000, None, 0.0, 0.0, 1);
+    ani_calculator.table_mut("consensus_ani").unwrap()[[0, 1]] = 0.99;
+    ani_calculator.write_tables(output_prefix, &["sample_1", "sample_2"], "genome");
+
+    let table = ANICalculator::read_ani_table(&format!(
+        "{}/genome_consensus_ani.tsv",
+        output_prefix
+    ))
+    .unwrap();
+    assert_eq!(table.shape(), &[2, 2]);
+    assert_eq!(table[[0, 0]], 1.0);
+    assert!((table[[0, 1]] - 0.99).abs() < 1e-6);
+    assert!(ANICalculator::read_ani_table(&format!("{}/missing.tsv", output_prefix)).is_err());
+}
+
+#[test]
+fn test_apply_existing_strains() {
+    let contigs = vec![("contig_1".to_string(), Some(1000)), ("contig_2".to_string(), Some(500))];
+    let mut strains = HashMap::new();
+    strains.insert(
+        ("contig_2".to_string(), 10, b"A".to_vec()),
+        (b"T".to_vec(), vec![0, 2]),
+    );
+    strains.insert(
+        ("contig_1".to_string(), 20, b"C".to_vec()),
+        (b"G".to_vec(), vec![1]),
+    );
+
+    let mut contexts = vec![
+        context(1, 10, b"A", b"T"),
+        // the alternate allele differs from the one the strains were assigned to
+        context(0, 20, b"C", b"A"),
+        context(0, 30, b"G", b"T"),
+    ];
+    let n_strains = SampleAddition::apply_strains(&mut contexts, &contigs, &strains);
+
+    assert_eq!(n_strains, 3);
+    match contexts[0]
+        .attributes
+        .get(VariantAnnotations::Strain.to_key())
+    {
+        Some(AttributeObject::VecUnsize(strain_ids)) => assert_eq!(strain_ids, &vec![0, 2]),
+        _ => panic!("Strains were not applied"),
+    }
+    assert!(!contexts[1]
+        .attributes
+        .contains_key(VariantAnnotations::Strain.to_key()));
+    assert!(!contexts[2]
+        .attributes
+        .contains_key(VariantAnnotations::Strain.to_key()));
+}
@@ -0,0 +1,176 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::scatter_gather::{ScatterShard, ShardGatherer};
+use lorikeet_genome::utils::errors::BirdToolError;
+use rust_htslib::bcf::{Format, Header, Read, Reader, Writer};
+use std::path::Path;
+
+/// Writes a VCF file with a SNV at each of the given positions of two contigs
+fn write_shard(path: &Path, positions: &[(u32, i64)]) {
+    let mut header = Header::new();
+    header.push_record(b"##contig=<ID=contig_1,length=1000>");
+    header.push_record(b"##contig=<ID=contig_2,length=1000>");
+    let mut writer = Writer::from_path(
+        path,
+        &header,
+        !path.to_string_lossy().ends_with(".gz"),
+        Format::Vcf,
+    )
+    .unwrap();
+    for (rid, pos) in positions {
+        let mut record = writer.empty_record();
+        record.set_rid(Some(*rid));
+        record.set_pos(*pos);
+        record.set_alleles(&[b"A", b"T"]).unwrap();
+        writer.write(&record).unwrap();
+    }
+}
+
+#[test]
+fn test_invalid_shards() {
+    assert!(ScatterShard::new(0, 0).is_err());
+    assert!(ScatterShard::new(4, 4).is_err());
+    assert!(ScatterShard::new(4, 3).is_ok());
+    assert!(matches!(
+        ScatterShard::new(4, 4),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_chunk_offsets() {
+    // contig 1 is too short to be called, so it has no chunks
+    let target_lens = vec![(2, 2500), (0, 1000), (1, 100)];
+    let offsets = ScatterShard::chunk_offsets(&target_lens, 1000, 500);
+    assert_eq!(offsets[&0], 0);
+    assert_eq!(offsets[&1], 1);
+    assert_eq!(offsets[&2], 1);
+}
+
+#[test]
+fn test_shards_partition_chunks() {
+    let shards = (0..3)
+        .map(|shard_index| ScatterShard::new(3, shard_index).unwrap())
+        .collect::<Vec<ScatterShard>>();
+    for chunk_ordinal in 0..20 {
+        assert_eq!(
+            shards
+                .iter()
+                .filter(|shard| shard.contains(chunk_ordinal))
+                .count(),
+            1
+        );
+    }
+}
+
+#[test]
+fn test_file_stem_round_trip() {
+    let shard = ScatterShard::new(8, 5).unwrap();
+    let file_stem = shard.file_stem("genome.shard_like");
+    assert_eq!(file_stem, "genome.shard_like.shard_5_of_8");
+    assert_eq!(
+        ScatterShard::parse_file_stem(&file_stem),
+        Some(("genome.shard_like".to_string(), shard))
+    );
+    assert_eq!(ScatterShard::parse_file_stem("genome"), None);
+    assert_eq!(ScatterShard::parse_file_stem("genome.shard_8_of_8"), None);
+}
+
+#[test]
+fn test_check_complete() {
+    let shard = |shard_index| {
+        (
+            ScatterShard::new(3, shard_index).unwrap(),
+            format!("genome.shard_{}_of_3.vcf", shard_index),
+        )
+    };
+    assert!(ShardGatherer::check_complete("genome.vcf", &[shard(0), shard(1), shard(2)]).is_ok());
+    assert!(ShardGatherer::check_complete("genome.vcf", &[shard(0), shard(2)]).is_err());
+    assert!(ShardGatherer::check_complete("genome.vcf", &[shard(0), shard(0), shard(2)]).is_err());
+}
+
+#[test]
+fn test_shard_from_args() {
+    let command = clap::Command::new("call")
+        .arg(
+            clap::Arg::new("scatter")
+                .long("scatter")
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            clap::Arg::new("shard-index")
+                .long("shard-index")
+                .value_parser(clap::value_parser!(usize)),
+        );
+    let from_args = |args: Vec<&str>| {
+        ScatterShard::from_args(&command.clone().try_get_matches_from(args).unwrap())
+    };
+
+    assert_eq!(from_args(vec!["call"]).unwrap(), None);
+    assert_eq!(
+        from_args(vec!["call", "--scatter", "4", "--shard-index", "2"]).unwrap(),
+        Some(ScatterShard::new(4, 2).unwrap())
+    );
+    // an index outside of the scatter is a configuration error rather than a panic
+    assert!(matches!(
+        from_args(vec!["call", "--scatter", "4", "--shard-index", "4"]),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_find_compressed_shards() {
+    let directory = tempfile::tempdir().unwrap();
+    let genome_directory = directory.path().join("genome");
+    std::fs::create_dir_all(&genome_directory).unwrap();
+    for shard_index in 0..2 {
+        write_shard(
+            &genome_directory.join(format!("genome.shard_{}_of_2.vcf.gz", shard_index)),
+            &[(0, 10)],
+        );
+    }
+    write_shard(&genome_directory.join("other.shard_0_of_1.vcf"), &[(0, 10)]);
+
+    let shards = ShardGatherer::find_shards(directory.path().to_str().unwrap()).unwrap();
+    let gathered_path = genome_directory.join("genome.vcf.gz");
+    let genome_shards = shards.get(gathered_path.to_str().unwrap()).unwrap();
+    assert_eq!(genome_shards.len(), 2);
+    assert!(ShardGatherer::check_complete(gathered_path.to_str().unwrap(), genome_shards).is_ok());
+    assert!(shards.contains_key(genome_directory.join("other.vcf").to_str().unwrap()));
+    assert_eq!(shards.len(), 2);
+}
+
+#[test]
+fn test_gather_merges_shards_by_position() {
+    let directory = tempfile::tempdir().unwrap();
+    let shard_paths = vec![
+        directory.path().join("genome.shard_0_of_2.vcf"),
+        directory.path().join("genome.shard_1_of_2.vcf.gz"),
+    ];
+    write_shard(&shard_paths[0], &[(0, 10), (0, 300), (1, 50)]);
+    write_shard(&shard_paths[1], &[(0, 20), (1, 5), (1, 500)]);
+    let shard_paths = shard_paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect::<Vec<String>>();
+
+    let output_path = directory.path().join("genome.vcf.gz");
+    let n_records = ShardGatherer::gather(&shard_paths, output_path.to_str().unwrap()).unwrap();
+    assert_eq!(n_records, 6);
+
+    let mut reader = Reader::from_path(&output_path).unwrap();
+    let positions = reader
+        .records()
+        .map(|record| {
+            let record = record.unwrap();
+            (record.rid().unwrap(), record.pos())
+        })
+        .collect::<Vec<(u32, i64)>>();
+    assert_eq!(
+        positions,
+        vec![(0, 10), (0, 20), (0, 300), (1, 5), (1, 50), (1, 500)]
+    );
+}
@@ -0,0 +1,33 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::sequence_complexity::SequenceComplexity;
+
+#[test]
+fn test_entropy() {
+    assert_eq!(SequenceComplexity::entropy(b"AAAAAAAA"), 0.0);
+    assert!((SequenceComplexity::entropy(b"ACGTACGT") - 2.0).abs() < 1e-12);
+    assert!((SequenceComplexity::entropy(b"AAAATTTT") - 1.0).abs() < 1e-12);
+    // bases other than A, C, G and T are ignored
+    assert!((SequenceComplexity::entropy(b"ACNNGT") - 2.0).abs() < 1e-12);
+    assert_eq!(SequenceComplexity::entropy(b"NNNN"), 0.0);
+}
+
+#[test]
+fn test_dust_score() {
+    // 62 identical triplets give 62 * 61 / 2 pairs over 61
+    let homopolymer = vec![b'A'; 64];
+    assert!((SequenceComplexity::dust_score(&homopolymer) - 31.0).abs() < 1e-12);
+
+    let dinucleotide = b"ATATATATATATATATATATATATATATATATATATATATATATATATATATATATATATATAT";
+    let complex = b"ACGGTCATGCTAGCTTAGCCGATCGATTGCAACGTGGCTATCCGATGAGCTAAGTCCTGAACGT";
+    assert!(SequenceComplexity::dust_score(dinucleotide) > 10.0);
+    assert!(SequenceComplexity::dust_score(complex) < 2.0);
+    assert!(
+        SequenceComplexity::dust_score(&homopolymer) > SequenceComplexity::dust_score(dinucleotide)
+    );
+
+    assert_eq!(SequenceComplexity::dust_score(b"ACG"), 0.0);
+}
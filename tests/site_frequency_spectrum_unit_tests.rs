@@ -0,0 +1,50 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::model::site_frequency_spectrum::SiteFrequencySpectrum;
+
+fn spectrum(n_chromosomes: usize, alternate_counts: &[usize]) -> SiteFrequencySpectrum {
+    let mut spectrum = SiteFrequencySpectrum::new(n_chromosomes);
+    for alternate_count in alternate_counts {
+        spectrum.add_site(*alternate_count);
+    }
+    spectrum
+}
+
+#[test]
+fn test_unfolded_and_folded_spectra() {
+    let spectrum = spectrum(4, &[1, 1, 3, 2, 4, 0, 5]);
+    assert_eq!(spectrum.unfolded(), &[1, 2, 1, 1, 1]);
+    // minor allele counts, with the middle bin not folded onto itself
+    assert_eq!(spectrum.folded(), vec![2, 3, 1]);
+    assert_eq!(spectrum.counted_sites(), 6);
+    // more alternate alleles than chromosomes
+    assert_eq!(spectrum.skipped_sites(), 1);
+}
+
+#[test]
+fn test_spectrum_summary() {
+    // n = 4: a1 = 11 / 6, pairs = 6
+    let spectrum = spectrum(4, &[1, 2, 3, 3, 0]);
+    let summary = spectrum.summary();
+    assert_eq!(summary.segregating_sites, 4);
+    assert_eq!(summary.singletons, 3);
+    assert!((summary.watterson_theta - 4.0 / (11.0 / 6.0)).abs() < 1e-9);
+    // (1 * 3 + 2 * 2 + 2 * (3 * 1)) / 6
+    assert!((summary.pi - 13.0 / 6.0).abs() < 1e-9);
+    // (1 + 4 + 2 * 9) / 6
+    assert!((summary.theta_h - 23.0 / 6.0).abs() < 1e-9);
+    assert!((summary.fay_and_wus_h - (13.0 - 23.0) / 6.0).abs() < 1e-9);
+    assert!(summary.tajimas_d.is_finite());
+}
+
+#[test]
+fn test_spectrum_summary_without_segregating_sites() {
+    let summary = spectrum(6, &[0, 6]).summary();
+    assert_eq!(summary.segregating_sites, 0);
+    assert_eq!(summary.watterson_theta, 0.0);
+    assert_eq!(summary.pi, 0.0);
+    assert!(summary.tajimas_d.is_nan());
+}
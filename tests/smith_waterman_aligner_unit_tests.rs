@@ -34,8 +34,10 @@ use lorikeet_genome::pair_hmm::pair_hmm_likelihood_calculation_engine::{
 use lorikeet_genome::reads::cigar_utils::{
     CigarUtils, ALIGNMENT_TO_BEST_HAPLOTYPE_SW_PARAMETERS, NEW_SW_PARAMETERS,
 };
+use lorikeet_genome::smith_waterman::bindings::SWParameters;
 use lorikeet_genome::smith_waterman::smith_waterman_aligner::{
-    SmithWatermanAligner, SmithWatermanAlignmentResult, ORIGINAL_DEFAULT, STANDARD_NGS,
+    AssemblySWParameters, SmithWatermanAligner, SmithWatermanAlignmentResult, ORIGINAL_DEFAULT,
+    STANDARD_NGS,
 };
 
 
@@ -1101,3 +1103,24 @@ fn assert_cigar_are_equal(reference: &[u8], read: &[u8], cigar_string: Vec<Cigar
         }
     });
 }
+
+#[test]
+fn test_assembly_sw_parameter_presets() {
+    assert_eq!(
+        AssemblySWParameters::preset("short-read"),
+        Some(AssemblySWParameters::short_read())
+    );
+    assert_eq!(
+        AssemblySWParameters::preset("long-read"),
+        Some(AssemblySWParameters::long_read())
+    );
+    assert_eq!(AssemblySWParameters::preset("ont"), None);
+
+    // given weights replace those of the preset in both parameter sets
+    let parameters = AssemblySWParameters::short_read().with_weights(None, None, Some(-40), None);
+    assert_eq!(parameters.dangling_end, SWParameters::new(25, -50, -40, -6));
+    assert_eq!(
+        parameters.haplotype_to_reference,
+        SWParameters::new(200, -150, -40, -11)
+    );
+}
@@ -0,0 +1,111 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::assembly::soft_clip_rescue::{ClipSide, ClippedSequence, SoftClipRescue};
+use lorikeet_genome::model::byte_array_allele::Allele;
+use lorikeet_genome::utils::simple_interval::{Locatable, SimpleInterval};
+
+static REFERENCE: &[u8] = b"GATTACAGGCTTACCGATGCATCGTAGCTAGGCTAACGTT";
+static INSERTED: &[u8] = b"TTTTGGGGCCCCAAAA";
+
+fn concat(a: &[u8], b: &[u8]) -> Vec<u8> {
+    let mut joined = a.to_vec();
+    joined.extend_from_slice(b);
+    joined
+}
+
+#[test]
+fn test_resolve_insertion() {
+    // a right clip that spans the insertion reads back into the reference
+    let spanning_right = concat(INSERTED, &REFERENCE[20..30]);
+    assert_eq!(
+        SoftClipRescue::resolve_insertion(Some(spanning_right.as_slice()), None, REFERENCE, 20),
+        Some(INSERTED.to_vec())
+    );
+
+    // as does a left clip that starts upstream of the insertion
+    let spanning_left = concat(&REFERENCE[10..20], INSERTED);
+    assert_eq!(
+        SoftClipRescue::resolve_insertion(None, Some(spanning_left.as_slice()), REFERENCE, 20),
+        Some(INSERTED.to_vec())
+    );
+
+    // otherwise the two halves are joined by their overlap
+    assert_eq!(
+        SoftClipRescue::resolve_insertion(
+            Some(&INSERTED[..12]),
+            Some(&INSERTED[4..]),
+            REFERENCE,
+            20
+        ),
+        Some(INSERTED.to_vec())
+    );
+    assert_eq!(
+        SoftClipRescue::resolve_insertion(
+            Some(&INSERTED[..10]),
+            Some(&INSERTED[6..]),
+            REFERENCE,
+            20
+        ),
+        None
+    );
+
+    // clips that just continue the reference are not insertions
+    assert_eq!(
+        SoftClipRescue::resolve_insertion(Some(&REFERENCE[20..32]), None, REFERENCE, 20),
+        None
+    );
+}
+
+#[test]
+fn test_clip_consensus() {
+    let clips = vec![
+        ClippedSequence::new(120, ClipSide::Left, b"ACGTACGT".to_vec()),
+        ClippedSequence::new(120, ClipSide::Left, b"TTGTACGA".to_vec()),
+        ClippedSequence::new(120, ClipSide::Left, b"GTCCGT".to_vec()),
+        ClippedSequence::new(120, ClipSide::Left, b"ACGT".to_vec()),
+    ];
+    let clips = clips.iter().collect::<Vec<&ClippedSequence>>();
+
+    // built outwards from the breakpoint, so left clips are compared from their last base and
+    // the consensus stops at the first column without a clear majority
+    assert_eq!(
+        SoftClipRescue::consensus(&clips, ClipSide::Left),
+        b"GTACGT".to_vec()
+    );
+    assert_eq!(
+        SoftClipRescue::consensus(&clips[..1], ClipSide::Right),
+        b"ACGTACGT".to_vec()
+    );
+}
+
+#[test]
+fn test_rescue_insertion() {
+    let rescue = SoftClipRescue::new(10, 3, 10);
+    let clips = vec![
+        ClippedSequence::new(120, ClipSide::Right, concat(INSERTED, &REFERENCE[20..30])),
+        ClippedSequence::new(120, ClipSide::Right, concat(INSERTED, &REFERENCE[20..29])),
+        ClippedSequence::new(121, ClipSide::Left, concat(&REFERENCE[15..20], INSERTED)),
+        // too few reads support this breakpoint
+        ClippedSequence::new(105, ClipSide::Right, concat(INSERTED, &REFERENCE[5..15])),
+    ];
+
+    let insertions = rescue.rescue(
+        clips,
+        0,
+        REFERENCE,
+        100,
+        &SimpleInterval::new(0, 100, 139),
+    );
+    assert_eq!(insertions.len(), 1);
+    let insertion = &insertions[0];
+    assert_eq!(insertion.loc.get_start(), 119);
+    assert_eq!(insertion.loc.get_end(), 119);
+    assert_eq!(insertion.get_reference().get_bases(), b"C");
+    assert_eq!(
+        insertion.get_alternate_alleles()[0].get_bases(),
+        concat(b"C", INSERTED).as_slice()
+    );
+}
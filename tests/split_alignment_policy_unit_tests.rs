@@ -0,0 +1,91 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::reads::split_alignment_policy::SplitAlignmentPolicy;
+use lorikeet_genome::utils::artificial_read_utils::ArtificialReadUtils;
+use lorikeet_genome::utils::simple_interval::Locatable;
+
+fn alignment(name: &str, start: i64, mapq: u8, supplementary: bool) -> BirdToolRead {
+    let mut record = ArtificialReadUtils::create_artificial_read_default(name, 0, start, 10, false);
+    record.set_mapq(mapq);
+    if supplementary {
+        record.set_supplementary();
+    }
+    BirdToolRead::new(record, 0, ReadType::Long)
+}
+
+fn split_reads() -> Vec<BirdToolRead> {
+    vec![
+        alignment("split", 100, 20, true),
+        alignment("split", 500, 10, false),
+        alignment("split", 900, 40, true),
+        alignment("single", 200, 60, false),
+    ]
+}
+
+#[test]
+fn test_count_all_split_alignments() {
+    let mut reads = split_reads();
+    SplitAlignmentPolicy::CountAll.apply(&mut reads);
+    assert_eq!(
+        reads.iter().map(|read| read.get_start()).collect::<Vec<usize>>(),
+        vec![100, 500, 900, 200]
+    );
+    assert!(reads
+        .iter()
+        .all(|read| SplitAlignmentPolicy::alignment_weight(read) == 1.0));
+
+    assert_eq!(
+        SplitAlignmentPolicy::from_name("count-all"),
+        Some(SplitAlignmentPolicy::CountAll)
+    );
+    assert_eq!(SplitAlignmentPolicy::from_name("count-twice"), None);
+}
+
+#[test]
+fn test_skip_split_alignments() {
+    let mut reads = split_reads();
+    SplitAlignmentPolicy::Skip.apply(&mut reads);
+    assert_eq!(
+        reads.iter().map(|read| read.get_start()).collect::<Vec<usize>>(),
+        vec![500, 200]
+    );
+}
+
+#[test]
+fn test_count_split_alignments_once() {
+    let mut reads = split_reads();
+    SplitAlignmentPolicy::CountOncePerFragment.apply(&mut reads);
+    assert_eq!(
+        reads.iter().map(|read| read.get_start()).collect::<Vec<usize>>(),
+        vec![500, 200]
+    );
+
+    // without a primary alignment the alignment with the highest MAPQ is kept
+    let mut reads = vec![
+        alignment("split", 100, 20, true),
+        alignment("split", 900, 40, true),
+    ];
+    SplitAlignmentPolicy::CountOncePerFragment.apply(&mut reads);
+    assert_eq!(reads.len(), 1);
+    assert_eq!(reads[0].get_start(), 900);
+}
+
+#[test]
+fn test_weight_split_alignments_by_mapq() {
+    let mut reads = split_reads();
+    SplitAlignmentPolicy::WeightByMapq.apply(&mut reads);
+    assert_eq!(reads.len(), 4);
+
+    let weights = reads
+        .iter()
+        .map(SplitAlignmentPolicy::alignment_weight)
+        .collect::<Vec<f64>>();
+    assert!((weights[0..3].iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    assert!(weights[2] > weights[0] && weights[0] > weights[1]);
+    assert_eq!(weights[3], 1.0);
+}
@@ -0,0 +1,149 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::bam_parsing::split_alignment::{AlignmentSegment, SplitAlignmentChain};
+use lorikeet_genome::processing::sv_evidence::{
+    SplitRead, SvCandidate, SvEvidenceCollector, SvEvidenceType,
+};
+use rust_htslib::bam::record::CigarString;
+
+fn tid_of(contig_name: &[u8]) -> Option<u32> {
+    match contig_name {
+        b"contig_1" => Some(0),
+        b"contig_2" => Some(1),
+        _ => None,
+    }
+}
+
+fn segment(ref_start: i64, reverse: bool, cigar: &str) -> AlignmentSegment {
+    let cigar = CigarString::try_from(cigar).unwrap();
+    AlignmentSegment::from_cigar(Some(0), ref_start, reverse, 60, &cigar.0)
+}
+
+fn deletion(sample_index: usize, start: u64, end: u64) -> SplitRead {
+    SplitRead {
+        tid: 0,
+        sample_index,
+        sv_type: SvEvidenceType::Deletion,
+        start,
+        end,
+    }
+}
+
+#[test]
+fn test_parse_sa_tag() {
+    let segment = AlignmentSegment::from_sa_entry("contig_2,101,-,30S70M,60,0", tid_of).unwrap();
+    assert_eq!(segment.tid, Some(1));
+    assert_eq!((segment.ref_start, segment.ref_end), (100, 170));
+    assert!(segment.reverse);
+    assert_eq!(segment.mapq, 60);
+    // the clip is at the start of the sequenced read once reverse complemented
+    assert_eq!((segment.query_start, segment.query_end), (0, 70));
+
+    assert!(AlignmentSegment::from_sa_entry("contig_1,0,+,10M,60,0", tid_of).is_none());
+    assert!(AlignmentSegment::from_sa_entry("contig_1,10,*,10M,60,0", tid_of).is_none());
+    assert!(AlignmentSegment::from_sa_entry("contig_1,10,+,10Q,60,0", tid_of).is_none());
+
+    let segments = SplitAlignmentChain::parse_sa_tag(
+        "contig_1,1001,+,60S40M,60,1;unknown,5,+,40M60H,3,0;",
+        tid_of,
+    );
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].query_start, 60);
+    assert_eq!(segments[1].tid, None);
+    assert_eq!((segments[1].query_start, segments[1].query_end), (0, 40));
+}
+
+#[test]
+fn test_split_read_events() {
+    let collector = SvEvidenceCollector::new(20, 3, 4.0);
+
+    // the read skips from 140 to 1000 along the reference
+    let chain = SplitAlignmentChain::new(vec![
+        segment(1000, false, "40S60M"),
+        segment(100, false, "40M60S"),
+    ]);
+    assert!(chain.is_split());
+    assert_eq!(chain.segments[0].ref_start, 100);
+    assert_eq!(chain.tids().into_iter().collect::<Vec<u32>>(), vec![0]);
+    assert_eq!(
+        collector.classify_split_read(&chain, 1),
+        vec![deletion(1, 140, 1000)]
+    );
+
+    // the same deletion seen from the reverse strand
+    let chain = SplitAlignmentChain::new(vec![
+        segment(100, true, "40M60S"),
+        segment(1000, true, "40S60M"),
+    ]);
+    assert_eq!(chain.segments[0].ref_start, 1000);
+    assert_eq!(
+        collector.classify_split_read(&chain, 0),
+        vec![deletion(0, 140, 1000)]
+    );
+
+    // the read jumps back to align to 400..600 again
+    let chain = SplitAlignmentChain::new(vec![
+        segment(500, false, "100M50S"),
+        segment(400, false, "100S50M"),
+    ]);
+    assert_eq!(
+        collector.classify_split_read(&chain, 0),
+        vec![SplitRead {
+            tid: 0,
+            sample_index: 0,
+            sv_type: SvEvidenceType::Duplication,
+            start: 400,
+            end: 600,
+        }]
+    );
+
+    // too short, and on opposite strands
+    let chain = SplitAlignmentChain::new(vec![
+        segment(100, false, "40M60S"),
+        segment(160, false, "40S60M"),
+    ]);
+    assert!(collector.classify_split_read(&chain, 0).is_empty());
+    let chain = SplitAlignmentChain::new(vec![
+        segment(100, false, "40M60S"),
+        segment(1000, true, "60M40S"),
+    ]);
+    assert!(collector.classify_split_read(&chain, 0).is_empty());
+}
+
+#[test]
+fn test_combine_with_split_reads() {
+    let collector = SvEvidenceCollector::new(20, 3, 4.0);
+    let candidates = vec![SvCandidate {
+        tid: 0,
+        sv_type: SvEvidenceType::Deletion,
+        start: 150,
+        end: 990,
+        discordant_pairs: vec![2, 2],
+        split_reads: vec![0, 0],
+        depth_ratios: vec![0.0, 0.0],
+        depth_change: false,
+    }];
+    let split_reads = vec![
+        deletion(0, 141, 1000),
+        deletion(1, 140, 1000),
+        deletion(1, 140, 1002),
+        // too few reads support this event
+        deletion(0, 5000, 6000),
+        deletion(1, 5001, 6000),
+        deletion(0, 8000, 9000),
+        deletion(0, 8000, 9000),
+        deletion(1, 8002, 9001),
+    ];
+
+    let candidates = collector.combine_with_split_reads(candidates, split_reads, 2);
+    assert_eq!(candidates.len(), 2);
+    assert_eq!((candidates[0].start, candidates[0].end), (140, 1000));
+    assert_eq!(candidates[0].split_reads, vec![1, 2]);
+    assert_eq!(candidates[0].evidence(), vec!["PE", "SR"]);
+    assert_eq!((candidates[1].start, candidates[1].end), (8000, 9000));
+    assert_eq!(candidates[1].split_reads, vec![2, 1]);
+    assert_eq!(candidates[1].evidence(), vec!["SR"]);
+}
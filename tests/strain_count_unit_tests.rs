@@ -0,0 +1,70 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::abundance::strain_count::{SampleStrainCount, StrainCountEstimator};
+
+// sites whose alternate fractions sit at the given fractions, with a little sampling noise
+fn sites(fractions: &[f64], n_sites: usize, depth: u32) -> Vec<(u32, u32)> {
+    (0..n_sites)
+        .map(|site| {
+            let fraction = fractions[site % fractions.len()];
+            let noise = (site % 5) as i32 - 2;
+            let alternate = ((fraction * depth as f64).round() as i32 + noise).max(1) as u32;
+            (alternate.min(depth - 1), depth)
+        })
+        .collect()
+}
+
+#[test]
+fn test_estimate_sample() {
+    let estimator = StrainCountEstimator::new(6);
+    assert_eq!(
+        estimator.estimate_sample(&sites(&[0.5], 200, 60), 100_000),
+        2
+    );
+    assert_eq!(
+        estimator.estimate_sample(&sites(&[1.0 / 3.0, 2.0 / 3.0], 200, 60), 100_000),
+        3
+    );
+    assert_eq!(
+        estimator.estimate_sample(&sites(&[0.25, 0.5, 0.75], 300, 80), 100_000),
+        4
+    );
+}
+
+#[test]
+fn test_single_strain_samples() {
+    let estimator = StrainCountEstimator::new(6);
+    // too few polymorphic sites to be anything but mismapping
+    assert_eq!(
+        estimator.estimate_sample(&sites(&[0.5], 10, 60), 100_000),
+        1
+    );
+    assert_eq!(
+        estimator.estimate_sample(&sites(&[0.5], 200, 60), 10_000_000),
+        1
+    );
+    // no more strains than allowed
+    assert_eq!(
+        StrainCountEstimator::new(2).estimate_sample(&sites(&[0.25, 0.5, 0.75], 300, 80), 100_000),
+        2
+    );
+}
+
+#[test]
+fn test_genome_estimate() {
+    let samples = vec![
+        SampleStrainCount {
+            polymorphic_sites: 0,
+            strains: 1,
+        },
+        SampleStrainCount {
+            polymorphic_sites: 250,
+            strains: 3,
+        },
+    ];
+    assert_eq!(StrainCountEstimator::genome_estimate(&samples), 3);
+    assert_eq!(StrainCountEstimator::genome_estimate(&[]), 1);
+}
@@ -0,0 +1,37 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::abundance::strain_discrimination::StrainDiscrimination;
+
+#[test]
+fn test_between_strain_variance() {
+    assert_eq!(StrainDiscrimination::between_strain_variance(2, 4), 1.0);
+    assert_eq!(StrainDiscrimination::between_strain_variance(0, 4), 0.0);
+    assert_eq!(StrainDiscrimination::between_strain_variance(4, 4), 0.0);
+    assert!((StrainDiscrimination::between_strain_variance(1, 4) - 0.75).abs() < 1e-12);
+    assert_eq!(StrainDiscrimination::between_strain_variance(1, 0), 0.0);
+}
+
+#[test]
+fn test_concordance() {
+    // two samples dominated by different strains
+    let discrimination = StrainDiscrimination::new(&[0, 1], vec![vec![0.8, 0.2], vec![0.2, 0.8]]);
+
+    // alt allele carried by strain 1 observed at the expected fractions
+    let concordance = discrimination.concordance(&[false, true], &[(80.0, 20.0), (20.0, 80.0)]);
+    assert!((concordance - 1.0).abs() < 1e-12);
+
+    // the same reads are discordant with the alt allele being carried by strain 0
+    let concordance = discrimination.concordance(&[true, false], &[(80.0, 20.0), (20.0, 80.0)]);
+    assert!((concordance - 0.4).abs() < 1e-12);
+
+    // samples without reads are ignored
+    let concordance = discrimination.concordance(&[false, true], &[(80.0, 20.0), (0.0, 0.0)]);
+    assert!((concordance - 1.0).abs() < 1e-12);
+    assert_eq!(
+        discrimination.concordance(&[false, true], &[(0.0, 0.0), (0.0, 0.0)]),
+        0.0
+    );
+}
@@ -0,0 +1,61 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::abundance::strain_frequencies::{StrainFrequencyEstimator, StrainSite};
+
+fn site(alt_strains: Vec<usize>, ref_reads: f64, alt_reads: f64) -> StrainSite {
+    StrainSite {
+        alt_strains,
+        ref_reads,
+        alt_reads,
+        weight: 1.0,
+    }
+}
+
+#[test]
+fn test_two_strain_frequencies() {
+    let estimator = StrainFrequencyEstimator::new(&[3, 5]);
+    let sites = vec![
+        site(vec![0], 30.0, 70.0),
+        site(vec![1], 72.0, 28.0),
+        // carried by both strains, so it can not tell them apart
+        site(vec![0, 1], 0.0, 50.0),
+    ];
+
+    let estimates = estimator.estimate(&sites);
+    assert_eq!(estimates.len(), 2);
+    assert_eq!(estimates[0].strain, 3);
+    assert_eq!(estimates[1].strain, 5);
+    assert!((estimates[0].frequency - 0.71).abs() < 1e-6);
+    assert!((estimates[1].frequency - 0.29).abs() < 1e-6);
+    assert!((estimates[0].standard_error - (0.71 * 0.29 / 200.0_f64).sqrt()).abs() < 1e-6);
+    assert_eq!(estimates[0].sites, 2);
+    assert!((estimates.iter().map(|e| e.reads).sum::<f64>() - 250.0).abs() < 1e-6);
+}
+
+#[test]
+fn test_shared_alleles_are_split_by_frequency() {
+    // strains 0 and 1 share the alternate allele of the first site, the second site separates them
+    let estimator = StrainFrequencyEstimator::new(&[0, 1, 2]);
+    let sites = vec![site(vec![0, 1], 10.0, 90.0), site(vec![0], 40.0, 60.0)];
+
+    let estimates = estimator.estimate(&sites);
+    let total = estimates.iter().map(|e| e.frequency).sum::<f64>();
+    assert!((total - 1.0).abs() < 1e-6);
+    assert!((estimates[0].frequency - 0.6).abs() < 1e-3);
+    assert!((estimates[1].frequency - 0.3).abs() < 1e-3);
+    assert!((estimates[2].frequency - 0.1).abs() < 1e-3);
+    assert!(estimates.iter().all(|e| e.standard_error > 0.0));
+}
+
+#[test]
+fn test_no_informative_sites() {
+    let estimator = StrainFrequencyEstimator::new(&[0]);
+    let estimates = estimator.estimate(&[site(vec![0], 10.0, 10.0)]);
+    assert_eq!(estimates.len(), 1);
+    assert!((estimates[0].frequency - 1.0).abs() < 1e-6);
+    assert!(estimates[0].standard_error.is_nan());
+    assert_eq!(estimates[0].sites, 0);
+}
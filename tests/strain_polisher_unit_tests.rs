@@ -0,0 +1,98 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::reference::partial_order_alignment::PartialOrderGraph;
+use lorikeet_genome::reference::strain_polisher::{StrainCoordinates, StrainPolisher};
+use rust_htslib::bam::record::Cigar;
+
+static BACKBONE: &[u8] = b"ACGTACGTTGCAACGTAGGCTAGCTAGGATCCATGCA";
+
+fn corrected_backbone() -> Vec<u8> {
+    let mut truth = BACKBONE.to_vec();
+    // one substitution, one insertion and one deletion
+    truth[10] = b'T';
+    truth.insert(20, b'G');
+    truth.remove(30);
+    truth
+}
+
+#[test]
+fn test_strain_coordinates() {
+    let mut coordinates = StrainCoordinates::new();
+    coordinates.add_shift(0, 11, 2);
+    // unchanged shifts are not recorded again
+    coordinates.add_shift(0, 21, 2);
+    coordinates.add_shift(0, 31, -1);
+
+    assert_eq!(coordinates.to_strain(0, 5), 5);
+    assert_eq!(coordinates.to_strain(0, 11), 13);
+    assert_eq!(coordinates.to_strain(0, 30), 32);
+    assert_eq!(coordinates.to_strain(0, 40), 39);
+    assert_eq!(coordinates.to_strain(1, 40), 40);
+}
+
+#[test]
+fn test_partial_order_consensus() {
+    let truth = corrected_backbone();
+    let mut graph = PartialOrderGraph::new(BACKBONE);
+    for _ in 0..5 {
+        graph.add_sequence(&truth);
+    }
+    // fragments covering part of the window and reads matching the backbone are outvoted
+    graph.add_sequence(&truth[5..25]);
+    graph.add_sequence(BACKBONE);
+
+    let (consensus, corrections) = graph.consensus();
+    assert_eq!(consensus, truth);
+    assert_eq!(corrections, 3);
+}
+
+#[test]
+fn test_polish_window() {
+    let truth = corrected_backbone();
+    let polisher = StrainPolisher::new(10, 3);
+
+    assert_eq!(polisher.polish_window(BACKBONE, &[truth.clone()]), None);
+    assert_eq!(
+        polisher.polish_window(BACKBONE, &[truth.clone(), truth.clone(), truth.clone()]),
+        Some((truth, 3))
+    );
+    assert_eq!(
+        polisher.polish_window(
+            BACKBONE,
+            &[BACKBONE.to_vec(), BACKBONE.to_vec(), BACKBONE.to_vec()]
+        ),
+        Some((BACKBONE.to_vec(), 0))
+    );
+}
+
+#[test]
+fn test_split_read() {
+    let polisher = StrainPolisher::new(10, 3);
+    let seq = b"NNAAAAAAAAAACCCCCCCCCCGG";
+    let cigar = [
+        Cigar::SoftClip(2),
+        Cigar::Match(10),
+        Cigar::Ins(2),
+        Cigar::Match(8),
+        Cigar::Del(3),
+        Cigar::Match(2),
+    ];
+
+    // inserted bases stay with the window of the base before them
+    let fragments = polisher.split_read(5, &cigar, seq, |ref_pos| ref_pos as i64, 40);
+    assert_eq!(
+        fragments,
+        vec![
+            (0, b"AAAAA".to_vec()),
+            (1, b"AAAAACCCCCCC".to_vec()),
+            (2, b"CCCGG".to_vec()),
+        ]
+    );
+
+    // bases shifted past the end of the strain contig are dropped
+    let fragments = polisher.split_read(5, &cigar, seq, |ref_pos| ref_pos as i64 + 15, 30);
+    assert_eq!(fragments, vec![(2, b"AAAAAAAAAACC".to_vec())]);
+}
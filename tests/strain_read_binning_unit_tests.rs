@@ -0,0 +1,98 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::linkage::strain_read_binning::{ReadStrainLikelihoods, StrainReadBinner};
+use rust_htslib::bam::record::{Aux, CigarString, Record};
+
+#[test]
+fn test_posteriors() {
+    let posteriors = StrainReadBinner::posteriors(&[0.0, 0.0], &[0.5, 0.5]);
+    assert!((posteriors[0] - 0.5).abs() < 1e-9);
+    assert!((posteriors[1] - 0.5).abs() < 1e-9);
+
+    // three sites of strain 0 at a 5% error rate, against a prior favouring strain 1
+    let log_likelihoods = [3.0 * 0.95_f64.ln(), 3.0 * 0.05_f64.ln()];
+    let posteriors = StrainReadBinner::posteriors(&log_likelihoods, &[0.2, 0.8]);
+    let expected = 0.2 * 0.95_f64.powi(3) / (0.2 * 0.95_f64.powi(3) + 0.8 * 0.05_f64.powi(3));
+    assert!((posteriors[0] - expected).abs() < 1e-9);
+    assert!((posteriors.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+    // strains absent from the sample can not be assigned reads
+    let posteriors = StrainReadBinner::posteriors(&log_likelihoods, &[0.0, 1.0]);
+    assert_eq!(posteriors, vec![0.0, 1.0]);
+}
+
+#[test]
+fn test_assign_reads() {
+    let binner = StrainReadBinner::new(&[4, 7], 0.9);
+    let supported = ReadStrainLikelihoods {
+        log_likelihoods: vec![2.0 * 0.95_f64.ln(), 2.0 * 0.05_f64.ln()],
+        sites: 2,
+    };
+    let (strain, posterior) = binner.assign(&supported, &[0.5, 0.5]).unwrap();
+    assert_eq!(strain, 0);
+    assert!(posterior > 0.99);
+
+    let ambiguous = ReadStrainLikelihoods {
+        log_likelihoods: vec![0.95_f64.ln(), 0.95_f64.ln()],
+        sites: 1,
+    };
+    assert!(binner.assign(&ambiguous, &[0.5, 0.5]).is_none());
+
+    let no_sites = ReadStrainLikelihoods {
+        log_likelihoods: vec![0.0, 0.0],
+        sites: 0,
+    };
+    assert!(binner.assign(&no_sites, &[1.0, 0.0]).is_none());
+
+    assert_eq!(
+        StrainReadBinner::bam_path("out/genome", "genome", "sample_1", 7),
+        "out/genome/genome_strain_7_sample_1.bam"
+    );
+}
+
+fn aligned_read(qname: &[u8], flags: u16, sa_tag: Option<&str>) -> Record {
+    let mut record = Record::new();
+    record.set(
+        qname,
+        Some(&CigarString::try_from("4M").unwrap()),
+        b"ACGT",
+        &[30, 30, 30, 30],
+    );
+    record.set_flags(flags);
+    if let Some(sa_tag) = sa_tag {
+        record.push_aux(b"SA", Aux::String(sa_tag)).unwrap();
+    }
+    record
+}
+
+#[test]
+fn test_split_read_keys() {
+    // the mates of a properly paired read are both counted
+    let first_mate = aligned_read(b"pair", 0x1 | 0x2 | 0x40, None);
+    let second_mate = aligned_read(b"pair", 0x1 | 0x2 | 0x80, None);
+    assert_eq!(StrainReadBinner::split_read_key(&first_mate), None);
+    assert_eq!(StrainReadBinner::split_read_key(&second_mate), None);
+
+    // the primary and supplementary alignments of a split read share a key
+    let primary = aligned_read(b"split", 0, Some("contig_2,100,+,4M,60,0;"));
+    let supplementary = aligned_read(b"split", 0x800, None);
+    assert_eq!(
+        StrainReadBinner::split_read_key(&primary),
+        Some((b"split".to_vec(), false))
+    );
+    assert_eq!(
+        StrainReadBinner::split_read_key(&primary),
+        StrainReadBinner::split_read_key(&supplementary)
+    );
+
+    // while split mates of a pair are still told apart
+    let split_first_mate = aligned_read(b"pair", 0x1 | 0x40, Some("contig_2,100,+,4M,60,0;"));
+    let split_second_mate = aligned_read(b"pair", 0x1 | 0x80 | 0x800, None);
+    assert_ne!(
+        StrainReadBinner::split_read_key(&split_first_mate),
+        StrainReadBinner::split_read_key(&split_second_mate)
+    );
+}
@@ -0,0 +1,77 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::structural_variant_caller::StructuralVariantCaller;
+
+#[test]
+fn test_sv_caller_from_name() {
+    assert_eq!(
+        StructuralVariantCaller::from_name("svim").unwrap(),
+        StructuralVariantCaller::Svim
+    );
+    assert_eq!(
+        StructuralVariantCaller::from_name("sniffles2").unwrap(),
+        StructuralVariantCaller::Sniffles2
+    );
+    assert_eq!(
+        StructuralVariantCaller::from_name("cuteSV").unwrap(),
+        StructuralVariantCaller::CuteSV
+    );
+    assert!(StructuralVariantCaller::from_name("delly").is_err());
+}
+
+#[test]
+fn test_sv_caller_commands_write_variants_vcf() {
+    for caller in [
+        StructuralVariantCaller::Svim,
+        StructuralVariantCaller::Sniffles2,
+        StructuralVariantCaller::CuteSV,
+    ] {
+        let working_directory = caller.working_directory("out/genome", 1);
+        assert_eq!(working_directory, format!("out/genome/{}_1", caller.name()));
+
+        let command = caller.command("long.bam", "ref.fna", &working_directory, 20, 4);
+        assert!(command.contains(caller.executable()));
+        assert!(command.contains("long.bam"));
+        assert!(command.contains("ref.fna"));
+        if caller != StructuralVariantCaller::Svim {
+            // svim names the file itself within its working directory
+            assert!(command.contains(&format!("{}/variants.vcf", working_directory)));
+        }
+    }
+}
+
+#[test]
+fn test_normalize_sv_type() {
+    assert_eq!(StructuralVariantCaller::normalize_sv_type("DEL"), "DEL");
+    assert_eq!(
+        StructuralVariantCaller::normalize_sv_type("DUP:TANDEM"),
+        "DUP"
+    );
+    assert_eq!(StructuralVariantCaller::normalize_sv_type("DUP_INT"), "DUP");
+    assert_eq!(StructuralVariantCaller::normalize_sv_type("TRA"), "BND");
+    assert_eq!(StructuralVariantCaller::normalize_sv_type("ins"), "INS");
+    assert_eq!(
+        StructuralVariantCaller::normalize_sv_type("INVERSION"),
+        "INV"
+    );
+}
+
+#[test]
+fn test_normalize_sv_length() {
+    assert_eq!(
+        StructuralVariantCaller::normalize_sv_length("DEL", 120),
+        -120
+    );
+    assert_eq!(
+        StructuralVariantCaller::normalize_sv_length("DEL", -120),
+        -120
+    );
+    assert_eq!(StructuralVariantCaller::normalize_sv_length("INS", -40), 40);
+    assert_eq!(
+        StructuralVariantCaller::normalize_sv_length("DUP", 500),
+        500
+    );
+}
@@ -0,0 +1,53 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::subsampler::Subsampler;
+use lorikeet_genome::utils::run_rng::RunRng;
+
+#[test]
+fn test_subsampler_validation() {
+    assert!(Subsampler::new(Some(0.5), None).is_ok());
+    assert!(Subsampler::new(Some(1.0), Some(3)).is_ok());
+    assert!(Subsampler::new(Some(0.0), None).is_err());
+    assert!(Subsampler::new(Some(1.5), None).is_err());
+    assert!(Subsampler::new(None, Some(0)).is_err());
+}
+
+#[test]
+fn test_selected_windows() {
+    RunRng::init(42);
+    let subsampler = Subsampler::new(None, Some(3)).unwrap();
+    let selected = subsampler.selected_windows(10, 0).unwrap();
+    assert_eq!(selected.len(), 3);
+    assert!(selected.iter().all(|ordinal| *ordinal < 10));
+    // the same windows are chosen for the same seed and genome
+    assert_eq!(subsampler.selected_windows(10, 0), Some(selected));
+
+    // every window is profiled when there are no more windows than requested
+    assert_eq!(subsampler.selected_windows(3, 0), None);
+    assert_eq!(
+        Subsampler::new(Some(0.5), None)
+            .unwrap()
+            .selected_windows(10, 0),
+        None
+    );
+}
+
+#[test]
+fn test_keeps_region() {
+    RunRng::init(42);
+    let subsampler = Subsampler::new(Some(0.25), None).unwrap();
+    let kept = (0..4000)
+        .filter(|start| subsampler.keeps_region(1, start * 100))
+        .count();
+    assert!(kept > 800 && kept < 1200, "kept {} regions", kept);
+    assert_eq!(
+        subsampler.keeps_region(1, 500),
+        subsampler.keeps_region(1, 500)
+    );
+
+    let everything = Subsampler::new(Some(1.0), Some(2)).unwrap();
+    assert!((0..100).all(|start| everything.keeps_region(0, start)));
+}
@@ -0,0 +1,93 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::sv_evidence::{
+    DepthProfile, DiscordantPair, SvEvidenceCollector, SvEvidenceType,
+};
+use lorikeet_genome::reads::insert_size_distribution::InsertSizeDistribution;
+
+fn deletion_pair(sample_index: usize, start: u64, end: u64, insert_size: i64) -> DiscordantPair {
+    DiscordantPair {
+        tid: 0,
+        sample_index,
+        sv_type: SvEvidenceType::Deletion,
+        start,
+        end,
+        insert_size,
+    }
+}
+
+#[test]
+fn test_insert_size_distribution() {
+    let distribution =
+        InsertSizeDistribution::from_insert_sizes(vec![290, 300, 310, 300, 5000, -300, 0]).unwrap();
+    assert_eq!(distribution.median, 300.0);
+    assert_eq!(distribution.n_pairs, 5);
+    assert!((distribution.stdev - 10.0 * 1.4826).abs() < 1e-9);
+    assert!(!distribution.is_too_long(350, 4.0));
+    assert!(distribution.is_too_long(5000, 4.0));
+    assert!(distribution.is_too_long(-5000, 4.0));
+
+    assert!(InsertSizeDistribution::from_insert_sizes(vec![0, -10]).is_none());
+}
+
+#[test]
+fn test_cluster_discordant_pairs() {
+    let collector = SvEvidenceCollector::new(20, 3, 4.0);
+    let pairs = vec![
+        deletion_pair(0, 1000, 1390, 900),
+        deletion_pair(1, 1020, 1400, 900),
+        deletion_pair(0, 1010, 1380, 900),
+        // too few pairs support this event
+        deletion_pair(1, 8000, 8400, 900),
+        deletion_pair(1, 8010, 8410, 900),
+    ];
+
+    let candidates = collector.cluster_pairs(pairs, 400, 300.0, 2);
+    assert_eq!(candidates.len(), 1);
+    let candidate = &candidates[0];
+    assert_eq!(candidate.sv_type, SvEvidenceType::Deletion);
+    assert_eq!(candidate.discordant_pairs, vec![2, 1]);
+    assert_eq!(candidate.start, 1020);
+    assert_eq!(candidate.end, 1380);
+    assert_eq!(candidate.evidence(), vec!["PE"]);
+}
+
+#[test]
+fn test_depth_profile() {
+    let mut depth = DepthProfile::new(1000, 100);
+    assert_eq!(depth.depths.len(), 10);
+    depth.add_alignment(50, 250);
+    assert_eq!(depth.depths[0], 0.5);
+    assert_eq!(depth.depths[1], 1.0);
+    assert_eq!(depth.depths[2], 0.5);
+    assert_eq!(depth.mean_depth(0, 300), 2.0 / 3.0);
+}
+
+#[test]
+fn test_depth_change_points() {
+    let mut depth = DepthProfile {
+        window_size: 100,
+        depths: vec![30.0; 40],
+    };
+    depth.depths[10..20].iter_mut().for_each(|d| *d = 0.0);
+    depth.depths[28..34].iter_mut().for_each(|d| *d = 90.0);
+
+    let segments = depth.change_point_segments(1.8, 5, 5.0);
+    assert_eq!(
+        segments,
+        vec![
+            (1000, 2000, SvEvidenceType::Deletion),
+            (2800, 3400, SvEvidenceType::Duplication),
+        ]
+    );
+    assert!(depth.depth_ratio(1000, 2000, 10_000) < 0.01);
+    assert!((depth.depth_ratio(2800, 3400, 10_000) - 3.0).abs() < 1e-9);
+
+    let collector = SvEvidenceCollector::new(20, 3, 4.0);
+    let candidates = collector.combine_with_depth(Vec::new(), 0, &[depth]);
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates.iter().all(|candidate| candidate.evidence() == vec!["RD"]));
+}
@@ -0,0 +1,47 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::temp_resources::TempResources;
+
+// the registry is global, so every step runs in a single test
+#[test]
+fn test_temp_resources_are_removed() {
+    let tmp_root = tempfile::tempdir().unwrap();
+    let tmp_dir = tmp_root.path().join("scratch");
+    TempResources::configure(Some(tmp_dir.to_str().unwrap()), false).unwrap();
+    assert!(tmp_dir.is_dir());
+    assert_eq!(
+        TempResources::directory(),
+        std::fs::canonicalize(&tmp_dir).unwrap()
+    );
+    assert!(!TempResources::keep());
+
+    // resources are created in the temporary directory and removed when dropped
+    let resource = TempResources::create_dir("lorikeet_test").unwrap();
+    let resource_path = resource.path().to_path_buf();
+    assert!(resource_path.starts_with(TempResources::directory()));
+    std::fs::write(resource_path.join("cached.bam"), b"bam").unwrap();
+    drop(resource);
+    assert!(!resource_path.exists());
+
+    // registered paths are removed on cleanup, once
+    let index_path = TempResources::directory().join("reference.fna.fai");
+    std::fs::write(&index_path, b"fai").unwrap();
+    TempResources::register(&index_path);
+    TempResources::register(&index_path);
+    TempResources::register(TempResources::directory().join("never_written"));
+    assert_eq!(TempResources::cleanup(), 2);
+    assert!(!index_path.exists());
+    assert_eq!(TempResources::cleanup(), 0);
+
+    // nothing is removed when temporary resources are kept
+    TempResources::configure(Some(tmp_dir.to_str().unwrap()), true).unwrap();
+    let kept = TempResources::create_dir("lorikeet_kept").unwrap();
+    let kept_path = kept.path().to_path_buf();
+    drop(kept);
+    assert!(kept_path.exists());
+
+    TempResources::configure(None, false).unwrap();
+}
@@ -0,0 +1,51 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::thread_budget::ThreadBudget;
+
+#[test]
+fn test_threads_per_genome() {
+    let budget = ThreadBudget::new(16, 4, 3, false);
+    assert_eq!(budget.compute_threads(), 12);
+    assert_eq!(budget.threads_per_genome(3), 4);
+    // fewer genomes than parallel genomes share the threads between the genomes present
+    assert_eq!(budget.threads_per_genome(2), 6);
+    assert_eq!(budget.threads_per_genome(0), 12);
+}
+
+#[test]
+fn test_threads_per_genome_oversubscribed() {
+    // more parallel genomes than compute threads still give each genome two threads
+    let budget = ThreadBudget::new(4, 0, 8, false);
+    assert_eq!(budget.threads_per_genome(8), 2);
+    assert_eq!(budget.threads_per_genome(3), 2);
+
+    let budget = ThreadBudget::new(1, 0, 1, false);
+    assert_eq!(budget.compute_threads(), 1);
+    assert_eq!(budget.threads_per_genome(1), 2);
+}
+
+#[test]
+fn test_io_threads_per_reader() {
+    let budget = ThreadBudget::new(16, 4, 1, false);
+    // each of the 12 compute threads holds at most one reader
+    assert_eq!(budget.io_threads_per_reader(), 0);
+    assert_eq!(budget.reader_threads(), 1);
+
+    let budget = ThreadBudget::new(12, 8, 1, false);
+    assert_eq!(budget.io_threads_per_reader(), 2);
+    assert_eq!(budget.reader_threads(), 3);
+
+    // at least one thread is always left for computation
+    let budget = ThreadBudget::new(4, 10, 1, false);
+    assert_eq!(budget.io_threads, 3);
+    assert_eq!(budget.compute_threads(), 1);
+    assert_eq!(budget.io_threads_per_reader(), 3);
+
+    let budget = ThreadBudget::new(0, 0, 0, false);
+    assert_eq!(budget.threads, 1);
+    assert_eq!(budget.parallel_genomes, 1);
+    assert_eq!(budget.io_threads_per_reader(), 0);
+}
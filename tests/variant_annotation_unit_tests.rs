@@ -0,0 +1,293 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::annotator::allele_fraction::AlleleFractionDenominator;
+use lorikeet_genome::annotator::variant_annotation::{AnnotationType, VariantAnnotations};
+use lorikeet_genome::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
+use lorikeet_genome::model::allele_likelihoods::AlleleLikelihoods;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::processing::lorikeet_engine::ReadType;
+use lorikeet_genome::reads::bird_tool_reads::BirdToolRead;
+use lorikeet_genome::reads::split_alignment_policy::SplitAlignmentPolicy;
+use rust_htslib::bam::record::{Cigar, CigarString, Record};
+use rust_htslib::bcf::record::Numeric;
+use std::collections::HashMap;
+
+fn read(name: &str, sample_index: usize, weight: Option<f64>) -> BirdToolRead {
+    let mut record = Record::new();
+    let cigar = CigarString(vec![Cigar::Match(10)]);
+    record.set(name.as_bytes(), Some(&cigar), b"AAAAAAAAAA", &[30; 10]);
+    record.set_pos(100);
+    record.set_mapq(60);
+    let mut read = BirdToolRead::new(record, sample_index, ReadType::Short);
+    if let Some(weight) = weight {
+        read.set_transient_attribute(
+            SplitAlignmentPolicy::WEIGHT_ATTRIBUTE.to_string(),
+            weight.to_le_bytes().to_vec(),
+        );
+    }
+    read
+}
+
+fn alleles() -> Vec<ByteArrayAllele> {
+    vec![
+        ByteArrayAllele::new(b"A", true),
+        ByteArrayAllele::new(b"T", false),
+    ]
+}
+
+/// Likelihoods of reads that each clearly support the allele given for them
+fn likelihoods(
+    reads_by_sample: Vec<Vec<(BirdToolRead, usize)>>,
+) -> AlleleLikelihoods<ByteArrayAllele> {
+    let samples = (0..reads_by_sample.len()).collect::<Vec<usize>>();
+    let mut evidence_by_sample = HashMap::new();
+    let mut supported_alleles = Vec::new();
+    for (sample_index, reads) in reads_by_sample.into_iter().enumerate() {
+        if reads.is_empty() {
+            continue;
+        }
+        let (reads, alleles): (Vec<BirdToolRead>, Vec<usize>) = reads.into_iter().unzip();
+        evidence_by_sample.insert(sample_index, reads);
+        supported_alleles.push((sample_index, alleles));
+    }
+
+    let mut likelihoods = AlleleLikelihoods::new(alleles(), samples, evidence_by_sample);
+    for (sample_index, alleles) in supported_alleles {
+        let matrix = likelihoods.sample_matrix(sample_index);
+        for (evidence_index, allele_index) in alleles.into_iter().enumerate() {
+            for other in 0..2 {
+                matrix[[other, evidence_index]] = if other == allele_index { -1.0 } else { -10.0 };
+            }
+        }
+    }
+    likelihoods
+}
+
+#[test]
+fn test_allele_depths_use_read_weights() {
+    let mut likelihoods = likelihoods(vec![vec![
+        (read("ref_1", 0, Some(0.5)), 0),
+        (read("ref_2", 0, Some(0.5)), 0),
+        (read("ref_3", 0, Some(0.5)), 0),
+        (read("ref_4", 0, Some(0.5)), 0),
+        (read("alt_1", 0, None), 1),
+        (read("alt_2", 0, None), 1),
+        (read("alt_3", 0, Some(0.6)), 1),
+    ]]);
+    let mut vc = VariantContext::build(0, 105, 105, alleles());
+    let mut genotype = Genotype::build(2, vec![0.0; 3], 0);
+
+    VariantAnnotations::Depth.annotate(
+        &mut vc,
+        Some(&mut genotype),
+        &mut likelihoods,
+        AnnotationType::Format,
+    );
+    // reads count for their weight, rounded to whole reads, and DP follows AD
+    assert_eq!(genotype.ad, vec![2, 3]);
+    assert_eq!(genotype.dp, 5);
+}
+
+#[test]
+fn test_allele_depths_follow_split_alignment_policy() {
+    // three alignments of one split read support the alternate allele
+    let split_reads = || {
+        let mut reads = [(20, true), (10, false), (40, true)]
+            .iter()
+            .map(|(mapq, supplementary)| {
+                let mut read = read("split", 0, None);
+                read.read.set_mapq(*mapq);
+                if *supplementary {
+                    read.read.set_supplementary();
+                }
+                read
+            })
+            .collect::<Vec<BirdToolRead>>();
+        reads.push(read("ref_1", 0, None));
+        reads.push(read("ref_2", 0, None));
+        reads
+    };
+    let allele_depths = |policy: SplitAlignmentPolicy| {
+        let mut reads = split_reads();
+        policy.apply(&mut reads);
+        let reads = reads
+            .into_iter()
+            .map(|read| {
+                let allele_index = if read.name() == b"split" { 1 } else { 0 };
+                (read, allele_index)
+            })
+            .collect();
+        let mut likelihoods = likelihoods(vec![reads]);
+        let mut vc = VariantContext::build(0, 105, 105, alleles());
+        let mut genotype = Genotype::build(2, vec![0.0; 3], 0);
+        VariantAnnotations::DepthPerAlleleBySample.annotate(
+            &mut vc,
+            Some(&mut genotype),
+            &mut likelihoods,
+            AnnotationType::Format,
+        );
+        genotype.ad
+    };
+
+    // every alignment is counted by default, as before split alignments were handled
+    assert_eq!(allele_depths(SplitAlignmentPolicy::CountAll), vec![2, 3]);
+    assert_eq!(allele_depths(SplitAlignmentPolicy::Skip), vec![2, 1]);
+    assert_eq!(
+        allele_depths(SplitAlignmentPolicy::CountOncePerFragment),
+        vec![2, 1]
+    );
+    // the weights of the alignments of a read sum to one read in AD
+    assert_eq!(
+        allele_depths(SplitAlignmentPolicy::WeightByMapq),
+        vec![2, 1]
+    );
+}
+
+#[test]
+fn test_sample_qualities_of_a_sample_without_reads() {
+    // the second sample has no reads in the region
+    let mut likelihoods = likelihoods(vec![
+        vec![(read("ref_1", 0, None), 0), (read("alt_1", 0, None), 1)],
+        Vec::new(),
+    ]);
+    let mut vc = VariantContext::build(0, 105, 105, alleles());
+
+    let mut genotype = Genotype::build(2, vec![0.0; 3], 0);
+    VariantAnnotations::SampleMappingQuality.annotate(
+        &mut vc,
+        Some(&mut genotype),
+        &mut likelihoods,
+        AnnotationType::Format,
+    );
+    assert_eq!(
+        genotype.get_attribute(&"SMQ".to_string()),
+        Some(&AttributeObject::VecI32(vec![60, 60]))
+    );
+
+    let mut genotype = Genotype::build(2, vec![0.0; 3], 1);
+    for annotation in [
+        VariantAnnotations::SampleMappingQuality,
+        VariantAnnotations::SampleBaseQuality,
+    ] {
+        annotation.annotate(
+            &mut vc,
+            Some(&mut genotype),
+            &mut likelihoods,
+            AnnotationType::Format,
+        );
+    }
+    // alleles without reads are written as missing values
+    assert_eq!(
+        genotype.get_attribute(&"SMQ".to_string()),
+        Some(&AttributeObject::VecI32(vec![i32::missing(), i32::missing()]))
+    );
+    assert_eq!(
+        genotype.get_attribute(&"SBQ".to_string()),
+        Some(&AttributeObject::VecI32(vec![i32::missing(), i32::missing()]))
+    );
+}
+
+#[test]
+fn test_per_sample_allele_fractions() {
+    // the second sample has no reads at the site
+    let mut likelihoods = likelihoods(vec![
+        vec![
+            (read("ref_1", 0, None), 0),
+            (read("ref_2", 0, None), 0),
+            (read("alt_1", 0, None), 1),
+            (read("alt_2", 0, None), 1),
+            (read("alt_3", 0, None), 1),
+        ],
+        Vec::new(),
+    ]);
+    let mut vc = VariantContext::build(0, 105, 105, alleles());
+    let af_key = VariantAnnotations::AlleleFraction.to_key().to_string();
+
+    let mut genotype = Genotype::build(2, vec![0.0; 3], 0);
+    VariantAnnotations::AlleleFraction.annotate(
+        &mut vc,
+        Some(&mut genotype),
+        &mut likelihoods,
+        AnnotationType::Format,
+    );
+    assert_eq!(genotype.ad, vec![2, 3]);
+    assert_eq!(
+        genotype.get_attribute(&af_key),
+        Some(&AttributeObject::Vecf64(vec![0.6]))
+    );
+    assert_eq!(
+        genotype.get_attribute(&AlleleFractionDenominator::UNASSIGNED_READS_KEY.to_string()),
+        Some(&AttributeObject::UnsizedInteger(0))
+    );
+
+    // a sample without reads has no allele fraction rather than dividing by zero
+    let mut genotype = Genotype::build(2, vec![0.0; 3], 1);
+    VariantAnnotations::AlleleFraction.annotate(
+        &mut vc,
+        Some(&mut genotype),
+        &mut likelihoods,
+        AnnotationType::Format,
+    );
+    assert_eq!(genotype.ad, vec![0, 0]);
+    assert_eq!(genotype.get_attribute(&af_key), None);
+}
+
+#[test]
+fn test_allele_fraction_denominator() {
+    let mut genotype = Genotype::build_from_ads(2, vec![2, 3, 5]);
+    genotype.attribute(
+        AlleleFractionDenominator::UNASSIGNED_READS_KEY.to_string(),
+        AttributeObject::UnsizedInteger(10),
+    );
+    assert_eq!(
+        AlleleFractionDenominator::Informative.allele_fractions(&genotype),
+        Some(vec![0.3, 0.5])
+    );
+    // reads not assigned to an allele are counted with --allele-fraction-denominator all
+    assert_eq!(
+        AlleleFractionDenominator::All.allele_fractions(&genotype),
+        Some(vec![0.15, 0.25])
+    );
+
+    let mut zero_depth = Genotype::build_from_ads(2, vec![0, 0, 0]);
+    zero_depth.attribute(
+        AlleleFractionDenominator::UNASSIGNED_READS_KEY.to_string(),
+        AttributeObject::UnsizedInteger(0),
+    );
+    assert_eq!(
+        AlleleFractionDenominator::Informative.allele_fractions(&zero_depth),
+        None
+    );
+    assert_eq!(
+        AlleleFractionDenominator::All.allele_fractions(&zero_depth),
+        None
+    );
+
+    // stale fractions of a sample without reads are removed
+    let af_key = VariantAnnotations::AlleleFraction.to_key().to_string();
+    zero_depth.attribute(af_key.clone(), AttributeObject::Vecf64(vec![0.5, 0.5]));
+    let mut vc = VariantContext::build(
+        0,
+        105,
+        105,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+            ByteArrayAllele::new(b"G", false),
+        ],
+    );
+    vc.genotypes = GenotypesContext::new(vec![genotype, zero_depth]);
+    let mut contexts = vec![vc];
+    AlleleFractionDenominator::Informative.annotate_contexts(&mut contexts);
+
+    let genotypes = contexts[0].genotypes.genotypes();
+    assert_eq!(
+        genotypes[0].get_attribute(&af_key),
+        Some(&AttributeObject::Vecf64(vec![0.3, 0.5]))
+    );
+    assert_eq!(genotypes[1].get_attribute(&af_key), None);
+}
@@ -9,9 +9,12 @@ extern crate lazy_static;
 extern crate num;
 extern crate statrs;
 
-use lorikeet_genome::model::byte_array_allele::{ByteArrayAllele};
+use lorikeet_genome::model::byte_array_allele::{Allele, ByteArrayAllele};
 
+use lorikeet_genome::genotype::genotype_builder::{AttributeObject, Genotype};
 use lorikeet_genome::model::variant_context::{VariantContext, VariantType};
+use lorikeet_genome::model::variant_context_json::VariantContextDump;
+use lorikeet_genome::model::variants::Filter;
 
 use lorikeet_genome::utils::simple_interval::Locatable;
 
@@ -416,3 +419,158 @@ fn test_genotype_tag_calc() {
         }
     }
 }
+
+#[test]
+fn test_json_round_trip() {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"T", false);
+    let mut vc = VariantContext::build(1, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    let mut genotype = Genotype::build_from_alleles(vec![ref_allele.clone(), alt_allele.clone()], 0);
+    genotype.ad = vec![3, 7];
+    genotype.pl = vec![70, 0, 30];
+    genotype.attributes.insert(
+        "PS".to_string(),
+        AttributeObject::String("10".to_string()),
+    );
+    vc.add_genotypes(vec![genotype.clone()]);
+    vc.log10_p_error = -4.5;
+    vc.filter(Filter::PASS);
+    vc.set_attribute("AF".to_string(), AttributeObject::Vecf64(vec![0.7]));
+    vc.set_attribute("Strain".to_string(), AttributeObject::VecUnsize(vec![0, 2]));
+
+    let dump = VariantContextDump::new(vec!["sample_1".to_string()], vec![vc.clone()]);
+    let json = dump.to_json().unwrap();
+    let mut reloaded = VariantContextDump::from_json(&json).unwrap();
+
+    assert_eq!(reloaded.samples, vec!["sample_1".to_string()]);
+    assert_eq!(reloaded.contexts.len(), 1);
+    let reloaded_vc = &mut reloaded.contexts[0];
+    assert_eq!(reloaded_vc, &vc);
+    assert_eq!(reloaded_vc.log10_p_error, -4.5);
+    assert!(reloaded_vc.filters.contains(&Filter::PASS));
+    assert_eq!(reloaded_vc.attributes, vc.attributes);
+    assert_eq!(reloaded_vc.genotypes.genotypes()[0], genotype);
+    assert_eq!(reloaded_vc.genotypes.genotypes()[0].pl, genotype.pl);
+    assert_eq!(
+        reloaded_vc.genotypes.genotypes()[0].attributes,
+        genotype.attributes
+    );
+    // cached values are recomputed after reloading
+    assert_eq!(reloaded_vc.get_type(), &VariantType::Snp);
+
+    // dumps from a different schema version are rejected
+    let stale = json.replacen("\"version\":1", "\"version\":0", 1);
+    assert!(VariantContextDump::from_json(&stale).is_err());
+}
+
+#[test]
+fn test_json_round_trip_non_finite_values() {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let alt_allele = ByteArrayAllele::new(b"T", false);
+    let mut vc = VariantContext::build(1, 10, 10, vec![ref_allele.clone(), alt_allele.clone()]);
+    let mut genotype = Genotype::build_from_alleles(vec![ref_allele, alt_allele], 0);
+    genotype
+        .attributes
+        .insert("LR".to_string(), AttributeObject::f64(f64::NEG_INFINITY));
+    vc.add_genotypes(vec![genotype]);
+    // a record without a QUAL has a missing, i.e. NaN, quality
+    vc.log10_p_error(f64::NAN / -10.0);
+    vc.set_attribute("QD".to_string(), AttributeObject::f64(f64::NAN));
+    vc.set_attribute(
+        "AF".to_string(),
+        AttributeObject::Vecf64(vec![0.5, f64::INFINITY]),
+    );
+
+    let json = VariantContextDump::new(vec!["sample_1".to_string()], vec![vc])
+        .to_json()
+        .unwrap();
+    let reloaded = VariantContextDump::from_json(&json).unwrap();
+    let reloaded_vc = &reloaded.contexts[0];
+
+    assert!(reloaded_vc.log10_p_error.is_nan());
+    match reloaded_vc.attributes.get("QD") {
+        Some(AttributeObject::f64(qd)) => assert!(qd.is_nan()),
+        other => panic!("Unexpected QD {:?}", other),
+    }
+    assert_eq!(
+        reloaded_vc.attributes.get("AF"),
+        Some(&AttributeObject::Vecf64(vec![0.5, f64::INFINITY]))
+    );
+    assert_eq!(
+        reloaded_vc.genotypes.genotypes()[0].attributes.get("LR"),
+        Some(&AttributeObject::f64(f64::NEG_INFINITY))
+    );
+
+    // finite values are still written as numbers, and nulls from older dumps read back as NaN
+    assert!(json.contains("\"value\":[0.5,\"inf\"]"));
+    let nulls = json.replace("\"NaN\"", "null");
+    let reloaded = VariantContextDump::from_json(&nulls).unwrap();
+    assert!(reloaded.contexts[0].log10_p_error.is_nan());
+}
+
+#[test]
+fn test_symbolic_alleles_written_as_is() {
+    let ref_allele = ByteArrayAllele::new(b"A", true);
+    let del = ByteArrayAllele::new(b"<DEL>", false);
+    let span_del = ByteArrayAllele::new(b"*", false);
+    assert!(del.is_symbolic());
+    assert!(span_del.is_span_del());
+
+    let vc = VariantContext::build(0, 10, 10, vec![ref_allele, del, span_del]);
+    assert_eq!(
+        vc.get_alleles_as_bytes(),
+        vec![&b"A"[..], &b"<DEL>"[..], &b"*"[..]]
+    );
+}
+
+#[test]
+fn test_structural_variant_info() {
+    // a 60bp deletion is described once it passes the minimum length
+    let ref_bases = vec![b'A'; 61];
+    let mut deletion = VariantContext::build(
+        0,
+        100,
+        160,
+        vec![
+            ByteArrayAllele::new(&ref_bases, true),
+            ByteArrayAllele::new(b"A", false),
+        ],
+    );
+    deletion.add_structural_variant_info(100);
+    assert!(deletion.attributes.is_empty());
+
+    deletion.add_structural_variant_info(50);
+    assert_eq!(
+        deletion.attributes.get("END"),
+        Some(&AttributeObject::I32(161))
+    );
+    assert_eq!(
+        deletion.attributes.get("SVLEN"),
+        Some(&AttributeObject::VecI32(vec![-60]))
+    );
+    assert_eq!(
+        deletion.attributes.get("SVTYPE"),
+        Some(&AttributeObject::String("DEL".to_string()))
+    );
+
+    // symbolic alleles are always described
+    let mut duplication = VariantContext::build(
+        0,
+        10,
+        509,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"<DUP:TANDEM>", false),
+        ],
+    );
+    duplication.add_structural_variant_info(0);
+    assert_eq!(
+        duplication.attributes.get("END"),
+        Some(&AttributeObject::I32(510))
+    );
+    assert_eq!(duplication.attributes.get("SVLEN"), None);
+    assert_eq!(
+        duplication.attributes.get("SVTYPE"),
+        Some(&AttributeObject::String("DUP".to_string()))
+    );
+}
@@ -7,17 +7,20 @@ extern crate lorikeet_genome;
 extern crate hashlink;
 
 use hashlink::LinkedHashSet;
-use lorikeet_genome::genotype::genotype_builder::{Genotype, GenotypesContext};
+use lorikeet_genome::annotator::variant_annotation::VariantAnnotations;
+use lorikeet_genome::genotype::genotype_builder::{AttributeObject, Genotype, GenotypesContext};
 use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
 
 use lorikeet_genome::model::variant_context::VariantContext;
 
 use lorikeet_genome::model::variant_context_utils::{
-    FilteredRecordMergeType, GenotypeMergeType, VariantContextUtils,
+    FilteredRecordMergeType, GenotypeMergeType, SiteAllele, VariantContextUtils,
 };
+use lorikeet_genome::model::variants::Filter;
+use lorikeet_genome::utils::simple_interval::Locatable;
 
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 #[test]
 fn test_find_number_of_repetitions() {
@@ -1006,3 +1009,109 @@ fn assert_genotypes_are_mostly_equal(
         };
     }
 }
+
+fn site_alleles(positions: &[usize]) -> HashSet<SiteAllele> {
+    positions
+        .iter()
+        .map(|position| {
+            (
+                "contig".to_string(),
+                *position,
+                b"A".to_vec(),
+                b"T".to_vec(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_reconcile_ids() {
+    let first_run = HashMap::from([
+        (0, site_alleles(&[1, 2, 3, 4])),
+        (1, site_alleles(&[10, 11, 12])),
+    ]);
+    // the same two strains numbered the other way round, plus a strain unique to this run
+    let second_run = HashMap::from([
+        (0, site_alleles(&[10, 11, 12, 13])),
+        (1, site_alleles(&[1, 2, 3])),
+        (2, site_alleles(&[20, 21])),
+    ]);
+    // a strain only sharing one of its sites with any earlier strain
+    let third_run = HashMap::from([(0, site_alleles(&[4, 30, 31]))]);
+
+    let mappings = VariantContextUtils::reconcile_ids(&[first_run, second_run, third_run], 0.5);
+
+    assert_eq!(mappings[0], HashMap::from([(0, 0), (1, 1)]));
+    assert_eq!(mappings[1], HashMap::from([(0, 1), (1, 0), (2, 2)]));
+    assert_eq!(mappings[2], HashMap::from([(0, 3)]));
+}
+
+/// A SNP with the given QD, if any, and allele depths in one sample
+fn quality_filter_context(position: usize, qd: Option<f64>, ad: Vec<i32>) -> VariantContext {
+    let mut vc = VariantContext::build(
+        0,
+        position,
+        position,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+        ],
+    );
+    if let Some(qd) = qd {
+        vc.set_attribute(
+            VariantAnnotations::QualByDepth.to_key().to_string(),
+            AttributeObject::f64(qd),
+        );
+    }
+    if !ad.is_empty() {
+        vc.genotypes = GenotypesContext::new(vec![Genotype::build_from_ads(2, ad)]);
+    }
+    vc
+}
+
+fn quality_filter_contexts() -> Vec<VariantContext> {
+    vec![
+        quality_filter_context(1, Some(10.0), vec![5, 8]),
+        quality_filter_context(2, Some(1.0), vec![5, 8]),
+        quality_filter_context(3, Some(10.0), vec![10, 2]),
+        quality_filter_context(4, Some(1.0), vec![10, 2]),
+        // contexts without QD or allele depths are not filtered
+        quality_filter_context(5, None, Vec::new()),
+    ]
+}
+
+#[test]
+fn test_apply_quality_filters() {
+    let mut contexts = quality_filter_contexts();
+    let filtered = VariantContextUtils::apply_quality_filters(&mut contexts, 2.0, 5, false);
+    // a context failing both filters is counted once
+    assert_eq!(filtered, 3);
+    assert_eq!(contexts.len(), 5);
+    let filters = contexts
+        .iter()
+        .map(|vc| vc.filters.clone())
+        .collect::<Vec<HashSet<Filter>>>();
+    assert_eq!(
+        filters,
+        vec![
+            HashSet::new(),
+            HashSet::from([Filter::LowQD]),
+            HashSet::from([Filter::LowDepth]),
+            HashSet::from([Filter::LowQD, Filter::LowDepth]),
+            HashSet::new(),
+        ]
+    );
+
+    // with --hard-filter the filtered contexts are removed
+    let mut contexts = quality_filter_contexts();
+    let filtered = VariantContextUtils::apply_quality_filters(&mut contexts, 2.0, 5, true);
+    assert_eq!(filtered, 3);
+    assert_eq!(
+        contexts
+            .iter()
+            .map(|vc| vc.loc.get_start())
+            .collect::<Vec<usize>>(),
+        vec![1, 5]
+    );
+    assert!(contexts.iter().all(|vc| vc.is_not_filtered()));
+}
@@ -0,0 +1,66 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::variant_features::{FeatureModel, VariantFeatures};
+
+fn features(values: &[(&str, f64)]) -> VariantFeatures {
+    let mut features = VariantFeatures {
+        contig: "genome~contig_1".to_string(),
+        position: 101,
+        ref_allele: "A".to_string(),
+        alt_allele: "AT".to_string(),
+        filter: ".".to_string(),
+        values: vec![None; VariantFeatures::FEATURE_NAMES.len()],
+    };
+    for (name, value) in values {
+        features.values[VariantFeatures::feature_index(name).unwrap()] = Some(*value);
+    }
+    features
+}
+
+#[test]
+fn testStrandOddsRatio() {
+    assert!(
+        (VariantFeatures::strand_odds_ratio(10.0, 10.0, 10.0, 10.0) - 2.0_f64.ln()).abs() < 1e-9
+    );
+    // reference and ALT reads on opposite strands
+    assert!((VariantFeatures::strand_odds_ratio(20.0, 0.0, 0.0, 20.0) - 6.089050).abs() < 1e-6);
+    // every read on the same strand is not strand bias of the ALT allele
+    assert!((VariantFeatures::strand_odds_ratio(20.0, 0.0, 20.0, 0.0) - 2.0_f64.ln()).abs() < 1e-9);
+}
+
+#[test]
+fn testFeaturesLine() {
+    let features = features(&[("QUAL", 250.0), ("QD", 12.345678), ("LENGTH_DIFF", 1.0)]);
+    assert_eq!(features.value("qd"), Some(12.345678));
+    assert_eq!(features.value("CNR"), None);
+    assert_eq!(features.value("unknown"), None);
+
+    let header = VariantFeatures::header_line();
+    let line = features.to_line();
+    assert_eq!(header.split('\t').count(), line.split('\t').count());
+    assert!(header.starts_with("contig\tposition\tref\talt\tfilter\tQUAL\tQD\tDP\t"));
+    assert!(line.starts_with("genome~contig_1\t101\tA\tAT\t.\t250\t12.3457\tNA\t"));
+}
+
+#[test]
+fn testFeatureModel() {
+    let model =
+        FeatureModel::parse("# trained on replicate calls\nintercept -2\nQD 0.1\n\nsor\t0.5\n")
+            .unwrap();
+    assert_eq!(model.intercept, -2.0);
+    assert_eq!(model.weights.len(), 2);
+
+    let score = model.score(&features(&[("QD", 30.0), ("SOR", 2.0)]));
+    assert!((score - 0.880797).abs() < 1e-6);
+    // missing features contribute nothing
+    let score = model.score(&features(&[("SOR", 2.0)]));
+    assert!((score - 0.268941).abs() < 1e-6);
+
+    assert!(FeatureModel::parse("HAPLOTYPE_SCORE 1.0").is_err());
+    assert!(FeatureModel::parse("QD one").is_err());
+    assert!(FeatureModel::parse("QD 1.0 2.0").is_err());
+    assert!(FeatureModel::parse("QD 1.0\nqd 2.0").is_err());
+}
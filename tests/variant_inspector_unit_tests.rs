@@ -0,0 +1,118 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::variant_inspector::{
+    marker, parse_region, render, AlleleSupport, PileupRow, PileupWindow,
+};
+use rust_htslib::bam::record::CigarString;
+
+#[test]
+fn test_parse_region() {
+    assert_eq!(
+        parse_region("ctg1:1,234").unwrap(),
+        ("ctg1".to_string(), 1233)
+    );
+    assert_eq!(
+        parse_region("genome~ctg:with:colons:5").unwrap(),
+        ("genome~ctg:with:colons".to_string(), 4)
+    );
+    assert!(parse_region("ctg1").is_err());
+    assert!(parse_region("ctg1:0").is_err());
+    assert!(parse_region("ctg1:abc").is_err());
+    assert!(parse_region(":10").is_err());
+}
+
+#[test]
+fn test_alignment_row() {
+    let window = PileupWindow::new(10, 10);
+    let cigar = CigarString::try_from("4M2I3M1D4M").unwrap();
+    let row = PileupRow::from_alignment("read", window, 8, &cigar.0, b"ACGTTTGGACCCC", false);
+
+    assert_eq!(
+        row.bases,
+        vec![
+            Some(b'G'),
+            Some(b'T'),
+            Some(b'G'),
+            Some(b'G'),
+            Some(b'A'),
+            Some(PileupRow::DELETED),
+            Some(b'C'),
+            Some(b'C'),
+            Some(b'C'),
+            Some(b'C'),
+        ]
+    );
+    assert_eq!(row.insertions[1], b"TT".to_vec());
+    assert_eq!(row.allele_bases(window, 11, 11), Some(b"TTT".to_vec()));
+    assert_eq!(row.allele_bases(window, 14, 15), Some(b"A".to_vec()));
+    // the span starts before the window
+    assert_eq!(row.allele_bases(window, 9, 10), None);
+}
+
+#[test]
+fn test_allele_rows() {
+    let window = PileupWindow::new(100, 10);
+    let reference = b"ACGTACGTAC";
+
+    let insertion = PileupRow::from_allele("ins", window, reference, 100, 103, 1, b"TGG");
+    assert!(insertion
+        .bases
+        .iter()
+        .zip(reference.iter())
+        .all(|(base, ref_base)| *base == Some(*ref_base)));
+    assert_eq!(insertion.insertions[3], b"GG".to_vec());
+    assert_eq!(
+        insertion.allele_bases(window, 103, 103),
+        Some(b"TGG".to_vec())
+    );
+
+    let deletion = PileupRow::from_allele("del", window, reference, 100, 104, 3, b"A");
+    assert_eq!(deletion.bases[5], Some(PileupRow::DELETED));
+    assert_eq!(deletion.bases[6], Some(PileupRow::DELETED));
+    assert_eq!(deletion.bases[7], Some(b'T'));
+    assert_eq!(deletion.allele_bases(window, 104, 106), Some(b"A".to_vec()));
+}
+
+#[test]
+fn test_allele_support() {
+    let alleles = vec![b"A".to_vec(), b"AT".to_vec()];
+    assert_eq!(
+        AlleleSupport::assign(Some(&b"at"[..]), &alleles),
+        AlleleSupport::Allele(1)
+    );
+    assert_eq!(
+        AlleleSupport::assign(Some(&b"G"[..]), &alleles),
+        AlleleSupport::Other
+    );
+    assert_eq!(
+        AlleleSupport::assign(None, &alleles),
+        AlleleSupport::NotSpanning
+    );
+    assert_eq!(AlleleSupport::Allele(0).to_label(), "REF");
+    assert_eq!(AlleleSupport::Allele(2).to_label(), "ALT2");
+}
+
+#[test]
+fn test_render() {
+    let window = PileupWindow::new(0, 4);
+    let forward = CigarString::try_from("2M1I2M").unwrap();
+    let reverse = CigarString::try_from("3M").unwrap();
+    let rows = vec![
+        PileupRow::from_reference("reference", b"ACGT"),
+        PileupRow::from_alignment("read", window, 0, &forward.0, b"ACTGA", false),
+        PileupRow::from_alignment("rev", window, 1, &reverse.0, b"CGA", true),
+    ];
+
+    assert_eq!(
+        render(&rows),
+        vec![
+            "reference  AC-GT".to_string(),
+            "read       ..T.A".to_string(),
+            "rev         ,-,a".to_string(),
+        ]
+    );
+    assert_eq!(marker(&rows, 3), format!("{}^", " ".repeat(15)));
+}
@@ -0,0 +1,80 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::linkage::variant_linkage_graph::{VariantLinkageGraph, VariantNode};
+use std::collections::HashSet;
+
+fn node(position: usize, variant_group: i32, supporting_reads: usize) -> VariantNode {
+    VariantNode {
+        contig: "contig_1".to_string(),
+        position,
+        reference: "A".to_string(),
+        alternate: "T".to_string(),
+        variant_group,
+        strains: vec![variant_group as usize],
+        supporting_reads,
+    }
+}
+
+fn reads(names: &[&str]) -> HashSet<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn test_shared_read_edges() {
+    let reads_1 = reads(&["0_r1", "0_r2", "1_r1"]);
+    let reads_2 = reads(&["0_r1", "0_r2"]);
+    let reads_3 = reads(&["1_r1", "0_r3"]);
+    let reads_4 = reads(&["0_r4"]);
+    let graph = VariantLinkageGraph::new(
+        vec![node(10, 0, 3), node(20, 0, 2), node(30, 1, 2), node(40, 1, 1)],
+        &[&reads_1, &reads_2, &reads_3, &reads_4],
+    );
+
+    assert_eq!(graph.edges, vec![(0, 1, 2), (0, 2, 1)]);
+}
+
+#[test]
+fn test_write_tsv() {
+    let reads_1 = reads(&["0_r1"]);
+    let reads_2 = reads(&["0_r1"]);
+    let mut unassigned = node(20, -1, 1);
+    unassigned.strains = Vec::new();
+    let graph = VariantLinkageGraph::new(vec![node(10, 0, 1), unassigned], &[&reads_1, &reads_2]);
+
+    let mut nodes = Vec::new();
+    graph.write_nodes_tsv(&mut nodes).unwrap();
+    assert_eq!(
+        String::from_utf8(nodes).unwrap(),
+        "id\tcontig\tposition\tref\talt\tvariant_group\tstrains\tsupporting_reads\n\
+        contig_1:10:A>T\tcontig_1\t10\tA\tT\t0\t0\t1\n\
+        contig_1:20:A>T\tcontig_1\t20\tA\tT\t-1\t.\t1\n"
+    );
+
+    let mut edges = Vec::new();
+    graph.write_edges_tsv(&mut edges).unwrap();
+    assert_eq!(
+        String::from_utf8(edges).unwrap(),
+        "source\ttarget\tshared_reads\ncontig_1:10:A>T\tcontig_1:20:A>T\t1\n"
+    );
+}
+
+#[test]
+fn test_write_graphml() {
+    let reads_1 = reads(&["0_r1"]);
+    let reads_2 = reads(&["0_r1"]);
+    let mut escaped = node(20, 0, 1);
+    escaped.alternate = "<DEL>".to_string();
+    let graph = VariantLinkageGraph::new(vec![node(10, 0, 1), escaped], &[&reads_1, &reads_2]);
+
+    let mut graphml = Vec::new();
+    graph.write_graphml(&mut graphml).unwrap();
+    let graphml = String::from_utf8(graphml).unwrap();
+    assert!(graphml.contains("<data key=\"alt\">&lt;DEL&gt;</data>"));
+    assert!(graphml.contains(
+        "<edge source=\"n0\" target=\"n1\"><data key=\"shared_reads\">1</data></edge>"
+    ));
+    assert!(graphml.trim_end().ends_with("</graphml>"));
+}
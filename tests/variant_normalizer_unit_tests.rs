@@ -0,0 +1,130 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::model::variant_normalizer::VariantNormalizer;
+use lorikeet_genome::utils::simple_interval::Locatable;
+
+fn alleles(alleles: &[&str]) -> Vec<Vec<u8>> {
+    alleles
+        .iter()
+        .map(|allele| allele.as_bytes().to_vec())
+        .collect()
+}
+
+#[test]
+fn test_left_align_insertion_in_repeat() {
+    let contig = b"GCATTTG";
+    // an extra T placed at the end of the T run
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["T", "TT"]), 4, contig);
+    assert_eq!(start, 2);
+    assert_eq!(aligned, alleles(&["A", "AT"]));
+}
+
+#[test]
+fn test_left_align_deletion_in_repeat() {
+    let contig = b"GCATTTG";
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["TT", "T"]), 4, contig);
+    assert_eq!(start, 2);
+    assert_eq!(aligned, alleles(&["AT", "A"]));
+
+    // soft masked reference bases are written in upper case
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["TT", "T"]), 4, b"gcatttg");
+    assert_eq!(start, 2);
+    assert_eq!(aligned, alleles(&["AT", "A"]));
+}
+
+#[test]
+fn test_left_align_trims_shared_bases() {
+    let contig = b"GACGTAC";
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["ACG", "ATG"]), 1, contig);
+    assert_eq!(start, 2);
+    assert_eq!(aligned, alleles(&["C", "T"]));
+
+    // SNPs and normalised indels are unchanged
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["C", "T"]), 2, contig);
+    assert_eq!(start, 2);
+    assert_eq!(aligned, alleles(&["C", "T"]));
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["G", "GTT"]), 3, contig);
+    assert_eq!(start, 3);
+    assert_eq!(aligned, alleles(&["G", "GTT"]));
+}
+
+#[test]
+fn test_left_align_stops_at_contig_start() {
+    let contig = b"AAAC";
+    let (start, aligned) = VariantNormalizer::left_align(&alleles(&["AA", "A"]), 1, contig);
+    assert_eq!(start, 0);
+    assert_eq!(aligned, alleles(&["AA", "A"]));
+}
+
+#[test]
+fn test_atomize_alleles() {
+    assert_eq!(
+        VariantNormalizer::atomize_alleles(&alleles(&["ACG", "TCA"]), 10),
+        Some(vec![(10, b'A', b'T'), (12, b'G', b'A')])
+    );
+    assert_eq!(
+        VariantNormalizer::atomize_alleles(&alleles(&["A", "T"]), 10),
+        None
+    );
+    assert_eq!(
+        VariantNormalizer::atomize_alleles(&alleles(&["A", "AT"]), 10),
+        None
+    );
+    assert_eq!(
+        VariantNormalizer::atomize_alleles(&alleles(&["AC", "TC", "AG"]), 10),
+        None
+    );
+}
+
+#[test]
+fn test_normalize_long_deletion_in_repeat() {
+    // a 55bp deletion placed at the end of an 80bp A run
+    let mut contig = b"C".to_vec();
+    contig.extend(vec![b'A'; 80]);
+    contig.push(b'G');
+    let mut deletion = VariantContext::build(
+        0,
+        25,
+        80,
+        vec![
+            ByteArrayAllele::new(&vec![b'A'; 56], true),
+            ByteArrayAllele::new(b"A", false),
+        ],
+    );
+    deletion.add_structural_variant_info(50);
+    assert_eq!(
+        deletion.attributes.get("END"),
+        Some(&AttributeObject::I32(81))
+    );
+
+    let normalized = VariantNormalizer::new(false).normalize(&deletion, &contig);
+    assert_eq!(normalized.len(), 1);
+    let normalized = &normalized[0];
+    let mut ref_bases = b"C".to_vec();
+    ref_bases.extend(vec![b'A'; 55]);
+    assert_eq!(normalized.loc.get_start(), 0);
+    assert_eq!(normalized.loc.get_end(), 55);
+    assert_eq!(
+        normalized.get_alleles_as_bytes(),
+        vec![&ref_bases[..], &b"C"[..]]
+    );
+    // END follows the new position, while the length and type are unchanged
+    assert_eq!(
+        normalized.attributes.get("END"),
+        Some(&AttributeObject::I32(56))
+    );
+    assert_eq!(
+        normalized.attributes.get("SVLEN"),
+        Some(&AttributeObject::VecI32(vec![-55]))
+    );
+    assert_eq!(
+        normalized.attributes.get("SVTYPE"),
+        Some(&AttributeObject::String("DEL".to_string()))
+    );
+}
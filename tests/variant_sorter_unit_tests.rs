@@ -0,0 +1,134 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::{Allele, ByteArrayAllele};
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::model::variant_sorter::VariantSorter;
+use lorikeet_genome::utils::simple_interval::Locatable;
+
+fn variant(tid: usize, pos: usize, alt: &[u8]) -> VariantContext {
+    let mut vc = VariantContext::build(
+        tid,
+        pos,
+        pos,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(alt, false),
+        ],
+    );
+    vc.set_attribute("VG".to_string(), AttributeObject::I32(pos as i32));
+    vc
+}
+
+fn unsorted() -> Vec<VariantContext> {
+    vec![
+        variant(1, 50, b"T"),
+        variant(0, 400, b"G"),
+        variant(0, 10, b"T"),
+        variant(1, 5, b"C"),
+        variant(0, 10, b"C"),
+        variant(0, 250, b"T"),
+        variant(2, 0, b"G"),
+        variant(0, 3, b"T"),
+    ]
+}
+
+fn keys(contexts: &[VariantContext]) -> Vec<(usize, usize, Vec<u8>)> {
+    contexts
+        .iter()
+        .map(|vc| {
+            (
+                vc.loc.get_contig(),
+                vc.loc.get_start(),
+                vc.alleles[1].get_bases().to_vec(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_spilled_sort_matches_in_memory_sort() {
+    let directory = tempfile::tempdir().unwrap();
+    let mut expected = unsorted();
+    expected.sort_unstable();
+
+    let in_memory = VariantSorter::new(directory.path(), 100)
+        .sort(unsorted())
+        .unwrap();
+    assert_eq!(keys(&in_memory), keys(&expected));
+
+    // three runs of at most three contexts each
+    let spilled = VariantSorter::new(directory.path(), 3)
+        .sort(unsorted())
+        .unwrap();
+    assert_eq!(keys(&spilled), keys(&expected));
+    assert_eq!(
+        spilled
+            .iter()
+            .map(|vc| vc.attributes.get("VG").cloned())
+            .collect::<Vec<Option<AttributeObject>>>(),
+        expected
+            .iter()
+            .map(|vc| vc.attributes.get("VG").cloned())
+            .collect::<Vec<Option<AttributeObject>>>()
+    );
+
+    // the runs are removed once the sorted contexts have been read
+    assert_eq!(std::fs::read_dir(directory.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_sort_iter_streams_sorted_contexts() {
+    let directory = tempfile::tempdir().unwrap();
+    let sorted = VariantSorter::new(directory.path(), 2)
+        .sort_iter(unsorted())
+        .unwrap()
+        .map(|vc| vc.unwrap())
+        .collect::<Vec<VariantContext>>();
+
+    let positions = sorted
+        .iter()
+        .map(|vc| (vc.loc.get_contig(), vc.loc.get_start()))
+        .collect::<Vec<(usize, usize)>>();
+    assert_eq!(
+        positions,
+        vec![
+            (0, 3),
+            (0, 10),
+            (0, 10),
+            (0, 250),
+            (0, 400),
+            (1, 5),
+            (1, 50),
+            (2, 0)
+        ]
+    );
+}
+
+#[test]
+fn test_sort_iter_keeps_input_order_of_ties() {
+    let directory = tempfile::tempdir().unwrap();
+    let contexts = (0..4)
+        .map(|index| {
+            let mut vc = variant(0, 10, b"T");
+            vc.set_attribute("VG".to_string(), AttributeObject::I32(index));
+            vc
+        })
+        .collect::<Vec<VariantContext>>();
+
+    // every context is spilled to a run of its own
+    let sorted = VariantSorter::new(directory.path(), 1)
+        .sort_iter(contexts)
+        .unwrap()
+        .map(|vc| vc.unwrap().attributes.get("VG").cloned())
+        .collect::<Vec<Option<AttributeObject>>>();
+    assert_eq!(
+        sorted,
+        (0..4)
+            .map(|index| Some(AttributeObject::I32(index)))
+            .collect::<Vec<Option<AttributeObject>>>()
+    );
+}
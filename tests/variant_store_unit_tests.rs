@@ -0,0 +1,105 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::genotype::genotype_builder::AttributeObject;
+use lorikeet_genome::model::byte_array_allele::ByteArrayAllele;
+use lorikeet_genome::model::variant_context::VariantContext;
+use lorikeet_genome::model::variant_store::VariantStore;
+use lorikeet_genome::utils::simple_interval::Locatable;
+
+fn snp(tid: usize, pos: usize) -> VariantContext {
+    let mut vc = VariantContext::build(
+        tid,
+        pos,
+        pos,
+        vec![
+            ByteArrayAllele::new(b"A", true),
+            ByteArrayAllele::new(b"T", false),
+        ],
+    );
+    vc.set_attribute("VG".to_string(), AttributeObject::I32(pos as i32));
+    vc
+}
+
+fn positions(contexts: &[VariantContext]) -> Vec<(usize, usize)> {
+    contexts
+        .iter()
+        .map(|vc| (vc.loc.get_contig(), vc.loc.get_start()))
+        .collect()
+}
+
+#[test]
+fn test_variant_store_queries() {
+    let mut store = VariantStore::new(3).unwrap();
+    store
+        .extend(vec![
+            snp(1, 50),
+            snp(0, 400),
+            snp(0, 10),
+            snp(1, 5),
+            snp(0, 250),
+            snp(0, 100),
+            snp(2, 1),
+        ])
+        .unwrap();
+
+    // two full buffers have been split by contig and written, the last context is still buffered
+    assert_eq!(store.len(), 7);
+    assert_eq!(store.n_chunks(), 4);
+    assert_eq!(store.contigs(), vec![0, 1, 2]);
+
+    assert_eq!(
+        positions(&store.query(0, 90, 300).unwrap()),
+        vec![(0, 100), (0, 250)]
+    );
+    assert_eq!(
+        positions(&store.query_contig(1).unwrap()),
+        vec![(1, 5), (1, 50)]
+    );
+    assert_eq!(positions(&store.query(2, 0, 10).unwrap()), vec![(2, 1)]);
+    assert!(store.query(3, 0, 10).unwrap().is_empty());
+
+    // attributes survive the round trip through disk
+    let queried = store.query(0, 10, 10).unwrap();
+    assert_eq!(
+        queried[0].attributes.get("VG"),
+        Some(&AttributeObject::I32(10))
+    );
+
+    store.flush().unwrap();
+    let stored = store
+        .iter()
+        .collect::<Result<Vec<VariantContext>, _>>()
+        .unwrap();
+    assert_eq!(
+        positions(&stored),
+        vec![
+            (0, 10),
+            (0, 100),
+            (0, 250),
+            (0, 400),
+            (1, 5),
+            (1, 50),
+            (2, 1)
+        ]
+    );
+}
+
+#[test]
+fn test_merge_sorted_contexts() {
+    let mut store = VariantStore::new(2).unwrap();
+    store
+        .extend(vec![snp(0, 100), snp(1, 5), snp(0, 20)])
+        .unwrap();
+    let in_memory = vec![snp(0, 10), snp(0, 150), snp(2, 1)];
+
+    let merged = VariantStore::merge_sorted(in_memory.into_iter().map(Ok), store.iter())
+        .collect::<Result<Vec<VariantContext>, _>>()
+        .unwrap();
+    assert_eq!(
+        positions(&merged),
+        vec![(0, 10), (0, 20), (0, 100), (0, 150), (1, 5), (2, 1)]
+    );
+}
@@ -0,0 +1,43 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::processing::vcf_combiner::{CombineInput, VcfCombiner};
+use lorikeet_genome::utils::errors::BirdToolError;
+use rust_htslib::bcf::{Format, Header, Writer};
+
+#[test]
+fn test_remap_likelihoods() {
+    // diploid PLs over [ref, A] moved onto [ref, C, A]
+    let pls = vec![0, 10, 20];
+    let remapped = VcfCombiner::remap_likelihoods(&pls, 2, &[0, 2], 3);
+    // genotype order is 0/0, 0/1, 1/1, 0/2, 1/2, 2/2 and genotypes with the unseen
+    // allele C get the least likely PL
+    assert_eq!(remapped, vec![0, 20, 20, 10, 20, 20]);
+
+    // identity mapping leaves the PLs untouched
+    let remapped = VcfCombiner::remap_likelihoods(&pls, 2, &[0, 1], 2);
+    assert_eq!(remapped, pls);
+
+    // PLs that do not match the ploidy and allele count are rejected
+    assert!(VcfCombiner::remap_likelihoods(&[0, 10], 2, &[0, 1], 3).is_empty());
+    assert!(VcfCombiner::remap_likelihoods(&[], 2, &[0, 1], 3).is_empty());
+}
+
+#[test]
+fn test_gvcf_input_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let gvcf_path = dir.path().join("sample.g.vcf");
+    let mut header = Header::new();
+    header.push_record(b"##contig=<ID=contig_1,length=1000>");
+    header.push_record(b"##ALT=<ID=NON_REF,Description=\"Any other allele\">");
+    header.push_sample(b"sample_1");
+    Writer::from_path(&gvcf_path, &header, true, Format::Vcf).unwrap();
+
+    // reference blocks are not read, so a gVCF can not be combined
+    assert!(matches!(
+        CombineInput::from_vcf(gvcf_path.to_str().unwrap()),
+        Err(BirdToolError::ConfigError(_))
+    ));
+}
@@ -0,0 +1,48 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::vcf_input::VcfInput;
+
+#[test]
+fn test_vcf_stem() {
+    assert_eq!(VcfInput::stem("output/genome_1.vcf"), "genome_1");
+    assert_eq!(VcfInput::stem("output/genome_1.vcf.gz"), "genome_1");
+    assert_eq!(VcfInput::stem("genome_1.bcf"), "genome_1");
+    assert_eq!(
+        VcfInput::stem("https://example.org/calls/genome_1.vcf.gz"),
+        "genome_1"
+    );
+}
+
+#[test]
+fn test_remote_paths() {
+    assert!(VcfInput::is_remote("https://example.org/genome_1.vcf.gz"));
+    assert!(VcfInput::is_remote("s3://bucket/genome_1.vcf.gz"));
+    assert!(!VcfInput::is_remote("output/genome_1.vcf.gz"));
+    // remote paths are not checked for existence
+    assert_eq!(
+        VcfInput::resolve("s3://bucket/genome_1.vcf.gz").unwrap(),
+        "s3://bucket/genome_1.vcf.gz"
+    );
+}
+
+#[test]
+fn test_resolve_compressed_and_plain() {
+    let directory = tempfile::tempdir().unwrap();
+    let plain = format!("{}/genome_1.vcf", directory.path().to_str().unwrap());
+    let compressed = format!("{}.gz", plain);
+
+    assert!(VcfInput::resolve(&plain).is_err());
+    assert!(VcfInput::open(&plain).is_err());
+
+    std::fs::write(&compressed, b"").unwrap();
+    assert_eq!(VcfInput::resolve(&plain).unwrap(), compressed);
+    assert_eq!(VcfInput::resolve(&compressed).unwrap(), compressed);
+
+    std::fs::remove_file(&compressed).unwrap();
+    std::fs::write(&plain, b"").unwrap();
+    assert_eq!(VcfInput::resolve(&compressed).unwrap(), plain);
+    assert_eq!(VcfInput::resolve(&plain).unwrap(), plain);
+}
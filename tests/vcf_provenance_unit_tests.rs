@@ -0,0 +1,40 @@
+#![allow(
+    non_upper_case_globals,
+    non_snake_case
+)]
+
+use lorikeet_genome::utils::vcf_provenance::VcfProvenance;
+
+#[test]
+fn test_contig_md5() {
+    let md5 = "252fe4e1c9aa67ce660443056dfa3799";
+    assert_eq!(VcfProvenance::contig_md5(b"ACGTN"), md5);
+    // checksums ignore case and line breaks, as for the M5 field of SAM headers
+    assert_eq!(VcfProvenance::contig_md5(b"acg\ntn"), md5);
+}
+
+#[test]
+fn test_header_records() {
+    let provenance = VcfProvenance::new(
+        "lorikeet call -r 'my genome.fna' -t 4".to_string(),
+        vec![
+            ("threads".to_string(), "4".to_string()),
+            ("output-prefix".to_string(), "run \"1\"".to_string()),
+        ],
+    );
+
+    let records = provenance.header_records();
+    assert_eq!(
+        records[0],
+        format!("##lorikeet_version={}", env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(
+        records[1],
+        "##lorikeet_command=lorikeet call -r 'my genome.fna' -t 4"
+    );
+    assert_eq!(records[2], "##lorikeet_parameter=<ID=threads,Value=\"4\">");
+    assert_eq!(
+        records[3],
+        "##lorikeet_parameter=<ID=output-prefix,Value=\"run \\\"1\\\"\">"
+    );
+}